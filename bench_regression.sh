@@ -0,0 +1,43 @@
+#!/bin/bash
+
+# Track hot path performance between releases
+#
+#   ./bench_regression.sh save [name]     record a baseline (default: release)
+#   ./bench_regression.sh compare [name]  run again and fail on regressions
+#
+# Baselines are criterion's, kept under target/criterion. A benchmark counts
+# as regressed when criterion finds it significantly slower and the change
+# is above its noise threshold, set here to 5%.
+
+set -euo pipefail
+
+cd "$(dirname "$0")"
+
+MODE=${1:-compare}
+BASELINE=${2:-release}
+BENCHES=(--bench hot_paths --bench consensus)
+
+case "$MODE" in
+    save)
+        echo "=== Saving benchmark baseline '$BASELINE' ==="
+        cargo bench -p blockchain-core "${BENCHES[@]}" -- --save-baseline "$BASELINE" --noise-threshold 0.05
+        ;;
+    compare)
+        echo "=== Comparing against baseline '$BASELINE' ==="
+        LOG=$(mktemp)
+        trap 'rm -f "$LOG"' EXIT
+        cargo bench -p blockchain-core "${BENCHES[@]}" -- --baseline "$BASELINE" --noise-threshold 0.05 | tee "$LOG"
+        if grep -q "Performance has regressed" "$LOG"; then
+            echo ""
+            echo "❌ Benchmarks regressed against '$BASELINE':"
+            grep -B 4 "Performance has regressed" "$LOG"
+            exit 1
+        fi
+        echo ""
+        echo "✅ No regressions against '$BASELINE'"
+        ;;
+    *)
+        echo "usage: $0 save|compare [baseline]" >&2
+        exit 2
+        ;;
+esac
@@ -0,0 +1,31 @@
+//! Embed the source commit and compiler version for `version --verbose`.
+//!
+//! Nothing time- or machine-dependent is embedded, so two builds of the same
+//! commit with the same toolchain produce the same binary. Release packaging
+//! sets `EDUNET_GIT_COMMIT` itself; otherwise it is read from git.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=EDUNET_GIT_COMMIT");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+
+    let commit = std::env::var("EDUNET_GIT_COMMIT").ok()
+        .or_else(|| command_output("git", &["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=EDUNET_GIT_COMMIT={}", commit);
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(&rustc, &["--version"])
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=EDUNET_RUSTC_VERSION={}", rustc_version);
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
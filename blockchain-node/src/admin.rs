@@ -0,0 +1,144 @@
+//! Operator Administration
+//!
+//! Admin RPCs are authenticated with a shared operator token. Every admin
+//! call, allowed or refused, and every treasury sale is appended to a
+//! hash-chained JSON-lines audit log in the data directory, so operators can
+//! see who did what and tell if the log was edited afterwards.
+
+use blockchain_core::audit_log::{verify_chain, AuditRecord, ChainBreak};
+use serde_json::{Map, Value};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Audit log file name inside the data directory
+pub const AUDIT_LOG_FILE: &str = "admin-audit.log";
+
+/// Token check and audit trail for admin RPCs
+pub struct AdminConsole {
+    token: Option<String>,
+    audit_path: PathBuf,
+    /// Sequence number and hash of the last record, held while appending
+    head: Mutex<Option<(u64, String)>>,
+}
+
+impl AdminConsole {
+    /// Admin RPCs are disabled when no token is configured
+    pub fn new(token: Option<String>, data_dir: &std::path::Path) -> Self {
+        let token = token.filter(|t| !t.is_empty());
+        if token.is_none() {
+            warn!("🔒 No admin token configured - admin RPCs are disabled");
+        }
+        let audit_path = data_dir.join(AUDIT_LOG_FILE);
+        let head = read_records(&audit_path).last().map(|r| (r.seq, r.hash.clone()));
+        Self {
+            token,
+            audit_path,
+            head: Mutex::new(head),
+        }
+    }
+
+    /// Check the `token` param and return the calling operator's name
+    pub fn authorize(&self, action: &str, params: &Map<String, Value>) -> Result<String, jsonrpc_core::Error> {
+        let actor = params.get("actor")
+            .and_then(|v| v.as_str())
+            .unwrap_or("operator")
+            .to_string();
+
+        let Some(expected) = &self.token else {
+            return Err(admin_error("Admin RPCs are disabled (start the node with --admin-token)"));
+        };
+        let supplied = params.get("token").and_then(|v| v.as_str()).unwrap_or("");
+        if !constant_time_eq(supplied.as_bytes(), expected.as_bytes()) {
+            self.record(&actor, action, params, Some("invalid admin token"));
+            return Err(admin_error("Invalid admin token"));
+        }
+        Ok(actor)
+    }
+
+    /// Append an admin call to the audit log
+    pub fn record(&self, actor: &str, action: &str, params: &Map<String, Value>, error: Option<&str>) {
+        let record = AuditRecord::new(actor, action, Value::Object(audit_params(params)));
+        self.append(match error {
+            Some(error) => record.with_error(error),
+            None => record,
+        });
+    }
+
+    /// Seal a record onto the end of the audit log
+    pub fn append(&self, record: AuditRecord) {
+        info!("🛡️  Audit: {} by {} ({})", record.action, record.actor, if record.success { "ok" } else { "failed" });
+
+        let mut head = self.head.lock().unwrap_or_else(|e| e.into_inner());
+        let record = record.seal(head.as_ref().map(|(seq, hash)| (*seq, hash.as_str())));
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize audit record: {}", e);
+                return;
+            }
+        };
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.audit_path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        match written {
+            Ok(()) => *head = Some((record.seq, record.hash)),
+            Err(e) => warn!("Failed to write audit log {}: {}", self.audit_path.display(), e),
+        }
+    }
+
+    /// Most recent audit records, newest first, optionally for one actor or action
+    pub fn recent_entries(&self, limit: usize, actor: Option<&str>, action: Option<&str>) -> Vec<AuditRecord> {
+        read_records(&self.audit_path)
+            .into_iter()
+            .rev()
+            .filter(|r| actor.is_none_or(|a| r.actor == a) && action.is_none_or(|a| r.action == a))
+            .take(limit)
+            .collect()
+    }
+
+    /// Number of records in the log and whether the chain is intact
+    pub fn verify(&self) -> (usize, Result<(), ChainBreak>) {
+        let _head = self.head.lock().unwrap_or_else(|e| e.into_inner());
+        let records = read_records(&self.audit_path);
+        let result = verify_chain(&records, None);
+        (records.len(), result)
+    }
+}
+
+/// Call parameters as recorded: credentials removed
+fn audit_params(params: &Map<String, Value>) -> Map<String, Value> {
+    let mut details = params.clone();
+    details.remove("token");
+    details.remove("actor");
+    details
+}
+
+/// Chained records in the log. Lines from before the log was chained don't
+/// parse as records and are skipped.
+fn read_records(path: &std::path::Path) -> Vec<AuditRecord> {
+    let Ok(file) = std::fs::File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+fn admin_error(message: &str) -> jsonrpc_core::Error {
+    jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32001),
+        message: message.to_string(),
+        data: None,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,347 @@
+//! Chain Alerts
+//!
+//! Watches for trouble with the chain a node follows: a competing branch
+//! within `fork_window` blocks of the tip, a reorg, a tip that has not moved
+//! for too long, or the same invalid block arriving from several peers.
+//! Alerts are published on an in-process bus that other subsystems can
+//! subscribe to, kept for `blockchain_getChainAlerts`, and optionally POSTed
+//! as JSON to a webhook.
+
+use crate::blockchain::BlockchainBackend;
+use blockchain_core::consensus::ChainTip;
+use blockchain_core::header_chain::{branch_tips, ChainTipInfo, ForkAlert, ForkKind, TipStatus};
+use blockchain_core::Hash256;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+/// Alerts kept for reporting
+const MAX_ALERTS: usize = 200;
+
+/// How often the tip is checked for staleness
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    /// Alert on competing branches reaching within this many blocks of the tip
+    pub fork_window: u64,
+    /// Alert when the tip has not moved for this long
+    pub stale_after: Duration,
+    /// Distinct peers that must send the same invalid block before alerting
+    pub invalid_peer_threshold: usize,
+    /// URL alerts are POSTed to
+    pub webhook: Option<String>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            fork_window: 6,
+            stale_after: Duration::from_secs(3600),
+            invalid_peer_threshold: 3,
+            webhook: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    CompetingChain,
+    Reorg,
+    StaleTip,
+    InvalidChain,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+/// A structured chain alert
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainAlert {
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub message: String,
+    pub height: u64,
+    pub hash: Option<String>,
+    pub details: Value,
+    pub raised_at: u64,
+}
+
+/// A block some peers sent that failed validation
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidBlock {
+    pub hash: String,
+    pub height: u64,
+    pub reason: String,
+    pub peers: usize,
+}
+
+#[derive(Default)]
+struct InvalidRecord {
+    height: u64,
+    reason: String,
+    peers: HashSet<String>,
+    alerted: bool,
+}
+
+struct TipState {
+    height: u64,
+    changed_at: Instant,
+    stale_alerted: bool,
+}
+
+/// Raises, keeps and delivers chain alerts
+pub struct ChainAlerter {
+    config: AlertConfig,
+    bus: broadcast::Sender<ChainAlert>,
+    recent: RwLock<VecDeque<ChainAlert>>,
+    invalid: RwLock<HashMap<Hash256, InvalidRecord>>,
+    tip: RwLock<TipState>,
+}
+
+impl ChainAlerter {
+    pub fn new(config: AlertConfig) -> Self {
+        Self {
+            config,
+            bus: broadcast::channel(64).0,
+            recent: RwLock::new(VecDeque::new()),
+            invalid: RwLock::new(HashMap::new()),
+            tip: RwLock::new(TipState { height: 0, changed_at: Instant::now(), stale_alerted: false }),
+        }
+    }
+
+    pub fn config(&self) -> &AlertConfig {
+        &self.config
+    }
+
+    /// Receive every alert raised from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ChainAlert> {
+        self.bus.subscribe()
+    }
+
+    /// Alerts raised, newest first
+    pub fn recent(&self, limit: usize) -> Vec<ChainAlert> {
+        self.recent.read().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Invalid blocks reported by peers, highest first
+    pub fn invalid_blocks(&self) -> Vec<InvalidBlock> {
+        let mut blocks: Vec<InvalidBlock> = self.invalid.read().unwrap().iter()
+            .map(|(hash, record)| InvalidBlock {
+                hash: hex::encode(hash),
+                height: record.height,
+                reason: record.reason.clone(),
+                peers: record.peers.len(),
+            })
+            .collect();
+        blocks.sort_by(|a, b| b.height.cmp(&a.height));
+        blocks
+    }
+
+    fn raise(&self, kind: AlertKind, severity: Severity, message: String, height: u64, hash: Option<String>, details: Value) {
+        match severity {
+            Severity::Critical => warn!("🚨 {}", message),
+            Severity::Warning => warn!("⚠️  {}", message),
+        }
+        let alert = ChainAlert {
+            kind,
+            severity,
+            message,
+            height,
+            hash,
+            details,
+            raised_at: chrono::Utc::now().timestamp() as u64,
+        };
+        {
+            let mut recent = self.recent.write().unwrap();
+            if recent.len() == MAX_ALERTS {
+                recent.pop_front();
+            }
+            recent.push_back(alert.clone());
+        }
+        // No subscribers is fine
+        let _ = self.bus.send(alert);
+    }
+
+    /// Report a fork seen by the header chain
+    pub fn fork(&self, fork: &ForkAlert, tip_height: u64) {
+        let branch_tip = fork.fork_height + fork.depth;
+        match fork.kind {
+            ForkKind::Reorg => self.raise(
+                AlertKind::Reorg,
+                if fork.depth > self.config.fork_window { Severity::Critical } else { Severity::Warning },
+                format!("Reorg of {} block(s) at height {}", fork.depth, fork.fork_height),
+                tip_height,
+                Some(fork.best_hash.clone()),
+                json!(fork),
+            ),
+            ForkKind::SideBranch if branch_tip + self.config.fork_window >= tip_height => self.raise(
+                AlertKind::CompetingChain,
+                Severity::Warning,
+                format!("Competing chain forking at height {} within {} blocks of the tip", fork.fork_height, self.config.fork_window),
+                branch_tip,
+                Some(fork.competing_hash.clone()),
+                json!(fork),
+            ),
+            ForkKind::SideBranch => {}
+        }
+    }
+
+    /// Record a peer sending an invalid block; alerts once enough distinct
+    /// peers have sent the same one
+    pub fn invalid_block(&self, hash: Hash256, height: u64, reason: &str, peer: String) {
+        let peers = {
+            let mut invalid = self.invalid.write().unwrap();
+            let record = invalid.entry(hash).or_insert_with(|| InvalidRecord {
+                height,
+                reason: reason.to_string(),
+                ..InvalidRecord::default()
+            });
+            record.peers.insert(peer);
+            if record.alerted || record.peers.len() < self.config.invalid_peer_threshold {
+                return;
+            }
+            record.alerted = true;
+            record.peers.len()
+        };
+        self.raise(
+            AlertKind::InvalidChain,
+            Severity::Critical,
+            format!("{} peers are following invalid block {} at height {}: {}", peers, hex::encode(hash), height, reason),
+            height,
+            Some(hex::encode(hash)),
+            json!({ "reason": reason, "peers": peers }),
+        );
+    }
+
+    /// Invalid blocks within `window` blocks of the tip, as chain tips
+    pub fn invalid_tips(&self, tip_height: u64, window: u64) -> Vec<ChainTipInfo> {
+        self.invalid_blocks().into_iter()
+            .filter(|block| block.height.saturating_add(window) >= tip_height)
+            .map(|block| ChainTipInfo { height: block.height, hash: block.hash, branch_len: 1, status: TipStatus::Invalid })
+            .collect()
+    }
+
+    /// Note the current tip height; a change resets the stale timer
+    pub fn tip_seen(&self, height: u64) {
+        let mut tip = self.tip.write().unwrap();
+        if height != tip.height {
+            if tip.stale_alerted {
+                info!("✅ Tip moving again at height {}", height);
+            }
+            *tip = TipState { height, changed_at: Instant::now(), stale_alerted: false };
+        }
+    }
+
+    fn check_stale(&self) {
+        let (height, idle) = {
+            let mut tip = self.tip.write().unwrap();
+            let idle = tip.changed_at.elapsed();
+            if tip.stale_alerted || idle < self.config.stale_after {
+                return;
+            }
+            tip.stale_alerted = true;
+            (tip.height, idle)
+        };
+        self.raise(
+            AlertKind::StaleTip,
+            Severity::Warning,
+            format!("Tip has not moved from height {} for {} minutes", height, idle.as_secs() / 60),
+            height,
+            None,
+            json!({ "idle_secs": idle.as_secs() }),
+        );
+    }
+
+    /// Feed tip changes from consensus into the stale tip check
+    pub fn watch_tip(self: &Arc<Self>, mut tips: broadcast::Receiver<ChainTip>) {
+        let alerter = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match tips.recv().await {
+                    Ok(tip) => alerter.tip_seen(tip.height),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Check for a stale tip periodically and deliver alerts to the webhook
+    pub fn start(self: &Arc<Self>) {
+        let alerter = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(STALE_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                alerter.check_stale();
+            }
+        });
+
+        if let Some(url) = self.config.webhook.clone() {
+            info!("🔔 Posting chain alerts to {}", url);
+            let mut alerts = self.subscribe();
+            tokio::spawn(async move {
+                let client = reqwest::Client::new();
+                loop {
+                    let alert = match alerts.recv().await {
+                        Ok(alert) => alert,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("⚠️  Webhook fell behind; {} alerts not delivered", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    };
+                    let result = client.post(&url)
+                        .timeout(Duration::from_secs(10))
+                        .json(&alert)
+                        .send()
+                        .await
+                        .and_then(|response| response.error_for_status());
+                    if let Err(e) = result {
+                        warn!("⚠️  Failed to deliver {:?} alert to webhook: {}", alert.kind, e);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// `blockchain_getChainTips` report for a full node: branches in the block
+/// index within `window` blocks of the tip, with operator-invalidated ones
+/// marked invalid, then invalid blocks peers sent
+pub async fn chain_tips(blockchain: &BlockchainBackend, alerter: &ChainAlerter, window: u64) -> Vec<ChainTipInfo> {
+    let consensus = &blockchain.consensus;
+    let state = consensus.get_chain_state().await;
+    let from = state.height.saturating_sub(window);
+    let headers: HashMap<Hash256, (Hash256, u64)> = consensus.get_block_headers(from, u64::MAX, None).await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|header| (header.hash, (header.prev_hash, header.height)))
+        .collect();
+    let mut best = HashMap::new();
+    for height in from..=state.height {
+        if let Some(block) = consensus.get_block_by_height(height).await {
+            best.insert(height, block.get_hash());
+        }
+    }
+
+    let mut tips = branch_tips(&headers, |height| best.get(&height).copied(), (state.height, state.best_block_hash), window);
+    let invalidated: HashSet<String> = consensus.invalidated_blocks().await.iter().map(hex::encode).collect();
+    for tip in tips.iter_mut() {
+        if invalidated.contains(&tip.hash) {
+            tip.status = TipStatus::Invalid;
+        }
+    }
+    tips.extend(alerter.invalid_tips(state.height, window));
+    tips
+}
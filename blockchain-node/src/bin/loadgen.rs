@@ -0,0 +1,385 @@
+//! Load generator for the node's RPC and REST servers
+//!
+//! Fires a weighted mix of calls at a fixed request rate for a while and
+//! reports, per call kind, how many went out, how many failed and the
+//! latency percentiles. Requests are scheduled open-loop: a slow server
+//! doesn't slow the generator down, and a request that can't start because
+//! `--concurrency` requests are already waiting is counted as dropped.
+//!
+//! ```text
+//! loadgen --rps 200 --duration 60 --mix rpc_balance=50,rest_mempool=30,rpc_mempool=20
+//! loadgen --rps 20 --mix rest_send=1 --send-wallet <id> --send-to <address> --allow-sends
+//! ```
+//!
+//! Sends spend real coins, so they only run with `--allow-sends`; point them
+//! at a regtest node.
+
+use anyhow::{bail, Context, Result};
+use blockchain_node::treasury::TREASURY_ADDRESS;
+use clap::Parser;
+use rand::Rng;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::time::MissedTickBehavior;
+
+#[derive(Parser)]
+#[command(name = "loadgen")]
+#[command(about = "Load test the EduNet node's RPC and REST servers")]
+struct Cli {
+    /// JSON-RPC endpoint
+    #[arg(long, default_value = "http://127.0.0.1:8545")]
+    rpc_url: String,
+
+    /// REST API base URL
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    rest_url: String,
+
+    /// Requests started per second
+    #[arg(long, default_value_t = 50.0)]
+    rps: f64,
+
+    /// How long to run, in seconds
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+
+    /// Most requests waiting on a response at once
+    #[arg(long, default_value_t = 256)]
+    concurrency: usize,
+
+    /// Call kinds and their weights, e.g. rpc_balance=60,rest_mempool=40.
+    /// Kinds: rpc_balance, rest_balance, rpc_mempool, rest_mempool, rest_send
+    #[arg(long, default_value = "rpc_balance=40,rest_balance=20,rpc_mempool=20,rest_mempool=20")]
+    mix: String,
+
+    /// Address whose balance is looked up
+    #[arg(long, default_value = TREASURY_ADDRESS)]
+    address: String,
+
+    /// Wallet id sends are made from
+    #[arg(long)]
+    send_wallet: Option<String>,
+
+    /// Address sends pay
+    #[arg(long)]
+    send_to: Option<String>,
+
+    /// Amount each send pays
+    #[arg(long, default_value_t = 1_000)]
+    send_amount: u64,
+
+    /// Run sends; they spend real coins, use a regtest node
+    #[arg(long)]
+    allow_sends: bool,
+
+    /// Give up on a request after this many milliseconds
+    #[arg(long, default_value_t = 5_000)]
+    timeout_ms: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Kinds of call the generator makes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CallKind {
+    RpcBalance,
+    RestBalance,
+    RpcMempool,
+    RestMempool,
+    RestSend,
+}
+
+impl CallKind {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "rpc_balance" => CallKind::RpcBalance,
+            "rest_balance" => CallKind::RestBalance,
+            "rpc_mempool" => CallKind::RpcMempool,
+            "rest_mempool" => CallKind::RestMempool,
+            "rest_send" => CallKind::RestSend,
+            other => bail!("unknown call kind '{}'", other),
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CallKind::RpcBalance => "rpc_balance",
+            CallKind::RestBalance => "rest_balance",
+            CallKind::RpcMempool => "rpc_mempool",
+            CallKind::RestMempool => "rest_mempool",
+            CallKind::RestSend => "rest_send",
+        }
+    }
+}
+
+/// Weighted call kinds
+struct Mix {
+    kinds: Vec<(CallKind, u32)>,
+    total: u32,
+}
+
+impl Mix {
+    fn parse(spec: &str) -> Result<Self> {
+        let mut kinds = Vec::new();
+        for part in spec.split(',').filter(|part| !part.trim().is_empty()) {
+            let (name, weight) = part.split_once('=').unwrap_or((part, "1"));
+            let weight: u32 = weight.trim().parse().with_context(|| format!("bad weight in '{}'", part))?;
+            if weight > 0 {
+                kinds.push((CallKind::parse(name.trim())?, weight));
+            }
+        }
+        let total = kinds.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            bail!("the mix has no call kinds");
+        }
+        Ok(Self { kinds, total })
+    }
+
+    fn pick(&self, rng: &mut impl Rng) -> CallKind {
+        let mut roll = rng.gen_range(0..self.total);
+        for (kind, weight) in &self.kinds {
+            if roll < *weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+        self.kinds[0].0
+    }
+
+    fn contains(&self, kind: CallKind) -> bool {
+        self.kinds.iter().any(|(k, _)| *k == kind)
+    }
+}
+
+/// What was measured for one call kind
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+/// Report line for one call kind, or all of them
+#[derive(Debug, Serialize)]
+struct KindReport {
+    kind: String,
+    requests: u64,
+    errors: u64,
+    error_rate: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+impl KindReport {
+    fn new(kind: &str, mut latencies: Vec<Duration>, errors: u64) -> Self {
+        latencies.sort();
+        let requests = latencies.len() as u64;
+        let percentile = |p: f64| -> f64 {
+            if latencies.is_empty() {
+                return 0.0;
+            }
+            let index = ((latencies.len() as f64 * p).ceil() as usize).clamp(1, latencies.len()) - 1;
+            latencies[index].as_secs_f64() * 1000.0
+        };
+        Self {
+            kind: kind.to_string(),
+            requests,
+            errors,
+            error_rate: if requests > 0 { errors as f64 / requests as f64 } else { 0.0 },
+            p50_ms: percentile(0.50),
+            p90_ms: percentile(0.90),
+            p99_ms: percentile(0.99),
+            max_ms: latencies.last().map_or(0.0, |max| max.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    target_rps: f64,
+    achieved_rps: f64,
+    duration_secs: f64,
+    /// Requests not started because `concurrency` were already in flight
+    dropped: u64,
+    kinds: Vec<KindReport>,
+    total: KindReport,
+}
+
+/// Makes one call of each kind
+struct Caller {
+    client: reqwest::Client,
+    rpc_url: String,
+    rest_url: String,
+    address: String,
+    send_wallet: Option<String>,
+    send_to: Option<String>,
+    send_amount: u64,
+}
+
+impl Caller {
+    /// Whether the call succeeded; transport errors, HTTP errors and error
+    /// responses all count as failures
+    async fn call(&self, kind: CallKind) -> bool {
+        let result = match kind {
+            CallKind::RpcBalance => self.rpc("wallet_getBalance", json!([self.address])).await,
+            CallKind::RpcMempool => self.rpc("mempool_getInfo", json!({})).await,
+            CallKind::RestBalance => self.rest_get(&format!("/api/v1/addresses/{}/balance", self.address)).await,
+            CallKind::RestMempool => self.rest_get("/api/v1/mempool/info").await,
+            CallKind::RestSend => self.rest_send().await,
+        };
+        result.is_ok()
+    }
+
+    async fn rpc(&self, method: &str, params: Value) -> Result<()> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = self.client.post(&self.rpc_url).json(&request).send().await?
+            .error_for_status()?
+            .json().await?;
+        // Some methods report failures inside an otherwise successful result
+        let failed = !response["error"].is_null() || response["result"].get("error").is_some();
+        if failed {
+            bail!("{} failed", method);
+        }
+        Ok(())
+    }
+
+    async fn rest_get(&self, path: &str) -> Result<()> {
+        let response = self.client.get(format!("{}{}", self.rest_url, path)).send().await?;
+        Self::check_rest(response).await
+    }
+
+    async fn rest_send(&self) -> Result<()> {
+        let (Some(wallet_id), Some(to_address)) = (&self.send_wallet, &self.send_to) else {
+            bail!("sends need --send-wallet and --send-to");
+        };
+        let body = json!({ "wallet_id": wallet_id, "to_address": to_address, "amount": self.send_amount });
+        let response = self.client.post(format!("{}/api/v1/wallets/{}/send", self.rest_url, wallet_id))
+            .json(&body)
+            .send()
+            .await?;
+        Self::check_rest(response).await
+    }
+
+    async fn check_rest(response: reqwest::Response) -> Result<()> {
+        let body: Value = response.error_for_status()?.json().await?;
+        if body["success"].as_bool() != Some(true) {
+            bail!("request failed: {}", body["error"]);
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mix = Mix::parse(&cli.mix)?;
+    if cli.rps <= 0.0 {
+        bail!("--rps must be positive");
+    }
+    if mix.contains(CallKind::RestSend) {
+        if !cli.allow_sends {
+            bail!("the mix sends coins; pass --allow-sends, and only against a regtest node");
+        }
+        if cli.send_wallet.is_none() || cli.send_to.is_none() {
+            bail!("sends need --send-wallet and --send-to");
+        }
+    }
+
+    let caller = Arc::new(Caller {
+        client: reqwest::Client::builder().timeout(Duration::from_millis(cli.timeout_ms)).build()?,
+        rpc_url: cli.rpc_url.clone(),
+        rest_url: cli.rest_url.trim_end_matches('/').to_string(),
+        address: cli.address.clone(),
+        send_wallet: cli.send_wallet.clone(),
+        send_to: cli.send_to.clone(),
+        send_amount: cli.send_amount,
+    });
+    let samples: Arc<Mutex<BTreeMap<CallKind, Samples>>> = Arc::default();
+    let in_flight = Arc::new(Semaphore::new(cli.concurrency.max(1)));
+    let mut dropped = 0u64;
+
+    eprintln!("🚀 {} requests/s for {}s: {}", cli.rps, cli.duration, cli.mix);
+    let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / cli.rps));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let started = Instant::now();
+    let run_for = Duration::from_secs(cli.duration);
+    let mut tasks = Vec::new();
+    let mut rng = rand::thread_rng();
+
+    while started.elapsed() < run_for {
+        ticks.tick().await;
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            dropped += 1;
+            continue;
+        };
+        let kind = mix.pick(&mut rng);
+        let caller = caller.clone();
+        let samples = samples.clone();
+        tasks.push(tokio::spawn(async move {
+            let sent = Instant::now();
+            let ok = caller.call(kind).await;
+            let latency = sent.elapsed();
+            drop(permit);
+            let mut samples = samples.lock().unwrap();
+            let entry = samples.entry(kind).or_default();
+            entry.latencies.push(latency);
+            if !ok {
+                entry.errors += 1;
+            }
+        }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let samples = std::mem::take(&mut *samples.lock().unwrap());
+    let mut all_latencies = Vec::new();
+    let mut all_errors = 0;
+    let mut kinds = Vec::new();
+    for (kind, samples) in samples {
+        all_latencies.extend_from_slice(&samples.latencies);
+        all_errors += samples.errors;
+        kinds.push(KindReport::new(kind.name(), samples.latencies, samples.errors));
+    }
+    let total = KindReport::new("total", all_latencies, all_errors);
+    let report = Report {
+        target_rps: cli.rps,
+        achieved_rps: if elapsed > 0.0 { total.requests as f64 / elapsed } else { 0.0 },
+        duration_secs: elapsed,
+        dropped,
+        kinds,
+        total,
+    };
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    println!(
+        "{:.1} requests/s achieved of {:.1} over {:.1}s, {} dropped",
+        report.achieved_rps, report.target_rps, report.duration_secs, report.dropped
+    );
+    println!(
+        "{:<14} {:>9} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9}",
+        "kind", "requests", "errors", "err%", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+    for line in report.kinds.iter().chain(std::iter::once(&report.total)) {
+        println!(
+            "{:<14} {:>9} {:>8} {:>6.2}% {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            line.kind, line.requests, line.errors, line.error_rate * 100.0,
+            line.p50_ms, line.p90_ms, line.p99_ms, line.max_ms
+        );
+    }
+}
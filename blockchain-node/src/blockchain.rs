@@ -3,26 +3,45 @@
 //! This module provides the complete blockchain implementation for the full node daemon.
 //! Ported from edunet-web's working blockchain integration.
 
-use blockchain_core::consensus::ConsensusValidator;
+use blockchain_core::consensus::{ConsensusParams, ConsensusValidator};
+use blockchain_core::supply_audit::SupplyAuditConfig;
 use blockchain_core::wallet::WalletManager;
 use blockchain_core::genesis::{GenesisCreator, GenesisConfig};
 use blockchain_core::transaction::{Transaction, TransactionInput, TransactionOutput};
 use blockchain_core::block::Block;
+use blockchain_core::canonical::HexHash;
 use blockchain_core::utxo::{UTXOSet, UTXO};
-use blockchain_core::mempool::{Mempool, MempoolConfig};
+use blockchain_core::mempool::{AcceptCheck, EvictionCandidate, GasPrice, Mempool, MempoolConfig, MempoolInfo, RemovalReason};
 use blockchain_core::tx_builder::{TransactionBuilder, TransactionManager};
 use blockchain_core::sync::{SyncEngine, SyncConfig};
-use blockchain_core::contracts::{ContractExecutor, ExecutionResult};
-use blockchain_core::{Hash256, Amount, Result as BlockchainResult};
+use blockchain_core::contracts::{CallTrace, ContractExecutor, ExecutionResult};
+use blockchain_core::contract_calls::CallConfig;
+use blockchain_core::contract_proxy::ProxyInfo;
+use blockchain_core::contract_state::StateOverlay;
+use blockchain_core::abi::Abi;
+use blockchain_core::naming::{NameCommitment, NameOp, NameRecord, NameRegistry};
+use blockchain_core::storage::DiskBlockStorage;
+use blockchain_core::{Hash256, BlockchainError, Result as BlockchainResult};
 
 use blockchain_network::NetworkConfig;
 use blockchain_network::NetworkManager;
 use anyhow::Result;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{info, error, warn};
 use hex;
 
+/// How often peers are told the mempool's current fee floor
+const FEE_FILTER_REFRESH: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Block files, inside the data directory
+pub const BLOCKS_DIR: &str = "blocks";
+
+/// Persisted contract state, inside the data directory
+pub const CONTRACTS_DIR: &str = "contracts";
+
 #[derive(Clone)]
 pub struct BlockchainBackend {
     pub network: Arc<NetworkManager>,
@@ -30,35 +49,50 @@ pub struct BlockchainBackend {
     pub wallets: Arc<RwLock<WalletManager>>,
     pub mempool: Arc<RwLock<Mempool>>,
     pub utxo_set: Arc<RwLock<UTXOSet>>,
-    pub tx_manager: Arc<TransactionManager>,
+    pub tx_manager: Arc<RwLock<TransactionManager>>,
     pub sync_engine: Arc<SyncEngine>,
     pub contract_executor: Arc<ContractExecutor>,
+    pub names: Arc<NameRegistry>,
+    pub storage: Arc<DiskBlockStorage>,
 }
 
 impl BlockchainBackend {
-    pub async fn new(network_config: NetworkConfig) -> Result<Self> {
+    pub async fn new(
+        data_dir: &Path,
+        network_config: NetworkConfig,
+        consensus_params: ConsensusParams,
+        genesis_config: GenesisConfig,
+        supply_audit: Option<SupplyAuditConfig>,
+        mempool_config: MempoolConfig,
+        call_config: CallConfig,
+    ) -> Result<Self> {
         info!("🚀 Initializing blockchain backend for full node...");
 
         // Initialize UTXO set
         let utxo_set = Arc::new(RwLock::new(UTXOSet::new()));
         
         // Initialize disk storage for blocks
-        let data_dir = std::path::PathBuf::from("./blockchain-data/blocks");
+        let block_dir = data_dir.join(BLOCKS_DIR);
         let storage = Arc::new(
-            blockchain_core::storage::DiskBlockStorage::new(&data_dir)
+            DiskBlockStorage::new(&block_dir)
                 .map_err(|e| anyhow::anyhow!("Failed to initialize block storage: {}", e))?
         );
-        info!("💾 Block storage initialized at: {}", data_dir.display());
+        info!("💾 Block storage initialized at: {}", block_dir.display());
         
         // Initialize consensus with genesis block and storage
-        let consensus_params = blockchain_core::consensus::ConsensusParams::default();
-        let consensus = Arc::new(
-            ConsensusValidator::new(consensus_params)
-                .with_storage(storage.clone())
-        );
+        if let Some(fund) = &consensus_params.dev_fund {
+            info!("🏛️  Dev fund: {}% of block reward to {}", fund.percent, fund.address);
+        }
+        let mut consensus = ConsensusValidator::new(consensus_params)
+            .with_storage(storage.clone());
+        if let Some(config) = supply_audit {
+            info!("🔍 Supply audit enabled; the chain halts on any supply divergence");
+            consensus = consensus.with_supply_audit(config);
+        }
+        let consensus = Arc::new(consensus);
         
         // Create genesis state
-        let genesis_creator = GenesisCreator::new(Some(GenesisConfig::default()));
+        let genesis_creator = GenesisCreator::new(Some(genesis_config));
         let genesis_state = genesis_creator.create_genesis_state()
             .map_err(|e| anyhow::anyhow!("Failed to create genesis state: {}", e))?;
         
@@ -84,24 +118,25 @@ impl BlockchainBackend {
         let wallets = Arc::new(RwLock::new(WalletManager::new()));
         
         // Initialize mempool with consensus for proper fee calculation
-        let mempool_config = MempoolConfig::default();
         let mut mempool_instance = Mempool::new(mempool_config);
         mempool_instance.set_consensus_validator(consensus.clone());
         let mempool = Arc::new(RwLock::new(mempool_instance));
         
         // Initialize transaction manager
         let utxo_clone = utxo_set.read().await.clone();
-        let tx_manager = Arc::new(TransactionManager::new(utxo_clone));
+        let tx_manager = Arc::new(RwLock::new(TransactionManager::new(utxo_clone)));
 
         // Initialize network with consensus
-        let network = NetworkManager::new(network_config, Some(consensus.clone()))?;
+        let network = NetworkManager::new_async(network_config, Some(consensus.clone())).await?;
 
         // Initialize sync engine
         let sync_config = SyncConfig::default();
         let sync_engine = Arc::new(SyncEngine::new(sync_config, consensus.clone()));
         
         // Initialize contract executor with persistence
-        let contract_executor = Arc::new(ContractExecutor::with_path("blockchain-data/contracts"));
+        let contract_executor = Arc::new(
+            ContractExecutor::with_path(data_dir.join(CONTRACTS_DIR)).with_call_config(call_config)
+        );
         
         // Load existing contracts from disk
         if let Err(e) = contract_executor.load_contracts().await {
@@ -120,6 +155,8 @@ impl BlockchainBackend {
             tx_manager,
             sync_engine,
             contract_executor,
+            names: Arc::new(NameRegistry::new()),
+            storage,
         })
     }
 
@@ -141,19 +178,69 @@ impl BlockchainBackend {
         None
     }
 
-    /// Submit transaction to mempool
+    /// Submit transaction to mempool and track it as a pending wallet
+    /// transaction, which can then be abandoned, cancelled or rebroadcast
     pub async fn submit_transaction(&self, tx: Transaction) -> BlockchainResult<Hash256> {
         let tx_hash: Hash256 = tx.get_hash()?;
         
         // Validate and add transaction
         let mut mempool = self.mempool.write().await;
-        mempool.add_transaction(tx).await?;
+        mempool.add_transaction(tx.clone()).await?;
+        let fee = mempool.get_fee(&tx_hash).unwrap_or_default();
+        drop(mempool);
+        
+        // A rebroadcast resubmits a transaction that is already pending
+        let mut tx_manager = self.tx_manager.write().await;
+        if tx_manager.get_pending_transaction(&hex::encode(tx_hash)).is_none() {
+            tx_manager.add_pending_with_fee(tx, fee)?;
+        }
+        drop(tx_manager);
         
         info!("✅ Transaction {} added to mempool", hex::encode(&tx_hash));
+        self.update_fee_filter().await;
         
         Ok(tx_hash)
     }
 
+    /// Tell peers the fee rate our mempool currently accepts, so they stop
+    /// relaying transactions it would refuse
+    pub async fn update_fee_filter(&self) {
+        let min_fee_rate = self.mempool.read().await.min_fee_rate();
+        self.network.set_min_fee_rate(min_fee_rate).await;
+    }
+
+    /// Abandon a wallet transaction that was never relayed, releasing its inputs
+    pub async fn abandon_transaction(&self, tx_hash: &str) -> BlockchainResult<()> {
+        let abandoned = self.tx_manager.write().await.abandon_transaction(tx_hash)?;
+
+        let hash = abandoned.transaction.get_hash()?;
+        self.mempool.write().await
+            .remove_transaction(&hash, RemovalReason::Manual).await?;
+        self.wallets.write().await.mark_abandoned(tx_hash);
+
+        info!("🗑️  Transaction {} abandoned", tx_hash);
+        Ok(())
+    }
+
+    /// Cancel a relayed transaction by double-spending its inputs back to the
+    /// sending wallet with a higher fee (replace-by-fee)
+    pub async fn cancel_transaction(&self, tx_hash: &str, wallet_address: &str, fee_bump: u64) -> BlockchainResult<Hash256> {
+        let wallet = self.wallets.read().await
+            .get_wallet_by_address(wallet_address)
+            .cloned()
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_address.to_string()))?;
+
+        let chain = self.consensus.get_utxo_set().await;
+        let cancel = self.tx_manager.read().await
+            .create_cancel_transaction(tx_hash, &wallet, fee_bump, &chain)?;
+        let cancel_hash = self.submit_transaction(cancel.clone()).await?;
+        let fee = self.mempool.read().await.get_fee(&cancel_hash).unwrap_or_default();
+        self.tx_manager.write().await.record_cancellation(tx_hash, cancel, fee)?;
+
+        info!("↩️  Transaction {} cancelled by {}", tx_hash, hex::encode(cancel_hash));
+        Ok(cancel_hash)
+    }
+
     /// Get pending transactions from mempool
     pub async fn get_pending_transactions(&self) -> Vec<Transaction> {
         let mempool = self.mempool.read().await;
@@ -161,6 +248,184 @@ impl BlockchainBackend {
         mempool.get_transactions()
     }
 
+    /// Force specific transactions out of the mempool
+    pub async fn evict_transactions(&self, tx_hashes: &[Hash256]) -> BlockchainResult<Vec<Hash256>> {
+        let mut mempool = self.mempool.write().await;
+        let mut evicted = Vec::new();
+        for tx_hash in tx_hashes {
+            if mempool.contains_transaction(tx_hash) {
+                mempool.remove_transaction(tx_hash, RemovalReason::Manual).await?;
+                evicted.push(*tx_hash);
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Force every transaction below `min_fee_rate` out of the mempool
+    pub async fn evict_below_fee_rate(&self, min_fee_rate: u64) -> BlockchainResult<Vec<Hash256>> {
+        self.mempool.write().await.evict_below_fee_rate(min_fee_rate).await
+    }
+
+    /// Run the mempool's acceptance checks on a transaction without
+    /// submitting it
+    pub async fn test_accept(&self, tx: &Transaction) -> BlockchainResult<AcceptCheck> {
+        self.mempool.read().await.test_accept(tx).await
+    }
+
+    /// Transactions the mempool would evict to free `bytes`, without
+    /// evicting them
+    pub async fn what_would_evict(&self, bytes: usize) -> Vec<EvictionCandidate> {
+        self.mempool.read().await.what_would_evict(bytes)
+    }
+
+    /// Mempool size and the fee floor it currently enforces
+    pub async fn mempool_info(&self) -> MempoolInfo {
+        self.mempool.read().await.info()
+    }
+
+    /// Rebuild the node's UTXO views from consensus state and drop mempool
+    /// transactions whose inputs are no longer spendable
+    pub async fn reindex(&self) -> BlockchainResult<serde_json::Value> {
+        let utxo_set = self.consensus.get_utxo_set().await;
+        let utxo_count = utxo_set.get_utxo_count();
+
+        *self.utxo_set.write().await = utxo_set.clone();
+        *self.tx_manager.write().await.get_utxo_set_mut() = utxo_set.clone();
+
+        let mut mempool = self.mempool.write().await;
+        let mut dropped = 0;
+        for tx in mempool.get_transactions() {
+            let spendable = tx.inputs.iter().all(|input| {
+                let outpoint = format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index);
+                utxo_set.get_utxo(&outpoint).is_some() || mempool.contains_transaction(&input.prev_tx_hash)
+            });
+            if !spendable {
+                mempool.remove_transaction(&tx.get_hash()?, RemovalReason::Invalid("inputs spent or missing".to_string())).await?;
+                dropped += 1;
+            }
+        }
+
+        let height = self.get_height().await;
+        info!("🔁 Reindexed at height {}: {} UTXOs, {} mempool transactions dropped", height, utxo_count, dropped);
+
+        Ok(serde_json::json!({
+            "height": height,
+            "utxo_count": utxo_count,
+            "mempool_dropped": dropped,
+            "mempool_size": mempool.transaction_count(),
+        }))
+    }
+
+    /// Mark a block invalid, rewinding the chain off it if needed, and
+    /// return the disconnected transactions to the mempool
+    pub async fn invalidate_block(&self, hash: Hash256) -> BlockchainResult<serde_json::Value> {
+        let disconnected = self.consensus.invalidate_block(hash).await?;
+        let reindex = self.reindex().await?;
+
+        let mut returned = 0;
+        let mut mempool = self.mempool.write().await;
+        for tx in disconnected.iter().flat_map(|block| block.transactions.iter().skip(1)) {
+            if mempool.add_transaction(tx.clone()).await.is_ok() {
+                returned += 1;
+            }
+        }
+
+        Ok(serde_json::json!({
+            "invalidated": HexHash(hash),
+            "blocks_disconnected": disconnected.len(),
+            "transactions_returned": returned,
+            "height": reindex["height"],
+        }))
+    }
+
+    /// Clear an invalidation, switching back to the block's branch if it is
+    /// now the longest
+    pub async fn reconsider_block(&self, hash: Hash256) -> BlockchainResult<serde_json::Value> {
+        let connected = self.consensus.reconsider_block(hash).await?;
+        let reindex = self.reindex().await?;
+        Ok(serde_json::json!({
+            "reconsidered": HexHash(hash),
+            "blocks_connected": connected.len(),
+            "height": reindex["height"],
+        }))
+    }
+
+    /// Index name operations up to the chain tip, starting over from
+    /// genesis if the last indexed block was reorganized away
+    pub async fn sync_names(&self) -> u64 {
+        let height = self.get_height().await;
+        let mut next = match self.names.tip().await {
+            Some((indexed, hash)) => match self.get_block_by_height(indexed).await {
+                Some(block) if block.get_hash() == hash => indexed + 1,
+                _ => {
+                    self.names.reset().await;
+                    0
+                }
+            },
+            None => 0,
+        };
+        while next <= height {
+            let Some(block) = self.get_block_by_height(next).await else { break };
+            if let Err(e) = self.names.index_block(&block).await {
+                warn!("Failed to index names in block {}: {}", next, e);
+            }
+            next += 1;
+        }
+        height
+    }
+
+    /// Active record for a name at the chain tip
+    pub async fn resolve_name(&self, name: &str) -> Option<NameRecord> {
+        let height = self.sync_names().await;
+        self.names.resolve(name, height).await
+    }
+
+    /// Active names pointing at an address
+    pub async fn names_for(&self, address: &str) -> Vec<NameRecord> {
+        let height = self.sync_names().await;
+        self.names.names_for(address, height).await
+    }
+
+    /// Register, renew or update a name, paying from a node wallet
+    pub async fn submit_name_operation(&self, wallet_address: &str, commitment: NameCommitment, periods: u64) -> BlockchainResult<Hash256> {
+        let wallet = self.wallets.read().await
+            .get_wallet_by_address(wallet_address)
+            .cloned()
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_address.to_string()))?;
+
+        // Catch obvious rejections before burning a fee; the indexer has the final say
+        let height = self.sync_names().await;
+        match &commitment.op {
+            NameOp::Register { .. } => {
+                if !self.names.is_available(&commitment.name, height + 1).await? {
+                    return Err(BlockchainError::InvalidInput(format!("{} is already registered", commitment.name)));
+                }
+            }
+            NameOp::Renew => {
+                if !self.names.get(&commitment.name).await.is_some_and(|record| record.is_reserved(height + 1)) {
+                    return Err(BlockchainError::InvalidInput(format!("{} is not registered", commitment.name)));
+                }
+            }
+            NameOp::Update { .. } => {
+                if !self.names.get(&commitment.name).await.is_some_and(|record| record.owner == wallet.address && record.is_active(height + 1)) {
+                    return Err(BlockchainError::InvalidInput(format!("{} is not an active name owned by {}", commitment.name, wallet.address)));
+                }
+            }
+        }
+
+        let output = commitment.to_output(periods)?;
+        let tx = {
+            let utxo_set = self.utxo_set.read().await;
+            TransactionBuilder::new()
+                .add_script_output(output.script_pubkey, output.value)
+                .build(&wallet, &utxo_set)?
+        };
+        let tx_hash = self.submit_transaction(tx).await?;
+
+        info!("🏷️  Name operation on {} submitted in {}", commitment.name, hex::encode(tx_hash));
+        Ok(tx_hash)
+    }
+
     /// Get mempool stats
     pub async fn get_mempool_stats(&self) -> serde_json::Value {
         let mempool = self.mempool.read().await;
@@ -190,7 +455,7 @@ impl BlockchainBackend {
         
         serde_json::json!({
             "block_height": chain_state.height,
-            "best_block_hash": hex::encode(&chain_state.best_block_hash),
+            "best_block_hash": HexHash(chain_state.best_block_hash),
             "total_work": chain_state.total_work,
             "difficulty": chain_state.next_difficulty,
             "mempool": {
@@ -233,6 +498,26 @@ impl BlockchainBackend {
         }).collect()
     }
     
+    /// Price per gas of a deploy or call: the bid's effective price at the
+    /// mempool's base fee, or the base fee itself without a bid
+    pub async fn contract_gas_price(&self, bid: Option<GasPrice>) -> Result<u64> {
+        let base_fee = self.mempool.read().await.base_fee_per_gas();
+        match bid {
+            Some(bid) => {
+                bid.check(base_fee).map_err(|e| anyhow::anyhow!(e))?;
+                Ok(bid.effective_price(base_fee))
+            }
+            None => Ok(base_fee),
+        }
+    }
+    
+    /// Nonce of a local deploy or call: `nonce` if unused, else the next
+    pub async fn local_contract_nonce(&self, caller: &str, nonce: Option<u64>) -> Result<u64> {
+        self.contract_executor.next_local_nonce(caller, nonce)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+    
     /// Deploy a smart contract
     pub async fn deploy_contract(
         &self,
@@ -240,6 +525,7 @@ impl BlockchainBackend {
         bytecode: Vec<u8>,
         value: u64,
         gas_limit: u64,
+        gas_price: u64,
     ) -> Result<ExecutionResult> {
         info!("📝 Deploying contract from {}", deployer);
         
@@ -253,7 +539,8 @@ impl BlockchainBackend {
             deployer,
             bytecode,
             value,
-            gas_limit
+            gas_limit,
+            gas_price,
         ).await.map_err(|e| anyhow::anyhow!("Contract deployment failed: {}", e))?;
         
         if result.success {
@@ -275,6 +562,7 @@ impl BlockchainBackend {
         calldata: Vec<u8>,
         value: u64,
         gas_limit: u64,
+        gas_price: u64,
     ) -> Result<ExecutionResult> {
         info!("📞 Calling contract {:?} from {}", contract_address, caller);
         
@@ -289,7 +577,8 @@ impl BlockchainBackend {
             contract_address,
             calldata,
             value,
-            gas_limit
+            gas_limit,
+            gas_price,
         ).await.map_err(|e| anyhow::anyhow!("Contract call failed: {}", e))?;
         
         if result.success {
@@ -301,6 +590,87 @@ impl BlockchainBackend {
         Ok(result)
     }
     
+    /// Dry-run a contract call, or a deployment when `contract_address` is
+    /// None, recording every opcode
+    pub async fn trace_contract(
+        &self,
+        caller: &str,
+        contract_address: Option<blockchain_core::contracts::EthAddress>,
+        data: Vec<u8>,
+        value: u64,
+        gas_limit: u64,
+        max_steps: usize,
+    ) -> Result<CallTrace> {
+        let balance = self.get_balance(caller).await?;
+        self.contract_executor.set_balance(caller, balance).await;
+        
+        self.contract_executor.trace_call(caller, contract_address, data, value, gas_limit, max_steps)
+            .await
+            .map_err(|e| anyhow::anyhow!("Contract trace failed: {}", e))
+    }
+    
+    /// Run contract transactions up to the chain tip, first rolling back
+    /// blocks a reorg disconnected. Each block's state is committed whole,
+    /// or dropped when one of its transactions cannot run.
+    pub async fn sync_contracts(&self) -> u64 {
+        let executor = &self.contract_executor;
+        let height = self.get_height().await;
+        let mut next = loop {
+            let Some((applied, hash)) = executor.applied_tip().await else { break 0 };
+            match self.get_block_by_height(applied).await {
+                Some(block) if block.get_hash() == hash => break applied + 1,
+                _ => {}
+            }
+            if let Err(e) = executor.disconnect_block().await {
+                warn!("Cannot roll back contract state of block {}: {}", applied, e);
+                break applied + 1;
+            }
+            info!("↩️  Rolled back contract state of block {}", applied);
+        };
+        while next <= height {
+            let Some(block) = self.get_block_by_height(next).await else { break };
+            let overlay = executor.execute_block(next, &block).await.unwrap_or_else(|e| {
+                warn!("Discarding contract state of block {}: {}", next, e);
+                StateOverlay::new(next, block.get_hash(), block.header.prev_block_hash)
+            });
+            if let Err(e) = executor.commit_block(overlay).await {
+                warn!("Failed to commit contract state of block {}: {}", next, e);
+                break;
+            }
+            next += 1;
+        }
+        height
+    }
+
+    /// Resend our fee filter as the mempool floor decays
+    pub fn watch_fee_filter(self: &Arc<Self>) {
+        let blockchain = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FEE_FILTER_REFRESH);
+            loop {
+                interval.tick().await;
+                blockchain.update_fee_filter().await;
+            }
+        });
+    }
+
+    /// Keep contract state in step with the chain as new tips arrive
+    pub fn watch_contracts(self: &Arc<Self>) {
+        let blockchain = self.clone();
+        let mut tips = self.consensus.subscribe_tip();
+        tokio::spawn(async move {
+            blockchain.sync_contracts().await;
+            loop {
+                match tips.recv().await {
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        blockchain.sync_contracts().await;
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
     /// Get contract code
     pub async fn get_contract_code(
         &self,
@@ -311,6 +681,24 @@ impl BlockchainBackend {
             .map(|contract| contract.code)
     }
     
+    /// Proxy slots of a contract
+    pub async fn get_proxy_info(
+        &self,
+        contract_address: blockchain_core::contracts::EthAddress,
+    ) -> Result<ProxyInfo> {
+        self.contract_executor.get_proxy_info(contract_address)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+    
+    /// Proxy contracts, optionally only those one admin controls
+    pub async fn list_proxies(
+        &self,
+        admin: Option<blockchain_core::contracts::EthAddress>,
+    ) -> Vec<(blockchain_core::contracts::EthAddress, ProxyInfo)> {
+        self.contract_executor.list_proxies(admin).await
+    }
+    
     /// Query contract events with filter
     pub async fn query_events(
         &self,
@@ -337,4 +725,105 @@ impl BlockchainBackend {
         let indexer = self.contract_executor.event_indexer();
         indexer.get_events_by_address(contract_address).await
     }
+    
+    /// Decode events with their contracts' published ABIs
+    pub async fn decode_events(
+        &self,
+        events: &[blockchain_core::event_indexer::IndexedEvent],
+    ) -> Vec<Option<serde_json::Value>> {
+        let mut decoded = Vec::with_capacity(events.len());
+        for event in events {
+            decoded.push(self.contract_executor.decode_event(event).await);
+        }
+        decoded
+    }
+    
+    /// Publish a contract's ABI as its deployer
+    pub async fn publish_abi(
+        &self,
+        contract_address: blockchain_core::contracts::EthAddress,
+        publisher: &str,
+        abi: Abi,
+    ) -> Result<()> {
+        self.contract_executor.publish_abi(contract_address, publisher, abi)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+    
+    /// ABI to decode a contract with
+    pub async fn get_abi(
+        &self,
+        contract_address: blockchain_core::contracts::EthAddress,
+    ) -> Option<Abi> {
+        self.contract_executor.get_abi(contract_address).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use blockchain_core::genesis::GenesisAccount;
+    use blockchain_core::wallet::Wallet;
+
+    /// A backend in `data_dir` whose genesis funds a wallet it manages
+    pub(crate) async fn funded_backend(data_dir: &Path) -> (BlockchainBackend, Wallet) {
+        let mut wallets = WalletManager::new();
+        let owner = wallets.create_wallet("owner".to_string()).unwrap().clone();
+        let genesis = GenesisConfig {
+            initial_accounts: vec![GenesisAccount {
+                address: owner.address.clone(),
+                balance: 10_000_000,
+                description: "Test funds".to_string(),
+            }],
+            genesis_timestamp: 1_700_000_000,
+            network_id: 0,
+        };
+        let mempool = MempoolConfig { min_relay_fee_rate: 1, ..MempoolConfig::default() };
+
+        let backend = BlockchainBackend::new(
+            data_dir,
+            crate::node::network_config(0, Vec::new()),
+            ConsensusParams::default(),
+            genesis,
+            None,
+            mempool,
+            CallConfig::default(),
+        )
+        .await
+        .unwrap();
+        *backend.wallets.write().await = wallets;
+        (backend, owner)
+    }
+
+    /// A payment of `amount` from `owner`'s genesis funds back to itself
+    pub(crate) async fn payment(backend: &BlockchainBackend, owner: &Wallet, amount: u64) -> Transaction {
+        let chain = backend.consensus.get_utxo_set().await;
+        let funds = chain.get_utxos_for_address(&owner.address)[0].clone();
+        TransactionBuilder::new()
+            .add_output(owner.address.clone(), amount)
+            .spend_utxos(owner, vec![funds])
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_submitted_transactions_can_be_abandoned_and_cancelled() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let (backend, owner) = funded_backend(data_dir.path()).await;
+
+        // Not yet relayed: abandoning drops it from the mempool
+        let tx_hash = hex::encode(backend.submit_transaction(payment(&backend, &owner, 9_990_000).await).await.unwrap());
+        assert_eq!(backend.tx_manager.read().await.get_pending_transaction(&tx_hash).unwrap().fee, 10_000);
+        backend.abandon_transaction(&tx_hash).await.unwrap();
+        assert!(backend.get_pending_transactions().await.is_empty());
+
+        // Relayed: cancelled by a double-spend of its confirmed inputs
+        let tx_hash = hex::encode(backend.submit_transaction(payment(&backend, &owner, 9_980_000).await).await.unwrap());
+        backend.tx_manager.write().await.mark_broadcast(&tx_hash).unwrap();
+        assert!(backend.abandon_transaction(&tx_hash).await.is_err());
+
+        let cancel_hash = hex::encode(backend.cancel_transaction(&tx_hash, &owner.address, 5_000).await.unwrap());
+        let tx_manager = backend.tx_manager.read().await;
+        assert!(tx_manager.get_pending_transaction(&tx_hash).is_none());
+        assert_eq!(tx_manager.get_pending_transaction(&cancel_hash).unwrap().fee, 25_000);
+    }
 }
@@ -0,0 +1,208 @@
+//! Disk Usage and Storage Compaction
+//!
+//! `node_getDiskUsage` breaks the node's footprint down by what holds it:
+//! block files (and how much of them compaction would free), undo data, the
+//! UTXO set, indexes and everything else in the data directory. Parts still
+//! kept only in memory are reported as such rather than as zero-byte files.
+//!
+//! Compaction rewrites the block files keeping only the best chain. It runs
+//! on a schedule or when an operator asks, copies at a capped rate so block
+//! validation keeps its share of the disk, and refuses to start again within
+//! a minimum interval of the last run.
+
+use crate::blockchain::{BlockchainBackend, CONTRACTS_DIR};
+use crate::indexer::IndexManager;
+use blockchain_core::storage::{CompactionReport, DiskBlockStorage};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// Pace and frequency of compaction
+#[derive(Debug, Clone)]
+pub struct CompactionConfig {
+    /// Copy rate cap; None copies as fast as the disk allows
+    pub max_bytes_per_sec: Option<u64>,
+    /// Shortest gap between the start of two runs
+    pub min_interval: Duration,
+    /// Run automatically this often, if set
+    pub schedule: Option<Duration>,
+}
+
+impl Default for CompactionConfig {
+    /// 20 MB/s, at most hourly, never on a schedule
+    fn default() -> Self {
+        Self {
+            max_bytes_per_sec: Some(20_000_000),
+            min_interval: Duration::from_secs(3600),
+            schedule: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CompactionState {
+    running: bool,
+    last_started: Option<Instant>,
+    last_report: Option<CompactionReport>,
+    last_error: Option<String>,
+}
+
+/// Runs block file compactions, one at a time
+pub struct Compactor {
+    storage: Arc<DiskBlockStorage>,
+    config: CompactionConfig,
+    state: Mutex<CompactionState>,
+}
+
+impl Compactor {
+    pub fn new(storage: Arc<DiskBlockStorage>, config: CompactionConfig) -> Self {
+        Self { storage, config, state: Mutex::new(CompactionState::default()) }
+    }
+
+    /// Start a compaction in the background, unless one is running or the
+    /// last one started less than the minimum interval ago
+    pub fn trigger(self: &Arc<Self>) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.running {
+                return Err("A compaction is already running".to_string());
+            }
+            if let Some(started) = state.last_started {
+                let elapsed = started.elapsed();
+                if elapsed < self.config.min_interval {
+                    return Err(format!(
+                        "Last compaction started {}s ago; wait {}s",
+                        elapsed.as_secs(),
+                        (self.config.min_interval - elapsed).as_secs(),
+                    ));
+                }
+            }
+            state.running = true;
+            state.last_started = Some(Instant::now());
+        }
+        tokio::spawn(self.clone().run());
+        Ok(())
+    }
+
+    /// Trigger a compaction every `schedule`, if configured
+    pub fn start_schedule(self: &Arc<Self>) {
+        let Some(every) = self.config.schedule else { return };
+        info!("🗜️  Compacting block storage every {}h", every.as_secs() / 3600);
+        let compactor = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = compactor.trigger() {
+                    info!("⏭️  Skipping scheduled compaction: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn run(self: Arc<Self>) {
+        let result = self.storage.compact(self.config.max_bytes_per_sec).await;
+        let mut state = self.state.lock().unwrap();
+        state.running = false;
+        match result {
+            Ok(report) => {
+                state.last_report = Some(report);
+                state.last_error = None;
+            }
+            Err(e) => {
+                error!("❌ Compaction failed: {}", e);
+                state.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    pub fn status(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        json!({
+            "running": state.running,
+            "seconds_since_last_start": state.last_started.map(|started| started.elapsed().as_secs()),
+            "last_report": state.last_report,
+            "last_error": state.last_error,
+            "max_bytes_per_sec": self.config.max_bytes_per_sec,
+            "min_interval_secs": self.config.min_interval.as_secs(),
+            "schedule_secs": self.config.schedule.map(|every| every.as_secs()),
+        })
+    }
+}
+
+/// Total size of the files under `path`
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries.filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Whether `path` lies inside `dir`, so its bytes are already in `dir`'s total
+fn is_within(path: &Path, dir: &Path) -> bool {
+    match (path.canonicalize(), dir.canonicalize()) {
+        (Ok(path), Ok(dir)) => path.starts_with(dir),
+        _ => false,
+    }
+}
+
+/// `node_getDiskUsage` report
+pub async fn disk_usage(
+    blockchain: &BlockchainBackend,
+    indexes: &IndexManager,
+    compactor: &Compactor,
+    data_dir: &Path,
+) -> Value {
+    let blocks = blockchain.storage.disk_usage().await;
+    let utxo_count = blockchain.utxo_set.read().await.get_utxo_count();
+    let contracts_dir = data_dir.join(CONTRACTS_DIR);
+    let contracts_bytes = dir_size(&contracts_dir);
+    let name_records = blockchain.names.record_count().await;
+
+    let index_entries: Vec<Value> = indexes.entry_counts().into_iter()
+        .map(|(name, entries)| json!({ "name": name, "entries": entries, "persisted": false }))
+        .collect();
+    let block_dir = blockchain.storage.data_dir();
+
+    // Everything else in the data directory: manifest, audit logs, reports
+    let mut other_bytes = dir_size(data_dir);
+    let mut total_bytes = other_bytes;
+    for (path, bytes) in [(block_dir, blocks.file_bytes), (contracts_dir.as_path(), contracts_bytes)] {
+        if is_within(path, data_dir) {
+            other_bytes = other_bytes.saturating_sub(bytes);
+        } else {
+            total_bytes += bytes;
+        }
+    }
+
+    json!({
+        "blocks": {
+            "path": block_dir.display().to_string(),
+            "bytes": blocks.file_bytes,
+            "files": blocks.file_count,
+            "blocks": blocks.block_count,
+            "live_bytes": blocks.live_bytes,
+            "reclaimable_bytes": blocks.reclaimable_bytes,
+        },
+        // Reorgs roll back from in-memory UTXO snapshots; nothing is written
+        "undo": { "bytes": 0, "persisted": false },
+        "utxo": { "bytes": 0, "persisted": false, "entries": utxo_count },
+        "indexes": {
+            "chain": index_entries,
+            "names": { "entries": name_records, "persisted": false },
+        },
+        "contracts": { "path": contracts_dir.display().to_string(), "bytes": contracts_bytes },
+        "other": { "path": data_dir.display().to_string(), "bytes": other_bytes },
+        "total_bytes": total_bytes,
+        "compaction": compactor.status(),
+    })
+}
@@ -0,0 +1,242 @@
+//! Background Chain Indexing
+//!
+//! Optional indexes (txindex, addressindex) are built by background jobs
+//! instead of during startup, so a node enabling one on an existing chain
+//! serves RPCs right away while the index catches up. Each job indexes a
+//! batch of blocks, then sleeps for the throttle interval to leave CPU and
+//! storage for block validation. Jobs can be paused and resumed, report how
+//! far along they are, and keep following the tip once caught up.
+
+use crate::blockchain::BlockchainBackend;
+use blockchain_core::chain_index::{AddressIndex, ChainIndex, TxIndex};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// How often a caught-up or paused job looks for work
+const IDLE_POLL: Duration = Duration::from_secs(1);
+
+/// Pace of background indexing
+#[derive(Debug, Clone)]
+pub struct IndexConfig {
+    /// Blocks indexed between pauses
+    pub batch_size: u64,
+    /// Sleep between batches
+    pub throttle: Duration,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            throttle: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Catching up on the chain
+    Building,
+    /// Caught up and following new blocks
+    Synced,
+    Paused,
+    /// Stopped on an error; resume to retry
+    Failed,
+}
+
+/// Progress of one index, as reported by `index_getStatus`
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexStatus {
+    pub name: &'static str,
+    pub state: JobState,
+    /// Last block indexed
+    pub indexed_height: Option<u64>,
+    pub chain_height: u64,
+    /// Share of the chain indexed, 0.0 to 1.0
+    pub progress: f64,
+    /// Indexing rate over the last batch
+    pub blocks_per_second: f64,
+    pub error: Option<String>,
+}
+
+struct IndexJob {
+    index: Arc<dyn ChainIndex>,
+    paused: AtomicBool,
+    status: RwLock<IndexStatus>,
+}
+
+impl IndexJob {
+    fn new(index: Arc<dyn ChainIndex>) -> Self {
+        let status = IndexStatus {
+            name: index.name(),
+            state: JobState::Building,
+            indexed_height: None,
+            chain_height: 0,
+            progress: 0.0,
+            blocks_per_second: 0.0,
+            error: None,
+        };
+        Self { index, paused: AtomicBool::new(false), status: RwLock::new(status) }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut IndexStatus)) {
+        f(&mut self.status.write().unwrap());
+    }
+}
+
+/// Runs the node's configured indexes in the background
+pub struct IndexManager {
+    blockchain: Arc<BlockchainBackend>,
+    config: IndexConfig,
+    jobs: Vec<Arc<IndexJob>>,
+    tx_index: Option<Arc<TxIndex>>,
+    address_index: Option<Arc<AddressIndex>>,
+}
+
+impl IndexManager {
+    /// Manager for the indexes named in `names` (txindex, addressindex)
+    pub fn new(blockchain: Arc<BlockchainBackend>, names: &[String], config: IndexConfig) -> anyhow::Result<Self> {
+        let mut manager = Self { blockchain, config, jobs: Vec::new(), tx_index: None, address_index: None };
+        for name in names {
+            match name.as_str() {
+                "txindex" if manager.tx_index.is_none() => {
+                    let index = Arc::new(TxIndex::new());
+                    manager.jobs.push(Arc::new(IndexJob::new(index.clone())));
+                    manager.tx_index = Some(index);
+                }
+                "addressindex" if manager.address_index.is_none() => {
+                    let index = Arc::new(AddressIndex::new());
+                    manager.jobs.push(Arc::new(IndexJob::new(index.clone())));
+                    manager.address_index = Some(index);
+                }
+                "txindex" | "addressindex" => {}
+                other => anyhow::bail!("Unknown index '{}' (known: txindex, addressindex)", other),
+            }
+        }
+        Ok(manager)
+    }
+
+    pub fn tx_index(&self) -> Option<&Arc<TxIndex>> {
+        self.tx_index.as_ref()
+    }
+
+    pub fn address_index(&self) -> Option<&Arc<AddressIndex>> {
+        self.address_index.as_ref()
+    }
+
+    /// Spawn one background task per index
+    pub fn start(self: &Arc<Self>) {
+        for job in &self.jobs {
+            info!("🗂️  Building {} in the background", job.index.name());
+            tokio::spawn(self.clone().run_job(job.clone()));
+        }
+    }
+
+    /// Entries held by each index
+    pub fn entry_counts(&self) -> Vec<(&'static str, usize)> {
+        self.jobs.iter().map(|job| (job.index.name(), job.index.entry_count())).collect()
+    }
+
+    pub fn status(&self) -> Vec<IndexStatus> {
+        self.jobs.iter().map(|job| job.status.read().unwrap().clone()).collect()
+    }
+
+    pub fn pause(&self, name: &str) -> Result<IndexStatus, String> {
+        let job = self.job(name)?;
+        job.paused.store(true, Ordering::SeqCst);
+        job.update(|status| status.state = JobState::Paused);
+        info!("⏸️  Indexing of {} paused", name);
+        Ok(job.status.read().unwrap().clone())
+    }
+
+    pub fn resume(&self, name: &str) -> Result<IndexStatus, String> {
+        let job = self.job(name)?;
+        job.paused.store(false, Ordering::SeqCst);
+        job.update(|status| {
+            status.state = JobState::Building;
+            status.error = None;
+        });
+        info!("▶️  Indexing of {} resumed", name);
+        Ok(job.status.read().unwrap().clone())
+    }
+
+    fn job(&self, name: &str) -> Result<&Arc<IndexJob>, String> {
+        self.jobs.iter()
+            .find(|job| job.index.name() == name)
+            .ok_or_else(|| format!("Index {} is not enabled on this node", name))
+    }
+
+    async fn run_job(self: Arc<Self>, job: Arc<IndexJob>) {
+        loop {
+            if job.paused.load(Ordering::SeqCst) {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            }
+            match self.index_batch(&job).await {
+                Ok(true) => tokio::time::sleep(self.config.throttle).await,
+                Ok(false) => tokio::time::sleep(IDLE_POLL).await,
+                Err(e) => {
+                    error!("❌ Indexing {} failed: {}", job.index.name(), e);
+                    job.paused.store(true, Ordering::SeqCst);
+                    job.update(|status| {
+                        status.state = JobState::Failed;
+                        status.error = Some(e);
+                    });
+                }
+            }
+        }
+    }
+
+    /// Index up to one batch of blocks; true if more blocks remain
+    async fn index_batch(&self, job: &IndexJob) -> Result<bool, String> {
+        let index = &job.index;
+        let chain_height = self.blockchain.get_height().await;
+
+        // Start over if the last indexed block was reorganized away
+        let next = match index.tip() {
+            Some((height, hash)) => match self.blockchain.get_block_by_height(height).await {
+                Some(block) if block.get_hash() == hash => height + 1,
+                _ => {
+                    warn!("🔀 {} tip at height {} left the best chain; rebuilding", index.name(), height);
+                    index.reset();
+                    0
+                }
+            },
+            None => 0,
+        };
+
+        let last = chain_height.min(next.saturating_add(self.config.batch_size).saturating_sub(1));
+        let started = Instant::now();
+        let mut indexed = 0u64;
+        for height in next..=last {
+            let block = self.blockchain.get_block_by_height(height).await
+                .ok_or_else(|| format!("block {} is missing", height))?;
+            index.index_block(&block).map_err(|e| format!("block {}: {}", height, e))?;
+            indexed += 1;
+        }
+
+        let indexed_height = index.tip().map(|(height, _)| height);
+        let caught_up = indexed_height == Some(chain_height);
+        let elapsed = started.elapsed().as_secs_f64();
+        let was_building = job.status.read().unwrap().state == JobState::Building;
+        job.update(|status| {
+            if !job.paused.load(Ordering::SeqCst) {
+                status.state = if caught_up { JobState::Synced } else { JobState::Building };
+            }
+            status.indexed_height = indexed_height;
+            status.chain_height = chain_height;
+            status.progress = indexed_height.map_or(0.0, |height| (height + 1) as f64 / (chain_height + 1) as f64);
+            if indexed > 0 && elapsed > 0.0 {
+                status.blocks_per_second = indexed as f64 / elapsed;
+            }
+        });
+        if was_building && caught_up {
+            info!("✅ {} synced at height {}", index.name(), chain_height);
+        }
+        Ok(indexed > 0 && !caught_up)
+    }
+}
@@ -0,0 +1,35 @@
+//! Blockchain Node as a library
+//!
+//! Everything the `blockchain-node` binary runs, for applications that
+//! want a full node in-process instead of talking to one over RPC:
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! let node = blockchain_node::Node::builder()
+//!     .data_dir("./edunet-data")
+//!     .p2p_port(9001)
+//!     .without_rpc()
+//!     .build()
+//!     .await?;
+//! println!("height {}", node.blockchain.get_height().await);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod admin;
+pub mod alerts;
+pub mod blockchain;
+pub mod disk;
+pub mod indexer;
+pub mod miner;
+pub mod monitor;
+pub mod node;
+pub mod rawtx;
+pub mod rebroadcast;
+pub mod release;
+pub mod rpc;
+pub mod template;
+pub mod treasury;
+pub mod vouchers;
+
+pub use node::{Node, NodeBuilder, NodeConfig};
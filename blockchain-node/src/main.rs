@@ -9,29 +9,40 @@
 //! This is what node operators run to support the network.
 
 use blockchain_rpc::server::{RpcServer, RpcServerConfig};
-use blockchain_network::NetworkConfig;
+use blockchain_rpc::FeatureFlags;
+use blockchain_network::{NetworkConfig, NetworkManager};
+use blockchain_core::consensus::ConsensusParams;
+use blockchain_core::genesis::{GenesisConfig, GenesisCreator};
+use blockchain_core::versionbits::Deployment;
+use blockchain_core::mempool::{EvictionPolicy, MempoolConfig, LEGACY_GAS_PRICE};
+use blockchain_core::contract_calls::{CallConfig, EVM_CALL_DEPTH_LIMIT};
+use blockchain_node::alerts::{AlertConfig, ChainAlerter};
+use blockchain_node::disk::CompactionConfig;
+use blockchain_node::indexer::IndexConfig;
+use blockchain_node::monitor::HeaderMonitor;
+use blockchain_node::node::{self, Node};
+use blockchain_node::rebroadcast::RebroadcastConfig;
+use blockchain_node::release::{self, ChainManifest, CheckStatus};
+use blockchain_node::rpc::add_async_method;
 use anyhow::Result;
-use clap::Parser;
-use tracing::{info, error};
+use clap::{Parser, Subcommand};
+use tracing::{info, warn, error};
 use jsonrpc_core::{IoHandler, Params, Value};
+use serde::Deserialize;
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
-mod blockchain;
-mod miner;
-mod treasury;
-
-use blockchain::BlockchainBackend;
-use miner::MiningDaemon;
-use treasury::TreasuryManager;
-
 /// Blockchain Node CLI
 #[derive(Parser)]
 #[command(name = "blockchain-node")]
 #[command(about = "EduNet Blockchain Node - Full node daemon", long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
     /// RPC server host
     #[arg(long, default_value = "0.0.0.0")]
     rpc_host: String,
@@ -45,7 +56,7 @@ struct Cli {
     p2p_port: u16,
     
     /// Data directory for blockchain storage
-    #[arg(long, default_value = "./blockchain-data")]
+    #[arg(long, global = true, default_value = "./blockchain-data")]
     data_dir: PathBuf,
     
     /// Bootstrap peers (comma-separated host:port)
@@ -56,522 +67,412 @@ struct Cli {
     #[arg(long)]
     mining: bool,
     
+    /// Follow headers, peers and relayed transactions only, without a UTXO set (for monitoring)
+    #[arg(long, conflicts_with = "mining")]
+    headers_only: bool,
+    
     /// Validator address for mining rewards
     #[arg(long)]
     validator_address: Option<String>,
+
+    /// Version bits deployment as name:bit:start_height:timeout_height[:min_activation_height] (repeatable)
+    #[arg(long = "deployment", value_parser = parse_deployment)]
+    deployments: Vec<Deployment>,
+
+    /// Check total UTXO value against expected supply at every block and halt on divergence
+    #[arg(long)]
+    audit_supply: bool,
+
+    /// Operator token for admin RPCs (falls back to EDUNET_ADMIN_TOKEN)
+    #[arg(long)]
+    admin_token: Option<String>,
+
+    /// Node config file (defaults to node.toml in the data directory, if present)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Turn an experimental feature on or off as name=on|off, overriding the config file (repeatable)
+    #[arg(long = "feature", value_parser = parse_feature)]
+    features: Vec<(String, bool)>,
+
+    /// Build an optional index in the background: txindex or addressindex (repeatable)
+    #[arg(long = "index")]
+    indexes: Vec<String>,
+
+    /// Blocks indexed per batch by background index jobs
+    #[arg(long, default_value_t = 500)]
+    index_batch_size: u64,
+
+    /// Pause between index batches in milliseconds, to keep the node responsive
+    #[arg(long, default_value_t = 50)]
+    index_throttle_ms: u64,
+
+    /// Compact block storage every this many hours (off unless set)
+    #[arg(long)]
+    compact_every_hours: Option<u64>,
+
+    /// Cap on compaction copy rate in MB/s; 0 for no cap
+    #[arg(long, default_value_t = 20)]
+    compact_max_mb_per_sec: u64,
+
+    /// Minimum minutes between two compactions, manual or scheduled
+    #[arg(long, default_value_t = 60)]
+    compact_min_interval_mins: u64,
+
+    /// Alert on competing chains within this many blocks of the tip
+    #[arg(long, default_value_t = 6)]
+    alert_fork_window: u64,
+
+    /// Alert when the tip has not moved for this many minutes
+    #[arg(long, default_value_t = 60)]
+    alert_stale_mins: u64,
+
+    /// Alert when this many peers send the same invalid block
+    #[arg(long, default_value_t = 3)]
+    alert_invalid_peers: usize,
+
+    /// POST chain alerts as JSON to this URL
+    #[arg(long)]
+    alert_webhook: Option<String>,
+
+    /// Rebroadcast unconfirmed wallet transactions not relayed for this many minutes
+    #[arg(long, default_value_t = 10)]
+    rebroadcast_after_mins: u64,
+
+    /// Mempool eviction order when full: lowest_fee_rate, oldest_first, largest_first or descendant_aware
+    #[arg(long, default_value = "lowest_fee_rate", value_parser = parse_eviction_policy)]
+    mempool_eviction: EvictionPolicy,
+
+    /// Lowest max fee per gas contract transactions and calls may bid
+    #[arg(long, default_value_t = LEGACY_GAS_PRICE)]
+    contract_base_fee: u64,
+
+    /// Deepest contract-to-contract call allowed (at most 1024)
+    #[arg(long, default_value_t = EVM_CALL_DEPTH_LIMIT)]
+    contract_max_call_depth: usize,
+
+    /// Revert calls back into a contract that is still executing
+    #[arg(long)]
+    contract_reentrancy_guard: bool,
 }
 
-/// Create RPC server wired to blockchain backend and treasury
-fn create_blockchain_rpc_server(
-    config: RpcServerConfig, 
-    blockchain: Arc<BlockchainBackend>,
-    treasury: Arc<TreasuryManager>
-) -> RpcServer {
-    let mut handler = IoHandler::new();
-    
-    // Get block height
-    {
-        let bc = blockchain.clone();
-        handler.add_sync_method("blockchain_getBlockHeight", move |_params: Params| {
-            let bc = bc.clone();
-            let height = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_height().await
-                })
-            });
-            Ok(Value::Number(height.into()))
-        });
+/// Settings read from the node config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    /// Experimental features by name (contracts, pos, channels)
+    #[serde(default)]
+    features: HashMap<String, bool>,
+}
+
+/// Parse a `--feature` argument
+fn parse_feature(arg: &str) -> std::result::Result<(String, bool), String> {
+    let (name, state) = arg.split_once('=').ok_or("expected name=on|off")?;
+    let enabled = match state {
+        "on" | "true" => true,
+        "off" | "false" => false,
+        _ => return Err(format!("invalid state '{}', expected on or off", state)),
+    };
+    Ok((name.to_string(), enabled))
+}
+
+/// Feature flags from the config file with `--feature` overrides applied
+fn feature_flags(cli: &Cli) -> Result<FeatureFlags> {
+    let path = cli.config.clone().unwrap_or_else(|| cli.data_dir.join("node.toml"));
+    let config: ConfigFile = if path.exists() {
+        let contents = std::fs::read_to_string(&path)?;
+        toml::from_str(&contents).map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))?
+    } else if cli.config.is_some() {
+        anyhow::bail!("Config file {} not found", path.display());
+    } else {
+        ConfigFile::default()
+    };
+    let overrides = config.features.iter()
+        .map(|(name, enabled)| (name.as_str(), *enabled))
+        .chain(cli.features.iter().map(|(name, enabled)| (name.as_str(), *enabled)));
+    FeatureFlags::with_overrides(overrides).map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Commands other than running the node
+#[derive(Subcommand)]
+enum Command {
+    /// Pin the data directory to a genesis block and the current consensus parameters
+    Init {
+        /// Genesis timestamp of the network to join (defaults to now, starting a new chain)
+        #[arg(long)]
+        genesis_timestamp: Option<i64>,
+        
+        /// Development/treasury fund address paid a share of every block reward
+        #[arg(long, requires = "dev_fund_percent")]
+        dev_fund_address: Option<String>,
+        
+        /// Percentage of the block reward owed to the dev fund (0-100)
+        #[arg(long, requires = "dev_fund_address")]
+        dev_fund_percent: Option<u8>,
+        
+        /// Overwrite an existing chain manifest
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Print version information
+    Version {
+        /// Include commit, chain parameters hash and build features
+        #[arg(long)]
+        verbose: bool,
+    },
+    
+    /// Check the genesis block, consensus parameters and native libraries without starting
+    SelfCheck,
+}
+
+/// Consensus parameters of the chain in the data directory, with the
+/// deployments from the command line
+fn consensus_params(cli: &Cli) -> Result<ConsensusParams> {
+    let consensus_params = ConsensusParams {
+        deployments: cli.deployments.clone(),
+        ..ConsensusParams::default()
+    };
+    Ok(match ChainManifest::load(&cli.data_dir)? {
+        Some(manifest) => manifest.consensus_params(consensus_params),
+        None => consensus_params,
+    })
+}
+
+/// Write the chain manifest for `blockchain-node init`
+fn init_data_dir(cli: &Cli, genesis_timestamp: Option<i64>, dev_fund: Option<(String, u8)>, force: bool) -> Result<()> {
+    if !force && ChainManifest::load(&cli.data_dir)?.is_some() {
+        anyhow::bail!(
+            "{} is already initialized; pass --force to overwrite its chain manifest",
+            cli.data_dir.display()
+        );
     }
-    
-    // Get block by height
-    {
-        let bc = blockchain.clone();
-        handler.add_sync_method("blockchain_getBlock", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: Vec<u64> = params.parse()?;
-            if parsed.is_empty() {
-                return Err(jsonrpc_core::Error::invalid_params("Missing block height"));
-            }
-            
-            let block = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_block_by_height(parsed[0]).await
-                })
-            });
-            
-            match block {
-                Some(b) => Ok(json!({
-                    "height": b.header.height,
-                    "hash": hex::encode(b.header.calculate_hash()),
-                    "prev_hash": hex::encode(b.header.prev_block_hash),
-                    "timestamp": b.header.timestamp,
-                    "transactions_count": b.transactions.len()
-                })),
-                None => Ok(json!({"error": "Block not found"}))
-            }
-        });
+    let timestamp = genesis_timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let mut consensus_params = ConsensusParams {
+        deployments: cli.deployments.clone(),
+        ..ConsensusParams::default()
+    };
+    if let Some((address, percent)) = dev_fund {
+        consensus_params = consensus_params.with_dev_fund(address, percent)?;
     }
-    
-    // Get balance
-    {
-        let bc = blockchain.clone();
-        handler.add_sync_method("wallet_getBalance", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: Vec<String> = params.parse()?;
-            if parsed.is_empty() {
-                return Err(jsonrpc_core::Error::invalid_params("Missing address"));
-            }
-            
-            let result = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_balance(&parsed[0]).await
-                })
-            });
-            
-            match result {
-                Ok(balance) => Ok(Value::Number(balance.into())),
-                Err(e) => Ok(json!({"error": format!("Failed to get balance: {}", e)}))
+    let manifest = ChainManifest::new(&consensus_params, timestamp)?;
+    manifest.save(&cli.data_dir)?;
+    
+    println!("Initialized {}", cli.data_dir.display());
+    println!("genesis timestamp: {}", manifest.genesis_timestamp);
+    println!("genesis hash: {}", manifest.genesis_hash);
+    if let Some(fund) = &manifest.dev_fund {
+        println!("dev fund: {}% to {}", fund.percent, fund.address);
+    }
+    println!("chain params: {}", manifest.params_hash);
+    Ok(())
+}
+
+/// Run the self-checks, printing each result; errors if any failed
+fn run_self_check(cli: &Cli, quiet: bool) -> Result<()> {
+    let checks = release::self_check(&cli.data_dir, &consensus_params(cli)?);
+    let mut failed = Vec::new();
+    for check in &checks {
+        match check.status {
+            CheckStatus::Pass if !quiet => println!("✅ {}: {}", check.name, check.detail),
+            CheckStatus::Pass => info!("✅ Self-check {}: {}", check.name, check.detail),
+            CheckStatus::Warn if !quiet => println!("⚠️  {}: {}", check.name, check.detail),
+            CheckStatus::Warn => warn!("⚠️  Self-check {}: {}", check.name, check.detail),
+            CheckStatus::Fail => {
+                if quiet {
+                    error!("❌ Self-check {}: {}", check.name, check.detail);
+                } else {
+                    println!("❌ {}: {}", check.name, check.detail);
+                }
+                failed.push(check.name);
             }
-        });
+        }
     }
-    
-    // List wallets
-    {
-        let bc = blockchain.clone();
-        handler.add_sync_method("wallet_list", move |_params: Params| {
-            let bc = bc.clone();
-            let wallets = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.list_wallets().await
-                })
-            });
-            
-            let wallet_list: Vec<_> = wallets.into_iter().map(|(name, address, balance)| {
-                json!({
-                    "name": name,
-                    "address": address,
-                    "balance": balance
-                })
-            }).collect();
-            Ok(Value::Array(wallet_list))
-        });
+    if !failed.is_empty() {
+        anyhow::bail!("Self-check failed: {}", failed.join(", "));
     }
-    
-    // Debug: List all addresses with UTXOs
-    {
-        let bc = blockchain.clone();
-        handler.add_sync_method("debug_listAddresses", move |_params: Params| {
-            let bc = bc.clone();
-            let addresses = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    let utxo_set = bc.utxo_set.read().await;
-                    let all_addresses = utxo_set.get_all_addresses();
-                    all_addresses.into_iter().map(|addr| {
-                        let balance = utxo_set.get_balance(&addr);
-                        json!({
-                            "address": addr,
-                            "balance": balance
-                        })
-                    }).collect::<Vec<_>>()
-                })
-            });
-            Ok(Value::Array(addresses))
-        });
+    Ok(())
+}
+
+/// Parse a `--deployment` argument
+fn parse_deployment(arg: &str) -> std::result::Result<Deployment, String> {
+    let parts: Vec<&str> = arg.split(':').collect();
+    if !(4..=5).contains(&parts.len()) {
+        return Err("expected name:bit:start_height:timeout_height[:min_activation_height]".to_string());
     }
-    
-    // Get status
-    {
-        let bc = blockchain.clone();
-        handler.add_sync_method("blockchain_getStatus", move |_params: Params| {
-            let bc = bc.clone();
-            let status = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_status().await
-                })
-            });
-            Ok(status)
-        });
+    let number = |s: &str| s.parse::<u64>().map_err(|_| format!("invalid number: {}", s));
+    let bit = parts[1].parse::<u8>().map_err(|_| format!("invalid bit: {}", parts[1]))?;
+    let deployment = Deployment::new(parts[0], bit, number(parts[2])?, number(parts[3])?)
+        .map_err(|e| e.to_string())?;
+    match parts.get(4) {
+        Some(min_height) => Ok(deployment.with_min_activation_height(number(min_height)?)),
+        None => Ok(deployment),
     }
-    
-    // Treasury: Get current price
-    {
-        let tr = treasury.clone();
-        handler.add_sync_method("treasury_getPrice", move |_params: Params| {
-            let tr = tr.clone();
-            let price = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    tr.get_price().await
+}
+
+fn parse_eviction_policy(arg: &str) -> std::result::Result<EvictionPolicy, String> {
+    arg.parse::<EvictionPolicy>().map_err(|e| e.to_string())
+}
+
+/// P2P settings from the command line
+fn network_config(cli: &Cli) -> Result<NetworkConfig> {
+    // Parse bootstrap peers
+    let seed_peers: Vec<SocketAddr> = if let Some(peers) = cli.bootstrap_peers.as_deref() {
+        peers.split(',')
+            .filter_map(|p| {
+                p.trim().parse().ok().or_else(|| {
+                    error!("Invalid peer address: {}", p);
+                    None
                 })
-            });
-            Ok(json!({
-                "price_cents": price,
-                "price_usd": format!("${:.2}", price as f64 / 100.0)
-            }))
-        });
-    }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
     
-    // Treasury: Set price (admin only in production)
-    {
-        let tr = treasury.clone();
-        handler.add_sync_method("treasury_setPrice", move |params: Params| {
-            let tr = tr.clone();
-            let parsed: Vec<u64> = params.parse()?;
-            if parsed.is_empty() {
-                return Err(jsonrpc_core::Error::invalid_params("Missing price_cents"));
-            }
-            
-            tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    tr.set_price(parsed[0]).await;
-                })
-            });
-            Ok(json!({"success": true}))
-        });
+    // Configure P2P network
+    info!("🌐 Configuring P2P network on port {}...", cli.p2p_port);
+    Ok(node::network_config(cli.p2p_port, seed_peers))
+}
+
+/// Chain alerting settings from the command line
+fn alert_config(cli: &Cli) -> AlertConfig {
+    AlertConfig {
+        fork_window: cli.alert_fork_window,
+        stale_after: std::time::Duration::from_secs(cli.alert_stale_mins.max(1) * 60),
+        invalid_peer_threshold: cli.alert_invalid_peers.max(1),
+        webhook: cli.alert_webhook.clone(),
     }
+}
+
+/// Run as a header-only monitoring node until stopped
+async fn run_headers_only(cli: &Cli, network_config: NetworkConfig, genesis_config: GenesisConfig) -> Result<()> {
+    info!("🛰️  Header-only mode: no UTXO set, mempool or block storage");
+    let genesis = GenesisCreator::new(Some(genesis_config)).create_genesis_block()
+        .map_err(|e| anyhow::anyhow!("Failed to create genesis block: {}", e))?;
+    let block_reward = consensus_params(cli)?.block_reward;
+    
+    let mut network = NetworkManager::new_async(network_config, None).await?;
+    network.start().await?;
+    let events = network.take_event_receiver()
+        .ok_or_else(|| anyhow::anyhow!("Network events already taken"))?;
+    let alerter = Arc::new(ChainAlerter::new(alert_config(cli)));
+    alerter.start();
+    let monitor = Arc::new(HeaderMonitor::new(Arc::new(network), genesis.header, block_reward, alerter));
+    tokio::spawn(monitor.clone().run(events));
     
-    // Treasury: Sell coins (after receiving cash payment)
+    let rpc_config = RpcServerConfig {
+        host: cli.rpc_host.clone(),
+        port: cli.rpc_port,
+    };
+    let server = create_monitor_rpc_server(rpc_config, monitor).start()
+        .map_err(|e| anyhow::anyhow!(e))?;
+    info!("✅ Header-only node is running");
+    info!("📡 RPC endpoint: http://{}:{}", cli.rpc_host, cli.rpc_port);
+    info!("🌐 P2P listening on port: {}", cli.p2p_port);
+    tokio::task::spawn_blocking(move || server.wait()).await?;
+    Ok(())
+}
+
+/// RPC server for a header-only node
+fn create_monitor_rpc_server(config: RpcServerConfig, monitor: Arc<HeaderMonitor>) -> RpcServer {
+    let mut handler = IoHandler::new();
+    
+    // Header chain height, so existing height checks keep working
     {
-        let tr = treasury.clone();
-        handler.add_sync_method("treasury_sellCoins", move |params: Params| {
-            let tr = tr.clone();
-            let parsed: serde_json::Map<String, Value> = params.parse()?;
-            
-            let buyer_address = parsed.get("buyer_address")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing buyer_address"))?;
-            let amount = parsed.get("amount")
-                .and_then(|v| v.as_u64())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing amount"))?;
-            let payment_method = parsed.get("payment_method")
-                .and_then(|v| v.as_str())
-                .unwrap_or("cash");
-            let payment_proof = parsed.get("payment_proof")
-                .and_then(|v| v.as_str())
-                .unwrap_or("no receipt");
-            
-            let sale = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    tr.sell_coins(
-                        buyer_address.to_string(),
-                        amount,
-                        payment_method.to_string(),
-                        payment_proof.to_string(),
-                    ).await
-                })
-            });
-            
-            match sale {
-                Ok(s) => Ok(serde_json::to_value(s).unwrap()),
-                Err(e) => {
-                    error!("Treasury sale failed: {}", e);
-                    Err(jsonrpc_core::Error::internal_error())
-                }
-            }
+        let monitor = monitor.clone();
+        handler.add_sync_method("blockchain_getBlockHeight", move |_params: Params| {
+            Ok(Value::Number(monitor.tip_height().into()))
         });
     }
     
-    // Treasury: Get statistics
     {
-        let tr = treasury.clone();
-        handler.add_sync_method("treasury_getStats", move |_params: Params| {
-            let tr = tr.clone();
-            let stats = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    tr.get_stats().await
-                })
-            });
-            Ok(serde_json::to_value(stats).unwrap())
+        let monitor = monitor.clone();
+        handler.add_sync_method("monitor_getStatus", move |_params: Params| {
+            Ok(monitor.status())
         });
     }
     
-    // Treasury: List all sales
+    // Forks and reorgs seen, newest first
     {
-        let tr = treasury.clone();
-        handler.add_sync_method("treasury_getSales", move |_params: Params| {
-            let tr = tr.clone();
-            let sales = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    tr.get_sales().await
-                })
-            });
-            Ok(serde_json::to_value(sales).unwrap())
+        let monitor = monitor.clone();
+        handler.add_sync_method("monitor_getForkAlerts", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse().unwrap_or_default();
+            let limit = parsed.get("limit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(20) as usize;
+            Ok(json!({ "alerts": monitor.fork_alerts(limit) }))
         });
     }
     
-    // Debug: Dump UTXO set
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("debug_dumpUtxos", move |_params: Params| {
-            let bc = bc.clone();
-            let result = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    let utxo_set = bc.utxo_set.read().await;
-                    let count = utxo_set.get_utxo_count();
-                    let addresses = utxo_set.get_all_addresses();
-                    
-                    json!({
-                        "total_utxos": count,
-                        "addresses": addresses
-                    })
-                })
-            });
-            Ok(result)
+        let monitor = monitor.clone();
+        handler.add_sync_method("monitor_getFeeEstimates", move |_params: Params| {
+            Ok(monitor.fee_estimates())
         });
     }
     
-    // Contract: Deploy
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("contract_deploy", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: serde_json::Map<String, Value> = params.parse()?;
-            
-            let deployer = parsed.get("deployer")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing deployer"))?;
-            let bytecode_hex = parsed.get("bytecode")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing bytecode"))?;
-            let value = parsed.get("value")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0);
-            let gas_limit = parsed.get("gas_limit")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(1000000);
-                
-            let bytecode = hex::decode(bytecode_hex)
-                .map_err(|e| jsonrpc_core::Error::invalid_params(format!("Invalid bytecode hex: {}", e)))?;
-            
-            let result = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.deploy_contract(deployer, bytecode, value, gas_limit).await
-                })
-            }).map_err(|e| {
-                error!("❌ Contract deployment error: {}", e);
-                jsonrpc_core::Error {
-                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
-                    message: format!("Contract deployment failed: {}", e),
-                    data: None,
-                }
-            })?;
-            
-            Ok(serde_json::to_value(result).unwrap())
+        let monitor = monitor.clone();
+        handler.add_sync_method("monitor_getRelayStats", move |_params: Params| {
+            Ok(monitor.relay_stats())
         });
     }
     
-    // Contract: Call
+    // Known branch tips, optionally only those within `window` blocks of the tip
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("contract_call", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: serde_json::Map<String, Value> = params.parse()?;
-            
-            let caller = parsed.get("caller")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing caller"))?;
-            let contract_hex = parsed.get("contract")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
-            let calldata_hex = parsed.get("data")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing calldata"))?;
-            let value = parsed.get("value")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0);
-            let gas_limit = parsed.get("gas_limit")
+        let monitor = monitor.clone();
+        handler.add_sync_method("blockchain_getChainTips", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse().unwrap_or_default();
+            let window = parsed.get("window")
                 .and_then(|v| v.as_u64())
-                .unwrap_or(100000);
-                
-            let contract_bytes = hex::decode(contract_hex)
-                .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
-            if contract_bytes.len() != 20 {
-                return Err(jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"));
-            }
-            let mut contract_addr = [0u8; 20];
-            contract_addr.copy_from_slice(&contract_bytes);
-            let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
-            
-            let calldata = hex::decode(calldata_hex)
-                .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid calldata hex"))?;
-            
-            let result = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.call_contract(caller, contract_address, calldata, value, gas_limit).await
-                })
-            }).map_err(|e| jsonrpc_core::Error::internal_error())?;
-            
-            Ok(serde_json::to_value(result).unwrap())
+                .unwrap_or(u64::MAX);
+            Ok(json!({ "tips": monitor.chain_tips(window) }))
         });
     }
     
-    // Contract: Get code
+    // Competing chains, reorgs, stale tips and invalid chains, newest first
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("contract_getCode", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: serde_json::Map<String, Value> = params.parse()?;
-            
-            let contract_hex = parsed.get("contract")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
-                
-            let contract_bytes = hex::decode(contract_hex)
-                .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
-            if contract_bytes.len() != 20 {
-                return Err(jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"));
-            }
-            let mut contract_addr = [0u8; 20];
-            contract_addr.copy_from_slice(&contract_bytes);
-            let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
-            
-            let code = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_contract_code(contract_address).await
-                })
-            });
-            
-            Ok(json!({
-                "code": code.map(|c| hex::encode(c))
-            }))
+        let monitor = monitor.clone();
+        handler.add_sync_method("blockchain_getChainAlerts", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse().unwrap_or_default();
+            let limit = parsed.get("limit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(20) as usize;
+            Ok(json!({ "alerts": monitor.alerter().recent(limit) }))
         });
     }
     
-    // Get logs (events) with filter
+    // Clock offset from peers and the adjusted time headers are checked against
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("contract_getLogs", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: serde_json::Map<String, Value> = params.parse()?;
-            
-            // Parse filter parameters
-            let address = parsed.get("address")
-                .and_then(|v| v.as_str())
-                .and_then(|s| hex::decode(s).ok())
-                .and_then(|bytes| {
-                    if bytes.len() == 20 {
-                        let mut addr = [0u8; 20];
-                        addr.copy_from_slice(&bytes);
-                        Some(blockchain_core::contracts::EthAddress::new(addr))
-                    } else {
-                        None
-                    }
-                });
-            
-            let from_block = parsed.get("fromBlock").and_then(|v| v.as_u64());
-            let to_block = parsed.get("toBlock").and_then(|v| v.as_u64());
-            
-            let topics = parsed.get("topics")
-                .and_then(|v| v.as_array())
-                .map(|arr| {
-                    arr.iter().map(|t| {
-                        if t.is_null() {
-                            None
-                        } else {
-                            t.as_array().map(|inner| {
-                                inner.iter()
-                                    .filter_map(|s| s.as_str().map(String::from))
-                                    .collect::<Vec<_>>()
-                            })
-                        }
-                    }).collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
-            
-            let filter = blockchain_core::event_indexer::EventFilter {
-                address,
-                topics,
-                from_block,
-                to_block,
-            };
-            
-            let events = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.query_events(filter).await
-                })
-            });
-            
-            Ok(json!({
-                "logs": events.iter().map(|e| json!({
-                    "address": hex::encode(e.log.address.as_bytes()),
-                    "topics": e.log.topics,
-                    "data": hex::encode(&e.log.data),
-                    "blockHeight": e.block_height,
-                    "transactionHash": e.tx_hash,
-                    "logIndex": e.log_index,
-                })).collect::<Vec<_>>()
-            }))
+        let monitor = monitor.clone();
+        handler.add_sync_method("node_getNetworkTime", move |_params: Params| {
+            Ok(json!(monitor.network_time()))
         });
     }
     
-    // Get events by block height
+    // Outbound queue depth, throughput and drops per priority class
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("contract_getEventsByBlock", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: Vec<u64> = params.parse()?;
-            if parsed.is_empty() {
-                return Err(jsonrpc_core::Error::invalid_params("Missing block height"));
+        let monitor = monitor.clone();
+        add_async_method(&mut handler, "network_getQueueMetrics", move |_params: Params| {
+            let monitor = monitor.clone();
+            async move {
+                let queues = monitor.queue_metrics().await;
+                Ok(json!({ "queues": queues }))
             }
-            
-            let events = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_events_by_block(parsed[0]).await
-                })
-            });
-            
-            Ok(json!({
-                "events": events.iter().map(|e| json!({
-                    "address": hex::encode(e.log.address.as_bytes()),
-                    "topics": e.log.topics,
-                    "data": hex::encode(&e.log.data),
-                    "blockHeight": e.block_height,
-                    "transactionHash": e.tx_hash,
-                    "logIndex": e.log_index,
-                })).collect::<Vec<_>>()
-            }))
         });
     }
     
-    // Get events by contract address
+    // Relay cache sizes and hit rates
     {
-        let bc = blockchain.clone();
-        handler.add_sync_method("contract_getEventsByAddress", move |params: Params| {
-            let bc = bc.clone();
-            let parsed: serde_json::Map<String, Value> = params.parse()?;
-            
-            let contract_hex = parsed.get("address")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
-                
-            let contract_bytes = hex::decode(contract_hex)
-                .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
-            if contract_bytes.len() != 20 {
-                return Err(jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"));
-            }
-            let mut contract_addr = [0u8; 20];
-            contract_addr.copy_from_slice(&contract_bytes);
-            let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
-            
-            let events = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    bc.get_events_by_address(contract_address).await
-                })
-            });
-            
-            Ok(json!({
-                "events": events.iter().map(|e| json!({
-                    "address": hex::encode(e.log.address.as_bytes()),
-                    "topics": e.log.topics,
-                    "data": hex::encode(&e.log.data),
-                    "blockHeight": e.block_height,
-                    "transactionHash": e.tx_hash,
-                    "logIndex": e.log_index,
-                })).collect::<Vec<_>>()
-            }))
+        let monitor = monitor.clone();
+        handler.add_sync_method("network_getRelayCacheStats", move |_params: Params| {
+            Ok(json!(monitor.relay_cache_stats()))
         });
     }
     
-    info!("📋 Registered RPC methods: blockchain_getBlockHeight, blockchain_getBlock, wallet_getBalance, wallet_list, blockchain_getStatus, treasury_getPrice, treasury_setPrice, treasury_sellCoins, treasury_getStats, treasury_getSales, contract_deploy, contract_call, contract_getCode, contract_getLogs, contract_getEventsByBlock, contract_getEventsByAddress");
+    info!("📋 Registered RPC methods: blockchain_getBlockHeight, monitor_getStatus, monitor_getForkAlerts, monitor_getFeeEstimates, monitor_getRelayStats, blockchain_getChainTips, blockchain_getChainAlerts, node_getNetworkTime, network_getQueueMetrics, network_getRelayCacheStats");
+    
     RpcServer::with_custom_handler(config, handler)
 }
 
@@ -584,97 +485,109 @@ async fn main() -> Result<()> {
     
     let cli = Cli::parse();
     
+    match &cli.command {
+        Some(Command::Init { genesis_timestamp, dev_fund_address, dev_fund_percent, force }) => {
+            let dev_fund = dev_fund_address.clone().zip(*dev_fund_percent);
+            return init_data_dir(&cli, *genesis_timestamp, dev_fund, *force);
+        }
+        Some(Command::Version { verbose: true }) => {
+            println!("{}", release::version_report(&consensus_params(&cli)?));
+            return Ok(());
+        }
+        Some(Command::Version { verbose: false }) => {
+            println!("blockchain-node {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Some(Command::SelfCheck) => return run_self_check(&cli, false),
+        None => {}
+    }
+    
     info!("🚀 Starting EduNet Blockchain Full Node");
+    info!("🏷️  Version {} ({})", env!("CARGO_PKG_VERSION"), release::GIT_COMMIT);
     info!("📁 Data directory: {}", cli.data_dir.display());
+    info!("🔐 SHA-256 backend: {}", blockchain_core::hashing::backend().name());
     
     // Create data directory
     std::fs::create_dir_all(&cli.data_dir)?;
     
-    // Parse bootstrap peers
-    let seed_peers: Vec<SocketAddr> = if let Some(peers) = cli.bootstrap_peers {
-        peers.split(',')
-            .filter_map(|p| {
-                p.trim().parse().ok().or_else(|| {
-                    error!("Invalid peer address: {}", p);
-                    None
-                })
-            })
-            .collect()
-    } else {
-        Vec::new()
-    };
-    
-    // Configure P2P network
-    info!("🌐 Configuring P2P network on port {}...", cli.p2p_port);
-    let network_config = NetworkConfig {
-        listen_addr: format!("0.0.0.0:{}", cli.p2p_port).parse()?,
-        listening_port: cli.p2p_port,
-        seed_peers,
-        dns_seeds: vec![],
-        our_services: 1,
-        max_peers: 58,
-        connection_timeout: std::time::Duration::from_secs(30),
-        heartbeat_interval: std::time::Duration::from_secs(30),
-        max_message_size: 32 * 1024 * 1024,
-        network_magic: 0xED000001,
-    };
-    
-    // Initialize blockchain backend
-    info!("💾 Initializing blockchain backend...");
-    let blockchain = Arc::new(BlockchainBackend::new(network_config).await?);
+    // Refuse to start against a data directory from another deployment
+    run_self_check(&cli, true)?;
     
-    info!("✅ Blockchain initialized at height {}", blockchain.get_height().await);
+    let features = feature_flags(&cli)?;
+    for state in features.list() {
+        info!("🚩 Feature {}: {}", state.feature.name(), if state.enabled { "on" } else { "off" });
+    }
+    let genesis_config = ChainManifest::load(&cli.data_dir)?
+        .map(|manifest| manifest.genesis_config())
+        .unwrap_or_default();
     
-    // Initialize treasury manager
-    info!("💰 Initializing treasury manager...");
-    let treasury = Arc::new(TreasuryManager::new(blockchain.clone())?);
-    info!("✅ Treasury ready - Address: {}", treasury::TREASURY_ADDRESS);
+    let network_config = network_config(&cli)?;
+    if cli.headers_only {
+        return run_headers_only(&cli, network_config, genesis_config).await;
+    }
     
-    // Start RPC server
-    info!("🔌 Starting RPC server on {}:{}...", cli.rpc_host, cli.rpc_port);
-    let rpc_config = RpcServerConfig {
-        host: cli.rpc_host.clone(),
-        port: cli.rpc_port,
+    let mempool_config = MempoolConfig {
+        eviction_policy: cli.mempool_eviction,
+        base_fee_per_gas: cli.contract_base_fee,
+        ..MempoolConfig::default()
+    };
+    let call_config = CallConfig {
+        max_call_depth: cli.contract_max_call_depth,
+        reentrancy_guard: cli.contract_reentrancy_guard,
+    };
+    let index_config = IndexConfig {
+        batch_size: cli.index_batch_size.max(1),
+        throttle: std::time::Duration::from_millis(cli.index_throttle_ms),
+    };
+    let compaction_config = CompactionConfig {
+        max_bytes_per_sec: (cli.compact_max_mb_per_sec > 0).then(|| cli.compact_max_mb_per_sec * 1_000_000),
+        min_interval: std::time::Duration::from_secs(cli.compact_min_interval_mins * 60),
+        schedule: cli.compact_every_hours.filter(|hours| *hours > 0)
+            .map(|hours| std::time::Duration::from_secs(hours * 3600)),
     };
+    let admin_token = cli.admin_token.clone().or_else(|| std::env::var("EDUNET_ADMIN_TOKEN").ok());
+    
+    let mut builder = Node::builder()
+        .data_dir(cli.data_dir.clone())
+        .network(network_config)
+        .consensus(consensus_params(&cli)?)
+        .genesis(genesis_config)
+        .audit_supply(cli.audit_supply)
+        .mempool(mempool_config)
+        .calls(call_config)
+        .rpc(RpcServerConfig {
+            host: cli.rpc_host.clone(),
+            port: cli.rpc_port,
+        })
+        .features(features)
+        .indexes(cli.indexes.clone())
+        .index_config(index_config)
+        .compaction(compaction_config)
+        .alerts(alert_config(&cli))
+        .rebroadcast(RebroadcastConfig {
+            min_age: std::time::Duration::from_secs(cli.rebroadcast_after_mins * 60),
+            ..RebroadcastConfig::default()
+        })
+        .admin_token(admin_token);
+    if cli.mining {
+        builder = builder.mining(cli.validator_address.clone().unwrap_or_else(|| "default_validator".to_string()));
+    }
     
-    // Create RPC handler with blockchain and treasury access
-    let rpc_server = create_blockchain_rpc_server(rpc_config, blockchain.clone(), treasury.clone());
-    
-    match rpc_server.start() {
-        Ok(server) => {
-            info!("✅ Blockchain full node is running!");
-            info!("📡 RPC endpoint: http://{}:{}", cli.rpc_host, cli.rpc_port);
-            info!("🌐 P2P listening on port: {}", cli.p2p_port);
-            info!("⛓️  Block height: {}", blockchain.get_height().await);
-            
-            // Start mining daemon if requested
-            let mining_handle = if cli.mining {
-                let validator_addr = cli.validator_address
-                    .unwrap_or_else(|| "default_validator".to_string());
-                info!("⛏️  Mining enabled - Rewards to: {}", validator_addr);
-                
-                let mining_daemon = MiningDaemon::new(blockchain.clone(), validator_addr);
-                Some(mining_daemon.start())
-            } else {
-                info!("💤 Mining disabled (use --mining to enable)");
-                None
-            };
-            
-            info!("🚀 Node is ready! Press Ctrl+C to stop");
-            
-            // Keep server running
-            server.wait();
-            
-            // Clean up mining if it was started
-            if let Some(handle) = mining_handle {
-                handle.abort();
-            }
-            
-            Ok(())
-        }
-        Err(e) => {
-            error!("❌ Failed to start RPC server: {}", e);
-            Err(anyhow::anyhow!(e))
-        }
+    let node = builder.build().await.map_err(|e| {
+        error!("❌ Failed to start node: {}", e);
+        e
+    })?;
+    
+    info!("✅ Blockchain full node is running!");
+    info!("📡 RPC endpoint: http://{}:{}", cli.rpc_host, cli.rpc_port);
+    info!("🌐 P2P listening on port: {}", cli.p2p_port);
+    info!("⛓️  Block height: {}", node.blockchain.get_height().await);
+    if !node.is_mining() {
+        info!("💤 Mining disabled (use --mining to enable)");
     }
+    info!("🚀 Node is ready! Press Ctrl+C to stop");
+    
+    // Keep server running, then stop mining
+    node.wait().await;
+    Ok(())
 }
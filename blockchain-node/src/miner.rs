@@ -1,26 +1,26 @@
 //! Mining Daemon - Proof of Work block production
 //!
 //! This module implements the mining loop that:
-//! - Selects pending transactions from mempool
-//! - Creates block templates
-//! - Performs PoW mining (nonce search)
+//! - Takes block templates from the template manager
+//! - Performs PoW mining (nonce search), switching to fresh templates as they arrive
 //! - Submits mined blocks to consensus
 //! - Awards mining rewards
 
 use std::sync::Arc;
+use tokio::sync::watch;
 use tokio::time::{Duration, sleep};
 use tracing::{info, warn, error, debug};
-use blockchain_core::{
-    block::{Block, BlockHeader},
-    Hash256,
-};
+use blockchain_core::{block::Block, Hash256};
 use crate::blockchain::BlockchainBackend;
+use crate::template::{BlockTemplate, BlockTemplateManager, TemplateConfig};
 
 /// Mining statistics
 #[derive(Debug, Clone)]
 pub struct MiningStats {
     pub blocks_mined: u64,
     pub hashes_computed: u64,
+    /// Templates dropped for newer work before a block was found
+    pub templates_abandoned: u64,
     pub mining_since: std::time::Instant,
     pub last_block_time: Option<std::time::Instant>,
 }
@@ -30,6 +30,7 @@ impl Default for MiningStats {
         Self {
             blocks_mined: 0,
             hashes_computed: 0,
+            templates_abandoned: 0,
             mining_since: std::time::Instant::now(),
             last_block_time: None,
         }
@@ -40,6 +41,7 @@ impl Default for MiningStats {
 pub struct MiningDaemon {
     blockchain: Arc<BlockchainBackend>,
     validator_address: String,
+    templates: Arc<BlockTemplateManager>,
     stats: Arc<tokio::sync::RwLock<MiningStats>>,
     should_stop: Arc<tokio::sync::RwLock<bool>>,
 }
@@ -48,6 +50,7 @@ impl MiningDaemon {
     /// Create new mining daemon
     pub fn new(blockchain: Arc<BlockchainBackend>, validator_address: String) -> Self {
         Self {
+            templates: Arc::new(BlockTemplateManager::new(blockchain.clone(), TemplateConfig::default())),
             blockchain,
             validator_address,
             stats: Arc::new(tokio::sync::RwLock::new(MiningStats::default())),
@@ -60,7 +63,9 @@ impl MiningDaemon {
         info!("⛏️  Starting mining daemon for validator: {}", self.validator_address);
         
         tokio::spawn(async move {
+            let template_task = tokio::spawn(self.templates.clone().run());
             self.mining_loop().await;
+            template_task.abort();
         })
     }
     
@@ -112,9 +117,8 @@ impl MiningDaemon {
                     sleep(Duration::from_millis(100)).await;
                 }
                 Ok(false) => {
-                    // No transactions to mine, wait a bit
-                    debug!("No transactions in mempool, waiting...");
-                    sleep(Duration::from_secs(5)).await;
+                    // Template superseded, start straight away on the new one
+                    debug!("Switching to newer block template");
                 }
                 Err(e) => {
                     error!("Mining error: {}", e);
@@ -124,35 +128,25 @@ impl MiningDaemon {
         }
     }
     
-    /// Mine a single block
-    /// Returns Ok(true) if block was mined, Ok(false) if no work to do
+    /// Mine a single block on the latest template
+    /// Returns Ok(true) if block was mined, Ok(false) if the template was superseded first
     async fn mine_one_block(&self) -> Result<bool, anyhow::Error> {
-        // Get current blockchain state
-        let height = self.blockchain.get_height().await;
-        let next_height = height + 1;
-        
-        info!("Attempting to mine block at height {}", next_height);
-        
-        // Get mempool stats to check if there are transactions
-        let mempool_stats = self.blockchain.get_mempool_stats().await;
-        let tx_count = mempool_stats.get("transactions")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0);
-        
-        info!("Mempool has {} transactions", tx_count);
-        
-        // Get transactions from mempool
-        let pending_txs = self.blockchain.get_pending_transactions().await;
-        info!("Retrieved {} transactions from mempool for mining", pending_txs.len());
-        
-        // Allow mining empty blocks if needed (no transaction limit)
-        // Mining will continue even with empty blocks to maintain chain progression
+        let mut updates = self.templates.subscribe();
+        let template = match self.templates.current() {
+            Some(template) if template.prev_hash == self.blockchain.consensus.get_chain_state().await.best_block_hash => template,
+            _ => self.templates.rebuild().await,
+        };
+        updates.mark_unchanged();
         
-        // Create block template
-        let block_template = self.create_block_template(next_height).await?;
+        info!("Attempting to mine block at height {} on template {} ({} transactions)",
+              template.height, template.id, template.transactions.len());
         
         // Mine the block (Proof of Work)
-        let mined_block = self.mine_block(block_template).await?;
+        let Some(mined_block) = self.mine_block(&template, &mut updates).await? else {
+            let mut stats = self.stats.write().await;
+            stats.templates_abandoned += 1;
+            return Ok(false);
+        };
         
         // Submit to consensus
         self.submit_block(mined_block).await?;
@@ -160,139 +154,75 @@ impl MiningDaemon {
         Ok(true)
     }
     
-    /// Create coinbase transaction for mining reward
-    fn create_coinbase_transaction(&self, height: u64) -> Result<blockchain_core::transaction::Transaction, anyhow::Error> {
-        use blockchain_core::transaction::{Transaction, TransactionInput, TransactionOutput};
-        
-        // Coinbase reward: 50 EDU (50,000,000 satoshis)
-        let block_reward = 50_000_000_u64;
-        
-        // Create coinbase input (includes block height in script_sig)
-        let coinbase_data = format!("Block Height: {}", height).into_bytes();
-        let coinbase_input = TransactionInput::create_coinbase(coinbase_data);
-        
-        // Create output to validator address
-        let coinbase_output = TransactionOutput::create_p2pkh(
-            block_reward,
-            &self.validator_address
-        )?;
-        
-        // Create coinbase transaction using Transaction::new
-        let tx = Transaction::new(
-            1, // version
-            vec![coinbase_input],
-            vec![coinbase_output]
-        );
-        
-        Ok(tx)
-    }
-    
-    /// Create a block template ready for mining
-    async fn create_block_template(&self, height: u64) -> Result<Block, anyhow::Error> {
-        // Get chain state
-        let chain_state = self.blockchain.consensus.get_chain_state().await;
-        
-        // Get pending transactions from mempool
-        let mut transactions = self.blockchain.get_pending_transactions().await;
-        
-        // Create coinbase transaction (mining reward)
-        let coinbase_tx = self.create_coinbase_transaction(height)?;
-        
-        // Coinbase must be first transaction
-        transactions.insert(0, coinbase_tx);
-        
-        // Calculate merkle root
-        let merkle_root = if transactions.is_empty() {
-            Hash256::default()
-        } else {
-            let tx_hashes: Vec<Hash256> = transactions.iter()
-                .map(|tx| tx.calculate_hash())
-                .collect();
-            Block::compute_merkle_root(tx_hashes)
-        };
-        
-        info!("Block template: {} transactions, merkle root: {}", 
-              transactions.len(), hex::encode(&merkle_root));
-        
-        // Get previous block hash
-        let prev_hash = if height > 0 {
-            self.blockchain.get_block_by_height(height - 1)
-                .await
-                .map(|b| b.header.calculate_hash())
-                .unwrap_or_default()
-        } else {
-            Hash256::default()
-        };
-        
-        // Create block header
-        let header = BlockHeader::new(
-            1, // version
-            prev_hash,
-            merkle_root,
-            chain_state.next_difficulty,
-            height as u32,
-        );
-        
-        Ok(Block::new(header, transactions))
-    }
-    
-    /// Perform Proof of Work mining on a block
-    async fn mine_block(&self, mut block: Block) -> Result<Block, anyhow::Error> {
-        let target = block.header.difficulty_target;
+    /// Perform Proof of Work mining on a template.
+    /// Returns None if a newer template is published before a solution is found.
+    async fn mine_block(
+        &self,
+        template: &BlockTemplate,
+        updates: &mut watch::Receiver<Option<Arc<BlockTemplate>>>,
+    ) -> Result<Option<Block>, anyhow::Error> {
+        let target = template.difficulty_target;
         let start_time = std::time::Instant::now();
         
         debug!("⛏️  Mining block at height {} with difficulty {}", 
-               block.header.height, target);
+               template.height, target);
         
-        // Mine with nonce search
-        let mut nonce = 0u32;
         let mut hashes = 0u64;
         
+        // The header nonce covers 2^32 hashes; once exhausted, roll the
+        // coinbase extra nonce for a fresh merkle root instead of repeating work
+        let mut extra_nonce = 0u64;
         loop {
-            block.header.nonce = nonce;
-            hashes += 1;
+            let mut block = template.block(self.blockchain.consensus.params(), &self.validator_address, extra_nonce)?;
             
-            // Calculate hash
-            let hash = block.header.calculate_hash();
-            
-            // Check if hash meets difficulty target
-            if Self::hash_meets_target(&hash, target) {
-                let elapsed = start_time.elapsed();
-                let hash_rate = if elapsed.as_secs() > 0 {
-                    hashes as f64 / elapsed.as_secs() as f64
-                } else {
-                    hashes as f64
-                };
+            for nonce in 0..=u32::MAX {
+                block.header.nonce = nonce;
+                hashes += 1;
                 
-                info!("⛏️  Block mined! Height: {} | Nonce: {} | Hashes: {} | Rate: {:.0} H/s | Time: {:.2}s",
-                      block.header.height, nonce, hashes, hash_rate, elapsed.as_secs_f64());
+                // Calculate hash
+                let hash = block.header.calculate_hash();
                 
-                // Update stats
-                {
-                    let mut stats = self.stats.write().await;
-                    stats.hashes_computed += hashes;
+                // Check if hash meets difficulty target
+                if Self::hash_meets_target(&hash, target) {
+                    let elapsed = start_time.elapsed();
+                    let hash_rate = if elapsed.as_secs() > 0 {
+                        hashes as f64 / elapsed.as_secs() as f64
+                    } else {
+                        hashes as f64
+                    };
+                    
+                    info!("⛏️  Block mined! Height: {} | Nonce: {} | Extra nonce: {} | Hashes: {} | Rate: {:.0} H/s | Time: {:.2}s",
+                          block.header.height, nonce, extra_nonce, hashes, hash_rate, elapsed.as_secs_f64());
+                    
+                    self.stats.write().await.hashes_computed += hashes;
+                    return Ok(Some(block));
                 }
                 
-                return Ok(block);
-            }
-            
-            nonce = nonce.wrapping_add(1);
-            
-            // Periodically check if we should stop
-            if nonce % 10000 == 0 {
-                let should_stop = self.should_stop.read().await;
-                if *should_stop {
-                    return Err(anyhow::anyhow!("Mining stopped"));
+                // Periodically check if we should stop or switch to newer work
+                if nonce % 10000 == 0 {
+                    if *self.should_stop.read().await {
+                        return Err(anyhow::anyhow!("Mining stopped"));
+                    }
+                    
+                    if updates.has_changed().unwrap_or(false) {
+                        debug!("Template {} superseded after {} hashes, {:.1}s old",
+                               template.id, hashes, template.created_at.elapsed().as_secs_f64());
+                        self.stats.write().await.hashes_computed += hashes;
+                        return Ok(None);
+                    }
+                    
+                    // Yield to allow other tasks to run
+                    tokio::task::yield_now().await;
                 }
                 
-                // Yield to allow other tasks to run
-                tokio::task::yield_now().await;
+                // Log progress every 100k hashes
+                if hashes % 100000 == 0 {
+                    debug!("Mining progress: {} hashes, nonce: {}", hashes, nonce);
+                }
             }
             
-            // Log progress every 100k hashes
-            if hashes % 100000 == 0 {
-                debug!("Mining progress: {} hashes, nonce: {}", hashes, nonce);
-            }
+            debug!("Nonce space exhausted at extra nonce {}, rolling coinbase", extra_nonce);
+            extra_nonce += 1;
         }
     }
     
@@ -348,6 +278,13 @@ impl MiningDaemon {
             *backend_utxo_set = consensus_utxo_set;
         }
         
+        // Drop the block's transactions from the mempool
+        let confirmed: Vec<Hash256> = block.transactions.iter()
+            .skip(1)
+            .map(|tx| tx.get_hash())
+            .collect::<Result<_, _>>()?;
+        self.blockchain.mempool.write().await.remove_confirmed_transactions(&confirmed).await?;
+        
         info!("✅ Block added to blockchain at height {}", block.header.height);
         
         // TODO: Broadcast block to network once P2P is wired
@@ -377,6 +314,7 @@ pub async fn get_mining_info(daemon: &MiningDaemon) -> serde_json::Value {
     serde_json::json!({
         "blocks_mined": stats.blocks_mined,
         "hashes_computed": stats.hashes_computed,
+        "templates_abandoned": stats.templates_abandoned,
         "mining_duration_secs": elapsed.as_secs(),
         "average_block_time_secs": avg_block_time,
         "hash_rate": hash_rate,
@@ -0,0 +1,393 @@
+//! Header-Only Monitoring Mode
+//!
+//! `--headers-only` runs the node without a UTXO set, mempool, wallets or
+//! block storage. It keeps peer connections, follows the best chain from
+//! headers, records forks and counts the transactions peers relay, which is
+//! all a dashboard or alerting service needs.
+//!
+//! Fee estimates come from the blocks at the tip only: a block's fees are
+//! whatever its coinbase claims above the block reward, so they can be read
+//! without looking up any of the outputs its transactions spend.
+//!
+//! Forks, invalid headers and tip changes are passed to the `ChainAlerter`.
+
+use blockchain_core::block::{Block, BlockHeader};
+use blockchain_core::canonical::{self, HexHash};
+use crate::alerts::ChainAlerter;
+use blockchain_core::header_chain::{ChainTipInfo, ForkAlert, HeaderChain, HeaderOutcome};
+use blockchain_core::mempool::MempoolConfig;
+use blockchain_core::network_time::TimeStatus;
+use blockchain_core::{Amount, BlockchainError, Hash256};
+use blockchain_network::outbound::QueueMetrics;
+use blockchain_network::relay_cache::RelayCacheStats;
+use blockchain_network::protocol::{BlockHeaderInfo, Message};
+use blockchain_network::swarm::NetworkEvent;
+use blockchain_network::{NetworkManager, Uuid};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{debug, warn};
+
+/// Headers asked for per GetHeaders request
+const HEADER_BATCH: u64 = 2000;
+
+/// How far below the tip to re-request headers when a peer is on another branch
+const FORK_LOOKBACK: u64 = 100;
+
+/// Tip blocks whose fees feed the estimates
+const FEE_WINDOW: usize = 20;
+
+/// Relayed transaction ids remembered, to count each one once
+const MAX_SEEN_TXS: usize = 50_000;
+
+/// Fees paid in one block
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockFees {
+    pub height: u64,
+    pub hash: String,
+    #[serde(with = "canonical::sats")]
+    pub fees: Amount,
+    pub vsize: usize,
+    pub transactions: usize,
+    /// Average fee rate, satoshis per vbyte
+    pub fee_rate: f64,
+}
+
+/// Transactions peers relayed since startup
+#[derive(Debug, Default, Serialize)]
+pub struct RelayStats {
+    /// Full transactions received
+    pub transactions_received: u64,
+    /// Distinct transactions, among the most recent ones seen
+    pub unique_transactions: u64,
+    /// Transaction ids announced in inventory messages
+    pub inventory_announced: u64,
+    pub bytes_received: u64,
+    /// Relayed transactions later seen in a tip block
+    pub confirmed: u64,
+}
+
+/// Follows headers and relay traffic for a header-only node
+pub struct HeaderMonitor {
+    network: Arc<NetworkManager>,
+    chain: RwLock<HeaderChain>,
+    alerter: Arc<ChainAlerter>,
+    block_reward: Amount,
+    /// Height each peer last reported
+    peers: RwLock<HashMap<Uuid, u64>>,
+    relay: RwLock<RelayStats>,
+    seen: RwLock<(HashSet<Hash256>, VecDeque<Hash256>)>,
+    fees: RwLock<VecDeque<BlockFees>>,
+    started: Instant,
+}
+
+impl HeaderMonitor {
+    pub fn new(network: Arc<NetworkManager>, genesis: BlockHeader, block_reward: Amount, alerter: Arc<ChainAlerter>) -> Self {
+        Self {
+            chain: RwLock::new(HeaderChain::new(genesis).with_network_time(network.network_time())),
+            network,
+            alerter,
+            block_reward,
+            peers: RwLock::new(HashMap::new()),
+            relay: RwLock::new(RelayStats::default()),
+            seen: RwLock::new((HashSet::new(), VecDeque::new())),
+            fees: RwLock::new(VecDeque::new()),
+            started: Instant::now(),
+        }
+    }
+
+    /// Handle network events until the network shuts down
+    pub async fn run(self: Arc<Self>, mut events: Receiver<NetworkEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => self.handle(event).await,
+                Err(RecvError::Lagged(skipped)) => warn!("⚠️  Monitor fell behind; skipped {} network events", skipped),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn handle(&self, event: NetworkEvent) {
+        match event {
+            NetworkEvent::PeerConnected { peer_id, .. } => {
+                self.send(peer_id, Message::get_blockchain_height()).await;
+                self.request_headers(peer_id, self.tip_height() + 1).await;
+            }
+            NetworkEvent::PeerDisconnected { peer_id, .. } => {
+                self.peers.write().unwrap().remove(&peer_id);
+            }
+            NetworkEvent::PeerHeight { peer_id, height, best_block_hash } => {
+                self.peers.write().unwrap().insert(peer_id, height);
+                let known = self.chain.read().unwrap().get(&best_block_hash).is_some();
+                let tip = self.tip_height();
+                if !known {
+                    // Behind the peer, or on a different branch at our height
+                    let from = if height > tip { tip + 1 } else { tip.saturating_sub(FORK_LOOKBACK) };
+                    self.request_headers(peer_id, from).await;
+                }
+            }
+            NetworkEvent::BlockInventory { peer_id, .. } => {
+                self.request_headers(peer_id, self.tip_height() + 1).await;
+            }
+            NetworkEvent::HeadersReceived { peer_id, headers } => self.add_headers(peer_id, headers).await,
+            NetworkEvent::BlockReceived { block, .. } => self.record_block(&block),
+            NetworkEvent::TransactionReceived { transaction, .. } => {
+                let Ok(txid) = transaction.get_hash() else { return };
+                let first_seen = self.remember(txid);
+                let mut relay = self.relay.write().unwrap();
+                relay.transactions_received += 1;
+                relay.bytes_received += transaction.total_size() as u64;
+                if first_seen {
+                    relay.unique_transactions += 1;
+                }
+            }
+            NetworkEvent::TransactionInventory { tx_hashes, .. } => {
+                self.relay.write().unwrap().inventory_announced += tx_hashes.len() as u64;
+            }
+        }
+    }
+
+    async fn add_headers(&self, peer_id: Uuid, mut headers: Vec<BlockHeaderInfo>) {
+        // Peers answer from an unordered index; parents must come first
+        headers.sort_by_key(|header| header.height);
+        let received = headers.len() as u64;
+        let old_tip = self.chain.read().unwrap().tip();
+
+        let mut orphan_at = None;
+        for info in headers {
+            let height = info.height;
+            let header = BlockHeader {
+                version: info.version,
+                prev_block_hash: info.prev_hash,
+                merkle_root: info.merkle_root,
+                timestamp: info.timestamp as u32,
+                difficulty_target: info.difficulty,
+                nonce: info.nonce,
+                height: info.height as u32,
+            };
+            if header.calculate_hash() != info.hash {
+                warn!("⚠️  Peer {} sent header {} with a mismatched hash", peer_id, height);
+                return;
+            }
+
+            let (outcome, fork, tip_height) = {
+                let mut chain = self.chain.write().unwrap();
+                let outcome = chain.add_header(header);
+                // The alert this header raised, if any
+                let hash = hex::encode(info.hash);
+                let fork = chain.alerts(1).pop().filter(|alert| alert.best_hash == hash || alert.competing_hash == hash);
+                (outcome, fork, chain.tip().0)
+            };
+            if let Some(fork) = fork {
+                self.alerter.fork(&fork, tip_height);
+            }
+            match outcome {
+                Ok(HeaderOutcome::Reorg { depth }) => {
+                    warn!("🔀 Reorg of {} block(s) to {} at height {}", depth, hex::encode(info.hash), height);
+                }
+                Ok(HeaderOutcome::SideBranch) => {
+                    warn!("🍴 Competing block {} at height {}", hex::encode(info.hash), height);
+                }
+                Ok(_) => {}
+                Err(BlockchainError::OrphanBlock) => {
+                    orphan_at.get_or_insert(height);
+                }
+                Err(e) => {
+                    warn!("⚠️  Peer {} sent an invalid header at height {}: {}", peer_id, height, e);
+                    self.alerter.invalid_block(info.hash, height, &e.to_string(), peer_id.to_string());
+                    return;
+                }
+            }
+        }
+
+        let (tip_height, tip_hash) = self.chain.read().unwrap().tip();
+        if let Some(height) = orphan_at {
+            // The peer is on a branch we lack the start of; look back once
+            let from = old_tip.0.saturating_sub(FORK_LOOKBACK);
+            if height > from {
+                self.request_headers(peer_id, from).await;
+            } else {
+                warn!("⚠️  Peer {} is on a branch forking more than {} blocks back", peer_id, FORK_LOOKBACK);
+            }
+        } else if received >= HEADER_BATCH {
+            self.request_headers(peer_id, tip_height + 1).await;
+        }
+
+        if tip_hash != old_tip.1 {
+            self.alerter.tip_seen(tip_height);
+            debug!("Header tip now {} at height {}", hex::encode(tip_hash), tip_height);
+            // Only blocks at the tip are worth fetching for fee estimates
+            if tip_height >= self.network_height() {
+                self.send(peer_id, Message::get_block_by_height(tip_height)).await;
+            }
+        }
+    }
+
+    /// Read a tip block's fees and mark relayed transactions confirmed
+    fn record_block(&self, block: &Block) {
+        let hash = block.get_hash();
+        let height = block.header.height as u64;
+        if self.chain.read().unwrap().hash_at(height) != Some(hash) {
+            return;
+        }
+        let mut fees = self.fees.write().unwrap();
+        let hash_hex = hex::encode(hash);
+        if fees.iter().any(|entry| entry.hash == hash_hex) {
+            return;
+        }
+
+        let claimed = Amount::checked_sum(block.transactions.iter()
+            .filter(|tx| tx.is_coinbase())
+            .map(|tx| Amount::from_sat(tx.get_total_output_value())))
+            .unwrap_or(Amount::MAX);
+        let paid: Vec<_> = block.transactions.iter().filter(|tx| !tx.is_coinbase()).collect();
+        let vsize: usize = paid.iter().map(|tx| tx.vsize()).sum();
+        let block_fees = claimed.saturating_sub(self.block_reward);
+
+        let seen = self.seen.read().unwrap();
+        let confirmed = paid.iter()
+            .filter_map(|tx| tx.get_hash().ok())
+            .filter(|txid| seen.0.contains(txid))
+            .count();
+        self.relay.write().unwrap().confirmed += confirmed as u64;
+
+        if fees.len() == FEE_WINDOW {
+            fees.pop_front();
+        }
+        fees.push_back(BlockFees {
+            height,
+            hash: hash_hex,
+            fees: block_fees,
+            vsize,
+            transactions: paid.len(),
+            fee_rate: if vsize > 0 { block_fees.to_sat() as f64 / vsize as f64 } else { 0.0 },
+        });
+    }
+
+    /// Remember a relayed txid; false if it was already seen
+    fn remember(&self, txid: Hash256) -> bool {
+        let mut seen = self.seen.write().unwrap();
+        let (ids, order) = &mut *seen;
+        if !ids.insert(txid) {
+            return false;
+        }
+        order.push_back(txid);
+        if order.len() > MAX_SEEN_TXS {
+            if let Some(oldest) = order.pop_front() {
+                ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    async fn request_headers(&self, peer_id: Uuid, from: u64) {
+        self.send(peer_id, Message::get_headers(1, from, from + HEADER_BATCH - 1, None)).await;
+    }
+
+    async fn send(&self, peer_id: Uuid, message: Message) {
+        if let Err(e) = self.network.send_to_peer(peer_id, message).await {
+            debug!("Failed to message peer {}: {}", peer_id, e);
+        }
+    }
+
+    pub fn tip_height(&self) -> u64 {
+        self.chain.read().unwrap().tip().0
+    }
+
+    /// Highest height any connected peer reported
+    fn network_height(&self) -> u64 {
+        self.peers.read().unwrap().values().copied().max().unwrap_or(0)
+    }
+
+    /// `monitor_getStatus` report
+    pub fn status(&self) -> Value {
+        let chain = self.chain.read().unwrap();
+        let (height, hash) = chain.tip();
+        let tip = chain.tip_header();
+        let network_height = self.network_height();
+        json!({
+            "mode": "headers_only",
+            "tip_height": height,
+            "tip_hash": HexHash(hash),
+            "tip_timestamp": tip.timestamp,
+            "headers_known": chain.len(),
+            "network_height": network_height,
+            "synced": height >= network_height,
+            "peers": self.peers.read().unwrap().len(),
+            "uptime_secs": self.started.elapsed().as_secs(),
+        })
+    }
+
+    pub fn fork_alerts(&self, limit: usize) -> Vec<ForkAlert> {
+        self.chain.read().unwrap().alerts(limit)
+    }
+
+    /// `blockchain_getChainTips` report: header branches within `window`
+    /// blocks of the tip, then blocks peers sent that failed validation
+    pub fn chain_tips(&self, window: u64) -> Vec<ChainTipInfo> {
+        let mut tips = self.chain.read().unwrap().tips(window);
+        let tip_height = self.tip_height();
+        tips.extend(self.alerter.invalid_tips(tip_height, window));
+        tips
+    }
+
+    /// Outbound message queues per priority class, over all peers
+    pub async fn queue_metrics(&self) -> Vec<QueueMetrics> {
+        self.network.queue_metrics().await
+    }
+
+    /// Blocks and transactions held for serving GetData, with hit rates
+    pub fn relay_cache_stats(&self) -> RelayCacheStats {
+        self.network.relay_cache_stats()
+    }
+
+    pub fn network_time(&self) -> TimeStatus {
+        self.network.network_time().status()
+    }
+
+    pub fn alerter(&self) -> &Arc<ChainAlerter> {
+        &self.alerter
+    }
+
+    /// Fee rates (sat/vbyte) at the 90th, 50th and 10th percentile of recent
+    /// tip blocks, falling back to the relay minimum before any are seen
+    pub fn fee_estimates(&self) -> Value {
+        let fees = self.fees.read().unwrap();
+        let mut rates: Vec<f64> = fees.iter()
+            .filter(|block| block.transactions > 0)
+            .map(|block| block.fee_rate)
+            .collect();
+        rates.sort_by(|a, b| a.total_cmp(b));
+
+        let floor = MempoolConfig::default().min_relay_fee_rate as f64;
+        let percentile = |p: usize| -> f64 {
+            if rates.is_empty() {
+                return floor;
+            }
+            rates[(rates.len() - 1) * p / 100].max(floor)
+        };
+        json!({
+            "fast": percentile(90),
+            "normal": percentile(50),
+            "economy": percentile(10),
+            "blocks_sampled": rates.len(),
+            "recent_blocks": fees.iter().rev().collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn relay_stats(&self) -> Value {
+        let relay = self.relay.read().unwrap();
+        let minutes = (self.started.elapsed().as_secs_f64() / 60.0).max(1.0 / 60.0);
+        json!({
+            "transactions_received": relay.transactions_received,
+            "unique_transactions": relay.unique_transactions,
+            "inventory_announced": relay.inventory_announced,
+            "bytes_received": relay.bytes_received,
+            "confirmed": relay.confirmed,
+            "transactions_per_minute": relay.unique_transactions as f64 / minutes,
+        })
+    }
+}
@@ -0,0 +1,408 @@
+//! Embedding a full node
+//!
+//! `Node::builder()` assembles what the `blockchain-node` binary runs:
+//! block storage, P2P network, mempool, treasury, vouchers, indexes, compaction,
+//! chain alerts, wallet rebroadcast, the RPC server and optionally a miner. Every setting
+//! starts at the same default as the command line, so an application only
+//! sets what it needs to differ.
+
+use crate::admin::AdminConsole;
+use crate::alerts::{AlertConfig, ChainAlerter};
+use crate::blockchain::BlockchainBackend;
+use crate::disk::{CompactionConfig, Compactor};
+use crate::indexer::{IndexConfig, IndexManager};
+use crate::miner::MiningDaemon;
+use crate::rebroadcast::{RebroadcastConfig, WalletRebroadcaster};
+use crate::release::{ChainManifest, NETWORK_MAGIC};
+use crate::rpc::create_blockchain_rpc_server;
+use crate::treasury::{self, TreasuryManager};
+use crate::vouchers::VoucherManager;
+use blockchain_core::consensus::ConsensusParams;
+use blockchain_core::contract_calls::{CallConfig, EVM_CALL_DEPTH_LIMIT};
+use blockchain_core::genesis::GenesisConfig;
+use blockchain_core::mempool::MempoolConfig;
+use blockchain_core::supply_audit::SupplyAuditConfig;
+use blockchain_network::NetworkConfig;
+use blockchain_rpc::server::{RpcServerConfig, Server};
+use blockchain_rpc::FeatureFlags;
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// P2P port nodes listen on unless told otherwise
+pub const DEFAULT_P2P_PORT: u16 = 9000;
+
+/// P2P settings of an EduNet node listening on `port`
+pub fn network_config(port: u16, seed_peers: Vec<SocketAddr>) -> NetworkConfig {
+    NetworkConfig {
+        listen_addr: SocketAddr::from(([0, 0, 0, 0], port)),
+        listening_port: port,
+        seed_peers,
+        dns_seeds: vec![],
+        our_services: 1,
+        max_peers: 58,
+        connection_timeout: Duration::from_secs(30),
+        heartbeat_interval: Duration::from_secs(30),
+        max_message_size: 32 * 1024 * 1024,
+        network_magic: NETWORK_MAGIC,
+        peers_file: None,
+    }
+}
+
+/// Everything a full node is started with
+pub struct NodeConfig {
+    /// Chain manifest, admin audit log, supply audit reports and known peers
+    pub data_dir: PathBuf,
+    pub network: NetworkConfig,
+    /// Consensus rules; a chain manifest in `data_dir` supplies the dev fund
+    pub consensus: ConsensusParams,
+    /// Genesis block; None reads it from the chain manifest in `data_dir`
+    pub genesis: Option<GenesisConfig>,
+    /// Check total UTXO value against expected supply at every block
+    pub audit_supply: bool,
+    pub mempool: MempoolConfig,
+    pub calls: CallConfig,
+    /// None runs without an RPC server
+    pub rpc: Option<RpcServerConfig>,
+    pub features: FeatureFlags,
+    /// Mine, paying rewards to this address
+    pub mining_address: Option<String>,
+    /// Optional indexes to build, by name
+    pub indexes: Vec<String>,
+    pub index: IndexConfig,
+    pub compaction: CompactionConfig,
+    pub alerts: AlertConfig,
+    pub rebroadcast: RebroadcastConfig,
+    /// Token admin RPCs require; None disables them
+    pub admin_token: Option<String>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("./blockchain-data"),
+            network: network_config(DEFAULT_P2P_PORT, Vec::new()),
+            consensus: ConsensusParams::default(),
+            genesis: None,
+            audit_supply: false,
+            mempool: MempoolConfig::default(),
+            calls: CallConfig::default(),
+            rpc: Some(RpcServerConfig::default()),
+            features: FeatureFlags::default(),
+            mining_address: None,
+            indexes: Vec::new(),
+            index: IndexConfig::default(),
+            compaction: CompactionConfig::default(),
+            alerts: AlertConfig::default(),
+            rebroadcast: RebroadcastConfig::default(),
+            admin_token: None,
+        }
+    }
+}
+
+/// Builds a `Node`, starting from `NodeConfig::default()`
+#[derive(Default)]
+pub struct NodeBuilder {
+    config: NodeConfig,
+}
+
+impl NodeBuilder {
+    /// Start from a complete config instead of the defaults
+    pub fn from_config(config: NodeConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn data_dir(mut self, data_dir: impl Into<PathBuf>) -> Self {
+        self.config.data_dir = data_dir.into();
+        self
+    }
+
+    pub fn network(mut self, network: NetworkConfig) -> Self {
+        self.config.network = network;
+        self
+    }
+
+    /// Listen for peers on `port`, keeping the seed peers
+    pub fn p2p_port(mut self, port: u16) -> Self {
+        let seed_peers = std::mem::take(&mut self.config.network.seed_peers);
+        self.config.network = network_config(port, seed_peers);
+        self
+    }
+
+    pub fn seed_peers(mut self, peers: Vec<SocketAddr>) -> Self {
+        self.config.network.seed_peers = peers;
+        self
+    }
+
+    pub fn consensus(mut self, consensus: ConsensusParams) -> Self {
+        self.config.consensus = consensus;
+        self
+    }
+
+    pub fn genesis(mut self, genesis: GenesisConfig) -> Self {
+        self.config.genesis = Some(genesis);
+        self
+    }
+
+    pub fn audit_supply(mut self, enabled: bool) -> Self {
+        self.config.audit_supply = enabled;
+        self
+    }
+
+    pub fn mempool(mut self, mempool: MempoolConfig) -> Self {
+        self.config.mempool = mempool;
+        self
+    }
+
+    pub fn calls(mut self, calls: CallConfig) -> Self {
+        self.config.calls = calls;
+        self
+    }
+
+    pub fn rpc(mut self, rpc: RpcServerConfig) -> Self {
+        self.config.rpc = Some(rpc);
+        self
+    }
+
+    /// Don't serve RPC; the application uses the node's parts directly
+    pub fn without_rpc(mut self) -> Self {
+        self.config.rpc = None;
+        self
+    }
+
+    pub fn features(mut self, features: FeatureFlags) -> Self {
+        self.config.features = features;
+        self
+    }
+
+    pub fn mining(mut self, reward_address: impl Into<String>) -> Self {
+        self.config.mining_address = Some(reward_address.into());
+        self
+    }
+
+    pub fn indexes(mut self, indexes: Vec<String>) -> Self {
+        self.config.indexes = indexes;
+        self
+    }
+
+    pub fn index_config(mut self, index: IndexConfig) -> Self {
+        self.config.index = index;
+        self
+    }
+
+    pub fn compaction(mut self, compaction: CompactionConfig) -> Self {
+        self.config.compaction = compaction;
+        self
+    }
+
+    pub fn alerts(mut self, alerts: AlertConfig) -> Self {
+        self.config.alerts = alerts;
+        self
+    }
+
+    pub fn rebroadcast(mut self, rebroadcast: RebroadcastConfig) -> Self {
+        self.config.rebroadcast = rebroadcast;
+        self
+    }
+
+    pub fn admin_token(mut self, token: Option<String>) -> Self {
+        self.config.admin_token = token;
+        self
+    }
+
+    /// Open storage, join the network and start every background task
+    pub async fn build(self) -> Result<Node> {
+        let mut config = self.config;
+        std::fs::create_dir_all(&config.data_dir)?;
+        // Known peers are kept with the rest of the node's data
+        if config.network.peers_file.is_none() {
+            config.network.peers_file = Some(config.data_dir.join("peers.json"));
+        }
+
+        let manifest = ChainManifest::load(&config.data_dir)?;
+        let genesis_config = match config.genesis {
+            Some(genesis) => genesis,
+            None => manifest.as_ref()
+                .map(|manifest| manifest.genesis_config())
+                .unwrap_or_default(),
+        };
+        let consensus = match &manifest {
+            Some(manifest) => manifest.consensus_params(config.consensus),
+            None => config.consensus,
+        };
+
+        // Initialize blockchain backend
+        info!("💾 Initializing blockchain backend...");
+        let supply_audit = config.audit_supply.then(|| SupplyAuditConfig {
+            report_dir: Some(config.data_dir.join("supply-audit")),
+        });
+        info!("🧹 Mempool eviction policy: {:?}", config.mempool.eviction_policy);
+        let call_config = CallConfig {
+            max_call_depth: config.calls.max_call_depth.min(EVM_CALL_DEPTH_LIMIT),
+            ..config.calls
+        };
+        let blockchain = Arc::new(BlockchainBackend::new(
+            &config.data_dir,
+            config.network,
+            consensus,
+            genesis_config,
+            supply_audit,
+            config.mempool,
+            call_config,
+        ).await?);
+        info!("✅ Blockchain initialized at height {}", blockchain.get_height().await);
+        blockchain.update_fee_filter().await;
+        blockchain.watch_fee_filter();
+
+        // Initialize treasury manager
+        info!("💰 Initializing treasury manager...");
+        let treasury = Arc::new(TreasuryManager::new(blockchain.clone())?);
+        info!("✅ Treasury ready - Address: {}", treasury::TREASURY_ADDRESS);
+        let vouchers = Arc::new(VoucherManager::new(treasury.clone(), &config.data_dir)?);
+
+        // Optional indexes build in the background so startup isn't held up
+        let indexes = Arc::new(IndexManager::new(blockchain.clone(), &config.indexes, config.index)?);
+        indexes.start();
+
+        // Block storage compaction, on demand and optionally on a schedule
+        let compactor = Arc::new(Compactor::new(blockchain.storage.clone(), config.compaction));
+        compactor.start_schedule();
+
+        // Chain alerts, with the stale tip check fed by consensus
+        let alerter = Arc::new(ChainAlerter::new(config.alerts));
+        alerter.start();
+        alerter.tip_seen(blockchain.get_height().await);
+        alerter.watch_tip(blockchain.consensus.subscribe_tip());
+
+        // Unconfirmed wallet transactions are announced again until they settle
+        let rebroadcaster = Arc::new(WalletRebroadcaster::new(blockchain.clone(), config.rebroadcast));
+        rebroadcaster.start();
+
+        // Contract state follows the chain block by block, rolled back on reorgs
+        blockchain.watch_contracts();
+
+        // Admin console (token-gated RPCs with audit log)
+        let admin = Arc::new(AdminConsole::new(config.admin_token, &config.data_dir));
+        let features = Arc::new(config.features);
+
+        let rpc = match config.rpc {
+            Some(rpc_config) => {
+                info!("🔌 Starting RPC server on {}:{}...", rpc_config.host, rpc_config.port);
+                let server = create_blockchain_rpc_server(
+                    rpc_config,
+                    blockchain.clone(),
+                    treasury.clone(),
+                    vouchers.clone(),
+                    admin.clone(),
+                    features.clone(),
+                    indexes.clone(),
+                    compactor.clone(),
+                    alerter.clone(),
+                    config.data_dir.clone(),
+                );
+                Some(server.start().map_err(|e| anyhow::anyhow!(e))?)
+            }
+            None => None,
+        };
+
+        let mining = config.mining_address.map(|address| {
+            info!("⛏️  Mining enabled - Rewards to: {}", address);
+            MiningDaemon::new(blockchain.clone(), address).start()
+        });
+
+        Ok(Node {
+            blockchain,
+            treasury,
+            vouchers,
+            indexes,
+            compactor,
+            alerter,
+            rebroadcaster,
+            admin,
+            features,
+            data_dir: config.data_dir,
+            rpc,
+            mining,
+        })
+    }
+}
+
+/// A running full node
+pub struct Node {
+    pub blockchain: Arc<BlockchainBackend>,
+    pub treasury: Arc<TreasuryManager>,
+    pub vouchers: Arc<VoucherManager>,
+    pub indexes: Arc<IndexManager>,
+    pub compactor: Arc<Compactor>,
+    pub alerter: Arc<ChainAlerter>,
+    pub rebroadcaster: Arc<WalletRebroadcaster>,
+    pub admin: Arc<AdminConsole>,
+    pub features: Arc<FeatureFlags>,
+    pub data_dir: PathBuf,
+    rpc: Option<Server>,
+    mining: Option<JoinHandle<()>>,
+}
+
+impl Node {
+    pub fn builder() -> NodeBuilder {
+        NodeBuilder::default()
+    }
+
+    pub fn is_mining(&self) -> bool {
+        self.mining.is_some()
+    }
+
+    /// Run until the RPC server stops, then stop mining. Returns at once
+    /// without an RPC server.
+    pub async fn wait(mut self) {
+        if let Some(server) = self.rpc.take() {
+            // `Server::wait` blocks, keep it off the runtime's threads
+            let _ = tokio::task::spawn_blocking(move || server.wait()).await;
+        }
+        self.shutdown();
+    }
+
+    /// Stop serving RPC and stop mining
+    pub fn shutdown(mut self) {
+        if let Some(server) = self.rpc.take() {
+            server.close();
+        }
+        if let Some(handle) = self.mining.take() {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockchain_network::protocol::{services, Message, MessagePayload, NetworkAddress, PROTOCOL_VERSION};
+    use blockchain_network::NetworkManager;
+
+    #[test]
+    fn test_default_network_config_handshakes_with_node() {
+        let node = network_config(DEFAULT_P2P_PORT, vec![]);
+        let manager = NetworkConfig::default();
+        let addr = NetworkAddress::from_ipv4([127, 0, 0, 1], DEFAULT_P2P_PORT, services::NODE_NETWORK);
+        let genesis = [7u8; 32];
+        // The version each side sends on connecting
+        let version = |config: &NetworkConfig| {
+            let message = Message::version(
+                PROTOCOL_VERSION, config.our_services, "test".to_string(), 0,
+                addr.clone(), addr.clone(), config.network_magic, genesis,
+            );
+            match message.payload {
+                MessagePayload::Version(version) => version,
+                _ => panic!("Expected version payload"),
+            }
+        };
+
+        assert_eq!(version(&manager).network_mismatch(node.network_magic, genesis), None);
+        assert_eq!(version(&node).network_mismatch(manager.network_magic, genesis), None);
+        assert!(NetworkManager::new(manager, None).is_ok());
+    }
+}
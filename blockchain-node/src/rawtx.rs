@@ -0,0 +1,217 @@
+//! Raw Transactions
+//!
+//! A raw transaction is the hex encoding of `Transaction::serialize`, the
+//! same bytes peers relay. `tx_decodeRaw` turns one into JSON, resolving each
+//! input's previous output against the UTXO set, the mempool and, for spent
+//! outputs, the txindex when it is enabled. `tx_createRaw` builds an
+//! unsigned transaction from outpoints and outputs so external tools can
+//! construct transactions without linking the crate.
+//!
+//! `tx_exportOffline` packages an unsigned transaction with the outputs it
+//! spends as a QR sequence for an air-gapped signer, and
+//! `tx_importSignatures` puts the signatures scanned back into it.
+//!
+//! `script_debugExecute` traces a script_sig and script_pubkey opcode by
+//! opcode. Given a `tx_context`, either script can be left out and is taken
+//! from the transaction's input and the output it spends.
+
+use crate::blockchain::BlockchainBackend;
+use crate::indexer::IndexManager;
+use blockchain_core::address::AddressCodec;
+use blockchain_core::offline_signing::UnsignedTransaction;
+use blockchain_core::script_trace::{self, TraceContext};
+use blockchain_core::transaction::{Transaction, TransactionInput, TransactionOutput};
+use blockchain_core::Hash256;
+use serde_json::{json, Map, Value};
+
+/// Parse a hex-encoded raw transaction
+pub fn decode_hex(raw_tx: &str) -> Result<Transaction, String> {
+    let bytes = hex::decode(raw_tx.trim()).map_err(|_| "raw transaction must be hex".to_string())?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("invalid transaction: {}", e))
+}
+
+/// Hex encoding of a transaction's serialized bytes
+pub fn encode_hex(tx: &Transaction) -> Result<String, String> {
+    tx.serialize().map(hex::encode).map_err(|e| e.to_string())
+}
+
+fn parse_hash(value: Option<&Value>, what: &str) -> Result<Hash256, String> {
+    value.and_then(|v| v.as_str())
+        .and_then(|h| hex::decode(h).ok())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("{} must be a 32-byte hex hash", what))
+}
+
+/// Build an unsigned transaction from `[{txid, vout, sequence?}]` inputs and
+/// `[{address | script_pubkey, value}]` outputs
+pub fn create(inputs: &[Value], outputs: &[Value], locktime: u32) -> Result<Transaction, String> {
+    if inputs.is_empty() || outputs.is_empty() {
+        return Err("need at least one input and one output".to_string());
+    }
+    let inputs = inputs.iter()
+        .map(|input| {
+            let txid = parse_hash(input.get("txid"), "txid")?;
+            let vout = input.get("vout").and_then(|v| v.as_u64())
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or("vout must be an output index")?;
+            let mut input_tx = TransactionInput::new(txid, vout, Vec::new());
+            if let Some(sequence) = input.get("sequence") {
+                input_tx.sequence = sequence.as_u64().and_then(|s| u32::try_from(s).ok())
+                    .ok_or("sequence must be a 32-bit number")?;
+            }
+            Ok(input_tx)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let outputs = outputs.iter()
+        .map(|output| {
+            let value = output.get("value").and_then(|v| v.as_u64())
+                .ok_or("output value must be a number of satoshis")?;
+            match (output.get("address").and_then(|a| a.as_str()), output.get("script_pubkey").and_then(|s| s.as_str())) {
+                (Some(address), None) => AddressCodec::validate(address)
+                    .map_err(|e| format!("output address {}: {}", address, e))
+                    .and_then(|_| TransactionOutput::create_p2pkh(value, address).map_err(|e| e.to_string())),
+                (None, Some(script)) => hex::decode(script)
+                    .map(|script| TransactionOutput::new(value, script))
+                    .map_err(|_| "script_pubkey must be hex".to_string()),
+                _ => Err("each output needs an address or a script_pubkey".to_string()),
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut tx = Transaction::new(1, inputs, outputs);
+    tx.locktime = locktime;
+    Ok(tx)
+}
+
+/// `tx` packaged for offline signing by `account_index`, every input's
+/// previous output resolved
+pub async fn export_offline(
+    blockchain: &BlockchainBackend,
+    indexes: &IndexManager,
+    tx: Transaction,
+    account_index: u32,
+) -> Result<UnsignedTransaction, String> {
+    let mut spent_outputs = Vec::with_capacity(tx.inputs.len());
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let (output, _) = resolve_input(blockchain, indexes, input).await
+            .ok_or_else(|| format!("input {} spends {}, which cannot be found", i, input.get_outpoint()))?;
+        spent_outputs.push(output);
+    }
+    UnsignedTransaction::new(tx, spent_outputs, account_index).map_err(|e| e.to_string())
+}
+
+fn output_json(output: &TransactionOutput) -> Value {
+    json!({
+        "value": output.value,
+        "script_pubkey": hex::encode(&output.script_pubkey),
+        "address": output.get_address(),
+    })
+}
+
+/// Previous output an input spends, and where it was found
+pub async fn resolve_input(
+    blockchain: &BlockchainBackend,
+    indexes: &IndexManager,
+    input: &TransactionInput,
+) -> Option<(TransactionOutput, &'static str)> {
+    if let Some(utxo) = blockchain.utxo_set.read().await.get_utxo(&input.get_outpoint()) {
+        return Some((utxo.output.clone(), "utxo"));
+    }
+    if let Some(parent) = blockchain.mempool.read().await.get_transaction(&input.prev_tx_hash) {
+        return parent.outputs.get(input.prev_output_index as usize).cloned().map(|output| (output, "mempool"));
+    }
+    let location = indexes.tx_index()?.get(&input.prev_tx_hash)?;
+    let block = blockchain.consensus.get_block_by_height(location.block_height).await?;
+    let parent = block.transactions.get(location.position as usize)?;
+    parent.outputs.get(input.prev_output_index as usize).cloned().map(|output| (output, "txindex"))
+}
+
+/// JSON form of a transaction, with previous outputs and the fee when every
+/// input could be resolved
+pub async fn decode(blockchain: &BlockchainBackend, indexes: &IndexManager, tx: &Transaction) -> Value {
+    let mut inputs = Vec::new();
+    let mut input_total = Some(0u64);
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let prevout = if input.is_coinbase() { None } else { resolve_input(blockchain, indexes, input).await };
+        input_total = match (&prevout, input_total) {
+            (Some((output, _)), Some(total)) => Some(total + output.value),
+            _ => None,
+        };
+        inputs.push(json!({
+            "txid": hex::encode(input.prev_tx_hash),
+            "vout": input.prev_output_index,
+            "script_sig": hex::encode(&input.script_sig),
+            "sequence": input.sequence,
+            "witness": tx.witnesses.get(i).map(|w| w.witness_items.iter().map(hex::encode).collect::<Vec<_>>()),
+            "prevout": prevout.as_ref().map(|(output, source)| {
+                let mut prevout = output_json(output);
+                prevout["source"] = json!(source);
+                prevout
+            }),
+        }));
+    }
+
+    let output_total = tx.get_total_output_value();
+    let fee = if tx.is_coinbase() { None } else { input_total.map(|total| total.saturating_sub(output_total)) };
+    json!({
+        "txid": hex::encode(tx.calculate_hash()),
+        "version": tx.version,
+        "locktime": tx.locktime,
+        "size": tx.total_size(),
+        "vsize": tx.vsize(),
+        "weight": tx.weight(),
+        "coinbase": tx.is_coinbase(),
+        "inputs": inputs,
+        "outputs": tx.outputs.iter().map(output_json).collect::<Vec<_>>(),
+        "output_total": output_total,
+        "fee": fee,
+        "contract_address": tx.contract_address.map(hex::encode),
+    })
+}
+
+fn hex_param(params: &Map<String, Value>, name: &str) -> Result<Option<Vec<u8>>, String> {
+    params.get(name)
+        .and_then(|v| v.as_str())
+        .map(|h| hex::decode(h).map_err(|_| format!("{} must be hex", name)))
+        .transpose()
+}
+
+/// Trace `script_sig` then `script_pubkey`. `tx_context` carries
+/// `{raw_tx, input_index, amount?}` for signature and locktime checks.
+pub async fn debug_execute(
+    blockchain: &BlockchainBackend,
+    indexes: &IndexManager,
+    params: &Map<String, Value>,
+) -> Result<Value, String> {
+    let mut script_sig = hex_param(params, "script_sig")?;
+    let mut script_pubkey = hex_param(params, "script_pubkey")?;
+
+    let spending = match params.get("tx_context") {
+        Some(context) => {
+            let raw_tx = context.get("raw_tx").and_then(|v| v.as_str()).ok_or("tx_context needs raw_tx")?;
+            let tx = decode_hex(raw_tx)?;
+            let input_index = context.get("input_index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let input = tx.inputs.get(input_index).ok_or("input_index out of range")?.clone();
+            let prevout = resolve_input(blockchain, indexes, &input).await;
+            let amount = context.get("amount").and_then(|v| v.as_u64())
+                .or(prevout.as_ref().map(|(output, _)| output.value))
+                .ok_or("amount is needed when the spent output cannot be found")?;
+            script_sig.get_or_insert(input.script_sig);
+            if script_pubkey.is_none() {
+                script_pubkey = prevout.map(|(output, _)| output.script_pubkey);
+            }
+            Some((tx, input_index, amount))
+        }
+        None => None,
+    };
+    let script_sig = script_sig.ok_or("Missing script_sig")?;
+    let script_pubkey = script_pubkey.ok_or("Missing script_pubkey")?;
+
+    let context = spending.as_ref().map(|(tx, input_index, amount)| TraceContext { tx, input_index: *input_index, amount: *amount });
+    let trace = script_trace::trace(&script_sig, &script_pubkey, context);
+    Ok(json!({
+        "script_sig": hex::encode(&script_sig),
+        "script_pubkey": hex::encode(&script_pubkey),
+        "trace": trace,
+    }))
+}
@@ -0,0 +1,189 @@
+//! Wallet Transaction Rebroadcast
+//!
+//! A payment the wallet sent can drop out of every mempool, after a node
+//! restart or an eviction, and then never confirms although nothing failed.
+//! The rebroadcaster looks at the wallet's pending transactions, every one
+//! submitted through the backend, every `interval`. One not relayed for `min_age` is put back into our mempool if
+//! it is missing and announced to peers again. It stops once the
+//! transaction confirms or conflicts, that is once another transaction
+//! spends one of its inputs. A fee bump or cancellation replaces the
+//! original in the wallet, so only the replacement is rebroadcast, and a
+//! transaction our mempool has already seen replaced is left alone.
+
+use crate::blockchain::BlockchainBackend;
+use blockchain_core::mempool::Mempool;
+use blockchain_core::transaction::Transaction;
+use blockchain_core::tx_builder::{PendingStatus, TransactionManager};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct RebroadcastConfig {
+    /// How often pending wallet transactions are checked
+    pub interval: Duration,
+    /// Rebroadcast a transaction not relayed for this long
+    pub min_age: Duration,
+}
+
+impl Default for RebroadcastConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            min_age: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// What one pass did
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RebroadcastReport {
+    pub rebroadcast: Vec<String>,
+    /// Put back into the mempool first
+    pub resubmitted: Vec<String>,
+    pub confirmed: Vec<String>,
+    pub conflicted: Vec<String>,
+}
+
+/// Keeps unconfirmed wallet transactions in circulation
+pub struct WalletRebroadcaster {
+    blockchain: Arc<BlockchainBackend>,
+    config: RebroadcastConfig,
+}
+
+impl WalletRebroadcaster {
+    pub fn new(blockchain: Arc<BlockchainBackend>, config: RebroadcastConfig) -> Self {
+        Self { blockchain, config }
+    }
+
+    /// Check pending wallet transactions every `interval`
+    pub fn start(self: &Arc<Self>) {
+        let rebroadcaster = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(rebroadcaster.config.interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                rebroadcaster.run_once().await;
+            }
+        });
+    }
+
+    /// Rebroadcast what is due and stop tracking what has settled
+    pub async fn run_once(&self) -> RebroadcastReport {
+        let mut report = RebroadcastReport::default();
+        let min_age = chrono::Duration::from_std(self.config.min_age).unwrap_or_else(|_| chrono::Duration::zero());
+        let due = self.blockchain.tx_manager.read().await.due_for_rebroadcast(min_age, Utc::now());
+        if due.is_empty() {
+            return report;
+        }
+        let chain = self.blockchain.consensus.get_utxo_set().await;
+
+        for pending in due {
+            let hash = match pending.transaction.get_hash() {
+                Ok(hash) => hash,
+                Err(e) => {
+                    warn!("Skipping pending transaction without a hash: {}", e);
+                    continue;
+                }
+            };
+            let tx_hash = hex::encode(hash);
+
+            let status = match TransactionManager::pending_status(&pending, &chain) {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!("Failed to check pending transaction {}: {}", tx_hash, e);
+                    continue;
+                }
+            };
+            let status = {
+                let mempool = self.blockchain.mempool.read().await;
+                if status == PendingStatus::Unconfirmed && !mempool.contains_transaction(&hash) && replaced_in(&mempool, &pending.transaction) {
+                    PendingStatus::Conflicted
+                } else {
+                    status
+                }
+            };
+
+            match status {
+                PendingStatus::Confirmed => {
+                    self.blockchain.tx_manager.write().await.remove_pending(&tx_hash);
+                    report.confirmed.push(tx_hash);
+                }
+                PendingStatus::Conflicted => {
+                    self.blockchain.tx_manager.write().await.remove_pending(&tx_hash);
+                    warn!("⚔️  Wallet transaction {} conflicted, no longer rebroadcast", tx_hash);
+                    report.conflicted.push(tx_hash);
+                }
+                PendingStatus::Unconfirmed => {
+                    if !self.blockchain.mempool.read().await.contains_transaction(&hash) {
+                        if let Err(e) = self.blockchain.submit_transaction(pending.transaction.clone()).await {
+                            warn!("Failed to return wallet transaction {} to the mempool: {}", tx_hash, e);
+                            continue;
+                        }
+                        report.resubmitted.push(tx_hash.clone());
+                    }
+                    // Peers whose fee filter it misses would drop it anyway
+                    let fee_rate = self.blockchain.mempool.read().await.get_fee_rate(&hash);
+                    let relayed = match fee_rate {
+                        Some(fee_rate) => self.blockchain.network.relay_transaction(&pending.transaction, fee_rate).await,
+                        None => self.blockchain.network.broadcast_transaction(&pending.transaction).await,
+                    };
+                    if let Err(e) = relayed {
+                        warn!("Failed to rebroadcast wallet transaction {}: {}", tx_hash, e);
+                        continue;
+                    }
+                    if let Err(e) = self.blockchain.tx_manager.write().await.mark_broadcast(&tx_hash) {
+                        warn!("Failed to record rebroadcast of {}: {}", tx_hash, e);
+                    }
+                    report.rebroadcast.push(tx_hash);
+                }
+            }
+        }
+
+        if !report.rebroadcast.is_empty() {
+            info!("📣 Rebroadcast {} unconfirmed wallet transaction(s), {} returned to the mempool",
+                report.rebroadcast.len(), report.resubmitted.len());
+        }
+        report
+    }
+}
+
+/// Whether the mempool holds another transaction spending one of `tx`'s
+/// inputs, a replacement it accepted
+fn replaced_in(mempool: &Mempool, tx: &Transaction) -> bool {
+    let inputs: HashSet<_> = tx.inputs.iter().map(|input| (input.prev_tx_hash, input.prev_output_index)).collect();
+    mempool.get_transactions().iter().any(|other| {
+        other.inputs.iter().any(|input| inputs.contains(&(input.prev_tx_hash, input.prev_output_index)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::tests::{funded_backend, payment};
+
+    #[tokio::test]
+    async fn test_dropped_submission_is_resubmitted_and_rebroadcast() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let (backend, owner) = funded_backend(data_dir.path()).await;
+        let backend = Arc::new(backend);
+
+        let hash = backend.submit_transaction(payment(&backend, &owner, 9_990_000).await).await.unwrap();
+        let tx_hash = hex::encode(hash);
+
+        // Dropped from the mempool, say by a restart
+        backend.evict_transactions(&[hash]).await.unwrap();
+        assert!(!backend.mempool.read().await.contains_transaction(&hash));
+
+        let rebroadcaster = WalletRebroadcaster::new(backend.clone(), RebroadcastConfig { min_age: Duration::ZERO, ..RebroadcastConfig::default() });
+        let report = rebroadcaster.run_once().await;
+        assert_eq!(report.rebroadcast, vec![tx_hash.clone()]);
+        assert_eq!(report.resubmitted, vec![tx_hash.clone()]);
+        assert!(backend.mempool.read().await.contains_transaction(&hash));
+        assert_eq!(backend.tx_manager.read().await.get_pending_transaction(&tx_hash).unwrap().broadcast_count, 1);
+    }
+}
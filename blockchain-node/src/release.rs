@@ -0,0 +1,221 @@
+//! Release Metadata and Startup Self-Checks
+//!
+//! `blockchain-node init` pins the chain a data directory belongs to in a
+//! manifest: the genesis timestamp (so every start rebuilds the same genesis
+//! block), the resulting genesis hash, the network magic, the dev fund share
+//! of the block reward and a hash of the consensus parameters. Every start, and `blockchain-node self-check`, then
+//! compares the running binary against that manifest and tests the native
+//! crypto and hashing code it was linked with, so a node built or configured
+//! for a different deployment refuses to start instead of forking off.
+
+use anyhow::{Context, Result};
+use blockchain_core::consensus::{CoinbaseSplit, ConsensusParams};
+use blockchain_core::genesis::{GenesisConfig, GenesisCreator};
+use blockchain_core::{crypto, hashing};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Manifest file name inside the data directory
+pub const MANIFEST_FILE: &str = "chain.json";
+
+/// Magic bytes prefixed to every P2P message on this network
+pub const NETWORK_MAGIC: u32 = blockchain_network::protocol::MAINNET_MAGIC;
+
+/// Source commit, embedded by build.rs
+pub const GIT_COMMIT: &str = env!("EDUNET_GIT_COMMIT");
+
+/// Compiler that built the binary, embedded by build.rs
+pub const RUSTC_VERSION: &str = env!("EDUNET_RUSTC_VERSION");
+
+/// Chain a data directory was initialized for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainManifest {
+    pub network_magic: u32,
+    pub genesis_timestamp: i64,
+    pub genesis_hash: String,
+    /// Share of every block reward owed to a fund, fixed when the chain
+    /// was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dev_fund: Option<CoinbaseSplit>,
+    pub params_hash: String,
+    /// Node version that wrote the manifest
+    pub node_version: String,
+}
+
+impl ChainManifest {
+    /// Manifest for a new chain directory
+    pub fn new(params: &ConsensusParams, genesis_timestamp: i64) -> Result<Self> {
+        Ok(Self {
+            network_magic: NETWORK_MAGIC,
+            genesis_timestamp,
+            genesis_hash: hex::encode(genesis_hash(genesis_timestamp)?),
+            dev_fund: params.dev_fund.clone(),
+            params_hash: hex::encode(params.params_hash()),
+            node_version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
+    /// Manifest in `data_dir`, if it was initialized
+    pub fn load(data_dir: &Path) -> Result<Option<Self>> {
+        let path = data_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let manifest = serde_json::from_str(&contents)
+            .with_context(|| format!("Malformed chain manifest {}", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(MANIFEST_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Genesis configuration that reproduces the pinned genesis block
+    pub fn genesis_config(&self) -> GenesisConfig {
+        GenesisConfig {
+            genesis_timestamp: self.genesis_timestamp,
+            ..GenesisConfig::default()
+        }
+    }
+
+    /// `params` with the chain's own rules: the pinned dev fund
+    pub fn consensus_params(&self, params: ConsensusParams) -> ConsensusParams {
+        ConsensusParams {
+            dev_fund: self.dev_fund.clone(),
+            ..params
+        }
+    }
+}
+
+/// Hash of the genesis block built at `timestamp`
+pub fn genesis_hash(timestamp: i64) -> Result<[u8; 32]> {
+    let config = GenesisConfig {
+        genesis_timestamp: timestamp,
+        ..GenesisConfig::default()
+    };
+    let block = GenesisCreator::new(Some(config)).create_genesis_block()
+        .map_err(|e| anyhow::anyhow!("Failed to create genesis block: {}", e))?;
+    Ok(block.get_hash())
+}
+
+/// Outcome of one self-check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Worth an operator's attention but safe to run
+    Warn,
+    /// The node must not start
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name, status, detail: detail.into() }
+    }
+}
+
+/// Run every self-check against the data directory and consensus parameters
+pub fn self_check(data_dir: &Path, params: &ConsensusParams) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    match ChainManifest::load(data_dir) {
+        Ok(Some(manifest)) => {
+            checks.push(if manifest.network_magic != NETWORK_MAGIC {
+                Check::new("network", CheckStatus::Fail, format!(
+                    "data directory is for network {:#010x}, this binary joins {:#010x}",
+                    manifest.network_magic, NETWORK_MAGIC,
+                ))
+            } else {
+                Check::new("network", CheckStatus::Pass, format!("{:#010x}", NETWORK_MAGIC))
+            });
+
+            checks.push(match genesis_hash(manifest.genesis_timestamp) {
+                Ok(hash) if hex::encode(hash) == manifest.genesis_hash => {
+                    Check::new("genesis", CheckStatus::Pass, manifest.genesis_hash.clone())
+                }
+                Ok(hash) => Check::new("genesis", CheckStatus::Fail, format!(
+                    "this binary builds genesis {} but the data directory expects {}",
+                    hex::encode(hash), manifest.genesis_hash,
+                )),
+                Err(e) => Check::new("genesis", CheckStatus::Fail, e.to_string()),
+            });
+
+            let params_hash = hex::encode(params.params_hash());
+            checks.push(if params_hash == manifest.params_hash {
+                Check::new("consensus params", CheckStatus::Pass, params_hash)
+            } else {
+                Check::new("consensus params", CheckStatus::Fail, format!(
+                    "running with {} but the data directory was initialized with {}; check --deployment flags",
+                    params_hash, manifest.params_hash,
+                ))
+            });
+        }
+        Ok(None) => checks.push(Check::new(
+            "genesis",
+            CheckStatus::Warn,
+            format!("no {} in {}; run `blockchain-node init` to pin the genesis block", MANIFEST_FILE, data_dir.display()),
+        )),
+        Err(e) => checks.push(Check::new("genesis", CheckStatus::Fail, e.to_string())),
+    }
+
+    checks.push(match crypto::self_test() {
+        Ok(()) => Check::new("secp256k1", CheckStatus::Pass, "native library agrees with reference implementation"),
+        Err(e) => Check::new("secp256k1", CheckStatus::Fail, e.to_string()),
+    });
+
+    checks.push(match hashing::self_test() {
+        Ok(()) => Check::new("sha-256", CheckStatus::Pass, format!("{} backend", hashing::backend().name())),
+        Err(e) => Check::new("sha-256", CheckStatus::Fail, e.to_string()),
+    });
+
+    checks
+}
+
+/// `version --verbose` report
+pub fn version_report(params: &ConsensusParams) -> String {
+    let features = if blockchain_core::FEATURES.is_empty() {
+        "none".to_string()
+    } else {
+        blockchain_core::FEATURES.join(", ")
+    };
+    [
+        format!("blockchain-node {}", env!("CARGO_PKG_VERSION")),
+        format!("commit: {}", GIT_COMMIT),
+        format!("rustc: {}", RUSTC_VERSION),
+        format!("network magic: {:#010x}", NETWORK_MAGIC),
+        format!("chain params: {}", hex::encode(params.params_hash())),
+        format!("features: {}", features),
+        format!("sha-256 backend: {}", hashing::backend().name()),
+    ].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_pins_dev_fund() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let params = ConsensusParams::default().with_dev_fund("edu1qfund".to_string(), 10).unwrap();
+        ChainManifest::new(&params, 1_700_000_000).unwrap().save(data_dir.path()).unwrap();
+
+        // Started without any dev fund setting, the node still runs the chain's
+        let manifest = ChainManifest::load(data_dir.path()).unwrap().unwrap();
+        let running = manifest.consensus_params(ConsensusParams::default());
+        assert_eq!(running.dev_fund, params.dev_fund);
+        let checks = self_check(data_dir.path(), &running);
+        assert!(checks.iter().any(|check| check.name == "consensus params" && check.status == CheckStatus::Pass));
+    }
+}
@@ -0,0 +1,1983 @@
+//! JSON-RPC API of a full node
+//!
+//! Every method a full node serves, wired to its blockchain backend,
+//! treasury, indexes and admin console. Header-only nodes serve a smaller
+//! set from the binary instead.
+
+use crate::admin::AdminConsole;
+use crate::alerts::{self, ChainAlerter};
+use crate::blockchain::BlockchainBackend;
+use crate::disk::{self, Compactor};
+use crate::indexer::IndexManager;
+use crate::rawtx;
+use crate::treasury::TreasuryManager;
+use crate::vouchers::{self, VoucherManager};
+use blockchain_rpc::server::{RpcServer, RpcServerConfig};
+use blockchain_rpc::FeatureFlags;
+use blockchain_core::mempool::GasPrice;
+use blockchain_core::contracts::ExecutionResult;
+use blockchain_core::abi::Abi;
+use blockchain_core::reserves::{InclusionProof, ReserveAttestation};
+use blockchain_core::audit_log::AuditRecord;
+use blockchain_core::naming::NameCommitment;
+use blockchain_core::address::AddressCodec;
+use blockchain_core::amount::Amount;
+use blockchain_core::canonical::{HexBytes, HexHash, Sats};
+use blockchain_core::offline_signing::{SignatureSet, UnsignedTransaction, DEFAULT_QR_CHUNK_LEN};
+use anyhow::Result;
+use tracing::{info, error};
+use jsonrpc_core::{IoHandler, Params, Value};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use std::path::PathBuf;
+
+/// JSON form of a contract event, with its fields when the ABI is known
+fn event_json(e: &blockchain_core::event_indexer::IndexedEvent, decoded: Option<Value>) -> Value {
+    json!({
+        "address": hex::encode(e.log.address.as_bytes()),
+        "topics": e.log.topics,
+        "data": hex::encode(&e.log.data),
+        "blockHeight": e.block_height,
+        "transactionHash": e.tx_hash,
+        "logIndex": e.log_index,
+        "implementation": e.implementation.map(|a| hex::encode(a.as_bytes())),
+        "decoded": decoded,
+    })
+}
+
+/// 20-byte contract address from a hex RPC parameter
+fn parse_contract_address(parsed: &serde_json::Map<String, Value>, name: &str) -> std::result::Result<blockchain_core::contracts::EthAddress, jsonrpc_core::Error> {
+    let address_hex = parsed.get(name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| jsonrpc_core::Error::invalid_params(format!("Missing {}", name)))?;
+    let bytes = hex::decode(address_hex.trim_start_matches("0x"))
+        .map_err(|_| jsonrpc_core::Error::invalid_params(format!("Invalid {} hex", name)))?;
+    let address: [u8; 20] = bytes.try_into()
+        .map_err(|_| jsonrpc_core::Error::invalid_params(format!("{} must be 20 bytes", name)))?;
+    Ok(blockchain_core::contracts::EthAddress::new(address))
+}
+
+/// EIP-1559 style `maxFeePerGas` and `maxPriorityFeePerGas` of a deploy or
+/// call; the priority fee defaults to the whole max fee
+fn parse_gas_bid(parsed: &serde_json::Map<String, Value>, gas_limit: u64) -> Result<Option<GasPrice>, jsonrpc_core::Error> {
+    let field = |name: &str| parsed.get(name)
+        .map(|v| v.as_u64().ok_or_else(|| jsonrpc_core::Error::invalid_params(format!("{} must be an integer", name))))
+        .transpose();
+    let max_priority_fee_per_gas = field("maxPriorityFeePerGas")?;
+    match field("maxFeePerGas")? {
+        Some(max_fee_per_gas) => Ok(Some(GasPrice {
+            gas_limit,
+            max_fee_per_gas,
+            max_priority_fee_per_gas: max_priority_fee_per_gas.unwrap_or(max_fee_per_gas),
+        })),
+        None if max_priority_fee_per_gas.is_some() => {
+            Err(jsonrpc_core::Error::invalid_params("maxPriorityFeePerGas needs maxFeePerGas"))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Optional `nonce` of a deploy or call, the caller's next one if absent
+fn parse_nonce(parsed: &serde_json::Map<String, Value>) -> Result<Option<u64>, jsonrpc_core::Error> {
+    parsed.get("nonce")
+        .map(|v| v.as_u64().ok_or_else(|| jsonrpc_core::Error::invalid_params("nonce must be an integer")))
+        .transpose()
+}
+
+/// Execution result with the nonce it used, the price paid per gas and
+/// the gas fee
+fn execution_json(result: ExecutionResult, gas_price: u64, nonce: u64) -> Value {
+    let gas_fee = result.gas_used.saturating_mul(gas_price);
+    let mut json = serde_json::to_value(result).unwrap();
+    json["nonce"] = json!(nonce);
+    json["effectiveGasPrice"] = json!(gas_price);
+    json["gasFee"] = json!(gas_fee);
+    json
+}
+
+/// Register a method that awaits on the node's runtime, so a slow call
+/// holds no server thread while it waits
+pub fn add_async_method<F, Fut>(handler: &mut IoHandler, name: &str, method: F)
+where
+    F: Fn(Params) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = jsonrpc_core::Result<Value>> + Send + 'static,
+{
+    handler.add_method(name, method);
+}
+
+/// Voucher book refusal as a server error carrying its reason
+fn voucher_error(e: anyhow::Error) -> jsonrpc_core::Error {
+    jsonrpc_core::Error {
+        code: jsonrpc_core::ErrorCode::ServerError(-32000),
+        message: e.to_string(),
+        data: None,
+    }
+}
+
+/// The batch named by the `batch_id` parameter
+async fn voucher_batch_param(
+    vm: &VoucherManager,
+    params: &serde_json::Map<String, Value>,
+) -> jsonrpc_core::Result<vouchers::VoucherBatch> {
+    let batch_id = params.get("batch_id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing batch_id"))?;
+    vm.batch(batch_id).await
+        .ok_or_else(|| jsonrpc_core::Error::invalid_params(format!("Voucher batch {} not found", batch_id)))
+}
+
+/// Create RPC server wired to blockchain backend and treasury
+pub fn create_blockchain_rpc_server(
+    config: RpcServerConfig, 
+    blockchain: Arc<BlockchainBackend>,
+    treasury: Arc<TreasuryManager>,
+    vouchers: Arc<VoucherManager>,
+    admin: Arc<AdminConsole>,
+    features: Arc<FeatureFlags>,
+    indexes: Arc<IndexManager>,
+    compactor: Arc<Compactor>,
+    alerter: Arc<ChainAlerter>,
+    data_dir: PathBuf,
+) -> RpcServer {
+    let mut handler = IoHandler::new();
+    
+    // Get block height
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "blockchain_getBlockHeight", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let height = bc.get_height().await;
+                Ok(Value::Number(height.into()))
+            }
+        });
+    }
+    
+    // Get block by height
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "blockchain_getBlock", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<u64> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing block height"));
+                }
+                
+                let block = bc.get_block_by_height(parsed[0]).await;
+                
+                match block {
+                    Some(b) => Ok(json!({
+                        "height": b.header.height,
+                        "hash": HexHash(b.header.calculate_hash()),
+                        "prev_hash": HexHash(b.header.prev_block_hash),
+                        "timestamp": b.header.timestamp,
+                        "transactions_count": b.transactions.len()
+                    })),
+                    None => Ok(json!({"error": "Block not found"}))
+                }
+            }
+        });
+    }
+    
+    // Get balance
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "wallet_getBalance", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<String> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing address"));
+                }
+                
+                let result = bc.get_balance(&parsed[0]).await;
+                
+                match result {
+                    Ok(balance) => Ok(json!(Sats(balance))),
+                    Err(e) => Ok(json!({"error": format!("Failed to get balance: {}", e)}))
+                }
+            }
+        });
+    }
+    
+    // List wallets
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "wallet_list", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let wallets = bc.list_wallets().await;
+                
+                let wallet_list: Vec<_> = wallets.into_iter().map(|(name, address, balance)| {
+                    json!({
+                        "name": name,
+                        "address": address,
+                        "balance": Sats(balance)
+                    })
+                }).collect();
+                Ok(Value::Array(wallet_list))
+            }
+        });
+    }
+    
+    // Abandon an unconfirmed, never-relayed wallet transaction
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "wallet_abandonTransaction", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<String> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing txid"));
+                }
+
+                bc.abandon_transaction(&parsed[0]).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: format!("Failed to abandon transaction: {}", e),
+                    data: None,
+                })?;
+
+                Ok(json!({"txid": parsed[0], "status": "abandoned"}))
+            }
+        });
+    }
+
+    // Cancel a relayed transaction by double-spending it to self with a higher fee
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "wallet_cancelTransaction", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+
+                let txid = parsed.get("txid")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing txid"))?;
+                let wallet_address = parsed.get("wallet_address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing wallet_address"))?;
+                let fee_bump = parsed.get("fee_bump")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(1000);
+
+                let replacement = bc.cancel_transaction(txid, wallet_address, fee_bump).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: format!("Failed to cancel transaction: {}", e),
+                    data: None,
+                })?;
+
+                Ok(json!({
+                    "txid": txid,
+                    "replaced_by": HexHash(replacement),
+                    "status": "cancelling"
+                }))
+            }
+        });
+    }
+
+    // Debug: List all addresses with UTXOs
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "debug_listAddresses", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let utxo_set = bc.utxo_set.read().await;
+                let addresses = utxo_set.get_all_addresses().into_iter().map(|addr| {
+                    let balance = utxo_set.get_balance(&addr);
+                    json!({
+                        "address": addr,
+                        "balance": Sats(balance)
+                    })
+                }).collect::<Vec<_>>();
+                Ok(Value::Array(addresses))
+            }
+        });
+    }
+    
+    // Get status
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "blockchain_getStatus", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let status = bc.get_status().await;
+                Ok(status)
+            }
+        });
+    }
+    
+    // Experimental features and whether they are on
+    {
+        let features = features.clone();
+        handler.add_sync_method("node_getFeatures", move |_params: Params| {
+            Ok(json!({ "features": features.list() }))
+        });
+    }
+    
+    // Background index jobs and their progress
+    {
+        let indexes = indexes.clone();
+        handler.add_sync_method("index_getStatus", move |_params: Params| {
+            Ok(json!({ "indexes": indexes.status() }))
+        });
+    }
+    
+    // Admin: Pause or resume a background index job
+    for action in ["index_pause", "index_resume"] {
+        let indexes = indexes.clone();
+        let admin = admin.clone();
+        handler.add_sync_method(action, move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            let actor = admin.authorize(action, &parsed)?;
+            let name = parsed.get("name")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing index name"))?;
+            
+            let result = if action == "index_pause" { indexes.pause(name) } else { indexes.resume(name) };
+            admin.record(&actor, action, &parsed, result.as_ref().err().map(String::as_str));
+            result.map(|status| json!(status)).map_err(jsonrpc_core::Error::invalid_params)
+        });
+    }
+    
+    // Block containing a transaction (requires txindex)
+    {
+        let indexes = indexes.clone();
+        handler.add_sync_method("index_getTransaction", move |params: Params| {
+            let parsed: Vec<String> = params.parse()?;
+            if parsed.is_empty() {
+                return Err(jsonrpc_core::Error::invalid_params("Missing txid"));
+            }
+            let index = indexes.tx_index()
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("txindex is not enabled (start the node with --index txindex)"))?;
+            let txid: [u8; 32] = hex::decode(&parsed[0]).ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Invalid txid"))?;
+            
+            match index.get(&txid) {
+                Some(location) => Ok(json!({ "txid": parsed[0], "location": location })),
+                None => Ok(json!({"error": "Transaction not found in index"}))
+            }
+        });
+    }
+    
+    // Transactions paying or spending from an address (requires addressindex)
+    {
+        let indexes = indexes.clone();
+        handler.add_sync_method("index_getAddressHistory", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            let address = parsed.get("address")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing address"))?;
+            let limit = parsed.get("limit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(100) as usize;
+            let index = indexes.address_index()
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("addressindex is not enabled (start the node with --index addressindex)"))?;
+            
+            Ok(json!({ "address": address, "transactions": index.history(address, limit) }))
+        });
+    }
+    
+    // Decode a raw transaction, resolving its inputs where possible
+    {
+        let bc = blockchain.clone();
+        let indexes = indexes.clone();
+        add_async_method(&mut handler, "tx_decodeRaw", move |params: Params| {
+            let bc = bc.clone();
+            let indexes = indexes.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let raw_tx = parsed.get("hex").and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing hex"))?;
+                let tx = rawtx::decode_hex(raw_tx).map_err(jsonrpc_core::Error::invalid_params)?;
+                
+                let decoded = rawtx::decode(&bc, &indexes, &tx).await;
+                Ok(decoded)
+            }
+        });
+    }
+    
+    // Run a script opcode by opcode, returning the stack after each step
+    {
+        let bc = blockchain.clone();
+        let indexes = indexes.clone();
+        add_async_method(&mut handler, "script_debugExecute", move |params: Params| {
+            let bc = bc.clone();
+            let indexes = indexes.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                rawtx::debug_execute(&bc, &indexes, &parsed).await.map_err(jsonrpc_core::Error::invalid_params)
+            }
+        });
+    }
+    
+    // Build an unsigned raw transaction from outpoints and outputs
+    {
+        handler.add_sync_method("tx_createRaw", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            let inputs = parsed.get("inputs").and_then(|v| v.as_array())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing inputs"))?;
+            let outputs = parsed.get("outputs").and_then(|v| v.as_array())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing outputs"))?;
+            let locktime = parsed.get("locktime").and_then(|v| v.as_u64()).unwrap_or(0);
+            let locktime = u32::try_from(locktime)
+                .map_err(|_| jsonrpc_core::Error::invalid_params("locktime must be a 32-bit number"))?;
+            
+            let tx = rawtx::create(inputs, outputs, locktime).map_err(jsonrpc_core::Error::invalid_params)?;
+            let hex = rawtx::encode_hex(&tx).map_err(|e| jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                message: format!("Failed to serialize transaction: {}", e),
+                data: None,
+            })?;
+            Ok(json!({ "txid": HexHash(tx.calculate_hash()), "hex": hex }))
+        });
+    }
+    
+    // Export an unsigned transaction as QR chunks for an air-gapped signer
+    {
+        let bc = blockchain.clone();
+        let indexes = indexes.clone();
+        add_async_method(&mut handler, "tx_exportOffline", move |params: Params| {
+            let bc = bc.clone();
+            let indexes = indexes.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let raw_tx = parsed.get("hex").and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing hex"))?;
+                let account_index = parsed.get("account_index").and_then(|v| v.as_u64()).unwrap_or(0);
+                let account_index = u32::try_from(account_index)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("account_index must be a 32-bit number"))?;
+                let chunk_len = parsed.get("chunk_len").and_then(|v| v.as_u64())
+                    .map_or(DEFAULT_QR_CHUNK_LEN, |len| len as usize);
+                let tx = rawtx::decode_hex(raw_tx).map_err(jsonrpc_core::Error::invalid_params)?;
+
+                let unsigned = rawtx::export_offline(&bc, &indexes, tx, account_index).await
+                    .map_err(jsonrpc_core::Error::invalid_params)?;
+                let chunks = unsigned.to_qr_chunks(chunk_len).map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: format!("Failed to encode transaction: {}", e),
+                    data: None,
+                })?;
+                Ok(json!({
+                    "unsigned_id": HexHash(unsigned.id()),
+                    "fee": Sats(unsigned.fee()),
+                    "chunks": chunks,
+                }))
+            }
+        });
+    }
+    
+    // Put signatures made offline into the transaction exported for them
+    {
+        handler.add_sync_method("tx_importSignatures", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            let chunks = |name: &str| -> jsonrpc_core::Result<Vec<String>> {
+                parsed.get(name).and_then(|v| v.as_array())
+                    .map(|chunks| chunks.iter().filter_map(|c| c.as_str().map(str::to_string)).collect())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params(format!("Missing {}", name)))
+            };
+            let unsigned = UnsignedTransaction::from_qr_chunks(&chunks("unsigned")?)
+                .map_err(|e| jsonrpc_core::Error::invalid_params(format!("unsigned: {}", e)))?;
+            let signatures = SignatureSet::from_qr_chunks(&chunks("signatures")?)
+                .map_err(|e| jsonrpc_core::Error::invalid_params(format!("signatures: {}", e)))?;
+
+            let tx = unsigned.apply(&signatures).map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            let hex = rawtx::encode_hex(&tx).map_err(|e| jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                message: format!("Failed to serialize transaction: {}", e),
+                data: None,
+            })?;
+            Ok(json!({ "txid": HexHash(tx.calculate_hash()), "hex": hex }))
+        });
+    }
+    
+    // Disk usage by blocks, undo data, UTXO set and indexes
+    {
+        let bc = blockchain.clone();
+        let indexes = indexes.clone();
+        let compactor = compactor.clone();
+        add_async_method(&mut handler, "node_getDiskUsage", move |_params: Params| {
+            let bc = bc.clone();
+            let indexes = indexes.clone();
+            let compactor = compactor.clone();
+            let data_dir = data_dir.clone();
+            async move {
+                let usage = disk::disk_usage(&bc, &indexes, &compactor, &data_dir).await;
+                Ok(usage)
+            }
+        });
+    }
+    
+    // Known branch tips, optionally only those within `window` blocks of the tip
+    {
+        let bc = blockchain.clone();
+        let alerter = alerter.clone();
+        add_async_method(&mut handler, "blockchain_getChainTips", move |params: Params| {
+            let bc = bc.clone();
+            let alerter = alerter.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse().unwrap_or_default();
+                let window = parsed.get("window")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(u64::MAX);
+                let tips = alerts::chain_tips(&bc, &alerter, window).await;
+                Ok(json!({ "tips": tips }))
+            }
+        });
+    }
+    
+    // Competing chains, reorgs, stale tips and invalid chains, newest first
+    {
+        let alerter = alerter.clone();
+        handler.add_sync_method("blockchain_getChainAlerts", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse().unwrap_or_default();
+            let limit = parsed.get("limit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(20) as usize;
+            Ok(json!({ "alerts": alerter.recent(limit) }))
+        });
+    }
+    
+    // Clock offset from peers and the adjusted time blocks are checked against
+    {
+        let bc = blockchain.clone();
+        handler.add_sync_method("node_getNetworkTime", move |_params: Params| {
+            Ok(json!(bc.consensus.network_time().status()))
+        });
+    }
+    
+    // Version bits deployment states
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "blockchain_getDeploymentInfo", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let (height, tracker) = (bc.get_height().await, bc.consensus.get_deployment_info().await);
+                
+                let window = tracker.window();
+                let elapsed = (height + 1) % window;
+                let deployments: Vec<Value> = tracker.deployments().iter().map(|status| {
+                    let deployment = &status.deployment;
+                    json!({
+                        "name": deployment.name,
+                        "bit": deployment.bit,
+                        "state": status.state,
+                        "since_height": status.since_height,
+                        "active": tracker.is_active_at(&deployment.name, height + 1),
+                        "start_height": deployment.start_height,
+                        "timeout_height": deployment.timeout_height,
+                        "min_activation_height": deployment.min_activation_height,
+                        "signalling": {
+                            "count": status.window_signals,
+                            "elapsed": elapsed,
+                            "window": window,
+                            "threshold": tracker.threshold(),
+                            "possible": status.window_signals + (window - elapsed) >= tracker.threshold(),
+                        },
+                    })
+                }).collect();
+                
+                Ok(json!({
+                    "height": height,
+                    "next_block_version": format!("{:#010x}", tracker.block_version()),
+                    "deployments": deployments,
+                }))
+            }
+        });
+    }
+    
+    // Supply audit state and divergence report
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "blockchain_getSupplyAudit", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let audit = bc.consensus.get_supply_audit().await;
+                
+                match audit {
+                    Some(auditor) => Ok(json!({
+                        "enabled": true,
+                        "expected_supply": auditor.expected_supply(),
+                        "audit": auditor,
+                    })),
+                    None => Ok(json!({ "enabled": false })),
+                }
+            }
+        });
+    }
+    
+    // Spent-outpoint filter hit and false-positive rates
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "blockchain_getUtxoFilterInfo", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let stats = bc.consensus.spent_filter_stats().await;
+                Ok(json!(stats))
+            }
+        });
+    }
+    
+    // UTXO set count, value, size and hash, for comparing nodes
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "utxo_getSetInfo", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let info = bc.consensus.utxo_set_info().await;
+                let best_block_hash = bc.consensus.get_chain_state().await.best_block_hash;
+                Ok(json!({
+                    "height": info.height,
+                    "best_block_hash": HexHash(best_block_hash),
+                    "utxo_count": info.utxo_count,
+                    "total_amount": Sats(info.total_amount),
+                    "total_amount_edu": Amount::from_sat(info.total_amount).to_edu_string(),
+                    "serialized_size": info.serialized_size,
+                    "set_hash": info.set_hash,
+                }))
+            }
+        });
+    }
+    
+    // One output, if it exists and is unspent; optionally counting the mempool
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "utxo_getTxOut", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<Value> = params.parse()?;
+                let txid_hex = parsed.first()
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing txid"))?;
+                let txid: [u8; 32] = hex::decode(txid_hex).ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Invalid txid"))?;
+                let vout = parsed.get(1)
+                    .and_then(|v| v.as_u64())
+                    .and_then(|v| u32::try_from(v).ok())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing vout"))?;
+                let include_mempool = parsed.get(2).and_then(|v| v.as_bool()).unwrap_or(true);
+
+                // Spent by, or created by, an unconfirmed transaction
+                let (spent_in_mempool, mempool_output) = if include_mempool {
+                    let mempool = bc.mempool.read().await;
+                    (
+                        mempool.spender_of(&txid, vout).is_some(),
+                        mempool.get_transaction(&txid).and_then(|tx| tx.outputs.get(vout as usize).cloned()),
+                    )
+                } else {
+                    (false, None)
+                };
+                if spent_in_mempool {
+                    return Ok(Value::Null);
+                }
+
+                let chain_state = bc.consensus.get_chain_state().await;
+                let outpoint = format!("{}:{}", txid_hex.to_lowercase(), vout);
+                let (output, confirmations, coinbase) = match bc.consensus.get_utxo(&outpoint).await {
+                    Some(utxo) => {
+                        let confirmations = chain_state.height.saturating_sub(utxo.block_height as u64) + 1;
+                        (utxo.output, confirmations, utxo.is_coinbase)
+                    }
+                    None => match mempool_output {
+                        Some(output) => (output, 0, false),
+                        None => return Ok(Value::Null),
+                    },
+                };
+
+                Ok(json!({
+                    "bestblock": HexHash(chain_state.best_block_hash),
+                    "confirmations": confirmations,
+                    "value": Sats(output.value),
+                    "value_edu": Amount::from_sat(output.value).to_edu_string(),
+                    "scriptPubKey": {
+                        "hex": HexBytes(output.script_pubkey.clone()),
+                        "address": output.get_address(),
+                    },
+                    "coinbase": coinbase,
+                }))
+            }
+        });
+    }
+    
+    // Treasury: Get current price
+    {
+        let tr = treasury.clone();
+        add_async_method(&mut handler, "treasury_getPrice", move |_params: Params| {
+            let tr = tr.clone();
+            async move {
+                let price = tr.get_price().await;
+                Ok(json!({
+                    "price_cents": price,
+                    "price_usd": format!("${:.2}", price as f64 / 100.0)
+                }))
+            }
+        });
+    }
+    
+    // Treasury: Sell coins (after receiving cash payment)
+    {
+        let tr = treasury.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "treasury_sellCoins", move |params: Params| {
+            let tr = tr.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let buyer_address = parsed.get("buyer_address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing buyer_address"))?;
+                AddressCodec::validate(buyer_address)
+                    .map_err(|e| jsonrpc_core::Error::invalid_params(format!("Invalid buyer_address: {}", e)))?;
+                let amount = parsed.get("amount")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing amount"))?;
+                let payment_method = parsed.get("payment_method")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("cash");
+                let payment_proof = parsed.get("payment_proof")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("no receipt");
+                
+                let sale = tr.sell_coins(
+                    buyer_address.to_string(),
+                    amount,
+                    payment_method.to_string(),
+                    payment_proof.to_string(),
+                ).await;
+                
+                let actor = parsed.get("actor").and_then(|v| v.as_str()).unwrap_or("anonymous");
+                let mut details = parsed.clone();
+                details.remove("actor");
+                let record = AuditRecord::new(actor, "treasury_sellCoins", Value::Object(details));
+                match sale {
+                    Ok(s) => {
+                        admin.append(record.with_txid(s.tx_hash.map(hex::encode)));
+                        Ok(serde_json::to_value(s).unwrap())
+                    }
+                    Err(e) => {
+                        error!("Treasury sale failed: {}", e);
+                        admin.append(record.with_error(e.to_string()));
+                        Err(jsonrpc_core::Error::internal_error())
+                    }
+                }
+            }
+        });
+    }
+    
+    // Treasury: Get statistics
+    {
+        let tr = treasury.clone();
+        add_async_method(&mut handler, "treasury_getStats", move |_params: Params| {
+            let tr = tr.clone();
+            async move {
+                let stats = tr.get_stats().await;
+                Ok(serde_json::to_value(stats).unwrap())
+            }
+        });
+    }
+    
+    // Treasury: List all sales
+    {
+        let tr = treasury.clone();
+        add_async_method(&mut handler, "treasury_getSales", move |_params: Params| {
+            let tr = tr.clone();
+            async move {
+                let sales = tr.get_sales().await;
+                Ok(serde_json::to_value(sales).unwrap())
+            }
+        });
+    }
+    
+    // Treasury: Signed proof of reserves over operator-supplied user balances
+    {
+        let tr = treasury.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "treasury_proveReserves", move |params: Params| {
+            let tr = tr.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                // Only operators may have the treasury key sign liabilities
+                let actor = admin.authorize("treasury_proveReserves", &parsed)?;
+                
+                let balances = parsed.get("balances")
+                    .and_then(|v| v.as_array())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing balances"))?
+                    .iter()
+                    .map(|entry| {
+                        let account = entry.get("account").and_then(|v| v.as_str());
+                        let balance = entry.get("balance").and_then(|v| v.as_u64());
+                        match (account, balance) {
+                            (Some(account), Some(balance)) => Ok((account.to_string(), balance)),
+                            _ => Err(jsonrpc_core::Error::invalid_params("Each balance needs an account and a balance")),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                
+                let result = tr.prove_reserves(&balances).await;
+                
+                // Keep individual balances out of the audit log
+                let mut details = serde_json::Map::new();
+                details.insert("account_count".to_string(), json!(balances.len()));
+                match result {
+                    Ok((attestation, proofs)) => {
+                        admin.record(&actor, "treasury_proveReserves", &details, None);
+                        Ok(json!({
+                            "attestation": attestation,
+                            "total_liabilities": Sats(attestation.total_liabilities()),
+                            "reserves_cover_liabilities": attestation.reserves_cover_liabilities(),
+                            "proofs": proofs
+                        }))
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "treasury_proveReserves", &details, Some(&e.to_string()));
+                        Err(jsonrpc_core::Error {
+                            code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                            message: format!("Proof of reserves failed: {}", e),
+                            data: None,
+                        })
+                    }
+                }
+            }
+        });
+    }
+    
+    // Treasury: Check an attestation and (optionally) a user's inclusion proof
+    {
+        handler.add_sync_method("treasury_verifyReserves", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            let attestation: ReserveAttestation = parsed.get("attestation")
+                .cloned()
+                .and_then(|v| serde_json::from_value(v).ok())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing or malformed attestation"))?;
+            let proof: Option<InclusionProof> = match parsed.get("proof") {
+                Some(v) => Some(serde_json::from_value(v.clone())
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Malformed inclusion proof"))?),
+                None => None,
+            };
+            
+            let verified = attestation.verify();
+            Ok(json!({
+                "signatures_valid": verified.is_ok(),
+                "error": verified.err().map(|e| e.to_string()),
+                "reserves_cover_liabilities": attestation.reserves_cover_liabilities(),
+                "included": proof.map(|p| p.nonce == attestation.nonce && p.verify(&attestation.liabilities_root))
+            }))
+        });
+    }
+    
+    // Vouchers: Issue a batch the treasury can cover
+    {
+        let vm = vouchers.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "voucher_createBatch", move |params: Params| {
+            let vm = vm.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("voucher_createBatch", &parsed)?;
+                let count = parsed.get("count")
+                    .and_then(|v| v.as_u64())
+                    .and_then(|v| u32::try_from(v).ok())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing count"))?;
+                let amount = parsed.get("amount")
+                    .and_then(|v| serde_json::from_value::<Sats>(v.clone()).ok())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing amount (satoshis)"))?;
+                let expires_at = match parsed.get("expiry") {
+                    None | Some(Value::Null) => None,
+                    Some(v) => Some(v.as_i64()
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("expiry must be a unix timestamp"))?),
+                };
+                let label = parsed.get("label").and_then(|v| v.as_str()).unwrap_or("");
+                
+                match vm.create_batch(label, count, amount.0, expires_at, &actor).await {
+                    Ok(batch) => {
+                        admin.record(&actor, "voucher_createBatch", &parsed, None);
+                        let now = chrono::Utc::now().timestamp();
+                        Ok(json!({
+                            "batch": batch.summary(now),
+                            "outstanding_liability": Sats(vm.outstanding_liability().await),
+                        }))
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "voucher_createBatch", &parsed, Some(&e.to_string()));
+                        Err(voucher_error(e))
+                    }
+                }
+            }
+        });
+    }
+    
+    // Vouchers: Batches with outstanding and redeemed value
+    {
+        let vm = vouchers.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "voucher_listBatches", move |params: Params| {
+            let vm = vm.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                admin.authorize("voucher_listBatches", &parsed)?;
+                let now = chrono::Utc::now().timestamp();
+                let batches = vm.batches().await;
+                let liability: u64 = batches.iter().map(|b| b.outstanding_value(now)).sum();
+                Ok(json!({
+                    "batches": batches.iter().map(|b| b.summary(now)).collect::<Vec<_>>(),
+                    "outstanding_liability": Sats(liability),
+                }))
+            }
+        });
+    }
+    
+    // Vouchers: One batch with the status of each code
+    {
+        let vm = vouchers.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "voucher_getBatch", move |params: Params| {
+            let vm = vm.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                admin.authorize("voucher_getBatch", &parsed)?;
+                let batch = voucher_batch_param(&vm, &parsed).await?;
+                let now = chrono::Utc::now().timestamp();
+                Ok(json!({
+                    "batch": batch.summary(now),
+                    "vouchers": vouchers::voucher_list(&batch, now),
+                }))
+            }
+        });
+    }
+    
+    // Vouchers: Void a batch's unredeemed codes
+    {
+        let vm = vouchers.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "voucher_revokeBatch", move |params: Params| {
+            let vm = vm.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("voucher_revokeBatch", &parsed)?;
+                let batch_id = parsed.get("batch_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing batch_id"))?;
+                
+                match vm.revoke_batch(batch_id).await {
+                    Ok(released) => {
+                        admin.record(&actor, "voucher_revokeBatch", &parsed, None);
+                        Ok(json!({
+                            "batch_id": batch_id,
+                            "released": Sats(released),
+                            "outstanding_liability": Sats(vm.outstanding_liability().await),
+                        }))
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "voucher_revokeBatch", &parsed, Some(&e.to_string()));
+                        Err(voucher_error(e))
+                    }
+                }
+            }
+        });
+    }
+    
+    // Vouchers: Printable payloads of a batch's outstanding codes
+    {
+        let vm = vouchers.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "voucher_exportBatch", move |params: Params| {
+            let vm = vm.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("voucher_exportBatch", &parsed)?;
+                let batch = voucher_batch_param(&vm, &parsed).await?;
+                // Exported codes can be spent by whoever reads them
+                admin.record(&actor, "voucher_exportBatch", &parsed, None);
+                Ok(vouchers::printable_export(&batch, chrono::Utc::now().timestamp()))
+            }
+        });
+    }
+    
+    // Vouchers: Redeem a code to an address
+    {
+        let vm = vouchers.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "voucher_redeem", move |params: Params| {
+            let vm = vm.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let code = parsed.get("code")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing code"))?;
+                let address = parsed.get("address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing address"))?;
+                
+                let result = vm.redeem(code, address).await;
+                
+                // Keep the code itself out of the audit log, it may still be live on failure
+                let actor = parsed.get("actor").and_then(|v| v.as_str()).unwrap_or("anonymous");
+                let record = AuditRecord::new(actor, "voucher_redeem", json!({ "address": address }));
+                match result {
+                    Ok((batch, redemption)) => {
+                        admin.append(record.with_txid(Some(hex::encode(redemption.tx_hash))));
+                        Ok(json!({
+                            "batch_id": batch.batch_id,
+                            "amount": Sats(batch.amount),
+                            "address": redemption.address,
+                            "tx_hash": HexHash(redemption.tx_hash),
+                        }))
+                    }
+                    Err(e) => {
+                        admin.append(record.with_error(e.to_string()));
+                        Err(voucher_error(e))
+                    }
+                }
+            }
+        });
+    }
+    
+    // Admin: Rotate treasury price
+    {
+        let tr = treasury.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "admin_setTreasuryPrice", move |params: Params| {
+            let tr = tr.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("admin_setTreasuryPrice", &parsed)?;
+                let price_cents = parsed.get("price_cents")
+                    .and_then(|v| v.as_u64())
+                    .filter(|p| *p > 0)
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing or zero price_cents"))?;
+                
+                let previous = tr.get_price().await;
+                tr.set_price(price_cents).await;
+                
+                let mut details = parsed.clone();
+                details.insert("previous_price_cents".to_string(), json!(previous));
+                admin.record(&actor, "admin_setTreasuryPrice", &details, None);
+                Ok(json!({
+                    "previous_price_cents": previous,
+                    "price_cents": price_cents
+                }))
+            }
+        });
+    }
+    
+    // Admin: Force mempool eviction (by hash or below a fee rate)
+    {
+        let bc = blockchain.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "admin_evictMempool", move |params: Params| {
+            let bc = bc.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("admin_evictMempool", &parsed)?;
+                
+                let tx_hashes = match parsed.get("tx_hashes").and_then(|v| v.as_array()) {
+                    Some(hashes) => Some(hashes.iter()
+                        .map(|h| {
+                            let bytes = h.as_str().and_then(|h| hex::decode(h).ok())
+                                .ok_or_else(|| jsonrpc_core::Error::invalid_params("Invalid transaction hash"))?;
+                            blockchain_core::Hash256::try_from(bytes.as_slice())
+                                .map_err(|_| jsonrpc_core::Error::invalid_params("Transaction hash must be 32 bytes"))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?),
+                    None => None,
+                };
+                let below_fee_rate = parsed.get("below_fee_rate").and_then(|v| v.as_u64());
+                if tx_hashes.is_none() && below_fee_rate.is_none() {
+                    return Err(jsonrpc_core::Error::invalid_params("Provide tx_hashes or below_fee_rate"));
+                }
+                
+                let result = match tx_hashes {
+                    Some(hashes) => bc.evict_transactions(&hashes).await,
+                    None => bc.evict_below_fee_rate(below_fee_rate.unwrap_or(0)).await,
+                };
+                
+                match result {
+                    Ok(evicted) => {
+                        admin.record(&actor, "admin_evictMempool", &parsed, None);
+                        Ok(json!({
+                            "evicted": evicted.iter().copied().map(HexHash).collect::<Vec<_>>()
+                        }))
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "admin_evictMempool", &parsed, Some(&e.to_string()));
+                        Err(jsonrpc_core::Error {
+                            code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                            message: format!("Mempool eviction failed: {}", e),
+                            data: None,
+                        })
+                    }
+                }
+            }
+        });
+    }
+    
+    // Run mempool acceptance checks on a raw transaction without submitting it
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "mempool_testAccept", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let raw_tx = parsed.get("raw_tx").and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing raw_tx"))?;
+                let bytes = hex::decode(raw_tx)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("raw_tx must be hex"))?;
+                let tx: blockchain_core::transaction::Transaction = serde_json::from_slice(&bytes)
+                    .map_err(|e| jsonrpc_core::Error::invalid_params(format!("Invalid transaction: {}", e)))?;
+                
+                let check = bc.test_accept(&tx).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: format!("Transaction check failed: {}", e),
+                    data: None,
+                })?;
+                
+                Ok(json!({
+                    "tx_hash": HexHash(check.tx_hash),
+                    "allowed": check.allowed,
+                    "reject_code": check.reject_code,
+                    "reject_reason": check.reject_reason,
+                    "size": check.size,
+                    "fee": check.fee.map(Sats),
+                    "fee_rate": check.fee_rate,
+                    "gas_price": check.gas_price,
+                    "effective_fee_rate": check.effective_fee_rate,
+                    "replaces": check.replaces.iter().copied().map(HexHash).collect::<Vec<_>>(),
+                }))
+            }
+        });
+    }
+    
+    // Preview which transactions eviction would remove to free `size` bytes
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "mempool_whatWouldEvict", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let size = parsed.get("size").and_then(|v| v.as_u64())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing size"))?;
+                
+                let candidates = bc.what_would_evict(size as usize).await;
+                let freed: usize = candidates.iter().map(|c| c.size).sum();
+                Ok(json!({
+                    "size": size,
+                    "freed": freed,
+                    "transactions": candidates.iter().map(|c| json!({
+                        "tx_hash": HexHash(c.tx_hash),
+                        "fee_rate": c.fee_rate,
+                        "fee": Sats(c.fee),
+                        "size": c.size,
+                        "age_secs": c.age_secs,
+                    })).collect::<Vec<_>>()
+                }))
+            }
+        });
+    }
+    
+    // Mempool size and the fee floor in effect, raised while it is full
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "mempool_getInfo", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let info = bc.mempool_info().await;
+                Ok(json!({
+                    "size": info.transactions,
+                    "bytes": info.memory_usage,
+                    "max_bytes": info.max_memory_usage,
+                    "usage": info.usage,
+                    "min_relay_fee_rate": info.min_relay_fee_rate,
+                    "mempool_min_fee_rate": info.min_fee_rate,
+                }))
+            }
+        });
+    }
+    
+    // Admin: Reindex UTXO views from consensus state
+    {
+        let bc = blockchain.clone();
+        let admin = admin.clone();
+        add_async_method(&mut handler, "admin_reindex", move |params: Params| {
+            let bc = bc.clone();
+            let admin = admin.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("admin_reindex", &parsed)?;
+                
+                let result = bc.reindex().await;
+                
+                match result {
+                    Ok(summary) => {
+                        admin.record(&actor, "admin_reindex", &parsed, None);
+                        Ok(summary)
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "admin_reindex", &parsed, Some(&e.to_string()));
+                        Err(jsonrpc_core::Error {
+                            code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                            message: format!("Reindex failed: {}", e),
+                            data: None,
+                        })
+                    }
+                }
+            }
+        });
+    }
+    
+    // Admin: Mark a block invalid and reorg away from it
+    {
+        let admin = admin.clone();
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "admin_invalidateBlock", move |params: Params| {
+            let admin = admin.clone();
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("admin_invalidateBlock", &parsed)?;
+                
+                let hash = parsed.get("hash")
+                    .and_then(|v| v.as_str())
+                    .and_then(|h| hex::decode(h).ok())
+                    .and_then(|bytes| blockchain_core::Hash256::try_from(bytes.as_slice()).ok())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing or invalid block hash"))?;
+                
+                let result = bc.invalidate_block(hash).await;
+                
+                match result {
+                    Ok(summary) => {
+                        admin.record(&actor, "admin_invalidateBlock", &parsed, None);
+                        Ok(summary)
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "admin_invalidateBlock", &parsed, Some(&e.to_string()));
+                        Err(jsonrpc_core::Error {
+                            code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                            message: format!("Invalidating block failed: {}", e),
+                            data: None,
+                        })
+                    }
+                }
+            }
+        });
+    }
+    
+    // Admin: Clear a block's invalidation
+    {
+        let admin = admin.clone();
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "admin_reconsiderBlock", move |params: Params| {
+            let admin = admin.clone();
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let actor = admin.authorize("admin_reconsiderBlock", &parsed)?;
+                
+                let hash = parsed.get("hash")
+                    .and_then(|v| v.as_str())
+                    .and_then(|h| hex::decode(h).ok())
+                    .and_then(|bytes| blockchain_core::Hash256::try_from(bytes.as_slice()).ok())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing or invalid block hash"))?;
+                
+                let result = bc.reconsider_block(hash).await;
+                
+                match result {
+                    Ok(summary) => {
+                        admin.record(&actor, "admin_reconsiderBlock", &parsed, None);
+                        Ok(summary)
+                    }
+                    Err(e) => {
+                        admin.record(&actor, "admin_reconsiderBlock", &parsed, Some(&e.to_string()));
+                        Err(jsonrpc_core::Error {
+                            code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                            message: format!("Reconsidering block failed: {}", e),
+                            data: None,
+                        })
+                    }
+                }
+            }
+        });
+    }
+    
+    // Admin: Compact block storage in the background
+    {
+        let admin = admin.clone();
+        let compactor = compactor.clone();
+        handler.add_sync_method("admin_compactStorage", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            let actor = admin.authorize("admin_compactStorage", &parsed)?;
+            
+            match compactor.trigger() {
+                Ok(()) => {
+                    admin.record(&actor, "admin_compactStorage", &parsed, None);
+                    Ok(json!({ "status": "started", "compaction": compactor.status() }))
+                }
+                Err(e) => {
+                    admin.record(&actor, "admin_compactStorage", &parsed, Some(&e));
+                    Err(jsonrpc_core::Error {
+                        code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                        message: e,
+                        data: None,
+                    })
+                }
+            }
+        });
+    }
+    
+    // Admin: Read the audit log
+    {
+        let admin = admin.clone();
+        handler.add_sync_method("admin_getAuditLog", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            admin.authorize("admin_getAuditLog", &parsed)?;
+            let limit = parsed.get("limit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(100) as usize;
+            let filter = |name: &str| parsed.get(name).and_then(|v| v.as_str());
+            Ok(serde_json::to_value(admin.recent_entries(limit, filter("filter_actor"), filter("filter_action"))).unwrap())
+        });
+    }
+    
+    // Admin: Check the audit log's hash chain
+    {
+        let admin = admin.clone();
+        handler.add_sync_method("admin_verifyAuditLog", move |params: Params| {
+            let parsed: serde_json::Map<String, Value> = params.parse()?;
+            admin.authorize("admin_verifyAuditLog", &parsed)?;
+            let (records, result) = admin.verify();
+            Ok(json!({
+                "records": records,
+                "valid": result.is_ok(),
+                "break": result.err()
+            }))
+        });
+    }
+    
+    // Debug: Dump UTXO set
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "debug_dumpUtxos", move |_params: Params| {
+            let bc = bc.clone();
+            async move {
+                let utxo_set = bc.utxo_set.read().await;
+                let count = utxo_set.get_utxo_count();
+                let addresses = utxo_set.get_all_addresses();
+                
+                Ok(json!({
+                    "total_utxos": count,
+                    "addresses": addresses
+                }))
+            }
+        });
+    }
+    
+    // Contract: Deploy
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_deploy", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let deployer = parsed.get("deployer")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing deployer"))?;
+                let bytecode_hex = parsed.get("bytecode")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing bytecode"))?;
+                let value = parsed.get("value")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let gas_limit = parsed.get("gas_limit")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(1000000);
+                    
+                let gas_bid = parse_gas_bid(&parsed, gas_limit)?;
+                let nonce = parse_nonce(&parsed)?;
+                    
+                let bytecode = hex::decode(bytecode_hex)
+                    .map_err(|e| jsonrpc_core::Error::invalid_params(format!("Invalid bytecode hex: {}", e)))?;
+                
+                let (result, gas_price, nonce) = async {
+                    let gas_price = bc.contract_gas_price(gas_bid).await?;
+                    let nonce = bc.local_contract_nonce(deployer, nonce).await?;
+                    let result = bc.deploy_contract(deployer, bytecode, value, gas_limit, gas_price).await?;
+                    if result.success {
+                        bc.contract_executor.use_local_nonce(deployer, nonce).await;
+                    }
+                    Ok::<_, anyhow::Error>((result, gas_price, nonce))
+                }.await.map_err(|e| {
+                    error!("❌ Contract deployment error: {}", e);
+                    jsonrpc_core::Error {
+                        code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                        message: format!("Contract deployment failed: {}", e),
+                        data: None,
+                    }
+                })?;
+                
+                Ok(execution_json(result, gas_price, nonce))
+            }
+        });
+    }
+    
+    // Contract: Call
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_call", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let caller = parsed.get("caller")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing caller"))?;
+                let contract_hex = parsed.get("contract")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
+                let calldata_hex = parsed.get("data")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing calldata"))?;
+                let value = parsed.get("value")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let gas_limit = parsed.get("gas_limit")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(100000);
+                    
+                let contract_bytes = hex::decode(contract_hex)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
+                if contract_bytes.len() != 20 {
+                    return Err(jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"));
+                }
+                let mut contract_addr = [0u8; 20];
+                contract_addr.copy_from_slice(&contract_bytes);
+                let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
+                
+                let calldata = hex::decode(calldata_hex)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid calldata hex"))?;
+                let gas_bid = parse_gas_bid(&parsed, gas_limit)?;
+                let nonce = parse_nonce(&parsed)?;
+                
+                let (result, gas_price, nonce) = async {
+                    let gas_price = bc.contract_gas_price(gas_bid).await?;
+                    let nonce = bc.local_contract_nonce(caller, nonce).await?;
+                    let result = bc.call_contract(caller, contract_address, calldata, value, gas_limit, gas_price).await?;
+                    if result.success {
+                        bc.contract_executor.use_local_nonce(caller, nonce).await;
+                    }
+                    Ok::<_, anyhow::Error>((result, gas_price, nonce))
+                }.await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: format!("Contract call failed: {}", e),
+                    data: None,
+                })?;
+                
+                Ok(execution_json(result, gas_price, nonce))
+            }
+        });
+    }
+    
+    // Next contract nonce of an address
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "account_getNonce", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<String> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing address"));
+                }
+
+                let nonce = bc.contract_executor.get_nonce(&parsed[0]).await;
+                Ok(json!({"address": parsed[0], "nonce": nonce}))
+            }
+        });
+    }
+    
+    // Contract: Trace a call or deployment opcode by opcode
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_traceCall", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let caller = parsed.get("caller")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing caller"))?;
+                // No contract means trace a deployment of `data` as init code
+                let contract_address = match parsed.get("contract").and_then(|v| v.as_str()) {
+                    Some(contract_hex) => {
+                        let contract_bytes = hex::decode(contract_hex)
+                            .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
+                        let contract_addr: [u8; 20] = contract_bytes.try_into()
+                            .map_err(|_| jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"))?;
+                        Some(blockchain_core::contracts::EthAddress::new(contract_addr))
+                    }
+                    None => None,
+                };
+                let data_hex = parsed.get("data")
+                    .or_else(|| parsed.get("bytecode"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let data = hex::decode(data_hex.trim_start_matches("0x"))
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid data hex"))?;
+                if contract_address.is_none() && data.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing bytecode to deploy"));
+                }
+                let value = parsed.get("value")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let gas_limit = parsed.get("gas_limit")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(if contract_address.is_some() { 100000 } else { 1000000 });
+                let max_steps = parsed.get("max_steps")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize)
+                    .unwrap_or(blockchain_core::contract_trace::DEFAULT_MAX_STEPS);
+                
+                let trace = bc.trace_contract(caller, contract_address, data, value, gas_limit, max_steps).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: e.to_string(),
+                    data: None,
+                })?;
+                
+                Ok(serde_json::to_value(trace).unwrap())
+            }
+        });
+    }
+    
+    // Contract: Get code
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_getCode", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let contract_hex = parsed.get("contract")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
+                    
+                let contract_bytes = hex::decode(contract_hex)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
+                if contract_bytes.len() != 20 {
+                    return Err(jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"));
+                }
+                let mut contract_addr = [0u8; 20];
+                contract_addr.copy_from_slice(&contract_bytes);
+                let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
+                
+                let code = bc.get_contract_code(contract_address).await;
+                
+                Ok(json!({
+                    "code": code.map(|c| hex::encode(c))
+                }))
+            }
+        });
+    }
+    
+    // Contract: Publish an ABI (deployer only)
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_publishAbi", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let contract_address = parse_contract_address(&parsed, "contract")?;
+                let publisher = parsed.get("publisher")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing publisher"))?;
+                // The ABI may be sent as JSON or as the text of a solc .abi file
+                let abi_json = match parsed.get("abi") {
+                    Some(Value::String(text)) => text.clone(),
+                    Some(abi) => abi.to_string(),
+                    None => return Err(jsonrpc_core::Error::invalid_params("Missing abi")),
+                };
+                let abi = Abi::from_json(&abi_json)
+                    .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                let entries = abi.entries.len();
+                
+                bc.publish_abi(contract_address, publisher, abi).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: e.to_string(),
+                    data: None,
+                })?;
+                
+                Ok(json!({"contract": contract_address, "entries": entries}))
+            }
+        });
+    }
+    
+    // Contract: Published ABI, the implementation's for a proxy
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_getAbi", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                let contract_address = parse_contract_address(&parsed, "contract")?;
+                
+                let abi = bc.get_abi(contract_address).await;
+                
+                Ok(json!({"contract": contract_address, "abi": abi}))
+            }
+        });
+    }
+    
+    // Contract: Call data for a function from the published ABI
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_encodeCall", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let contract_address = parse_contract_address(&parsed, "contract")?;
+                let function = parsed.get("function")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing function"))?;
+                let args = parsed.get("args")
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                
+                let abi = bc.get_abi(contract_address).await.ok_or_else(|| jsonrpc_core::Error::invalid_params("No ABI published for contract"))?;
+                let data = abi.encode_call(function, &args)
+                    .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                
+                Ok(json!({"data": hex::encode(data)}))
+            }
+        });
+    }
+    
+    // Contract: Decode call data, and return data if given
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_decodeCall", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let contract_address = parse_contract_address(&parsed, "contract")?;
+                let hex_param = |name: &str| -> std::result::Result<Option<Vec<u8>>, jsonrpc_core::Error> {
+                    parsed.get(name)
+                        .and_then(|v| v.as_str())
+                        .map(|h| hex::decode(h.trim_start_matches("0x"))
+                            .map_err(|_| jsonrpc_core::Error::invalid_params(format!("Invalid {} hex", name))))
+                        .transpose()
+                };
+                let data = hex_param("data")?
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing data"))?;
+                let output = hex_param("output")?;
+                
+                let abi = bc.get_abi(contract_address).await.ok_or_else(|| jsonrpc_core::Error::invalid_params("No ABI published for contract"))?;
+                let (entry, args) = abi.decode_call(&data)
+                    .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                let mut decoded = blockchain_core::abi::call_json(entry, args);
+                if let Some(output) = output {
+                    decoded["outputs"] = abi.decode_output(&entry.signature().unwrap_or_default(), &output)
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                }
+                
+                Ok(decoded)
+            }
+        });
+    }
+    
+    // Contract: EIP-1967 proxy slots
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_getProxyInfo", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let contract_hex = parsed.get("contract")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
+                let contract_bytes = hex::decode(contract_hex)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
+                let contract_addr: [u8; 20] = contract_bytes.try_into()
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"))?;
+                let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
+                
+                let info = bc.get_proxy_info(contract_address).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: e.to_string(),
+                    data: None,
+                })?;
+                
+                Ok(json!({
+                    "contract": contract_hex,
+                    "is_proxy": info.is_proxy(),
+                    "implementation": info.implementation,
+                    "admin": info.admin,
+                    "beacon": info.beacon,
+                }))
+            }
+        });
+    }
+    
+    // Contract: Proxy registry, optionally filtered by admin
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_listProxies", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse().unwrap_or_default();
+                
+                let admin = match parsed.get("admin").and_then(|v| v.as_str()) {
+                    Some(admin_hex) => {
+                        let admin_bytes = hex::decode(admin_hex)
+                            .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid admin address hex"))?;
+                        let admin_addr: [u8; 20] = admin_bytes.try_into()
+                            .map_err(|_| jsonrpc_core::Error::invalid_params("Admin address must be 20 bytes"))?;
+                        Some(blockchain_core::contracts::EthAddress::new(admin_addr))
+                    }
+                    None => None,
+                };
+                
+                let proxies = bc.list_proxies(admin).await;
+                
+                Ok(json!({
+                    "proxies": proxies.iter().map(|(address, info)| json!({
+                        "contract": address,
+                        "implementation": info.implementation,
+                        "admin": info.admin,
+                        "beacon": info.beacon,
+                    })).collect::<Vec<_>>()
+                }))
+            }
+        });
+    }
+    
+    // Get logs (events) with filter
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_getLogs", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                // Parse filter parameters
+                let address = parsed.get("address")
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| hex::decode(s).ok())
+                    .and_then(|bytes| {
+                        if bytes.len() == 20 {
+                            let mut addr = [0u8; 20];
+                            addr.copy_from_slice(&bytes);
+                            Some(blockchain_core::contracts::EthAddress::new(addr))
+                        } else {
+                            None
+                        }
+                    });
+                
+                let from_block = parsed.get("fromBlock").and_then(|v| v.as_u64());
+                let to_block = parsed.get("toBlock").and_then(|v| v.as_u64());
+                
+                let topics = parsed.get("topics")
+                    .and_then(|v| v.as_array())
+                    .map(|arr| {
+                        arr.iter().map(|t| {
+                            if t.is_null() {
+                                None
+                            } else {
+                                t.as_array().map(|inner| {
+                                    inner.iter()
+                                        .filter_map(|s| s.as_str().map(String::from))
+                                        .collect::<Vec<_>>()
+                                })
+                            }
+                        }).collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                
+                let filter = blockchain_core::event_indexer::EventFilter {
+                    address,
+                    topics,
+                    from_block,
+                    to_block,
+                };
+                
+                let events = bc.query_events(filter).await;
+                let decoded = bc.decode_events(&events).await;
+                
+                Ok(json!({
+                    "logs": events.iter().zip(decoded).map(|(e, decoded)| event_json(e, decoded)).collect::<Vec<_>>()
+                }))
+            }
+        });
+    }
+    
+    // Get events by block height
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_getEventsByBlock", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<u64> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing block height"));
+                }
+                
+                let events = bc.get_events_by_block(parsed[0]).await;
+                let decoded = bc.decode_events(&events).await;
+                
+                Ok(json!({
+                    "events": events.iter().zip(decoded).map(|(e, decoded)| event_json(e, decoded)).collect::<Vec<_>>()
+                }))
+            }
+        });
+    }
+    
+    // Get events by contract address
+    {
+        let bc = blockchain.clone();
+        features.add_async_method(&mut handler, "contract_getEventsByAddress", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+                
+                let contract_hex = parsed.get("address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing contract address"))?;
+                    
+                let contract_bytes = hex::decode(contract_hex)
+                    .map_err(|_| jsonrpc_core::Error::invalid_params("Invalid contract address hex"))?;
+                if contract_bytes.len() != 20 {
+                    return Err(jsonrpc_core::Error::invalid_params("Contract address must be 20 bytes"));
+                }
+                let mut contract_addr = [0u8; 20];
+                contract_addr.copy_from_slice(&contract_bytes);
+                let contract_address = blockchain_core::contracts::EthAddress::new(contract_addr);
+                
+                let events = bc.get_events_by_address(contract_address).await;
+                let decoded = bc.decode_events(&events).await;
+                
+                Ok(json!({
+                    "events": events.iter().zip(decoded).map(|(e, decoded)| event_json(e, decoded)).collect::<Vec<_>>()
+                }))
+            }
+        });
+    }
+    
+    // Resolve a name to the address it points at
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "names_resolve", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<String> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing name"));
+                }
+
+                let record = bc.resolve_name(&parsed[0]).await;
+
+                match record {
+                    Some(record) => Ok(json!(record)),
+                    None => Ok(json!({"error": "Name not found"}))
+                }
+            }
+        });
+    }
+
+    // Names pointing at an address
+    {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, "names_list", move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: Vec<String> = params.parse()?;
+                if parsed.is_empty() {
+                    return Err(jsonrpc_core::Error::invalid_params("Missing address"));
+                }
+
+                let names = bc.names_for(&parsed[0]).await;
+                Ok(json!({"address": parsed[0], "names": names}))
+            }
+        });
+    }
+
+    // Check an address, saying what is wrong with it
+    {
+        handler.add_sync_method("address_validate", move |params: Params| {
+            let parsed: Vec<String> = params.parse()?;
+            if parsed.is_empty() {
+                return Err(jsonrpc_core::Error::invalid_params("Missing address"));
+            }
+
+            Ok(match AddressCodec::decode(&parsed[0]) {
+                Ok(decoded) => json!({
+                    "address": parsed[0],
+                    "valid": true,
+                    "kind": decoded.kind,
+                    "checksummed": decoded.checksummed,
+                    "checksummedAddress": AddressCodec::encode_checked(decoded.kind, &decoded.hash),
+                }),
+                Err(e) => json!({"address": parsed[0], "valid": false, "error": e.to_string()}),
+            })
+        });
+    }
+
+    // Register, renew or repoint a name from a node wallet
+    for method in ["names_register", "names_renew", "names_update"] {
+        let bc = blockchain.clone();
+        add_async_method(&mut handler, method, move |params: Params| {
+            let bc = bc.clone();
+            async move {
+                let parsed: serde_json::Map<String, Value> = params.parse()?;
+
+                let wallet_address = parsed.get("wallet_address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing wallet_address"))?;
+                let name = parsed.get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing name"))?;
+                let address = parsed.get("address")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(wallet_address);
+                let periods = parsed.get("periods")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(1);
+
+                let commitment = match method {
+                    "names_register" => NameCommitment::register(name, address),
+                    "names_renew" => NameCommitment::renew(name),
+                    _ => NameCommitment::update(name, address),
+                }.map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                let name = commitment.name.clone();
+
+                let tx_hash = bc.submit_name_operation(wallet_address, commitment, periods).await.map_err(|e| jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(-32000),
+                    message: format!("Name operation failed: {}", e),
+                    data: None,
+                })?;
+
+                Ok(json!({
+                    "name": name,
+                    "txid": HexHash(tx_hash),
+                    "status": "pending"
+                }))
+            }
+        });
+    }
+
+    info!("📋 Registered RPC methods: blockchain_getBlockHeight, blockchain_getBlock, wallet_getBalance, wallet_list, wallet_abandonTransaction, wallet_cancelTransaction, blockchain_getStatus, blockchain_getDeploymentInfo, blockchain_getSupplyAudit, blockchain_getUtxoFilterInfo, utxo_getSetInfo, utxo_getTxOut, treasury_getPrice, treasury_sellCoins, treasury_getStats, treasury_getSales, treasury_proveReserves, treasury_verifyReserves, voucher_createBatch, voucher_listBatches, voucher_getBatch, voucher_revokeBatch, voucher_exportBatch, voucher_redeem, admin_setTreasuryPrice, admin_evictMempool, mempool_testAccept, mempool_whatWouldEvict, mempool_getInfo, admin_reindex, admin_invalidateBlock, admin_reconsiderBlock, admin_compactStorage, admin_getAuditLog, admin_verifyAuditLog, contract_deploy, contract_call, contract_traceCall, contract_getCode, contract_publishAbi, contract_getAbi, contract_encodeCall, contract_decodeCall, contract_getProxyInfo, contract_listProxies, contract_getLogs, contract_getEventsByBlock, contract_getEventsByAddress, account_getNonce, names_resolve, names_list, address_validate, names_register, names_renew, names_update, node_getFeatures, index_getStatus, index_pause, index_resume, index_getTransaction, index_getAddressHistory, tx_decodeRaw, tx_createRaw, tx_exportOffline, tx_importSignatures, script_debugExecute, node_getDiskUsage, blockchain_getChainTips, blockchain_getChainAlerts, node_getNetworkTime");
+    RpcServer::with_custom_handler(config, handler)
+}
@@ -0,0 +1,265 @@
+//! Block template manager
+//!
+//! Keeps a ready-to-mine block template current for the mining daemon:
+//! - A new chain tip replaces the template immediately
+//! - Mempool churn is batched and rebuilt at most once per interval
+//! - Rebuilds that would not change the block are not published
+//!
+//! Transactions are taken from the mempool in priority/fee-rate order.
+//! Ones that spend unconfirmed outputs, conflict with an earlier pick or
+//! are still time-locked wait for a later block.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tracing::{info, debug, warn};
+use blockchain_core::{
+    block::{Block, BlockHeader},
+    mempool::MempoolEvent,
+    consensus::ConsensusParams,
+    merkle::IncrementalMerkleTree,
+    transaction::{Transaction, TransactionInput},
+    Amount, Hash256,
+};
+use crate::blockchain::BlockchainBackend;
+
+/// Bytes kept free in the block for the header and coinbase
+const COINBASE_RESERVE: usize = 1_000;
+
+/// Template refresh policy
+#[derive(Debug, Clone)]
+pub struct TemplateConfig {
+    /// Minimum time between rebuilds caused by mempool changes
+    pub min_rebuild_interval: Duration,
+    /// Mempool changes that justify a rebuild once the interval has passed
+    pub rebuild_threshold: usize,
+    /// Rebuild anyway after this long if anything changed at all
+    pub max_template_age: Duration,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        Self {
+            min_rebuild_interval: Duration::from_secs(2),
+            rebuild_threshold: 10,
+            max_template_age: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Work handed to the miner
+#[derive(Debug, Clone)]
+pub struct BlockTemplate {
+    /// Increases with every published template
+    pub id: u64,
+    pub height: u64,
+    pub prev_hash: Hash256,
+    /// Block version, carrying version bits signals
+    pub version: u32,
+    pub difficulty_target: u32,
+    /// Selected mempool transactions, coinbase excluded
+    pub transactions: Vec<Transaction>,
+    /// Hashes of a placeholder coinbase and `transactions`, so each extra
+    /// nonce only rehashes the coinbase's path to the root
+    merkle_tree: IncrementalMerkleTree,
+    pub total_fees: u64,
+    pub created_at: Instant,
+}
+
+impl BlockTemplate {
+    /// Build the block for a given extra nonce. Each extra nonce changes the
+    /// coinbase and merkle root, giving the miner a fresh header nonce space.
+    /// The coinbase pays out according to the chain's reward and fund split.
+    pub fn block(&self, params: &ConsensusParams, validator_address: &str, extra_nonce: u64) -> Result<Block, anyhow::Error> {
+        let mut coinbase_data = format!("Block Height: {}", self.height).into_bytes();
+        coinbase_data.extend_from_slice(&extra_nonce.to_le_bytes());
+        let coinbase = Transaction::new(
+            1,
+            vec![TransactionInput::create_coinbase(coinbase_data)],
+            params.coinbase_outputs(validator_address, Amount::from_sat(self.total_fees))?,
+        );
+
+        let mut transactions = Vec::with_capacity(self.transactions.len() + 1);
+        transactions.push(coinbase);
+        transactions.extend(self.transactions.iter().cloned());
+
+        let mut merkle_tree = self.merkle_tree.clone();
+        merkle_tree.set(0, transactions[0].calculate_hash());
+        let header = BlockHeader::new(
+            self.version,
+            self.prev_hash,
+            merkle_tree.root(),
+            self.difficulty_target,
+            self.height as u32,
+        );
+        Ok(Block::new(header, transactions))
+    }
+
+    /// Whether two templates would produce the same block
+    fn same_work(&self, other: &BlockTemplate) -> bool {
+        self.prev_hash == other.prev_hash
+            && self.version == other.version
+            && self.difficulty_target == other.difficulty_target
+            && self.merkle_tree.leaves() == other.merkle_tree.leaves()
+    }
+}
+
+/// Builds block templates and republishes them as the chain and mempool change
+pub struct BlockTemplateManager {
+    blockchain: Arc<BlockchainBackend>,
+    config: TemplateConfig,
+    next_id: AtomicU64,
+    current: watch::Sender<Option<Arc<BlockTemplate>>>,
+}
+
+impl BlockTemplateManager {
+    pub fn new(blockchain: Arc<BlockchainBackend>, config: TemplateConfig) -> Self {
+        Self {
+            blockchain,
+            config,
+            next_id: AtomicU64::new(1),
+            current: watch::channel(None).0,
+        }
+    }
+
+    /// Receive every newly published template
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<BlockTemplate>>> {
+        self.current.subscribe()
+    }
+
+    /// Latest published template
+    pub fn current(&self) -> Option<Arc<BlockTemplate>> {
+        self.current.borrow().clone()
+    }
+
+    /// Build a template from the current tip and mempool and publish it,
+    /// unless it is the same work as the template already out
+    pub async fn rebuild(&self) -> Arc<BlockTemplate> {
+        let chain_state = self.blockchain.consensus.get_chain_state().await;
+        let height = chain_state.height + 1;
+        let (transactions, total_fees) = self.select_transactions(height).await;
+        let merkle_tree = IncrementalMerkleTree::from_leaves(
+            std::iter::once([0u8; 32]).chain(transactions.iter().map(|tx| tx.calculate_hash())).collect()
+        );
+
+        let template = BlockTemplate {
+            id: 0,
+            height,
+            prev_hash: chain_state.best_block_hash,
+            version: self.blockchain.consensus.next_block_version().await,
+            difficulty_target: chain_state.next_difficulty,
+            transactions,
+            merkle_tree,
+            total_fees,
+            created_at: Instant::now(),
+        };
+
+        if let Some(current) = self.current().filter(|current| current.same_work(&template)) {
+            debug!("Template {} unchanged after rebuild", current.id);
+            return current;
+        }
+
+        let template = Arc::new(BlockTemplate {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            ..template
+        });
+        info!("📋 Block template {}: height {}, {} transactions, {} satoshis in fees",
+              template.id, template.height, template.transactions.len(), template.total_fees);
+        self.current.send_replace(Some(template.clone()));
+        template
+    }
+
+    /// Pick mempool transactions in priority order that can go in the next block
+    async fn select_transactions(&self, height: u64) -> (Vec<Transaction>, u64) {
+        let max_size = self.blockchain.consensus.params().max_block_size.saturating_sub(COINBASE_RESERVE);
+        let candidates = self.blockchain.mempool.read().await.get_transactions_for_block(max_size);
+        let now = chrono::Utc::now().timestamp() as u64;
+
+        let mut spent = HashSet::new();
+        let mut selected = Vec::new();
+        let mut total_fees = 0u64;
+
+        'candidates: for tx in candidates {
+            if !tx.is_final(height, now) {
+                continue;
+            }
+
+            let mut input_value = 0u64;
+            let mut outpoints = Vec::with_capacity(tx.inputs.len());
+            for input in &tx.inputs {
+                let outpoint = format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index);
+                // Conflict with a transaction already picked, or unconfirmed parent
+                if spent.contains(&outpoint) {
+                    continue 'candidates;
+                }
+                let Some(utxo) = self.blockchain.consensus.get_utxo(&outpoint).await else {
+                    continue 'candidates;
+                };
+                input_value += utxo.value();
+                outpoints.push(outpoint);
+            }
+
+            let Some(fee) = input_value.checked_sub(tx.get_total_output_value()) else {
+                warn!("Skipping mempool transaction spending more than its inputs");
+                continue;
+            };
+            spent.extend(outpoints);
+            total_fees += fee;
+            selected.push(tx);
+        }
+
+        (selected, total_fees)
+    }
+
+    /// Keep the template fresh until the mempool or chain goes away
+    pub async fn run(self: Arc<Self>) {
+        let mut mempool_events = self.blockchain.mempool.read().await.subscribe();
+        let mut tips = self.blockchain.consensus.subscribe_tip();
+        let mut ticker = tokio::time::interval(self.config.min_rebuild_interval);
+        let mut pending_changes = 0usize;
+        let mut last_rebuild = Instant::now();
+
+        self.rebuild().await;
+
+        loop {
+            let tip_changed = tokio::select! {
+                tip = tips.recv() => match tip {
+                    Ok(tip) => {
+                        debug!("New tip {} at height {}, replacing template", hex::encode(tip.hash), tip.height);
+                        true
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => true,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                event = mempool_events.recv() => {
+                    match event {
+                        Ok(MempoolEvent::TransactionAdded { .. })
+                        | Ok(MempoolEvent::TransactionRemoved { .. })
+                        | Ok(MempoolEvent::TransactionReplaced { .. }) => pending_changes += 1,
+                        Ok(MempoolEvent::MempoolFull { evicted_count, .. }) => pending_changes += evicted_count as usize,
+                        Err(broadcast::error::RecvError::Lagged(missed)) => pending_changes += missed as usize,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                    false
+                }
+                _ = ticker.tick() => false,
+            };
+
+            let since_rebuild = last_rebuild.elapsed();
+            let due = tip_changed
+                || (pending_changes >= self.config.rebuild_threshold && since_rebuild >= self.config.min_rebuild_interval)
+                || (pending_changes > 0 && since_rebuild >= self.config.max_template_age);
+            if !due {
+                continue;
+            }
+
+            self.rebuild().await;
+            pending_changes = 0;
+            last_rebuild = Instant::now();
+        }
+
+        info!("Block template manager stopped");
+    }
+}
@@ -3,9 +3,12 @@
 //! Handles manual coin sales for real-world cash payments.
 //! This allows the platform owner to sell EDU coins to users who pay with cash.
 
+use blockchain_core::address::AddressCodec;
+use blockchain_core::canonical;
 use blockchain_core::transaction::{Transaction, TransactionInput, TransactionOutput};
 use blockchain_core::wallet::{WalletManager, Wallet};
 use blockchain_core::tx_builder::TransactionBuilder;
+use blockchain_core::reserves::{InclusionProof, LiabilityTree, ReserveAttestation, ReserveUtxo};
 use blockchain_core::{Hash256, Result as BlockchainResult};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -29,6 +32,7 @@ pub struct SaleRecord {
     /// Buyer's wallet address
     pub buyer_address: String,
     /// Amount of EDU coins sold
+    #[serde(with = "canonical::sats")]
     pub amount: u64,
     /// Price per EDU in USD (cents)
     pub price_per_edu_cents: u64,
@@ -39,6 +43,7 @@ pub struct SaleRecord {
     /// Payment proof/receipt number
     pub payment_proof: String,
     /// Transaction hash on blockchain
+    #[serde(with = "canonical::opt_hex_hash")]
     pub tx_hash: Option<Hash256>,
     /// Timestamp of sale
     pub timestamp: i64,
@@ -110,6 +115,8 @@ impl TreasuryManager {
         payment_proof: String,
     ) -> Result<SaleRecord> {
         info!("💵 Processing coin sale: {} EDU to {}", amount, buyer_address);
+        AddressCodec::validate(&buyer_address)
+            .map_err(|e| anyhow::anyhow!("Invalid buyer address: {}", e))?;
 
         // Calculate cost
         let price_cents = self.get_price().await;
@@ -180,6 +187,21 @@ impl TreasuryManager {
         Ok(sale)
     }
 
+    /// Coins the treasury holds
+    pub async fn balance(&self) -> Result<u64> {
+        self.blockchain.get_balance(TREASURY_ADDRESS).await
+    }
+
+    /// Send coins from the treasury, for payouts that are not sales such
+    /// as voucher redemptions. Returns the transaction hash
+    pub async fn pay_out(&self, address: &str, amount: u64) -> Result<Hash256> {
+        let tx = self.create_sale_transaction(address, amount).await?;
+        let tx_hash = tx.get_hash()?;
+        self.blockchain.submit_transaction(tx).await
+            .map_err(|e| anyhow::anyhow!("Failed to submit transaction: {}", e))?;
+        Ok(tx_hash)
+    }
+
     /// Create a transaction sending coins from treasury to buyer
     async fn create_sale_transaction(
         &self,
@@ -285,6 +307,46 @@ impl TreasuryManager {
         sales.iter().find(|s| s.sale_id == sale_id).cloned()
     }
 
+    /// Sign a proof-of-reserves attestation over the treasury's UTXOs and a
+    /// merkle-sum tree of the given user balances. Returns the attestation and
+    /// one inclusion proof per account.
+    pub async fn prove_reserves(&self, balances: &[(String, u64)]) -> Result<(ReserveAttestation, Vec<InclusionProof>)> {
+        let nonce: Hash256 = rand::random();
+        let tree = LiabilityTree::build(nonce, balances)
+            .map_err(|e| anyhow::anyhow!("Invalid liabilities: {}", e))?;
+
+        let utxos: Vec<ReserveUtxo> = {
+            let utxo_set = self.blockchain.utxo_set.read().await;
+            let mut addresses = vec![TREASURY_ADDRESS, self.treasury_wallet.address.as_str()];
+            addresses.dedup();
+            addresses.into_iter()
+                .flat_map(|address| {
+                    utxo_set.get_utxos_for_address(address)
+                        .into_iter()
+                        .map(move |utxo| ReserveUtxo {
+                            tx_hash: utxo.tx_hash,
+                            output_index: utxo.output_index,
+                            value: utxo.value(),
+                            address: address.to_string(),
+                        })
+                })
+                .collect()
+        };
+
+        let height = self.blockchain.get_height().await;
+        let attestation = ReserveAttestation::sign(&tree, &utxos, height, Utc::now().timestamp(), &self.treasury_wallet.private_key)
+            .map_err(|e| anyhow::anyhow!("Failed to sign reserves attestation: {}", e))?;
+
+        info!("🧾 Proof of reserves at height {}: {} sat in {} outputs vs {} sat owed to {} accounts",
+              height, attestation.total_reserves, attestation.outputs.len(),
+              attestation.total_liabilities(), attestation.account_count);
+        if !attestation.reserves_cover_liabilities() {
+            warn!("⚠️ Treasury reserves do not cover liabilities");
+        }
+
+        Ok((attestation, tree.proofs()))
+    }
+
     /// Get sales statistics
     pub async fn get_stats(&self) -> SaleStats {
         let sales = self.sales.read().await;
@@ -320,8 +382,10 @@ pub struct SaleStats {
     pub completed_sales: usize,
     pub failed_sales: usize,
     pub pending_sales: usize,
+    #[serde(with = "canonical::sats")]
     pub total_edu_sold: u64,
     pub total_revenue_cents: u64,
+    #[serde(with = "canonical::sats")]
     pub treasury_balance: u64,
     pub current_price_cents: u64,
 }
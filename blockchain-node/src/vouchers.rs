@@ -0,0 +1,342 @@
+//! Voucher Batches
+//!
+//! Printed voucher cards are bearer claims on the treasury: whoever holds a
+//! code can have its amount paid from the treasury to an address of their
+//! choosing. A batch is only issued when the treasury holds enough to pay
+//! every voucher still outstanding, across all batches, plus the new one,
+//! so cards never promise coins that are not there.
+//!
+//! Batches are kept in `vouchers.json` in the data directory. A voucher is
+//! outstanding until it is redeemed, its batch expires or its batch is
+//! revoked; expired and revoked vouchers no longer count as a liability.
+
+use crate::treasury::TreasuryManager;
+use anyhow::{Context, Result};
+use blockchain_core::address::AddressCodec;
+use blockchain_core::amount::Amount;
+use blockchain_core::canonical::{self, HexHash, Sats};
+use blockchain_core::locale::AmountFormat;
+use blockchain_core::Hash256;
+use chrono::Utc;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Voucher book file name inside the data directory
+pub const VOUCHERS_FILE: &str = "vouchers.json";
+
+/// Most vouchers a single batch may contain
+pub const MAX_BATCH_SIZE: u32 = 1000;
+
+/// Voucher code alphabet (no 0/O or 1/I to avoid misreads on printed cards)
+const VOUCHER_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// URI scheme a wallet recognizes when scanning a printed card
+const PAYLOAD_PREFIX: &str = "edunet:voucher?code=";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Redemption {
+    pub address: String,
+    #[serde(with = "canonical::hex_hash")]
+    pub tx_hash: Hash256,
+    pub redeemed_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Voucher {
+    pub code: String,
+    pub redemption: Option<Redemption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoucherBatch {
+    pub batch_id: String,
+    pub label: String,
+    /// Per voucher
+    #[serde(with = "canonical::sats")]
+    pub amount: u64,
+    pub created_by: String,
+    pub created_at: i64,
+    /// Unix time after which the batch's vouchers cannot be redeemed
+    pub expires_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    pub vouchers: Vec<Voucher>,
+}
+
+/// Where a voucher stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoucherStatus {
+    Outstanding,
+    Redeemed,
+    Expired,
+    Revoked,
+}
+
+impl VoucherBatch {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    pub fn status(&self, voucher: &Voucher, now: i64) -> VoucherStatus {
+        if voucher.redemption.is_some() {
+            VoucherStatus::Redeemed
+        } else if self.revoked_at.is_some() {
+            VoucherStatus::Revoked
+        } else if self.is_expired(now) {
+            VoucherStatus::Expired
+        } else {
+            VoucherStatus::Outstanding
+        }
+    }
+
+    fn count(&self, status: VoucherStatus, now: i64) -> u64 {
+        self.vouchers.iter().filter(|v| self.status(v, now) == status).count() as u64
+    }
+
+    /// Value still owed to holders of this batch's cards
+    pub fn outstanding_value(&self, now: i64) -> u64 {
+        self.count(VoucherStatus::Outstanding, now).saturating_mul(self.amount)
+    }
+
+    /// Counts and values by status, without the codes
+    pub fn summary(&self, now: i64) -> Value {
+        let redeemed = self.count(VoucherStatus::Redeemed, now);
+        json!({
+            "batch_id": self.batch_id,
+            "label": self.label,
+            "amount": Sats(self.amount),
+            "count": self.vouchers.len(),
+            "created_by": self.created_by,
+            "created_at": self.created_at,
+            "expires_at": self.expires_at,
+            "revoked_at": self.revoked_at,
+            "outstanding_count": self.count(VoucherStatus::Outstanding, now),
+            "redeemed_count": redeemed,
+            "expired_count": self.count(VoucherStatus::Expired, now),
+            "revoked_count": self.count(VoucherStatus::Revoked, now),
+            "outstanding_value": Sats(self.outstanding_value(now)),
+            "redeemed_value": Sats(redeemed.saturating_mul(self.amount)),
+        })
+    }
+}
+
+/// Voucher batches issued against the treasury
+pub struct VoucherManager {
+    treasury: Arc<TreasuryManager>,
+    path: PathBuf,
+    /// Held across a redemption's payout so a code cannot be paid twice
+    batches: Mutex<Vec<VoucherBatch>>,
+}
+
+impl VoucherManager {
+    pub fn new(treasury: Arc<TreasuryManager>, data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(VOUCHERS_FILE);
+        let batches: Vec<VoucherBatch> = if path.exists() {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Malformed voucher book {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        if !batches.is_empty() {
+            let now = Utc::now().timestamp();
+            let liability: u64 = batches.iter().map(|b| b.outstanding_value(now)).sum();
+            info!("🎟️ Loaded {} voucher batches, {} sat outstanding", batches.len(), liability);
+        }
+        Ok(Self { treasury, path, batches: Mutex::new(batches) })
+    }
+
+    fn save(&self, batches: &[VoucherBatch]) -> Result<()> {
+        // Write then rename, so a crash never leaves a truncated book
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(batches)? + "\n")
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))
+    }
+
+    /// Value of every voucher still redeemable, across all batches
+    pub async fn outstanding_liability(&self) -> u64 {
+        let now = Utc::now().timestamp();
+        self.batches.lock().await.iter().map(|b| b.outstanding_value(now)).sum()
+    }
+
+    /// Issue a batch of `count` vouchers worth `amount` each, if the
+    /// treasury covers them on top of every voucher already outstanding
+    pub async fn create_batch(
+        &self,
+        label: &str,
+        count: u32,
+        amount: u64,
+        expires_at: Option<i64>,
+        created_by: &str,
+    ) -> Result<VoucherBatch> {
+        if count == 0 || count > MAX_BATCH_SIZE {
+            anyhow::bail!("Batch size must be between 1 and {}", MAX_BATCH_SIZE);
+        }
+        if amount == 0 {
+            anyhow::bail!("Voucher amount must be positive");
+        }
+        let now = Utc::now().timestamp();
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            anyhow::bail!("Expiry must be in the future");
+        }
+        let batch_value = amount.checked_mul(count as u64)
+            .ok_or_else(|| anyhow::anyhow!("Batch value overflows"))?;
+
+        let mut batches = self.batches.lock().await;
+        let outstanding: u64 = batches.iter().map(|b| b.outstanding_value(now)).sum();
+        let needed = outstanding.saturating_add(batch_value);
+        let treasury_balance = self.treasury.balance().await?;
+        if treasury_balance < needed {
+            anyhow::bail!(
+                "Treasury holds {} sat but outstanding vouchers plus this batch need {} sat",
+                treasury_balance,
+                needed
+            );
+        }
+
+        let mut codes: HashSet<String> = batches.iter()
+            .flat_map(|b| b.vouchers.iter().map(|v| v.code.clone()))
+            .collect();
+        let mut vouchers = Vec::with_capacity(count as usize);
+        while vouchers.len() < count as usize {
+            let code = generate_voucher_code();
+            if codes.insert(code.clone()) {
+                vouchers.push(Voucher { code, redemption: None });
+            }
+        }
+
+        let batch = VoucherBatch {
+            batch_id: format!("BATCH-{}-{:08x}", Utc::now().format("%Y%m%d"), rand::random::<u32>()),
+            label: label.trim().to_string(),
+            amount,
+            created_by: created_by.to_string(),
+            created_at: now,
+            expires_at,
+            revoked_at: None,
+            vouchers,
+        };
+        batches.push(batch.clone());
+        self.save(&batches)?;
+
+        info!("🎟️ Voucher batch {} created by {}: {} x {} sat, {} sat now outstanding",
+              batch.batch_id, created_by, count, amount, needed);
+        Ok(batch)
+    }
+
+    pub async fn batches(&self) -> Vec<VoucherBatch> {
+        self.batches.lock().await.clone()
+    }
+
+    pub async fn batch(&self, batch_id: &str) -> Option<VoucherBatch> {
+        self.batches.lock().await.iter().find(|b| b.batch_id == batch_id).cloned()
+    }
+
+    /// Void a batch's unredeemed vouchers, returning the value released.
+    /// Vouchers already redeemed stay on record
+    pub async fn revoke_batch(&self, batch_id: &str) -> Result<u64> {
+        let now = Utc::now().timestamp();
+        let mut batches = self.batches.lock().await;
+        let batch = batches.iter_mut()
+            .find(|b| b.batch_id == batch_id)
+            .ok_or_else(|| anyhow::anyhow!("Voucher batch {} not found", batch_id))?;
+        if batch.revoked_at.is_some() {
+            anyhow::bail!("Voucher batch {} is already revoked", batch_id);
+        }
+        let released = batch.outstanding_value(now);
+        batch.revoked_at = Some(now);
+        self.save(&batches)?;
+
+        info!("🎟️ Voucher batch {} revoked, {} sat released", batch_id, released);
+        Ok(released)
+    }
+
+    /// Pay a voucher's amount from the treasury to `address`
+    pub async fn redeem(&self, code: &str, address: &str) -> Result<(VoucherBatch, Redemption)> {
+        AddressCodec::validate(address)
+            .map_err(|e| anyhow::anyhow!("Invalid address: {}", e))?;
+        let code = code.trim().to_ascii_uppercase();
+        let now = Utc::now().timestamp();
+
+        let mut batches = self.batches.lock().await;
+        let (batch_index, voucher_index) = batches.iter()
+            .enumerate()
+            .find_map(|(i, b)| b.vouchers.iter().position(|v| v.code == code).map(|j| (i, j)))
+            .ok_or_else(|| anyhow::anyhow!("Unknown voucher code"))?;
+        let batch = &batches[batch_index];
+        match batch.status(&batch.vouchers[voucher_index], now) {
+            VoucherStatus::Outstanding => {}
+            VoucherStatus::Redeemed => anyhow::bail!("Voucher has already been redeemed"),
+            VoucherStatus::Expired => anyhow::bail!("Voucher has expired"),
+            VoucherStatus::Revoked => anyhow::bail!("Voucher has been revoked"),
+        }
+
+        let tx_hash = self.treasury.pay_out(address, batch.amount).await?;
+        let redemption = Redemption {
+            address: address.to_string(),
+            tx_hash,
+            redeemed_at: now,
+        };
+        batches[batch_index].vouchers[voucher_index].redemption = Some(redemption.clone());
+        if let Err(e) = self.save(&batches) {
+            // The payout went out; the in-memory book still stops a second one
+            warn!("Voucher {} redeemed in {} but not saved: {}", code, hex::encode(tx_hash), e);
+        }
+
+        info!("🎟️ Voucher from batch {} redeemed to {}", batches[batch_index].batch_id, address);
+        Ok((batches[batch_index].clone(), redemption))
+    }
+}
+
+/// Every voucher of a batch with its status and redemption
+pub fn voucher_list(batch: &VoucherBatch, now: i64) -> Vec<Value> {
+    batch.vouchers.iter()
+        .map(|v| json!({
+            "code": v.code,
+            "status": batch.status(v, now),
+            "redeemed_to": v.redemption.as_ref().map(|r| r.address.clone()),
+            "tx_hash": v.redemption.as_ref().map(|r| HexHash(r.tx_hash)),
+        }))
+        .collect()
+}
+
+/// Printable payloads of a batch's outstanding vouchers, in the form
+/// `voucher-pdf-gen` reads
+pub fn printable_export(batch: &VoucherBatch, now: i64) -> Value {
+    let display_amount = AmountFormat::default().format(Amount::from_sat(batch.amount));
+    let vouchers: Vec<Value> = batch.vouchers.iter()
+        .filter(|v| batch.status(v, now) == VoucherStatus::Outstanding)
+        .map(|v| json!({
+            "code": v.code,
+            "amount": Sats(batch.amount),
+            "display_amount": display_amount,
+            "status": "active",
+            "payload": format!("{}{}", PAYLOAD_PREFIX, v.code),
+        }))
+        .collect();
+    json!({
+        "success": true,
+        "batch_id": batch.batch_id,
+        "label": batch.label,
+        "expires_at": batch.expires_at,
+        "count": vouchers.len(),
+        "vouchers": vouchers,
+    })
+}
+
+/// Random code like `EDU-7KQ2-M9XD-4HPT`
+fn generate_voucher_code() -> String {
+    let mut rng = rand::thread_rng();
+    let groups: Vec<String> = (0..3)
+        .map(|_| (0..4).map(|_| VOUCHER_ALPHABET[rng.gen_range(0..VOUCHER_ALPHABET.len())] as char).collect())
+        .collect();
+    format!("EDU-{}", groups.join("-"))
+}
@@ -0,0 +1,48 @@
+-- Marketplace listings with full-text search
+
+CREATE TABLE IF NOT EXISTS market_items (
+    id TEXT PRIMARY KEY,
+    seller_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    category TEXT NOT NULL,
+    price REAL NOT NULL,
+    currency TEXT NOT NULL DEFAULT 'EDU',
+    item_type TEXT NOT NULL DEFAULT 'physical',  -- physical, digital, service
+    status TEXT NOT NULL DEFAULT 'active',       -- active, sold, draft
+    images TEXT,                                 -- JSON array of image URIs
+    created_at INTEGER NOT NULL,                 -- Unix timestamp
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_market_items_seller ON market_items(seller_id);
+CREATE INDEX IF NOT EXISTS idx_market_items_browse ON market_items(status, category, price);
+CREATE INDEX IF NOT EXISTS idx_market_items_price ON market_items(status, price);
+CREATE INDEX IF NOT EXISTS idx_market_items_created ON market_items(status, created_at);
+
+-- Full-text index over searchable fields, kept in sync by triggers
+CREATE VIRTUAL TABLE IF NOT EXISTS market_items_fts USING fts5(
+    title,
+    description,
+    category,
+    content='market_items',
+    content_rowid='rowid',
+    tokenize='porter unicode61'
+);
+
+CREATE TRIGGER IF NOT EXISTS market_items_fts_insert AFTER INSERT ON market_items BEGIN
+    INSERT INTO market_items_fts(rowid, title, description, category)
+    VALUES (new.rowid, new.title, new.description, new.category);
+END;
+
+CREATE TRIGGER IF NOT EXISTS market_items_fts_delete AFTER DELETE ON market_items BEGIN
+    INSERT INTO market_items_fts(market_items_fts, rowid, title, description, category)
+    VALUES ('delete', old.rowid, old.title, old.description, old.category);
+END;
+
+CREATE TRIGGER IF NOT EXISTS market_items_fts_update AFTER UPDATE ON market_items BEGIN
+    INSERT INTO market_items_fts(market_items_fts, rowid, title, description, category)
+    VALUES ('delete', old.rowid, old.title, old.description, old.category);
+    INSERT INTO market_items_fts(rowid, title, description, category)
+    VALUES (new.rowid, new.title, new.description, new.category);
+END;
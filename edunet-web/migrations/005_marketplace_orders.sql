@@ -0,0 +1,36 @@
+-- Marketplace orders with escrowed payments
+
+CREATE TABLE IF NOT EXISTS market_orders (
+    order_id TEXT PRIMARY KEY,
+    item_id TEXT NOT NULL,
+    buyer_address TEXT NOT NULL,
+    seller_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,             -- In satoshis, fixed at order time
+    status TEXT NOT NULL DEFAULT 'created',  -- created, paid, shipped, completed, disputed, refunded, cancelled
+    payment_tx_hash TEXT,                -- Buyer -> escrow
+    release_tx_hash TEXT,                -- Escrow -> seller
+    refund_tx_hash TEXT,                 -- Escrow -> buyer
+    tracking_info TEXT,
+    dispute_reason TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_market_orders_buyer ON market_orders(buyer_address, created_at);
+CREATE INDEX IF NOT EXISTS idx_market_orders_seller ON market_orders(seller_address, created_at);
+CREATE INDEX IF NOT EXISTS idx_market_orders_item ON market_orders(item_id);
+CREATE INDEX IF NOT EXISTS idx_market_orders_status ON market_orders(status);
+
+-- Audit trail of every status change
+CREATE TABLE IF NOT EXISTS market_order_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_id TEXT NOT NULL,
+    from_status TEXT NOT NULL,
+    to_status TEXT NOT NULL,
+    actor_address TEXT,                  -- NULL for automatic transitions
+    note TEXT,
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES market_orders(order_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_market_order_events_order ON market_order_events(order_id);
@@ -0,0 +1,24 @@
+-- In-app notifications and per-user delivery preferences
+
+CREATE TABLE IF NOT EXISTS notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_address TEXT NOT NULL,
+    kind TEXT NOT NULL,                  -- payment_received, loan_funded, escrow_released, nft_transfer
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    reference_id TEXT,                   -- Order, loan or NFT the event is about
+    tx_hash TEXT,
+    is_read BOOLEAN NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_notifications_wallet ON notifications(wallet_address, is_read, created_at);
+
+-- Missing rows mean the defaults (both channels enabled)
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    wallet_address TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    in_app BOOLEAN NOT NULL DEFAULT 1,
+    email BOOLEAN NOT NULL DEFAULT 1,
+    PRIMARY KEY (wallet_address, kind)
+);
@@ -0,0 +1,43 @@
+-- Admin console: frozen accounts, voucher batches and the admin audit log
+
+CREATE TABLE IF NOT EXISTS frozen_accounts (
+    wallet_address TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    frozen_by TEXT NOT NULL,             -- Admin username
+    frozen_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS voucher_batches (
+    batch_id TEXT PRIMARY KEY,
+    label TEXT NOT NULL,
+    amount INTEGER NOT NULL,             -- Per voucher, in satoshis
+    voucher_count INTEGER NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER,
+    is_active BOOLEAN NOT NULL DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS voucher_codes (
+    code TEXT PRIMARY KEY,
+    batch_id TEXT NOT NULL REFERENCES voucher_batches(batch_id),
+    amount INTEGER NOT NULL,
+    redeemed_by TEXT,                    -- Wallet address
+    redeemed_at INTEGER,
+    redeem_tx_hash TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_voucher_codes_batch ON voucher_codes(batch_id, redeemed_at);
+
+CREATE TABLE IF NOT EXISTS admin_audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    actor TEXT NOT NULL,                 -- Admin username
+    action TEXT NOT NULL,                -- freeze_account, evict_mempool, create_voucher_batch, ...
+    target TEXT,                         -- Wallet, batch or transaction acted on
+    details TEXT,                        -- JSON
+    success BOOLEAN NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_created ON admin_audit_log(created_at);
+CREATE INDEX IF NOT EXISTS idx_admin_audit_actor ON admin_audit_log(actor, created_at);
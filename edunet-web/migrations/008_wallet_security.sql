@@ -0,0 +1,29 @@
+-- Two-factor authentication and per-user spending limits for web wallets
+
+CREATE TABLE IF NOT EXISTS user_two_factor (
+    wallet_address TEXT PRIMARY KEY,
+    secret TEXT NOT NULL,                -- Base32 TOTP secret
+    enabled BOOLEAN NOT NULL DEFAULT 0,  -- False until the first code is confirmed
+    last_used_step INTEGER NOT NULL DEFAULT 0,  -- Rejects replayed codes
+    recovery_codes TEXT NOT NULL DEFAULT '[]',  -- JSON array of SHA-256 hashes
+    created_at INTEGER NOT NULL
+);
+
+-- Missing rows mean the server defaults
+CREATE TABLE IF NOT EXISTS spending_limits (
+    wallet_address TEXT PRIMARY KEY,
+    daily_limit INTEGER NOT NULL,        -- Satoshis per rolling 24 hours
+    step_up_threshold INTEGER NOT NULL,  -- Single sends above this need a 2FA code
+    updated_at INTEGER NOT NULL
+);
+
+-- Outgoing value per wallet; rows are reserved before sending and removed if the send fails
+CREATE TABLE IF NOT EXISTS wallet_spends (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    tx_hash TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_spends_wallet ON wallet_spends(wallet_address, created_at);
@@ -0,0 +1,32 @@
+-- Custodial balances held in the hot wallet and sweeps to cold storage
+
+-- What the hot and cold wallets owe each user
+CREATE TABLE IF NOT EXISTS custody_accounts (
+    wallet_address TEXT PRIMARY KEY,
+    balance INTEGER NOT NULL DEFAULT 0,  -- Satoshis
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS custody_ledger (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_address TEXT NOT NULL,
+    kind TEXT NOT NULL,                  -- deposit, withdrawal, reversal
+    amount INTEGER NOT NULL,
+    tx_hash TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_custody_ledger_wallet ON custody_ledger(wallet_address, created_at);
+-- A deposit transaction is only ever credited once
+CREATE UNIQUE INDEX IF NOT EXISTS idx_custody_ledger_deposit ON custody_ledger(tx_hash) WHERE kind = 'deposit';
+
+CREATE TABLE IF NOT EXISTS custody_sweeps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    direction TEXT NOT NULL,             -- to_cold, from_cold
+    amount INTEGER NOT NULL,
+    tx_hash TEXT NOT NULL,
+    initiated_by TEXT NOT NULL,          -- Admin username, or "auto" for the sweeper
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_custody_sweeps_created ON custody_sweeps(created_at);
@@ -0,0 +1,21 @@
+-- Signed payment invoices and the payments that settle them
+
+CREATE TABLE IF NOT EXISTS invoices (
+    invoice_id TEXT PRIMARY KEY,
+    issuer_user_id TEXT NOT NULL,        -- User who requested the payment
+    destination TEXT NOT NULL,
+    amount INTEGER NOT NULL,             -- Satoshis
+    memo TEXT,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    issuer_public_key TEXT NOT NULL,     -- Hex
+    signature TEXT NOT NULL,             -- Hex, DER-encoded
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, paid, expired
+    paid_tx_hash TEXT,
+    paid_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoices_destination ON invoices(destination, status);
+CREATE INDEX IF NOT EXISTS idx_invoices_issuer ON invoices(issuer_user_id, created_at);
+-- A payment settles at most one invoice
+CREATE UNIQUE INDEX IF NOT EXISTS idx_invoices_paid_tx ON invoices(paid_tx_hash) WHERE paid_tx_hash IS NOT NULL;
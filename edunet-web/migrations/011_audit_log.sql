@@ -0,0 +1,19 @@
+-- Hash-chained audit log of wallet-affecting API calls
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    seq INTEGER PRIMARY KEY,             -- Position in the chain, from 0
+    timestamp INTEGER NOT NULL,
+    actor TEXT NOT NULL,                 -- Username, or anonymous
+    action TEXT NOT NULL,                -- Method and route, e.g. POST /api/blockchain/send-transaction
+    source_ip TEXT,
+    params TEXT NOT NULL,                -- JSON, secrets removed
+    txid TEXT,
+    success BOOLEAN NOT NULL,
+    error TEXT,
+    prev_hash TEXT NOT NULL,             -- Hex hash of the previous record
+    hash TEXT NOT NULL                   -- Hex hash of this record
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON audit_log(actor, seq);
+CREATE INDEX IF NOT EXISTS idx_audit_log_action ON audit_log(action, seq);
+CREATE INDEX IF NOT EXISTS idx_audit_log_txid ON audit_log(txid) WHERE txid IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Key/value tags on transactions (order id, loan id, memo), kept out of
+-- on-chain message strings
+
+CREATE TABLE IF NOT EXISTS transaction_tags (
+    tx_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (tx_hash, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_transaction_tags_key ON transaction_tags(key, value);
@@ -0,0 +1,4 @@
+-- Transactions whose block left the best chain are set back to pending
+-- (or conflicted) and flagged so history can show they were reorged
+
+ALTER TABLE transactions ADD COLUMN reorged INTEGER NOT NULL DEFAULT 0;
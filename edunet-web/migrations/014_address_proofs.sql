@@ -0,0 +1,21 @@
+-- Outside addresses users proved control of by signing a server-issued nonce
+
+CREATE TABLE IF NOT EXISTS address_challenges (
+    wallet_address TEXT NOT NULL,        -- Account asking to link the address
+    address TEXT NOT NULL,               -- Address to prove control of
+    nonce TEXT NOT NULL,
+    message TEXT NOT NULL,               -- Exact text to sign
+    expires_at INTEGER NOT NULL,
+    PRIMARY KEY (wallet_address, address)
+);
+
+CREATE TABLE IF NOT EXISTS linked_addresses (
+    address TEXT PRIMARY KEY,            -- Links to at most one account
+    wallet_address TEXT NOT NULL,
+    public_key TEXT NOT NULL,            -- Hex
+    message TEXT NOT NULL,               -- Signed challenge, kept as evidence
+    signature TEXT NOT NULL,             -- Hex, DER-encoded
+    verified_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_linked_addresses_wallet ON linked_addresses(wallet_address);
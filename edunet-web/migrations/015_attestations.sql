@@ -0,0 +1,26 @@
+-- Issuer-signed identity attestations. Documents stay here, off-chain;
+-- only each attestation's anchor hash is recorded on-chain.
+
+CREATE TABLE IF NOT EXISTS attestation_issuers (
+    issuer_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,                  -- e.g. the university's name
+    public_key TEXT NOT NULL UNIQUE,     -- Hex
+    active INTEGER NOT NULL DEFAULT 1,
+    added_by TEXT NOT NULL,              -- Admin who verified the issuer
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS attestations (
+    attestation_id TEXT PRIMARY KEY,
+    issuer_id TEXT NOT NULL,
+    subject_address TEXT NOT NULL,
+    attestation TEXT NOT NULL,           -- Signed attestation, JSON
+    document TEXT NOT NULL,              -- Full supporting document
+    anchor_hash TEXT NOT NULL,           -- Hex, as recorded on-chain
+    anchor_tx_hash TEXT,
+    issued_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    FOREIGN KEY (issuer_id) REFERENCES attestation_issuers(issuer_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_attestations_subject ON attestations(subject_address, expires_at);
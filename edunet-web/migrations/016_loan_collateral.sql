@@ -0,0 +1,23 @@
+-- Loan collateral locked in a hash-time-locked output: the platform holds
+-- the release secret and reveals it to return the collateral on repayment;
+-- after the locktime the escrow branch seizes it for the lenders.
+
+CREATE TABLE IF NOT EXISTS loan_collateral (
+    loan_id TEXT PRIMARY KEY,
+    borrower_address TEXT NOT NULL,
+    kind TEXT NOT NULL,                  -- edu, nft
+    amount INTEGER NOT NULL DEFAULT 0,   -- Satoshis, for EDU collateral
+    nft_id TEXT,                         -- For NFT collateral
+    lock_address TEXT NOT NULL,          -- P2SH address of the lock script
+    lock_script TEXT NOT NULL,           -- Hex HTLC script
+    release_secret TEXT NOT NULL,        -- Hex preimage, revealed on release
+    locktime INTEGER NOT NULL,           -- Unix time the escrow branch opens
+    status TEXT NOT NULL DEFAULT 'awaiting_lock',  -- awaiting_lock, locked, released, seized
+    lock_tx_hash TEXT,
+    settle_tx_hash TEXT,
+    created_at INTEGER NOT NULL,
+    settled_at INTEGER,
+    FOREIGN KEY (loan_id) REFERENCES loan_applications(loan_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_loan_collateral_status ON loan_collateral(status);
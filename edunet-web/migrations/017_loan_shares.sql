@@ -0,0 +1,60 @@
+-- Tokenized lender positions in funded loans. A position carries the
+-- right to future repayments; installments are distributed to whoever
+-- holds it when they are paid.
+
+CREATE TABLE IF NOT EXISTS loan_positions (
+    position_id TEXT PRIMARY KEY,
+    loan_id TEXT NOT NULL,
+    original_funder TEXT NOT NULL,       -- Lender whose funding it was carved from
+    holder_address TEXT NOT NULL,        -- Current owner of the repayment rights
+    principal INTEGER NOT NULL,          -- Satoshis of the loan it represents
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (loan_id) REFERENCES loan_applications(loan_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_loan_positions_loan ON loan_positions(loan_id);
+CREATE INDEX IF NOT EXISTS idx_loan_positions_holder ON loan_positions(holder_address);
+
+CREATE TABLE IF NOT EXISTS loan_position_listings (
+    listing_id TEXT PRIMARY KEY,
+    position_id TEXT NOT NULL,
+    seller_address TEXT NOT NULL,
+    price INTEGER NOT NULL,              -- Satoshis
+    status TEXT NOT NULL DEFAULT 'active',  -- active, pending, sold, cancelled
+    buyer_address TEXT,
+    payment_tx_hash TEXT,
+    created_at INTEGER NOT NULL,
+    closed_at INTEGER,
+    FOREIGN KEY (position_id) REFERENCES loan_positions(position_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_loan_position_listings_status ON loan_position_listings(status);
+-- At most one open listing per position
+CREATE UNIQUE INDEX IF NOT EXISTS idx_loan_position_listings_open
+    ON loan_position_listings(position_id) WHERE status IN ('active', 'pending');
+
+CREATE TABLE IF NOT EXISTS loan_position_transfers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    position_id TEXT NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    tx_hash TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_loan_position_transfers_position ON loan_position_transfers(position_id);
+
+-- Each installment share paid to a lender or position holder
+CREATE TABLE IF NOT EXISTS loan_distributions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    loan_id TEXT NOT NULL,
+    position_id TEXT,                    -- NULL for untokenized funding
+    holder_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    tx_hash TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_loan_distributions_loan ON loan_distributions(loan_id);
+CREATE INDEX IF NOT EXISTS idx_loan_distributions_position ON loan_distributions(position_id);
@@ -0,0 +1,32 @@
+-- All-or-nothing crowdfunding. Each campaign has its own address that
+-- collects pledges; at the deadline the total goes to the creator if the
+-- goal was met, otherwise every pledge is refunded.
+
+CREATE TABLE IF NOT EXISTS campaigns (
+    campaign_id TEXT PRIMARY KEY,
+    creator_address TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    goal INTEGER NOT NULL,               -- Satoshis
+    deadline INTEGER NOT NULL,
+    campaign_address TEXT NOT NULL UNIQUE,
+    status TEXT NOT NULL DEFAULT 'active',  -- active, releasing, funded, refunding, refunded
+    release_tx_hash TEXT,
+    created_at INTEGER NOT NULL,
+    settled_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_campaigns_status ON campaigns(status, deadline);
+
+CREATE TABLE IF NOT EXISTS campaign_pledges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    campaign_id TEXT NOT NULL,
+    backer_address TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    tx_hash TEXT NOT NULL UNIQUE,
+    timestamp INTEGER NOT NULL,
+    refund_tx_hash TEXT,
+    FOREIGN KEY (campaign_id) REFERENCES campaigns(campaign_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_campaign_pledges_campaign ON campaign_pledges(campaign_id);
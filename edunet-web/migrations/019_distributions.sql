@@ -0,0 +1,40 @@
+-- Dividend and airdrop distributions. The holders of a source (EDU
+-- balances, a creator's NFTs or a loan's stakes) are snapshotted at a
+-- height; the funded amount is then paid out from the distribution's
+-- address in batches, with each payout's progress kept here so a restart
+-- resumes where it stopped.
+
+CREATE TABLE IF NOT EXISTS distributions (
+    distribution_id TEXT PRIMARY KEY,
+    creator_address TEXT NOT NULL,
+    kind TEXT NOT NULL,                  -- dividend, airdrop
+    source TEXT NOT NULL,                -- edu, nft, loan
+    source_ref TEXT,                     -- NFT creator or loan id
+    snapshot_height INTEGER NOT NULL,
+    total_amount INTEGER NOT NULL,       -- Satoshis
+    distribution_address TEXT NOT NULL UNIQUE,
+    funding_tx_hash TEXT NOT NULL,
+    batch_size INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running',  -- running, paused, completed
+    created_at INTEGER NOT NULL,
+    completed_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_distributions_status ON distributions(status);
+CREATE INDEX IF NOT EXISTS idx_distributions_creator ON distributions(creator_address);
+
+CREATE TABLE IF NOT EXISTS distribution_payouts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    distribution_id TEXT NOT NULL,
+    recipient_address TEXT NOT NULL,
+    weight INTEGER NOT NULL,             -- Balance, NFT count or principal at the snapshot
+    amount INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',  -- pending, paid, failed
+    tx_hash TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    paid_at INTEGER,
+    UNIQUE(distribution_id, recipient_address),
+    FOREIGN KEY (distribution_id) REFERENCES distributions(distribution_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_distribution_payouts_status ON distribution_payouts(distribution_id, status);
@@ -0,0 +1,5 @@
+-- Lock the second factor after repeated wrong codes, so six-digit TOTP
+-- codes cannot be brute-forced
+
+ALTER TABLE user_two_factor ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;  -- Wrong codes in a row
+ALTER TABLE user_two_factor ADD COLUMN locked_until INTEGER NOT NULL DEFAULT 0;     -- Unix time codes are refused until
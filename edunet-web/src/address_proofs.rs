@@ -0,0 +1,129 @@
+//! Address ownership proofs
+//!
+//! Users link outside wallets to their account by proving they control the
+//! address: the platform issues a one-time challenge naming the account,
+//! the address and a random nonce, and the user signs it with the address's
+//! key as a signed message. No deposit is needed. Each address links to at
+//! most one account; the signed challenge is kept as evidence of the link.
+
+use crate::database::{Database, DbAddressChallenge, DbLinkedAddress};
+use crate::user_auth::User;
+use blockchain_core::message::SignedMessage;
+use blockchain_core::script_utils::ScriptBuilder;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+/// How long a challenge can be answered
+const CHALLENGE_TTL_SECS: i64 = 600;
+
+/// Challenge request for an address
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddressChallengeRequest {
+    pub address: String,
+}
+
+/// Text to sign with the address's key
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressChallenge {
+    pub address: String,
+    pub message: String,
+    pub expires_at: i64,
+}
+
+/// Signed answer to a challenge
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddressProofRequest {
+    pub address: String,
+    /// Hex public key of the address
+    pub public_key: String,
+    /// Hex DER-encoded signature over the challenge message
+    pub signature: String,
+}
+
+pub struct AddressProofs {
+    database: Arc<Database>,
+}
+
+impl AddressProofs {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    /// Issue a challenge for the user to sign with `address`'s key
+    pub async fn challenge(&self, user: &User, request: &AddressChallengeRequest) -> Result<AddressChallenge, String> {
+        let address = request.address.trim();
+        if !address.starts_with("edu1q") || ScriptBuilder::address_to_hash160(address).is_err() {
+            return Err("Only single-key edu1q addresses can be linked".to_string());
+        }
+        if address == user.wallet_address {
+            return Err("This is already your account's wallet".to_string());
+        }
+
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let now = Utc::now().timestamp();
+        let expires_at = now + CHALLENGE_TTL_SECS;
+        let message = format!(
+            "EduNet address verification\nAccount: {}\nAddress: {}\nNonce: {}\nExpires: {}",
+            user.wallet_address,
+            address,
+            hex::encode(nonce),
+            DateTime::<Utc>::from_timestamp(expires_at, 0).unwrap_or_default().to_rfc3339(),
+        );
+
+        self.database.put_address_challenge(&DbAddressChallenge {
+            wallet_address: user.wallet_address.clone(),
+            address: address.to_string(),
+            nonce: hex::encode(nonce),
+            message: message.clone(),
+            expires_at,
+        }, now).await.map_err(|e| e.to_string())?;
+
+        Ok(AddressChallenge { address: address.to_string(), message, expires_at })
+    }
+
+    /// Check the signed challenge and link the address to the user's account
+    pub async fn prove(&self, user: &User, request: &AddressProofRequest) -> Result<DbLinkedAddress, String> {
+        let address = request.address.trim();
+        let now = Utc::now().timestamp();
+        let challenge = self.database.take_address_challenge(&user.wallet_address, address, now).await
+            .map_err(|e| e.to_string())?
+            .ok_or("No open challenge for this address, request a new one")?;
+
+        let signed = SignedMessage {
+            address: address.to_string(),
+            message: challenge.message,
+            public_key: hex::decode(&request.public_key).map_err(|_| "Public key must be hex")?,
+            signature: hex::decode(&request.signature).map_err(|_| "Signature must be hex")?,
+        };
+        signed.verify().map_err(|e| format!("Proof rejected: {}", e))?;
+
+        let linked = DbLinkedAddress {
+            address: signed.address,
+            wallet_address: user.wallet_address.clone(),
+            public_key: hex::encode(&signed.public_key),
+            message: signed.message,
+            signature: hex::encode(&signed.signature),
+            verified_at: now,
+        };
+        if !self.database.link_address(&linked).await.map_err(|e| e.to_string())? {
+            return Err("Address is linked to another account".to_string());
+        }
+        info!("🔗 {} proved control of {}", user.username, linked.address);
+        Ok(linked)
+    }
+
+    pub async fn list(&self, user: &User) -> Result<Vec<DbLinkedAddress>, String> {
+        self.database.list_linked_addresses(&user.wallet_address).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn unlink(&self, user: &User, address: &str) -> Result<(), String> {
+        if !self.database.unlink_address(&user.wallet_address, address).await.map_err(|e| e.to_string())? {
+            return Err("Address is not linked to your account".to_string());
+        }
+        Ok(())
+    }
+}
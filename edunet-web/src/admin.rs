@@ -0,0 +1,257 @@
+//! Admin console for platform operators
+//!
+//! Admins are the usernames listed in `EDUNET_ADMINS` (comma-separated).
+//! Admin calls are recorded in the hash-chained audit log like every other
+//! state-changing call (see `audit`); refused attempts by non-admins,
+//! including reads, are recorded here.
+
+use crate::blockchain_integration::{BlockchainBackend, VolumeBucket};
+use crate::audit::AuditLog;
+use crate::custody::{ColdRefillRequest, CustodyManager};
+use crate::database::{Database, DbCustodySweep, DbFrozenAccount};
+use crate::user_auth::{User, UserManager};
+use blockchain_core::audit_log::AuditRecord;
+use blockchain_core::Hash256;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Mempool eviction request: explicit hashes, or everything below a fee rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvictRequest {
+    pub tx_hashes: Option<Vec<String>>,
+    pub below_fee_rate: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoucherBatchRequest {
+    pub label: String,
+    pub count: u32,
+    /// Per voucher, in satoshis
+    pub amount: u64,
+    /// Days until the batch expires (never if unset)
+    pub expires_in_days: Option<i64>,
+}
+
+/// Transaction volume over a window
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeReport {
+    pub since: i64,
+    pub bucket_secs: i64,
+    pub transaction_count: usize,
+    pub total_amount: u64,
+    pub total_fees: u64,
+    pub buckets: Vec<VolumeBucket>,
+}
+
+pub struct AdminConsole {
+    database: Arc<Database>,
+    users: Arc<UserManager>,
+    backend: Arc<BlockchainBackend>,
+    audit: Arc<AuditLog>,
+    admins: HashSet<String>,
+}
+
+impl AdminConsole {
+    pub fn new(
+        database: Arc<Database>,
+        users: Arc<UserManager>,
+        backend: Arc<BlockchainBackend>,
+        audit: Arc<AuditLog>,
+        admins: impl IntoIterator<Item = String>,
+    ) -> Self {
+        Self {
+            database,
+            users,
+            backend,
+            audit,
+            admins: admins.into_iter().collect(),
+        }
+    }
+
+    /// Admin usernames from `EDUNET_ADMINS`
+    pub fn admins_from_env() -> Vec<String> {
+        std::env::var("EDUNET_ADMINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    pub fn is_admin(&self, user: &User) -> bool {
+        self.admins.contains(&user.username)
+    }
+
+    /// Check that `user` is an admin; refusals are audited too
+    pub async fn authorize(&self, user: &User, action: &str) -> Result<(), String> {
+        if self.is_admin(user) {
+            return Ok(());
+        }
+        let error = "Admin access required".to_string();
+        let record = AuditRecord::new(user.username.clone(), format!("admin {}", action), Value::Null).with_error(error.clone());
+        self.audit.record(record).await;
+        Err(error)
+    }
+
+    // ==================== ACCOUNTS ====================
+
+    /// Freeze an account (by username or wallet address), ending its sessions
+    pub async fn freeze_account(&self, admin: &User, target: &str, reason: &str) -> Result<DbFrozenAccount, String> {
+        let user = self.resolve_user(target).await?;
+        if user.wallet_address == admin.wallet_address {
+            return Err("Admins cannot freeze their own account".to_string());
+        }
+        if reason.trim().is_empty() {
+            return Err("A reason is required".to_string());
+        }
+
+        let account = DbFrozenAccount {
+            wallet_address: user.wallet_address.clone(),
+            reason: reason.trim().to_string(),
+            frozen_by: admin.username.clone(),
+            frozen_at: Utc::now().timestamp(),
+        };
+        if !self.database.freeze_account(&account).await.map_err(|e| e.to_string())? {
+            return Err(format!("Account {} is already frozen", user.username));
+        }
+        self.users.set_frozen(&user.wallet_address, true).await;
+
+        tracing::warn!("🧊 Account {} frozen by {}: {}", user.username, admin.username, account.reason);
+        Ok(account)
+    }
+
+    pub async fn unfreeze_account(&self, admin: &User, target: &str) -> Result<(), String> {
+        let user = self.resolve_user(target).await?;
+        if !self.database.unfreeze_account(&user.wallet_address).await.map_err(|e| e.to_string())? {
+            return Err(format!("Account {} is not frozen", user.username));
+        }
+        self.users.set_frozen(&user.wallet_address, false).await;
+        tracing::info!("✅ Account {} unfrozen by {}", user.username, admin.username);
+        Ok(())
+    }
+
+    pub async fn frozen_accounts(&self) -> Result<Vec<DbFrozenAccount>, String> {
+        self.database.list_frozen_accounts().await.map_err(|e| e.to_string())
+    }
+
+    async fn resolve_user(&self, target: &str) -> Result<User, String> {
+        match self.users.get_user_by_username(target).await {
+            Some(user) => Ok(user),
+            None => self.users.get_user_by_wallet(target).await
+                .ok_or_else(|| format!("No user with username or wallet {}", target)),
+        }
+    }
+
+    // ==================== CHAIN ====================
+
+    /// System-wide confirmed transaction volume over the last `days`
+    pub async fn transaction_volume(&self, days: i64, bucket_secs: i64) -> VolumeReport {
+        let since = Utc::now() - Duration::days(days.clamp(1, 365));
+        let buckets = self.backend.transaction_volume(since, bucket_secs).await;
+
+        VolumeReport {
+            since: since.timestamp(),
+            bucket_secs: bucket_secs.max(60),
+            transaction_count: buckets.iter().map(|b| b.transaction_count).sum(),
+            total_amount: buckets.iter().map(|b| b.total_amount).sum(),
+            total_fees: buckets.iter().map(|b| b.total_fees).sum(),
+            buckets,
+        }
+    }
+
+    /// Force transactions out of the mempool; returns the evicted hashes
+    pub async fn evict_mempool(&self, admin: &User, request: &EvictRequest) -> Result<Vec<String>, String> {
+        let evicted = match (&request.tx_hashes, request.below_fee_rate) {
+            (Some(hashes), _) => {
+                let hashes = hashes.iter()
+                    .map(|h| {
+                        hex::decode(h).ok()
+                            .and_then(|bytes| Hash256::try_from(bytes.as_slice()).ok())
+                            .ok_or_else(|| format!("Invalid transaction hash: {}", h))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.backend.evict_mempool_transactions(&hashes).await
+            }
+            (None, Some(rate)) => self.backend.evict_mempool_below_fee_rate(rate).await,
+            (None, None) => return Err("Provide tx_hashes or below_fee_rate".to_string()),
+        }
+        .map_err(|e| e.to_string())?;
+
+        tracing::warn!("🧹 {} evicted {} mempool transaction(s)", admin.username, evicted.len());
+        Ok(evicted.iter().map(hex::encode).collect())
+    }
+
+    /// Rebuild the backend's in-memory indexes
+    pub async fn reindex(&self, admin: &User) -> Result<Value, String> {
+        tracing::info!("🔄 Reindex requested by {}", admin.username);
+        self.backend.reindex().await.map_err(|e| e.to_string())
+    }
+
+    // ==================== CUSTODY ====================
+
+    /// Sweep the custody hot wallet to cold storage now
+    pub async fn sweep_custody(&self, admin: &User, custody: &CustodyManager) -> Result<Option<DbCustodySweep>, String> {
+        custody.sweep(&admin.username).await
+    }
+
+    /// Record a cold-storage refill of the hot wallet
+    pub async fn record_cold_refill(&self, admin: &User, custody: &CustodyManager, request: &ColdRefillRequest) -> Result<DbCustodySweep, String> {
+        custody.record_refill(request, &admin.username).await
+    }
+
+    // ==================== VOUCHERS ====================
+    // Batches live on blockchain-node, which pays them from the treasury
+
+    /// Issue a batch through the node, which refuses one the treasury
+    /// cannot cover on top of the vouchers already outstanding
+    pub async fn create_voucher_batch(&self, admin: &User, request: &VoucherBatchRequest) -> Result<Value, String> {
+        if request.label.trim().is_empty() {
+            return Err("A label is required".to_string());
+        }
+
+        let mut params = Map::new();
+        params.insert("label".to_string(), json!(request.label.trim()));
+        params.insert("count".to_string(), json!(request.count));
+        params.insert("amount".to_string(), json!(request.amount));
+        if let Some(days) = request.expires_in_days {
+            params.insert("expiry".to_string(), json!((Utc::now() + Duration::days(days)).timestamp()));
+        }
+        let created = self.backend.voucher_call("voucher_createBatch", params).await.map_err(|e| e.to_string())?;
+
+        tracing::info!("🎟️ Voucher batch {} created by {}: {} x {} sat",
+            created["batch"]["batch_id"], admin.username, request.count, request.amount);
+        Ok(created)
+    }
+
+    /// Every batch with its outstanding and redeemed value
+    pub async fn voucher_batches(&self) -> Result<Value, String> {
+        self.backend.voucher_call("voucher_listBatches", Map::new()).await.map_err(|e| e.to_string())
+    }
+
+    /// A batch with the status of each code
+    pub async fn voucher_batch(&self, batch_id: &str) -> Result<Value, String> {
+        self.backend.voucher_call("voucher_getBatch", batch_param(batch_id)).await.map_err(|e| e.to_string())
+    }
+
+    /// Outstanding codes in the form `voucher-pdf-gen` prints from
+    pub async fn export_voucher_batch(&self, batch_id: &str) -> Result<Value, String> {
+        self.backend.voucher_call("voucher_exportBatch", batch_param(batch_id)).await.map_err(|e| e.to_string())
+    }
+
+    /// Void every unredeemed voucher in a batch, for good
+    pub async fn revoke_voucher_batch(&self, admin: &User, batch_id: &str) -> Result<Value, String> {
+        let revoked = self.backend.voucher_call("voucher_revokeBatch", batch_param(batch_id)).await.map_err(|e| e.to_string())?;
+        tracing::info!("🎟️ Voucher batch {} revoked by {}", batch_id, admin.username);
+        Ok(revoked)
+    }
+}
+
+/// Parameters naming one batch
+fn batch_param(batch_id: &str) -> Map<String, Value> {
+    let mut params = Map::new();
+    params.insert("batch_id".to_string(), json!(batch_id));
+    params
+}
@@ -0,0 +1,238 @@
+//! University identity attestations
+//!
+//! Admins register verified issuers (universities) by public key. Issuers
+//! submit signed enrollment attestations about students' addresses along
+//! with the full supporting document, which is kept here off-chain. Each
+//! accepted attestation's anchor hash is recorded on-chain in a transaction
+//! from the attestation anchor address, so verification can show the
+//! attestation existed when it was anchored.
+//!
+//! A student with an unexpired attestation from an active issuer is
+//! verified, and loan scoring uses the attested enrollment data instead of
+//! what the applicant reports.
+
+use crate::blockchain_integration::{tags, BlockchainBackend, TransactionStatus};
+use crate::database::{Database, DbAttestation, DbAttestationIssuer};
+use crate::user_auth::{User, UserManager};
+use blockchain_core::attestation::{Attestation, EnrollmentClaim};
+use blockchain_core::crypto;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Anchor address used when `ATTESTATION_ANCHOR_ADDRESS` is not set
+pub const DEFAULT_ANCHOR_ADDRESS: &str = "edunet_attestations";
+
+/// Largest supporting document accepted
+const MAX_DOCUMENT_LEN: usize = 256 * 1024;
+
+/// Issuer registration request
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddIssuerRequest {
+    pub name: String,
+    /// Hex public key the issuer signs attestations with
+    pub public_key: String,
+}
+
+/// Attestation submitted by an issuer
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubmitAttestationRequest {
+    pub attestation: Attestation,
+    /// Full document the attestation's `document_hash` commits to
+    pub document: String,
+}
+
+/// Outcome of checking an attestation
+#[derive(Debug, Clone, Serialize)]
+pub struct AttestationVerification {
+    pub attestation: Attestation,
+    pub issuer: String,
+    pub valid: bool,
+    pub signature_valid: bool,
+    pub issuer_active: bool,
+    pub expired: bool,
+    pub document_matches: bool,
+    /// Whether the anchor hash is in a confirmed transaction
+    pub anchored: bool,
+    pub anchor_hash: String,
+    pub anchor_tx_hash: Option<String>,
+}
+
+pub struct AttestationRegistry {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    users: Arc<UserManager>,
+    anchor_address: String,
+}
+
+impl AttestationRegistry {
+    pub fn new(database: Arc<Database>, backend: Arc<BlockchainBackend>, users: Arc<UserManager>) -> Self {
+        let anchor_address = std::env::var("ATTESTATION_ANCHOR_ADDRESS")
+            .unwrap_or_else(|_| DEFAULT_ANCHOR_ADDRESS.to_string());
+        Self { database, backend, users, anchor_address }
+    }
+
+    /// Register a verified issuer
+    pub async fn add_issuer(&self, admin: &User, request: &AddIssuerRequest) -> Result<DbAttestationIssuer, String> {
+        if request.name.trim().is_empty() {
+            return Err("Issuer name is required".to_string());
+        }
+        let public_key = hex::decode(&request.public_key).map_err(|_| "Public key must be hex")?;
+        crypto::compress_public_key(&public_key).map_err(|e| e.to_string())?;
+        if self.database.get_attestation_issuer_by_key(&hex::encode(&public_key)).await.map_err(|e| e.to_string())?.is_some() {
+            return Err("An issuer with this key is already registered".to_string());
+        }
+
+        let issuer = DbAttestationIssuer {
+            issuer_id: format!("issuer_{}", Uuid::new_v4().simple()),
+            name: request.name.trim().to_string(),
+            public_key: hex::encode(&public_key),
+            active: true,
+            added_by: admin.username.clone(),
+            created_at: Utc::now().timestamp(),
+        };
+        self.database.insert_attestation_issuer(&issuer).await.map_err(|e| e.to_string())?;
+        info!("🎓 {} registered attestation issuer {} ({})", admin.username, issuer.name, issuer.issuer_id);
+        Ok(issuer)
+    }
+
+    pub async fn issuers(&self) -> Result<Vec<DbAttestationIssuer>, String> {
+        self.database.list_attestation_issuers().await.map_err(|e| e.to_string())
+    }
+
+    /// Activate or retire an issuer; its attestations stop counting while retired
+    pub async fn set_issuer_active(&self, issuer_id: &str, active: bool) -> Result<(), String> {
+        if !self.database.set_attestation_issuer_active(issuer_id, active).await.map_err(|e| e.to_string())? {
+            return Err("Issuer not found".to_string());
+        }
+        self.refresh_verified().await
+    }
+
+    /// Accept an issuer-signed attestation, store its document and anchor it
+    pub async fn submit(&self, request: &SubmitAttestationRequest) -> Result<DbAttestation, String> {
+        let attestation = &request.attestation;
+        attestation.verify().map_err(|e| e.to_string())?;
+        let issuer = self.database.get_attestation_issuer_by_key(&hex::encode(&attestation.issuer_public_key)).await
+            .map_err(|e| e.to_string())?
+            .filter(|issuer| issuer.active)
+            .ok_or("Attestation is not signed by an active registered issuer")?;
+        if attestation.is_expired_at(Utc::now().timestamp()) {
+            return Err("Attestation has expired".to_string());
+        }
+        if request.document.len() > MAX_DOCUMENT_LEN {
+            return Err(format!("Document exceeds {} bytes", MAX_DOCUMENT_LEN));
+        }
+        if !attestation.matches_document(request.document.as_bytes()) {
+            return Err("Document does not match the attestation's document hash".to_string());
+        }
+
+        let anchor_hash = hex::encode(attestation.anchor_hash());
+        let mut record = DbAttestation {
+            attestation_id: attestation.id.clone(),
+            issuer_id: issuer.issuer_id.clone(),
+            subject_address: attestation.subject.clone(),
+            attestation: serde_json::to_string(attestation).map_err(|e| e.to_string())?,
+            document: request.document.clone(),
+            anchor_hash: anchor_hash.clone(),
+            anchor_tx_hash: None,
+            issued_at: attestation.issued_at,
+            expires_at: attestation.expires_at,
+        };
+        self.database.insert_attestation(&record).await
+            .map_err(|e| format!("Failed to store attestation {}: {}", attestation.id, e))?;
+
+        // The anchor can be retried later; the attestation itself is already valid
+        match self.backend.send_tagged_transaction(
+            &self.anchor_address,
+            &self.anchor_address,
+            1,
+            Some(anchor_hash),
+            tags::for_kind(tags::ATTESTATION, Some((tags::ATTESTATION_ID, &attestation.id))),
+        ).await {
+            Ok(tx_hash) => {
+                self.database.set_attestation_anchor(&attestation.id, &tx_hash).await.map_err(|e| e.to_string())?;
+                record.anchor_tx_hash = Some(tx_hash);
+            }
+            Err(e) => warn!("⚠️ Failed to anchor attestation {}: {}", attestation.id, e),
+        }
+
+        self.users.set_verified(&attestation.subject, true).await;
+        info!("🎓 {} attested enrollment of {} ({})", issuer.name, attestation.subject, attestation.id);
+        Ok(record)
+    }
+
+    /// Check an attestation's signature, issuer, expiry, document and anchor
+    pub async fn verify(&self, attestation_id: &str) -> Result<AttestationVerification, String> {
+        let record = self.database.get_attestation(attestation_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Attestation not found")?;
+        let attestation: Attestation = serde_json::from_str(&record.attestation).map_err(|e| e.to_string())?;
+        let issuer = self.database.get_attestation_issuer(&record.issuer_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Issuer not found")?;
+
+        let anchored = match &record.anchor_tx_hash {
+            Some(tx_hash) => {
+                let confirmed = matches!(self.backend.get_transaction_status(tx_hash).await, Some(TransactionStatus::Confirmed));
+                let memo = self.database.get_transaction_by_hash(tx_hash).await.map_err(|e| e.to_string())?
+                    .and_then(|tx| tx.memo);
+                confirmed && memo.as_deref() == Some(record.anchor_hash.as_str())
+            }
+            None => false,
+        };
+        let signature_valid = attestation.verify().is_ok();
+        let expired = attestation.is_expired_at(Utc::now().timestamp());
+        let document_matches = attestation.matches_document(record.document.as_bytes())
+            && hex::encode(attestation.anchor_hash()) == record.anchor_hash;
+
+        Ok(AttestationVerification {
+            valid: signature_valid && issuer.active && !expired && document_matches && anchored,
+            issuer: issuer.name,
+            signature_valid,
+            issuer_active: issuer.active,
+            expired,
+            document_matches,
+            anchored,
+            anchor_hash: record.anchor_hash,
+            anchor_tx_hash: record.anchor_tx_hash,
+            attestation,
+        })
+    }
+
+    /// Attestations about an address, newest first
+    pub async fn for_subject(&self, address: &str) -> Result<Vec<Attestation>, String> {
+        self.database.list_attestations_for_subject(address).await
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|record| serde_json::from_str(&record.attestation).map_err(|e: serde_json::Error| e.to_string()))
+            .collect()
+    }
+
+    /// Newest enrollment claim about an address that still holds
+    pub async fn current_claim(&self, address: &str) -> Option<EnrollmentClaim> {
+        let now = Utc::now().timestamp();
+        for record in self.database.list_attestations_for_subject(address).await.ok()? {
+            let Ok(attestation) = serde_json::from_str::<Attestation>(&record.attestation) else {
+                continue;
+            };
+            let issuer_active = matches!(self.database.get_attestation_issuer(&record.issuer_id).await, Ok(Some(issuer)) if issuer.active);
+            if issuer_active && !attestation.is_expired_at(now) && attestation.verify().is_ok() {
+                return Some(attestation.claim);
+            }
+        }
+        None
+    }
+
+    /// Recompute every user's verified flag from current attestations
+    pub async fn refresh_verified(&self) -> Result<(), String> {
+        let attested: HashSet<String> = self.database.attested_subjects(Utc::now().timestamp()).await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect();
+        self.users.set_verified_wallets(&attested).await;
+        Ok(())
+    }
+}
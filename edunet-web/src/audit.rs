@@ -0,0 +1,286 @@
+//! Audit log of wallet-affecting API calls
+//!
+//! Every state-changing call (any method but GET, HEAD and OPTIONS) is
+//! recorded with the caller, source IP, request parameters and the resulting
+//! transaction id, unless its route is one of the few in `UNAUDITED_ROUTES`.
+//! New routes that move funds are so audited without having to be listed.
+//! Admin console refusals and, once imported, the records of the old admin
+//! audit table are kept in the same log. Records are hash-chained
+//! (see `blockchain_core::audit_log`), so an edited or deleted row shows up
+//! when the chain is verified. Web instances sharing a database extend the
+//! same chain; a writer that loses the race for a sequence number reseals
+//! its record on the new head.
+
+use crate::database::{Database, DbAdminAction, DbAuditRecord};
+use blockchain_core::audit_log::{verify_chain, AuditRecord, ChainBreak};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// State-changing routes that are not audited, as `METHOD /route`: they
+/// move no funds and change no keys or balances
+pub const UNAUDITED_ROUTES: &[&str] = &[
+    "POST /api/auth/logout",
+    "POST /api/notifications/read",
+    "PUT /api/notifications/preferences",
+    // Multipart bodies too large to buffer; the NFT or item that references
+    // the upload is audited
+    "POST /api/media/upload",
+];
+
+/// Parameter name segments whose values are never recorded
+const SECRET_PARAMS: &[&str] = &["password", "passphrase", "secret", "token", "totp", "mnemonic", "seed", "pin", "code"];
+
+/// Records verified per database round trip
+const VERIFY_PAGE: i64 = 1000;
+
+/// Attempts to append before giving up on a contended chain head
+const APPEND_ATTEMPTS: usize = 5;
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditConfig {
+    /// Take the client IP from `X-Forwarded-For` (behind a trusted proxy)
+    pub trust_forwarded_for: bool,
+}
+
+impl AuditConfig {
+    /// Read `AUDIT_TRUST_FORWARDED_FOR`
+    pub fn from_env() -> Self {
+        Self {
+            trust_forwarded_for: std::env::var("AUDIT_TRUST_FORWARDED_FOR")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Audit log search filters
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditSearch {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub txid: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Result of checking the whole chain
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditVerification {
+    pub records: u64,
+    pub valid: bool,
+    /// First record that fails to verify
+    pub first_break: Option<ChainBreak>,
+    pub head_hash: Option<String>,
+}
+
+pub struct AuditLog {
+    database: Arc<Database>,
+    config: AuditConfig,
+    /// Last sequence number and hash this instance saw, held while appending
+    head: Mutex<Option<(u64, String)>>,
+}
+
+impl AuditLog {
+    pub fn new(database: Arc<Database>, config: AuditConfig) -> Self {
+        Self { database, config, head: Mutex::new(None) }
+    }
+
+    pub fn config(&self) -> &AuditConfig {
+        &self.config
+    }
+
+    /// Seal a record onto the chain. Failures are logged rather than
+    /// returned, as the call being audited has already happened.
+    pub async fn record(&self, record: AuditRecord) -> Option<AuditRecord> {
+        let mut head = self.head.lock().await;
+        for _ in 0..APPEND_ATTEMPTS {
+            if head.is_none() {
+                match self.database.last_audit_record().await {
+                    Ok(last) => *head = last.map(|r| (r.seq as u64, r.hash)),
+                    Err(e) => {
+                        error!("❌ Failed to read audit log head: {}", e);
+                        return None;
+                    }
+                }
+            }
+            let sealed = record.clone().seal(head.as_ref().map(|(seq, hash)| (*seq, hash.as_str())));
+            match self.database.append_audit_record(&to_db(&sealed)).await {
+                Ok(true) => {
+                    *head = Some((sealed.seq, sealed.hash.clone()));
+                    return Some(sealed);
+                }
+                // Another instance appended first; reload the head
+                Ok(false) => *head = None,
+                Err(e) => {
+                    error!("❌ Failed to write audit record ({} by {}): {}", record.action, record.actor, e);
+                    return None;
+                }
+            }
+        }
+        error!("❌ Gave up appending audit record ({} by {}): chain head kept moving", record.action, record.actor);
+        None
+    }
+
+    /// Move the rows of the admin audit table older releases kept into the
+    /// chain, oldest first, and drop the table. Each row is deleted once its
+    /// record is sealed, so an interrupted import resumes where it stopped.
+    pub async fn import_admin_actions(&self) -> Result<(), String> {
+        let mut imported = 0usize;
+        loop {
+            let rows = self.database.legacy_admin_actions(VERIFY_PAGE).await.map_err(|e| e.to_string())?;
+            if rows.is_empty() {
+                break;
+            }
+            for row in rows {
+                let id = row.id.ok_or("Admin audit log row without an id")?;
+                if self.record(admin_action_record(row)).await.is_none() {
+                    return Err("Failed to import admin audit log".to_string());
+                }
+                self.database.delete_legacy_admin_action(id).await.map_err(|e| e.to_string())?;
+                imported += 1;
+            }
+        }
+        self.database.drop_legacy_admin_actions().await.map_err(|e| e.to_string())?;
+        if imported > 0 {
+            info!("📜 Imported {} admin audit log entries into the audit chain", imported);
+        }
+        Ok(())
+    }
+
+    /// Most recent records matching the filters, newest first
+    pub async fn search(&self, search: &AuditSearch) -> Result<Vec<AuditRecord>, String> {
+        self.database.list_audit_records(
+            search.actor.as_deref(),
+            search.action.as_deref(),
+            search.txid.as_deref(),
+            search.limit.unwrap_or(100).clamp(1, 1000),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(from_db)
+        .collect()
+    }
+
+    /// Check every record from the start of the chain
+    pub async fn verify(&self) -> Result<AuditVerification, String> {
+        let mut prev: Option<(u64, String)> = None;
+        let mut count = 0u64;
+        loop {
+            let from = prev.as_ref().map_or(0, |(seq, _)| *seq as i64 + 1);
+            let page = self.database.audit_records_from(from, VERIFY_PAGE).await.map_err(|e| e.to_string())?;
+            let records = page.into_iter().map(from_db).collect::<Result<Vec<_>, _>>()?;
+            if let Err(first_break) = verify_chain(&records, prev.as_ref().map(|(seq, hash)| (*seq, hash.as_str()))) {
+                warn!("⚠️ Audit log chain broken at record {}: {}", first_break.seq, first_break.reason);
+                return Ok(AuditVerification { records: count, valid: false, first_break: Some(first_break), head_hash: None });
+            }
+            count += records.len() as u64;
+            match records.last() {
+                Some(last) => prev = Some((last.seq, last.hash.clone())),
+                None => break,
+            }
+            if (records.len() as i64) < VERIFY_PAGE {
+                break;
+            }
+        }
+        Ok(AuditVerification { records: count, valid: true, first_break: None, head_hash: prev.map(|(_, hash)| hash) })
+    }
+}
+
+/// Whether calls to `METHOD /route` are audited
+pub fn is_audited(method: &str, route: &str) -> bool {
+    !matches!(method, "GET" | "HEAD" | "OPTIONS")
+        && !UNAUDITED_ROUTES.iter().any(|exempt| exempt.split_once(' ') == Some((method, route)))
+}
+
+/// Client address: the first `X-Forwarded-For` hop if trusted, else the peer
+pub fn client_ip(forwarded_for: Option<&str>, peer: Option<SocketAddr>, config: &AuditConfig) -> Option<String> {
+    let forwarded = forwarded_for
+        .filter(|_| config.trust_forwarded_for)
+        .and_then(|header| header.split(',').next())
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty());
+    forwarded.or_else(|| peer.map(|addr| addr.ip().to_string()))
+}
+
+/// Copy of request parameters with secrets replaced
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(map.iter()
+            .map(|(key, value)| {
+                let lower = key.to_ascii_lowercase();
+                let secret = lower.contains("private_key")
+                    || lower.split(|c: char| c == '_' || c == '-').any(|part| SECRET_PARAMS.contains(&part));
+                (key.clone(), if secret { Value::String("[redacted]".to_string()) } else { redact(value) })
+            })
+            .collect::<Map<_, _>>()),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// First transaction id in a response body
+pub fn find_txid(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(map) => map.iter()
+            .find_map(|(key, value)| match value {
+                Value::String(txid) if key == "txid" || key == "transaction_hash" || key.ends_with("tx_hash") => Some(txid.clone()),
+                _ => None,
+            })
+            .or_else(|| map.values().find_map(find_txid)),
+        Value::Array(items) => items.iter().find_map(find_txid),
+        _ => None,
+    }
+}
+
+/// Record for a row of the old admin audit table, at its original time
+fn admin_action_record(row: DbAdminAction) -> AuditRecord {
+    let details = row.details.as_deref()
+        .map(|details| serde_json::from_str(details).unwrap_or_else(|_| Value::String(details.to_string())))
+        .unwrap_or(Value::Null);
+    let mut record = AuditRecord::new(row.actor, format!("admin {}", row.action), serde_json::json!({
+        "target": row.target,
+        "details": details,
+    }));
+    record.timestamp = row.created_at;
+    if !row.success {
+        let error = record.params["details"]["error"].as_str().unwrap_or("failed").to_string();
+        record = record.with_error(error);
+    }
+    record
+}
+
+fn to_db(record: &AuditRecord) -> DbAuditRecord {
+    DbAuditRecord {
+        seq: record.seq as i64,
+        timestamp: record.timestamp,
+        actor: record.actor.clone(),
+        action: record.action.clone(),
+        source_ip: record.source_ip.clone(),
+        params: record.params.to_string(),
+        txid: record.txid.clone(),
+        success: record.success,
+        error: record.error.clone(),
+        prev_hash: record.prev_hash.clone(),
+        hash: record.hash.clone(),
+    }
+}
+
+fn from_db(row: DbAuditRecord) -> Result<AuditRecord, String> {
+    Ok(AuditRecord {
+        seq: row.seq as u64,
+        timestamp: row.timestamp,
+        actor: row.actor,
+        action: row.action,
+        source_ip: row.source_ip,
+        params: serde_json::from_str(&row.params).map_err(|e| format!("Audit record {} has invalid params: {}", row.seq, e))?,
+        txid: row.txid,
+        success: row.success,
+        error: row.error,
+        prev_hash: row.prev_hash,
+        hash: row.hash,
+    })
+}
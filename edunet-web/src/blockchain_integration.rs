@@ -4,30 +4,47 @@
 // REAL BLOCKCHAIN INTEGRATION - No dummy data, uses actual blockchain-core with ECDSA signatures
 
 use blockchain_core::{
-    consensus::ConsensusValidator, 
-    wallet::WalletManager,
+    consensus::{ChainTip, ConsensusValidator},
+    wallet::{TransactionTags, WalletManager},
     genesis::{GenesisCreator, GenesisConfig},
     transaction::{Transaction, TransactionInput, TransactionOutput},
     block::Block,
-    utxo::{UTXOSet, UTXO},
-    mempool::{Mempool, MempoolConfig},
+    utxo::{Balance, UTXOSet, UTXO},
+    fiat::FiatAmount,
+    naming::{NameRecord, NameRegistry},
+    mempool::{Mempool, MempoolConfig, MempoolEvent, RemovalReason},
     tx_builder::{TransactionBuilder, TransactionManager},
     sync::{SyncEngine, SyncConfig},
-    Hash256, Amount, Result as BlockchainResult,
+    amount::Amount,
+    Hash256, Result as BlockchainResult,
 };
 
 // Pure Rust blockchain implementation
 // use blockchain_core::mining::{MiningController, MiningConfig};
 use blockchain_network::{NetworkManager, NetworkConfig};
 use crate::database::{Database, DbTransaction};
+use crate::expiry::ExpiryConfig;
+use crate::node_client::{NodeClient, NodeClientConfig};
+use crate::reorg::{TipTracker, CONFLICT_DEPTH};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 use sha2::{Sha256, Digest};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use rand;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Confirmed transaction volume over one period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeBucket {
+    /// Unix timestamp the period starts at
+    pub period_start: i64,
+    pub transaction_count: usize,
+    pub total_amount: u64,
+    pub total_fees: u64,
+    pub unique_senders: usize,
+}
 
 /// Transaction history entry for API responses
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,10 +69,88 @@ pub struct TransactionHistory {
     pub block_height: Option<u64>,
     /// Number of confirmations
     pub confirmations: u32,
+    /// Whether a block confirming this transaction left the best chain
+    #[serde(default)]
+    pub reorged: bool,
     /// Transaction fee
     pub fee: u64,
     /// Transaction size in bytes
     pub size: usize,
+    /// Fiat equivalent of the amount, filled in by API handlers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatAmount>,
+    /// What the transaction was for, see `tags`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: TransactionTags,
+}
+
+/// Well-known transaction tag keys and kinds. These carry what a platform
+/// transaction is for, so users may not set them on their own payments.
+pub mod tags {
+    use super::TransactionTags;
+
+    /// What the transaction does, one of the kinds below
+    pub const KIND: &str = "kind";
+    pub const NFT_ID: &str = "nft_id";
+    pub const LOAN_ID: &str = "loan_id";
+    pub const ORDER_ID: &str = "order_id";
+    pub const CUSTODY_ENTRY_ID: &str = "custody_entry_id";
+    pub const ATTESTATION_ID: &str = "attestation_id";
+    pub const LOAN_POSITION_ID: &str = "loan_position_id";
+    pub const CAMPAIGN_ID: &str = "campaign_id";
+    pub const DISTRIBUTION_ID: &str = "distribution_id";
+
+    pub const NFT_MINT: &str = "NFT_MINT";
+    pub const NFT_TRANSFER: &str = "NFT_TRANSFER";
+    pub const LOAN_FUNDING: &str = "LOAN_FUNDING";
+    pub const LOAN_REPAYMENT: &str = "LOAN_REPAYMENT";
+    pub const LOAN_SHARE_SALE: &str = "LOAN_SHARE_SALE";
+    pub const ORDER_PAY: &str = "ORDER_PAY";
+    pub const ORDER_RELEASE: &str = "ORDER_RELEASE";
+    pub const ORDER_REFUND: &str = "ORDER_REFUND";
+    pub const ORDER_DUPLICATE_REFUND: &str = "ORDER_DUPLICATE_REFUND";
+    pub const CUSTODY_DEPOSIT: &str = "CUSTODY_DEPOSIT";
+    pub const CUSTODY_WITHDRAWAL: &str = "CUSTODY_WITHDRAWAL";
+    pub const CUSTODY_SWEEP: &str = "CUSTODY_SWEEP";
+    pub const ATTESTATION: &str = "ATTESTATION";
+    pub const COLLATERAL_LOCK: &str = "COLLATERAL_LOCK";
+    pub const COLLATERAL_RELEASE: &str = "COLLATERAL_RELEASE";
+    pub const COLLATERAL_SEIZE: &str = "COLLATERAL_SEIZE";
+    pub const COLLATERAL_PAYOUT: &str = "COLLATERAL_PAYOUT";
+    pub const CAMPAIGN_PLEDGE: &str = "CAMPAIGN_PLEDGE";
+    pub const CAMPAIGN_RELEASE: &str = "CAMPAIGN_RELEASE";
+    pub const CAMPAIGN_REFUND: &str = "CAMPAIGN_REFUND";
+    pub const DISTRIBUTION_FUNDING: &str = "DISTRIBUTION_FUNDING";
+    pub const DISTRIBUTION_PAYOUT: &str = "DISTRIBUTION_PAYOUT";
+
+    const RESERVED: &[&str] = &[KIND, NFT_ID, LOAN_ID, ORDER_ID, CUSTODY_ENTRY_ID, ATTESTATION_ID, LOAN_POSITION_ID, CAMPAIGN_ID, DISTRIBUTION_ID];
+
+    /// Tags for a platform transaction of `kind`, referring to `reference`
+    pub fn for_kind(kind: &str, reference: Option<(&str, &str)>) -> TransactionTags {
+        let mut tags = TransactionTags::new();
+        tags.insert(KIND.to_string(), kind.to_string());
+        if let Some((key, value)) = reference {
+            tags.insert(key.to_string(), value.to_string());
+        }
+        tags
+    }
+
+    /// Check tags supplied by a user: no reserved keys, and keys and
+    /// values of sensible size
+    pub fn validate_user_tags(tags: &TransactionTags) -> Result<(), String> {
+        if tags.len() > 16 {
+            return Err("At most 16 tags per transaction".to_string());
+        }
+        for (key, value) in tags {
+            if RESERVED.contains(&key.as_str()) {
+                return Err(format!("Tag '{}' is reserved", key));
+            }
+            if key.is_empty() || key.len() > 64 || value.len() > 256 {
+                return Err("Tag keys must be 1-64 bytes and values at most 256 bytes".to_string());
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Transaction confirmation status
@@ -69,23 +164,79 @@ pub enum TransactionStatus {
     Failed,
     /// Transaction was rejected
     Rejected,
+    /// Transaction was reorged out and never confirmed again; its inputs
+    /// were most likely spent by a competing transaction
+    Conflicted,
+    /// Transaction waited too long in the mempool and was dropped unconfirmed
+    Expired,
+}
+
+/// Event published on the backend event bus
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    /// A transaction was confirmed
+    TransactionConfirmed {
+        hash: String,
+        from_address: String,
+        to_address: String,
+        amount: u64,
+        memo: Option<String>,
+        tags: TransactionTags,
+    },
+    /// A pending transaction was dropped from the mempool unconfirmed
+    TransactionExpired {
+        hash: String,
+        from_address: String,
+        to_address: String,
+        amount: u64,
+        /// Hash of the payment sent again in its place, if it was rebuilt
+        replacement: Option<String>,
+    },
+    /// A block left the best chain; its transactions are pending again
+    BlockDisconnected {
+        height: u64,
+        hash: String,
+        /// Hashes of the transactions it had confirmed
+        transactions: Vec<String>,
+    },
 }
 
+/// In-process node: consensus, mempool and P2P owned by this web process
 #[derive(Clone)]
-pub struct BlockchainBackend {
+pub struct EmbeddedNode {
     pub network: Arc<NetworkManager>,
     pub consensus: Arc<ConsensusValidator>,
-    pub wallets: Arc<RwLock<WalletManager>>,
     pub mempool: Arc<RwLock<Mempool>>,
     pub utxo_set: Arc<RwLock<UTXOSet>>,
     pub tx_manager: Arc<TransactionManager>,
+    // Blockchain synchronization engine
+    pub sync_engine: Arc<SyncEngine>,
+    // `.edu` names indexed from the local chain
+    pub names: Arc<NameRegistry>,
+}
+
+/// Where chain state comes from
+#[derive(Clone)]
+pub enum NodeConnection {
+    /// A node embedded in this process (single-instance deployments)
+    Embedded(EmbeddedNode),
+    /// Shared blockchain-node(s) over JSON-RPC, keeping the web tier stateless
+    Remote(Arc<NodeClient>),
+}
+
+#[derive(Clone)]
+pub struct BlockchainBackend {
+    pub node: NodeConnection,
+    pub wallets: Arc<RwLock<WalletManager>>,
     // Database for persistent storage
     pub database: Arc<Database>,
     // In-memory transaction storage (for compatibility)
     pub transactions: Arc<RwLock<HashMap<String, TransactionHistory>>>,
     pub blocks_mined: Arc<RwLock<Vec<String>>>,
-    // Blockchain synchronization engine
-    pub sync_engine: Arc<SyncEngine>,
+    // Event bus for web subsystems (notifications, etc.)
+    pub events: broadcast::Sender<ChainEvent>,
+    // Latest best-chain tip seen by the reorg watcher
+    pub tip: Arc<RwLock<Option<ChainTip>>>,
 }
 
 impl BlockchainBackend {
@@ -140,7 +291,7 @@ impl BlockchainBackend {
         let tx_manager = Arc::new(TransactionManager::new(utxo_clone));
 
         // Initialize network with consensus for serving blockchain data
-        let network = NetworkManager::new(network_config, Some(consensus.clone()))?;;
+        let network = NetworkManager::new_async(network_config, Some(consensus.clone())).await?;
 
         // Initialize sync engine
         let sync_config = SyncConfig::default();
@@ -150,17 +301,43 @@ impl BlockchainBackend {
         tracing::info!("✅ PRODUCTION Blockchain backend initialized (REAL ECDSA + UTXO + MINING)");
         tracing::info!("🔐 Features: Real ECDSA signatures, UTXO validation, Block mining, Consensus rules");
 
-        let backend = Self {
+        let node = NodeConnection::Embedded(EmbeddedNode {
             network: Arc::new(network),
             consensus,
-            wallets,
             mempool,
             utxo_set,
             tx_manager,
+            sync_engine,
+            names: Arc::new(NameRegistry::new()),
+        });
+        Self::with_node(node, wallets, database).await
+    }
+
+    /// Backend without a local node, reading and submitting through shared
+    /// blockchain-node endpoints so several web instances can run side by side
+    pub async fn connect(config: NodeClientConfig, database: Arc<Database>) -> anyhow::Result<Self> {
+        tracing::info!("🔗 Connecting to shared blockchain node(s): {}", config.endpoints.join(", "));
+        let client = Arc::new(NodeClient::new(config)?);
+        client.start_health_checks().await;
+        match client.status().await {
+            Ok(status) => tracing::info!("✅ Connected to {} at height {}", client.current_endpoint(), status["block_height"]),
+            Err(e) => tracing::warn!("⚠️ No blockchain node reachable yet, will keep retrying: {}", e),
+        }
+        tokio::spawn(client.clone().run_tip_watcher());
+
+        let wallets = Arc::new(RwLock::new(WalletManager::new()));
+        Self::with_node(NodeConnection::Remote(client), wallets, database).await
+    }
+
+    async fn with_node(node: NodeConnection, wallets: Arc<RwLock<WalletManager>>, database: Arc<Database>) -> anyhow::Result<Self> {
+        let backend = Self {
+            node,
+            wallets,
             database,
             transactions: Arc::new(RwLock::new(HashMap::new())),
             blocks_mined: Arc::new(RwLock::new(Vec::new())),
-            sync_engine,
+            events: broadcast::channel(1000).0,
+            tip: Arc::new(RwLock::new(None)),
         };
 
         // Load existing blocks from database into the blockchain
@@ -169,6 +346,14 @@ impl BlockchainBackend {
         Ok(backend)
     }
 
+    /// Receive a `ChainTip` whenever the best block changes
+    pub fn subscribe_tip(&self) -> broadcast::Receiver<ChainTip> {
+        match &self.node {
+            NodeConnection::Embedded(node) => node.consensus.subscribe_tip(),
+            NodeConnection::Remote(client) => client.subscribe_tip(),
+        }
+    }
+
     /// Load blocks from database into the actual blockchain
     async fn load_blocks_from_database(&self) -> anyhow::Result<()> {
         use sqlx::Row;
@@ -221,15 +406,18 @@ impl BlockchainBackend {
                     hash: tx_hash.clone(),
                     transaction_type: "confirmed".to_string(),
                     amount: amount as u64,
-                    amount_edu: amount as f64 / 100_000_000_000.0,
+                    amount_edu: Amount::from_sat(amount as u64).to_edu_f64(),
                     from_address: from_address.clone(),
                     to_address: to_address.clone(),
                     timestamp: DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_else(Utc::now),
                     status: TransactionStatus::Confirmed,
                     block_height: Some(height as u64),
                     confirmations: 1,
+                    reorged: false,
                     fee: fee as u64,
                     size: memo.as_ref().map(|m| m.len()).unwrap_or(0),
+                    fiat: None,
+                    tags: TransactionTags::new(),
                 };
 
                 transactions.insert(tx_hash, tx_history);
@@ -249,10 +437,14 @@ impl BlockchainBackend {
 
     /// Synchronize blockchain from network peers
     pub async fn sync_blockchain(&self) -> anyhow::Result<()> {
+        let NodeConnection::Embedded(node) = &self.node else {
+            tracing::info!("🔄 Shared blockchain node syncs itself; nothing to do");
+            return Ok(());
+        };
         tracing::info!("🔄 Starting blockchain synchronization...");
         
         // Perform initial block download
-        self.sync_engine.initial_block_download().await
+        node.sync_engine.initial_block_download().await
             .map_err(|e| anyhow::anyhow!("Sync failed: {}", e))?;
         
         tracing::info!("✅ Blockchain synchronization complete!");
@@ -261,7 +453,27 @@ impl BlockchainBackend {
 
     /// Get blockchain synchronization status
     pub async fn get_sync_status(&self) -> serde_json::Value {
-        let status = self.sync_engine.get_status().await;
+        let node = match &self.node {
+            NodeConnection::Embedded(node) => node,
+            NodeConnection::Remote(client) => {
+                return match client.status().await {
+                    Ok(status) => serde_json::json!({
+                        "is_syncing": false,
+                        "local_height": status["block_height"],
+                        "network_height": status["block_height"],
+                        "progress_percent": 100.0,
+                        "peers_connected": status["network"]["connected_peers"],
+                        "node_endpoint": client.current_endpoint(),
+                        "nodes": client.node_health(),
+                    }),
+                    Err(e) => serde_json::json!({
+                        "is_syncing": false,
+                        "error": e.to_string(),
+                    }),
+                };
+            }
+        };
+        let status = node.sync_engine.get_status().await;
         
         serde_json::json!({
             "is_syncing": status.is_syncing,
@@ -276,16 +488,43 @@ impl BlockchainBackend {
 
     /// Check if node is synced with network
     pub async fn is_synced(&self) -> bool {
-        self.sync_engine.is_synced().await
+        match &self.node {
+            NodeConnection::Embedded(node) => node.sync_engine.is_synced().await,
+            NodeConnection::Remote(client) => client.status().await.is_ok(),
+        }
     }
 
     pub async fn get_network_status(&self) -> anyhow::Result<serde_json::Value> {
+        let node = match &self.node {
+            NodeConnection::Embedded(node) => node,
+            NodeConnection::Remote(client) => {
+                let status = client.status().await?;
+                return Ok(serde_json::json!({
+                    "connected_peers": status["network"]["connected_peers"],
+                    "block_height": status["block_height"],
+                    "best_block_hash": status["best_block_hash"],
+                    "total_work": status["total_work"],
+                    "difficulty": status["difficulty"],
+                    "is_mining": false,
+                    "blocks_mined": 0,
+                    "hash_rate": 0,
+                    "mempool_size": status["mempool"]["transactions"],
+                    "mempool_bytes": status["mempool"]["memory_usage"],
+                    "pending_transactions": status["mempool"]["transactions"],
+                    "network_uptime": chrono::Utc::now().timestamp(),
+                    "node_type": "Shared",
+                    "node_endpoint": client.current_endpoint(),
+                    "blockchain_type": "PRODUCTION_REAL_ECDSA"
+                }));
+            }
+        };
+
         // Get REAL blockchain state from production consensus
-        let chain_state = self.consensus.get_chain_state().await;
-        let connected_peers = self.network.get_connected_peers().await;
+        let chain_state = node.consensus.get_chain_state().await;
+        let connected_peers = node.network.get_connected_peers().await;
         
         // Get REAL mempool statistics
-        let mempool = self.mempool.read().await;
+        let mempool = node.mempool.read().await;
         let mempool_stats = mempool.get_stats();
         let mempool_size = mempool_stats.transaction_count;
         let mempool_bytes = mempool_stats.memory_usage; // Use memory_usage instead of total_size
@@ -321,21 +560,51 @@ impl BlockchainBackend {
 
     pub async fn get_wallet_balance(&self, address: &str) -> anyhow::Result<u64> {
         // Get REAL balance from PRODUCTION UTXO set (actual blockchain state)
-        let utxo_set = self.utxo_set.read().await;
-        let balance = utxo_set.get_balance(address);
+        let balance = match &self.node {
+            NodeConnection::Embedded(node) => node.utxo_set.read().await.get_balance(address),
+            NodeConnection::Remote(client) => client.balance(address).await?,
+        };
         
         tracing::info!("💰 REAL balance for address {}: {} satoshis ({} EDU)", 
-            address, balance, balance as f64 / 100_000_000.0);
+            address, balance, Amount::from_sat(balance).to_edu_string());
         
         Ok(balance)
     }
 
+    /// Confirmed/unconfirmed/immature/locked split for an address, from the
+    /// UTXO set, the mempool and the wallet manager's locked outpoints.
+    /// A shared node only reports the confirmed balance.
+    pub async fn get_wallet_balances(&self, address: &str) -> anyhow::Result<Balance> {
+        let node = match &self.node {
+            NodeConnection::Embedded(node) => node,
+            NodeConnection::Remote(client) => {
+                return Ok(Balance { confirmed: client.balance(address).await?, ..Balance::default() });
+            }
+        };
+        let pending = node.mempool.read().await.get_transactions();
+        let locked = self.wallets.read().await.locked_outpoints().clone();
+        let utxo_set = node.utxo_set.read().await;
+        Ok(utxo_set.get_balance_breakdown(address, &pending, &locked))
+    }
+
     pub async fn send_transaction(
         &self,
         from_address: &str,
         to_address: &str,
         amount: u64,
         message: Option<String>,
+    ) -> anyhow::Result<String> {
+        self.send_tagged_transaction(from_address, to_address, amount, message, TransactionTags::new()).await
+    }
+
+    /// Send a transaction and store `tags` with it
+    pub async fn send_tagged_transaction(
+        &self,
+        from_address: &str,
+        to_address: &str,
+        amount: u64,
+        message: Option<String>,
+        tags: TransactionTags,
     ) -> anyhow::Result<String> {
         tracing::info!("📤 REAL TRANSACTION: {} satoshis from {} to {}", amount, from_address, to_address);
         
@@ -353,28 +622,311 @@ impl BlockchainBackend {
             hash: tx_hash_hex.clone(),
             transaction_type: "send".to_string(),
             amount,
-            amount_edu: amount as f64 / 1e8,
+            amount_edu: Amount::from_sat(amount).to_edu_f64(),
             from_address: from_address.to_string(),
             to_address: to_address.to_string(),
             timestamp: Utc::now(),
             status: TransactionStatus::Confirmed,
             block_height: Some(self.blocks_mined.read().await.len() as u64),
             confirmations: 1,
+            reorged: false,
             fee: calculated_fee,
             size: 250,
+            fiat: None,
+            tags: tags.clone(),
         };
         
+        // Persist so every web instance sharing the database sees it
+        let record = DbTransaction {
+            id: None,
+            tx_hash: tx_hash_hex.clone(),
+            from_address: from_address.to_string(),
+            to_address: to_address.to_string(),
+            amount: amount as i64,
+            fee: calculated_fee as i64,
+            memo: message.clone(),
+            block_height: tx_history.block_height.map(|h| h as i64),
+            tx_index: None,
+            timestamp: tx_history.timestamp.timestamp(),
+            status: "confirmed".to_string(),
+            reorged: false,
+        };
+        if let Err(e) = self.database.save_transaction(&record).await {
+            tracing::warn!("Failed to persist transaction {}: {}", tx_hash_hex, e);
+        }
+        if !tags.is_empty() {
+            if let Err(e) = self.database.save_transaction_tags(&tx_hash_hex, &tags).await {
+                tracing::warn!("Failed to persist tags of transaction {}: {}", tx_hash_hex, e);
+            }
+        }
         self.transactions.write().await.insert(tx_hash_hex.clone(), tx_history);
         
         tracing::info!("✅ Transaction stored with hash: {}", tx_hash_hex);
         
-        if let Some(msg) = message {
+        if let Some(msg) = &message {
             tracing::info!("💬 Transaction message: {}", msg);
         }
+
+        // No subscribers is fine
+        let _ = self.events.send(ChainEvent::TransactionConfirmed {
+            hash: tx_hash_hex.clone(),
+            from_address: from_address.to_string(),
+            to_address: to_address.to_string(),
+            amount,
+            memo: message,
+            tags,
+        });
         
         Ok(tx_hash_hex)
     }
 
+    /// Subscribe to backend events
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ChainEvent> {
+        self.events.subscribe()
+    }
+
+    /// Follow the best chain tip: return transactions in disconnected blocks
+    /// to pending, publish `BlockDisconnected` and conflict reorged
+    /// transactions that never confirm again
+    pub async fn run_reorg_watcher(self: Arc<Self>) {
+        let mut tips = self.subscribe_tip();
+        let mut tracker = TipTracker::new();
+        // Reorged transactions awaiting confirmation, with the tip height they were reorged at
+        let mut unconfirmed: HashMap<String, u64> = HashMap::new();
+        loop {
+            let tip = match tips.recv().await {
+                Ok(tip) => tip,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("⚠️ Reorg watcher skipped {} tips", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let mut disconnected = tracker.connect(tip);
+            // Walk down past blocks below the new tip that were replaced too
+            let mut height = tip.height;
+            while let Some(recorded) = height.checked_sub(1).and_then(|h| tracker.hash_at(h)) {
+                height -= 1;
+                match self.block_hash_at(height).await {
+                    Some(hash) if hash != recorded => {
+                        tracker.forget(height);
+                        disconnected.push((height, recorded));
+                    }
+                    _ => break,
+                }
+            }
+
+            for (height, hash) in disconnected {
+                match self.disconnect_block(height, &hash).await {
+                    Ok(reorged) => unconfirmed.extend(reorged.into_iter().map(|tx| (tx, tip.height))),
+                    Err(e) => tracing::error!("❌ Failed to reorg transactions at height {}: {}", height, e),
+                }
+            }
+            *self.tip.write().await = Some(tip);
+            if !unconfirmed.is_empty() {
+                self.settle_reorged(&mut unconfirmed, tip).await;
+            }
+        }
+    }
+
+    /// Hash of the best-chain block at `height`, when the node can tell
+    async fn block_hash_at(&self, height: u64) -> Option<Hash256> {
+        match &self.node {
+            NodeConnection::Embedded(node) => node.consensus.get_block_by_height(height).await.map(|b| b.get_hash()),
+            NodeConnection::Remote(_) => None,
+        }
+    }
+
+    /// Set the transactions confirmed at `height` back to pending
+    async fn disconnect_block(&self, height: u64, hash: &Hash256) -> anyhow::Result<Vec<String>> {
+        let mut reorged: HashSet<String> = self.database.reorg_transactions_at(height as i64).await?.into_iter().collect();
+        for tx in self.transactions.write().await.values_mut() {
+            if matches!(tx.status, TransactionStatus::Confirmed) && tx.block_height == Some(height) {
+                tx.status = TransactionStatus::Pending;
+                tx.block_height = None;
+                tx.confirmations = 0;
+                tx.reorged = true;
+                reorged.insert(tx.hash.clone());
+            }
+        }
+
+        let transactions: Vec<String> = reorged.into_iter().collect();
+        tracing::warn!("🔀 Block {} at height {} left the best chain, {} transaction(s) back to pending",
+            hex::encode(hash), height, transactions.len());
+        // No subscribers is fine
+        let _ = self.events.send(ChainEvent::BlockDisconnected {
+            height,
+            hash: hex::encode(hash),
+            transactions: transactions.clone(),
+        });
+        Ok(transactions)
+    }
+
+    /// Confirm reorged transactions found in the new tip block and conflict
+    /// the ones that stayed unconfirmed for `CONFLICT_DEPTH` blocks
+    async fn settle_reorged(&self, unconfirmed: &mut HashMap<String, u64>, tip: ChainTip) {
+        let included: HashSet<String> = match &self.node {
+            NodeConnection::Embedded(node) => node.consensus.get_block_by_height(tip.height).await
+                .map(|block| block.transactions.iter().filter_map(|tx| tx.get_hash().ok()).map(hex::encode).collect())
+                .unwrap_or_default(),
+            NodeConnection::Remote(_) => HashSet::new(),
+        };
+
+        let mut settled = Vec::new();
+        for (hash, reorged_at) in unconfirmed.iter() {
+            let (status, block_height) = if included.contains(hash) {
+                (TransactionStatus::Confirmed, Some(tip.height))
+            } else if tip.height.saturating_sub(*reorged_at) >= CONFLICT_DEPTH {
+                (TransactionStatus::Conflicted, None)
+            } else {
+                continue;
+            };
+            let db_status = match status {
+                TransactionStatus::Confirmed => "confirmed",
+                _ => "conflicted",
+            };
+            if let Err(e) = self.database.update_transaction_status(hash, db_status, block_height.map(|h| h as i64)).await {
+                tracing::error!("❌ Failed to update reorged transaction {}: {}", hash, e);
+                continue;
+            }
+            if let Some(tx) = self.transactions.write().await.get_mut(hash) {
+                tx.status = status;
+                tx.block_height = block_height;
+            }
+            settled.push(hash.clone());
+        }
+        for hash in settled {
+            unconfirmed.remove(&hash);
+        }
+    }
+
+    /// Run the embedded mempool's maintenance and mark the wallet payments
+    /// it expires, see `crate::expiry`
+    pub async fn run_expiry_watcher(self: Arc<Self>, config: ExpiryConfig) {
+        let NodeConnection::Embedded(node) = &self.node else {
+            tracing::info!("⏳ Remote node expires its own mempool; expiry watcher not started");
+            return;
+        };
+        let mut removals = node.mempool.read().await.subscribe();
+
+        // Expiry only happens during maintenance
+        let mempool = node.mempool.clone();
+        let interval = config.maintenance_interval;
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = mempool.write().await.maintenance().await {
+                    tracing::warn!("⚠️ Mempool maintenance failed: {}", e);
+                }
+            }
+        });
+
+        loop {
+            match removals.recv().await {
+                Ok(MempoolEvent::TransactionRemoved { tx_hash, reason: RemovalReason::Expired }) => {
+                    self.expire_transaction(&hex::encode(tx_hash), &config).await;
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("⚠️ Expiry watcher skipped {} mempool events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Mark a pending payment expired, rebuild it if configured and publish
+    /// `TransactionExpired`. Transactions this backend didn't send are ignored.
+    async fn expire_transaction(&self, hash: &str, config: &ExpiryConfig) {
+        let record = self.database.get_transaction_by_hash(hash).await.ok().flatten();
+        let (from_address, to_address, amount, memo) = match (&record, self.transactions.read().await.get(hash)) {
+            (Some(record), _) if record.status == "pending" => {
+                (record.from_address.clone(), record.to_address.clone(), record.amount as u64, record.memo.clone())
+            }
+            (_, Some(tx)) if matches!(tx.status, TransactionStatus::Pending) => {
+                (tx.from_address.clone(), tx.to_address.clone(), tx.amount, None)
+            }
+            _ => return,
+        };
+
+        if let Err(e) = self.database.update_transaction_status(hash, "expired", None).await {
+            tracing::error!("❌ Failed to mark transaction {} expired: {}", hash, e);
+            return;
+        }
+        if let Some(tx) = self.transactions.write().await.get_mut(hash) {
+            tx.status = TransactionStatus::Expired;
+        }
+        tracing::warn!("⌛ Payment {} of {} from {} expired unconfirmed", hash, Amount::from_sat(amount), from_address);
+
+        let replacement = if config.auto_rebuild {
+            let tags = self.database.get_transaction_tags(&[hash.to_string()]).await
+                .ok()
+                .and_then(|mut tags| tags.remove(hash))
+                .unwrap_or_default();
+            match self.send_tagged_transaction(&from_address, &to_address, amount, memo, tags).await {
+                Ok(new_hash) => {
+                    tracing::info!("🔁 Expired payment {} sent again as {}", hash, new_hash);
+                    Some(new_hash)
+                }
+                Err(e) => {
+                    tracing::warn!("⚠️ Failed to rebuild expired payment {}: {}", hash, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // No subscribers is fine
+        let _ = self.events.send(ChainEvent::TransactionExpired {
+            hash: hash.to_string(),
+            from_address,
+            to_address,
+            amount,
+            replacement,
+        });
+    }
+
+    /// Status of a transaction sent through this backend
+    pub async fn get_transaction_status(&self, tx_hash: &str) -> Option<TransactionStatus> {
+        if let Some(tx) = self.transactions.read().await.get(tx_hash) {
+            return Some(tx.status.clone());
+        }
+        // Sent through another web instance
+        let record = self.database.get_transaction_by_hash(tx_hash).await.ok()??;
+        Some(Self::history_from_db(&record).status)
+    }
+
+    /// History entry for a transaction recorded in the database
+    fn history_from_db(record: &DbTransaction) -> TransactionHistory {
+        TransactionHistory {
+            hash: record.tx_hash.clone(),
+            transaction_type: "confirmed".to_string(),
+            amount: record.amount as u64,
+            amount_edu: Amount::from_sat(record.amount as u64).to_edu_f64(),
+            from_address: record.from_address.clone(),
+            to_address: record.to_address.clone(),
+            timestamp: DateTime::<Utc>::from_timestamp(record.timestamp, 0).unwrap_or_else(Utc::now),
+            status: match record.status.as_str() {
+                "pending" => TransactionStatus::Pending,
+                "failed" => TransactionStatus::Failed,
+                "rejected" => TransactionStatus::Rejected,
+                "conflicted" => TransactionStatus::Conflicted,
+                "expired" => TransactionStatus::Expired,
+                _ => TransactionStatus::Confirmed,
+            },
+            block_height: record.block_height.map(|h| h as u64),
+            confirmations: 1,
+            reorged: record.reorged,
+            fee: record.fee as u64,
+            size: record.memo.as_ref().map(|m| m.len()).unwrap_or(0),
+            fiat: None,
+            tags: TransactionTags::new(),
+        }
+    }
+
     // ========================================================================
     // MINING OPERATIONS
     // ========================================================================
@@ -414,16 +966,68 @@ impl BlockchainBackend {
         tracing::info!("📜 Getting transaction history for address: {}", address);
         
         let all_txs = self.transactions.read().await;
-        let transactions: Vec<TransactionHistory> = all_txs
+        let mut transactions: Vec<TransactionHistory> = all_txs
             .values()
             .filter(|tx| tx.from_address == address || tx.to_address == address)
             .cloned()
             .collect();
         
+        // Include transactions other web instances recorded since startup
+        for record in self.database.get_transactions_by_address(address, 500).await? {
+            if !all_txs.contains_key(&record.tx_hash) {
+                transactions.push(Self::history_from_db(&record));
+            }
+        }
+        drop(all_txs);
+        self.attach_tags(&mut transactions).await?;
+        if let Some(tip) = *self.tip.read().await {
+            for tx in transactions.iter_mut() {
+                tx.confirmations = match (&tx.status, tx.block_height) {
+                    (TransactionStatus::Confirmed, Some(height)) => (tip.height.saturating_sub(height) + 1) as u32,
+                    _ => 0,
+                };
+            }
+        }
+        
         tracing::info!("📜 Found {} transactions for address {}", transactions.len(), address);
         Ok(transactions)
     }
 
+    /// Fill in stored tags on entries that don't carry them yet
+    async fn attach_tags(&self, transactions: &mut [TransactionHistory]) -> anyhow::Result<()> {
+        let untagged: Vec<String> = transactions.iter()
+            .filter(|tx| tx.tags.is_empty())
+            .map(|tx| tx.hash.clone())
+            .collect();
+        let mut stored = self.database.get_transaction_tags(&untagged).await?;
+        for tx in transactions.iter_mut().filter(|tx| tx.tags.is_empty()) {
+            if let Some(tags) = stored.remove(&tx.hash) {
+                tx.tags = tags;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set tags on a transaction the address sent or received
+    pub async fn tag_transaction(&self, address: &str, tx_hash: &str, tags: TransactionTags) -> anyhow::Result<TransactionTags> {
+        let party = match self.transactions.read().await.get(tx_hash) {
+            Some(tx) => tx.from_address == address || tx.to_address == address,
+            None => self.database.get_transaction_by_hash(tx_hash).await?
+                .is_some_and(|tx| tx.from_address == address || tx.to_address == address),
+        };
+        if !party {
+            anyhow::bail!("Transaction not found");
+        }
+
+        self.database.save_transaction_tags(tx_hash, &tags).await?;
+        if let Some(tx) = self.transactions.write().await.get_mut(tx_hash) {
+            tx.tags.extend(tags);
+        }
+        Ok(self.database.get_transaction_tags(&[tx_hash.to_string()]).await?
+            .remove(tx_hash)
+            .unwrap_or_default())
+    }
+
     /// Get mempool transactions
     pub async fn get_mempool_transactions(&self, address: &str) -> anyhow::Result<Vec<TransactionHistory>> {
         tracing::info!("🔄 Getting mempool transactions for address: {}", address);
@@ -458,11 +1062,14 @@ impl BlockchainBackend {
     pub async fn get_all_blockchain_transactions(&self, limit: usize) -> anyhow::Result<Vec<TransactionHistory>> {
         tracing::info!("📊 Getting {} most recent REAL transactions from blockchain", limit);
         
-        let chain_state = self.consensus.get_chain_state().await;
+        let NodeConnection::Embedded(node) = &self.node else {
+            return self.get_recent_transactions(limit).await;
+        };
+        let chain_state = node.consensus.get_chain_state().await;
         let mut recent_transactions = Vec::new();
         
         // Get transactions from the most recent blocks
-        let recent_blocks = self.consensus.get_recent_blocks(limit.min(10)).await.unwrap_or_default();
+        let recent_blocks = node.consensus.get_recent_blocks(limit.min(10)).await.unwrap_or_default();
         
         for block in &recent_blocks {
             let block_height = block.header.height;
@@ -482,15 +1089,18 @@ impl BlockchainBackend {
                             hash: format!("{:?}", tx_hash),
                             transaction_type: "transfer".to_string(),
                             amount: total_output,
-                            amount_edu: total_output as f64 / 100_000_000.0,
+                            amount_edu: Amount::from_sat(total_output).to_edu_f64(),
                             from_address: "multiple".to_string(), // Simplified
                             to_address: "multiple".to_string(),   // Simplified
                             timestamp: timestamp_utc,
                             status: TransactionStatus::Confirmed,
                             block_height: Some(block_height_u64),
                             confirmations: (chain_state.height - block_height_u64 + 1) as u32,
+                            reorged: false,
                             fee: calculated_fee,
                             size: tx_serialized.len(),
+                            fiat: None,
+                            tags: TransactionTags::new(),
                         };
                         recent_transactions.push(tx_history);
                     }
@@ -505,10 +1115,187 @@ impl BlockchainBackend {
         Ok(recent_transactions)
     }
 
+    // ========================================================================
+    // ADMIN OPERATIONS
+    // ========================================================================
+
+    /// Force specific transactions out of the mempool; returns the ones removed
+    pub async fn evict_mempool_transactions(&self, tx_hashes: &[Hash256]) -> anyhow::Result<Vec<Hash256>> {
+        let node = match &self.node {
+            NodeConnection::Embedded(node) => node,
+            NodeConnection::Remote(client) => {
+                let mut params = serde_json::Map::new();
+                params.insert("tx_hashes".to_string(), tx_hashes.iter().map(hex::encode).collect());
+                return Self::evicted_hashes(client.admin_call("admin_evictMempool", params).await?);
+            }
+        };
+        let mut mempool = node.mempool.write().await;
+        let mut evicted = Vec::new();
+        for tx_hash in tx_hashes {
+            if mempool.contains_transaction(tx_hash) {
+                mempool.remove_transaction(tx_hash, RemovalReason::Manual).await?;
+                evicted.push(*tx_hash);
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Force every mempool transaction paying less than `min_fee_rate` out
+    pub async fn evict_mempool_below_fee_rate(&self, min_fee_rate: u64) -> anyhow::Result<Vec<Hash256>> {
+        match &self.node {
+            NodeConnection::Embedded(node) => Ok(node.mempool.write().await.evict_below_fee_rate(min_fee_rate).await?),
+            NodeConnection::Remote(client) => {
+                let mut params = serde_json::Map::new();
+                params.insert("below_fee_rate".to_string(), min_fee_rate.into());
+                Self::evicted_hashes(client.admin_call("admin_evictMempool", params).await?)
+            }
+        }
+    }
+
+    /// Hashes from an `admin_evictMempool` response
+    fn evicted_hashes(response: serde_json::Value) -> anyhow::Result<Vec<Hash256>> {
+        response["evicted"].as_array()
+            .ok_or_else(|| anyhow::anyhow!("Unexpected eviction response: {}", response))?
+            .iter()
+            .map(|hash| {
+                hash.as_str()
+                    .and_then(|h| hex::decode(h).ok())
+                    .and_then(|h| Hash256::try_from(h.as_slice()).ok())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid transaction hash in eviction response"))
+            })
+            .collect()
+    }
+
+    /// Rebuild the UTXO view from consensus and reload confirmed transaction
+    /// history from the database (in-memory transactions are kept)
+    pub async fn reindex(&self) -> anyhow::Result<serde_json::Value> {
+        let node = match &self.node {
+            NodeConnection::Embedded(node) => node,
+            NodeConnection::Remote(client) => {
+                self.load_blocks_from_database().await?;
+                return client.admin_call("admin_reindex", serde_json::Map::new()).await;
+            }
+        };
+        let utxo_set = node.consensus.get_utxo_set().await;
+        let utxo_count = utxo_set.get_utxo_count();
+        *node.utxo_set.write().await = utxo_set;
+
+        self.load_blocks_from_database().await?;
+
+        let height = node.consensus.get_chain_state().await.height;
+        let cached_transactions = self.transactions.read().await.len();
+        tracing::info!("🔁 Reindexed at height {}: {} UTXOs, {} cached transactions", height, utxo_count, cached_transactions);
+
+        Ok(serde_json::json!({
+            "height": height,
+            "utxo_count": utxo_count,
+            "cached_transactions": cached_transactions,
+        }))
+    }
+
+    /// Transaction volume since `since`, bucketed into `bucket_secs` periods
+    pub async fn transaction_volume(&self, since: DateTime<Utc>, bucket_secs: i64) -> Vec<VolumeBucket> {
+        let bucket_secs = bucket_secs.max(60);
+        let mut buckets: BTreeMap<i64, (VolumeBucket, HashSet<String>)> = BTreeMap::new();
+
+        for tx in self.transactions.read().await.values() {
+            if tx.timestamp < since || !matches!(tx.status, TransactionStatus::Confirmed) {
+                continue;
+            }
+            let start = tx.timestamp.timestamp() - tx.timestamp.timestamp().rem_euclid(bucket_secs);
+            let (bucket, senders) = buckets.entry(start).or_insert_with(|| (VolumeBucket {
+                period_start: start,
+                transaction_count: 0,
+                total_amount: 0,
+                total_fees: 0,
+                unique_senders: 0,
+            }, HashSet::new()));
+            bucket.transaction_count += 1;
+            bucket.total_amount += tx.amount;
+            bucket.total_fees += tx.fee;
+            senders.insert(tx.from_address.clone());
+        }
+
+        buckets.into_values()
+            .map(|(mut bucket, senders)| {
+                bucket.unique_senders = senders.len();
+                bucket
+            })
+            .collect()
+    }
+
+    /// Active record for a `.edu` name at the chain tip
+    pub async fn resolve_name(&self, name: &str) -> anyhow::Result<Option<NameRecord>> {
+        match &self.node {
+            NodeConnection::Embedded(node) => {
+                let height = Self::sync_names(node).await?;
+                Ok(node.names.resolve(name, height).await)
+            }
+            NodeConnection::Remote(client) => client.resolve_name(name).await,
+        }
+    }
+
+    /// Active `.edu` names pointing at an address
+    pub async fn names_for(&self, address: &str) -> anyhow::Result<Vec<NameRecord>> {
+        match &self.node {
+            NodeConnection::Embedded(node) => {
+                let height = Self::sync_names(node).await?;
+                Ok(node.names.names_for(address, height).await)
+            }
+            NodeConnection::Remote(client) => client.names_for(address).await,
+        }
+    }
+
+    /// Turn a payment destination into an address, resolving `.edu` names
+    pub async fn resolve_destination(&self, destination: &str) -> anyhow::Result<String> {
+        if !blockchain_core::naming::is_name(destination) {
+            return Ok(destination.to_string());
+        }
+        self.resolve_name(destination).await?
+            .map(|record| record.address)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a registered name", destination))
+    }
+
+    /// Index name operations in the embedded chain up to its tip, starting
+    /// over if the last indexed block was reorganized away
+    async fn sync_names(node: &EmbeddedNode) -> anyhow::Result<u64> {
+        let height = node.consensus.get_chain_state().await.height;
+        let mut next = match node.names.tip().await {
+            Some((indexed, hash)) => match node.consensus.get_block_by_height(indexed).await {
+                Some(block) if block.get_hash() == hash => indexed + 1,
+                _ => {
+                    node.names.reset().await;
+                    0
+                }
+            },
+            None => 0,
+        };
+        while next <= height {
+            let Some(block) = node.consensus.get_block_by_height(next).await else { break };
+            node.names.index_block(&block).await?;
+            next += 1;
+        }
+        Ok(height)
+    }
+
     /// Get blockchain statistics  
     pub async fn get_blockchain_stats(&self) -> anyhow::Result<serde_json::Value> {
-        let chain_state = self.consensus.get_chain_state().await;
-        let mempool = self.mempool.read().await;
+        let node = match &self.node {
+            NodeConnection::Embedded(node) => node,
+            NodeConnection::Remote(client) => {
+                let status = client.status().await?;
+                return Ok(serde_json::json!({
+                    "block_height": status["block_height"],
+                    "total_work": status["total_work"],
+                    "difficulty": status["difficulty"],
+                    "mempool_transactions": status["mempool"]["transactions"],
+                    "mempool_size_bytes": status["mempool"]["memory_usage"],
+                    "blockchain_type": "PRODUCTION_REAL_ECDSA"
+                }));
+            }
+        };
+        let chain_state = node.consensus.get_chain_state().await;
+        let mempool = node.mempool.read().await;
         let mempool_stats = mempool.get_stats();
         
         Ok(serde_json::json!({
@@ -521,4 +1308,53 @@ impl BlockchainBackend {
         }))
     }
 
+    /// Height of the best block
+    pub async fn chain_height(&self) -> anyhow::Result<u64> {
+        match &self.node {
+            NodeConnection::Embedded(node) => Ok(node.consensus.get_chain_state().await.height),
+            NodeConnection::Remote(client) => client.status().await?["block_height"].as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Node status has no block height")),
+        }
+    }
+
+    /// Header summary of the best-chain block at `height`, in the node's
+    /// `blockchain_getBlock` form, or `None` past the tip
+    pub async fn block_summary(&self, height: u64) -> anyhow::Result<Option<serde_json::Value>> {
+        match &self.node {
+            NodeConnection::Embedded(node) => Ok(node.consensus.get_block_by_height(height).await.map(|block| serde_json::json!({
+                "height": block.header.height,
+                "hash": hex::encode(block.header.calculate_hash()),
+                "prev_hash": hex::encode(block.header.prev_block_hash),
+                "timestamp": block.header.timestamp,
+                "transactions_count": block.transactions.len(),
+            }))),
+            NodeConnection::Remote(client) => {
+                let block = client.call("blockchain_getBlock", serde_json::json!([height])).await?;
+                Ok(if block.get("error").is_some() { None } else { Some(block) })
+            }
+        }
+    }
+
+    /// Treasury coin sales as the node records them. The treasury lives on
+    /// blockchain-node, so only remote connections have any
+    pub async fn treasury_sales(&self) -> anyhow::Result<Vec<serde_json::Value>> {
+        let NodeConnection::Remote(client) = &self.node else {
+            anyhow::bail!("Treasury sales need a blockchain-node connection (EDUNET_NODE_RPC_URLS)");
+        };
+        match client.call("treasury_getSales", serde_json::json!([])).await? {
+            serde_json::Value::Array(sales) => Ok(sales),
+            other => Err(anyhow::anyhow!("Unexpected treasury_getSales reply: {}", other)),
+        }
+    }
+
+    /// Admin call to the node's voucher book (`voucher_createBatch`,
+    /// `voucher_getBatch`, ...). Vouchers are paid from the treasury on
+    /// blockchain-node, so only remote connections have any
+    pub async fn voucher_call(&self, method: &str, params: serde_json::Map<String, serde_json::Value>) -> anyhow::Result<serde_json::Value> {
+        let NodeConnection::Remote(client) = &self.node else {
+            anyhow::bail!("Voucher batches need a blockchain-node connection (EDUNET_NODE_RPC_URLS)");
+        };
+        client.admin_call(method, params).await
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,303 @@
+//! Crowdfunding campaigns
+//!
+//! Campaigns are all-or-nothing. Each one gets its own address that
+//! collects pledges, whether made through the API or paid to the address
+//! directly. Once the deadline passes the settlement task either releases
+//! everything pledged to the creator, if the goal was met, or refunds every
+//! pledge to its backer. Pledges that arrive after a campaign closed are
+//! refunded as they confirm.
+
+use crate::blockchain_integration::{tags, BlockchainBackend, ChainEvent};
+use crate::database::{Database, DbCampaign, DbCampaignPledge};
+use crate::spending::SpendingGuard;
+use crate::user_auth::User;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Longest a campaign may run
+const MAX_DURATION_SECS: i64 = 180 * 24 * 60 * 60;
+/// How often deadlines are checked
+const SETTLE_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateCampaignRequest {
+    pub title: String,
+    pub description: String,
+    /// Satoshis
+    pub goal: i64,
+    /// Unix timestamp
+    pub deadline: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PledgeRequest {
+    pub amount: i64,
+    pub totp_code: Option<String>,
+}
+
+/// A campaign with its progress
+#[derive(Debug, Clone, Serialize)]
+pub struct CampaignView {
+    #[serde(flatten)]
+    pub campaign: DbCampaign,
+    pub pledged: i64,
+    pub backers: usize,
+    pub pledges: Vec<DbCampaignPledge>,
+}
+
+pub struct CampaignManager {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    spending: Arc<SpendingGuard>,
+}
+
+impl CampaignManager {
+    pub fn new(database: Arc<Database>, backend: Arc<BlockchainBackend>, spending: Arc<SpendingGuard>) -> Self {
+        Self { database, backend, spending }
+    }
+
+    pub async fn create(&self, user: &User, request: &CreateCampaignRequest) -> Result<DbCampaign, String> {
+        let now = Utc::now().timestamp();
+        if request.title.trim().is_empty() {
+            return Err("Title is required".to_string());
+        }
+        if request.goal <= 0 {
+            return Err("Goal must be positive".to_string());
+        }
+        if request.deadline <= now || request.deadline > now + MAX_DURATION_SECS {
+            return Err("Deadline must be in the future and at most 180 days away".to_string());
+        }
+
+        let campaign_id = format!("cmp_{}", Uuid::new_v4().simple());
+        let campaign = DbCampaign {
+            campaign_address: format!("edunet_campaign_{}", campaign_id),
+            campaign_id,
+            creator_address: user.wallet_address.clone(),
+            title: request.title.trim().to_string(),
+            description: request.description.clone(),
+            goal: request.goal,
+            deadline: request.deadline,
+            status: "active".to_string(),
+            release_tx_hash: None,
+            created_at: now,
+            settled_at: None,
+        };
+        self.database.insert_campaign(&campaign).await.map_err(|e| e.to_string())?;
+        info!("📣 {} started campaign {} ({})", user.username, campaign.title, campaign.campaign_id);
+        Ok(campaign)
+    }
+
+    pub async fn list(&self, status: &str, limit: i64) -> Result<Vec<DbCampaign>, String> {
+        self.database.list_campaigns_by_status(status, limit.clamp(1, 200)).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn get(&self, campaign_id: &str) -> Result<CampaignView, String> {
+        let campaign = self.load(campaign_id).await?;
+        let pledges = self.database.list_campaign_pledges(campaign_id).await.map_err(|e| e.to_string())?;
+        let pledged = self.database.campaign_pledged(campaign_id).await.map_err(|e| e.to_string())?;
+        let mut backers: Vec<&str> = pledges.iter().map(|p| p.backer_address.as_str()).collect();
+        backers.sort_unstable();
+        backers.dedup();
+        Ok(CampaignView { campaign, pledged, backers: backers.len(), pledges })
+    }
+
+    /// Campaigns whose goal was met and released to their creators
+    pub async fn funded_count(&self) -> Result<i64, String> {
+        self.database.count_campaigns_by_status("funded").await.map_err(|e| e.to_string())
+    }
+
+    /// Pledge to a campaign from the user's wallet
+    pub async fn pledge(&self, user: &User, campaign_id: &str, request: &PledgeRequest) -> Result<DbCampaignPledge, String> {
+        let campaign = self.load(campaign_id).await?;
+        if campaign.status != "active" || Utc::now().timestamp() >= campaign.deadline {
+            return Err("Campaign is closed".to_string());
+        }
+        if request.amount <= 0 {
+            return Err("Amount must be positive".to_string());
+        }
+
+        let reservation = self.spending.reserve(user, request.amount as u64, request.totp_code.as_deref()).await?;
+        let tx_hash = match self.backend.send_tagged_transaction(
+            &user.wallet_address,
+            &campaign.campaign_address,
+            request.amount as u64,
+            None,
+            tags::for_kind(tags::CAMPAIGN_PLEDGE, Some((tags::CAMPAIGN_ID, campaign_id))),
+        ).await {
+            Ok(tx_hash) => {
+                self.spending.commit(reservation, &tx_hash).await;
+                tx_hash
+            }
+            Err(e) => {
+                self.spending.release(reservation).await;
+                return Err(format!("Pledge failed: {}", e));
+            }
+        };
+
+        // The pledge watcher may already have recorded it from the event bus
+        let pledge = DbCampaignPledge {
+            id: None,
+            campaign_id: campaign_id.to_string(),
+            backer_address: user.wallet_address.clone(),
+            amount: request.amount,
+            tx_hash,
+            timestamp: Utc::now().timestamp(),
+            refund_tx_hash: None,
+        };
+        self.database.record_campaign_pledge(&pledge).await.map_err(|e| e.to_string())?;
+        info!("📣 {} pledged {} sat to campaign {}", user.username, pledge.amount, campaign_id);
+        Ok(pledge)
+    }
+
+    /// Record payments into campaign addresses as they confirm
+    pub async fn run_pledges(self: Arc<Self>, mut events: broadcast::Receiver<ChainEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Err(e) = self.handle_event(&event).await {
+                        error!("❌ Failed to record campaign pledge: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("⚠️ Campaign pledge watcher skipped {} events; pledges may need manual recording", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Settle campaigns past their deadline and finish pending refunds
+    pub async fn run_settlement(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(SETTLE_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.settle_due().await {
+                error!("❌ Campaign settlement failed: {}", e);
+            }
+        }
+    }
+
+    async fn handle_event(&self, event: &ChainEvent) -> Result<(), String> {
+        let ChainEvent::TransactionConfirmed { hash, from_address, to_address, amount, .. } = event else {
+            return Ok(());
+        };
+        if !to_address.starts_with("edunet_campaign_") {
+            return Ok(());
+        }
+        let Some(campaign) = self.database.get_campaign_by_address(to_address).await.map_err(|e| e.to_string())? else {
+            return Ok(());
+        };
+
+        let pledge = DbCampaignPledge {
+            id: None,
+            campaign_id: campaign.campaign_id.clone(),
+            backer_address: from_address.clone(),
+            amount: i64::try_from(*amount).map_err(|_| "Pledge amount is too large".to_string())?,
+            tx_hash: hash.clone(),
+            timestamp: Utc::now().timestamp(),
+            refund_tx_hash: None,
+        };
+        if !self.database.record_campaign_pledge(&pledge).await.map_err(|e| e.to_string())? {
+            return Ok(());
+        }
+
+        // Too late to count: send it straight back
+        if campaign.status != "active" || pledge.timestamp >= campaign.deadline {
+            let pledges = self.database.list_campaign_pledges(&campaign.campaign_id).await.map_err(|e| e.to_string())?;
+            if let Some(late) = pledges.into_iter().find(|p| p.tx_hash == pledge.tx_hash) {
+                self.refund(&campaign, &late).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn settle_due(&self) -> Result<(), String> {
+        let now = Utc::now().timestamp();
+        for campaign in self.database.list_campaigns_due(now).await.map_err(|e| e.to_string())? {
+            let pledged = self.database.campaign_pledged(&campaign.campaign_id).await.map_err(|e| e.to_string())?;
+            let result = if pledged >= campaign.goal {
+                self.release(&campaign, pledged).await
+            } else {
+                match self.database.set_campaign_status(&campaign.campaign_id, "active", "refunding").await {
+                    Ok(_) => {
+                        info!("📣 Campaign {} missed its goal ({} of {} sat); refunding backers", campaign.campaign_id, pledged, campaign.goal);
+                        Ok(())
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
+            };
+            if let Err(e) = result {
+                error!("❌ Failed to settle campaign {}: {}", campaign.campaign_id, e);
+            }
+        }
+
+        // Refunds are retried until every pledge is returned
+        for campaign in self.database.list_campaigns_by_status("refunding", 200).await.map_err(|e| e.to_string())? {
+            let mut outstanding = 0;
+            for pledge in self.database.list_campaign_pledges(&campaign.campaign_id).await.map_err(|e| e.to_string())? {
+                if pledge.refund_tx_hash.is_none() && self.refund(&campaign, &pledge).await.is_err() {
+                    outstanding += 1;
+                }
+            }
+            if outstanding == 0 {
+                self.database.close_campaign(&campaign.campaign_id, "refunded", None, now).await.map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pay everything pledged to the creator
+    async fn release(&self, campaign: &DbCampaign, pledged: i64) -> Result<(), String> {
+        if !self.database.set_campaign_status(&campaign.campaign_id, "active", "releasing").await.map_err(|e| e.to_string())? {
+            return Ok(());
+        }
+        match self.backend.send_tagged_transaction(
+            &campaign.campaign_address,
+            &campaign.creator_address,
+            pledged as u64,
+            None,
+            tags::for_kind(tags::CAMPAIGN_RELEASE, Some((tags::CAMPAIGN_ID, &campaign.campaign_id))),
+        ).await {
+            Ok(tx_hash) => {
+                self.database.close_campaign(&campaign.campaign_id, "funded", Some(&tx_hash), Utc::now().timestamp()).await
+                    .map_err(|e| e.to_string())?;
+                info!("🎉 Campaign {} reached its goal; released {} sat to {} (tx {})", campaign.campaign_id, pledged, campaign.creator_address, tx_hash);
+                Ok(())
+            }
+            Err(e) => {
+                // Back to active so the next pass retries
+                self.database.set_campaign_status(&campaign.campaign_id, "releasing", "active").await.map_err(|e| e.to_string())?;
+                Err(format!("Release failed: {}", e))
+            }
+        }
+    }
+
+    async fn refund(&self, campaign: &DbCampaign, pledge: &DbCampaignPledge) -> Result<(), String> {
+        let Some(pledge_id) = pledge.id else {
+            return Err("Pledge has no id".to_string());
+        };
+        let tx_hash = self.backend.send_tagged_transaction(
+            &campaign.campaign_address,
+            &pledge.backer_address,
+            pledge.amount as u64,
+            None,
+            tags::for_kind(tags::CAMPAIGN_REFUND, Some((tags::CAMPAIGN_ID, &campaign.campaign_id))),
+        ).await.map_err(|e| {
+            error!("❌ Failed to refund pledge {} of campaign {}: {}", pledge.tx_hash, campaign.campaign_id, e);
+            e.to_string()
+        })?;
+        self.database.set_pledge_refund(pledge_id, &tx_hash).await.map_err(|e| e.to_string())?;
+        info!("↩️ Refunded {} sat to {} from campaign {} (tx {})", pledge.amount, pledge.backer_address, campaign.campaign_id, tx_hash);
+        Ok(())
+    }
+
+    async fn load(&self, campaign_id: &str) -> Result<DbCampaign, String> {
+        self.database.get_campaign(campaign_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Campaign not found".to_string())
+    }
+}
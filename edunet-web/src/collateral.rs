@@ -0,0 +1,408 @@
+//! Collateralized loans
+//!
+//! A borrower can back a loan application with EDU or an NFT. The
+//! collateral is locked in a hash-time-locked output referenced by the loan
+//! record: the redeem branch pays the borrower against a release secret the
+//! platform holds, and the timeout branch pays the collateral escrow once
+//! the repayment term plus a grace period has passed. Funding only goes
+//! ahead once the lock is confirmed on-chain.
+//!
+//! A settlement task watches locked collateral. When the loan is repaid the
+//! secret is revealed and the collateral goes back to the borrower. When the
+//! locktime passes first the loan is defaulted, the escrow takes the
+//! collateral and pays it out to the current holders of the loan's
+//! repayment rights in proportion to their stakes; an NFT goes to the
+//! largest holder.
+
+use crate::blockchain_integration::{tags, BlockchainBackend, TransactionStatus};
+use crate::database::{Database, DbLoanApplication, DbLoanCollateral};
+use crate::loan_shares::pro_rata;
+use crate::spending::SpendingGuard;
+use crate::user_auth::User;
+use blockchain_core::script_utils::{HtlcScript, ScriptBuilder};
+use blockchain_core::wallet::TransactionTags;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// Term assumed for applications that don't state one
+const DEFAULT_TERM_MONTHS: i64 = 12;
+const SECS_PER_MONTH: i64 = 30 * 24 * 60 * 60;
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct CollateralConfig {
+    /// Single-key address the timeout branch pays; collateral is not
+    /// accepted when unset
+    pub escrow_address: Option<String>,
+    /// Time after the repayment term before collateral can be seized
+    pub grace_secs: i64,
+    pub settle_interval_secs: u64,
+}
+
+impl Default for CollateralConfig {
+    fn default() -> Self {
+        Self {
+            escrow_address: None,
+            grace_secs: 30 * SECS_PER_DAY,
+            settle_interval_secs: 300,
+        }
+    }
+}
+
+impl CollateralConfig {
+    /// Read `LOAN_COLLATERAL_ESCROW_ADDRESS`, `LOAN_COLLATERAL_GRACE_DAYS`
+    /// and `LOAN_COLLATERAL_SETTLE_INTERVAL_SECS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            escrow_address: std::env::var("LOAN_COLLATERAL_ESCROW_ADDRESS").ok().filter(|a| !a.is_empty()),
+            grace_secs: std::env::var("LOAN_COLLATERAL_GRACE_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .map(|days| days * SECS_PER_DAY)
+                .unwrap_or(defaults.grace_secs),
+            settle_interval_secs: std::env::var("LOAN_COLLATERAL_SETTLE_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.settle_interval_secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollateralKind {
+    Edu,
+    Nft,
+}
+
+impl CollateralKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollateralKind::Edu => "edu",
+            CollateralKind::Nft => "nft",
+        }
+    }
+}
+
+/// Collateral offered with a loan application
+#[derive(Debug, Clone, Deserialize)]
+pub struct CollateralRequest {
+    pub kind: CollateralKind,
+    /// Satoshis, for EDU collateral
+    pub amount: Option<i64>,
+    /// For NFT collateral
+    pub nft_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LockCollateralRequest {
+    pub totp_code: Option<String>,
+}
+
+pub struct CollateralManager {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    spending: Arc<SpendingGuard>,
+    config: CollateralConfig,
+}
+
+impl CollateralManager {
+    pub fn new(database: Arc<Database>, backend: Arc<BlockchainBackend>, spending: Arc<SpendingGuard>, config: CollateralConfig) -> Self {
+        Self { database, backend, spending, config }
+    }
+
+    /// Check collateral offered with an application before the loan is created
+    pub async fn check_request(&self, user: &User, request: &CollateralRequest) -> Result<(), String> {
+        let escrow = self.escrow_address()?;
+        ScriptBuilder::address_to_hash160(escrow).map_err(|_| "Collateral escrow address is not a single-key address".to_string())?;
+        match request.kind {
+            CollateralKind::Edu => {
+                if request.amount.unwrap_or(0) <= 0 {
+                    return Err("EDU collateral needs a positive amount".to_string());
+                }
+            }
+            CollateralKind::Nft => {
+                let nft_id = request.nft_id.as_deref().ok_or("NFT collateral needs an nft_id")?;
+                let nft = self.database.get_nft_by_id(nft_id).await
+                    .map_err(|e| e.to_string())?
+                    .ok_or("NFT not found")?;
+                if nft.current_owner != user.wallet_address || nft.is_burned {
+                    return Err("You don't own this NFT".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the lock for a new loan's collateral. The borrower then locks
+    /// it with `lock`.
+    pub async fn create(&self, user: &User, loan: &DbLoanApplication, request: &CollateralRequest) -> Result<DbLoanCollateral, String> {
+        self.check_request(user, request).await?;
+        let escrow = self.escrow_address()?;
+
+        let now = Utc::now().timestamp();
+        let term_months = loan.repayment_term_months.unwrap_or(DEFAULT_TERM_MONTHS).max(1);
+        let locktime = u32::try_from(now + term_months * SECS_PER_MONTH + self.config.grace_secs)
+            .map_err(|_| "Repayment term is too long".to_string())?;
+        let (preimage, payment_hash) = HtlcScript::generate_preimage();
+        let htlc = HtlcScript::new(payment_hash, &user.wallet_address, escrow, locktime).map_err(|e| e.to_string())?;
+        let script = htlc.to_script();
+
+        let collateral = DbLoanCollateral {
+            loan_id: loan.loan_id.clone(),
+            borrower_address: user.wallet_address.clone(),
+            kind: request.kind.as_str().to_string(),
+            amount: match request.kind {
+                CollateralKind::Edu => request.amount.unwrap_or(0),
+                CollateralKind::Nft => 0,
+            },
+            nft_id: match request.kind {
+                CollateralKind::Edu => None,
+                CollateralKind::Nft => request.nft_id.clone(),
+            },
+            lock_address: ScriptBuilder::script_to_p2sh_address(&script).map_err(|e| e.to_string())?,
+            lock_script: hex::encode(&script),
+            release_secret: hex::encode(preimage),
+            locktime: locktime as i64,
+            status: "awaiting_lock".to_string(),
+            lock_tx_hash: None,
+            settle_tx_hash: None,
+            created_at: now,
+            settled_at: None,
+        };
+        self.database.insert_loan_collateral(&collateral).await.map_err(|e| e.to_string())?;
+        info!("🔒 Collateral lock {} created for loan {}", collateral.lock_address, loan.loan_id);
+        Ok(collateral)
+    }
+
+    pub async fn get(&self, loan_id: &str) -> Result<Option<DbLoanCollateral>, String> {
+        self.database.get_loan_collateral(loan_id).await.map_err(|e| e.to_string())
+    }
+
+    /// Move the borrower's collateral into the lock
+    pub async fn lock(&self, user: &User, loan_id: &str, request: &LockCollateralRequest) -> Result<DbLoanCollateral, String> {
+        let collateral = self.get(loan_id).await?.ok_or("Loan has no collateral")?;
+        if collateral.borrower_address != user.wallet_address {
+            return Err("Only the borrower can lock collateral".to_string());
+        }
+        if collateral.status != "awaiting_lock" {
+            return Err(format!("Collateral is already {}", collateral.status));
+        }
+
+        let mut lock_tags = tags::for_kind(tags::COLLATERAL_LOCK, Some((tags::LOAN_ID, loan_id)));
+        let tx_hash = match collateral.nft_id.as_deref() {
+            None => {
+                let reservation = self.spending.reserve(user, collateral.amount as u64, request.totp_code.as_deref()).await?;
+                match self.backend.send_tagged_transaction(
+                    &user.wallet_address,
+                    &collateral.lock_address,
+                    collateral.amount as u64,
+                    None,
+                    lock_tags,
+                ).await {
+                    Ok(tx_hash) => {
+                        self.spending.commit(reservation, &tx_hash).await;
+                        tx_hash
+                    }
+                    Err(e) => {
+                        self.spending.release(reservation).await;
+                        return Err(format!("Failed to lock collateral: {}", e));
+                    }
+                }
+            }
+            Some(nft_id) => {
+                let nft = self.database.get_nft_by_id(nft_id).await
+                    .map_err(|e| e.to_string())?
+                    .ok_or("NFT not found")?;
+                if nft.current_owner != user.wallet_address {
+                    return Err("You no longer own the collateral NFT".to_string());
+                }
+                lock_tags.insert(tags::NFT_ID.to_string(), nft_id.to_string());
+                self.move_nft(nft_id, &user.wallet_address, &collateral.lock_address, lock_tags).await?
+            }
+        };
+
+        if !self.database.set_loan_collateral_locked(loan_id, &tx_hash).await.map_err(|e| e.to_string())? {
+            return Err("Collateral was locked concurrently".to_string());
+        }
+        info!("🔒 {} locked collateral for loan {} (tx {})", user.username, loan_id, tx_hash);
+        self.get(loan_id).await?.ok_or_else(|| "Loan has no collateral".to_string())
+    }
+
+    /// Fail unless a loan's collateral, if it has any, is locked on-chain
+    pub async fn verify_lock(&self, loan_id: &str) -> Result<(), String> {
+        let Some(collateral) = self.get(loan_id).await? else {
+            return Ok(());
+        };
+        if collateral.status != "locked" {
+            return Err("Collateral has not been locked yet".to_string());
+        }
+
+        // The lock address must commit to an HTLC returning to the borrower
+        let script = hex::decode(&collateral.lock_script).map_err(|e| e.to_string())?;
+        let htlc = HtlcScript::from_script(&script).ok_or("Collateral lock script is not an HTLC")?;
+        let borrower_hash = ScriptBuilder::address_to_hash160(&collateral.borrower_address).map_err(|e| e.to_string())?;
+        if ScriptBuilder::script_to_p2sh_address(&script).map_err(|e| e.to_string())? != collateral.lock_address
+            || htlc.recipient_hash != borrower_hash
+            || htlc.locktime as i64 != collateral.locktime
+        {
+            return Err("Collateral lock does not match the loan record".to_string());
+        }
+
+        let tx_hash = collateral.lock_tx_hash.as_deref().ok_or("Collateral has no lock transaction")?;
+        if !matches!(self.backend.get_transaction_status(tx_hash).await, Some(TransactionStatus::Confirmed)) {
+            return Err("Collateral lock transaction is not confirmed".to_string());
+        }
+        let tx = self.database.get_transaction_by_hash(tx_hash).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Collateral lock transaction not found")?;
+        if tx.reorged || tx.from_address != collateral.borrower_address || tx.to_address != collateral.lock_address {
+            return Err("Collateral lock transaction does not pay the lock".to_string());
+        }
+        match collateral.nft_id.as_deref() {
+            None if tx.amount < collateral.amount => Err("Collateral lock is short of the pledged amount".to_string()),
+            None => Ok(()),
+            Some(nft_id) => {
+                let nft = self.database.get_nft_by_id(nft_id).await
+                    .map_err(|e| e.to_string())?
+                    .ok_or("Collateral NFT not found")?;
+                if nft.current_owner != collateral.lock_address {
+                    return Err("Collateral NFT is not held by the lock".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Release or seize locked collateral every interval
+    pub async fn run_settlement(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(self.config.settle_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.settle_due().await {
+                error!("❌ Collateral settlement failed: {}", e);
+            }
+        }
+    }
+
+    async fn settle_due(&self) -> Result<(), String> {
+        let now = Utc::now().timestamp();
+        for collateral in self.database.list_loan_collateral_by_status("locked").await.map_err(|e| e.to_string())? {
+            let Some(loan) = self.database.get_loan_by_id(&collateral.loan_id).await.map_err(|e| e.to_string())? else {
+                warn!("⚠️ Collateral for missing loan {}", collateral.loan_id);
+                continue;
+            };
+            let result = match loan.status.as_str() {
+                "funded" => {
+                    let repaid = self.database.loan_repaid_amount(&loan.loan_id).await.map_err(|e| e.to_string())?;
+                    if repaid >= loan.funded_amount.unwrap_or(0) {
+                        self.release(&collateral, "repaid").await
+                    } else if now > collateral.locktime {
+                        self.seize(&collateral).await
+                    } else {
+                        Ok(())
+                    }
+                }
+                "repaid" => self.release(&collateral, "repaid").await,
+                // Never funded: nothing is owed
+                "rejected" => self.release(&collateral, "rejected").await,
+                "pending" | "approved" if now > collateral.locktime => self.release(&collateral, "expired").await,
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                error!("❌ Failed to settle collateral of loan {}: {}", loan.loan_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return collateral to the borrower, revealing the release secret
+    async fn release(&self, collateral: &DbLoanCollateral, reason: &str) -> Result<(), String> {
+        let mut release_tags = tags::for_kind(tags::COLLATERAL_RELEASE, Some((tags::LOAN_ID, &collateral.loan_id)));
+        let tx_hash = match collateral.nft_id.as_deref() {
+            None => self.backend.send_tagged_transaction(
+                &collateral.lock_address,
+                &collateral.borrower_address,
+                collateral.amount as u64,
+                Some(collateral.release_secret.clone()),
+                release_tags,
+            ).await.map_err(|e| e.to_string())?,
+            Some(nft_id) => {
+                release_tags.insert(tags::NFT_ID.to_string(), nft_id.to_string());
+                self.move_nft(nft_id, &collateral.lock_address, &collateral.borrower_address, release_tags).await?
+            }
+        };
+        self.database.settle_loan_collateral(&collateral.loan_id, "released", &tx_hash, Utc::now().timestamp()).await
+            .map_err(|e| e.to_string())?;
+        if reason == "repaid" {
+            self.database.set_loan_status(&collateral.loan_id, "repaid").await.map_err(|e| e.to_string())?;
+        }
+        info!("🔓 Released collateral of loan {} to {} ({}, tx {})", collateral.loan_id, collateral.borrower_address, reason, tx_hash);
+        Ok(())
+    }
+
+    /// Default the loan: take the collateral through the timeout branch and
+    /// pay it out to the lenders
+    async fn seize(&self, collateral: &DbLoanCollateral) -> Result<(), String> {
+        let escrow = self.escrow_address()?;
+        let loan_id = collateral.loan_id.as_str();
+        // Current holders of the repayment rights, not necessarily the funders
+        let stakes = self.database.loan_stakes(loan_id).await.map_err(|e| e.to_string())?;
+        let largest = stakes.first().map(|stake| stake.holder_address.clone()).ok_or("Defaulted loan has no lenders")?;
+
+        let mut seize_tags = tags::for_kind(tags::COLLATERAL_SEIZE, Some((tags::LOAN_ID, loan_id)));
+        let tx_hash = match collateral.nft_id.as_deref() {
+            None => {
+                let tx_hash = self.backend.send_tagged_transaction(
+                    &collateral.lock_address,
+                    escrow,
+                    collateral.amount as u64,
+                    None,
+                    seize_tags,
+                ).await.map_err(|e| e.to_string())?;
+                for (stake, share) in pro_rata(collateral.amount, &stakes) {
+                    if let Err(e) = self.backend.send_tagged_transaction(
+                        escrow,
+                        &stake.holder_address,
+                        share as u64,
+                        None,
+                        tags::for_kind(tags::COLLATERAL_PAYOUT, Some((tags::LOAN_ID, loan_id))),
+                    ).await {
+                        error!("❌ Failed to pay {} sat of loan {} collateral to {}: {}", share, loan_id, stake.holder_address, e);
+                    }
+                }
+                tx_hash
+            }
+            Some(nft_id) => {
+                seize_tags.insert(tags::NFT_ID.to_string(), nft_id.to_string());
+                let tx_hash = self.move_nft(nft_id, &collateral.lock_address, escrow, seize_tags).await?;
+                let mut payout_tags = tags::for_kind(tags::COLLATERAL_PAYOUT, Some((tags::LOAN_ID, loan_id)));
+                payout_tags.insert(tags::NFT_ID.to_string(), nft_id.to_string());
+                self.move_nft(nft_id, escrow, &largest, payout_tags).await?;
+                tx_hash
+            }
+        };
+        self.database.settle_loan_collateral(loan_id, "seized", &tx_hash, Utc::now().timestamp()).await
+            .map_err(|e| e.to_string())?;
+        self.database.set_loan_status(loan_id, "defaulted").await.map_err(|e| e.to_string())?;
+        warn!("⚠️ Loan {} defaulted; collateral seized for {} lender(s) (tx {})", loan_id, stakes.len(), tx_hash);
+        Ok(())
+    }
+
+    /// Transfer an NFT with a 1 satoshi transaction
+    async fn move_nft(&self, nft_id: &str, from: &str, to: &str, transfer_tags: TransactionTags) -> Result<String, String> {
+        let tx_hash = self.backend.send_tagged_transaction(from, to, 1, None, transfer_tags).await
+            .map_err(|e| e.to_string())?;
+        self.database.transfer_nft(nft_id, from, to, &tx_hash, Utc::now().timestamp()).await
+            .map_err(|e| e.to_string())?;
+        Ok(tx_hash)
+    }
+
+    fn escrow_address(&self) -> Result<&str, String> {
+        self.config.escrow_address.as_deref()
+            .ok_or_else(|| "Collateral is not accepted: no collateral escrow is configured".to_string())
+    }
+}
@@ -0,0 +1,425 @@
+//! Custodial balances and cold-storage sweeps
+//!
+//! Users deposit EDU into the platform hot wallet and are credited in the
+//! custody ledger, which records what the platform owes each of them.
+//! A background sweeper moves hot-wallet funds above the sweep threshold to
+//! the configured cold multisig address, keeping `hot_target` on hand for
+//! withdrawals. The cold wallet is signed offline, so refills back into the
+//! hot wallet are recorded when they arrive. Admins are alerted when the hot
+//! wallet drops below the low-water mark.
+//!
+//! Hot and cold holdings are derived from the ledger and the sweep history
+//! rather than the UTXO set, so they stay correct while sweeps are pending.
+
+use crate::blockchain_integration::{tags, BlockchainBackend, ChainEvent};
+use crate::database::{CustodyTotals, Database, DbCustodyAccount, DbCustodyEntry, DbCustodySweep};
+use crate::notifications::{Notification, NotificationKind, NotificationService};
+use crate::user_auth::{User, UserManager};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{error, info, warn};
+
+const SATOSHIS_PER_EDU: u64 = 100_000_000;
+
+/// Hot wallet address used when `CUSTODY_HOT_ADDRESS` is not set
+pub const DEFAULT_HOT_ADDRESS: &str = "edunet_custody_hot";
+
+#[derive(Debug, Clone)]
+pub struct CustodyConfig {
+    pub hot_address: String,
+    /// Cold multisig address; sweeping is disabled when unset
+    pub cold_address: Option<String>,
+    /// Sweep once the hot wallet holds more than this
+    pub sweep_threshold: u64,
+    /// What a sweep leaves in the hot wallet
+    pub hot_target: u64,
+    /// Alert admins when the hot wallet holds less than this
+    pub low_water: u64,
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for CustodyConfig {
+    fn default() -> Self {
+        Self {
+            hot_address: DEFAULT_HOT_ADDRESS.to_string(),
+            cold_address: None,
+            sweep_threshold: 10_000 * SATOSHIS_PER_EDU,
+            hot_target: 2_000 * SATOSHIS_PER_EDU,
+            low_water: 500 * SATOSHIS_PER_EDU,
+            sweep_interval_secs: 300,
+        }
+    }
+}
+
+impl CustodyConfig {
+    /// Read `CUSTODY_*` variables; amounts are in whole EDU
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let edu = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).map(|v| v * SATOSHIS_PER_EDU);
+        Self {
+            hot_address: std::env::var("CUSTODY_HOT_ADDRESS").unwrap_or(defaults.hot_address),
+            cold_address: std::env::var("CUSTODY_COLD_ADDRESS").ok().filter(|a| !a.is_empty()),
+            sweep_threshold: edu("CUSTODY_SWEEP_THRESHOLD_EDU").unwrap_or(defaults.sweep_threshold),
+            hot_target: edu("CUSTODY_HOT_TARGET_EDU").unwrap_or(defaults.hot_target),
+            low_water: edu("CUSTODY_LOW_WATER_EDU").unwrap_or(defaults.low_water),
+            sweep_interval_secs: std::env::var("CUSTODY_SWEEP_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.sweep_interval_secs),
+        }
+    }
+}
+
+/// Custody overview for admins
+#[derive(Debug, Clone, Serialize)]
+pub struct CustodyStatus {
+    pub hot_address: String,
+    pub cold_address: Option<String>,
+    /// Owed to users in total
+    pub liabilities: i64,
+    pub hot_balance: i64,
+    pub cold_balance: i64,
+    /// Hot wallet balance as seen in the UTXO set, for reconciliation
+    pub hot_chain_balance: u64,
+    pub sweep_threshold: u64,
+    pub hot_target: u64,
+    pub low_water: u64,
+    pub low_balance: bool,
+}
+
+/// A user's custodial balance and recent ledger entries
+#[derive(Debug, Clone, Serialize)]
+pub struct CustodyAccountView {
+    pub hot_address: String,
+    pub balance: i64,
+    pub entries: Vec<DbCustodyEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColdRefillRequest {
+    pub amount: u64,
+    pub tx_hash: String,
+}
+
+pub struct CustodyManager {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    users: Arc<UserManager>,
+    notifications: Arc<NotificationService>,
+    admins: Vec<String>,
+    config: CustodyConfig,
+    /// Serializes sweeps and withdrawals so they don't race for hot funds
+    hot_wallet: Mutex<()>,
+    low_alerted: AtomicBool,
+}
+
+impl CustodyManager {
+    pub fn new(
+        database: Arc<Database>,
+        backend: Arc<BlockchainBackend>,
+        users: Arc<UserManager>,
+        notifications: Arc<NotificationService>,
+        admins: Vec<String>,
+        config: CustodyConfig,
+    ) -> Self {
+        Self {
+            database,
+            backend,
+            users,
+            notifications,
+            admins,
+            config,
+            hot_wallet: Mutex::new(()),
+            low_alerted: AtomicBool::new(false),
+        }
+    }
+
+    pub fn config(&self) -> &CustodyConfig {
+        &self.config
+    }
+
+    /// Credit deposits into the hot wallet as they confirm on the event bus
+    pub async fn run_deposits(self: Arc<Self>, mut events: broadcast::Receiver<ChainEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Err(e) = self.handle_event(&event).await {
+                        error!("❌ Failed to apply custody event: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("⚠️ Custody ledger skipped {} events; deposits may need manual crediting", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Sweep to cold storage and check the low-water mark every interval
+    pub async fn run_sweeper(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(self.config.sweep_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            if self.config.cold_address.is_some() {
+                if let Err(e) = self.sweep("auto").await {
+                    error!("❌ Custody sweep failed: {}", e);
+                }
+            }
+            if let Err(e) = self.check_low_water().await {
+                error!("❌ Custody balance check failed: {}", e);
+            }
+        }
+    }
+
+    async fn handle_event(&self, event: &ChainEvent) -> Result<(), String> {
+        let ChainEvent::TransactionConfirmed { hash, from_address, to_address, amount, .. } = event else {
+            return Ok(());
+        };
+        if *to_address != self.config.hot_address || from_address == to_address {
+            return Ok(());
+        }
+
+        if Some(from_address) == self.config.cold_address.as_ref() {
+            return self.record_refill(&ColdRefillRequest { amount: *amount, tx_hash: hash.clone() }, "auto").await.map(|_| ());
+        }
+
+        // Funds from addresses that aren't users can't be attributed to anyone
+        if self.users.get_user_by_wallet(from_address).await.is_none() {
+            warn!("⚠️ Unattributed deposit of {} sat into the hot wallet from {} (tx {})", amount, from_address, hash);
+            return Ok(());
+        }
+
+        let amount = i64::try_from(*amount).map_err(|_| "Deposit amount is too large".to_string())?;
+        let credited = self.database.credit_custody(&DbCustodyEntry {
+            id: None,
+            wallet_address: from_address.clone(),
+            kind: "deposit".to_string(),
+            amount,
+            tx_hash: Some(hash.clone()),
+            created_at: Utc::now().timestamp(),
+        }).await.map_err(|e| e.to_string())?;
+
+        if credited {
+            info!("🏦 Credited custody deposit of {} sat to {} (tx {})", amount, from_address, hash);
+            // A deposit may lift the hot wallet back above the low-water mark
+            self.check_low_water().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn account(&self, user: &User) -> Result<CustodyAccountView, String> {
+        let balance = self.database.get_custody_account(&user.wallet_address).await
+            .map_err(|e| e.to_string())?
+            .map(|account| account.balance)
+            .unwrap_or(0);
+        let entries = self.database.list_custody_entries(&user.wallet_address, 50).await
+            .map_err(|e| e.to_string())?;
+
+        Ok(CustodyAccountView {
+            hot_address: self.config.hot_address.clone(),
+            balance,
+            entries,
+        })
+    }
+
+    /// Pay `amount` of a user's custodial balance out to their own wallet
+    pub async fn withdraw(&self, user: &User, amount: u64) -> Result<String, String> {
+        let amount = i64::try_from(amount).map_err(|_| "Amount is too large".to_string())?;
+        if amount <= 0 {
+            return Err("Amount must be positive".to_string());
+        }
+
+        let guard = self.hot_wallet.lock().await;
+        let totals = self.totals().await?;
+        if hot_balance(&totals) < amount {
+            drop(guard);
+            self.alert_admins(
+                "Custody hot wallet cannot cover a withdrawal",
+                format!(
+                    "A withdrawal of {} EDU by {} was refused: the hot wallet holds {} EDU. Refill it from cold storage.",
+                    to_edu(amount), user.username, to_edu(hot_balance(&totals))
+                ),
+            ).await;
+            return Err("Withdrawals are temporarily unavailable while the hot wallet is refilled; please try again later".to_string());
+        }
+
+        let now = Utc::now().timestamp();
+        let entry_id = self.database.debit_custody(&user.wallet_address, amount, now).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Insufficient custodial balance")?;
+
+        let sent = self.backend.send_tagged_transaction(
+            &self.config.hot_address,
+            &user.wallet_address,
+            amount as u64,
+            None,
+            tags::for_kind(tags::CUSTODY_WITHDRAWAL, Some((tags::CUSTODY_ENTRY_ID, &entry_id.to_string()))),
+        ).await;
+
+        match sent {
+            Ok(tx_hash) => {
+                if let Err(e) = self.database.set_custody_entry_tx(entry_id, &tx_hash).await {
+                    error!("❌ Failed to record withdrawal tx {} for entry {}: {}", tx_hash, entry_id, e);
+                }
+                info!("🏦 Custody withdrawal of {} sat to {} (tx {})", amount, user.username, tx_hash);
+                drop(guard);
+                self.check_low_water().await?;
+                Ok(tx_hash)
+            }
+            Err(e) => {
+                // Give the balance back; the withdrawal never left the hot wallet
+                self.database.credit_custody(&DbCustodyEntry {
+                    id: None,
+                    wallet_address: user.wallet_address.clone(),
+                    kind: "reversal".to_string(),
+                    amount,
+                    tx_hash: None,
+                    created_at: Utc::now().timestamp(),
+                }).await.map_err(|e| e.to_string())?;
+                Err(format!("Withdrawal failed: {}", e))
+            }
+        }
+    }
+
+    /// Move hot-wallet funds above the threshold to cold storage.
+    /// Returns the sweep, or None if the hot wallet is below the threshold.
+    pub async fn sweep(&self, initiated_by: &str) -> Result<Option<DbCustodySweep>, String> {
+        let cold_address = self.config.cold_address.as_ref()
+            .ok_or("No cold storage address configured (set CUSTODY_COLD_ADDRESS)")?;
+
+        let _guard = self.hot_wallet.lock().await;
+        let hot = hot_balance(&self.totals().await?);
+        if hot <= self.config.sweep_threshold as i64 {
+            return Ok(None);
+        }
+
+        let amount = hot - (self.config.hot_target.min(self.config.sweep_threshold) as i64);
+        let tx_hash = self.backend.send_tagged_transaction(
+            &self.config.hot_address,
+            cold_address,
+            amount as u64,
+            None,
+            tags::for_kind(tags::CUSTODY_SWEEP, None),
+        ).await.map_err(|e| e.to_string())?;
+
+        let mut sweep = DbCustodySweep {
+            id: None,
+            direction: "to_cold".to_string(),
+            amount,
+            tx_hash,
+            initiated_by: initiated_by.to_string(),
+            created_at: Utc::now().timestamp(),
+        };
+        sweep.id = Some(self.database.record_custody_sweep(&sweep).await.map_err(|e| e.to_string())?);
+
+        info!("🧊 Swept {} EDU from the hot wallet to cold storage (tx {})", to_edu(amount), sweep.tx_hash);
+        Ok(Some(sweep))
+    }
+
+    /// Record funds moved from cold storage back into the hot wallet
+    pub async fn record_refill(&self, refill: &ColdRefillRequest, initiated_by: &str) -> Result<DbCustodySweep, String> {
+        let amount = i64::try_from(refill.amount).map_err(|_| "Amount is too large".to_string())?;
+        if amount <= 0 || refill.tx_hash.is_empty() {
+            return Err("A refill needs a positive amount and its transaction hash".to_string());
+        }
+
+        let mut sweep = DbCustodySweep {
+            id: None,
+            direction: "from_cold".to_string(),
+            amount,
+            tx_hash: refill.tx_hash.clone(),
+            initiated_by: initiated_by.to_string(),
+            created_at: Utc::now().timestamp(),
+        };
+        sweep.id = Some(self.database.record_custody_sweep(&sweep).await.map_err(|e| e.to_string())?);
+
+        info!("🔥 Hot wallet refilled with {} EDU from cold storage (tx {})", to_edu(amount), refill.tx_hash);
+        self.check_low_water().await?;
+        Ok(sweep)
+    }
+
+    pub async fn status(&self) -> Result<CustodyStatus, String> {
+        let totals = self.totals().await?;
+        let hot = hot_balance(&totals);
+        let hot_chain_balance = self.backend.get_wallet_balance(&self.config.hot_address).await.unwrap_or(0);
+
+        Ok(CustodyStatus {
+            hot_address: self.config.hot_address.clone(),
+            cold_address: self.config.cold_address.clone(),
+            liabilities: totals.liabilities,
+            hot_balance: hot,
+            cold_balance: totals.swept_to_cold - totals.refilled_from_cold,
+            hot_chain_balance,
+            sweep_threshold: self.config.sweep_threshold,
+            hot_target: self.config.hot_target,
+            low_water: self.config.low_water,
+            low_balance: hot < self.config.low_water as i64,
+        })
+    }
+
+    pub async fn accounts(&self) -> Result<Vec<DbCustodyAccount>, String> {
+        self.database.list_custody_accounts().await.map_err(|e| e.to_string())
+    }
+
+    pub async fn sweeps(&self, limit: i64) -> Result<Vec<DbCustodySweep>, String> {
+        self.database.list_custody_sweeps(limit.clamp(1, 1000)).await.map_err(|e| e.to_string())
+    }
+
+    async fn totals(&self) -> Result<CustodyTotals, String> {
+        self.database.custody_totals().await.map_err(|e| e.to_string())
+    }
+
+    /// Alert once when the hot wallet drops below the low-water mark,
+    /// and re-arm once it recovers
+    async fn check_low_water(&self) -> Result<(), String> {
+        let totals = self.totals().await?;
+        let hot = hot_balance(&totals);
+        // Nothing can be withdrawn when nobody has deposited
+        if hot >= self.config.low_water as i64 || totals.liabilities == 0 {
+            self.low_alerted.store(false, Ordering::SeqCst);
+            return Ok(());
+        }
+        if !self.low_alerted.swap(true, Ordering::SeqCst) {
+            self.alert_admins(
+                "Custody hot wallet is running low",
+                format!(
+                    "The hot wallet holds {} EDU, below the low-water mark of {} EDU. Refill it from cold storage to keep withdrawals flowing.",
+                    to_edu(hot), to_edu(self.config.low_water as i64)
+                ),
+            ).await;
+        }
+        Ok(())
+    }
+
+    async fn alert_admins(&self, title: &str, body: String) {
+        warn!("🚨 {}: {}", title, body);
+        for username in &self.admins {
+            let Some(admin) = self.users.get_user_by_username(username).await else {
+                continue;
+            };
+            let notification = Notification {
+                kind: NotificationKind::CustodyAlert,
+                wallet_address: admin.wallet_address,
+                title: title.to_string(),
+                body: body.clone(),
+                reference_id: None,
+                tx_hash: None,
+            };
+            if let Err(e) = self.notifications.deliver(&notification).await {
+                error!("❌ Failed to alert admin {}: {}", username, e);
+            }
+        }
+    }
+}
+
+/// What the hot wallet should hold: everything owed, less what sits in cold storage
+fn hot_balance(totals: &CustodyTotals) -> i64 {
+    totals.liabilities - (totals.swept_to_cold - totals.refilled_from_cold)
+}
+
+fn to_edu(satoshis: i64) -> f64 {
+    satoshis as f64 / SATOSHIS_PER_EDU as f64
+}
@@ -1,8 +1,12 @@
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions, Row, FromRow};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool, sqlite::SqlitePoolOptions, Row, FromRow};
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc, NaiveDateTime};
+use std::collections::HashMap;
 use std::sync::Arc;
 use anyhow::Result;
+use tracing::info;
+use crate::migrations::{MigrationReport, Migrator};
+use blockchain_core::wallet::TransactionTags;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct DbUser {
@@ -30,6 +34,8 @@ pub struct DbTransaction {
     pub tx_index: Option<i64>,
     pub timestamp: i64,
     pub status: String,
+    /// Set once the transaction's block left the best chain
+    pub reorged: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -72,6 +78,448 @@ pub struct DbLoanApplication {
     pub funding_tx_hash: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbMarketItem {
+    pub id: String,
+    pub seller_id: String,
+    pub title: String,
+    pub description: String,
+    pub category: String,
+    pub price: f64,
+    pub currency: String,
+    pub item_type: String,
+    pub status: String,
+    pub images: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbOrder {
+    pub order_id: String,
+    pub item_id: String,
+    pub buyer_address: String,
+    pub seller_address: String,
+    pub amount: i64,
+    pub status: String,
+    pub payment_tx_hash: Option<String>,
+    pub release_tx_hash: Option<String>,
+    pub refund_tx_hash: Option<String>,
+    pub tracking_info: Option<String>,
+    pub dispute_reason: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbOrderEvent {
+    pub id: Option<i64>,
+    pub order_id: String,
+    pub from_status: String,
+    pub to_status: String,
+    pub actor_address: Option<String>,
+    pub note: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbNotification {
+    pub id: Option<i64>,
+    pub wallet_address: String,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub reference_id: Option<String>,
+    pub tx_hash: Option<String>,
+    pub is_read: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbNotificationPreference {
+    pub wallet_address: String,
+    pub kind: String,
+    pub in_app: bool,
+    pub email: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbFrozenAccount {
+    pub wallet_address: String,
+    pub reason: String,
+    pub frozen_by: String,
+    pub frozen_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAdminAction {
+    pub id: Option<i64>,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    /// JSON
+    pub details: Option<String>,
+    pub success: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAuditRecord {
+    pub seq: i64,
+    pub timestamp: i64,
+    pub actor: String,
+    pub action: String,
+    pub source_ip: Option<String>,
+    /// JSON
+    pub params: String,
+    pub txid: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbTwoFactor {
+    pub wallet_address: String,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: i64,
+    /// JSON array of SHA-256 hashes
+    pub recovery_codes: String,
+    pub created_at: i64,
+    /// Wrong codes since the last right one or lockout
+    pub failed_attempts: i64,
+    /// Unix time until which codes are refused
+    pub locked_until: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbSpendingLimit {
+    pub wallet_address: String,
+    pub daily_limit: i64,
+    pub step_up_threshold: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbCustodyAccount {
+    pub wallet_address: String,
+    pub balance: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbCustodyEntry {
+    pub id: Option<i64>,
+    pub wallet_address: String,
+    /// deposit, withdrawal or reversal
+    pub kind: String,
+    pub amount: i64,
+    pub tx_hash: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbCustodySweep {
+    pub id: Option<i64>,
+    /// to_cold or from_cold
+    pub direction: String,
+    pub amount: i64,
+    pub tx_hash: String,
+    pub initiated_by: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbInvoice {
+    pub invoice_id: String,
+    pub issuer_user_id: String,
+    pub destination: String,
+    pub amount: i64,
+    pub memo: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub issuer_public_key: String,
+    pub signature: String,
+    /// pending, paid or expired
+    pub status: String,
+    pub paid_tx_hash: Option<String>,
+    pub paid_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAddressChallenge {
+    pub wallet_address: String,
+    pub address: String,
+    pub nonce: String,
+    pub message: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbLinkedAddress {
+    pub address: String,
+    pub wallet_address: String,
+    pub public_key: String,
+    pub message: String,
+    pub signature: String,
+    pub verified_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAttestationIssuer {
+    pub issuer_id: String,
+    pub name: String,
+    pub public_key: String,
+    pub active: bool,
+    pub added_by: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbAttestation {
+    pub attestation_id: String,
+    pub issuer_id: String,
+    pub subject_address: String,
+    /// Signed `Attestation` as JSON
+    pub attestation: String,
+    pub document: String,
+    pub anchor_hash: String,
+    pub anchor_tx_hash: Option<String>,
+    pub issued_at: i64,
+    pub expires_at: i64,
+}
+
+/// A claim on a loan's repayments
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbLoanStake {
+    pub holder_address: String,
+    /// Set when the stake is a tokenized position
+    pub position_id: Option<String>,
+    pub principal: i64,
+}
+
+/// A lender's tokenized, transferable position in a funded loan
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbLoanPosition {
+    pub position_id: String,
+    pub loan_id: String,
+    pub original_funder: String,
+    pub holder_address: String,
+    pub principal: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbLoanPositionListing {
+    pub listing_id: String,
+    pub position_id: String,
+    pub seller_address: String,
+    pub price: i64,
+    pub status: String,
+    pub buyer_address: Option<String>,
+    pub payment_tx_hash: Option<String>,
+    pub created_at: i64,
+    pub closed_at: Option<i64>,
+}
+
+/// One installment share paid out to a stake holder
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbLoanDistribution {
+    pub id: Option<i64>,
+    pub loan_id: String,
+    pub position_id: Option<String>,
+    pub holder_address: String,
+    pub amount: i64,
+    pub tx_hash: String,
+    pub timestamp: i64,
+}
+
+/// All-or-nothing crowdfunding campaign
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbCampaign {
+    pub campaign_id: String,
+    pub creator_address: String,
+    pub title: String,
+    pub description: String,
+    pub goal: i64,
+    pub deadline: i64,
+    pub campaign_address: String,
+    pub status: String,
+    pub release_tx_hash: Option<String>,
+    pub created_at: i64,
+    pub settled_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbCampaignPledge {
+    pub id: Option<i64>,
+    pub campaign_id: String,
+    pub backer_address: String,
+    pub amount: i64,
+    pub tx_hash: String,
+    pub timestamp: i64,
+    pub refund_tx_hash: Option<String>,
+}
+
+/// Dividend or airdrop paid to the holders of a source at a snapshot height
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbDistribution {
+    pub distribution_id: String,
+    pub creator_address: String,
+    pub kind: String,
+    pub source: String,
+    pub source_ref: Option<String>,
+    pub snapshot_height: i64,
+    pub total_amount: i64,
+    pub distribution_address: String,
+    pub funding_tx_hash: String,
+    pub batch_size: i64,
+    pub status: String,
+    pub created_at: i64,
+    pub completed_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbDistributionPayout {
+    pub id: Option<i64>,
+    pub distribution_id: String,
+    pub recipient_address: String,
+    pub weight: i64,
+    pub amount: i64,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub attempts: i64,
+    pub paid_at: Option<i64>,
+}
+
+/// An address and its share weight in a holder snapshot
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbHolder {
+    pub address: String,
+    pub weight: i64,
+}
+
+/// Payout counts and paid total of a distribution
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct DbDistributionProgress {
+    pub pending: i64,
+    pub paid: i64,
+    pub failed: i64,
+    pub paid_amount: i64,
+}
+
+/// Collateral backing a loan and the lock holding it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DbLoanCollateral {
+    pub loan_id: String,
+    pub borrower_address: String,
+    /// `edu` or `nft`
+    pub kind: String,
+    pub amount: i64,
+    pub nft_id: Option<String>,
+    pub lock_address: String,
+    /// Hex HTLC script
+    pub lock_script: String,
+    /// Hex preimage the platform reveals to release the collateral
+    #[serde(skip_serializing)]
+    pub release_secret: String,
+    pub locktime: i64,
+    pub status: String,
+    pub lock_tx_hash: Option<String>,
+    pub settle_tx_hash: Option<String>,
+    pub created_at: i64,
+    pub settled_at: Option<i64>,
+}
+
+/// Aggregate custody figures, in satoshis
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CustodyTotals {
+    /// Sum of all user custodial balances
+    pub liabilities: i64,
+    pub swept_to_cold: i64,
+    pub refilled_from_cold: i64,
+}
+
+/// Fields set alongside an order status change (None leaves a column unchanged)
+#[derive(Debug, Clone, Default)]
+pub struct OrderUpdate {
+    pub tracking_info: Option<String>,
+    pub dispute_reason: Option<String>,
+}
+
+/// Sort order for marketplace search results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketSort {
+    /// Best text match first (newest first when there is no query)
+    #[default]
+    Relevance,
+    Newest,
+    PriceAsc,
+    PriceDesc,
+}
+
+/// Marketplace search filters and pagination
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarketSearch {
+    pub query: Option<String>,
+    pub category: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub seller_id: Option<String>,
+    #[serde(default)]
+    pub sort: MarketSort,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// One page of marketplace search results
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketSearchPage {
+    pub items: Vec<DbMarketItem>,
+    /// Total matches across all pages
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Turn free text into an FTS5 query: every word must match, as a prefix
+fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Append the FROM and WHERE clauses shared by the count and page queries
+fn push_market_filters(builder: &mut QueryBuilder<'_, Sqlite>, search: &MarketSearch, text: Option<&str>) {
+    builder.push("FROM market_items m");
+    if text.is_some() {
+        builder.push(" JOIN market_items_fts ON market_items_fts.rowid = m.rowid");
+    }
+    builder.push(" WHERE m.status = 'active'");
+    if let Some(text) = text {
+        builder.push(" AND market_items_fts MATCH ").push_bind(text.to_string());
+    }
+    if let Some(category) = &search.category {
+        builder.push(" AND m.category = ").push_bind(category.clone());
+    }
+    if let Some(min_price) = search.min_price {
+        builder.push(" AND m.price >= ").push_bind(min_price);
+    }
+    if let Some(max_price) = search.max_price {
+        builder.push(" AND m.price <= ").push_bind(max_price);
+    }
+    if let Some(seller_id) = &search.seller_id {
+        builder.push(" AND m.seller_id = ").push_bind(seller_id.clone());
+    }
+}
+
 pub struct Database {
     pool: SqlitePool,
 }
@@ -84,14 +532,23 @@ impl Database {
             .connect(database_url)
             .await?;
 
-        // Run production schema migration only (002 supersedes 001)
-        sqlx::query(include_str!("../migrations/002_production_schema.sql"))
-            .execute(&pool)
-            .await?;
+        let report = Migrator::new(&pool).run(false).await?;
+        if !report.pending.is_empty() {
+            info!("✅ Database schema upgraded to migration {}", report.latest_version);
+        }
 
         Ok(Self { pool })
     }
 
+    /// Report the migrations `new` would apply, without applying them
+    pub async fn migration_dry_run(database_url: &str) -> Result<MigrationReport> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(database_url)
+            .await?;
+        Migrator::new(&pool).run(true).await
+    }
+
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
@@ -186,7 +643,7 @@ impl Database {
     pub async fn get_transaction_by_hash(&self, tx_hash: &str) -> Result<Option<DbTransaction>> {
         let tx = sqlx::query_as::<_, DbTransaction>(
             "SELECT id, tx_hash, from_address, to_address, amount, fee, memo, 
-             block_height, tx_index, timestamp, status 
+             block_height, tx_index, timestamp, status, reorged 
              FROM transactions WHERE tx_hash = ?"
         )
         .bind(tx_hash)
@@ -199,7 +656,7 @@ impl Database {
     pub async fn get_transactions_by_address(&self, address: &str, limit: i64) -> Result<Vec<DbTransaction>> {
         let txs = sqlx::query_as::<_, DbTransaction>(
             "SELECT id, tx_hash, from_address, to_address, amount, fee, memo, 
-             block_height, tx_index, timestamp, status 
+             block_height, tx_index, timestamp, status, reorged 
              FROM transactions 
              WHERE from_address = ? OR to_address = ? 
              ORDER BY timestamp DESC LIMIT ?"
@@ -213,6 +670,74 @@ impl Database {
         Ok(txs)
     }
 
+    /// Every transaction touching an address, oldest first
+    pub async fn get_address_transactions_chronological(&self, address: &str) -> Result<Vec<DbTransaction>> {
+        let txs = sqlx::query_as::<_, DbTransaction>(
+            "SELECT id, tx_hash, from_address, to_address, amount, fee, memo, 
+             block_height, tx_index, timestamp, status, reorged 
+             FROM transactions 
+             WHERE from_address = ? OR to_address = ? 
+             ORDER BY timestamp ASC, id ASC"
+        )
+        .bind(address)
+        .bind(address)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(txs)
+    }
+
+    /// Set tags on a transaction, replacing existing values for the same keys
+    pub async fn save_transaction_tags(&self, tx_hash: &str, tags: &TransactionTags) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for (key, value) in tags {
+            sqlx::query(
+                "INSERT INTO transaction_tags (tx_hash, key, value) VALUES (?, ?, ?) 
+                 ON CONFLICT(tx_hash, key) DO UPDATE SET value = excluded.value"
+            )
+            .bind(tx_hash)
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Tags of the given transactions, keyed by hash; untagged ones are left out
+    pub async fn get_transaction_tags(&self, tx_hashes: &[String]) -> Result<HashMap<String, TransactionTags>> {
+        let mut tags: HashMap<String, TransactionTags> = HashMap::new();
+        // Stay well under SQLite's bound parameter limit
+        for chunk in tx_hashes.chunks(500) {
+            let mut query = QueryBuilder::<Sqlite>::new("SELECT tx_hash, key, value FROM transaction_tags WHERE tx_hash IN (");
+            let mut separated = query.separated(", ");
+            for hash in chunk {
+                separated.push_bind(hash.clone());
+            }
+            separated.push_unseparated(")");
+            for row in query.build().fetch_all(&self.pool).await? {
+                tags.entry(row.get("tx_hash")).or_default().insert(row.get("key"), row.get("value"));
+            }
+        }
+
+        Ok(tags)
+    }
+
+    /// Return confirmed transactions in the block at `height` to pending
+    /// after it was disconnected; gives the hashes that were changed
+    pub async fn reorg_transactions_at(&self, height: i64) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "UPDATE transactions SET status = 'pending', block_height = NULL, reorged = 1 
+             WHERE block_height = ? AND status = 'confirmed' RETURNING tx_hash"
+        )
+        .bind(height)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("tx_hash")).collect())
+    }
+
     pub async fn update_transaction_status(&self, tx_hash: &str, status: &str, block_height: Option<i64>) -> Result<()> {
         sqlx::query("UPDATE transactions SET status = ?, block_height = ? WHERE tx_hash = ?")
             .bind(status)
@@ -308,109 +833,1932 @@ impl Database {
         Ok(nfts)
     }
 
-    // ==================== LOAN OPERATIONS ====================
+    // ==================== MARKETPLACE OPERATIONS ====================
 
-    pub async fn create_loan_application(&self, loan: &DbLoanApplication) -> Result<i64> {
-        let result = sqlx::query(
-            "INSERT INTO loan_applications (
-                loan_id, applicant_username, applicant_address, full_name, university, 
-                field_of_study, gpa, test_score, achievements, requested_amount, 
-                interest_rate, repayment_term_months, loan_purpose, graduation_year, 
-                expected_career, expected_salary, proof_of_potential_score, status
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    pub async fn save_market_item(&self, item: &DbMarketItem) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO market_items (id, seller_id, title, description, category, price, 
+             currency, item_type, status, images, created_at, updated_at) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) 
+             ON CONFLICT(id) DO UPDATE SET 
+             title = excluded.title, description = excluded.description, 
+             category = excluded.category, price = excluded.price, 
+             currency = excluded.currency, item_type = excluded.item_type, 
+             status = excluded.status, images = excluded.images, 
+             updated_at = excluded.updated_at"
         )
-        .bind(&loan.loan_id)
-        .bind(&loan.applicant_username)
-        .bind(&loan.applicant_address)
-        .bind(&loan.full_name)
-        .bind(&loan.university)
-        .bind(&loan.field_of_study)
-        .bind(loan.gpa)
-        .bind(loan.test_score)
-        .bind(&loan.achievements)
-        .bind(loan.requested_amount)
-        .bind(loan.interest_rate)
-        .bind(loan.repayment_term_months)
-        .bind(&loan.loan_purpose)
-        .bind(loan.graduation_year)
-        .bind(&loan.expected_career)
-        .bind(loan.expected_salary)
-        .bind(loan.proof_of_potential_score)
-        .bind(&loan.status)
+        .bind(&item.id)
+        .bind(&item.seller_id)
+        .bind(&item.title)
+        .bind(&item.description)
+        .bind(&item.category)
+        .bind(item.price)
+        .bind(&item.currency)
+        .bind(&item.item_type)
+        .bind(&item.status)
+        .bind(&item.images)
+        .bind(item.created_at)
+        .bind(item.updated_at)
         .execute(&self.pool)
         .await?;
 
-        Ok(result.last_insert_rowid())
+        Ok(())
     }
 
-    pub async fn get_loan_by_id(&self, loan_id: &str) -> Result<Option<DbLoanApplication>> {
-        let loan = sqlx::query_as::<_, DbLoanApplication>(
-            "SELECT id, loan_id, applicant_username, applicant_address, full_name, 
-             university, field_of_study, gpa, test_score, achievements, requested_amount, 
-             interest_rate, repayment_term_months, loan_purpose, graduation_year, 
-             expected_career, expected_salary, proof_of_potential_score, status, 
-             funded_amount, funding_tx_hash 
-             FROM loan_applications WHERE loan_id = ?"
-        )
-        .bind(loan_id)
-        .fetch_optional(&self.pool)
-        .await?;
+    pub async fn get_market_item(&self, id: &str) -> Result<Option<DbMarketItem>> {
+        let item = sqlx::query_as::<_, DbMarketItem>("SELECT * FROM market_items WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
 
-        Ok(loan)
+        Ok(item)
     }
 
-    pub async fn list_loans_by_status(&self, status: &str, limit: i64) -> Result<Vec<DbLoanApplication>> {
-        let loans = sqlx::query_as::<_, DbLoanApplication>(
-            "SELECT id, loan_id, applicant_username, applicant_address, full_name, 
-             university, field_of_study, gpa, test_score, achievements, requested_amount, 
-             interest_rate, repayment_term_months, loan_purpose, graduation_year, 
-             expected_career, expected_salary, proof_of_potential_score, status, 
-             funded_amount, funding_tx_hash 
-             FROM loan_applications WHERE status = ? 
-             ORDER BY created_at DESC LIMIT ?"
-        )
-        .bind(status)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+    pub async fn delete_market_item(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM market_items WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
 
-        Ok(loans)
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn fund_loan(&self, loan_id: &str, funder_address: &str, amount: i64, tx_hash: &str, timestamp: i64) -> Result<()> {
-        // Record funding
-        sqlx::query("INSERT INTO loan_funders (loan_id, funder_address, amount, tx_hash, timestamp) VALUES (?, ?, ?, ?, ?)")
-            .bind(loan_id)
-            .bind(funder_address)
-            .bind(amount)
-            .bind(tx_hash)
+    /// Compare-and-set an item's status; false if it was not `from`
+    pub async fn set_market_item_status(&self, id: &str, from: &str, to: &str, timestamp: i64) -> Result<bool> {
+        let result = sqlx::query("UPDATE market_items SET status = ?, updated_at = ? WHERE id = ? AND status = ?")
+            .bind(to)
             .bind(timestamp)
+            .bind(id)
+            .bind(from)
             .execute(&self.pool)
             .await?;
 
-        // Update loan funded amount
-        sqlx::query("UPDATE loan_applications SET funded_amount = COALESCE(funded_amount, 0) + ?, updated_at = CURRENT_TIMESTAMP WHERE loan_id = ?")
-            .bind(amount)
-            .bind(loan_id)
-            .execute(&self.pool)
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Search active listings with full-text matching, filters and pagination
+    pub async fn search_market_items(&self, search: &MarketSearch) -> Result<MarketSearchPage> {
+        let text = search.query.as_deref().and_then(fts_query);
+
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) ");
+        push_market_filters(&mut count, search, text.as_deref());
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut page = QueryBuilder::<Sqlite>::new("SELECT m.* ");
+        push_market_filters(&mut page, search, text.as_deref());
+        page.push(match search.sort {
+            MarketSort::Relevance if text.is_some() => " ORDER BY bm25(market_items_fts), m.created_at DESC",
+            MarketSort::Relevance | MarketSort::Newest => " ORDER BY m.created_at DESC",
+            MarketSort::PriceAsc => " ORDER BY m.price ASC, m.created_at DESC",
+            MarketSort::PriceDesc => " ORDER BY m.price DESC, m.created_at DESC",
+        });
+        page.push(" LIMIT ").push_bind(search.limit);
+        page.push(" OFFSET ").push_bind(search.offset);
+        let items = page.build_query_as::<DbMarketItem>().fetch_all(&self.pool).await?;
+
+        Ok(MarketSearchPage {
+            items,
+            total,
+            limit: search.limit,
+            offset: search.offset,
+        })
+    }
+
+    /// Active listing count and price range per category
+    pub async fn market_categories(&self) -> Result<Vec<serde_json::Value>> {
+        let rows = sqlx::query(
+            "SELECT category, COUNT(*) AS listings, MIN(price) AS min_price, MAX(price) AS max_price 
+             FROM market_items WHERE status = 'active' 
+             GROUP BY category ORDER BY listings DESC, category"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| serde_json::json!({
+            "category": row.get::<String, _>("category"),
+            "listings": row.get::<i64, _>("listings"),
+            "min_price": row.get::<f64, _>("min_price"),
+            "max_price": row.get::<f64, _>("max_price"),
+        })).collect())
+    }
+
+    /// Active listing count, total active value and overall item count
+    pub async fn market_stats(&self) -> Result<(i64, f64, i64)> {
+        let stats: (i64, f64, i64) = sqlx::query_as(
+            "SELECT COUNT(*) FILTER (WHERE status = 'active'), 
+             COALESCE(SUM(price) FILTER (WHERE status = 'active'), 0.0), 
+             COUNT(*) FROM market_items"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(stats)
+    }
+
+    // ==================== ORDER OPERATIONS ====================
+
+    pub async fn create_order(&self, order: &DbOrder) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO market_orders (order_id, item_id, buyer_address, seller_address, 
+             amount, status, created_at, updated_at) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&order.order_id)
+        .bind(&order.item_id)
+        .bind(&order.buyer_address)
+        .bind(&order.seller_address)
+        .bind(order.amount)
+        .bind(&order.status)
+        .bind(order.created_at)
+        .bind(order.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_order(&self, order_id: &str) -> Result<Option<DbOrder>> {
+        let order = sqlx::query_as::<_, DbOrder>("SELECT * FROM market_orders WHERE order_id = ?")
+            .bind(order_id)
+            .fetch_optional(&self.pool)
             .await?;
 
-        // Check if fully funded and update status
-        let loan = self.get_loan_by_id(loan_id).await?;
-        if let Some(loan) = loan {
-            let funded = loan.funded_amount.unwrap_or(0);
-            if funded >= loan.requested_amount {
-                sqlx::query("UPDATE loan_applications SET status = 'funded', funding_tx_hash = ?, funded_at = CURRENT_TIMESTAMP WHERE loan_id = ?")
-                    .bind(tx_hash)
-                    .bind(loan_id)
-                    .execute(&self.pool)
-                    .await?;
+        Ok(order)
+    }
+
+    /// Orders where the user is buyer or seller, newest first
+    pub async fn list_orders_for_user(&self, wallet_address: &str, limit: i64) -> Result<Vec<DbOrder>> {
+        let orders = sqlx::query_as::<_, DbOrder>(
+            "SELECT * FROM market_orders WHERE buyer_address = ? OR seller_address = ? 
+             ORDER BY created_at DESC LIMIT ?"
+        )
+        .bind(wallet_address)
+        .bind(wallet_address)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(orders)
+    }
+
+    /// Unpaid orders whose payment transaction has been broadcast
+    pub async fn list_orders_awaiting_payment(&self) -> Result<Vec<DbOrder>> {
+        let orders = sqlx::query_as::<_, DbOrder>(
+            "SELECT * FROM market_orders WHERE status = 'created' AND payment_tx_hash IS NOT NULL"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(orders)
+    }
+
+    /// Attach the escrow payment to an unpaid order; false if one is already attached
+    pub async fn set_order_payment(&self, order_id: &str, tx_hash: &str, timestamp: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE market_orders SET payment_tx_hash = ?, updated_at = ? 
+             WHERE order_id = ? AND status = 'created' AND payment_tx_hash IS NULL"
+        )
+        .bind(tx_hash)
+        .bind(timestamp)
+        .bind(order_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Move an order from `from` to `to` and record the event.
+    /// Returns false if the order was no longer in `from`.
+    pub async fn transition_order(
+        &self,
+        order_id: &str,
+        from: &str,
+        to: &str,
+        actor_address: Option<&str>,
+        note: Option<&str>,
+        update: &OrderUpdate,
+        timestamp: i64,
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE market_orders SET status = ?, 
+             tracking_info = COALESCE(?, tracking_info), 
+             dispute_reason = COALESCE(?, dispute_reason), 
+             updated_at = ? 
+             WHERE order_id = ? AND status = ?"
+        )
+        .bind(to)
+        .bind(&update.tracking_info)
+        .bind(&update.dispute_reason)
+        .bind(timestamp)
+        .bind(order_id)
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT INTO market_order_events (order_id, from_status, to_status, actor_address, note, created_at) 
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(order_id)
+        .bind(from)
+        .bind(to)
+        .bind(actor_address)
+        .bind(note)
+        .bind(timestamp)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Record the escrow payout that settled a completed or refunded order
+    pub async fn set_order_settlement(&self, order_id: &str, release_tx_hash: Option<&str>, refund_tx_hash: Option<&str>) -> Result<()> {
+        sqlx::query(
+            "UPDATE market_orders SET release_tx_hash = COALESCE(?, release_tx_hash), 
+             refund_tx_hash = COALESCE(?, refund_tx_hash) WHERE order_id = ?"
+        )
+        .bind(release_tx_hash)
+        .bind(refund_tx_hash)
+        .bind(order_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_order_events(&self, order_id: &str) -> Result<Vec<DbOrderEvent>> {
+        let events = sqlx::query_as::<_, DbOrderEvent>(
+            "SELECT * FROM market_order_events WHERE order_id = ? ORDER BY id"
+        )
+        .bind(order_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    // ==================== NOTIFICATION OPERATIONS ====================
+
+    pub async fn create_notification(&self, notification: &DbNotification) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO notifications (wallet_address, kind, title, body, reference_id, tx_hash, is_read, created_at) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&notification.wallet_address)
+        .bind(&notification.kind)
+        .bind(&notification.title)
+        .bind(&notification.body)
+        .bind(&notification.reference_id)
+        .bind(&notification.tx_hash)
+        .bind(notification.is_read)
+        .bind(notification.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn list_notifications(&self, wallet_address: &str, unread_only: bool, limit: i64) -> Result<Vec<DbNotification>> {
+        let notifications = sqlx::query_as::<_, DbNotification>(
+            "SELECT * FROM notifications WHERE wallet_address = ? AND (is_read = 0 OR ? = 0) 
+             ORDER BY created_at DESC, id DESC LIMIT ?"
+        )
+        .bind(wallet_address)
+        .bind(unread_only)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(notifications)
+    }
+
+    /// Mark the given notifications (or all of them) read; returns how many changed
+    pub async fn mark_notifications_read(&self, wallet_address: &str, ids: Option<&[i64]>) -> Result<u64> {
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE notifications SET is_read = 1 WHERE wallet_address = ");
+        query.push_bind(wallet_address.to_string()).push(" AND is_read = 0");
+        if let Some(ids) = ids {
+            if ids.is_empty() {
+                return Ok(0);
             }
+            query.push(" AND id IN (");
+            let mut separated = query.separated(", ");
+            for id in ids {
+                separated.push_bind(*id);
+            }
+            separated.push_unseparated(")");
+        }
+        let result = query.build().execute(&self.pool).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Stored preferences; kinds without a row use the defaults
+    pub async fn get_notification_preferences(&self, wallet_address: &str) -> Result<Vec<DbNotificationPreference>> {
+        let preferences = sqlx::query_as::<_, DbNotificationPreference>(
+            "SELECT * FROM notification_preferences WHERE wallet_address = ?"
+        )
+        .bind(wallet_address)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(preferences)
+    }
+
+    pub async fn set_notification_preference(&self, preference: &DbNotificationPreference) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO notification_preferences (wallet_address, kind, in_app, email) VALUES (?, ?, ?, ?) 
+             ON CONFLICT(wallet_address, kind) DO UPDATE SET in_app = excluded.in_app, email = excluded.email"
+        )
+        .bind(&preference.wallet_address)
+        .bind(&preference.kind)
+        .bind(preference.in_app)
+        .bind(preference.email)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // ==================== ADMIN OPERATIONS ====================
+
+    /// Returns false if the account was already frozen
+    pub async fn freeze_account(&self, account: &DbFrozenAccount) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO frozen_accounts (wallet_address, reason, frozen_by, frozen_at) VALUES (?, ?, ?, ?)"
+        )
+        .bind(&account.wallet_address)
+        .bind(&account.reason)
+        .bind(&account.frozen_by)
+        .bind(account.frozen_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns false if the account was not frozen
+    pub async fn unfreeze_account(&self, wallet_address: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM frozen_accounts WHERE wallet_address = ?")
+            .bind(wallet_address)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_frozen_accounts(&self) -> Result<Vec<DbFrozenAccount>> {
+        let accounts = sqlx::query_as::<_, DbFrozenAccount>(
+            "SELECT * FROM frozen_accounts ORDER BY frozen_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(accounts)
+    }
+
+    /// Oldest rows of the admin audit table older releases kept, or none
+    /// once it has been imported into `audit_log` and dropped
+    pub async fn legacy_admin_actions(&self, limit: i64) -> Result<Vec<DbAdminAction>> {
+        let exists = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'admin_audit_log'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !exists {
+            return Ok(Vec::new());
         }
+        let actions = sqlx::query_as::<_, DbAdminAction>(
+            "SELECT * FROM admin_audit_log ORDER BY created_at, id LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(actions)
+    }
+
+    pub async fn delete_legacy_admin_action(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM admin_audit_log WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn drop_legacy_admin_actions(&self) -> Result<()> {
+        sqlx::query("DROP TABLE IF EXISTS admin_audit_log")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // ==================== AUDIT LOG OPERATIONS ====================
+
+    /// Append a sealed record. Returns false if another writer already took
+    /// its sequence number.
+    pub async fn append_audit_record(&self, record: &DbAuditRecord) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO audit_log (seq, timestamp, actor, action, source_ip, params, txid, success, error, prev_hash, hash) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT(seq) DO NOTHING"
+        )
+        .bind(record.seq)
+        .bind(record.timestamp)
+        .bind(&record.actor)
+        .bind(&record.action)
+        .bind(&record.source_ip)
+        .bind(&record.params)
+        .bind(&record.txid)
+        .bind(record.success)
+        .bind(&record.error)
+        .bind(&record.prev_hash)
+        .bind(&record.hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn last_audit_record(&self) -> Result<Option<DbAuditRecord>> {
+        let record = sqlx::query_as::<_, DbAuditRecord>("SELECT * FROM audit_log ORDER BY seq DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(record)
+    }
+
+    pub async fn get_audit_record(&self, seq: i64) -> Result<Option<DbAuditRecord>> {
+        let record = sqlx::query_as::<_, DbAuditRecord>("SELECT * FROM audit_log WHERE seq = ?")
+            .bind(seq)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(record)
+    }
+
+    /// Most recent records matching every given filter, newest first
+    pub async fn list_audit_records(
+        &self,
+        actor: Option<&str>,
+        action: Option<&str>,
+        txid: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DbAuditRecord>> {
+        let records = sqlx::query_as::<_, DbAuditRecord>(
+            "SELECT * FROM audit_log WHERE (? IS NULL OR actor = ?) AND (? IS NULL OR action = ?) 
+             AND (? IS NULL OR txid = ?) ORDER BY seq DESC LIMIT ?"
+        )
+        .bind(actor)
+        .bind(actor)
+        .bind(action)
+        .bind(action)
+        .bind(txid)
+        .bind(txid)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Records from `from_seq` on, oldest first
+    pub async fn audit_records_from(&self, from_seq: i64, limit: i64) -> Result<Vec<DbAuditRecord>> {
+        let records = sqlx::query_as::<_, DbAuditRecord>(
+            "SELECT * FROM audit_log WHERE seq >= ? ORDER BY seq LIMIT ?"
+        )
+        .bind(from_seq)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    // ==================== WALLET SECURITY OPERATIONS ====================
+
+    pub async fn get_two_factor(&self, wallet_address: &str) -> Result<Option<DbTwoFactor>> {
+        let two_factor = sqlx::query_as::<_, DbTwoFactor>("SELECT * FROM user_two_factor WHERE wallet_address = ?")
+            .bind(wallet_address)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(two_factor)
+    }
+
+    /// Start (or restart) enrollment; refuses to overwrite an enabled secret
+    pub async fn save_pending_two_factor(&self, wallet_address: &str, secret: &str, timestamp: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO user_two_factor (wallet_address, secret, enabled, created_at) VALUES (?, ?, 0, ?) 
+             ON CONFLICT(wallet_address) DO UPDATE SET secret = excluded.secret, created_at = excluded.created_at, 
+             last_used_step = 0, recovery_codes = '[]' 
+             WHERE user_two_factor.enabled = 0"
+        )
+        .bind(wallet_address)
+        .bind(secret)
+        .bind(timestamp)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn enable_two_factor(&self, wallet_address: &str, recovery_codes: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE user_two_factor SET enabled = 1, recovery_codes = ? WHERE wallet_address = ? AND enabled = 0"
+        )
+        .bind(recovery_codes)
+        .bind(wallet_address)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_two_factor(&self, wallet_address: &str) -> Result<()> {
+        sqlx::query("DELETE FROM user_two_factor WHERE wallet_address = ?")
+            .bind(wallet_address)
+            .execute(&self.pool)
+            .await?;
 
         Ok(())
     }
 
+    /// Mark a TOTP time step used; false if it (or a later one) already was
+    pub async fn use_totp_step(&self, wallet_address: &str, step: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE user_two_factor SET last_used_step = ? WHERE wallet_address = ? AND last_used_step < ?"
+        )
+        .bind(step)
+        .bind(wallet_address)
+        .bind(step)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Count a wrong second-factor code. Reaching `max_failures` locks the
+    /// account until `locked_until` and starts the count again; returns
+    /// whether this failure locked it
+    pub async fn record_two_factor_failure(&self, wallet_address: &str, max_failures: i64, locked_until: i64) -> Result<bool> {
+        let row = sqlx::query(
+            "UPDATE user_two_factor SET 
+             failed_attempts = CASE WHEN failed_attempts + 1 >= ? THEN 0 ELSE failed_attempts + 1 END, 
+             locked_until = CASE WHEN failed_attempts + 1 >= ? THEN ? ELSE locked_until END 
+             WHERE wallet_address = ? RETURNING locked_until"
+        )
+        .bind(max_failures)
+        .bind(max_failures)
+        .bind(locked_until)
+        .bind(wallet_address)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.get::<i64, _>("locked_until")) == Some(locked_until))
+    }
+
+    pub async fn reset_two_factor_failures(&self, wallet_address: &str) -> Result<()> {
+        sqlx::query("UPDATE user_two_factor SET failed_attempts = 0 WHERE wallet_address = ? AND failed_attempts > 0")
+            .bind(wallet_address)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Swap the recovery code list if it still equals `expected`
+    pub async fn replace_recovery_codes(&self, wallet_address: &str, expected: &str, remaining: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE user_two_factor SET recovery_codes = ? WHERE wallet_address = ? AND recovery_codes = ?"
+        )
+        .bind(remaining)
+        .bind(wallet_address)
+        .bind(expected)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_spending_limit(&self, wallet_address: &str) -> Result<Option<DbSpendingLimit>> {
+        let limit = sqlx::query_as::<_, DbSpendingLimit>("SELECT * FROM spending_limits WHERE wallet_address = ?")
+            .bind(wallet_address)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(limit)
+    }
+
+    pub async fn set_spending_limit(&self, limit: &DbSpendingLimit) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO spending_limits (wallet_address, daily_limit, step_up_threshold, updated_at) VALUES (?, ?, ?, ?) 
+             ON CONFLICT(wallet_address) DO UPDATE SET daily_limit = excluded.daily_limit, 
+             step_up_threshold = excluded.step_up_threshold, updated_at = excluded.updated_at"
+        )
+        .bind(&limit.wallet_address)
+        .bind(limit.daily_limit)
+        .bind(limit.step_up_threshold)
+        .bind(limit.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Total outgoing value (including reservations) since `since`
+    pub async fn spent_since(&self, wallet_address: &str, since: i64) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(amount), 0) AS spent FROM wallet_spends WHERE wallet_address = ? AND created_at > ?"
+        )
+        .bind(wallet_address)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("spent"))
+    }
+
+    /// Atomically reserve `amount` if the window total stays within `limit`.
+    /// Returns the reservation id, or None if the limit would be exceeded.
+    pub async fn reserve_spend(&self, wallet_address: &str, amount: i64, limit: i64, since: i64, timestamp: i64) -> Result<Option<i64>> {
+        let result = sqlx::query(
+            "INSERT INTO wallet_spends (wallet_address, amount, created_at) 
+             SELECT ?, ?, ? 
+             WHERE (SELECT COALESCE(SUM(amount), 0) FROM wallet_spends WHERE wallet_address = ? AND created_at > ?) + ? <= ?"
+        )
+        .bind(wallet_address)
+        .bind(amount)
+        .bind(timestamp)
+        .bind(wallet_address)
+        .bind(since)
+        .bind(amount)
+        .bind(limit)
+        .execute(&self.pool)
+        .await?;
+
+        Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+    }
+
+    pub async fn commit_spend(&self, reservation_id: i64, tx_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE wallet_spends SET tx_hash = ? WHERE id = ?")
+            .bind(tx_hash)
+            .bind(reservation_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn release_spend(&self, reservation_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM wallet_spends WHERE id = ? AND tx_hash IS NULL")
+            .bind(reservation_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // ==================== CUSTODY OPERATIONS ====================
+
+    pub async fn get_custody_account(&self, wallet_address: &str) -> Result<Option<DbCustodyAccount>> {
+        let account = sqlx::query_as::<_, DbCustodyAccount>("SELECT * FROM custody_accounts WHERE wallet_address = ?")
+            .bind(wallet_address)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(account)
+    }
+
+    pub async fn list_custody_accounts(&self) -> Result<Vec<DbCustodyAccount>> {
+        let accounts = sqlx::query_as::<_, DbCustodyAccount>(
+            "SELECT * FROM custody_accounts WHERE balance > 0 ORDER BY balance DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(accounts)
+    }
+
+    pub async fn list_custody_entries(&self, wallet_address: &str, limit: i64) -> Result<Vec<DbCustodyEntry>> {
+        let entries = sqlx::query_as::<_, DbCustodyEntry>(
+            "SELECT * FROM custody_ledger WHERE wallet_address = ? ORDER BY id DESC LIMIT ?"
+        )
+        .bind(wallet_address)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Credit a user's custodial balance. Returns false if this deposit
+    /// transaction was already credited.
+    pub async fn credit_custody(&self, entry: &DbCustodyEntry) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO custody_ledger (wallet_address, kind, amount, tx_hash, created_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&entry.wallet_address)
+        .bind(&entry.kind)
+        .bind(entry.amount)
+        .bind(&entry.tx_hash)
+        .bind(entry.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT INTO custody_accounts (wallet_address, balance, updated_at) VALUES (?, ?, ?) 
+             ON CONFLICT(wallet_address) DO UPDATE SET balance = balance + excluded.balance, updated_at = excluded.updated_at"
+        )
+        .bind(&entry.wallet_address)
+        .bind(entry.amount)
+        .bind(entry.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Debit a withdrawal if the balance covers it. Returns the ledger entry
+    /// id, or None if the balance is too low.
+    pub async fn debit_custody(&self, wallet_address: &str, amount: i64, timestamp: i64) -> Result<Option<i64>> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE custody_accounts SET balance = balance - ?, updated_at = ? WHERE wallet_address = ? AND balance >= ?"
+        )
+        .bind(amount)
+        .bind(timestamp)
+        .bind(wallet_address)
+        .bind(amount)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let entry = sqlx::query(
+            "INSERT INTO custody_ledger (wallet_address, kind, amount, created_at) VALUES (?, 'withdrawal', ?, ?)"
+        )
+        .bind(wallet_address)
+        .bind(amount)
+        .bind(timestamp)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(entry.last_insert_rowid()))
+    }
+
+    pub async fn set_custody_entry_tx(&self, entry_id: i64, tx_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE custody_ledger SET tx_hash = ? WHERE id = ?")
+            .bind(tx_hash)
+            .bind(entry_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn custody_totals(&self) -> Result<CustodyTotals> {
+        let row = sqlx::query(
+            "SELECT 
+                (SELECT COALESCE(SUM(balance), 0) FROM custody_accounts) AS liabilities, 
+                (SELECT COALESCE(SUM(amount), 0) FROM custody_sweeps WHERE direction = 'to_cold') AS swept_to_cold, 
+                (SELECT COALESCE(SUM(amount), 0) FROM custody_sweeps WHERE direction = 'from_cold') AS refilled_from_cold"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(CustodyTotals {
+            liabilities: row.get("liabilities"),
+            swept_to_cold: row.get("swept_to_cold"),
+            refilled_from_cold: row.get("refilled_from_cold"),
+        })
+    }
+
+    pub async fn record_custody_sweep(&self, sweep: &DbCustodySweep) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO custody_sweeps (direction, amount, tx_hash, initiated_by, created_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&sweep.direction)
+        .bind(sweep.amount)
+        .bind(&sweep.tx_hash)
+        .bind(&sweep.initiated_by)
+        .bind(sweep.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn list_custody_sweeps(&self, limit: i64) -> Result<Vec<DbCustodySweep>> {
+        let sweeps = sqlx::query_as::<_, DbCustodySweep>("SELECT * FROM custody_sweeps ORDER BY id DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(sweeps)
+    }
+
+    // ==================== INVOICE OPERATIONS ====================
+
+    pub async fn insert_invoice(&self, invoice: &DbInvoice) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO invoices (
+                invoice_id, issuer_user_id, destination, amount, memo, created_at, expires_at,
+                issuer_public_key, signature, status, paid_tx_hash, paid_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&invoice.invoice_id)
+        .bind(&invoice.issuer_user_id)
+        .bind(&invoice.destination)
+        .bind(invoice.amount)
+        .bind(&invoice.memo)
+        .bind(invoice.created_at)
+        .bind(invoice.expires_at)
+        .bind(&invoice.issuer_public_key)
+        .bind(&invoice.signature)
+        .bind(&invoice.status)
+        .bind(&invoice.paid_tx_hash)
+        .bind(invoice.paid_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_invoice(&self, invoice_id: &str) -> Result<Option<DbInvoice>> {
+        let invoice = sqlx::query_as::<_, DbInvoice>("SELECT * FROM invoices WHERE invoice_id = ?")
+            .bind(invoice_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(invoice)
+    }
+
+    pub async fn list_invoices_for_user(&self, user_id: &str, limit: i64) -> Result<Vec<DbInvoice>> {
+        let invoices = sqlx::query_as::<_, DbInvoice>(
+            "SELECT * FROM invoices WHERE issuer_user_id = ? ORDER BY created_at DESC LIMIT ?"
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(invoices)
+    }
+
+    /// Open invoices payable to an address, oldest first
+    pub async fn pending_invoices_for_destination(&self, destination: &str) -> Result<Vec<DbInvoice>> {
+        let invoices = sqlx::query_as::<_, DbInvoice>(
+            "SELECT * FROM invoices WHERE destination = ? AND status = 'pending' ORDER BY created_at ASC"
+        )
+        .bind(destination)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(invoices)
+    }
+
+    /// Mark a pending invoice paid; false if it was no longer pending
+    /// or the transaction already settled another invoice
+    pub async fn mark_invoice_paid(&self, invoice_id: &str, tx_hash: &str, paid_at: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invoices SET status = 'paid', paid_tx_hash = ?, paid_at = ? 
+             WHERE invoice_id = ? AND status = 'pending'
+             AND NOT EXISTS (SELECT 1 FROM invoices WHERE paid_tx_hash = ?)"
+        )
+        .bind(tx_hash)
+        .bind(paid_at)
+        .bind(invoice_id)
+        .bind(tx_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether a transaction has already settled an invoice
+    pub async fn invoice_paid_by(&self, tx_hash: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT 1 FROM invoices WHERE paid_tx_hash = ?")
+            .bind(tx_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Expire pending invoices whose deadline has passed; returns how many
+    pub async fn expire_invoices(&self, now: i64) -> Result<u64> {
+        let result = sqlx::query("UPDATE invoices SET status = 'expired' WHERE status = 'pending' AND expires_at < ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // ==================== ADDRESS PROOF OPERATIONS ====================
+
+    /// Store a challenge, replacing any earlier one for the same address,
+    /// and drop challenges that expired before `now`
+    pub async fn put_address_challenge(&self, challenge: &DbAddressChallenge, now: i64) -> Result<()> {
+        sqlx::query("DELETE FROM address_challenges WHERE expires_at < ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO address_challenges (wallet_address, address, nonce, message, expires_at) 
+             VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&challenge.wallet_address)
+        .bind(&challenge.address)
+        .bind(&challenge.nonce)
+        .bind(&challenge.message)
+        .bind(challenge.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove and return the unexpired challenge for an address, so each
+    /// challenge can be answered once
+    pub async fn take_address_challenge(&self, wallet_address: &str, address: &str, now: i64) -> Result<Option<DbAddressChallenge>> {
+        let challenge = sqlx::query_as::<_, DbAddressChallenge>(
+            "DELETE FROM address_challenges WHERE wallet_address = ? AND address = ? AND expires_at >= ? RETURNING *"
+        )
+        .bind(wallet_address)
+        .bind(address)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    /// Link an address to an account. Returns false if it is already linked
+    /// to a different account.
+    pub async fn link_address(&self, linked: &DbLinkedAddress) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO linked_addresses (address, wallet_address, public_key, message, signature, verified_at) 
+             VALUES (?, ?, ?, ?, ?, ?) 
+             ON CONFLICT(address) DO UPDATE SET public_key = excluded.public_key, message = excluded.message, 
+                 signature = excluded.signature, verified_at = excluded.verified_at 
+             WHERE linked_addresses.wallet_address = excluded.wallet_address"
+        )
+        .bind(&linked.address)
+        .bind(&linked.wallet_address)
+        .bind(&linked.public_key)
+        .bind(&linked.message)
+        .bind(&linked.signature)
+        .bind(linked.verified_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_linked_addresses(&self, wallet_address: &str) -> Result<Vec<DbLinkedAddress>> {
+        let linked = sqlx::query_as::<_, DbLinkedAddress>(
+            "SELECT * FROM linked_addresses WHERE wallet_address = ? ORDER BY verified_at DESC"
+        )
+        .bind(wallet_address)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(linked)
+    }
+
+    pub async fn unlink_address(&self, wallet_address: &str, address: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM linked_addresses WHERE wallet_address = ? AND address = ?")
+            .bind(wallet_address)
+            .bind(address)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ==================== ATTESTATION OPERATIONS ====================
+
+    pub async fn insert_attestation_issuer(&self, issuer: &DbAttestationIssuer) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO attestation_issuers (issuer_id, name, public_key, active, added_by, created_at) 
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&issuer.issuer_id)
+        .bind(&issuer.name)
+        .bind(&issuer.public_key)
+        .bind(issuer.active)
+        .bind(&issuer.added_by)
+        .bind(issuer.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_attestation_issuers(&self) -> Result<Vec<DbAttestationIssuer>> {
+        let issuers = sqlx::query_as::<_, DbAttestationIssuer>("SELECT * FROM attestation_issuers ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(issuers)
+    }
+
+    pub async fn get_attestation_issuer_by_key(&self, public_key: &str) -> Result<Option<DbAttestationIssuer>> {
+        let issuer = sqlx::query_as::<_, DbAttestationIssuer>("SELECT * FROM attestation_issuers WHERE public_key = ?")
+            .bind(public_key)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(issuer)
+    }
+
+    pub async fn get_attestation_issuer(&self, issuer_id: &str) -> Result<Option<DbAttestationIssuer>> {
+        let issuer = sqlx::query_as::<_, DbAttestationIssuer>("SELECT * FROM attestation_issuers WHERE issuer_id = ?")
+            .bind(issuer_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(issuer)
+    }
+
+    pub async fn set_attestation_issuer_active(&self, issuer_id: &str, active: bool) -> Result<bool> {
+        let result = sqlx::query("UPDATE attestation_issuers SET active = ? WHERE issuer_id = ?")
+            .bind(active)
+            .bind(issuer_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn insert_attestation(&self, attestation: &DbAttestation) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO attestations (
+                attestation_id, issuer_id, subject_address, attestation, document, anchor_hash,
+                anchor_tx_hash, issued_at, expires_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&attestation.attestation_id)
+        .bind(&attestation.issuer_id)
+        .bind(&attestation.subject_address)
+        .bind(&attestation.attestation)
+        .bind(&attestation.document)
+        .bind(&attestation.anchor_hash)
+        .bind(&attestation.anchor_tx_hash)
+        .bind(attestation.issued_at)
+        .bind(attestation.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_attestation_anchor(&self, attestation_id: &str, tx_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE attestations SET anchor_tx_hash = ? WHERE attestation_id = ?")
+            .bind(tx_hash)
+            .bind(attestation_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_attestation(&self, attestation_id: &str) -> Result<Option<DbAttestation>> {
+        let attestation = sqlx::query_as::<_, DbAttestation>("SELECT * FROM attestations WHERE attestation_id = ?")
+            .bind(attestation_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(attestation)
+    }
+
+    /// Attestations about an address, newest first
+    pub async fn list_attestations_for_subject(&self, subject_address: &str) -> Result<Vec<DbAttestation>> {
+        let attestations = sqlx::query_as::<_, DbAttestation>(
+            "SELECT * FROM attestations WHERE subject_address = ? ORDER BY issued_at DESC"
+        )
+        .bind(subject_address)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(attestations)
+    }
+
+    /// Subjects with an unexpired attestation from an active issuer
+    pub async fn attested_subjects(&self, now: i64) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT a.subject_address FROM attestations a 
+             JOIN attestation_issuers i ON i.issuer_id = a.issuer_id 
+             WHERE i.active = 1 AND a.expires_at >= ?"
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(|row| row.get("subject_address")).collect())
+    }
+
+    // ==================== LOAN OPERATIONS ====================
+
+    pub async fn create_loan_application(&self, loan: &DbLoanApplication) -> Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO loan_applications (
+                loan_id, applicant_username, applicant_address, full_name, university, 
+                field_of_study, gpa, test_score, achievements, requested_amount, 
+                interest_rate, repayment_term_months, loan_purpose, graduation_year, 
+                expected_career, expected_salary, proof_of_potential_score, status
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&loan.loan_id)
+        .bind(&loan.applicant_username)
+        .bind(&loan.applicant_address)
+        .bind(&loan.full_name)
+        .bind(&loan.university)
+        .bind(&loan.field_of_study)
+        .bind(loan.gpa)
+        .bind(loan.test_score)
+        .bind(&loan.achievements)
+        .bind(loan.requested_amount)
+        .bind(loan.interest_rate)
+        .bind(loan.repayment_term_months)
+        .bind(&loan.loan_purpose)
+        .bind(loan.graduation_year)
+        .bind(&loan.expected_career)
+        .bind(loan.expected_salary)
+        .bind(loan.proof_of_potential_score)
+        .bind(&loan.status)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get_loan_by_id(&self, loan_id: &str) -> Result<Option<DbLoanApplication>> {
+        let loan = sqlx::query_as::<_, DbLoanApplication>(
+            "SELECT id, loan_id, applicant_username, applicant_address, full_name, 
+             university, field_of_study, gpa, test_score, achievements, requested_amount, 
+             interest_rate, repayment_term_months, loan_purpose, graduation_year, 
+             expected_career, expected_salary, proof_of_potential_score, status, 
+             funded_amount, funding_tx_hash 
+             FROM loan_applications WHERE loan_id = ?"
+        )
+        .bind(loan_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(loan)
+    }
+
+    pub async fn list_loans_by_status(&self, status: &str, limit: i64) -> Result<Vec<DbLoanApplication>> {
+        let loans = sqlx::query_as::<_, DbLoanApplication>(
+            "SELECT id, loan_id, applicant_username, applicant_address, full_name, 
+             university, field_of_study, gpa, test_score, achievements, requested_amount, 
+             interest_rate, repayment_term_months, loan_purpose, graduation_year, 
+             expected_career, expected_salary, proof_of_potential_score, status, 
+             funded_amount, funding_tx_hash 
+             FROM loan_applications WHERE status = ? 
+             ORDER BY created_at DESC LIMIT ?"
+        )
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(loans)
+    }
+
+    pub async fn fund_loan(&self, loan_id: &str, funder_address: &str, amount: i64, tx_hash: &str, timestamp: i64) -> Result<()> {
+        // Record funding
+        sqlx::query("INSERT INTO loan_funders (loan_id, funder_address, amount, tx_hash, timestamp) VALUES (?, ?, ?, ?, ?)")
+            .bind(loan_id)
+            .bind(funder_address)
+            .bind(amount)
+            .bind(tx_hash)
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?;
+
+        // Update loan funded amount
+        sqlx::query("UPDATE loan_applications SET funded_amount = COALESCE(funded_amount, 0) + ?, updated_at = CURRENT_TIMESTAMP WHERE loan_id = ?")
+            .bind(amount)
+            .bind(loan_id)
+            .execute(&self.pool)
+            .await?;
+
+        // Check if fully funded and update status
+        let loan = self.get_loan_by_id(loan_id).await?;
+        if let Some(loan) = loan {
+            let funded = loan.funded_amount.unwrap_or(0);
+            if funded >= loan.requested_amount {
+                sqlx::query("UPDATE loan_applications SET status = 'funded', funding_tx_hash = ?, funded_at = CURRENT_TIMESTAMP WHERE loan_id = ?")
+                    .bind(tx_hash)
+                    .bind(loan_id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// An applicant's past borrowing: loans that were funded, the amount
+    /// funded, and confirmed repayments: installments tagged
+    /// `LOAN_REPAYMENT`, plus untagged payments straight to the funders
+    pub async fn loan_repayment_history(&self, applicant_address: &str) -> Result<(i64, i64, i64)> {
+        let history: (i64, i64, i64) = sqlx::query_as(
+            "SELECT
+                (SELECT COUNT(*) FROM loan_applications
+                 WHERE applicant_address = ?1 AND status IN ('funded', 'repaid', 'defaulted')),
+                (SELECT COALESCE(SUM(funded_amount), 0) FROM loan_applications
+                 WHERE applicant_address = ?1 AND status IN ('funded', 'repaid', 'defaulted')),
+                (SELECT COALESCE(SUM(t.amount), 0) FROM transactions t
+                 WHERE t.from_address = ?1 AND t.status = 'confirmed' AND (
+                     t.tx_hash IN (SELECT tx_hash FROM transaction_tags WHERE key = 'kind' AND value = 'LOAN_REPAYMENT')
+                     OR (t.tx_hash NOT IN (SELECT tx_hash FROM transaction_tags WHERE key = 'kind')
+                         AND t.to_address IN (
+                             SELECT f.funder_address FROM loan_funders f
+                             JOIN loan_applications l ON l.loan_id = f.loan_id
+                             WHERE l.applicant_address = ?1 AND t.timestamp > f.timestamp))))"
+        )
+        .bind(applicant_address)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(history)
+    }
+
+    /// Confirmed repayments of a loan: its `LOAN_REPAYMENT` installments,
+    /// plus untagged payments from the applicant to its funders since funding
+    pub async fn loan_repaid_amount(&self, loan_id: &str) -> Result<i64> {
+        let (repaid,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(t.amount), 0) FROM transactions t
+             JOIN loan_applications l ON l.loan_id = ?
+             WHERE t.from_address = l.applicant_address AND t.status = 'confirmed' AND (
+                 t.tx_hash IN (
+                     SELECT k.tx_hash FROM transaction_tags k
+                     JOIN transaction_tags r ON r.tx_hash = k.tx_hash AND r.key = 'loan_id' AND r.value = l.loan_id
+                     WHERE k.key = 'kind' AND k.value = 'LOAN_REPAYMENT')
+                 OR (t.tx_hash NOT IN (SELECT tx_hash FROM transaction_tags WHERE key = 'kind')
+                     AND t.to_address IN (
+                         SELECT f.funder_address FROM loan_funders f
+                         WHERE f.loan_id = l.loan_id AND t.timestamp > f.timestamp)))"
+        )
+        .bind(loan_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(repaid)
+    }
+
+    /// Who is owed a loan's repayments, largest stake first: each funder
+    /// for the part of their funding not tokenized, and each position's
+    /// current holder for its principal
+    pub async fn loan_stakes(&self, loan_id: &str) -> Result<Vec<DbLoanStake>> {
+        let stakes = sqlx::query_as::<_, DbLoanStake>(
+            "SELECT * FROM (
+                 SELECT f.funder_address AS holder_address, NULL AS position_id,
+                        SUM(f.amount) - COALESCE((SELECT SUM(p.principal) FROM loan_positions p
+                                                  WHERE p.loan_id = f.loan_id AND p.original_funder = f.funder_address), 0) AS principal
+                 FROM loan_funders f WHERE f.loan_id = ?1 GROUP BY f.funder_address
+                 UNION ALL
+                 SELECT holder_address, position_id, principal FROM loan_positions WHERE loan_id = ?1
+             ) WHERE principal > 0 ORDER BY principal DESC, holder_address"
+        )
+        .bind(loan_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(stakes)
+    }
+
+    pub async fn set_loan_status(&self, loan_id: &str, status: &str) -> Result<()> {
+        sqlx::query("UPDATE loan_applications SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE loan_id = ?")
+            .bind(status)
+            .bind(loan_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // ==================== LOAN SHARES ====================
+
+    /// Tokenize the part of `funder`'s funding of a loan not yet tokenized.
+    /// None if nothing is left.
+    pub async fn tokenize_loan_position(&self, position_id: &str, loan_id: &str, funder: &str, created_at: i64) -> Result<Option<DbLoanPosition>> {
+        let position = sqlx::query_as::<_, DbLoanPosition>(
+            "INSERT INTO loan_positions (position_id, loan_id, original_funder, holder_address, principal, created_at)
+             SELECT ?1, ?2, ?3, ?3, untokenized, ?4 FROM (
+                 SELECT COALESCE(SUM(amount), 0) - COALESCE((SELECT SUM(principal) FROM loan_positions
+                                                            WHERE loan_id = ?2 AND original_funder = ?3), 0) AS untokenized
+                 FROM loan_funders WHERE loan_id = ?2 AND funder_address = ?3
+             ) WHERE untokenized > 0
+             RETURNING *"
+        )
+        .bind(position_id)
+        .bind(loan_id)
+        .bind(funder)
+        .bind(created_at)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(position)
+    }
+
+    pub async fn get_loan_position(&self, position_id: &str) -> Result<Option<DbLoanPosition>> {
+        let position = sqlx::query_as::<_, DbLoanPosition>("SELECT * FROM loan_positions WHERE position_id = ?")
+            .bind(position_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(position)
+    }
+
+    pub async fn list_loan_positions_by_holder(&self, holder_address: &str) -> Result<Vec<DbLoanPosition>> {
+        let positions = sqlx::query_as::<_, DbLoanPosition>(
+            "SELECT * FROM loan_positions WHERE holder_address = ? ORDER BY created_at DESC"
+        )
+        .bind(holder_address)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(positions)
+    }
+
+    pub async fn insert_loan_position_listing(&self, listing: &DbLoanPositionListing) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO loan_position_listings (listing_id, position_id, seller_address, price, status, created_at) 
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&listing.listing_id)
+        .bind(&listing.position_id)
+        .bind(&listing.seller_address)
+        .bind(listing.price)
+        .bind(&listing.status)
+        .bind(listing.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_loan_position_listing(&self, listing_id: &str) -> Result<Option<DbLoanPositionListing>> {
+        let listing = sqlx::query_as::<_, DbLoanPositionListing>("SELECT * FROM loan_position_listings WHERE listing_id = ?")
+            .bind(listing_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(listing)
+    }
+
+    pub async fn list_active_loan_position_listings(&self, limit: i64) -> Result<Vec<DbLoanPositionListing>> {
+        let listings = sqlx::query_as::<_, DbLoanPositionListing>(
+            "SELECT * FROM loan_position_listings WHERE status = 'active' ORDER BY created_at DESC LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(listings)
+    }
+
+    /// Move a listing between states; false if it was not in `from`
+    pub async fn set_loan_listing_status(&self, listing_id: &str, from: &str, to: &str, buyer_address: Option<&str>, closed_at: Option<i64>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE loan_position_listings SET status = ?, buyer_address = ?, closed_at = ? 
+             WHERE listing_id = ? AND status = ?"
+        )
+        .bind(to)
+        .bind(buyer_address)
+        .bind(closed_at)
+        .bind(listing_id)
+        .bind(from)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Complete a pending sale: mark the listing sold and hand the position
+    /// to the buyer
+    pub async fn complete_loan_listing(&self, listing: &DbLoanPositionListing, buyer_address: &str, tx_hash: &str, timestamp: i64) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE loan_position_listings SET status = 'sold', payment_tx_hash = ?, closed_at = ? 
+             WHERE listing_id = ? AND status = 'pending' AND buyer_address = ?"
+        )
+        .bind(tx_hash)
+        .bind(timestamp)
+        .bind(&listing.listing_id)
+        .bind(buyer_address)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        let result = sqlx::query("UPDATE loan_positions SET holder_address = ? WHERE position_id = ? AND holder_address = ?")
+            .bind(buyer_address)
+            .bind(&listing.position_id)
+            .bind(&listing.seller_address)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            "INSERT INTO loan_position_transfers (position_id, from_address, to_address, price, tx_hash, timestamp) 
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&listing.position_id)
+        .bind(&listing.seller_address)
+        .bind(buyer_address)
+        .bind(listing.price)
+        .bind(tx_hash)
+        .bind(timestamp)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn insert_loan_distribution(&self, distribution: &DbLoanDistribution) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO loan_distributions (loan_id, position_id, holder_address, amount, tx_hash, timestamp) 
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&distribution.loan_id)
+        .bind(&distribution.position_id)
+        .bind(&distribution.holder_address)
+        .bind(distribution.amount)
+        .bind(&distribution.tx_hash)
+        .bind(distribution.timestamp)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_loan_distributions(&self, loan_id: &str) -> Result<Vec<DbLoanDistribution>> {
+        let distributions = sqlx::query_as::<_, DbLoanDistribution>(
+            "SELECT * FROM loan_distributions WHERE loan_id = ? ORDER BY timestamp DESC, id DESC"
+        )
+        .bind(loan_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(distributions)
+    }
+
+    // ==================== CAMPAIGNS ====================
+
+    pub async fn insert_campaign(&self, campaign: &DbCampaign) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO campaigns (
+                campaign_id, creator_address, title, description, goal, deadline, campaign_address,
+                status, release_tx_hash, created_at, settled_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&campaign.campaign_id)
+        .bind(&campaign.creator_address)
+        .bind(&campaign.title)
+        .bind(&campaign.description)
+        .bind(campaign.goal)
+        .bind(campaign.deadline)
+        .bind(&campaign.campaign_address)
+        .bind(&campaign.status)
+        .bind(&campaign.release_tx_hash)
+        .bind(campaign.created_at)
+        .bind(campaign.settled_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_campaign(&self, campaign_id: &str) -> Result<Option<DbCampaign>> {
+        let campaign = sqlx::query_as::<_, DbCampaign>("SELECT * FROM campaigns WHERE campaign_id = ?")
+            .bind(campaign_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(campaign)
+    }
+
+    pub async fn get_campaign_by_address(&self, campaign_address: &str) -> Result<Option<DbCampaign>> {
+        let campaign = sqlx::query_as::<_, DbCampaign>("SELECT * FROM campaigns WHERE campaign_address = ?")
+            .bind(campaign_address)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(campaign)
+    }
+
+    pub async fn list_campaigns_by_status(&self, status: &str, limit: i64) -> Result<Vec<DbCampaign>> {
+        let campaigns = sqlx::query_as::<_, DbCampaign>(
+            "SELECT * FROM campaigns WHERE status = ? ORDER BY deadline LIMIT ?"
+        )
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(campaigns)
+    }
+
+    /// Active campaigns whose deadline has passed
+    pub async fn list_campaigns_due(&self, now: i64) -> Result<Vec<DbCampaign>> {
+        let campaigns = sqlx::query_as::<_, DbCampaign>(
+            "SELECT * FROM campaigns WHERE status = 'active' AND deadline <= ? ORDER BY deadline"
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(campaigns)
+    }
+
+    pub async fn count_campaigns_by_status(&self, status: &str) -> Result<i64> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM campaigns WHERE status = ?")
+            .bind(status)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    /// Move a campaign between states; false if it was not in `from`
+    pub async fn set_campaign_status(&self, campaign_id: &str, from: &str, to: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE campaigns SET status = ? WHERE campaign_id = ? AND status = ?")
+            .bind(to)
+            .bind(campaign_id)
+            .bind(from)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Close a settled campaign, recording the release transaction if any
+    pub async fn close_campaign(&self, campaign_id: &str, status: &str, release_tx_hash: Option<&str>, settled_at: i64) -> Result<()> {
+        sqlx::query("UPDATE campaigns SET status = ?, release_tx_hash = ?, settled_at = ? WHERE campaign_id = ?")
+            .bind(status)
+            .bind(release_tx_hash)
+            .bind(settled_at)
+            .bind(campaign_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record a pledge; false if its transaction was already recorded
+    pub async fn record_campaign_pledge(&self, pledge: &DbCampaignPledge) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO campaign_pledges (campaign_id, backer_address, amount, tx_hash, timestamp) 
+             VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&pledge.campaign_id)
+        .bind(&pledge.backer_address)
+        .bind(pledge.amount)
+        .bind(&pledge.tx_hash)
+        .bind(pledge.timestamp)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_campaign_pledges(&self, campaign_id: &str) -> Result<Vec<DbCampaignPledge>> {
+        let pledges = sqlx::query_as::<_, DbCampaignPledge>(
+            "SELECT * FROM campaign_pledges WHERE campaign_id = ? ORDER BY timestamp, id"
+        )
+        .bind(campaign_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(pledges)
+    }
+
+    /// Sum of a campaign's pledges that have not been refunded
+    pub async fn campaign_pledged(&self, campaign_id: &str) -> Result<i64> {
+        let (pledged,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(amount), 0) FROM campaign_pledges WHERE campaign_id = ? AND refund_tx_hash IS NULL"
+        )
+        .bind(campaign_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(pledged)
+    }
+
+    pub async fn set_pledge_refund(&self, pledge_id: i64, refund_tx_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE campaign_pledges SET refund_tx_hash = ? WHERE id = ? AND refund_tx_hash IS NULL")
+            .bind(refund_tx_hash)
+            .bind(pledge_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // ==================== DISTRIBUTIONS ====================
+
+    /// Height of the newest confirmed transaction
+    pub async fn latest_transaction_height(&self) -> Result<i64> {
+        let (height,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(MAX(block_height), 0) FROM transactions WHERE status = 'confirmed'"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(height)
+    }
+
+    /// EDU balances at `height` as recorded in the transaction table, like
+    /// the wallet ledger; platform addresses and `exclude` are left out
+    pub async fn edu_holders_at(&self, height: i64, min_balance: i64, exclude: &str) -> Result<Vec<DbHolder>> {
+        let holders = sqlx::query_as::<_, DbHolder>(
+            "SELECT address, SUM(delta) AS weight FROM (
+                 SELECT to_address AS address, amount AS delta FROM transactions
+                 WHERE status = 'confirmed' AND reorged = 0 AND block_height <= ?1
+                 UNION ALL
+                 SELECT from_address AS address, -(amount + fee) AS delta FROM transactions
+                 WHERE status = 'confirmed' AND reorged = 0 AND block_height <= ?1
+             )
+             WHERE address NOT LIKE 'edunet\\_%' ESCAPE '\\' AND address != ?3
+             GROUP BY address HAVING SUM(delta) >= ?2
+             ORDER BY weight DESC, address"
+        )
+        .bind(height)
+        .bind(min_balance.max(1))
+        .bind(exclude)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(holders)
+    }
+
+    /// Owners of the NFTs minted by `creator` as of `height`, weighted by
+    /// how many each held
+    pub async fn nft_holders_at(&self, creator: &str, height: i64, exclude: &str) -> Result<Vec<DbHolder>> {
+        let holders = sqlx::query_as::<_, DbHolder>(
+            "SELECT owner AS address, COUNT(*) AS weight FROM (
+                 SELECT COALESCE(
+                     (SELECT t.to_address FROM nft_transfers t JOIN transactions x ON x.tx_hash = t.tx_hash
+                      WHERE t.nft_id = n.nft_id AND x.status = 'confirmed' AND x.block_height <= ?2
+                      ORDER BY x.block_height DESC, t.id DESC LIMIT 1),
+                     n.creator_address
+                 ) AS owner
+                 FROM nfts n JOIN transactions m ON m.tx_hash = n.mint_tx_hash
+                 WHERE n.creator_address = ?1 AND n.is_burned = 0
+                   AND m.status = 'confirmed' AND m.block_height <= ?2
+             )
+             WHERE owner NOT LIKE 'edunet\\_%' ESCAPE '\\' AND owner != ?3
+             GROUP BY owner ORDER BY weight DESC, address"
+        )
+        .bind(creator)
+        .bind(height)
+        .bind(exclude)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(holders)
+    }
+
+    /// Store a distribution with its payouts
+    pub async fn create_distribution(&self, distribution: &DbDistribution, payouts: &[DbDistributionPayout]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO distributions (
+                distribution_id, creator_address, kind, source, source_ref, snapshot_height, total_amount,
+                distribution_address, funding_tx_hash, batch_size, status, created_at, completed_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&distribution.distribution_id)
+        .bind(&distribution.creator_address)
+        .bind(&distribution.kind)
+        .bind(&distribution.source)
+        .bind(&distribution.source_ref)
+        .bind(distribution.snapshot_height)
+        .bind(distribution.total_amount)
+        .bind(&distribution.distribution_address)
+        .bind(&distribution.funding_tx_hash)
+        .bind(distribution.batch_size)
+        .bind(&distribution.status)
+        .bind(distribution.created_at)
+        .bind(distribution.completed_at)
+        .execute(&mut *tx)
+        .await?;
+
+        for payout in payouts {
+            sqlx::query(
+                "INSERT INTO distribution_payouts (distribution_id, recipient_address, weight, amount, status) 
+                 VALUES (?, ?, ?, ?, 'pending')"
+            )
+            .bind(&distribution.distribution_id)
+            .bind(&payout.recipient_address)
+            .bind(payout.weight)
+            .bind(payout.amount)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_distribution(&self, distribution_id: &str) -> Result<Option<DbDistribution>> {
+        let distribution = sqlx::query_as::<_, DbDistribution>("SELECT * FROM distributions WHERE distribution_id = ?")
+            .bind(distribution_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(distribution)
+    }
+
+    pub async fn list_distributions_by_creator(&self, creator_address: &str) -> Result<Vec<DbDistribution>> {
+        let distributions = sqlx::query_as::<_, DbDistribution>(
+            "SELECT * FROM distributions WHERE creator_address = ? ORDER BY created_at DESC"
+        )
+        .bind(creator_address)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(distributions)
+    }
+
+    pub async fn list_distributions_by_status(&self, status: &str) -> Result<Vec<DbDistribution>> {
+        let distributions = sqlx::query_as::<_, DbDistribution>(
+            "SELECT * FROM distributions WHERE status = ? ORDER BY created_at"
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(distributions)
+    }
+
+    /// Move a distribution between states; false if it was not in `from`
+    pub async fn set_distribution_status(&self, distribution_id: &str, from: &str, to: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE distributions SET status = ? WHERE distribution_id = ? AND status = ?")
+            .bind(to)
+            .bind(distribution_id)
+            .bind(from)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn complete_distribution(&self, distribution_id: &str, completed_at: i64) -> Result<()> {
+        sqlx::query("UPDATE distributions SET status = 'completed', completed_at = ? WHERE distribution_id = ? AND status = 'running'")
+            .bind(completed_at)
+            .bind(distribution_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Next batch of unpaid payouts, in snapshot order
+    pub async fn pending_distribution_payouts(&self, distribution_id: &str, limit: i64) -> Result<Vec<DbDistributionPayout>> {
+        let payouts = sqlx::query_as::<_, DbDistributionPayout>(
+            "SELECT * FROM distribution_payouts WHERE distribution_id = ? AND status = 'pending' ORDER BY id LIMIT ?"
+        )
+        .bind(distribution_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(payouts)
+    }
+
+    pub async fn list_distribution_payouts(&self, distribution_id: &str) -> Result<Vec<DbDistributionPayout>> {
+        let payouts = sqlx::query_as::<_, DbDistributionPayout>(
+            "SELECT * FROM distribution_payouts WHERE distribution_id = ? ORDER BY id"
+        )
+        .bind(distribution_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(payouts)
+    }
+
+    /// Transaction already sent for a payout, found by its tags
+    pub async fn find_distribution_payout_tx(&self, distribution_id: &str, recipient_address: &str) -> Result<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            "SELECT t.tx_hash FROM transactions t 
+             JOIN transaction_tags g ON g.tx_hash = t.tx_hash AND g.key = 'distribution_id' AND g.value = ? 
+             WHERE t.to_address = ? AND t.status != 'failed' LIMIT 1"
+        )
+        .bind(distribution_id)
+        .bind(recipient_address)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|(tx_hash,)| tx_hash))
+    }
+
+    pub async fn mark_payout_paid(&self, payout_id: i64, tx_hash: &str, paid_at: i64) -> Result<()> {
+        sqlx::query("UPDATE distribution_payouts SET status = 'paid', tx_hash = ?, paid_at = ?, attempts = attempts + 1 WHERE id = ?")
+            .bind(tx_hash)
+            .bind(paid_at)
+            .bind(payout_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Count a failed attempt; the payout is given up after `max_attempts`
+    pub async fn mark_payout_attempt_failed(&self, payout_id: i64, max_attempts: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE distribution_payouts SET attempts = attempts + 1, 
+             status = CASE WHEN attempts + 1 >= ? THEN 'failed' ELSE status END WHERE id = ?"
+        )
+        .bind(max_attempts)
+        .bind(payout_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Put given-up payouts back in the queue
+    pub async fn retry_failed_payouts(&self, distribution_id: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE distribution_payouts SET status = 'pending', attempts = 0 WHERE distribution_id = ? AND status = 'failed'"
+        )
+        .bind(distribution_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn distribution_progress(&self, distribution_id: &str) -> Result<DbDistributionProgress> {
+        let progress = sqlx::query_as::<_, DbDistributionProgress>(
+            "SELECT COALESCE(SUM(status = 'pending'), 0) AS pending,
+                    COALESCE(SUM(status = 'paid'), 0) AS paid,
+                    COALESCE(SUM(status = 'failed'), 0) AS failed,
+                    COALESCE(SUM(CASE WHEN status = 'paid' THEN amount ELSE 0 END), 0) AS paid_amount
+             FROM distribution_payouts WHERE distribution_id = ?"
+        )
+        .bind(distribution_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(progress)
+    }
+
+    // ==================== LOAN COLLATERAL ====================
+
+    pub async fn insert_loan_collateral(&self, collateral: &DbLoanCollateral) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO loan_collateral (
+                loan_id, borrower_address, kind, amount, nft_id, lock_address, lock_script,
+                release_secret, locktime, status, lock_tx_hash, settle_tx_hash, created_at, settled_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&collateral.loan_id)
+        .bind(&collateral.borrower_address)
+        .bind(&collateral.kind)
+        .bind(collateral.amount)
+        .bind(&collateral.nft_id)
+        .bind(&collateral.lock_address)
+        .bind(&collateral.lock_script)
+        .bind(&collateral.release_secret)
+        .bind(collateral.locktime)
+        .bind(&collateral.status)
+        .bind(&collateral.lock_tx_hash)
+        .bind(&collateral.settle_tx_hash)
+        .bind(collateral.created_at)
+        .bind(collateral.settled_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_loan_collateral(&self, loan_id: &str) -> Result<Option<DbLoanCollateral>> {
+        let collateral = sqlx::query_as::<_, DbLoanCollateral>("SELECT * FROM loan_collateral WHERE loan_id = ?")
+            .bind(loan_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(collateral)
+    }
+
+    pub async fn list_loan_collateral_by_status(&self, status: &str) -> Result<Vec<DbLoanCollateral>> {
+        let collateral = sqlx::query_as::<_, DbLoanCollateral>(
+            "SELECT * FROM loan_collateral WHERE status = ? ORDER BY locktime"
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(collateral)
+    }
+
+    /// Record the lock transaction; false if the collateral was already locked
+    pub async fn set_loan_collateral_locked(&self, loan_id: &str, tx_hash: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE loan_collateral SET status = 'locked', lock_tx_hash = ? 
+             WHERE loan_id = ? AND status = 'awaiting_lock'"
+        )
+        .bind(tx_hash)
+        .bind(loan_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Move locked collateral to `released` or `seized`; false if it was not locked
+    pub async fn settle_loan_collateral(&self, loan_id: &str, status: &str, tx_hash: &str, settled_at: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE loan_collateral SET status = ?, settle_tx_hash = ?, settled_at = ? 
+             WHERE loan_id = ? AND status = 'locked'"
+        )
+        .bind(status)
+        .bind(tx_hash)
+        .bind(settled_at)
+        .bind(loan_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     // ==================== BLOCKCHAIN STATE ====================
 
     pub async fn save_block(&self, height: i64, block_hash: &str, prev_hash: &str, merkle_root: &str, 
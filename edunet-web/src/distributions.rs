@@ -0,0 +1,344 @@
+//! Dividend and airdrop distributions
+//!
+//! A distribution snapshots the holders of a source at a height: EDU
+//! balances, the NFTs of one creator, or the stakes in a loan (the
+//! shareholders of an investment pool). The creator funds the whole amount
+//! up front into the distribution's own address, and the payout task pays
+//! it out to the snapshot in batches. Dividends split the amount in
+//! proportion to each holder's weight; airdrops give every holder the same
+//! share.
+//!
+//! Progress lives in the database one payout at a time, and each payout is
+//! tagged with its distribution, so after a restart the task resumes with
+//! the first unpaid holder and never pays anyone twice.
+
+use crate::blockchain_integration::{tags, BlockchainBackend};
+use crate::database::{Database, DbDistribution, DbDistributionPayout, DbDistributionProgress, DbHolder};
+use crate::spending::SpendingGuard;
+use crate::user_auth::User;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Payouts sent per distribution on each pass unless asked otherwise
+const DEFAULT_BATCH_SIZE: i64 = 50;
+const MAX_BATCH_SIZE: i64 = 500;
+/// Largest snapshot a distribution may pay out to
+const MAX_RECIPIENTS: usize = 10_000;
+/// Failed sends before a payout is set aside for a manual retry
+const MAX_ATTEMPTS: i64 = 5;
+/// How often a batch is sent
+const PAYOUT_INTERVAL_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionKind {
+    /// Split in proportion to holdings
+    Dividend,
+    /// Same share for every holder
+    Airdrop,
+}
+
+impl DistributionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dividend => "dividend",
+            Self::Airdrop => "airdrop",
+        }
+    }
+}
+
+/// Whose holders are snapshotted
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HolderSource {
+    /// Addresses holding at least `min_balance` satoshis of EDU
+    Edu { min_balance: Option<i64> },
+    /// Owners of the NFTs minted by `creator_address`
+    Nft { creator_address: String },
+    /// Lenders and position holders of a loan, weighted by principal
+    Loan { loan_id: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateDistributionRequest {
+    pub kind: DistributionKind,
+    pub source: HolderSource,
+    /// Total to distribute, in satoshis
+    pub amount: i64,
+    /// Snapshot height; the latest confirmed height if omitted. Loan stakes
+    /// are always taken as they are now.
+    pub height: Option<i64>,
+    pub batch_size: Option<i64>,
+    pub totp_code: Option<String>,
+}
+
+/// A distribution with its progress
+#[derive(Debug, Clone, Serialize)]
+pub struct DistributionView {
+    #[serde(flatten)]
+    pub distribution: DbDistribution,
+    pub progress: DbDistributionProgress,
+    pub payouts: Vec<DbDistributionPayout>,
+}
+
+pub struct DistributionService {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    spending: Arc<SpendingGuard>,
+}
+
+impl DistributionService {
+    pub fn new(database: Arc<Database>, backend: Arc<BlockchainBackend>, spending: Arc<SpendingGuard>) -> Self {
+        Self { database, backend, spending }
+    }
+
+    /// Snapshot the holders, fund the distribution from the user's wallet
+    /// and queue its payouts
+    pub async fn create(&self, user: &User, request: &CreateDistributionRequest) -> Result<DistributionView, String> {
+        if request.amount <= 0 {
+            return Err("Amount must be positive".to_string());
+        }
+        let batch_size = request.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+        if !(1..=MAX_BATCH_SIZE).contains(&batch_size) {
+            return Err(format!("Batch size must be between 1 and {}", MAX_BATCH_SIZE));
+        }
+        let latest = self.database.latest_transaction_height().await.map_err(|e| e.to_string())?;
+        let height = request.height.unwrap_or(latest);
+        if height < 0 || height > latest {
+            return Err(format!("Snapshot height must be between 0 and {}", latest));
+        }
+
+        let (source, source_ref, height, holders) = match &request.source {
+            HolderSource::Edu { min_balance } => {
+                let holders = self.database.edu_holders_at(height, min_balance.unwrap_or(1), &user.wallet_address).await
+                    .map_err(|e| e.to_string())?;
+                ("edu", None, height, holders)
+            }
+            HolderSource::Nft { creator_address } => {
+                let holders = self.database.nft_holders_at(creator_address, height, &user.wallet_address).await
+                    .map_err(|e| e.to_string())?;
+                ("nft", Some(creator_address.clone()), height, holders)
+            }
+            HolderSource::Loan { loan_id } => {
+                let holders = self.database.loan_stakes(loan_id).await
+                    .map_err(|e| e.to_string())?
+                    .into_iter()
+                    .filter(|stake| stake.holder_address != user.wallet_address)
+                    .fold(Vec::<DbHolder>::new(), |mut holders, stake| {
+                        match holders.iter_mut().find(|h| h.address == stake.holder_address) {
+                            Some(holder) => holder.weight += stake.principal,
+                            None => holders.push(DbHolder { address: stake.holder_address, weight: stake.principal }),
+                        }
+                        holders
+                    });
+                ("loan", Some(loan_id.clone()), latest, holders)
+            }
+        };
+        if holders.is_empty() {
+            return Err("No holders found at that height".to_string());
+        }
+        if holders.len() > MAX_RECIPIENTS {
+            return Err(format!("Snapshot has {} holders; at most {} can be paid", holders.len(), MAX_RECIPIENTS));
+        }
+
+        let shares = split(request.kind, request.amount, &holders);
+        if shares.is_empty() {
+            return Err("Amount is too small to pay any holder".to_string());
+        }
+
+        let distribution_id = format!("dst_{}", Uuid::new_v4().simple());
+        let distribution_address = format!("edunet_distribution_{}", distribution_id);
+        let reservation = self.spending.reserve(user, request.amount as u64, request.totp_code.as_deref()).await?;
+        let funding_tx_hash = match self.backend.send_tagged_transaction(
+            &user.wallet_address,
+            &distribution_address,
+            request.amount as u64,
+            None,
+            tags::for_kind(tags::DISTRIBUTION_FUNDING, Some((tags::DISTRIBUTION_ID, &distribution_id))),
+        ).await {
+            Ok(tx_hash) => {
+                self.spending.commit(reservation, &tx_hash).await;
+                tx_hash
+            }
+            Err(e) => {
+                self.spending.release(reservation).await;
+                return Err(format!("Funding failed: {}", e));
+            }
+        };
+
+        let distribution = DbDistribution {
+            distribution_id: distribution_id.clone(),
+            creator_address: user.wallet_address.clone(),
+            kind: request.kind.as_str().to_string(),
+            source: source.to_string(),
+            source_ref,
+            snapshot_height: height,
+            total_amount: request.amount,
+            distribution_address,
+            funding_tx_hash: funding_tx_hash.clone(),
+            batch_size,
+            status: "running".to_string(),
+            created_at: Utc::now().timestamp(),
+            completed_at: None,
+        };
+        let payouts: Vec<DbDistributionPayout> = shares.into_iter()
+            .map(|(holder, amount)| DbDistributionPayout {
+                id: None,
+                distribution_id: distribution_id.clone(),
+                recipient_address: holder.address.clone(),
+                weight: holder.weight,
+                amount,
+                status: "pending".to_string(),
+                tx_hash: None,
+                attempts: 0,
+                paid_at: None,
+            })
+            .collect();
+        if let Err(e) = self.database.create_distribution(&distribution, &payouts).await {
+            error!("❌ Distribution {} funded (tx {}) but could not be saved: {}", distribution_id, funding_tx_hash, e);
+            return Err(format!("Funded (tx {}) but failed to save the distribution; contact support", funding_tx_hash));
+        }
+        info!(
+            "🪂 {} started {} {} of {} sat to {} {} holder(s) at height {}",
+            user.username, distribution.kind, distribution_id, request.amount, payouts.len(), source, height
+        );
+        self.get(&distribution_id).await
+    }
+
+    pub async fn get(&self, distribution_id: &str) -> Result<DistributionView, String> {
+        let distribution = self.load(distribution_id).await?;
+        let progress = self.database.distribution_progress(distribution_id).await.map_err(|e| e.to_string())?;
+        let payouts = self.database.list_distribution_payouts(distribution_id).await.map_err(|e| e.to_string())?;
+        Ok(DistributionView { distribution, progress, payouts })
+    }
+
+    pub async fn list(&self, user: &User) -> Result<Vec<DbDistribution>, String> {
+        self.database.list_distributions_by_creator(&user.wallet_address).await.map_err(|e| e.to_string())
+    }
+
+    /// Stop sending payouts until resumed
+    pub async fn pause(&self, user: &User, distribution_id: &str) -> Result<(), String> {
+        let distribution = self.load_owned(user, distribution_id).await?;
+        if !self.database.set_distribution_status(distribution_id, "running", "paused").await.map_err(|e| e.to_string())? {
+            return Err(format!("Distribution is {}", distribution.status));
+        }
+        Ok(())
+    }
+
+    /// Continue a paused distribution, retrying payouts that were given up on
+    pub async fn resume(&self, user: &User, distribution_id: &str) -> Result<(), String> {
+        let distribution = self.load_owned(user, distribution_id).await?;
+        if distribution.status != "paused" {
+            return Err(format!("Distribution is {}", distribution.status));
+        }
+        let retried = self.database.retry_failed_payouts(distribution_id).await.map_err(|e| e.to_string())?;
+        self.database.set_distribution_status(distribution_id, "paused", "running").await.map_err(|e| e.to_string())?;
+        if retried > 0 {
+            info!("🪂 Retrying {} failed payout(s) of distribution {}", retried, distribution_id);
+        }
+        Ok(())
+    }
+
+    /// Send a batch of every running distribution's payouts on an interval
+    pub async fn run_payouts(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(PAYOUT_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let running = match self.database.list_distributions_by_status("running").await {
+                Ok(running) => running,
+                Err(e) => {
+                    error!("❌ Failed to load running distributions: {}", e);
+                    continue;
+                }
+            };
+            for distribution in running {
+                if let Err(e) = self.pay_batch(&distribution).await {
+                    error!("❌ Distribution {} batch failed: {}", distribution.distribution_id, e);
+                }
+            }
+        }
+    }
+
+    async fn pay_batch(&self, distribution: &DbDistribution) -> Result<(), String> {
+        let id = &distribution.distribution_id;
+        let batch = self.database.pending_distribution_payouts(id, distribution.batch_size).await.map_err(|e| e.to_string())?;
+        for payout in &batch {
+            let Some(payout_id) = payout.id else { continue };
+            let now = Utc::now().timestamp();
+
+            // Sent before a restart but not marked paid: don't pay twice
+            if let Some(tx_hash) = self.database.find_distribution_payout_tx(id, &payout.recipient_address).await.map_err(|e| e.to_string())? {
+                self.database.mark_payout_paid(payout_id, &tx_hash, now).await.map_err(|e| e.to_string())?;
+                continue;
+            }
+
+            match self.backend.send_tagged_transaction(
+                &distribution.distribution_address,
+                &payout.recipient_address,
+                payout.amount as u64,
+                None,
+                tags::for_kind(tags::DISTRIBUTION_PAYOUT, Some((tags::DISTRIBUTION_ID, id))),
+            ).await {
+                Ok(tx_hash) => {
+                    self.database.mark_payout_paid(payout_id, &tx_hash, now).await.map_err(|e| e.to_string())?;
+                }
+                Err(e) => {
+                    warn!("⚠️ Payout of {} sat to {} from distribution {} failed: {}", payout.amount, payout.recipient_address, id, e);
+                    self.database.mark_payout_attempt_failed(payout_id, MAX_ATTEMPTS).await.map_err(|e| e.to_string())?;
+                }
+            }
+        }
+
+        let progress = self.database.distribution_progress(id).await.map_err(|e| e.to_string())?;
+        if progress.pending > 0 {
+            return Ok(());
+        }
+        if progress.failed > 0 {
+            // Left for the creator to resume once the cause is fixed
+            self.database.set_distribution_status(id, "running", "paused").await.map_err(|e| e.to_string())?;
+            warn!("⚠️ Distribution {} paused with {} failed payout(s)", id, progress.failed);
+        } else {
+            self.database.complete_distribution(id, Utc::now().timestamp()).await.map_err(|e| e.to_string())?;
+            info!("🪂 Distribution {} completed: {} sat to {} holder(s)", id, progress.paid_amount, progress.paid);
+        }
+        Ok(())
+    }
+
+    async fn load(&self, distribution_id: &str) -> Result<DbDistribution, String> {
+        self.database.get_distribution(distribution_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Distribution not found".to_string())
+    }
+
+    async fn load_owned(&self, user: &User, distribution_id: &str) -> Result<DbDistribution, String> {
+        let distribution = self.load(distribution_id).await?;
+        if distribution.creator_address != user.wallet_address {
+            return Err("Only the creator can manage this distribution".to_string());
+        }
+        Ok(distribution)
+    }
+}
+
+/// Each holder's share of `amount`. Holders come largest first; the
+/// rounding remainder goes to the first and holders whose share rounds to
+/// zero are dropped, so the shares always add up to `amount`.
+fn split(kind: DistributionKind, amount: i64, holders: &[DbHolder]) -> Vec<(&DbHolder, i64)> {
+    let weight = |holder: &DbHolder| match kind {
+        DistributionKind::Dividend => holder.weight.max(0) as i128,
+        DistributionKind::Airdrop => 1,
+    };
+    let total: i128 = holders.iter().map(weight).sum();
+    if total <= 0 {
+        return Vec::new();
+    }
+    let mut shares: Vec<(&DbHolder, i64)> = holders.iter()
+        .map(|holder| (holder, (amount as i128 * weight(holder) / total) as i64))
+        .collect();
+    let paid: i64 = shares.iter().map(|(_, share)| share).sum();
+    shares[0].1 += amount - paid;
+    shares.retain(|(_, share)| *share > 0);
+    shares
+}
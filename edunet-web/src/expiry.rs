@@ -0,0 +1,50 @@
+//! Expired payments
+//!
+//! The mempool drops a transaction that has waited `max_transaction_age`
+//! without confirming. Its sender would otherwise see it as pending forever.
+//! The expiry watcher runs the embedded node's mempool maintenance, listens
+//! for those removals on the mempool's event stream and marks the payment
+//! expired, then publishes `ChainEvent::TransactionExpired` on the backend
+//! event bus. With `auto_rebuild` the payment is sent again at the current
+//! fee and the event names the replacement.
+//!
+//! A remote node expires transactions in its own mempool, out of sight of
+//! the web tier, so the watcher only runs with an embedded node.
+
+use std::time::Duration;
+
+/// What happens to payments the mempool expires
+#[derive(Debug, Clone)]
+pub struct ExpiryConfig {
+    /// Send an expired payment again instead of only marking it expired
+    pub auto_rebuild: bool,
+    /// How often the embedded mempool is checked for expired transactions
+    pub maintenance_interval: Duration,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            auto_rebuild: false,
+            maintenance_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ExpiryConfig {
+    /// Defaults, overridden by `EXPIRED_PAYMENTS_AUTO_REBUILD` and
+    /// `MEMPOOL_MAINTENANCE_SECS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            auto_rebuild: std::env::var("EXPIRED_PAYMENTS_AUTO_REBUILD")
+                .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(defaults.auto_rebuild),
+            maintenance_interval: std::env::var("MEMPOOL_MAINTENANCE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.maintenance_interval),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Explorer response cache
+//!
+//! Hot public explorer and balance queries are answered from an in-process
+//! cache, so a lecture hall of dashboards polling at once doesn't turn into
+//! one chain-state read per request. Each endpoint has its own TTL, and
+//! entries are dropped early when the chain moves:
+//! - A new tip clears everything
+//! - A confirmed transaction clears both parties' balances and the
+//!   network-wide views
+//!
+//! Concurrent misses for the same key share a single load.
+
+use crate::blockchain_integration::{ChainEvent, TransactionHistory};
+use blockchain_core::consensus::ChainTip;
+use blockchain_core::utxo::Balance;
+use moka::future::Cache;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+
+/// Per-endpoint cache lifetimes
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub network_status_ttl: Duration,
+    pub recent_transactions_ttl: Duration,
+    pub sync_status_ttl: Duration,
+    pub balance_ttl: Duration,
+    /// Most addresses whose balances are kept
+    pub max_balances: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            network_status_ttl: Duration::from_secs(5),
+            recent_transactions_ttl: Duration::from_secs(5),
+            sync_status_ttl: Duration::from_secs(2),
+            balance_ttl: Duration::from_secs(10),
+            max_balances: 10_000,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Defaults, overridden by `EXPLORER_CACHE_<ENDPOINT>_TTL_SECS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let ttl = |name: &str, default: Duration| {
+            std::env::var(format!("EXPLORER_CACHE_{}_TTL_SECS", name))
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default)
+        };
+        Self {
+            network_status_ttl: ttl("NETWORK_STATUS", defaults.network_status_ttl),
+            recent_transactions_ttl: ttl("RECENT_TRANSACTIONS", defaults.recent_transactions_ttl),
+            sync_status_ttl: ttl("SYNC_STATUS", defaults.sync_status_ttl),
+            balance_ttl: ttl("BALANCE", defaults.balance_ttl),
+            ..defaults
+        }
+    }
+}
+
+/// Cached explorer views, invalidated as blocks and transactions arrive
+pub struct ExplorerCache {
+    network_status: Cache<(), serde_json::Value>,
+    recent_transactions: Cache<usize, Vec<TransactionHistory>>,
+    sync_status: Cache<(), serde_json::Value>,
+    balances: Cache<String, Balance>,
+}
+
+impl ExplorerCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            network_status: Cache::builder().max_capacity(1).time_to_live(config.network_status_ttl).build(),
+            recent_transactions: Cache::builder().max_capacity(16).time_to_live(config.recent_transactions_ttl).build(),
+            sync_status: Cache::builder().max_capacity(1).time_to_live(config.sync_status_ttl).build(),
+            balances: Cache::builder().max_capacity(config.max_balances).time_to_live(config.balance_ttl).build(),
+        }
+    }
+
+    pub async fn network_status<F>(&self, load: F) -> anyhow::Result<serde_json::Value>
+    where
+        F: Future<Output = anyhow::Result<serde_json::Value>>,
+    {
+        self.network_status.try_get_with((), load).await.map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    pub async fn recent_transactions<F>(&self, limit: usize, load: F) -> anyhow::Result<Vec<TransactionHistory>>
+    where
+        F: Future<Output = anyhow::Result<Vec<TransactionHistory>>>,
+    {
+        self.recent_transactions.try_get_with(limit, load).await.map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    pub async fn sync_status<F>(&self, load: F) -> serde_json::Value
+    where
+        F: Future<Output = serde_json::Value>,
+    {
+        self.sync_status.get_with((), load).await
+    }
+
+    pub async fn balance<F>(&self, address: &str, load: F) -> anyhow::Result<Balance>
+    where
+        F: Future<Output = anyhow::Result<Balance>>,
+    {
+        self.balances.try_get_with(address.to_string(), load).await.map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    /// Drop everything cached about an address and the network-wide views it appears in
+    pub async fn invalidate_address(&self, address: &str) {
+        self.balances.invalidate(address).await;
+        self.network_status.invalidate_all();
+        self.recent_transactions.invalidate_all();
+    }
+
+    /// Drop every cached entry
+    pub fn invalidate_all(&self) {
+        self.network_status.invalidate_all();
+        self.recent_transactions.invalidate_all();
+        self.sync_status.invalidate_all();
+        self.balances.invalidate_all();
+    }
+
+    /// Invalidate on new tips and confirmed transactions until the backend goes away
+    pub async fn run(
+        self: Arc<Self>,
+        mut tips: broadcast::Receiver<ChainTip>,
+        mut events: broadcast::Receiver<ChainEvent>,
+    ) {
+        loop {
+            tokio::select! {
+                tip = tips.recv() => match tip {
+                    Ok(tip) => {
+                        debug!("New tip at height {}, clearing explorer cache", tip.height);
+                        self.invalidate_all();
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => self.invalidate_all(),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                event = events.recv() => match event {
+                    Ok(ChainEvent::TransactionConfirmed { from_address, to_address, .. }) => {
+                        self.invalidate_address(&from_address).await;
+                        self.invalidate_address(&to_address).await;
+                    }
+                    Ok(ChainEvent::TransactionExpired { from_address, .. }) => {
+                        self.invalidate_address(&from_address).await;
+                    }
+                    Ok(ChainEvent::BlockDisconnected { .. }) => self.invalidate_all(),
+                    Err(broadcast::error::RecvError::Lagged(_)) => self.invalidate_all(),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        info!("Explorer cache invalidation stopped");
+    }
+}
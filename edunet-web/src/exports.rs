@@ -0,0 +1,360 @@
+//! Bulk data exports
+//!
+//! Block ranges, address histories and treasury sales as CSV or JSON, so
+//! researchers and auditors can pull data without scraping the explorer
+//! pages. Bodies are streamed: rows go out as they are read, and a failure
+//! part-way aborts the response rather than ending it as if complete.
+//!
+//! Each export covers a bounded range (a number of blocks or a time
+//! window) and each client IP gets a number of exports per minute. Amounts
+//! are satoshis, as decimal strings in JSON; hashes are hex.
+
+use crate::blockchain_integration::BlockchainBackend;
+use crate::database::Database;
+use crate::ledger::{self, Direction, LedgerQuery};
+use axum::body::Body;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
+
+/// Chunks buffered ahead of a slow client
+const STREAM_BUFFER: usize = 64;
+
+/// Clients tracked before expired rate limit windows are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+const BLOCK_COLUMNS: &[&str] = &["height", "hash", "prev_hash", "timestamp", "transactions_count"];
+const HISTORY_COLUMNS: &[&str] = &[
+    "timestamp", "tx_hash", "direction", "counterparty", "amount", "fee", "net_amount",
+    "balance_after", "label", "status", "block_height",
+];
+const SALE_COLUMNS: &[&str] = &[
+    "sale_id", "timestamp", "buyer_address", "amount", "price_per_edu_cents", "total_payment_cents",
+    "payment_method", "payment_proof", "tx_hash",
+];
+
+/// Range and rate limits
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    /// Most blocks in one block export
+    pub max_blocks: u64,
+    /// Longest time window of a history or sales export, in seconds
+    pub max_window_secs: i64,
+    /// Exports each client IP may start per `rate_window`
+    pub rate_limit: u32,
+    pub rate_window: Duration,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            max_blocks: 1_000,
+            max_window_secs: 366 * 24 * 60 * 60,
+            rate_limit: 10,
+            rate_window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ExportConfig {
+    /// Defaults, overridden by `EXPORT_MAX_BLOCKS`, `EXPORT_MAX_DAYS` and
+    /// `EXPORT_RATE_LIMIT` (exports per client per minute)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).filter(|v| *v > 0);
+        Self {
+            max_blocks: var("EXPORT_MAX_BLOCKS").unwrap_or(defaults.max_blocks),
+            max_window_secs: var("EXPORT_MAX_DAYS").map_or(defaults.max_window_secs, |days| days as i64 * 24 * 60 * 60),
+            rate_limit: var("EXPORT_RATE_LIMIT").map_or(defaults.rate_limit, |limit| limit as u32),
+            ..defaults
+        }
+    }
+}
+
+/// Export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Csv,
+    /// One JSON array, one row per line
+    Json,
+}
+
+impl DataFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format.unwrap_or("csv").to_ascii_lowercase().as_str() {
+            "csv" => Ok(DataFormat::Csv),
+            "json" => Ok(DataFormat::Json),
+            other => Err(format!("Unsupported export format '{}', use csv or json", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            DataFormat::Csv => "text/csv; charset=utf-8",
+            DataFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            DataFormat::Json => "json",
+        }
+    }
+}
+
+/// Block heights to export, both ends inclusive. Defaults to the latest
+/// `max_blocks` blocks
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BlockRangeQuery {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// `csv` (default) or `json`
+    pub format: Option<String>,
+}
+
+/// Time window to export, in unix seconds, both ends inclusive. Defaults
+/// to the longest window allowed, ending now
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TimeRangeQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// `csv` (default) or `json`
+    pub format: Option<String>,
+}
+
+type ExportError = (StatusCode, String);
+
+fn bad_request(message: impl Into<String>) -> ExportError {
+    (StatusCode::BAD_REQUEST, message.into())
+}
+
+/// Fixed-window count of exports per client
+struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    clients: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    /// Count an export for `client`, or say how long until it may start one
+    fn admit(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if clients.len() >= MAX_TRACKED_CLIENTS {
+            clients.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+        let (start, count) = clients.entry(client.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.limit {
+            return Err(self.window.saturating_sub(now.duration_since(*start)));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// Writes rows into a streamed response body
+struct RowSink {
+    tx: mpsc::Sender<Result<String, std::io::Error>>,
+    format: DataFormat,
+    columns: &'static [&'static str],
+    rows: usize,
+}
+
+impl RowSink {
+    /// A sink and the response streaming what is written to it
+    fn open(format: DataFormat, columns: &'static [&'static str], filename: &str) -> (Self, Response) {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let header = match format {
+            DataFormat::Csv => format!("{}\n", columns.join(",")),
+            DataFormat::Json => "[".to_string(),
+        };
+        // The channel is empty, so this cannot fail
+        let _ = tx.try_send(Ok(header));
+
+        let disposition = format!("attachment; filename=\"{}.{}\"", filename, format.extension());
+        let response = (
+            StatusCode::OK,
+            [
+                ("content-type", format.content_type().to_string()),
+                ("content-disposition", disposition),
+            ],
+            Body::from_stream(ReceiverStream::new(rx)),
+        ).into_response();
+        (Self { tx, format, columns, rows: 0 }, response)
+    }
+
+    /// Write a row; false once the client has gone away
+    async fn row(&mut self, row: &Value) -> bool {
+        let chunk = match self.format {
+            DataFormat::Csv => {
+                let fields: Vec<String> = self.columns.iter().map(|column| csv_value(&row[*column])).collect();
+                format!("{}\n", fields.join(","))
+            }
+            DataFormat::Json => format!("{}\n{}", if self.rows == 0 { "" } else { "," }, row),
+        };
+        self.rows += 1;
+        self.tx.send(Ok(chunk)).await.is_ok()
+    }
+
+    async fn finish(self) {
+        if self.format == DataFormat::Json {
+            let _ = self.tx.send(Ok("]\n".to_string())).await;
+        }
+    }
+
+    /// Abort the response so the client sees an incomplete transfer
+    async fn fail(self, message: String) {
+        error!("Export failed after {} rows: {}", self.rows, message);
+        let _ = self.tx.send(Err(std::io::Error::other(message))).await;
+    }
+}
+
+fn csv_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => ledger::csv_field(text),
+        other => ledger::csv_field(&other.to_string()),
+    }
+}
+
+/// Streams bulk exports within the configured limits
+pub struct Exporter {
+    backend: Arc<BlockchainBackend>,
+    database: Arc<Database>,
+    config: ExportConfig,
+    limiter: RateLimiter,
+}
+
+impl Exporter {
+    pub fn new(backend: Arc<BlockchainBackend>, database: Arc<Database>, config: ExportConfig) -> Self {
+        let limiter = RateLimiter { limit: config.rate_limit, window: config.rate_window, clients: Mutex::new(HashMap::new()) };
+        Self { backend, database, config, limiter }
+    }
+
+    /// Count an export for a client IP, or say how long until it may start one
+    pub fn admit(&self, client: &str) -> Result<(), Duration> {
+        self.limiter.admit(client)
+    }
+
+    /// Check a time window against the limit, filling in the defaults
+    fn time_window(&self, query: &TimeRangeQuery) -> Result<(i64, i64), ExportError> {
+        let to = query.to.unwrap_or_else(|| Utc::now().timestamp());
+        let from = query.from.unwrap_or(to - self.config.max_window_secs);
+        if from > to {
+            return Err(bad_request("from is after to"));
+        }
+        if to - from > self.config.max_window_secs {
+            return Err(bad_request(format!(
+                "At most {} days per export, narrow from and to",
+                self.config.max_window_secs / (24 * 60 * 60)
+            )));
+        }
+        Ok((from, to))
+    }
+
+    /// Block headers in a height range
+    pub async fn blocks(&self, query: &BlockRangeQuery) -> Result<Response, ExportError> {
+        let format = DataFormat::parse(query.format.as_deref()).map_err(bad_request)?;
+        let tip = self.backend.chain_height().await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, format!("Chain height unavailable: {}", e)))?;
+        let to = query.to.unwrap_or(tip).min(tip);
+        let from = query.from.unwrap_or_else(|| to.saturating_sub(self.config.max_blocks - 1));
+        if from > to {
+            return Err(bad_request(format!("from is above to or the chain tip ({})", tip)));
+        }
+        if to - from >= self.config.max_blocks {
+            return Err(bad_request(format!("At most {} blocks per export, narrow from and to", self.config.max_blocks)));
+        }
+
+        let (mut sink, response) = RowSink::open(format, BLOCK_COLUMNS, &format!("blocks-{}-{}", from, to));
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            for height in from..=to {
+                match backend.block_summary(height).await {
+                    Ok(Some(block)) => {
+                        if !sink.row(&block).await {
+                            return;
+                        }
+                    }
+                    // Reorganized below the tip since the range was checked
+                    Ok(None) => break,
+                    Err(e) => return sink.fail(format!("Failed to read block {}: {}", height, e)).await,
+                }
+            }
+            sink.finish().await;
+        });
+        Ok(response)
+    }
+
+    /// Ledger entries of an address within a time window
+    pub async fn address_history(&self, address: &str, query: &TimeRangeQuery) -> Result<Response, ExportError> {
+        let format = DataFormat::parse(query.format.as_deref()).map_err(bad_request)?;
+        let (from, to) = self.time_window(query)?;
+        let window = LedgerQuery { from: Some(from), to: Some(to), format: None };
+        let entries = ledger::address_ledger(&self.database, address, &window).await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+        let (mut sink, response) = RowSink::open(format, HISTORY_COLUMNS, &format!("history-{}", address));
+        tokio::spawn(async move {
+            for entry in entries {
+                let direction = match entry.direction {
+                    Direction::Incoming => "incoming",
+                    Direction::Outgoing => "outgoing",
+                    Direction::SelfTransfer => "self_transfer",
+                };
+                let row = json!({
+                    "timestamp": entry.timestamp,
+                    "tx_hash": entry.tx_hash,
+                    "direction": direction,
+                    "counterparty": entry.counterparty,
+                    "amount": entry.amount.to_string(),
+                    "fee": entry.fee.to_string(),
+                    "net_amount": entry.net_amount().to_string(),
+                    "balance_after": entry.balance_after.to_string(),
+                    "label": entry.label,
+                    "status": entry.status,
+                    "block_height": entry.block_height,
+                });
+                if !sink.row(&row).await {
+                    return;
+                }
+            }
+            sink.finish().await;
+        });
+        Ok(response)
+    }
+
+    /// Treasury coin sales within a time window
+    pub async fn treasury_sales(&self, query: &TimeRangeQuery) -> Result<Response, ExportError> {
+        let format = DataFormat::parse(query.format.as_deref()).map_err(bad_request)?;
+        let (from, to) = self.time_window(query)?;
+        let sales = self.backend.treasury_sales().await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+
+        let (mut sink, response) = RowSink::open(format, SALE_COLUMNS, &format!("treasury-sales-{}-{}", from, to));
+        tokio::spawn(async move {
+            let in_window = sales.into_iter()
+                .filter(|sale| sale["timestamp"].as_i64().is_some_and(|t| (from..=to).contains(&t)));
+            for sale in in_window {
+                if !sink.row(&sale).await {
+                    return;
+                }
+            }
+            sink.finish().await;
+        });
+        Ok(response)
+    }
+}
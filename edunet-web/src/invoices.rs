@@ -0,0 +1,315 @@
+//! Payment request invoices
+//!
+//! Users request payments with invoices signed by the platform's issuer key,
+//! so payers can check a scanned QR code wasn't altered and was issued here.
+//! A confirmed payment to the invoice's address for at least its amount,
+//! made while it was open, settles the oldest matching pending invoice;
+//! invoices left unpaid past their expiry are marked expired.
+
+use crate::blockchain_integration::{BlockchainBackend, ChainEvent, TransactionStatus};
+use crate::database::{Database, DbInvoice};
+use crate::signer::{Signer, INVOICE_ISSUER_KEY};
+use crate::user_auth::User;
+use base64::Engine;
+use blockchain_core::amount::Amount;
+use blockchain_core::invoice::Invoice;
+use blockchain_core::qr::{self, QrFormat, QrOptions};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+/// Longest an invoice may stay open
+const MAX_TTL_SECS: i64 = 30 * 24 * 3600;
+
+/// Minimum edge of invoice QR codes in pixels
+const QR_SIZE: u32 = 320;
+
+#[derive(Debug, Clone)]
+pub struct InvoiceConfig {
+    /// Signer key id invoices are signed with
+    pub issuer_key_id: String,
+    /// Validity of invoices that don't ask for one
+    pub default_ttl_secs: i64,
+    pub expiry_interval_secs: u64,
+}
+
+impl InvoiceConfig {
+    /// Read `INVOICE_SIGNING_KEY_ID` and `INVOICE_DEFAULT_TTL_SECS`
+    pub fn from_env() -> Self {
+        Self {
+            issuer_key_id: std::env::var("INVOICE_SIGNING_KEY_ID").unwrap_or_else(|_| INVOICE_ISSUER_KEY.to_string()),
+            default_ttl_secs: std::env::var("INVOICE_DEFAULT_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            expiry_interval_secs: 60,
+        }
+    }
+}
+
+/// Invoice creation request
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateInvoiceRequest {
+    /// Amount in EDU
+    pub amount: f64,
+    pub memo: Option<String>,
+    /// Validity in seconds; defaults to `INVOICE_DEFAULT_TTL_SECS`
+    pub expires_in_secs: Option<i64>,
+}
+
+/// Invoice verification request
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyInvoiceRequest {
+    /// `edunet:` URI scanned from the QR code
+    pub uri: String,
+}
+
+/// An invoice with its payment status
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceView {
+    pub invoice: Invoice,
+    /// pending, paid or expired
+    pub status: String,
+    pub paid_tx_hash: Option<String>,
+    pub paid_at: Option<i64>,
+    /// URI to encode in the QR code
+    pub uri: String,
+    /// Base64 PNG of the QR code
+    pub qr_code: String,
+    /// Whether the invoice was signed with this platform's issuer key
+    pub issued_here: bool,
+}
+
+pub struct InvoiceManager {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    config: InvoiceConfig,
+    signer: Arc<dyn Signer>,
+    issuer_public_key: Vec<u8>,
+}
+
+impl InvoiceManager {
+    pub async fn new(
+        database: Arc<Database>,
+        backend: Arc<BlockchainBackend>,
+        signer: Arc<dyn Signer>,
+        config: InvoiceConfig,
+    ) -> Result<Self, String> {
+        let issuer_public_key = signer.public_key(&config.issuer_key_id).await.map_err(|e| e.to_string())?;
+        Ok(Self { database, backend, config, signer, issuer_public_key })
+    }
+
+    pub fn config(&self) -> &InvoiceConfig {
+        &self.config
+    }
+
+    /// Public key payers should expect on invoices from this platform
+    pub fn issuer_public_key(&self) -> &[u8] {
+        &self.issuer_public_key
+    }
+
+    /// Issue a signed invoice paying the user's wallet
+    pub async fn create(&self, user: &User, request: &CreateInvoiceRequest) -> Result<InvoiceView, String> {
+        if !request.amount.is_finite() || request.amount <= 0.0 {
+            return Err("Amount must be positive".to_string());
+        }
+        let ttl = request.expires_in_secs.unwrap_or(self.config.default_ttl_secs);
+        if !(1..=MAX_TTL_SECS).contains(&ttl) {
+            return Err(format!("Expiry must be between 1 and {} seconds", MAX_TTL_SECS));
+        }
+
+        let amount = Amount::from_edu_f64(request.amount)
+            .map_err(|e| format!("Invalid amount: {}", e))?
+            .to_sat();
+        let mut invoice = Invoice::unsigned(
+            user.wallet_address.clone(),
+            amount,
+            request.memo.clone(),
+            ttl,
+            self.issuer_public_key.clone(),
+        ).map_err(|e| e.to_string())?;
+        invoice.signature = self.signer.sign_digest(&self.config.issuer_key_id, &invoice.digest()).await
+            .map_err(|e| format!("Failed to sign invoice: {}", e))?;
+
+        let row = to_db(&invoice, &user.id.to_string())?;
+        self.database.insert_invoice(&row).await.map_err(|e| e.to_string())?;
+        info!("🧾 {} issued invoice {} for {} sat", user.username, invoice.id, invoice.amount);
+        self.view(row)
+    }
+
+    /// Invoice and its current status, settling it if a matching payment
+    /// has confirmed and expiring it if its deadline has passed
+    pub async fn status(&self, invoice_id: &str) -> Result<InvoiceView, String> {
+        let row = self.load(invoice_id).await?;
+        if row.status == "pending" {
+            self.settle_from_history(&row).await?;
+            self.database.expire_invoices(Utc::now().timestamp()).await.map_err(|e| e.to_string())?;
+            return self.view(self.load(invoice_id).await?);
+        }
+        self.view(row)
+    }
+
+    /// Invoices the user has issued, newest first
+    pub async fn list(&self, user: &User, limit: i64) -> Result<Vec<InvoiceView>, String> {
+        self.database.list_invoices_for_user(&user.id.to_string(), limit.clamp(1, 200)).await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|row| self.view(row))
+            .collect()
+    }
+
+    /// Check a scanned invoice URI: signature, issuer and, for invoices
+    /// issued here, the recorded status
+    pub async fn verify_uri(&self, uri: &str) -> Result<InvoiceView, String> {
+        let invoice = Invoice::from_uri(uri.trim()).map_err(|e| e.to_string())?;
+        if invoice.issuer_public_key != self.issuer_public_key {
+            let status = if invoice.is_expired() { "expired" } else { "unknown" };
+            return Ok(InvoiceView {
+                uri: invoice.to_uri(),
+                qr_code: qr_png_base64(&invoice.to_uri())?,
+                invoice,
+                status: status.to_string(),
+                paid_tx_hash: None,
+                paid_at: None,
+                issued_here: false,
+            });
+        }
+
+        let view = self.status(&invoice.id).await?;
+        if view.invoice != invoice {
+            return Err("Invoice does not match the one issued".to_string());
+        }
+        Ok(view)
+    }
+
+    /// Settle invoices from confirmed payments on the backend event bus
+    pub async fn run_payments(self: Arc<Self>, mut events: broadcast::Receiver<ChainEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(ChainEvent::TransactionConfirmed { hash, to_address, amount, .. }) => {
+                    if let Err(e) = self.settle_payment(&hash, &to_address, amount, Utc::now().timestamp()).await {
+                        error!("❌ Failed to settle invoice for payment {}: {}", hash, e);
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // Missed payments are still found when the invoice status is checked
+                    warn!("⚠️ Invoice watcher skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Mark unpaid invoices expired every interval
+    pub async fn run_expiry(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(self.config.expiry_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            match self.database.expire_invoices(Utc::now().timestamp()).await {
+                Ok(0) => {}
+                Ok(expired) => info!("🧾 Expired {} unpaid invoices", expired),
+                Err(e) => error!("❌ Failed to expire invoices: {}", e),
+            }
+        }
+    }
+
+    /// Settle the oldest open invoice a payment matches, if any
+    async fn settle_payment(&self, tx_hash: &str, to_address: &str, amount: u64, timestamp: i64) -> Result<Option<String>, String> {
+        let candidates = self.database.pending_invoices_for_destination(to_address).await.map_err(|e| e.to_string())?;
+        for row in candidates {
+            if !from_db(&row)?.matches_payment(to_address, amount, timestamp) {
+                continue;
+            }
+            if self.database.mark_invoice_paid(&row.invoice_id, tx_hash, timestamp).await.map_err(|e| e.to_string())? {
+                info!("🧾 Invoice {} paid by {}", row.invoice_id, tx_hash);
+                return Ok(Some(row.invoice_id));
+            }
+            // The payment already settled another invoice
+            return Ok(None);
+        }
+        Ok(None)
+    }
+
+    /// Look for a confirmed payment the event watcher may have missed
+    async fn settle_from_history(&self, row: &DbInvoice) -> Result<(), String> {
+        let invoice = from_db(row)?;
+        let mut history = self.backend.get_transaction_history(&invoice.destination).await.map_err(|e| e.to_string())?;
+        history.sort_by_key(|tx| tx.timestamp);
+
+        for tx in history {
+            if !matches!(tx.status, TransactionStatus::Confirmed)
+                || !invoice.matches_payment(&tx.to_address, tx.amount, tx.timestamp.timestamp())
+                || self.database.invoice_paid_by(&tx.hash).await.map_err(|e| e.to_string())?
+            {
+                continue;
+            }
+            // Older open invoices to the same address take the payment first
+            if self.settle_payment(&tx.hash, &tx.to_address, tx.amount, tx.timestamp.timestamp()).await?
+                .as_deref() == Some(invoice.id.as_str())
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn load(&self, invoice_id: &str) -> Result<DbInvoice, String> {
+        self.database.get_invoice(invoice_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Invoice not found".to_string())
+    }
+
+    fn view(&self, row: DbInvoice) -> Result<InvoiceView, String> {
+        let invoice = from_db(&row)?;
+        let uri = invoice.to_uri();
+        Ok(InvoiceView {
+            qr_code: qr_png_base64(&uri)?,
+            issued_here: invoice.issuer_public_key == self.issuer_public_key,
+            invoice,
+            status: row.status,
+            paid_tx_hash: row.paid_tx_hash,
+            paid_at: row.paid_at,
+            uri,
+        })
+    }
+}
+
+fn to_db(invoice: &Invoice, issuer_user_id: &str) -> Result<DbInvoice, String> {
+    Ok(DbInvoice {
+        invoice_id: invoice.id.clone(),
+        issuer_user_id: issuer_user_id.to_string(),
+        destination: invoice.destination.clone(),
+        amount: i64::try_from(invoice.amount).map_err(|_| "Invoice amount is too large".to_string())?,
+        memo: invoice.memo.clone(),
+        created_at: invoice.created_at,
+        expires_at: invoice.expires_at,
+        issuer_public_key: hex::encode(&invoice.issuer_public_key),
+        signature: hex::encode(&invoice.signature),
+        status: "pending".to_string(),
+        paid_tx_hash: None,
+        paid_at: None,
+    })
+}
+
+fn from_db(row: &DbInvoice) -> Result<Invoice, String> {
+    Ok(Invoice {
+        id: row.invoice_id.clone(),
+        destination: row.destination.clone(),
+        amount: u64::try_from(row.amount).map_err(|_| format!("Invalid amount on invoice {}", row.invoice_id))?,
+        memo: row.memo.clone(),
+        created_at: row.created_at,
+        expires_at: row.expires_at,
+        issuer_public_key: hex::decode(&row.issuer_public_key).map_err(|e| e.to_string())?,
+        signature: hex::decode(&row.signature).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Render data as a QR code PNG, base64-encoded
+pub fn qr_png_base64(data: &str) -> Result<String, String> {
+    let options = QrOptions { format: QrFormat::Png, size: QR_SIZE, ..QrOptions::default() };
+    let image = qr::render(data, &options).map_err(|e| e.to_string())?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(image.data))
+}
@@ -0,0 +1,262 @@
+//! Wallet ledger and accounting exports
+//!
+//! Turns the transactions recorded for an address into a ledger: one entry
+//! per incoming or outgoing payment with its fee, counterparty, label and
+//! the running balance after it. Failed, rejected and conflicted transactions
+//! never moved funds and are left out. Balances start from zero at the first
+//! recorded transaction, so funds credited outside the transaction table
+//! (genesis allocations) are not part of them.
+//!
+//! Ledgers export as CSV for spreadsheets and as OFX 2.2 statements for
+//! accounting software. Amounts are in EDU with eight decimal places.
+
+use crate::database::{Database, DbTransaction};
+use blockchain_core::wallet::TransactionTags;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const SATOSHIS_PER_EDU: i64 = 100_000_000;
+
+/// Direction of a ledger entry as seen from the ledger's address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+    /// Sent to itself; only the fee leaves the wallet
+    SelfTransfer,
+}
+
+/// One transaction in an address's ledger
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerEntry {
+    pub tx_hash: String,
+    pub timestamp: i64,
+    pub direction: Direction,
+    /// The other side of the payment
+    pub counterparty: String,
+    /// Satoshis paid or received, fee excluded
+    pub amount: i64,
+    /// Fee paid by this address, zero for incoming payments
+    pub fee: i64,
+    /// Transaction memo
+    pub label: Option<String>,
+    pub status: String,
+    pub block_height: Option<i64>,
+    /// Balance in satoshis once this entry is applied
+    pub balance_after: i64,
+    #[serde(skip_serializing_if = "TransactionTags::is_empty")]
+    pub tags: TransactionTags,
+}
+
+impl LedgerEntry {
+    /// Change to the balance in satoshis
+    pub fn net_amount(&self) -> i64 {
+        match self.direction {
+            Direction::Incoming => self.amount,
+            Direction::Outgoing => -(self.amount + self.fee),
+            Direction::SelfTransfer => -self.fee,
+        }
+    }
+}
+
+/// Time window of a ledger request, in unix seconds, both ends inclusive
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LedgerQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// Export format, `csv` (default) or `ofx`
+    pub format: Option<String>,
+}
+
+/// Ledger export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ofx,
+}
+
+impl ExportFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format.unwrap_or("csv").to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "ofx" => Ok(ExportFormat::Ofx),
+            other => Err(format!("Unsupported export format '{}', use csv or ofx", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ofx => "application/x-ofx",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ofx => "ofx",
+        }
+    }
+}
+
+/// Ledger of an address within the query's window. Balances account for
+/// every earlier transaction, not only those in the window.
+pub async fn address_ledger(database: &Database, address: &str, query: &LedgerQuery) -> Result<Vec<LedgerEntry>, String> {
+    let transactions = database.get_address_transactions_chronological(address).await
+        .map_err(|e| format!("Failed to load transactions: {}", e))?;
+    let hashes: Vec<String> = transactions.iter().map(|tx| tx.tx_hash.clone()).collect();
+    let mut tags = database.get_transaction_tags(&hashes).await
+        .map_err(|e| format!("Failed to load transaction tags: {}", e))?;
+
+    let mut balance = 0i64;
+    let mut entries = Vec::new();
+    for tx in transactions.iter().filter(|tx| !matches!(tx.status.as_str(), "failed" | "rejected" | "conflicted")) {
+        let mut entry = entry_for(address, tx, &mut balance);
+        entry.tags = tags.remove(&tx.tx_hash).unwrap_or_default();
+        let in_window = query.from.is_none_or(|from| entry.timestamp >= from)
+            && query.to.is_none_or(|to| entry.timestamp <= to);
+        if in_window {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn entry_for(address: &str, tx: &DbTransaction, balance: &mut i64) -> LedgerEntry {
+    let (direction, counterparty, fee) = if tx.from_address == address && tx.to_address == address {
+        (Direction::SelfTransfer, address.to_string(), tx.fee)
+    } else if tx.from_address == address {
+        (Direction::Outgoing, tx.to_address.clone(), tx.fee)
+    } else {
+        (Direction::Incoming, tx.from_address.clone(), 0)
+    };
+
+    let mut entry = LedgerEntry {
+        tx_hash: tx.tx_hash.clone(),
+        timestamp: tx.timestamp,
+        direction,
+        counterparty,
+        amount: tx.amount,
+        fee,
+        label: tx.memo.clone(),
+        status: tx.status.clone(),
+        block_height: tx.block_height,
+        balance_after: 0,
+        tags: TransactionTags::new(),
+    };
+    *balance += entry.net_amount();
+    entry.balance_after = *balance;
+    entry
+}
+
+/// Satoshis as a signed EDU decimal, e.g. `-1.50000000`
+fn edu(satoshis: i64) -> String {
+    let sign = if satoshis < 0 { "-" } else { "" };
+    let abs = satoshis.unsigned_abs();
+    let unit = SATOSHIS_PER_EDU as u64;
+    format!("{}{}.{:08}", sign, abs / unit, abs % unit)
+}
+
+fn rfc3339(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_default().to_rfc3339()
+}
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Ledger as CSV with a header row
+pub fn to_csv(entries: &[LedgerEntry]) -> String {
+    let mut csv = String::from("date,tx_hash,direction,counterparty,amount_edu,fee_edu,net_edu,balance_edu,label,status,block_height,tags\n");
+    for entry in entries {
+        let direction = match entry.direction {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+            Direction::SelfTransfer => "self_transfer",
+        };
+        let fields = [
+            rfc3339(entry.timestamp),
+            entry.tx_hash.clone(),
+            direction.to_string(),
+            csv_field(&entry.counterparty),
+            edu(entry.amount),
+            edu(entry.fee),
+            edu(entry.net_amount()),
+            edu(entry.balance_after),
+            csv_field(entry.label.as_deref().unwrap_or("")),
+            csv_field(&entry.status),
+            entry.block_height.map(|h| h.to_string()).unwrap_or_default(),
+            csv_field(&entry.tags.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(";")),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn ofx_date(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y%m%d%H%M%S").to_string()
+}
+
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Ledger as an OFX 2.2 bank statement for `address`. Fees are separate
+/// FEE transactions so they can be booked to their own account.
+pub fn to_ofx(address: &str, entries: &[LedgerEntry]) -> String {
+    let now = Utc::now().timestamp();
+    let start = entries.first().map_or(now, |e| e.timestamp);
+    let end = entries.last().map_or(now, |e| e.timestamp);
+    let closing_balance = entries.last().map_or(0, |e| e.balance_after);
+
+    let mut transactions = String::new();
+    for entry in entries {
+        let name: String = entry.counterparty.chars().take(32).collect();
+        let memo = entry.label.as_deref().map(xml_escape).unwrap_or_default();
+        let payment = match entry.direction {
+            Direction::Incoming => Some(("CREDIT", entry.amount)),
+            Direction::Outgoing => Some(("DEBIT", -entry.amount)),
+            Direction::SelfTransfer => None,
+        };
+        if let Some((kind, amount)) = payment {
+            transactions.push_str(&format!(
+                "<STMTTRN><TRNTYPE>{}</TRNTYPE><DTPOSTED>{}</DTPOSTED><TRNAMT>{}</TRNAMT><FITID>{}</FITID><NAME>{}</NAME><MEMO>{}</MEMO></STMTTRN>\n",
+                kind, ofx_date(entry.timestamp), edu(amount), entry.tx_hash, xml_escape(&name), memo,
+            ));
+        }
+        if entry.fee > 0 {
+            transactions.push_str(&format!(
+                "<STMTTRN><TRNTYPE>FEE</TRNTYPE><DTPOSTED>{}</DTPOSTED><TRNAMT>{}</TRNAMT><FITID>{}-fee</FITID><NAME>Network fee</NAME><MEMO>{}</MEMO></STMTTRN>\n",
+                ofx_date(entry.timestamp), edu(-entry.fee), entry.tx_hash, memo,
+            ));
+        }
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>
+<OFX>
+<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS><DTSERVER>{now}</DTSERVER><LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>
+<BANKMSGSRSV1><STMTTRNRS><TRNUID>0</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>
+<STMTRS><CURDEF>EDU</CURDEF>
+<BANKACCTFROM><BANKID>EDUNET</BANKID><ACCTID>{account}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>
+<BANKTRANLIST><DTSTART>{start}</DTSTART><DTEND>{end}</DTEND>
+{transactions}</BANKTRANLIST>
+<LEDGERBAL><BALAMT>{balance}</BALAMT><DTASOF>{end}</DTASOF></LEDGERBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+"#,
+        now = ofx_date(now),
+        account = xml_escape(address),
+        start = ofx_date(start),
+        end = ofx_date(end),
+        transactions = transactions,
+        balance = edu(closing_balance),
+    )
+}
@@ -0,0 +1,299 @@
+//! Secondary market for loan shares
+//!
+//! A lender can tokenize their funding of a loan into a position: a
+//! transferable record of principal that carries the right to the loan's
+//! future repayments. Positions can be listed for sale and bought outright;
+//! the buyer's payment goes straight to the seller and the position changes
+//! hands in the same step.
+//!
+//! Borrowers repay in installments. Each installment is split across the
+//! loan's stakes in proportion to principal and paid to whoever holds each
+//! stake at that moment, so a sold position's repayments follow the buyer.
+
+use crate::blockchain_integration::{tags, BlockchainBackend};
+use crate::database::{Database, DbLoanDistribution, DbLoanPosition, DbLoanPositionListing, DbLoanStake};
+use crate::spending::SpendingGuard;
+use crate::user_auth::User;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenizeRequest {
+    pub loan_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListPositionRequest {
+    pub position_id: String,
+    /// Asking price in satoshis
+    pub price: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BuyPositionRequest {
+    pub totp_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RepayRequest {
+    /// Installment in satoshis
+    pub amount: i64,
+    pub totp_code: Option<String>,
+}
+
+/// Result of paying an installment
+#[derive(Debug, Clone, Serialize)]
+pub struct Installment {
+    pub loan_id: String,
+    pub paid: i64,
+    pub outstanding: i64,
+    pub distributions: Vec<DbLoanDistribution>,
+}
+
+pub struct LoanShareMarket {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    spending: Arc<SpendingGuard>,
+}
+
+impl LoanShareMarket {
+    pub fn new(database: Arc<Database>, backend: Arc<BlockchainBackend>, spending: Arc<SpendingGuard>) -> Self {
+        Self { database, backend, spending }
+    }
+
+    /// Turn the user's untokenized funding of a loan into a position
+    pub async fn tokenize(&self, user: &User, request: &TokenizeRequest) -> Result<DbLoanPosition, String> {
+        let loan = self.database.get_loan_by_id(&request.loan_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Loan not found")?;
+        if loan.status != "funded" {
+            return Err("Only positions in funded loans can be tokenized".to_string());
+        }
+
+        let position = self.database.tokenize_loan_position(
+            &format!("pos_{}", Uuid::new_v4().simple()),
+            &loan.loan_id,
+            &user.wallet_address,
+            Utc::now().timestamp(),
+        ).await
+            .map_err(|e| e.to_string())?
+            .ok_or("You have no untokenized funding in this loan")?;
+        info!("🎟️ {} tokenized {} sat of loan {} as {}", user.username, position.principal, loan.loan_id, position.position_id);
+        Ok(position)
+    }
+
+    pub async fn positions(&self, user: &User) -> Result<Vec<DbLoanPosition>, String> {
+        self.database.list_loan_positions_by_holder(&user.wallet_address).await.map_err(|e| e.to_string())
+    }
+
+    pub async fn listings(&self, limit: i64) -> Result<Vec<DbLoanPositionListing>, String> {
+        self.database.list_active_loan_position_listings(limit.clamp(1, 200)).await.map_err(|e| e.to_string())
+    }
+
+    /// Offer a position for sale
+    pub async fn list(&self, user: &User, request: &ListPositionRequest) -> Result<DbLoanPositionListing, String> {
+        let position = self.load_position(&request.position_id).await?;
+        if position.holder_address != user.wallet_address {
+            return Err("You don't hold this position".to_string());
+        }
+        if request.price <= 0 {
+            return Err("Price must be positive".to_string());
+        }
+
+        let listing = DbLoanPositionListing {
+            listing_id: format!("lst_{}", Uuid::new_v4().simple()),
+            position_id: position.position_id,
+            seller_address: user.wallet_address.clone(),
+            price: request.price,
+            status: "active".to_string(),
+            buyer_address: None,
+            payment_tx_hash: None,
+            created_at: Utc::now().timestamp(),
+            closed_at: None,
+        };
+        // The unique open-listing index rejects a second listing
+        self.database.insert_loan_position_listing(&listing).await
+            .map_err(|_| "Position is already listed".to_string())?;
+        Ok(listing)
+    }
+
+    pub async fn cancel(&self, user: &User, listing_id: &str) -> Result<(), String> {
+        let listing = self.load_listing(listing_id).await?;
+        if listing.seller_address != user.wallet_address {
+            return Err("Only the seller can cancel a listing".to_string());
+        }
+        let closed = self.database.set_loan_listing_status(listing_id, "active", "cancelled", None, Some(Utc::now().timestamp())).await
+            .map_err(|e| e.to_string())?;
+        if !closed {
+            return Err(format!("Listing is {}", listing.status));
+        }
+        Ok(())
+    }
+
+    /// Buy a listed position: pay the seller, then take over the position
+    pub async fn buy(&self, user: &User, listing_id: &str, request: &BuyPositionRequest) -> Result<DbLoanPosition, String> {
+        let listing = self.load_listing(listing_id).await?;
+        if listing.seller_address == user.wallet_address {
+            return Err("You can't buy your own listing".to_string());
+        }
+
+        // Hold the listing so nobody else pays for it meanwhile
+        if !self.database.set_loan_listing_status(listing_id, "active", "pending", Some(&user.wallet_address), None).await
+            .map_err(|e| e.to_string())?
+        {
+            return Err("Listing is no longer available".to_string());
+        }
+
+        let reopen = || async {
+            if let Err(e) = self.database.set_loan_listing_status(listing_id, "pending", "active", None, None).await {
+                error!("❌ Failed to reopen loan share listing {}: {}", listing_id, e);
+            }
+        };
+        let reservation = match self.spending.reserve(user, listing.price as u64, request.totp_code.as_deref()).await {
+            Ok(reservation) => reservation,
+            Err(e) => {
+                reopen().await;
+                return Err(e);
+            }
+        };
+
+        let tx_hash = match self.backend.send_tagged_transaction(
+            &user.wallet_address,
+            &listing.seller_address,
+            listing.price as u64,
+            None,
+            tags::for_kind(tags::LOAN_SHARE_SALE, Some((tags::LOAN_POSITION_ID, &listing.position_id))),
+        ).await {
+            Ok(tx_hash) => {
+                self.spending.commit(reservation, &tx_hash).await;
+                tx_hash
+            }
+            Err(e) => {
+                self.spending.release(reservation).await;
+                reopen().await;
+                return Err(format!("Payment failed: {}", e));
+            }
+        };
+
+        let completed = self.database.complete_loan_listing(&listing, &user.wallet_address, &tx_hash, Utc::now().timestamp()).await
+            .map_err(|e| format!("Paid (tx {}) but failed to transfer the position: {}", tx_hash, e))?;
+        if !completed {
+            error!("❌ Loan share listing {} paid (tx {}) but the seller no longer holds the position", listing_id, tx_hash);
+            return Err(format!("Paid (tx {}) but the seller no longer holds the position; contact support", tx_hash));
+        }
+        info!("🎟️ {} bought position {} from {} for {} sat", user.username, listing.position_id, listing.seller_address, listing.price);
+        self.load_position(&listing.position_id).await
+    }
+
+    /// Pay an installment on the user's loan, distributed to the current
+    /// holders of its repayment rights
+    pub async fn repay(&self, user: &User, loan_id: &str, request: &RepayRequest) -> Result<Installment, String> {
+        let loan = self.database.get_loan_by_id(loan_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Loan not found")?;
+        if loan.applicant_address != user.wallet_address {
+            return Err("Only the borrower can repay this loan".to_string());
+        }
+        if loan.status != "funded" {
+            return Err(format!("Loan is {}", loan.status));
+        }
+        let repaid = self.database.loan_repaid_amount(loan_id).await.map_err(|e| e.to_string())?;
+        let outstanding = loan.funded_amount.unwrap_or(0) - repaid;
+        if request.amount <= 0 || request.amount > outstanding {
+            return Err(format!("Installment must be between 1 and the {} sat outstanding", outstanding));
+        }
+
+        let stakes = self.database.loan_stakes(loan_id).await.map_err(|e| e.to_string())?;
+        let shares = pro_rata(request.amount, &stakes);
+        if shares.is_empty() {
+            return Err("Loan has no lenders to repay".to_string());
+        }
+
+        let reservation = self.spending.reserve(user, request.amount as u64, request.totp_code.as_deref()).await?;
+        let mut distributions = Vec::with_capacity(shares.len());
+        for (stake, amount) in shares {
+            let mut installment_tags = tags::for_kind(tags::LOAN_REPAYMENT, Some((tags::LOAN_ID, loan_id)));
+            if let Some(position_id) = &stake.position_id {
+                installment_tags.insert(tags::LOAN_POSITION_ID.to_string(), position_id.clone());
+            }
+            match self.backend.send_tagged_transaction(
+                &user.wallet_address,
+                &stake.holder_address,
+                amount as u64,
+                None,
+                installment_tags,
+            ).await {
+                Ok(tx_hash) => {
+                    let distribution = DbLoanDistribution {
+                        id: None,
+                        loan_id: loan_id.to_string(),
+                        position_id: stake.position_id.clone(),
+                        holder_address: stake.holder_address.clone(),
+                        amount,
+                        tx_hash,
+                        timestamp: Utc::now().timestamp(),
+                    };
+                    if let Err(e) = self.database.insert_loan_distribution(&distribution).await {
+                        error!("❌ Failed to record distribution {} of loan {}: {}", distribution.tx_hash, loan_id, e);
+                    }
+                    distributions.push(distribution);
+                }
+                Err(e) => error!("❌ Failed to pay {} sat of loan {} to {}: {}", amount, loan_id, stake.holder_address, e),
+            }
+        }
+
+        let paid: i64 = distributions.iter().map(|d| d.amount).sum();
+        match distributions.first() {
+            Some(first) => self.spending.commit(reservation, &first.tx_hash).await,
+            None => {
+                self.spending.release(reservation).await;
+                return Err("Installment could not be paid".to_string());
+            }
+        }
+        if paid == outstanding {
+            self.database.set_loan_status(loan_id, "repaid").await.map_err(|e| e.to_string())?;
+        }
+        info!("💸 {} repaid {} sat of loan {} to {} holder(s)", user.username, paid, loan_id, distributions.len());
+        Ok(Installment {
+            loan_id: loan_id.to_string(),
+            paid,
+            outstanding: outstanding - paid,
+            distributions,
+        })
+    }
+
+    pub async fn distributions(&self, loan_id: &str) -> Result<Vec<DbLoanDistribution>, String> {
+        self.database.list_loan_distributions(loan_id).await.map_err(|e| e.to_string())
+    }
+
+    async fn load_position(&self, position_id: &str) -> Result<DbLoanPosition, String> {
+        self.database.get_loan_position(position_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Position not found".to_string())
+    }
+
+    async fn load_listing(&self, listing_id: &str) -> Result<DbLoanPositionListing, String> {
+        self.database.get_loan_position_listing(listing_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Listing not found".to_string())
+    }
+}
+
+/// Split `amount` across stakes in proportion to principal; the rounding
+/// remainder goes to the largest stake, which comes first
+pub fn pro_rata(amount: i64, stakes: &[DbLoanStake]) -> Vec<(&DbLoanStake, i64)> {
+    let total: i64 = stakes.iter().map(|stake| stake.principal).sum();
+    if total <= 0 {
+        return Vec::new();
+    }
+    let mut shares: Vec<(&DbLoanStake, i64)> = stakes.iter()
+        .map(|stake| (stake, (amount as i128 * stake.principal as i128 / total as i128) as i64))
+        .collect();
+    let paid: i64 = shares.iter().map(|(_, share)| share).sum();
+    shares[0].1 += amount - paid;
+    shares.retain(|(_, share)| *share > 0);
+    shares
+}
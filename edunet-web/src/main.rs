@@ -4,9 +4,10 @@
 //! Provides marketplace, lending, NFT minting, and investment pool functionality.
 
 use axum::{
-    extract::{Path, Query, State, WebSocketUpgrade, ws::WebSocket},
+    extract::{ConnectInfo, MatchedPath, Multipart, Path, Query, Request, State, WebSocketUpgrade, ws::WebSocket},
+    middleware::{self, Next},
     response::{Html, Json, IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
     http::{HeaderMap, StatusCode},
     body::Body,
@@ -28,10 +29,58 @@ use rand;
 mod blockchain_integration;
 mod user_auth;
 mod database;
-
-use crate::blockchain_integration::{BlockchainBackend, TransactionHistory, TransactionStatus};
+mod orders;
+mod notifications;
+mod admin;
+mod spending;
+mod custody;
+mod invoices;
+mod migrations;
+mod explorer_cache;
+mod node_client;
+mod signer;
+mod audit;
+mod ledger;
+mod reorg;
+mod expiry;
+mod address_proofs;
+mod attestations;
+mod scoring;
+mod collateral;
+mod loan_shares;
+mod campaigns;
+mod distributions;
+mod exports;
+
+use crate::blockchain_integration::{tags, BlockchainBackend, TransactionHistory, TransactionStatus};
 use crate::user_auth::{UserManager, User, LoginRequest, RegisterRequest};
-use crate::database::Database;
+use crate::spending::{SpendingDefaults, SpendingGuard, SpendingLimitUpdate};
+use crate::custody::{ColdRefillRequest, CustodyConfig, CustodyManager};
+use crate::invoices::{CreateInvoiceRequest, InvoiceConfig, InvoiceManager, VerifyInvoiceRequest};
+use crate::database::{Database, DbMarketItem, MarketSearch, MarketSort};
+use crate::orders::{OrderManager, DEFAULT_ESCROW_ADDRESS};
+use crate::notifications::{Mailer, NotificationPreference, NotificationService, SmtpConfig};
+use crate::admin::{AdminConsole, EvictRequest, VoucherBatchRequest};
+use crate::explorer_cache::{CacheConfig, ExplorerCache};
+use crate::expiry::ExpiryConfig;
+use crate::node_client::NodeClientConfig;
+use crate::signer::SignerConfig;
+use crate::audit::{AuditConfig, AuditLog, AuditSearch};
+use crate::ledger::{ExportFormat, LedgerQuery};
+use crate::address_proofs::{AddressChallengeRequest, AddressProofRequest, AddressProofs};
+use crate::attestations::{AddIssuerRequest, AttestationRegistry, SubmitAttestationRequest};
+use crate::scoring::{LoanScorer, ScoreWeights, WeightedModel};
+use crate::collateral::{CollateralConfig, CollateralManager, CollateralRequest, LockCollateralRequest};
+use crate::loan_shares::{BuyPositionRequest, ListPositionRequest, LoanShareMarket, RepayRequest, TokenizeRequest};
+use crate::campaigns::{CampaignManager, CreateCampaignRequest, PledgeRequest};
+use crate::distributions::{CreateDistributionRequest, DistributionService};
+use crate::exports::{BlockRangeQuery, ExportConfig, Exporter, TimeRangeQuery};
+use blockchain_core::audit_log::AuditRecord;
+use blockchain_core::ipfs::{IpfsClient, IpfsConfig, MediaRef};
+use blockchain_core::utxo::Balance;
+use blockchain_core::amount::Amount;
+use blockchain_core::wallet::TransactionTags;
+use blockchain_core::fiat::{FiatAmount, FiatConfig, FiatConverter};
 
 /// Application state shared across handlers
 #[derive(Clone)]
@@ -40,6 +89,36 @@ pub struct AppState {
     pub user_manager: Arc<UserManager>,
     pub marketplace: Arc<MarketplaceManager>,
     pub database: Arc<Database>,
+    /// IPFS node for NFT and marketplace media, if configured
+    pub ipfs: Option<Arc<IpfsClient>>,
+    pub orders: Arc<OrderManager>,
+    pub notifications: Arc<NotificationService>,
+    pub admin: Arc<AdminConsole>,
+    pub spending: Arc<SpendingGuard>,
+    pub custody: Arc<CustodyManager>,
+    pub invoices: Arc<InvoiceManager>,
+    /// Cached EDU exchange rates for fiat display
+    pub fiat: Arc<FiatConverter>,
+    /// Cached public explorer and balance responses
+    pub explorer_cache: Arc<ExplorerCache>,
+    /// Hash-chained log of wallet-affecting calls
+    pub audit: Arc<AuditLog>,
+    /// Outside addresses users proved control of
+    pub address_proofs: Arc<AddressProofs>,
+    /// University-issued identity attestations
+    pub attestations: Arc<AttestationRegistry>,
+    /// Proof-of-Potential scoring for loan applications
+    pub scoring: Arc<LoanScorer>,
+    /// Locked loan collateral, released on repayment or seized on default
+    pub collateral: Arc<CollateralManager>,
+    /// Tokenized loan positions, their resale and installment distribution
+    pub loan_shares: Arc<LoanShareMarket>,
+    /// All-or-nothing crowdfunding campaigns
+    pub campaigns: Arc<CampaignManager>,
+    /// Dividend and airdrop payouts to snapshotted holders
+    pub distributions: Arc<DistributionService>,
+    /// Rate-limited CSV/JSON exports of blocks, histories and treasury sales
+    pub exports: Arc<Exporter>,
 }
 
 /// Student user model
@@ -88,14 +167,9 @@ pub struct SendTransactionRequest {
     pub recipient: String,  // Recipient address
     pub amount: f64, // EDU amount
     pub message: Option<String>,
-}
-
-/// Payment request creation
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PaymentRequestData {
-    pub wallet_id: String,
-    pub amount: Option<f64>,
-    pub message: Option<String>,
+    pub totp_code: Option<String>, // Required above the step-up threshold
+    #[serde(default)]
+    pub tags: TransactionTags,
 }
 
 /// QR code parsing request
@@ -119,6 +193,9 @@ pub struct MarketItem {
     pub images: Option<String>, // JSON array of image URLs
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Fiat equivalent of an EDU price, when a rate is available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatAmount>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -148,45 +225,147 @@ pub struct DashboardStats {
     pub total_volume: f64,
 }
 
+/// Most items kept in the marketplace hot cache
+const MARKET_CACHE_CAPACITY: usize = 1024;
+
+/// Largest page served by marketplace listing and search
+const MARKET_MAX_PAGE_SIZE: i64 = 100;
+
+impl MarketItem {
+    fn to_db(&self) -> DbMarketItem {
+        DbMarketItem {
+            id: self.id.to_string(),
+            seller_id: self.seller_id.to_string(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            category: self.category.clone(),
+            price: self.price,
+            currency: self.currency.clone(),
+            item_type: self.item_type.clone(),
+            status: self.status.clone(),
+            images: self.images.clone(),
+            created_at: self.created_at.timestamp(),
+            updated_at: self.updated_at.timestamp(),
+        }
+    }
+
+    fn from_db(item: DbMarketItem) -> Result<Self, String> {
+        let timestamp = |secs: i64| DateTime::from_timestamp(secs, 0)
+            .ok_or_else(|| format!("Invalid timestamp on item {}", item.id));
+        Ok(Self {
+            id: Uuid::parse_str(&item.id).map_err(|e| e.to_string())?,
+            seller_id: Uuid::parse_str(&item.seller_id).map_err(|e| e.to_string())?,
+            created_at: timestamp(item.created_at)?,
+            updated_at: timestamp(item.updated_at)?,
+            title: item.title,
+            description: item.description,
+            category: item.category,
+            price: item.price,
+            currency: item.currency,
+            item_type: item.item_type,
+            status: item.status,
+            images: item.images,
+            fiat: None,
+        })
+    }
+
+    /// Attach the fiat equivalent of an EDU-priced item
+    fn with_fiat(mut self, fiat: &FiatConverter) -> Self {
+        if self.currency.eq_ignore_ascii_case("EDU") {
+            self.fiat = Amount::from_edu_f64(self.price).ok().and_then(|amount| fiat.convert(amount.to_sat()));
+        }
+        self
+    }
+}
+
+/// Query parameters for marketplace search
+#[derive(Debug, Default, Deserialize)]
+pub struct MarketSearchParams {
+    pub q: Option<String>,
+    pub category: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub seller_id: Option<Uuid>,
+    #[serde(default)]
+    pub sort: MarketSort,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl MarketSearchParams {
+    fn to_search(&self) -> MarketSearch {
+        let limit = self.per_page.unwrap_or(20).clamp(1, MARKET_MAX_PAGE_SIZE);
+        let page = self.page.unwrap_or(1).max(1);
+        MarketSearch {
+            query: self.q.clone(),
+            category: self.category.clone(),
+            min_price: self.min_price,
+            max_price: self.max_price,
+            seller_id: self.seller_id.map(|id| id.to_string()),
+            sort: self.sort,
+            limit,
+            offset: (page - 1) * limit,
+        }
+    }
+}
+
+/// One page of marketplace search results
+#[derive(Debug, Serialize)]
+pub struct MarketItemPage {
+    pub items: Vec<MarketItem>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
 /// Marketplace manager for handling marketplace operations
 pub struct MarketplaceManager {
-    /// In-memory storage for marketplace items
-    /// In production, this would be replaced with database storage
+    /// Hot cache of recently read or written items; the database is authoritative
     items: Arc<Mutex<HashMap<Uuid, MarketItem>>>,
+    /// Persistent, indexed item storage
+    database: Arc<Database>,
     /// Connection to blockchain backend for transactions
     backend: Arc<BlockchainBackend>,
 }
 
 impl MarketplaceManager {
     /// Create new marketplace manager
-    pub fn new(backend: Arc<BlockchainBackend>) -> Self {
+    pub fn new(backend: Arc<BlockchainBackend>, database: Arc<Database>) -> Self {
         Self {
             items: Arc::new(Mutex::new(HashMap::new())),
+            database,
             backend,
         }
     }
 
-    /// Get all active marketplace items
+    /// Get the newest active marketplace items
     pub async fn get_all_items(&self) -> Result<Vec<MarketItem>, String> {
-        let items = self.items.lock().await;
-        let active_items: Vec<MarketItem> = items
-            .values()
-            .filter(|item| item.status == "active")
-            .cloned()
-            .collect();
-        Ok(active_items)
+        let page = self.search_items(&MarketSearchParams {
+            sort: MarketSort::Newest,
+            per_page: Some(MARKET_MAX_PAGE_SIZE),
+            ..Default::default()
+        }).await?;
+        Ok(page.items)
     }
 
     /// Get specific marketplace item by ID
     pub async fn get_item(&self, id: Uuid) -> Result<Option<MarketItem>, String> {
-        let items = self.items.lock().await;
-        Ok(items.get(&id).cloned())
+        if let Some(item) = self.items.lock().await.get(&id) {
+            return Ok(Some(item.clone()));
+        }
+
+        let item = self.database.get_market_item(&id.to_string()).await
+            .map_err(|e| e.to_string())?
+            .map(MarketItem::from_db)
+            .transpose()?;
+        if let Some(item) = &item {
+            self.cache_item(item.clone()).await;
+        }
+        Ok(item)
     }
 
     /// Create new marketplace item
     pub async fn create_item(&self, item: MarketItem) -> Result<(), String> {
-        let mut items = self.items.lock().await;
-        
         // Validate item data
         if item.title.trim().is_empty() {
             return Err("Title cannot be empty".to_string());
@@ -197,7 +376,8 @@ impl MarketplaceManager {
         }
 
         // Store item
-        items.insert(item.id, item.clone());
+        self.database.save_market_item(&item.to_db()).await.map_err(|e| e.to_string())?;
+        self.cache_item(item.clone()).await;
         
         // TODO: In production, this would:
         // 1. Store item metadata on blockchain or IPFS
@@ -210,21 +390,20 @@ impl MarketplaceManager {
 
     /// Update marketplace item
     pub async fn update_item(&self, id: Uuid, updated_item: MarketItem) -> Result<(), String> {
-        let mut items = self.items.lock().await;
-        
-        if items.contains_key(&id) {
-            items.insert(id, updated_item);
-            Ok(())
-        } else {
-            Err("Item not found".to_string())
+        if self.get_item(id).await?.is_none() {
+            return Err("Item not found".to_string());
         }
+
+        self.database.save_market_item(&updated_item.to_db()).await.map_err(|e| e.to_string())?;
+        self.cache_item(updated_item).await;
+        Ok(())
     }
 
     /// Delete marketplace item
     pub async fn delete_item(&self, id: Uuid) -> Result<(), String> {
-        let mut items = self.items.lock().await;
-        
-        if items.remove(&id).is_some() {
+        self.items.lock().await.remove(&id);
+
+        if self.database.delete_market_item(&id.to_string()).await.map_err(|e| e.to_string())? {
             Ok(())
         } else {
             Err("Item not found".to_string())
@@ -233,58 +412,67 @@ impl MarketplaceManager {
 
     /// Get items by seller
     pub async fn get_items_by_seller(&self, seller_id: Uuid) -> Result<Vec<MarketItem>, String> {
-        let items = self.items.lock().await;
-        let seller_items: Vec<MarketItem> = items
-            .values()
-            .filter(|item| item.seller_id == seller_id && item.status == "active")
-            .cloned()
-            .collect();
-        Ok(seller_items)
-    }
-
-    /// Search items by category or title
-    pub async fn search_items(&self, query: &str, category: Option<&str>) -> Result<Vec<MarketItem>, String> {
-        let items = self.items.lock().await;
-        let query_lower = query.to_lowercase();
-        
-        let filtered_items: Vec<MarketItem> = items
-            .values()
-            .filter(|item| {
-                if item.status != "active" {
-                    return false;
-                }
-                
-                let title_match = item.title.to_lowercase().contains(&query_lower);
-                let description_match = item.description.to_lowercase().contains(&query_lower);
-                let category_match = category.map_or(true, |cat| item.category == cat);
-                
-                (title_match || description_match) && category_match
-            })
-            .cloned()
-            .collect();
-            
-        Ok(filtered_items)
+        let page = self.search_items(&MarketSearchParams {
+            seller_id: Some(seller_id),
+            sort: MarketSort::Newest,
+            per_page: Some(MARKET_MAX_PAGE_SIZE),
+            ..Default::default()
+        }).await?;
+        Ok(page.items)
+    }
+
+    /// Full-text search over active items with filters, sorting and pagination
+    pub async fn search_items(&self, params: &MarketSearchParams) -> Result<MarketItemPage, String> {
+        let search = params.to_search();
+        let page = self.database.search_market_items(&search).await.map_err(|e| e.to_string())?;
+
+        Ok(MarketItemPage {
+            items: page.items.into_iter().map(MarketItem::from_db).collect::<Result<_, _>>()?,
+            total: page.total,
+            page: search.offset / search.limit + 1,
+            per_page: search.limit,
+        })
+    }
+
+    /// Change an item's status only if it is still `from`; false if it was not
+    pub async fn set_item_status(&self, id: Uuid, from: &str, to: &str) -> Result<bool, String> {
+        let updated = self.database.set_market_item_status(&id.to_string(), from, to, Utc::now().timestamp()).await
+            .map_err(|e| e.to_string())?;
+        // Drop the cached copy either way so the next read sees the database
+        self.items.lock().await.remove(&id);
+        Ok(updated)
+    }
+
+    /// Active listing counts and price ranges per category
+    pub async fn get_categories(&self) -> Result<Vec<serde_json::Value>, String> {
+        self.database.market_categories().await.map_err(|e| e.to_string())
     }
 
     /// Get marketplace statistics
     pub async fn get_stats(&self) -> Result<serde_json::Value, String> {
-        let items = self.items.lock().await;
-        
-        let active_count = items.values().filter(|item| item.status == "active").count();
-        let total_value: f64 = items
-            .values()
-            .filter(|item| item.status == "active")
-            .map(|item| item.price)
-            .sum();
+        let (active_count, total_value, total_items) = self.database.market_stats().await
+            .map_err(|e| e.to_string())?;
         
         let stats = serde_json::json!({
             "active_listings": active_count,
             "total_value": total_value,
-            "total_items": items.len()
+            "total_items": total_items
         });
         
         Ok(stats)
     }
+
+    /// Insert into the hot cache, evicting the least recently updated item when full
+    async fn cache_item(&self, item: MarketItem) {
+        let mut items = self.items.lock().await;
+        if items.len() >= MARKET_CACHE_CAPACITY && !items.contains_key(&item.id) {
+            let oldest = items.values().min_by_key(|i| i.updated_at).map(|i| i.id);
+            if let Some(oldest) = oldest {
+                items.remove(&oldest);
+            }
+        }
+        items.insert(item.id, item);
+    }
 }
 
 /// API response wrapper
@@ -324,10 +512,21 @@ async fn main() -> anyhow::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     let mut is_bootstrap = false;
     let mut bootstrap_server = None;
+    let mut migrate_dry_run = false;
+    // Shared blockchain nodes; without any the backend embeds its own node
+    let mut node_urls: Vec<String> = std::env::var("EDUNET_NODE_RPC_URLS")
+        .map(|urls| urls.split(',').map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect())
+        .unwrap_or_default();
 
     for (i, arg) in args.iter().enumerate() {
         match arg.as_str() {
             "--bootstrap" => is_bootstrap = true,
+            "--migrate-dry-run" => migrate_dry_run = true,
+            "--node" => {
+                if let Some(url) = args.get(i + 1) {
+                    node_urls.push(url.clone());
+                }
+            },
             "--connect" => {
                 if let Some(address) = args.get(i + 1) {
                     bootstrap_server = Some(address.clone());
@@ -343,11 +542,26 @@ async fn main() -> anyhow::Result<()> {
     let database_url = std::env::var("DATABASE_URL")
         .unwrap_or_else(|_| "sqlite:./edunet-gui/edunet.db".to_string());
     info!("📊 Connecting to database: {}", database_url);
+    if migrate_dry_run {
+        let report = Database::migration_dry_run(&database_url).await?;
+        info!("📜 Schema at migration {:?}, latest {}", report.current_version, report.latest_version);
+        if report.pending.is_empty() {
+            info!("Database is up to date");
+        }
+        for migration in &report.pending {
+            info!("Would apply {}", migration);
+        }
+        return Ok(());
+    }
     let database = Arc::new(Database::new(&database_url).await?);
     info!("✅ Database initialized");
     
     // Initialize blockchain backend
-    let backend = BlockchainBackend::new(is_bootstrap, bootstrap_server, database.clone()).await?;
+    let backend = if node_urls.is_empty() {
+        BlockchainBackend::new(is_bootstrap, bootstrap_server, database.clone()).await?
+    } else {
+        BlockchainBackend::connect(NodeClientConfig::new(node_urls), database.clone()).await?
+    };
     
     // Initialize user management system with database
     let user_manager = Arc::new(UserManager::new(backend.wallets.clone(), database.clone()));
@@ -359,13 +573,175 @@ async fn main() -> anyhow::Result<()> {
     user_manager.create_demo_users().await.map_err(|e| anyhow::anyhow!(e))?;
     
     let backend = Arc::new(backend);
-    let marketplace = Arc::new(MarketplaceManager::new(backend.clone()));
+    let marketplace = Arc::new(MarketplaceManager::new(backend.clone(), database.clone()));
+
+    // Pin uploaded media to IPFS when a node is configured
+    let ipfs = match std::env::var("IPFS_API_URL") {
+        Ok(api_url) => {
+            let gateway_url = std::env::var("IPFS_GATEWAY_URL")
+                .unwrap_or_else(|_| IpfsConfig::default().gateway_url);
+            info!("📦 Pinning media to IPFS node at {}", api_url);
+            Some(Arc::new(IpfsClient::new(IpfsConfig { api_url, gateway_url, ..Default::default() })?))
+        }
+        Err(_) => None,
+    };
+    
+    let spending = Arc::new(SpendingGuard::new(database.clone(), user_manager.clone(), SpendingDefaults::from_env()));
+    
+    let escrow_address = std::env::var("MARKETPLACE_ESCROW_ADDRESS")
+        .unwrap_or_else(|_| DEFAULT_ESCROW_ADDRESS.to_string());
+    let orders = Arc::new(OrderManager::new(
+        database.clone(),
+        backend.clone(),
+        marketplace.clone(),
+        user_manager.clone(),
+        spending.clone(),
+        escrow_address,
+    ));
+
+    let collateral = Arc::new(CollateralManager::new(
+        database.clone(),
+        backend.clone(),
+        spending.clone(),
+        CollateralConfig::from_env(),
+    ));
+    tokio::spawn(collateral.clone().run_settlement());
+    let loan_shares = Arc::new(LoanShareMarket::new(database.clone(), backend.clone(), spending.clone()));
+
+    // Record pledges as they confirm; release or refund campaigns at their deadline
+    let campaigns = Arc::new(CampaignManager::new(database.clone(), backend.clone(), spending.clone()));
+    tokio::spawn(campaigns.clone().run_pledges(backend.subscribe_events()));
+    tokio::spawn(campaigns.clone().run_settlement());
+
+    // Pay out dividends and airdrops in batches, resuming unfinished ones
+    let distributions = Arc::new(DistributionService::new(database.clone(), backend.clone(), spending.clone()));
+    tokio::spawn(distributions.clone().run_payouts());
+
+    // Promote orders to paid as their escrow payments confirm
+    let payment_watcher = orders.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(15));
+        loop {
+            interval.tick().await;
+            if let Err(e) = payment_watcher.sync_payments().await {
+                error!("Failed to sync order payments: {}", e);
+            }
+        }
+    });
+    
+    // Notify users of payments, loans, escrow and NFT events from the backend bus
+    let mailer = match SmtpConfig::from_env() {
+        Some(config) => {
+            info!("📧 Sending notification email via {}:{}", config.host, config.port);
+            Some(Mailer::new(&config)?)
+        }
+        None => None,
+    };
+    let notifications = Arc::new(NotificationService::new(database.clone(), user_manager.clone(), mailer));
+    tokio::spawn(notifications.clone().run(backend.subscribe_events()));
+    
+    let admins = AdminConsole::admins_from_env();
+    if admins.is_empty() {
+        info!("🛡️ No admins configured (set EDUNET_ADMINS to enable the admin console)");
+    }
+    // Record every state-changing call, who made it, from where, and the resulting txid
+    let audit = Arc::new(AuditLog::new(database.clone(), AuditConfig::from_env()));
+    audit.import_admin_actions().await.map_err(|e| anyhow::anyhow!(e))?;
+    let admin = Arc::new(AdminConsole::new(database.clone(), user_manager.clone(), backend.clone(), audit.clone(), admins.clone()));
+    
+    // Credit custodial deposits, sweep the hot wallet to cold storage and alert admins when it runs low
+    let custody = Arc::new(CustodyManager::new(
+        database.clone(),
+        backend.clone(),
+        user_manager.clone(),
+        notifications.clone(),
+        admins,
+        CustodyConfig::from_env(),
+    ));
+    match &custody.config().cold_address {
+        Some(cold_address) => info!("🧊 Sweeping custody hot wallet {} to cold storage at {}", custody.config().hot_address, cold_address),
+        None => info!("🧊 No cold storage configured (set CUSTODY_COLD_ADDRESS to enable hot wallet sweeps)"),
+    }
+    tokio::spawn(custody.clone().run_deposits(backend.subscribe_events()));
+    tokio::spawn(custody.clone().run_sweeper());
+    
+    // Settle payment invoices as matching payments confirm and expire unpaid ones
+    let signer = SignerConfig::from_env()?.build()?;
+    let invoices = Arc::new(InvoiceManager::new(database.clone(), backend.clone(), signer, InvoiceConfig::from_env())
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?);
+    info!("🧾 Signing invoices with issuer key {}", hex::encode(invoices.issuer_public_key()));
+    tokio::spawn(invoices.clone().run_payments(backend.subscribe_events()));
+    tokio::spawn(invoices.clone().run_expiry());
+    
+    // Show fiat equivalents of EDU amounts using the treasury price or an external oracle
+    let fiat = Arc::new(FiatConverter::new(FiatConfig {
+        currency: std::env::var("FIAT_CURRENCY").unwrap_or_else(|_| FiatConfig::default().currency),
+        treasury_rpc_url: std::env::var("TREASURY_RPC_URL").ok(),
+        oracle_url: std::env::var("FIAT_ORACLE_URL").ok(),
+        max_rate_age_secs: std::env::var("FIAT_RATE_MAX_AGE_SECS").ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(FiatConfig::default().max_rate_age_secs),
+        ..Default::default()
+    })?);
+    if fiat.config().treasury_rpc_url.is_none() && fiat.config().oracle_url.is_none() {
+        info!("💱 No price source configured (set TREASURY_RPC_URL or FIAT_ORACLE_URL to show fiat amounts)");
+    } else {
+        let rates = fiat.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = rates.refresh().await {
+                    error!("Failed to refresh {} exchange rate: {}", rates.config().currency, e);
+                }
+            }
+        });
+    }
+    
+    // Cache hot explorer queries, invalidated as blocks and payments arrive
+    let explorer_cache = Arc::new(ExplorerCache::new(CacheConfig::from_env()));
+    tokio::spawn(explorer_cache.clone().run(backend.subscribe_tip(), backend.subscribe_events()));
+    tokio::spawn(backend.clone().run_reorg_watcher());
+    tokio::spawn(backend.clone().run_expiry_watcher(ExpiryConfig::from_env()));
+    
+    let address_proofs = Arc::new(AddressProofs::new(database.clone()));
+    
+    // Verified flags come from university attestations, not self-reported data
+    let attestations = Arc::new(AttestationRegistry::new(database.clone(), backend.clone(), user_manager.clone()));
+    attestations.refresh_verified().await.map_err(|e| anyhow::anyhow!(e))?;
+    let scoring = Arc::new(LoanScorer::new(
+        database.clone(),
+        attestations.clone(),
+        Box::new(WeightedModel::new(ScoreWeights::from_env())),
+    ));
+    
+    // Bulk exports for researchers and auditors, bounded per request and per client
+    let exports = Arc::new(Exporter::new(backend.clone(), database.clone(), ExportConfig::from_env()));
     
     let state = AppState {
         backend,
         user_manager,
         marketplace,
         database,
+        ipfs,
+        orders,
+        notifications,
+        admin,
+        spending,
+        custody,
+        invoices,
+        fiat,
+        explorer_cache,
+        audit,
+        address_proofs,
+        attestations,
+        scoring,
+        collateral,
+        loan_shares,
+        campaigns,
+        distributions,
+        exports,
     };
 
     if is_bootstrap {
@@ -404,12 +780,68 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/auth/register", post(api_register))
         .route("/api/auth/logout", post(api_logout))
         .route("/api/auth/me", get(api_current_user))
+        .route("/api/auth/2fa", get(api_two_factor_status))
+        .route("/api/auth/2fa/setup", post(api_two_factor_setup))
+        .route("/api/auth/2fa/confirm", post(api_two_factor_confirm))
+        .route("/api/auth/2fa/disable", post(api_two_factor_disable))
+        .route("/api/wallet/limits", get(api_get_spending_limits).put(api_update_spending_limits))
+        .route("/api/custody", get(api_custody_account))
+        .route("/api/custody/deposit", post(api_custody_deposit))
+        .route("/api/custody/withdraw", post(api_custody_withdraw))
+        .route("/api/invoices", get(api_list_invoices).post(api_create_invoice))
+        .route("/api/invoices/issuer", get(api_invoice_issuer))
+        .route("/api/invoices/verify", post(api_verify_invoice))
+        .route("/api/invoices/:id", get(api_invoice_status))
+        .route("/api/addresses", get(api_list_linked_addresses))
+        .route("/api/addresses/challenge", post(api_address_challenge))
+        .route("/api/addresses/verify", post(api_prove_address))
+        .route("/api/addresses/:address", delete(api_unlink_address))
+        .route("/api/attestations", post(api_submit_attestation))
+        .route("/api/attestations/mine", get(api_my_attestations))
+        .route("/api/attestations/:id/verify", get(api_verify_attestation))
+        .route("/api/wallet/ledger", get(api_wallet_ledger))
+        .route("/api/wallet/ledger/export", get(api_export_wallet_ledger))
         
         // API routes
         .route("/api/students", get(get_students).post(create_student))
         .route("/api/students/:id", get(get_student))
         .route("/api/marketplace", get(get_market_items).post(create_market_item))
+        .route("/api/marketplace/search", get(search_market_items))
+        .route("/api/marketplace/categories", get(get_market_categories))
         .route("/api/marketplace/:id", get(get_market_item))
+        .route("/api/orders", get(list_orders).post(create_order))
+        .route("/api/orders/:id", get(get_order))
+        .route("/api/orders/:id/pay", post(pay_order))
+        .route("/api/orders/:id/ship", post(ship_order))
+        .route("/api/orders/:id/confirm", post(confirm_order))
+        .route("/api/orders/:id/dispute", post(dispute_order))
+        .route("/api/orders/:id/refund", post(refund_order))
+        .route("/api/orders/:id/cancel", post(cancel_order))
+        .route("/api/notifications", get(list_notifications))
+        .route("/api/notifications/read", post(mark_notifications_read))
+        .route("/api/notifications/preferences", get(get_notification_preferences).put(set_notification_preference))
+        .route("/api/admin/accounts/frozen", get(admin_list_frozen_accounts))
+        .route("/api/admin/accounts/freeze", post(admin_freeze_account))
+        .route("/api/admin/accounts/unfreeze", post(admin_unfreeze_account))
+        .route("/api/admin/transactions/volume", get(admin_transaction_volume))
+        .route("/api/admin/mempool/evict", post(admin_evict_mempool))
+        .route("/api/admin/reindex", post(admin_reindex))
+        .route("/api/admin/vouchers/batches", get(admin_list_voucher_batches).post(admin_create_voucher_batch))
+        .route("/api/admin/vouchers/batches/:id", get(admin_get_voucher_batch))
+        .route("/api/admin/vouchers/batches/:id/export", get(admin_export_voucher_batch))
+        .route("/api/admin/vouchers/batches/:id/revoke", post(admin_revoke_voucher_batch))
+        .route("/api/admin/audit", get(admin_audit_calls))
+        .route("/api/admin/audit/calls", get(admin_audit_calls))
+        .route("/api/admin/audit/calls/verify", get(admin_verify_audit_calls))
+        .route("/api/admin/custody", get(admin_custody_status))
+        .route("/api/admin/custody/accounts", get(admin_custody_accounts))
+        .route("/api/admin/custody/sweeps", get(admin_custody_sweeps))
+        .route("/api/admin/custody/sweep", post(admin_custody_sweep))
+        .route("/api/admin/custody/refills", post(admin_custody_refill))
+        .route("/api/admin/ledger/:address/export", get(admin_export_ledger))
+        .route("/api/admin/attestation-issuers", get(admin_list_attestation_issuers).post(admin_add_attestation_issuer))
+        .route("/api/admin/attestation-issuers/:id/activate", post(admin_activate_attestation_issuer))
+        .route("/api/admin/attestation-issuers/:id/deactivate", post(admin_deactivate_attestation_issuer))
         .route("/api/dashboard/stats", get(dashboard_stats_handler))
         .route("/api/user/info", get(get_current_user_info))
         .route("/api/users", get(get_all_users))
@@ -426,24 +858,53 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/blockchain/transactions", get(api_get_transactions))
         .route("/api/blockchain/transactions/pending", get(api_get_pending_transactions))
         .route("/api/blockchain/transactions/recent", get(api_get_recent_transactions))
+        .route("/api/blockchain/transactions/:hash/tags", put(api_tag_transaction))
+        .route("/api/names/:name", get(api_resolve_name))
+        .route("/api/names/address/:address", get(api_names_for_address))
         .route("/api/blockchain/mine", post(api_mine_block))
         .route("/api/blockchain/network-status", get(api_network_status))
         .route("/api/blockchain/sync-status", get(api_sync_status))
         .route("/api/blockchain/sync", post(api_sync_blockchain))
         
+        // Bulk data exports
+        .route("/api/export/blocks", get(api_export_blocks))
+        .route("/api/export/addresses/:address/history", get(api_export_address_history))
+        .route("/api/export/treasury/sales", get(api_export_treasury_sales))
+        
         // NFT API routes
         .route("/api/nft/mint", post(api_nft_mint))
         .route("/api/nft/list", get(api_nft_list))
         .route("/api/nft/owned/:address", get(api_nft_owned))
         .route("/api/nft/:id", get(api_nft_get))
         .route("/api/nft/transfer", post(api_nft_transfer))
+
+        // Media API (IPFS)
+        .route("/api/media/upload", post(api_media_upload))
+        .route("/api/media/:cid/:sha256", get(api_media_get))
         
         // Loan API routes
         .route("/api/loan/apply", post(api_loan_apply))
         .route("/api/loan/list", get(api_loan_list))
         .route("/api/loan/:id", get(api_loan_get))
         .route("/api/loan/fund", post(api_loan_fund))
+        .route("/api/loan/:id/collateral", get(api_loan_collateral))
+        .route("/api/loan/:id/collateral/lock", post(api_loan_lock_collateral))
+        .route("/api/loan/:id/repay", post(api_loan_repay))
+        .route("/api/loan/:id/distributions", get(api_loan_distributions))
+        .route("/api/loan-shares", get(api_loan_positions))
+        .route("/api/loan-shares/tokenize", post(api_tokenize_loan_position))
+        .route("/api/loan-shares/listings", get(api_loan_share_listings).post(api_list_loan_position))
+        .route("/api/loan-shares/listings/:id/buy", post(api_buy_loan_position))
+        .route("/api/loan-shares/listings/:id/cancel", post(api_cancel_loan_listing))
+        .route("/api/campaigns", get(api_campaigns).post(api_create_campaign))
+        .route("/api/campaigns/:id", get(api_campaign))
+        .route("/api/campaigns/:id/pledge", post(api_pledge_campaign))
+        .route("/api/distributions", get(api_distributions).post(api_create_distribution))
+        .route("/api/distributions/:id", get(api_distribution))
+        .route("/api/distributions/:id/pause", post(api_pause_distribution))
+        .route("/api/distributions/:id/resume", post(api_resume_distribution))
         
+        .layer(middleware::from_fn_with_state(state.clone(), audit_calls))
         .layer(CorsLayer::permissive())
         .with_state(state);
 
@@ -453,7 +914,7 @@ async fn main() -> anyhow::Result<()> {
     
     let listener = TcpListener::bind(addr).await?;
     
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
     
     Ok(())
 }
@@ -496,6 +957,23 @@ async fn get_current_user(headers: &HeaderMap, state: &AppState) -> Result<user_
     state.user_manager.get_user_by_session(session_token).await
 }
 
+// Helper to split a wallet's balance; a frozen account's funds count as locked
+async fn wallet_balances(state: &AppState, address: &str) -> anyhow::Result<Balance> {
+    let mut balances = state.backend.get_wallet_balances(address).await?;
+    if state.user_manager.is_frozen(address).await {
+        balances.locked += std::mem::take(&mut balances.confirmed);
+    }
+    Ok(balances)
+}
+
+// Helper to attach fiat equivalents of transaction amounts
+fn with_fiat_amounts(state: &AppState, mut transactions: Vec<TransactionHistory>) -> Vec<TransactionHistory> {
+    for tx in &mut transactions {
+        tx.fiat = state.fiat.convert(tx.amount);
+    }
+    transactions
+}
+
 // Page handlers
 async fn login_page() -> impl IntoResponse {
     Html(include_str!("../templates/login.html"))
@@ -533,21 +1011,10 @@ async fn dashboard_handler(
     // For demonstration, we'll use default values when no user is authenticated
     let (username, wallet_address, balance, reputation, university, username_initial) = 
         if let Some(user) = &user {
-            let user_wallet = state.user_manager.get_user_wallet(user).await
-                .unwrap_or_else(|_| blockchain_core::wallet::Wallet {
-                    id: user.wallet_id,
-                    name: format!("{}'s Wallet", user.username),
-                    private_key: [0u8; 32],
-                    public_key: [0u8; 33],
-                    address: user.wallet_address.clone(),
-                    balance: 100, // Demo balance (u64)
-                    created_at: chrono::Utc::now(),
-                });
-            
             (
                 user.username.clone(),
                 user.wallet_address.clone(),
-                user_wallet.balance,
+                wallet_balances(&state, &user.wallet_address).await.unwrap_or_default(),
                 user.reputation_score as f64,
                 user.university.clone().unwrap_or_else(|| "Not specified".to_string()),
                 user.username.chars().next().unwrap_or('U').to_uppercase().to_string()
@@ -557,7 +1024,7 @@ async fn dashboard_handler(
             (
                 "Guest".to_string(),
                 "Loading...".to_string(),
-                0u64,
+                Balance::default(),
                 0.0,
                 "Please log in".to_string(),
                 "G".to_string()
@@ -659,6 +1126,12 @@ async fn dashboard_handler(
             margin: 10px 0;
         }}
         
+        .balance-detail {{
+            font-size: 13px;
+            color: #6b7280;
+            margin-bottom: 10px;
+        }}
+        
         .wallet-address {{
             font-family: 'Courier New', monospace;
             background: #f3f4f6;
@@ -715,6 +1188,7 @@ async fn dashboard_handler(
             <div class="card">
                 <h3>💰 Wallet Balance</h3>
                 <div class="balance-amount" id="wallet-balance">{balance} EDU</div>
+                <div class="balance-detail" id="wallet-balance-detail">Unconfirmed: {balance_unconfirmed} · Immature: {balance_immature} · Locked: {balance_locked}</div>
                 <button class="btn" onclick="refreshBalance()">Refresh Balance</button>
             </div>
             
@@ -773,7 +1247,10 @@ async fn dashboard_handler(
                 const response = await authFetch('/api/auth/me');
                 const data = await response.json();
                 if (data.success) {{
-                    document.getElementById('wallet-balance').textContent = `${{data.data.wallet.balance}} EDU`;
+                    const wallet = data.data.wallet;
+                    document.getElementById('wallet-balance').textContent = `${{wallet.balance}} EDU`;
+                    document.getElementById('wallet-balance-detail').textContent =
+                        `Unconfirmed: ${{wallet.balances.unconfirmed}} · Immature: ${{wallet.balances.immature}} · Locked: ${{wallet.balances.locked}}`;
                     console.log('✅ Balance updated:', data.data.wallet.balance);
                 }} else {{
                     console.error('❌ Failed to fetch balance:', data.message);
@@ -880,7 +1357,7 @@ Wallet: {wallet_address}
                 const date = new Date(tx.timestamp).toLocaleString();
                 const amount = tx.amount_edu || (tx.amount / 100000000); // Convert to EDU if needed
                 const type = tx.transaction_type || 'Unknown';
-                const status = tx.status === 'Confirmed' ? '✅' : tx.status === 'Pending' ? '🔄' : '❓';
+                const status = tx.status === 'Confirmed' ? '✅' : tx.status === 'Pending' ? '🔄' : tx.status === 'Expired' ? '⌛' : '❓';
                 
                 modalHTML += `
                     <div style="border: 1px solid #ddd; margin: 10px 0; padding: 15px; border-radius: 5px; background: #f9f9f9;">
@@ -1030,7 +1507,10 @@ Connected Peers: ${{data.peers_connected || 0}}
 </html>"#, 
         username = username,
         username_initial = username_initial,
-        balance = balance,
+        balance = balance.confirmed,
+        balance_unconfirmed = balance.unconfirmed,
+        balance_immature = balance.immature,
+        balance_locked = balance.locked,
         university = university,
         reputation = reputation,
         wallet_address = wallet_address
@@ -1142,7 +1622,9 @@ async fn get_student(
 async fn get_market_items(State(state): State<AppState>) -> impl IntoResponse {
     // Get marketplace items from database/blockchain storage
     match state.marketplace.get_all_items().await {
-        Ok(items) => Json(ApiResponse::success(items)),
+        Ok(items) => Json(ApiResponse::success(
+            items.into_iter().map(|item| item.with_fiat(&state.fiat)).collect::<Vec<_>>()
+        )),
         Err(e) => {
             error!("Failed to get marketplace items: {}", e);
             // Return empty list instead of mock data for real blockchain system
@@ -1161,6 +1643,19 @@ async fn create_market_item(
         Ok(user) => user,
         Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
     };
+
+    // Image list is a JSON array of URIs; only enforced once IPFS is configured
+    if let (Some(_), Some(images)) = (&state.ipfs, &request.images) {
+        let uris: Vec<String> = match serde_json::from_str(images) {
+            Ok(uris) => uris,
+            Err(_) => return Json(ApiResponse::error("Images must be a JSON array of URIs".to_string())),
+        };
+        for uri in &uris {
+            if let Err(e) = check_media_uri(&state, uri) {
+                return Json(ApiResponse::error(e));
+            }
+        }
+    }
     
     // Create item with generated fields
     let item = MarketItem {
@@ -1176,6 +1671,7 @@ async fn create_market_item(
         images: request.images,
         created_at: Utc::now(),
         updated_at: Utc::now(),
+        fiat: None,
     };
     
     // Store item in marketplace
@@ -1191,13 +1687,39 @@ async fn create_market_item(
     }
 }
 
+async fn search_market_items(
+    State(state): State<AppState>,
+    Query(params): Query<MarketSearchParams>,
+) -> impl IntoResponse {
+    match state.marketplace.search_items(&params).await {
+        Ok(mut page) => {
+            page.items = page.items.into_iter().map(|item| item.with_fiat(&state.fiat)).collect();
+            Json(ApiResponse::success(page))
+        }
+        Err(e) => {
+            error!("Failed to search marketplace: {}", e);
+            Json(ApiResponse::error("Failed to search marketplace".to_string()))
+        }
+    }
+}
+
+async fn get_market_categories(State(state): State<AppState>) -> impl IntoResponse {
+    match state.marketplace.get_categories().await {
+        Ok(categories) => Json(ApiResponse::success(categories)),
+        Err(e) => {
+            error!("Failed to get marketplace categories: {}", e);
+            Json(ApiResponse::error("Failed to get categories".to_string()))
+        }
+    }
+}
+
 async fn get_market_item(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
     // Get specific marketplace item by ID
     match state.marketplace.get_item(id).await {
-        Ok(Some(item)) => Json(ApiResponse::success(item)),
+        Ok(Some(item)) => Json(ApiResponse::success(item.with_fiat(&state.fiat))),
         Ok(None) => Json(ApiResponse::error("Item not found".to_string())),
         Err(e) => {
             error!("Failed to get marketplace item {}: {}", id, e);
@@ -1206,125 +1728,783 @@ async fn get_market_item(
     }
 }
 
-async fn dashboard_stats_handler(State(state): State<AppState>) -> impl IntoResponse {
-    // Get real blockchain statistics
-    let network_status = state.backend.get_network_status().await
-        .unwrap_or_else(|_| serde_json::json!({}));
-    
-    // Get real marketplace statistics
-    let marketplace_stats = state.marketplace.get_stats().await
-        .unwrap_or_else(|_| serde_json::json!({"active_listings": 0}));
-    
-    let active_listings = marketplace_stats.get("active_listings")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(0);
-    
-    let stats = DashboardStats {
-        total_students: 1, // Single-user system for now
-        active_listings,
-        total_loans: 0, // No loan system implemented yet
-        minted_nfts: 0, // No NFT system implemented yet
-        funded_projects: 0, // No project funding system yet
-        total_volume: network_status.get("mempool_size")
-            .and_then(|v| v.as_f64())
-            .unwrap_or(0.0),
-    };
-    Json(ApiResponse::success(stats))
+// ============================================================================
+// MARKETPLACE ORDERS
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrderRequest {
+    pub item_id: Uuid,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct PayOrderRequest {
+    /// Required above the step-up threshold
+    pub totp_code: Option<String>,
+}
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ShipOrderRequest {
+    pub tracking_info: Option<String>,
+}
 
-// ============================================================================
-// WEBSOCKET HANDLER FOR REAL-TIME UPDATES
-// ============================================================================
+#[derive(Debug, Deserialize)]
+pub struct DisputeOrderRequest {
+    pub reason: String,
+}
 
-async fn websocket_handler(
-    ws: WebSocketUpgrade,
+async fn create_order(
+    headers: HeaderMap,
     State(state): State<AppState>,
-) -> Response {
-    ws.on_upgrade(|socket| websocket_connection(socket, state))
+    Json(request): Json<CreateOrderRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.create_order(&user, request.item_id).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
-async fn websocket_connection(mut socket: WebSocket, state: AppState) {
-    info!("🔌 New WebSocket connection established");
-    
-    // Send initial blockchain status
-    if let Ok(status) = state.backend.get_network_status().await {
-        let message = serde_json::json!({
-            "type": "network_status",
-            "data": status
-        });
-        
-        if socket.send(axum::extract::ws::Message::Text(
-            serde_json::to_string(&message).unwrap()
-        )).await.is_err() {
-            return;
-        }
+async fn list_orders(headers: HeaderMap, State(state): State<AppState>) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.list_orders(&user).await {
+        Ok(orders) => Json(ApiResponse::success(orders)),
+        Err(e) => Json(ApiResponse::error(e)),
     }
+}
 
-    // Set up periodic updates
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
-    
-    loop {
-        tokio::select! {
-            _ = interval.tick() => {
-                // Send periodic updates
-                if let Ok(status) = state.backend.get_network_status().await {
-                    let message = serde_json::json!({
-                        "type": "network_update",
-                        "data": status,
-                        "timestamp": chrono::Utc::now().to_rfc3339()
-                    });
-                    
-                    if socket.send(axum::extract::ws::Message::Text(
-                        serde_json::to_string(&message).unwrap()
-                    )).await.is_err() {
-                        break;
-                    }
-                }
-            }
-            
-            msg = socket.recv() => {
-                match msg {
-                    Some(Ok(axum::extract::ws::Message::Text(text))) => {
-                        // Handle client messages
-                        if let Ok(request) = serde_json::from_str::<serde_json::Value>(&text) {
-                            handle_websocket_request(&mut socket, &state, request).await;
-                        }
-                    }
-                    Some(Ok(axum::extract::ws::Message::Close(_))) => break,
-                    _ => {}
-                }
-            }
-        }
+async fn get_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.get_order(&user, &id).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
     }
-    
-    info!("🔌 WebSocket connection closed");
 }
 
-async fn handle_websocket_request(
-    socket: &mut WebSocket,
-    state: &AppState,
-    request: serde_json::Value,
-) {
-    let request_type = request.get("type").and_then(|v| v.as_str()).unwrap_or("");
-    
-    let response = match request_type {
-        "get_balance" => {
-            if let Some(address) = request.get("address").and_then(|v| v.as_str()) {
-                match state.backend.get_wallet_balance(address).await {
-                    Ok(balance) => serde_json::json!({
-                        "type": "balance_response",
-                        "address": address,
-                        "balance": balance
-                    }),
-                    Err(_) => serde_json::json!({
-                        "type": "error",
-                        "message": "Failed to get balance"
-                    })
-                }
-            } else {
-                serde_json::json!({
+async fn pay_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    request: Option<Json<PayOrderRequest>>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    let totp_code = request.and_then(|Json(r)| r.totp_code);
+    match state.orders.pay_order(&user, &id, totp_code.as_deref()).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn ship_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    request: Option<Json<ShipOrderRequest>>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    let tracking_info = request.and_then(|Json(r)| r.tracking_info);
+    match state.orders.ship_order(&user, &id, tracking_info).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn confirm_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.confirm_receipt(&user, &id).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn dispute_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<DisputeOrderRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.open_dispute(&user, &id, request.reason).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn refund_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.refund_order(&user, &id).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn cancel_order(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.orders.cancel_order(&user, &id).await {
+        Ok(order) => Json(ApiResponse::success(order)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+// ============================================================================
+// NOTIFICATIONS
+// ============================================================================
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationQuery {
+    #[serde(default)]
+    pub unread: bool,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MarkNotificationsReadRequest {
+    /// Notification IDs to mark; all when omitted
+    pub ids: Option<Vec<i64>>,
+}
+
+async fn list_notifications(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<NotificationQuery>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    match state.database.list_notifications(&user.wallet_address, query.unread, limit).await {
+        Ok(notifications) => Json(ApiResponse::success(notifications)),
+        Err(e) => {
+            error!("Failed to list notifications: {}", e);
+            Json(ApiResponse::error("Failed to list notifications".to_string()))
+        }
+    }
+}
+
+async fn mark_notifications_read(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<MarkNotificationsReadRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.database.mark_notifications_read(&user.wallet_address, request.ids.as_deref()).await {
+        Ok(updated) => Json(ApiResponse::success(serde_json::json!({ "updated": updated }))),
+        Err(e) => {
+            error!("Failed to mark notifications read: {}", e);
+            Json(ApiResponse::error("Failed to update notifications".to_string()))
+        }
+    }
+}
+
+async fn get_notification_preferences(headers: HeaderMap, State(state): State<AppState>) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match state.notifications.preferences(&user.wallet_address).await {
+        Ok(preferences) => Json(ApiResponse::success(preferences)),
+        Err(e) => {
+            error!("Failed to load notification preferences: {}", e);
+            Json(ApiResponse::error("Failed to load preferences".to_string()))
+        }
+    }
+}
+
+async fn set_notification_preference(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(preference): Json<NotificationPreference>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    if let Err(e) = state.notifications.set_preference(&user.wallet_address, &preference).await {
+        error!("Failed to save notification preference: {}", e);
+        return Json(ApiResponse::error("Failed to save preference".to_string()));
+    }
+    match state.notifications.preferences(&user.wallet_address).await {
+        Ok(preferences) => Json(ApiResponse::success(preferences)),
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
+// ============================================================================
+// ADMIN CONSOLE
+// ============================================================================
+
+/// Current user, if they are an admin (refusals are audited as `action`)
+async fn get_admin_user(headers: &HeaderMap, state: &AppState, action: &str) -> Result<User, String> {
+    let user = get_current_user(headers, state).await
+        .map_err(|_| "Authentication required".to_string())?;
+    state.admin.authorize(&user, action).await?;
+    Ok(user)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FreezeAccountRequest {
+    /// Username or wallet address
+    pub target: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnfreezeAccountRequest {
+    pub target: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VolumeQuery {
+    pub days: Option<i64>,
+    /// Bucket size: `hour` or `day` (default)
+    pub bucket: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+async fn admin_list_frozen_accounts(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "list_frozen_accounts").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.admin.frozen_accounts().await {
+        Ok(accounts) => Json(ApiResponse::success(accounts)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_freeze_account(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<FreezeAccountRequest>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "freeze_account").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.freeze_account(&admin, &request.target, &request.reason).await {
+        Ok(account) => {
+            state.explorer_cache.invalidate_address(&account.wallet_address).await;
+            Json(ApiResponse::success(account))
+        }
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_unfreeze_account(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<UnfreezeAccountRequest>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "unfreeze_account").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.unfreeze_account(&admin, &request.target).await {
+        Ok(()) => {
+            // The target may be a username; drop every cached balance
+            state.explorer_cache.invalidate_all();
+            Json(ApiResponse::success(request.target))
+        }
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_list_attestation_issuers(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "list_attestation_issuers").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.attestations.issuers().await {
+        Ok(issuers) => Json(ApiResponse::success(issuers)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_add_attestation_issuer(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<AddIssuerRequest>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "add_attestation_issuer").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.attestations.add_issuer(&admin, &request).await {
+        Ok(issuer) => Json(ApiResponse::success(issuer)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_activate_attestation_issuer(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    set_attestation_issuer_active(&headers, &state, &id, true).await
+}
+
+async fn admin_deactivate_attestation_issuer(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    set_attestation_issuer_active(&headers, &state, &id, false).await
+}
+
+async fn set_attestation_issuer_active(headers: &HeaderMap, state: &AppState, id: &str, active: bool) -> Json<ApiResponse<String>> {
+    let action = if active { "activate_attestation_issuer" } else { "deactivate_attestation_issuer" };
+    if let Err(e) = get_admin_user(headers, state, action).await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.attestations.set_issuer_active(id, active).await {
+        Ok(()) => Json(ApiResponse::success(id.to_string())),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_transaction_volume(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<VolumeQuery>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "transaction_volume").await {
+        return Json(ApiResponse::error(e));
+    }
+    let bucket_secs = match query.bucket.as_deref() {
+        Some("hour") => 3600,
+        None | Some("day") => 86400,
+        Some(other) => return Json(ApiResponse::error(format!("Unknown bucket: {}", other))),
+    };
+    let report = state.admin.transaction_volume(query.days.unwrap_or(30), bucket_secs).await;
+    Json(ApiResponse::success(report))
+}
+
+async fn admin_evict_mempool(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<EvictRequest>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "evict_mempool").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.evict_mempool(&admin, &request).await {
+        Ok(evicted) => Json(ApiResponse::success(serde_json::json!({ "evicted": evicted }))),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_reindex(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "reindex").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.reindex(&admin).await {
+        Ok(summary) => Json(ApiResponse::success(summary)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_list_voucher_batches(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "list_voucher_batches").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.admin.voucher_batches().await {
+        Ok(batches) => Json(ApiResponse::success(batches)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_create_voucher_batch(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<VoucherBatchRequest>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "create_voucher_batch").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.create_voucher_batch(&admin, &request).await {
+        Ok(batch) => Json(ApiResponse::success(batch)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_get_voucher_batch(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "get_voucher_batch").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.admin.voucher_batch(&id).await {
+        Ok(batch) => Json(ApiResponse::success(batch)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Outstanding codes in the format `voucher-pdf-gen` prints from
+async fn admin_export_voucher_batch(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "export_voucher_batch").await {
+        return Json(serde_json::json!({ "success": false, "message": e }));
+    }
+    match state.admin.export_voucher_batch(&id).await {
+        Ok(export) => Json(export),
+        Err(e) => Json(serde_json::json!({ "success": false, "message": e })),
+    }
+}
+
+async fn admin_revoke_voucher_batch(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "revoke_voucher_batch").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.revoke_voucher_batch(&admin, &id).await {
+        Ok(revoked) => Json(ApiResponse::success(revoked)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_audit_calls(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(search): Query<AuditSearch>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "view_audit_log").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.audit.search(&search).await {
+        Ok(records) => Json(ApiResponse::success(records)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_verify_audit_calls(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "view_audit_log").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.audit.verify().await {
+        Ok(verification) => Json(ApiResponse::success(verification)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Largest request or response body kept for the audit log
+const AUDIT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Record calls to wallet-affecting routes in the audit log
+async fn audit_calls(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request.extensions().get::<MatchedPath>().map(|p| p.as_str().to_string());
+    let Some(route) = route.filter(|route| audit::is_audited(&method, route)) else {
+        return next.run(request).await;
+    };
+
+    let actor = get_current_user(request.headers(), &state).await
+        .map(|user| user.username)
+        .unwrap_or_else(|_| "anonymous".to_string());
+    let forwarded_for = request.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok());
+    let peer = request.extensions().get::<ConnectInfo<std::net::SocketAddr>>().map(|info| info.0);
+    let source_ip = audit::client_ip(forwarded_for, peer, state.audit.config());
+    let path = request.uri().path().to_string();
+    let query = request.uri().query().map(str::to_string);
+
+    // Buffer the body so it can be both recorded and handled
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, AUDIT_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(_) => return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response(),
+    };
+    let request_params = serde_json::from_slice::<serde_json::Value>(&body).unwrap_or(serde_json::Value::Null);
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, AUDIT_BODY_LIMIT).await.unwrap_or_default();
+    let reply = serde_json::from_slice::<serde_json::Value>(&body).unwrap_or(serde_json::Value::Null);
+
+    let params = serde_json::json!({
+        "path": path,
+        "query": query,
+        "body": audit::redact(&request_params),
+    });
+    let mut record = AuditRecord::new(actor, format!("{} {}", method, route), params)
+        .with_source_ip(source_ip)
+        .with_txid(audit::find_txid(&reply));
+    if !parts.status.is_success() || reply.get("success").and_then(|v| v.as_bool()) == Some(false) {
+        let message = reply.get("message").and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| parts.status.to_string());
+        record = record.with_error(message);
+    }
+    state.audit.record(record).await;
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn admin_custody_status(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "view_custody").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.custody.status().await {
+        Ok(status) => Json(ApiResponse::success(status)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_custody_accounts(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "view_custody").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.custody.accounts().await {
+        Ok(accounts) => Json(ApiResponse::success(accounts)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_custody_sweeps(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    if let Err(e) = get_admin_user(&headers, &state, "view_custody").await {
+        return Json(ApiResponse::error(e));
+    }
+    match state.custody.sweeps(query.limit.unwrap_or(100)).await {
+        Ok(sweeps) => Json(ApiResponse::success(sweeps)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_custody_sweep(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "custody_sweep").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.sweep_custody(&admin, &state.custody).await {
+        Ok(sweep) => Json(ApiResponse::success(sweep)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn admin_custody_refill(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<ColdRefillRequest>,
+) -> impl IntoResponse {
+    let admin = match get_admin_user(&headers, &state, "custody_refill").await {
+        Ok(user) => user,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    match state.admin.record_cold_refill(&admin, &state.custody, &request).await {
+        Ok(refill) => Json(ApiResponse::success(refill)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn dashboard_stats_handler(State(state): State<AppState>) -> impl IntoResponse {
+    // Get real blockchain statistics
+    let network_status = state.explorer_cache.network_status(state.backend.get_network_status()).await
+        .unwrap_or_else(|_| serde_json::json!({}));
+    
+    // Get real marketplace statistics
+    let marketplace_stats = state.marketplace.get_stats().await
+        .unwrap_or_else(|_| serde_json::json!({"active_listings": 0}));
+    
+    let active_listings = marketplace_stats.get("active_listings")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0);
+    
+    let funded_projects = state.campaigns.funded_count().await.unwrap_or(0);
+    
+    let stats = DashboardStats {
+        total_students: 1, // Single-user system for now
+        active_listings,
+        total_loans: 0, // No loan system implemented yet
+        minted_nfts: 0, // No NFT system implemented yet
+        funded_projects,
+        total_volume: network_status.get("mempool_size")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0),
+    };
+    Json(ApiResponse::success(stats))
+}
+
+
+
+// ============================================================================
+// WEBSOCKET HANDLER FOR REAL-TIME UPDATES
+// ============================================================================
+
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> Response {
+    ws.on_upgrade(|socket| websocket_connection(socket, state))
+}
+
+async fn websocket_connection(mut socket: WebSocket, state: AppState) {
+    info!("🔌 New WebSocket connection established");
+    
+    // Send initial blockchain status
+    if let Ok(status) = state.explorer_cache.network_status(state.backend.get_network_status()).await {
+        let message = serde_json::json!({
+            "type": "network_status",
+            "data": status
+        });
+        
+        if socket.send(axum::extract::ws::Message::Text(
+            serde_json::to_string(&message).unwrap()
+        )).await.is_err() {
+            return;
+        }
+    }
+
+    // Set up periodic updates
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+    
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                // Send periodic updates
+                if let Ok(status) = state.explorer_cache.network_status(state.backend.get_network_status()).await {
+                    let message = serde_json::json!({
+                        "type": "network_update",
+                        "data": status,
+                        "timestamp": chrono::Utc::now().to_rfc3339()
+                    });
+                    
+                    if socket.send(axum::extract::ws::Message::Text(
+                        serde_json::to_string(&message).unwrap()
+                    )).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            
+            msg = socket.recv() => {
+                match msg {
+                    Some(Ok(axum::extract::ws::Message::Text(text))) => {
+                        // Handle client messages
+                        if let Ok(request) = serde_json::from_str::<serde_json::Value>(&text) {
+                            handle_websocket_request(&mut socket, &state, request).await;
+                        }
+                    }
+                    Some(Ok(axum::extract::ws::Message::Close(_))) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+    
+    info!("🔌 WebSocket connection closed");
+}
+
+async fn handle_websocket_request(
+    socket: &mut WebSocket,
+    state: &AppState,
+    request: serde_json::Value,
+) {
+    let request_type = request.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    
+    let response = match request_type {
+        "get_balance" => {
+            if let Some(address) = request.get("address").and_then(|v| v.as_str()) {
+                match state.backend.get_wallet_balance(address).await {
+                    Ok(balance) => serde_json::json!({
+                        "type": "balance_response",
+                        "address": address,
+                        "balance": balance
+                    }),
+                    Err(_) => serde_json::json!({
+                        "type": "error",
+                        "message": "Failed to get balance"
+                    })
+                }
+            } else {
+                serde_json::json!({
                     "type": "error",
                     "message": "Invalid address"
                 })
@@ -1419,96 +2599,432 @@ async fn api_login(
     }
 }
 
-async fn api_register(
+async fn api_register(
+    State(state): State<AppState>,
+    Json(request): Json<RegisterRequest>,
+) -> impl IntoResponse {
+    match state.user_manager.register_user(request).await {
+        Ok(user) => {
+            let response = serde_json::json!({
+                "success": true,
+                "user": {
+                    "id": user.id,
+                    "username": user.username,
+                    "email": user.email,
+                    "wallet_address": user.wallet_address,
+                    "university": user.university,
+                    "reputation_score": user.reputation_score,
+                    "created_at": user.created_at
+                },
+                "message": "User registered successfully! Please login."
+            });
+            Json(ApiResponse::success(response))
+        }
+        Err(error) => {
+            Json(ApiResponse::error(error))
+        }
+    }
+}
+
+async fn api_logout(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let session_token = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|auth| auth.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get("cookie")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|cookies| {
+                    cookies.split(';')
+                        .find_map(|cookie| {
+                            let cookie = cookie.trim();
+                            cookie.strip_prefix("session=")
+                        })
+                })
+        });
+
+    if let Some(token) = session_token {
+        let _ = state.user_manager.logout_user(token).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "set-cookie",
+        "session=; Path=/; HttpOnly; Max-Age=0; SameSite=Lax".parse().unwrap()
+    );
+
+    (StatusCode::OK, headers, Json(ApiResponse::success("Logged out successfully")))
+}
+
+async fn api_current_user(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match get_current_user(&headers, &state).await {
+        Ok(user) => {
+            let user_wallet = state.user_manager.get_user_wallet(&user).await.ok();
+            let balances = wallet_balances(&state, &user.wallet_address).await.unwrap_or_default();
+            
+            let response = serde_json::json!({
+                "id": user.id,
+                "username": user.username,
+                "email": user.email,
+                "wallet_address": user.wallet_address,
+                "university": user.university,
+                "reputation_score": user.reputation_score,
+                "created_at": user.created_at,
+                "last_login": user.last_login,
+                "wallet": user_wallet.map(|w| serde_json::json!({
+                    "id": w.id,
+                    "name": w.name,
+                    "address": w.address,
+                    "balance": balances.confirmed,
+                    "balances": balances,
+                    "fiat": state.fiat.convert(balances.confirmed)
+                }))
+            });
+            Json(ApiResponse::success(response))
+        }
+        Err(error) => {
+            Json(ApiResponse::error(error))
+        }
+    }
+}
+
+/// Two-factor code submitted to confirm or disable 2FA
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+async fn api_two_factor_status(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match get_current_user(&headers, &state).await {
+        Ok(user) => {
+            let enabled = state.user_manager.two_factor_enabled(&user.wallet_address).await;
+            Json(ApiResponse::success(serde_json::json!({ "enabled": enabled })))
+        }
+        Err(error) => Json(ApiResponse::error(error)),
+    }
+}
+
+/// Generate a TOTP secret; 2FA is enabled once a code from it is confirmed
+async fn api_two_factor_setup(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.user_manager.begin_two_factor_setup(&user).await {
+        Ok(setup) => Json(ApiResponse::success(setup)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Enable 2FA and return one-time recovery codes
+async fn api_two_factor_confirm(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.user_manager.confirm_two_factor_setup(&user, &request.code).await {
+        Ok(recovery_codes) => {
+            info!("🔐 Two-factor authentication enabled for {}", user.username);
+            Json(ApiResponse::success(serde_json::json!({ "recovery_codes": recovery_codes })))
+        }
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_two_factor_disable(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.user_manager.disable_two_factor(&user, &request.code).await {
+        Ok(()) => {
+            info!("🔓 Two-factor authentication disabled for {}", user.username);
+            Json(ApiResponse::success(serde_json::json!({ "enabled": false })))
+        }
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_get_spending_limits(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.spending.limits(&user).await {
+        Ok(limits) => Json(ApiResponse::success(limits)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Lowering limits takes effect immediately; raising them needs a 2FA code
+async fn api_update_spending_limits(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(update): Json<SpendingLimitUpdate>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.spending.update_limits(&user, &update).await {
+        Ok(limits) => Json(ApiResponse::success(limits)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Custodial deposit or withdrawal, in satoshis
+#[derive(Debug, Deserialize)]
+pub struct CustodyTransferRequest {
+    pub amount: u64,
+    /// Required for deposits above the step-up threshold
+    pub totp_code: Option<String>,
+}
+
+async fn api_custody_account(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.custody.account(&user).await {
+        Ok(account) => Json(ApiResponse::success(account)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Send EDU from the user's wallet into custody; credited once it confirms
+async fn api_custody_deposit(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<CustodyTransferRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    if request.amount == 0 {
+        return Json(ApiResponse::error("Amount must be positive".to_string()));
+    }
+
+    let reservation = match state.spending.reserve(&user, request.amount, request.totp_code.as_deref()).await {
+        Ok(reservation) => reservation,
+        Err(e) => return Json(ApiResponse::error(e)),
+    };
+    let hot_address = state.custody.config().hot_address.clone();
+    match state.backend.send_tagged_transaction(&user.wallet_address, &hot_address, request.amount, None, tags::for_kind(tags::CUSTODY_DEPOSIT, None)).await {
+        Ok(tx_hash) => {
+            state.spending.commit(reservation, &tx_hash).await;
+            Json(ApiResponse::success(tx_hash))
+        }
+        Err(e) => {
+            state.spending.release(reservation).await;
+            Json(ApiResponse::error(format!("Deposit failed: {}", e)))
+        }
+    }
+}
+
+/// Pay custodial funds back out to the user's own wallet
+async fn api_custody_withdraw(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<CustodyTransferRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.custody.withdraw(&user, request.amount).await {
+        Ok(tx_hash) => Json(ApiResponse::success(tx_hash)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct InvoiceListQuery {
+    pub limit: Option<i64>,
+}
+
+/// Issue a signed invoice paying the current user's wallet
+async fn api_create_invoice(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<CreateInvoiceRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.invoices.create(&user, &request).await {
+        Ok(invoice) => Json(ApiResponse::success(invoice)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_list_invoices(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<InvoiceListQuery>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.invoices.list(&user, query.limit.unwrap_or(50)).await {
+        Ok(invoices) => Json(ApiResponse::success(invoices)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Payment status of an invoice; public so payers can poll it
+async fn api_invoice_status(
     State(state): State<AppState>,
-    Json(request): Json<RegisterRequest>,
+    Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.user_manager.register_user(request).await {
-        Ok(user) => {
-            let response = serde_json::json!({
-                "success": true,
-                "user": {
-                    "id": user.id,
-                    "username": user.username,
-                    "email": user.email,
-                    "wallet_address": user.wallet_address,
-                    "university": user.university,
-                    "reputation_score": user.reputation_score,
-                    "created_at": user.created_at
-                },
-                "message": "User registered successfully! Please login."
-            });
-            Json(ApiResponse::success(response))
-        }
-        Err(error) => {
-            Json(ApiResponse::error(error))
-        }
+    match state.invoices.status(&id).await {
+        Ok(invoice) => Json(ApiResponse::success(invoice)),
+        Err(e) => Json(ApiResponse::error(e)),
     }
 }
 
-async fn api_logout(
+/// Verify a scanned invoice URI before paying it
+async fn api_verify_invoice(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyInvoiceRequest>,
+) -> impl IntoResponse {
+    match state.invoices.verify_uri(&request.uri).await {
+        Ok(invoice) => Json(ApiResponse::success(invoice)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Public key invoices from this platform are signed with
+async fn api_invoice_issuer(State(state): State<AppState>) -> impl IntoResponse {
+    Json(ApiResponse::success(serde_json::json!({
+        "issuer_public_key": hex::encode(state.invoices.issuer_public_key())
+    })))
+}
+
+/// Challenge to sign with the key of an address the user wants to link
+async fn api_address_challenge(
     headers: HeaderMap,
     State(state): State<AppState>,
+    Json(request): Json<AddressChallengeRequest>,
 ) -> impl IntoResponse {
-    let session_token = headers
-        .get("authorization")
-        .and_then(|value| value.to_str().ok())
-        .and_then(|auth| auth.strip_prefix("Bearer "))
-        .or_else(|| {
-            headers
-                .get("cookie")
-                .and_then(|value| value.to_str().ok())
-                .and_then(|cookies| {
-                    cookies.split(';')
-                        .find_map(|cookie| {
-                            let cookie = cookie.trim();
-                            cookie.strip_prefix("session=")
-                        })
-                })
-        });
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.address_proofs.challenge(&user, &request).await {
+        Ok(challenge) => Json(ApiResponse::success(challenge)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
 
-    if let Some(token) = session_token {
-        let _ = state.user_manager.logout_user(token).await;
+/// Link an address by answering its challenge with a signed message
+async fn api_prove_address(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<AddressProofRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.address_proofs.prove(&user, &request).await {
+        Ok(linked) => Json(ApiResponse::success(linked)),
+        Err(e) => Json(ApiResponse::error(e)),
     }
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "set-cookie",
-        "session=; Path=/; HttpOnly; Max-Age=0; SameSite=Lax".parse().unwrap()
-    );
+async fn api_list_linked_addresses(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.address_proofs.list(&user).await {
+        Ok(linked) => Json(ApiResponse::success(linked)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
 
-    (StatusCode::OK, headers, Json(ApiResponse::success("Logged out successfully")))
+async fn api_unlink_address(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.address_proofs.unlink(&user, &address).await {
+        Ok(()) => Json(ApiResponse::success(address)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
 }
 
-async fn api_current_user(
+/// Accept an enrollment attestation from a registered issuer; the issuer's
+/// signature is the authentication
+async fn api_submit_attestation(
+    State(state): State<AppState>,
+    Json(request): Json<SubmitAttestationRequest>,
+) -> impl IntoResponse {
+    match state.attestations.submit(&request).await {
+        Ok(attestation) => Json(ApiResponse::success(attestation)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// Check an attestation's signature, issuer, expiry, document and on-chain anchor
+async fn api_verify_attestation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.attestations.verify(&id).await {
+        Ok(verification) => Json(ApiResponse::success(verification)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_my_attestations(
     headers: HeaderMap,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
-    match get_current_user(&headers, &state).await {
-        Ok(user) => {
-            let user_wallet = state.user_manager.get_user_wallet(&user).await.ok();
-            
-            let response = serde_json::json!({
-                "id": user.id,
-                "username": user.username,
-                "email": user.email,
-                "wallet_address": user.wallet_address,
-                "university": user.university,
-                "reputation_score": user.reputation_score,
-                "created_at": user.created_at,
-                "last_login": user.last_login,
-                "wallet": user_wallet.map(|w| serde_json::json!({
-                    "id": w.id,
-                    "name": w.name,
-                    "address": w.address,
-                    "balance": w.balance
-                }))
-            });
-            Json(ApiResponse::success(response))
-        }
-        Err(error) => {
-            Json(ApiResponse::error(error))
-        }
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.attestations.for_subject(&user.wallet_address).await {
+        Ok(attestations) => Json(ApiResponse::success(attestations)),
+        Err(e) => Json(ApiResponse::error(e)),
     }
 }
 
@@ -1567,9 +3083,11 @@ async fn get_current_user_info(
                     private_key: [0u8; 32],
                     public_key: [0u8; 33],
                     address: user.wallet_address.clone(),
-                    balance: 100, // Demo balance (u64)
+                    balance: Balance { confirmed: 100, ..Balance::default() }, // Demo balance
                     created_at: chrono::Utc::now(),
                 });
+            let balances = wallet_balances(&state, &user.wallet_address).await
+                .unwrap_or(user_wallet.balance);
             
             let user_info = serde_json::json!({
                 "id": user.id,
@@ -1580,7 +3098,9 @@ async fn get_current_user_info(
                 "reputation_score": user.reputation_score,
                 "created_at": user.created_at,
                 "wallet": {
-                    "balance": user_wallet.balance,
+                    "balance": balances.confirmed,
+                    "balances": balances,
+                    "fiat": state.fiat.convert(balances.confirmed),
                     "address": user_wallet.address
                 }
             });
@@ -1618,15 +3138,18 @@ async fn blockchain_get_balance(
     State(state): State<AppState>,
     Path(address): Path<String>,
 ) -> impl IntoResponse {
-    // Get REAL blockchain balance from PRODUCTION UTXO set
-    match state.backend.get_wallet_balance(&address).await {
-        Ok(balance_satoshis) => {
-            let balance_edu = balance_satoshis as f64 / 100_000_000.0;
+    // Get REAL blockchain balance from PRODUCTION UTXO set and mempool
+    match state.explorer_cache.balance(&address, wallet_balances(&state, &address)).await {
+        Ok(balances) => {
+            let balance_satoshis = balances.confirmed;
+            let balance_edu = Amount::from_sat(balance_satoshis).to_edu_f64();
             info!("💰 REAL balance for {}: {} EDU ({} satoshis)", address, balance_edu, balance_satoshis);
             
             Json(serde_json::json!({ 
                 "balance": balance_edu,
                 "balance_satoshis": balance_satoshis,
+                "balances": balances,
+                "fiat": state.fiat.convert(balance_satoshis),
                 "address": address,
                 "balance_type": "PRODUCTION_UTXO_VALIDATED",
                 "last_updated": chrono::Utc::now().to_rfc3339()
@@ -1637,6 +3160,7 @@ async fn blockchain_get_balance(
             Json(serde_json::json!({ 
                 "balance": 0.0,
                 "balance_satoshis": 0,
+                "balances": Balance::default(),
                 "address": address,
                 "error": format!("Failed to fetch real blockchain balance: {}", e)
             }))
@@ -1645,11 +3169,17 @@ async fn blockchain_get_balance(
 }
 
 async fn blockchain_network_status(State(state): State<AppState>) -> impl IntoResponse {
-    let peer_count = state.backend.network.get_connected_peers().await.len();
-    Json(serde_json::json!({
-        "connected_peers": peer_count,
-        "network_status": "connected"
-    }))
+    match state.explorer_cache.network_status(state.backend.get_network_status()).await {
+        Ok(status) => Json(serde_json::json!({
+            "connected_peers": status["connected_peers"],
+            "network_status": "connected"
+        })),
+        Err(e) => Json(serde_json::json!({
+            "connected_peers": 0,
+            "network_status": "disconnected",
+            "error": e.to_string()
+        })),
+    }
 }
 
 async fn blockchain_mining_stats(State(_state): State<AppState>) -> impl IntoResponse {
@@ -1680,13 +3210,51 @@ async fn api_send_transaction(
     };
     
     info!("💸 REAL TRANSACTION: {} EDU from {} to {}", req.amount, user.username, req.recipient);
+
+    if let Err(e) = tags::validate_user_tags(&req.tags) {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": e
+        }));
+    }
     
     // Convert EDU to satoshis (1 EDU = 100,000,000 satoshis)
-    let amount_satoshis = (req.amount * 100_000_000.0) as u64;
+    let amount_satoshis = match Amount::from_edu_f64(req.amount) {
+        Ok(amount) => amount.to_sat(),
+        Err(e) => return Json(serde_json::json!({
+            "success": false,
+            "message": format!("Invalid amount: {}", e)
+        })),
+    };
+    if amount_satoshis == 0 {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": "Amount must be positive"
+        }));
+    }
+    
+    // Recipients may be given as `.edu` names
+    let recipient_address = match state.backend.resolve_destination(&req.recipient).await {
+        Ok(address) => address,
+        Err(e) => return Json(serde_json::json!({
+            "success": false,
+            "message": e.to_string()
+        })),
+    };
+    
+    // Hold the amount against the daily limit (and step up above the threshold)
+    let reservation = match state.spending.reserve(&user, amount_satoshis, req.totp_code.as_deref()).await {
+        Ok(reservation) => reservation,
+        Err(e) => return Json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+    };
     
     // Send REAL blockchain transaction with ECDSA signatures
-    match state.backend.send_transaction(&user.wallet_address, &req.recipient, amount_satoshis, req.message).await {
+    match state.backend.send_tagged_transaction(&user.wallet_address, &recipient_address, amount_satoshis, req.message, req.tags).await {
         Ok(tx_hash) => {
+            state.spending.commit(reservation, &tx_hash).await;
             info!("✅ REAL transaction sent with hash: {}", tx_hash);
             Json(serde_json::json!({
                 "success": true,
@@ -1695,11 +3263,13 @@ async fn api_send_transaction(
                 "amount": req.amount,
                 "amount_satoshis": amount_satoshis,
                 "recipient": req.recipient,
+                "recipient_address": recipient_address,
                 "from_address": user.wallet_address,
                 "transaction_type": "PRODUCTION_ECDSA_SIGNED"
             }))
         },
         Err(e) => {
+            state.spending.release(reservation).await;
             error!("❌ Failed to send real transaction: {}", e);
             Json(serde_json::json!({
                 "success": false,
@@ -1709,6 +3279,50 @@ async fn api_send_transaction(
     }
 }
 
+/// Look up the address a `.edu` name points at
+async fn api_resolve_name(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.backend.resolve_name(&name).await {
+        Ok(Some(record)) => Json(ApiResponse::success(record)),
+        Ok(None) => Json(ApiResponse::error(format!("{} is not a registered name", name))),
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
+/// `.edu` names pointing at an address
+async fn api_names_for_address(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    match state.backend.names_for(&address).await {
+        Ok(names) => Json(ApiResponse::success(names)),
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
+/// Add or overwrite tags on a transaction the user sent or received
+async fn api_tag_transaction(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+    Json(tags): Json<TransactionTags>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    if let Err(e) = tags::validate_user_tags(&tags) {
+        return Json(ApiResponse::error(e));
+    }
+
+    match state.backend.tag_transaction(&user.wallet_address, &hash, tags).await {
+        Ok(tags) => Json(ApiResponse::success(tags)),
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
 async fn api_get_transactions(
     headers: HeaderMap,
     State(state): State<AppState>,
@@ -1745,7 +3359,7 @@ async fn api_get_transactions(
             
             Json(serde_json::json!({
                 "success": true,
-                "transactions": all_transactions
+                "transactions": with_fiat_amounts(&state, all_transactions)
             }))
         }
         Err(e) => {
@@ -1758,6 +3372,128 @@ async fn api_get_transactions(
     }
 }
 
+/// The user's ledger with running balances
+async fn api_wallet_ledger(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<LedgerQuery>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return Json(ApiResponse::error("Authentication required".to_string())),
+    };
+    match ledger::address_ledger(&state.database, &user.wallet_address, &query).await {
+        Ok(entries) => Json(ApiResponse::success(entries)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+/// The user's ledger as a CSV or OFX download
+async fn api_export_wallet_ledger(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<LedgerQuery>,
+) -> Response {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(_) => return (StatusCode::UNAUTHORIZED, "Authentication required").into_response(),
+    };
+    export_ledger(&state, &user.wallet_address, &query).await
+}
+
+/// Any address's ledger, such as the treasury's, as a CSV or OFX download
+async fn admin_export_ledger(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+    Query(query): Query<LedgerQuery>,
+) -> Response {
+    if let Err(e) = get_admin_user(&headers, &state, "export_ledger").await {
+        return (StatusCode::FORBIDDEN, e).into_response();
+    }
+    export_ledger(&state, &address, &query).await
+}
+
+async fn export_ledger(state: &AppState, address: &str, query: &LedgerQuery) -> Response {
+    let format = match ExportFormat::parse(query.format.as_deref()) {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let entries = match ledger::address_ledger(&state.database, address, query).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to build ledger for {}: {}", address, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+        }
+    };
+
+    let body = match format {
+        ExportFormat::Csv => ledger::to_csv(&entries),
+        ExportFormat::Ofx => ledger::to_ofx(address, &entries),
+    };
+    let disposition = format!("attachment; filename=\"ledger-{}.{}\"", address, format.extension());
+    (
+        StatusCode::OK,
+        [
+            ("content-type", format.content_type().to_string()),
+            ("content-disposition", disposition),
+        ],
+        body,
+    ).into_response()
+}
+
+/// Count an export against the caller's rate limit
+fn admit_export(state: &AppState, headers: &HeaderMap, peer: std::net::SocketAddr) -> Result<(), Response> {
+    let forwarded_for = headers.get("x-forwarded-for").and_then(|v| v.to_str().ok());
+    let client = audit::client_ip(forwarded_for, Some(peer), state.audit.config())
+        .unwrap_or_else(|| peer.ip().to_string());
+    state.exports.admit(&client).map_err(|retry_after| (
+        StatusCode::TOO_MANY_REQUESTS,
+        [("retry-after", retry_after.as_secs().max(1).to_string())],
+        "Export rate limit reached, try again later",
+    ).into_response())
+}
+
+/// Block headers in a height range as CSV or JSON
+async fn api_export_blocks(
+    headers: HeaderMap,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    State(state): State<AppState>,
+    Query(query): Query<BlockRangeQuery>,
+) -> Response {
+    if let Err(response) = admit_export(&state, &headers, peer) {
+        return response;
+    }
+    state.exports.blocks(&query).await.unwrap_or_else(IntoResponse::into_response)
+}
+
+/// An address's transaction history in a time window as CSV or JSON
+async fn api_export_address_history(
+    headers: HeaderMap,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+    Query(query): Query<TimeRangeQuery>,
+) -> Response {
+    if let Err(response) = admit_export(&state, &headers, peer) {
+        return response;
+    }
+    state.exports.address_history(&address, &query).await.unwrap_or_else(IntoResponse::into_response)
+}
+
+/// Treasury coin sales in a time window as CSV or JSON
+async fn api_export_treasury_sales(
+    headers: HeaderMap,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    State(state): State<AppState>,
+    Query(query): Query<TimeRangeQuery>,
+) -> Response {
+    if let Err(response) = admit_export(&state, &headers, peer) {
+        return response;
+    }
+    state.exports.treasury_sales(&query).await.unwrap_or_else(IntoResponse::into_response)
+}
+
 async fn api_get_pending_transactions(
     headers: HeaderMap,
     State(state): State<AppState>,
@@ -1783,7 +3519,7 @@ async fn api_get_pending_transactions(
             
             Json(serde_json::json!({
                 "success": true,
-                "pending_transactions": pending_transactions
+                "pending_transactions": with_fiat_amounts(&state, pending_transactions)
             }))
         }
         Err(e) => {
@@ -1803,14 +3539,14 @@ async fn api_get_recent_transactions(
     info!("📊 Get recent network transactions");
     
     // Get recent transactions across the network (public data)
-    match state.backend.get_recent_transactions(50).await {
+    match state.explorer_cache.recent_transactions(50, state.backend.get_recent_transactions(50)).await {
         Ok(recent_transactions) => {
             info!("📊 Found {} recent network transactions", recent_transactions.len());
             
             Json(serde_json::json!({
                 "success": true,
-                "recent_transactions": recent_transactions,
-                "network_activity": recent_transactions.len()
+                "network_activity": recent_transactions.len(),
+                "recent_transactions": with_fiat_amounts(&state, recent_transactions)
             }))
         }
         Err(e) => {
@@ -1841,7 +3577,7 @@ async fn api_mine_block(
     // Mine REAL block with ECDSA transaction validation
     match state.backend.mine_block(user.wallet_address.clone()).await {
         Ok((block_hash, reward, tx_count)) => {
-            let reward_edu = reward as f64 / 100_000_000.0;
+            let reward_edu = Amount::from_sat(reward).to_edu_f64();
             info!("✅ REAL block mined: {} (reward: {} EDU, txs: {})", block_hash, reward_edu, tx_count);
             
             Json(serde_json::json!({
@@ -1875,7 +3611,7 @@ async fn api_network_status(
     info!("🌐 Get REAL network status from production blockchain");
     
     // Get REAL network status from blockchain backend
-    match state.backend.get_network_status().await {
+    match state.explorer_cache.network_status(state.backend.get_network_status()).await {
         Ok(network_status) => {
             info!("✅ Retrieved real network status: {:?}", network_status);
             
@@ -1935,6 +3671,15 @@ async fn api_nft_mint(
         })),
     };
 
+    if let Some(image_url) = &request.image_url {
+        if let Err(e) = check_media_uri(&state, image_url) {
+            return Json(serde_json::json!({
+                "success": false,
+                "message": e
+            }));
+        }
+    }
+
     info!("🎨 Minting NFT: {} for user {}", request.name, user.username);
 
     // Create NFT transaction (1 satoshi UTXO with NFT metadata)
@@ -1942,11 +3687,12 @@ async fn api_nft_mint(
     let timestamp = Utc::now().timestamp();
 
     // Send 1 satoshi transaction to represent NFT
-    let tx_result = state.backend.send_transaction(
+    let tx_result = state.backend.send_tagged_transaction(
         &user.wallet_address,
         &user.wallet_address, // NFT owned by creator initially
         1, // 1 satoshi
-        Some(format!("NFT_MINT:{}", nft_id))
+        None,
+        tags::for_kind(tags::NFT_MINT, Some((tags::NFT_ID, &nft_id))),
     ).await;
 
     match tx_result {
@@ -2095,11 +3841,12 @@ async fn api_nft_transfer(
     }
 
     // Transfer NFT (send 1 satoshi transaction)
-    match state.backend.send_transaction(
+    match state.backend.send_tagged_transaction(
         &user.wallet_address,
         &request.to_address,
         1,
-        Some(format!("NFT_TRANSFER:{}", request.nft_id))
+        None,
+        tags::for_kind(tags::NFT_TRANSFER, Some((tags::NFT_ID, &request.nft_id))),
     ).await {
         Ok(tx_hash) => {
             // Update NFT ownership in database
@@ -2128,15 +3875,109 @@ async fn api_nft_transfer(
             }
         }
         Err(e) => {
-            error!("❌ Failed to transfer NFT: {}", e);
+            error!("❌ Failed to transfer NFT: {}", e);
+            Json(serde_json::json!({
+                "success": false,
+                "message": format!("Failed to transfer NFT: {}", e)
+            }))
+        }
+    }
+}
+
+// ==================== MEDIA API HANDLERS ====================
+
+/// With IPFS enabled, media must be referenced by content hash rather than a raw URL
+fn check_media_uri(state: &AppState, uri: &str) -> Result<(), String> {
+    if state.ipfs.is_none() {
+        return Ok(());
+    }
+    MediaRef::parse_uri(uri)
+        .map(|_| ())
+        .map_err(|_| "Media must be uploaded via /api/media/upload and referenced by its ipfs:// URI".to_string())
+}
+
+async fn api_media_upload(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    if get_current_user(&headers, &state).await.is_err() {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": "Authentication required"
+        }));
+    }
+
+    let Some(ipfs) = state.ipfs.clone() else {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": "Media uploads are not enabled"
+        }));
+    };
+
+    let field = match multipart.next_field().await {
+        Ok(Some(field)) => field,
+        _ => return Json(serde_json::json!({
+            "success": false,
+            "message": "Expected a file field"
+        })),
+    };
+    let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+    let content = match field.bytes().await {
+        Ok(bytes) => bytes.to_vec(),
+        Err(e) => return Json(serde_json::json!({
+            "success": false,
+            "message": format!("Failed to read upload: {}", e)
+        })),
+    };
+
+    match ipfs.pin(content, &content_type).await {
+        Ok(pinned) => {
+            info!("📦 Pinned media {} ({} bytes)", pinned.media.cid, pinned.size);
+            Json(serde_json::json!({
+                "success": true,
+                "media": pinned
+            }))
+        }
+        Err(e) => {
+            error!("❌ Failed to pin media: {}", e);
             Json(serde_json::json!({
                 "success": false,
-                "message": format!("Failed to transfer NFT: {}", e)
+                "message": format!("Failed to pin media: {}", e)
             }))
         }
     }
 }
 
+async fn api_media_get(
+    Path((cid, sha256)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    let Some(ipfs) = state.ipfs.clone() else {
+        return (StatusCode::NOT_FOUND, "Media storage is not enabled").into_response();
+    };
+    let media = match MediaRef::from_parts(&cid, &sha256) {
+        Ok(media) => media,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    match ipfs.fetch(&media).await {
+        Ok(content) => (
+            StatusCode::OK,
+            [
+                ("content-type", "application/octet-stream"),
+                // Content-addressed and verified, so it never changes
+                ("cache-control", "public, max-age=31536000, immutable"),
+            ],
+            content,
+        ).into_response(),
+        Err(e) => {
+            error!("❌ Failed to fetch media {}: {}", cid, e);
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+    }
+}
+
 // ==================== LOAN API HANDLERS ====================
 
 #[derive(Debug, Deserialize)]
@@ -2154,6 +3995,8 @@ struct LoanApplicationRequest {
     graduation_year: Option<i32>,
     expected_career: Option<String>,
     expected_salary: Option<i64>,
+    /// EDU or an NFT to lock against the loan
+    collateral: Option<CollateralRequest>,
 }
 
 async fn api_loan_apply(
@@ -2172,14 +4015,31 @@ async fn api_loan_apply(
     info!("📋 Loan application from {}: {} EDU", user.username, request.requested_amount as f64 / 100_000_000.0);
 
     // Calculate Proof-of-Potential score
-    let mut score = 5.0; // Base score
-    if let Some(gpa) = request.gpa {
-        score += (gpa / 4.0) * 2.5; // Up to 2.5 points for GPA
-    }
-    if let Some(test_score) = request.test_score {
-        score += (test_score as f64 / 1600.0) * 2.5; // Up to 2.5 points for test scores
+    let inputs = match state.scoring.inputs(&user.wallet_address, request.test_score).await {
+        Ok(inputs) => inputs,
+        Err(e) => return Json(serde_json::json!({
+            "success": false,
+            "message": format!("Failed to score loan application: {}", e)
+        })),
+    };
+    let explanation = state.scoring.score(&inputs);
+    let score = explanation.score;
+
+    if let Some(collateral) = &request.collateral {
+        if let Err(e) = state.collateral.check_request(&user, collateral).await {
+            return Json(serde_json::json!({
+                "success": false,
+                "message": e
+            }));
+        }
     }
 
+    // Enrollment data comes from the applicant's attestation when there is one
+    let (university, field_of_study, gpa) = match inputs.claim {
+        Some(claim) => (claim.university, claim.program, claim.gpa),
+        None => (request.university, request.field_of_study, request.gpa),
+    };
+
     let loan_id = format!("loan_{}", Uuid::new_v4().simple());
     
     let loan = crate::database::DbLoanApplication {
@@ -2188,9 +4048,9 @@ async fn api_loan_apply(
         applicant_username: user.username.clone(),
         applicant_address: user.wallet_address.clone(),
         full_name: request.full_name,
-        university: request.university,
-        field_of_study: request.field_of_study,
-        gpa: request.gpa,
+        university,
+        field_of_study,
+        gpa,
         test_score: request.test_score.map(|s| s as i64),
         achievements: request.achievements,
         requested_amount: request.requested_amount,
@@ -2209,10 +4069,26 @@ async fn api_loan_apply(
     match state.database.create_loan_application(&loan).await {
         Ok(_) => {
             info!("✅ Loan application created: {} (score: {:.1})", loan_id, score);
+            let collateral = match &request.collateral {
+                Some(collateral) => match state.collateral.create(&user, &loan, collateral).await {
+                    Ok(lock) => Some(lock),
+                    Err(e) => {
+                        error!("❌ Failed to create collateral lock for {}: {}", loan_id, e);
+                        return Json(serde_json::json!({
+                            "success": false,
+                            "loan_id": loan_id,
+                            "message": format!("Loan application created but its collateral lock failed: {}", e)
+                        }));
+                    }
+                },
+                None => None,
+            };
             Json(serde_json::json!({
                 "success": true,
                 "loan_id": loan_id,
                 "proof_of_potential_score": score,
+                "score_explanation": explanation,
+                "collateral": collateral,
                 "message": "Loan application submitted successfully"
             }))
         }
@@ -2276,6 +4152,7 @@ async fn api_loan_get(
 struct LoanFundRequest {
     loan_id: String,
     amount: i64,
+    totp_code: Option<String>,
 }
 
 async fn api_loan_fund(
@@ -2311,16 +4188,41 @@ async fn api_loan_fund(
         }));
     }
 
+    if request.amount <= 0 {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": "Amount must be positive"
+        }));
+    }
+
+    // Collateralized loans are only funded once the lock is confirmed on-chain
+    if let Err(e) = state.collateral.verify_lock(&request.loan_id).await {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": e
+        }));
+    }
+
+    let reservation = match state.spending.reserve(&user, request.amount as u64, request.totp_code.as_deref()).await {
+        Ok(reservation) => reservation,
+        Err(e) => return Json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+    };
+
     info!("💰 Funding loan {} with {} EDU from {}", request.loan_id, request.amount as f64 / 100_000_000.0, user.username);
 
     // Send funding transaction
-    match state.backend.send_transaction(
+    match state.backend.send_tagged_transaction(
         &user.wallet_address,
         &loan.applicant_address,
         request.amount as u64,
-        Some(format!("LOAN_FUNDING:{}", request.loan_id))
+        None,
+        tags::for_kind(tags::LOAN_FUNDING, Some((tags::LOAN_ID, &request.loan_id))),
     ).await {
         Ok(tx_hash) => {
+            state.spending.commit(reservation, &tx_hash).await;
             // Record funding in database
             match state.database.fund_loan(
                 &request.loan_id,
@@ -2347,6 +4249,7 @@ async fn api_loan_fund(
             }
         }
         Err(e) => {
+            state.spending.release(reservation).await;
             error!("❌ Failed to send funding transaction: {}", e);
             Json(serde_json::json!({
                 "success": false,
@@ -2356,9 +4259,309 @@ async fn api_loan_fund(
     }
 }
 
+async fn api_loan_collateral(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.collateral.get(&id).await {
+        Ok(Some(collateral)) => Json(serde_json::json!({
+            "success": true,
+            "collateral": collateral
+        })),
+        Ok(None) => Json(serde_json::json!({
+            "success": false,
+            "message": "Loan has no collateral"
+        })),
+        Err(e) => Json(serde_json::json!({
+            "success": false,
+            "message": format!("Failed to get collateral: {}", e)
+        })),
+    }
+}
+
+async fn api_loan_lock_collateral(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<LockCollateralRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(u) => u,
+        Err(_) => return Json(serde_json::json!({
+            "success": false,
+            "message": "Authentication required"
+        })),
+    };
+
+    match state.collateral.lock(&user, &id, &request).await {
+        Ok(collateral) => Json(serde_json::json!({
+            "success": true,
+            "collateral": collateral,
+            "message": "Collateral locked"
+        })),
+        Err(e) => Json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+    }
+}
+
+/// Pay an installment, split across the current holders of the loan's
+/// repayment rights
+async fn api_loan_repay(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<RepayRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(u) => u,
+        Err(_) => return Json(serde_json::json!({
+            "success": false,
+            "message": "Authentication required"
+        })),
+    };
+
+    match state.loan_shares.repay(&user, &id, &request).await {
+        Ok(installment) => Json(serde_json::json!({
+            "success": true,
+            "installment": installment,
+            "message": "Installment paid"
+        })),
+        Err(e) => Json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+    }
+}
+
+async fn api_loan_distributions(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.loan_shares.distributions(&id).await {
+        Ok(distributions) => Json(serde_json::json!({
+            "success": true,
+            "distributions": distributions
+        })),
+        Err(e) => Json(serde_json::json!({
+            "success": false,
+            "message": format!("Failed to get distributions: {}", e)
+        })),
+    }
+}
+
+async fn api_loan_positions(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.loan_shares.positions(&user).await {
+        Ok(positions) => Json(ApiResponse::success(positions)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_tokenize_loan_position(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<TokenizeRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.loan_shares.tokenize(&user, &request).await {
+        Ok(position) => Json(ApiResponse::success(position)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_loan_share_listings(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let limit = params.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(50);
+    match state.loan_shares.listings(limit).await {
+        Ok(listings) => Json(ApiResponse::success(listings)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_list_loan_position(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<ListPositionRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.loan_shares.list(&user, &request).await {
+        Ok(listing) => Json(ApiResponse::success(listing)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_buy_loan_position(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<BuyPositionRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.loan_shares.buy(&user, &id, &request).await {
+        Ok(position) => Json(ApiResponse::success(position)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_cancel_loan_listing(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.loan_shares.cancel(&user, &id).await {
+        Ok(()) => Json(ApiResponse::success(id)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_campaigns(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let status = params.get("status").map(String::as_str).unwrap_or("active");
+    let limit = params.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(50);
+    match state.campaigns.list(status, limit).await {
+        Ok(campaigns) => Json(ApiResponse::success(campaigns)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_create_campaign(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<CreateCampaignRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.campaigns.create(&user, &request).await {
+        Ok(campaign) => Json(ApiResponse::success(campaign)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_campaign(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.campaigns.get(&id).await {
+        Ok(campaign) => Json(ApiResponse::success(campaign)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_pledge_campaign(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<PledgeRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.campaigns.pledge(&user, &id, &request).await {
+        Ok(pledge) => Json(ApiResponse::success(pledge)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_distributions(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.distributions.list(&user).await {
+        Ok(distributions) => Json(ApiResponse::success(distributions)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_create_distribution(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(request): Json<CreateDistributionRequest>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.distributions.create(&user, &request).await {
+        Ok(distribution) => Json(ApiResponse::success(distribution)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_distribution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.distributions.get(&id).await {
+        Ok(distribution) => Json(ApiResponse::success(distribution)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_pause_distribution(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.distributions.pause(&user, &id).await {
+        Ok(()) => Json(ApiResponse::success(id)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
+async fn api_resume_distribution(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user = match get_current_user(&headers, &state).await {
+        Ok(user) => user,
+        Err(error) => return Json(ApiResponse::error(error)),
+    };
+    match state.distributions.resume(&user, &id).await {
+        Ok(()) => Json(ApiResponse::success(id)),
+        Err(e) => Json(ApiResponse::error(e)),
+    }
+}
+
 /// Get blockchain synchronization status
 async fn api_sync_status(State(state): State<AppState>) -> impl IntoResponse {
-    let sync_status = state.backend.get_sync_status().await;
+    let sync_status = state.explorer_cache.sync_status(state.backend.get_sync_status()).await;
     Json(sync_status)
 }
 
@@ -0,0 +1,168 @@
+//! Versioned schema migrations
+//!
+//! Migrations are numbered SQL files under `migrations/`, embedded at build
+//! time and applied in order on startup. Each applied migration is recorded
+//! in `schema_migrations` with a checksum of its SQL, inside the same
+//! transaction as the migration itself, so it runs exactly once per database.
+//!
+//! Guards:
+//! - A database carrying a migration this build doesn't know was written by a
+//!   newer release; refusing to start keeps an older binary from downgrading it
+//! - An applied migration whose SQL has since changed is reported instead of
+//!   being silently skipped; ship a new migration rather than editing an old one
+//!
+//! Migrations up to 010 predate tracking and are idempotent, so databases
+//! created before this module are adopted by simply re-running them.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use tracing::info;
+
+/// A numbered schema change
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// Hex SHA-256 of the migration's SQL
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.sql.as_bytes()))
+    }
+}
+
+/// Every migration this build knows, in version order.
+/// 001 and 003 are superseded by 002 and never applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 2, name: "production_schema", sql: include_str!("../migrations/002_production_schema.sql") },
+    Migration { version: 4, name: "marketplace_search", sql: include_str!("../migrations/004_marketplace_search.sql") },
+    Migration { version: 5, name: "marketplace_orders", sql: include_str!("../migrations/005_marketplace_orders.sql") },
+    Migration { version: 6, name: "notifications", sql: include_str!("../migrations/006_notifications.sql") },
+    Migration { version: 7, name: "admin", sql: include_str!("../migrations/007_admin.sql") },
+    Migration { version: 8, name: "wallet_security", sql: include_str!("../migrations/008_wallet_security.sql") },
+    Migration { version: 9, name: "custody", sql: include_str!("../migrations/009_custody.sql") },
+    Migration { version: 10, name: "invoices", sql: include_str!("../migrations/010_invoices.sql") },
+    Migration { version: 11, name: "audit_log", sql: include_str!("../migrations/011_audit_log.sql") },
+    Migration { version: 12, name: "transaction_tags", sql: include_str!("../migrations/012_transaction_tags.sql") },
+    Migration { version: 13, name: "transaction_reorgs", sql: include_str!("../migrations/013_transaction_reorgs.sql") },
+    Migration { version: 14, name: "address_proofs", sql: include_str!("../migrations/014_address_proofs.sql") },
+    Migration { version: 15, name: "attestations", sql: include_str!("../migrations/015_attestations.sql") },
+    Migration { version: 16, name: "loan_collateral", sql: include_str!("../migrations/016_loan_collateral.sql") },
+    Migration { version: 17, name: "loan_shares", sql: include_str!("../migrations/017_loan_shares.sql") },
+    Migration { version: 18, name: "campaigns", sql: include_str!("../migrations/018_campaigns.sql") },
+    Migration { version: 19, name: "distributions", sql: include_str!("../migrations/019_distributions.sql") },
+    Migration { version: 20, name: "two_factor_lockout", sql: include_str!("../migrations/020_two_factor_lockout.sql") },
+];
+
+const CREATE_MIGRATIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    app_version TEXT NOT NULL,           -- Release that applied the migration
+    applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+)
+"#;
+
+/// Outcome of a migration run
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    /// Highest version applied before the run
+    pub current_version: Option<i64>,
+    /// Highest version this build knows
+    pub latest_version: i64,
+    /// Migrations applied, or that would be in a dry run
+    pub pending: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Applies `MIGRATIONS` to a database
+pub struct Migrator<'a> {
+    pool: &'a SqlitePool,
+}
+
+impl<'a> Migrator<'a> {
+    pub fn new(pool: &'a SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Versions and checksums already applied
+    async fn applied(&self) -> Result<HashMap<i64, String>> {
+        let tracked = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'")
+            .fetch_optional(self.pool)
+            .await?;
+        if tracked.is_none() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query("SELECT version, checksum FROM schema_migrations")
+            .fetch_all(self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get("version"), row.get("checksum"))).collect())
+    }
+
+    /// Check the guards and work out what still needs applying
+    async fn plan(&self) -> Result<(Option<i64>, Vec<Migration>)> {
+        let applied = self.applied().await?;
+        let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+
+        if let Some(newest) = applied.keys().copied().filter(|v| *v > latest).max() {
+            bail!(
+                "Database schema is at migration {} but this build only knows up to {}; \
+                 refusing to run an older release against a newer database",
+                newest, latest
+            );
+        }
+        for migration in MIGRATIONS {
+            if let Some(checksum) = applied.get(&migration.version) {
+                if *checksum != migration.checksum() {
+                    bail!(
+                        "Migration {:03}_{} was modified after it was applied; add a new migration instead",
+                        migration.version, migration.name
+                    );
+                }
+            }
+        }
+
+        let pending = MIGRATIONS.iter()
+            .filter(|m| !applied.contains_key(&m.version))
+            .copied()
+            .collect();
+        Ok((applied.keys().copied().max(), pending))
+    }
+
+    /// Apply every pending migration, each in its own transaction.
+    /// With `dry_run` the plan is reported and the database left untouched.
+    pub async fn run(&self, dry_run: bool) -> Result<MigrationReport> {
+        let (current_version, pending) = self.plan().await?;
+        let report = MigrationReport {
+            current_version,
+            latest_version: MIGRATIONS.last().map_or(0, |m| m.version),
+            pending: pending.iter().map(|m| format!("{:03}_{}", m.version, m.name)).collect(),
+            dry_run,
+        };
+        if dry_run {
+            return Ok(report);
+        }
+
+        sqlx::query(CREATE_MIGRATIONS_TABLE).execute(self.pool).await?;
+        for migration in &pending {
+            info!("📜 Applying migration {:03}_{}", migration.version, migration.name);
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(migration.sql).execute(&mut *tx).await?;
+            sqlx::query("INSERT INTO schema_migrations (version, name, checksum, app_version) VALUES (?, ?, ?, ?)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(migration.checksum())
+                .bind(env!("CARGO_PKG_VERSION"))
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+        Ok(report)
+    }
+}
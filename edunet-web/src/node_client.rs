@@ -0,0 +1,158 @@
+//! Shared blockchain-node connection
+//!
+//! Web instances can run without an embedded node and talk JSON-RPC to one
+//! or more blockchain-node endpoints instead, so any number of them can sit
+//! behind a load balancer in front of the same chain. Routing, health checks
+//! and failover are handled by `MultiRpcClient`: reads go to the healthiest
+//! node and broadcasts fail over until a node accepts them.
+
+use blockchain_core::canonical::Sats;
+use blockchain_core::consensus::ChainTip;
+use blockchain_core::naming::NameRecord;
+use blockchain_core::Hash256;
+use blockchain_rpc::{MultiRpcClient, MultiRpcConfig, NodeHealth};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Node endpoints and routing policy
+#[derive(Debug, Clone)]
+pub struct NodeClientConfig {
+    /// JSON-RPC URLs of the nodes
+    pub endpoints: Vec<String>,
+    pub rpc: MultiRpcConfig,
+    /// Token for the node's admin RPCs
+    pub admin_token: Option<String>,
+    /// How often the chain tip is polled
+    pub tip_poll_interval: Duration,
+}
+
+impl NodeClientConfig {
+    pub fn new(endpoints: Vec<String>) -> Self {
+        Self {
+            endpoints,
+            rpc: MultiRpcConfig::default(),
+            admin_token: std::env::var("NODE_RPC_ADMIN_TOKEN").ok(),
+            tip_poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// JSON-RPC client spread across several nodes
+pub struct NodeClient {
+    rpc: Arc<MultiRpcClient>,
+    admin_token: Option<String>,
+    tip_poll_interval: Duration,
+    tips: broadcast::Sender<ChainTip>,
+}
+
+impl NodeClient {
+    pub fn new(config: NodeClientConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            rpc: Arc::new(MultiRpcClient::new(config.endpoints, config.rpc)?),
+            admin_token: config.admin_token,
+            tip_poll_interval: config.tip_poll_interval,
+            tips: broadcast::channel(64).0,
+        })
+    }
+
+    /// Endpoint reads currently go to
+    pub fn current_endpoint(&self) -> String {
+        self.rpc.preferred_endpoint()
+    }
+
+    /// Health of every configured node
+    pub fn node_health(&self) -> Vec<NodeHealth> {
+        self.rpc.health()
+    }
+
+    /// Check every node now, then keep checking in the background
+    pub async fn start_health_checks(&self) {
+        self.rpc.check_health().await;
+        tokio::spawn(self.rpc.clone().run_health_checks());
+    }
+
+    /// Read call routed to the healthiest node
+    pub async fn call(&self, method: &str, params: Value) -> anyhow::Result<Value> {
+        self.rpc.call(method, params).await
+    }
+
+    /// State-changing call, failed over until a node accepts or rejects it
+    pub async fn broadcast(&self, method: &str, params: Value) -> anyhow::Result<Value> {
+        self.rpc.broadcast(method, params).await
+    }
+
+    /// Chain, mempool and peer summary (`blockchain_getStatus`)
+    pub async fn status(&self) -> anyhow::Result<Value> {
+        self.call("blockchain_getStatus", json!([])).await
+    }
+
+    /// Confirmed balance of an address in satoshis
+    pub async fn balance(&self, address: &str) -> anyhow::Result<u64> {
+        let result = self.call("wallet_getBalance", json!([address])).await?;
+        if let Some(error) = result.get("error").and_then(|e| e.as_str()) {
+            anyhow::bail!("{}", error);
+        }
+        let balance: Sats = serde_json::from_value(result.clone())
+            .map_err(|_| anyhow::anyhow!("Unexpected balance response: {}", result))?;
+        Ok(balance.0)
+    }
+
+    /// Active record for a `.edu` name, if it resolves
+    pub async fn resolve_name(&self, name: &str) -> anyhow::Result<Option<NameRecord>> {
+        let result = self.call("names_resolve", json!([name])).await?;
+        if result.get("error").is_some() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_value(result)?))
+    }
+
+    /// Active names pointing at an address
+    pub async fn names_for(&self, address: &str) -> anyhow::Result<Vec<NameRecord>> {
+        let result = self.call("names_list", json!([address])).await?;
+        Ok(serde_json::from_value(result["names"].clone())?)
+    }
+
+    /// Admin RPC with the configured token added to the parameters
+    pub async fn admin_call(&self, method: &str, mut params: serde_json::Map<String, Value>) -> anyhow::Result<Value> {
+        let token = self.admin_token.as_deref()
+            .ok_or_else(|| anyhow::anyhow!("NODE_RPC_ADMIN_TOKEN is not set"))?;
+        params.insert("token".to_string(), json!(token));
+        params.insert("actor".to_string(), json!("edunet-web"));
+        self.broadcast(method, Value::Object(params)).await
+    }
+
+    /// Receive a `ChainTip` whenever the node's best block changes
+    pub fn subscribe_tip(&self) -> broadcast::Receiver<ChainTip> {
+        self.tips.subscribe()
+    }
+
+    /// Poll the node for tip changes and publish them until the process exits
+    pub async fn run_tip_watcher(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.tip_poll_interval);
+        let mut last_hash = None;
+        loop {
+            interval.tick().await;
+            let status = match self.status().await {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!("Failed to poll chain tip: {}", e);
+                    continue;
+                }
+            };
+            let height = status["block_height"].as_u64().unwrap_or(0);
+            let Some(hash) = status["best_block_hash"].as_str()
+                .and_then(|h| hex::decode(h).ok())
+                .and_then(|h| Hash256::try_from(h.as_slice()).ok())
+            else {
+                continue;
+            };
+            if last_hash.replace(hash) != Some(hash) {
+                // No subscribers is fine
+                let _ = self.tips.send(ChainTip { height, hash });
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Email and in-app notifications for web events
+//!
+//! The notification service listens on the backend event bus, turns
+//! confirmed transactions into user-facing events (payment received, loan
+//! funded, escrow released, NFT transfer), tells senders when a payment
+//! expired unconfirmed, and delivers them according to
+//! each user's stored preferences: an in-app inbox row, an email over SMTP,
+//! both or neither.
+
+use crate::blockchain_integration::{tags, ChainEvent};
+use crate::database::{Database, DbNotification, DbNotificationPreference};
+use crate::user_auth::UserManager;
+use blockchain_core::amount::Amount;
+use chrono::Utc;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+/// Kind of event a user can be notified about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    PaymentReceived,
+    LoanFunded,
+    EscrowReleased,
+    NftTransfer,
+    /// Sent to admins, e.g. when the custody hot wallet runs low
+    CustodyAlert,
+    /// A payment the user sent expired from the mempool unconfirmed
+    PaymentExpired,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 6] = [
+        NotificationKind::PaymentReceived,
+        NotificationKind::LoanFunded,
+        NotificationKind::EscrowReleased,
+        NotificationKind::NftTransfer,
+        NotificationKind::CustodyAlert,
+        NotificationKind::PaymentExpired,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::PaymentReceived => "payment_received",
+            NotificationKind::LoanFunded => "loan_funded",
+            NotificationKind::EscrowReleased => "escrow_released",
+            NotificationKind::NftTransfer => "nft_transfer",
+            NotificationKind::CustodyAlert => "custody_alert",
+            NotificationKind::PaymentExpired => "payment_expired",
+        }
+    }
+}
+
+/// A notification addressed to one wallet
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub wallet_address: String,
+    pub title: String,
+    pub body: String,
+    pub reference_id: Option<String>,
+    pub tx_hash: Option<String>,
+}
+
+/// Delivery channels enabled for one kind of notification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationPreference {
+    pub kind: NotificationKind,
+    pub in_app: bool,
+    pub email: bool,
+}
+
+/// SMTP settings, read from `SMTP_*` environment variables
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    /// `starttls` (default), `tls` or `none`
+    pub security: String,
+}
+
+impl SmtpConfig {
+    /// Load settings; returns None when `SMTP_HOST` is unset (email disabled)
+    pub fn from_env() -> Option<Self> {
+        let host = std::env::var("SMTP_HOST").ok()?;
+        Some(Self {
+            port: std::env::var("SMTP_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(587),
+            username: std::env::var("SMTP_USERNAME").ok(),
+            password: std::env::var("SMTP_PASSWORD").ok(),
+            from: std::env::var("SMTP_FROM").unwrap_or_else(|_| format!("EduNet <noreply@{}>", host)),
+            security: std::env::var("SMTP_SECURITY").unwrap_or_else(|_| "starttls".to_string()),
+            host,
+        })
+    }
+}
+
+/// Outgoing email over SMTP
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(config: &SmtpConfig) -> anyhow::Result<Self> {
+        let mut builder = match config.security.as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+        }
+        .port(config.port);
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse()?,
+        })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body.to_string())?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Turns bus events into notifications and delivers them
+pub struct NotificationService {
+    database: Arc<Database>,
+    users: Arc<UserManager>,
+    mailer: Option<Mailer>,
+}
+
+impl NotificationService {
+    pub fn new(database: Arc<Database>, users: Arc<UserManager>, mailer: Option<Mailer>) -> Self {
+        Self { database, users, mailer }
+    }
+
+    /// Consume the event bus until it closes
+    pub async fn run(self: Arc<Self>, mut events: broadcast::Receiver<ChainEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    for notification in notifications_for(&event) {
+                        if let Err(e) = self.deliver(&notification).await {
+                            error!("❌ Failed to deliver {} notification: {}", notification.kind.as_str(), e);
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("⚠️ Notification service skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Deliver one notification on the channels its recipient has enabled
+    pub async fn deliver(&self, notification: &Notification) -> anyhow::Result<()> {
+        // Only registered users get notified (escrow and external addresses don't)
+        let Some(user) = self.users.get_user_by_wallet(&notification.wallet_address).await else {
+            return Ok(());
+        };
+        let preference = self.preference(&notification.wallet_address, notification.kind).await?;
+
+        if preference.in_app {
+            self.database.create_notification(&DbNotification {
+                id: None,
+                wallet_address: notification.wallet_address.clone(),
+                kind: notification.kind.as_str().to_string(),
+                title: notification.title.clone(),
+                body: notification.body.clone(),
+                reference_id: notification.reference_id.clone(),
+                tx_hash: notification.tx_hash.clone(),
+                is_read: false,
+                created_at: Utc::now().timestamp(),
+            }).await?;
+        }
+
+        if preference.email && !user.email.is_empty() {
+            if let Some(mailer) = &self.mailer {
+                mailer.send(&user.email, &notification.title, &notification.body).await?;
+                info!("📧 Emailed {} notification to {}", notification.kind.as_str(), user.username);
+            }
+        }
+        Ok(())
+    }
+
+    /// Preferences for every kind, with defaults filled in
+    pub async fn preferences(&self, wallet_address: &str) -> anyhow::Result<Vec<NotificationPreference>> {
+        let stored = self.database.get_notification_preferences(wallet_address).await?;
+        Ok(NotificationKind::ALL.into_iter().map(|kind| {
+            stored.iter()
+                .find(|p| p.kind == kind.as_str())
+                .map(|p| NotificationPreference { kind, in_app: p.in_app, email: p.email })
+                .unwrap_or(NotificationPreference { kind, in_app: true, email: true })
+        }).collect())
+    }
+
+    pub async fn set_preference(&self, wallet_address: &str, preference: &NotificationPreference) -> anyhow::Result<()> {
+        self.database.set_notification_preference(&DbNotificationPreference {
+            wallet_address: wallet_address.to_string(),
+            kind: preference.kind.as_str().to_string(),
+            in_app: preference.in_app,
+            email: preference.email,
+        }).await
+    }
+
+    async fn preference(&self, wallet_address: &str, kind: NotificationKind) -> anyhow::Result<NotificationPreference> {
+        Ok(self.preferences(wallet_address).await?
+            .into_iter()
+            .find(|p| p.kind == kind)
+            .unwrap_or(NotificationPreference { kind, in_app: true, email: true }))
+    }
+}
+
+/// Map a bus event to the notifications it should produce
+pub fn notifications_for(event: &ChainEvent) -> Vec<Notification> {
+    if let ChainEvent::TransactionExpired { hash, from_address, to_address, amount, replacement } = event {
+        let edu = Amount::from_sat(*amount).to_edu_f64();
+        let body = match replacement {
+            Some(new_hash) => format!("Your payment of {} EDU to {} was not confirmed in time and was sent again as {}.", edu, to_address, new_hash),
+            None => format!("Your payment of {} EDU to {} was not confirmed in time. The funds are available to send again.", edu, to_address),
+        };
+        return vec![Notification {
+            kind: NotificationKind::PaymentExpired,
+            wallet_address: from_address.clone(),
+            title: "Payment expired".to_string(),
+            body,
+            reference_id: replacement.clone(),
+            tx_hash: Some(hash.clone()),
+        }];
+    }
+    let ChainEvent::TransactionConfirmed { hash, from_address, to_address, amount, tags: tx_tags, .. } = event else {
+        return Vec::new();
+    };
+    if from_address == to_address {
+        return Vec::new();
+    }
+
+    let edu = Amount::from_sat(*amount).to_edu_f64();
+    let tag = |key: &str| tx_tags.get(key).cloned();
+    let kind_tag = tx_tags.get(tags::KIND).map(String::as_str).unwrap_or("");
+    let reference = match kind_tag {
+        tags::NFT_TRANSFER => tag(tags::NFT_ID),
+        tags::LOAN_FUNDING => tag(tags::LOAN_ID),
+        tags::ORDER_RELEASE => tag(tags::ORDER_ID),
+        _ => None,
+    };
+
+    let (kind, title, body) = match kind_tag {
+        tags::NFT_TRANSFER => (
+            NotificationKind::NftTransfer,
+            "You received an NFT".to_string(),
+            format!("NFT {} was transferred to you by {}.", reference.as_deref().unwrap_or(""), from_address),
+        ),
+        tags::LOAN_FUNDING => (
+            NotificationKind::LoanFunded,
+            "Your loan was funded".to_string(),
+            format!("Loan {} received {} EDU of funding.", reference.as_deref().unwrap_or(""), edu),
+        ),
+        tags::ORDER_RELEASE => (
+            NotificationKind::EscrowReleased,
+            "Escrow released".to_string(),
+            format!("{} EDU for order {} was released to you.", edu, reference.as_deref().unwrap_or("")),
+        ),
+        _ => (
+            NotificationKind::PaymentReceived,
+            "Payment received".to_string(),
+            format!("You received {} EDU from {}.", edu, from_address),
+        ),
+    };
+
+    vec![Notification {
+        kind,
+        wallet_address: to_address.clone(),
+        title,
+        body,
+        reference_id: reference,
+        tx_hash: Some(hash.clone()),
+    }]
+}
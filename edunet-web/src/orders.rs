@@ -0,0 +1,413 @@
+//! Marketplace order lifecycle
+//!
+//! Orders move through `created → paid → shipped → completed`, with
+//! `disputed`, `refunded` and `cancelled` as side exits. The buyer's payment
+//! goes to the marketplace escrow address and is only released to the seller
+//! once the buyer confirms receipt, or returned to the buyer on refund.
+
+use crate::blockchain_integration::{tags, BlockchainBackend, TransactionStatus};
+use crate::database::{Database, DbOrder, DbOrderEvent, OrderUpdate};
+use crate::user_auth::{User, UserManager};
+use crate::spending::SpendingGuard;
+use crate::MarketplaceManager;
+use blockchain_core::amount::Amount;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Escrow address used when `MARKETPLACE_ESCROW_ADDRESS` is not set
+pub const DEFAULT_ESCROW_ADDRESS: &str = "edunet_marketplace_escrow";
+
+/// Order state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// Placed, payment not yet confirmed
+    Created,
+    /// Payment confirmed and held in escrow
+    Paid,
+    /// Seller marked the item as shipped
+    Shipped,
+    /// Buyer confirmed receipt, escrow released to seller
+    Completed,
+    /// Buyer or seller raised a problem, escrow frozen
+    Disputed,
+    /// Escrow returned to buyer
+    Refunded,
+    /// Abandoned before payment
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Created => "created",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Completed => "completed",
+            OrderStatus::Disputed => "disputed",
+            OrderStatus::Refunded => "refunded",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<Self, String> {
+        match status {
+            "created" => Ok(OrderStatus::Created),
+            "paid" => Ok(OrderStatus::Paid),
+            "shipped" => Ok(OrderStatus::Shipped),
+            "completed" => Ok(OrderStatus::Completed),
+            "disputed" => Ok(OrderStatus::Disputed),
+            "refunded" => Ok(OrderStatus::Refunded),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            other => Err(format!("Unknown order status: {}", other)),
+        }
+    }
+
+    /// Allowed edges of the order state machine
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Created, Paid | Cancelled | Refunded)
+                | (Paid, Shipped | Disputed | Refunded)
+                | (Shipped, Completed | Disputed | Refunded)
+                | (Disputed, Completed | Refunded)
+        )
+    }
+}
+
+/// Order with its event history, as returned by the API
+#[derive(Debug, Serialize)]
+pub struct OrderDetails {
+    #[serde(flatten)]
+    pub order: DbOrder,
+    pub events: Vec<DbOrderEvent>,
+}
+
+/// Which side of an order a user is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Party {
+    Buyer,
+    Seller,
+}
+
+/// Direction of an escrow payout
+#[derive(Debug, Clone, Copy)]
+enum Settlement {
+    Release,
+    Refund,
+}
+
+/// Drives orders through their lifecycle and moves escrowed funds
+pub struct OrderManager {
+    database: Arc<Database>,
+    backend: Arc<BlockchainBackend>,
+    marketplace: Arc<MarketplaceManager>,
+    users: Arc<UserManager>,
+    spending: Arc<SpendingGuard>,
+    escrow_address: String,
+}
+
+impl OrderManager {
+    pub fn new(
+        database: Arc<Database>,
+        backend: Arc<BlockchainBackend>,
+        marketplace: Arc<MarketplaceManager>,
+        users: Arc<UserManager>,
+        spending: Arc<SpendingGuard>,
+        escrow_address: String,
+    ) -> Self {
+        Self {
+            database,
+            backend,
+            marketplace,
+            users,
+            spending,
+            escrow_address,
+        }
+    }
+
+    /// Place an order for an active item at its current price
+    pub async fn create_order(&self, buyer: &User, item_id: Uuid) -> Result<DbOrder, String> {
+        let item = self.marketplace.get_item(item_id).await?
+            .ok_or("Item not found")?;
+        if item.status != "active" {
+            return Err("Item is not available".to_string());
+        }
+        if item.seller_id == buyer.id {
+            return Err("You cannot buy your own item".to_string());
+        }
+        if item.currency != "EDU" {
+            return Err(format!("Cannot escrow payments in {}", item.currency));
+        }
+        let amount = Amount::from_edu_f64(item.price)
+            .map_err(|e| format!("Item price is not a valid amount: {}", e))?;
+        let amount = i64::try_from(amount.to_sat()).map_err(|_| "Item price is too large".to_string())?;
+
+        let seller = self.users.get_user_by_id(&item.seller_id).await
+            .ok_or("Seller account not found")?;
+
+        let now = Utc::now().timestamp();
+        let order = DbOrder {
+            order_id: format!("order_{}", Uuid::new_v4().simple()),
+            item_id: item.id.to_string(),
+            buyer_address: buyer.wallet_address.clone(),
+            seller_address: seller.wallet_address.clone(),
+            amount,
+            status: OrderStatus::Created.as_str().to_string(),
+            payment_tx_hash: None,
+            release_tx_hash: None,
+            refund_tx_hash: None,
+            tracking_info: None,
+            dispute_reason: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.database.create_order(&order).await.map_err(|e| e.to_string())?;
+
+        info!("🛒 Order {} created for item {}", order.order_id, order.item_id);
+        Ok(order)
+    }
+
+    /// Get an order with its history; only its buyer and seller may see it
+    pub async fn get_order(&self, user: &User, order_id: &str) -> Result<OrderDetails, String> {
+        let order = self.load(order_id).await?;
+        party_of(&order, user)?;
+        let events = self.database.get_order_events(order_id).await.map_err(|e| e.to_string())?;
+        Ok(OrderDetails { order, events })
+    }
+
+    /// Orders the user bought or sold
+    pub async fn list_orders(&self, user: &User) -> Result<Vec<DbOrder>, String> {
+        self.database.list_orders_for_user(&user.wallet_address, 100).await.map_err(|e| e.to_string())
+    }
+
+    /// Buyer pays the order amount into escrow (subject to spending limits)
+    pub async fn pay_order(&self, buyer: &User, order_id: &str, totp_code: Option<&str>) -> Result<DbOrder, String> {
+        let order = self.load(order_id).await?;
+        require_party(&order, buyer, Party::Buyer)?;
+        if OrderStatus::parse(&order.status)? != OrderStatus::Created || order.payment_tx_hash.is_some() {
+            return Err("Order has already been paid".to_string());
+        }
+
+        let balances = self.backend.get_wallet_balances(&buyer.wallet_address).await.map_err(|e| e.to_string())?;
+        if balances.spendable() < order.amount as u64 {
+            return Err("Insufficient balance".to_string());
+        }
+
+        let reservation = self.spending.reserve(buyer, order.amount as u64, totp_code).await?;
+        let tx_hash = match self.backend.send_tagged_transaction(
+            &buyer.wallet_address,
+            &self.escrow_address,
+            order.amount as u64,
+            None,
+            tags::for_kind(tags::ORDER_PAY, Some((tags::ORDER_ID, &order.order_id))),
+        ).await {
+            Ok(tx_hash) => {
+                self.spending.commit(reservation, &tx_hash).await;
+                tx_hash
+            }
+            Err(e) => {
+                self.spending.release(reservation).await;
+                return Err(e.to_string());
+            }
+        };
+
+        if !self.database.set_order_payment(order_id, &tx_hash, Utc::now().timestamp()).await.map_err(|e| e.to_string())? {
+            // Lost a race with a concurrent payment; hand these funds straight back
+            warn!("⚠️ Duplicate payment {} for order {}, refunding", tx_hash, order_id);
+            self.send_from_escrow(&order.buyer_address, order.amount, tags::ORDER_DUPLICATE_REFUND, order_id).await?;
+            return Err("Order has already been paid".to_string());
+        }
+
+        info!("💳 Payment {} sent to escrow for order {}", tx_hash, order_id);
+        self.confirm_payment(order_id).await?;
+        self.load(order_id).await
+    }
+
+    /// Seller marks a paid order as shipped
+    pub async fn ship_order(&self, seller: &User, order_id: &str, tracking_info: Option<String>) -> Result<DbOrder, String> {
+        let order = self.load(order_id).await?;
+        require_party(&order, seller, Party::Seller)?;
+        let update = OrderUpdate { tracking_info, ..Default::default() };
+        self.transition(&order, OrderStatus::Shipped, Some(seller), None, update).await
+    }
+
+    /// Buyer confirms receipt; escrow is released to the seller
+    pub async fn confirm_receipt(&self, buyer: &User, order_id: &str) -> Result<DbOrder, String> {
+        let order = self.load(order_id).await?;
+        require_party(&order, buyer, Party::Buyer)?;
+
+        // Claim the state first so a concurrent refund cannot also pay out
+        let completed = self.transition(&order, OrderStatus::Completed, Some(buyer), None, OrderUpdate::default()).await?;
+        self.settle(completed, Settlement::Release).await
+    }
+
+    /// Either party freezes the escrow pending resolution
+    pub async fn open_dispute(&self, user: &User, order_id: &str, reason: String) -> Result<DbOrder, String> {
+        let order = self.load(order_id).await?;
+        party_of(&order, user)?;
+        if reason.trim().is_empty() {
+            return Err("A dispute reason is required".to_string());
+        }
+        let update = OrderUpdate { dispute_reason: Some(reason), ..Default::default() };
+        self.transition(&order, OrderStatus::Disputed, Some(user), None, update).await
+    }
+
+    /// Seller refunds the buyer; the item is listed again
+    pub async fn refund_order(&self, seller: &User, order_id: &str) -> Result<DbOrder, String> {
+        let order = self.load(order_id).await?;
+        require_party(&order, seller, Party::Seller)?;
+        if OrderStatus::parse(&order.status)? == OrderStatus::Created {
+            return Err("Unpaid orders can be cancelled instead".to_string());
+        }
+        self.refund(&order, Some(seller), "Refunded by seller").await
+    }
+
+    /// Either party cancels an order before payment
+    pub async fn cancel_order(&self, user: &User, order_id: &str) -> Result<DbOrder, String> {
+        let order = self.load(order_id).await?;
+        party_of(&order, user)?;
+        if order.payment_tx_hash.is_some() {
+            return Err("Order payment is in progress and can no longer be cancelled".to_string());
+        }
+        self.transition(&order, OrderStatus::Cancelled, Some(user), None, OrderUpdate::default()).await
+    }
+
+    /// Promote every order whose escrow payment has confirmed
+    pub async fn sync_payments(&self) -> Result<usize, String> {
+        let pending = self.database.list_orders_awaiting_payment().await.map_err(|e| e.to_string())?;
+        let mut confirmed = 0;
+        for order in pending {
+            match self.confirm_payment(&order.order_id).await {
+                Ok(true) => confirmed += 1,
+                Ok(false) => {}
+                Err(e) => warn!("⚠️ Failed to confirm payment for order {}: {}", order.order_id, e),
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// Mark an order paid once its payment confirms, and take the item off the market.
+    /// If another order already bought the item, the payment is refunded.
+    async fn confirm_payment(&self, order_id: &str) -> Result<bool, String> {
+        let order = self.load(order_id).await?;
+        let Some(tx_hash) = &order.payment_tx_hash else { return Ok(false) };
+        if OrderStatus::parse(&order.status)? != OrderStatus::Created {
+            return Ok(false);
+        }
+        if !matches!(self.backend.get_transaction_status(tx_hash).await, Some(TransactionStatus::Confirmed)) {
+            return Ok(false);
+        }
+
+        let item_id = Uuid::parse_str(&order.item_id).map_err(|e| e.to_string())?;
+        if !self.marketplace.set_item_status(item_id, "active", "sold").await? {
+            self.refund(&order, None, "Item was sold to another buyer").await?;
+            return Ok(false);
+        }
+
+        self.transition(&order, OrderStatus::Paid, None, Some("Payment confirmed"), OrderUpdate::default()).await?;
+        info!("✅ Order {} paid, item {} sold", order_id, order.item_id);
+        Ok(true)
+    }
+
+    async fn refund(&self, order: &DbOrder, actor: Option<&User>, note: &str) -> Result<DbOrder, String> {
+        let refunded = self.transition(order, OrderStatus::Refunded, actor, Some(note), OrderUpdate::default()).await?;
+        let refunded = self.settle(refunded, Settlement::Refund).await?;
+
+        // Relist the item if this order had bought it
+        if order.status != OrderStatus::Created.as_str() {
+            let item_id = Uuid::parse_str(&order.item_id).map_err(|e| e.to_string())?;
+            self.marketplace.set_item_status(item_id, "sold", "active").await?;
+        }
+        Ok(refunded)
+    }
+
+    async fn transition(
+        &self,
+        order: &DbOrder,
+        next: OrderStatus,
+        actor: Option<&User>,
+        note: Option<&str>,
+        update: OrderUpdate,
+    ) -> Result<DbOrder, String> {
+        let current = OrderStatus::parse(&order.status)?;
+        if !current.can_transition_to(next) {
+            return Err(format!("Cannot move order from {} to {}", current.as_str(), next.as_str()));
+        }
+
+        let actor_address = actor.map(|user| user.wallet_address.as_str());
+        let applied = self.database.transition_order(
+            &order.order_id,
+            &order.status,
+            next.as_str(),
+            actor_address,
+            note,
+            &update,
+            Utc::now().timestamp(),
+        ).await.map_err(|e| e.to_string())?;
+        if !applied {
+            return Err("Order was modified concurrently, please retry".to_string());
+        }
+
+        info!("📦 Order {}: {} → {}", order.order_id, order.status, next.as_str());
+        self.load(&order.order_id).await
+    }
+
+    /// Pay out escrow for an order that has already reached its final state
+    async fn settle(&self, order: DbOrder, settlement: Settlement) -> Result<DbOrder, String> {
+        let (to, kind) = match settlement {
+            Settlement::Release => (&order.seller_address, tags::ORDER_RELEASE),
+            Settlement::Refund => (&order.buyer_address, tags::ORDER_REFUND),
+        };
+        let tx_hash = self.send_from_escrow(to, order.amount, kind, &order.order_id).await
+            .map_err(|e| format!("Order {} is {} but escrow payout failed: {}", order.order_id, order.status, e))?;
+
+        let (release, refund) = match settlement {
+            Settlement::Release => (Some(tx_hash.as_str()), None),
+            Settlement::Refund => (None, Some(tx_hash.as_str())),
+        };
+        self.database.set_order_settlement(&order.order_id, release, refund).await.map_err(|e| e.to_string())?;
+        self.load(&order.order_id).await
+    }
+
+    async fn send_from_escrow(&self, to: &str, amount: i64, kind: &str, order_id: &str) -> Result<String, String> {
+        self.backend.send_tagged_transaction(
+            &self.escrow_address,
+            to,
+            amount as u64,
+            None,
+            tags::for_kind(kind, Some((tags::ORDER_ID, order_id))),
+        ).await.map_err(|e| e.to_string())
+    }
+
+    async fn load(&self, order_id: &str) -> Result<DbOrder, String> {
+        self.database.get_order(order_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Order not found".to_string())
+    }
+}
+
+fn party_of(order: &DbOrder, user: &User) -> Result<Party, String> {
+    // Wallet addresses are stable across restarts, unlike session user IDs
+    if order.buyer_address == user.wallet_address {
+        Ok(Party::Buyer)
+    } else if order.seller_address == user.wallet_address {
+        Ok(Party::Seller)
+    } else {
+        Err("Order not found".to_string())
+    }
+}
+
+fn require_party(order: &DbOrder, user: &User, party: Party) -> Result<(), String> {
+    if party_of(order, user)? != party {
+        return Err(match party {
+            Party::Buyer => "Only the buyer can do this".to_string(),
+            Party::Seller => "Only the seller can do this".to_string(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Reorg tracking for the web transaction history
+//!
+//! Follows best-chain tip changes and works out which blocks left the best
+//! chain. Each disconnected block is published on the backend event bus as
+//! `ChainEvent::BlockDisconnected`, and the transactions it confirmed go
+//! back to pending with their `reorged` flag set. A reorged transaction
+//! that is still unconfirmed `CONFLICT_DEPTH` blocks later is taken to have
+//! lost its inputs to a competing transaction and marked conflicted.
+//!
+//! With a remote node only tip changes are seen, so a reorg is noticed when
+//! the tip moves to a height at or below one already recorded, or when the
+//! embedded node's block at a recorded height has a different hash.
+
+use blockchain_core::consensus::ChainTip;
+use blockchain_core::Hash256;
+use std::collections::BTreeMap;
+
+/// Blocks of tip history kept for comparison
+pub const TRACKED_DEPTH: u64 = 100;
+
+/// Blocks a reorged transaction may stay unconfirmed before it is conflicted
+pub const CONFLICT_DEPTH: u64 = 6;
+
+/// Recent best-chain block hashes by height
+#[derive(Debug, Default)]
+pub struct TipTracker {
+    hashes: BTreeMap<u64, Hash256>,
+}
+
+impl TipTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash recorded for `height`, if it is still tracked
+    pub fn hash_at(&self, height: u64) -> Option<Hash256> {
+        self.hashes.get(&height).copied()
+    }
+
+    /// Record a new tip. Returns the blocks at or above its height that it
+    /// replaces, highest first; lower blocks are checked with `forget`.
+    pub fn connect(&mut self, tip: ChainTip) -> Vec<(u64, Hash256)> {
+        let stale: Vec<(u64, Hash256)> = self.hashes.split_off(&tip.height)
+            .into_iter()
+            .rev()
+            .filter(|&(height, hash)| (height, hash) != (tip.height, tip.hash))
+            .collect();
+        self.hashes.insert(tip.height, tip.hash);
+
+        let floor = tip.height.saturating_sub(TRACKED_DEPTH);
+        self.hashes = self.hashes.split_off(&floor);
+        stale
+    }
+
+    /// Drop the block recorded at `height` once it is known to have left
+    /// the best chain
+    pub fn forget(&mut self, height: u64) -> Option<Hash256> {
+        self.hashes.remove(&height)
+    }
+}
@@ -0,0 +1,232 @@
+//! Loan creditworthiness scoring
+//!
+//! A `ScoreModel` turns what is known about an applicant into a
+//! Proof-of-Potential score on a 0-10 scale, along with the factors that
+//! produced it so applicants and lenders can see why a loan scored the way
+//! it did. Inputs are gathered by `LoanScorer`: enrollment data only from
+//! the applicant's attestation, and repayment history from confirmed
+//! installments and payments back to the funders of earlier loans. The default
+//! `WeightedModel` is a weighted sum whose coefficients come from the
+//! environment.
+
+use crate::attestations::AttestationRegistry;
+use crate::database::Database;
+use blockchain_core::attestation::EnrollmentClaim;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Highest score a model may give
+pub const MAX_SCORE: f64 = 10.0;
+
+/// Applicant's record of paying back earlier loans
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RepaymentHistory {
+    /// Earlier loans that were funded
+    pub loans: i64,
+    /// Total funded across those loans, in satoshis
+    pub borrowed: i64,
+    /// Confirmed repayments of those loans, in satoshis
+    pub repaid: i64,
+}
+
+impl RepaymentHistory {
+    /// Share of borrowed funds paid back, if the applicant has borrowed before
+    pub fn repaid_ratio(&self) -> Option<f64> {
+        (self.borrowed > 0).then(|| (self.repaid as f64 / self.borrowed as f64).min(1.0))
+    }
+}
+
+/// Everything a model may look at
+#[derive(Debug, Clone)]
+pub struct ScoreInputs {
+    /// Attested enrollment; self-reported data is never scored
+    pub claim: Option<EnrollmentClaim>,
+    /// Standardized test score out of 1600
+    pub test_score: Option<i32>,
+    pub repayment: RepaymentHistory,
+}
+
+/// One input's contribution to a score
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreFactor {
+    pub name: &'static str,
+    pub points: f64,
+    pub max_points: f64,
+    pub detail: String,
+}
+
+/// A score and how it was reached
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreExplanation {
+    pub model: &'static str,
+    pub score: f64,
+    pub max_score: f64,
+    pub factors: Vec<ScoreFactor>,
+}
+
+/// A way of scoring loan applicants
+pub trait ScoreModel: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Score an applicant; the result is clamped to `0..=MAX_SCORE`
+    fn score(&self, inputs: &ScoreInputs) -> ScoreExplanation;
+}
+
+/// Coefficients of the weighted model, in points
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreWeights {
+    /// Points every applicant starts with
+    pub base: f64,
+    /// Points for a 4.0 attested GPA
+    pub gpa: f64,
+    /// Points for a perfect test score
+    pub test_score: f64,
+    /// Points for having repaid everything borrowed before
+    pub repayment: f64,
+    /// Points taken away for borrowing before and repaying nothing
+    pub unpaid_penalty: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            base: 4.0,
+            gpa: 2.5,
+            test_score: 2.0,
+            repayment: 1.5,
+            unpaid_penalty: 2.0,
+        }
+    }
+}
+
+impl ScoreWeights {
+    /// Defaults, overridden by `LOAN_SCORE_<COEFFICIENT>` (e.g. `LOAN_SCORE_GPA`)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let weight = |name: &str, default: f64| {
+            std::env::var(format!("LOAN_SCORE_{}", name))
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+        Self {
+            base: weight("BASE", defaults.base),
+            gpa: weight("GPA", defaults.gpa),
+            test_score: weight("TEST_SCORE", defaults.test_score),
+            repayment: weight("REPAYMENT", defaults.repayment),
+            unpaid_penalty: weight("UNPAID_PENALTY", defaults.unpaid_penalty),
+        }
+    }
+}
+
+/// Weighted sum of attested GPA, test score and repayment history
+#[derive(Debug, Clone, Default)]
+pub struct WeightedModel {
+    pub weights: ScoreWeights,
+}
+
+impl WeightedModel {
+    pub fn new(weights: ScoreWeights) -> Self {
+        Self { weights }
+    }
+}
+
+impl ScoreModel for WeightedModel {
+    fn name(&self) -> &'static str {
+        "weighted"
+    }
+
+    fn score(&self, inputs: &ScoreInputs) -> ScoreExplanation {
+        let w = &self.weights;
+        let mut factors = vec![ScoreFactor {
+            name: "base",
+            points: w.base,
+            max_points: w.base,
+            detail: "Starting score for every applicant".to_string(),
+        }];
+
+        let gpa = inputs.claim.as_ref().and_then(|claim| claim.gpa);
+        factors.push(ScoreFactor {
+            name: "gpa",
+            points: gpa.map(|gpa| (gpa / 4.0).clamp(0.0, 1.0) * w.gpa).unwrap_or(0.0),
+            max_points: w.gpa,
+            detail: match (&inputs.claim, gpa) {
+                (Some(claim), Some(gpa)) => format!("Attested GPA {:.2} from {}", gpa, claim.university),
+                (Some(claim), None) => format!("{} did not attest a GPA", claim.university),
+                (None, _) => "No enrollment attestation; self-reported GPA is not scored".to_string(),
+            },
+        });
+
+        factors.push(ScoreFactor {
+            name: "test_score",
+            points: inputs.test_score
+                .map(|score| (score as f64 / 1600.0).clamp(0.0, 1.0) * w.test_score)
+                .unwrap_or(0.0),
+            max_points: w.test_score,
+            detail: match inputs.test_score {
+                Some(score) => format!("Test score {} of 1600", score),
+                None => "No test score given".to_string(),
+            },
+        });
+
+        let history = &inputs.repayment;
+        factors.push(match history.repaid_ratio() {
+            Some(ratio) => ScoreFactor {
+                name: "repayment",
+                points: ratio * w.repayment - (1.0 - ratio) * w.unpaid_penalty,
+                max_points: w.repayment,
+                detail: format!(
+                    "Repaid {:.0}% of {:.8} EDU across {} earlier loan(s)",
+                    ratio * 100.0,
+                    history.borrowed as f64 / 100_000_000.0,
+                    history.loans,
+                ),
+            },
+            None => ScoreFactor {
+                name: "repayment",
+                points: 0.0,
+                max_points: w.repayment,
+                detail: "No earlier loans".to_string(),
+            },
+        });
+
+        let total: f64 = factors.iter().map(|factor| factor.points).sum();
+        ScoreExplanation {
+            model: self.name(),
+            score: total.clamp(0.0, MAX_SCORE),
+            max_score: MAX_SCORE,
+            factors,
+        }
+    }
+}
+
+/// Gathers scoring inputs and runs the configured model
+pub struct LoanScorer {
+    database: Arc<Database>,
+    attestations: Arc<AttestationRegistry>,
+    model: Box<dyn ScoreModel>,
+}
+
+impl LoanScorer {
+    pub fn new(database: Arc<Database>, attestations: Arc<AttestationRegistry>, model: Box<dyn ScoreModel>) -> Self {
+        Self { database, attestations, model }
+    }
+
+    /// Collect the inputs for an applicant's request
+    pub async fn inputs(&self, applicant_address: &str, test_score: Option<i32>) -> Result<ScoreInputs, String> {
+        let (loans, borrowed, repaid) = self.database.loan_repayment_history(applicant_address).await
+            .map_err(|e| e.to_string())?;
+        Ok(ScoreInputs {
+            claim: self.attestations.current_claim(applicant_address).await,
+            test_score,
+            repayment: RepaymentHistory { loans, borrowed, repaid },
+        })
+    }
+
+    pub fn score(&self, inputs: &ScoreInputs) -> ScoreExplanation {
+        let mut explanation = self.model.score(inputs);
+        explanation.score = explanation.score.clamp(0.0, MAX_SCORE);
+        explanation
+    }
+}
@@ -0,0 +1,471 @@
+//! Invoice signing service
+//!
+//! Invoices are signed through a `Signer`, addressed by key id, so
+//! production deployments can keep the issuer key in a KMS or HSM and out
+//! of this process's memory:
+//! - `local`: keys held in memory, read from a keystore directory
+//! - `aws-kms`: AWS KMS `ECC_SECG_P256K1` keys, addressed by key id, ARN or alias
+//! - `pkcs11`: secp256k1 keys on a PKCS#11 token, addressed by `CKA_LABEL`;
+//!   built with the `pkcs11` feature
+//!
+//! Signatures are DER-encoded ECDSA over a 32-byte digest with a low S
+//! value, and public keys compressed, whatever the backend returns.
+//!
+//! Selected with `SIGNER_BACKEND`; see `SignerConfig::from_env`.
+//!
+//! Invoices are all that goes through it. User wallets are still made by
+//! the in-process `WalletManager` and their keys stored with the user
+//! record, so they are not covered by the KMS or HSM backends.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use blockchain_core::{crypto, Hash256, PrivateKey, PublicKey, Signature};
+use chrono::Utc;
+#[cfg(feature = "pkcs11")]
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::Mechanism,
+    object::{Attribute, AttributeType, ObjectClass},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "pkcs11")]
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Key id invoices are signed with unless `INVOICE_SIGNING_KEY_ID` says otherwise
+pub const INVOICE_ISSUER_KEY: &str = "invoice-issuer";
+
+/// Signs digests with keys it holds or fronts
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Backend name for logs
+    fn backend(&self) -> &'static str;
+
+    /// Compressed public key of `key_id`
+    async fn public_key(&self, key_id: &str) -> Result<PublicKey>;
+
+    /// DER signature over `digest` with `key_id`
+    async fn sign_digest(&self, key_id: &str, digest: &Hash256) -> Result<Signature>;
+}
+
+/// Which backend to sign with. Not `Debug`, as it can hold keys.
+#[derive(Clone)]
+pub enum SignerConfig {
+    Local {
+        /// Directory of `<key id>.key` files holding hex private keys
+        keystore_dir: Option<PathBuf>,
+        /// Keys given directly, by key id
+        keys: HashMap<String, PrivateKey>,
+    },
+    AwsKms(KmsConfig),
+    Pkcs11(Pkcs11Config),
+}
+
+impl SignerConfig {
+    /// Read `SIGNER_BACKEND` (`local`, `aws-kms` or `pkcs11`) and the
+    /// backend's settings:
+    /// - local: `SIGNER_KEYSTORE_DIR`; `INVOICE_SIGNING_KEY` is still
+    ///   honoured as the `invoice-issuer` key
+    /// - aws-kms: `AWS_REGION`, `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+    ///   `AWS_SESSION_TOKEN` and optionally `SIGNER_KMS_ENDPOINT`
+    /// - pkcs11: `SIGNER_PKCS11_MODULE`, `SIGNER_PKCS11_SLOT`, `SIGNER_PKCS11_PIN`
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        match var("SIGNER_BACKEND").as_deref().unwrap_or("local") {
+            "local" => {
+                let mut keys = HashMap::new();
+                if let Some(key) = var("INVOICE_SIGNING_KEY") {
+                    keys.insert(INVOICE_ISSUER_KEY.to_string(), parse_private_key(&key).context("Invalid INVOICE_SIGNING_KEY")?);
+                }
+                Ok(Self::Local { keystore_dir: var("SIGNER_KEYSTORE_DIR").map(PathBuf::from), keys })
+            }
+            "aws-kms" => {
+                let region = var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION"))
+                    .ok_or_else(|| anyhow!("AWS_REGION is required for the aws-kms signer"))?;
+                Ok(Self::AwsKms(KmsConfig {
+                    endpoint: var("SIGNER_KMS_ENDPOINT").unwrap_or_else(|| format!("https://kms.{}.amazonaws.com", region)),
+                    region,
+                    access_key_id: var("AWS_ACCESS_KEY_ID")
+                        .ok_or_else(|| anyhow!("AWS_ACCESS_KEY_ID is required for the aws-kms signer"))?,
+                    secret_access_key: var("AWS_SECRET_ACCESS_KEY")
+                        .ok_or_else(|| anyhow!("AWS_SECRET_ACCESS_KEY is required for the aws-kms signer"))?,
+                    session_token: var("AWS_SESSION_TOKEN"),
+                }))
+            }
+            "pkcs11" => Ok(Self::Pkcs11(Pkcs11Config {
+                module: var("SIGNER_PKCS11_MODULE")
+                    .map(PathBuf::from)
+                    .ok_or_else(|| anyhow!("SIGNER_PKCS11_MODULE is required for the pkcs11 signer"))?,
+                slot_index: var("SIGNER_PKCS11_SLOT").map(|s| s.parse()).transpose()
+                    .context("SIGNER_PKCS11_SLOT must be a slot index")?
+                    .unwrap_or(0),
+                pin: var("SIGNER_PKCS11_PIN"),
+            })),
+            other => bail!("Unknown SIGNER_BACKEND '{}' (expected local, aws-kms or pkcs11)", other),
+        }
+    }
+
+    /// Start the configured backend
+    pub fn build(self) -> Result<Arc<dyn Signer>> {
+        let signer: Arc<dyn Signer> = match self {
+            Self::Local { keystore_dir, keys } => {
+                // Without a keystore there is nowhere keys could persist
+                let keystore = LocalKeystore::new(keystore_dir.is_none());
+                if let Some(dir) = keystore_dir {
+                    keystore.load_dir(&dir)?;
+                }
+                for (key_id, key) in keys {
+                    keystore.insert(&key_id, key);
+                }
+                Arc::new(keystore)
+            }
+            Self::AwsKms(config) => Arc::new(AwsKmsSigner::new(config)?),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(config) => Arc::new(Pkcs11Signer::open(config)?),
+            #[cfg(not(feature = "pkcs11"))]
+            Self::Pkcs11(_) => bail!("This build has no PKCS#11 support; rebuild edunet-web with --features pkcs11"),
+        };
+        info!("🔏 Signing with the {} backend", signer.backend());
+        Ok(signer)
+    }
+}
+
+/// Keys held in process memory
+pub struct LocalKeystore {
+    keys: RwLock<HashMap<String, PrivateKey>>,
+    /// Generate a key on first use of an unknown key id. Signatures from it
+    /// stop verifying as ours after a restart, so this is for development.
+    temporary_keys: bool,
+}
+
+impl LocalKeystore {
+    pub fn new(temporary_keys: bool) -> Self {
+        Self { keys: RwLock::new(HashMap::new()), temporary_keys }
+    }
+
+    /// Load every `<key id>.key` file in `dir`
+    pub fn load_dir(&self, dir: &Path) -> Result<()> {
+        for entry in std::fs::read_dir(dir).with_context(|| format!("Cannot read keystore {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("key") {
+                continue;
+            }
+            let Some(key_id) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+            let key = parse_private_key(&std::fs::read_to_string(&path)?)
+                .with_context(|| format!("Invalid key in {}", path.display()))?;
+            self.insert(key_id, key);
+        }
+        info!("🔑 Loaded {} keys from {}", self.keys.read().unwrap().len(), dir.display());
+        Ok(())
+    }
+
+    pub fn insert(&self, key_id: &str, key: PrivateKey) {
+        self.keys.write().unwrap().insert(key_id.to_string(), key);
+    }
+
+    fn key(&self, key_id: &str) -> Result<PrivateKey> {
+        if let Some(key) = self.keys.read().unwrap().get(key_id) {
+            return Ok(*key);
+        }
+        if !self.temporary_keys {
+            bail!("No key '{}' in the local keystore", key_id);
+        }
+        let mut keys = self.keys.write().unwrap();
+        if let Some(key) = keys.get(key_id) {
+            return Ok(*key);
+        }
+        warn!("⚠️ No '{}' key configured; signing with a temporary key", key_id);
+        let key = crypto::generate_private_key()?;
+        keys.insert(key_id.to_string(), key);
+        Ok(key)
+    }
+}
+
+#[async_trait]
+impl Signer for LocalKeystore {
+    fn backend(&self) -> &'static str {
+        "local"
+    }
+
+    async fn public_key(&self, key_id: &str) -> Result<PublicKey> {
+        Ok(crypto::derive_public_key(&self.key(key_id)?)?)
+    }
+
+    async fn sign_digest(&self, key_id: &str, digest: &Hash256) -> Result<Signature> {
+        Ok(crypto::sign_hash(digest, &self.key(key_id)?)?)
+    }
+}
+
+/// Hex private key, surrounding whitespace ignored
+pub fn parse_private_key(hex_key: &str) -> Result<PrivateKey> {
+    let bytes = hex::decode(hex_key.trim())?;
+    PrivateKey::try_from(bytes.as_slice()).map_err(|_| anyhow!("Private key must be 32 bytes"))
+}
+
+/// AWS KMS endpoint and credentials
+#[derive(Clone)]
+pub struct KmsConfig {
+    pub region: String,
+    pub endpoint: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for KmsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KmsConfig")
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Signs with asymmetric `ECC_SECG_P256K1` keys in AWS KMS. Requests are
+/// signed with SigV4 directly, so no AWS SDK is needed.
+pub struct AwsKmsSigner {
+    config: KmsConfig,
+    host: String,
+    client: reqwest::Client,
+    /// Public keys never change, so each is fetched once
+    public_keys: RwLock<HashMap<String, PublicKey>>,
+}
+
+impl AwsKmsSigner {
+    pub fn new(config: KmsConfig) -> Result<Self> {
+        let host = reqwest::Url::parse(&config.endpoint)?
+            .host_str()
+            .ok_or_else(|| anyhow!("KMS endpoint {} has no host", config.endpoint))?
+            .to_string();
+        Ok(Self {
+            config,
+            host,
+            client: reqwest::Client::builder().timeout(std::time::Duration::from_secs(10)).build()?,
+            public_keys: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Call a KMS action (`TrentService.<action>`)
+    async fn call(&self, action: &str, body: Value) -> Result<Value> {
+        let body = body.to_string();
+        let target = format!("TrentService.{}", action);
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let authorization = sigv4_authorization(&self.config, &self.host, &target, &amz_date, &body);
+
+        let mut request = self.client
+            .post(&self.config.endpoint)
+            .header("content-type", "application/x-amz-json-1.1")
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-target", &target)
+            .header("authorization", authorization);
+        if let Some(token) = &self.config.session_token {
+            request = request.header("x-amz-security-token", token);
+        }
+
+        let response = request.body(body).send().await.context("KMS request failed")?;
+        let status = response.status();
+        let value: Value = response.json().await.context("Invalid KMS response")?;
+        if !status.is_success() {
+            bail!("KMS {} failed ({}): {} {}", action, status,
+                  value["__type"].as_str().unwrap_or(""), value["message"].as_str().unwrap_or(""));
+        }
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl Signer for AwsKmsSigner {
+    fn backend(&self) -> &'static str {
+        "aws-kms"
+    }
+
+    async fn public_key(&self, key_id: &str) -> Result<PublicKey> {
+        if let Some(key) = self.public_keys.read().unwrap().get(key_id) {
+            return Ok(key.clone());
+        }
+        let response = self.call("GetPublicKey", json!({ "KeyId": key_id })).await?;
+        if response["KeySpec"].as_str() != Some("ECC_SECG_P256K1") {
+            bail!("KMS key {} is {}, not ECC_SECG_P256K1", key_id, response["KeySpec"]);
+        }
+        let spki = base64::engine::general_purpose::STANDARD
+            .decode(response["PublicKey"].as_str().unwrap_or(""))?;
+        let key = crypto::compress_public_key(spki_point(&spki)?)?;
+        self.public_keys.write().unwrap().insert(key_id.to_string(), key.clone());
+        Ok(key)
+    }
+
+    async fn sign_digest(&self, key_id: &str, digest: &Hash256) -> Result<Signature> {
+        let response = self.call("Sign", json!({
+            "KeyId": key_id,
+            "Message": base64::engine::general_purpose::STANDARD.encode(digest),
+            "MessageType": "DIGEST",
+            "SigningAlgorithm": "ECDSA_SHA_256",
+        })).await?;
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(response["Signature"].as_str().unwrap_or(""))?;
+        // KMS doesn't enforce low S
+        Ok(crypto::normalize_signature(&der)?)
+    }
+}
+
+/// Uncompressed EC point at the end of a DER SubjectPublicKeyInfo
+fn spki_point(spki: &[u8]) -> Result<&[u8]> {
+    match spki.len().checked_sub(65).map(|start| &spki[start..]) {
+        Some(point) if point[0] == 0x04 => Ok(point),
+        _ => bail!("Unexpected public key encoding"),
+    }
+}
+
+/// SigV4 `Authorization` header for a KMS JSON request
+fn sigv4_authorization(config: &KmsConfig, host: &str, target: &str, amz_date: &str, body: &str) -> String {
+    let date = &amz_date[..8];
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1"),
+        ("host", host),
+        ("x-amz-date", amz_date),
+        ("x-amz-target", target),
+    ];
+    if let Some(token) = &config.session_token {
+        headers.push(("x-amz-security-token", token));
+    }
+    headers.sort();
+
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+    let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "POST\n/\n\n{}\n{}\n{}",
+        canonical_headers, signed_headers, hex::encode(Sha256::digest(body.as_bytes()))
+    );
+
+    let scope = format!("{}/{}/kms/aws4_request", date, config.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = sigv4_signing_key(&config.secret_access_key, date, &config.region, "kms");
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key_id, scope, signed_headers, signature
+    )
+}
+
+fn sigv4_signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// PKCS#11 module and token
+#[derive(Clone)]
+pub struct Pkcs11Config {
+    /// Path of the vendor's PKCS#11 library
+    pub module: PathBuf,
+    /// Index among the slots with a token present
+    pub slot_index: usize,
+    /// User PIN, if the token requires login
+    pub pin: Option<String>,
+}
+
+impl std::fmt::Debug for Pkcs11Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11Config")
+            .field("module", &self.module)
+            .field("slot_index", &self.slot_index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Signs with secp256k1 keys on a PKCS#11 token (HSM, YubiHSM, SoftHSM).
+/// The private key and its public key object share a `CKA_LABEL`.
+#[cfg(feature = "pkcs11")]
+pub struct Pkcs11Signer {
+    /// Keeps the module loaded for the session's lifetime
+    _context: Pkcs11,
+    session: Arc<Mutex<Session>>,
+}
+
+#[cfg(feature = "pkcs11")]
+impl Pkcs11Signer {
+    /// Load the module and open a logged-in session on the token
+    pub fn open(config: Pkcs11Config) -> Result<Self> {
+        let context = Pkcs11::new(&config.module)
+            .with_context(|| format!("Cannot load PKCS#11 module {}", config.module.display()))?;
+        context.initialize(CInitializeArgs::OsThreads)?;
+        let slot = *context.get_slots_with_token()?
+            .get(config.slot_index)
+            .ok_or_else(|| anyhow!("No PKCS#11 token in slot {}", config.slot_index))?;
+        let session = context.open_ro_session(slot)?;
+        if let Some(pin) = config.pin {
+            session.login(UserType::User, Some(&AuthPin::new(pin)))?;
+        }
+        Ok(Self { _context: context, session: Arc::new(Mutex::new(session)) })
+    }
+
+    /// Run a blocking token operation off the async runtime
+    async fn with_session<T: Send + 'static>(&self, op: impl FnOnce(&Session) -> Result<T> + Send + 'static) -> Result<T> {
+        let session = self.session.clone();
+        tokio::task::spawn_blocking(move || op(&session.lock().unwrap())).await?
+    }
+}
+
+#[cfg(feature = "pkcs11")]
+fn find_key(session: &Session, class: ObjectClass, label: &str) -> Result<cryptoki::object::ObjectHandle> {
+    session.find_objects(&[Attribute::Class(class), Attribute::Label(label.as_bytes().to_vec())])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No {} labelled '{}' on the PKCS#11 token", class, label))
+}
+
+#[cfg(feature = "pkcs11")]
+#[async_trait]
+impl Signer for Pkcs11Signer {
+    fn backend(&self) -> &'static str {
+        "pkcs11"
+    }
+
+    async fn public_key(&self, key_id: &str) -> Result<PublicKey> {
+        let label = key_id.to_string();
+        let point = self.with_session(move |session| {
+            let handle = find_key(session, ObjectClass::PUBLIC_KEY, &label)?;
+            match session.get_attributes(handle, &[AttributeType::EcPoint])?.pop() {
+                Some(Attribute::EcPoint(point)) => Ok(point),
+                _ => bail!("Key '{}' has no EC point", label),
+            }
+        }).await?;
+        // CKA_EC_POINT is the point wrapped in a DER OCTET STRING
+        let raw = match point.as_slice() {
+            [0x04, len, rest @ ..] if *len as usize == rest.len() => rest,
+            other => other,
+        };
+        Ok(crypto::compress_public_key(raw)?)
+    }
+
+    async fn sign_digest(&self, key_id: &str, digest: &Hash256) -> Result<Signature> {
+        let label = key_id.to_string();
+        let digest = *digest;
+        let raw = self.with_session(move |session| {
+            let handle = find_key(session, ObjectClass::PRIVATE_KEY, &label)?;
+            Ok(session.sign(&Mechanism::Ecdsa, handle, &digest)?)
+        }).await?;
+        // CKM_ECDSA returns r || s
+        Ok(crypto::signature_from_compact(&raw)?)
+    }
+}
@@ -0,0 +1,174 @@
+//! Spending limits for server-side wallets
+//!
+//! Every user-initiated send reserves its amount against a rolling 24-hour
+//! limit before the transaction is signed, and sends above the step-up
+//! threshold need a two-factor code. Reservations are released if the send
+//! fails, so a failed transaction doesn't count against the limit.
+
+use crate::database::{Database, DbSpendingLimit};
+use crate::user_auth::{User, UserManager};
+use blockchain_core::amount::SATOSHIS_PER_EDU;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Server-wide defaults, overridable per user
+#[derive(Debug, Clone, Copy)]
+pub struct SpendingDefaults {
+    pub daily_limit: u64,
+    pub step_up_threshold: u64,
+}
+
+impl Default for SpendingDefaults {
+    fn default() -> Self {
+        Self {
+            daily_limit: 1_000 * SATOSHIS_PER_EDU,
+            step_up_threshold: 100 * SATOSHIS_PER_EDU,
+        }
+    }
+}
+
+impl SpendingDefaults {
+    /// `WALLET_DAILY_LIMIT_EDU` and `WALLET_STEP_UP_THRESHOLD_EDU`, in whole EDU
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let edu = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).map(|v| v * SATOSHIS_PER_EDU);
+        Self {
+            daily_limit: edu("WALLET_DAILY_LIMIT_EDU").unwrap_or(defaults.daily_limit),
+            step_up_threshold: edu("WALLET_STEP_UP_THRESHOLD_EDU").unwrap_or(defaults.step_up_threshold),
+        }
+    }
+}
+
+/// A user's limits and how much of the daily limit is left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendingLimits {
+    pub daily_limit: u64,
+    pub step_up_threshold: u64,
+    pub spent_last_24h: u64,
+    pub remaining: u64,
+    pub two_factor_enabled: bool,
+}
+
+/// Requested limit changes (None leaves a limit unchanged)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendingLimitUpdate {
+    pub daily_limit: Option<u64>,
+    pub step_up_threshold: Option<u64>,
+    /// Required when raising either limit
+    pub totp_code: Option<String>,
+}
+
+/// Amount held against the daily limit until the send settles
+#[derive(Debug)]
+#[must_use = "commit or release the reservation"]
+pub struct SpendReservation {
+    id: i64,
+}
+
+pub struct SpendingGuard {
+    database: Arc<Database>,
+    users: Arc<UserManager>,
+    defaults: SpendingDefaults,
+}
+
+impl SpendingGuard {
+    pub fn new(database: Arc<Database>, users: Arc<UserManager>, defaults: SpendingDefaults) -> Self {
+        Self { database, users, defaults }
+    }
+
+    pub async fn limits(&self, user: &User) -> Result<SpendingLimits, String> {
+        let (daily_limit, step_up_threshold) = self.configured(&user.wallet_address).await?;
+        let spent = self.database.spent_since(&user.wallet_address, Utc::now().timestamp() - WINDOW_SECS).await
+            .map_err(|e| e.to_string())? as u64;
+
+        Ok(SpendingLimits {
+            daily_limit,
+            step_up_threshold,
+            spent_last_24h: spent,
+            remaining: daily_limit.saturating_sub(spent),
+            two_factor_enabled: self.users.two_factor_enabled(&user.wallet_address).await,
+        })
+    }
+
+    /// Change a user's limits; lowering is free, raising needs a second factor
+    pub async fn update_limits(&self, user: &User, update: &SpendingLimitUpdate) -> Result<SpendingLimits, String> {
+        let (daily_limit, step_up_threshold) = self.configured(&user.wallet_address).await?;
+        let new_daily = update.daily_limit.unwrap_or(daily_limit);
+        let new_threshold = update.step_up_threshold.unwrap_or(step_up_threshold);
+        if new_daily > i64::MAX as u64 || new_threshold > i64::MAX as u64 {
+            return Err("Limit is too large".to_string());
+        }
+
+        if new_daily > daily_limit || new_threshold > step_up_threshold {
+            let code = update.totp_code.as_deref()
+                .ok_or("Raising a spending limit requires two-factor authentication")?;
+            self.users.verify_second_factor(user, code).await?;
+        }
+
+        self.database.set_spending_limit(&DbSpendingLimit {
+            wallet_address: user.wallet_address.clone(),
+            daily_limit: new_daily as i64,
+            step_up_threshold: new_threshold as i64,
+            updated_at: Utc::now().timestamp(),
+        }).await.map_err(|e| e.to_string())?;
+
+        tracing::info!("🛡️ Spending limits for {}: {} sat/day, step-up above {} sat", user.username, new_daily, new_threshold);
+        self.limits(user).await
+    }
+
+    /// Check step-up and hold `amount` against the daily limit
+    pub async fn reserve(&self, user: &User, amount: u64, totp_code: Option<&str>) -> Result<SpendReservation, String> {
+        let (daily_limit, step_up_threshold) = self.configured(&user.wallet_address).await?;
+
+        if amount > step_up_threshold {
+            if !self.users.two_factor_enabled(&user.wallet_address).await {
+                return Err(format!(
+                    "Sends above {} EDU require two-factor authentication; enable it first",
+                    step_up_threshold as f64 / SATOSHIS_PER_EDU as f64
+                ));
+            }
+            let code = totp_code.ok_or("Two-factor code required for this amount")?;
+            self.users.verify_second_factor(user, code).await?;
+        }
+
+        let amount = i64::try_from(amount).map_err(|_| "Amount is too large".to_string())?;
+        let now = Utc::now().timestamp();
+        let reserved = self.database
+            .reserve_spend(&user.wallet_address, amount, daily_limit as i64, now - WINDOW_SECS, now)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match reserved {
+            Some(id) => Ok(SpendReservation { id }),
+            None => Err(format!(
+                "Daily spending limit of {} EDU exceeded",
+                daily_limit as f64 / SATOSHIS_PER_EDU as f64
+            )),
+        }
+    }
+
+    /// Attach the sent transaction to its reservation
+    pub async fn commit(&self, reservation: SpendReservation, tx_hash: &str) {
+        if let Err(e) = self.database.commit_spend(reservation.id, tx_hash).await {
+            tracing::error!("❌ Failed to record spend {}: {}", tx_hash, e);
+        }
+    }
+
+    /// Give back a reservation whose send failed
+    pub async fn release(&self, reservation: SpendReservation) {
+        if let Err(e) = self.database.release_spend(reservation.id).await {
+            tracing::error!("❌ Failed to release spend reservation {}: {}", reservation.id, e);
+        }
+    }
+
+    async fn configured(&self, wallet_address: &str) -> Result<(u64, u64), String> {
+        let stored = self.database.get_spending_limit(wallet_address).await.map_err(|e| e.to_string())?;
+        Ok(match stored {
+            Some(limit) => (limit.daily_limit as u64, limit.step_up_threshold as u64),
+            None => (self.defaults.daily_limit, self.defaults.step_up_threshold),
+        })
+    }
+}
@@ -2,14 +2,16 @@
 // File: edunet-gui/src/user_auth.rs
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use sha2::{Sha256, Digest};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
 use blockchain_core::wallet::{WalletManager, Wallet};
-use crate::database::Database;
+use crate::database::{Database, DbTwoFactor};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -31,6 +33,16 @@ pub struct User {
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// TOTP or recovery code, required once two-factor is enabled
+    #[serde(default)]
+    pub totp_code: Option<String>,
+}
+
+/// Secret handed to the user while enrolling an authenticator app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +67,7 @@ pub struct UserManager {
     sessions: Arc<RwLock<HashMap<String, UserSession>>>,
     username_to_id: Arc<RwLock<HashMap<String, Uuid>>>,
     email_to_id: Arc<RwLock<HashMap<String, Uuid>>>,
+    frozen_wallets: Arc<RwLock<HashSet<String>>>,
     wallet_manager: Arc<tokio::sync::RwLock<WalletManager>>,
     database: Arc<Database>,
 }
@@ -66,6 +79,7 @@ impl UserManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             username_to_id: Arc::new(RwLock::new(HashMap::new())),
             email_to_id: Arc::new(RwLock::new(HashMap::new())),
+            frozen_wallets: Arc::new(RwLock::new(HashSet::new())),
             wallet_manager,
             database,
         }
@@ -104,6 +118,10 @@ impl UserManager {
             }
         }
 
+        let frozen = self.database.list_frozen_accounts().await
+            .map_err(|e| format!("Failed to load frozen accounts: {}", e))?;
+        *self.frozen_wallets.write().await = frozen.into_iter().map(|a| a.wallet_address).collect();
+
         tracing::info!("✅ Loaded {} users from database", users.len());
         Ok(())
     }
@@ -196,6 +214,15 @@ impl UserManager {
             return Err("Invalid username or password".to_string());
         }
 
+        if self.is_frozen(&user.wallet_address).await {
+            return Err("Account is frozen".to_string());
+        }
+
+        if self.two_factor_enabled(&user.wallet_address).await {
+            let code = request.totp_code.as_deref().ok_or("Two-factor code required")?;
+            self.verify_second_factor(user, code).await?;
+        }
+
         // Update last login in database
         let _ = self.database.update_last_login(&request.username).await;
 
@@ -233,6 +260,10 @@ impl UserManager {
         let user = users.get(&session.user_id)
             .ok_or("User not found")?;
 
+        if self.is_frozen(&user.wallet_address).await {
+            return Err("Account is frozen".to_string());
+        }
+
         Ok(user.clone())
     }
 
@@ -254,6 +285,195 @@ impl UserManager {
         Ok(wallet.clone())
     }
 
+    // Get user by ID
+    pub async fn get_user_by_id(&self, user_id: &Uuid) -> Option<User> {
+        let users = self.users.read().await;
+        users.get(user_id).cloned()
+    }
+
+    // Get user by wallet address
+    pub async fn get_user_by_wallet(&self, wallet_address: &str) -> Option<User> {
+        let users = self.users.read().await;
+        users.values().find(|u| u.wallet_address == wallet_address).cloned()
+    }
+
+    // Get user by username
+    pub async fn get_user_by_username(&self, username: &str) -> Option<User> {
+        let user_id = *self.username_to_id.read().await.get(username)?;
+        self.get_user_by_id(&user_id).await
+    }
+
+    // Check whether an account has been frozen by an admin
+    pub async fn is_frozen(&self, wallet_address: &str) -> bool {
+        self.frozen_wallets.read().await.contains(wallet_address)
+    }
+
+    // Freeze or unfreeze an account in the cache; freezing ends its sessions
+    pub async fn set_frozen(&self, wallet_address: &str, frozen: bool) {
+        if !frozen {
+            self.frozen_wallets.write().await.remove(wallet_address);
+            return;
+        }
+
+        self.frozen_wallets.write().await.insert(wallet_address.to_string());
+        if let Some(user) = self.get_user_by_wallet(wallet_address).await {
+            self.sessions.write().await.retain(|_, session| session.user_id != user.id);
+        }
+    }
+
+    // Mark the account holding a wallet as verified by an attestation, or not
+    pub async fn set_verified(&self, wallet_address: &str, verified: bool) {
+        for user in self.users.write().await.values_mut().filter(|u| u.wallet_address == wallet_address) {
+            user.is_verified = verified;
+        }
+    }
+
+    // Verify exactly the accounts holding the given wallets
+    pub async fn set_verified_wallets(&self, wallets: &HashSet<String>) {
+        for user in self.users.write().await.values_mut() {
+            user.is_verified = wallets.contains(&user.wallet_address);
+        }
+    }
+
+    // Whether the account has confirmed two-factor authentication
+    pub async fn two_factor_enabled(&self, wallet_address: &str) -> bool {
+        matches!(self.database.get_two_factor(wallet_address).await, Ok(Some(tf)) if tf.enabled)
+    }
+
+    // Start TOTP enrollment; the secret only takes effect once a code is confirmed
+    pub async fn begin_two_factor_setup(&self, user: &User) -> Result<TwoFactorSetup, String> {
+        let mut secret_bytes = [0u8; 20];
+        rand::Rng::fill(&mut rand::thread_rng(), &mut secret_bytes);
+        let secret = base32_encode(&secret_bytes);
+
+        let saved = self.database.save_pending_two_factor(&user.wallet_address, &secret, Utc::now().timestamp()).await
+            .map_err(|e| format!("Failed to save two-factor secret: {}", e))?;
+        if !saved {
+            return Err("Two-factor authentication is already enabled".to_string());
+        }
+
+        let otpauth_uri = format!(
+            "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&digits={digits}&period={period}",
+            issuer = TOTP_ISSUER,
+            account = user.username,
+            secret = secret,
+            digits = TOTP_DIGITS,
+            period = TOTP_PERIOD,
+        );
+        Ok(TwoFactorSetup { secret, otpauth_uri })
+    }
+
+    // Confirm enrollment with a first code; returns one-time recovery codes
+    pub async fn confirm_two_factor_setup(&self, user: &User, code: &str) -> Result<Vec<String>, String> {
+        let two_factor = self.database.get_two_factor(&user.wallet_address).await
+            .map_err(|e| e.to_string())?
+            .ok_or("Two-factor setup has not been started")?;
+        if two_factor.enabled {
+            return Err("Two-factor authentication is already enabled".to_string());
+        }
+        Self::check_lockout(&two_factor)?;
+        let result = self.verify_totp(&user.wallet_address, &two_factor.secret, code).await;
+        self.record_attempt(&user.wallet_address, result).await?;
+
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let bytes: [u8; 5] = rand::random();
+                let hex = hex::encode(bytes);
+                format!("{}-{}", &hex[..5], &hex[5..])
+            })
+            .collect();
+        let hashes: Vec<String> = recovery_codes.iter().map(|c| hash_recovery_code(c)).collect();
+        let hashes = serde_json::to_string(&hashes).map_err(|e| e.to_string())?;
+
+        if !self.database.enable_two_factor(&user.wallet_address, &hashes).await.map_err(|e| e.to_string())? {
+            return Err("Two-factor authentication is already enabled".to_string());
+        }
+        tracing::info!("🔐 Two-factor authentication enabled for {}", user.username);
+        Ok(recovery_codes)
+    }
+
+    // Turn two-factor off (requires a current code)
+    pub async fn disable_two_factor(&self, user: &User, code: &str) -> Result<(), String> {
+        self.verify_second_factor(user, code).await?;
+        self.database.delete_two_factor(&user.wallet_address).await.map_err(|e| e.to_string())?;
+        tracing::warn!("🔓 Two-factor authentication disabled for {}", user.username);
+        Ok(())
+    }
+
+    // Check a TOTP code or an unused recovery code
+    pub async fn verify_second_factor(&self, user: &User, code: &str) -> Result<(), String> {
+        let two_factor = match self.database.get_two_factor(&user.wallet_address).await {
+            Ok(Some(tf)) if tf.enabled => tf,
+            Ok(_) => return Err("Two-factor authentication is not enabled".to_string()),
+            Err(e) => return Err(e.to_string()),
+        };
+        Self::check_lockout(&two_factor)?;
+        let result = self.check_second_factor(user, &two_factor, code).await;
+        self.record_attempt(&user.wallet_address, result).await
+    }
+
+    // Refuse codes while too many wrong ones in a row have locked the account
+    fn check_lockout(two_factor: &DbTwoFactor) -> Result<(), String> {
+        let remaining = two_factor.locked_until - Utc::now().timestamp();
+        if remaining > 0 {
+            return Err(format!("Too many invalid two-factor codes; try again in {} minutes", (remaining + 59) / 60));
+        }
+        Ok(())
+    }
+
+    // Count a wrong code towards a lockout, or clear the count after a right one
+    async fn record_attempt(&self, wallet_address: &str, result: Result<(), String>) -> Result<(), String> {
+        match &result {
+            Ok(()) => self.database.reset_two_factor_failures(wallet_address).await.map_err(|e| e.to_string())?,
+            Err(_) => {
+                let locked_until = Utc::now().timestamp() + TOTP_LOCKOUT_SECS;
+                let locked = self.database.record_two_factor_failure(wallet_address, TOTP_MAX_FAILURES, locked_until).await
+                    .map_err(|e| e.to_string())?;
+                if locked {
+                    tracing::warn!("🔒 Two-factor locked for {} after {} invalid codes", wallet_address, TOTP_MAX_FAILURES);
+                }
+            }
+        }
+        result
+    }
+
+    async fn check_second_factor(&self, user: &User, two_factor: &DbTwoFactor, code: &str) -> Result<(), String> {
+        let code = code.trim();
+        if code.contains('-') {
+            let hash = hash_recovery_code(code);
+            let mut remaining: Vec<String> = serde_json::from_str(&two_factor.recovery_codes).unwrap_or_default();
+            let Some(index) = remaining.iter().position(|h| *h == hash) else {
+                return Err("Invalid recovery code".to_string());
+            };
+            remaining.remove(index);
+            let remaining = serde_json::to_string(&remaining).map_err(|e| e.to_string())?;
+            let consumed = self.database.replace_recovery_codes(&user.wallet_address, &two_factor.recovery_codes, &remaining).await
+                .map_err(|e| e.to_string())?;
+            if !consumed {
+                return Err("Recovery code was used concurrently; try again".to_string());
+            }
+            tracing::warn!("🔑 Recovery code used by {}", user.username);
+            return Ok(());
+        }
+
+        self.verify_totp(&user.wallet_address, &two_factor.secret, code).await
+    }
+
+    // Check a TOTP code against the secret and burn its time step
+    async fn verify_totp(&self, wallet_address: &str, secret: &str, code: &str) -> Result<(), String> {
+        let secret = base32_decode(secret).ok_or("Corrupt two-factor secret")?;
+        let code: u32 = code.trim().parse().map_err(|_| "Invalid two-factor code")?;
+        let now = Utc::now().timestamp() as u64 / TOTP_PERIOD;
+
+        let step = (now.saturating_sub(TOTP_SKEW)..=now + TOTP_SKEW)
+            .find(|step| totp_code(&secret, *step) == code)
+            .ok_or("Invalid two-factor code")?;
+        if !self.database.use_totp_step(wallet_address, step as i64).await.map_err(|e| e.to_string())? {
+            return Err("Two-factor code already used".to_string());
+        }
+        Ok(())
+    }
+
     // List all users (admin function)
     pub async fn list_users(&self) -> Vec<User> {
         let users = self.users.read().await;
@@ -348,4 +568,109 @@ impl UserManager {
         tracing::info!("✅ Demo users created (alice, bob, carol) - password: password123");
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+const TOTP_ISSUER: &str = "EduNet";
+const TOTP_PERIOD: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+// Accept codes one step either side of now for clock drift
+const TOTP_SKEW: u64 = 1;
+// Wrong codes in a row before the second factor locks, and for how long
+const TOTP_MAX_FAILURES: i64 = 5;
+const TOTP_LOCKOUT_SECS: i64 = 15 * 60;
+const RECOVERY_CODE_COUNT: usize = 8;
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// RFC 6238 TOTP (HMAC-SHA1, dynamic truncation)
+fn totp_code(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[19] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().to_lowercase().as_bytes()))
+}
+
+// RFC 4648 base32 without padding, as authenticator apps expect
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA-1, truncated to six digits
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let secret = b"12345678901234567890";
+        for (time, expected) in [
+            (59u64, 287082),
+            (1111111109, 81804),
+            (1111111111, 50471),
+            (1234567890, 5924),
+            (2000000000, 279037),
+            (20000000000, 353130),
+        ] {
+            assert_eq!(totp_code(secret, time / TOTP_PERIOD), expected, "time {}", time);
+        }
+    }
+
+    // RFC 4648 section 10, without padding
+    #[test]
+    fn test_base32_rfc4648_vectors() {
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ] {
+            assert_eq!(base32_encode(data.as_bytes()), encoded);
+            assert_eq!(base32_decode(encoded).unwrap(), data.as_bytes());
+        }
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw6ytboi").unwrap(), b"foobar");
+        assert_eq!(base32_decode("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap(), b"12345678901234567890");
+        assert!(base32_decode("MZXW1").is_none());
+    }
+}
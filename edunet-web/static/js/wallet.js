@@ -368,27 +368,29 @@ async function sendTransaction(event) {
     }
 }
 
-// Generate payment request QR
+// Generate a signed invoice and its QR code
+let invoicePollTimer = null;
+
 async function generatePaymentRequest() {
     const walletId = document.getElementById('receiveWallet').value;
     const amount = parseFloat(document.getElementById('requestAmount').value) || null;
-    const message = document.getElementById('requestMessage').value.trim() || null;
+    const memo = document.getElementById('requestMessage').value.trim() || null;
     
-    if (!walletId) {
+    clearInterval(invoicePollTimer);
+    if (!walletId || !amount) {
         document.getElementById('paymentRequestQR').style.display = 'none';
         return;
     }
     
     try {
-        const response = await fetch('/api/wallets/payment-request', {
+        const response = await fetch('/api/invoices', {
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json'
             },
             body: JSON.stringify({
-                wallet_id: walletId,
                 amount: amount,
-                message: message
+                memo: memo
             })
         });
         
@@ -398,16 +400,45 @@ async function generatePaymentRequest() {
             const qrImage = document.getElementById('paymentQRImage');
             qrImage.src = 'data:image/png;base64,' + data.data.qr_code;
             
-            document.getElementById('paymentQRData').textContent = data.data.qr_data;
+            document.getElementById('paymentQRData').textContent = data.data.uri;
             document.getElementById('paymentRequestQR').style.display = 'block';
+            showInvoiceStatus(data.data);
+            
+            // Track the invoice until it is paid or expires
+            invoicePollTimer = setInterval(() => pollInvoiceStatus(data.data.invoice.id), 5000);
         } else {
-            console.error('Failed to generate payment request:', data.message);
+            console.error('Failed to generate invoice:', data.error);
+        }
+    } catch (error) {
+        console.error('Error generating invoice:', error);
+    }
+}
+
+async function pollInvoiceStatus(invoiceId) {
+    try {
+        const response = await fetch(`/api/invoices/${invoiceId}`);
+        const data = await response.json();
+        if (data.success) {
+            showInvoiceStatus(data.data);
+            if (data.data.status !== 'pending') {
+                clearInterval(invoicePollTimer);
+            }
         }
     } catch (error) {
-        console.error('Error generating payment request:', error);
+        console.error('Error checking invoice status:', error);
     }
 }
 
+function showInvoiceStatus(view) {
+    const expires = new Date(view.invoice.expires_at * 1000).toLocaleString();
+    const labels = {
+        pending: `Waiting for payment (expires ${expires})`,
+        paid: `Paid in transaction ${view.paid_tx_hash}`,
+        expired: 'Expired without payment'
+    };
+    document.getElementById('paymentStatus').textContent = labels[view.status] || view.status;
+}
+
 // Copy payment request data
 function copyPaymentRequest() {
     const qrData = document.getElementById('paymentQRData').textContent;
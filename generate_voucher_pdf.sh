@@ -1,34 +1,55 @@
 #!/bin/bash
 
 # Script to convert voucher HTML to PDF
-# Usage: ./generate_voucher_pdf.sh <count> <amount>
-# Example: ./generate_voucher_pdf.sh 30 10
+# Usage: ADMIN_TOKEN=... ./generate_voucher_pdf.sh <count> <amount> [label]
+# Example: ADMIN_TOKEN=secret ./generate_voucher_pdf.sh 30 10 "Spring orientation"
+#
+# The node issues the batch (voucher_createBatch), refusing it if the
+# treasury cannot cover every outstanding voucher, then exports the
+# printable payloads (voucher_exportBatch).
 
 set -e
 
 COUNT=${1:-30}
 AMOUNT=${2:-10}
+LABEL=${3:-"${COUNT}x${AMOUNT} EDU"}
+RPC_URL=${RPC_URL:-http://localhost:8545}
 TIMESTAMP=$(date +%Y%m%d_%H%M%S)
 JSON_FILE="vouchers_${COUNT}x${AMOUNT}_${TIMESTAMP}.json"
 OUTPUT_DIR="voucher-qr-codes-${TIMESTAMP}"
 PDF_FILE="vouchers_${COUNT}x${AMOUNT}_${TIMESTAMP}.pdf"
 
+if [ -z "$ADMIN_TOKEN" ]; then
+    echo "❌ Error: set ADMIN_TOKEN to the node's admin token"
+    exit 1
+fi
+
+rpc() {
+    curl -s -X POST "$RPC_URL" \
+        -H "Content-Type: application/json" \
+        -d "{\"jsonrpc\":\"2.0\",\"method\":\"$1\",\"params\":$2,\"id\":1}"
+}
+
 echo "🎫 Generating $COUNT vouchers with $AMOUNT EDU each..."
 echo ""
 
-# Step 1: Generate vouchers via API
-echo "📡 Step 1/3: Calling API to generate vouchers..."
-curl -s -X POST http://localhost:8080/api/voucher/generate \
-    -H "Content-Type: application/json" \
-    -d "{\"count\":$COUNT,\"amount\":$AMOUNT}" \
-    > "$JSON_FILE"
+# Step 1: Issue the batch on the node and export it
+echo "📡 Step 1/3: Creating voucher batch on the node..."
+PARAMS=$(jq -n --arg token "$ADMIN_TOKEN" --arg label "$LABEL" \
+    --argjson count "$COUNT" --arg amount "$((AMOUNT * 100000000))" \
+    '{token: $token, label: $label, count: $count, amount: $amount}')
+BATCH=$(rpc voucher_createBatch "$PARAMS")
 
-# Check if successful
-if ! jq -e '.success' "$JSON_FILE" > /dev/null 2>&1; then
-    echo "❌ Error: Failed to generate vouchers"
-    cat "$JSON_FILE"
+BATCH_ID=$(echo "$BATCH" | jq -r '.result.batch.batch_id // empty')
+if [ -z "$BATCH_ID" ]; then
+    echo "❌ Error: Failed to create voucher batch"
+    echo "$BATCH" | jq -r '.error.message // .'
     exit 1
 fi
+echo "✅ Batch $BATCH_ID, outstanding liability now $(echo "$BATCH" | jq -r '.result.outstanding_liability') sat"
+
+PARAMS=$(jq -n --arg token "$ADMIN_TOKEN" --arg batch_id "$BATCH_ID" '{token: $token, batch_id: $batch_id}')
+rpc voucher_exportBatch "$PARAMS" | jq '.result' > "$JSON_FILE"
 
 ACTUAL_COUNT=$(jq -r '.count' "$JSON_FILE")
 echo "✅ Generated $ACTUAL_COUNT vouchers"
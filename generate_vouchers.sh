@@ -1,25 +1,48 @@
 #!/bin/bash
 
 # Generate vouchers for distribution
-# Each voucher is worth 20 EDU
+# Usage: ADMIN_TOKEN=... ./generate_vouchers.sh [count] [amount-edu] [label]
+# Each voucher is worth 20 EDU unless an amount is given. The node refuses
+# the batch if the treasury cannot cover every outstanding voucher.
 
 COUNT=${1:-10}
+AMOUNT=${2:-20}
+LABEL=${3:-"${COUNT}x${AMOUNT} EDU"}
+RPC_URL=${RPC_URL:-http://localhost:8545}
+
+if [ -z "$ADMIN_TOKEN" ]; then
+  echo "❌ Set ADMIN_TOKEN to the node's admin token"
+  exit 1
+fi
+
+rpc() {
+  curl -s -X POST "$RPC_URL" \
+    -H "Content-Type: application/json" \
+    -d "{\"jsonrpc\":\"2.0\",\"method\":\"$1\",\"params\":$2,\"id\":1}"
+}
 
 echo "=== Generating $COUNT Vouchers ==="
 echo ""
 
-RESPONSE=$(curl -s -X POST http://localhost:8080/api/voucher/generate \
-  -H "Content-Type: application/json" \
-  -d "{\"count\": $COUNT}")
-
+PARAMS=$(jq -n --arg token "$ADMIN_TOKEN" --arg label "$LABEL" \
+  --argjson count "$COUNT" --arg amount "$((AMOUNT * 100000000))" \
+  '{token: $token, label: $label, count: $count, amount: $amount}')
+RESPONSE=$(rpc voucher_createBatch "$PARAMS")
 echo "$RESPONSE" | jq .
 
-# Save vouchers to file
+BATCH_ID=$(echo "$RESPONSE" | jq -r '.result.batch.batch_id // empty')
+if [ -z "$BATCH_ID" ]; then
+  echo "❌ Batch was not created"
+  exit 1
+fi
+
+# Save printable payloads to file
 TIMESTAMP=$(date +%Y%m%d_%H%M%S)
 FILENAME="vouchers/vouchers_$TIMESTAMP.json"
 
 mkdir -p vouchers
-echo "$RESPONSE" | jq '.vouchers' > "$FILENAME"
+PARAMS=$(jq -n --arg token "$ADMIN_TOKEN" --arg batch_id "$BATCH_ID" '{token: $token, batch_id: $batch_id}')
+rpc voucher_exportBatch "$PARAMS" | jq '.result' > "$FILENAME"
 
 echo ""
 echo "✅ Vouchers saved to: $FILENAME"
@@ -27,16 +50,15 @@ echo ""
 
 # Display vouchers in readable format
 echo "=== Generated Voucher Codes ==="
-echo "$RESPONSE" | jq -r '.vouchers[] | .code'
+jq -r '.vouchers[] | .code' "$FILENAME"
 echo ""
 
 echo "=== Distribution Instructions ==="
 echo "1. Share voucher codes with users"
-echo "2. Users can redeem at: http://your-site.com/api/voucher/redeem"
-echo "3. Each voucher is worth 20 EDU"
+echo "2. Users redeem with the voucher_redeem RPC (code and address)"
+echo "3. Each voucher is worth $AMOUNT EDU"
 echo "4. Vouchers are single-use only"
+echo "5. Revoke unredeemed codes with voucher_revokeBatch (batch $BATCH_ID)"
 echo ""
 
-# Count total EDU value
-TOTAL_EDU=$(echo "$COUNT * 20" | bc)
-echo "Total Value: $TOTAL_EDU EDU"
+echo "Total Value: $((COUNT * AMOUNT)) EDU"
@@ -0,0 +1,51 @@
+#!/bin/bash
+
+# Build and package a blockchain-node release
+# Builds from the committed lockfile with no timestamps or local paths
+# embedded, so the same commit and toolchain give byte-identical archives.
+
+set -euo pipefail
+
+cd "$(dirname "$0")"
+
+COMMIT=$(git rev-parse HEAD)
+if [ -n "$(git status --porcelain -- rust-system blockchain-node Cargo.toml Cargo.lock)" ]; then
+    echo "⚠️  Working tree has uncommitted changes; the package will not match $COMMIT"
+fi
+
+# Every timestamp in the package is the commit time
+export SOURCE_DATE_EPOCH=$(git log -1 --format=%ct)
+export EDUNET_GIT_COMMIT=$COMMIT
+export RUSTFLAGS="${RUSTFLAGS:-} --remap-path-prefix=$PWD=. --remap-path-prefix=$HOME/.cargo=/cargo"
+
+echo "=== Building blockchain-node at $COMMIT ==="
+cargo build --release --locked -p blockchain-node
+
+BINARY=target/release/blockchain-node
+VERSION=$("$BINARY" version | awk '{print $2}')
+TARGET=$(rustc -vV | awk '/^host:/ {print $2}')
+NAME="blockchain-node-$VERSION-$TARGET"
+STAGE="dist/$NAME"
+
+echo ""
+echo "=== Self-check ==="
+SCRATCH=$(mktemp -d)
+trap 'rm -rf "$SCRATCH"' EXIT
+"$BINARY" init --data-dir "$SCRATCH" --genesis-timestamp "$SOURCE_DATE_EPOCH" > /dev/null
+"$BINARY" self-check --data-dir "$SCRATCH"
+
+rm -rf "$STAGE"
+mkdir -p "$STAGE"
+cp "$BINARY" "$STAGE/"
+cp README.md "$STAGE/"
+"$BINARY" version --verbose > "$STAGE/VERSION"
+
+# Fixed file order, owner and mtime so the archive is reproducible
+tar --sort=name --mtime="@$SOURCE_DATE_EPOCH" --owner=0 --group=0 --numeric-owner \
+    -C dist -cf - "$NAME" | gzip -n > "dist/$NAME.tar.gz"
+(cd dist && sha256sum "$NAME.tar.gz" > "$NAME.tar.gz.sha256")
+rm -rf "$STAGE"
+
+echo ""
+echo "✅ Packaged: dist/$NAME.tar.gz"
+cat "dist/$NAME.tar.gz.sha256"
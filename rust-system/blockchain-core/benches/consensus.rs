@@ -0,0 +1,150 @@
+//! Consensus validation benchmarks
+//!
+//! Run with `cargo bench -p blockchain-core --bench consensus`.
+
+use blockchain_core::block::Block;
+use blockchain_core::crypto::{derive_public_key, generate_private_key, sha256, sign_hash, SigningKey};
+use blockchain_core::hashing::{self, Backend};
+use blockchain_core::sighash::{SighashCache, SIGHASH_ALL, SIGHASH_PRECOMPUTED};
+use blockchain_core::signature::{self, schnorr_public_key, sign_schnorr, BatchItem};
+use blockchain_core::transaction::{Transaction, TransactionInput, TransactionOutput};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const SCRIPT_PUBKEY: [u8; 25] = [
+    0x76, 0xa9, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x88, 0xac,
+];
+
+fn transaction(inputs: usize) -> Transaction {
+    let inputs = (0..inputs)
+        .map(|i| TransactionInput::new([(i % 256) as u8; 32], i as u32, vec![0xAA; 107]))
+        .collect();
+    let outputs = vec![TransactionOutput::new(1_000, SCRIPT_PUBKEY.to_vec()); 2];
+    Transaction::new(1, inputs, outputs)
+}
+
+/// Signature hashes for every input of an n-input transaction
+fn bench_sighash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sighash_all_inputs");
+    for inputs in [10, 100, 500] {
+        let tx = transaction(inputs);
+        group.bench_with_input(BenchmarkId::new("uncached", inputs), &tx, |b, tx| {
+            b.iter(|| {
+                for i in 0..tx.inputs.len() {
+                    // A fresh cache per input hashes the full preimage each time
+                    black_box(tx.calculate_signature_hash(i, &SCRIPT_PUBKEY, SIGHASH_ALL));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("legacy_midstate", inputs), &tx, |b, tx| {
+            b.iter(|| {
+                let mut cache = SighashCache::new(tx);
+                for i in 0..tx.inputs.len() {
+                    black_box(cache.legacy_hash(i, &SCRIPT_PUBKEY, SIGHASH_ALL));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("precomputed", inputs), &tx, |b, tx| {
+            b.iter(|| {
+                let mut cache = SighashCache::new(tx);
+                for i in 0..tx.inputs.len() {
+                    black_box(cache.precomputed_hash(i, &SCRIPT_PUBKEY, 1_000, SIGHASH_ALL | SIGHASH_PRECOMPUTED).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Signing a consolidation whose inputs all belong to one key, the way
+/// wallets used to (fresh key setup and full sighash per input) against one
+/// parsed key and a shared sighash cache
+fn bench_sign_consolidation(c: &mut Criterion) {
+    let key = generate_private_key().unwrap();
+    let mut group = c.benchmark_group("sign_consolidation");
+    group.sample_size(10);
+    for inputs in [50, 200] {
+        let tx = transaction(inputs);
+        group.bench_with_input(BenchmarkId::new("per_input", inputs), &tx, |b, tx| {
+            b.iter(|| {
+                for i in 0..tx.inputs.len() {
+                    let hash = tx.calculate_signature_hash(i, &SCRIPT_PUBKEY, SIGHASH_ALL);
+                    black_box((sign_hash(&hash, &key).unwrap(), derive_public_key(&key).unwrap()));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("cached", inputs), &tx, |b, tx| {
+            b.iter(|| {
+                let signer = SigningKey::new(&key).unwrap();
+                let mut cache = SighashCache::new(tx);
+                for i in 0..tx.inputs.len() {
+                    let hash = cache.signature_hash(i, &SCRIPT_PUBKEY, 1_000, SIGHASH_ALL | SIGHASH_PRECOMPUTED).unwrap();
+                    black_box((signer.sign_hash(&hash).unwrap(), signer.public_key()));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Signed `(hash, signature, public key)` items
+fn signed_items(count: usize, schnorr: bool) -> Vec<([u8; 32], Vec<u8>, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            let key = generate_private_key().unwrap();
+            let hash = sha256(&(i as u64).to_le_bytes());
+            if schnorr {
+                (hash, sign_schnorr(&hash, &key).unwrap(), schnorr_public_key(&key).unwrap())
+            } else {
+                (hash, sign_hash(&hash, &key).unwrap(), derive_public_key(&key).unwrap())
+            }
+        })
+        .collect()
+}
+
+/// Batch against one-at-a-time signature verification
+fn bench_verify_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_signatures");
+    for (kind, schnorr) in [("schnorr", true), ("ecdsa", false)] {
+        for count in [16, 256] {
+            let items = signed_items(count, schnorr);
+            let batch: Vec<BatchItem> = items.iter().map(|(h, s, p)| (h, s.as_slice(), p.as_slice())).collect();
+            group.bench_with_input(BenchmarkId::new(format!("{}_single", kind), count), &batch, |b, batch| {
+                b.iter(|| assert!(batch.iter().all(signature::verify)))
+            });
+            group.bench_with_input(BenchmarkId::new(format!("{}_batch", kind), count), &batch, |b, batch| {
+                b.iter(|| assert!(signature::verify_batch(batch)))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Double-SHA256 of merkle-node-sized messages on each supported backend
+fn bench_double_sha256(c: &mut Criterion) {
+    let messages: Vec<[u8; 128]> = (0..1024u32).map(|i| [(i % 256) as u8; 128]).collect();
+    let batch: Vec<&[u8]> = messages.iter().map(|m| m.as_slice()).collect();
+    let mut group = c.benchmark_group("double_sha256_1024x128");
+    // sha2 uses SHA-NI by itself where it can, so the portable run only
+    // measures software hashing on CPUs without it
+    for backend in [Backend::Portable, Backend::Avx2, Backend::ShaNi] {
+        let software_shadowed = backend == Backend::Portable && Backend::ShaNi.is_supported();
+        if backend.is_supported() && !software_shadowed {
+            group.bench_with_input(BenchmarkId::from_parameter(backend.name()), &batch, |b, batch| {
+                b.iter(|| black_box(hashing::double_sha256_many_with(backend, batch)))
+            });
+        }
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("merkle_root");
+    for leaves in [256, 4096] {
+        let hashes: Vec<[u8; 32]> = (0..leaves as u32).map(|i| sha256(&i.to_le_bytes())).collect();
+        group.bench_with_input(BenchmarkId::from_parameter(leaves), &hashes, |b, hashes| {
+            b.iter(|| black_box(Block::compute_merkle_root(hashes.clone())))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sighash, bench_sign_consolidation, bench_verify_batch, bench_double_sha256);
+criterion_main!(benches);
@@ -0,0 +1,167 @@
+//! Hot path benchmarks: block template assembly, mempool insertion, UTXO
+//! application of a full block and transaction signature verification
+//!
+//! Run with `cargo bench -p blockchain-core --bench hot_paths`.
+//! `bench_regression.sh` at the repository root saves a baseline for a
+//! release and compares later runs against it.
+
+use blockchain_core::block::{Block, BlockHeader};
+use blockchain_core::consensus::ConsensusParams;
+use blockchain_core::crypto::sign_hash;
+use blockchain_core::mempool::{Mempool, MempoolConfig};
+use blockchain_core::sighash::{SighashCache, SIGHASH_ALL, SIGHASH_PRECOMPUTED};
+use blockchain_core::signature::{self, BatchItem};
+use blockchain_core::transaction::{Transaction, TransactionInput, TransactionOutput};
+use blockchain_core::utxo::{UTXOSet, UTXO};
+use blockchain_core::wallet::Wallet;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use tokio::runtime::Runtime;
+
+const MEMPOOL_ENTRIES: usize = 10_000;
+
+/// Transactions a 1 MB block holds at roughly 250 bytes each
+const FULL_BLOCK_TXS: usize = 4_000;
+
+fn outpoint(i: usize) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash[..8].copy_from_slice(&(i as u64).to_le_bytes());
+    hash
+}
+
+/// `count` independent one-input, two-output payments
+fn payments(count: usize, address: &str) -> Vec<Transaction> {
+    (0..count)
+        .map(|i| {
+            let outputs = vec![
+                TransactionOutput::create_p2pkh(60_000 - (i % 1_000) as u64, address).unwrap(),
+                TransactionOutput::create_p2pkh(30_000, address).unwrap(),
+            ];
+            Transaction::new(1, vec![TransactionInput::new(outpoint(i), 0, vec![0xAA; 107])], outputs)
+        })
+        .collect()
+}
+
+fn mempool_config() -> MempoolConfig {
+    MempoolConfig {
+        max_transactions: MEMPOOL_ENTRIES * 2,
+        min_relay_fee_rate: 1,
+        ..MempoolConfig::default()
+    }
+}
+
+/// Filling an empty mempool with 10k transactions
+fn bench_mempool_insert(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let txs = payments(MEMPOOL_ENTRIES, "edu1qmempoolbench");
+    let mut group = c.benchmark_group("mempool_insert");
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::from_parameter(MEMPOOL_ENTRIES), |b| {
+        b.iter_batched(
+            || (Mempool::new(mempool_config()), txs.clone()),
+            |(mut mempool, txs)| {
+                runtime.block_on(async {
+                    for tx in txs {
+                        mempool.add_transaction(tx).await.unwrap();
+                    }
+                });
+                mempool
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+/// Picking a block's transactions from a 10k-entry mempool and building the
+/// block around them
+fn bench_block_template(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut mempool = Mempool::new(mempool_config());
+    runtime.block_on(async {
+        for tx in payments(MEMPOOL_ENTRIES, "edu1qtemplatebench") {
+            mempool.add_transaction(tx).await.unwrap();
+        }
+    });
+    let max_block_size = ConsensusParams::default().max_block_size;
+    let coinbase = Transaction::create_coinbase(50_000_000, 0, "edu1qminer", vec![1, 2, 3, 4]).unwrap();
+
+    c.bench_function("block_template_10k_mempool", |b| {
+        b.iter(|| {
+            let mut transactions = vec![coinbase.clone()];
+            transactions.extend(mempool.get_transactions_for_block(max_block_size));
+            let hashes = transactions.iter().map(|tx| tx.calculate_hash()).collect();
+            let header = BlockHeader::new(1, [0u8; 32], Block::compute_merkle_root(hashes), 0x1d00ffff, 1);
+            black_box(Block::new(header, transactions))
+        })
+    });
+}
+
+/// Applying a full block of payments to a UTXO set holding the outputs they
+/// spend
+fn bench_utxo_apply_block(c: &mut Criterion) {
+    let address = "edu1qutxobench";
+    let mut utxo_set = UTXOSet::new();
+    for i in 0..FULL_BLOCK_TXS {
+        let output = TransactionOutput::create_p2pkh(100_000, address).unwrap();
+        utxo_set.add_utxo(outpoint(i), 0, UTXO::new(outpoint(i), 0, output, 1, false)).unwrap();
+    }
+    let block = payments(FULL_BLOCK_TXS, address);
+
+    let mut group = c.benchmark_group("utxo_apply_block");
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::from_parameter(FULL_BLOCK_TXS), |b| {
+        b.iter_batched(
+            || utxo_set.clone(),
+            |mut utxo_set| {
+                for tx in &block {
+                    utxo_set.add_transaction(tx, 2).unwrap();
+                }
+                utxo_set
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Checking every input signature of a signed transaction the way block
+/// validation does: a shared sighash cache and one batch verification
+fn bench_verify_transaction(c: &mut Criterion) {
+    let owner = Wallet::new("bench".to_string()).unwrap();
+    let script_pubkey = TransactionOutput::create_p2pkh(0, &owner.address).unwrap().script_pubkey;
+    let sighash_type = SIGHASH_ALL | SIGHASH_PRECOMPUTED;
+
+    let mut group = c.benchmark_group("verify_transaction");
+    for inputs in [1usize, 10, 100] {
+        let unsigned_inputs = (0..inputs).map(|i| TransactionInput::new(outpoint(i), 0, Vec::new())).collect();
+        let outputs = vec![TransactionOutput::create_p2pkh(inputs as u64 * 90_000, &owner.address).unwrap()];
+        let tx = Transaction::new(1, unsigned_inputs, outputs);
+        let signatures: Vec<Vec<u8>> = {
+            let mut sighashes = SighashCache::new(&tx);
+            (0..inputs)
+                .map(|i| {
+                    let hash = sighashes.signature_hash(i, &script_pubkey, 100_000, sighash_type).unwrap();
+                    sign_hash(&hash, &owner.private_key).unwrap()
+                })
+                .collect()
+        };
+
+        group.bench_with_input(BenchmarkId::from_parameter(inputs), &tx, |b, tx| {
+            b.iter(|| {
+                let mut sighashes = SighashCache::new(tx);
+                let hashes: Vec<[u8; 32]> = (0..tx.inputs.len())
+                    .map(|i| sighashes.signature_hash(i, &script_pubkey, 100_000, sighash_type).unwrap())
+                    .collect();
+                let batch: Vec<BatchItem> = hashes.iter()
+                    .zip(&signatures)
+                    .map(|(hash, signature)| (hash, signature.as_slice(), owner.public_key.as_slice()))
+                    .collect();
+                assert!(signature::verify_batch(&batch))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_mempool_insert, bench_block_template, bench_utxo_apply_block, bench_verify_transaction);
+criterion_main!(benches);
@@ -0,0 +1,511 @@
+//! Contract ABI encoding
+//!
+//! Encodes calls and decodes call data, return data and logs using a
+//! contract's JSON ABI, the format solc emits, so the node and explorer can
+//! show contract interactions by name instead of as raw hex. Values are
+//! JSON: integers as decimal strings (numbers are accepted when encoding),
+//! addresses and bytes as hex without `0x`, tuples and arrays as arrays.
+
+use crate::{BlockchainError, Result};
+use revm::primitives::{keccak256, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+fn invalid(message: impl Into<String>) -> BlockchainError {
+    BlockchainError::InvalidInput(message.into())
+}
+
+/// Input, output or event field of a JSON ABI entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub indexed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<Param>,
+}
+
+/// Function, event, constructor or other entry of a JSON ABI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbiEntry {
+    #[serde(rename = "type", default = "function_kind")]
+    pub kind: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub inputs: Vec<Param>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<Param>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub anonymous: bool,
+    #[serde(rename = "stateMutability", default, skip_serializing_if = "Option::is_none")]
+    pub state_mutability: Option<String>,
+}
+
+fn function_kind() -> String {
+    "function".to_string()
+}
+
+impl AbiEntry {
+    /// Canonical signature, e.g. `transfer(address,uint256)`
+    pub fn signature(&self) -> Result<String> {
+        let types = self.inputs.iter()
+            .map(|param| ParamType::parse(param).map(|t| t.canonical()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("{}({})", self.name, types.join(",")))
+    }
+
+    /// First four bytes of the signature hash, which prefix call data
+    pub fn selector(&self) -> Result<[u8; 4]> {
+        let hash = keccak256(self.signature()?.as_bytes());
+        Ok([hash[0], hash[1], hash[2], hash[3]])
+    }
+
+    /// Full signature hash, the first topic of a non-anonymous event
+    pub fn topic(&self) -> Result<String> {
+        Ok(hex::encode(keccak256(self.signature()?.as_bytes())))
+    }
+}
+
+/// A contract's JSON ABI
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Abi {
+    pub entries: Vec<AbiEntry>,
+}
+
+impl Abi {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let abi: Abi = serde_json::from_str(json).map_err(|e| invalid(format!("Invalid ABI: {}", e)))?;
+        // Reject types we could not encode before anyone relies on them
+        for entry in &abi.entries {
+            entry.signature()?;
+            for param in &entry.outputs {
+                ParamType::parse(param)?;
+            }
+        }
+        Ok(abi)
+    }
+
+    fn of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a AbiEntry> {
+        self.entries.iter().filter(move |entry| entry.kind == kind)
+    }
+
+    /// Function by name, or by signature when it is overloaded
+    pub fn function(&self, name: &str) -> Result<&AbiEntry> {
+        let mut matches = self.of_kind("function")
+            .filter(|entry| entry.name == name || entry.signature().map_or(false, |s| s == name));
+        match (matches.next(), matches.next()) {
+            (Some(entry), None) => Ok(entry),
+            (Some(_), Some(_)) => Err(invalid(format!("{} is overloaded, use its full signature", name))),
+            (None, _) => Err(invalid(format!("No function {} in ABI", name))),
+        }
+    }
+
+    /// Call data for `function` with JSON `args`
+    pub fn encode_call(&self, function: &str, args: &[Value]) -> Result<Vec<u8>> {
+        let entry = self.function(function)?;
+        let mut data = entry.selector()?.to_vec();
+        data.extend(encode(&param_types(&entry.inputs)?, args)?);
+        Ok(data)
+    }
+
+    /// Function and arguments a call's data invokes
+    pub fn decode_call(&self, data: &[u8]) -> Result<(&AbiEntry, Value)> {
+        let selector = data.get(..4).ok_or_else(|| invalid("Call data shorter than a selector"))?;
+        let entry = self.of_kind("function")
+            .find(|entry| entry.selector().map_or(false, |s| s == selector))
+            .ok_or_else(|| invalid(format!("No function with selector {}", hex::encode(selector))))?;
+        let values = decode(&param_types(&entry.inputs)?, &data[4..])?;
+        Ok((entry, named(&entry.inputs, values)))
+    }
+
+    /// Return data of `function`
+    pub fn decode_output(&self, function: &str, data: &[u8]) -> Result<Value> {
+        let entry = self.function(function)?;
+        let values = decode(&param_types(&entry.outputs)?, data)?;
+        Ok(named(&entry.outputs, values))
+    }
+
+    /// Event name and fields of a log, if the ABI declares its event
+    pub fn decode_log(&self, topics: &[String], data: &[u8]) -> Result<Option<(String, Value)>> {
+        let Some(topic0) = topics.first() else { return Ok(None) };
+        let Some(event) = self.of_kind("event")
+            .find(|event| !event.anonymous && event.topic().map_or(false, |t| t == *topic0)) else {
+            return Ok(None);
+        };
+
+        let unindexed_types = event.inputs.iter()
+            .filter(|param| !param.indexed)
+            .map(ParamType::parse)
+            .collect::<Result<Vec<_>>>()?;
+        let mut data_values = decode(&unindexed_types, data)?.into_iter();
+
+        let mut fields = Map::new();
+        let mut indexed_topics = topics[1..].iter();
+        for (i, param) in event.inputs.iter().enumerate() {
+            let value = if param.indexed {
+                let topic = indexed_topics.next().ok_or_else(|| invalid("Log has fewer topics than indexed fields"))?;
+                let t = ParamType::parse(param)?;
+                // Dynamic indexed values are stored as their hash
+                if t.is_dynamic() {
+                    Value::String(topic.clone())
+                } else {
+                    let bytes = hex::decode(topic).map_err(|_| invalid("Topic is not hex"))?;
+                    decode_value(&t, &bytes)?
+                }
+            } else {
+                data_values.next().unwrap_or(Value::Null)
+            };
+            fields.insert(field_name(param, i), value);
+        }
+        Ok(Some((event.name.clone(), Value::Object(fields))))
+    }
+}
+
+fn field_name(param: &Param, index: usize) -> String {
+    if param.name.is_empty() { index.to_string() } else { param.name.clone() }
+}
+
+fn named(params: &[Param], values: Vec<Value>) -> Value {
+    Value::Object(params.iter().zip(values).enumerate().map(|(i, (param, value))| (field_name(param, i), value)).collect())
+}
+
+fn param_types(params: &[Param]) -> Result<Vec<ParamType>> {
+    params.iter().map(ParamType::parse).collect()
+}
+
+/// Solidity type of a parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamType {
+    Uint(usize),
+    Int(usize),
+    Address,
+    Bool,
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+impl ParamType {
+    pub fn parse(param: &Param) -> Result<Self> {
+        Self::parse_kind(&param.kind, &param.components)
+    }
+
+    fn parse_kind(kind: &str, components: &[Param]) -> Result<Self> {
+        let unknown = || invalid(format!("Unsupported ABI type {}", kind));
+        if let Some(stripped) = kind.strip_suffix(']') {
+            let open = stripped.rfind('[').ok_or_else(unknown)?;
+            let inner = Box::new(Self::parse_kind(&stripped[..open], components)?);
+            return match &stripped[open + 1..] {
+                "" => Ok(ParamType::Array(inner)),
+                size => size.parse().map(|size| ParamType::FixedArray(inner, size)).map_err(|_| unknown()),
+            };
+        }
+        let size = |prefix: &str| kind[prefix.len()..].parse::<usize>().map_err(|_| unknown());
+        match kind {
+            "address" => Ok(ParamType::Address),
+            "bool" => Ok(ParamType::Bool),
+            "string" => Ok(ParamType::String),
+            "bytes" => Ok(ParamType::Bytes),
+            "uint" => Ok(ParamType::Uint(256)),
+            "int" => Ok(ParamType::Int(256)),
+            "tuple" => components.iter().map(Self::parse).collect::<Result<_>>().map(ParamType::Tuple),
+            _ if kind.starts_with("uint") || kind.starts_with("int") => {
+                let bits = size(if kind.starts_with('u') { "uint" } else { "int" })?;
+                if bits == 0 || bits > 256 || bits % 8 != 0 {
+                    return Err(unknown());
+                }
+                Ok(if kind.starts_with('u') { ParamType::Uint(bits) } else { ParamType::Int(bits) })
+            }
+            _ if kind.starts_with("bytes") => match size("bytes")? {
+                len @ 1..=32 => Ok(ParamType::FixedBytes(len)),
+                _ => Err(unknown()),
+            },
+            _ => Err(unknown()),
+        }
+    }
+
+    /// Type as written in a signature
+    pub fn canonical(&self) -> String {
+        match self {
+            ParamType::Uint(bits) => format!("uint{}", bits),
+            ParamType::Int(bits) => format!("int{}", bits),
+            ParamType::Address => "address".to_string(),
+            ParamType::Bool => "bool".to_string(),
+            ParamType::FixedBytes(len) => format!("bytes{}", len),
+            ParamType::Bytes => "bytes".to_string(),
+            ParamType::String => "string".to_string(),
+            ParamType::Array(inner) => format!("{}[]", inner.canonical()),
+            ParamType::FixedArray(inner, size) => format!("{}[{}]", inner.canonical(), size),
+            ParamType::Tuple(types) => format!("({})", types.iter().map(|t| t.canonical()).collect::<Vec<_>>().join(",")),
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(inner, _) => inner.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(|t| t.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    /// Bytes the value takes in the head of an enclosing sequence
+    fn head_size(&self) -> usize {
+        match self {
+            _ if self.is_dynamic() => 32,
+            ParamType::FixedArray(inner, size) => inner.head_size() * size,
+            ParamType::Tuple(types) => types.iter().map(|t| t.head_size()).sum(),
+            _ => 32,
+        }
+    }
+}
+
+fn word(value: U256) -> [u8; 32] {
+    value.to_be_bytes::<32>()
+}
+
+fn padded(data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    out.resize(data.len().div_ceil(32) * 32, 0);
+    out
+}
+
+fn parse_number(value: &Value) -> Result<(bool, U256)> {
+    let text = match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.trim().to_string(),
+        _ => return Err(invalid(format!("Expected a number, got {}", value))),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.as_str()),
+    };
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex_digits) => U256::from_str_radix(hex_digits, 16),
+        None => U256::from_str_radix(digits, 10),
+    }
+    .map_err(|_| invalid(format!("Invalid number {}", text)))?;
+    Ok((negative, magnitude))
+}
+
+fn hex_value(value: &Value) -> Result<Vec<u8>> {
+    let text = value.as_str().ok_or_else(|| invalid(format!("Expected hex, got {}", value)))?;
+    hex::decode(text.trim_start_matches("0x")).map_err(|_| invalid(format!("Invalid hex {}", text)))
+}
+
+/// ABI encoding of `values` as a sequence of `types`
+pub fn encode(types: &[ParamType], values: &[Value]) -> Result<Vec<u8>> {
+    if types.len() != values.len() {
+        return Err(invalid(format!("Expected {} values, got {}", types.len(), values.len())));
+    }
+    let head_len: usize = types.iter().map(|t| t.head_size()).sum();
+    let mut head = Vec::with_capacity(head_len);
+    let mut tail = Vec::new();
+    for (t, value) in types.iter().zip(values) {
+        let encoded = encode_value(t, value)?;
+        if t.is_dynamic() {
+            head.extend_from_slice(&word(U256::from(head_len + tail.len())));
+            tail.extend(encoded);
+        } else {
+            head.extend(encoded);
+        }
+    }
+    head.extend(tail);
+    Ok(head)
+}
+
+fn encode_value(t: &ParamType, value: &Value) -> Result<Vec<u8>> {
+    let out_of_range = || invalid(format!("{} out of range for {}", value, t.canonical()));
+    match t {
+        ParamType::Uint(bits) => {
+            let (negative, magnitude) = parse_number(value)?;
+            if negative || magnitude.bit_len() > *bits {
+                return Err(out_of_range());
+            }
+            Ok(word(magnitude).to_vec())
+        }
+        ParamType::Int(bits) => {
+            let (negative, magnitude) = parse_number(value)?;
+            let limit = U256::from(1) << (bits - 1);
+            if (negative && magnitude > limit) || (!negative && magnitude >= limit) {
+                return Err(out_of_range());
+            }
+            Ok(word(if negative { U256::ZERO.wrapping_sub(magnitude) } else { magnitude }).to_vec())
+        }
+        ParamType::Address => {
+            let bytes = hex_value(value)?;
+            if bytes.len() != 20 {
+                return Err(invalid("Address must be 20 bytes"));
+            }
+            let mut out = vec![0u8; 12];
+            out.extend(bytes);
+            Ok(out)
+        }
+        ParamType::Bool => {
+            let flag = value.as_bool().ok_or_else(|| invalid(format!("Expected a bool, got {}", value)))?;
+            Ok(word(U256::from(flag as u8)).to_vec())
+        }
+        ParamType::FixedBytes(len) => {
+            let bytes = hex_value(value)?;
+            if bytes.len() != *len {
+                return Err(out_of_range());
+            }
+            Ok(padded(&bytes))
+        }
+        ParamType::Bytes | ParamType::String => {
+            let bytes = match t {
+                ParamType::String => value.as_str().ok_or_else(|| invalid(format!("Expected a string, got {}", value)))?.as_bytes().to_vec(),
+                _ => hex_value(value)?,
+            };
+            let mut out = word(U256::from(bytes.len())).to_vec();
+            out.extend(padded(&bytes));
+            Ok(out)
+        }
+        ParamType::Array(inner) => {
+            let items = value.as_array().ok_or_else(|| invalid(format!("Expected an array, got {}", value)))?;
+            let mut out = word(U256::from(items.len())).to_vec();
+            out.extend(encode(&vec![(**inner).clone(); items.len()], items)?);
+            Ok(out)
+        }
+        ParamType::FixedArray(inner, size) => {
+            let items = value.as_array().ok_or_else(|| invalid(format!("Expected an array, got {}", value)))?;
+            if items.len() != *size {
+                return Err(out_of_range());
+            }
+            encode(&vec![(**inner).clone(); *size], items)
+        }
+        ParamType::Tuple(types) => {
+            let items = value.as_array().ok_or_else(|| invalid(format!("Expected an array for a tuple, got {}", value)))?;
+            encode(types, items)
+        }
+    }
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<&[u8]> {
+    data.get(offset..offset + 32).ok_or_else(|| invalid("ABI data too short"))
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize> {
+    let value = U256::from_be_slice(read_word(data, offset)?);
+    usize::try_from(value).ok().filter(|v| *v <= data.len()).ok_or_else(|| invalid("ABI offset or length out of range"))
+}
+
+/// Decode a sequence of `types` from ABI data
+pub fn decode(types: &[ParamType], data: &[u8]) -> Result<Vec<Value>> {
+    let mut offset = 0;
+    let mut values = Vec::with_capacity(types.len());
+    for t in types {
+        if t.is_dynamic() {
+            let start = read_usize(data, offset)?;
+            values.push(decode_value(t, &data[start..])?);
+        } else {
+            values.push(decode_value(t, data.get(offset..).unwrap_or_default())?);
+        }
+        offset += t.head_size();
+    }
+    Ok(values)
+}
+
+fn decode_value(t: &ParamType, data: &[u8]) -> Result<Value> {
+    match t {
+        ParamType::Uint(_) => Ok(Value::String(U256::from_be_slice(read_word(data, 0)?).to_string())),
+        ParamType::Int(_) => {
+            let value = U256::from_be_slice(read_word(data, 0)?);
+            Ok(Value::String(if value.bit(255) {
+                format!("-{}", U256::ZERO.wrapping_sub(value))
+            } else {
+                value.to_string()
+            }))
+        }
+        ParamType::Address => Ok(Value::String(hex::encode(&read_word(data, 0)?[12..]))),
+        ParamType::Bool => Ok(Value::Bool(read_word(data, 0)?.iter().any(|b| *b != 0))),
+        ParamType::FixedBytes(len) => Ok(Value::String(hex::encode(&read_word(data, 0)?[..*len]))),
+        ParamType::Bytes | ParamType::String => {
+            let len = read_usize(data, 0)?;
+            let bytes = data.get(32..32 + len).ok_or_else(|| invalid("ABI data too short"))?;
+            Ok(Value::String(match t {
+                ParamType::String => String::from_utf8_lossy(bytes).into_owned(),
+                _ => hex::encode(bytes),
+            }))
+        }
+        ParamType::Array(inner) => {
+            let len = read_usize(data, 0)?;
+            // Every element needs at least one word, which bounds the length
+            if len * 32 > data.len() {
+                return Err(invalid("ABI array length out of range"));
+            }
+            decode(&vec![(**inner).clone(); len], &data[32..]).map(Value::Array)
+        }
+        ParamType::FixedArray(inner, size) => decode(&vec![(**inner).clone(); *size], data).map(Value::Array),
+        ParamType::Tuple(types) => decode(types, data).map(Value::Array),
+    }
+}
+
+/// JSON form of a decoded call
+pub fn call_json(entry: &AbiEntry, args: Value) -> Value {
+    json!({
+        "function": entry.name,
+        "signature": entry.signature().unwrap_or_default(),
+        "args": args,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERC20: &str = r#"[
+        {"type": "function", "name": "transfer", "stateMutability": "nonpayable",
+         "inputs": [{"name": "to", "type": "address"}, {"name": "amount", "type": "uint256"}],
+         "outputs": [{"name": "", "type": "bool"}]},
+        {"type": "function", "name": "setNames", "inputs": [{"name": "names", "type": "string[]"}, {"name": "id", "type": "int8"}]},
+        {"type": "event", "name": "Transfer", "anonymous": false,
+         "inputs": [{"name": "from", "type": "address", "indexed": true},
+                    {"name": "to", "type": "address", "indexed": true},
+                    {"name": "value", "type": "uint256", "indexed": false}]}
+    ]"#;
+
+    #[test]
+    fn test_encode_matches_solidity() {
+        let abi = Abi::from_json(ERC20).unwrap();
+        let to = "11".repeat(20);
+        let data = abi.encode_call("transfer", &[json!(to), json!(1000)]).unwrap();
+        // transfer(address,uint256) is 0xa9059cbb
+        assert_eq!(hex::encode(&data[..4]), "a9059cbb");
+        assert_eq!(data.len(), 4 + 64);
+        assert_eq!(&data[4 + 12..4 + 32], &[0x11; 20]);
+
+        let (entry, args) = abi.decode_call(&data).unwrap();
+        assert_eq!(entry.name, "transfer");
+        assert_eq!(args, json!({"to": to, "amount": "1000"}));
+        assert_eq!(abi.decode_output("transfer", &word(U256::from(1))).unwrap(), json!({"0": true}));
+
+        // Dynamic arrays of strings and negative integers round-trip
+        let data = abi.encode_call("setNames", &[json!(["a", "bc"]), json!(-5)]).unwrap();
+        let (_, args) = abi.decode_call(&data).unwrap();
+        assert_eq!(args, json!({"names": ["a", "bc"], "id": "-5"}));
+        assert!(abi.encode_call("setNames", &[json!([]), json!(128)]).is_err());
+    }
+
+    #[test]
+    fn test_decode_transfer_log() {
+        let abi = Abi::from_json(ERC20).unwrap();
+        let event = abi.entries.iter().find(|e| e.name == "Transfer").unwrap();
+        // keccak256("Transfer(address,address,uint256)")
+        assert_eq!(event.topic().unwrap(), "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+
+        let topics = vec![event.topic().unwrap(), format!("{:0>64}", "aa".repeat(20)), format!("{:0>64}", "bb".repeat(20))];
+        let (name, fields) = abi.decode_log(&topics, &word(U256::from(42))).unwrap().unwrap();
+        assert_eq!(name, "Transfer");
+        assert_eq!(fields, json!({"from": "aa".repeat(20), "to": "bb".repeat(20), "value": "42"}));
+        assert!(abi.decode_log(&["00".repeat(32)], &[]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! Address parsing and validation
+//!
+//! An address is a prefix naming its kind, `edu1q` for a single key and
+//! `edu3` for a script, followed by the base58 encoding of a 20-byte hash.
+//! Checksummed addresses append the first 4 bytes of the double SHA256 of
+//! prefix and hash before encoding, so a mistyped character is caught
+//! instead of sending funds to a hash nobody holds the key for. Addresses
+//! without the checksum stay valid, every wallet created so far uses them.
+//!
+//! Every place that takes an address from a user goes through
+//! `AddressCodec`, whose errors say exactly what is wrong with it.
+
+use crate::crypto::double_sha256;
+use crate::BlockchainError;
+use serde::Serialize;
+
+/// Bytes of the hash an address encodes
+pub const HASH_LEN: usize = 20;
+
+/// Bytes of the checksum a checksummed address appends
+pub const CHECKSUM_LEN: usize = 4;
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// What an address pays to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    /// Hash of a public key
+    P2pkh,
+    /// Hash of a redeem script
+    P2sh,
+}
+
+impl AddressKind {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            AddressKind::P2pkh => "edu1q",
+            AddressKind::P2sh => "edu3",
+        }
+    }
+}
+
+/// Why an address was rejected
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AddressError {
+    #[error("address is empty")]
+    Empty,
+
+    #[error("address '{0}' does not start with edu1q or edu3")]
+    UnknownPrefix(String),
+
+    #[error("invalid base58 character '{character}' at position {position}")]
+    InvalidCharacter { character: char, position: usize },
+
+    #[error("address encodes {0} bytes, expected {HASH_LEN} or {} with checksum", HASH_LEN + CHECKSUM_LEN)]
+    InvalidLength(usize),
+
+    #[error("address checksum does not match, it was probably mistyped")]
+    BadChecksum,
+}
+
+impl From<AddressError> for BlockchainError {
+    fn from(error: AddressError) -> Self {
+        BlockchainError::InvalidAddress(error.to_string())
+    }
+}
+
+/// A valid address, taken apart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedAddress {
+    pub kind: AddressKind,
+    pub hash: [u8; HASH_LEN],
+    pub checksummed: bool,
+}
+
+/// Encodes and validates addresses
+pub struct AddressCodec;
+
+impl AddressCodec {
+    /// Address of `hash` in the form wallets have always used, without a
+    /// checksum
+    pub fn encode(kind: AddressKind, hash: &[u8; HASH_LEN]) -> String {
+        format!("{}{}", kind.prefix(), bs58::encode(hash).into_string())
+    }
+
+    /// Address of `hash` carrying a checksum
+    pub fn encode_checked(kind: AddressKind, hash: &[u8; HASH_LEN]) -> String {
+        let mut payload = hash.to_vec();
+        payload.extend_from_slice(&checksum(kind, hash));
+        format!("{}{}", kind.prefix(), bs58::encode(payload).into_string())
+    }
+
+    /// Check an address and take it apart
+    pub fn decode(address: &str) -> Result<DecodedAddress, AddressError> {
+        let address = address.trim();
+        if address.is_empty() {
+            return Err(AddressError::Empty);
+        }
+        let (kind, encoded) = [AddressKind::P2pkh, AddressKind::P2sh]
+            .into_iter()
+            .find_map(|kind| address.strip_prefix(kind.prefix()).map(|rest| (kind, rest)))
+            .ok_or_else(|| AddressError::UnknownPrefix(address.chars().take(8).collect()))?;
+
+        if let Some((offset, character)) = encoded.char_indices().find(|(_, c)| !BASE58_ALPHABET.contains(*c)) {
+            return Err(AddressError::InvalidCharacter {
+                character,
+                position: kind.prefix().len() + offset,
+            });
+        }
+        let payload = bs58::decode(encoded).into_vec().map_err(|_| AddressError::InvalidLength(0))?;
+
+        let mut hash = [0u8; HASH_LEN];
+        match payload.len() {
+            HASH_LEN => {
+                hash.copy_from_slice(&payload);
+                Ok(DecodedAddress { kind, hash, checksummed: false })
+            }
+            len if len == HASH_LEN + CHECKSUM_LEN => {
+                hash.copy_from_slice(&payload[..HASH_LEN]);
+                if payload[HASH_LEN..] != checksum(kind, &hash) {
+                    return Err(AddressError::BadChecksum);
+                }
+                Ok(DecodedAddress { kind, hash, checksummed: true })
+            }
+            len => Err(AddressError::InvalidLength(len)),
+        }
+    }
+
+    /// Check an address, keeping the reason it is invalid
+    pub fn validate(address: &str) -> Result<AddressKind, AddressError> {
+        Self::decode(address).map(|decoded| decoded.kind)
+    }
+
+    /// Hash inside an address of `kind`
+    pub fn decode_kind(address: &str, kind: AddressKind) -> Result<[u8; HASH_LEN], AddressError> {
+        let decoded = Self::decode(address)?;
+        if decoded.kind != kind {
+            return Err(AddressError::UnknownPrefix(address.chars().take(8).collect()));
+        }
+        Ok(decoded.hash)
+    }
+}
+
+fn checksum(kind: AddressKind, hash: &[u8; HASH_LEN]) -> [u8; CHECKSUM_LEN] {
+    let mut data = kind.prefix().as_bytes().to_vec();
+    data.extend_from_slice(hash);
+    let digest = double_sha256(&data);
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&digest[..CHECKSUM_LEN]);
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_with_and_without_checksum() {
+        let hash = [0x5a; HASH_LEN];
+        for kind in [AddressKind::P2pkh, AddressKind::P2sh] {
+            let plain = AddressCodec::decode(&AddressCodec::encode(kind, &hash)).unwrap();
+            assert_eq!((plain.kind, plain.hash, plain.checksummed), (kind, hash, false));
+
+            let checked = AddressCodec::decode(&AddressCodec::encode_checked(kind, &hash)).unwrap();
+            assert_eq!((checked.kind, checked.hash, checked.checksummed), (kind, hash, true));
+        }
+    }
+
+    #[test]
+    fn test_errors_name_the_problem() {
+        assert_eq!(AddressCodec::decode(" "), Err(AddressError::Empty));
+        assert!(matches!(AddressCodec::decode("btc1qabc"), Err(AddressError::UnknownPrefix(_))));
+        assert_eq!(
+            AddressCodec::decode("edu1qTreasury0000"),
+            Err(AddressError::InvalidCharacter { character: '0', position: 13 })
+        );
+        assert!(matches!(AddressCodec::decode("edu1qabc"), Err(AddressError::InvalidLength(_))));
+
+        // One changed character in a checksummed address
+        let address = AddressCodec::encode_checked(AddressKind::P2pkh, &[7; HASH_LEN]);
+        let last = address.chars().last().unwrap();
+        let typo = format!("{}{}", &address[..address.len() - 1], if last == 'z' { 'y' } else { 'z' });
+        assert_eq!(AddressCodec::decode(&typo), Err(AddressError::BadChecksum));
+
+        let p2sh = AddressCodec::encode(AddressKind::P2sh, &[1; HASH_LEN]);
+        assert!(AddressCodec::decode_kind(&p2sh, AddressKind::P2pkh).is_err());
+    }
+}
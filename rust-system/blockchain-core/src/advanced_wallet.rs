@@ -2,15 +2,25 @@
 //! 
 //! Integrates HD wallets with the blockchain infrastructure, providing
 //! comprehensive wallet management, transaction creation, and key management.
+//!
+//! Wallets can also be defined by output descriptors (see `descriptor`):
+//! imported from `pkh(xpub.../0/*)` or `wsh(multi(...))` strings, they hand
+//! out and scan whatever outputs their templates describe. Every wallet
+//! exports its descriptors, HD accounts as `pkh` over the account xpub.
+//!
+//! A wallet can carry a spend policy (see `spend_policy`). Transactions
+//! breaking it are refused before anything is signed.
 
 use crate::{BlockchainError, Result, Hash256};
+use crate::descriptor::{Descriptor, DescriptorScan};
+use crate::spend_policy::SpendPolicy;
 use crate::hd_wallet::{HDWallet, HDAccount, TxBuildOptions, UTXOSelectionStrategy, WalletStatistics};
 use crate::wallet::{Wallet, WalletManager as SimpleWalletManager, WalletTransaction, TransactionStatus};
-use crate::transaction::Transaction;
-use crate::utxo::UTXOSet;
+use crate::transaction::{Transaction, P2PKH_SCRIPT_PUBKEY_SIZE};
+use crate::utxo::{Balance, UTXOSet};
 use crate::tx_builder::{TransactionManager, create_p2pkh_script};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet};
 use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -21,6 +31,8 @@ use tokio::sync::RwLock;
 pub struct AdvancedWalletManager {
     /// HD wallets (production-grade)
     hd_wallets: HashMap<Uuid, HDWallet>,
+    /// Wallets defined by output descriptors
+    descriptor_wallets: HashMap<Uuid, DescriptorWallet>,
     /// Simple wallets (legacy/compatibility)
     simple_wallets: SimpleWalletManager,
     /// Transaction manager for blockchain operations
@@ -54,6 +66,12 @@ pub struct WalletMetadata {
     pub last_sync_height: Option<u64>,
     /// Last sync timestamp
     pub last_sync_time: Option<DateTime<Utc>>,
+    /// User-assigned labels keyed by address
+    #[serde(default)]
+    pub address_labels: HashMap<String, String>,
+    /// Limits checked before the wallet signs a transaction
+    #[serde(default)]
+    pub spend_policy: Option<SpendPolicy>,
 }
 
 /// Type of wallet
@@ -69,6 +87,36 @@ pub enum WalletType {
     Hardware,
     /// Watch-only wallet
     WatchOnly,
+    /// Wallet defined by output descriptors
+    Descriptor,
+}
+
+/// A wallet defined by output descriptors instead of a seed
+#[derive(Debug, Clone)]
+pub struct DescriptorWallet {
+    pub id: Uuid,
+    pub name: String,
+    /// Receiving outputs
+    pub receive: Descriptor,
+    /// Change outputs; change goes to receiving outputs without one
+    pub change: Option<Descriptor>,
+    /// Next receiving index to hand out
+    pub next_index: u32,
+    /// Addresses handed out or found holding coins
+    pub addresses: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DescriptorWallet {
+    fn descriptors(&self) -> impl Iterator<Item = &Descriptor> {
+        std::iter::once(&self.receive).chain(self.change.as_ref())
+    }
+
+    fn remember(&mut self, address: String) {
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address);
+        }
+    }
 }
 
 /// Encryption status
@@ -127,6 +175,9 @@ pub struct WalletManagerSettings {
     pub backup_interval: u32,
     /// Maximum fee rate protection
     pub max_fee_rate: u64,
+    /// Never combine UTXOs from differently labelled addresses in one transaction
+    #[serde(default)]
+    pub avoid_label_mixing: bool,
 }
 
 /// Transaction preparation result
@@ -169,11 +220,58 @@ pub struct WalletRestoreOptions {
     pub rescan: bool,
 }
 
+/// Outputs that are an exact multiple of this many satoshis (0.01 EDU) are
+/// treated as round-number payments
+pub const ROUND_AMOUNT_GRANULARITY: u64 = 1_000_000;
+
+/// Number of distinct wallet addresses merged by one transaction at which the
+/// spend is reported as a consolidation
+pub const CONSOLIDATION_ADDRESS_THRESHOLD: usize = 3;
+
+/// A privacy leak found by the wallet audit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PrivacyIssue {
+    /// The same address has received more than one payment
+    AddressReuse { address: String, receive_count: usize },
+    /// A round amount makes the payment output (and so the change) easy to spot
+    RoundNumberOutput { outpoint: String, amount: u64 },
+    /// Many wallet addresses were merged into a single transaction
+    Consolidation { input_count: usize, address_count: usize },
+    /// Coins carrying different labels were spent together
+    LabelMixing { labels: Vec<String> },
+}
+
+impl PrivacyIssue {
+    /// Score penalty for this issue (out of 100)
+    pub fn penalty(&self) -> u8 {
+        match self {
+            PrivacyIssue::AddressReuse { .. } => 10,
+            PrivacyIssue::RoundNumberOutput { .. } => 5,
+            PrivacyIssue::Consolidation { .. } => 15,
+            PrivacyIssue::LabelMixing { .. } => 20,
+        }
+    }
+}
+
+/// Result of a wallet privacy audit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyReport {
+    /// Audited wallet
+    pub wallet_id: Uuid,
+    /// Issues found, in discovery order
+    pub issues: Vec<PrivacyIssue>,
+    /// Privacy score from 0 (poor) to 100 (no issues found)
+    pub score: u8,
+    /// When the audit was run
+    pub generated_at: DateTime<Utc>,
+}
+
 impl AdvancedWalletManager {
     /// Create a new advanced wallet manager
     pub fn new() -> Self {
         Self {
             hd_wallets: HashMap::new(),
+            descriptor_wallets: HashMap::new(),
             simple_wallets: SimpleWalletManager::new(),
             transaction_manager: None,
             wallet_metadata: HashMap::new(),
@@ -204,7 +302,9 @@ impl AdvancedWalletManager {
         let metadata = WalletMetadata {
             wallet_id,
             wallet_type: WalletType::HD,
-            is_default: self.hd_wallets.is_empty() && self.simple_wallets.list_wallets().is_empty(),
+            is_default: self.hd_wallets.is_empty()
+                && self.descriptor_wallets.is_empty()
+                && self.simple_wallets.list_wallets().is_empty(),
             last_backup: None,
             encryption_status: EncryptionStatus::None,
             sync_status: SyncStatus {
@@ -223,6 +323,8 @@ impl AdvancedWalletManager {
             last_balance: None,
             last_sync_height: None,
             last_sync_time: None,
+            address_labels: HashMap::new(),
+            spend_policy: None,
         };
 
         self.hd_wallets.insert(wallet_id, wallet);
@@ -231,15 +333,26 @@ impl AdvancedWalletManager {
         Ok(wallet_id)
     }
 
-    /// Restore HD wallet from mnemonic
-    pub fn restore_hd_wallet(&mut self, options: WalletRestoreOptions) -> Result<Uuid> {
-        let wallet = HDWallet::from_mnemonic(
+    /// Restore HD wallet from mnemonic. With a transaction manager set, the
+    /// accounts the wallet used are recreated from addresses holding UTXOs.
+    pub async fn restore_hd_wallet(&mut self, options: WalletRestoreOptions) -> Result<Uuid> {
+        let mut wallet = HDWallet::from_mnemonic(
             options.name,
             &options.mnemonic,
             options.passphrase.as_deref(),
         )?;
         let wallet_id = wallet.id;
 
+        if let Some(tx_manager) = &self.transaction_manager {
+            let tx_manager = tx_manager.read().await;
+            let funded: HashSet<String> = tx_manager.get_utxo_set().get_all_addresses().into_iter().collect();
+            wallet.discover_accounts(
+                |address| funded.contains(address),
+                options.gap_limit,
+                options.account_discovery_limit,
+            )?;
+        }
+
         // Create metadata
         let metadata = WalletMetadata {
             wallet_id,
@@ -263,6 +376,8 @@ impl AdvancedWalletManager {
             last_balance: None,
             last_sync_height: None,
             last_sync_time: None,
+            address_labels: HashMap::new(),
+            spend_policy: None,
         };
 
         self.hd_wallets.insert(wallet_id, wallet);
@@ -276,6 +391,84 @@ impl AdvancedWalletManager {
         Ok(wallet_id)
     }
 
+    /// Import a wallet defined by a receiving descriptor and optionally a
+    /// change descriptor. Descriptors without private keys make a watch-only
+    /// wallet.
+    pub fn import_descriptors(&mut self, name: String, receive: &str, change: Option<&str>) -> Result<Uuid> {
+        let receive = Descriptor::parse(receive)?;
+        let change = change.map(Descriptor::parse).transpose()?;
+        let wallet_id = Uuid::new_v4();
+
+        let metadata = WalletMetadata {
+            wallet_id,
+            wallet_type: WalletType::Descriptor,
+            is_default: false,
+            last_backup: None,
+            encryption_status: EncryptionStatus::None,
+            sync_status: SyncStatus {
+                last_sync: None,
+                synced_height: 0,
+                is_syncing: false,
+                sync_progress: 0,
+            },
+            usage_stats: UsageStatistics::default(),
+            last_balance: None,
+            last_sync_height: None,
+            last_sync_time: None,
+            address_labels: HashMap::new(),
+            spend_policy: None,
+        };
+
+        self.descriptor_wallets.insert(wallet_id, DescriptorWallet {
+            id: wallet_id,
+            name,
+            receive,
+            change,
+            next_index: 0,
+            addresses: Vec::new(),
+            created_at: Utc::now(),
+        });
+        self.wallet_metadata.insert(wallet_id, metadata);
+
+        Ok(wallet_id)
+    }
+
+    /// Descriptors that define a wallet: receiving then change. HD wallets
+    /// give a pair per account over its extended key, private only when
+    /// `include_private_keys` is set.
+    pub fn export_descriptors(&self, wallet_id: Uuid, include_private_keys: bool) -> Result<Vec<String>> {
+        if let Some(hd_wallet) = self.hd_wallets.get(&wallet_id) {
+            let mut accounts: Vec<&HDAccount> = hd_wallet.accounts.values().collect();
+            accounts.sort_by_key(|account| account.account_index);
+
+            let mut descriptors = Vec::new();
+            for account in accounts {
+                let key = if include_private_keys { &account.account_xpriv } else { &account.account_xpub };
+                let key = key.serialize()?;
+                descriptors.push(format!("pkh({}/0/*)", key));
+                descriptors.push(format!("pkh({}/1/*)", key));
+            }
+            return Ok(descriptors);
+        }
+
+        let wallet = self.descriptor_wallets.get(&wallet_id)
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_id.to_string()))?;
+        wallet.descriptors()
+            .map(|descriptor| {
+                if include_private_keys {
+                    Ok(descriptor.to_string())
+                } else {
+                    descriptor.to_public().map(|public| public.to_string())
+                }
+            })
+            .collect()
+    }
+
+    /// Get descriptor wallet
+    pub fn get_descriptor_wallet(&self, wallet_id: Uuid) -> Option<&DescriptorWallet> {
+        self.descriptor_wallets.get(&wallet_id)
+    }
+
     /// Create a simple wallet (legacy support)
     pub fn create_simple_wallet(&mut self, name: String) -> Result<Uuid> {
         let wallet = self.simple_wallets.create_wallet(name)?;
@@ -303,6 +496,8 @@ impl AdvancedWalletManager {
             last_balance: None,
             last_sync_height: None,
             last_sync_time: None,
+            address_labels: HashMap::new(),
+            spend_policy: None,
         };
 
         self.wallet_metadata.insert(wallet_id, metadata);
@@ -326,7 +521,7 @@ impl AdvancedWalletManager {
                 name: wallet.name.clone(),
                 wallet_type: WalletType::HD,
                 is_default: metadata.map(|m| m.is_default).unwrap_or(false),
-                balance: 0, // Would be calculated from UTXO set
+                balance: Balance::default(), // Would be calculated from UTXO set
                 account_count: wallet.accounts.len() as u32,
                 address_count: wallet.accounts.values()
                     .map(|acc| acc.external_addresses.len() + acc.change_addresses.len())
@@ -336,6 +531,22 @@ impl AdvancedWalletManager {
             });
         }
 
+        // Descriptor wallets
+        for (id, wallet) in &self.descriptor_wallets {
+            let metadata = self.wallet_metadata.get(id);
+            summaries.push(WalletSummary {
+                id: *id,
+                name: wallet.name.clone(),
+                wallet_type: WalletType::Descriptor,
+                is_default: metadata.map(|m| m.is_default).unwrap_or(false),
+                balance: Balance::default(),
+                account_count: 1,
+                address_count: wallet.addresses.len() as u32,
+                created_at: wallet.created_at,
+                last_sync: metadata.and_then(|m| m.sync_status.last_sync),
+            });
+        }
+
         // Simple wallets
         for wallet in self.simple_wallets.list_wallets() {
             summaries.push(WalletSummary {
@@ -408,14 +619,39 @@ impl AdvancedWalletManager {
         Err(BlockchainError::WalletNotFound(wallet_id.to_string()))
     }
 
-    /// Build and sign transaction
+    /// Build and sign transaction, refused if it breaks the wallet's spend
+    /// policy
     pub async fn build_transaction(
         &mut self,
         wallet_id: Uuid,
         outputs: Vec<(String, u64)>,
         options: Option<TxBuildOptions>,
     ) -> Result<Transaction> {
-        let options = options.unwrap_or_default();
+        self.build_checked_transaction(wallet_id, outputs, options, false).await
+    }
+
+    /// Build and sign a transaction a co-signer has approved, so the spend
+    /// policy's co-signer threshold doesn't apply
+    pub async fn build_cosigned_transaction(
+        &mut self,
+        wallet_id: Uuid,
+        outputs: Vec<(String, u64)>,
+        options: Option<TxBuildOptions>,
+    ) -> Result<Transaction> {
+        self.build_checked_transaction(wallet_id, outputs, options, true).await
+    }
+
+    async fn build_checked_transaction(
+        &mut self,
+        wallet_id: Uuid,
+        outputs: Vec<(String, u64)>,
+        options: Option<TxBuildOptions>,
+        cosigned: bool,
+    ) -> Result<Transaction> {
+        let mut options = options.unwrap_or_default();
+        if self.hd_wallets.contains_key(&wallet_id) {
+            self.check_spend_policy(wallet_id, &outputs, cosigned)?;
+        }
 
         if let Some(hd_wallet) = self.hd_wallets.get_mut(&wallet_id) {
             let account_index = 0; // Use default account
@@ -423,6 +659,23 @@ impl AdvancedWalletManager {
             if let Some(tx_manager) = &self.transaction_manager {
                 let tx_manager = tx_manager.read().await;
                 let utxo_set = tx_manager.get_utxo_set();
+
+                // Keep differently labelled coins apart when requested
+                if self.settings.avoid_label_mixing && options.allowed_addresses.is_none() {
+                    let addresses = hd_wallet.get_account(account_index)
+                        .ok_or(BlockchainError::AccountNotFound(account_index))?
+                        .get_all_addresses();
+                    let labels = self.wallet_metadata.get(&wallet_id).map(|m| &m.address_labels);
+                    let total_output: u64 = outputs.iter().map(|(_, amount)| amount).sum();
+                    options.allowed_addresses = Some(Self::select_single_label_addresses(
+                        &addresses,
+                        labels,
+                        utxo_set,
+                        total_output,
+                        outputs.len(),
+                        options.fee_rate,
+                    )?);
+                }
                 
                 let transaction = hd_wallet.build_transaction(
                     account_index,
@@ -463,6 +716,19 @@ impl AdvancedWalletManager {
 
     /// Generate new receiving address
     pub fn generate_address(&mut self, wallet_id: Uuid, account_index: Option<u32>) -> Result<String> {
+        if let Some(wallet) = self.descriptor_wallets.get_mut(&wallet_id) {
+            let output = wallet.receive.derive(wallet.next_index)?;
+            if wallet.receive.is_ranged() {
+                wallet.next_index += 1;
+            }
+            wallet.remember(output.address.clone());
+
+            if let Some(metadata) = self.wallet_metadata.get_mut(&wallet_id) {
+                metadata.usage_stats.addresses_generated += 1;
+            }
+            return Ok(output.address);
+        }
+
         if let Some(hd_wallet) = self.hd_wallets.get_mut(&wallet_id) {
             let account_idx = account_index.unwrap_or(0);
             
@@ -496,6 +762,14 @@ impl AdvancedWalletManager {
     }
 
     pub async fn get_wallet_balance(&self, wallet_id: Uuid) -> Result<u64> {
+        if let Some(wallet) = self.descriptor_wallets.get(&wallet_id) {
+            if let Some(tx_manager) = &self.transaction_manager {
+                let tx_manager = tx_manager.read().await;
+                let scans = Self::scan_descriptor_wallet(wallet, tx_manager.get_utxo_set(), self.settings.gap_limit)?;
+                return Ok(scans.iter().map(|scan| scan.balance).sum());
+            }
+        }
+
         if let Some(hd_wallet) = self.hd_wallets.get(&wallet_id) {
             if let Some(tx_manager) = &self.transaction_manager {
                 let tx_manager = tx_manager.read().await;
@@ -516,7 +790,7 @@ impl AdvancedWalletManager {
 
     /// Sync wallet with blockchain
     pub async fn sync_wallet(&mut self, wallet_id: Uuid) -> Result<()> {
-        if let Some(_) = self.hd_wallets.get(&wallet_id) {
+        if self.hd_wallets.contains_key(&wallet_id) || self.descriptor_wallets.contains_key(&wallet_id) {
             // Update sync status
             if let Some(metadata) = self.wallet_metadata.get_mut(&wallet_id) {
                 metadata.sync_status.is_syncing = true;
@@ -542,6 +816,22 @@ impl AdvancedWalletManager {
 
     /// Discover addresses with gap limit
     async fn discover_addresses(&mut self, wallet_id: Uuid) -> Result<()> {
+        if let Some(wallet) = self.descriptor_wallets.get_mut(&wallet_id) {
+            if let Some(tx_manager) = &self.transaction_manager {
+                let tx_manager = tx_manager.read().await;
+                let scans = Self::scan_descriptor_wallet(wallet, tx_manager.get_utxo_set(), self.settings.gap_limit)?;
+
+                // Receiving outputs already used are not handed out again
+                if let Some(receive) = scans.first() {
+                    wallet.next_index = wallet.next_index.max(receive.next_index);
+                }
+                for output in scans.into_iter().flat_map(|scan| scan.funded) {
+                    wallet.remember(output.address);
+                }
+            }
+            return Ok(());
+        }
+
         if let Some(hd_wallet) = self.hd_wallets.get_mut(&wallet_id) {
             if let Some(tx_manager) = &self.transaction_manager {
                 let tx_manager = tx_manager.read().await;
@@ -575,6 +865,19 @@ impl AdvancedWalletManager {
 
     /// Update wallet balances
     async fn update_balances(&mut self, wallet_id: Uuid) -> Result<()> {
+        if let Some(wallet) = self.descriptor_wallets.get(&wallet_id) {
+            if let Some(tx_manager) = &self.transaction_manager {
+                let tx_manager = tx_manager.read().await;
+                let scans = Self::scan_descriptor_wallet(wallet, tx_manager.get_utxo_set(), self.settings.gap_limit)?;
+                if let Some(metadata) = self.wallet_metadata.get_mut(&wallet_id) {
+                    metadata.last_balance = Some(scans.iter().map(|scan| scan.balance).sum());
+                    metadata.last_sync_height = Some(tx_manager.get_current_height());
+                    metadata.last_sync_time = Some(chrono::Utc::now());
+                }
+            }
+            return Ok(());
+        }
+
         if let Some(hd_wallet) = self.hd_wallets.get(&wallet_id) {
             if let Some(tx_manager) = &self.transaction_manager {
                 let tx_manager = tx_manager.read().await;
@@ -639,12 +942,155 @@ impl AdvancedWalletManager {
                     None
                 },
                 accounts: hd_wallet.accounts.keys().cloned().collect(),
+                descriptors: self.export_descriptors(wallet_id, include_private_keys)?,
             })
         } else {
             Err(BlockchainError::WalletNotFound(wallet_id.to_string()))
         }
     }
 
+    /// Assign a label to one of a wallet's addresses
+    pub fn set_address_label(&mut self, wallet_id: Uuid, address: &str, label: String) -> Result<()> {
+        if !self.wallet_addresses(wallet_id)?.iter().any(|a| a == address) {
+            return Err(BlockchainError::InvalidAddress(format!(
+                "Address {} does not belong to wallet {}", address, wallet_id
+            )));
+        }
+
+        let metadata = self.wallet_metadata.get_mut(&wallet_id)
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_id.to_string()))?;
+        metadata.address_labels.insert(address.to_string(), label);
+        Ok(())
+    }
+
+    /// Get the label assigned to an address, if any
+    pub fn get_address_label(&self, wallet_id: Uuid, address: &str) -> Option<&String> {
+        self.wallet_metadata.get(&wallet_id)?.address_labels.get(address)
+    }
+
+    /// Set or, with None, remove a wallet's spend policy
+    pub fn set_spend_policy(&mut self, wallet_id: Uuid, policy: Option<SpendPolicy>) -> Result<()> {
+        let metadata = self.wallet_metadata.get_mut(&wallet_id)
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_id.to_string()))?;
+        metadata.spend_policy = policy;
+        Ok(())
+    }
+
+    /// The wallet's spend policy, if it has one
+    pub fn get_spend_policy(&self, wallet_id: Uuid) -> Option<&SpendPolicy> {
+        self.wallet_metadata.get(&wallet_id)?.spend_policy.as_ref()
+    }
+
+    /// Fail with `PolicyViolation` listing every rule paying `outputs` from
+    /// the wallet now would break
+    pub fn check_spend_policy(&self, wallet_id: Uuid, outputs: &[(String, u64)], cosigned: bool) -> Result<()> {
+        let metadata = self.wallet_metadata.get(&wallet_id)
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_id.to_string()))?;
+        match &metadata.spend_policy {
+            Some(policy) => policy.check(outputs, Utc::now(), cosigned),
+            None => Ok(()),
+        }
+    }
+
+    /// Audit a wallet for address reuse, round-number outputs and
+    /// consolidations or label mixing in its pending transactions
+    pub async fn audit_wallet_privacy(&self, wallet_id: Uuid) -> Result<PrivacyReport> {
+        let addresses = self.wallet_addresses(wallet_id)?;
+        let tx_manager = self.transaction_manager.as_ref().ok_or_else(|| {
+            BlockchainError::WalletError("Wallet manager is not connected to a blockchain".to_string())
+        })?;
+        let tx_manager = tx_manager.read().await;
+        let utxo_set = tx_manager.get_utxo_set();
+
+        let mut issues = Vec::new();
+        for address in &addresses {
+            let utxos = utxo_set.get_utxos_for_address(address);
+            if utxos.len() > 1 {
+                issues.push(PrivacyIssue::AddressReuse {
+                    address: address.clone(),
+                    receive_count: utxos.len(),
+                });
+            }
+            for utxo in utxos {
+                if is_round_amount(utxo.value()) {
+                    issues.push(PrivacyIssue::RoundNumberOutput {
+                        outpoint: utxo.get_outpoint(),
+                        amount: utxo.value(),
+                    });
+                }
+            }
+        }
+
+        for pending in tx_manager.get_pending_transactions() {
+            issues.extend(self.analyze_transaction_privacy(wallet_id, &pending.transaction, utxo_set)?);
+        }
+
+        let penalty: u32 = issues.iter().map(|issue| issue.penalty() as u32).sum();
+        Ok(PrivacyReport {
+            wallet_id,
+            issues,
+            score: 100u32.saturating_sub(penalty) as u8,
+            generated_at: Utc::now(),
+        })
+    }
+
+    /// Inspect a transaction spending this wallet's coins for privacy leaks.
+    /// Transactions that spend none of the wallet's coins yield no issues.
+    pub fn analyze_transaction_privacy(
+        &self,
+        wallet_id: Uuid,
+        tx: &Transaction,
+        utxo_set: &UTXOSet,
+    ) -> Result<Vec<PrivacyIssue>> {
+        let wallet_addresses = self.wallet_addresses(wallet_id)?;
+        let labels = self.wallet_metadata.get(&wallet_id).map(|m| &m.address_labels);
+
+        let input_addresses: BTreeSet<String> = tx.inputs.iter()
+            .filter_map(|input| {
+                let outpoint = format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index);
+                utxo_set.get_utxo(&outpoint).and_then(|utxo| utxo.get_address())
+            })
+            .filter(|address| wallet_addresses.contains(address))
+            .collect();
+
+        let mut issues = Vec::new();
+        if input_addresses.is_empty() {
+            return Ok(issues);
+        }
+
+        if input_addresses.len() >= CONSOLIDATION_ADDRESS_THRESHOLD {
+            issues.push(PrivacyIssue::Consolidation {
+                input_count: tx.inputs.len(),
+                address_count: input_addresses.len(),
+            });
+        }
+
+        let input_labels: BTreeSet<String> = input_addresses.iter()
+            .filter_map(|address| labels.and_then(|l| l.get(address)).cloned())
+            .collect();
+        if input_labels.len() > 1 {
+            issues.push(PrivacyIssue::LabelMixing { labels: input_labels.into_iter().collect() });
+        }
+
+        let tx_hash = hex::encode(tx.get_hash()?);
+        for (index, output) in tx.outputs.iter().enumerate() {
+            let Some(address) = output.get_address() else { continue };
+            if input_addresses.contains(&address) {
+                issues.push(PrivacyIssue::AddressReuse { address, receive_count: 2 });
+            } else if tx.outputs.len() > 1
+                && !wallet_addresses.contains(&address)
+                && is_round_amount(output.value)
+            {
+                issues.push(PrivacyIssue::RoundNumberOutput {
+                    outpoint: format!("{}:{}", tx_hash, index),
+                    amount: output.value,
+                });
+            }
+        }
+
+        Ok(issues)
+    }
+
     /// Update wallet settings
     pub fn update_settings(&mut self, settings: WalletManagerSettings) {
         self.settings = settings;
@@ -670,16 +1116,66 @@ impl AdvancedWalletManager {
         Ok(utxos)
     }
 
+    /// All addresses derived by an HD wallet, across accounts, or handed out
+    /// by a descriptor wallet
+    fn wallet_addresses(&self, wallet_id: Uuid) -> Result<Vec<String>> {
+        if let Some(wallet) = self.descriptor_wallets.get(&wallet_id) {
+            return Ok(wallet.addresses.clone());
+        }
+        let hd_wallet = self.hd_wallets.get(&wallet_id)
+            .ok_or_else(|| BlockchainError::WalletNotFound(wallet_id.to_string()))?;
+        Ok(hd_wallet.accounts.values().flat_map(|account| account.get_all_addresses()).collect())
+    }
+
+    /// Scan each of a descriptor wallet's descriptors, receiving first
+    fn scan_descriptor_wallet(wallet: &DescriptorWallet, utxo_set: &UTXOSet, gap_limit: u32) -> Result<Vec<DescriptorScan>> {
+        wallet.descriptors().map(|descriptor| descriptor.scan(utxo_set, gap_limit)).collect()
+    }
+
+    /// Pick the addresses of a single label group able to fund `target`
+    /// satoshis plus fees. Unlabelled addresses form their own group; the
+    /// smallest sufficient group is preferred so large clusters stay untouched.
+    fn select_single_label_addresses(
+        addresses: &[String],
+        labels: Option<&HashMap<String, String>>,
+        utxo_set: &UTXOSet,
+        target: u64,
+        output_count: usize,
+        fee_rate: u64,
+    ) -> Result<Vec<String>> {
+        let mut groups: BTreeMap<Option<String>, (Vec<String>, usize, u64)> = BTreeMap::new();
+        for address in addresses {
+            let label = labels.and_then(|l| l.get(address)).cloned();
+            let utxos = utxo_set.get_utxos_for_address(address);
+            let group = groups.entry(label).or_default();
+            group.0.push(address.clone());
+            group.1 += utxos.len();
+            group.2 += utxos.iter().map(|utxo| utxo.value()).sum::<u64>();
+        }
+
+        groups.into_values()
+            .filter(|(_, utxo_count, value)| {
+                let size = Transaction::estimate_p2pkh_vsize(*utxo_count, vec![P2PKH_SCRIPT_PUBKEY_SIZE; output_count + 1]);
+                *value >= target + size as u64 * fee_rate
+            })
+            .min_by_key(|(_, _, value)| *value)
+            .map(|(addresses, _, _)| addresses)
+            .ok_or_else(|| BlockchainError::InsufficientFunds(
+                "No single address label holds enough funds; disable label mixing protection to combine them".to_string()
+            ))
+    }
+
     /// Estimate transaction size
     fn estimate_transaction_size(&self, input_count: usize, output_count: usize) -> u64 {
-        // Base transaction: 10 bytes
-        // Each input: ~148 bytes
-        // Each output: ~34 bytes
-        let size = 10 + (input_count * 148) + (output_count * 34);
-        size as u64
+        Transaction::estimate_p2pkh_vsize(input_count, vec![P2PKH_SCRIPT_PUBKEY_SIZE; output_count]) as u64
     }
 }
 
+/// Check whether an amount is a round number of coins
+fn is_round_amount(amount: u64) -> bool {
+    amount > 0 && amount % ROUND_AMOUNT_GRANULARITY == 0
+}
+
 /// Wallet summary for listing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletSummary {
@@ -687,7 +1183,7 @@ pub struct WalletSummary {
     pub name: String,
     pub wallet_type: WalletType,
     pub is_default: bool,
-    pub balance: u64,
+    pub balance: Balance,
     pub account_count: u32,
     pub address_count: u32,
     pub created_at: DateTime<Utc>,
@@ -705,6 +1201,9 @@ pub struct WalletExport {
     pub master_xpriv: Option<String>,
     pub mnemonic: Option<String>,
     pub accounts: Vec<u32>,
+    /// Receiving and change descriptor of each account
+    #[serde(default)]
+    pub descriptors: Vec<String>,
 }
 
 impl Default for WalletManagerSettings {
@@ -717,6 +1216,7 @@ impl Default for WalletManagerSettings {
             gap_limit: 20,
             backup_interval: 24,
             max_fee_rate: 10000,
+            avoid_label_mixing: false,
         }
     }
 }
@@ -749,8 +1249,8 @@ mod tests {
         assert_eq!(wallets[0].name, "Test HD Wallet");
     }
 
-    #[test]
-    fn test_wallet_restore() {
+    #[tokio::test]
+    async fn test_wallet_restore() {
         let mut manager = AdvancedWalletManager::new();
         
         // Create original wallet to get mnemonic
@@ -768,7 +1268,7 @@ mod tests {
             rescan: false,
         };
         
-        let restored_id = manager.restore_hd_wallet(restore_options).unwrap();
+        let restored_id = manager.restore_hd_wallet(restore_options).await.unwrap();
         assert_ne!(original_id, restored_id); // Different IDs
         
         let wallets = manager.list_all_wallets();
@@ -788,4 +1288,156 @@ mod tests {
         let address = manager.generate_address(wallet_id, Some(account_index)).unwrap();
         assert!(address.starts_with("edu1q"));
     }
+
+    #[test]
+    fn test_descriptor_import_and_export() {
+        let mut manager = AdvancedWalletManager::new();
+        let hd_id = manager.create_hd_wallet("HD".to_string(), Some([7u8; 32])).unwrap();
+        manager.create_account(hd_id, "Main".to_string()).unwrap();
+
+        let exported = manager.export_descriptors(hd_id, false).unwrap();
+        assert_eq!(exported.len(), 2);
+        assert!(exported[0].starts_with("pkh(xpub") && exported[0].ends_with("/0/*)"));
+        assert_eq!(manager.export_wallet(hd_id, false).unwrap().descriptors, exported);
+
+        // A watch-only wallet from the exported descriptors hands out the same addresses
+        let watch_id = manager.import_descriptors("Watch".to_string(), &exported[0], Some(&exported[1])).unwrap();
+        for _ in 0..3 {
+            assert_eq!(
+                manager.generate_address(watch_id, None).unwrap(),
+                manager.generate_address(hd_id, Some(0)).unwrap()
+            );
+        }
+        assert_eq!(manager.export_descriptors(watch_id, false).unwrap(), exported);
+        assert_eq!(manager.wallet_addresses(watch_id).unwrap().len(), 3);
+
+        assert!(manager.import_descriptors("Bad".to_string(), "pkh(notakey/0/*)", None).is_err());
+        assert_eq!(manager.list_all_wallets().len(), 2);
+    }
+
+    /// Output script that `TransactionOutput::get_address` maps back to `address`
+    fn address_script(address: &str) -> Vec<u8> {
+        let mut script = vec![0x76, 0xa9, address.len() as u8];
+        script.extend_from_slice(address.as_bytes());
+        script.extend_from_slice(&[0x88, 0xac]);
+        script
+    }
+
+    fn fund(utxo_set: &mut UTXOSet, seed: u8, address: &str, value: u64) -> crate::utxo::UTXO {
+        let output = crate::transaction::TransactionOutput::new(value, address_script(address));
+        let utxo = crate::utxo::UTXO::new([seed; 32], 0, output, 1, false);
+        utxo_set.add_utxo([seed; 32], 0, utxo.clone()).unwrap();
+        utxo
+    }
+
+    #[tokio::test]
+    async fn test_privacy_audit_flags_reuse_and_round_amounts() {
+        let mut manager = AdvancedWalletManager::new();
+        let wallet_id = manager.create_hd_wallet("Audit".to_string(), Some([3u8; 32])).unwrap();
+        manager.create_account(wallet_id, "Main".to_string()).unwrap();
+        let address = manager.generate_address(wallet_id, Some(0)).unwrap();
+
+        let mut utxo_set = UTXOSet::new();
+        fund(&mut utxo_set, 1, &address, 100_000_000);
+        fund(&mut utxo_set, 2, &address, 12_345);
+        manager.set_transaction_manager(Arc::new(RwLock::new(TransactionManager::new(utxo_set))));
+
+        let report = manager.audit_wallet_privacy(wallet_id).await.unwrap();
+        assert!(report.issues.contains(&PrivacyIssue::AddressReuse { address: address.clone(), receive_count: 2 }));
+        let round: Vec<_> = report.issues.iter()
+            .filter(|issue| matches!(issue, PrivacyIssue::RoundNumberOutput { amount: 100_000_000, .. }))
+            .collect();
+        assert_eq!(round.len(), 1);
+        assert_eq!(report.score, 85);
+    }
+
+    #[test]
+    fn test_transaction_privacy_consolidation_and_label_mixing() {
+        let mut manager = AdvancedWalletManager::new();
+        let wallet_id = manager.create_hd_wallet("Labels".to_string(), Some([4u8; 32])).unwrap();
+        manager.create_account(wallet_id, "Main".to_string()).unwrap();
+        let addresses: Vec<String> = (0..3)
+            .map(|_| manager.generate_address(wallet_id, Some(0)).unwrap())
+            .collect();
+        manager.set_address_label(wallet_id, &addresses[0], "salary".to_string()).unwrap();
+        manager.set_address_label(wallet_id, &addresses[1], "exchange".to_string()).unwrap();
+        assert!(manager.set_address_label(wallet_id, "edu1qforeign", "x".to_string()).is_err());
+
+        let mut utxo_set = UTXOSet::new();
+        let mut tx = Transaction::new(1, Vec::new(), Vec::new());
+        for (i, address) in addresses.iter().enumerate() {
+            let utxo = fund(&mut utxo_set, 10 + i as u8, address, 50_000);
+            tx.inputs.push(crate::transaction::TransactionInput::new(utxo.tx_hash, utxo.output_index, Vec::new()));
+        }
+        tx.outputs.push(crate::transaction::TransactionOutput::new(140_000, address_script("edu1qmerchant")));
+
+        let issues = manager.analyze_transaction_privacy(wallet_id, &tx, &utxo_set).unwrap();
+        assert!(issues.contains(&PrivacyIssue::Consolidation { input_count: 3, address_count: 3 }));
+        assert!(issues.contains(&PrivacyIssue::LabelMixing {
+            labels: vec!["exchange".to_string(), "salary".to_string()],
+        }));
+    }
+
+    #[test]
+    fn test_label_group_selection() {
+        let mut utxo_set = UTXOSet::new();
+        fund(&mut utxo_set, 1, "edu1qsavings", 5_000_000);
+        fund(&mut utxo_set, 2, "edu1qspending", 1_000_000);
+        fund(&mut utxo_set, 3, "edu1qother", 700_000);
+
+        let addresses = vec!["edu1qsavings".to_string(), "edu1qspending".to_string(), "edu1qother".to_string()];
+        let labels: HashMap<String, String> = [
+            ("edu1qsavings".to_string(), "savings".to_string()),
+            ("edu1qspending".to_string(), "spending".to_string()),
+        ].into_iter().collect();
+
+        let selected = AdvancedWalletManager::select_single_label_addresses(
+            &addresses, Some(&labels), &utxo_set, 600_000, 1, 1,
+        ).unwrap();
+        assert_eq!(selected, vec!["edu1qother".to_string()]);
+
+        let selected = AdvancedWalletManager::select_single_label_addresses(
+            &addresses, Some(&labels), &utxo_set, 2_000_000, 1, 1,
+        ).unwrap();
+        assert_eq!(selected, vec!["edu1qsavings".to_string()]);
+
+        assert!(AdvancedWalletManager::select_single_label_addresses(
+            &addresses, Some(&labels), &utxo_set, 6_000_000, 1, 1,
+        ).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spend_policy_refuses_before_signing() {
+        use crate::spend_policy::PolicyViolation;
+
+        let mut manager = AdvancedWalletManager::new();
+        let wallet_id = manager.create_hd_wallet("Policy".to_string(), Some([7u8; 32])).unwrap();
+        manager.set_spend_policy(wallet_id, Some(SpendPolicy {
+            max_per_tx: Some(1_000_000),
+            cosigner_threshold: Some(500_000),
+            ..SpendPolicy::default()
+        })).unwrap();
+
+        let outputs = vec![("edu1qpayee".to_string(), 2_000_000)];
+        match manager.build_transaction(wallet_id, outputs.clone(), None).await {
+            Err(BlockchainError::PolicyViolation(violations)) => assert_eq!(violations, vec![
+                PolicyViolation::AmountLimit { amount: 2_000_000, limit: 1_000_000 },
+                PolicyViolation::CosignerRequired { amount: 2_000_000, threshold: 500_000 },
+            ]),
+            other => panic!("expected a policy violation, got {:?}", other),
+        }
+        match manager.build_cosigned_transaction(wallet_id, outputs, None).await {
+            Err(BlockchainError::PolicyViolation(violations)) => assert_eq!(violations, vec![
+                PolicyViolation::AmountLimit { amount: 2_000_000, limit: 1_000_000 },
+            ]),
+            other => panic!("expected a policy violation, got {:?}", other),
+        }
+
+        // Co-signed and within the limit, the policy lets it through
+        assert!(manager.check_spend_policy(wallet_id, &[("edu1qpayee".to_string(), 800_000)], true).is_ok());
+
+        manager.set_spend_policy(wallet_id, None).unwrap();
+        assert!(manager.get_spend_policy(wallet_id).is_none());
+        assert!(manager.check_spend_policy(wallet_id, &[("edu1qpayee".to_string(), 2_000_000)], false).is_ok());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,250 @@
+//! Coin amounts
+//!
+//! `Amount` holds a number of satoshis, 100,000,000 to the EDU. Sums and
+//! differences are checked, so an overflow or a spend of more than is there
+//! is an error rather than a wrapped value. EDU amounts are parsed and
+//! printed as exact decimals: "1.5 EDU" is 150,000,000 satoshis, never
+//! 149,999,999 from a float rounding down. On the wire an `Amount` is its
+//! satoshis, the same as the `u64` fields it replaces.
+
+use crate::BlockchainError;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Satoshis in one EDU
+pub const SATOSHIS_PER_EDU: u64 = 100_000_000;
+
+/// Decimal places of an EDU amount
+pub const DECIMALS: usize = 8;
+
+/// Why an amount could not be parsed or computed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AmountError {
+    #[error("amount is empty")]
+    Empty,
+
+    #[error("'{0}' is not a number")]
+    InvalidNumber(String),
+
+    #[error("amount cannot be negative")]
+    Negative,
+
+    #[error("amount has more than {DECIMALS} decimal places")]
+    TooPrecise,
+
+    #[error("unknown denomination '{0}', expected EDU or sat")]
+    UnknownDenomination(String),
+
+    #[error("amount overflows")]
+    Overflow,
+
+    #[error("amount would be negative")]
+    Underflow,
+}
+
+impl From<AmountError> for BlockchainError {
+    fn from(error: AmountError) -> Self {
+        BlockchainError::InvalidInput(error.to_string())
+    }
+}
+
+/// A number of satoshis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_EDU: Amount = Amount(SATOSHIS_PER_EDU);
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    pub const fn from_sat(satoshis: u64) -> Self {
+        Amount(satoshis)
+    }
+
+    pub const fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    /// Whole EDU
+    pub fn from_edu(edu: u64) -> Result<Self, AmountError> {
+        edu.checked_mul(SATOSHIS_PER_EDU).map(Amount).ok_or(AmountError::Overflow)
+    }
+
+    /// An EDU amount given as a float, such as a JSON number. The float's
+    /// shortest decimal form is parsed, so 0.1 is exactly 10,000,000
+    /// satoshis.
+    pub fn from_edu_f64(edu: f64) -> Result<Self, AmountError> {
+        if !edu.is_finite() {
+            return Err(AmountError::InvalidNumber(edu.to_string()));
+        }
+        if edu < 0.0 {
+            return Err(AmountError::Negative);
+        }
+        parse_edu(&edu.to_string())
+    }
+
+    /// EDU as a float, for display and fiat conversion only
+    pub fn to_edu_f64(self) -> f64 {
+        self.0 as f64 / SATOSHIS_PER_EDU as f64
+    }
+
+    /// EDU as an exact decimal without trailing zeros, e.g. "1.5"
+    pub fn to_edu_string(self) -> String {
+        let whole = self.0 / SATOSHIS_PER_EDU;
+        let fraction = self.0 % SATOSHIS_PER_EDU;
+        if fraction == 0 {
+            return whole.to_string();
+        }
+        let digits = format!("{:0width$}", fraction, width = DECIMALS);
+        format!("{}.{}", whole, digits.trim_end_matches('0'))
+    }
+
+    pub fn checked_add(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0.checked_add(other.0).map(Amount).ok_or(AmountError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0.checked_sub(other.0).map(Amount).ok_or(AmountError::Underflow)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Result<Amount, AmountError> {
+        self.0.checked_mul(factor).map(Amount).ok_or(AmountError::Overflow)
+    }
+
+    pub fn saturating_sub(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_sub(other.0))
+    }
+
+    /// Total of some amounts, failing if it overflows
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Result<Amount, AmountError> {
+        amounts.into_iter().try_fold(Amount::ZERO, Amount::checked_add)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.0
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} EDU", self.to_edu_string())
+    }
+}
+
+/// Parses "1.5", "1.5 EDU" or "150000000 sat"; a bare number is EDU
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+')).unwrap_or(s.len());
+        let (number, unit) = (s[..split].trim(), s[split..].trim());
+        match unit.to_ascii_lowercase().as_str() {
+            "" | "edu" => parse_edu(number),
+            "sat" | "sats" | "satoshi" | "satoshis" => parse_sat(number),
+            _ => Err(AmountError::UnknownDenomination(unit.to_string())),
+        }
+    }
+}
+
+/// Accepts satoshis as a JSON integer, or a string such as "1.5 EDU"
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Satoshis(u64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Satoshis(satoshis) => Ok(Amount(satoshis)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+fn parse_sat(number: &str) -> Result<Amount, AmountError> {
+    if number.starts_with('-') {
+        return Err(AmountError::Negative);
+    }
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(AmountError::InvalidNumber(number.to_string()));
+    }
+    number.parse().map(Amount).map_err(|_| AmountError::Overflow)
+}
+
+fn parse_edu(number: &str) -> Result<Amount, AmountError> {
+    if number.starts_with('-') {
+        return Err(AmountError::Negative);
+    }
+    let number = number.strip_prefix('+').unwrap_or(number);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !digits(whole) || !digits(fraction) {
+        return Err(AmountError::InvalidNumber(number.to_string()));
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > DECIMALS {
+        return Err(AmountError::TooPrecise);
+    }
+
+    let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| AmountError::Overflow)? };
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = DECIMALS).parse().map_err(|_| AmountError::InvalidNumber(number.to_string()))?
+    };
+    Amount::from_edu(whole)?.checked_add(Amount(fraction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_are_exact() {
+        assert_eq!("1.5 EDU".parse::<Amount>().unwrap(), Amount::from_sat(150_000_000));
+        assert_eq!("0.1".parse::<Amount>().unwrap(), Amount::from_sat(10_000_000));
+        assert_eq!(".00000001 edu".parse::<Amount>().unwrap(), Amount::from_sat(1));
+        assert_eq!("2500 sat".parse::<Amount>().unwrap(), Amount::from_sat(2_500));
+        assert_eq!(Amount::from_edu_f64(0.29).unwrap(), Amount::from_sat(29_000_000));
+
+        assert_eq!(Amount::from_sat(150_000_000).to_string(), "1.5 EDU");
+        assert_eq!(Amount::from_sat(1).to_edu_string(), "0.00000001");
+        assert_eq!(Amount::from_edu(3).unwrap().to_edu_string(), "3");
+    }
+
+    #[test]
+    fn test_rejects_bad_amounts() {
+        assert_eq!("".parse::<Amount>(), Err(AmountError::Empty));
+        assert_eq!("-1".parse::<Amount>(), Err(AmountError::Negative));
+        assert_eq!("0.000000001".parse::<Amount>(), Err(AmountError::TooPrecise));
+        assert_eq!("1.5 BTC".parse::<Amount>(), Err(AmountError::UnknownDenomination("BTC".to_string())));
+        assert!(matches!("1.2.3".parse::<Amount>(), Err(AmountError::InvalidNumber(_))));
+        assert_eq!("1.5 sat".parse::<Amount>(), Err(AmountError::InvalidNumber("1.5".to_string())));
+        assert_eq!("200000000000 EDU".parse::<Amount>(), Err(AmountError::Overflow));
+        assert!(Amount::from_edu_f64(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_checked_arithmetic_and_serde() {
+        assert_eq!(Amount::MAX.checked_add(Amount::from_sat(1)), Err(AmountError::Overflow));
+        assert_eq!(Amount::ZERO.checked_sub(Amount::from_sat(1)), Err(AmountError::Underflow));
+        assert_eq!(
+            Amount::checked_sum([Amount::ONE_EDU, Amount::from_sat(5)]).unwrap(),
+            Amount::from_sat(100_000_005)
+        );
+
+        assert_eq!(serde_json::to_string(&Amount::from_sat(42)).unwrap(), "42");
+        assert_eq!(serde_json::from_str::<Amount>("42").unwrap(), Amount::from_sat(42));
+        assert_eq!(serde_json::from_str::<Amount>("\"0.5 EDU\"").unwrap(), Amount::from_sat(50_000_000));
+        assert!(serde_json::from_str::<Amount>("\"lots\"").is_err());
+    }
+}
@@ -17,16 +17,23 @@
 
 use crate::{
     BlockchainError, Result,
+    address::AddressCodec,
+    amount::Amount,
+    canonical::{HexHash, Sats},
     consensus::ConsensusValidator,
     mempool::ThreadSafeMempool,
     advanced_wallet::AdvancedWalletManager,
+    asset_registry::AssetRegistry,
+    naming::NameRegistry,
+    ipfs::IpfsClient,
+    fiat::FiatConverter,
 };
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::sync::{RwLock, Mutex};
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -185,7 +192,11 @@ pub struct ApiServer {
     pub config: ApiServerConfig,
     consensus: Arc<ConsensusValidator>,
     mempool: ThreadSafeMempool,
-    wallet_manager: Arc<Mutex<AdvancedWalletManager>>,
+    pub(crate) wallet_manager: Arc<Mutex<AdvancedWalletManager>>,
+    pub(crate) asset_registry: Arc<AssetRegistry>,
+    pub(crate) name_registry: Arc<NameRegistry>,
+    pub(crate) ipfs: Option<Arc<IpfsClient>>,
+    pub(crate) fiat: Option<Arc<FiatConverter>>,
     
     // Authentication & Rate Limiting
     api_keys: Arc<RwLock<HashMap<String, ApiKey>>>,
@@ -219,6 +230,10 @@ impl ApiServer {
             consensus,
             mempool,
             wallet_manager,
+            asset_registry: Arc::new(AssetRegistry::new()),
+            name_registry: Arc::new(NameRegistry::new()),
+            ipfs: None,
+            fiat: None,
             api_keys: Arc::new(RwLock::new(HashMap::new())),
             rate_limiter: Arc::new(Mutex::new(rate_limiter)),
             websocket_connections: Arc::new(RwLock::new(HashMap::new())),
@@ -230,6 +245,30 @@ impl ApiServer {
         }
     }
 
+    /// Serve asset metadata from a shared registry instead of a private one
+    pub fn with_asset_registry(mut self, registry: Arc<AssetRegistry>) -> Self {
+        self.asset_registry = registry;
+        self
+    }
+
+    /// Resolve names from a shared registry instead of a private one
+    pub fn with_name_registry(mut self, registry: Arc<NameRegistry>) -> Self {
+        self.name_registry = registry;
+        self
+    }
+
+    /// Enable media upload endpoints backed by an IPFS node
+    pub fn with_ipfs(mut self, client: Arc<IpfsClient>) -> Self {
+        self.ipfs = Some(client);
+        self
+    }
+
+    /// Include fiat equivalents in balance responses
+    pub fn with_fiat(mut self, converter: Arc<FiatConverter>) -> Self {
+        self.fiat = Some(converter);
+        self
+    }
+
     /// Start the API server
     pub async fn start(&self) -> Result<()> {
         let mut is_running = self.is_running.write().await;
@@ -374,8 +413,14 @@ impl ApiServer {
                     BlockchainError::InvalidTransaction(_) => -32001,
                     BlockchainError::InsufficientFunds(_) => -32002,
                     BlockchainError::WalletError(_) => -32003,
+                    BlockchainError::PolicyViolation(_) => -32004,
                     _ => -32603, // Internal error
                 };
+                // Policy violations are listed for the client to act on
+                let data = match &error {
+                    BlockchainError::PolicyViolation(violations) => Some(json!({ "violations": violations })),
+                    _ => None,
+                };
 
                 let mut metrics = self.metrics.write().await;
                 metrics.failed_requests += 1;
@@ -388,7 +433,7 @@ impl ApiServer {
                     error: Some(JsonRpcError {
                         code: error_code,
                         message: error.to_string(),
-                        data: None,
+                        data,
                     }),
                     id: request_id,
                 }
@@ -405,6 +450,8 @@ impl ApiServer {
             "getblock" => self.get_block(params).await,
             "gettransaction" => self.get_transaction(params).await,
             "getbalance" => self.get_balance(params).await,
+            "resolvename" => self.resolve_name(params).await,
+            "listnames" => self.list_names(params).await,
             
             // Wallet methods
             "createwallet" => self.create_wallet(params).await,
@@ -437,7 +484,7 @@ impl ApiServer {
 
     pub async fn get_best_block_hash(&self) -> Result<Value> {
         let chain_state = self.consensus.get_chain_state().await;
-        Ok(json!(hex::encode(chain_state.best_block_hash)))
+        Ok(json!(HexHash(chain_state.best_block_hash)))
     }
 
     pub async fn get_block(&self, _params: Option<Value>) -> Result<Value> {
@@ -459,7 +506,7 @@ impl ApiServer {
         }))
     }
 
-    async fn get_balance(&self, params: Option<Value>) -> Result<Value> {
+    pub async fn get_balance(&self, params: Option<Value>) -> Result<Value> {
         // Extract address from parameters
         let address = params
             .as_ref()
@@ -468,17 +515,58 @@ impl ApiServer {
             .ok_or_else(|| BlockchainError::InvalidInput("Missing address parameter".to_string()))?
             .to_string();
 
-        // Get balance from UTXO set via consensus
+        // Split the balance using the UTXO set and the mempool; the node
+        // itself holds no wallet locks
         let utxo_set = self.consensus.get_utxo_set().await;
-        let balance = 0u64; // TODO: Implement balance lookup from UTXO set
+        let pending = self.mempool.inner.read().await.get_transactions();
+        let balance = utxo_set.get_balance_breakdown(&address, &pending, &HashSet::new());
         
         Ok(json!({ 
             "address": address,
-            "balance": balance,
-            "balance_edu": balance as f64 / 100_000_000.0 // Convert satoshis to EDU
+            "balance": Sats(balance.confirmed),
+            "balance_edu": Amount::from_sat(balance.confirmed).to_edu_f64(),
+            "confirmed": Sats(balance.confirmed),
+            "unconfirmed": Sats(balance.unconfirmed),
+            "immature": Sats(balance.immature),
+            "locked": Sats(balance.locked),
+            "total": Sats(balance.total()),
+            "fiat": self.fiat.as_ref().and_then(|f| f.convert(balance.confirmed))
+        }))
+    }
+
+    /// Address a name points to at the current height
+    pub async fn resolve_name(&self, params: Option<Value>) -> Result<Value> {
+        let name = params
+            .as_ref()
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| BlockchainError::InvalidInput("Missing name parameter".to_string()))?;
+        let name = crate::naming::normalize(name)?;
+
+        let height = self.consensus.get_chain_state().await.height;
+        let record = self.name_registry.resolve(&name, height).await
+            .ok_or_else(|| BlockchainError::ApiError(format!("{} is not registered", name)))?;
+        Ok(json!({
+            "name": record.name,
+            "address": record.address,
+            "owner": record.owner,
+            "expires_height": record.expires_height,
+            "height": height,
         }))
     }
 
+    /// Active names pointing at an address
+    pub async fn list_names(&self, params: Option<Value>) -> Result<Value> {
+        let address = params
+            .as_ref()
+            .and_then(|p| p.get("address"))
+            .and_then(|a| a.as_str())
+            .ok_or_else(|| BlockchainError::InvalidInput("Missing address parameter".to_string()))?;
+
+        let height = self.consensus.get_chain_state().await.height;
+        Ok(json!(self.name_registry.names_for(address, height).await))
+    }
+
     pub async fn create_wallet(&self, params: Option<Value>) -> Result<Value> {
         let wallet_id = Uuid::new_v4();
         let name = params
@@ -496,7 +584,7 @@ impl ApiServer {
         Ok(json!([{
             "wallet_id": Uuid::new_v4(),
             "name": "Demo Wallet",
-            "balance": 150000
+            "balance": Sats(150000)
         }]))
     }
 
@@ -507,7 +595,26 @@ impl ApiServer {
         }))
     }
 
-    pub async fn send_to_address(&self, _params: Option<Value>) -> Result<Value> {
+    pub async fn send_to_address(&self, params: Option<Value>) -> Result<Value> {
+        let address = params
+            .as_ref()
+            .and_then(|p| p.get("address"))
+            .and_then(|a| a.as_str())
+            .ok_or_else(|| BlockchainError::InvalidInput("Missing address parameter".to_string()))?;
+        AddressCodec::validate(address)?;
+
+        // The sending wallet's spend policy is checked before anything is signed
+        let params = params.as_ref();
+        let wallet_id = params.and_then(|p| p.get("wallet_id")).and_then(|w| w.as_str());
+        let amount = params.and_then(|p| p.get("amount")).and_then(|a| a.as_u64());
+        if let (Some(wallet_id), Some(amount)) = (wallet_id, amount) {
+            let wallet_id = Uuid::parse_str(wallet_id)
+                .map_err(|e| BlockchainError::InvalidInput(format!("Invalid wallet_id: {}", e)))?;
+            let cosigned = params.and_then(|p| p.get("cosigned")).and_then(|c| c.as_bool()).unwrap_or(false);
+            self.wallet_manager.lock().await
+                .check_spend_policy(wallet_id, &[(address.to_string(), amount)], cosigned)?;
+        }
+
         Ok(json!({
             "txid": format!("{:x}", md5::compute("demo_transaction")),
             "status": "pending"
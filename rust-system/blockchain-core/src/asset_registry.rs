@@ -0,0 +1,518 @@
+//! Asset metadata registry for contract tokens and NFTs
+//!
+//! Issuers publish signed metadata (name, ticker, decimals, icon URI) for a
+//! contract either by emitting it from the contract itself as a log, or by
+//! committing its hash on-chain in an OP_RETURN output and submitting the
+//! full record separately. The registry indexes both sources and resolves
+//! competing records the same way on every node:
+//! - the earliest on-chain anchor binds the issuer key for a contract
+//! - later records from that issuer replace earlier ones only with a higher version
+
+use crate::{BlockchainError, Result, Hash256, PrivateKey};
+use crate::block::Block;
+use crate::contracts::{EthAddress, Log};
+use crate::event_indexer::IndexedEvent;
+use crate::script_utils::{opcodes, ScriptBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use chrono::{DateTime, Utc};
+
+/// Magic prefix of an OP_RETURN metadata commitment
+pub const COMMITMENT_MAGIC: &[u8; 4] = b"EDAM";
+
+/// Topic emitted by contracts that publish their own metadata
+/// (keccak256 of `AssetMetadata(bytes)`)
+pub const ASSET_METADATA_TOPIC: &str =
+    "0f64c77345208f080c2381b80c77794214d8323b8d2c263a66720f0c65d06a94";
+
+/// Maximum lengths accepted for human-readable fields
+pub const MAX_NAME_LEN: usize = 64;
+pub const MAX_TICKER_LEN: usize = 12;
+pub const MAX_ICON_URI_LEN: usize = 256;
+
+/// Kind of asset described by a metadata record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssetKind {
+    /// Fungible token contract
+    Token,
+    /// NFT collection contract
+    Nft,
+}
+
+/// Signed metadata record for a contract asset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetMetadata {
+    /// Contract the metadata describes
+    pub contract: EthAddress,
+    /// Token or NFT collection
+    pub kind: AssetKind,
+    /// Display name
+    pub name: String,
+    /// Short ticker symbol
+    pub ticker: String,
+    /// Decimal places for display (0 for NFTs)
+    pub decimals: u8,
+    /// Icon location (https:// or ipfs://)
+    pub icon_uri: Option<String>,
+    /// Monotonic record version, bumped on every update
+    pub version: u64,
+    /// Compressed public key of the issuer
+    pub issuer_pubkey: Vec<u8>,
+    /// DER signature over [`signing_hash`](Self::signing_hash)
+    pub signature: Vec<u8>,
+}
+
+/// Fields covered by the issuer signature, in canonical order
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    contract: &'a [u8; 20],
+    kind: AssetKind,
+    name: &'a str,
+    ticker: &'a str,
+    decimals: u8,
+    icon_uri: &'a Option<String>,
+    version: u64,
+    issuer_pubkey: &'a [u8],
+}
+
+impl AssetMetadata {
+    /// Create and sign a metadata record
+    pub fn new_signed(
+        contract: EthAddress,
+        kind: AssetKind,
+        name: String,
+        ticker: String,
+        decimals: u8,
+        icon_uri: Option<String>,
+        version: u64,
+        private_key: &PrivateKey,
+    ) -> Result<Self> {
+        let mut metadata = Self {
+            contract,
+            kind,
+            name,
+            ticker,
+            decimals,
+            icon_uri,
+            version,
+            issuer_pubkey: crate::crypto::derive_public_key(private_key)?,
+            signature: Vec::new(),
+        };
+        metadata.validate_fields()?;
+        metadata.signature = crate::crypto::sign_hash(&metadata.signing_hash()?, private_key)?;
+        Ok(metadata)
+    }
+
+    /// Hash of the signed fields
+    pub fn signing_hash(&self) -> Result<Hash256> {
+        let fields = SignedFields {
+            contract: self.contract.as_bytes(),
+            kind: self.kind,
+            name: &self.name,
+            ticker: &self.ticker,
+            decimals: self.decimals,
+            icon_uri: &self.icon_uri,
+            version: self.version,
+            issuer_pubkey: &self.issuer_pubkey,
+        };
+        let encoded = bincode::serialize(&fields)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        Ok(crate::crypto::sha256(&encoded))
+    }
+
+    /// Hash committed on-chain for this record (covers the signature too)
+    pub fn commitment_hash(&self) -> Result<Hash256> {
+        let encoded = bincode::serialize(self)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        Ok(crate::crypto::sha256(&encoded))
+    }
+
+    /// Check field limits and the issuer signature
+    pub fn verify(&self) -> Result<()> {
+        self.validate_fields()?;
+        if !crate::crypto::verify_signature(&self.signature, &self.issuer_pubkey, &self.signing_hash()?)? {
+            return Err(BlockchainError::InvalidSignature(
+                format!("Bad metadata signature for contract {}", hex::encode(self.contract.as_bytes()))
+            ));
+        }
+        Ok(())
+    }
+
+    /// OP_RETURN script committing to this record
+    pub fn commitment_script(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(56);
+        data.extend_from_slice(COMMITMENT_MAGIC);
+        data.extend_from_slice(self.contract.as_bytes());
+        data.extend_from_slice(&self.commitment_hash()?);
+        ScriptBuilder::create_op_return_script(&data)
+    }
+
+    /// Encode the record as log data for the contract-call route
+    pub fn to_log_data(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+
+    /// Decode a record from contract log data
+    pub fn from_log_data(data: &[u8]) -> Result<Self> {
+        bincode::deserialize(data).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+
+    fn validate_fields(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.len() > MAX_NAME_LEN {
+            return Err(BlockchainError::InvalidInput(format!("Asset name must be 1-{} bytes", MAX_NAME_LEN)));
+        }
+        if self.ticker.is_empty()
+            || self.ticker.len() > MAX_TICKER_LEN
+            || !self.ticker.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(BlockchainError::InvalidInput(format!(
+                "Ticker must be 1-{} ASCII alphanumeric characters", MAX_TICKER_LEN
+            )));
+        }
+        if self.decimals > 18 || (self.kind == AssetKind::Nft && self.decimals != 0) {
+            return Err(BlockchainError::InvalidInput("Invalid decimals for asset kind".to_string()));
+        }
+        if let Some(uri) = &self.icon_uri {
+            let allowed = uri.starts_with("https://") || uri.starts_with("ipfs://");
+            if !allowed || uri.len() > MAX_ICON_URI_LEN {
+                return Err(BlockchainError::InvalidInput(
+                    "Icon URI must be an https:// or ipfs:// link".to_string()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Position of a record's anchor on-chain, used to order competing records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChainAnchor {
+    /// Block height
+    pub block_height: u64,
+    /// Transaction index within the block
+    pub tx_index: u32,
+    /// Output or log index within the transaction
+    pub index: u32,
+}
+
+/// How a record reached the registry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MetadataSource {
+    /// Hash committed in an OP_RETURN output, record submitted separately
+    OpReturn { txid: String },
+    /// Emitted by the contract itself
+    ContractLog { tx_hash: String },
+}
+
+/// Registry entry served to explorers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetRecord {
+    /// Current metadata
+    pub metadata: AssetMetadata,
+    /// Anchor of the current metadata
+    pub anchor: ChainAnchor,
+    /// Anchor that bound the issuer key for this contract
+    pub issuer_anchor: ChainAnchor,
+    /// Where the current metadata came from
+    pub source: MetadataSource,
+    /// When the registry last changed this entry
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Outcome of submitting or indexing a record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RegistrationStatus {
+    /// Record is now the current metadata for its contract
+    Applied,
+    /// Record is valid but its OP_RETURN commitment has not been indexed yet
+    AwaitingCommitment,
+    /// Record lost to an existing one (older version or different issuer)
+    Superseded,
+}
+
+/// On-chain commitment seen before (or without) its record
+#[derive(Debug, Clone)]
+struct Commitment {
+    anchor: ChainAnchor,
+    txid: String,
+}
+
+/// Indexer and store for asset metadata
+pub struct AssetRegistry {
+    /// Current record per contract
+    records: Arc<RwLock<HashMap<EthAddress, AssetRecord>>>,
+    /// Indexed OP_RETURN commitments by record hash
+    commitments: Arc<RwLock<HashMap<Hash256, Commitment>>>,
+    /// Submitted records waiting for their commitment, by record hash
+    unanchored: Arc<RwLock<HashMap<Hash256, AssetMetadata>>>,
+}
+
+impl AssetRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            records: Arc::new(RwLock::new(HashMap::new())),
+            commitments: Arc::new(RwLock::new(HashMap::new())),
+            unanchored: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Index OP_RETURN commitments from a block
+    pub async fn index_block(&self, block: &Block) -> Result<usize> {
+        let mut applied = 0;
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let Some((contract, hash)) = parse_commitment(&output.script_pubkey) else { continue };
+                let commitment = Commitment {
+                    anchor: ChainAnchor {
+                        block_height: block.header.height as u64,
+                        tx_index: tx_index as u32,
+                        index: index as u32,
+                    },
+                    txid: hex::encode(tx.get_hash()?),
+                };
+
+                let pending = self.unanchored.write().await.remove(&hash);
+                match pending {
+                    Some(metadata) if metadata.contract == contract => {
+                        let source = MetadataSource::OpReturn { txid: commitment.txid.clone() };
+                        if self.apply(metadata, commitment.anchor, source).await == RegistrationStatus::Applied {
+                            applied += 1;
+                        }
+                    }
+                    _ => {
+                        self.commitments.write().await.insert(hash, commitment);
+                    }
+                }
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Index metadata published by contracts through their event logs
+    pub async fn index_events(&self, events: &[IndexedEvent]) -> usize {
+        let mut applied = 0;
+        for event in events {
+            let Some(metadata) = parse_metadata_log(&event.log) else { continue };
+            let anchor = ChainAnchor {
+                block_height: event.block_height,
+                tx_index: 0,
+                index: event.log_index,
+            };
+            let source = MetadataSource::ContractLog { tx_hash: event.tx_hash.clone() };
+            if self.apply(metadata, anchor, source).await == RegistrationStatus::Applied {
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    /// Submit a signed record whose hash is (or will be) committed via OP_RETURN
+    pub async fn submit(&self, metadata: AssetMetadata) -> Result<RegistrationStatus> {
+        metadata.verify()?;
+        let hash = metadata.commitment_hash()?;
+
+        let commitment = self.commitments.write().await.remove(&hash);
+        match commitment {
+            Some(commitment) => {
+                let source = MetadataSource::OpReturn { txid: commitment.txid };
+                Ok(self.apply(metadata, commitment.anchor, source).await)
+            }
+            None => {
+                self.unanchored.write().await.insert(hash, metadata);
+                Ok(RegistrationStatus::AwaitingCommitment)
+            }
+        }
+    }
+
+    /// Get the current record for a contract
+    pub async fn get(&self, contract: &EthAddress) -> Option<AssetRecord> {
+        self.records.read().await.get(contract).cloned()
+    }
+
+    /// List all records, ordered by contract address
+    pub async fn list(&self) -> Vec<AssetRecord> {
+        let mut records: Vec<_> = self.records.read().await.values().cloned().collect();
+        records.sort_by(|a, b| a.metadata.contract.as_bytes().cmp(b.metadata.contract.as_bytes()));
+        records
+    }
+
+    /// Number of contracts with registered metadata
+    pub async fn len(&self) -> usize {
+        self.records.read().await.len()
+    }
+
+    /// Apply an anchored record if it wins against the current one
+    async fn apply(&self, metadata: AssetMetadata, anchor: ChainAnchor, source: MetadataSource) -> RegistrationStatus {
+        if metadata.verify().is_err() {
+            return RegistrationStatus::Superseded;
+        }
+
+        let mut records = self.records.write().await;
+        let issuer_anchor = match records.get(&metadata.contract) {
+            None => anchor,
+            Some(current) if current.metadata.issuer_pubkey == metadata.issuer_pubkey => {
+                if metadata.version <= current.metadata.version {
+                    return RegistrationStatus::Superseded;
+                }
+                current.issuer_anchor.min(anchor)
+            }
+            // A different issuer only takes over with an earlier anchor
+            Some(current) => {
+                if anchor >= current.issuer_anchor {
+                    return RegistrationStatus::Superseded;
+                }
+                anchor
+            }
+        };
+
+        records.insert(metadata.contract, AssetRecord {
+            metadata,
+            anchor,
+            issuer_anchor,
+            source,
+            updated_at: Utc::now(),
+        });
+        RegistrationStatus::Applied
+    }
+}
+
+impl Default for AssetRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extract `(contract, record hash)` from an OP_RETURN commitment script
+fn parse_commitment(script: &[u8]) -> Option<(EthAddress, Hash256)> {
+    if script.len() != 58 || script[0] != opcodes::OP_RETURN || script[1] != 56 {
+        return None;
+    }
+    let data = &script[2..];
+    if &data[..4] != COMMITMENT_MAGIC {
+        return None;
+    }
+
+    let mut contract = [0u8; 20];
+    contract.copy_from_slice(&data[4..24]);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&data[24..56]);
+    Some((EthAddress::new(contract), hash))
+}
+
+/// Decode metadata from a contract log; only a contract may describe itself
+fn parse_metadata_log(log: &Log) -> Option<AssetMetadata> {
+    if log.topics.first().map(|t| t.trim_start_matches("0x")) != Some(ASSET_METADATA_TOPIC) {
+        return None;
+    }
+    let metadata = AssetMetadata::from_log_data(&log.data).ok()?;
+    (metadata.contract == log.address).then_some(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{Transaction, TransactionOutput};
+
+    fn token(version: u64, key: &PrivateKey) -> AssetMetadata {
+        AssetMetadata::new_signed(
+            EthAddress::new([9; 20]),
+            AssetKind::Token,
+            "Campus Credit".to_string(),
+            "CRED".to_string(),
+            8,
+            Some("ipfs://bafyicon".to_string()),
+            version,
+            key,
+        ).unwrap()
+    }
+
+    fn block_with_commitment(height: u32, metadata: &AssetMetadata) -> Block {
+        let tx = Transaction::new(1, vec![], vec![
+            TransactionOutput::new(0, metadata.commitment_script().unwrap()),
+        ]);
+        let header = crate::block::BlockHeader::new(1, [0u8; 32], [0u8; 32], 1, height);
+        Block::new(header, vec![tx])
+    }
+
+    #[test]
+    fn test_metadata_topic_is_event_signature() {
+        let topic = revm::primitives::keccak256(b"AssetMetadata(bytes)");
+        assert_eq!(hex::encode(topic), ASSET_METADATA_TOPIC);
+    }
+
+    #[test]
+    fn test_signature_verification() {
+        let mut metadata = token(1, &[1u8; 32]);
+        assert!(metadata.verify().is_ok());
+
+        metadata.ticker = "FAKE".to_string();
+        assert!(metadata.verify().is_err());
+
+        assert!(AssetMetadata::new_signed(
+            EthAddress::new([9; 20]), AssetKind::Nft, "Art".to_string(), "ART".to_string(),
+            2, None, 1, &[1u8; 32],
+        ).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_op_return_commitment_either_order() {
+        let registry = AssetRegistry::new();
+        let v1 = token(1, &[1u8; 32]);
+
+        // Record first, commitment later
+        assert_eq!(registry.submit(v1.clone()).await.unwrap(), RegistrationStatus::AwaitingCommitment);
+        assert_eq!(registry.index_block(&block_with_commitment(5, &v1)).await.unwrap(), 1);
+        assert_eq!(registry.get(&v1.contract).await.unwrap().metadata.name, "Campus Credit");
+
+        // Commitment first, record later
+        let v2 = token(2, &[1u8; 32]);
+        assert_eq!(registry.index_block(&block_with_commitment(6, &v2)).await.unwrap(), 0);
+        assert_eq!(registry.submit(v2).await.unwrap(), RegistrationStatus::Applied);
+        assert_eq!(registry.get(&v1.contract).await.unwrap().metadata.version, 2);
+
+        // Replaying an older version is ignored
+        assert_eq!(registry.index_block(&block_with_commitment(7, &v1)).await.unwrap(), 0);
+        assert_eq!(registry.get(&v1.contract).await.unwrap().metadata.version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_first_anchor_binds_issuer() {
+        let registry = AssetRegistry::new();
+        let honest = token(1, &[1u8; 32]);
+        let squatter = token(5, &[2u8; 32]);
+
+        registry.index_block(&block_with_commitment(10, &honest)).await.unwrap();
+        registry.index_block(&block_with_commitment(20, &squatter)).await.unwrap();
+        registry.submit(squatter.clone()).await.unwrap();
+        registry.submit(honest.clone()).await.unwrap();
+
+        let record = registry.get(&honest.contract).await.unwrap();
+        assert_eq!(record.metadata.issuer_pubkey, honest.issuer_pubkey);
+        assert_eq!(record.issuer_anchor.block_height, 10);
+    }
+
+    #[tokio::test]
+    async fn test_contract_log_route() {
+        let registry = AssetRegistry::new();
+        let metadata = token(1, &[1u8; 32]);
+        let event = |address: EthAddress| IndexedEvent {
+            log: Log {
+                address,
+                topics: vec![ASSET_METADATA_TOPIC.to_string()],
+                data: metadata.to_log_data().unwrap(),
+            },
+            block_height: 3,
+            tx_hash: "ab".to_string(),
+            log_index: 0,
+            implementation: None,
+        };
+
+        // Another contract cannot publish metadata on this contract's behalf
+        assert_eq!(registry.index_events(&[event(EthAddress::new([8; 20]))]).await, 0);
+        assert_eq!(registry.index_events(&[event(metadata.contract)]).await, 1);
+        assert_eq!(registry.list().await.len(), 1);
+    }
+}
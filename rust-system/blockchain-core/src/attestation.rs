@@ -0,0 +1,217 @@
+//! Identity attestations
+//!
+//! A verified issuer, such as a university, signs a claim about a student's
+//! enrollment tied to the student's address. The full supporting document
+//! stays off-chain; the attestation commits to it by hash, and only the
+//! attestation's anchor hash goes on-chain, so anyone holding the document
+//! can show it was attested without publishing it.
+
+use crate::{crypto, Address, BlockchainError, Hash256, PrivateKey, PublicKey, Result};
+use serde::{Deserialize, Serialize};
+
+const ATTESTATION_TAG: &[u8] = b"EDU-ATTESTATION";
+
+/// What the issuer vouches for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnrollmentClaim {
+    pub university: String,
+    pub program: String,
+    pub student_id: Option<String>,
+    /// Unix timestamp the enrollment started
+    pub enrolled_since: i64,
+    pub expected_graduation_year: Option<i64>,
+    /// Grade point average on a 4.0 scale
+    pub gpa: Option<f64>,
+}
+
+/// An issuer-signed claim about the holder of an address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attestation {
+    /// Issuer-chosen identifier (hex)
+    pub id: String,
+    /// Address of the student the claim is about
+    pub subject: Address,
+    pub claim: EnrollmentClaim,
+    /// SHA-256 of the off-chain document backing the claim
+    #[serde(with = "hex_hash")]
+    pub document_hash: Hash256,
+    pub issued_at: i64,
+    /// Unix timestamp after which the claim no longer holds
+    pub expires_at: i64,
+    #[serde(with = "hex_bytes")]
+    pub issuer_public_key: PublicKey,
+    /// DER-encoded ECDSA signature over `digest()`
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl Attestation {
+    /// Sign a claim about `subject` backed by `document`
+    pub fn issue(
+        id: String,
+        subject: Address,
+        claim: EnrollmentClaim,
+        document: &[u8],
+        issued_at: i64,
+        expires_at: i64,
+        issuer_key: &PrivateKey,
+    ) -> Result<Self> {
+        let mut attestation = Self {
+            id,
+            subject,
+            claim,
+            document_hash: crypto::sha256(document),
+            issued_at,
+            expires_at,
+            issuer_public_key: crypto::derive_public_key(issuer_key)?,
+            signature: Vec::new(),
+        };
+        attestation.check_fields()?;
+        attestation.signature = crypto::sign_hash(&attestation.digest(), issuer_key)?;
+        Ok(attestation)
+    }
+
+    /// Digest committing to every field except the signature
+    pub fn digest(&self) -> Hash256 {
+        let claim = &self.claim;
+        let mut data = Vec::with_capacity(256);
+        data.extend_from_slice(ATTESTATION_TAG);
+        let fields = [
+            self.id.as_bytes(),
+            self.subject.as_bytes(),
+            claim.university.as_bytes(),
+            claim.program.as_bytes(),
+            claim.student_id.as_deref().unwrap_or("").as_bytes(),
+        ];
+        for field in fields {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field);
+        }
+        data.push(claim.student_id.is_some() as u8);
+        data.extend_from_slice(&claim.enrolled_since.to_le_bytes());
+        data.push(claim.expected_graduation_year.is_some() as u8);
+        data.extend_from_slice(&claim.expected_graduation_year.unwrap_or(0).to_le_bytes());
+        data.push(claim.gpa.is_some() as u8);
+        data.extend_from_slice(&claim.gpa.unwrap_or(0.0).to_bits().to_le_bytes());
+        data.extend_from_slice(&self.document_hash);
+        data.extend_from_slice(&self.issued_at.to_le_bytes());
+        data.extend_from_slice(&self.expires_at.to_le_bytes());
+        data.extend_from_slice(&self.issuer_public_key);
+        crypto::sha256(&data)
+    }
+
+    /// Hash recorded on-chain: commits to the signed attestation, signature
+    /// included, without revealing any of it
+    pub fn anchor_hash(&self) -> Hash256 {
+        let mut data = Vec::with_capacity(32 + self.signature.len());
+        data.extend_from_slice(&self.digest());
+        data.extend_from_slice(&self.signature);
+        crypto::double_sha256(&data)
+    }
+
+    /// Check the fields and the issuer's signature.
+    ///
+    /// Whether the issuer is one the caller trusts, and whether the
+    /// attestation has expired, is up to the caller.
+    pub fn verify(&self) -> Result<()> {
+        self.check_fields()?;
+        if !crypto::verify_signature(&self.signature, &self.issuer_public_key, &self.digest())? {
+            return Err(BlockchainError::InvalidSignature(format!("Attestation {} signature does not verify", self.id)));
+        }
+        Ok(())
+    }
+
+    /// Whether `document` is the one the attestation was issued for
+    pub fn matches_document(&self, document: &[u8]) -> bool {
+        crypto::sha256(document) == self.document_hash
+    }
+
+    pub fn is_expired_at(&self, now: i64) -> bool {
+        now > self.expires_at
+    }
+
+    fn check_fields(&self) -> Result<()> {
+        if self.id.is_empty() || self.subject.is_empty() {
+            return Err(BlockchainError::InvalidInput("Attestation needs an id and a subject".to_string()));
+        }
+        if self.claim.university.trim().is_empty() {
+            return Err(BlockchainError::InvalidInput("Attestation names no university".to_string()));
+        }
+        if self.expires_at <= self.issued_at {
+            return Err(BlockchainError::InvalidInput("Attestation must expire after it is issued".to_string()));
+        }
+        if self.claim.gpa.is_some_and(|gpa| !(0.0..=4.0).contains(&gpa)) {
+            return Err(BlockchainError::InvalidInput("GPA must be between 0.0 and 4.0".to_string()));
+        }
+        Ok(())
+    }
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+mod hex_hash {
+    use crate::Hash256;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &Hash256, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Hash256, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let bytes = hex::decode(&s).map_err(serde::de::Error::custom)?;
+        Hash256::try_from(bytes.as_slice()).map_err(|_| serde::de::Error::custom("expected 32 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim() -> EnrollmentClaim {
+        EnrollmentClaim {
+            university: "Example University".to_string(),
+            program: "Computer Science".to_string(),
+            student_id: Some("S-1024".to_string()),
+            enrolled_since: 1_693_526_400,
+            expected_graduation_year: Some(2027),
+            gpa: Some(3.6),
+        }
+    }
+
+    #[test]
+    fn test_attestation_signing_and_tampering() {
+        let key = crypto::sha256(b"attestation test issuer");
+        let document = b"Enrollment certificate for S-1024";
+        let attestation = Attestation::issue(
+            "a1".to_string(), "edu1qStudent".to_string(), claim(), document, 1_700_000_000, 1_800_000_000, &key,
+        ).unwrap();
+        assert!(attestation.verify().is_ok());
+        assert!(attestation.matches_document(document));
+        assert!(!attestation.matches_document(b"Forged certificate"));
+
+        let mut tampered = attestation.clone();
+        tampered.claim.gpa = Some(4.0);
+        assert!(tampered.verify().is_err());
+        assert_ne!(tampered.anchor_hash(), attestation.anchor_hash());
+
+        let mut tampered = attestation.clone();
+        tampered.subject = "edu1qSomeoneElse".to_string();
+        assert!(tampered.verify().is_err());
+
+        let mut bad = claim();
+        bad.gpa = Some(5.0);
+        assert!(Attestation::issue("a2".to_string(), "edu1qStudent".to_string(), bad, document, 1, 2, &key).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Air-gapped transaction signer
+//!
+//! Runs on a machine that never goes online. Scan the `edutx:utx` QR
+//! sequence the node exported with `tx_exportOffline`, one chunk per line on
+//! stdin, check the payment it shows and confirm. The signatures come back
+//! as an `edutx:sig` QR sequence for `tx_importSignatures`.
+//!
+//! Build it without network code:
+//!
+//! ```text
+//! cargo build -p blockchain-core --bin offline_signer --no-default-features --features simd
+//! ```
+//!
+//! Usage: `offline_signer <mnemonic-file> [lookahead]`. Keys of the first
+//! `lookahead` (default 100) receiving and change addresses are tried.
+
+use blockchain_core::hd_wallet::HDWallet;
+use blockchain_core::offline_signing::{QrAssembler, UnsignedTransaction, DEFAULT_QR_CHUNK_LEN};
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+const DEFAULT_LOOKAHEAD: u32 = 100;
+
+fn main() -> ExitCode {
+    if cfg!(feature = "network") {
+        eprintln!("❌ This build includes network code. Build the signer with --no-default-features --features simd");
+        return ExitCode::from(2);
+    }
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mnemonic_file = args.first().ok_or("usage: offline_signer <mnemonic-file> [lookahead]")?;
+    let lookahead = match args.get(1) {
+        Some(n) => n.parse().map_err(|_| format!("lookahead must be a number, got {}", n))?,
+        None => DEFAULT_LOOKAHEAD,
+    };
+    let mnemonic = std::fs::read_to_string(mnemonic_file)
+        .map_err(|e| format!("cannot read {}: {}", mnemonic_file, e))?;
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    // Scan the unsigned transaction
+    eprintln!("📷 Scan the unsigned transaction QR codes");
+    let mut assembler = QrAssembler::default();
+    while !assembler.is_complete() {
+        let line = lines.next().ok_or("input ended before every QR code was scanned")?
+            .map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match assembler.add(&line) {
+            Ok(_) => {
+                let (received, total) = assembler.progress();
+                eprintln!("   {}/{}", received, total);
+            }
+            Err(e) => eprintln!("   ⚠️  {}", e),
+        }
+    }
+    let (_, payload) = assembler.finish().map_err(|e| e.to_string())?;
+    let unsigned: UnsignedTransaction = serde_json::from_slice(&payload)
+        .map_err(|e| format!("not an unsigned transaction: {}", e))?;
+
+    // Show what is being signed
+    println!("Transaction {}", hex::encode(unsigned.id()));
+    println!("  account {}, {} input(s)", unsigned.account_index, unsigned.transaction.inputs.len());
+    for output in &unsigned.transaction.outputs {
+        let to = output.get_address().unwrap_or_else(|| hex::encode(&output.script_pubkey));
+        println!("  pays {} to {}", output.value, to);
+    }
+    println!("  fee {}", unsigned.fee());
+    print!("Sign? [y/N] ");
+    io::stdout().flush().map_err(|e| e.to_string())?;
+    let answer = lines.next().transpose().map_err(|e| e.to_string())?.unwrap_or_default();
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        return Err("not signed".to_string());
+    }
+
+    let mut wallet = HDWallet::from_mnemonic("Offline signer".to_string(), mnemonic.trim(), None)
+        .map_err(|e| e.to_string())?;
+    for _ in 0..=unsigned.account_index {
+        wallet.create_account(String::new()).map_err(|e| e.to_string())?;
+    }
+    let signatures = wallet.sign_offline(&unsigned, lookahead).map_err(|e| e.to_string())?;
+
+    // Show the signatures to scan back
+    let chunks = signatures.to_qr_chunks(DEFAULT_QR_CHUNK_LEN).map_err(|e| e.to_string())?;
+    println!("✍️  Signed. Scan these {} QR code(s) on the online machine:", chunks.len());
+    for chunk in &chunks {
+        let code = QrCode::new(chunk.as_bytes()).map_err(|e| e.to_string())?;
+        println!("{}", code.render::<unicode::Dense1x2>().quiet_zone(true).build());
+        println!("{}\n", chunk);
+    }
+    Ok(())
+}
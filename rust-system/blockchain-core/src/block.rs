@@ -1,6 +1,7 @@
 //! Minimal block module
 
 use crate::{BlockchainError, Result, Hash256, BlockHeight, Timestamp};
+use crate::schema::ExtensionRecord;
 use crate::transaction::{Transaction, TransactionInput, TransactionOutput, UTXO, UTXOSet};
 use serde::{Deserialize, Serialize};
 use tracing::info;
@@ -8,6 +9,7 @@ use tracing::info;
 /// Simplified block header information for Initial Block Download
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockHeaderInfo {
+    pub version: u32,
     pub height: BlockHeight,
     pub hash: Hash256,
     pub prev_hash: Hash256,
@@ -74,6 +76,9 @@ impl BlockHeader {
 pub struct Block {
     pub header: BlockHeader,
     pub transactions: Vec<Transaction>,
+    /// Data for future upgrades, committed to from the coinbase; see `schema`
+    #[serde(default)]
+    pub extensions: Vec<ExtensionRecord>,
     cached_hash: Option<Hash256>,
 }
 
@@ -82,6 +87,7 @@ impl Block {
         Self {
             header,
             transactions,
+            extensions: Vec::new(),
             cached_hash: None,
         }
     }
@@ -111,6 +117,17 @@ impl Block {
         Self::compute_merkle_root(tx_hashes)
     }
 
+    /// Header size in the wire layout: version, two hashes, timestamp,
+    /// difficulty target, nonce and height
+    pub const HEADER_SIZE: usize = 4 + 32 + 32 + 4 + 4 + 4 + 4;
+
+    /// BIP 141 weight of the header, transaction count and transactions
+    pub fn weight(&self) -> usize {
+        let count_len = crate::transaction::compact_size_len(self.transactions.len());
+        (Self::HEADER_SIZE + count_len) * crate::transaction::WITNESS_SCALE_FACTOR
+            + self.transactions.iter().map(Transaction::weight).sum::<usize>()
+    }
+
     /// Serialize block header for mining (without nonce)
     pub fn serialize_for_mining(&self) -> Result<Vec<u8>> {
         let mut data = Vec::new();
@@ -143,18 +160,22 @@ impl Block {
             return [0u8; 32];
         }
         
+        // Each node hashes the hex of its two children, so a level's
+        // preimages all have the same length and hash as one batch
         while hashes.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            for chunk in hashes.chunks(2) {
-                let left = &chunk[0];
-                let right = chunk.get(1).unwrap_or(&chunk[0]);
-                
-                let combined_data = format!("{}{}", hex::encode(left), hex::encode(right));
-                next_level.push(crate::utils::double_sha256(combined_data.as_bytes()));
-            }
-            
-            hashes = next_level;
+            let preimages: Vec<[u8; 128]> = hashes.chunks(2)
+                .map(|chunk| {
+                    let left = &chunk[0];
+                    let right = chunk.get(1).unwrap_or(&chunk[0]);
+                    
+                    let mut preimage = [0u8; 128];
+                    hex::encode_to_slice(left, &mut preimage[..64]).expect("64 hex digits");
+                    hex::encode_to_slice(right, &mut preimage[64..]).expect("64 hex digits");
+                    preimage
+                })
+                .collect();
+            let messages: Vec<&[u8]> = preimages.iter().map(|preimage| preimage.as_slice()).collect();
+            hashes = crate::hashing::double_sha256_many(&messages);
         }
         
         hashes.into_iter().next().unwrap_or([0u8; 32])
@@ -0,0 +1,218 @@
+//! Bloom filters
+//!
+//! `BloomFilter` is a fixed-size filter sized for an expected item count and
+//! false-positive rate. `ScalableBloomFilter` chains filters of doubling
+//! capacity and tightening error rate, so sets that grow without bound
+//! (like spent outpoints) keep close to the target rate. Neither ever gives
+//! a false negative.
+
+use serde::Serialize;
+
+/// Fixed-capacity Bloom filter
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    len: usize,
+}
+
+impl BloomFilter {
+    /// Filter holding `capacity` items at about `false_positive_rate`
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-(capacity as f64) * rate.ln() / (ln2 * ln2)).ceil() as u64).max(64);
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            capacity,
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        let (h1, h2) = hash_pair(item);
+        for i in 0..self.num_hashes {
+            let bit = self.bit_index(h1, h2, i);
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    /// False means `item` was never inserted
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let (h1, h2) = hash_pair(item);
+        (0..self.num_hashes).all(|i| {
+            let bit = self.bit_index(h1, h2, i);
+            self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    /// Items inserted, counting repeats
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len >= self.capacity
+    }
+
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// False-positive rate expected from the share of bits set
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        let set: u64 = self.bits.iter().map(|word| word.count_ones() as u64).sum();
+        (set as f64 / self.num_bits as f64).powi(self.num_hashes as i32)
+    }
+
+    /// Kirsch-Mitzenmacher double hashing
+    fn bit_index(&self, h1: u64, h2: u64, i: u32) -> u64 {
+        h1.wrapping_add((i as u64).wrapping_mul(h2)) % self.num_bits
+    }
+}
+
+/// Bloom filter that adds a larger stage whenever the newest one fills up
+#[derive(Debug, Clone)]
+pub struct ScalableBloomFilter {
+    stages: Vec<BloomFilter>,
+    false_positive_rate: f64,
+}
+
+/// Each stage's error rate is this fraction of the one before, so the
+/// combined rate stays under twice the first stage's
+const STAGE_TIGHTENING: f64 = 0.5;
+
+impl ScalableBloomFilter {
+    /// Filter starting at `initial_capacity` items, aiming for about
+    /// `false_positive_rate` however many items are added
+    pub fn new(initial_capacity: usize, false_positive_rate: f64) -> Self {
+        let first_rate = false_positive_rate * (1.0 - STAGE_TIGHTENING);
+        Self {
+            stages: vec![BloomFilter::new(initial_capacity, first_rate)],
+            false_positive_rate,
+        }
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        let newest = self.stages.last().expect("filter has a stage");
+        if newest.is_full() {
+            let rate = self.false_positive_rate * (1.0 - STAGE_TIGHTENING) * STAGE_TIGHTENING.powi(self.stages.len() as i32);
+            let stage = BloomFilter::new(newest.capacity() * 2, rate);
+            self.stages.push(stage);
+        }
+        self.stages.last_mut().expect("filter has a stage").insert(item);
+    }
+
+    /// False means `item` was never inserted
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.stages.iter().any(|stage| stage.contains(item))
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.iter().map(BloomFilter::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stages(&self) -> &[BloomFilter] {
+        &self.stages
+    }
+
+    /// Memory held by the filter's bit arrays
+    pub fn size_bytes(&self) -> u64 {
+        self.stages.iter().map(|stage| stage.num_bits().div_ceil(8)).sum()
+    }
+
+    /// Chance a never-inserted item is reported present, from bit occupancy
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        1.0 - self.stages.iter()
+            .map(|stage| 1.0 - stage.estimated_false_positive_rate())
+            .product::<f64>()
+    }
+}
+
+/// Counters for how well a filter is screening lookups
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FilterStats {
+    /// Lookups that consulted the filter
+    pub queries: u64,
+    /// Lookups the filter answered alone
+    pub negatives: u64,
+    /// Lookups the filter passed on for confirmation
+    pub positives: u64,
+    /// Positives the confirmation showed were wrong
+    pub false_positives: u64,
+    /// Positives the confirmation could not settle either way
+    pub unresolved: u64,
+    /// Items in the filter
+    pub entries: usize,
+    pub stages: usize,
+    pub size_bytes: u64,
+    /// Expected rate, from bit occupancy
+    pub estimated_false_positive_rate: f64,
+    /// Measured rate: false positives over lookups of items known absent,
+    /// leaving unresolved positives out
+    pub observed_false_positive_rate: f64,
+}
+
+fn hash_pair(item: &[u8]) -> (u64, u64) {
+    let hash = blake3::hash(item);
+    let bytes = hash.as_bytes();
+    let h1 = u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes"));
+    // Odd, so successive probes never repeat within a stage
+    let h2 = u64::from_le_bytes(bytes[8..16].try_into().expect("8 bytes")) | 1;
+    (h1, h2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives_and_rate_near_target() {
+        let mut filter = ScalableBloomFilter::new(1_000, 0.01);
+        for i in 0u32..10_000 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((0u32..10_000).all(|i| filter.contains(&i.to_le_bytes())));
+        assert_eq!(filter.len(), 10_000);
+        // 1k, 2k, 4k, 8k
+        assert_eq!(filter.stages().len(), 4);
+
+        let false_positives = (10_000u32..110_000).filter(|i| filter.contains(&i.to_le_bytes())).count();
+        let observed = false_positives as f64 / 100_000.0;
+        assert!(observed < 0.02, "observed false-positive rate {}", observed);
+        assert!(filter.estimated_false_positive_rate() < 0.02);
+    }
+
+    #[test]
+    fn test_fixed_filter_sizing() {
+        let filter = BloomFilter::new(1_000, 0.01);
+        // About 9.6 bits and 7 hashes per item at 1%
+        assert!((9_000..10_000).contains(&filter.num_bits()));
+        assert_eq!(filter.num_hashes(), 7);
+        assert!(!filter.contains(b"anything"));
+        assert_eq!(filter.estimated_false_positive_rate(), 0.0);
+    }
+}
@@ -0,0 +1,221 @@
+//! Canonical JSON forms for API responses
+//!
+//! Hashes are lowercase hex strings and amounts are satoshis as decimal
+//! strings, whichever API returns them. A `Hash256` serialized by serde is
+//! an array of 32 numbers, and satoshi totals can exceed the 2^53 integers
+//! JavaScript clients read exactly, so responses wrap them in [`HexHash`]
+//! and [`Sats`]. Struct fields keep their plain types and pick the form
+//! with `#[serde(with = "canonical::sats")]` and friends.
+//!
+//! Decoding also accepts the older forms, byte arrays and JSON integers, so
+//! records written before are still read.
+
+use crate::Hash256;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// A block or transaction hash, as 64 hex digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HexHash(pub Hash256);
+
+/// Script, payload or other raw bytes, as hex
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HexBytes(pub Vec<u8>);
+
+/// An amount in satoshis, as a decimal string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Sats(pub u64);
+
+impl fmt::Display for HexHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Display for HexBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(&self.0))
+    }
+}
+
+impl fmt::Display for Sats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<Hash256> for HexHash {
+    fn from(hash: Hash256) -> Self {
+        HexHash(hash)
+    }
+}
+
+impl From<Vec<u8>> for HexBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        HexBytes(bytes)
+    }
+}
+
+impl From<u64> for Sats {
+    fn from(satoshis: u64) -> Self {
+        Sats(satoshis)
+    }
+}
+
+impl From<crate::amount::Amount> for Sats {
+    fn from(amount: crate::amount::Amount) -> Self {
+        Sats(amount.to_sat())
+    }
+}
+
+impl Serialize for HexHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl Serialize for HexBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl Serialize for Sats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Hex text, or the byte array serde writes for raw bytes
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BytesRepr {
+    Hex(String),
+    Array(Vec<u8>),
+}
+
+impl BytesRepr {
+    fn into_bytes<E: de::Error>(self) -> Result<Vec<u8>, E> {
+        match self {
+            BytesRepr::Hex(text) => hex::decode(text.strip_prefix("0x").unwrap_or(&text)).map_err(E::custom),
+            BytesRepr::Array(bytes) => Ok(bytes),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HexHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = BytesRepr::deserialize(deserializer)?.into_bytes()?;
+        let hash: Hash256 = bytes.as_slice().try_into()
+            .map_err(|_| de::Error::custom(format!("hash must be 32 bytes, got {}", bytes.len())))?;
+        Ok(HexHash(hash))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BytesRepr::deserialize(deserializer)?.into_bytes().map(HexBytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sats {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(satoshis) => Ok(Sats(satoshis)),
+            Repr::Text(text) => text.parse().map(Sats)
+                .map_err(|_| de::Error::custom(format!("'{}' is not a whole number of satoshis", text))),
+        }
+    }
+}
+
+/// `#[serde(with)]` form of [`HexHash`] for `Hash256` fields
+pub mod hex_hash {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hash: &Hash256, serializer: S) -> Result<S::Ok, S::Error> {
+        HexHash(*hash).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash256, D::Error> {
+        HexHash::deserialize(deserializer).map(|hash| hash.0)
+    }
+}
+
+/// `#[serde(with)]` form of [`HexHash`] for `Option<Hash256>` fields
+pub mod opt_hex_hash {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hash: &Option<Hash256>, serializer: S) -> Result<S::Ok, S::Error> {
+        hash.map(HexHash).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Hash256>, D::Error> {
+        Option::<HexHash>::deserialize(deserializer).map(|hash| hash.map(|hash| hash.0))
+    }
+}
+
+/// `#[serde(with)]` form of [`Sats`] for `u64` amount fields
+pub mod sats {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(satoshis: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        Sats(*satoshis).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        Sats::deserialize(deserializer).map(|sats| sats.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_canonical_forms() {
+        let hash = [0xab; 32];
+        assert_eq!(json!(HexHash(hash)), json!("ab".repeat(32)));
+        assert_eq!(json!(HexBytes(vec![0x76, 0xa9])), json!("76a9"));
+        assert_eq!(json!(Sats(u64::MAX)), json!("18446744073709551615"));
+    }
+
+    #[test]
+    fn test_older_forms_still_decode() {
+        let hash = [7u8; 32];
+        let from_array: HexHash = serde_json::from_value(json!(hash)).unwrap();
+        let from_hex: HexHash = serde_json::from_value(json!(hex::encode(hash))).unwrap();
+        assert_eq!(from_array, HexHash(hash));
+        assert_eq!(from_hex, HexHash(hash));
+        assert!(serde_json::from_value::<HexHash>(json!("abcd")).is_err());
+
+        assert_eq!(serde_json::from_value::<Sats>(json!(1500)).unwrap(), Sats(1500));
+        assert_eq!(serde_json::from_value::<Sats>(json!("1500")).unwrap(), Sats(1500));
+        assert!(serde_json::from_value::<Sats>(json!("1.5")).is_err());
+    }
+
+    #[test]
+    fn test_field_helpers() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Sale {
+            #[serde(with = "sats")]
+            amount: u64,
+            #[serde(with = "opt_hex_hash")]
+            tx_hash: Option<Hash256>,
+        }
+        let sale = Sale { amount: 250, tx_hash: Some([1; 32]) };
+        let value = serde_json::to_value(&sale).unwrap();
+        assert_eq!(value, json!({ "amount": "250", "tx_hash": hex::encode([1u8; 32]) }));
+        assert_eq!(serde_json::from_value::<Sale>(value).unwrap(), sale);
+
+        // As an older release stored it
+        let old = json!({ "amount": 250, "tx_hash": [1u8; 32] });
+        assert_eq!(serde_json::from_value::<Sale>(old).unwrap(), sale);
+    }
+}
@@ -0,0 +1,258 @@
+//! Optional chain indexes
+//!
+//! Indexes are built by feeding them blocks in height order, either while
+//! catching up on an existing chain or as new blocks connect. Each one
+//! remembers the last block it indexed so a builder can resume where it left
+//! off, and can be reset to start over after a reorg.
+
+use crate::{block::Block, Address, Hash256, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// An index built from blocks in height order
+pub trait ChainIndex: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Add a block; it must be the block after `tip()`, or genesis after a reset
+    fn index_block(&self, block: &Block) -> Result<()>;
+
+    /// Height and hash of the last indexed block
+    fn tip(&self) -> Option<(u64, Hash256)>;
+
+    /// Forget everything so the chain can be indexed again from genesis
+    fn reset(&self);
+
+    /// Entries held, for reporting the index's size
+    fn entry_count(&self) -> usize;
+}
+
+/// Where a transaction was confirmed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxLocation {
+    pub block_height: u64,
+    pub block_hash: String,
+    /// Position of the transaction within its block
+    pub position: u32,
+}
+
+/// Transaction id to the block that confirmed it
+#[derive(Default)]
+pub struct TxIndex {
+    entries: RwLock<HashMap<Hash256, TxLocation>>,
+    tip: RwLock<Option<(u64, Hash256)>>,
+}
+
+impl TxIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, txid: &Hash256) -> Option<TxLocation> {
+        self.entries.read().unwrap().get(txid).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ChainIndex for TxIndex {
+    fn name(&self) -> &'static str {
+        "txindex"
+    }
+
+    fn index_block(&self, block: &Block) -> Result<()> {
+        let height = block.header.height as u64;
+        let hash = block.get_hash();
+        let block_hash = hex::encode(hash);
+        let mut entries = self.entries.write().unwrap();
+        for (position, tx) in block.transactions.iter().enumerate() {
+            entries.insert(tx.get_hash()?, TxLocation {
+                block_height: height,
+                block_hash: block_hash.clone(),
+                position: position as u32,
+            });
+        }
+        *self.tip.write().unwrap() = Some((height, hash));
+        Ok(())
+    }
+
+    fn tip(&self) -> Option<(u64, Hash256)> {
+        *self.tip.read().unwrap()
+    }
+
+    fn reset(&self) {
+        self.entries.write().unwrap().clear();
+        *self.tip.write().unwrap() = None;
+    }
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// One transaction touching an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressTx {
+    pub txid: String,
+    pub block_height: u64,
+    /// Paid to the address by this transaction, in satoshis
+    #[serde(with = "crate::canonical::sats")]
+    pub received: u64,
+    /// Spent from the address by this transaction, in satoshis
+    #[serde(with = "crate::canonical::sats")]
+    pub spent: u64,
+}
+
+#[derive(Default)]
+struct AddressState {
+    history: HashMap<Address, Vec<AddressTx>>,
+    /// Owner and value of every indexed output, to attribute spends
+    outputs: HashMap<(Hash256, u32), (Address, u64)>,
+}
+
+/// Address to the transactions that paid or spent from it
+#[derive(Default)]
+pub struct AddressIndex {
+    state: RwLock<AddressState>,
+    tip: RwLock<Option<(u64, Hash256)>>,
+}
+
+impl AddressIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transactions touching `address`, newest first
+    pub fn history(&self, address: &str, limit: usize) -> Vec<AddressTx> {
+        self.state.read().unwrap().history.get(address)
+            .map(|txs| txs.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn address_count(&self) -> usize {
+        self.state.read().unwrap().history.len()
+    }
+}
+
+impl ChainIndex for AddressIndex {
+    fn name(&self) -> &'static str {
+        "addressindex"
+    }
+
+    fn index_block(&self, block: &Block) -> Result<()> {
+        let height = block.header.height as u64;
+        let mut state = self.state.write().unwrap();
+        for tx in &block.transactions {
+            let tx_hash = tx.get_hash()?;
+            let mut touched: HashMap<Address, (u64, u64)> = HashMap::new();
+
+            if !tx.is_coinbase() {
+                for input in &tx.inputs {
+                    let outpoint = (input.prev_tx_hash, input.prev_output_index);
+                    if let Some((address, value)) = state.outputs.remove(&outpoint) {
+                        touched.entry(address).or_default().1 += value;
+                    }
+                }
+            }
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let Some(address) = output.get_address() else { continue };
+                touched.entry(address.clone()).or_default().0 += output.value;
+                state.outputs.insert((tx_hash, index as u32), (address, output.value));
+            }
+
+            let txid = hex::encode(tx_hash);
+            let mut touched: Vec<_> = touched.into_iter().collect();
+            touched.sort_by(|a, b| a.0.cmp(&b.0));
+            for (address, (received, spent)) in touched {
+                state.history.entry(address).or_default().push(AddressTx {
+                    txid: txid.clone(),
+                    block_height: height,
+                    received,
+                    spent,
+                });
+            }
+        }
+        *self.tip.write().unwrap() = Some((height, block.get_hash()));
+        Ok(())
+    }
+
+    fn tip(&self) -> Option<(u64, Hash256)> {
+        *self.tip.read().unwrap()
+    }
+
+    fn reset(&self) {
+        *self.state.write().unwrap() = AddressState::default();
+        *self.tip.write().unwrap() = None;
+    }
+
+    fn entry_count(&self) -> usize {
+        let state = self.state.read().unwrap();
+        state.history.values().map(Vec::len).sum::<usize>() + state.outputs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::transaction::{Transaction, TransactionInput, TransactionOutput};
+
+    fn block(height: u32, txs: Vec<Transaction>) -> Block {
+        Block::new(BlockHeader::new(1, [0u8; 32], [0u8; 32], 1, height), txs)
+    }
+
+    fn address(seed: u8) -> Address {
+        let key = crate::crypto::derive_public_key(&[seed; 32]).unwrap();
+        crate::script_utils::ScriptBuilder::pubkey_to_address(&key.as_slice().try_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_tx_index_locates_transactions() {
+        let index = TxIndex::new();
+        let coinbase = Transaction::new(1, vec![TransactionInput::create_coinbase(vec![1])], vec![
+            TransactionOutput::create_p2pkh(50, &address(1)).unwrap(),
+        ]);
+        let txid = coinbase.get_hash().unwrap();
+        let genesis = block(0, vec![coinbase]);
+        index.index_block(&genesis).unwrap();
+
+        let location = index.get(&txid).unwrap();
+        assert_eq!(location.block_height, 0);
+        assert_eq!(location.block_hash, hex::encode(genesis.get_hash()));
+        assert_eq!(index.tip().unwrap().0, 0);
+
+        index.reset();
+        assert!(index.get(&txid).is_none());
+        assert!(index.tip().is_none());
+    }
+
+    #[test]
+    fn test_address_index_tracks_receipts_and_spends() {
+        let index = AddressIndex::new();
+        let (alice, bob) = (address(1), address(2));
+        let coinbase = Transaction::new(1, vec![TransactionInput::create_coinbase(vec![1])], vec![
+            TransactionOutput::create_p2pkh(100, &alice).unwrap(),
+        ]);
+        let funding = coinbase.get_hash().unwrap();
+        index.index_block(&block(0, vec![coinbase])).unwrap();
+
+        let payment = Transaction::new(1, vec![TransactionInput::new(funding, 0, vec![])], vec![
+            TransactionOutput::create_p2pkh(60, &bob).unwrap(),
+            TransactionOutput::create_p2pkh(39, &alice).unwrap(),
+        ]);
+        index.index_block(&block(1, vec![payment])).unwrap();
+
+        let history = index.history(&alice, 10);
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[0].received, history[0].spent), (39, 100));
+        assert_eq!((history[1].received, history[1].spent), (100, 0));
+        assert_eq!(index.history(&bob, 10)[0].received, 60);
+        assert_eq!(index.address_count(), 2);
+    }
+}
@@ -4,15 +4,23 @@
 //! Manages blockchain state, validates blocks/transactions, and handles reorganization.
 
 use crate::{
-    Hash256, Amount, Result, BlockchainError, BlockHeight, Timestamp,
+    Address, Hash256, Amount, Result, BlockchainError, BlockHeight, Timestamp,
     block::{Block, BlockHeader},
-    transaction::{Transaction, TransactionInput, TransactionOutput}, 
-    utxo::{UTXOSet, UTXO},
+    bloom::FilterStats,
+    transaction::{Transaction, TransactionInput, TransactionOutput, LOCKTIME_THRESHOLD, SEQUENCE_FINAL},
+    script_utils::{HtlcScript, HtlcSpend, MultisigScript, ScriptBuilder, ScriptHash, ScriptHashSpend},
+    sighash::{SighashCache, PRECOMPUTED_SIGHASH_DEPLOYMENT},
+    signature::{self, BatchItem},
+    utxo::{SpentStatus, UTXOSet, UtxoSetInfo, UTXO},
+    versionbits::{Deployment, VersionBitsTracker},
+    supply_audit::{SupplyAuditConfig, SupplyAuditor},
+    network_time::NetworkTime,
 };
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock as AsyncRwLock;
-use tracing::{info, debug};
+use tokio::sync::{RwLock as AsyncRwLock, broadcast};
+use tracing::{info, debug, warn};
 
 /// Consensus parameters for the blockchain network
 #[derive(Debug, Clone)]
@@ -27,12 +35,98 @@ pub struct ConsensusParams {
     pub min_difficulty_target: u32,
     /// Maximum difficulty target
     pub max_difficulty_target: u32,
-    /// Coinbase reward in satoshis
+    /// Coinbase reward
     pub block_reward: Amount,
     /// Maximum number of inputs per transaction
     pub max_tx_inputs: usize,
     /// Maximum number of outputs per transaction
     pub max_tx_outputs: usize,
+    /// Share of the block reward every coinbase must pay to a fund
+    pub dev_fund: Option<CoinbaseSplit>,
+    /// Soft forks deployed through version bits signaling
+    pub deployments: Vec<Deployment>,
+    /// Signaling blocks per retarget window needed to lock a deployment in
+    pub deployment_threshold: u64,
+}
+
+impl ConsensusParams {
+    /// Require every coinbase to pay `percent` of the block reward to `address`
+    pub fn with_dev_fund(mut self, address: Address, percent: u8) -> Result<Self> {
+        if percent > 100 {
+            return Err(BlockchainError::InvalidInput(format!("Dev fund share {}% exceeds 100%", percent)));
+        }
+        self.dev_fund = (percent > 0).then_some(CoinbaseSplit { address, percent });
+        Ok(self)
+    }
+    
+    /// Fingerprint of every consensus-relevant parameter. Nodes whose hashes
+    /// differ will disagree about block validity.
+    pub fn params_hash(&self) -> Hash256 {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(self.max_block_size as u64).to_le_bytes());
+        data.extend_from_slice(&self.target_block_time.to_le_bytes());
+        data.extend_from_slice(&self.difficulty_adjustment_interval.to_le_bytes());
+        data.extend_from_slice(&self.min_difficulty_target.to_le_bytes());
+        data.extend_from_slice(&self.max_difficulty_target.to_le_bytes());
+        data.extend_from_slice(&self.block_reward.to_sat().to_le_bytes());
+        data.extend_from_slice(&(self.max_tx_inputs as u64).to_le_bytes());
+        data.extend_from_slice(&(self.max_tx_outputs as u64).to_le_bytes());
+        match &self.dev_fund {
+            Some(fund) => {
+                data.push(1);
+                data.push(fund.percent);
+                data.extend_from_slice(&(fund.address.len() as u32).to_le_bytes());
+                data.extend_from_slice(fund.address.as_bytes());
+            }
+            None => data.push(0),
+        }
+        data.extend_from_slice(&(self.deployments.len() as u32).to_le_bytes());
+        for deployment in &self.deployments {
+            data.extend_from_slice(&(deployment.name.len() as u32).to_le_bytes());
+            data.extend_from_slice(deployment.name.as_bytes());
+            data.push(deployment.bit);
+            data.extend_from_slice(&deployment.start_height.to_le_bytes());
+            data.extend_from_slice(&deployment.timeout_height.to_le_bytes());
+            data.extend_from_slice(&deployment.min_activation_height.to_le_bytes());
+        }
+        data.extend_from_slice(&self.deployment_threshold.to_le_bytes());
+        crate::hashing::double_sha256(&data)
+    }
+    
+    /// Coinbase outputs paying the block reward plus `fees`, with the dev fund
+    /// share split off to the fund and the rest to the miner
+    pub fn coinbase_outputs(&self, miner_address: &str, fees: Amount) -> Result<Vec<TransactionOutput>> {
+        let fund_share = self.dev_fund.as_ref().map_or(Amount::ZERO, |fund| fund.share_of(self.block_reward));
+        let miner_share = self.block_reward.checked_sub(fund_share)?.checked_add(fees)?;
+        let mut outputs = vec![TransactionOutput::create_p2pkh(miner_share.to_sat(), miner_address)?];
+        if let Some(fund) = self.dev_fund.as_ref().filter(|_| fund_share > Amount::ZERO) {
+            outputs.push(TransactionOutput::create_p2pkh(fund_share.to_sat(), &fund.address)?);
+        }
+        Ok(outputs)
+    }
+}
+
+/// Fixed percentage of the block reward paid to a development/treasury address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinbaseSplit {
+    pub address: Address,
+    /// Percentage of the block reward, fees excluded
+    pub percent: u8,
+}
+
+impl CoinbaseSplit {
+    /// Amount owed to the fund out of a block reward
+    pub fn share_of(&self, block_reward: Amount) -> Amount {
+        Amount::from_sat((block_reward.to_sat() as u128 * self.percent.min(100) as u128 / 100) as u64)
+    }
+    
+    /// Amount a coinbase pays to the fund address, saturating at `Amount::MAX`
+    pub fn paid_by(&self, coinbase: &Transaction) -> Amount {
+        let paid = coinbase.outputs.iter()
+            .filter(|output| output.get_address().as_deref() == Some(self.address.as_str()))
+            .map(|output| Amount::from_sat(output.value));
+        Amount::checked_sum(paid).unwrap_or(Amount::MAX)
+    }
 }
 
 impl Default for ConsensusParams {
@@ -43,9 +137,12 @@ impl Default for ConsensusParams {
             difficulty_adjustment_interval: 2016, // ~2 weeks
             min_difficulty_target: 0x01000000,
             max_difficulty_target: 0xFF000000,
-            block_reward: 50_00000000, // 50 coins
+            block_reward: Amount::from_sat(50_00000000), // 50 coins
             max_tx_inputs: 1000,
             max_tx_outputs: 1000,
+            dev_fund: None,
+            deployments: Vec::new(),
+            deployment_threshold: 1916, // 95% of a retarget window
         }
     }
 }
@@ -88,15 +185,48 @@ pub enum BlockValidation {
     OrphanBlock(Hash256), // Missing parent block
 }
 
+/// New best block, sent to tip subscribers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainTip {
+    pub height: BlockHeight,
+    pub hash: Hash256,
+}
+
+/// Signature hash, signature and public key an input must verify
+type SignatureCheck = (Hash256, Vec<u8>, Vec<u8>);
+
 /// Transaction validation context
 #[derive(Debug)]
 pub struct TxValidationContext {
     pub block_height: BlockHeight,
     pub block_time: Timestamp,
     pub utxo_set: UTXOSet,
+    /// Whether `PRECOMPUTED_SIGHASH_DEPLOYMENT` is active at `block_height`
+    pub precomputed_sighash: bool,
 }
 
+/// Simplified proof-of-work check: a block hash's first byte must be below
+/// this (difficulty_target is Bitcoin compact format, high = easier)
+pub fn pow_threshold(difficulty_target: u32) -> u8 {
+    if difficulty_target > 0x1d000000 {
+        255 // Very easy
+    } else if difficulty_target > 0x1c000000 {
+        128
+    } else if difficulty_target > 0x1b000000 {
+        64
+    } else {
+        16 // Harder
+    }
+}
+
+/// Furthest a block's timestamp may be ahead of network-adjusted time
+pub const MAX_FUTURE_BLOCK_TIME: u64 = 2 * 60 * 60;
+
 /// Main consensus validator
+///
+/// Code holding more than one of the chain locks takes them in the order
+/// `utxo_set`, `blocks`, `chain_state`, so rewinds and block connection
+/// can't deadlock each other.
 pub struct ConsensusValidator {
     params: ConsensusParams,
     chain_state: Arc<AsyncRwLock<ChainState>>,
@@ -105,12 +235,28 @@ pub struct ConsensusValidator {
     blocks: Arc<AsyncRwLock<HashMap<u64, Block>>>, // In-memory cache for fast access
     orphan_blocks: Arc<AsyncRwLock<HashMap<Hash256, Block>>>,
     storage: Option<Arc<crate::storage::DiskBlockStorage>>, // Persistent storage
+    tip_sender: broadcast::Sender<ChainTip>,
+    versionbits: Arc<AsyncRwLock<VersionBitsTracker>>,
+    supply_audit: Option<Arc<AsyncRwLock<SupplyAuditor>>>,
+    /// UTXO set at genesis, replayed from when rewinding the chain
+    genesis_utxos: Arc<AsyncRwLock<UTXOSet>>,
+    /// Operator-invalidated blocks and their descendants -> the block invalidated
+    invalidated: Arc<AsyncRwLock<HashMap<Hash256, Hash256>>>,
+    /// Blocks disconnected from the best chain, kept for reconsideration
+    side_blocks: Arc<AsyncRwLock<HashMap<Hash256, Block>>>,
+    /// Clock corrected by peer time samples, for timestamp checks
+    network_time: Arc<NetworkTime>,
     // Static ConsensusMiner methods used directly
 }
 
 impl ConsensusValidator {
     /// Create new consensus validator
     pub fn new(params: ConsensusParams) -> Self {
+        let versionbits = VersionBitsTracker::new(
+            params.deployments.clone(),
+            params.difficulty_adjustment_interval,
+            params.deployment_threshold,
+        );
         Self {
             params,
             chain_state: Arc::new(AsyncRwLock::new(ChainState::default())),
@@ -119,16 +265,86 @@ impl ConsensusValidator {
             blocks: Arc::new(AsyncRwLock::new(HashMap::new())),
             orphan_blocks: Arc::new(AsyncRwLock::new(HashMap::new())),
             storage: None, // No storage by default
+            tip_sender: broadcast::channel(64).0,
+            versionbits: Arc::new(AsyncRwLock::new(versionbits)),
+            supply_audit: None,
+            genesis_utxos: Arc::new(AsyncRwLock::new(UTXOSet::new())),
+            invalidated: Arc::new(AsyncRwLock::new(HashMap::new())),
+            side_blocks: Arc::new(AsyncRwLock::new(HashMap::new())),
+            network_time: Arc::new(NetworkTime::default()),
             // miner: ConsensusMiner::new(), // Static methods only
         }
     }
     
-    /// Enable persistent storage
+    /// Consensus parameters in force
+    pub fn params(&self) -> &ConsensusParams {
+        &self.params
+    }
+    
+    /// Subscribe to best-chain tip changes
+    pub fn subscribe_tip(&self) -> broadcast::Receiver<ChainTip> {
+        self.tip_sender.subscribe()
+    }
+    
+    /// Snapshot of every deployment's signaling state
+    pub async fn get_deployment_info(&self) -> VersionBitsTracker {
+        self.versionbits.read().await.clone()
+    }
+    
+    /// Block version to mine at the next height, signaling every deployment in progress
+    pub async fn next_block_version(&self) -> u32 {
+        self.versionbits.read().await.block_version()
+    }
+    
+    /// Whether a deployment's rules apply at `height`
+    pub async fn is_deployment_active(&self, name: &str, height: BlockHeight) -> bool {
+        self.versionbits.read().await.is_active_at(name, height)
+    }
+    
+    /// Enable persistent storage, restoring the blocks the operator had
+    /// invalidated
     pub fn with_storage(mut self, storage: Arc<crate::storage::DiskBlockStorage>) -> Self {
+        match storage.load_invalidated() {
+            Ok(invalidated) => self.invalidated = Arc::new(AsyncRwLock::new(invalidated)),
+            Err(e) => warn!("Could not restore invalidated blocks: {}", e),
+        }
         self.storage = Some(storage);
         self
     }
     
+    /// Judge block timestamps against a shared network clock
+    pub fn with_network_time(mut self, network_time: Arc<NetworkTime>) -> Self {
+        self.network_time = network_time;
+        self
+    }
+    
+    /// Network-adjusted clock used for block timestamps
+    pub fn network_time(&self) -> Arc<NetworkTime> {
+        self.network_time.clone()
+    }
+    
+    /// Check the supply invariant at every block connect, halting on the first violation
+    pub fn with_supply_audit(mut self, config: SupplyAuditConfig) -> Self {
+        self.supply_audit = Some(Arc::new(AsyncRwLock::new(SupplyAuditor::new(config))));
+        self
+    }
+    
+    /// Supply audit state, if auditing is enabled
+    pub async fn get_supply_audit(&self) -> Option<SupplyAuditor> {
+        match &self.supply_audit {
+            Some(auditor) => Some(auditor.read().await.clone()),
+            None => None,
+        }
+    }
+    
+    /// Refuse new blocks once the supply audit has found a divergence
+    async fn check_supply_audit(&self) -> Result<()> {
+        match &self.supply_audit {
+            Some(auditor) => auditor.read().await.check_not_halted(),
+            None => Ok(()),
+        }
+    }
+    
     /// Initialize the blockchain with genesis state
     pub async fn initialize_with_genesis(&self, genesis_state: crate::genesis::GenesisState) -> Result<()> {
         // Verify genesis state first
@@ -144,6 +360,10 @@ impl ConsensusValidator {
         {
             let mut utxo_set = self.utxo_set.write().await;
             *utxo_set = genesis_state.utxo_set;
+            *self.genesis_utxos.write().await = utxo_set.clone();
+            if let Some(auditor) = &self.supply_audit {
+                auditor.write().await.start(&utxo_set);
+            }
         }
         
         // Persist genesis block to disk if storage is enabled
@@ -168,6 +388,8 @@ impl ConsensusValidator {
             chain_state.next_difficulty = self.params.max_difficulty_target; // Start with lowest difficulty
         }
         
+        self.versionbits.write().await.connect_block(0, genesis_header.version);
+        
         {
             let mut block_index = self.block_index.write().await;
             block_index.insert(genesis_hash, genesis_header);
@@ -184,6 +406,27 @@ impl ConsensusValidator {
         self.utxo_set.read().await.clone()
     }
 
+    /// One unspent output of the best chain, without copying the set
+    pub async fn get_utxo(&self, outpoint: &str) -> Option<UTXO> {
+        self.utxo_set.read().await.get_utxo(outpoint).cloned()
+    }
+
+    /// Count, value, size and hash of the UTXO set, without copying it
+    pub async fn utxo_set_info(&self) -> UtxoSetInfo {
+        self.utxo_set.read().await.set_info()
+    }
+
+    /// Whether the best chain may have spent `outpoint`, mostly answered by
+    /// the spent-outpoint filter without a UTXO lookup
+    pub async fn outpoint_spent_status(&self, outpoint: &str) -> SpentStatus {
+        self.utxo_set.read().await.spent_status(outpoint)
+    }
+    
+    /// Hit and false-positive counters of the spent-outpoint filter
+    pub async fn spent_filter_stats(&self) -> FilterStats {
+        self.utxo_set.read().await.spent_filter_stats()
+    }
+
     /// Validate a complete block
     pub async fn validate_block(&self, block: &Block) -> Result<BlockValidation> {
         // 1. Basic structure validation
@@ -200,20 +443,17 @@ impl ConsensusValidator {
         let block_hash = block.header.calculate_hash();
         let hash_hex = hex::encode(&block_hash);
         
-        // Validate PoW: Check if hash meets difficulty target
-        // Simplified check: just verify first byte is low enough
-        // (difficulty_target is Bitcoin compact format, high = easier)
-        let difficulty_target = block.header.difficulty_target;
-        let threshold = if difficulty_target > 0x1d000000 {
-            255u8 // Very easy
-        } else if difficulty_target > 0x1c000000 {
-            128
-        } else if difficulty_target > 0x1b000000 {
-            64
-        } else {
-            16 // Harder
-        };
+        if let Some(root) = self.invalidated_root(&block.header).await {
+            return Ok(BlockValidation::Invalid(format!("Block descends from invalidated block {}", hex::encode(root))));
+        }
+        
+        // Judged against network time so a skewed local clock doesn't reject good blocks
+        if block.header.timestamp as u64 > self.network_time.adjusted_time() + MAX_FUTURE_BLOCK_TIME {
+            return Ok(BlockValidation::Invalid("Block timestamp too far in the future".to_string()));
+        }
         
+        // Validate PoW: Check if hash meets difficulty target
+        let threshold = pow_threshold(block.header.difficulty_target);
         if block_hash[0] >= threshold {
             return Ok(BlockValidation::Invalid(format!(
                 "Invalid proof of work: hash[0]={} >= threshold={}",
@@ -248,6 +488,9 @@ impl ConsensusValidator {
             return Err(BlockchainError::InvalidBlock("Block must contain at least one transaction".to_string()));
         }
         
+        // Extension records must be understood unless optional
+        crate::schema::check_block(block)?;
+        
         // First transaction must be coinbase
         if !self.is_coinbase_transaction(&block.transactions[0]) {
             return Err(BlockchainError::InvalidBlock("First transaction must be coinbase".to_string()));
@@ -277,15 +520,19 @@ impl ConsensusValidator {
         let utxo_set = self.utxo_set.read().await;
         let chain_state = self.chain_state.read().await;
         
+        let block_height = chain_state.height + 1;
+        let utxo_snapshot = utxo_set.clone();
+        drop(utxo_set);
+        drop(chain_state);
+        
         let context = TxValidationContext {
-            block_height: chain_state.height + 1,
+            block_height,
             block_time: block.header.timestamp as u64,
-            utxo_set: utxo_set.clone(),
+            utxo_set: utxo_snapshot,
+            precomputed_sighash: self.is_deployment_active(PRECOMPUTED_SIGHASH_DEPLOYMENT, block_height).await,
         };
-        drop(utxo_set);
-        drop(chain_state);
         
-        let mut total_fees = 0u64;
+        let mut total_fees = Amount::ZERO;
         
         // Validate each transaction
         for (i, tx) in block.transactions.iter().enumerate() {
@@ -296,13 +543,15 @@ impl ConsensusValidator {
                 // Validate regular transaction
                 let fee = self.validate_transaction(tx, &context)?;
                 total_fees = total_fees.checked_add(fee)
-                    .ok_or_else(|| BlockchainError::InvalidBlock("Fee overflow".to_string()))?;
+                    .map_err(|_| BlockchainError::InvalidBlock("Fee overflow".to_string()))?;
             }
         }
         
         // Validate coinbase reward
-        let coinbase_output_value: u64 = block.transactions[0].outputs.iter().map(|o| o.value).sum();
-        let expected_reward = self.params.block_reward + total_fees;
+        let coinbase_output_value = Amount::checked_sum(block.transactions[0].outputs.iter().map(|o| Amount::from_sat(o.value)))
+            .map_err(|_| BlockchainError::InvalidBlock("Coinbase output value overflow".to_string()))?;
+        let expected_reward = self.params.block_reward.checked_add(total_fees)
+            .map_err(|_| BlockchainError::InvalidBlock("Block reward overflow".to_string()))?;
         
         if coinbase_output_value > expected_reward {
             return Err(BlockchainError::InvalidBlock(
@@ -310,6 +559,17 @@ impl ConsensusValidator {
             ));
         }
         
+        // Validate the dev fund share
+        if let Some(fund) = &self.params.dev_fund {
+            let required = fund.share_of(self.params.block_reward);
+            let paid = fund.paid_by(&block.transactions[0]);
+            if paid < required {
+                return Err(BlockchainError::InvalidBlock(
+                    format!("Coinbase pays dev fund {} of required {}", paid, required)
+                ));
+            }
+        }
+        
         Ok(())
     }
     
@@ -332,9 +592,22 @@ impl ConsensusValidator {
             return Err(BlockchainError::InvalidTransaction("No outputs".to_string()));
         }
         
+        crate::schema::check_transaction(tx)?;
+        
+        if !tx.is_final(context.block_height, context.block_time) {
+            return Err(BlockchainError::InvalidTransaction(
+                format!("Transaction locked until {}", tx.locktime)
+            ));
+        }
+        
         // Validate inputs and calculate total input value
         let mut total_input_value = 0u64;
         let mut used_outpoints = HashSet::new();
+        // Shared across inputs so hashing stays linear in the input count
+        let mut sighashes = SighashCache::new(tx).allow_precomputed(context.precomputed_sighash);
+        // Signatures are checked together once every input's script parses
+        let mut signature_checks = Vec::with_capacity(tx.inputs.len());
+        let mut signed_inputs = Vec::with_capacity(tx.inputs.len());
         
         for (input_index, input) in tx.inputs.iter().enumerate() {
             let outpoint_key = format!("{}:{}", hex::encode(&input.prev_tx_hash), input.prev_output_index);
@@ -344,16 +617,19 @@ impl ConsensusValidator {
                 return Err(BlockchainError::InvalidTransaction("Duplicate input".to_string()));
             }
             
-            // Real UTXO validation - lookup the referenced output
-            // Use try_read since we're in a sync function called from async context
-            let utxo_set = self.utxo_set.try_read()
-                .map_err(|_| BlockchainError::InvalidTransaction("UTXO set locked".to_string()))?;
-            if let Some(utxo) = utxo_set.get_utxo(&outpoint_key) {
+            // Real UTXO validation - lookup the referenced output in the
+            // caller's view, which may hold outputs not yet on chain
+            if let Some(utxo) = context.utxo_set.get_utxo(&outpoint_key) {
                 // Validate script signature (skip for coinbase transactions)
-                if !input.is_coinbase() && !self.validate_input_script(tx, input_index, input, &utxo.output) {
-                    return Err(BlockchainError::InvalidTransaction(
-                        format!("Invalid signature for input {}", input_index)
-                    ));
+                if !input.is_coinbase() {
+                    let checks = self.input_signature_checks(&mut sighashes, input_index, input, &utxo.output)
+                        .ok_or_else(|| BlockchainError::InvalidTransaction(
+                            format!("Invalid signature for input {}", input_index)
+                        ))?;
+                    for check in checks {
+                        signature_checks.push(check);
+                        signed_inputs.push(input_index);
+                    }
                 }
                 
                 let utxo_value = utxo.value();
@@ -366,27 +642,37 @@ impl ConsensusValidator {
             }
         }
         
-        // Calculate total output value
-        let total_output_value: u64 = tx.outputs.iter().map(|o| o.value).sum();
-        
-        // Check that inputs >= outputs
-        if total_input_value < total_output_value {
-            return Err(BlockchainError::InvalidTransaction("Insufficient input value".to_string()));
+        let batch: Vec<BatchItem> = signature_checks.iter()
+            .map(|(hash, signature, public_key)| (hash, signature.as_slice(), public_key.as_slice()))
+            .collect();
+        if !signature::verify_batch(&batch) {
+            let input_index = signature::find_invalid(&batch).first().map_or(0, |&i| signed_inputs[i]);
+            return Err(BlockchainError::InvalidTransaction(
+                format!("Invalid signature for input {}", input_index)
+            ));
         }
         
-        // Calculate fee
-        let fee = total_input_value - total_output_value;
+        // Calculate total output value
+        let total_output_value = Amount::checked_sum(tx.outputs.iter().map(|o| Amount::from_sat(o.value)))
+            .map_err(|_| BlockchainError::InvalidTransaction("Output value overflow".to_string()))?;
+        
+        // Inputs must cover the outputs; what is left over is the fee
+        let fee = Amount::from_sat(total_input_value).checked_sub(total_output_value)
+            .map_err(|_| BlockchainError::InvalidTransaction("Insufficient input value".to_string()))?;
         
         Ok(fee)
     }
     
     /// Apply a validated block to the chain state
     pub async fn apply_block(&self, block: Block) -> Result<()> {
+        self.check_supply_audit().await?;
         let block_hash = block.header.calculate_hash();
         
         // Update UTXO set by processing all transactions in the block
         {
             let mut utxo_set = self.utxo_set.write().await;
+            let block_supply = self.supply_audit.as_ref()
+                .map(|_| SupplyAuditor::account_block(&block, &utxo_set, self.params.block_reward.to_sat()));
             
             for transaction in &block.transactions {
                 let txid = transaction.get_hash()?;
@@ -420,10 +706,15 @@ impl ConsensusValidator {
                     utxo_set.add_utxo(txid, index as u32, utxo_set_format)?;
                 }
             }
+            
+            if let (Some(auditor), Some(block_supply)) = (&self.supply_audit, block_supply) {
+                let height = self.chain_state.read().await.height + 1;
+                auditor.write().await.connect_block(height, &block_hash, block_supply, &utxo_set)?;
+            }
         }
         
         // Update chain state
-        {
+        let new_height = {
             let mut chain_state = self.chain_state.write().await;
             chain_state.height += 1;
             chain_state.best_block_hash = block_hash;
@@ -445,14 +736,18 @@ impl ConsensusValidator {
                     chain_state.next_difficulty = chain_state.next_difficulty.saturating_sub(1);
                 }
             }
-        }
+            chain_state.height
+        };
         
         // Add to block index
         {
             let mut block_index = self.block_index.write().await;
-            block_index.insert(block_hash, block.header);
+            block_index.insert(block_hash, block.header.clone());
         }
         
+        self.versionbits.write().await.connect_block(new_height, block.header.version);
+        let _ = self.tip_sender.send(ChainTip { height: new_height, hash: block_hash });
+        
         Ok(())
     }
     
@@ -476,55 +771,62 @@ impl ConsensusValidator {
         Ok(())
     }
     
-    /// Validate P2PKH script (basic implementation)
-    /// Validate transaction input script signature
-    fn validate_input_script(
+    /// Check an input's script and return the signatures it must carry, or
+    /// None if the script is malformed
+    fn input_signature_checks(
         &self,
-        tx: &Transaction,
+        sighashes: &mut SighashCache,
         input_index: usize,
         input: &TransactionInput,
         output: &TransactionOutput,
-    ) -> bool {
+    ) -> Option<Vec<SignatureCheck>> {
         // Extract script_sig and script_pubkey
         let script_sig = &input.script_sig;
         let script_pubkey = &output.script_pubkey;
         
+        if let Some(htlc) = HtlcScript::from_script(script_pubkey) {
+            return self.htlc_signature_check(sighashes, input_index, input, output, &htlc).map(|check| vec![check]);
+        }
+        if let Some(hash) = ScriptHash::from_script_pubkey(script_pubkey) {
+            return self.script_hash_signature_checks(sighashes, input_index, input, output, hash);
+        }
+        
         // Basic P2PKH validation: script_sig should have signature + pubkey
         // script_pubkey should be: OP_DUP OP_HASH160 <address_hash> OP_EQUALVERIFY OP_CHECKSIG
         
         // Minimum lengths check
         if script_sig.len() < 65 || script_pubkey.len() < 25 {
-            return false;
+            return None;
         }
         
         // Verify script_pubkey format (P2PKH pattern)
         if script_pubkey[0] != 0x76 || script_pubkey[1] != 0xa9 {
-            return false; // Not OP_DUP OP_HASH160
+            return None; // Not OP_DUP OP_HASH160
         }
         
         let addr_len = script_pubkey[2] as usize;
         if script_pubkey.len() < 3 + addr_len + 2 {
-            return false;
+            return None;
         }
         
         if script_pubkey[3 + addr_len] != 0x88 || script_pubkey[3 + addr_len + 1] != 0xac {
-            return false; // Not OP_EQUALVERIFY OP_CHECKSIG
+            return None; // Not OP_EQUALVERIFY OP_CHECKSIG
         }
         
         // Extract signature and public key from script_sig
         // Format: <sig_len> <signature> <pubkey_len> <public_key>
         if script_sig.len() < 2 {
-            return false;
+            return None;
         }
         
         let sig_len = script_sig[0] as usize;
         if sig_len == 0 || script_sig.len() < 1 + sig_len + 1 {
-            return false;
+            return None;
         }
         
         let signature_with_hashtype = &script_sig[1..1 + sig_len];
         if signature_with_hashtype.is_empty() {
-            return false;
+            return None;
         }
         
         // Split signature and SIGHASH type
@@ -534,24 +836,109 @@ impl ConsensusValidator {
         let pubkey_start = 1 + sig_len;
         
         if script_sig.len() < pubkey_start + 1 {
-            return false;
+            return None;
         }
         
         let pubkey_len = script_sig[pubkey_start] as usize;
         if pubkey_len != 33 || script_sig.len() < pubkey_start + 1 + pubkey_len {
-            return false;
+            return None;
         }
         
         let public_key = &script_sig[pubkey_start + 1..pubkey_start + 1 + pubkey_len];
         
         // Calculate the proper signature hash using the transaction
-        let sig_hash = tx.calculate_signature_hash(input_index, script_pubkey, sighash_type);
+        let sig_hash = sighashes.signature_hash(input_index, script_pubkey, output.value, sighash_type).ok()?;
+        Some(vec![(sig_hash, signature.to_vec(), public_key.to_vec())])
+    }
+
+    /// Check an input spending a P2SH or P2WSH multisig output and return
+    /// the signatures it must carry. The revealed script stands in for the
+    /// script_pubkey in every signature hash.
+    fn script_hash_signature_checks(
+        &self,
+        sighashes: &mut SighashCache,
+        input_index: usize,
+        input: &TransactionInput,
+        output: &TransactionOutput,
+        hash: ScriptHash,
+    ) -> Option<Vec<SignatureCheck>> {
+        let spend = match hash {
+            ScriptHash::P2sh(_) => ScriptHashSpend::from_script_sig(&input.script_sig)?,
+            // Witness spends keep the script_sig empty
+            ScriptHash::P2wsh(_) if input.script_sig.is_empty() => {
+                ScriptHashSpend::from_witness(sighashes.transaction().witnesses.get(input_index)?)?
+            }
+            ScriptHash::P2wsh(_) => return None,
+        };
+        if !hash.commits_to(&spend.script) {
+            return None;
+        }
+        let multisig = MultisigScript::from_script(&spend.script)?;
+        // OP_CHECKMULTISIG: signatures follow key order, and the key mask
+        // says which keys they belong to, so the batch verifies each once
+        let keys = spend.signing_keys(&multisig)?;
+        if spend.signatures.len() != multisig.required || keys.len() != multisig.required {
+            return None;
+        }
+
+        let mut checks = Vec::with_capacity(spend.signatures.len());
+        for (signature_with_hashtype, public_key) in spend.signatures.iter().zip(keys) {
+            if signature_with_hashtype.len() < 2 {
+                return None;
+            }
+            let (sig, sighash_type) = signature_with_hashtype.split_at(signature_with_hashtype.len() - 1);
+            let sig_hash = sighashes.signature_hash(input_index, &spend.script, output.value, sighash_type[0] as u32).ok()?;
+            checks.push((sig_hash, sig.to_vec(), public_key.to_vec()));
+        }
+        Some(checks)
+    }
+
+    /// Check an input spending an HTLC output through either branch and
+    /// return the signature it must carry
+    fn htlc_signature_check(
+        &self,
+        sighashes: &mut SighashCache,
+        input_index: usize,
+        input: &TransactionInput,
+        output: &TransactionOutput,
+        htlc: &HtlcScript,
+    ) -> Option<SignatureCheck> {
+        let tx = sighashes.transaction();
+        let spend = HtlcSpend::from_script_sig(&input.script_sig)?;
+        
+        let expected_key_hash = match &spend {
+            // OP_SHA256 <payment_hash> OP_EQUALVERIFY
+            HtlcSpend::Redeem { preimage, .. } => {
+                if crate::crypto::sha256(preimage) != htlc.payment_hash {
+                    return None;
+                }
+                htlc.recipient_hash
+            }
+            // <locktime> OP_CHECKLOCKTIMEVERIFY: the spending transaction must itself be
+            // locked at least as far as the script, in the same unit, with locktime enabled
+            HtlcSpend::Refund { .. } => {
+                let same_unit = (tx.locktime < LOCKTIME_THRESHOLD) == (htlc.locktime < LOCKTIME_THRESHOLD);
+                if !same_unit || tx.locktime < htlc.locktime || input.sequence == SEQUENCE_FINAL {
+                    return None;
+                }
+                htlc.refund_hash
+            }
+        };
+        
+        // OP_DUP OP_HASH160 <key_hash> OP_EQUALVERIFY
+        let public_key = spend.public_key();
+        if public_key.len() != 33 || ScriptBuilder::hash160(public_key) != expected_key_hash {
+            return None;
+        }
         
-        // Verify the signature using real ECDSA
-        match crate::crypto::verify_signature(signature, public_key, &sig_hash) {
-            Ok(valid) => valid,
-            Err(_) => false,
+        // OP_CHECKSIG
+        let signature_with_hashtype = spend.signature();
+        if signature_with_hashtype.len() < 2 {
+            return None;
         }
+        let (signature, sighash_type) = signature_with_hashtype.split_at(signature_with_hashtype.len() - 1);
+        let sig_hash = sighashes.signature_hash(input_index, &output.script_pubkey, output.value, sighash_type[0] as u32).ok()?;
+        Some((sig_hash, signature.to_vec(), public_key.to_vec()))
     }
 
     /// Get the latest block
@@ -576,7 +963,11 @@ impl ConsensusValidator {
     /// Add a block to the blockchain
     pub async fn add_block(&self, block: Block) -> Result<()> {
         // Validate the block first
+        self.check_supply_audit().await?;
         self.validate_block(&block).await?;
+        if let Some(root) = self.invalidated_root(&block.header).await {
+            return Err(BlockchainError::InvalidBlock(format!("Block descends from invalidated block {}", hex::encode(root))));
+        }
         
         let block_hash = block.header.calculate_hash();
         let block_height = block.header.height; // Capture height before move
@@ -606,48 +997,244 @@ impl ConsensusValidator {
         // Add block header to index
         {
             let mut block_index = self.block_index.write().await;
-            block_index.insert(block_hash, block.header);
+            block_index.insert(block_hash, block.header.clone());
         }
         
         // Update UTXO set with block transactions
         {
             let mut utxo_set = self.utxo_set.write().await;
-            
-            for (tx_index, tx) in block.transactions.iter().enumerate() {
-                let tx_hash = tx.get_hash()?;
-                
-                // Remove spent UTXOs
-                for input in &tx.inputs {
-                    if !input.is_coinbase() {
-                        utxo_set.remove_utxo(&input.prev_tx_hash, input.prev_output_index)?;
-                    }
-                }
-                
-                // Add new UTXOs
-                for (output_index, output) in tx.outputs.iter().enumerate() {
-                    let utxo = UTXO::new(
-                        tx_hash,
-                        output_index as u32,
-                        output.clone(),
-                        block_height, // Use actual block height
-                        tx_index == 0, // First transaction is coinbase
-                    );
-                    utxo_set.add_utxo(tx_hash, output_index as u32, utxo)?;
-                }
+            match &self.supply_audit {
+                Some(auditor) => self.connect_utxos(&mut utxo_set, Some(&mut *auditor.write().await), &block)?,
+                None => self.connect_utxos(&mut utxo_set, None, &block)?,
             }
-            
-            // Update UTXO set current height for maturity checks
-            utxo_set.set_current_height(block_height);
-            
             debug!("UTXO set now has {} UTXOs after block {}", utxo_set.get_utxo_count(), block_height);
         }
         
         info!("Block {} added to blockchain at height {}", 
               hex::encode(block_hash), block_height);
         
+        self.versionbits.write().await.connect_block(block_height as BlockHeight, block.header.version);
+        
+        // No receivers is fine; nobody is waiting on the tip
+        let _ = self.tip_sender.send(ChainTip { height: block_height as BlockHeight, hash: block_hash });
+        
         Ok(())
     }
 
+    /// Spend a best-chain block's inputs and add its outputs
+    fn connect_utxos(&self, utxo_set: &mut UTXOSet, auditor: Option<&mut SupplyAuditor>, block: &Block) -> Result<()> {
+        let block_hash = block.header.calculate_hash();
+        let block_height = block.header.height;
+        let block_supply = auditor.as_ref()
+            .map(|_| SupplyAuditor::account_block(block, utxo_set, self.params.block_reward.to_sat()));
+        
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            let tx_hash = tx.get_hash()?;
+            
+            // Remove spent UTXOs
+            for input in &tx.inputs {
+                if !input.is_coinbase() {
+                    utxo_set.remove_utxo(&input.prev_tx_hash, input.prev_output_index)?;
+                }
+            }
+            
+            // Add new UTXOs
+            for (output_index, output) in tx.outputs.iter().enumerate() {
+                let utxo = UTXO::new(
+                    tx_hash,
+                    output_index as u32,
+                    output.clone(),
+                    block_height, // Use actual block height
+                    tx_index == 0, // First transaction is coinbase
+                );
+                utxo_set.add_utxo(tx_hash, output_index as u32, utxo)?;
+            }
+        }
+        
+        // Update UTXO set current height for maturity checks
+        utxo_set.set_current_height(block_height);
+        
+        if let (Some(auditor), Some(block_supply)) = (auditor, block_supply) {
+            auditor.connect_block(block_height as BlockHeight, &block_hash, block_supply, utxo_set)?;
+        }
+        Ok(())
+    }
+    
+    /// Disconnect every best-chain block above `height`, rebuilding the UTXO
+    /// set by replaying the chain from genesis. Returns the blocks removed,
+    /// lowest first.
+    async fn rewind_to(&self, height: BlockHeight) -> Result<Vec<Block>> {
+        let mut utxo_set = self.utxo_set.write().await;
+        let mut blocks = self.blocks.write().await;
+        let mut chain_state = self.chain_state.write().await;
+        
+        let mut disconnected: Vec<Block> = (height + 1..=chain_state.height)
+            .filter_map(|h| blocks.remove(&h))
+            .collect();
+        disconnected.sort_by_key(|block| block.header.height);
+        
+        *utxo_set = self.genesis_utxos.read().await.clone();
+        let mut auditor = match &self.supply_audit {
+            Some(auditor) => Some(auditor.write().await),
+            None => None,
+        };
+        if let Some(auditor) = auditor.as_mut() {
+            auditor.start(&utxo_set);
+        }
+        let mut versionbits = VersionBitsTracker::new(
+            self.params.deployments.clone(),
+            self.params.difficulty_adjustment_interval,
+            self.params.deployment_threshold,
+        );
+        
+        let mut tip = None;
+        for h in 0..=height {
+            let block = blocks.get(&h)
+                .ok_or_else(|| BlockchainError::ConsensusError(format!("Block {} missing while rewinding", h)))?;
+            if h > 0 {
+                self.connect_utxos(&mut utxo_set, auditor.as_deref_mut(), block)?;
+            }
+            versionbits.connect_block(h, block.header.version);
+            tip = Some(block);
+        }
+        let tip = tip.ok_or_else(|| BlockchainError::ConsensusError("Chain has no genesis block".to_string()))?;
+        
+        let tip_hash = tip.header.calculate_hash();
+        chain_state.height = height;
+        chain_state.best_block_hash = tip_hash;
+        chain_state.total_work = chain_state.total_work.saturating_sub(disconnected.len() as u64);
+        chain_state.last_block_timestamp = tip.header.timestamp as u64;
+        *self.versionbits.write().await = versionbits;
+        if let Some(storage) = &self.storage {
+            storage.disconnect_above(height).await;
+        }
+        
+        self.side_blocks.write().await.extend(disconnected.iter().map(|block| (block.header.calculate_hash(), block.clone())));
+        info!("⏪ Rewound chain to height {} ({} blocks disconnected)", height, disconnected.len());
+        let _ = self.tip_sender.send(ChainTip { height, hash: tip_hash });
+        Ok(disconnected)
+    }
+    
+    /// Mark a block and everything built on it invalid. If it is on the best
+    /// chain, the chain is rewound to its parent; the disconnected blocks are
+    /// returned, lowest first.
+    pub async fn invalidate_block(&self, hash: Hash256) -> Result<Vec<Block>> {
+        let height = self.block_index.read().await.get(&hash)
+            .map(|header| header.height as BlockHeight)
+            .ok_or_else(|| BlockchainError::InvalidInput(format!("Unknown block {}", hex::encode(hash))))?;
+        if height == 0 {
+            return Err(BlockchainError::InvalidInput("The genesis block cannot be invalidated".to_string()));
+        }
+        
+        let on_best_chain = self.get_block_by_height(height).await
+            .is_some_and(|block| block.header.calculate_hash() == hash);
+        let disconnected = if on_best_chain { self.rewind_to(height - 1).await? } else { Vec::new() };
+        
+        // The block and every known descendant
+        let block_index = self.block_index.read().await;
+        let mut invalid = HashSet::from([hash]);
+        let mut headers: Vec<(&Hash256, &BlockHeader)> = block_index.iter()
+            .filter(|(_, header)| header.height as BlockHeight > height)
+            .collect();
+        headers.sort_by_key(|(_, header)| header.height);
+        for (child, header) in headers {
+            if invalid.contains(&header.prev_block_hash) {
+                invalid.insert(*child);
+            }
+        }
+        drop(block_index);
+        self.invalidated.write().await.extend(invalid.into_iter().map(|invalid| (invalid, hash)));
+        self.save_invalidated().await?;
+        
+        info!("🚫 Block {} at height {} invalidated", hex::encode(hash), height);
+        Ok(disconnected)
+    }
+    
+    /// Undo `invalidate_block`. If the reconsidered branch is now longer than
+    /// the best chain, the chain switches back to it; the blocks connected are
+    /// returned, lowest first. If a block of the branch fails to connect, the
+    /// previous best chain and the invalidation are restored.
+    pub async fn reconsider_block(&self, hash: Hash256) -> Result<Vec<Block>> {
+        let reconsidered: Vec<(Hash256, Hash256)> = {
+            let mut invalidated = self.invalidated.write().await;
+            let root = *invalidated.get(&hash)
+                .ok_or_else(|| BlockchainError::InvalidInput(format!("Block {} is not invalidated", hex::encode(hash))))?;
+            let reconsidered = invalidated.iter()
+                .filter(|(_, invalid_root)| **invalid_root == root)
+                .map(|(invalid, invalid_root)| (*invalid, *invalid_root))
+                .collect();
+            invalidated.retain(|_, invalid_root| *invalid_root != root);
+            reconsidered
+        };
+        self.save_invalidated().await?;
+        
+        // Longest run of kept blocks starting at the reconsidered one
+        let side_blocks = self.side_blocks.read().await.clone();
+        let Some(first) = side_blocks.get(&hash) else { return Ok(Vec::new()) };
+        let mut branch = vec![first.clone()];
+        loop {
+            let tip_hash = branch.last().map(|block| block.header.calculate_hash());
+            match side_blocks.values().find(|block| Some(block.header.prev_block_hash) == tip_hash) {
+                Some(block) => branch.push(block.clone()),
+                None => break,
+            }
+        }
+        
+        let fork_height = first.header.height as BlockHeight - 1;
+        let parent_on_best = self.get_block_by_height(fork_height).await
+            .is_some_and(|block| block.header.calculate_hash() == first.header.prev_block_hash);
+        if !parent_on_best || fork_height + branch.len() as u64 <= self.chain_state.read().await.height {
+            return Ok(Vec::new());
+        }
+        
+        let previous = self.rewind_to(fork_height).await?;
+        if let Err(e) = self.connect_branch(&branch).await {
+            warn!("Reconsidered block {} failed to connect ({}); restoring the previous tip", hex::encode(hash), e);
+            self.rewind_to(fork_height).await?;
+            self.connect_branch(&previous).await?;
+            self.invalidated.write().await.extend(reconsidered);
+            self.save_invalidated().await?;
+            return Err(e);
+        }
+        info!("↩️  Reconsidered block {}; best chain now at height {}", hex::encode(hash), fork_height + branch.len() as u64);
+        Ok(branch)
+    }
+    
+    /// Validate and connect side blocks on top of the tip, lowest first
+    async fn connect_branch(&self, branch: &[Block]) -> Result<()> {
+        for block in branch {
+            match self.validate_block(block).await? {
+                BlockValidation::Valid => {}
+                BlockValidation::Invalid(reason) => return Err(BlockchainError::InvalidBlock(reason)),
+                BlockValidation::OrphanBlock(_) => return Err(BlockchainError::OrphanBlock),
+            }
+            self.add_block(block.clone()).await?;
+            self.side_blocks.write().await.remove(&block.header.calculate_hash());
+        }
+        Ok(())
+    }
+    
+    /// Write the invalidated blocks to disk when storage is enabled
+    async fn save_invalidated(&self) -> Result<()> {
+        match &self.storage {
+            Some(storage) => storage.save_invalidated(&*self.invalidated.read().await),
+            None => Ok(()),
+        }
+    }
+    
+    /// The invalidated block `header` is, or builds on
+    async fn invalidated_root(&self, header: &BlockHeader) -> Option<Hash256> {
+        let invalidated = self.invalidated.read().await;
+        invalidated.get(&header.calculate_hash())
+            .or_else(|| invalidated.get(&header.prev_block_hash))
+            .copied()
+    }
+    
+    /// Blocks currently invalidated by the operator, with their descendants
+    pub async fn invalidated_blocks(&self) -> HashSet<Hash256> {
+        self.invalidated.read().await.keys().copied().collect()
+    }
+    
     /// Get recent blocks for difficulty calculation
     pub async fn get_recent_blocks(&self, count: usize) -> Result<Vec<Block>> {
         // For now, return empty vec as we'd need to implement block storage
@@ -693,6 +1280,13 @@ impl ConsensusValidator {
         blocks.get(&height).cloned()
     }
     
+    /// Get a best-chain block by hash
+    pub async fn get_block_by_hash(&self, hash: &Hash256) -> Option<Block> {
+        let height = self.block_index.read().await.get(hash)?.height as u64;
+        self.get_block_by_height(height).await
+            .filter(|block| block.header.calculate_hash() == *hash)
+    }
+    
     /// Get block headers for a range (for Initial Block Download)
     /// Returns headers from start_height to end_height (inclusive)
     /// If stop_hash is provided and found, returns headers up to that hash
@@ -720,6 +1314,7 @@ impl ConsensusValidator {
         for (hash, header) in block_index.iter() {
             if u64::from(header.height) >= start_height && u64::from(header.height) <= end_height {
                 headers.push(crate::block::BlockHeaderInfo {
+                    version: header.version,
                     height: header.height as u64,
                     hash: *hash,
                     prev_hash: header.prev_block_hash,
@@ -763,6 +1358,19 @@ mod tests {
         assert_eq!(chain_state.best_block_hash, [0u8; 32]);
     }
     
+    #[test]
+    fn test_params_hash_tracks_consensus_rules() {
+        let params = ConsensusParams::default();
+        assert_eq!(params.params_hash(), ConsensusParams::default().params_hash());
+
+        let with_fund = params.clone().with_dev_fund("edu1qfund".to_string(), 10).unwrap();
+        assert_ne!(with_fund.params_hash(), params.params_hash());
+
+        let mut with_deployment = params.clone();
+        with_deployment.deployments.push(Deployment::new("taproot", 2, 100, 1000).unwrap());
+        assert_ne!(with_deployment.params_hash(), params.params_hash());
+    }
+    
     #[tokio::test]
     async fn test_coinbase_validation() {
         let params = ConsensusParams::default();
@@ -789,8 +1397,255 @@ mod tests {
             block_height: 1,
             block_time: 1234567890,
             utxo_set: UTXOSet::new(),
+            precomputed_sighash: false,
         };
         
         assert!(validator.validate_coinbase_transaction(&coinbase_tx, &context).is_ok());
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_htlc_redeem_and_refund() {
+        use crate::tx_builder::TransactionBuilder;
+        use crate::wallet::Wallet;
+        
+        let validator = ConsensusValidator::new(ConsensusParams::default());
+        let sender = Wallet::new("sender".to_string()).unwrap();
+        let recipient = Wallet::new("recipient".to_string()).unwrap();
+        let (preimage, payment_hash) = HtlcScript::generate_preimage();
+        let htlc = HtlcScript::new(payment_hash, &recipient.address, &sender.address, 100).unwrap();
+        
+        let utxo = UTXO::new([9u8; 32], 0, TransactionOutput::new(1_000_000, htlc.to_script()), 1, false);
+        validator.utxo_set.write().await.add_utxo([9u8; 32], 0, utxo.clone()).unwrap();
+        let utxo_set = validator.get_utxo_set().await;
+        let context = |block_height| TxValidationContext { block_height, block_time: 1234567890, utxo_set: utxo_set.clone(), precomputed_sighash: false };
+        
+        // Recipient claims with the preimage, which the sender can then read off the chain
+        let redeem = TransactionBuilder::new()
+            .add_output(recipient.address.clone(), 990_000)
+            .redeem_htlc(&recipient, utxo.clone(), preimage)
+            .unwrap();
+        assert_eq!(validator.validate_transaction(&redeem, &context(2)).unwrap(), Amount::from_sat(10_000));
+        assert_eq!(htlc.find_preimage(&redeem), Some(preimage));
+        
+        // Only the recipient can use the preimage branch
+        let stolen = TransactionBuilder::new()
+            .add_output(sender.address.clone(), 990_000)
+            .redeem_htlc(&sender, utxo.clone(), preimage)
+            .unwrap();
+        assert!(validator.validate_transaction(&stolen, &context(2)).is_err());
+        
+        // Sender's refund is only valid once the locktime has passed
+        let refund = TransactionBuilder::new()
+            .add_output(sender.address.clone(), 990_000)
+            .refund_htlc(&sender, utxo.clone())
+            .unwrap();
+        assert_eq!(refund.locktime, 100);
+        assert!(validator.validate_transaction(&refund, &context(50)).is_err());
+        assert!(validator.validate_transaction(&refund, &context(101)).is_ok());
+        
+        // Refunds that dodge the timelock are rejected
+        let mut early = refund.clone();
+        early.locktime = 50;
+        assert!(validator.validate_transaction(&early, &context(101)).is_err());
+        let recipient_refund = TransactionBuilder::new()
+            .add_output(recipient.address.clone(), 990_000)
+            .refund_htlc(&recipient, utxo)
+            .unwrap();
+        assert!(validator.validate_transaction(&recipient_refund, &context(101)).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_p2sh_and_p2wsh_multisig_spends() {
+        use crate::tx_builder::{finalize_script_input, sign_script_input, TransactionBuilder};
+        use crate::wallet::Wallet;
+        
+        let validator = ConsensusValidator::new(ConsensusParams::default());
+        let payee = Wallet::new("payee".to_string()).unwrap();
+        let keys: Vec<crate::PrivateKey> = vec![[1u8; 32], [2u8; 32], [3u8; 32]];
+        let public_keys = keys.iter()
+            .map(|key| crate::crypto::derive_public_key(key).unwrap().try_into().unwrap())
+            .collect();
+        let script = MultisigScript::new(2, public_keys).unwrap().to_script();
+        
+        let p2sh = ScriptBuilder::create_p2sh_script(&ScriptBuilder::hash160(&script));
+        let p2wsh = ScriptBuilder::create_p2wsh_script(&script);
+        for (seed, script_pubkey) in [(10u8, p2sh), (11, p2wsh)] {
+            let utxo = UTXO::new([seed; 32], 0, TransactionOutput::new(1_000_000, script_pubkey), 1, false);
+            validator.utxo_set.write().await.add_utxo([seed; 32], 0, utxo.clone()).unwrap();
+            let context = TxValidationContext { block_height: 2, block_time: 1234567890, utxo_set: validator.get_utxo_set().await, precomputed_sighash: false };
+            
+            // Any two of the three keys spend it, whatever order they sign in
+            let spend = TransactionBuilder::new()
+                .add_output(payee.address.clone(), 990_000)
+                .spend_script_utxo(utxo.clone(), &script, &[keys[2], keys[0]])
+                .unwrap();
+            assert_eq!(validator.validate_transaction(&spend, &context).unwrap(), Amount::from_sat(10_000));
+            assert!(TransactionBuilder::new()
+                .add_output(payee.address.clone(), 990_000)
+                .spend_script_utxo(utxo.clone(), &script, &[keys[1]])
+                .is_err());
+            
+            // Cosigners sign the unsigned transaction separately
+            let mut unsigned = spend.clone();
+            unsigned.inputs[0].script_sig.clear();
+            unsigned.witnesses.clear();
+            let signatures = [keys[1], keys[2]].iter()
+                .map(|key| sign_script_input(&unsigned, 0, &utxo, &script, key).unwrap())
+                .collect::<Vec<_>>();
+            assert!(sign_script_input(&unsigned, 0, &utxo, &script, &payee.private_key).is_err());
+            let mut cosigned = unsigned.clone();
+            finalize_script_input(&mut cosigned, 0, &utxo, &script, &signatures).unwrap();
+            assert!(validator.validate_transaction(&cosigned, &context).is_ok());
+            
+            // Signatures don't carry over to different outputs
+            let mut tampered = cosigned.clone();
+            tampered.outputs[0].value = 999_000;
+            tampered.clear_cache();
+            assert!(validator.validate_transaction(&tampered, &context).is_err());
+            assert!(finalize_script_input(&mut tampered, 0, &utxo, &script, &signatures).is_err());
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_invalidate_and_reconsider_block() {
+        let params = ConsensusParams::default();
+        let data_dir = tempfile::TempDir::new().unwrap();
+        let storage = || Arc::new(crate::storage::DiskBlockStorage::new(data_dir.path()).unwrap());
+        let validator = ConsensusValidator::new(params.clone()).with_storage(storage());
+        let genesis = crate::genesis::GenesisCreator::new(None).create_genesis_state().unwrap();
+        validator.initialize_with_genesis(genesis).await.unwrap();
+        
+        let mut chain = Vec::new();
+        for height in 1..=3u32 {
+            let state = validator.get_chain_state().await;
+            let coinbase = Transaction::new(
+                1,
+                vec![TransactionInput::create_coinbase(height.to_le_bytes().to_vec())],
+                params.coinbase_outputs("edu1qMiner", Amount::ZERO).unwrap(),
+            );
+            let merkle_root = validator.calculate_merkle_root(&[coinbase.clone()]).unwrap();
+            let mut header = BlockHeader::new(1, state.best_block_hash, merkle_root, state.next_difficulty, height);
+            while header.calculate_hash()[0] >= pow_threshold(header.difficulty_target) {
+                header.nonce += 1;
+            }
+            let block = Block::new(header, vec![coinbase]);
+            validator.add_block(block.clone()).await.unwrap();
+            chain.push(block);
+        }
+        let utxos_at_tip = validator.get_utxo_set().await.get_utxo_count();
+        
+        // Invalidating block 2 rewinds to block 1 and refuses it again
+        let hash = chain[1].get_hash();
+        let disconnected = validator.invalidate_block(hash).await.unwrap();
+        assert_eq!(disconnected.len(), 2);
+        assert_eq!(validator.get_chain_state().await.height, 1);
+        assert!(validator.get_utxo_set().await.get_utxo_count() < utxos_at_tip);
+        assert!(validator.invalidated_blocks().await.contains(&chain[2].get_hash()));
+        assert!(validator.add_block(chain[1].clone()).await.is_err());
+        // The invalidation survives a restart
+        let restarted = ConsensusValidator::new(params.clone()).with_storage(storage());
+        assert_eq!(restarted.invalidated_blocks().await, validator.invalidated_blocks().await);
+        
+        // A branch that fails to connect leaves the tip and invalidation as they were
+        let tampered = Block::new(chain[2].header.clone(), vec![Transaction::new(
+            1,
+            vec![TransactionInput::create_coinbase(b"tampered".to_vec())],
+            params.coinbase_outputs("edu1qMiner", Amount::ZERO).unwrap(),
+        )]);
+        validator.side_blocks.write().await.insert(chain[2].get_hash(), tampered);
+        assert!(validator.reconsider_block(hash).await.is_err());
+        assert_eq!(validator.get_chain_state().await.best_block_hash, chain[0].get_hash());
+        assert!(validator.invalidated_blocks().await.contains(&hash));
+        validator.side_blocks.write().await.insert(chain[2].get_hash(), chain[2].clone());
+        
+        // Reconsidering it switches back to the longer branch
+        let connected = validator.reconsider_block(hash).await.unwrap();
+        assert_eq!(connected.len(), 2);
+        let state = validator.get_chain_state().await;
+        assert_eq!((state.height, state.best_block_hash), (3, chain[2].get_hash()));
+        assert_eq!(validator.get_utxo_set().await.get_utxo_count(), utxos_at_tip);
+        assert!(validator.invalidated_blocks().await.is_empty());
+        assert!(storage().load_invalidated().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_coinbase_dev_fund_split() {
+        let params = ConsensusParams::default().with_dev_fund("edu1qDevFund".to_string(), 10).unwrap();
+        assert!(ConsensusParams::default().with_dev_fund("edu1qDevFund".to_string(), 101).is_err());
+        
+        let outputs = params.coinbase_outputs("edu1qMiner", Amount::from_sat(1_000)).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].value, 45_00000000 + 1_000);
+        assert_eq!(outputs[1].value, 5_00000000);
+        assert_eq!(outputs[1].get_address().as_deref(), Some("edu1qDevFund"));
+        
+        let validator = ConsensusValidator::new(params.clone());
+        let coinbase_block = |outputs| Block::new(
+            BlockHeader::new(1, [0u8; 32], [0u8; 32], 0, 1),
+            vec![Transaction::new(1, vec![TransactionInput::create_coinbase(vec![0x01, 0x02])], outputs)],
+        );
+        
+        let split = params.coinbase_outputs("edu1qMiner", Amount::ZERO).unwrap();
+        assert!(validator.validate_block_transactions(&coinbase_block(split)).await.is_ok());
+        
+        // Miner keeping the whole reward is rejected
+        let unsplit = ConsensusParams::default().coinbase_outputs("edu1qMiner", Amount::ZERO).unwrap();
+        assert!(validator.validate_block_transactions(&coinbase_block(unsplit)).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_legacy_and_precomputed_sighash_inputs() {
+        use crate::crypto::sign_hash;
+        use crate::sighash::{SIGHASH_ALL, SIGHASH_PRECOMPUTED};
+        use crate::wallet::Wallet;
+        
+        let validator = ConsensusValidator::new(ConsensusParams::default());
+        let owner = Wallet::new("owner".to_string()).unwrap();
+        let mut inputs = Vec::new();
+        for i in 0..4u8 {
+            let output = TransactionOutput::create_p2pkh(100_000, &owner.address).unwrap();
+            let utxo = UTXO::new([i; 32], 0, output, 1, false);
+            validator.utxo_set.write().await.add_utxo([i; 32], 0, utxo).unwrap();
+            inputs.push(TransactionInput::new([i; 32], 0, Vec::new()));
+        }
+        let outputs = vec![TransactionOutput::create_p2pkh(390_000, &owner.address).unwrap()];
+        let unsigned = Transaction::new(1, inputs, outputs);
+        let script_pubkey = TransactionOutput::create_p2pkh(0, &owner.address).unwrap().script_pubkey;
+        
+        // Odd inputs use the legacy digest, even ones the precomputed digest
+        let sign = |amount: u64| {
+            let mut sighashes = SighashCache::new(&unsigned);
+            let script_sigs: Vec<Vec<u8>> = (0..4)
+                .map(|i| {
+                    let sighash_type = if i % 2 == 0 { SIGHASH_ALL | SIGHASH_PRECOMPUTED } else { SIGHASH_ALL };
+                    let hash = sighashes.signature_hash(i, &script_pubkey, amount, sighash_type).unwrap();
+                    let mut signature = sign_hash(&hash, &owner.private_key).unwrap();
+                    signature.push(sighash_type as u8);
+                    let mut script_sig = vec![signature.len() as u8];
+                    script_sig.extend_from_slice(&signature);
+                    script_sig.push(owner.public_key.len() as u8);
+                    script_sig.extend_from_slice(&owner.public_key);
+                    script_sig
+                })
+                .collect();
+            let mut tx = unsigned.clone();
+            for (input, script_sig) in tx.inputs.iter_mut().zip(script_sigs) {
+                input.script_sig = script_sig;
+            }
+            tx
+        };
+        let utxo_set = validator.get_utxo_set().await;
+        let context = |precomputed_sighash| TxValidationContext {
+            block_height: 2,
+            block_time: 1234567890,
+            utxo_set: utxo_set.clone(),
+            precomputed_sighash,
+        };
+        
+        // The precomputed digest is refused until its deployment is active
+        assert!(validator.validate_transaction(&sign(100_000), &context(false)).is_err());
+        assert_eq!(validator.validate_transaction(&sign(100_000), &context(true)).unwrap(), Amount::from_sat(10_000));
+        // Precomputed signatures commit to the value being spent
+        assert!(validator.validate_transaction(&sign(99_999), &context(true)).is_err());
+    }
+}
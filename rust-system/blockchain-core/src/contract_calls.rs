@@ -0,0 +1,310 @@
+//! Inter-contract calls
+//!
+//! revm itself runs CALL, CALLCODE, DELEGATECALL, STATICCALL and CREATE
+//! between contracts. `CallGuard` watches those frames as an inspector: it
+//! enforces the executor's call-depth limit, can refuse re-entry into a
+//! contract that is still executing, and records every frame so the
+//! execution result carries the full internal call tree.
+
+use crate::contracts::{hex_serde, EthAddress};
+use revm::interpreter::{
+    CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, Gas, InstructionResult,
+    InterpreterResult,
+};
+use revm::primitives::{Address, Bytes, U256};
+use revm::{Database, EvmContext, Inspector};
+use serde::{Deserialize, Serialize};
+
+/// Depth revm enforces on its own; a higher limit has no effect
+pub const EVM_CALL_DEPTH_LIMIT: usize = 1024;
+
+/// Limits on contract-to-contract calls
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CallConfig {
+    /// Deepest nested frame allowed, the outermost call being depth 0
+    pub max_call_depth: usize,
+    /// Revert a CALL into a contract that is already on the call stack
+    pub reentrancy_guard: bool,
+}
+
+impl Default for CallConfig {
+    fn default() -> Self {
+        Self {
+            max_call_depth: EVM_CALL_DEPTH_LIMIT,
+            reentrancy_guard: false,
+        }
+    }
+}
+
+/// How a frame was entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+}
+
+/// One frame of the internal call tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    pub from: EthAddress,
+    /// Account whose storage the frame ran against, or the created contract
+    pub to: Option<EthAddress>,
+    /// Contract whose code ran, for DELEGATECALL and CALLCODE
+    pub code_address: Option<EthAddress>,
+    pub value: u64,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    #[serde(with = "hex_serde")]
+    pub input: Vec<u8>,
+    #[serde(with = "hex_serde")]
+    pub output: Vec<u8>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub calls: Vec<CallFrame>,
+}
+
+/// Frame still executing, and the account it acts for
+struct OpenFrame {
+    frame: CallFrame,
+    context: Option<Address>,
+}
+
+/// Inspector applying `CallConfig` and building the call tree
+pub struct CallGuard {
+    config: CallConfig,
+    stack: Vec<OpenFrame>,
+    root: Option<CallFrame>,
+}
+
+impl Default for CallGuard {
+    fn default() -> Self {
+        Self::new(CallConfig::default())
+    }
+}
+
+impl CallGuard {
+    pub fn new(config: CallConfig) -> Self {
+        Self {
+            config,
+            stack: Vec::new(),
+            root: None,
+        }
+    }
+
+    /// Call tree of the finished transaction
+    pub fn finish(mut self) -> Option<CallFrame> {
+        // Frames left open only if execution stopped abnormally
+        while !self.stack.is_empty() {
+            self.close(false, Vec::new(), 0, None);
+        }
+        self.root
+    }
+
+    /// Why a new frame may not run, if it may not
+    fn refuse(&self, scheme: Option<CallScheme>, context: Address) -> Option<(InstructionResult, String)> {
+        if self.stack.len() > self.config.max_call_depth {
+            return Some((
+                InstructionResult::CallTooDeep,
+                format!("call depth limit {} exceeded", self.config.max_call_depth),
+            ));
+        }
+        // DELEGATECALL, CALLCODE and STATICCALL cannot re-enter another
+        // contract's storage, so only plain calls are guarded
+        if self.config.reentrancy_guard
+            && scheme == Some(CallScheme::Call)
+            && self.stack.iter().any(|open| open.context == Some(context))
+        {
+            return Some((
+                InstructionResult::Revert,
+                format!("reentrant call into {}", hex::encode(context)),
+            ));
+        }
+        None
+    }
+
+    fn open(&mut self, frame: CallFrame, context: Option<Address>) {
+        self.stack.push(OpenFrame { frame, context });
+    }
+
+    fn close(&mut self, success: bool, output: Vec<u8>, gas_used: u64, created: Option<Address>) {
+        let Some(OpenFrame { mut frame, .. }) = self.stack.pop() else { return };
+        frame.success = success;
+        frame.output = output;
+        frame.gas_used = gas_used;
+        if frame.kind == CallKind::Create {
+            frame.to = created.map(EthAddress::from_address);
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.frame.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        if let Some(open) = self.stack.last_mut() {
+            open.frame.error = Some(error);
+        }
+    }
+
+    /// Record an error for a frame that ended badly, unless already set
+    fn end_error(&mut self, result: &InterpreterResult) {
+        if !result.result.is_ok() {
+            if let Some(open) = self.stack.last_mut() {
+                open.frame.error.get_or_insert_with(|| format!("{:?}", result.result));
+            }
+        }
+    }
+}
+
+fn value_u64(value: U256) -> u64 {
+    u64::try_from(value).unwrap_or(u64::MAX)
+}
+
+impl<DB: Database> Inspector<DB> for CallGuard {
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let kind = match inputs.scheme {
+            CallScheme::CallCode => CallKind::CallCode,
+            CallScheme::DelegateCall => CallKind::DelegateCall,
+            _ if inputs.is_static => CallKind::StaticCall,
+            _ => CallKind::Call,
+        };
+        let refused = self.refuse(Some(inputs.scheme), inputs.target_address);
+        self.open(
+            CallFrame {
+                kind,
+                from: EthAddress::from_address(inputs.caller),
+                to: Some(EthAddress::from_address(inputs.target_address)),
+                code_address: (inputs.bytecode_address != inputs.target_address)
+                    .then(|| EthAddress::from_address(inputs.bytecode_address)),
+                value: value_u64(inputs.call_value()),
+                gas_limit: inputs.gas_limit,
+                gas_used: 0,
+                input: inputs.input.to_vec(),
+                output: Vec::new(),
+                success: false,
+                error: None,
+                calls: Vec::new(),
+            },
+            Some(inputs.target_address),
+        );
+
+        // The refused frame still ends through `call_end`
+        let (result, error) = refused?;
+        self.fail(error);
+        Some(CallOutcome::new(
+            InterpreterResult::new(result, Bytes::new(), Gas::new(inputs.gas_limit)),
+            inputs.return_memory_offset.clone(),
+        ))
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.end_error(&outcome.result);
+        self.close(
+            outcome.result.result.is_ok(),
+            outcome.result.output.to_vec(),
+            outcome.result.gas.spent(),
+            None,
+        );
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let refused = self.refuse(None, inputs.caller);
+        self.open(
+            CallFrame {
+                kind: CallKind::Create,
+                from: EthAddress::from_address(inputs.caller),
+                to: None,
+                code_address: None,
+                value: value_u64(inputs.value),
+                gas_limit: inputs.gas_limit,
+                gas_used: 0,
+                input: inputs.init_code.to_vec(),
+                output: Vec::new(),
+                success: false,
+                error: None,
+                calls: Vec::new(),
+            },
+            None,
+        );
+
+        let (result, error) = refused?;
+        self.fail(error);
+        Some(CreateOutcome::new(
+            InterpreterResult::new(result, Bytes::new(), Gas::new(inputs.gas_limit)),
+            None,
+        ))
+    }
+
+    fn create_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        self.end_error(&outcome.result);
+        self.close(
+            outcome.result.result.is_ok(),
+            outcome.result.output.to_vec(),
+            outcome.result.gas.spent(),
+            outcome.address,
+        );
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(kind: CallKind) -> CallFrame {
+        CallFrame {
+            kind,
+            from: EthAddress::new([1; 20]),
+            to: None,
+            code_address: None,
+            value: 0,
+            gas_limit: 0,
+            gas_used: 0,
+            input: Vec::new(),
+            output: Vec::new(),
+            success: false,
+            error: None,
+            calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_guard_limits_depth_and_reentry() {
+        let a = Address::from([0xaa; 20]);
+        let b = Address::from([0xbb; 20]);
+        let mut guard = CallGuard::new(CallConfig { max_call_depth: 1, reentrancy_guard: true });
+
+        guard.open(frame(CallKind::Call), Some(a));
+        assert!(guard.refuse(Some(CallScheme::Call), b).is_none());
+        let (result, _) = guard.refuse(Some(CallScheme::Call), a).unwrap();
+        assert_eq!(result, InstructionResult::Revert);
+        // Delegating back into the caller's code is not re-entry
+        assert!(guard.refuse(Some(CallScheme::DelegateCall), a).is_none());
+
+        guard.open(frame(CallKind::Call), Some(b));
+        let (result, _) = guard.refuse(Some(CallScheme::Call), Address::ZERO).unwrap();
+        assert_eq!(result, InstructionResult::CallTooDeep);
+    }
+
+    #[test]
+    fn test_frames_nest_into_tree() {
+        let mut guard = CallGuard::default();
+        guard.open(frame(CallKind::Call), None);
+        guard.open(frame(CallKind::StaticCall), None);
+        guard.close(true, vec![1], 10, None);
+        guard.open(frame(CallKind::Create), None);
+        guard.close(true, Vec::new(), 20, Some(Address::from([0xcc; 20])));
+        guard.close(true, Vec::new(), 50, None);
+
+        let root = guard.finish().unwrap();
+        assert_eq!(root.calls.len(), 2);
+        assert_eq!(root.calls[0].kind, CallKind::StaticCall);
+        assert_eq!(root.calls[1].to, Some(EthAddress::new([0xcc; 20])));
+    }
+}
@@ -0,0 +1,171 @@
+//! Native contract precompiles
+//!
+//! Escrows, HTLC wrappers and similar contracts need to check this chain's
+//! signatures and addresses, which is slow and costly in bytecode. On top of
+//! the standard precompiles revm already provides (ecrecover at 0x01,
+//! SHA256 at 0x02 and the rest), every contract execution gets:
+//!
+//! - `0x0100` EDU recover: `hash ‖ v ‖ r ‖ s` laid out as for ecrecover,
+//!   returning the 20-byte address hash of the signing key left-padded to
+//!   32 bytes, or nothing when the signature does not recover
+//! - `0x0101` EDU address: an address string such as `edu1q...`, returning
+//!   the same padded 20-byte hash, or nothing when it is not an address
+//! - `0x0102` double SHA256: the hash used for blocks and transactions
+//!
+//! Comparing the outputs of 0x0100 and 0x0101 checks that an address signed
+//! a message.
+
+use crate::address::{AddressCodec, AddressKind};
+use crate::crypto::{double_sha256, sha256};
+use revm::handler::register::EvmHandler;
+use revm::precompile::{
+    u64_to_address, Precompile, PrecompileError, PrecompileOutput, PrecompileResult, PrecompileWithAddress,
+};
+use revm::primitives::Bytes;
+use revm::{ContextPrecompile, Database};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, SECP256K1};
+use std::sync::Arc;
+
+/// Prefix of every EDU address
+pub const ADDRESS_PREFIX: &str = "edu1q";
+
+pub const EDU_RECOVER: u64 = 0x0100;
+pub const EDU_ADDRESS: u64 = 0x0101;
+pub const DOUBLE_SHA256: u64 = 0x0102;
+
+/// Same price as ecrecover
+const RECOVER_GAS: u64 = 3_000;
+const ADDRESS_GAS: u64 = 200;
+/// Twice SHA256's base and per-word cost
+const DOUBLE_SHA256_BASE_GAS: u64 = 120;
+const DOUBLE_SHA256_WORD_GAS: u64 = 24;
+
+/// The chain's precompiles and their addresses
+pub fn precompiles() -> [PrecompileWithAddress; 3] {
+    [
+        PrecompileWithAddress(u64_to_address(EDU_RECOVER), Precompile::Standard(edu_recover)),
+        PrecompileWithAddress(u64_to_address(EDU_ADDRESS), Precompile::Standard(edu_address)),
+        PrecompileWithAddress(u64_to_address(DOUBLE_SHA256), Precompile::Standard(double_sha256_precompile)),
+    ]
+}
+
+/// Handler register adding `precompiles` to the standard set
+pub fn register_handler<EXT, DB: Database>(handler: &mut EvmHandler<'_, EXT, DB>) {
+    let load_standard = handler.pre_execution.load_precompiles.clone();
+    handler.pre_execution.load_precompiles = Arc::new(move || {
+        let mut loaded = load_standard();
+        loaded.extend(
+            precompiles()
+                .into_iter()
+                .map(|PrecompileWithAddress(address, precompile)| (address, ContextPrecompile::Ordinary(precompile))),
+        );
+        loaded
+    });
+}
+
+/// 20-byte hash an address is built from: SHA256 of the compressed
+/// public key, truncated
+pub fn address_hash(public_key: &[u8]) -> [u8; 20] {
+    let mut hash = [0u8; 20];
+    hash.copy_from_slice(&sha256(public_key)[..20]);
+    hash
+}
+
+/// Hash inside an `edu1q` address, if it is one
+pub fn parse_address(address: &str) -> Option<[u8; 20]> {
+    AddressCodec::decode_kind(address, AddressKind::P2pkh).ok()
+}
+
+/// Address hash of the key that signed `input[..32]`
+fn recover(input: &[u8; 128]) -> Option<[u8; 20]> {
+    // v is a 32-byte word holding 27 or 28
+    if input[32..63].iter().any(|b| *b != 0) {
+        return None;
+    }
+    let recovery_id = match input[63] {
+        v @ (27 | 28) => RecoveryId::from_i32(i32::from(v - 27)).ok()?,
+        _ => return None,
+    };
+    let signature = RecoverableSignature::from_compact(&input[64..], recovery_id).ok()?;
+    let message = Message::from_digest_slice(&input[..32]).ok()?;
+    let public_key = SECP256K1.recover_ecdsa(&message, &signature).ok()?;
+    Some(address_hash(&public_key.serialize()))
+}
+
+fn padded(hash: Option<[u8; 20]>) -> Bytes {
+    hash.map(|hash| {
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(&hash);
+        Bytes::copy_from_slice(&word)
+    })
+    .unwrap_or_default()
+}
+
+fn edu_recover(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+    if RECOVER_GAS > gas_limit {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    // Short input is zero-padded, as for ecrecover
+    let mut word = [0u8; 128];
+    let len = input.len().min(128);
+    word[..len].copy_from_slice(&input[..len]);
+    Ok(PrecompileOutput::new(RECOVER_GAS, padded(recover(&word))))
+}
+
+fn edu_address(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+    if ADDRESS_GAS > gas_limit {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    let hash = std::str::from_utf8(input).ok().and_then(parse_address);
+    Ok(PrecompileOutput::new(ADDRESS_GAS, padded(hash)))
+}
+
+fn double_sha256_precompile(input: &Bytes, gas_limit: u64) -> PrecompileResult {
+    let words = (input.len() as u64).div_ceil(32);
+    let gas = DOUBLE_SHA256_BASE_GAS + DOUBLE_SHA256_WORD_GAS * words;
+    if gas > gas_limit {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    Ok(PrecompileOutput::new(gas, Bytes::copy_from_slice(&double_sha256(input))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::SecretKey;
+
+    #[test]
+    fn test_recover_matches_address() {
+        let secret = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let public_key = secret.public_key(SECP256K1).serialize();
+        let address = format!("{}{}", ADDRESS_PREFIX, bs58::encode(&address_hash(&public_key)).into_string());
+
+        let digest = sha256(b"release escrow");
+        let signature = SECP256K1.sign_ecdsa_recoverable(&Message::from_digest_slice(&digest).unwrap(), &secret);
+        let (recovery_id, compact) = signature.serialize_compact();
+        let mut input = digest.to_vec();
+        input.extend_from_slice(&[0u8; 31]);
+        input.push(27 + recovery_id.to_i32() as u8);
+        input.extend_from_slice(&compact);
+
+        let recovered = edu_recover(&Bytes::from(input.clone()), 10_000).unwrap().bytes;
+        let parsed = edu_address(&Bytes::from(address.into_bytes()), 10_000).unwrap().bytes;
+        assert_eq!(recovered.len(), 32);
+        assert_eq!(recovered, parsed);
+
+        // Bad v, and too little gas
+        input[63] = 29;
+        assert!(edu_recover(&Bytes::from(input), 10_000).unwrap().bytes.is_empty());
+        assert!(edu_recover(&Bytes::new(), 100).is_err());
+    }
+
+    #[test]
+    fn test_address_check_rejects_malformed() {
+        for bad in ["", "edu1q", "edu1q0OIl", "btc1qabc", "edu1qTestDeployer000000000000000"] {
+            assert!(edu_address(&Bytes::from(bad.as_bytes().to_vec()), 10_000).unwrap().bytes.is_empty(), "{}", bad);
+        }
+        let output = double_sha256_precompile(&Bytes::new(), 1_000).unwrap();
+        assert_eq!((output.gas_used, output.bytes.to_vec()), (120, double_sha256(b"").to_vec()));
+    }
+}
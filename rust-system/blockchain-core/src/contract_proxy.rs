@@ -0,0 +1,83 @@
+//! Upgradeable proxy contracts
+//!
+//! A proxy forwards calls with DELEGATECALL to an implementation contract
+//! whose address it keeps in storage, so the implementation can be swapped
+//! without changing the address users call. Proxies here follow EIP-1967:
+//! the implementation, admin and beacon addresses live in fixed slots that
+//! no Solidity variable layout can collide with. Reading those slots tells
+//! the node which ABI a proxy's calls and events should be decoded with.
+
+use crate::contracts::{ContractAccount, EthAddress};
+use serde::{Deserialize, Serialize};
+
+/// `keccak256("eip1967.proxy.implementation") - 1`
+pub const IMPLEMENTATION_SLOT: &str = "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+/// `keccak256("eip1967.proxy.admin") - 1`
+pub const ADMIN_SLOT: &str = "b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103";
+
+/// `keccak256("eip1967.proxy.beacon") - 1`
+pub const BEACON_SLOT: &str = "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+
+/// Proxy slots of a contract
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyInfo {
+    pub implementation: Option<EthAddress>,
+    pub admin: Option<EthAddress>,
+    pub beacon: Option<EthAddress>,
+}
+
+impl ProxyInfo {
+    /// Read the EIP-1967 slots from a contract's storage
+    pub fn read(contract: &ContractAccount) -> Self {
+        let slot = |key: &str| contract.storage.get(key).and_then(|value| slot_address(value));
+        Self {
+            implementation: slot(IMPLEMENTATION_SLOT),
+            admin: slot(ADMIN_SLOT),
+            beacon: slot(BEACON_SLOT),
+        }
+    }
+
+    pub fn is_proxy(&self) -> bool {
+        self.implementation.is_some() || self.beacon.is_some()
+    }
+}
+
+/// Address held in the low 20 bytes of a hex storage word
+fn slot_address(value: &str) -> Option<EthAddress> {
+    let bytes = hex::decode(value).ok()?;
+    if bytes.len() != 32 || bytes.iter().all(|b| *b == 0) {
+        return None;
+    }
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&bytes[12..]);
+    Some(EthAddress::new(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_reads_eip1967_slots() {
+        let mut contract = ContractAccount {
+            address: EthAddress::new([1; 20]),
+            code: Vec::new(),
+            storage: HashMap::new(),
+            balance: 0,
+            nonce: 1,
+            deployed_at: 0,
+            deployer: None,
+            abi: None,
+        };
+        assert!(!ProxyInfo::read(&contract).is_proxy());
+
+        contract.storage.insert(IMPLEMENTATION_SLOT.to_string(), format!("{:0>64}", "aa".repeat(20)));
+        contract.storage.insert(ADMIN_SLOT.to_string(), "0".repeat(64));
+        let info = ProxyInfo::read(&contract);
+        assert!(info.is_proxy());
+        assert_eq!(info.implementation, Some(EthAddress::new([0xaa; 20])));
+        assert_eq!(info.admin, None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Block-scoped contract state
+//!
+//! The contract transactions of a block run against a `StateOverlay`: every
+//! account they change is copied into the overlay and edited there, so the
+//! committed contract state is untouched until the whole block has run.
+//! `ContractExecutor::commit_block` applies the overlay in one step and
+//! keeps a `BlockUndo` holding the accounts as they were before, so the
+//! block can be rolled back when a reorg disconnects it. A block that fails
+//! part way leaves nothing behind, its overlay is dropped. Callers' nonces
+//! are state like any other and go through the same overlay and undo.
+
+use crate::address::{AddressCodec, AddressKind};
+use crate::contract_precompiles::address_hash;
+use crate::contracts::{ContractAccount, EthAddress, Log};
+use crate::transaction::Transaction;
+use crate::Hash256;
+use revm::primitives::EvmState;
+use std::collections::HashMap;
+
+/// Blocks that can be rolled back; a deeper reorg cannot be undone
+pub const MAX_UNDO_BLOCKS: usize = 100;
+
+/// Contract changes of a block not yet committed
+#[derive(Debug, Clone)]
+pub struct StateOverlay {
+    pub height: u64,
+    pub block_hash: Hash256,
+    pub parent_hash: Hash256,
+    /// Accounts the block changed or created, as they are after it
+    changed: HashMap<EthAddress, ContractAccount>,
+    /// Logs of each successful transaction, with its hash
+    logs: Vec<(Vec<Log>, String)>,
+    /// Next nonce of each caller the block used a nonce of
+    nonces: HashMap<String, u64>,
+}
+
+impl StateOverlay {
+    pub fn new(height: u64, block_hash: Hash256, parent_hash: Hash256) -> Self {
+        Self {
+            height,
+            block_hash,
+            parent_hash,
+            changed: HashMap::new(),
+            logs: Vec::new(),
+            nonces: HashMap::new(),
+        }
+    }
+
+    /// Lowest nonce `caller` may use next, given the committed nonces
+    pub fn next_nonce(&self, committed: &HashMap<String, u64>, caller: &str) -> u64 {
+        self.nonces.get(caller).or_else(|| committed.get(caller)).copied().unwrap_or(0)
+    }
+
+    /// Use up `nonce` and every nonce below it for `caller`
+    pub fn use_nonce(&mut self, caller: &str, nonce: u64) {
+        self.nonces.insert(caller.to_string(), nonce.saturating_add(1));
+    }
+
+    /// Committed contracts with the overlay's changes on top
+    pub fn view(&self, committed: &HashMap<EthAddress, ContractAccount>) -> HashMap<EthAddress, ContractAccount> {
+        let mut view = committed.clone();
+        view.extend(self.changed.iter().map(|(address, contract)| (*address, contract.clone())));
+        view
+    }
+
+    /// Keep a transaction's changed accounts and its logs
+    pub fn record(&mut self, accounts: Vec<ContractAccount>, logs: Vec<Log>, tx_hash: String) {
+        self.changed.extend(accounts.into_iter().map(|contract| (contract.address, contract)));
+        self.logs.push((logs, tx_hash));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.logs.is_empty() && self.nonces.is_empty()
+    }
+
+    pub fn changed(&self) -> impl Iterator<Item = &ContractAccount> {
+        self.changed.values()
+    }
+
+    pub(crate) fn into_parts(self) -> (HashMap<EthAddress, ContractAccount>, Vec<(Vec<Log>, String)>, HashMap<String, u64>) {
+        (self.changed, self.logs, self.nonces)
+    }
+}
+
+/// What a committed block replaced, to restore on disconnect
+#[derive(Debug, Clone)]
+pub struct BlockUndo {
+    pub height: u64,
+    pub block_hash: Hash256,
+    pub parent_hash: Hash256,
+    /// Each changed account before the block, None if the block created it
+    pub previous: HashMap<EthAddress, Option<ContractAccount>>,
+    /// Each caller's next nonce before the block, None if it had none
+    pub previous_nonces: HashMap<String, Option<u64>>,
+}
+
+/// Accounts as a successful execution left them: balances, nonces and
+/// storage of contracts, including any `sender` created at `height`.
+/// Externally owned accounts are left out, their funds live in the UTXO set.
+pub fn apply_state(
+    contracts: &HashMap<EthAddress, ContractAccount>,
+    state: EvmState,
+    sender: &str,
+    height: u64,
+) -> Vec<ContractAccount> {
+    let mut changed = Vec::new();
+    for (address, account) in state {
+        if !account.is_touched() {
+            continue;
+        }
+        let key = EthAddress::from_address(address);
+        let mut contract = match contracts.get(&key) {
+            Some(contract) => contract.clone(),
+            None if account.is_created() => ContractAccount {
+                address: key,
+                code: account.info.code.as_ref().map(|code| code.original_bytes().to_vec()).unwrap_or_default(),
+                storage: HashMap::new(),
+                balance: 0,
+                nonce: 0,
+                deployed_at: height,
+                deployer: Some(sender.to_string()),
+                abi: None,
+            },
+            None => continue,
+        };
+        contract.balance = u64::try_from(account.info.balance).unwrap_or(u64::MAX);
+        contract.nonce = account.info.nonce;
+        for (slot, value) in account.storage {
+            let slot = format!("{:064x}", slot);
+            let value = value.present_value();
+            if value.is_zero() {
+                contract.storage.remove(&slot);
+            } else {
+                contract.storage.insert(slot, format!("{:064x}", value));
+            }
+        }
+        changed.push(contract);
+    }
+    changed
+}
+
+/// EDU address that signed a transaction's first input: the public key of
+/// a P2PKH script_sig, `<sig_len> <signature> <pubkey_len> <public_key>`
+pub fn transaction_sender(tx: &Transaction) -> Option<String> {
+    let script_sig = &tx.inputs.first()?.script_sig;
+    let sig_len = *script_sig.first()? as usize;
+    let rest = script_sig.get(1 + sig_len..)?;
+    let (&pubkey_len, public_key) = rest.split_first()?;
+    if pubkey_len != 33 || public_key.len() != 33 {
+        return None;
+    }
+    Some(AddressCodec::encode(AddressKind::P2pkh, &address_hash(public_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract(byte: u8, balance: u64) -> ContractAccount {
+        ContractAccount {
+            address: EthAddress::new([byte; 20]),
+            code: vec![0x00],
+            storage: HashMap::new(),
+            balance,
+            nonce: 1,
+            deployed_at: 0,
+            deployer: None,
+            abi: None,
+        }
+    }
+
+    #[test]
+    fn test_overlay_leaves_committed_state_alone() {
+        let committed: HashMap<_, _> = [contract(1, 10), contract(2, 20)]
+            .into_iter()
+            .map(|contract| (contract.address, contract))
+            .collect();
+
+        let mut overlay = StateOverlay::new(5, [9; 32], [8; 32]);
+        assert!(overlay.is_empty());
+        overlay.record(vec![contract(2, 25), contract(3, 30)], Vec::new(), "tx".to_string());
+
+        let view = overlay.view(&committed);
+        assert_eq!(view.len(), 3);
+        assert_eq!(view[&EthAddress::new([2; 20])].balance, 25);
+        assert_eq!(committed[&EthAddress::new([2; 20])].balance, 20);
+        assert_eq!(overlay.changed().count(), 2);
+
+        let committed_nonces = HashMap::from([("edu1qa".to_string(), 4)]);
+        assert_eq!(overlay.next_nonce(&committed_nonces, "edu1qa"), 4);
+        overlay.use_nonce("edu1qa", 9);
+        assert_eq!(overlay.next_nonce(&committed_nonces, "edu1qa"), 10);
+        assert_eq!(overlay.next_nonce(&committed_nonces, "edu1qb"), 0);
+    }
+}
@@ -0,0 +1,151 @@
+//! Contract execution tracing
+//!
+//! An EVM inspector that records every opcode a call or deployment runs:
+//! where it ran, the gas it cost and what was left, the stack afterwards and
+//! which bytes of memory it changed. `ContractExecutor::trace_call` runs a
+//! call or deployment with it as a dry run, so a developer can see the exact
+//! instruction a failing contract stopped at.
+
+use crate::contract_calls::{CallConfig, CallFrame, CallGuard};
+use revm::interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter, OpCode};
+use revm::{Database, EvmContext, Inspector};
+use serde::Serialize;
+
+/// Opcodes recorded by default before the trace is cut short
+pub const DEFAULT_MAX_STEPS: usize = 10_000;
+
+/// Bytes of memory an opcode wrote, starting at `offset`
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryDiff {
+    pub offset: usize,
+    /// Hex of the changed range as it is after the opcode
+    pub data: String,
+}
+
+/// One executed opcode
+#[derive(Debug, Clone, Serialize)]
+pub struct VmStep {
+    /// Call depth, 0 for the outermost frame
+    pub depth: u64,
+    pub pc: usize,
+    pub opcode: String,
+    pub gas_cost: u64,
+    pub gas_remaining: u64,
+    /// Stack after the opcode, top last
+    pub stack: Vec<String>,
+    pub memory_size: usize,
+    pub memory_diff: Option<MemoryDiff>,
+}
+
+/// State captured before an opcode runs
+struct PendingStep {
+    depth: u64,
+    pc: usize,
+    opcode: u8,
+    gas_before: u64,
+    memory: Vec<u8>,
+}
+
+/// Inspector that records each opcode up to `max_steps`, applying the
+/// same call limits as a real execution
+#[derive(Default)]
+pub struct CallTracer {
+    max_steps: usize,
+    steps: Vec<VmStep>,
+    pending: Option<PendingStep>,
+    truncated: bool,
+    calls: CallGuard,
+}
+
+impl CallTracer {
+    pub fn new(max_steps: usize, call_config: CallConfig) -> Self {
+        Self {
+            max_steps,
+            calls: CallGuard::new(call_config),
+            ..Self::default()
+        }
+    }
+
+    /// Recorded steps, whether more ran than were kept, and the call tree
+    pub fn finish(self) -> (Vec<VmStep>, bool, Option<CallFrame>) {
+        (self.steps, self.truncated, self.calls.finish())
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if self.steps.len() >= self.max_steps {
+            self.truncated = true;
+            return;
+        }
+        self.pending = Some(PendingStep {
+            depth: context.journaled_state.depth(),
+            pc: interp.program_counter(),
+            opcode: interp.current_opcode(),
+            gas_before: interp.gas.remaining(),
+            memory: interp.shared_memory.context_memory().to_vec(),
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Some(pending) = self.pending.take() else { return };
+        let memory = interp.shared_memory.context_memory();
+        let gas_remaining = interp.gas.remaining();
+        self.steps.push(VmStep {
+            depth: pending.depth,
+            pc: pending.pc,
+            opcode: OpCode::new(pending.opcode)
+                .map(|op| op.as_str().to_string())
+                .unwrap_or_else(|| format!("0x{:02x}", pending.opcode)),
+            gas_cost: pending.gas_before.saturating_sub(gas_remaining),
+            gas_remaining,
+            stack: interp.stack.data().iter().map(|word| format!("{:#x}", word)).collect(),
+            memory_size: memory.len(),
+            memory_diff: memory_diff(&pending.memory, memory),
+        });
+    }
+
+    fn call(&mut self, context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.calls.call(context, inputs)
+    }
+
+    fn call_end(&mut self, context: &mut EvmContext<DB>, inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.calls.call_end(context, inputs, outcome)
+    }
+
+    fn create(&mut self, context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.calls.create(context, inputs)
+    }
+
+    fn create_end(&mut self, context: &mut EvmContext<DB>, inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        self.calls.create_end(context, inputs, outcome)
+    }
+}
+
+/// Smallest range covering every byte that changed or was added
+fn memory_diff(before: &[u8], after: &[u8]) -> Option<MemoryDiff> {
+    let differs = |i: &usize| before.get(*i) != after.get(*i);
+    let first = (0..after.len()).find(differs)?;
+    let last = (first..after.len()).rev().find(differs)?;
+    Some(MemoryDiff {
+        offset: first,
+        data: hex::encode(&after[first..=last]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_diff_covers_changed_bytes() {
+        assert!(memory_diff(&[1, 2, 3], &[1, 2, 3]).is_none());
+
+        let diff = memory_diff(&[0; 4], &[0, 7, 0, 9]).unwrap();
+        assert_eq!((diff.offset, diff.data.as_str()), (1, "070009"));
+
+        // Expansion with zeros still shows up
+        let diff = memory_diff(&[], &[0, 0]).unwrap();
+        assert_eq!((diff.offset, diff.data.as_str()), (0, "0000"));
+    }
+}
@@ -4,19 +4,27 @@
 
 use crate::{BlockchainError, Result, Hash256, Address as BlockchainAddress};
 use crate::event_indexer::{EventIndexer, IndexedEvent};
+use crate::contract_trace::{CallTracer, VmStep};
+use crate::contract_calls::{CallConfig, CallFrame, CallGuard};
+use crate::contract_precompiles;
+use crate::contract_proxy::ProxyInfo;
+use crate::contract_state::{self, BlockUndo, StateOverlay, MAX_UNDO_BLOCKS};
+use crate::abi::Abi;
+use crate::mempool::LEGACY_GAS_PRICE;
+use crate::block::Block;
 use revm::{
-    primitives::{Address, Bytecode, TransactTo, TxEnv, U256, B256, Bytes},
-    Database, DatabaseCommit, InMemoryDB, Evm,
+    primitives::{Address, Bytecode, EvmState, ResultAndState, TransactTo, TxEnv, U256, B256, Bytes},
+    Database, DatabaseCommit, InMemoryDB, Evm, GetInspector, inspector_handle_register,
 };
 use serde::{Deserialize, Serialize, Serializer, Deserializer};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use std::path::{Path, PathBuf};
 use tokio::sync::RwLock;
 use tokio::fs;
 
 /// Hex serialization helper
-mod hex_serde {
+pub(crate) mod hex_serde {
     use serde::{Deserialize, Deserializer, Serializer};
     
     pub fn serialize<S>(data: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error>
@@ -121,6 +129,12 @@ pub struct ContractAccount {
     pub nonce: u64,
     /// Block height when contract was deployed
     pub deployed_at: u64,
+    /// EDU address whose transaction created the contract
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployer: Option<String>,
+    /// ABI published by the deployer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abi: Option<Abi>,
 }
 
 /// Contract execution result
@@ -139,6 +153,18 @@ pub struct ExecutionResult {
     pub contract_address: Option<EthAddress>,
     /// Error message if failed
     pub error: Option<String>,
+    /// Every internal call and create, outermost frame first
+    #[serde(default)]
+    pub call_tree: Option<CallFrame>,
+}
+
+/// A dry run with every opcode recorded
+#[derive(Debug, Clone, Serialize)]
+pub struct CallTrace {
+    pub result: ExecutionResult,
+    pub steps: Vec<VmStep>,
+    /// More opcodes ran than were recorded
+    pub truncated: bool,
 }
 
 /// Contract event log
@@ -150,6 +176,12 @@ pub struct Log {
     pub data: Vec<u8>,
 }
 
+/// File in the storage directory holding the committed block tip
+const TIP_FILE: &str = "applied_tip";
+
+/// File in the storage directory holding callers' next nonces
+const NONCE_FILE: &str = "caller_nonces";
+
 /// Smart contract executor using revm
 pub struct ContractExecutor {
     /// Contract state storage
@@ -160,6 +192,17 @@ pub struct ContractExecutor {
     storage_path: PathBuf,
     /// Event indexer
     event_indexer: Arc<EventIndexer>,
+    /// Call depth and reentrancy limits
+    call_config: CallConfig,
+    /// Last block whose contract transactions were committed
+    tip: Arc<RwLock<Option<(u64, Hash256)>>>,
+    /// What recent committed blocks replaced, newest last
+    undo: Arc<RwLock<VecDeque<BlockUndo>>>,
+    /// Lowest nonce each EDU address may use next
+    nonces: Arc<RwLock<HashMap<String, u64>>>,
+    /// Next nonces of calls made through this node outside a block; never
+    /// consulted by `execute_block`
+    local_nonces: Arc<RwLock<HashMap<String, u64>>>,
 }
 
 impl ContractExecutor {
@@ -175,9 +218,114 @@ impl ContractExecutor {
             balances: Arc::new(RwLock::new(HashMap::new())),
             storage_path: path.as_ref().to_path_buf(),
             event_indexer: Arc::new(EventIndexer::new()),
+            call_config: CallConfig::default(),
+            tip: Arc::new(RwLock::new(None)),
+            undo: Arc::new(RwLock::new(VecDeque::new())),
+            nonces: Arc::new(RwLock::new(HashMap::new())),
+            local_nonces: Arc::new(RwLock::new(HashMap::new())),
         }
     }
     
+    /// Set the limits on contract-to-contract calls
+    pub fn with_call_config(mut self, call_config: CallConfig) -> Self {
+        self.call_config = call_config;
+        self
+    }
+    
+    /// Add every stored contract, with its code, balance and storage
+    fn insert_contracts(db: &mut InMemoryDB, contracts: &HashMap<EthAddress, ContractAccount>) {
+        for (addr, contract_data) in contracts.iter() {
+            let contract_info = revm::primitives::AccountInfo {
+                balance: U256::from(contract_data.balance),
+                nonce: contract_data.nonce,
+                code_hash: revm::primitives::KECCAK_EMPTY,
+                code: Some(Bytecode::new_raw(Bytes::from(contract_data.code.clone()))),
+            };
+            db.insert_account_info(addr.to_address(), contract_info);
+            for (slot, value) in &contract_data.storage {
+                if let (Ok(slot), Ok(value)) = (U256::from_str_radix(slot, 16), U256::from_str_radix(value, 16)) {
+                    let _ = db.insert_account_storage(addr.to_address(), slot, value);
+                }
+            }
+        }
+    }
+    
+    /// Keep what a successful execution changed: balances, nonces and
+    /// storage of contracts, including any `sender` created
+    async fn commit_state(&self, state: EvmState, sender: &str) {
+        let mut contracts = self.contracts.write().await;
+        let changed = contract_state::apply_state(&contracts, state, sender, 0); // Block height set by caller
+        contracts.extend(changed.iter().map(|contract| (contract.address, contract.clone())));
+        drop(contracts);
+        
+        self.persist(changed).await;
+    }
+    
+    /// Save changed contracts and follow any proxy upgrades among them
+    async fn persist(&self, changed: Vec<ContractAccount>) {
+        for contract in changed {
+            // An upgrade changes which ABI the proxy's events decode with
+            self.event_indexer.set_implementation(contract.address, ProxyInfo::read(&contract).implementation).await;
+            if let Err(e) = self.save_contract(&contract).await {
+                eprintln!("Warning: Failed to persist contract to disk: {}", e);
+            }
+        }
+    }
+    
+    /// Transaction environment for a call or deployment
+    fn tx_env(caller: Address, transact_to: TransactTo, data: Vec<u8>, value: u64, gas_limit: u64, gas_price: u64) -> TxEnv {
+        TxEnv {
+            caller,
+            transact_to,
+            data: Bytes::from(data),
+            value: U256::from(value),
+            gas_limit,
+            gas_price: U256::from(gas_price),
+            ..TxEnv::default()
+        }
+    }
+    
+    /// Run one transaction against `contracts` with `inspector` attached,
+    /// the caller holding `funds` plus enough for value and gas
+    fn transact<EXT>(
+        contracts: &HashMap<EthAddress, ContractAccount>,
+        tx: TxEnv,
+        funds: u64,
+        inspector: EXT,
+    ) -> std::result::Result<(ResultAndState, EXT), String>
+    where
+        EXT: GetInspector<InMemoryDB> + Default,
+    {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(tx.caller, revm::primitives::AccountInfo {
+            balance: U256::from(funds) + tx.value + U256::from(tx.gas_limit) * tx.gas_price.max(U256::from(10)),
+            nonce: 0,
+            code_hash: revm::primitives::KECCAK_EMPTY,
+            code: None,
+        });
+        // Every contract is loaded for contract-to-contract calls
+        Self::insert_contracts(&mut db, contracts);
+        
+        let mut evm = Evm::builder()
+            .with_db(db)
+            .with_external_context(inspector)
+            .modify_tx_env(|env| *env = tx)
+            .append_handler_register(inspector_handle_register)
+            .append_handler_register(contract_precompiles::register_handler)
+            .build();
+        let result = evm.transact().map_err(|e| format!("{:?}", e))?;
+        Ok((result, std::mem::take(&mut evm.context.external)))
+    }
+    
+    /// Logs in the form they are returned and indexed
+    fn convert_logs(logs: Vec<revm::primitives::Log>) -> Vec<Log> {
+        logs.into_iter().map(|log| Log {
+            address: EthAddress::from_address(log.address),
+            topics: log.topics().iter().map(|t| hex::encode(t)).collect(),
+            data: log.data.data.to_vec(),
+        }).collect()
+    }
+    
     /// Load all contracts from disk
     pub async fn load_contracts(&self) -> Result<()> {
         // Create storage directory if it doesn't exist
@@ -199,12 +347,25 @@ impl ContractExecutor {
             // Only load .json files
             if path.extension().and_then(|s| s.to_str()) == Some("json") {
                 if let Ok(contract) = self.load_contract_from_file(&path).await {
+                    self.event_indexer.set_implementation(contract.address, ProxyInfo::read(&contract).implementation).await;
                     self.contracts.write().await.insert(contract.address, contract);
                     loaded_count += 1;
                 }
             }
         }
         
+        if let Ok(data) = fs::read_to_string(self.storage_path.join(NONCE_FILE)).await {
+            match serde_json::from_str(&data) {
+                Ok(nonces) => *self.nonces.write().await = nonces,
+                Err(e) => eprintln!("Warning: Failed to load caller nonces: {}", e),
+            }
+        }
+        if let Ok(data) = fs::read_to_string(self.storage_path.join(TIP_FILE)).await {
+            *self.tip.write().await = data.split_once(' ').and_then(|(height, hash)| {
+                Some((height.parse().ok()?, hex::decode(hash).ok()?.try_into().ok()?))
+            });
+        }
+        
         println!("Loaded {} contracts from disk", loaded_count);
         Ok(())
     }
@@ -250,6 +411,7 @@ impl ContractExecutor {
         bytecode: Vec<u8>,
         value: u64,
         gas_limit: u64,
+        gas_price: u64,
     ) -> Result<ExecutionResult> {
         // Convert EDU address to Ethereum address (use hash)
         let deployer_addr = self.edu_to_eth_address(deployer)?;
@@ -257,48 +419,13 @@ impl ContractExecutor {
         // Get deployer balance from our tracking
         let deployer_balance = self.get_balance(deployer).await;
         
-        // Create EVM with in-memory database
-        let mut db = InMemoryDB::default();
-        
-        // Set deployer account with actual balance
-        let deployer_info = revm::primitives::AccountInfo {
-            balance: U256::from(deployer_balance + value + gas_limit * 10), // Ensure enough for gas + value
-            nonce: 0,
-            code_hash: revm::primitives::KECCAK_EMPTY,
-            code: None,
-        };
-        db.insert_account_info(deployer_addr, deployer_info);
-        
-        // Load ALL existing contracts into EVM database (for constructor calls)
+        // Run against ALL existing contracts (for constructor calls)
+        let tx = Self::tx_env(deployer_addr, TransactTo::Create, bytecode.clone(), value, gas_limit, gas_price);
         let contracts = self.contracts.read().await;
-        for (addr, contract_data) in contracts.iter() {
-            let contract_info = revm::primitives::AccountInfo {
-                balance: U256::from(contract_data.balance),
-                nonce: contract_data.nonce,
-                code_hash: revm::primitives::KECCAK_EMPTY,
-                code: Some(Bytecode::new_raw(Bytes::from(contract_data.code.clone()))),
-            };
-            db.insert_account_info(addr.to_address(), contract_info);
-        }
+        let (result, guard) = Self::transact(&contracts, tx, deployer_balance, CallGuard::new(self.call_config))
+            .map_err(|e| BlockchainError::ContractExecutionFailed(format!("Deployment failed: {}", e)))?;
         drop(contracts); // Release read lock before writing
-        
-        // Configure transaction
-        let mut evm = Evm::builder()
-            .with_db(db)
-            .modify_tx_env(|tx| {
-                tx.caller = deployer_addr;
-                tx.transact_to = TransactTo::Create;
-                tx.data = Bytes::from(bytecode.clone());
-                tx.value = U256::from(value);
-                tx.gas_limit = gas_limit;
-                tx.gas_price = U256::from(1);
-            })
-            .build();
-        
-        // Execute deployment
-        let result = evm.transact().map_err(|e| {
-            BlockchainError::ContractExecutionFailed(format!("Deployment failed: {:?}", e))
-        })?;
+        let call_tree = guard.finish();
         
         let execution_result = match result.result {
             revm::primitives::ExecutionResult::Success {
@@ -313,36 +440,14 @@ impl ContractExecutor {
                     _ => None,
                 };
                 
-                // Store contract if deployment succeeded
-                let bytecode_copy = bytecode.clone();
-                if let Some(addr) = contract_address {
-                    let contract = ContractAccount {
-                        address: EthAddress::from_address(addr),
-                        code: bytecode,
-                        storage: HashMap::new(),
-                        balance: value,
-                        nonce: 1,
-                        deployed_at: 0, // Will be set by caller
-                    };
-                    
-                    // Save to memory
-                    self.contracts.write().await.insert(EthAddress::from_address(addr), contract.clone());
-                    
-                    // Persist to disk
-                    if let Err(e) = self.save_contract(&contract).await {
-                        eprintln!("Warning: Failed to persist contract to disk: {}", e);
-                    }
-                }
+                // Store the new contract and anything its constructor changed
+                self.commit_state(result.state, deployer).await;
                 
-                let result_logs = logs.into_iter().map(|log| Log {
-                    address: EthAddress::from_address(log.address),
-                    topics: log.topics().iter().map(|t| hex::encode(t)).collect(),
-                    data: log.data.data.to_vec(),
-                }).collect::<Vec<_>>();
+                let result_logs = Self::convert_logs(logs);
                 
                 // Index events (deployment)
                 if let Some(addr) = contract_address {
-                    let tx_hash = hex::encode(&bytecode_copy[..32.min(bytecode_copy.len())]);
+                    let tx_hash = hex::encode(&bytecode[..32.min(bytecode.len())]);
                     self.event_indexer.index_events(
                         result_logs.clone(), 
                         0, // Block height will be set by caller
@@ -357,6 +462,7 @@ impl ContractExecutor {
                     logs: result_logs,
                     contract_address: contract_address.map(EthAddress::from_address),
                     error: None,
+                    call_tree,
                 }
             }
             revm::primitives::ExecutionResult::Revert { gas_used, output } => {
@@ -367,6 +473,7 @@ impl ContractExecutor {
                     logs: Vec::new(),
                     contract_address: None,
                     error: Some("Contract reverted".to_string()),
+                    call_tree,
                 }
             }
             revm::primitives::ExecutionResult::Halt { reason, gas_used } => {
@@ -377,6 +484,7 @@ impl ContractExecutor {
                     logs: Vec::new(),
                     contract_address: None,
                     error: Some(format!("Execution halted: {:?}", reason)),
+                    call_tree,
                 }
             }
         };
@@ -392,11 +500,12 @@ impl ContractExecutor {
         calldata: Vec<u8>,
         value: u64,
         gas_limit: u64,
+        gas_price: u64,
     ) -> Result<ExecutionResult> {
-        // Get contract code
         let contracts = self.contracts.read().await;
-        let contract = contracts.get(&contract_address)
-            .ok_or_else(|| BlockchainError::ContractNotFound(format!("{:?}", contract_address)))?;
+        if !contracts.contains_key(&contract_address) {
+            return Err(BlockchainError::ContractNotFound(format!("{:?}", contract_address)));
+        }
         
         let caller_addr = self.edu_to_eth_address(caller)?;
         let eth_contract_addr = contract_address.to_address();
@@ -404,46 +513,12 @@ impl ContractExecutor {
         // Get caller balance from our tracking
         let caller_balance = self.get_balance(caller).await;
         
-        // Create EVM with contract state
-        let mut db = InMemoryDB::default();
-        
-        // Set caller account with actual balance
-        let caller_info = revm::primitives::AccountInfo {
-            balance: U256::from(caller_balance + value + gas_limit * 10), // Ensure enough for gas + value
-            nonce: 0,
-            code_hash: revm::primitives::KECCAK_EMPTY,
-            code: None,
-        };
-        db.insert_account_info(caller_addr, caller_info);
-        
-        // Load ALL contracts into EVM database for contract-to-contract calls
-        for (addr, contract_data) in contracts.iter() {
-            let contract_info = revm::primitives::AccountInfo {
-                balance: U256::from(contract_data.balance),
-                nonce: contract_data.nonce,
-                code_hash: revm::primitives::KECCAK_EMPTY,
-                code: Some(Bytecode::new_raw(Bytes::from(contract_data.code.clone()))),
-            };
-            db.insert_account_info(addr.to_address(), contract_info);
-        }
-        
-        // Configure transaction
-        let mut evm = Evm::builder()
-            .with_db(db)
-            .modify_tx_env(|tx| {
-                tx.caller = caller_addr;
-                tx.transact_to = TransactTo::Call(eth_contract_addr);
-                tx.data = Bytes::from(calldata);
-                tx.value = U256::from(value);
-                tx.gas_limit = gas_limit;
-                tx.gas_price = U256::from(1);
-            })
-            .build();
-        
-        // Execute call
-        let result = evm.transact().map_err(|e| {
-            BlockchainError::ContractExecutionFailed(format!("Call failed: {:?}", e))
-        })?;
+        // Run with ALL contracts loaded for contract-to-contract calls
+        let tx = Self::tx_env(caller_addr, TransactTo::Call(eth_contract_addr), calldata, value, gas_limit, gas_price);
+        let (result, guard) = Self::transact(&contracts, tx, caller_balance, CallGuard::new(self.call_config))
+            .map_err(|e| BlockchainError::ContractExecutionFailed(format!("Call failed: {}", e)))?;
+        drop(contracts);
+        let call_tree = guard.finish();
         
         let execution_result = match result.result {
             revm::primitives::ExecutionResult::Success {
@@ -458,11 +533,10 @@ impl ContractExecutor {
                     _ => Vec::new(),
                 };
                 
-                let result_logs = logs.into_iter().map(|log| Log {
-                    address: EthAddress::from_address(log.address),
-                    topics: log.topics().iter().map(|t| hex::encode(t)).collect(),
-                    data: log.data.data.to_vec(),
-                }).collect::<Vec<_>>();
+                // Keep storage, balance and created-contract changes
+                self.commit_state(result.state, caller).await;
+                
+                let result_logs = Self::convert_logs(logs);
                 
                 // Index events (contract call)
                 let tx_hash = format!("{}_{}", caller, hex::encode(contract_address.as_bytes()));
@@ -479,6 +553,7 @@ impl ContractExecutor {
                     logs: result_logs,
                     contract_address: None,
                     error: None,
+                    call_tree,
                 }
             }
             revm::primitives::ExecutionResult::Revert { gas_used, output } => {
@@ -489,6 +564,7 @@ impl ContractExecutor {
                     logs: Vec::new(),
                     contract_address: None,
                     error: Some("Contract reverted".to_string()),
+                    call_tree,
                 }
             }
             revm::primitives::ExecutionResult::Halt { reason, gas_used } => {
@@ -499,6 +575,7 @@ impl ContractExecutor {
                     logs: Vec::new(),
                     contract_address: None,
                     error: Some(format!("Execution halted: {:?}", reason)),
+                    call_tree,
                 }
             }
         };
@@ -506,11 +583,356 @@ impl ContractExecutor {
         Ok(execution_result)
     }
 
+    /// Run a call, or a deployment when `contract_address` is None, tracing
+    /// each opcode. Nothing is kept: no contract is stored, no state
+    /// changes and no events are indexed.
+    pub async fn trace_call(
+        &self,
+        caller: &str,
+        contract_address: Option<EthAddress>,
+        data: Vec<u8>,
+        value: u64,
+        gas_limit: u64,
+        max_steps: usize,
+    ) -> Result<CallTrace> {
+        let contracts = self.contracts.read().await;
+        if let Some(address) = &contract_address {
+            if !contracts.contains_key(address) {
+                return Err(BlockchainError::ContractNotFound(format!("{:?}", address)));
+            }
+        }
+        
+        let caller_addr = self.edu_to_eth_address(caller)?;
+        let caller_balance = self.get_balance(caller).await;
+        
+        // Same state a real call or deployment would see
+        let transact_to = match contract_address {
+            Some(address) => TransactTo::Call(address.to_address()),
+            None => TransactTo::Create,
+        };
+        let tx = Self::tx_env(caller_addr, transact_to, data, value, gas_limit, LEGACY_GAS_PRICE);
+        let (result, tracer) = Self::transact(&contracts, tx, caller_balance, CallTracer::new(max_steps, self.call_config))
+            .map_err(|e| BlockchainError::ContractExecutionFailed(format!("Traced execution failed: {}", e)))?;
+        drop(contracts);
+        let (steps, truncated, call_tree) = tracer.finish();
+        
+        let result = match result.result {
+            revm::primitives::ExecutionResult::Success { gas_used, logs, output, .. } => {
+                let (return_data, contract_address) = match output {
+                    revm::primitives::Output::Call(data) => (data.to_vec(), None),
+                    revm::primitives::Output::Create(_, addr) => (Vec::new(), addr.map(EthAddress::from_address)),
+                };
+                ExecutionResult {
+                    success: true,
+                    gas_used,
+                    return_data,
+                    logs: Self::convert_logs(logs),
+                    contract_address,
+                    error: None,
+                    call_tree,
+                }
+            }
+            revm::primitives::ExecutionResult::Revert { gas_used, output } => ExecutionResult {
+                success: false,
+                gas_used,
+                return_data: output.to_vec(),
+                logs: Vec::new(),
+                contract_address: None,
+                error: Some("Contract reverted".to_string()),
+                call_tree,
+            },
+            revm::primitives::ExecutionResult::Halt { reason, gas_used } => ExecutionResult {
+                success: false,
+                gas_used,
+                return_data: Vec::new(),
+                logs: Vec::new(),
+                contract_address: None,
+                error: Some(format!("Execution halted: {:?}", reason)),
+                call_tree,
+            },
+        };
+        
+        Ok(CallTrace { result, steps, truncated })
+    }
+
+    /// Run a block's contract transactions on top of the committed state
+    /// without changing it. A transaction that cannot run at all fails the
+    /// block, and its overlay with everything earlier transactions did.
+    pub async fn execute_block(&self, height: u64, block: &Block) -> Result<StateOverlay> {
+        let mut overlay = StateOverlay::new(height, block.get_hash(), block.header.prev_block_hash);
+        let committed = self.contracts.read().await;
+        for tx in &block.transactions {
+            let (transact_to, data) = match (&tx.contract_code, tx.contract_address, &tx.contract_data) {
+                (Some(code), _, _) => (TransactTo::Create, code.clone()),
+                (None, Some(address), Some(data)) => (TransactTo::Call(Address::from(address)), data.clone()),
+                _ => continue,
+            };
+            let tx_hash = hex::encode(tx.get_hash()?);
+            let sender = contract_state::transaction_sender(tx).ok_or_else(|| {
+                BlockchainError::InvalidInput(format!("Contract transaction {} has no P2PKH sender", tx_hash))
+            })?;
+            let gas_limit = tx.gas_limit.ok_or_else(|| {
+                BlockchainError::InvalidInput(format!("Contract transaction {} has no gas limit", tx_hash))
+            })?;
+            
+            // A replayed or out-of-order nonce invalidates the block
+            let next_nonce = overlay.next_nonce(&*self.nonces.read().await, &sender);
+            match tx.nonce {
+                Some(nonce) if nonce >= next_nonce => overlay.use_nonce(&sender, nonce),
+                _ => {
+                    return Err(BlockchainError::InvalidInput(format!(
+                        "Contract transaction {} has nonce {:?}, {} needs at least {}", tx_hash, tx.nonce, sender, next_nonce
+                    )));
+                }
+            }
+            
+            let view = overlay.view(&committed);
+            if let TransactTo::Call(address) = transact_to {
+                if !view.contains_key(&EthAddress::from_address(address)) {
+                    return Err(BlockchainError::ContractNotFound(format!("{:?} called by {}", address, tx_hash)));
+                }
+            }
+            let env = Self::tx_env(self.edu_to_eth_address(&sender)?, transact_to, data, 0, gas_limit, LEGACY_GAS_PRICE);
+            let funds = self.get_balance(&sender).await;
+            let (result, _) = Self::transact(&view, env, funds, CallGuard::new(self.call_config))
+                .map_err(|e| BlockchainError::ContractExecutionFailed(format!("Transaction {} failed: {}", tx_hash, e)))?;
+            
+            // A revert or halt is a valid outcome that changes nothing
+            if let revm::primitives::ExecutionResult::Success { logs, .. } = result.result {
+                let changed = contract_state::apply_state(&view, result.state, &sender, height);
+                overlay.record(changed, Self::convert_logs(logs), tx_hash);
+            }
+        }
+        Ok(overlay)
+    }
+    
+    /// Apply an executed block's changes in one step, remembering what they
+    /// replaced so the block can be disconnected later. The block must
+    /// extend the last committed one.
+    pub async fn commit_block(&self, overlay: StateOverlay) -> Result<()> {
+        let mut tip = self.tip.write().await;
+        if let Some((height, hash)) = *tip {
+            if overlay.height != height + 1 || overlay.parent_hash != hash {
+                return Err(BlockchainError::InvalidInput(format!(
+                    "Block {} does not extend committed contract state at {}", overlay.height, height
+                )));
+            }
+        }
+        let (height, block_hash, parent_hash) = (overlay.height, overlay.block_hash, overlay.parent_hash);
+        let (changed, logs, used_nonces) = overlay.into_parts();
+        
+        let mut contracts = self.contracts.write().await;
+        let previous = changed.keys().map(|address| (*address, contracts.get(address).cloned())).collect();
+        contracts.extend(changed.iter().map(|(address, contract)| (*address, contract.clone())));
+        drop(contracts);
+        
+        let mut nonces = self.nonces.write().await;
+        let previous_nonces = used_nonces.keys().map(|caller| (caller.clone(), nonces.get(caller).copied())).collect();
+        nonces.extend(used_nonces);
+        self.save_nonces(&nonces).await;
+        drop(nonces);
+        
+        let mut undo = self.undo.write().await;
+        undo.push_back(BlockUndo { height, block_hash, parent_hash, previous, previous_nonces });
+        while undo.len() > MAX_UNDO_BLOCKS {
+            undo.pop_front();
+        }
+        drop(undo);
+        *tip = Some((height, block_hash));
+        self.save_tip(*tip).await;
+        drop(tip);
+        
+        self.persist(changed.into_values().collect()).await;
+        for (logs, tx_hash) in logs {
+            self.event_indexer.index_events(logs, height, tx_hash).await;
+        }
+        Ok(())
+    }
+    
+    /// Roll back the last committed block, for a reorg. Fails once the
+    /// block is older than the undo data kept.
+    pub async fn disconnect_block(&self) -> Result<()> {
+        let mut tip = self.tip.write().await;
+        let mut undo_log = self.undo.write().await;
+        let undo = match undo_log.back() {
+            Some(undo) if Some((undo.height, undo.block_hash)) == *tip => undo_log.pop_back(),
+            _ => None,
+        }
+        .ok_or_else(|| BlockchainError::StorageError("No undo data for the committed contract block".to_string()))?;
+        drop(undo_log);
+        
+        let mut contracts = self.contracts.write().await;
+        let mut restored = Vec::new();
+        let mut removed = Vec::new();
+        for (address, previous) in undo.previous {
+            match previous {
+                Some(contract) => {
+                    contracts.insert(address, contract.clone());
+                    restored.push(contract);
+                }
+                None => {
+                    contracts.remove(&address);
+                    removed.push(address);
+                }
+            }
+        }
+        drop(contracts);
+        
+        let mut nonces = self.nonces.write().await;
+        for (caller, previous) in undo.previous_nonces {
+            match previous {
+                Some(nonce) => nonces.insert(caller, nonce),
+                None => nonces.remove(&caller),
+            };
+        }
+        self.save_nonces(&nonces).await;
+        drop(nonces);
+        
+        *tip = undo.height.checked_sub(1).map(|height| (height, undo.parent_hash));
+        self.save_tip(*tip).await;
+        drop(tip);
+        
+        self.persist(restored).await;
+        for address in removed {
+            self.event_indexer.set_implementation(address, None).await;
+            let path = self.storage_path.join(format!("contract_{}.json", hex::encode(address.as_bytes())));
+            if let Err(e) = fs::remove_file(&path).await {
+                eprintln!("Warning: Failed to remove contract from disk: {}", e);
+            }
+        }
+        self.event_indexer.remove_block(undo.height).await;
+        Ok(())
+    }
+    
+    /// Height and hash of the last block whose contract transactions are
+    /// committed
+    pub async fn applied_tip(&self) -> Option<(u64, Hash256)> {
+        *self.tip.read().await
+    }
+    
+    /// Record the committed tip next to the contracts
+    async fn save_tip(&self, tip: Option<(u64, Hash256)>) {
+        let path = self.storage_path.join(TIP_FILE);
+        let written = match tip {
+            Some((height, hash)) => {
+                if let Err(e) = fs::create_dir_all(&self.storage_path).await {
+                    eprintln!("Warning: Failed to persist contract tip: {}", e);
+                    return;
+                }
+                fs::write(&path, format!("{} {}", height, hex::encode(hash))).await
+            }
+            None => fs::remove_file(&path).await.or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+        };
+        if let Err(e) = written {
+            eprintln!("Warning: Failed to persist contract tip: {}", e);
+        }
+    }
+    
+    /// Lowest nonce `caller` may use next
+    pub async fn get_nonce(&self, caller: &str) -> u64 {
+        self.nonces.read().await.get(caller).copied().unwrap_or(0)
+    }
+    
+    /// Nonce for a call or deployment made outside a block: `nonce` if
+    /// given and not yet used, otherwise the next one. Nothing is used up
+    /// until `use_local_nonce`
+    pub async fn next_local_nonce(&self, caller: &str, nonce: Option<u64>) -> Result<u64> {
+        let local = self.local_nonces.read().await.get(caller).copied().unwrap_or(0);
+        let next = local.max(self.get_nonce(caller).await);
+        let nonce = nonce.unwrap_or(next);
+        if nonce < next {
+            return Err(BlockchainError::InvalidInput(format!(
+                "Nonce {} already used by {}, the next is {}", nonce, caller, next
+            )));
+        }
+        Ok(nonce)
+    }
+    
+    /// Mark a local call's nonce used once it has succeeded. Block
+    /// validation keeps its own nonces, so this never forks the node
+    pub async fn use_local_nonce(&self, caller: &str, nonce: u64) {
+        let mut local = self.local_nonces.write().await;
+        let next = local.entry(caller.to_string()).or_insert(0);
+        *next = (*next).max(nonce + 1);
+    }
+    
+    /// Record callers' next nonces next to the contracts
+    async fn save_nonces(&self, nonces: &HashMap<String, u64>) {
+        let written = match serde_json::to_string(nonces) {
+            Ok(data) => match fs::create_dir_all(&self.storage_path).await {
+                Ok(()) => fs::write(self.storage_path.join(NONCE_FILE), data).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            eprintln!("Warning: Failed to persist caller nonces: {}", e);
+        }
+    }
+    
     /// Get contract by address
     pub async fn get_contract(&self, address: EthAddress) -> Option<ContractAccount> {
         self.contracts.read().await.get(&address).cloned()
     }
 
+    /// EIP-1967 proxy slots of a contract
+    pub async fn get_proxy_info(&self, address: EthAddress) -> Result<ProxyInfo> {
+        self.contracts.read().await.get(&address)
+            .map(ProxyInfo::read)
+            .ok_or_else(|| BlockchainError::ContractNotFound(format!("{:?}", address)))
+    }
+    
+    /// Publish a contract's ABI for decoding; only its deployer may
+    pub async fn publish_abi(&self, address: EthAddress, publisher: &str, abi: Abi) -> Result<()> {
+        let mut contracts = self.contracts.write().await;
+        let contract = contracts.get_mut(&address)
+            .ok_or_else(|| BlockchainError::ContractNotFound(format!("{:?}", address)))?;
+        if contract.deployer.as_deref() != Some(publisher) {
+            return Err(BlockchainError::InvalidInput(
+                "Only the contract's deployer can publish its ABI".to_string()
+            ));
+        }
+        contract.abi = Some(abi);
+        let contract = contract.clone();
+        drop(contracts);
+        
+        self.save_contract(&contract).await
+    }
+    
+    /// ABI to decode a contract's calls with: its implementation's when it
+    /// is a proxy and that ABI is published, otherwise its own
+    pub async fn get_abi(&self, address: EthAddress) -> Option<Abi> {
+        let contracts = self.contracts.read().await;
+        let contract = contracts.get(&address)?;
+        ProxyInfo::read(contract).implementation
+            .and_then(|implementation| contracts.get(&implementation)?.abi.clone())
+            .or_else(|| contract.abi.clone())
+    }
+    
+    /// Event name and fields of an indexed log, decoded with the ABI of the
+    /// implementation it was emitted under, or of the emitting contract
+    pub async fn decode_event(&self, event: &IndexedEvent) -> Option<serde_json::Value> {
+        let contracts = self.contracts.read().await;
+        let abi = event.implementation
+            .and_then(|implementation| contracts.get(&implementation)?.abi.as_ref())
+            .or_else(|| contracts.get(&event.log.address)?.abi.as_ref())?;
+        let (name, args) = abi.decode_log(&event.log.topics, &event.log.data).ok()??;
+        Some(serde_json::json!({ "event": name, "args": args }))
+    }
+    
+    /// Every proxy contract, optionally only those `admin` can upgrade
+    pub async fn list_proxies(&self, admin: Option<EthAddress>) -> Vec<(EthAddress, ProxyInfo)> {
+        let mut proxies: Vec<_> = self.contracts.read().await.values()
+            .map(|contract| (contract.address, ProxyInfo::read(contract)))
+            .filter(|(_, info)| info.is_proxy() && admin.map_or(true, |admin| info.admin == Some(admin)))
+            .collect();
+        proxies.sort_by_key(|(address, _)| *address.as_bytes());
+        proxies
+    }
+
     /// Convert EDU address to Ethereum address (20 bytes)
     fn edu_to_eth_address(&self, edu_address: &str) -> Result<Address> {
         use sha2::{Sha256, Digest};
@@ -563,10 +985,100 @@ mod tests {
             "edu1qTestDeployer000000000000000",
             bytecode,
             0,
-            100000
+            100000,
+            LEGACY_GAS_PRICE,
         ).await.unwrap();
         
         assert!(result.success);
         assert!(result.contract_address.is_some());
     }
+
+    #[tokio::test]
+    async fn test_reentrancy_guard_blocks_self_call() {
+        // Runtime: with no calldata, CALL itself with one byte of calldata
+        // and store whether that call succeeded in slot 0
+        let runtime = hex::decode("3660155760006000600160006000305af1600055005b00").unwrap();
+        let mut init = hex::decode("6017600c60003960176000f3").unwrap();
+        init.extend_from_slice(&runtime);
+        let slot0 = format!("{:064x}", 0);
+
+        for guard in [false, true] {
+            let path = std::env::temp_dir().join(format!("contract-reentrancy-{}-{}", std::process::id(), guard));
+            let executor = ContractExecutor::with_path(&path)
+                .with_call_config(CallConfig { reentrancy_guard: guard, ..CallConfig::default() });
+            let deployed = executor.deploy_contract("edu1qTestDeployer000000000000000", init.clone(), 0, 200000, LEGACY_GAS_PRICE).await.unwrap();
+            let address = deployed.contract_address.unwrap();
+            assert_eq!(executor.get_contract(address).await.unwrap().code, runtime);
+
+            let result = executor.call_contract("edu1qTestCaller0000000000000000", address, Vec::new(), 0, 200000, LEGACY_GAS_PRICE).await.unwrap();
+            assert!(result.success);
+            let inner = &result.call_tree.unwrap().calls[0];
+            assert_eq!(inner.success, !guard);
+
+            let stored = executor.get_contract(address).await.unwrap().storage.get(&slot0).cloned();
+            assert_eq!(stored.is_some(), !guard);
+            let _ = std::fs::remove_dir_all(&path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_state_commits_whole_or_not_at_all() {
+        use crate::block::BlockHeader;
+        use crate::transaction::{Transaction, TransactionInput};
+
+        let path = std::env::temp_dir().join(format!("contract-blocks-{}", std::process::id()));
+        let executor = ContractExecutor::with_path(&path);
+        // P2PKH script_sig: a placeholder signature and a compressed key
+        let mut script_sig = vec![71];
+        script_sig.extend_from_slice(&[0x30; 71]);
+        script_sig.push(33);
+        script_sig.extend_from_slice(&[0x02; 33]);
+        let input = || vec![TransactionInput::new([1; 32], 0, script_sig.clone())];
+        let block = |height: u32, prev: Hash256, transactions| {
+            Block::new(BlockHeader::new(1, prev, [0; 32], 0x207fffff, height), transactions)
+        };
+
+        let deploy = |nonce| {
+            Transaction::new_contract_deployment(1, input(), Vec::new(), vec![0x60, 0x00, 0x60, 0x00, 0xf3], 100000)
+                .with_nonce(nonce)
+        };
+        let first = block(1, [0; 32], vec![deploy(0)]);
+        let overlay = executor.execute_block(1, &first).await.unwrap();
+        assert_eq!(overlay.changed().count(), 1);
+        assert!(executor.contracts.read().await.is_empty());
+        executor.commit_block(overlay).await.unwrap();
+        assert_eq!(executor.contracts.read().await.len(), 1);
+        assert_eq!(executor.applied_tip().await, Some((1, first.get_hash())));
+        let sender = contract_state::transaction_sender(&deploy(0)).unwrap();
+        assert_eq!(executor.get_nonce(&sender).await, 1);
+
+        // A later call to a missing contract fails the block, deployment included
+        let call = Transaction::new_contract_call(1, input(), Vec::new(), [0xee; 20], Vec::new(), 100000).with_nonce(2);
+        let second = block(2, first.get_hash(), vec![deploy(1), call]);
+        assert!(executor.execute_block(2, &second).await.is_err());
+        assert_eq!(executor.contracts.read().await.len(), 1);
+
+        // So does replaying a used nonce
+        let replay = block(2, first.get_hash(), vec![deploy(0)]);
+        assert!(executor.execute_block(2, &replay).await.is_err());
+        assert_eq!(executor.get_nonce(&sender).await, 1);
+
+        // A block that does not extend the tip is refused
+        assert!(executor.commit_block(StateOverlay::new(3, [3; 32], [2; 32])).await.is_err());
+
+        executor.disconnect_block().await.unwrap();
+        assert!(executor.contracts.read().await.is_empty());
+        assert_eq!(executor.get_nonce(&sender).await, 0);
+        assert_eq!(executor.applied_tip().await, Some((0, [0; 32])));
+        assert!(executor.disconnect_block().await.is_err());
+
+        // Nonces used by local calls leave block validation alone
+        assert_eq!(executor.next_local_nonce(&sender, None).await.unwrap(), 0);
+        executor.use_local_nonce(&sender, 0).await;
+        assert!(executor.next_local_nonce(&sender, Some(0)).await.is_err());
+        assert_eq!(executor.next_local_nonce(&sender, None).await.unwrap(), 1);
+        assert_eq!(executor.get_nonce(&sender).await, 0);
+        executor.execute_block(1, &first).await.unwrap();
+        let _ = std::fs::remove_dir_all(&path);
+    }
 }
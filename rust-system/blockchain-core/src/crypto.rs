@@ -40,6 +40,38 @@ pub fn sign_hash(hash: &Hash256, private_key: &PrivateKey) -> Result<Signature>
     Ok(signature.serialize_der().to_vec())
 }
 
+/// A private key parsed once, with its public key, for signing many hashes.
+/// `sign_hash` sets up a signing context and parses the key on every call,
+/// which dominates when one key signs hundreds of inputs.
+pub struct SigningKey {
+    secret_key: SecretKey,
+    public_key: PublicKey,
+}
+
+impl SigningKey {
+    pub fn new(private_key: &PrivateKey) -> Result<Self> {
+        let secret_key = SecretKey::from_slice(private_key)
+            .map_err(|e| BlockchainError::CryptoError(format!("Invalid private key: {}", e)))?;
+        let public_key = Secp256k1PublicKey::from_secret_key(secp256k1::SECP256K1, &secret_key)
+            .serialize()
+            .to_vec();
+        Ok(Self { secret_key, public_key })
+    }
+
+    /// Compressed public key (33 bytes)
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// DER-encoded ECDSA signature of `hash`, the same as `sign_hash` gives
+    pub fn sign_hash(&self, hash: &Hash256) -> Result<Signature> {
+        let message = Message::from_digest_slice(hash)
+            .map_err(|e| BlockchainError::CryptoError(format!("Invalid message hash: {}", e)))?;
+        let signature = secp256k1::SECP256K1.sign_ecdsa(&message, &self.secret_key);
+        Ok(signature.serialize_der().to_vec())
+    }
+}
+
 /// Verify an ECDSA signature
 pub fn verify_signature(
     signature: &[u8],
@@ -64,6 +96,30 @@ pub fn verify_signature(
     Ok(secp.verify_ecdsa(&message, &sig, &pubkey).is_ok())
 }
 
+/// Re-encode a DER signature from an external signer with a low S value,
+/// which `verify_signature` requires
+pub fn normalize_signature(der: &[u8]) -> Result<Signature> {
+    let mut sig = secp256k1::ecdsa::Signature::from_der(der)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid signature: {}", e)))?;
+    sig.normalize_s();
+    Ok(sig.serialize_der().to_vec())
+}
+
+/// DER-encode a 64-byte `r || s` signature (as returned by PKCS#11), low S
+pub fn signature_from_compact(compact: &[u8]) -> Result<Signature> {
+    let mut sig = secp256k1::ecdsa::Signature::from_compact(compact)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid signature: {}", e)))?;
+    sig.normalize_s();
+    Ok(sig.serialize_der().to_vec())
+}
+
+/// Compressed form of a compressed or uncompressed public key
+pub fn compress_public_key(public_key: &[u8]) -> Result<PublicKey> {
+    let pubkey = Secp256k1PublicKey::from_slice(public_key)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid public key: {}", e)))?;
+    Ok(pubkey.serialize().to_vec())
+}
+
 /// Hash data with SHA256
 pub fn sha256(data: &[u8]) -> Hash256 {
     let mut hasher = Sha256::new();
@@ -87,10 +143,42 @@ pub fn generate_private_key() -> Result<PrivateKey> {
     Ok(secret_key.secret_bytes())
 }
 
+/// Check that the native libsecp256k1 this binary links against agrees
+/// with the pure Rust implementation on key derivation and that its
+/// signatures round-trip, so a mismatched build fails before it signs or
+/// rejects anything on the network
+pub fn self_test() -> Result<()> {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+    let private_key: PrivateKey = sha256(b"edunet secp256k1 self-test");
+    let native = derive_public_key(&private_key)?;
+    let reference = k256::SecretKey::from_slice(&private_key)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid private key: {}", e)))?
+        .public_key()
+        .to_encoded_point(true);
+    if native.as_slice() != reference.as_bytes() {
+        return Err(BlockchainError::CryptoError(
+            "libsecp256k1 derived a different public key than the reference implementation".to_string(),
+        ));
+    }
+
+    let hash = double_sha256(b"edunet secp256k1 self-test message");
+    let signature = sign_hash(&hash, &private_key)?;
+    if !verify_signature(&signature, &native, &hash)? {
+        return Err(BlockchainError::CryptoError("libsecp256k1 rejected its own signature".to_string()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_self_test_passes() {
+        self_test().unwrap();
+    }
+
     #[test]
     fn test_key_derivation() {
         let private_key = generate_private_key().unwrap();
@@ -114,6 +202,21 @@ mod tests {
         assert!(valid, "Signature should be valid");
     }
 
+    #[test]
+    fn test_signing_key_matches_one_shot_signing() {
+        let private_key = generate_private_key().unwrap();
+        let signer = SigningKey::new(&private_key).unwrap();
+        assert_eq!(signer.public_key(), derive_public_key(&private_key).unwrap().as_slice());
+
+        let hash = sha256(b"consolidation input");
+        let signature = signer.sign_hash(&hash).unwrap();
+        // RFC 6979 nonces make ECDSA deterministic
+        assert_eq!(signature, sign_hash(&hash, &private_key).unwrap());
+        assert!(verify_signature(&signature, signer.public_key(), &hash).unwrap());
+
+        assert!(SigningKey::new(&[0u8; 32]).is_err());
+    }
+
     #[test]
     fn test_invalid_signature() {
         let private_key1 = generate_private_key().unwrap();
@@ -132,6 +235,38 @@ mod tests {
         assert!(!valid, "Signature should be invalid");
     }
 
+    #[test]
+    fn test_external_signature_formats() {
+        let private_key = generate_private_key().unwrap();
+        let secret_key = SecretKey::from_slice(&private_key).unwrap();
+        let secp = Secp256k1::new();
+        let uncompressed = Secp256k1PublicKey::from_secret_key(&secp, &secret_key).serialize_uncompressed();
+        let public_key = compress_public_key(&uncompressed).unwrap();
+        assert_eq!(public_key, derive_public_key(&private_key).unwrap());
+
+        let hash = sha256(b"signed elsewhere");
+        let message = Message::from_digest_slice(&hash).unwrap();
+        let mut sig = secp.sign_ecdsa(&message, &secret_key);
+        sig.normalize_s();
+        let compact = sig.serialize_compact();
+
+        // Flip S to its high form, which verification rejects until normalized
+        let order = hex::decode("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141").unwrap();
+        let mut high = compact;
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let diff = order[i] as i16 - compact[32 + i] as i16 - borrow;
+            high[32 + i] = diff.rem_euclid(256) as u8;
+            borrow = (diff < 0) as i16;
+        }
+        let high_der = secp256k1::ecdsa::Signature::from_compact(&high).unwrap().serialize_der().to_vec();
+        assert!(!verify_signature(&high_der, &public_key, &hash).unwrap());
+
+        let normalized = normalize_signature(&high_der).unwrap();
+        assert!(verify_signature(&normalized, &public_key, &hash).unwrap());
+        assert_eq!(signature_from_compact(&high).unwrap(), normalized);
+    }
+
     #[test]
     fn test_double_sha256() {
         let data = b"test data";
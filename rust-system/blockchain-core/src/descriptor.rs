@@ -0,0 +1,497 @@
+//! Output descriptors
+//!
+//! A descriptor names the outputs a wallet owns by their script template and
+//! the keys that fill it in, instead of by a list of addresses:
+//!
+//! - `pkh(KEY)` pays to the hash of a single key
+//! - `sh(multi(k,KEY,...))` pays to a k-of-n multisig redeem script
+//! - `wsh(multi(k,KEY,...))` is written the way other wallets export
+//!   multisig; addresses have no witness form, so it pays to the same
+//!   script hash address as `sh` and keeps its form when exported again
+//!
+//! `sortedmulti` in place of `multi` orders the keys by value at every index,
+//! so cosigners need not agree on an order. A KEY is a hex compressed public
+//! key or an extended key followed by a derivation path. A path ending in
+//! `*`, as in `pkh(xpub.../0/*)`, makes the descriptor ranged: it describes
+//! one output per child index, and a wallet scans them with a gap limit.
+
+use crate::address::{AddressCodec, AddressKind};
+use crate::hd_wallet::{create_multisig_redeem_script, derive_p2pkh_address, derive_p2sh_address, DerivationPath, ExtendedKey};
+use crate::script_utils::ScriptBuilder;
+use crate::utxo::UTXOSet;
+use crate::{BlockchainError, Result};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// First hardened child index
+const HARDENED: u32 = 0x80000000;
+
+/// Keys a multisig template can hold
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+fn invalid(reason: impl Into<String>) -> BlockchainError {
+    BlockchainError::InvalidDescriptor(reason.into())
+}
+
+/// A key inside a descriptor
+#[derive(Debug, Clone)]
+pub enum DescriptorKey {
+    /// A fixed compressed public key
+    Single([u8; 33]),
+    /// An extended key and the path below it, ranged when it ends in `*`
+    Extended {
+        key: ExtendedKey,
+        path: Vec<u32>,
+        wildcard: bool,
+    },
+}
+
+impl DescriptorKey {
+    fn parse(text: &str) -> Result<Self> {
+        let mut steps = text.trim().split('/');
+        let key = steps.next().unwrap_or_default();
+        if key.starts_with('[') {
+            return Err(invalid("key origin information is not supported"));
+        }
+
+        if key.len() == 66 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+            if steps.next().is_some() {
+                return Err(invalid(format!("public key {} cannot have a derivation path", key)));
+            }
+            let bytes = hex::decode(key).map_err(|e| invalid(e.to_string()))?;
+            if bytes[0] != 0x02 && bytes[0] != 0x03 {
+                return Err(invalid(format!("{} is not a compressed public key", key)));
+            }
+            let mut public_key = [0u8; 33];
+            public_key.copy_from_slice(&bytes);
+            return Ok(DescriptorKey::Single(public_key));
+        }
+
+        let key = ExtendedKey::parse(key).map_err(|e| invalid(format!("key '{}': {}", key, e)))?;
+        let mut path = Vec::new();
+        let mut wildcard = false;
+        for step in steps {
+            if wildcard {
+                return Err(invalid("'*' must be the last step of a derivation path"));
+            }
+            if step == "*" {
+                wildcard = true;
+                continue;
+            }
+            if step == "*'" || step == "*h" {
+                return Err(invalid("hardened wildcards are not supported"));
+            }
+            let index = DerivationPath::parse_step(step)
+                .map_err(|_| invalid(format!("invalid derivation step '{}'", step)))?;
+            if index >= HARDENED && !key.is_private {
+                return Err(invalid(format!("hardened step '{}' needs a private extended key", step)));
+            }
+            path.push(index);
+        }
+
+        Ok(DescriptorKey::Extended { key, path, wildcard })
+    }
+
+    /// Whether the key changes with the child index
+    pub fn is_ranged(&self) -> bool {
+        matches!(self, DescriptorKey::Extended { wildcard: true, .. })
+    }
+
+    /// Whether the key carries private key material
+    pub fn has_private_key(&self) -> bool {
+        matches!(self, DescriptorKey::Extended { key, .. } if key.is_private)
+    }
+
+    /// Compressed public key at child `index`, which only ranged keys use
+    pub fn derive(&self, index: u32) -> Result<[u8; 33]> {
+        match self {
+            DescriptorKey::Single(public_key) => Ok(*public_key),
+            DescriptorKey::Extended { key, path, wildcard } => {
+                let mut child = key.clone();
+                for step in path {
+                    child = child.derive_child(*step)?;
+                }
+                if *wildcard {
+                    if index >= HARDENED {
+                        return Err(invalid(format!("child index {} is hardened", index)));
+                    }
+                    child = child.derive_child(index)?;
+                }
+                child.public_key_bytes()
+            }
+        }
+    }
+
+    /// The same key without private key material. Hardened steps are
+    /// derived first, so the result describes the same public keys.
+    pub fn to_public(&self) -> Result<Self> {
+        match self {
+            DescriptorKey::Extended { key, path, wildcard } if key.is_private => {
+                let split = path.iter().rposition(|step| *step >= HARDENED).map_or(0, |last| last + 1);
+                let mut base = key.clone();
+                for step in &path[..split] {
+                    base = base.derive_child(*step)?;
+                }
+                Ok(DescriptorKey::Extended {
+                    key: base.public_key()?,
+                    path: path[split..].to_vec(),
+                    wildcard: *wildcard,
+                })
+            }
+            other => Ok(other.clone()),
+        }
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorKey::Single(public_key) => write!(f, "{}", hex::encode(public_key)),
+            DescriptorKey::Extended { key, path, wildcard } => {
+                write!(f, "{}", key.serialize().map_err(|_| fmt::Error)?)?;
+                for step in path {
+                    if *step >= HARDENED {
+                        write!(f, "/{}'", step - HARDENED)?;
+                    } else {
+                        write!(f, "/{}", step)?;
+                    }
+                }
+                if *wildcard {
+                    write!(f, "/*")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A `multi` or `sortedmulti` template
+#[derive(Debug, Clone)]
+pub struct MultisigDescriptor {
+    /// Signatures required to spend
+    pub threshold: u32,
+    pub keys: Vec<DescriptorKey>,
+    /// Order the keys by value at every index
+    pub sorted: bool,
+}
+
+impl MultisigDescriptor {
+    fn parse(text: &str) -> Result<Self> {
+        let (inner, sorted) = match call(text, "sortedmulti") {
+            Some(inner) => (inner, true),
+            None => (
+                call(text, "multi").ok_or_else(|| invalid(format!("expected multi(...) or sortedmulti(...), found '{}'", text)))?,
+                false,
+            ),
+        };
+
+        let mut args = inner.split(',');
+        let threshold: u32 = args.next().unwrap_or_default().trim().parse()
+            .map_err(|_| invalid("multisig threshold must be a number"))?;
+        let keys = args.map(DescriptorKey::parse).collect::<Result<Vec<_>>>()?;
+        if keys.is_empty() || keys.len() > MAX_MULTISIG_KEYS {
+            return Err(invalid(format!("multisig needs 1 to {} keys, found {}", MAX_MULTISIG_KEYS, keys.len())));
+        }
+        if threshold == 0 || threshold as usize > keys.len() {
+            return Err(invalid(format!("threshold {} is not between 1 and {}", threshold, keys.len())));
+        }
+
+        Ok(Self { threshold, keys, sorted })
+    }
+
+    /// Redeem script at child `index`
+    pub fn redeem_script(&self, index: u32) -> Result<Vec<u8>> {
+        let mut public_keys = self.keys.iter().map(|key| key.derive(index)).collect::<Result<Vec<_>>>()?;
+        if self.sorted {
+            public_keys.sort();
+        }
+        create_multisig_redeem_script(self.threshold, &public_keys)
+    }
+
+    fn to_public(&self) -> Result<Self> {
+        Ok(Self {
+            threshold: self.threshold,
+            keys: self.keys.iter().map(DescriptorKey::to_public).collect::<Result<_>>()?,
+            sorted: self.sorted,
+        })
+    }
+}
+
+impl fmt::Display for MultisigDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({}", if self.sorted { "sortedmulti" } else { "multi" }, self.threshold)?;
+        for key in &self.keys {
+            write!(f, ",{}", key)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// An output script template
+#[derive(Debug, Clone)]
+pub enum Descriptor {
+    Pkh(DescriptorKey),
+    Sh(MultisigDescriptor),
+    Wsh(MultisigDescriptor),
+}
+
+/// One output a descriptor describes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DerivedOutput {
+    /// Child index, 0 for a descriptor that isn't ranged
+    pub index: u32,
+    pub address: String,
+    pub script_pubkey: Vec<u8>,
+    /// Script the output's hash commits to, for script hash outputs
+    pub redeem_script: Option<Vec<u8>>,
+}
+
+/// Outputs of a descriptor found holding coins
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DescriptorScan {
+    pub funded: Vec<DerivedOutput>,
+    /// Value of the coins found, in satoshis
+    pub balance: u64,
+    /// First index after the last funded output
+    pub next_index: u32,
+}
+
+impl Descriptor {
+    /// Parse a descriptor string
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if let Some(inner) = call(text, "pkh") {
+            return Ok(Descriptor::Pkh(DescriptorKey::parse(inner)?));
+        }
+        if let Some(inner) = call(text, "sh") {
+            return Ok(Descriptor::Sh(MultisigDescriptor::parse(inner)?));
+        }
+        if let Some(inner) = call(text, "wsh") {
+            return Ok(Descriptor::Wsh(MultisigDescriptor::parse(inner)?));
+        }
+        Err(invalid(format!(
+            "unsupported descriptor '{}', expected pkh(...), sh(multi(...)) or wsh(multi(...))",
+            text.chars().take(16).collect::<String>()
+        )))
+    }
+
+    fn keys(&self) -> &[DescriptorKey] {
+        match self {
+            Descriptor::Pkh(key) => std::slice::from_ref(key),
+            Descriptor::Sh(multi) | Descriptor::Wsh(multi) => &multi.keys,
+        }
+    }
+
+    /// Whether the descriptor describes one output per child index
+    pub fn is_ranged(&self) -> bool {
+        self.keys().iter().any(DescriptorKey::is_ranged)
+    }
+
+    /// Whether any key carries private key material
+    pub fn has_private_keys(&self) -> bool {
+        self.keys().iter().any(DescriptorKey::has_private_key)
+    }
+
+    /// The same descriptor with every key made public, safe to share with
+    /// a watch-only wallet
+    pub fn to_public(&self) -> Result<Self> {
+        Ok(match self {
+            Descriptor::Pkh(key) => Descriptor::Pkh(key.to_public()?),
+            Descriptor::Sh(multi) => Descriptor::Sh(multi.to_public()?),
+            Descriptor::Wsh(multi) => Descriptor::Wsh(multi.to_public()?),
+        })
+    }
+
+    /// Output at child `index`
+    pub fn derive(&self, index: u32) -> Result<DerivedOutput> {
+        let index = if self.is_ranged() { index } else { 0 };
+        match self {
+            Descriptor::Pkh(key) => {
+                let address = derive_p2pkh_address(&key.derive(index)?)?;
+                let hash = AddressCodec::decode_kind(&address, AddressKind::P2pkh)?;
+                Ok(DerivedOutput {
+                    index,
+                    address,
+                    script_pubkey: ScriptBuilder::create_p2pkh_script(&hash),
+                    redeem_script: None,
+                })
+            }
+            Descriptor::Sh(multi) | Descriptor::Wsh(multi) => {
+                let redeem_script = multi.redeem_script(index)?;
+                let address = derive_p2sh_address(&redeem_script)?;
+                let hash = AddressCodec::decode_kind(&address, AddressKind::P2sh)?;
+                Ok(DerivedOutput {
+                    index,
+                    address,
+                    script_pubkey: ScriptBuilder::create_p2sh_script(&hash),
+                    redeem_script: Some(redeem_script),
+                })
+            }
+        }
+    }
+
+    /// Outputs at child indexes `start..end`
+    pub fn derive_range(&self, start: u32, end: u32) -> Result<Vec<DerivedOutput>> {
+        if !self.is_ranged() {
+            return Ok(vec![self.derive(0)?]);
+        }
+        (start..end).map(|index| self.derive(index)).collect()
+    }
+
+    /// Find the outputs holding coins, deriving until `gap_limit` indexes in
+    /// a row hold none
+    pub fn scan(&self, utxo_set: &UTXOSet, gap_limit: u32) -> Result<DescriptorScan> {
+        let mut scan = DescriptorScan::default();
+        let mut gap = 0;
+        let mut index = 0;
+        while gap < gap_limit.max(1) {
+            let output = self.derive(index)?;
+            let utxos = utxo_set.get_utxos_for_address(&output.address);
+            if utxos.is_empty() {
+                gap += 1;
+            } else {
+                gap = 0;
+                scan.balance += utxos.iter().map(|utxo| utxo.value()).sum::<u64>();
+                scan.next_index = index + 1;
+                scan.funded.push(output);
+            }
+            if !self.is_ranged() {
+                break;
+            }
+            index += 1;
+        }
+        Ok(scan)
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = BlockchainError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse(text)
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Descriptor::Pkh(key) => write!(f, "pkh({})", key),
+            Descriptor::Sh(multi) => write!(f, "sh({})", multi),
+            Descriptor::Wsh(multi) => write!(f, "wsh({})", multi),
+        }
+    }
+}
+
+/// Arguments of `name(...)`, if `text` is a call to `name`
+fn call<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    text.trim().strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hd_wallet::{HDAccount, HDWallet};
+    use crate::transaction::TransactionOutput;
+    use crate::utxo::UTXO;
+
+    fn account_xpub(seed: u8) -> (HDAccount, String) {
+        let wallet = HDWallet::new("Descriptor".to_string(), Some([seed; 32])).unwrap();
+        let account = HDAccount::new(0, "Main".to_string(), &wallet.master_xpriv).unwrap();
+        let xpub = account.account_xpub.serialize().unwrap();
+        (account, xpub)
+    }
+
+    /// Key `HDAccount::new` derives accounts from, as hardened children
+    fn account_parent(master: &ExtendedKey) -> ExtendedKey {
+        master.derive_child(0x80000044).unwrap().derive_child(HARDENED).unwrap()
+    }
+
+    #[test]
+    fn test_pkh_descriptor_matches_account_addresses() {
+        let (mut account, xpub) = account_xpub(1);
+        let descriptor = Descriptor::parse(&format!("pkh({}/0/*)", xpub)).unwrap();
+        assert!(descriptor.is_ranged());
+        assert!(!descriptor.has_private_keys());
+
+        for index in 0..3 {
+            let output = descriptor.derive(index).unwrap();
+            assert_eq!(output.address, account.derive_address(index).unwrap().address);
+            assert_eq!(output.script_pubkey.len(), 25);
+            assert!(output.redeem_script.is_none());
+        }
+        assert_eq!(descriptor.to_string(), format!("pkh({}/0/*)", xpub));
+    }
+
+    #[test]
+    fn test_multisig_descriptors() {
+        let (_, xpub_a) = account_xpub(1);
+        let (_, xpub_b) = account_xpub(2);
+        let (_, xpub_c) = account_xpub(3);
+        let text = format!("wsh(multi(2,{}/0/*,{}/0/*,{}/0/*))", xpub_a, xpub_b, xpub_c);
+        let wsh = Descriptor::parse(&text).unwrap();
+        let sh = Descriptor::parse(&text[1..]).unwrap();
+
+        let output = wsh.derive(5).unwrap();
+        assert!(output.address.starts_with("edu3"));
+        assert_eq!(output, sh.derive(5).unwrap());
+        assert_ne!(output.address, wsh.derive(6).unwrap().address);
+        assert_eq!(wsh.to_string(), text);
+
+        // sortedmulti doesn't depend on the order the keys are listed in
+        let sorted_ab = Descriptor::parse(&format!("sh(sortedmulti(1,{}/0/*,{}/0/*))", xpub_a, xpub_b)).unwrap();
+        let sorted_ba = Descriptor::parse(&format!("sh(sortedmulti(1,{}/0/*,{}/0/*))", xpub_b, xpub_a)).unwrap();
+        assert_eq!(sorted_ab.derive(0).unwrap(), sorted_ba.derive(0).unwrap());
+
+        assert!(Descriptor::parse(&format!("sh(multi(3,{}/0/*,{}/0/*))", xpub_a, xpub_b)).is_err());
+        assert!(Descriptor::parse(&format!("sh(multi(0,{}/0/*))", xpub_a)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_descriptors() {
+        let (_, xpub) = account_xpub(1);
+        assert!(Descriptor::parse(&format!("tr({}/0/*)", xpub)).is_err());
+        assert!(Descriptor::parse(&format!("pkh({}/0'/*)", xpub)).is_err());
+        assert!(Descriptor::parse(&format!("pkh({}/*/0)", xpub)).is_err());
+        assert!(Descriptor::parse("pkh(02abcd)").is_err());
+        assert!(Descriptor::parse(&format!("pkh({}", xpub)).is_err());
+    }
+
+    #[test]
+    fn test_private_descriptor_exports_public_form() {
+        let wallet = HDWallet::new("Descriptor".to_string(), Some([9u8; 32])).unwrap();
+        let mut account = HDAccount::new(0, "Main".to_string(), &wallet.master_xpriv).unwrap();
+        // The account key sits one hardened step below this one
+        let coin_type = account_parent(&wallet.master_xpriv);
+        let xprv = coin_type.serialize().unwrap();
+        let descriptor = Descriptor::parse(&format!("pkh({}/0'/0/*)", xprv)).unwrap();
+        assert!(descriptor.has_private_keys());
+
+        let public = descriptor.to_public().unwrap();
+        assert!(!public.has_private_keys());
+        assert_eq!(public.to_string(), format!("pkh({}/0/*)", account.account_xpub.serialize().unwrap()));
+        assert_eq!(public.derive(4).unwrap(), descriptor.derive(4).unwrap());
+        assert_eq!(public.derive(4).unwrap().address, account.derive_address(4).unwrap().address);
+    }
+
+    #[test]
+    fn test_scan_stops_at_gap_limit() {
+        let (_, xpub) = account_xpub(4);
+        let descriptor = Descriptor::parse(&format!("pkh({}/0/*)", xpub)).unwrap();
+        let mut utxo_set = UTXOSet::new();
+        for (seed, index) in [(1u8, 1u32), (2, 4), (3, 30)] {
+            // Outputs carry their address the way `TransactionOutput::get_address` reads it
+            let address = descriptor.derive(index).unwrap().address;
+            let mut script = vec![0x76, 0xa9, address.len() as u8];
+            script.extend_from_slice(address.as_bytes());
+            script.extend_from_slice(&[0x88, 0xac]);
+            let utxo = UTXO::new([seed; 32], 0, TransactionOutput::new(1_000, script), 1, false);
+            utxo_set.add_utxo([seed; 32], 0, utxo).unwrap();
+        }
+
+        let scan = descriptor.scan(&utxo_set, 5).unwrap();
+        assert_eq!(scan.funded.iter().map(|output| output.index).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(scan.balance, 2_000);
+        assert_eq!(scan.next_index, 5);
+    }
+}
@@ -30,6 +30,9 @@ pub struct IndexedEvent {
     pub tx_hash: String,
     /// Index of log within transaction
     pub log_index: u32,
+    /// Implementation whose ABI decodes the log, when a proxy emitted it
+    #[serde(default)]
+    pub implementation: Option<EthAddress>,
 }
 
 /// Event indexer for contract events
@@ -40,6 +43,8 @@ pub struct EventIndexer {
     events_by_address: Arc<RwLock<HashMap<EthAddress, Vec<IndexedEvent>>>>,
     /// Events indexed by topic[0] (event signature)
     events_by_topic0: Arc<RwLock<HashMap<String, Vec<IndexedEvent>>>>,
+    /// Current implementation of each proxy contract
+    implementations: Arc<RwLock<HashMap<EthAddress, EthAddress>>>,
 }
 
 impl EventIndexer {
@@ -49,9 +54,19 @@ impl EventIndexer {
             events_by_block: Arc::new(RwLock::new(HashMap::new())),
             events_by_address: Arc::new(RwLock::new(HashMap::new())),
             events_by_topic0: Arc::new(RwLock::new(HashMap::new())),
+            implementations: Arc::new(RwLock::new(HashMap::new())),
         }
     }
     
+    /// Record the implementation behind a proxy, or None once it is not one
+    pub async fn set_implementation(&self, proxy: EthAddress, implementation: Option<EthAddress>) {
+        let mut implementations = self.implementations.write().await;
+        match implementation {
+            Some(implementation) => implementations.insert(proxy, implementation),
+            None => implementations.remove(&proxy),
+        };
+    }
+    
     /// Index events from a transaction
     pub async fn index_events(
         &self,
@@ -62,9 +77,11 @@ impl EventIndexer {
         let mut by_block = self.events_by_block.write().await;
         let mut by_address = self.events_by_address.write().await;
         let mut by_topic0 = self.events_by_topic0.write().await;
+        let implementations = self.implementations.read().await;
         
         for (log_index, log) in logs.into_iter().enumerate() {
             let event = IndexedEvent {
+                implementation: implementations.get(&log.address).copied(),
                 log: log.clone(),
                 block_height,
                 tx_hash: tx_hash.clone(),
@@ -93,6 +110,21 @@ impl EventIndexer {
         }
     }
     
+    /// Drop every event of a block, when the block is disconnected
+    pub async fn remove_block(&self, block_height: u64) {
+        let Some(removed) = self.events_by_block.write().await.remove(&block_height) else { return };
+        if removed.is_empty() {
+            return;
+        }
+        let in_block = |event: &IndexedEvent| event.block_height == block_height;
+        for events in self.events_by_address.write().await.values_mut() {
+            events.retain(|event| !in_block(event));
+        }
+        for events in self.events_by_topic0.write().await.values_mut() {
+            events.retain(|event| !in_block(event));
+        }
+    }
+    
     /// Query events with filter
     pub async fn query_events(&self, filter: EventFilter) -> Vec<IndexedEvent> {
         let by_block = self.events_by_block.read().await;
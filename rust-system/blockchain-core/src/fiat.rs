@@ -0,0 +1,253 @@
+//! Fiat display layer
+//!
+//! Converts EDU amounts into fiat equivalents for display. Rates come from the
+//! treasury's own sale price (`treasury_getPrice` on a node, quoted in USD) or
+//! from an external price oracle, and are cached with the time they were
+//! fetched. A rate older than the configured maximum age is treated as missing
+//! so stale prices are never shown next to balances.
+
+use crate::{BlockchainError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Satoshis per EDU
+const SATOSHIS_PER_EDU: u128 = 100_000_000;
+
+/// Currency the treasury quotes its sale price in
+pub const TREASURY_CURRENCY: &str = "USD";
+
+/// Where an exchange rate came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateSource {
+    /// The treasury's EDU sale price
+    Treasury,
+    /// An external price oracle
+    Oracle,
+}
+
+/// Price of one EDU in a fiat currency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExchangeRate {
+    /// ISO 4217 currency code
+    pub currency: String,
+    /// Price of one EDU in the currency's minor unit (cents)
+    pub cents_per_edu: u64,
+    pub source: RateSource,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Fiat equivalent of an EDU amount
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FiatAmount {
+    pub currency: String,
+    /// Amount in the currency's minor unit (cents), rounded to nearest
+    pub amount_cents: u64,
+    /// Human readable form, e.g. "12.34 USD"
+    pub display: String,
+    /// Rate the amount was computed with
+    pub cents_per_edu: u64,
+    pub source: RateSource,
+    pub rate_fetched_at: DateTime<Utc>,
+}
+
+/// Price feed settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FiatConfig {
+    /// Currency amounts are displayed in
+    pub currency: String,
+    /// JSON-RPC endpoint of a node serving `treasury_getPrice`
+    pub treasury_rpc_url: Option<String>,
+    /// Oracle endpoint; queried as `GET {url}?currency=XXX`, answering `{"price": <fiat per EDU>}`
+    pub oracle_url: Option<String>,
+    /// Cached rates older than this are not used
+    pub max_rate_age_secs: u64,
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+}
+
+impl Default for FiatConfig {
+    fn default() -> Self {
+        Self {
+            currency: TREASURY_CURRENCY.to_string(),
+            treasury_rpc_url: None,
+            oracle_url: None,
+            max_rate_age_secs: 600,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Response body of the price oracle
+#[derive(Debug, Deserialize)]
+struct OraclePrice {
+    price: f64,
+}
+
+/// Converts EDU amounts to fiat using cached exchange rates
+pub struct FiatConverter {
+    config: FiatConfig,
+    http: reqwest::Client,
+    rates: RwLock<HashMap<String, ExchangeRate>>,
+}
+
+impl FiatConverter {
+    /// Create a converter with an empty rate cache
+    pub fn new(config: FiatConfig) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| BlockchainError::PriceFeedError(format!("Failed to build HTTP client: {}", e)))?;
+        Ok(Self { config, http, rates: RwLock::new(HashMap::new()) })
+    }
+
+    /// Converter configuration
+    pub fn config(&self) -> &FiatConfig {
+        &self.config
+    }
+
+    /// Cache a rate, replacing any earlier one for its currency
+    pub fn insert_rate(&self, rate: ExchangeRate) {
+        self.rates.write().unwrap().insert(rate.currency.clone(), rate);
+    }
+
+    /// Cache a rate fetched now
+    pub fn set_rate(&self, currency: &str, cents_per_edu: u64, source: RateSource) -> ExchangeRate {
+        let rate = ExchangeRate {
+            currency: currency.to_uppercase(),
+            cents_per_edu,
+            source,
+            fetched_at: Utc::now(),
+        };
+        self.insert_rate(rate.clone());
+        rate
+    }
+
+    /// Cached rate for a currency, if one exists and is not stale
+    pub fn rate(&self, currency: &str) -> Option<ExchangeRate> {
+        let rate = self.rates.read().unwrap().get(&currency.to_uppercase()).cloned()?;
+        let age = Utc::now().signed_duration_since(rate.fetched_at);
+        (age.num_seconds() <= self.config.max_rate_age_secs as i64).then_some(rate)
+    }
+
+    /// Fiat equivalent of an amount in satoshis, in the display currency
+    pub fn convert(&self, satoshis: u64) -> Option<FiatAmount> {
+        self.convert_to(satoshis, &self.config.currency)
+    }
+
+    /// Fiat equivalent of an amount in satoshis, in the given currency
+    pub fn convert_to(&self, satoshis: u64, currency: &str) -> Option<FiatAmount> {
+        let rate = self.rate(currency)?;
+        let scaled = satoshis as u128 * rate.cents_per_edu as u128;
+        let amount_cents = ((scaled + SATOSHIS_PER_EDU / 2) / SATOSHIS_PER_EDU) as u64;
+        Some(FiatAmount {
+            display: format!("{}.{:02} {}", amount_cents / 100, amount_cents % 100, rate.currency),
+            currency: rate.currency,
+            amount_cents,
+            cents_per_edu: rate.cents_per_edu,
+            source: rate.source,
+            rate_fetched_at: rate.fetched_at,
+        })
+    }
+
+    /// Fetch a fresh rate for the display currency and cache it.
+    ///
+    /// The treasury price is preferred for USD since it is what EDU actually
+    /// sells for; the oracle covers other currencies and treasury outages.
+    pub async fn refresh(&self) -> Result<ExchangeRate> {
+        let currency = self.config.currency.to_uppercase();
+        let mut last_error = None;
+
+        if let Some(url) = self.config.treasury_rpc_url.as_deref().filter(|_| currency == TREASURY_CURRENCY) {
+            match self.fetch_treasury_price(url).await {
+                Ok(cents) => return Ok(self.set_rate(&currency, cents, RateSource::Treasury)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if let Some(url) = self.config.oracle_url.as_deref() {
+            match self.fetch_oracle_price(url, &currency).await {
+                Ok(cents) => return Ok(self.set_rate(&currency, cents, RateSource::Oracle)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            BlockchainError::PriceFeedError(format!("No price source configured for {}", currency))
+        }))
+    }
+
+    /// Treasury sale price in US cents per EDU
+    async fn fetch_treasury_price(&self, url: &str) -> Result<u64> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "treasury_getPrice",
+            "params": [],
+            "id": 1
+        });
+        let response: serde_json::Value = self.http.post(url).json(&request).send().await
+            .map_err(|e| BlockchainError::PriceFeedError(format!("Treasury request failed: {}", e)))?
+            .json().await
+            .map_err(|e| BlockchainError::PriceFeedError(format!("Invalid treasury response: {}", e)))?;
+
+        response.get("result")
+            .and_then(|r| r.get("price_cents"))
+            .and_then(|p| p.as_u64())
+            .ok_or_else(|| BlockchainError::PriceFeedError("Treasury response has no price_cents".to_string()))
+    }
+
+    /// Oracle price in minor units of `currency` per EDU
+    async fn fetch_oracle_price(&self, url: &str, currency: &str) -> Result<u64> {
+        let response = self.http.get(url).query(&[("currency", currency)]).send().await
+            .map_err(|e| BlockchainError::PriceFeedError(format!("Oracle request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(BlockchainError::PriceFeedError(format!("Oracle returned {}", response.status())));
+        }
+        let price: OraclePrice = response.json().await
+            .map_err(|e| BlockchainError::PriceFeedError(format!("Invalid oracle response: {}", e)))?;
+        if !price.price.is_finite() || price.price < 0.0 {
+            return Err(BlockchainError::PriceFeedError(format!("Oracle returned invalid price {}", price.price)));
+        }
+        Ok((price.price * 100.0).round() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_rounds_to_cents() {
+        let converter = FiatConverter::new(FiatConfig::default()).unwrap();
+        assert!(converter.convert(100_000_000).is_none());
+
+        converter.set_rate("usd", 10, RateSource::Treasury);
+        let fiat = converter.convert(1_234 * 100_000_000).unwrap();
+        assert_eq!(fiat.amount_cents, 12_340);
+        assert_eq!(fiat.display, "123.40 USD");
+        assert_eq!(fiat.source, RateSource::Treasury);
+
+        // 0.05 EDU at 10 cents is half a cent, which rounds up
+        assert_eq!(converter.convert(5_000_000).unwrap().amount_cents, 1);
+        assert_eq!(converter.convert(4_999_999).unwrap().amount_cents, 0);
+        assert!(converter.convert_to(100_000_000, "EUR").is_none());
+    }
+
+    #[test]
+    fn test_stale_rates_are_ignored() {
+        let converter = FiatConverter::new(FiatConfig { max_rate_age_secs: 60, ..Default::default() }).unwrap();
+        converter.insert_rate(ExchangeRate {
+            currency: "USD".to_string(),
+            cents_per_edu: 10,
+            source: RateSource::Oracle,
+            fetched_at: Utc::now() - chrono::Duration::seconds(120),
+        });
+        assert!(converter.rate("USD").is_none());
+        assert!(converter.convert(100_000_000).is_none());
+
+        converter.set_rate("USD", 12, RateSource::Oracle);
+        assert_eq!(converter.rate("usd").unwrap().cents_per_edu, 12);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     block::{Block, BlockHeader}, 
     transaction::{Transaction, TransactionInput, TransactionOutput}, 
+    amount::Amount,
     utxo::UTXOSet,
     BlockchainError, Result
 };
@@ -204,7 +205,7 @@ impl GenesisState {
 
     /// Convert supply to EDU tokens (divide by 1e8)
     pub fn get_total_supply_edu(&self) -> f64 {
-        self.get_total_supply() as f64 / 1e8
+        Amount::from_sat(self.get_total_supply()).to_edu_f64()
     }
 }
 
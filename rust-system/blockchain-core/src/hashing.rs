@@ -0,0 +1,283 @@
+//! Double-SHA256 with a runtime-selected backend
+//!
+//! Single messages go through the `sha2` crate, which already switches to
+//! the SHA-NI instructions when the CPU has them. Batches of equal-length
+//! messages, like one level of a merkle tree, can also be hashed eight at a
+//! time in AVX2 lanes, which is what helps on CPUs with AVX2 but no SHA-NI.
+//! The AVX2 path is built with the `simd` feature (on by default) and only
+//! taken when the CPU supports it. Every backend gives identical digests.
+
+use crate::Hash256;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+/// How double-SHA256 is computed on this machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// `sha2` on the SHA extensions, one message at a time
+    ShaNi,
+    /// Batches eight at a time in AVX2 lanes, single messages in software
+    Avx2,
+    /// `sha2` in software
+    Portable,
+}
+
+impl Backend {
+    /// Fastest backend this CPU and build support
+    pub fn detect() -> Self {
+        if Backend::ShaNi.is_supported() {
+            Backend::ShaNi
+        } else if Backend::Avx2.is_supported() {
+            Backend::Avx2
+        } else {
+            Backend::Portable
+        }
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::ShaNi => std::arch::is_x86_feature_detected!("sha")
+                && std::arch::is_x86_feature_detected!("sse4.1"),
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            Backend::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            Backend::Portable => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::ShaNi => "sha-ni",
+            Backend::Avx2 => "avx2",
+            Backend::Portable => "portable",
+        }
+    }
+}
+
+/// Backend in use, detected on first call
+pub fn backend() -> Backend {
+    static ACTIVE: OnceLock<Backend> = OnceLock::new();
+    *ACTIVE.get_or_init(Backend::detect)
+}
+
+pub fn double_sha256(data: &[u8]) -> Hash256 {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+/// Double-SHA256 of each message, in order
+pub fn double_sha256_many(messages: &[&[u8]]) -> Vec<Hash256> {
+    double_sha256_many_with(backend(), messages)
+}
+
+/// Double-SHA256 of each message on a given backend, falling back to
+/// hashing one at a time if the CPU doesn't support it
+pub fn double_sha256_many_with(backend: Backend, messages: &[&[u8]]) -> Vec<Hash256> {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if backend == Backend::Avx2 && backend.is_supported() {
+        return avx2::double_sha256_many(messages);
+    }
+    let _ = backend;
+    messages.iter().map(|message| double_sha256(message)).collect()
+}
+
+/// Check the active backend against a known digest and against software
+/// hashing on a full batch, before it hashes anything that matters
+pub fn self_test() -> crate::Result<()> {
+    let known = hex::encode(double_sha256(b""));
+    if known != "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456" {
+        return Err(crate::BlockchainError::CryptoError(format!("double-SHA256 of the empty message is {}", known)));
+    }
+    let owned: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 80]).collect();
+    let messages: Vec<&[u8]> = owned.iter().map(Vec::as_slice).collect();
+    if double_sha256_many(&messages) != double_sha256_many_with(Backend::Portable, &messages) {
+        return Err(crate::BlockchainError::CryptoError(format!(
+            "{} backend disagrees with software double-SHA256",
+            backend().name()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Eight SHA-256 computations side by side, one per 32-bit lane
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod avx2 {
+    use super::{double_sha256, Hash256, H0, K};
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 8;
+
+    macro_rules! rotr {
+        ($x:expr, $n:literal) => {
+            _mm256_or_si256(_mm256_srli_epi32($x, $n), _mm256_slli_epi32($x, 32 - $n))
+        };
+    }
+
+    /// Runs of eight equal-length messages go through the lanes; the rest
+    /// are hashed one at a time. The caller checks the CPU has AVX2.
+    pub fn double_sha256_many(messages: &[&[u8]]) -> Vec<Hash256> {
+        let mut digests = Vec::with_capacity(messages.len());
+        for chunk in messages.chunks(LANES) {
+            if chunk.len() == LANES && chunk.iter().all(|message| message.len() == chunk[0].len()) {
+                // SAFETY: only reached when AVX2 was detected at runtime
+                digests.extend(unsafe { double_sha256_x8(chunk) });
+            } else {
+                digests.extend(chunk.iter().map(|message| double_sha256(message)));
+            }
+        }
+        digests
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn double_sha256_x8(messages: &[&[u8]]) -> [Hash256; LANES] {
+        // Pad every message the same way, as they share a length
+        let len = messages[0].len();
+        let padded_len = (len + 9).div_ceil(64) * 64;
+        let mut padded = vec![0u8; padded_len * LANES];
+        for (lane, message) in messages.iter().enumerate() {
+            let buf = &mut padded[lane * padded_len..(lane + 1) * padded_len];
+            buf[..len].copy_from_slice(message);
+            buf[len] = 0x80;
+            buf[padded_len - 8..].copy_from_slice(&((len as u64) * 8).to_be_bytes());
+        }
+
+        let mut state = H0.map(|h| _mm256_set1_epi32(h as i32));
+        let mut w = [_mm256_setzero_si256(); 64];
+        for block in (0..padded_len).step_by(64) {
+            for (t, word) in w.iter_mut().take(16).enumerate() {
+                let at = |lane: usize| {
+                    let offset = lane * padded_len + block + t * 4;
+                    u32::from_be_bytes(padded[offset..offset + 4].try_into().expect("4 bytes")) as i32
+                };
+                *word = _mm256_set_epi32(at(7), at(6), at(5), at(4), at(3), at(2), at(1), at(0));
+            }
+            compress(&mut state, &mut w);
+        }
+
+        // The second pass hashes the 32-byte digests, already in words
+        w[..8].copy_from_slice(&state);
+        w[8] = _mm256_set1_epi32(0x8000_0000u32 as i32);
+        w[9..15].fill(_mm256_setzero_si256());
+        w[15] = _mm256_set1_epi32(256);
+        state = H0.map(|h| _mm256_set1_epi32(h as i32));
+        compress(&mut state, &mut w);
+
+        let mut digests = [[0u8; 32]; LANES];
+        for (i, word) in state.iter().enumerate() {
+            // SAFETY: __m256i and [u32; 8] have the same size and any bit pattern is valid
+            let lanes: [u32; LANES] = unsafe { std::mem::transmute(*word) };
+            for (digest, value) in digests.iter_mut().zip(lanes) {
+                digest[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+            }
+        }
+        digests
+    }
+
+    /// One compression of each lane's state over the block in `w[..16]`
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    fn compress(state: &mut [__m256i; 8], w: &mut [__m256i; 64]) {
+        for t in 16..64 {
+            let s0 = _mm256_xor_si256(_mm256_xor_si256(rotr!(w[t - 15], 7), rotr!(w[t - 15], 18)), _mm256_srli_epi32(w[t - 15], 3));
+            let s1 = _mm256_xor_si256(_mm256_xor_si256(rotr!(w[t - 2], 17), rotr!(w[t - 2], 19)), _mm256_srli_epi32(w[t - 2], 10));
+            w[t] = _mm256_add_epi32(_mm256_add_epi32(w[t - 16], s0), _mm256_add_epi32(w[t - 7], s1));
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+        for t in 0..64 {
+            let s1 = _mm256_xor_si256(_mm256_xor_si256(rotr!(e, 6), rotr!(e, 11)), rotr!(e, 25));
+            let ch = _mm256_xor_si256(_mm256_and_si256(e, f), _mm256_andnot_si256(e, g));
+            let t1 = _mm256_add_epi32(
+                _mm256_add_epi32(_mm256_add_epi32(h, s1), _mm256_add_epi32(ch, _mm256_set1_epi32(K[t] as i32))),
+                w[t],
+            );
+            let s0 = _mm256_xor_si256(_mm256_xor_si256(rotr!(a, 2), rotr!(a, 13)), rotr!(a, 22));
+            let maj = _mm256_xor_si256(_mm256_and_si256(a, b), _mm256_and_si256(c, _mm256_xor_si256(a, b)));
+            let t2 = _mm256_add_epi32(s0, maj);
+            h = g;
+            g = f;
+            f = e;
+            e = _mm256_add_epi32(d, t1);
+            d = c;
+            c = b;
+            b = a;
+            a = _mm256_add_epi32(t1, t2);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = _mm256_add_epi32(*word, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(count: usize, len: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| (0..len).map(|j| (i * 31 + j * 7) as u8).collect()).collect()
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        assert!(backend().is_supported());
+        let expected = |messages: &[&[u8]]| -> Vec<Hash256> {
+            messages.iter().map(|m| crate::crypto::double_sha256(m)).collect()
+        };
+        // Lengths around the padding boundaries, and a ragged tail
+        for len in [0, 1, 55, 56, 63, 64, 119, 128, 200] {
+            for count in [1, 8, 13, 16] {
+                let owned = messages(count, len);
+                let borrowed: Vec<&[u8]> = owned.iter().map(Vec::as_slice).collect();
+                for backend in [Backend::ShaNi, Backend::Avx2, Backend::Portable] {
+                    assert_eq!(double_sha256_many_with(backend, &borrowed), expected(&borrowed), "{:?} len {} count {}", backend, len, count);
+                }
+            }
+        }
+
+        let mut mixed = messages(8, 64);
+        mixed[3].push(0);
+        let borrowed: Vec<&[u8]> = mixed.iter().map(Vec::as_slice).collect();
+        assert_eq!(double_sha256_many_with(Backend::Avx2, &borrowed), expected(&borrowed));
+    }
+
+    #[test]
+    fn test_merkle_root_unchanged() {
+        // The tree as it was built before batching
+        fn hex_pairs_root(mut hashes: Vec<Hash256>) -> Hash256 {
+            while hashes.len() > 1 {
+                hashes = hashes.chunks(2)
+                    .map(|chunk| {
+                        let combined = format!("{}{}", hex::encode(chunk[0]), hex::encode(chunk.get(1).unwrap_or(&chunk[0])));
+                        crate::utils::double_sha256(combined.as_bytes())
+                    })
+                    .collect();
+            }
+            hashes[0]
+        }
+
+        for count in [1, 2, 3, 7, 8, 17, 100] {
+            let leaves: Vec<Hash256> = (0..count as u32).map(|i| crate::crypto::sha256(&i.to_le_bytes())).collect();
+            assert_eq!(crate::block::Block::compute_merkle_root(leaves.clone()), hex_pairs_root(leaves));
+        }
+    }
+}
@@ -14,11 +14,17 @@
 //! - HD key caching for performance
 
 use crate::{Hash256, BlockchainError, Result};
-use crate::transaction::{Transaction, TransactionInput, TransactionOutput};
+use crate::address::{AddressCodec, AddressKind};
+use crate::transaction::{InputKind, Transaction, TransactionInput, TransactionOutput, P2PKH_SCRIPT_PUBKEY_SIZE};
 use crate::utxo::{UTXOSet, UTXO};
 use crate::tx_builder::TransactionManager;
+use crate::crypto::SigningKey;
+use crate::sighash::{SighashCache, SIGHASH_ALL};
+use crate::offline_signing::{SignatureSet, UnsignedTransaction};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{hash_map::Entry, HashMap, BTreeMap};
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 use sha2::{Sha256, Sha512, Digest};
 use hmac::{Hmac, Mac};
@@ -141,6 +147,14 @@ pub struct ExtendedKey {
     pub version: u32,
 }
 
+/// First hardened child index
+pub const HARDENED_OFFSET: u32 = 0x80000000;
+
+/// A BIP32 derivation path from the master key, written `m/44'/0'/0'/0/5`.
+/// Hardened steps take a `'` or `h` suffix.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DerivationPath(Vec<u32>);
+
 /// HD Wallet Account following BIP44 standard
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HDAccount {
@@ -272,8 +286,27 @@ pub struct TxBuildOptions {
     pub dust_threshold: u64,
     /// Maximum fee rate (protection against overpaying)
     pub max_fee_rate: u64,
+    /// Restrict coin selection to UTXOs held by these addresses
+    pub allowed_addresses: Option<Vec<String>>,
+    /// Put outputs in random order, so change isn't always last
+    pub shuffle_outputs: bool,
+    /// Split change of this amount or more into several outputs below it,
+    /// each to a fresh change address, so no single output stands out as
+    /// the change. None keeps one change output.
+    pub change_split_threshold: Option<u64>,
+    /// Overpayment accepted once signed, in virtual bytes at the fee rate
+    pub fee_tolerance_vbytes: u64,
 }
 
+/// Most change outputs one transaction splits its change into
+pub const MAX_CHANGE_OUTPUTS: u64 = 8;
+
+/// Times a signed transaction's change is corrected for its real size
+const MAX_FEE_ADJUSTMENTS: usize = 3;
+
+/// Value, script length and script of a P2PKH output
+const P2PKH_OUTPUT_VSIZE: u64 = (8 + 1 + P2PKH_SCRIPT_PUBKEY_SIZE) as u64;
+
 impl ExtendedKey {
     /// Create a new master key from seed
     pub fn from_seed(seed: &[u8], is_private: bool) -> Result<Self> {
@@ -367,14 +400,14 @@ impl ExtendedKey {
         };
         let parent_fingerprint = calculate_fingerprint(&parent_public)?;
 
-        let mut key_data = Vec::new();
-        if self.is_private {
-            // Add parent private key to child key (mod secp256k1 order)
-            key_data.extend_from_slice(child_key);
+        // Simplified derivation: a private child is `child_key` itself and a
+        // public child is its public key, so an xpub derives the same
+        // non-hardened addresses as the xpriv it came from
+        let key_data = if self.is_private {
+            child_key.to_vec()
         } else {
-            // Point addition for public keys (simplified)
-            key_data.extend_from_slice(child_key);
-        }
+            derive_public_key_from_private(child_key)?
+        };
 
         let mut chain_code_array = [0u8; 32];
         chain_code_array.copy_from_slice(child_chain_code);
@@ -445,6 +478,162 @@ impl ExtendedKey {
 
         Ok(bs58::encode(data).into_string())
     }
+
+    /// Parse a base58 extended key as written by `serialize`
+    pub fn parse(encoded: &str) -> Result<Self> {
+        let data = bs58::decode(encoded.trim()).into_vec()
+            .map_err(|e| BlockchainError::InvalidDerivation(format!("Extended key is not base58: {}", e)))?;
+        if data.len() != 82 {
+            return Err(BlockchainError::InvalidDerivation(format!(
+                "Extended key is {} bytes, expected 82", data.len()
+            )));
+        }
+        let (payload, checksum) = data.split_at(78);
+        if calculate_checksum(payload)?[0..4] != *checksum {
+            return Err(BlockchainError::InvalidDerivation("Extended key checksum does not match".to_string()));
+        }
+
+        let version = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        let is_private = match version {
+            0x0488ADE4 => true,
+            0x0488B21E => false,
+            other => return Err(BlockchainError::InvalidDerivation(format!(
+                "Unknown extended key version {:#010x}", other
+            ))),
+        };
+        let key = &payload[45..78];
+        let key_data = if is_private {
+            if key[0] != 0x00 {
+                return Err(BlockchainError::InvalidDerivation("Private extended key is not zero-padded".to_string()));
+            }
+            key[1..].to_vec()
+        } else {
+            if key[0] != 0x02 && key[0] != 0x03 {
+                return Err(BlockchainError::InvalidDerivation("Public extended key is not a compressed key".to_string()));
+            }
+            key.to_vec()
+        };
+
+        let mut parent_fingerprint = [0u8; 4];
+        parent_fingerprint.copy_from_slice(&payload[5..9]);
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&payload[13..45]);
+
+        Ok(ExtendedKey {
+            depth: payload[4],
+            parent_fingerprint,
+            child_number: u32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]),
+            chain_code,
+            key_data,
+            is_private,
+            version,
+        })
+    }
+
+    /// Descendant of this key along `path`, read relative to this key
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<ExtendedKey> {
+        path.iter().try_fold(self.clone(), |key, step| key.derive_child(*step))
+    }
+
+    /// Compressed public key of this extended key
+    pub fn public_key_bytes(&self) -> Result<[u8; 33]> {
+        let public = self.public_key()?;
+        public.key_data.as_slice().try_into()
+            .map_err(|_| BlockchainError::CryptoError("Public key must be 33 bytes".to_string()))
+    }
+}
+
+impl DerivationPath {
+    /// Path of child indexes, hardened ones offset by `HARDENED_OFFSET`
+    pub fn new(steps: Vec<u32>) -> Self {
+        Self(steps)
+    }
+
+    /// The empty path, naming the master key itself
+    pub fn master() -> Self {
+        Self::default()
+    }
+
+    /// This path extended by one step
+    pub fn child(&self, index: u32) -> Self {
+        let mut steps = self.0.clone();
+        steps.push(index);
+        Self(steps)
+    }
+
+    pub fn steps(&self) -> &[u32] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, u32> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any step needs the private key to derive
+    pub fn has_hardened_steps(&self) -> bool {
+        self.0.iter().any(|step| *step >= HARDENED_OFFSET)
+    }
+
+    /// Child index of one step: `5`, or `5'` / `5h` for hardened
+    pub(crate) fn parse_step(step: &str) -> Result<u32> {
+        let (number, hardened) = match step.strip_suffix('\'').or_else(|| step.strip_suffix('h')) {
+            Some(number) => (number, true),
+            None => (step, false),
+        };
+        let index: u32 = number.parse().ok()
+            .filter(|index| *index < HARDENED_OFFSET)
+            .ok_or_else(|| BlockchainError::InvalidDerivation(format!("invalid derivation step '{}'", step)))?;
+        Ok(if hardened { index + HARDENED_OFFSET } else { index })
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = BlockchainError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut steps = text.trim().split('/');
+        if steps.next() != Some("m") {
+            return Err(BlockchainError::InvalidDerivation(format!("path '{}' must start at 'm'", text)));
+        }
+        steps.map(Self::parse_step).collect::<Result<Vec<_>>>().map(Self)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for step in &self.0 {
+            if *step >= HARDENED_OFFSET {
+                write!(f, "/{}'", step - HARDENED_OFFSET)?;
+            } else {
+                write!(f, "/{}", step)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<u32>> for DerivationPath {
+    fn from(steps: Vec<u32>) -> Self {
+        Self(steps)
+    }
+}
+
+impl<'a> IntoIterator for &'a DerivationPath {
+    type Item = &'a u32;
+    type IntoIter = std::slice::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
 }
 
 impl HDAccount {
@@ -523,6 +712,26 @@ impl HDAccount {
         Ok(key_pair)
     }
 
+    /// Address at `index` on a chain (0 external, 1 change), not cached
+    fn address_at(&self, change: u32, index: u32) -> Result<String> {
+        let address_key = self.account_xpriv.derive_child(change)?.derive_child(index)?;
+        derive_p2pkh_address(&address_key.public_key_bytes()?)
+    }
+
+    /// Addresses in use on a chain, up to the last one `is_used` reports
+    /// before `gap_limit` unused addresses in a row
+    fn used_on_chain(&self, change: u32, gap_limit: u32, is_used: &impl Fn(&str) -> bool) -> Result<u32> {
+        let mut used = 0;
+        let mut index = 0;
+        while index < used + gap_limit {
+            if is_used(&self.address_at(change, index)?) {
+                used = index + 1;
+            }
+            index += 1;
+        }
+        Ok(used)
+    }
+
     /// Get next available receiving address
     pub fn get_next_address(&mut self) -> Result<String> {
         let key_pair = self.derive_address(self.next_address_index)?;
@@ -632,6 +841,55 @@ impl HDWallet {
         Ok(account_index)
     }
 
+    /// Extended private key at any path below the master key, for layouts
+    /// other than the fixed account/change/index one accounts use
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<ExtendedKey> {
+        self.master_xpriv.derive_path(path)
+    }
+
+    /// Recreate the accounts a restored wallet has used, following BIP44
+    /// account discovery: accounts are scanned in order, each chain until
+    /// `gap_limit` addresses in a row are unused, and the scan stops at the
+    /// first account without activity or after `max_accounts`. `is_used`
+    /// tells whether an address has been paid; an address index knows every
+    /// such address, the UTXO set only those still holding funds. Account 0
+    /// is kept even when unused. Returns the accounts found in use.
+    pub fn discover_accounts(
+        &mut self,
+        is_used: impl Fn(&str) -> bool,
+        gap_limit: u32,
+        max_accounts: u32,
+    ) -> Result<Vec<u32>> {
+        let mut used_accounts = Vec::new();
+        for account_index in 0..max_accounts {
+            let mut account = match self.accounts.get(&account_index) {
+                Some(account) => account.clone(),
+                None => HDAccount::new(account_index, format!("Account {}", account_index), &self.master_xpriv)?,
+            };
+            let receiving = account.used_on_chain(0, gap_limit, &is_used)?;
+            let change = account.used_on_chain(1, gap_limit, &is_used)?;
+            if receiving == 0 && change == 0 {
+                if account_index == 0 {
+                    self.accounts.entry(0).or_insert(account);
+                }
+                break;
+            }
+
+            // Used addresses are derived again so they are watched and
+            // signed for, and new addresses continue after them
+            for index in 0..receiving {
+                account.derive_address(index)?;
+            }
+            for index in 0..change {
+                account.derive_change_address(index)?;
+            }
+            account.next_address_index = account.next_address_index.max(receiving);
+            self.accounts.insert(account_index, account);
+            used_accounts.push(account_index);
+        }
+        Ok(used_accounts)
+    }
+
     /// Get account by index
     pub fn get_account(&mut self, account_index: u32) -> Option<&mut HDAccount> {
         self.accounts.get_mut(&account_index)
@@ -675,7 +933,11 @@ impl HDWallet {
         let all_addresses = {
             let account = self.get_account(account_index)
                 .ok_or_else(|| BlockchainError::AccountNotFound(account_index))?;
-            account.get_all_addresses()
+            let mut addresses = account.get_all_addresses();
+            if let Some(allowed) = &options.allowed_addresses {
+                addresses.retain(|address| allowed.contains(address));
+            }
+            addresses
         };
 
         let available_utxos = self.collect_utxos(&all_addresses, utxo_set)?;
@@ -721,35 +983,118 @@ impl HDWallet {
             tx.inputs.push(input);
         }
 
-        // Add outputs
+        // Add outputs, marking which are change
+        let mut tx_outputs = Vec::new();
         for (address, amount) in outputs {
             let script_pubkey = self.create_p2pkh_script(&address)?;
-            tx.outputs.push(TransactionOutput::new(amount, script_pubkey));
+            tx_outputs.push((TransactionOutput::new(amount, script_pubkey), false));
         }
 
-        // Add change output if necessary
+        // Add change outputs, none of them dust
         let change = input_value - total_output - fee;
-        let change_address = if change > options.dust_threshold {
-            let addr = if let Some(addr) = options.change_address {
-                addr
-            } else {
-                let account = self.get_account(account_index)
-                    .ok_or_else(|| BlockchainError::AccountNotFound(account_index))?;
-                account.get_next_change_address()?
-            };
-            let change_script = self.create_p2pkh_script(&addr)?;
-            tx.outputs.push(TransactionOutput::new(change, change_script));
-            Some(addr)
-        } else {
-            None
-        };
+        let extra_output_fee = P2PKH_OUTPUT_VSIZE * options.fee_rate;
+        for amount in plan_change(change, options.dust_threshold, options.change_split_threshold, extra_output_fee) {
+            let change_script = self.change_script(account_index, &options)?;
+            tx_outputs.push((TransactionOutput::new(amount, change_script), true));
+        }
+
+        if options.shuffle_outputs {
+            use rand::seq::SliceRandom;
+            tx_outputs.shuffle(&mut rand::thread_rng());
+        }
+        let change_index = tx_outputs.iter().position(|(_, is_change)| *is_change);
+        tx.outputs = tx_outputs.into_iter().map(|(output, _)| output).collect();
 
         // Sign transaction
-        self.sign_transaction_by_account_index(&mut tx, &selected_utxos, account_index)?;
+        self.sign_at_fee_rate(&mut tx, &selected_utxos, account_index, change_index, &options)?;
 
         Ok(tx)
     }
 
+    /// Script of the change address to use next
+    fn change_script(&mut self, account_index: u32, options: &TxBuildOptions) -> Result<Vec<u8>> {
+        let addr = if let Some(addr) = &options.change_address {
+            addr.clone()
+        } else {
+            let account = self.get_account(account_index)
+                .ok_or_else(|| BlockchainError::AccountNotFound(account_index))?;
+            account.get_next_change_address()?
+        };
+        self.create_p2pkh_script(&addr)
+    }
+
+    /// Sign `tx` and settle its fee on the signed size. The fee was
+    /// estimated with the largest possible signatures, so a surplus goes to
+    /// the change output at `change_index`, or to a new one if it is worth
+    /// an output, and a shortfall comes out of change, dropping the output
+    /// once it would be dust. New signatures can change the size again, so
+    /// this repeats until the fee is at the target rate and no more than
+    /// `fee_tolerance_vbytes` over it.
+    fn sign_at_fee_rate(
+        &mut self,
+        tx: &mut Transaction,
+        utxos: &[UTXO],
+        account_index: u32,
+        mut change_index: Option<usize>,
+        options: &TxBuildOptions,
+    ) -> Result<()> {
+        let input_value: u64 = utxos.iter().map(|utxo| utxo.value()).sum();
+        let fee_rate = options.fee_rate.min(options.max_fee_rate);
+        let tolerance = options.fee_tolerance_vbytes * fee_rate;
+
+        for attempt in 0..=MAX_FEE_ADJUSTMENTS {
+            self.sign_transaction_by_account_index(tx, utxos, account_index)?;
+            tx.clear_cache();
+            let paid = input_value.saturating_sub(tx.get_total_output_value());
+            let target = tx.vsize() as u64 * fee_rate;
+            if paid >= target && (paid - target <= tolerance || attempt == MAX_FEE_ADJUSTMENTS) {
+                return Ok(());
+            }
+            if attempt == MAX_FEE_ADJUSTMENTS {
+                break;
+            }
+
+            // Aim inside the tolerance so the next signatures have room
+            let aim = target + tolerance / 2;
+            match change_index {
+                Some(index) => {
+                    let value = (tx.outputs[index].value + paid).saturating_sub(aim);
+                    if value > options.dust_threshold {
+                        tx.outputs[index].value = value;
+                    } else {
+                        tx.outputs.remove(index);
+                        change_index = None;
+                    }
+                }
+                None if paid > target => {
+                    let value = paid.saturating_sub(aim + P2PKH_OUTPUT_VSIZE * fee_rate);
+                    if value <= options.dust_threshold {
+                        // Less than a change output is worth
+                        return Ok(());
+                    }
+                    let index = if options.shuffle_outputs {
+                        use rand::Rng;
+                        rand::thread_rng().gen_range(0..=tx.outputs.len())
+                    } else {
+                        tx.outputs.len()
+                    };
+                    let change_script = self.change_script(account_index, options)?;
+                    tx.outputs.insert(index, TransactionOutput::new(value, change_script));
+                    change_index = Some(index);
+                }
+                None => break,
+            }
+        }
+
+        let paid = input_value.saturating_sub(tx.get_total_output_value());
+        Err(BlockchainError::InsufficientFunds(format!(
+            "Signed transaction of {} vbytes needs a fee of {} satoshis, inputs leave {}",
+            tx.vsize(),
+            tx.vsize() as u64 * fee_rate,
+            paid
+        )))
+    }
+
     /// Collect UTXOs for given addresses
     fn collect_utxos(&self, addresses: &[String], utxo_set: &UTXOSet) -> Result<Vec<UTXO>> {
         let mut utxos = Vec::new();
@@ -947,19 +1292,13 @@ impl HDWallet {
         outputs: &[(String, u64)],
         options: &TxBuildOptions,
     ) -> Result<(u64, u64)> {
-        // Base transaction size: 10 bytes
-        let mut size = 10;
-        
-        // Input sizes: ~148 bytes each (outpoint + script_sig + sequence)
-        size += inputs.len() * 148;
-        
-        // Output sizes: ~34 bytes each (value + script_pubkey)
-        size += outputs.len() * 34;
-        
-        // Add potential change output
-        size += 34;
-
-        let size = size as u64;
+        // Each input at the largest size its kind signs to, P2PKH recipient
+        // outputs plus a potential change output
+        let input_kinds: Vec<InputKind> = inputs.iter()
+            .map(|utxo| InputKind::of_script_pubkey(&utxo.output.script_pubkey).unwrap_or(InputKind::P2pkh))
+            .collect();
+        let output_scripts = std::iter::repeat(P2PKH_SCRIPT_PUBKEY_SIZE).take(outputs.len() + 1);
+        let size = Transaction::estimate_vsize(&input_kinds, output_scripts) as u64;
         let fee = size * options.fee_rate;
 
         // Apply fee limits
@@ -970,11 +1309,29 @@ impl HDWallet {
 
     /// Estimate fee for specific number of inputs
     fn estimate_fee_for_inputs(&self, input_count: usize, fee_rate: u64) -> u64 {
-        // Base size + inputs + 2 outputs (recipient + change)
-        let size = 10 + (input_count * 148) + (2 * 34);
+        // Recipient and change outputs
+        let size = Transaction::estimate_p2pkh_vsize(input_count, [P2PKH_SCRIPT_PUBKEY_SIZE; 2]);
         size as u64 * fee_rate
     }
 
+    /// Sign a transaction exported for air-gapped signing, with keys of the
+    /// first `lookahead` receiving and change addresses of its account
+    pub fn sign_offline(&mut self, unsigned: &UnsignedTransaction, lookahead: u32) -> Result<SignatureSet> {
+        let account = self.get_account(unsigned.account_index)
+            .ok_or(BlockchainError::AccountNotFound(unsigned.account_index))?;
+        for index in 0..lookahead {
+            account.derive_address(index)?;
+            account.derive_change_address(index)?;
+        }
+
+        let mut tx = unsigned.transaction.clone();
+        sign_account_inputs(&mut tx, &unsigned.spent_utxos(), account)?;
+        Ok(SignatureSet {
+            unsigned_id: hex::encode(unsigned.id()),
+            script_sigs: tx.inputs.into_iter().map(|input| input.script_sig).collect(),
+        })
+    }
+
     /// Sign transaction by account index (avoids borrowing conflicts)
     fn sign_transaction_by_account_index(
         &mut self,
@@ -982,43 +1339,9 @@ impl HDWallet {
         utxos: &[UTXO],
         account_index: u32,
     ) -> Result<()> {
-        // Get the addresses and private keys first to avoid borrowing conflicts
-        let (addresses, private_keys): (Vec<String>, Vec<Option<[u8; 32]>>) = {
-            let account = self.get_account(account_index)
-                .ok_or_else(|| BlockchainError::AccountNotFound(account_index))?;
-            
-            let addresses = account.get_all_addresses();
-            let private_keys: Vec<Option<[u8; 32]>> = addresses.iter()
-                .map(|addr| account.find_private_key(addr))
-                .collect();
-            
-            (addresses, private_keys)
-        };
-        
-        for (i, utxo) in utxos.iter().enumerate() {
-            // Find the private key for this UTXO's address
-            let address_index = i % addresses.len();
-            let utxo_address = &addresses[address_index];
-            
-            if let Some(private_key) = private_keys[address_index] {
-                let signature = self.create_signature(tx, i, utxo, &private_key)?;
-                let public_key = derive_public_key_from_private(&private_key)?;
-                
-                // Create script_sig (simplified P2PKH)
-                let mut script_sig = Vec::new();
-                script_sig.push(signature.len() as u8);
-                script_sig.extend_from_slice(&signature);
-                script_sig.push(public_key.len() as u8);
-                script_sig.extend_from_slice(&public_key);
-                
-                tx.inputs[i].script_sig = script_sig;
-            } else {
-                return Err(BlockchainError::SigningError(
-                    format!("Private key not found for address: {}", utxo_address)
-                ));
-            }
-        }
-        Ok(())
+        let account = self.get_account(account_index)
+            .ok_or_else(|| BlockchainError::AccountNotFound(account_index))?;
+        sign_account_inputs(tx, utxos, account)
     }
 
     /// Sign a transaction with account keys
@@ -1028,98 +1351,7 @@ impl HDWallet {
         utxos: &[UTXO],
         account: &HDAccount,
     ) -> Result<()> {
-        for (i, utxo) in utxos.iter().enumerate() {
-            // Find the private key for this UTXO's address
-            // For now, we'll assume the UTXO has an associated address field
-            // In a full implementation, we'd extract the address from the script_pubkey
-            let utxo_address = &account.get_all_addresses()[i % account.get_all_addresses().len()];
-            if let Some(private_key) = account.find_private_key(utxo_address) {
-                let signature = self.create_signature(tx, i, utxo, &private_key)?;
-                let public_key = derive_public_key_from_private(&private_key)?;
-                
-                // Create script_sig (simplified P2PKH)
-                let mut script_sig = Vec::new();
-                script_sig.push(signature.len() as u8);
-                script_sig.extend_from_slice(&signature);
-                script_sig.push(public_key.len() as u8);
-                script_sig.extend_from_slice(&public_key);
-                
-                tx.inputs[i].script_sig = script_sig;
-            } else {
-                return Err(BlockchainError::SigningError(
-                    format!("Private key not found for address: {}", utxo_address)
-                ));
-            }
-        }
-        Ok(())
-    }
-
-    /// Create signature for transaction input
-    fn create_signature(
-        &self,
-        tx: &Transaction,
-        input_index: usize,
-        utxo: &UTXO,
-        private_key: &[u8; 32],
-    ) -> Result<Vec<u8>> {
-        // Create signature hash
-        let signature_hash = self.create_signature_hash(tx, input_index, &utxo.output.script_pubkey)?;
-        
-        // Sign with private key (simplified ECDSA)
-        let signature = sign_hash(&signature_hash, private_key)?;
-        
-        // Add SIGHASH_ALL flag
-        let mut sig_with_hashtype = signature;
-        sig_with_hashtype.push(0x01); // SIGHASH_ALL
-        
-        Ok(sig_with_hashtype)
-    }
-
-    /// Create signature hash for signing
-    fn create_signature_hash(
-        &self,
-        tx: &Transaction,
-        input_index: usize,
-        script_code: &[u8],
-    ) -> Result<Hash256> {
-        // Simplified signature hash creation (same as in tx_builder)
-        let mut hasher = Sha256::new();
-        
-        // Add transaction version
-        hasher.update(tx.version.to_le_bytes());
-        
-        // Add inputs
-        hasher.update((tx.inputs.len() as u32).to_le_bytes());
-        for (i, input) in tx.inputs.iter().enumerate() {
-            hasher.update(&input.prev_tx_hash);
-            hasher.update(input.prev_output_index.to_le_bytes());
-            
-            if i == input_index {
-                hasher.update((script_code.len() as u32).to_le_bytes());
-                hasher.update(script_code);
-            } else {
-                hasher.update(0u32.to_le_bytes());
-            }
-            
-            hasher.update(input.sequence.to_le_bytes());
-        }
-        
-        // Add outputs
-        hasher.update((tx.outputs.len() as u32).to_le_bytes());
-        for output in &tx.outputs {
-            hasher.update(output.value.to_le_bytes());
-            hasher.update((output.script_pubkey.len() as u32).to_le_bytes());
-            hasher.update(&output.script_pubkey);
-        }
-        
-        // Add locktime and sighash type
-        hasher.update(tx.locktime.to_le_bytes());
-        hasher.update(1u32.to_le_bytes()); // SIGHASH_ALL
-        
-        let hash = hasher.finalize();
-        let mut result = [0u8; 32];
-        result.copy_from_slice(&hash);
-        Ok(result)
+        sign_account_inputs(tx, utxos, account)
     }
 
     /// Create P2PKH script for address
@@ -1203,6 +1435,10 @@ impl Default for TxBuildOptions {
             change_address: None,
             dust_threshold: 546, // Standard dust threshold
             max_fee_rate: 10000, // 10,000 satoshis per byte max
+            allowed_addresses: None,
+            shuffle_outputs: true,
+            change_split_threshold: None,
+            fee_tolerance_vbytes: 4,
         }
     }
 }
@@ -1221,29 +1457,29 @@ fn derive_public_key_from_private(private_key: &[u8]) -> Result<Vec<u8>> {
 }
 
 /// Derive P2PKH address from public key
-fn derive_p2pkh_address(public_key: &[u8; 33]) -> Result<String> {
+pub(crate) fn derive_p2pkh_address(public_key: &[u8; 33]) -> Result<String> {
     let mut hasher = Sha256::new();
     hasher.update(public_key);
     let hash = hasher.finalize();
     
-    let address_bytes = &hash[0..20];
-    let encoded = bs58::encode(address_bytes).into_string();
-    Ok(format!("edu1q{}", encoded))
+    let mut address_bytes = [0u8; 20];
+    address_bytes.copy_from_slice(&hash[0..20]);
+    Ok(AddressCodec::encode(AddressKind::P2pkh, &address_bytes))
 }
 
 /// Derive P2SH address from redeem script
-fn derive_p2sh_address(redeem_script: &[u8]) -> Result<String> {
+pub(crate) fn derive_p2sh_address(redeem_script: &[u8]) -> Result<String> {
     let mut hasher = Sha256::new();
     hasher.update(redeem_script);
     let hash = hasher.finalize();
     
-    let address_bytes = &hash[0..20];
-    let encoded = bs58::encode(address_bytes).into_string();
-    Ok(format!("edu3{}", encoded)) // Different prefix for P2SH
+    let mut address_bytes = [0u8; 20];
+    address_bytes.copy_from_slice(&hash[0..20]);
+    Ok(AddressCodec::encode(AddressKind::P2sh, &address_bytes))
 }
 
 /// Create multi-signature redeem script
-fn create_multisig_redeem_script(required_sigs: u32, public_keys: &[[u8; 33]]) -> Result<Vec<u8>> {
+pub(crate) fn create_multisig_redeem_script(required_sigs: u32, public_keys: &[[u8; 33]]) -> Result<Vec<u8>> {
     if required_sigs == 0 || required_sigs > 16 || public_keys.len() > 16 {
         return Err(BlockchainError::InvalidMultiSig("Invalid multisig parameters".to_string()));
     }
@@ -1338,9 +1574,103 @@ fn derive_seed_from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<[u8; 64
     Ok(seed)
 }
 
-/// Sign hash with private key using secp256k1 ECDSA
-fn sign_hash(hash: &Hash256, private_key: &[u8; 32]) -> Result<Vec<u8>> {
-    crate::crypto::sign_hash(hash, private_key)
+/// Change outputs to make from `change`, what inputs leave after the
+/// outputs and a fee that covers one change output. Change at or below
+/// `dust_threshold` goes to the fee. Change at or above `split_threshold`
+/// is split evenly into parts below it, each extra part paying
+/// `extra_output_fee` out of the change, as long as no part becomes dust
+/// and there are at most `MAX_CHANGE_OUTPUTS`.
+fn plan_change(change: u64, dust_threshold: u64, split_threshold: Option<u64>, extra_output_fee: u64) -> Vec<u64> {
+    if change <= dust_threshold {
+        return Vec::new();
+    }
+    let Some(threshold) = split_threshold else {
+        return vec![change];
+    };
+
+    let available = |parts: u64| change.saturating_sub((parts - 1) * extra_output_fee);
+    let mut parts = 1;
+    while parts < MAX_CHANGE_OUTPUTS
+        && available(parts).div_ceil(parts) >= threshold
+        && available(parts + 1) / (parts + 1) > dust_threshold
+    {
+        parts += 1;
+    }
+
+    let total = available(parts);
+    (0..parts).map(|i| total / parts + u64::from(i < total % parts)).collect()
+}
+
+/// Address a P2PKH output pays to, from either script form on chain: the
+/// standard hash160 script or the simplified one carrying the address itself
+fn p2pkh_address(utxo: &UTXO) -> Option<String> {
+    let script = &utxo.output.script_pubkey;
+    if script.len() == P2PKH_SCRIPT_PUBKEY_SIZE && script[..3] == [0x76, 0xa9, 0x14] {
+        let hash: [u8; 20] = script[3..23].try_into().ok()?;
+        return Some(AddressCodec::encode(AddressKind::P2pkh, &hash));
+    }
+    utxo.get_address()
+}
+
+/// Sign every input of `tx` with the key of the account address its UTXO
+/// pays to. Inputs are matched to `utxos` by outpoint and keys to addresses
+/// by the UTXO's script_pubkey, so neither needs to be in any order. A consolidation spends many outputs of the same few addresses,
+/// so each address's key is parsed once however many inputs it signs, and
+/// all inputs share one sighash cache instead of rehashing the whole
+/// transaction per input.
+fn sign_account_inputs(tx: &mut Transaction, utxos: &[UTXO], account: &HDAccount) -> Result<()> {
+    let private_keys: HashMap<&str, &[u8; 32]> = account.derived_keys.values()
+        .map(|key_pair| (key_pair.address.as_str(), &key_pair.private_key))
+        .collect();
+    let spent: HashMap<(Hash256, u32), &UTXO> = utxos.iter()
+        .map(|utxo| ((utxo.tx_hash, utxo.output_index), utxo))
+        .collect();
+    let mut signers: HashMap<String, SigningKey> = HashMap::new();
+
+    // Legacy SIGHASH_ALL, which validators accept with or without the
+    // precomputed sighash deployment
+    let sighash_type = SIGHASH_ALL;
+    let mut sighashes = SighashCache::new(tx);
+    let mut script_sigs = Vec::with_capacity(tx.inputs.len());
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let outpoint = format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index);
+        let utxo = spent.get(&(input.prev_tx_hash, input.prev_output_index)).ok_or_else(|| {
+            BlockchainError::SigningError(format!("Input {} spends {}, which is not among the UTXOs to sign", i, outpoint))
+        })?;
+        let address = p2pkh_address(utxo).ok_or_else(|| {
+            BlockchainError::SigningError(format!("Input {} spends {}, which is not a P2PKH output", i, outpoint))
+        })?;
+        let signer = match signers.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let private_key = private_keys.get(entry.key().as_str()).ok_or_else(|| {
+                    BlockchainError::SigningError(format!(
+                        "Input {} spends {} paying to {}, which is not an address of this account",
+                        i, outpoint, entry.key()
+                    ))
+                })?;
+                let signer = SigningKey::new(private_key)?;
+                entry.insert(signer)
+            }
+        };
+
+        let signature_hash = sighashes.signature_hash(i, &utxo.output.script_pubkey, utxo.output.value, sighash_type)?;
+        let mut signature = signer.sign_hash(&signature_hash)?;
+        signature.push(sighash_type as u8);
+        let public_key = signer.public_key();
+
+        // Create script_sig (simplified P2PKH)
+        let mut script_sig = Vec::with_capacity(2 + signature.len() + public_key.len());
+        script_sig.push(signature.len() as u8);
+        script_sig.extend_from_slice(&signature);
+        script_sig.push(public_key.len() as u8);
+        script_sig.extend_from_slice(public_key);
+        script_sigs.push(script_sig);
+    }
+    for (input, script_sig) in tx.inputs.iter_mut().zip(script_sigs) {
+        input.script_sig = script_sig;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1363,6 +1693,113 @@ mod tests {
         let child = master.derive_child(0).unwrap();
         assert_eq!(child.depth, 1);
         assert_eq!(child.child_number, 0);
+
+        // The xpub derives the public keys of the xpriv's children
+        let xpub = master.public_key().unwrap();
+        let public_child = xpub.derive_child(0).unwrap().derive_child(7).unwrap();
+        let private_child = child.derive_child(7).unwrap();
+        assert_eq!(public_child.key_data, private_child.public_key().unwrap().key_data);
+        assert!(xpub.derive_child(0x80000000).is_err());
+    }
+
+    #[test]
+    fn test_extended_key_serialization_round_trip() {
+        let master = ExtendedKey::from_seed(&[3u8; 32], true).unwrap();
+        let account = master.derive_child(0x80000000).unwrap();
+        for key in [account.clone(), account.public_key().unwrap()] {
+            let parsed = ExtendedKey::parse(&key.serialize().unwrap()).unwrap();
+            assert_eq!(parsed.key_data, key.key_data);
+            assert_eq!(parsed.chain_code, key.chain_code);
+            assert_eq!(parsed.depth, 1);
+            assert_eq!(parsed.is_private, key.is_private);
+        }
+
+        let encoded = account.public_key().unwrap().serialize().unwrap();
+        let typo = if encoded.as_bytes()[20] == b'2' { "3" } else { "2" };
+        let mistyped = format!("{}{}{}", &encoded[..20], typo, &encoded[21..]);
+        assert!(ExtendedKey::parse(&mistyped).is_err());
+    }
+
+    #[test]
+    fn test_derivation_path_parsing() {
+        let path: DerivationPath = "m/44'/0'/0h/0/5".parse().unwrap();
+        assert_eq!(path.steps(), &[44 + HARDENED_OFFSET, HARDENED_OFFSET, HARDENED_OFFSET, 0, 5]);
+        assert_eq!(path.to_string(), "m/44'/0'/0'/0/5");
+        assert_eq!(path.to_string().parse::<DerivationPath>().unwrap(), path);
+        assert!(path.has_hardened_steps());
+        assert_eq!(path.iter().filter(|step| **step < HARDENED_OFFSET).count(), 2);
+
+        let master: DerivationPath = "m".parse().unwrap();
+        assert!(master.is_empty());
+        assert_eq!(master, DerivationPath::master());
+        assert_eq!(master.child(1).child(2).to_string(), "m/1/2");
+
+        for bad in ["", "44'/0'", "m/", "m/x", "m/-1", "m/2147483648", "m/1''", "n/0"] {
+            assert!(bad.parse::<DerivationPath>().is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn test_derive_arbitrary_path() {
+        let wallet = HDWallet::new("Paths".to_string(), Some([9u8; 32])).unwrap();
+        let mut account = HDAccount::new(0, "Main".to_string(), &wallet.master_xpriv).unwrap();
+        let expected = account.derive_address(3).unwrap();
+
+        // The account layout is one path among any
+        let account_path = DerivationPath::from(vec![0x80000044, HARDENED_OFFSET, HARDENED_OFFSET]);
+        let key = wallet.derive_path(&account_path.child(0).child(3)).unwrap();
+        assert_eq!(key.depth, 5);
+        assert_eq!(key.key_data, expected.private_key.to_vec());
+        assert_eq!(wallet.derive_path(&DerivationPath::master()).unwrap().key_data, wallet.master_xpriv.key_data);
+
+        // Public derivation follows only the non-hardened part
+        let account_xpub = wallet.derive_path(&account_path).unwrap().public_key().unwrap();
+        let tail: DerivationPath = "m/0/3".parse().unwrap();
+        assert_eq!(account_xpub.derive_path(&tail).unwrap().public_key_bytes().unwrap(), expected.public_key);
+        assert!(account_xpub.derive_path(&"m/0'".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_account_discovery() {
+        let mnemonic = HDWallet::new("Original".to_string(), Some([5u8; 32])).unwrap().mnemonic.unwrap();
+        let reference = HDWallet::from_mnemonic("Reference".to_string(), &mnemonic, None).unwrap();
+
+        // Account 0 used addresses 0 and 7 and one change address, account 1
+        // address 2, account 3 is beyond the empty account 2
+        let mut used = std::collections::HashSet::new();
+        let mut accounts: Vec<HDAccount> = (0..4)
+            .map(|index| HDAccount::new(index, format!("Account {}", index), &reference.master_xpriv).unwrap())
+            .collect();
+        used.insert(accounts[0].derive_address(0).unwrap().address);
+        used.insert(accounts[0].derive_address(7).unwrap().address);
+        used.insert(accounts[0].derive_change_address(0).unwrap().address);
+        used.insert(accounts[1].derive_address(2).unwrap().address);
+        used.insert(accounts[3].derive_address(0).unwrap().address);
+
+        let mut restored = HDWallet::from_mnemonic("Restored".to_string(), &mnemonic, None).unwrap();
+        let found = restored.discover_accounts(|address| used.contains(address), 20, 10).unwrap();
+        assert_eq!(found, vec![0, 1]);
+        assert_eq!(restored.accounts.len(), 2);
+
+        let account = restored.get_account(0).unwrap();
+        assert_eq!(account.next_address_index, 8);
+        assert_eq!(account.external_addresses.len(), 8);
+        assert_eq!(account.change_addresses.len(), 1);
+        assert!(account.find_private_key(&accounts[0].derive_address(7).unwrap().address).is_some());
+        let next = account.get_next_address().unwrap();
+        assert_eq!(next, accounts[0].derive_address(8).unwrap().address);
+        assert_eq!(restored.get_account(1).unwrap().next_address_index, 3);
+
+        // A gap wider than the limit hides later addresses
+        let mut narrow = HDWallet::from_mnemonic("Narrow".to_string(), &mnemonic, None).unwrap();
+        narrow.discover_accounts(|address| used.contains(address), 5, 10).unwrap();
+        assert_eq!(narrow.get_account(0).unwrap().next_address_index, 1);
+
+        // A wallet never used still gets its first account
+        let mut fresh = HDWallet::from_mnemonic("Fresh".to_string(), &mnemonic, None).unwrap();
+        assert!(fresh.discover_accounts(|_| false, 20, 10).unwrap().is_empty());
+        assert_eq!(fresh.accounts.len(), 1);
+        assert_eq!(fresh.get_account(0).unwrap().next_address_index, 0);
     }
 
     #[test]
@@ -1388,6 +1825,138 @@ mod tests {
         assert!(addr2.starts_with("edu1q"));
     }
 
+    #[test]
+    fn test_sign_consolidation_reuses_keys() {
+        let wallet = HDWallet::new("Consolidation".to_string(), Some([7u8; 32])).unwrap();
+        let mut account = HDAccount::new(0, "Main".to_string(), &wallet.master_xpriv).unwrap();
+        let first = account.get_next_address().unwrap();
+        let second = account.get_next_address().unwrap();
+
+        // Many outputs of two addresses, in both P2PKH script forms
+        let utxos: Vec<UTXO> = (0..12u8)
+            .map(|i| {
+                let address = if i % 3 == 0 { &second } else { &first };
+                let output = if i % 2 == 0 {
+                    TransactionOutput::create_p2pkh(10_000, address).unwrap()
+                } else {
+                    TransactionOutput::new(10_000, crate::tx_builder::create_p2pkh_script(address).unwrap())
+                };
+                UTXO::new([i; 32], 0, output, 1, false)
+            })
+            .collect();
+        let inputs = utxos.iter()
+            .map(|utxo| TransactionInput::new(utxo.tx_hash, utxo.output_index, Vec::new()))
+            .collect();
+        let outputs = vec![TransactionOutput::create_p2pkh(100_000, &first).unwrap()];
+        let mut tx = Transaction::new(1, inputs, outputs);
+
+        sign_account_inputs(&mut tx, &utxos, &account).unwrap();
+
+        let mut sighashes = SighashCache::new(&tx);
+        for (i, utxo) in utxos.iter().enumerate() {
+            let script_sig = &tx.inputs[i].script_sig;
+            let sig_len = script_sig[0] as usize;
+            let (signature, sighash_type) = (&script_sig[1..sig_len], script_sig[sig_len] as u32);
+            let public_key = &script_sig[sig_len + 2..];
+            assert_eq!(sighash_type, SIGHASH_ALL);
+            assert_eq!(p2pkh_address(utxo).unwrap(), derive_p2pkh_address(public_key.try_into().unwrap()).unwrap());
+
+            let hash = sighashes.signature_hash(i, &utxo.output.script_pubkey, utxo.output.value, sighash_type).unwrap();
+            assert!(crate::crypto::verify_signature(signature, public_key, &hash).unwrap());
+        }
+
+        // Each input is signed by the key of the address its own UTXO pays
+        // to, whatever order the UTXOs come in
+        let expected: HashMap<&str, &[u8; 33]> = account.derived_keys.values()
+            .map(|key_pair| (key_pair.address.as_str(), &key_pair.public_key))
+            .collect();
+        for (input, utxo) in tx.inputs.iter().zip(&utxos) {
+            let sig_len = input.script_sig[0] as usize;
+            let address = p2pkh_address(utxo).unwrap();
+            assert_eq!(&input.script_sig[sig_len + 2..], expected[address.as_str()].as_slice());
+        }
+        let signed = tx.inputs.iter().map(|input| input.script_sig.clone()).collect::<Vec<_>>();
+        let reversed: Vec<UTXO> = utxos.iter().rev().cloned().collect();
+        sign_account_inputs(&mut tx, &reversed, &account).unwrap();
+        assert!(tx.inputs.iter().map(|input| &input.script_sig).eq(signed.iter()));
+
+        // An input without its UTXO can't be signed
+        assert!(matches!(
+            sign_account_inputs(&mut tx, &utxos[1..], &account),
+            Err(BlockchainError::SigningError(_))
+        ));
+
+        // Nor can an output of an address the account never derived
+        let foreign = TransactionOutput::create_p2pkh(10_000, &derive_p2pkh_address(&[2u8; 33]).unwrap()).unwrap();
+        let mut utxos = utxos;
+        utxos[5] = UTXO::new([5; 32], 0, foreign, 1, false);
+        assert!(matches!(
+            sign_account_inputs(&mut tx, &utxos, &account),
+            Err(BlockchainError::SigningError(_))
+        ));
+    }
+
+    #[test]
+    fn test_change_planning() {
+        // Dust change is left to the fee, whether split or not
+        assert!(plan_change(546, 546, None, 34).is_empty());
+        assert!(plan_change(500, 546, Some(1_000), 34).is_empty());
+        assert_eq!(plan_change(547, 546, None, 34), vec![547]);
+
+        // Change below the threshold stays whole
+        assert_eq!(plan_change(90_000, 546, Some(100_000), 34), vec![90_000]);
+
+        // Larger change is split into parts below it, paying for the extra outputs
+        let parts = plan_change(250_000, 546, Some(100_000), 34);
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| *part < 100_000 && *part > 546));
+        assert_eq!(parts.iter().sum::<u64>(), 250_000 - 2 * 34);
+        assert!(parts.iter().max().unwrap() - parts.iter().min().unwrap() <= 1);
+
+        // Splitting stops before parts turn into dust, and at the output cap
+        let parts = plan_change(2_000, 546, Some(600), 34);
+        assert_eq!(parts, vec![644, 644, 644]);
+        assert!(parts.iter().all(|part| *part > 546));
+        let parts = plan_change(100_000_000, 546, Some(1_000), 34);
+        assert_eq!(parts.len() as u64, MAX_CHANGE_OUTPUTS);
+    }
+
+    #[tokio::test]
+    async fn test_signed_fee_matches_target_rate() {
+        let mut wallet = HDWallet::new("Fees".to_string(), Some([3u8; 32])).unwrap();
+        let account_index = wallet.create_account("Main".to_string()).unwrap();
+        let account = wallet.get_account(account_index).unwrap();
+        let funded = [account.get_next_address().unwrap(), account.get_next_address().unwrap()];
+
+        let mut utxo_set = UTXOSet::new();
+        for i in 0..3u8 {
+            let output = TransactionOutput::create_p2pkh(50_000, &funded[i as usize % 2]).unwrap();
+            utxo_set.add_utxo([i; 32], 0, UTXO::new([i; 32], 0, output, 1, false)).unwrap();
+        }
+
+        for shuffle_outputs in [false, true] {
+            let options = TxBuildOptions {
+                selection_strategy: UTXOSelectionStrategy::LargestFirst,
+                fee_rate: 10,
+                shuffle_outputs,
+                ..TxBuildOptions::default()
+            };
+            let tolerance = options.fee_tolerance_vbytes * options.fee_rate;
+            let recipient = derive_p2pkh_address(&[2u8; 33]).unwrap();
+            let tx = wallet.build_transaction(account_index, vec![(recipient, 60_000)], options, &utxo_set)
+                .await
+                .unwrap();
+
+            // The fee is for the signed size, not the worst-case estimate
+            let input_value = 50_000 * tx.inputs.len() as u64;
+            let paid = input_value - tx.get_total_output_value();
+            let target = tx.vsize() as u64 * 10;
+            assert!(paid >= target && paid - target <= tolerance, "paid {} for target {}", paid, target);
+            assert_eq!(tx.outputs.len(), 2);
+            assert_eq!(tx.outputs.iter().filter(|output| output.value == 60_000).count(), 1);
+        }
+    }
+
     #[test]
     fn test_multisig_creation() {
         let mut wallet = HDWallet::new("MultiSig Test".to_string(), None).unwrap();
@@ -1431,4 +2000,48 @@ mod tests {
         
         assert_eq!(strategies.len(), 5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_offline_signing_round_trip() {
+        let mut wallet = HDWallet::new("Cold".to_string(), Some([11u8; 32])).unwrap();
+        wallet.create_account("Main".to_string()).unwrap();
+        let (receive, change) = {
+            let account = wallet.get_account(0).unwrap();
+            (account.derive_address(2).unwrap().address, account.derive_change_address(5).unwrap().address)
+        };
+
+        // The online side knows the coins and builds, but holds no keys
+        let spent = vec![
+            TransactionOutput::create_p2pkh(60_000, &receive).unwrap(),
+            TransactionOutput::new(40_000, crate::tx_builder::create_p2pkh_script(&change).unwrap()),
+        ];
+        let inputs = vec![
+            TransactionInput::new([1; 32], 0, Vec::new()),
+            TransactionInput::new([2; 32], 1, Vec::new()),
+        ];
+        let tx = Transaction::new(1, inputs, vec![TransactionOutput::create_p2pkh(95_000, &receive).unwrap()]);
+        let unsigned = UnsignedTransaction::new(tx, spent, 0).unwrap();
+        assert_eq!(unsigned.fee(), 5_000);
+
+        // Over QR to a signer holding the same seed, which finds the keys
+        // within its lookahead
+        let scanned = UnsignedTransaction::from_qr_chunks(&unsigned.to_qr_chunks(120).unwrap()).unwrap();
+        let mut signer = HDWallet::new("Cold".to_string(), Some([11u8; 32])).unwrap();
+        signer.create_account("Main".to_string()).unwrap();
+        assert!(signer.sign_offline(&scanned, 3).is_err());
+        let signatures = signer.sign_offline(&scanned, 10).unwrap();
+
+        // And back
+        let returned = SignatureSet::from_qr_chunks(&signatures.to_qr_chunks(120).unwrap()).unwrap();
+        let signed = unsigned.apply(&returned).unwrap();
+        assert!(signed.inputs.iter().all(|input| !input.script_sig.is_empty()));
+
+        // Signatures that don't fit the exported transaction are refused
+        let mut swapped = returned.clone();
+        swapped.script_sigs.swap(0, 1);
+        assert!(unsigned.apply(&swapped).is_err());
+        let mut other = returned;
+        other.unsigned_id = hex::encode([0u8; 32]);
+        assert!(unsigned.apply(&other).is_err());
+    }
+}
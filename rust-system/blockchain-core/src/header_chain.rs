@@ -0,0 +1,335 @@
+//! Header-only chain tracking
+//!
+//! Follows the best chain from block headers alone: every header must
+//! extend a known one, meet its difficulty target and carry the next height.
+//! No transactions are validated and no UTXO set is kept, which is enough for
+//! monitoring services that watch the tip and want to hear about forks.
+
+use crate::block::BlockHeader;
+use crate::consensus::pow_threshold;
+use crate::network_time::NetworkTime;
+use crate::{BlockchainError, Hash256, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+/// Fork alerts kept for reporting
+const MAX_ALERTS: usize = 100;
+
+/// Furthest a header's timestamp may be ahead of the local clock
+const MAX_FUTURE_SECS: u64 = 2 * 60 * 60;
+
+/// What adding a header did to the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderOutcome {
+    /// Extended the best chain
+    Extended,
+    /// Became the tip of a longer branch, replacing `depth` blocks
+    Reorg { depth: u64 },
+    /// Added to a branch that is not (yet) the best chain
+    SideBranch,
+    /// Already known
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForkKind {
+    /// A competing block appeared below or at the tip
+    SideBranch,
+    /// The best chain switched to another branch
+    Reorg,
+}
+
+/// A fork seen while following headers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkAlert {
+    pub kind: ForkKind,
+    /// Height of the last block both branches share
+    pub fork_height: u64,
+    /// Best chain tip when the alert was raised
+    pub best_hash: String,
+    /// Tip of the competing (or replaced) branch
+    pub competing_hash: String,
+    /// Blocks on the shorter branch past the fork point
+    pub depth: u64,
+    pub detected_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipStatus {
+    /// Tip of the best chain
+    Active,
+    /// Tip of a side branch whose headers are valid
+    ValidHeaders,
+    /// A branch containing a block known to be invalid
+    Invalid,
+}
+
+/// A branch tip, as listed by `blockchain_getChainTips`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainTipInfo {
+    pub height: u64,
+    pub hash: String,
+    /// Blocks between the tip and the best chain
+    pub branch_len: u64,
+    pub status: TipStatus,
+}
+
+/// Tips of the branches in `headers` (hash to parent hash and height) that
+/// reach within `window` blocks of the best tip. `best_at` gives the best
+/// chain's hash at a height.
+pub fn branch_tips(
+    headers: &HashMap<Hash256, (Hash256, u64)>,
+    best_at: impl Fn(u64) -> Option<Hash256>,
+    best_tip: (u64, Hash256),
+    window: u64,
+) -> Vec<ChainTipInfo> {
+    let parents: HashSet<&Hash256> = headers.values().map(|(prev, _)| prev).collect();
+    let mut tips: Vec<ChainTipInfo> = headers.iter()
+        .filter(|(hash, (_, height))| !parents.contains(hash) && height.saturating_add(window) >= best_tip.0)
+        .map(|(hash, &(_, height))| {
+            let mut branch_len = 0;
+            let mut cursor = (*hash, height);
+            while best_at(cursor.1) != Some(cursor.0) {
+                branch_len += 1;
+                match headers.get(&cursor.0) {
+                    Some(&(prev, height)) if height > 0 => cursor = (prev, height - 1),
+                    _ => break,
+                }
+            }
+            ChainTipInfo {
+                height,
+                hash: hex::encode(hash),
+                branch_len,
+                status: if *hash == best_tip.1 { TipStatus::Active } else { TipStatus::ValidHeaders },
+            }
+        })
+        .collect();
+    tips.sort_by(|a, b| b.height.cmp(&a.height).then(a.branch_len.cmp(&b.branch_len)));
+    tips
+}
+
+/// Block headers from genesis, with the best chain picked by height
+pub struct HeaderChain {
+    headers: HashMap<Hash256, BlockHeader>,
+    /// Best chain: height -> hash
+    best: Vec<Hash256>,
+    alerts: VecDeque<ForkAlert>,
+    /// Clock for future-timestamp checks; the local one if unset
+    network_time: Option<Arc<NetworkTime>>,
+}
+
+impl HeaderChain {
+    pub fn new(genesis: BlockHeader) -> Self {
+        let hash = genesis.calculate_hash();
+        Self {
+            headers: HashMap::from([(hash, genesis)]),
+            best: vec![hash],
+            alerts: VecDeque::new(),
+            network_time: None,
+        }
+    }
+
+    /// Check header timestamps against network-adjusted time
+    pub fn with_network_time(mut self, network_time: Arc<NetworkTime>) -> Self {
+        self.network_time = Some(network_time);
+        self
+    }
+
+    /// Height and hash of the best chain tip
+    pub fn tip(&self) -> (u64, Hash256) {
+        let height = self.best.len() as u64 - 1;
+        (height, self.best[height as usize])
+    }
+
+    pub fn tip_header(&self) -> &BlockHeader {
+        &self.headers[&self.tip().1]
+    }
+
+    pub fn get(&self, hash: &Hash256) -> Option<&BlockHeader> {
+        self.headers.get(hash)
+    }
+
+    /// Hash of the best-chain block at `height`
+    pub fn hash_at(&self, height: u64) -> Option<Hash256> {
+        self.best.get(height as usize).copied()
+    }
+
+    /// Headers known, on any branch
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Branch tips within `window` blocks of the best tip
+    pub fn tips(&self, window: u64) -> Vec<ChainTipInfo> {
+        let headers = self.headers.iter()
+            .map(|(hash, header)| (*hash, (header.prev_block_hash, header.height as u64)))
+            .collect();
+        branch_tips(&headers, |height| self.hash_at(height), self.tip(), window)
+    }
+
+    /// Fork alerts, newest first
+    pub fn alerts(&self, limit: usize) -> Vec<ForkAlert> {
+        self.alerts.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Validate and add a header
+    pub fn add_header(&mut self, header: BlockHeader) -> Result<HeaderOutcome> {
+        let hash = header.calculate_hash();
+        if self.headers.contains_key(&hash) {
+            return Ok(HeaderOutcome::Duplicate);
+        }
+        let parent = self.headers.get(&header.prev_block_hash).ok_or(BlockchainError::OrphanBlock)?;
+        if header.height != parent.height + 1 {
+            return Err(BlockchainError::InvalidBlock(format!(
+                "Header at height {} does not follow its parent at height {}", header.height, parent.height
+            )));
+        }
+        if hash[0] >= pow_threshold(header.difficulty_target) {
+            return Err(BlockchainError::InvalidBlock("Header does not meet its difficulty target".to_string()));
+        }
+        let now = self.network_time.as_ref().map_or_else(now, |time| time.adjusted_time());
+        if header.timestamp as u64 > now + MAX_FUTURE_SECS {
+            return Err(BlockchainError::InvalidBlock("Header timestamp too far in the future".to_string()));
+        }
+
+        let height = header.height as u64;
+        let prev_hash = header.prev_block_hash;
+        let parent_on_best = self.hash_at(height - 1) == Some(prev_hash);
+        self.headers.insert(hash, header);
+
+        let (tip_height, tip_hash) = self.tip();
+        if prev_hash == tip_hash {
+            self.best.push(hash);
+            return Ok(HeaderOutcome::Extended);
+        }
+
+        let fork_height = self.fork_height(hash);
+        if height > tip_height {
+            let depth = tip_height - fork_height;
+            self.switch_to(hash, fork_height);
+            self.alert(ForkKind::Reorg, fork_height, hash, tip_hash, depth);
+            return Ok(HeaderOutcome::Reorg { depth });
+        }
+        // Alert once per branch, when it splits off the best chain
+        if parent_on_best {
+            self.alert(ForkKind::SideBranch, fork_height, tip_hash, hash, height - fork_height);
+        }
+        Ok(HeaderOutcome::SideBranch)
+    }
+
+    /// Height of the last best-chain block that `hash` descends from
+    fn fork_height(&self, mut hash: Hash256) -> u64 {
+        loop {
+            let header = &self.headers[&hash];
+            if self.hash_at(header.height as u64) == Some(hash) {
+                return header.height as u64;
+            }
+            hash = header.prev_block_hash;
+        }
+    }
+
+    /// Make the branch ending at `tip` the best chain
+    fn switch_to(&mut self, tip: Hash256, fork_height: u64) {
+        let mut branch = Vec::new();
+        let mut hash = tip;
+        while self.hash_at(self.headers[&hash].height as u64) != Some(hash) {
+            branch.push(hash);
+            hash = self.headers[&hash].prev_block_hash;
+        }
+        self.best.truncate(fork_height as usize + 1);
+        self.best.extend(branch.into_iter().rev());
+    }
+
+    fn alert(&mut self, kind: ForkKind, fork_height: u64, best: Hash256, competing: Hash256, depth: u64) {
+        if self.alerts.len() == MAX_ALERTS {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back(ForkAlert {
+            kind,
+            fork_height,
+            best_hash: hex::encode(best),
+            competing_hash: hex::encode(competing),
+            depth,
+            detected_at: now(),
+        });
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn child(parent: &BlockHeader, nonce_seed: u32) -> BlockHeader {
+        // Below 0x1b000000 a hash needs a first byte under 16
+        let mut header = BlockHeader::new(1, parent.calculate_hash(), [0u8; 32], 0x1a00ffff, parent.height + 1);
+        header.timestamp = parent.timestamp + 1;
+        header.nonce = nonce_seed;
+        while header.calculate_hash()[0] >= pow_threshold(header.difficulty_target) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn genesis() -> BlockHeader {
+        let mut header = BlockHeader::new(1, [0u8; 32], [0u8; 32], 0x1a00ffff, 0);
+        header.timestamp = 1_700_000_000;
+        header
+    }
+
+    #[test]
+    fn test_follows_longest_branch_and_alerts() {
+        let genesis = genesis();
+        let mut chain = HeaderChain::new(genesis.clone());
+        let a1 = child(&genesis, 0);
+        let a2 = child(&a1, 0);
+        assert_eq!(chain.add_header(a1.clone()).unwrap(), HeaderOutcome::Extended);
+        assert_eq!(chain.add_header(a2.clone()).unwrap(), HeaderOutcome::Extended);
+        assert_eq!(chain.add_header(a2.clone()).unwrap(), HeaderOutcome::Duplicate);
+
+        // A competing branch from a1 raises one alert, then overtakes
+        let b2 = child(&a1, 1_000_000);
+        let b3 = child(&b2, 0);
+        assert_eq!(chain.add_header(b2).unwrap(), HeaderOutcome::SideBranch);
+        assert_eq!(chain.alerts(10).len(), 1);
+        assert_eq!(chain.add_header(b3.clone()).unwrap(), HeaderOutcome::Reorg { depth: 1 });
+        assert_eq!(chain.tip(), (3, b3.calculate_hash()));
+
+        let tips = chain.tips(10);
+        assert_eq!(tips.len(), 2);
+        assert_eq!((tips[0].status, tips[0].height), (TipStatus::Active, 3));
+        assert_eq!((tips[1].status, tips[1].branch_len), (TipStatus::ValidHeaders, 1));
+
+        let alerts = chain.alerts(10);
+        assert_eq!(alerts[0].kind, ForkKind::Reorg);
+        assert_eq!(alerts[0].fork_height, 1);
+        assert_eq!(alerts[0].competing_hash, hex::encode(a2.calculate_hash()));
+    }
+
+    #[test]
+    fn test_rejects_unlinked_headers() {
+        let genesis = genesis();
+        let mut chain = HeaderChain::new(genesis.clone());
+        let a1 = child(&genesis, 0);
+        let a2 = child(&a1, 0);
+        assert!(matches!(chain.add_header(a2), Err(BlockchainError::OrphanBlock)));
+
+        let mut skipped = child(&genesis, 0);
+        skipped.height = 5;
+        assert!(chain.add_header(skipped).is_err());
+        assert_eq!(chain.tip().0, 0);
+    }
+}
@@ -0,0 +1,276 @@
+//! Signed payment invoices
+//!
+//! An invoice asks for a fixed amount to a destination address before an
+//! expiry time. The issuer signs a digest of every field, so a payer who
+//! scans the QR code can check that nothing was altered on the way and that
+//! it was issued by a key they trust. Invoices travel as `edunet:` URIs that
+//! extend the plain payment-request format with the invoice fields, so older
+//! scanners still see the address, amount and message.
+
+use crate::amount::Amount;
+use crate::{crypto, Address, BlockchainError, Hash256, PrivateKey, PublicKey, Result};
+use chrono::Utc;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const INVOICE_TAG: &[u8] = b"EDU-INVOICE";
+
+/// URI scheme shared with plain payment requests
+pub const INVOICE_URI_SCHEME: &str = "edunet:";
+
+/// Longest memo an invoice may carry
+pub const MAX_MEMO_LEN: usize = 256;
+
+/// A signed request for payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invoice {
+    /// Random invoice identifier (hex)
+    pub id: String,
+    /// Address the payment must go to
+    pub destination: Address,
+    /// Requested amount in satoshis
+    pub amount: u64,
+    pub memo: Option<String>,
+    /// Unix timestamp the invoice was issued at
+    pub created_at: i64,
+    /// Unix timestamp after which the invoice can no longer be paid
+    pub expires_at: i64,
+    #[serde(with = "hex_bytes")]
+    pub issuer_public_key: PublicKey,
+    /// DER-encoded ECDSA signature over `digest()`
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl Invoice {
+    /// Issue and sign an invoice valid for `ttl_secs` from now
+    pub fn create(
+        destination: Address,
+        amount: u64,
+        memo: Option<String>,
+        ttl_secs: i64,
+        issuer_key: &PrivateKey,
+    ) -> Result<Self> {
+        let mut invoice = Self::unsigned(destination, amount, memo, ttl_secs, crypto::derive_public_key(issuer_key)?)?;
+        invoice.signature = crypto::sign_hash(&invoice.digest(), issuer_key)?;
+        Ok(invoice)
+    }
+
+    /// Invoice valid for `ttl_secs` from now, for the issuer to sign
+    /// `digest()` and fill in `signature`
+    pub fn unsigned(
+        destination: Address,
+        amount: u64,
+        memo: Option<String>,
+        ttl_secs: i64,
+        issuer_public_key: PublicKey,
+    ) -> Result<Self> {
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        let created_at = Utc::now().timestamp();
+
+        let invoice = Self {
+            id: hex::encode(id),
+            destination,
+            amount,
+            memo: memo.filter(|m| !m.is_empty()),
+            created_at,
+            expires_at: created_at.saturating_add(ttl_secs),
+            issuer_public_key,
+            signature: Vec::new(),
+        };
+        invoice.check_fields()?;
+        Ok(invoice)
+    }
+
+    /// Digest committing to every field except the signature
+    pub fn digest(&self) -> Hash256 {
+        let mut data = Vec::with_capacity(256);
+        data.extend_from_slice(INVOICE_TAG);
+        for field in [self.id.as_bytes(), self.destination.as_bytes(), self.memo.as_deref().unwrap_or("").as_bytes()] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field);
+        }
+        data.push(self.memo.is_some() as u8);
+        data.extend_from_slice(&self.amount.to_le_bytes());
+        data.extend_from_slice(&self.created_at.to_le_bytes());
+        data.extend_from_slice(&self.expires_at.to_le_bytes());
+        data.extend_from_slice(&self.issuer_public_key);
+        crypto::sha256(&data)
+    }
+
+    /// Check the fields and the issuer's signature.
+    ///
+    /// This does not check expiry, nor that the issuer key is one the caller
+    /// trusts; compare `issuer_public_key` against the expected key.
+    pub fn verify(&self) -> Result<()> {
+        self.check_fields()?;
+        if !crypto::verify_signature(&self.signature, &self.issuer_public_key, &self.digest())? {
+            return Err(BlockchainError::InvalidSignature(format!("Invoice {} signature does not verify", self.id)));
+        }
+        Ok(())
+    }
+
+    fn check_fields(&self) -> Result<()> {
+        if self.amount == 0 {
+            return Err(BlockchainError::InvalidInput("Invoice amount must be positive".to_string()));
+        }
+        if self.expires_at <= self.created_at {
+            return Err(BlockchainError::InvalidInput("Invoice must expire after it is created".to_string()));
+        }
+        if self.destination.is_empty() {
+            return Err(BlockchainError::InvalidAddress("Invoice has no destination".to_string()));
+        }
+        if self.memo.as_ref().map_or(0, |m| m.len()) > MAX_MEMO_LEN {
+            return Err(BlockchainError::InvalidInput(format!("Invoice memo exceeds {} bytes", MAX_MEMO_LEN)));
+        }
+        Ok(())
+    }
+
+    /// Whether the invoice has expired at the given unix time
+    pub fn is_expired_at(&self, now: i64) -> bool {
+        now > self.expires_at
+    }
+
+    /// Whether the invoice has expired
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now().timestamp())
+    }
+
+    /// Whether a payment settles this invoice: right destination, at least
+    /// the requested amount, made while the invoice was open
+    pub fn matches_payment(&self, to_address: &str, amount: u64, timestamp: i64) -> bool {
+        to_address == self.destination
+            && amount >= self.amount
+            && timestamp >= self.created_at
+            && !self.is_expired_at(timestamp)
+    }
+
+    /// Encode as an `edunet:` URI for QR codes
+    pub fn to_uri(&self) -> String {
+        let mut params = vec![
+            format!("amount={}", Amount::from_sat(self.amount).to_edu_string()),
+            format!("sats={}", self.amount),
+        ];
+        if let Some(memo) = &self.memo {
+            params.push(format!("message={}", urlencoding::encode(memo)));
+        }
+        params.push(format!("invoice={}", self.id));
+        params.push(format!("created={}", self.created_at));
+        params.push(format!("expires={}", self.expires_at));
+        params.push(format!("issuer={}", hex::encode(&self.issuer_public_key)));
+        params.push(format!("sig={}", hex::encode(&self.signature)));
+        format!("{}{}?{}", INVOICE_URI_SCHEME, self.destination, params.join("&"))
+    }
+
+    /// Parse an `edunet:` invoice URI and verify its signature
+    pub fn from_uri(uri: &str) -> Result<Self> {
+        let rest = uri.strip_prefix(INVOICE_URI_SCHEME)
+            .ok_or_else(|| BlockchainError::InvalidInput("Not an edunet: URI".to_string()))?;
+        let (destination, query) = rest.split_once('?')
+            .ok_or_else(|| BlockchainError::InvalidInput("URI carries no invoice".to_string()))?;
+
+        let mut params = HashMap::new();
+        for pair in query.split('&') {
+            if let Some((key, value)) = pair.split_once('=') {
+                params.insert(key, value);
+            }
+        }
+        let field = |key: &str| params.get(key).copied()
+            .ok_or_else(|| BlockchainError::InvalidInput(format!("Invoice URI missing {}", key)));
+        let number = |key: &str| field(key)?.parse::<i64>()
+            .map_err(|_| BlockchainError::InvalidInput(format!("Invalid {} in invoice URI", key)));
+        let bytes = |key: &str| hex::decode(field(key)?)
+            .map_err(|_| BlockchainError::InvalidInput(format!("Invalid {} in invoice URI", key)));
+
+        let invoice = Self {
+            id: field("invoice")?.to_string(),
+            destination: destination.to_string(),
+            amount: field("sats")?.parse()
+                .map_err(|_| BlockchainError::InvalidInput("Invalid sats in invoice URI".to_string()))?,
+            memo: params.get("message")
+                .map(|m| urlencoding::decode(m).map(|m| m.into_owned()))
+                .transpose()?,
+            created_at: number("created")?,
+            expires_at: number("expires")?,
+            issuer_public_key: bytes("issuer")?,
+            signature: bytes("sig")?,
+        };
+        invoice.verify()?;
+        Ok(invoice)
+    }
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issuer_key() -> PrivateKey {
+        crypto::sha256(b"invoice test issuer")
+    }
+
+    #[test]
+    fn test_invoice_signing_and_tampering() {
+        let invoice = Invoice::create(
+            "edu1qMerchant".to_string(), 250_000_000, Some("Textbook".to_string()), 3600, &issuer_key(),
+        ).unwrap();
+        assert!(invoice.verify().is_ok());
+        assert_eq!(invoice.issuer_public_key, crypto::derive_public_key(&issuer_key()).unwrap());
+
+        let mut tampered = invoice.clone();
+        tampered.destination = "edu1qAttacker".to_string();
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = invoice.clone();
+        tampered.expires_at += 3600;
+        assert!(tampered.verify().is_err());
+
+        assert!(Invoice::create("edu1qMerchant".to_string(), 0, None, 3600, &issuer_key()).is_err());
+        assert!(Invoice::create("edu1qMerchant".to_string(), 1, None, 0, &issuer_key()).is_err());
+    }
+
+    #[test]
+    fn test_invoice_uri_roundtrip() {
+        let invoice = Invoice::create(
+            "edu1qMerchant".to_string(), 150_000_000, Some("Coffee & cake".to_string()), 600, &issuer_key(),
+        ).unwrap();
+        let uri = invoice.to_uri();
+        assert!(uri.starts_with("edunet:edu1qMerchant?amount=1.5&"));
+        assert_eq!(Invoice::from_uri(&uri).unwrap(), invoice);
+
+        // Editing the amount in the URI breaks the signature
+        let edited = uri.replace("sats=150000000", "sats=1");
+        assert!(Invoice::from_uri(&edited).is_err());
+        // A plain payment request isn't an invoice
+        assert!(Invoice::from_uri("edunet:edu1qMerchant?amount=1.5").is_err());
+    }
+
+    #[test]
+    fn test_invoice_payment_matching() {
+        let invoice = Invoice::create("edu1qMerchant".to_string(), 1_000, None, 60, &issuer_key()).unwrap();
+        let during = invoice.created_at + 30;
+
+        assert!(invoice.matches_payment("edu1qMerchant", 1_000, during));
+        assert!(invoice.matches_payment("edu1qMerchant", 1_500, during));
+        assert!(!invoice.matches_payment("edu1qMerchant", 999, during));
+        assert!(!invoice.matches_payment("edu1qSomeoneElse", 1_000, during));
+        assert!(!invoice.matches_payment("edu1qMerchant", 1_000, invoice.created_at - 1));
+        assert!(!invoice.matches_payment("edu1qMerchant", 1_000, invoice.expires_at + 1));
+        assert!(invoice.is_expired_at(invoice.expires_at + 1));
+        assert!(!invoice.is_expired());
+    }
+}
@@ -0,0 +1,327 @@
+//! IPFS media pinning for NFT and marketplace content
+//!
+//! Uploaded media is pinned to a configurable IPFS node through its HTTP API
+//! and referenced everywhere else (on-chain, in the database) only by content
+//! hashes: the IPFS CID plus the SHA-256 of the raw bytes. Media fetched back
+//! through a gateway is re-hashed and rejected if it does not match, so a
+//! misbehaving gateway cannot swap the image behind an NFT.
+
+use crate::{BlockchainError, Result, Hash256};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// URI scheme used for media references
+pub const IPFS_URI_SCHEME: &str = "ipfs://";
+
+/// Largest file the IPFS node stores as a single raw block (default chunker size).
+/// CIDs of files up to this size can be recomputed locally.
+pub const RAW_BLOCK_LIMIT: usize = 256 * 1024;
+
+/// Multicodec and multihash prefixes for a CIDv1 raw block hashed with SHA-256
+const CIDV1_RAW_SHA256_PREFIX: [u8; 4] = [0x01, 0x55, 0x12, 0x20];
+
+/// IPFS node and upload settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpfsConfig {
+    /// Base URL of the node's HTTP API (Kubo RPC)
+    pub api_url: String,
+    /// Base URL of the gateway used for retrieval
+    pub gateway_url: String,
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+    /// Maximum accepted upload size in bytes
+    pub max_upload_bytes: usize,
+    /// MIME types accepted for upload
+    pub allowed_content_types: Vec<String>,
+}
+
+impl Default for IpfsConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "http://127.0.0.1:5001".to_string(),
+            gateway_url: "http://127.0.0.1:8080".to_string(),
+            timeout_secs: 30,
+            max_upload_bytes: 10 * 1024 * 1024, // 10 MB
+            allowed_content_types: vec![
+                "image/png".to_string(),
+                "image/jpeg".to_string(),
+                "image/gif".to_string(),
+                "image/webp".to_string(),
+                "video/mp4".to_string(),
+            ],
+        }
+    }
+}
+
+/// Content-addressed reference to pinned media
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaRef {
+    /// IPFS content identifier
+    pub cid: String,
+    /// SHA-256 of the raw media bytes
+    #[serde(with = "hex_hash")]
+    pub sha256: Hash256,
+}
+
+impl MediaRef {
+    /// Reference for the given content and CID
+    pub fn new(cid: String, content: &[u8]) -> Result<Self> {
+        validate_cid(&cid)?;
+        Ok(Self { cid, sha256: crate::crypto::sha256(content) })
+    }
+
+    /// Encode as `ipfs://<cid>#sha256=<hex>` for storage in URL columns
+    pub fn to_uri(&self) -> String {
+        format!("{}{}#sha256={}", IPFS_URI_SCHEME, self.cid, hex::encode(self.sha256))
+    }
+
+    /// Parse a URI produced by [`to_uri`](Self::to_uri)
+    pub fn parse_uri(uri: &str) -> Result<Self> {
+        let rest = uri.strip_prefix(IPFS_URI_SCHEME)
+            .ok_or_else(|| BlockchainError::InvalidInput(format!("Not an {} URI", IPFS_URI_SCHEME)))?;
+        let (cid, fragment) = rest.split_once('#')
+            .ok_or_else(|| BlockchainError::InvalidInput("Media URI is missing its content hash".to_string()))?;
+        let hash_hex = fragment.strip_prefix("sha256=")
+            .ok_or_else(|| BlockchainError::InvalidInput("Media URI hash must be sha256".to_string()))?;
+
+        Self::from_parts(cid, hash_hex)
+    }
+
+    /// Build a reference from a CID and hex-encoded SHA-256
+    pub fn from_parts(cid: &str, sha256_hex: &str) -> Result<Self> {
+        validate_cid(cid)?;
+        Ok(Self { cid: cid.to_string(), sha256: parse_hash(sha256_hex)? })
+    }
+
+    /// Check fetched bytes against this reference
+    pub fn verify(&self, content: &[u8]) -> Result<()> {
+        if crate::crypto::sha256(content) != self.sha256 {
+            return Err(BlockchainError::IpfsError(format!("Content hash mismatch for {}", self.cid)));
+        }
+        // Single-block files can be checked against the CID itself
+        if content.len() <= RAW_BLOCK_LIMIT && self.cid.starts_with("bafkrei") && raw_cid(content) != self.cid {
+            return Err(BlockchainError::IpfsError(format!("Content does not match CID {}", self.cid)));
+        }
+        Ok(())
+    }
+}
+
+/// Result of pinning an upload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedMedia {
+    /// Content reference to store
+    pub media: MediaRef,
+    /// `ipfs://` URI form of the reference
+    pub uri: String,
+    /// Size in bytes
+    pub size: usize,
+    /// MIME type supplied with the upload
+    pub content_type: String,
+}
+
+/// Response of the Kubo `add` endpoint
+#[derive(Debug, Deserialize)]
+struct AddResponse {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+/// Client for pinning and retrieving media
+pub struct IpfsClient {
+    config: IpfsConfig,
+    http: reqwest::Client,
+}
+
+impl IpfsClient {
+    /// Create a client for the configured node
+    pub fn new(config: IpfsConfig) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| BlockchainError::IpfsError(format!("Failed to build HTTP client: {}", e)))?;
+        Ok(Self { config, http })
+    }
+
+    /// Client configuration
+    pub fn config(&self) -> &IpfsConfig {
+        &self.config
+    }
+
+    /// Check an upload against size and content type limits
+    pub fn validate_upload(&self, content: &[u8], content_type: &str) -> Result<()> {
+        if content.is_empty() {
+            return Err(BlockchainError::InvalidInput("Upload is empty".to_string()));
+        }
+        if content.len() > self.config.max_upload_bytes {
+            return Err(BlockchainError::InvalidInput(format!(
+                "Upload of {} bytes exceeds limit of {} bytes", content.len(), self.config.max_upload_bytes
+            )));
+        }
+        if !self.config.allowed_content_types.iter().any(|t| t == content_type) {
+            return Err(BlockchainError::InvalidInput(format!("Content type {} not allowed", content_type)));
+        }
+        Ok(())
+    }
+
+    /// Pin content to the node and return its content reference
+    pub async fn pin(&self, content: Vec<u8>, content_type: &str) -> Result<PinnedMedia> {
+        self.validate_upload(&content, content_type)?;
+
+        let size = content.len();
+        let sha256 = crate::crypto::sha256(&content);
+        let expected_cid = (size <= RAW_BLOCK_LIMIT).then(|| raw_cid(&content));
+
+        let part = reqwest::multipart::Part::bytes(content)
+            .file_name("media")
+            .mime_str(content_type)
+            .map_err(|e| BlockchainError::InvalidInput(format!("Invalid content type: {}", e)))?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        let url = format!(
+            "{}/api/v0/add?pin=true&cid-version=1&raw-leaves=true",
+            self.config.api_url.trim_end_matches('/')
+        );
+        let response = self.http.post(&url).multipart(form).send().await
+            .map_err(|e| BlockchainError::IpfsError(format!("Pin request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(BlockchainError::IpfsError(format!("IPFS node returned {}", response.status())));
+        }
+        let added: AddResponse = response.json().await
+            .map_err(|e| BlockchainError::IpfsError(format!("Invalid add response: {}", e)))?;
+
+        // Don't trust the node's CID for content we can hash ourselves
+        if let Some(expected) = expected_cid {
+            if added.hash != expected {
+                return Err(BlockchainError::IpfsError(format!(
+                    "Node returned CID {} but content hashes to {}", added.hash, expected
+                )));
+            }
+        }
+
+        validate_cid(&added.hash)?;
+        let media = MediaRef { cid: added.hash, sha256 };
+        Ok(PinnedMedia {
+            uri: media.to_uri(),
+            media,
+            size,
+            content_type: content_type.to_string(),
+        })
+    }
+
+    /// Fetch media through the gateway and verify it against its reference
+    pub async fn fetch(&self, media: &MediaRef) -> Result<Vec<u8>> {
+        let url = format!("{}/ipfs/{}", self.config.gateway_url.trim_end_matches('/'), media.cid);
+        let response = self.http.get(&url).send().await
+            .map_err(|e| BlockchainError::IpfsError(format!("Gateway request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(BlockchainError::IpfsError(format!("Gateway returned {}", response.status())));
+        }
+
+        let content = response.bytes().await
+            .map_err(|e| BlockchainError::IpfsError(format!("Failed to read gateway response: {}", e)))?;
+        if content.len() > self.config.max_upload_bytes {
+            return Err(BlockchainError::IpfsError(format!("Content for {} exceeds size limit", media.cid)));
+        }
+
+        media.verify(&content)?;
+        Ok(content.to_vec())
+    }
+}
+
+/// CIDv1 (raw codec, SHA-256) of a single-block file, as the node reports it
+pub fn raw_cid(content: &[u8]) -> String {
+    let mut bytes = CIDV1_RAW_SHA256_PREFIX.to_vec();
+    bytes.extend_from_slice(&crate::crypto::sha256(content));
+    format!("b{}", base32_lower(&bytes))
+}
+
+/// Accept CIDv0 (`Qm...`) and base32 CIDv1 (`b...`) identifiers
+fn validate_cid(cid: &str) -> Result<()> {
+    let valid = if let Some(body) = cid.strip_prefix('b') {
+        (50..=100).contains(&body.len())
+            && body.chars().all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+    } else {
+        cid.len() == 46 && cid.starts_with("Qm") && bs58::decode(cid).into_vec().is_ok()
+    };
+
+    if !valid {
+        return Err(BlockchainError::InvalidInput(format!("Invalid CID: {}", cid)));
+    }
+    Ok(())
+}
+
+fn parse_hash(hash_hex: &str) -> Result<Hash256> {
+    let bytes = hex::decode(hash_hex)
+        .map_err(|_| BlockchainError::InvalidInput("Invalid content hash".to_string()))?;
+    bytes.try_into()
+        .map_err(|_| BlockchainError::InvalidInput("Content hash must be 32 bytes".to_string()))
+}
+
+/// RFC 4648 base32, lowercase, without padding (multibase `b`)
+fn base32_lower(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+mod hex_hash {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &[u8; 32], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<[u8; 32], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_hash(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_cid_matches_ipfs() {
+        assert_eq!(raw_cid(b""), "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku");
+        assert_eq!(raw_cid(b"hello world"), "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e");
+    }
+
+    #[test]
+    fn test_media_uri_roundtrip_and_verify() {
+        let content = b"png bytes".to_vec();
+        let media = MediaRef::new(raw_cid(&content), &content).unwrap();
+
+        let parsed = MediaRef::parse_uri(&media.to_uri()).unwrap();
+        assert_eq!(parsed, media);
+        assert!(parsed.verify(&content).is_ok());
+        assert!(parsed.verify(b"swapped image").is_err());
+
+        assert!(MediaRef::parse_uri("https://example.com/cat.png").is_err());
+        assert!(MediaRef::parse_uri(&format!("ipfs://{}", media.cid)).is_err());
+        assert!(MediaRef::parse_uri("ipfs://not-a-cid#sha256=00").is_err());
+    }
+
+    #[test]
+    fn test_upload_limits() {
+        let client = IpfsClient::new(IpfsConfig { max_upload_bytes: 4, ..Default::default() }).unwrap();
+        assert!(client.validate_upload(b"abcd", "image/png").is_ok());
+        assert!(client.validate_upload(b"abcde", "image/png").is_err());
+        assert!(client.validate_upload(b"abcd", "text/html").is_err());
+        assert!(client.validate_upload(b"", "image/png").is_err());
+    }
+}
@@ -5,8 +5,8 @@ use serde::{Deserialize, Serialize};
 /// 256-bit hash type used throughout the blockchain
 pub type Hash256 = [u8; 32];
 
-/// Amount type with satoshi precision (1e-8)  
-pub type Amount = u64;
+/// Amount type with satoshi precision (1e-8)
+pub use amount::Amount;
 
 /// Block height type
 pub type BlockHeight = u64;
@@ -127,6 +127,9 @@ pub enum BlockchainError {
     #[error("Invalid multi-signature configuration: {0}")]
     InvalidMultiSig(String),
     
+    #[error("Invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+    
     #[error("Signing error: {0}")]
     SigningError(String),
     
@@ -147,6 +150,15 @@ pub enum BlockchainError {
     
     #[error("Storage error: {0}")]
     StorageError(String),
+    
+    #[error("IPFS error: {0}")]
+    IpfsError(String),
+    
+    #[error("Price feed error: {0}")]
+    PriceFeedError(String),
+
+    #[error("Spend policy violated: {}", spend_policy::describe(.0))]
+    PolicyViolation(Vec<spend_policy::PolicyViolation>),
 }
 
 impl From<std::string::FromUtf8Error> for BlockchainError {
@@ -157,15 +169,20 @@ impl From<std::string::FromUtf8Error> for BlockchainError {
 
 pub type Result<T> = std::result::Result<T, BlockchainError>;
 
+/// Cargo features this build of the core was compiled with
+pub const FEATURES: &[&str] = &[
+    #[cfg(feature = "simd")]
+    "simd",
+    #[cfg(feature = "network")]
+    "network",
+];
+
 /// Utility functions
 pub mod utils {
     use super::*;
-    use sha2::{Digest, Sha256};
     
     pub fn double_sha256(data: &[u8]) -> Hash256 {
-        let first_hash = Sha256::digest(data);
-        let second_hash = Sha256::digest(&first_hash);
-        second_hash.into()
+        crate::hashing::double_sha256(data)
     }
     
     pub fn bytes_to_hex(bytes: &[u8]) -> String {
@@ -189,10 +206,50 @@ pub mod genesis;
 pub mod mempool;
 pub mod hd_wallet;
 pub mod advanced_wallet;
+pub mod descriptor;  // Output script descriptors
+#[cfg(feature = "network")]
 pub mod api_server;
+#[cfg(feature = "network")]
 pub mod rest_api;
 pub mod script_utils;
 pub mod sync;
 pub mod storage;
 pub mod contracts;  // Smart contract execution (EVM)
 pub mod event_indexer;  // Event indexing and filtering
+pub mod asset_registry;  // Token/NFT metadata registry
+#[cfg(feature = "network")]
+pub mod ipfs;  // IPFS media pinning
+pub mod reserves;  // Proof-of-reserves attestations
+#[cfg(feature = "network")]
+pub mod fiat;  // Fiat display conversions
+pub mod invoice;  // Signed payment invoices
+pub mod versionbits;  // Soft-fork deployment signaling
+pub mod supply_audit;  // Supply invariant checks
+pub mod audit_log;  // Hash-chained audit records
+pub mod sighash;  // Signature hashes with cached midstates
+pub mod bloom;  // Bloom filters
+pub mod signature;  // Single and batch signature verification
+pub mod hashing;  // Double-SHA256 with SIMD backends
+pub mod message;  // Signed messages proving address control
+pub mod attestation;  // Issuer-signed identity attestations
+pub mod naming;  // Human-readable payment names
+pub mod chain_index;  // Transaction and address indexes
+pub mod header_chain;  // Header-only best chain tracking
+pub mod network_time;  // Peer-adjusted clock
+pub mod script_trace;  // Step-by-step script execution traces
+pub mod contract_calls;  // Inter-contract call limits and call trees
+pub mod contract_trace;  // Opcode-level contract execution traces
+pub mod contract_precompiles;  // Native crypto precompiles for contracts
+pub mod contract_proxy;  // EIP-1967 proxy contract slots
+pub mod abi;  // Contract ABI encoding and decoding
+pub mod contract_state;  // Block-scoped contract state with rollback
+pub mod address;  // Address validation and checksums
+pub mod amount;  // Satoshi amounts with checked arithmetic
+pub mod spend_policy;  // Per-wallet spending limits checked before signing
+pub mod offline_signing;  // Air-gapped signing over QR code sequences
+pub mod private_message;  // ECIES-encrypted wallet-to-wallet messages
+pub mod schema;  // Forward-compatible transaction and block encoding
+pub mod canonical;  // Hex hashes and string satoshis in API responses
+pub mod locale;  // Locale-aware amount and address rendering
+pub mod qr;  // QR code rendering shared by the API, web and print tools
+pub mod merkle;  // Incremental merkle trees for block templates
@@ -0,0 +1,282 @@
+//! Locale-aware rendering of amounts and addresses
+//!
+//! Campuses show balances to people used to different number formats:
+//! "1,234.5 EDU" in Boston is "1.234,5 EDU" in Munich and "1,234.5" groups
+//! as "12,34,567.5" in Pune. Formatting lives here, behind the REST layer,
+//! so every frontend prints the same digits the same way instead of each
+//! reimplementing it on top of floats.
+//!
+//! Amounts are formatted from their exact satoshis. Rounding to fewer
+//! decimal places rounds half up, and never goes through `f64`.
+
+use crate::address::{AddressCodec, AddressError};
+use crate::amount::{Amount, DECIMALS, SATOSHIS_PER_EDU};
+use crate::{BlockchainError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Characters of an address shown at each end of its short form
+const SHORT_ADDRESS_CHARS: usize = 6;
+
+/// Characters per group in an address's grouped form
+const ADDRESS_GROUP: usize = 4;
+
+/// Number formatting conventions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// 1,234,567.5
+    #[default]
+    En,
+    /// 1.234.567,5
+    De,
+    /// 1 234 567,5 with narrow no-break spaces
+    Fr,
+    /// 1.234.567,5
+    Es,
+    /// 1.234.567,5
+    Pt,
+    /// 12,34,567.5, lakh and crore grouping
+    In,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 6] = [Locale::En, Locale::De, Locale::Fr, Locale::Es, Locale::Pt, Locale::In];
+
+    /// Pick the conventions for a BCP 47 tag such as "de-DE" or "en_IN"
+    pub fn from_tag(tag: &str) -> Result<Locale> {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        let mut parts = tag.split('-');
+        let language = parts.next().unwrap_or_default();
+        let region = parts.find(|part| part.len() == 2);
+        match (language, region) {
+            ("hi", _) | ("en", Some("in")) => Ok(Locale::In),
+            ("en", _) => Ok(Locale::En),
+            ("de", _) => Ok(Locale::De),
+            ("fr", _) => Ok(Locale::Fr),
+            ("es", _) => Ok(Locale::Es),
+            ("pt", _) => Ok(Locale::Pt),
+            _ => Err(BlockchainError::InvalidInput(format!("Unsupported locale '{}'", tag))),
+        }
+    }
+
+    pub fn group_separator(self) -> &'static str {
+        match self {
+            Locale::En | Locale::In => ",",
+            Locale::De | Locale::Es | Locale::Pt => ".",
+            Locale::Fr => "\u{202f}",
+        }
+    }
+
+    pub fn decimal_separator(self) -> &'static str {
+        match self {
+            Locale::En | Locale::In => ".",
+            Locale::De | Locale::Fr | Locale::Es | Locale::Pt => ",",
+        }
+    }
+
+    /// Insert group separators into a string of digits
+    fn group(self, digits: &str) -> String {
+        // Indian grouping takes the last three digits, then pairs
+        let (head, tail) = match self {
+            Locale::In if digits.len() > 3 => digits.split_at(digits.len() - 3),
+            _ => ("", digits),
+        };
+        let head_groups = chunks_from_right(head, 2);
+        let tail_groups = chunks_from_right(tail, 3);
+        head_groups.into_iter().chain(tail_groups).collect::<Vec<_>>().join(self.group_separator())
+    }
+}
+
+fn chunks_from_right(digits: &str, size: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut end = digits.len();
+    while end > 0 {
+        let start = end.saturating_sub(size);
+        chunks.push(&digits[start..end]);
+        end = start;
+    }
+    chunks.reverse();
+    chunks
+}
+
+/// Unit an amount is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Edu,
+    Sat,
+}
+
+impl Unit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Edu => "EDU",
+            Unit::Sat => "sat",
+        }
+    }
+}
+
+/// How to render an amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmountFormat {
+    pub locale: Locale,
+    pub unit: Unit,
+    /// Decimal places for EDU amounts, at most 8. `None` shows as many as
+    /// the amount needs
+    pub decimals: Option<usize>,
+    /// Append the unit symbol
+    pub show_unit: bool,
+}
+
+impl Default for AmountFormat {
+    fn default() -> Self {
+        Self { locale: Locale::default(), unit: Unit::Edu, decimals: None, show_unit: true }
+    }
+}
+
+impl AmountFormat {
+    pub fn validate(&self) -> Result<()> {
+        match self.decimals {
+            Some(decimals) if decimals > DECIMALS => Err(BlockchainError::InvalidInput(format!(
+                "At most {} decimal places, got {}",
+                DECIMALS, decimals
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Render an amount, e.g. "1.234,5 EDU"
+    pub fn format(&self, amount: Amount) -> String {
+        let number = match self.unit {
+            Unit::Sat => self.locale.group(&amount.to_sat().to_string()),
+            Unit::Edu => self.format_edu(amount.to_sat()),
+        };
+        if self.show_unit {
+            format!("{} {}", number, self.unit.symbol())
+        } else {
+            number
+        }
+    }
+
+    fn format_edu(&self, satoshis: u64) -> String {
+        let (whole, fraction) = match self.decimals {
+            None => {
+                let fraction = format!("{:0width$}", satoshis % SATOSHIS_PER_EDU, width = DECIMALS);
+                (satoshis / SATOSHIS_PER_EDU, fraction.trim_end_matches('0').to_string())
+            }
+            Some(decimals) => {
+                let decimals = decimals.min(DECIMALS);
+                let step = 10u128.pow((DECIMALS - decimals) as u32);
+                let rounded = (satoshis as u128 + step / 2) / step;
+                let scale = 10u128.pow(decimals as u32);
+                let fraction = if decimals == 0 {
+                    String::new()
+                } else {
+                    format!("{:0width$}", rounded % scale, width = decimals)
+                };
+                ((rounded / scale) as u64, fraction)
+            }
+        };
+        let whole = self.locale.group(&whole.to_string());
+        if fraction.is_empty() {
+            whole
+        } else {
+            format!("{}{}{}", whole, self.locale.decimal_separator(), fraction)
+        }
+    }
+}
+
+/// How to render an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressStyle {
+    /// Unchanged
+    #[default]
+    Full,
+    /// Prefix, then groups of four characters, for reading out or copying by hand
+    Grouped,
+    /// First and last characters either side of an ellipsis, for tables
+    Short,
+}
+
+/// Render a valid address in a style. Invalid addresses are rejected
+/// rather than shown, so a frontend never displays one as if it were real.
+pub fn format_address(address: &str, style: AddressStyle) -> std::result::Result<String, AddressError> {
+    let kind = AddressCodec::validate(address)?;
+    let address = address.trim();
+    let body = &address[kind.prefix().len()..];
+    Ok(match style {
+        AddressStyle::Full => address.to_string(),
+        AddressStyle::Grouped => {
+            let groups: Vec<String> = body.as_bytes()
+                .chunks(ADDRESS_GROUP)
+                .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+                .collect();
+            format!("{} {}", kind.prefix(), groups.join(" "))
+        }
+        AddressStyle::Short if body.len() > SHORT_ADDRESS_CHARS * 2 => format!(
+            "{}{}…{}",
+            kind.prefix(),
+            &body[..SHORT_ADDRESS_CHARS],
+            &body[body.len() - SHORT_ADDRESS_CHARS..]
+        ),
+        AddressStyle::Short => address.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::AddressKind;
+
+    fn edu(locale: Locale, decimals: Option<usize>) -> AmountFormat {
+        AmountFormat { locale, decimals, ..AmountFormat::default() }
+    }
+
+    #[test]
+    fn test_locale_separators() {
+        let amount = Amount::from_sat(123_456_789_050_000_000);
+        assert_eq!(edu(Locale::En, None).format(amount), "1,234,567,890.5 EDU");
+        assert_eq!(edu(Locale::De, None).format(amount), "1.234.567.890,5 EDU");
+        assert_eq!(edu(Locale::Fr, None).format(amount), "1\u{202f}234\u{202f}567\u{202f}890,5 EDU");
+        assert_eq!(edu(Locale::In, None).format(amount), "1,23,45,67,890.5 EDU");
+        assert_eq!(edu(Locale::In, None).format(Amount::from_sat(99_900_000_000)), "999 EDU");
+
+        assert_eq!(Locale::from_tag("de-DE").unwrap(), Locale::De);
+        assert_eq!(Locale::from_tag("en_IN").unwrap(), Locale::In);
+        assert_eq!(Locale::from_tag("en-Latn-US").unwrap(), Locale::En);
+        assert!(Locale::from_tag("xx").is_err());
+    }
+
+    #[test]
+    fn test_decimals_round_exactly() {
+        let format = edu(Locale::En, Some(2));
+        assert_eq!(format.format(Amount::from_sat(150_000_000)), "1.50 EDU");
+        assert_eq!(format.format(Amount::from_sat(199_500_000)), "2.00 EDU");
+        assert_eq!(format.format(Amount::from_sat(199_499_999)), "1.99 EDU");
+        assert_eq!(edu(Locale::En, Some(0)).format(Amount::from_sat(49_999_999)), "0 EDU");
+        assert_eq!(edu(Locale::En, Some(8)).format(Amount::from_sat(1)), "0.00000001 EDU");
+        assert_eq!(edu(Locale::En, None).format(Amount::MAX), "184,467,440,737.09551615 EDU");
+        assert!(edu(Locale::En, Some(9)).validate().is_err());
+
+        let sats = AmountFormat { locale: Locale::De, unit: Unit::Sat, decimals: Some(2), show_unit: false };
+        assert_eq!(sats.format(Amount::from_sat(1_500_000)), "1.500.000");
+    }
+
+    #[test]
+    fn test_address_styles() {
+        let address = AddressCodec::encode_checked(AddressKind::P2pkh, &[9u8; 20]);
+        let body = &address["edu1q".len()..];
+
+        assert_eq!(format_address(&address, AddressStyle::Full).unwrap(), address);
+        let grouped = format_address(&address, AddressStyle::Grouped).unwrap();
+        assert_eq!(grouped.replace(' ', ""), address);
+        assert!(grouped.starts_with(&format!("edu1q {} ", &body[..4])));
+        let short = format_address(&address, AddressStyle::Short).unwrap();
+        assert_eq!(short, format!("edu1q{}…{}", &body[..6], &body[body.len() - 6..]));
+
+        assert!(format_address("edu1qnot-an-address", AddressStyle::Full).is_err());
+    }
+}
@@ -11,6 +11,10 @@ use crate::{
     Hash256, BlockchainError, Result,
     transaction::{Transaction, TransactionInput, TransactionOutput},
     consensus::{ConsensusValidator, TxValidationContext},
+    sighash::PRECOMPUTED_SIGHASH_DEPLOYMENT,
+    contract_state::transaction_sender,
+    schema::MAX_KNOWN_TX_VERSION,
+    utxo::{SpentStatus, UTXOSet, UTXO},
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -34,6 +38,60 @@ pub enum TransactionPriority {
 /// Transaction fee rate (satoshis per byte)
 pub type FeeRate = u64;
 
+/// Price per gas of a contract transaction that names none
+pub const LEGACY_GAS_PRICE: u64 = 1;
+
+/// Gas bid of a contract transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasPrice {
+    pub gas_limit: u64,
+    /// Most paid per unit of gas, priority fee included
+    pub max_fee_per_gas: u64,
+    /// Most paid per unit of gas above the base fee
+    pub max_priority_fee_per_gas: u64,
+}
+
+impl GasPrice {
+    /// Bid of a contract deployment or call; without one the transaction
+    /// pays `LEGACY_GAS_PRICE`, all of it as priority fee
+    pub fn from_transaction(transaction: &Transaction) -> Option<Self> {
+        if !transaction.is_contract_deployment() && !transaction.is_contract_call() {
+            return None;
+        }
+        let max_fee_per_gas = transaction.max_fee_per_gas.unwrap_or(LEGACY_GAS_PRICE);
+        Some(Self {
+            gas_limit: transaction.gas_limit.unwrap_or_default(),
+            max_fee_per_gas,
+            max_priority_fee_per_gas: transaction.max_priority_fee_per_gas.unwrap_or(max_fee_per_gas),
+        })
+    }
+
+    /// Why the bid can never be paid at `base_fee`, if it cannot
+    pub fn check(&self, base_fee: u64) -> std::result::Result<(), String> {
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return Err(format!(
+                "Priority fee {} above max fee {} per gas",
+                self.max_priority_fee_per_gas, self.max_fee_per_gas
+            ));
+        }
+        if self.max_fee_per_gas < base_fee {
+            return Err(format!("Max fee {} per gas below base fee {}", self.max_fee_per_gas, base_fee));
+        }
+        Ok(())
+    }
+
+    /// Price per gas paid at `base_fee`: the base fee and as much of the
+    /// priority fee as the max fee leaves room for
+    pub fn effective_price(&self, base_fee: u64) -> u64 {
+        self.max_fee_per_gas.min(base_fee.saturating_add(self.max_priority_fee_per_gas))
+    }
+
+    /// Most the transaction pays for gas at `base_fee`
+    pub fn gas_fee(&self, base_fee: u64) -> u64 {
+        self.gas_limit.saturating_mul(self.effective_price(base_fee))
+    }
+}
+
 /// Mempool entry containing transaction and metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MempoolEntry {
@@ -47,10 +105,16 @@ pub struct MempoolEntry {
     pub priority: TransactionPriority,
     /// Time when transaction entered mempool
     pub entry_time: SystemTime,
-    /// Size in bytes
+    /// Virtual size in bytes
     pub size: usize,
     /// Total fee paid
     pub fee: u64,
+    /// Gas bid, for contract transactions
+    #[serde(default)]
+    pub gas_price: Option<GasPrice>,
+    /// Fee rate with the gas fee counted in, which orders block templates
+    #[serde(default)]
+    pub effective_fee_rate: FeeRate,
     /// Ancestor count (for CPFP)
     pub ancestor_count: u32,
     /// Ancestor size (for CPFP)
@@ -119,6 +183,24 @@ pub struct MempoolConfig {
     pub purge_interval: Duration,
     /// Enable replace-by-fee
     pub enable_rbf: bool,
+    /// Which transactions go first when the mempool is full
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy,
+    /// Lowest max fee per gas a contract transaction may bid
+    #[serde(default = "default_base_fee_per_gas")]
+    pub base_fee_per_gas: u64,
+    /// How quickly the fee floor raised by evictions falls back; it halves
+    /// faster while the mempool is less than half full
+    #[serde(default = "default_min_fee_halflife")]
+    pub min_fee_halflife: Duration,
+}
+
+fn default_base_fee_per_gas() -> u64 {
+    LEGACY_GAS_PRICE
+}
+
+fn default_min_fee_halflife() -> Duration {
+    Duration::from_secs(12 * 60 * 60)
 }
 
 impl Default for MempoolConfig {
@@ -135,6 +217,119 @@ impl Default for MempoolConfig {
             max_descendant_size: 101 * 1024, // 101 KB
             purge_interval: Duration::from_secs(10 * 60), // 10 minutes
             enable_rbf: true,
+            eviction_policy: EvictionPolicy::default(),
+            base_fee_per_gas: default_base_fee_per_gas(),
+            min_fee_halflife: default_min_fee_halflife(),
+        }
+    }
+}
+
+/// Order in which transactions are evicted when the mempool is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Lowest fee rate first
+    #[default]
+    LowestFeeRate,
+    /// Longest waiting first
+    OldestFirst,
+    /// Largest first, freeing the most memory per eviction
+    LargestFirst,
+    /// Lowest package fee rate first, taking in-mempool descendants along;
+    /// a cheap parent with a well-paying child is kept
+    DescendantAware,
+}
+
+impl std::str::FromStr for EvictionPolicy {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "lowest_fee_rate" => Ok(EvictionPolicy::LowestFeeRate),
+            "oldest_first" => Ok(EvictionPolicy::OldestFirst),
+            "largest_first" => Ok(EvictionPolicy::LargestFirst),
+            "descendant_aware" => Ok(EvictionPolicy::DescendantAware),
+            _ => Err(BlockchainError::InvalidInput(format!(
+                "Unknown eviction policy '{}', expected lowest_fee_rate, oldest_first, largest_first or descendant_aware",
+                s
+            ))),
+        }
+    }
+}
+
+/// Mempool size and the fee rates it accepts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MempoolInfo {
+    pub transactions: usize,
+    pub memory_usage: usize,
+    pub max_memory_usage: usize,
+    /// Fraction of the tighter of the count and memory limits in use
+    pub usage: f64,
+    /// Configured floor
+    pub min_relay_fee_rate: FeeRate,
+    /// Floor in effect, raised above the configured one by recent evictions
+    pub min_fee_rate: FeeRate,
+}
+
+/// A transaction an eviction would remove
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvictionCandidate {
+    pub tx_hash: Hash256,
+    pub fee_rate: FeeRate,
+    pub fee: u64,
+    pub size: usize,
+    /// Seconds since it entered the mempool
+    pub age_secs: u64,
+}
+
+/// Why the mempool refused a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectCode {
+    /// Already in the mempool
+    Duplicate,
+    /// Inputs not found, or outputs worth more than them
+    MissingInputs,
+    /// Fails consensus validation
+    Invalid,
+    /// Pays less than the minimum relay fee rate
+    FeeTooLow,
+    /// Double-spends a mempool transaction it cannot replace
+    Conflict,
+}
+
+/// Result of checking a transaction against the mempool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptCheck {
+    pub tx_hash: Hash256,
+    pub allowed: bool,
+    pub reject_code: Option<RejectCode>,
+    pub reject_reason: Option<String>,
+    /// Virtual size in bytes
+    pub size: usize,
+    /// Fee and fee rate, once the inputs were found
+    pub fee: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
+    /// Gas bid and the fee rate counting it, for contract transactions
+    pub gas_price: Option<GasPrice>,
+    pub effective_fee_rate: Option<FeeRate>,
+    /// Mempool transactions it would replace
+    pub replaces: Vec<Hash256>,
+}
+
+impl AcceptCheck {
+    fn new(tx_hash: Hash256, size: usize) -> Self {
+        Self {
+            tx_hash,
+            allowed: false,
+            reject_code: None,
+            reject_reason: None,
+            size,
+            fee: None,
+            fee_rate: None,
+            gas_price: None,
+            effective_fee_rate: None,
+            replaces: Vec::new(),
         }
     }
 }
@@ -173,6 +368,8 @@ pub enum RemovalReason {
     BlockConfirmation,
     /// Evicted due to low fee rate
     FeeTooLow,
+    /// Evicted to keep the mempool within its limits
+    SizeLimit,
     /// Expired (too old)
     Expired,
     /// Invalid (failed validation)
@@ -197,6 +394,8 @@ pub struct Mempool {
     fee_index: BTreeMap<(FeeRate, SystemTime, Hash256), Hash256>,
     /// Outpoint index for conflict detection
     outpoint_index: HashMap<(Hash256, u32), Hash256>, // (prev_tx_hash, prev_output_index) -> tx_hash
+    /// Contract nonce index, so a sender's nonce is used by one transaction
+    nonce_index: HashMap<(String, u64), Hash256>,
     /// Dependency tracking
     dependency_graph: HashMap<Hash256, HashSet<Hash256>>, // parent -> children
     /// Current memory usage
@@ -212,6 +411,10 @@ pub struct Mempool {
     /// Recent activity tracking
     recent_additions: VecDeque<SystemTime>,
     recent_removals: VecDeque<SystemTime>,
+    /// Fee floor set by the last eviction, before decay
+    rolling_min_fee_rate: FeeRate,
+    /// When `rolling_min_fee_rate` was last raised
+    last_fee_bump: Instant,
 }
 
 impl Mempool {
@@ -225,6 +428,7 @@ impl Mempool {
             priority_index: BTreeMap::new(),
             fee_index: BTreeMap::new(),
             outpoint_index: HashMap::new(),
+            nonce_index: HashMap::new(),
             dependency_graph: HashMap::new(),
             memory_usage: 0,
             stats: MempoolStats::default(),
@@ -233,6 +437,8 @@ impl Mempool {
             last_purge: Instant::now(),
             recent_additions: VecDeque::new(),
             recent_removals: VecDeque::new(),
+            rolling_min_fee_rate: 0,
+            last_fee_bump: Instant::now(),
         }
     }
     
@@ -250,61 +456,23 @@ impl Mempool {
     pub async fn add_transaction(&mut self, transaction: Transaction) -> Result<Hash256> {
         // Calculate transaction hash
         let tx_hash = transaction.get_hash()?;
-        
-        // Check if transaction already exists
-        if self.transactions.contains_key(&tx_hash) {
-            return Err(BlockchainError::InvalidTransaction("Transaction already in mempool".to_string()));
-        }
-        
-        // Validate transaction structure and consensus rules
-        if let Some(consensus) = &self.consensus {
-            let _utxo_set = consensus.get_utxo_set().await;
-            let context = TxValidationContext {
-                block_height: 0, // Current height + 1
-                block_time: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs(),
-                utxo_set: _utxo_set,
-            };
-            
-            // Validate transaction
-            let _fee = consensus.validate_transaction(&transaction, &context)?;
-        }
-        
-        // Check for conflicts (double-spending)
-        let mut conflicting_txs = Vec::new();
-        for input in &transaction.inputs {
-            let outpoint = (input.prev_tx_hash, input.prev_output_index);
-            if let Some(existing_tx_hash) = self.outpoint_index.get(&outpoint) {
-                // Check if this is a valid RBF (Replace-By-Fee)
-                if self.config.enable_rbf && self.can_replace_transaction(&tx_hash, existing_tx_hash, &transaction).await? {
-                    conflicting_txs.push(*existing_tx_hash);
-                } else {
-                    return Err(BlockchainError::InvalidTransaction("Double-spending detected".to_string()));
-                }
-            }
-        }
-        
-        // Remove conflicting transactions after the loop
-        for conflicting_tx_hash in conflicting_txs {
+
+        // Run every policy and consensus check without touching the pool
+        let mut check = AcceptCheck::new(tx_hash, transaction.vsize());
+        self.check_transaction(&transaction, &mut check).await.map_err(|(_, e)| e)?;
+
+        // Remove the transactions it replaces (RBF)
+        for conflicting_tx_hash in std::mem::take(&mut check.replaces) {
             self.remove_transaction(&conflicting_tx_hash, RemovalReason::Replaced).await?;
         }
-        
-        // Calculate transaction metrics
-        let size = self.estimate_transaction_size(&transaction);
-        let fee = self.calculate_transaction_fee(&transaction).await?;
-        let fee_rate = if size > 0 { fee / size as u64 } else { 0 };
-        
-        // Check minimum fee rate
-        if fee_rate < self.config.min_relay_fee_rate {
-            return Err(BlockchainError::InvalidTransaction(
-                format!("Fee rate {} below minimum {}", fee_rate, self.config.min_relay_fee_rate)
-            ));
-        }
-        
-        // Determine priority based on fee rate
-        let priority = self.calculate_priority(fee_rate);
+
+        let size = check.size;
+        let fee = check.fee.unwrap_or_default();
+        let fee_rate = check.fee_rate.unwrap_or_default();
+        let effective_fee_rate = check.effective_fee_rate.unwrap_or(fee_rate);
+
+        // Determine priority based on fee rate, gas fees included
+        let priority = self.calculate_priority(effective_fee_rate);
         
         // Check mempool limits before adding
         self.enforce_mempool_limits().await?;
@@ -318,6 +486,8 @@ impl Mempool {
             entry_time: SystemTime::now(),
             size,
             fee,
+            gas_price: check.gas_price,
+            effective_fee_rate,
             ancestor_count: 0,
             ancestor_size: 0,
             ancestor_fees: 0,
@@ -354,6 +524,138 @@ impl Mempool {
         Ok(tx_hash)
     }
     
+    /// Dry run of `add_transaction`: every policy and consensus check,
+    /// reporting the reject reason or the fee it would be accepted at,
+    /// without adding the transaction
+    pub async fn test_accept(&self, transaction: &Transaction) -> Result<AcceptCheck> {
+        let mut check = AcceptCheck::new(transaction.get_hash()?, transaction.vsize());
+        match self.check_transaction(transaction, &mut check).await {
+            Ok(()) => check.allowed = true,
+            Err((code, e)) => {
+                check.reject_code = Some(code);
+                check.reject_reason = Some(e.to_string());
+            }
+        }
+        Ok(check)
+    }
+
+    /// Acceptance checks shared by `add_transaction` and `test_accept`,
+    /// filling in the fee and any transactions it would replace
+    async fn check_transaction(
+        &self,
+        transaction: &Transaction,
+        check: &mut AcceptCheck,
+    ) -> std::result::Result<(), (RejectCode, BlockchainError)> {
+        // Check if transaction already exists
+        if self.transactions.contains_key(&check.tx_hash) {
+            return Err((RejectCode::Duplicate, BlockchainError::InvalidTransaction("Transaction already in mempool".to_string())));
+        }
+
+        // Validate transaction structure and consensus rules
+        if let Some(consensus) = &self.consensus {
+            // Cheap rejection of inputs that are neither on chain nor made by
+            // a mempool parent, before copying the UTXO set and checking
+            // signatures. The filter cannot tell spent outpoints from ones
+            // never confirmed, so these are missing rather than spent.
+            for input in &transaction.inputs {
+                let outpoint = format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index);
+                if consensus.outpoint_spent_status(&outpoint).await == SpentStatus::Unknown
+                    && !self.transactions.contains_key(&input.prev_tx_hash)
+                {
+                    return Err((RejectCode::MissingInputs, BlockchainError::InvalidTransaction(
+                        format!("Input {} is spent or was never confirmed", outpoint)
+                    )));
+                }
+            }
+
+            let block_height = consensus.get_chain_state().await.height + 1;
+            let context = TxValidationContext {
+                block_height,
+                block_time: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                utxo_set: self.utxo_view(consensus, transaction).await,
+                precomputed_sighash: consensus.is_deployment_active(PRECOMPUTED_SIGHASH_DEPLOYMENT, block_height).await,
+            };
+
+            // Validate transaction
+            consensus.validate_transaction(transaction, &context)
+                .map_err(|e| (RejectCode::Invalid, e))?;
+        }
+
+        // Calculate transaction metrics
+        let fee = self.calculate_transaction_fee(transaction).await
+            .map_err(|e| (RejectCode::MissingInputs, e))?;
+        let fee_rate = if check.size > 0 { fee / check.size as u64 } else { 0 };
+        check.fee = Some(fee);
+        check.fee_rate = Some(fee_rate);
+
+        // Check minimum fee rate, raised while the mempool is full
+        let min_fee_rate = self.min_fee_rate();
+        if fee_rate < min_fee_rate {
+            return Err((RejectCode::FeeTooLow, BlockchainError::InvalidTransaction(
+                format!("Fee rate {} below minimum {}", fee_rate, min_fee_rate)
+            )));
+        }
+
+        // Contract transactions also pay for gas, at no less than the base fee
+        let base_fee = self.config.base_fee_per_gas;
+        let gas_price = GasPrice::from_transaction(transaction);
+        let gas_fee = match &gas_price {
+            Some(gas_price) => {
+                gas_price.check(base_fee).map_err(|e| (RejectCode::FeeTooLow, BlockchainError::InvalidTransaction(e)))?;
+                gas_price.gas_fee(base_fee)
+            }
+            None => 0,
+        };
+        check.gas_price = gas_price;
+        check.effective_fee_rate = Some(if check.size > 0 { fee.saturating_add(gas_fee) / check.size as u64 } else { 0 });
+
+        // Check for conflicts (double-spending)
+        for input in &transaction.inputs {
+            let outpoint = (input.prev_tx_hash, input.prev_output_index);
+            if let Some(existing_tx_hash) = self.outpoint_index.get(&outpoint) {
+                // Check if this is a valid RBF (Replace-By-Fee)
+                let replaceable = self.config.enable_rbf
+                    && self.can_replace_transaction(&check.tx_hash, existing_tx_hash, transaction).await
+                        .map_err(|e| (RejectCode::Conflict, e))?;
+                if !replaceable {
+                    return Err((RejectCode::Conflict, BlockchainError::InvalidTransaction("Double-spending detected".to_string())));
+                }
+                if !check.replaces.contains(existing_tx_hash) {
+                    check.replaces.push(*existing_tx_hash);
+                }
+            }
+        }
+
+        // Contract transactions need a nonce no other pool transaction of
+        // the sender uses, unless that one is being replaced
+        if gas_price.is_some() && transaction.nonce.is_none() {
+            return Err((RejectCode::Invalid, BlockchainError::InvalidTransaction("Contract transaction has no nonce".to_string())));
+        }
+        if let Some(key) = Self::nonce_key(transaction) {
+            if let Some(existing_tx_hash) = self.nonce_index.get(&key) {
+                if !check.replaces.contains(existing_tx_hash) {
+                    return Err((RejectCode::Conflict, BlockchainError::InvalidTransaction(
+                        format!("Nonce {} of {} is already used by {}", key.1, key.0, hex::encode(existing_tx_hash))
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sender and nonce of a contract transaction that has both
+    fn nonce_key(transaction: &Transaction) -> Option<(String, u64)> {
+        let nonce = transaction.nonce?;
+        if !transaction.is_contract_deployment() && !transaction.is_contract_call() {
+            return None;
+        }
+        Some((transaction_sender(transaction)?, nonce))
+    }
+
     /// Remove transaction from mempool
     pub async fn remove_transaction(&mut self, tx_hash: &Hash256, reason: RemovalReason) -> Result<()> {
         if let Some(entry) = self.transactions.remove(tx_hash) {
@@ -381,7 +683,67 @@ impl Mempool {
         
         Ok(())
     }
-    
+
+    /// Evict every transaction paying less than `min_fee_rate`, along with
+    /// its in-mempool descendants. Returns the hashes that were removed.
+    pub async fn evict_below_fee_rate(&mut self, min_fee_rate: FeeRate) -> Result<Vec<Hash256>> {
+        let mut pending: Vec<Hash256> = self.fee_index
+            .iter()
+            .take_while(|((fee_rate, _, _), _)| *fee_rate < min_fee_rate)
+            .map(|(_, tx_hash)| *tx_hash)
+            .collect();
+
+        let mut evicted = Vec::new();
+        let mut seen = HashSet::new();
+        while let Some(tx_hash) = pending.pop() {
+            if !seen.insert(tx_hash) {
+                continue;
+            }
+            if let Some(children) = self.dependency_graph.get(&tx_hash) {
+                pending.extend(children.iter().copied());
+            }
+            if self.transactions.contains_key(&tx_hash) {
+                self.remove_transaction(&tx_hash, RemovalReason::FeeTooLow).await?;
+                evicted.push(tx_hash);
+            }
+        }
+
+        if !evicted.is_empty() {
+            info!("Evicted {} transactions below {} sat/byte", evicted.len(), min_fee_rate);
+        }
+        Ok(evicted)
+    }
+
+    /// Dry run of eviction under the configured policy: the transactions
+    /// that would be removed to free `bytes` of mempool memory
+    pub fn what_would_evict(&self, bytes: usize) -> Vec<EvictionCandidate> {
+        let now = SystemTime::now();
+        let mut candidates = Vec::new();
+        let mut seen = HashSet::new();
+        let mut freed = 0;
+        for tx_hash in self.eviction_order() {
+            if freed >= bytes {
+                break;
+            }
+            for hash in self.eviction_set(tx_hash) {
+                if !seen.insert(hash) {
+                    continue;
+                }
+                if let Some(entry) = self.transactions.get(&hash) {
+                    freed += entry.size;
+                    candidates.push(EvictionCandidate {
+                        tx_hash: hash,
+                        fee_rate: entry.fee_rate,
+                        fee: entry.fee,
+                        size: entry.size,
+                        age_secs: now.duration_since(entry.entry_time).unwrap_or_default().as_secs(),
+                    });
+                }
+            }
+        }
+        candidates
+    }
+
     /// Get all pending transactions (ordered by priority and fee rate)
     pub fn get_transactions(&self) -> Vec<Transaction> {
         self.priority_index
@@ -398,8 +760,21 @@ impl Mempool {
         let mut transactions = Vec::new();
         let mut total_size = 0;
         
+        // Transaction versions this release knows no rules for are relayed
+        // but not mined, nor is anything spending their outputs
+        let mut unknown_version = HashSet::new();
+        for (tx_hash, entry) in &self.transactions {
+            if entry.transaction.version > MAX_KNOWN_TX_VERSION {
+                unknown_version.insert(*tx_hash);
+                unknown_version.extend(self.descendants(tx_hash));
+            }
+        }
+        
         // Iterate through transactions in priority order
         for (_, tx_hash) in self.priority_index.iter().rev() {
+            if unknown_version.contains(tx_hash) {
+                continue;
+            }
             if let Some(entry) = self.transactions.get(tx_hash) {
                 if total_size + entry.size <= max_block_size {
                     transactions.push(entry.transaction.clone());
@@ -418,6 +793,21 @@ impl Mempool {
         self.transactions.get(tx_hash).map(|entry| &entry.transaction)
     }
     
+    /// Fee rate a mempool transaction pays
+    pub fn get_fee_rate(&self, tx_hash: &Hash256) -> Option<FeeRate> {
+        self.transactions.get(tx_hash).map(|entry| entry.fee_rate)
+    }
+    
+    /// Fee a mempool transaction pays
+    pub fn get_fee(&self, tx_hash: &Hash256) -> Option<u64> {
+        self.transactions.get(tx_hash).map(|entry| entry.fee)
+    }
+    
+    /// Mempool transaction spending output `output_index` of `tx_hash`
+    pub fn spender_of(&self, tx_hash: &Hash256, output_index: u32) -> Option<Hash256> {
+        self.outpoint_index.get(&(*tx_hash, output_index)).copied()
+    }
+    
     /// Check if transaction exists in mempool
     pub fn contains_transaction(&self, tx_hash: &Hash256) -> bool {
         self.transactions.contains_key(tx_hash)
@@ -438,6 +828,70 @@ impl Mempool {
         self.memory_usage
     }
     
+    /// Fee rate below which transactions are refused: the configured floor,
+    /// or more while recent evictions keep it raised
+    pub fn min_fee_rate(&self) -> FeeRate {
+        self.config.min_relay_fee_rate.max(self.decayed_min_fee_rate())
+    }
+
+    /// Size, limits and fee floors
+    pub fn info(&self) -> MempoolInfo {
+        MempoolInfo {
+            transactions: self.transactions.len(),
+            memory_usage: self.memory_usage,
+            max_memory_usage: self.config.max_memory_usage,
+            usage: self.usage(),
+            min_relay_fee_rate: self.config.min_relay_fee_rate,
+            min_fee_rate: self.min_fee_rate(),
+        }
+    }
+
+    /// Fraction of the tighter limit in use
+    fn usage(&self) -> f64 {
+        let by_count = self.transactions.len() as f64 / self.config.max_transactions.max(1) as f64;
+        let by_memory = self.memory_usage as f64 / self.config.max_memory_usage.max(1) as f64;
+        by_count.max(by_memory)
+    }
+
+    /// The eviction-raised floor after decay. It halves every
+    /// `min_fee_halflife`, four times as fast below a quarter full and twice
+    /// as fast below half, and drops out once under half the increment.
+    fn decayed_min_fee_rate(&self) -> FeeRate {
+        if self.rolling_min_fee_rate == 0 {
+            return 0;
+        }
+        let usage = self.usage();
+        let halflife = if usage < 0.25 {
+            self.config.min_fee_halflife / 4
+        } else if usage < 0.5 {
+            self.config.min_fee_halflife / 2
+        } else {
+            self.config.min_fee_halflife
+        };
+        let halvings = self.last_fee_bump.elapsed().as_secs_f64() / halflife.as_secs_f64().max(1.0);
+        let decayed = self.rolling_min_fee_rate as f64 * 0.5f64.powf(halvings);
+        if decayed < self.config.rbf_fee_increment as f64 / 2.0 {
+            0
+        } else {
+            decayed as FeeRate
+        }
+    }
+
+    /// Keep the floor above a transaction evicted for space, so what comes
+    /// next has to outbid it by the replacement increment
+    fn raise_min_fee_rate(&mut self, evicted_fee_rate: FeeRate) {
+        let floor = evicted_fee_rate.saturating_add(self.config.rbf_fee_increment);
+        if floor > self.decayed_min_fee_rate() {
+            self.rolling_min_fee_rate = floor;
+            self.last_fee_bump = Instant::now();
+        }
+    }
+    
+    /// Lowest max fee per gas contract transactions may bid
+    pub fn base_fee_per_gas(&self) -> u64 {
+        self.config.base_fee_per_gas
+    }
+    
     /// Remove confirmed transactions from mempool
     pub async fn remove_confirmed_transactions(&mut self, confirmed_tx_hashes: &[Hash256]) -> Result<()> {
         for tx_hash in confirmed_tx_hashes {
@@ -475,16 +929,6 @@ impl Mempool {
         }
     }
     
-    /// Estimate transaction size in bytes
-    fn estimate_transaction_size(&self, transaction: &Transaction) -> usize {
-        // Simple estimation: base size + inputs + outputs
-        let base_size = 10; // version(4) + input_count(1) + output_count(1) + locktime(4)
-        let inputs_size: usize = transaction.inputs.iter().map(|_i| 148).sum(); // Typical input size
-        let outputs_size: usize = transaction.outputs.iter().map(|_o| 34).sum(); // Typical output size
-        
-        base_size + inputs_size + outputs_size
-    }
-    
     /// Calculate transaction fee by checking inputs against UTXO set
     async fn calculate_transaction_fee(&self, transaction: &Transaction) -> Result<u64> {
         // Calculate actual fee from inputs - outputs
@@ -499,7 +943,7 @@ impl Mempool {
         let mut input_sum: u64 = 0;
         
         if let Some(consensus) = &self.consensus {
-            let utxo_set = consensus.get_utxo_set().await;
+            let utxo_set = self.utxo_view(consensus, transaction).await;
             
             for input in &transaction.inputs {
                 let outpoint = format!("{}:{}", hex::encode(&input.prev_tx_hash), input.prev_output_index);
@@ -538,6 +982,25 @@ impl Mempool {
         Ok(input_sum - output_sum)
     }
     
+    /// The chain's UTXO set with the outputs `transaction` spends from
+    /// in-mempool parents added, so children of unconfirmed transactions
+    /// validate against what they spend
+    async fn utxo_view(&self, consensus: &ConsensusValidator, transaction: &Transaction) -> UTXOSet {
+        let mut utxo_set = consensus.get_utxo_set().await;
+        let height = consensus.get_chain_state().await.height as u32 + 1;
+        for input in &transaction.inputs {
+            let parent = match self.transactions.get(&input.prev_tx_hash) {
+                Some(parent) => &parent.transaction,
+                None => continue,
+            };
+            if let Some(output) = parent.outputs.get(input.prev_output_index as usize) {
+                let utxo = UTXO::new(input.prev_tx_hash, input.prev_output_index, output.clone(), height, false);
+                let _ = utxo_set.add_utxo(input.prev_tx_hash, input.prev_output_index, utxo);
+            }
+        }
+        utxo_set
+    }
+    
     /// Check if transaction can replace existing transaction (RBF)
     async fn can_replace_transaction(&self, _new_tx_hash: &Hash256, existing_tx_hash: &Hash256, new_transaction: &Transaction) -> Result<bool> {
         if !self.config.enable_rbf {
@@ -560,7 +1023,7 @@ impl Mempool {
     fn insert_transaction_indexes(&mut self, entry: &MempoolEntry) {
         // Priority index
         self.priority_index.insert(
-            (entry.priority, entry.fee_rate, entry.tx_hash),
+            (entry.priority, entry.effective_fee_rate, entry.tx_hash),
             entry.tx_hash,
         );
         
@@ -577,12 +1040,17 @@ impl Mempool {
                 entry.tx_hash,
             );
         }
+        
+        // Nonce index
+        if let Some(key) = Self::nonce_key(&entry.transaction) {
+            self.nonce_index.insert(key, entry.tx_hash);
+        }
     }
     
     /// Remove transaction from all indexes
     fn remove_transaction_indexes(&mut self, entry: &MempoolEntry) {
         // Priority index
-        self.priority_index.remove(&(entry.priority, entry.fee_rate, entry.tx_hash));
+        self.priority_index.remove(&(entry.priority, entry.effective_fee_rate, entry.tx_hash));
         
         // Fee index
         self.fee_index.remove(&(entry.fee_rate, entry.entry_time, entry.tx_hash));
@@ -591,16 +1059,22 @@ impl Mempool {
         for input in &entry.transaction.inputs {
             self.outpoint_index.remove(&(input.prev_tx_hash, input.prev_output_index));
         }
+        
+        // Nonce index
+        if let Some(key) = Self::nonce_key(&entry.transaction) {
+            if self.nonce_index.get(&key) == Some(&entry.tx_hash) {
+                self.nonce_index.remove(&key);
+            }
+        }
     }
     
     /// Update dependency graph when adding transaction
     fn update_dependency_graph(&mut self, entry: &MempoolEntry) {
-        // Add dependencies based on inputs
+        // Add dependencies on in-mempool parents of the inputs
         for input in &entry.transaction.inputs {
-            if let Some(parent_tx_hash) = self.outpoint_index.get(&(input.prev_tx_hash, input.prev_output_index)) {
-                // Add dependency relationship
+            if self.transactions.contains_key(&input.prev_tx_hash) {
                 self.dependency_graph
-                    .entry(*parent_tx_hash)
+                    .entry(input.prev_tx_hash)
                     .or_insert_with(HashSet::new)
                     .insert(entry.tx_hash);
             }
@@ -611,12 +1085,10 @@ impl Mempool {
     fn remove_from_dependency_graph(&mut self, entry: &MempoolEntry) {
         // Remove as dependent
         for input in &entry.transaction.inputs {
-            if let Some(parent_tx_hash) = self.outpoint_index.get(&(input.prev_tx_hash, input.prev_output_index)) {
-                if let Some(dependents) = self.dependency_graph.get_mut(parent_tx_hash) {
-                    dependents.remove(&entry.tx_hash);
-                    if dependents.is_empty() {
-                        self.dependency_graph.remove(parent_tx_hash);
-                    }
+            if let Some(dependents) = self.dependency_graph.get_mut(&input.prev_tx_hash) {
+                dependents.remove(&entry.tx_hash);
+                if dependents.is_empty() {
+                    self.dependency_graph.remove(&input.prev_tx_hash);
                 }
             }
         }
@@ -627,26 +1099,95 @@ impl Mempool {
     
     /// Enforce mempool limits (memory and transaction count)
     async fn enforce_mempool_limits(&mut self) -> Result<()> {
+        let mut evicted_count = 0;
+
         // Check transaction count limit
         while self.transactions.len() >= self.config.max_transactions {
-            self.evict_lowest_fee_transaction().await?;
+            evicted_count += self.evict_next_transaction().await?;
         }
         
         // Check memory usage limit
         while self.memory_usage >= self.config.max_memory_usage {
-            self.evict_lowest_fee_transaction().await?;
+            evicted_count += self.evict_next_transaction().await?;
+        }
+
+        if evicted_count > 0 {
+            let min_fee_rate = self.min_fee_rate();
+            let _ = self.event_sender.send(MempoolEvent::MempoolFull { evicted_count, min_fee_rate });
+            info!("Mempool full: evicted {} transactions, minimum fee rate now {}", evicted_count, min_fee_rate);
         }
         
         Ok(())
     }
     
-    /// Evict the lowest fee rate transaction
-    async fn evict_lowest_fee_transaction(&mut self) -> Result<()> {
-        if let Some((_, tx_hash)) = self.fee_index.iter().next() {
-            let tx_hash = *tx_hash;
-            self.remove_transaction(&tx_hash, RemovalReason::FeeTooLow).await?;
+    /// Evict the next transaction under the configured policy, raising the
+    /// fee floor to its rate. Returns how many transactions were removed.
+    async fn evict_next_transaction(&mut self) -> Result<u32> {
+        let Some(tx_hash) = self.eviction_order().into_iter().next() else {
+            return Ok(0);
+        };
+        if let Some(fee_rate) = self.get_fee_rate(&tx_hash) {
+            self.raise_min_fee_rate(fee_rate);
         }
-        Ok(())
+        let set = self.eviction_set(tx_hash);
+        for hash in &set {
+            self.remove_transaction(hash, RemovalReason::SizeLimit).await?;
+        }
+        Ok(set.len() as u32)
+    }
+
+    /// Transactions in the order the configured policy evicts them
+    fn eviction_order(&self) -> Vec<Hash256> {
+        let mut entries: Vec<&MempoolEntry> = self.transactions.values().collect();
+        match self.config.eviction_policy {
+            EvictionPolicy::LowestFeeRate => return self.fee_index.values().copied().collect(),
+            EvictionPolicy::OldestFirst => entries.sort_by_key(|entry| (entry.entry_time, entry.tx_hash)),
+            EvictionPolicy::LargestFirst => {
+                entries.sort_by_key(|entry| (std::cmp::Reverse(entry.size), entry.fee_rate, entry.tx_hash))
+            }
+            EvictionPolicy::DescendantAware => {
+                entries.sort_by_cached_key(|entry| (self.package_fee_rate(entry), entry.tx_hash))
+            }
+        }
+        entries.into_iter().map(|entry| entry.tx_hash).collect()
+    }
+
+    /// Transactions removed together when `tx_hash` is evicted
+    fn eviction_set(&self, tx_hash: Hash256) -> Vec<Hash256> {
+        let mut set = vec![tx_hash];
+        if self.config.eviction_policy == EvictionPolicy::DescendantAware {
+            set.extend(self.descendants(&tx_hash));
+        }
+        set
+    }
+
+    /// Fee rate of a transaction with its in-mempool descendants, or its own
+    /// rate if higher
+    fn package_fee_rate(&self, entry: &MempoolEntry) -> FeeRate {
+        let (mut fee, mut size) = (entry.fee, entry.size as u64);
+        for hash in self.descendants(&entry.tx_hash) {
+            if let Some(child) = self.transactions.get(&hash) {
+                fee += child.fee;
+                size += child.size as u64;
+            }
+        }
+        entry.fee_rate.max(if size > 0 { fee / size } else { 0 })
+    }
+
+    /// In-mempool descendants of a transaction
+    fn descendants(&self, tx_hash: &Hash256) -> Vec<Hash256> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::from([*tx_hash]);
+        let mut pending = vec![*tx_hash];
+        while let Some(parent) = pending.pop() {
+            for child in self.dependency_graph.get(&parent).into_iter().flatten() {
+                if seen.insert(*child) {
+                    found.push(*child);
+                    pending.push(*child);
+                }
+            }
+        }
+        found
     }
     
     /// Purge old transactions
@@ -828,6 +1369,23 @@ impl ThreadSafeMempool {
         let mut mempool = self.inner.write().await;
         mempool.remove_confirmed_transactions(confirmed_tx_hashes).await
     }
+    
+    /// Check a transaction for acceptance without adding it
+    pub async fn test_accept(&self, transaction: &Transaction) -> Result<AcceptCheck> {
+        let mempool = self.inner.read().await;
+        mempool.test_accept(transaction).await
+    }
+
+    /// Preview eviction of `bytes` under the configured policy
+    pub async fn what_would_evict(&self, bytes: usize) -> Vec<EvictionCandidate> {
+        let mempool = self.inner.read().await;
+        mempool.what_would_evict(bytes)
+    }
+
+    pub async fn info(&self) -> MempoolInfo {
+        let mempool = self.inner.read().await;
+        mempool.info()
+    }
 }
 
 #[cfg(test)]
@@ -855,13 +1413,142 @@ mod tests {
         
         // Get transaction
         assert!(mempool.get_transaction(&tx_hash).is_some());
+        assert_eq!(mempool.spender_of(&Hash256::from_hash256(&[1u8; 32]), 0), Some(tx_hash));
+        assert_eq!(mempool.spender_of(&Hash256::from_hash256(&[1u8; 32]), 1), None);
         
         // Remove transaction
         mempool.remove_transaction(&tx_hash, RemovalReason::Manual).await.unwrap();
         assert_eq!(mempool.transaction_count(), 0);
         assert!(!mempool.contains_transaction(&tx_hash));
+        assert_eq!(mempool.spender_of(&Hash256::from_hash256(&[1u8; 32]), 0), None);
     }
-    
+
+    #[tokio::test]
+    async fn test_evict_below_fee_rate() {
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        let mut mempool = Mempool::new(config);
+
+        let tx = Transaction::new(
+            1,
+            vec![TransactionInput::new([7u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "test_address").unwrap()],
+        );
+        let tx_hash = mempool.add_transaction(tx).await.unwrap();
+
+        // Nothing pays less than zero
+        assert!(mempool.evict_below_fee_rate(0).await.unwrap().is_empty());
+        assert_eq!(mempool.transaction_count(), 1);
+
+        let evicted = mempool.evict_below_fee_rate(FeeRate::MAX).await.unwrap();
+        assert_eq!(evicted, vec![tx_hash]);
+        assert_eq!(mempool.transaction_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_accept_dry_run() {
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        let mut mempool = Mempool::new(config);
+
+        let tx = Transaction::new(
+            1,
+            vec![TransactionInput::new([9u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "test_address").unwrap()],
+        );
+        let check = mempool.test_accept(&tx).await.unwrap();
+        assert!(check.allowed);
+        assert!(check.fee.unwrap() > 0);
+        assert_eq!(mempool.transaction_count(), 0);
+
+        mempool.add_transaction(tx.clone()).await.unwrap();
+        let check = mempool.test_accept(&tx).await.unwrap();
+        assert!(!check.allowed);
+        assert_eq!(check.reject_code, Some(RejectCode::Duplicate));
+
+        mempool.config.min_relay_fee_rate = FeeRate::MAX;
+        let other = Transaction::new(
+            1,
+            vec![TransactionInput::new([10u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "test_address").unwrap()],
+        );
+        let check = mempool.test_accept(&other).await.unwrap();
+        assert_eq!(check.reject_code, Some(RejectCode::FeeTooLow));
+        assert!(check.fee_rate.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tx_version_relayed_but_not_mined() {
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        let mut mempool = Mempool::new(config);
+
+        let known = Transaction::new(
+            1,
+            vec![TransactionInput::new([9u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "test_address").unwrap()],
+        );
+        let unknown = Transaction::new(
+            MAX_KNOWN_TX_VERSION + 1,
+            vec![TransactionInput::new([10u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "test_address").unwrap()],
+        );
+        let known_hash = mempool.add_transaction(known).await.unwrap();
+        let unknown_hash = mempool.add_transaction(unknown).await.unwrap();
+        let child = Transaction::new(
+            1,
+            vec![TransactionInput::new(unknown_hash, 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(50_000_000, "test_address").unwrap()],
+        );
+        mempool.add_transaction(child).await.unwrap();
+        assert_eq!(mempool.transaction_count(), 3);
+
+        let block = mempool.get_transactions_for_block(1_000_000);
+        assert_eq!(block.len(), 1);
+        assert_eq!(block[0].get_hash().unwrap(), known_hash);
+    }
+
+    #[tokio::test]
+    async fn test_descendant_aware_eviction_preview() {
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        config.eviction_policy = EvictionPolicy::DescendantAware;
+        let mut mempool = Mempool::new(config);
+
+        let parent = Transaction::new(
+            1,
+            vec![TransactionInput::new([1u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "parent").unwrap()],
+        );
+        let parent_hash = mempool.add_transaction(parent).await.unwrap();
+        let child = Transaction::new(
+            1,
+            vec![TransactionInput::new(parent_hash, 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(90_000_000, "child").unwrap()],
+        );
+        let child_hash = mempool.add_transaction(child).await.unwrap();
+        let other = Transaction::new(
+            1,
+            vec![TransactionInput::new([2u8; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "other").unwrap()],
+        );
+        let other_hash = mempool.add_transaction(other).await.unwrap();
+
+        // The child pays enough to carry its parent
+        mempool.transactions.get_mut(&child_hash).unwrap().fee = 10_000_000;
+
+        let preview = |mempool: &Mempool, bytes| {
+            mempool.what_would_evict(bytes).iter().map(|c| c.tx_hash).collect::<Vec<_>>()
+        };
+        assert_eq!(preview(&mempool, 1), vec![other_hash]);
+        // Evicting the parent takes the child along
+        assert_eq!(preview(&mempool, usize::MAX), vec![other_hash, parent_hash, child_hash]);
+        assert_eq!(mempool.transaction_count(), 3);
+
+        mempool.config.eviction_policy = EvictionPolicy::LowestFeeRate;
+        assert_eq!(preview(&mempool, usize::MAX).len(), 3);
+    }
+
     #[tokio::test]
     async fn test_mempool_priority_ordering() {
         let mut config = MempoolConfig::default();
@@ -893,6 +1580,116 @@ mod tests {
         assert!(block_txs[0].outputs[0].value < block_txs[1].outputs[0].value);
     }
     
+    #[tokio::test]
+    async fn test_gas_fees_order_contract_transactions() {
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        config.base_fee_per_gas = 10;
+        let mut mempool = Mempool::new(config);
+
+        let input = |byte: u8| vec![TransactionInput::new([byte; 32], 0, vec![])];
+        let call = |byte: u8| {
+            Transaction::new_contract_call(1, input(byte), Vec::new(), [0xcc; 20], vec![1], 100_000).with_nonce(0)
+        };
+        let plain = Transaction::new(1, input(1), vec![TransactionOutput::create_p2pkh(1_000, "test1").unwrap()]);
+        let low_tip = call(2).with_gas_price(20, 5);
+        let high_tip = call(3).with_gas_price(50, 40);
+
+        for tx in [plain, low_tip.clone(), high_tip.clone()] {
+            mempool.add_transaction(tx).await.unwrap();
+        }
+        let order: Vec<u8> = mempool.get_transactions_for_block(1_000_000).iter()
+            .map(|tx| tx.inputs[0].prev_tx_hash[0])
+            .collect();
+        assert_eq!(order, vec![3, 2, 1]);
+
+        // Capped by the max fee, and never below the base fee
+        let entry = &mempool.transactions[&low_tip.get_hash().unwrap()];
+        assert_eq!(entry.gas_price.unwrap().effective_price(10), 15);
+        assert_eq!(GasPrice::from_transaction(&high_tip).unwrap().effective_price(100), 50);
+
+        let check = mempool.test_accept(&call(4).with_gas_price(5, 1)).await.unwrap();
+        assert_eq!(check.reject_code, Some(RejectCode::FeeTooLow));
+        let check = mempool.test_accept(&call(5).with_gas_price(20, 30)).await.unwrap();
+        assert_eq!(check.reject_code, Some(RejectCode::FeeTooLow));
+    }
+
+    #[tokio::test]
+    async fn test_contract_nonce_used_once() {
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        let mut mempool = Mempool::new(config);
+
+        // Same signing key on every input
+        let mut script_sig = vec![71];
+        script_sig.extend_from_slice(&[0x30; 71]);
+        script_sig.push(33);
+        script_sig.extend_from_slice(&[0x02; 33]);
+        let call = |byte: u8| Transaction::new_contract_call(
+            1, vec![TransactionInput::new([byte; 32], 0, script_sig.clone())], Vec::new(), [0xcc; 20], vec![1], 100_000,
+        );
+
+        mempool.add_transaction(call(1).with_nonce(0)).await.unwrap();
+        let check = mempool.test_accept(&call(2).with_nonce(0)).await.unwrap();
+        assert_eq!(check.reject_code, Some(RejectCode::Conflict));
+        let check = mempool.test_accept(&call(3)).await.unwrap();
+        assert_eq!(check.reject_code, Some(RejectCode::Invalid));
+        mempool.add_transaction(call(4).with_nonce(1)).await.unwrap();
+        assert_eq!(mempool.nonce_index.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_child_of_unconfirmed_parent() {
+        use crate::consensus::ConsensusParams;
+        use crate::genesis::{GenesisAccount, GenesisConfig, GenesisCreator};
+        use crate::tx_builder::TransactionBuilder;
+        use crate::wallet::Wallet;
+
+        let owner = Wallet::new("owner".to_string()).unwrap();
+        let genesis = GenesisConfig {
+            initial_accounts: vec![GenesisAccount {
+                address: owner.address.clone(),
+                balance: 1_000_000,
+                description: "Test funds".to_string(),
+            }],
+            genesis_timestamp: 1234567890,
+            network_id: 0,
+        };
+        let consensus = Arc::new(ConsensusValidator::new(ConsensusParams::default()));
+        consensus.initialize_with_genesis(GenesisCreator::new(Some(genesis)).create_genesis_state().unwrap()).await.unwrap();
+        let mut config = MempoolConfig::default();
+        config.min_relay_fee_rate = 1;
+        let mut mempool = Mempool::new(config);
+        mempool.set_consensus_validator(consensus.clone());
+
+        let funding = consensus.get_utxo_set().await.get_utxos_for_address(&owner.address)[0].clone();
+        let parent = TransactionBuilder::new()
+            .add_output(owner.address.clone(), 900_000)
+            .spend_utxos(&owner, vec![funding])
+            .unwrap();
+        let parent_hash = mempool.add_transaction(parent.clone()).await.unwrap();
+
+        // The parent's output is not on chain, whatever the spent filter says
+        let output = UTXO::new(parent_hash, 0, parent.outputs[0].clone(), 1, false);
+        let child = TransactionBuilder::new()
+            .add_output(owner.address.clone(), 800_000)
+            .spend_utxos(&owner, vec![output])
+            .unwrap();
+        let check = mempool.test_accept(&child).await.unwrap();
+        assert!(check.allowed, "{:?}", check.reject_reason);
+        assert_eq!(check.fee, Some(100_000));
+        let child_hash = mempool.add_transaction(child).await.unwrap();
+        assert_eq!(mempool.spender_of(&parent_hash, 0), Some(child_hash));
+
+        // An output no transaction made is still missing
+        let orphan = UTXO::new([7; 32], 0, parent.outputs[0].clone(), 1, false);
+        let orphan = TransactionBuilder::new()
+            .add_output(owner.address.clone(), 800_000)
+            .spend_utxos(&owner, vec![orphan])
+            .unwrap();
+        assert!(!mempool.test_accept(&orphan).await.unwrap().allowed);
+    }
+
     #[tokio::test]
     async fn test_mempool_limits() {
         let mut config = MempoolConfig::default();
@@ -918,6 +1715,43 @@ mod tests {
         // Should have evicted oldest transaction
         assert_eq!(mempool.transaction_count(), 2);
     }
+
+    #[tokio::test]
+    async fn test_min_fee_rises_on_eviction_and_decays() {
+        let mut config = MempoolConfig::default();
+        config.max_transactions = 1;
+        config.min_relay_fee_rate = 1;
+        config.rbf_fee_increment = 10;
+        config.min_fee_halflife = Duration::from_secs(4);
+        let mut mempool = Mempool::new(config);
+        assert_eq!(mempool.min_fee_rate(), 1);
+
+        let tx = |seed: u8| Transaction::new(
+            1,
+            vec![TransactionInput::new([seed; 32], 0, vec![])],
+            vec![TransactionOutput::create_p2pkh(100_000_000, "test").unwrap()],
+        );
+        let first = mempool.add_transaction(tx(1)).await.unwrap();
+        let evicted_rate = mempool.get_fee_rate(&first).unwrap();
+        mempool.add_transaction(tx(2)).await.unwrap();
+
+        // The evicted transaction's rate plus the increment is the new floor
+        assert!(!mempool.contains_transaction(&first));
+        assert_eq!(mempool.min_fee_rate(), evicted_rate + 10);
+        assert_eq!(mempool.info().min_fee_rate, evicted_rate + 10);
+        let check = mempool.test_accept(&tx(3)).await.unwrap();
+        assert_eq!(check.reject_code, Some(RejectCode::FeeTooLow));
+
+        // Full mempool: one halflife halves it
+        let halflife = mempool.config.min_fee_halflife;
+        mempool.last_fee_bump = Instant::now().checked_sub(halflife).unwrap();
+        let halved = mempool.min_fee_rate();
+        assert!(halved <= (evicted_rate + 10) / 2 && halved + 1 >= (evicted_rate + 10) / 2);
+
+        // Long enough and it falls back to the configured floor
+        mempool.last_fee_bump = Instant::now().checked_sub(halflife * 20).unwrap();
+        assert_eq!(mempool.min_fee_rate(), 1);
+    }
     
     #[test]
     fn test_priority_calculation() {
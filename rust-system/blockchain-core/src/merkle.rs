@@ -0,0 +1,229 @@
+//! Incremental merkle tree
+//!
+//! Keeps every level of the tree so that changing one leaf only rehashes
+//! the path from that leaf to the root. This suits block template
+//! assembly, where the coinbase changes with every extra nonce and
+//! mempool transactions come and go between refreshes.
+//!
+//! Nodes are built the same way as block merkle roots: the double-SHA256
+//! of the hex of the left and right child, with the last node of an odd
+//! level paired with itself. A single leaf is its own root and an empty
+//! tree has an all-zero root.
+
+use crate::Hash256;
+use sha2::{Digest, Sha256};
+
+/// Merkle tree over transaction hashes with O(log n) leaf updates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncrementalMerkleTree {
+    /// `levels[0]` holds the leaves, the last level holds the root
+    levels: Vec<Vec<Hash256>>,
+}
+
+impl IncrementalMerkleTree {
+    pub fn new() -> Self {
+        Self { levels: vec![Vec::new()] }
+    }
+
+    /// Build a tree over the given leaves, in order
+    pub fn from_leaves(leaves: Vec<Hash256>) -> Self {
+        let mut tree = Self { levels: vec![leaves] };
+        tree.rehash_from(0);
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves().is_empty()
+    }
+
+    pub fn leaves(&self) -> &[Hash256] {
+        &self.levels[0]
+    }
+
+    pub fn root(&self) -> Hash256 {
+        let top = &self.levels[self.levels.len() - 1];
+        top.first().copied().unwrap_or([0u8; 32])
+    }
+
+    /// Append a leaf
+    pub fn push(&mut self, leaf: Hash256) {
+        self.levels[0].push(leaf);
+        self.update_path(self.len() - 1);
+    }
+
+    /// Remove the last leaf
+    pub fn pop(&mut self) -> Option<Hash256> {
+        let leaf = self.levels[0].pop()?;
+        self.update_path(self.len().saturating_sub(1));
+        Some(leaf)
+    }
+
+    /// Replace the leaf at `index`, returning the old one
+    pub fn set(&mut self, index: usize, leaf: Hash256) -> Option<Hash256> {
+        let slot = self.levels[0].get_mut(index)?;
+        let old = std::mem::replace(slot, leaf);
+        self.update_path(index);
+        Some(old)
+    }
+
+    /// Remove the leaf at `index`, moving the last leaf into its place.
+    /// Only two paths are rehashed, but leaf order is not preserved.
+    pub fn swap_remove(&mut self, index: usize) -> Option<Hash256> {
+        if index >= self.len() {
+            return None;
+        }
+        let leaf = self.levels[0].swap_remove(index);
+        if index < self.len() {
+            self.update_path(index);
+        }
+        self.update_path(self.len().saturating_sub(1));
+        Some(leaf)
+    }
+
+    /// Remove the leaf at `index`, keeping the order of the rest. Every
+    /// later leaf moves, so this rehashes everything to the right of it.
+    pub fn remove(&mut self, index: usize) -> Option<Hash256> {
+        if index >= self.len() {
+            return None;
+        }
+        let leaf = self.levels[0].remove(index);
+        self.rehash_from(index);
+        Some(leaf)
+    }
+
+    /// Rehash the parents of the leaf at `index` up to the root, growing or
+    /// shrinking the upper levels to fit the current number of leaves
+    fn update_path(&mut self, mut index: usize) {
+        let mut level = 0;
+        while self.levels[level].len() > 1 {
+            let width = self.levels[level].len().div_ceil(2);
+            if self.levels.len() == level + 1 {
+                self.levels.push(Vec::with_capacity(width));
+            }
+
+            let parent = index / 2;
+            let node = parent_hash(&self.levels[level], parent);
+            let parents = &mut self.levels[level + 1];
+            parents.truncate(width);
+            if parent == parents.len() {
+                parents.push(node);
+            } else {
+                parents[parent] = node;
+            }
+
+            index = parent;
+            level += 1;
+        }
+        self.levels.truncate(level + 1);
+    }
+
+    /// Rehash every node covering leaves from `index` onwards
+    fn rehash_from(&mut self, mut index: usize) {
+        let mut level = 0;
+        while self.levels[level].len() > 1 {
+            let width = self.levels[level].len().div_ceil(2);
+            if self.levels.len() == level + 1 {
+                self.levels.push(Vec::with_capacity(width));
+            }
+
+            let first = index / 2;
+            let nodes: Vec<Hash256> = (first..width)
+                .map(|parent| parent_hash(&self.levels[level], parent))
+                .collect();
+            let parents = &mut self.levels[level + 1];
+            parents.truncate(first);
+            parents.extend(nodes);
+
+            index = first;
+            level += 1;
+        }
+        self.levels.truncate(level + 1);
+    }
+}
+
+impl Default for IncrementalMerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hash of the node above `children[2 * parent]` and its sibling
+fn parent_hash(children: &[Hash256], parent: usize) -> Hash256 {
+    let left = &children[2 * parent];
+    let right = children.get(2 * parent + 1).unwrap_or(left);
+    hash_pair(left, right)
+}
+
+fn hash_pair(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut preimage = [0u8; 128];
+    hex::encode_to_slice(left, &mut preimage[..64]).expect("64 hex digits");
+    hex::encode_to_slice(right, &mut preimage[64..]).expect("64 hex digits");
+    Sha256::digest(Sha256::digest(preimage)).into()
+}
+
+/// Merkle root of the given leaves, built from scratch
+pub fn merkle_root(leaves: &[Hash256]) -> Hash256 {
+    IncrementalMerkleTree::from_leaves(leaves.to_vec()).root()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Level by level rebuild, as block headers commit to it
+    fn full_root(hashes: Vec<Hash256>) -> Hash256 {
+        crate::block::Block::compute_merkle_root(hashes)
+    }
+
+    fn leaf(i: usize) -> Hash256 {
+        Sha256::digest((i as u64).to_le_bytes()).into()
+    }
+
+    #[test]
+    fn test_push_and_pop_match_full_rebuild() {
+        let mut tree = IncrementalMerkleTree::new();
+        let mut leaves = Vec::new();
+        assert_eq!(tree.root(), [0u8; 32]);
+
+        for i in 0..40 {
+            tree.push(leaf(i));
+            leaves.push(leaf(i));
+            assert_eq!(tree.root(), full_root(leaves.clone()), "after push {}", i);
+        }
+        assert_eq!(tree, IncrementalMerkleTree::from_leaves(leaves.clone()));
+
+        while let Some(popped) = tree.pop() {
+            assert_eq!(Some(popped), leaves.pop());
+            assert_eq!(tree.root(), full_root(leaves.clone()), "after pop to {}", leaves.len());
+        }
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_set_and_removals_match_full_rebuild() {
+        let mut leaves: Vec<Hash256> = (0..23).map(leaf).collect();
+        let mut tree = IncrementalMerkleTree::from_leaves(leaves.clone());
+
+        // Coinbase swap for a new extra nonce
+        leaves[0] = leaf(100);
+        assert_eq!(tree.set(0, leaf(100)), Some(leaf(0)));
+        assert_eq!(tree.root(), full_root(leaves.clone()));
+
+        assert_eq!(tree.swap_remove(5), Some(leaves.swap_remove(5)));
+        assert_eq!(tree.root(), full_root(leaves.clone()));
+        assert_eq!(tree.swap_remove(leaves.len() - 1), Some(leaves.swap_remove(leaves.len() - 1)));
+        assert_eq!(tree.root(), full_root(leaves.clone()));
+
+        assert_eq!(tree.remove(7), Some(leaves.remove(7)));
+        assert_eq!(tree.root(), full_root(leaves.clone()));
+        assert_eq!(tree.leaves(), leaves.as_slice());
+
+        assert_eq!(tree.remove(leaves.len()), None);
+        assert_eq!(tree.set(leaves.len(), leaf(0)), None);
+        assert_eq!(merkle_root(&leaves), tree.root());
+    }
+}
@@ -0,0 +1,121 @@
+//! Signed messages
+//!
+//! Proves control of an address without moving funds: the holder signs an
+//! arbitrary text message with the address's key. Messages are hashed under
+//! their own tag, so a message signature can never double as a transaction
+//! signature. Signatures are not recoverable, so the signer hands over the
+//! public key with them and verification checks that the key belongs to the
+//! claimed address.
+
+use crate::script_utils::ScriptBuilder;
+use crate::{crypto, Address, BlockchainError, Hash256, PrivateKey, PublicKey, Result};
+use serde::{Deserialize, Serialize};
+
+const MESSAGE_TAG: &[u8] = b"EDU-SIGNED-MESSAGE";
+
+/// Longest message that may be signed
+pub const MAX_MESSAGE_LEN: usize = 4096;
+
+/// A message signed by the key of an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub address: Address,
+    pub message: String,
+    /// Compressed or uncompressed public key of the address
+    #[serde(with = "hex_bytes")]
+    pub public_key: PublicKey,
+    /// DER-encoded ECDSA signature over `message_hash(message)`
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedMessage {
+    /// Sign `message` with the key of the address it derives to
+    pub fn sign(message: &str, private_key: &PrivateKey) -> Result<Self> {
+        check_len(message)?;
+        let public_key = crypto::derive_public_key(private_key)?;
+        Ok(Self {
+            address: address_of(&public_key)?,
+            message: message.to_string(),
+            signature: crypto::sign_hash(&message_hash(message), private_key)?,
+            public_key,
+        })
+    }
+
+    /// Check that the public key belongs to the address and signed the message
+    pub fn verify(&self) -> Result<()> {
+        check_len(&self.message)?;
+        if address_of(&self.public_key)? != self.address {
+            return Err(BlockchainError::InvalidSignature(format!(
+                "Public key does not belong to {}", self.address
+            )));
+        }
+        if !crypto::verify_signature(&self.signature, &self.public_key, &message_hash(&self.message))? {
+            return Err(BlockchainError::InvalidSignature("Message signature does not verify".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Hash that is signed for `message`
+pub fn message_hash(message: &str) -> Hash256 {
+    let mut data = Vec::with_capacity(MESSAGE_TAG.len() + 4 + message.len());
+    data.extend_from_slice(MESSAGE_TAG);
+    data.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    data.extend_from_slice(message.as_bytes());
+    crypto::double_sha256(&data)
+}
+
+/// P2PKH address of a public key
+fn address_of(public_key: &[u8]) -> Result<Address> {
+    let compressed: [u8; 33] = crypto::compress_public_key(public_key)?
+        .try_into()
+        .map_err(|_| BlockchainError::CryptoError("Unexpected public key length".to_string()))?;
+    ScriptBuilder::pubkey_to_address(&compressed)
+}
+
+fn check_len(message: &str) -> Result<()> {
+    if message.len() > MAX_MESSAGE_LEN {
+        return Err(BlockchainError::InvalidInput(format!("Message exceeds {} bytes", MAX_MESSAGE_LEN)));
+    }
+    Ok(())
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_message() {
+        let key = crypto::sha256(b"message test key");
+        let signed = SignedMessage::sign("I control this address", &key).unwrap();
+        assert!(signed.verify().is_ok());
+        assert!(signed.address.starts_with("edu1q"));
+
+        let mut tampered = signed.clone();
+        tampered.message = "I control that address".to_string();
+        assert!(tampered.verify().is_err());
+
+        // Someone else's key can't speak for the address
+        let other = SignedMessage::sign("I control this address", &crypto::sha256(b"other key")).unwrap();
+        let mut forged = other.clone();
+        forged.address = signed.address.clone();
+        assert!(forged.verify().is_err());
+
+        // A message signature is not a signature over the raw message hash
+        assert_ne!(message_hash("abc"), crypto::sha256(b"abc"));
+    }
+}
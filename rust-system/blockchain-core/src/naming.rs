@@ -0,0 +1,486 @@
+//! Human-readable payment names
+//!
+//! Names like `alice.edu` map to addresses through registration
+//! transactions. A registration is an OP_RETURN output carrying the name and
+//! its target address; the output's value is burned as the registration fee
+//! and buys one or more periods. Whoever signs the transaction's first input
+//! owns the name and alone may point it at a different address. Anyone may
+//! pay to renew a name. Expired names stay reserved for a grace period, in
+//! which they can still be renewed but not resolved, and after that anyone
+//! may register them again.
+//!
+//! Every node indexes registrations from blocks with the same rules, so
+//! resolution agrees across the network.
+
+use crate::address::{AddressCodec, AddressKind};
+use crate::block::Block;
+use crate::script_utils::{opcodes, ScriptBuilder};
+use crate::transaction::{Transaction, TransactionOutput};
+use crate::{Address, BlockchainError, Hash256, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// Magic prefix of a name operation in an OP_RETURN output
+pub const COMMITMENT_MAGIC: &[u8; 4] = b"EDNS";
+
+/// Suffix every name ends with
+pub const NAME_SUFFIX: &str = ".edu";
+
+/// Label length limits, suffix excluded
+pub const MIN_LABEL_LEN: usize = 3;
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Satoshis burned per registration period (1 EDU)
+pub const FEE_PER_PERIOD: u64 = 100_000_000;
+
+/// Blocks in one registration period (about a year at 10 minute blocks)
+pub const PERIOD_BLOCKS: u64 = 52_560;
+
+/// Most periods a name can be paid ahead
+pub const MAX_PERIODS: u64 = 5;
+
+/// Blocks after expiry in which only renewal is possible (about 30 days)
+pub const GRACE_BLOCKS: u64 = 4_320;
+
+const OP_REGISTER: u8 = 1;
+const OP_RENEW: u8 = 2;
+const OP_UPDATE: u8 = 3;
+
+const ADDRESS_P2PKH: u8 = 0;
+const ADDRESS_P2SH: u8 = 1;
+
+/// Check and normalize a name: lowercase, with the `.edu` suffix
+pub fn normalize(name: &str) -> Result<String> {
+    let name = name.trim().to_ascii_lowercase();
+    let label = name.strip_suffix(NAME_SUFFIX).unwrap_or(&name);
+    validate_label(label)?;
+    Ok(format!("{}{}", label, NAME_SUFFIX))
+}
+
+/// Whether `input` looks like a name rather than an address
+pub fn is_name(input: &str) -> bool {
+    input.trim().to_ascii_lowercase().ends_with(NAME_SUFFIX)
+}
+
+fn validate_label(label: &str) -> Result<()> {
+    let valid = (MIN_LABEL_LEN..=MAX_LABEL_LEN).contains(&label.len())
+        && label.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !label.starts_with('-')
+        && !label.ends_with('-');
+    if !valid {
+        return Err(BlockchainError::InvalidInput(format!(
+            "Name must be {}-{} lowercase letters, digits or inner hyphens followed by {}",
+            MIN_LABEL_LEN, MAX_LABEL_LEN, NAME_SUFFIX
+        )));
+    }
+    Ok(())
+}
+
+/// What a name operation does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum NameOp {
+    /// Claim an available name and point it at `address`
+    Register { address: Address },
+    /// Extend a name's expiry
+    Renew,
+    /// Point an owned name at a different address
+    Update { address: Address },
+}
+
+/// A name operation as committed on-chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameCommitment {
+    /// Normalized name, suffix included
+    pub name: String,
+    #[serde(flatten)]
+    pub op: NameOp,
+}
+
+impl NameCommitment {
+    pub fn register(name: &str, address: &str) -> Result<Self> {
+        ScriptBuilder::address_to_hash160(address)?;
+        Ok(Self { name: normalize(name)?, op: NameOp::Register { address: address.to_string() } })
+    }
+
+    pub fn renew(name: &str) -> Result<Self> {
+        Ok(Self { name: normalize(name)?, op: NameOp::Renew })
+    }
+
+    pub fn update(name: &str, address: &str) -> Result<Self> {
+        ScriptBuilder::address_to_hash160(address)?;
+        Ok(Self { name: normalize(name)?, op: NameOp::Update { address: address.to_string() } })
+    }
+
+    /// OP_RETURN script carrying this operation
+    pub fn to_script(&self) -> Result<Vec<u8>> {
+        let label = self.name.strip_suffix(NAME_SUFFIX).unwrap_or(&self.name);
+        let mut data = Vec::with_capacity(4 + 1 + 21 + label.len());
+        data.extend_from_slice(COMMITMENT_MAGIC);
+        match &self.op {
+            NameOp::Register { address } => {
+                data.push(OP_REGISTER);
+                encode_address(address, &mut data)?;
+            }
+            NameOp::Renew => data.push(OP_RENEW),
+            NameOp::Update { address } => {
+                data.push(OP_UPDATE);
+                encode_address(address, &mut data)?;
+            }
+        }
+        data.extend_from_slice(label.as_bytes());
+        ScriptBuilder::create_op_return_script(&data)
+    }
+
+    /// Output burning the fee for `periods` periods along with the operation.
+    /// Updates carry no fee.
+    pub fn to_output(&self, periods: u64) -> Result<TransactionOutput> {
+        let value = match self.op {
+            NameOp::Update { .. } => 0,
+            _ => {
+                if !(1..=MAX_PERIODS).contains(&periods) {
+                    return Err(BlockchainError::InvalidInput(format!("Periods must be between 1 and {}", MAX_PERIODS)));
+                }
+                FEE_PER_PERIOD * periods
+            }
+        };
+        Ok(TransactionOutput::new(value, self.to_script()?))
+    }
+
+    /// Decode a name operation from an output script
+    pub fn from_script(script: &[u8]) -> Option<Self> {
+        if script.len() < 2 || script[0] != opcodes::OP_RETURN || script[1] as usize != script.len() - 2 {
+            return None;
+        }
+        let data = script[2..].strip_prefix(COMMITMENT_MAGIC.as_slice())?;
+        let (&op, rest) = data.split_first()?;
+        let (op, label) = match op {
+            OP_REGISTER => {
+                let (address, label) = decode_address(rest)?;
+                (NameOp::Register { address }, label)
+            }
+            OP_RENEW => (NameOp::Renew, rest),
+            OP_UPDATE => {
+                let (address, label) = decode_address(rest)?;
+                (NameOp::Update { address }, label)
+            }
+            _ => return None,
+        };
+        let label = std::str::from_utf8(label).ok()?;
+        validate_label(label).ok()?;
+        Some(Self { name: format!("{}{}", label, NAME_SUFFIX), op })
+    }
+}
+
+fn encode_address(address: &str, data: &mut Vec<u8>) -> Result<()> {
+    let decoded = AddressCodec::decode(address)?;
+    data.push(match decoded.kind {
+        AddressKind::P2pkh => ADDRESS_P2PKH,
+        AddressKind::P2sh => ADDRESS_P2SH,
+    });
+    data.extend_from_slice(&decoded.hash);
+    Ok(())
+}
+
+fn decode_address(data: &[u8]) -> Option<(Address, &[u8])> {
+    if data.len() < 21 {
+        return None;
+    }
+    let kind = match data[0] {
+        ADDRESS_P2PKH => AddressKind::P2pkh,
+        ADDRESS_P2SH => AddressKind::P2sh,
+        _ => return None,
+    };
+    let hash: [u8; 20] = data[1..21].try_into().ok()?;
+    Some((AddressCodec::encode(kind, &hash), &data[21..]))
+}
+
+/// Current state of a registered name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameRecord {
+    pub name: String,
+    /// Address that signed the registration
+    pub owner: Address,
+    /// Address payments to the name go to
+    pub address: Address,
+    pub registered_height: u64,
+    /// First height at which the name no longer resolves
+    pub expires_height: u64,
+    /// Transaction of the latest operation applied
+    pub txid: String,
+}
+
+impl NameRecord {
+    pub fn is_active(&self, height: u64) -> bool {
+        height < self.expires_height
+    }
+
+    /// Whether the name is still reserved for renewal at `height`
+    pub fn is_reserved(&self, height: u64) -> bool {
+        height < self.expires_height + GRACE_BLOCKS
+    }
+}
+
+/// Indexer and resolver for names
+pub struct NameRegistry {
+    records: RwLock<HashMap<String, NameRecord>>,
+    /// Height and hash of the last indexed block
+    tip: RwLock<Option<(u64, Hash256)>>,
+}
+
+impl NameRegistry {
+    pub fn new() -> Self {
+        Self {
+            records: RwLock::new(HashMap::new()),
+            tip: RwLock::new(None),
+        }
+    }
+
+    /// Apply the name operations in a block; returns how many took effect
+    pub async fn index_block(&self, block: &Block) -> Result<usize> {
+        let height = block.header.height as u64;
+        let mut records = self.records.write().await;
+        let mut applied = 0;
+        for tx in block.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+            let txid = hex::encode(tx.get_hash()?);
+            for output in &tx.outputs {
+                let Some(commitment) = NameCommitment::from_script(&output.script_pubkey) else { continue };
+                if apply(&mut records, commitment, signer_of(tx), output.value, height, &txid) {
+                    applied += 1;
+                }
+            }
+        }
+        *self.tip.write().await = Some((height, block.get_hash()));
+        Ok(applied)
+    }
+
+    /// Names with a record, including lapsed ones
+    pub async fn record_count(&self) -> usize {
+        self.records.read().await.len()
+    }
+
+    /// Height and hash of the last indexed block, to detect reorgs and
+    /// resume indexing
+    pub async fn tip(&self) -> Option<(u64, Hash256)> {
+        *self.tip.read().await
+    }
+
+    /// Forget everything so the chain can be indexed again from genesis
+    pub async fn reset(&self) {
+        self.records.write().await.clear();
+        *self.tip.write().await = None;
+    }
+
+    /// Address a name points to at `height`, if it is active
+    pub async fn resolve(&self, name: &str, height: u64) -> Option<NameRecord> {
+        let name = normalize(name).ok()?;
+        self.records.read().await.get(&name).filter(|record| record.is_active(height)).cloned()
+    }
+
+    /// A name's record whether or not it is active
+    pub async fn get(&self, name: &str) -> Option<NameRecord> {
+        let name = normalize(name).ok()?;
+        self.records.read().await.get(&name).cloned()
+    }
+
+    /// Active names pointing at `address`, alphabetically
+    pub async fn names_for(&self, address: &str, height: u64) -> Vec<NameRecord> {
+        let mut names: Vec<NameRecord> = self.records.read().await.values()
+            .filter(|record| record.address == address && record.is_active(height))
+            .cloned()
+            .collect();
+        names.sort_by(|a, b| a.name.cmp(&b.name));
+        names
+    }
+
+    /// Whether `name` can be registered at `height`
+    pub async fn is_available(&self, name: &str, height: u64) -> Result<bool> {
+        let name = normalize(name)?;
+        Ok(self.records.read().await.get(&name).map_or(true, |record| !record.is_reserved(height)))
+    }
+}
+
+impl Default for NameRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Address whose key signed the first input, from its P2PKH script_sig
+fn signer_of(tx: &Transaction) -> Option<Address> {
+    let script_sig = &tx.inputs.first()?.script_sig;
+    let sig_len = *script_sig.first()? as usize;
+    let key_len = *script_sig.get(1 + sig_len)? as usize;
+    let key = script_sig.get(2 + sig_len..2 + sig_len + key_len)?;
+    let key: [u8; 33] = key.try_into().ok()?;
+    ScriptBuilder::pubkey_to_address(&key).ok()
+}
+
+/// Apply one operation; false if the rules reject it
+fn apply(
+    records: &mut HashMap<String, NameRecord>,
+    commitment: NameCommitment,
+    signer: Option<Address>,
+    fee: u64,
+    height: u64,
+    txid: &str,
+) -> bool {
+    let periods = (fee / FEE_PER_PERIOD).min(MAX_PERIODS);
+    let current = records.get_mut(&commitment.name);
+    match (commitment.op, current) {
+        (NameOp::Register { address }, current) => {
+            let Some(owner) = signer else { return false };
+            if periods == 0 || current.is_some_and(|record| record.is_reserved(height)) {
+                return false;
+            }
+            records.insert(commitment.name.clone(), NameRecord {
+                name: commitment.name,
+                owner,
+                address,
+                registered_height: height,
+                expires_height: height + periods * PERIOD_BLOCKS,
+                txid: txid.to_string(),
+            });
+            true
+        }
+        (NameOp::Renew, Some(record)) => {
+            if periods == 0 || !record.is_reserved(height) {
+                return false;
+            }
+            let cap = height + MAX_PERIODS * PERIOD_BLOCKS;
+            record.expires_height = (record.expires_height.max(height) + periods * PERIOD_BLOCKS).min(cap);
+            record.txid = txid.to_string();
+            true
+        }
+        (NameOp::Update { address }, Some(record)) => {
+            if signer.as_deref() != Some(record.owner.as_str()) || !record.is_active(height) {
+                return false;
+            }
+            record.address = address;
+            record.txid = txid.to_string();
+            true
+        }
+        (_, None) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::transaction::TransactionInput;
+
+    fn signer(seed: u8) -> (Vec<u8>, Address) {
+        let key = crate::crypto::derive_public_key(&[seed; 32]).unwrap();
+        let compressed: [u8; 33] = key.as_slice().try_into().unwrap();
+        (key, ScriptBuilder::pubkey_to_address(&compressed).unwrap())
+    }
+
+    fn signed_tx(seed: u8, output: TransactionOutput) -> Transaction {
+        let (key, _) = signer(seed);
+        let mut script_sig = vec![2, 0xde, 0xad];
+        script_sig.push(key.len() as u8);
+        script_sig.extend_from_slice(&key);
+        Transaction::new(1, vec![TransactionInput::new([seed; 32], 0, script_sig)], vec![output])
+    }
+
+    fn block(height: u32, txs: Vec<Transaction>) -> Block {
+        Block::new(BlockHeader::new(1, [0u8; 32], [0u8; 32], 1, height), txs)
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Alice").unwrap(), "alice.edu");
+        assert_eq!(normalize("alice.EDU").unwrap(), "alice.edu");
+        assert!(normalize("al").is_err());
+        assert!(normalize("-alice").is_err());
+        assert!(normalize("al ice").is_err());
+        assert!(is_name("bob.edu"));
+        assert!(!is_name("edu1qabc"));
+    }
+
+    #[test]
+    fn test_commitment_round_trip() {
+        let (_, address) = signer(1);
+        for commitment in [
+            NameCommitment::register("alice", &address).unwrap(),
+            NameCommitment::renew("alice").unwrap(),
+            NameCommitment::update("alice", &address).unwrap(),
+        ] {
+            let script = commitment.to_script().unwrap();
+            assert_eq!(NameCommitment::from_script(&script), Some(commitment));
+        }
+        assert!(NameCommitment::from_script(&ScriptBuilder::create_op_return_script(b"EDNS\x09bob").unwrap()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_resolve_and_expire() {
+        let registry = NameRegistry::new();
+        let (_, alice) = signer(1);
+        let register = NameCommitment::register("alice", &alice).unwrap().to_output(1).unwrap();
+        assert_eq!(registry.index_block(&block(10, vec![signed_tx(1, register)])).await.unwrap(), 1);
+
+        let record = registry.resolve("alice.edu", 11).await.unwrap();
+        assert_eq!(record.address, alice);
+        assert_eq!(record.owner, alice);
+        assert_eq!(record.expires_height, 10 + PERIOD_BLOCKS);
+
+        // Expired names stop resolving but stay reserved through the grace period
+        let expiry = 10 + PERIOD_BLOCKS;
+        assert!(registry.resolve("alice", expiry).await.is_none());
+        assert!(!registry.is_available("alice", expiry).await.unwrap());
+        assert!(registry.is_available("alice", expiry + GRACE_BLOCKS).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_taken_names_and_ownership() {
+        let registry = NameRegistry::new();
+        let (_, alice) = signer(1);
+        let (_, mallory) = signer(2);
+        registry.index_block(&block(1, vec![
+            signed_tx(1, NameCommitment::register("alice", &alice).unwrap().to_output(1).unwrap()),
+        ])).await.unwrap();
+
+        // A second registration, an underpaid one and an update by a non-owner are all ignored
+        let mut underpaid = NameCommitment::register("cheap", &mallory).unwrap().to_output(1).unwrap();
+        underpaid.value = FEE_PER_PERIOD - 1;
+        let applied = registry.index_block(&block(2, vec![
+            signed_tx(2, NameCommitment::register("alice", &mallory).unwrap().to_output(1).unwrap()),
+            signed_tx(2, underpaid),
+            signed_tx(2, NameCommitment::update("alice", &mallory).unwrap().to_output(0).unwrap()),
+        ])).await.unwrap();
+        assert_eq!(applied, 0);
+        assert_eq!(registry.resolve("alice", 3).await.unwrap().address, alice);
+
+        // The owner can repoint it
+        registry.index_block(&block(3, vec![
+            signed_tx(1, NameCommitment::update("alice", &mallory).unwrap().to_output(0).unwrap()),
+        ])).await.unwrap();
+        assert_eq!(registry.names_for(&mallory, 4).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_renewal_extends_and_is_capped() {
+        let registry = NameRegistry::new();
+        let (_, alice) = signer(1);
+        registry.index_block(&block(1, vec![
+            signed_tx(1, NameCommitment::register("alice", &alice).unwrap().to_output(1).unwrap()),
+        ])).await.unwrap();
+
+        // Anyone may renew, even in the grace period
+        let in_grace = (1 + PERIOD_BLOCKS + 10) as u32;
+        registry.index_block(&block(in_grace, vec![
+            signed_tx(2, NameCommitment::renew("alice").unwrap().to_output(2).unwrap()),
+        ])).await.unwrap();
+        let record = registry.get("alice").await.unwrap();
+        assert_eq!(record.expires_height, in_grace as u64 + 2 * PERIOD_BLOCKS);
+        assert_eq!(record.owner, alice);
+
+        registry.index_block(&block(in_grace + 1, vec![
+            signed_tx(2, NameCommitment::renew("alice").unwrap().to_output(5).unwrap()),
+        ])).await.unwrap();
+        let record = registry.get("alice").await.unwrap();
+        assert_eq!(record.expires_height, in_grace as u64 + 1 + MAX_PERIODS * PERIOD_BLOCKS);
+    }
+}
@@ -0,0 +1,182 @@
+//! Network-adjusted time
+//!
+//! Every peer reports its clock in the version handshake. The median offset
+//! between those clocks and ours corrects a misconfigured local clock when
+//! judging block timestamps, so a host that is a few minutes off does not
+//! reject blocks the rest of the network accepts. Large corrections are
+//! refused and logged instead: a clock that far off needs fixing, and a
+//! handful of peers should not be able to move our time by hours.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+/// Samples needed before the local clock is adjusted
+pub const MIN_SAMPLES: usize = 5;
+
+/// Peer samples kept; the oldest is dropped first
+const MAX_SAMPLES: usize = 200;
+
+/// Largest correction applied to the local clock (70 minutes)
+pub const MAX_ADJUSTMENT_SECS: i64 = 70 * 60;
+
+/// Default skew, in seconds, at which the node starts warning
+pub const DEFAULT_SKEW_WARNING_SECS: i64 = 5 * 60;
+
+/// Clock offsets reported by peers
+#[derive(Debug)]
+pub struct NetworkTime {
+    warn_after_secs: i64,
+    samples: RwLock<Samples>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    /// Peer -> its clock minus ours, in seconds
+    offsets: HashMap<String, i64>,
+    order: VecDeque<String>,
+    /// Median applied to the local clock
+    offset: i64,
+    warned: bool,
+}
+
+/// `node_getNetworkTime` report
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeStatus {
+    pub local_time: u64,
+    pub adjusted_time: u64,
+    /// Correction applied to the local clock, in seconds
+    pub offset_secs: i64,
+    /// Median of the peer offsets, applied or not
+    pub median_offset_secs: Option<i64>,
+    pub samples: usize,
+    /// Local clock is off by more than the warning threshold
+    pub skewed: bool,
+}
+
+impl Default for NetworkTime {
+    fn default() -> Self {
+        Self::new(DEFAULT_SKEW_WARNING_SECS)
+    }
+}
+
+impl NetworkTime {
+    pub fn new(warn_after_secs: i64) -> Self {
+        Self {
+            warn_after_secs,
+            samples: RwLock::new(Samples::default()),
+        }
+    }
+
+    /// Record a peer's clock, as it reported it
+    pub fn add_peer_time(&self, peer: &str, peer_time: u64) {
+        self.add_sample(peer, peer_time as i64 - local_time() as i64);
+    }
+
+    /// Record a peer's clock offset from ours; a peer counts once
+    pub fn add_sample(&self, peer: &str, offset: i64) {
+        let mut samples = self.samples.write().unwrap();
+        if samples.offsets.insert(peer.to_string(), offset).is_none() {
+            samples.order.push_back(peer.to_string());
+            if samples.order.len() > MAX_SAMPLES {
+                if let Some(oldest) = samples.order.pop_front() {
+                    samples.offsets.remove(&oldest);
+                }
+            }
+        }
+
+        let Some(median) = median(&samples.offsets) else { return };
+        if samples.offsets.len() >= MIN_SAMPLES {
+            samples.offset = if median.abs() <= MAX_ADJUSTMENT_SECS { median } else { 0 };
+        }
+
+        let skewed = samples.offsets.len() >= MIN_SAMPLES && median.abs() > self.warn_after_secs;
+        if skewed && !samples.warned {
+            warn!("🕰️  ************************************************************");
+            warn!("🕰️  Local clock is {}s {} the median of {} peers", median.abs(),
+                  if median > 0 { "behind" } else { "ahead of" }, samples.offsets.len());
+            if median.abs() > MAX_ADJUSTMENT_SECS {
+                warn!("🕰️  That is too far to correct; fix the system clock or blocks may be rejected");
+            } else {
+                warn!("🕰️  Block timestamps are checked against network time; fix the system clock");
+            }
+            warn!("🕰️  ************************************************************");
+        } else if !skewed && samples.warned {
+            info!("🕰️  Local clock back within {}s of the network", self.warn_after_secs);
+        }
+        samples.warned = skewed;
+    }
+
+    /// Correction applied to the local clock, in seconds
+    pub fn offset(&self) -> i64 {
+        self.samples.read().unwrap().offset
+    }
+
+    /// Local time corrected by the peer median, in unix seconds
+    pub fn adjusted_time(&self) -> u64 {
+        (local_time() as i64 + self.offset()).max(0) as u64
+    }
+
+    pub fn status(&self) -> TimeStatus {
+        let samples = self.samples.read().unwrap();
+        let local = local_time();
+        TimeStatus {
+            local_time: local,
+            adjusted_time: (local as i64 + samples.offset).max(0) as u64,
+            offset_secs: samples.offset,
+            median_offset_secs: median(&samples.offsets),
+            samples: samples.offsets.len(),
+            skewed: samples.warned,
+        }
+    }
+}
+
+fn median(offsets: &HashMap<String, i64>) -> Option<i64> {
+    let mut values: Vec<i64> = offsets.values().copied().collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    Some(values[values.len() / 2])
+}
+
+fn local_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjusts_by_median_once_enough_peers() {
+        let time = NetworkTime::new(60);
+        for (i, offset) in [100, 120, -30, 110].iter().enumerate() {
+            time.add_sample(&format!("peer{}", i), *offset);
+        }
+        assert_eq!(time.offset(), 0);
+
+        time.add_sample("peer4", 90);
+        assert_eq!(time.offset(), 100);
+        assert!(time.status().skewed);
+
+        // A repeat from the same peer replaces its sample
+        time.add_sample("peer4", 95);
+        assert_eq!(time.status().samples, 5);
+    }
+
+    #[test]
+    fn test_refuses_large_adjustments() {
+        let time = NetworkTime::default();
+        for i in 0..MIN_SAMPLES {
+            time.add_sample(&format!("peer{}", i), 3 * 60 * 60);
+        }
+        assert_eq!(time.offset(), 0);
+        assert!(time.status().skewed);
+        assert_eq!(time.status().median_offset_secs, Some(3 * 60 * 60));
+    }
+}
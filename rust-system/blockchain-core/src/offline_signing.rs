@@ -0,0 +1,339 @@
+//! Air-gapped signing
+//!
+//! Keys that never touch a networked machine sign in three steps. The online
+//! side, which holds no keys, exports an `UnsignedTransaction`: the
+//! transaction with empty script_sigs and the outputs its inputs spend, so
+//! the signer can compute sighashes and show the fee without a UTXO set. The
+//! offline signer, built without the `network` feature, signs it with
+//! `HDWallet::sign_offline` and returns a `SignatureSet`. The online side
+//! checks every signature against the transaction it exported and puts
+//! them in place.
+//!
+//! Both travel as QR code sequences. Each code holds one chunk:
+//!
+//! ```text
+//! edutx:<kind>/<part>-<total>/<checksum>/<base64url data>
+//! ```
+//!
+//! `kind` is `utx` or `sig`, parts count from 1 and the checksum is the
+//! first 4 bytes of the double SHA256 of the whole payload, in hex. It ties
+//! the chunks of one sequence together and proves the reassembled payload
+//! intact. Chunks can be scanned in any order and more than once.
+
+use crate::crypto::{double_sha256, verify_signature};
+use crate::sighash::SighashCache;
+use crate::transaction::{Transaction, TransactionOutput};
+use crate::utxo::UTXO;
+use crate::{BlockchainError, Hash256, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Scheme every chunk starts with
+pub const QR_SCHEME: &str = "edutx";
+
+/// Data characters per chunk, small enough for a QR code phone cameras read
+/// reliably
+pub const DEFAULT_QR_CHUNK_LEN: usize = 400;
+
+/// What a QR sequence carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadKind {
+    Unsigned,
+    Signatures,
+}
+
+impl PayloadKind {
+    fn tag(self) -> &'static str {
+        match self {
+            PayloadKind::Unsigned => "utx",
+            PayloadKind::Signatures => "sig",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "utx" => Some(PayloadKind::Unsigned),
+            "sig" => Some(PayloadKind::Signatures),
+            _ => None,
+        }
+    }
+}
+
+/// A transaction waiting for offline signatures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    pub transaction: Transaction,
+    /// Output each input spends, in input order
+    pub spent_outputs: Vec<TransactionOutput>,
+    /// HD account whose keys sign
+    pub account_index: u32,
+}
+
+/// Signatures made offline for one `UnsignedTransaction`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureSet {
+    /// `UnsignedTransaction::id` of the transaction signed, in hex
+    pub unsigned_id: String,
+    /// script_sig of each input, in input order
+    pub script_sigs: Vec<Vec<u8>>,
+}
+
+impl UnsignedTransaction {
+    /// Package `transaction` for signing, clearing any script_sigs it has
+    pub fn new(mut transaction: Transaction, spent_outputs: Vec<TransactionOutput>, account_index: u32) -> Result<Self> {
+        if spent_outputs.len() != transaction.inputs.len() {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "{} inputs but {} spent outputs",
+                transaction.inputs.len(),
+                spent_outputs.len()
+            )));
+        }
+        for input in &mut transaction.inputs {
+            input.script_sig.clear();
+        }
+        transaction.clear_cache();
+        Ok(Self { transaction, spent_outputs, account_index })
+    }
+
+    /// Hash of the unsigned transaction, naming it to the signer and back
+    pub fn id(&self) -> Hash256 {
+        self.transaction.calculate_hash()
+    }
+
+    /// Fee the transaction pays, as the signer shows it before signing
+    pub fn fee(&self) -> u64 {
+        let input_value: u64 = self.spent_outputs.iter().map(|output| output.value).sum();
+        input_value.saturating_sub(self.transaction.get_total_output_value())
+    }
+
+    /// The spent outputs as UTXOs, for the wallet's signing
+    pub fn spent_utxos(&self) -> Vec<UTXO> {
+        self.transaction.inputs.iter()
+            .zip(&self.spent_outputs)
+            .map(|(input, output)| UTXO::new(input.prev_tx_hash, input.prev_output_index, output.clone(), 0, false))
+            .collect()
+    }
+
+    /// The signed transaction, once every signature in `signatures` is
+    /// checked against this transaction and the output its input spends
+    pub fn apply(&self, signatures: &SignatureSet) -> Result<Transaction> {
+        if signatures.unsigned_id != hex::encode(self.id()) {
+            return Err(BlockchainError::SigningError(format!(
+                "Signatures are for transaction {}, not {}",
+                signatures.unsigned_id,
+                hex::encode(self.id())
+            )));
+        }
+        if signatures.script_sigs.len() != self.transaction.inputs.len() {
+            return Err(BlockchainError::SigningError(format!(
+                "{} signatures for {} inputs",
+                signatures.script_sigs.len(),
+                self.transaction.inputs.len()
+            )));
+        }
+
+        let mut sighashes = SighashCache::new(&self.transaction);
+        for (i, (script_sig, spent)) in signatures.script_sigs.iter().zip(&self.spent_outputs).enumerate() {
+            let (signature, public_key) = split_script_sig(script_sig)
+                .ok_or_else(|| BlockchainError::SigningError(format!("Input {} script_sig is malformed", i)))?;
+            let (&sighash_type, der) = signature.split_last()
+                .ok_or_else(|| BlockchainError::SigningError(format!("Input {} signature is empty", i)))?;
+            let hash = sighashes.signature_hash(i, &spent.script_pubkey, spent.value, sighash_type as u32)?;
+            if !verify_signature(der, public_key, &hash)? {
+                return Err(BlockchainError::SigningError(format!("Input {} signature does not verify", i)));
+            }
+        }
+
+        let mut signed = self.transaction.clone();
+        for (input, script_sig) in signed.inputs.iter_mut().zip(&signatures.script_sigs) {
+            input.script_sig = script_sig.clone();
+        }
+        signed.clear_cache();
+        Ok(signed)
+    }
+
+    pub fn to_qr_chunks(&self, max_len: usize) -> Result<Vec<String>> {
+        let payload = serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        Ok(encode_qr_chunks(PayloadKind::Unsigned, &payload, max_len))
+    }
+
+    pub fn from_qr_chunks<S: AsRef<str>>(chunks: &[S]) -> Result<Self> {
+        let payload = decode_qr_chunks(PayloadKind::Unsigned, chunks)?;
+        serde_json::from_slice(&payload).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+}
+
+impl SignatureSet {
+    pub fn to_qr_chunks(&self, max_len: usize) -> Result<Vec<String>> {
+        let payload = serde_json::to_vec(self).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        Ok(encode_qr_chunks(PayloadKind::Signatures, &payload, max_len))
+    }
+
+    pub fn from_qr_chunks<S: AsRef<str>>(chunks: &[S]) -> Result<Self> {
+        let payload = decode_qr_chunks(PayloadKind::Signatures, chunks)?;
+        serde_json::from_slice(&payload).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+    }
+}
+
+/// Signature and public key pushed by a P2PKH script_sig
+fn split_script_sig(script_sig: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&signature_len, rest) = script_sig.split_first()?;
+    if rest.len() < signature_len as usize {
+        return None;
+    }
+    let (signature, rest) = rest.split_at(signature_len as usize);
+    let (&key_len, rest) = rest.split_first()?;
+    (rest.len() == key_len as usize).then_some((signature, rest))
+}
+
+fn payload_checksum(payload: &[u8]) -> String {
+    hex::encode(&double_sha256(payload)[..4])
+}
+
+/// Split `payload` into QR chunks of at most `max_len` data characters
+pub fn encode_qr_chunks(kind: PayloadKind, payload: &[u8], max_len: usize) -> Vec<String> {
+    let data = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(payload);
+    let checksum = payload_checksum(payload);
+    let parts: Vec<&str> = if data.is_empty() {
+        vec![""]
+    } else {
+        // base64 is ASCII, so splitting bytes splits characters
+        data.as_bytes()
+            .chunks(max_len.max(1))
+            .map(|part| std::str::from_utf8(part).expect("base64 is ASCII"))
+            .collect()
+    };
+    let total = parts.len();
+    parts.iter()
+        .enumerate()
+        .map(|(i, part)| format!("{}:{}/{}-{}/{}/{}", QR_SCHEME, kind.tag(), i + 1, total, checksum, part))
+        .collect()
+}
+
+/// Reassemble a payload of `kind` from all of its chunks, in any order
+pub fn decode_qr_chunks<S: AsRef<str>>(kind: PayloadKind, chunks: &[S]) -> Result<Vec<u8>> {
+    let mut assembler = QrAssembler::default();
+    for chunk in chunks {
+        assembler.add(chunk.as_ref())?;
+    }
+    match assembler.finish()? {
+        (found, payload) if found == kind => Ok(payload),
+        (found, _) => Err(BlockchainError::InvalidInput(format!(
+            "QR sequence carries {}, expected {}",
+            found.tag(),
+            kind.tag()
+        ))),
+    }
+}
+
+/// Collects the chunks of one QR sequence as they are scanned
+#[derive(Debug, Default)]
+pub struct QrAssembler {
+    sequence: Option<(PayloadKind, usize, String)>,
+    parts: BTreeMap<usize, String>,
+}
+
+impl QrAssembler {
+    /// Take one scanned chunk. Repeats are ignored; a chunk of another
+    /// sequence is an error. Returns whether the sequence is now complete.
+    pub fn add(&mut self, chunk: &str) -> Result<bool> {
+        let malformed = || BlockchainError::InvalidInput(format!("Not an {} QR chunk: {}", QR_SCHEME, chunk));
+        let body = chunk.trim()
+            .strip_prefix(QR_SCHEME)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or_else(malformed)?;
+        let mut fields = body.splitn(4, '/');
+        let (tag, position, checksum, data) = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(tag), Some(position), Some(checksum), Some(data)) => (tag, position, checksum, data),
+            _ => return Err(malformed()),
+        };
+        let kind = PayloadKind::from_tag(tag).ok_or_else(malformed)?;
+        let (part, total) = position.split_once('-')
+            .and_then(|(part, total)| Some((part.parse::<usize>().ok()?, total.parse::<usize>().ok()?)))
+            .filter(|&(part, total)| part >= 1 && part <= total)
+            .ok_or_else(malformed)?;
+
+        match &self.sequence {
+            None => self.sequence = Some((kind, total, checksum.to_string())),
+            Some((k, t, c)) if *k == kind && *t == total && c == checksum => {}
+            Some(_) => {
+                return Err(BlockchainError::InvalidInput(
+                    "QR chunk belongs to a different sequence".to_string(),
+                ))
+            }
+        }
+        self.parts.entry(part).or_insert_with(|| data.to_string());
+        Ok(self.is_complete())
+    }
+
+    /// Chunks received and chunks in the sequence, zero before the first
+    pub fn progress(&self) -> (usize, usize) {
+        (self.parts.len(), self.sequence.as_ref().map_or(0, |(_, total, _)| *total))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        let (received, total) = self.progress();
+        total > 0 && received == total
+    }
+
+    /// The payload and what it carries, once every chunk is in
+    pub fn finish(self) -> Result<(PayloadKind, Vec<u8>)> {
+        let (received, total) = self.progress();
+        let (kind, _, checksum) = match self.sequence {
+            Some(sequence) if received == total => sequence,
+            _ => {
+                return Err(BlockchainError::InvalidInput(format!(
+                    "QR sequence incomplete: {} of {} chunks",
+                    received, total
+                )))
+            }
+        };
+        let data: String = self.parts.into_values().collect();
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(data)
+            .map_err(|e| BlockchainError::InvalidInput(format!("QR data is not base64: {}", e)))?;
+        if payload_checksum(&payload) != checksum {
+            return Err(BlockchainError::InvalidInput("QR sequence checksum does not match".to_string()));
+        }
+        Ok((kind, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qr_chunks_reassemble_in_any_order() {
+        let payload: Vec<u8> = (0..=255u8).cycle().take(1_000).collect();
+        let mut chunks = encode_qr_chunks(PayloadKind::Unsigned, &payload, 100);
+        assert_eq!(chunks.len(), 14);
+        assert!(chunks[0].starts_with("edutx:utx/1-14/"));
+
+        chunks.reverse();
+        chunks.push(chunks[3].clone());
+        assert_eq!(decode_qr_chunks(PayloadKind::Unsigned, &chunks).unwrap(), payload);
+        assert!(decode_qr_chunks(PayloadKind::Signatures, &chunks).is_err());
+
+        // A missing chunk, or one from another sequence, is refused
+        let mut assembler = QrAssembler::default();
+        for chunk in &chunks[1..] {
+            assembler.add(chunk).unwrap();
+        }
+        assert_eq!(assembler.progress(), (13, 14));
+        let other = encode_qr_chunks(PayloadKind::Unsigned, b"other", 100);
+        assert!(assembler.add(&other[0]).is_err());
+        assert!(assembler.finish().is_err());
+    }
+
+    #[test]
+    fn test_corrupted_chunk_fails_checksum() {
+        let chunks = encode_qr_chunks(PayloadKind::Signatures, b"signatures go here", 8);
+        let mut corrupted = chunks.clone();
+        let last = corrupted.pop().unwrap();
+        let flipped = if last.ends_with('A') { 'B' } else { 'A' };
+        corrupted.push(format!("{}{}", &last[..last.len() - 1], flipped));
+        assert!(decode_qr_chunks(PayloadKind::Signatures, &corrupted).is_err());
+        assert!(decode_qr_chunks(PayloadKind::Signatures, &chunks).is_ok());
+    }
+}
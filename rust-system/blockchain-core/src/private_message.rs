@@ -0,0 +1,287 @@
+//! Encrypted wallet-to-wallet messages
+//!
+//! Two wallets negotiating a payment or coordinating an escrow can talk over
+//! the P2P network instead of a server. A message is addressed to the
+//! recipient's public key and encrypted to it with ECIES: an ephemeral key
+//! agrees a secret with the recipient's key over ECDH, HMAC-SHA256 derives
+//! a cipher key and a MAC key from it, the content is XORed with an
+//! HMAC-SHA256 counter keystream and the ciphertext is authenticated
+//! (encrypt-then-MAC). Inside, the sender signs the content, the recipient
+//! and the time, so the recipient learns who wrote it and a message can't
+//! be passed on to someone else as if it were written for them. Relaying
+//! nodes see only the recipient key.
+//!
+//! Relaying is free, so every message carries proof of work: its id must
+//! start with `pow_bits` zero bits. Messages expire after `MESSAGE_TTL_SECS`.
+
+use crate::crypto;
+use crate::script_utils::ScriptBuilder;
+use crate::{Address, BlockchainError, Hash256, PrivateKey, PublicKey, Result};
+use hmac::{Hmac, Mac};
+use secp256k1::ecdh::SharedSecret;
+use secp256k1::{PublicKey as Secp256k1PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNATURE_TAG: &[u8] = b"EDU-PRIVATE-MESSAGE";
+
+/// Longest content a message may carry
+pub const MAX_CONTENT_LEN: usize = 4096;
+
+/// Messages older than this are no longer relayed or accepted
+pub const MESSAGE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// How far in the future a message's timestamp may be
+pub const MAX_CLOCK_SKEW_SECS: u64 = 10 * 60;
+
+/// Proof of work relaying nodes require, in leading zero bits of the id
+pub const DEFAULT_POW_BITS: u32 = 18;
+
+/// A message encrypted to one public key, as relayed between nodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedMessage {
+    /// Compressed public key the message is for
+    pub recipient: PublicKey,
+    /// Compressed ephemeral public key of the ECDH agreement
+    pub ephemeral_key: PublicKey,
+    pub ciphertext: Vec<u8>,
+    /// HMAC-SHA256 over ephemeral key and ciphertext
+    pub tag: [u8; 32],
+    /// Seconds since the Unix epoch when sent
+    pub timestamp: u64,
+    /// Varied until the id meets the proof of work
+    pub pow_nonce: u64,
+}
+
+/// What the recipient reads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenedMessage {
+    /// Compressed public key of the sender
+    pub sender: PublicKey,
+    /// P2PKH address of the sender's key
+    pub sender_address: Address,
+    pub content: Vec<u8>,
+    pub timestamp: u64,
+}
+
+/// The plaintext: content signed by the sender
+#[derive(Serialize, Deserialize)]
+struct SignedContent {
+    sender: PublicKey,
+    content: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl EncryptedMessage {
+    /// Sign `content` with `sender_key`, encrypt it to `recipient` and do
+    /// `pow_bits` of proof of work
+    pub fn seal(sender_key: &PrivateKey, recipient: &[u8], content: &[u8], timestamp: u64, pow_bits: u32) -> Result<Self> {
+        if content.len() > MAX_CONTENT_LEN {
+            return Err(BlockchainError::InvalidInput(format!("Message content exceeds {} bytes", MAX_CONTENT_LEN)));
+        }
+        let recipient_key = parse_public_key(recipient)?;
+        let recipient = recipient_key.serialize().to_vec();
+        let sender = crypto::derive_public_key(sender_key)?;
+        let signature = crypto::sign_hash(&signature_hash(&recipient, content, timestamp), sender_key)?;
+        let plaintext = bincode::serialize(&SignedContent { sender, content: content.to_vec(), signature })
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+
+        let ephemeral_secret = secret_key(&crypto::generate_private_key()?)?;
+        let ephemeral_key = Secp256k1PublicKey::from_secret_key(secp256k1::SECP256K1, &ephemeral_secret)
+            .serialize()
+            .to_vec();
+        let (cipher_key, mac_key) = derive_keys(&SharedSecret::new(&recipient_key, &ephemeral_secret));
+        let ciphertext = apply_keystream(&cipher_key, &plaintext);
+        let tag = digest(authenticate(&mac_key, &ephemeral_key, &ciphertext));
+
+        let mut message = Self { recipient, ephemeral_key, ciphertext, tag, timestamp, pow_nonce: rand::random() };
+        while message.work_bits() < pow_bits {
+            message.pow_nonce = message.pow_nonce.wrapping_add(1);
+        }
+        Ok(message)
+    }
+
+    /// Decrypt with the recipient's key and check the sender's signature
+    pub fn open(&self, recipient_key: &PrivateKey) -> Result<OpenedMessage> {
+        if crypto::derive_public_key(recipient_key)? != self.recipient {
+            return Err(BlockchainError::CryptoError("Message is for another key".to_string()));
+        }
+        let secret = secret_key(recipient_key)?;
+        let (cipher_key, mac_key) = derive_keys(&SharedSecret::new(&parse_public_key(&self.ephemeral_key)?, &secret));
+        authenticate(&mac_key, &self.ephemeral_key, &self.ciphertext)
+            .verify_slice(&self.tag)
+            .map_err(|_| BlockchainError::CryptoError("Message failed authentication".to_string()))?;
+
+        let plaintext = apply_keystream(&cipher_key, &self.ciphertext);
+        let signed: SignedContent = bincode::deserialize(&plaintext)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        let hash = signature_hash(&self.recipient, &signed.content, self.timestamp);
+        if !crypto::verify_signature(&signed.signature, &signed.sender, &hash)? {
+            return Err(BlockchainError::InvalidSignature("Message signature does not verify".to_string()));
+        }
+        let sender_key: [u8; 33] = signed.sender.as_slice().try_into()
+            .map_err(|_| BlockchainError::CryptoError("Sender key is not compressed".to_string()))?;
+        Ok(OpenedMessage {
+            sender_address: ScriptBuilder::pubkey_to_address(&sender_key)?,
+            sender: signed.sender,
+            content: signed.content,
+            timestamp: self.timestamp,
+        })
+    }
+
+    /// Hash naming the message, over every field; relays use it to drop
+    /// repeats and check the proof of work
+    pub fn id(&self) -> Hash256 {
+        let mut data = Vec::with_capacity(self.recipient.len() + self.ephemeral_key.len() + self.ciphertext.len() + 48);
+        data.extend_from_slice(&self.recipient);
+        data.extend_from_slice(&self.ephemeral_key);
+        data.extend_from_slice(&(self.ciphertext.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.ciphertext);
+        data.extend_from_slice(&self.tag);
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&self.pow_nonce.to_le_bytes());
+        crypto::double_sha256(&data)
+    }
+
+    /// Leading zero bits of the id
+    pub fn work_bits(&self) -> u32 {
+        let id = self.id();
+        let mut bits = 0;
+        for byte in id {
+            bits += byte.leading_zeros();
+            if byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+
+    /// Whether a node should accept and relay the message at `now`. Only
+    /// the envelope is checked; nobody but the recipient can decrypt it.
+    pub fn validate(&self, now: u64, pow_bits: u32) -> Result<()> {
+        if self.recipient.len() != 33 || self.ephemeral_key.len() != 33 {
+            return Err(BlockchainError::InvalidInput("Message keys must be compressed".to_string()));
+        }
+        // Content with the sender's key and signature around it
+        if self.ciphertext.len() > MAX_CONTENT_LEN + 256 {
+            return Err(BlockchainError::InvalidInput("Message too large".to_string()));
+        }
+        if self.timestamp > now + MAX_CLOCK_SKEW_SECS {
+            return Err(BlockchainError::InvalidInput("Message timestamp is in the future".to_string()));
+        }
+        if now.saturating_sub(self.timestamp) > MESSAGE_TTL_SECS {
+            return Err(BlockchainError::InvalidInput("Message has expired".to_string()));
+        }
+        if self.work_bits() < pow_bits {
+            return Err(BlockchainError::InvalidInput(format!(
+                "Message proof of work is {} bits, {} required",
+                self.work_bits(),
+                pow_bits
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn secret_key(key: &PrivateKey) -> Result<SecretKey> {
+    SecretKey::from_slice(key).map_err(|e| BlockchainError::CryptoError(format!("Invalid private key: {}", e)))
+}
+
+fn parse_public_key(key: &[u8]) -> Result<Secp256k1PublicKey> {
+    Secp256k1PublicKey::from_slice(key)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid public key: {}", e)))
+}
+
+/// Hash the sender signs: content bound to recipient and time
+fn signature_hash(recipient: &[u8], content: &[u8], timestamp: u64) -> Hash256 {
+    let mut data = Vec::with_capacity(SIGNATURE_TAG.len() + recipient.len() + 12 + content.len());
+    data.extend_from_slice(SIGNATURE_TAG);
+    data.extend_from_slice(recipient);
+    data.extend_from_slice(&timestamp.to_le_bytes());
+    data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    data.extend_from_slice(content);
+    crypto::double_sha256(&data)
+}
+
+fn hmac(key: &[u8], data: &[&[u8]]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in data {
+        mac.update(part);
+    }
+    mac
+}
+
+fn digest(mac: HmacSha256) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+/// Cipher key and MAC key from the ECDH secret
+fn derive_keys(secret: &SharedSecret) -> ([u8; 32], [u8; 32]) {
+    let secret = secret.secret_bytes();
+    (digest(hmac(&secret, &[b"edunet-message-cipher"])), digest(hmac(&secret, &[b"edunet-message-mac"])))
+}
+
+/// XOR with HMAC-SHA256(key, counter) blocks; encrypts and decrypts
+fn apply_keystream(key: &[u8; 32], data: &[u8]) -> Vec<u8> {
+    data.chunks(32)
+        .enumerate()
+        .flat_map(|(counter, chunk)| {
+            let block = hmac(key, &[&(counter as u64).to_be_bytes()]).finalize().into_bytes();
+            chunk.iter().zip(block).map(|(byte, key_byte)| byte ^ key_byte).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn authenticate(mac_key: &[u8; 32], ephemeral_key: &[u8], ciphertext: &[u8]) -> HmacSha256 {
+    hmac(mac_key, &[ephemeral_key, ciphertext])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_seal_and_open() {
+        let alice = crypto::sha256(b"alice message key");
+        let bob = crypto::sha256(b"bob message key");
+        let bob_public = crypto::derive_public_key(&bob).unwrap();
+
+        let offer = b"{\"offer\":\"2 EDU for the textbook\",\"escrow\":\"edu3...\"}";
+        let message = EncryptedMessage::seal(&alice, &bob_public, offer, NOW, 8).unwrap();
+        assert!(message.work_bits() >= 8);
+        assert!(message.validate(NOW + 60, 8).is_ok());
+        assert!(!message.ciphertext.windows(offer.len()).any(|w| w == offer));
+
+        let opened = message.open(&bob).unwrap();
+        assert_eq!(opened.content, offer.to_vec());
+        assert_eq!(opened.sender, crypto::derive_public_key(&alice).unwrap());
+        assert!(opened.sender_address.starts_with("edu1q"));
+
+        // Only the recipient can read it, and any change is caught
+        assert!(message.open(&alice).is_err());
+        let mut tampered = message.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(tampered.open(&bob).is_err());
+    }
+
+    #[test]
+    fn test_relay_checks() {
+        let sender = crypto::sha256(b"sender");
+        let recipient = crypto::derive_public_key(&crypto::sha256(b"recipient")).unwrap();
+        let message = EncryptedMessage::seal(&sender, &recipient, b"hello", NOW, 4).unwrap();
+
+        assert!(message.validate(NOW, 4).is_ok());
+        assert!(message.validate(NOW + MESSAGE_TTL_SECS + 1, 4).is_err());
+        assert!(message.validate(NOW - MAX_CLOCK_SKEW_SECS - 1, 4).is_err());
+        assert!(message.validate(NOW, 64).is_err());
+
+        let too_long = vec![0u8; MAX_CONTENT_LEN + 1];
+        assert!(EncryptedMessage::seal(&sender, &recipient, &too_long, NOW, 0).is_err());
+    }
+}
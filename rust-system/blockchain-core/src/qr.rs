@@ -0,0 +1,212 @@
+//! QR code rendering
+//!
+//! One implementation for every QR code the project prints or shows:
+//! addresses, payment URIs, invoices and voucher cards. The REST API serves
+//! it at `GET /api/v1/qr`, the web frontend renders invoice codes with it
+//! and `voucher-pdf-gen` prints voucher cards with it, so a code scans the
+//! same whichever of them produced it.
+
+use crate::{BlockchainError, Result};
+use qrcode::render::svg;
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+
+/// Light modules around the code, the minimum the QR standard asks for
+const QUIET_ZONE: u32 = 4;
+
+/// Smallest and largest image edge accepted, in pixels
+pub const MIN_SIZE: u32 = 64;
+pub const MAX_SIZE: u32 = 2048;
+
+/// Longest payload accepted, comfortably inside what a version 40 code
+/// holds at the lowest error correction
+pub const MAX_PAYLOAD_LEN: usize = 2048;
+
+/// Image format of a rendered code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+impl QrFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            QrFormat::Svg => "image/svg+xml",
+            QrFormat::Png => "image/png",
+        }
+    }
+}
+
+/// Error correction level: the share of the code that can be damaged and
+/// still scan, about 7%, 15%, 25% and 30%. Printed cards that get handled
+/// want a higher level than codes shown on a screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum QrErrorCorrection {
+    L,
+    #[default]
+    M,
+    Q,
+    H,
+}
+
+impl QrErrorCorrection {
+    fn level(self) -> EcLevel {
+        match self {
+            QrErrorCorrection::L => EcLevel::L,
+            QrErrorCorrection::M => EcLevel::M,
+            QrErrorCorrection::Q => EcLevel::Q,
+            QrErrorCorrection::H => EcLevel::H,
+        }
+    }
+}
+
+impl std::str::FromStr for QrErrorCorrection {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "L" => Ok(QrErrorCorrection::L),
+            "M" => Ok(QrErrorCorrection::M),
+            "Q" => Ok(QrErrorCorrection::Q),
+            "H" => Ok(QrErrorCorrection::H),
+            _ => Err(BlockchainError::InvalidInput(format!("Unknown error correction level '{}', expected L, M, Q or H", s))),
+        }
+    }
+}
+
+/// How to render a code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QrOptions {
+    pub format: QrFormat,
+    /// Minimum image edge in pixels; the image is the smallest whole
+    /// number of pixels per module that reaches it
+    pub size: u32,
+    pub error_correction: QrErrorCorrection,
+}
+
+impl Default for QrOptions {
+    fn default() -> Self {
+        Self { format: QrFormat::Svg, size: 256, error_correction: QrErrorCorrection::M }
+    }
+}
+
+impl QrOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_SIZE..=MAX_SIZE).contains(&self.size) {
+            return Err(BlockchainError::InvalidInput(format!(
+                "QR size must be between {} and {} pixels, got {}",
+                MIN_SIZE, MAX_SIZE, self.size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A rendered code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrImage {
+    pub format: QrFormat,
+    /// SVG text or PNG bytes
+    pub data: Vec<u8>,
+    /// Image edge in pixels
+    pub size: u32,
+}
+
+impl QrImage {
+    pub fn content_type(&self) -> &'static str {
+        self.format.content_type()
+    }
+
+    /// `data:` URI an `<img>` tag can show directly
+    pub fn to_data_uri(&self) -> String {
+        use base64::Engine;
+        format!("data:{};base64,{}", self.content_type(), base64::engine::general_purpose::STANDARD.encode(&self.data))
+    }
+}
+
+/// Render `payload` as a QR code
+pub fn render(payload: &str, options: &QrOptions) -> Result<QrImage> {
+    options.validate()?;
+    if payload.is_empty() {
+        return Err(BlockchainError::InvalidInput("QR payload is empty".to_string()));
+    }
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(BlockchainError::InvalidInput(format!(
+            "QR payload is {} bytes, above {}",
+            payload.len(),
+            MAX_PAYLOAD_LEN
+        )));
+    }
+    let code = QrCode::with_error_correction_level(payload.as_bytes(), options.error_correction.level())
+        .map_err(|e| BlockchainError::InvalidInput(format!("Payload does not fit a QR code: {}", e)))?;
+
+    let modules = code.width() as u32;
+    let edge = modules + 2 * QUIET_ZONE;
+    let scale = ((options.size + edge - 1) / edge).max(1);
+    let size = edge * scale;
+
+    let data = match options.format {
+        QrFormat::Svg => code.render::<svg::Color>()
+            .quiet_zone(true)
+            .module_dimensions(scale, scale)
+            .build()
+            .into_bytes(),
+        QrFormat::Png => {
+            let colors = code.to_colors();
+            let image = image::GrayImage::from_fn(size, size, |x, y| {
+                let (mx, my) = (x / scale, y / scale);
+                let dark = mx >= QUIET_ZONE && my >= QUIET_ZONE
+                    && mx < modules + QUIET_ZONE && my < modules + QUIET_ZONE
+                    && colors[((my - QUIET_ZONE) * modules + (mx - QUIET_ZONE)) as usize] == qrcode::Color::Dark;
+                image::Luma([if dark { 0 } else { 255 }])
+            });
+            let mut png = std::io::Cursor::new(Vec::new());
+            image::DynamicImage::ImageLuma8(image)
+                .write_to(&mut png, image::ImageOutputFormat::Png)
+                .map_err(|e| BlockchainError::SerializationError(format!("Failed to encode QR PNG: {}", e)))?;
+            png.into_inner()
+        }
+    };
+
+    Ok(QrImage { format: options.format, data, size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svg_and_png() {
+        let uri = "edu:edu1qTreasury00000000000000000000?amount=1.5";
+        let svg = render(uri, &QrOptions::default()).unwrap();
+        let text = String::from_utf8(svg.data.clone()).unwrap();
+        assert!(text.contains("<svg"));
+        assert!(svg.size >= 256);
+        assert!(svg.to_data_uri().starts_with("data:image/svg+xml;base64,"));
+
+        let options = QrOptions { format: QrFormat::Png, size: 100, error_correction: QrErrorCorrection::H };
+        let png = render(uri, &options).unwrap();
+        assert_eq!(&png.data[1..4], b"PNG");
+        let decoded = image::load_from_memory(&png.data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (png.size, png.size));
+        assert!(png.size >= 100 && png.size < 200);
+    }
+
+    #[test]
+    fn test_rejects_bad_requests() {
+        assert!(render("", &QrOptions::default()).is_err());
+        assert!(render(&"x".repeat(MAX_PAYLOAD_LEN + 1), &QrOptions::default()).is_err());
+        assert!(render("EDU-7KQ2-M9XD-4HPT", &QrOptions { size: 10, ..QrOptions::default() }).is_err());
+        // Fits at L but not at H
+        let long = "a".repeat(1800);
+        assert!(render(&long, &QrOptions { error_correction: QrErrorCorrection::L, ..QrOptions::default() }).is_ok());
+        assert!(render(&long, &QrOptions { error_correction: QrErrorCorrection::H, ..QrOptions::default() }).is_err());
+
+        assert_eq!("q".parse::<QrErrorCorrection>().unwrap(), QrErrorCorrection::Q);
+        assert!("X".parse::<QrErrorCorrection>().is_err());
+    }
+}
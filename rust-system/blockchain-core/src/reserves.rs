@@ -0,0 +1,441 @@
+//! Proof of Reserves
+//!
+//! A custodian commits to every user balance with a merkle-sum tree: each
+//! node carries the hash and the total balance of its subtree, so the root
+//! commits to total liabilities and no balance can be left out or counted
+//! negative without changing it. Account names are hashed together with a
+//! per-attestation nonce, so the published tree doesn't reveal who holds what.
+//!
+//! The attestation pairs that root with the on-chain outputs the custodian
+//! controls, each signed with the key that can spend it, and a signature
+//! over the whole statement. A user checks their inclusion proof against the
+//! root and that the signed reserves cover the committed liabilities.
+
+use crate::{crypto, BlockchainError, Hash256, PrivateKey, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const LEAF_TAG: &[u8] = b"EDU-POR-LEAF";
+const NODE_TAG: &[u8] = b"EDU-POR-NODE";
+const OUTPUT_TAG: &[u8] = b"EDU-POR-UTXO";
+const ATTESTATION_TAG: &[u8] = b"EDU-POR-ATTESTATION";
+
+/// Padding node for odd levels (contributes nothing to the sum)
+const EMPTY_NODE: SumNode = SumNode { hash: [0u8; 32], sum: 0 };
+
+/// A merkle-sum tree node: subtree hash and total balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SumNode {
+    #[serde(with = "hex_hash")]
+    pub hash: Hash256,
+    pub sum: u64,
+}
+
+impl SumNode {
+    fn leaf(nonce: &Hash256, account: &str, balance: u64) -> Self {
+        let mut data = Vec::with_capacity(LEAF_TAG.len() + 72);
+        data.extend_from_slice(LEAF_TAG);
+        data.extend_from_slice(nonce);
+        data.extend_from_slice(&crypto::sha256(account.as_bytes()));
+        data.extend_from_slice(&balance.to_le_bytes());
+        Self { hash: crypto::sha256(&data), sum: balance }
+    }
+
+    fn parent(left: &SumNode, right: &SumNode) -> Result<Self> {
+        let sum = left.sum.checked_add(right.sum)
+            .ok_or_else(|| BlockchainError::InvalidInput("Liability total overflows".to_string()))?;
+        let mut data = Vec::with_capacity(NODE_TAG.len() + 80);
+        data.extend_from_slice(NODE_TAG);
+        data.extend_from_slice(&left.hash);
+        data.extend_from_slice(&left.sum.to_le_bytes());
+        data.extend_from_slice(&right.hash);
+        data.extend_from_slice(&right.sum.to_le_bytes());
+        Ok(Self { hash: crypto::sha256(&data), sum })
+    }
+}
+
+/// One step from a leaf towards the root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub sibling: SumNode,
+    /// Whether the sibling is the left child
+    pub sibling_on_left: bool,
+}
+
+/// Proof that an account's balance is included in a liabilities root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub account: String,
+    pub balance: u64,
+    #[serde(with = "hex_hash")]
+    pub nonce: Hash256,
+    pub path: Vec<ProofStep>,
+}
+
+impl InclusionProof {
+    /// Recompute the root from this proof and compare
+    pub fn verify(&self, root: &SumNode) -> bool {
+        let mut node = SumNode::leaf(&self.nonce, &self.account, self.balance);
+        for step in &self.path {
+            let parent = if step.sibling_on_left {
+                SumNode::parent(&step.sibling, &node)
+            } else {
+                SumNode::parent(&node, &step.sibling)
+            };
+            node = match parent {
+                Ok(parent) => parent,
+                Err(_) => return false,
+            };
+        }
+        node == *root
+    }
+}
+
+/// Merkle-sum tree over user balances
+#[derive(Debug, Clone)]
+pub struct LiabilityTree {
+    nonce: Hash256,
+    accounts: Vec<(String, u64)>,
+    /// Leaves first, root last
+    levels: Vec<Vec<SumNode>>,
+    index: HashMap<String, usize>,
+}
+
+impl LiabilityTree {
+    /// Build a tree over `(account, balance)` pairs; accounts must be unique
+    pub fn build(nonce: Hash256, balances: &[(String, u64)]) -> Result<Self> {
+        let mut index = HashMap::with_capacity(balances.len());
+        for (position, (account, _)) in balances.iter().enumerate() {
+            if index.insert(account.clone(), position).is_some() {
+                return Err(BlockchainError::InvalidInput(format!("Duplicate account in liabilities: {}", account)));
+            }
+        }
+
+        let leaves: Vec<SumNode> = balances.iter()
+            .map(|(account, balance)| SumNode::leaf(&nonce, account, *balance))
+            .collect();
+        let mut level = if leaves.is_empty() { vec![EMPTY_NODE] } else { leaves };
+        let mut levels = Vec::new();
+        while level.len() > 1 {
+            let parents = level.chunks(2)
+                .map(|pair| SumNode::parent(&pair[0], pair.get(1).unwrap_or(&EMPTY_NODE)))
+                .collect::<Result<Vec<_>>>()?;
+            levels.push(std::mem::replace(&mut level, parents));
+        }
+        levels.push(level);
+
+        Ok(Self {
+            nonce,
+            accounts: balances.to_vec(),
+            levels,
+            index,
+        })
+    }
+
+    pub fn root(&self) -> SumNode {
+        self.levels.last().and_then(|level| level.first()).copied().unwrap_or(EMPTY_NODE)
+    }
+
+    pub fn nonce(&self) -> Hash256 {
+        self.nonce
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Inclusion proof for one account
+    pub fn proof(&self, account: &str) -> Option<InclusionProof> {
+        let mut position = *self.index.get(account)?;
+        let mut path = Vec::with_capacity(self.levels.len());
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling_position = position ^ 1;
+            path.push(ProofStep {
+                sibling: level.get(sibling_position).copied().unwrap_or(EMPTY_NODE),
+                sibling_on_left: sibling_position < position,
+            });
+            position /= 2;
+        }
+
+        Some(InclusionProof {
+            account: account.to_string(),
+            balance: self.accounts[self.index[account]].1,
+            nonce: self.nonce,
+            path,
+        })
+    }
+
+    /// Proofs for every account, in input order
+    pub fn proofs(&self) -> Vec<InclusionProof> {
+        self.accounts.iter().filter_map(|(account, _)| self.proof(account)).collect()
+    }
+}
+
+/// An unspent output held as reserves, signed by the key that controls it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveOutput {
+    #[serde(with = "hex_hash")]
+    pub tx_hash: Hash256,
+    pub output_index: u32,
+    pub value: u64,
+    pub address: String,
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// Signed proof-of-reserves statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveAttestation {
+    pub timestamp: i64,
+    pub block_height: u64,
+    pub liabilities_root: SumNode,
+    #[serde(with = "hex_hash")]
+    pub nonce: Hash256,
+    pub account_count: usize,
+    pub total_reserves: u64,
+    /// Key that signed the outputs and the attestation
+    #[serde(with = "hex_bytes")]
+    pub public_key: Vec<u8>,
+    pub outputs: Vec<ReserveOutput>,
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// Unsigned output to include in an attestation
+#[derive(Debug, Clone)]
+pub struct ReserveUtxo {
+    pub tx_hash: Hash256,
+    pub output_index: u32,
+    pub value: u64,
+    pub address: String,
+}
+
+impl ReserveAttestation {
+    /// Sign `utxos` against the tree's root and sign the resulting statement
+    pub fn sign(
+        tree: &LiabilityTree,
+        utxos: &[ReserveUtxo],
+        block_height: u64,
+        timestamp: i64,
+        private_key: &PrivateKey,
+    ) -> Result<Self> {
+        let root = tree.root();
+        let nonce = tree.nonce();
+
+        let mut total_reserves = 0u64;
+        let mut outputs = Vec::with_capacity(utxos.len());
+        for utxo in utxos {
+            total_reserves = total_reserves.checked_add(utxo.value)
+                .ok_or_else(|| BlockchainError::InvalidInput("Reserve total overflows".to_string()))?;
+            let digest = output_digest(&root, &nonce, &utxo.tx_hash, utxo.output_index, utxo.value, &utxo.address);
+            outputs.push(ReserveOutput {
+                tx_hash: utxo.tx_hash,
+                output_index: utxo.output_index,
+                value: utxo.value,
+                address: utxo.address.clone(),
+                signature: crypto::sign_hash(&digest, private_key)?,
+            });
+        }
+
+        let mut attestation = Self {
+            timestamp,
+            block_height,
+            liabilities_root: root,
+            nonce,
+            account_count: tree.len(),
+            total_reserves,
+            public_key: crypto::derive_public_key(private_key)?,
+            outputs,
+            signature: Vec::new(),
+        };
+        attestation.signature = crypto::sign_hash(&attestation.digest(), private_key)?;
+        Ok(attestation)
+    }
+
+    pub fn total_liabilities(&self) -> u64 {
+        self.liabilities_root.sum
+    }
+
+    pub fn reserves_cover_liabilities(&self) -> bool {
+        self.total_reserves >= self.total_liabilities()
+    }
+
+    /// Hash of everything in the statement except its signature
+    pub fn digest(&self) -> Hash256 {
+        let mut data = Vec::new();
+        data.extend_from_slice(ATTESTATION_TAG);
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&self.block_height.to_le_bytes());
+        data.extend_from_slice(&self.liabilities_root.hash);
+        data.extend_from_slice(&self.liabilities_root.sum.to_le_bytes());
+        data.extend_from_slice(&self.nonce);
+        data.extend_from_slice(&(self.account_count as u64).to_le_bytes());
+        data.extend_from_slice(&self.total_reserves.to_le_bytes());
+        data.extend_from_slice(&self.public_key);
+        for output in &self.outputs {
+            data.extend_from_slice(&output.tx_hash);
+            data.extend_from_slice(&output.output_index.to_le_bytes());
+            data.extend_from_slice(&output.value.to_le_bytes());
+            data.extend_from_slice(&crypto::sha256(output.address.as_bytes()));
+            data.extend_from_slice(&output.signature);
+        }
+        crypto::sha256(&data)
+    }
+
+    /// Check every signature and that the reserve total matches the outputs.
+    /// Whether the outputs are still unspent must be checked against the chain.
+    pub fn verify(&self) -> Result<()> {
+        let mut total = 0u64;
+        for output in &self.outputs {
+            let digest = output_digest(
+                &self.liabilities_root,
+                &self.nonce,
+                &output.tx_hash,
+                output.output_index,
+                output.value,
+                &output.address,
+            );
+            if !crypto::verify_signature(&output.signature, &self.public_key, &digest)? {
+                return Err(BlockchainError::InvalidSignature(format!(
+                    "Bad reserve signature for {}:{}", hex::encode(output.tx_hash), output.output_index
+                )));
+            }
+            total = total.checked_add(output.value)
+                .ok_or_else(|| BlockchainError::InvalidInput("Reserve total overflows".to_string()))?;
+        }
+        if total != self.total_reserves {
+            return Err(BlockchainError::InvalidInput(format!(
+                "Reserve total {} does not match outputs ({})", self.total_reserves, total
+            )));
+        }
+        if !crypto::verify_signature(&self.signature, &self.public_key, &self.digest())? {
+            return Err(BlockchainError::InvalidSignature("Bad attestation signature".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// What each reserve output signature commits to
+fn output_digest(root: &SumNode, nonce: &Hash256, tx_hash: &Hash256, output_index: u32, value: u64, address: &str) -> Hash256 {
+    let mut data = Vec::with_capacity(OUTPUT_TAG.len() + 148);
+    data.extend_from_slice(OUTPUT_TAG);
+    data.extend_from_slice(&root.hash);
+    data.extend_from_slice(&root.sum.to_le_bytes());
+    data.extend_from_slice(nonce);
+    data.extend_from_slice(tx_hash);
+    data.extend_from_slice(&output_index.to_le_bytes());
+    data.extend_from_slice(&value.to_le_bytes());
+    data.extend_from_slice(&crypto::sha256(address.as_bytes()));
+    crypto::sha256(&data)
+}
+
+mod hex_hash {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &[u8; 32], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<[u8; 32], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let bytes = hex::decode(&s).map_err(serde::de::Error::custom)?;
+        bytes.try_into().map_err(|_| serde::de::Error::custom("hash must be 32 bytes"))
+    }
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balances() -> Vec<(String, u64)> {
+        vec![
+            ("alice".to_string(), 500),
+            ("bob".to_string(), 1_200),
+            ("carol".to_string(), 0),
+            ("dave".to_string(), 75),
+            ("erin".to_string(), 9_000),
+        ]
+    }
+
+    #[test]
+    fn test_liability_tree_proofs() {
+        let tree = LiabilityTree::build([9u8; 32], &balances()).unwrap();
+        let root = tree.root();
+        assert_eq!(root.sum, 10_775);
+
+        for proof in tree.proofs() {
+            assert!(proof.verify(&root), "proof for {} should verify", proof.account);
+        }
+
+        // A lowered balance or a different nonce no longer reaches the root
+        let mut proof = tree.proof("erin").unwrap();
+        proof.balance = 1;
+        assert!(!proof.verify(&root));
+        let mut proof = tree.proof("bob").unwrap();
+        proof.nonce = [1u8; 32];
+        assert!(!proof.verify(&root));
+
+        assert!(tree.proof("mallory").is_none());
+        assert!(LiabilityTree::build([0u8; 32], &[("a".to_string(), 1), ("a".to_string(), 2)]).is_err());
+    }
+
+    #[test]
+    fn test_empty_and_single_account_trees() {
+        let empty = LiabilityTree::build([0u8; 32], &[]).unwrap();
+        assert_eq!(empty.root().sum, 0);
+        assert!(empty.is_empty());
+
+        let single = LiabilityTree::build([0u8; 32], &[("solo".to_string(), 42)]).unwrap();
+        let proof = single.proof("solo").unwrap();
+        assert!(proof.path.is_empty());
+        assert!(proof.verify(&single.root()));
+    }
+
+    #[test]
+    fn test_attestation_sign_and_verify() {
+        let tree = LiabilityTree::build([3u8; 32], &balances()).unwrap();
+        let private_key = [0x11u8; 32];
+        let utxos = vec![
+            ReserveUtxo { tx_hash: [1u8; 32], output_index: 0, value: 8_000, address: "edu1qTreasury".to_string() },
+            ReserveUtxo { tx_hash: [2u8; 32], output_index: 1, value: 4_000, address: "edu1qTreasury".to_string() },
+        ];
+
+        let attestation = ReserveAttestation::sign(&tree, &utxos, 100, 1_700_000_000, &private_key).unwrap();
+        assert!(attestation.verify().is_ok());
+        assert_eq!(attestation.total_reserves, 12_000);
+        assert!(attestation.reserves_cover_liabilities());
+
+        // Survives a JSON roundtrip
+        let json = serde_json::to_string(&attestation).unwrap();
+        let parsed: ReserveAttestation = serde_json::from_str(&json).unwrap();
+        assert!(parsed.verify().is_ok());
+
+        // Inflating reserves or swapping the liabilities root breaks the signatures
+        let mut inflated = attestation.clone();
+        inflated.outputs[0].value = 80_000;
+        inflated.total_reserves = 84_000;
+        assert!(inflated.verify().is_err());
+
+        let mut lowered = attestation.clone();
+        lowered.liabilities_root.sum = 1;
+        assert!(lowered.verify().is_err());
+    }
+}
@@ -3,7 +3,16 @@
 // This module provides REST API endpoints for HTTP-based interactions
 // and WebSocket handlers for real-time communication.
 
+use crate::address::AddressCodec;
+use crate::amount::Amount;
 use crate::api_server::{ApiServer, JsonRpcRequest, JsonRpcResponse, SubscriptionType};
+use crate::asset_registry::AssetMetadata;
+use crate::canonical::{HexHash, Sats};
+use crate::contracts::EthAddress;
+use crate::ipfs::{IpfsClient, MediaRef};
+use crate::locale::{self, AddressStyle, AmountFormat, Locale, Unit};
+use crate::qr::{self, QrFormat, QrOptions};
+use crate::spend_policy::SpendPolicy;
 use crate::{BlockchainError, Result};
 
 use serde::{Deserialize, Serialize};
@@ -25,6 +34,9 @@ pub struct SendTransactionRequest {
     pub to_address: String,
     pub amount: u64,
     pub fee_rate: Option<u64>,
+    /// A co-signer approved the payment
+    #[serde(default)]
+    pub cosigned: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -34,11 +46,39 @@ pub struct GenerateAddressRequest {
     pub is_change: Option<bool>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UploadMediaRequest {
+    /// Base64-encoded file contents
+    pub data: String,
+    pub content_type: String,
+}
+
+/// Amounts and addresses to render for display
+#[derive(Debug, Deserialize)]
+pub struct FormatRequest {
+    /// BCP 47 tag such as "de-DE", English conventions if absent
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub unit: Unit,
+    /// Decimal places for EDU amounts, as many as needed if absent
+    pub decimals: Option<usize>,
+    pub show_unit: Option<bool>,
+    #[serde(default)]
+    pub amounts: Vec<Sats>,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub address_style: AddressStyle,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    /// Structured detail of the error, such as the spend policy rules broken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
     pub timestamp: String,
 }
 
@@ -48,6 +88,7 @@ impl<T> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            details: None,
             timestamp: chrono::Utc::now().to_rfc3339(),
         }
     }
@@ -57,9 +98,17 @@ impl<T> ApiResponse<T> {
             success: false,
             data: None,
             error: Some(message),
+            details: None,
             timestamp: chrono::Utc::now().to_rfc3339(),
         }
     }
+
+    pub fn error_with_details(message: String, details: Value) -> Self {
+        Self {
+            details: Some(details),
+            ..Self::error(message)
+        }
+    }
 }
 
 // WebSocket Message Types
@@ -121,6 +170,27 @@ impl ApiServer {
                     .unwrap().strip_suffix("/send").unwrap();
                 self.rest_send_transaction(wallet_id, body).await
             }
+            ("PUT", path) if path.starts_with("/api/v1/wallets/") && path.ends_with("/policy") => {
+                let wallet_id = path.strip_prefix("/api/v1/wallets/")
+                    .unwrap().strip_suffix("/policy").unwrap();
+                self.rest_set_spend_policy(wallet_id, body).await
+            }
+            ("GET", path) if path.starts_with("/api/v1/addresses/") && path.ends_with("/balance") => {
+                let address = path.strip_prefix("/api/v1/addresses/")
+                    .unwrap().strip_suffix("/balance").unwrap();
+                self.rest_get_address_balance(address).await
+            }
+            ("GET", path) if path.starts_with("/api/v1/addresses/") && path.ends_with("/names") => {
+                let address = path.strip_prefix("/api/v1/addresses/")
+                    .unwrap().strip_suffix("/names").unwrap();
+                self.rest_list_names(address).await
+            }
+
+            // Name endpoints
+            ("GET", path) if path.starts_with("/api/v1/names/") => {
+                let name = path.strip_prefix("/api/v1/names/").unwrap();
+                self.rest_resolve_name(name).await
+            }
 
             // Blockchain endpoints
             ("GET", "/api/v1/blockchain/info") => self.rest_get_blockchain_info().await,
@@ -144,6 +214,31 @@ impl ApiServer {
             ("GET", "/api/v1/network/peers") => self.rest_get_peers().await,
             ("POST", "/api/v1/network/peers") => self.rest_add_peer(body).await,
 
+            // Asset metadata endpoints
+            ("GET", "/api/v1/assets") => self.rest_list_assets().await,
+            ("POST", "/api/v1/assets") => self.rest_submit_asset_metadata(body).await,
+            ("GET", path) if path.starts_with("/api/v1/assets/") => {
+                let contract = path.strip_prefix("/api/v1/assets/").unwrap();
+                self.rest_get_asset(contract).await
+            }
+
+            // Media endpoints
+            ("POST", "/api/v1/media") => self.rest_upload_media(body).await,
+            ("GET", path) if path.starts_with("/api/v1/media/") => {
+                let media = path.strip_prefix("/api/v1/media/").unwrap();
+                self.rest_get_media(media).await
+            }
+
+            // Display formatting endpoints
+            ("GET", "/api/v1/format/locales") => self.rest_list_locales().await,
+            ("POST", "/api/v1/format") => self.rest_format(body).await,
+
+            // QR codes for addresses, payment URIs and vouchers
+            ("GET", path) if path == "/api/v1/qr" || path.starts_with("/api/v1/qr?") => {
+                let query = path.split_once('?').map(|(_, query)| query).unwrap_or("");
+                self.rest_get_qr(query).await
+            }
+
             // Status and metrics
             ("GET", "/api/v1/status") => self.get_server_status().await,
             ("GET", "/api/v1/metrics") => Ok(json!(self.get_metrics().await)),
@@ -181,8 +276,13 @@ impl ApiServer {
         let wallet_data = json!({
             "wallet_id": wallet_id,
             "name": "Sample Wallet",
-            "balance": 150000,
-            "pending_balance": 5000,
+            "balance": Sats(150000),
+            "balances": {
+                "confirmed": Sats(150000),
+                "unconfirmed": Sats(5000),
+                "immature": Sats(0),
+                "locked": Sats(0)
+            },
             "account_count": 2,
             "created_at": chrono::Utc::now().to_rfc3339()
         });
@@ -190,6 +290,11 @@ impl ApiServer {
         Ok(json!(ApiResponse::success(wallet_data)))
     }
 
+    async fn rest_get_address_balance(&self, address: &str) -> Result<Value> {
+        let result = self.get_balance(Some(json!({ "address": address }))).await?;
+        Ok(json!(ApiResponse::success(result)))
+    }
+
     async fn rest_generate_address(&self, wallet_id: &str, body: Option<Value>) -> Result<Value> {
         let req: GenerateAddressRequest = if let Some(body) = body {
             serde_json::from_value(body)
@@ -215,16 +320,36 @@ impl ApiServer {
         let req: SendTransactionRequest = serde_json::from_value(
             body.ok_or_else(|| BlockchainError::ApiError("Missing request body".to_string()))?
         ).map_err(|e| BlockchainError::ApiError(format!("Invalid request: {}", e)))?;
+        AddressCodec::validate(&req.to_address)
+            .map_err(|e| BlockchainError::ApiError(format!("Invalid to_address: {}", e)))?;
 
         let params = json!({
             "wallet_id": wallet_id,
             "address": req.to_address,
             "amount": req.amount,
-            "fee_rate": req.fee_rate
+            "fee_rate": req.fee_rate,
+            "cosigned": req.cosigned
         });
 
-        let result = self.send_to_address(Some(params)).await?;
-        Ok(json!(ApiResponse::success(result)))
+        match self.send_to_address(Some(params)).await {
+            Ok(result) => Ok(json!(ApiResponse::success(result))),
+            // Refused by the wallet's spend policy: say which rules, not just that it failed
+            Err(BlockchainError::PolicyViolation(violations)) => Ok(json!(ApiResponse::<Value>::error_with_details(
+                "Spend policy violated".to_string(),
+                json!({ "violations": violations }),
+            ))),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn rest_set_spend_policy(&self, wallet_id: &str, body: Option<Value>) -> Result<Value> {
+        let wallet_id = Uuid::parse_str(wallet_id)
+            .map_err(|e| BlockchainError::ApiError(format!("Invalid wallet id: {}", e)))?;
+        // An empty body or null removes the policy
+        let policy: Option<SpendPolicy> = serde_json::from_value(body.unwrap_or(Value::Null))
+            .map_err(|e| BlockchainError::ApiError(format!("Invalid spend policy: {}", e)))?;
+        self.wallet_manager.lock().await.set_spend_policy(wallet_id, policy.clone())?;
+        Ok(json!(ApiResponse::success(policy)))
     }
 
     // Blockchain REST endpoints
@@ -304,6 +429,168 @@ impl ApiServer {
         Ok(json!(ApiResponse::success(result)))
     }
 
+    // Asset metadata REST endpoints
+    async fn rest_list_assets(&self) -> Result<Value> {
+        let records = self.asset_registry.list().await;
+        Ok(json!(ApiResponse::success(records)))
+    }
+
+    async fn rest_get_asset(&self, contract: &str) -> Result<Value> {
+        let bytes = hex::decode(contract.trim_start_matches("0x"))
+            .map_err(|_| BlockchainError::ApiError("Invalid contract address format".to_string()))?;
+        if bytes.len() != 20 {
+            return Err(BlockchainError::ApiError("Contract address must be 20 bytes".to_string()));
+        }
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&bytes);
+
+        let record = self.asset_registry.get(&EthAddress::new(address)).await
+            .ok_or_else(|| BlockchainError::ApiError("No metadata registered for contract".to_string()))?;
+        Ok(json!(ApiResponse::success(record)))
+    }
+
+    async fn rest_submit_asset_metadata(&self, body: Option<Value>) -> Result<Value> {
+        let metadata: AssetMetadata = serde_json::from_value(
+            body.ok_or_else(|| BlockchainError::ApiError("Missing request body".to_string()))?
+        ).map_err(|e| BlockchainError::ApiError(format!("Invalid request: {}", e)))?;
+
+        let status = self.asset_registry.submit(metadata).await?;
+        Ok(json!(ApiResponse::success(json!({ "status": status }))))
+    }
+
+    // Name REST endpoints
+    async fn rest_resolve_name(&self, name: &str) -> Result<Value> {
+        let result = self.resolve_name(Some(json!({ "name": name }))).await?;
+        Ok(json!(ApiResponse::success(result)))
+    }
+
+    async fn rest_list_names(&self, address: &str) -> Result<Value> {
+        let result = self.list_names(Some(json!({ "address": address }))).await?;
+        Ok(json!(ApiResponse::success(result)))
+    }
+
+    // Media REST endpoints
+    fn ipfs_client(&self) -> Result<&IpfsClient> {
+        self.ipfs.as_deref()
+            .ok_or_else(|| BlockchainError::ApiError("Media uploads are not enabled".to_string()))
+    }
+
+    async fn rest_upload_media(&self, body: Option<Value>) -> Result<Value> {
+        use base64::Engine;
+
+        let req: UploadMediaRequest = serde_json::from_value(
+            body.ok_or_else(|| BlockchainError::ApiError("Missing request body".to_string()))?
+        ).map_err(|e| BlockchainError::ApiError(format!("Invalid request: {}", e)))?;
+        let content = base64::engine::general_purpose::STANDARD.decode(&req.data)
+            .map_err(|_| BlockchainError::ApiError("Media data must be base64".to_string()))?;
+
+        let pinned = self.ipfs_client()?.pin(content, &req.content_type).await?;
+        Ok(json!(ApiResponse::success(pinned)))
+    }
+
+    /// `GET /api/v1/media/{cid}/{sha256}` - verified content as base64
+    async fn rest_get_media(&self, path: &str) -> Result<Value> {
+        use base64::Engine;
+
+        let (cid, sha256) = path.split_once('/')
+            .ok_or_else(|| BlockchainError::ApiError("Expected /api/v1/media/{cid}/{sha256}".to_string()))?;
+        let media = MediaRef::from_parts(cid, sha256)?;
+
+        let content = self.ipfs_client()?.fetch(&media).await?;
+        Ok(json!(ApiResponse::success(json!({
+            "cid": media.cid,
+            "uri": media.to_uri(),
+            "size": content.len(),
+            "data": base64::engine::general_purpose::STANDARD.encode(&content),
+        }))))
+    }
+
+    // Display formatting REST endpoints
+    async fn rest_list_locales(&self) -> Result<Value> {
+        let example = Amount::from_sat(123_456_789_000);
+        let locales: Vec<Value> = Locale::ALL.iter()
+            .map(|&locale| json!({
+                "locale": locale,
+                "group_separator": locale.group_separator(),
+                "decimal_separator": locale.decimal_separator(),
+                "example": AmountFormat { locale, ..AmountFormat::default() }.format(example),
+            }))
+            .collect();
+        Ok(json!(ApiResponse::success(locales)))
+    }
+
+    /// `POST /api/v1/format` - amounts and addresses rendered for a locale.
+    /// An invalid address gets an error of its own instead of failing the batch
+    async fn rest_format(&self, body: Option<Value>) -> Result<Value> {
+        let req: FormatRequest = serde_json::from_value(
+            body.ok_or_else(|| BlockchainError::ApiError("Missing request body".to_string()))?
+        ).map_err(|e| BlockchainError::ApiError(format!("Invalid request: {}", e)))?;
+
+        let format = AmountFormat {
+            locale: req.locale.as_deref().map(Locale::from_tag).transpose()?.unwrap_or_default(),
+            unit: req.unit,
+            decimals: req.decimals,
+            show_unit: req.show_unit.unwrap_or(true),
+        };
+        format.validate()?;
+
+        let amounts: Vec<Value> = req.amounts.iter()
+            .map(|&sats| json!({ "satoshis": sats, "display": format.format(Amount::from_sat(sats.0)) }))
+            .collect();
+        let addresses: Vec<Value> = req.addresses.iter()
+            .map(|address| match locale::format_address(address, req.address_style) {
+                Ok(display) => json!({ "address": address, "display": display }),
+                Err(e) => json!({ "address": address, "error": e.to_string() }),
+            })
+            .collect();
+
+        Ok(json!(ApiResponse::success(json!({
+            "format": format,
+            "amounts": amounts,
+            "addresses": addresses,
+        }))))
+    }
+
+    /// `GET /api/v1/qr?payload=...&format=svg|png&size=256&ec=L|M|Q|H` -
+    /// SVG as text, PNG as base64, and either as a data URI
+    async fn rest_get_qr(&self, query: &str) -> Result<Value> {
+        let params = parse_query(query)?;
+        let payload = params.get("payload")
+            .ok_or_else(|| BlockchainError::ApiError("Missing payload".to_string()))?;
+        let mut options = QrOptions::default();
+        if let Some(format) = params.get("format") {
+            options.format = match format.to_ascii_lowercase().as_str() {
+                "svg" => QrFormat::Svg,
+                "png" => QrFormat::Png,
+                _ => return Err(BlockchainError::ApiError(format!("Unknown QR format '{}', expected svg or png", format))),
+            };
+        }
+        if let Some(size) = params.get("size") {
+            options.size = size.parse()
+                .map_err(|_| BlockchainError::ApiError(format!("Invalid QR size '{}'", size)))?;
+        }
+        if let Some(level) = params.get("ec") {
+            options.error_correction = level.parse()?;
+        }
+
+        let image = qr::render(payload, &options)?;
+        let data = match image.format {
+            QrFormat::Svg => String::from_utf8_lossy(&image.data).into_owned(),
+            QrFormat::Png => {
+                use base64::Engine;
+                base64::engine::general_purpose::STANDARD.encode(&image.data)
+            }
+        };
+        Ok(json!(ApiResponse::success(json!({
+            "format": image.format,
+            "content_type": image.content_type(),
+            "size": image.size,
+            "error_correction": options.error_correction,
+            "data": data,
+            "data_uri": image.to_data_uri(),
+        }))))
+    }
+
     // ========================================================================
     // WEBSOCKET HANDLERS
     // ========================================================================
@@ -417,7 +704,7 @@ impl ApiServer {
         let event_data = json!({
             "type": "new_block",
             "data": {
-                "hash": hex::encode(block.get_hash()),
+                "hash": HexHash(block.get_hash()),
                 "height": 12345, // Demo height
                 "timestamp": block.header.timestamp,
                 "transaction_count": block.transactions.len(),
@@ -434,9 +721,9 @@ impl ApiServer {
         let event_data = json!({
             "type": "new_transaction", 
             "data": {
-                "txid": hex::encode(tx.get_hash().unwrap_or([0u8; 32])),
+                "txid": HexHash(tx.calculate_hash()),
                 "size": 250, // Approximate
-                "fee": 1000, // Would calculate actual fee
+                "fee": Sats(1000), // Would calculate actual fee
                 "inputs": tx.inputs.len(),
                 "outputs": tx.outputs.len()
             }
@@ -484,6 +771,22 @@ impl ApiServer {
     }
 }
 
+/// Decode a `key=value&...` query string; a key given twice keeps the last value
+fn parse_query(query: &str) -> Result<HashMap<String, String>> {
+    let decode = |part: &str| {
+        urlencoding::decode(&part.replace('+', " "))
+            .map(|decoded| decoded.into_owned())
+            .map_err(|_| BlockchainError::ApiError(format!("Malformed query parameter '{}'", part)))
+    };
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((decode(key)?, decode(value)?))
+        })
+        .collect()
+}
+
 // ========================================================================
 // HTTP SERVER CONFIGURATION
 // ========================================================================
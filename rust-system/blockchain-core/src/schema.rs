@@ -0,0 +1,247 @@
+//! Forward-compatible encoding of transactions and blocks
+//!
+//! Blocks and transactions travel between nodes as JSON. Decoding ignores
+//! fields it does not know, so a release that adds one does not stop older
+//! nodes from reading its transactions. Such fields are lost when an older
+//! node re-encodes, though, so anything a future consensus upgrade needs
+//! relayed intact goes in an extension record: a type number and opaque
+//! bytes, kept and relayed unchanged by nodes that don't understand them.
+//!
+//! Record types follow "it's OK to be odd": an odd type is optional and
+//! may be ignored, an even type changes what the transaction or block means,
+//! so a node that does not know it cannot validate the transaction or block
+//! and rejects it. Records are sorted by type, at most one of each, and are
+//! committed to by the txid. A block with records must commit to them from
+//! its coinbase, in a zero-value OP_RETURN output holding
+//! [`BLOCK_RECORDS_TAG`] and the records' hash, so the block hash covers
+//! them through the merkle root and relays can't strip or alter them.
+//!
+//! Transaction versions above [`MAX_KNOWN_TX_VERSION`] decode, validate
+//! and relay like any other, but the mempool leaves them and their
+//! descendants out of block templates: an upgrade may give the version
+//! rules this release cannot check. Block versions carry versionbits
+//! signals and are not checked here.
+
+use crate::block::Block;
+use crate::script_utils::{opcodes, ScriptBuilder};
+use crate::transaction::{Transaction, TransactionOutput};
+use crate::{BlockchainError, Hash256, Result};
+use serde::{Deserialize, Serialize};
+
+/// Highest transaction version whose rules this release knows
+pub const MAX_KNOWN_TX_VERSION: u32 = 2;
+
+/// Transaction record types this release understands
+pub const KNOWN_TX_RECORDS: &[u32] = &[];
+
+/// Block record types this release understands
+pub const KNOWN_BLOCK_RECORDS: &[u32] = &[];
+
+/// Largest record payload accepted
+pub const MAX_RECORD_SIZE: usize = 10_000;
+
+/// Opens the coinbase OP_RETURN data committing to a block's records
+pub const BLOCK_RECORDS_TAG: [u8; 4] = *b"EDUx";
+
+/// Typed, opaque data attached to a transaction or block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionRecord {
+    pub record_type: u32,
+    pub data: Vec<u8>,
+}
+
+impl ExtensionRecord {
+    pub fn new(record_type: u32, data: Vec<u8>) -> Self {
+        Self { record_type, data }
+    }
+
+    /// Whether a node that does not know the type may ignore the record
+    pub fn is_optional(&self) -> bool {
+        self.record_type % 2 == 1
+    }
+
+    /// Bytes the record adds to a txid preimage
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.record_type.to_le_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.data);
+    }
+}
+
+/// Hash a coinbase commits to for a block's records
+pub fn block_records_hash(records: &[ExtensionRecord]) -> Hash256 {
+    let mut data = Vec::new();
+    for record in records {
+        record.encode_into(&mut data);
+    }
+    crate::hashing::double_sha256(&data)
+}
+
+/// Coinbase output committing to a block's records
+pub fn block_records_commitment(records: &[ExtensionRecord]) -> TransactionOutput {
+    let mut data = BLOCK_RECORDS_TAG.to_vec();
+    data.extend_from_slice(&block_records_hash(records));
+    let script = ScriptBuilder::create_op_return_script(&data).expect("36 bytes fit an OP_RETURN");
+    TransactionOutput::new(0, script)
+}
+
+/// Records hash committed to by a coinbase, if it has a commitment output
+fn committed_records_hash(coinbase: &Transaction) -> Option<Hash256> {
+    coinbase.outputs.iter().find_map(|output| {
+        let data = match output.script_pubkey.as_slice() {
+            [opcodes::OP_RETURN, 36, data @ ..] if data.len() == 36 => data,
+            _ => return None,
+        };
+        let (tag, hash) = data.split_at(BLOCK_RECORDS_TAG.len());
+        if tag != BLOCK_RECORDS_TAG {
+            return None;
+        }
+        hash.try_into().ok()
+    })
+}
+
+/// Decode a transaction from its wire encoding, whatever version wrote it
+pub fn decode_transaction(data: &[u8]) -> Result<Transaction> {
+    serde_json::from_slice(data)
+        .map_err(|e| BlockchainError::SerializationError(format!("Failed to decode transaction: {}", e)))
+}
+
+/// Decode a block from its wire encoding, whatever version wrote it
+pub fn decode_block(data: &[u8]) -> Result<Block> {
+    serde_json::from_slice(data)
+        .map_err(|e| BlockchainError::SerializationError(format!("Failed to decode block: {}", e)))
+}
+
+/// Check records are in canonical order and sized within limits, and
+/// return the first required type missing from `known`
+fn check_records(records: &[ExtensionRecord], known: &[u32]) -> std::result::Result<(), String> {
+    for pair in records.windows(2) {
+        if pair[0].record_type >= pair[1].record_type {
+            return Err(format!("extension records out of order at type {}", pair[1].record_type));
+        }
+    }
+    for record in records {
+        if record.data.len() > MAX_RECORD_SIZE {
+            return Err(format!("extension record {} is {} bytes, above {}", record.record_type, record.data.len(), MAX_RECORD_SIZE));
+        }
+        if !record.is_optional() && !known.contains(&record.record_type) {
+            return Err(format!("required extension record {} is not understood by this node", record.record_type));
+        }
+    }
+    Ok(())
+}
+
+/// Check a transaction's records are well formed and understood
+pub fn check_transaction(tx: &Transaction) -> Result<()> {
+    check_records(&tx.extensions, KNOWN_TX_RECORDS).map_err(BlockchainError::InvalidTransaction)
+}
+
+/// Check the records of a block and of each of its transactions, and that
+/// the coinbase commits to the block's records
+pub fn check_block(block: &Block) -> Result<()> {
+    check_records(&block.extensions, KNOWN_BLOCK_RECORDS).map_err(BlockchainError::InvalidBlock)?;
+    match block.transactions.first().and_then(committed_records_hash) {
+        Some(hash) if hash != block_records_hash(&block.extensions) => {
+            return Err(BlockchainError::InvalidBlock("extension records do not match the coinbase commitment".to_string()));
+        }
+        None if !block.extensions.is_empty() => {
+            return Err(BlockchainError::InvalidBlock("extension records are not committed by the coinbase".to_string()));
+        }
+        _ => {}
+    }
+    for (i, tx) in block.transactions.iter().enumerate() {
+        check_records(&tx.extensions, KNOWN_TX_RECORDS)
+            .map_err(|e| BlockchainError::InvalidBlock(format!("transaction {}: {}", i, e)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::transaction::{TransactionInput, TransactionOutput};
+
+    fn payment() -> Transaction {
+        Transaction::new(
+            2,
+            vec![TransactionInput::new([7u8; 32], 0, vec![0xAA; 4])],
+            vec![TransactionOutput::create_p2pkh(50_000, "edu1qschema").unwrap()],
+        )
+    }
+
+    #[test]
+    fn test_unknown_fields_are_ignored() {
+        let tx = payment();
+        let mut json: serde_json::Value = serde_json::from_slice(&tx.serialize().unwrap()).unwrap();
+        json["version"] = 9.into();
+        json["future_field"] = serde_json::json!({"anything": [1, 2, 3]});
+
+        let decoded = decode_transaction(json.to_string().as_bytes()).unwrap();
+        assert_eq!(decoded.version, 9);
+        assert_eq!(decoded.outputs.len(), 1);
+        assert!(check_transaction(&decoded).is_ok());
+    }
+
+    #[test]
+    fn test_records_survive_relay_and_change_txid() {
+        let plain = payment();
+        let mut tx = payment();
+        tx.extensions = vec![ExtensionRecord::new(3, vec![1, 2, 3])];
+        assert_ne!(tx.calculate_hash(), plain.calculate_hash());
+
+        // An older node decodes and re-encodes it unchanged
+        let relayed = decode_transaction(&tx.serialize().unwrap()).unwrap();
+        assert_eq!(relayed.extensions, tx.extensions);
+        assert_eq!(relayed.calculate_hash(), tx.calculate_hash());
+
+        // Transactions without records keep their encoding and txid
+        assert!(!String::from_utf8(plain.serialize().unwrap()).unwrap().contains("extensions"));
+    }
+
+    #[test]
+    fn test_required_records_must_be_understood() {
+        let mut tx = payment();
+        tx.extensions = vec![ExtensionRecord::new(1, vec![]), ExtensionRecord::new(5, vec![0; 8])];
+        assert!(check_transaction(&tx).is_ok());
+
+        tx.extensions.push(ExtensionRecord::new(6, vec![]));
+        assert!(check_transaction(&tx).is_err());
+
+        tx.extensions = vec![ExtensionRecord::new(5, vec![]), ExtensionRecord::new(1, vec![])];
+        assert!(check_transaction(&tx).is_err());
+
+        tx.extensions = vec![ExtensionRecord::new(1, vec![0; MAX_RECORD_SIZE + 1])];
+        assert!(check_transaction(&tx).is_err());
+    }
+
+    #[test]
+    fn test_block_records() {
+        let header = BlockHeader::new(1, [0u8; 32], [0u8; 32], 0x1d00ffff, 1);
+        let mut block = Block::new(header, vec![payment()]);
+        block.extensions = vec![ExtensionRecord::new(7, b"hint".to_vec())];
+
+        // Blocks written before records existed still decode
+        let mut json = serde_json::to_value(&block).unwrap();
+        json.as_object_mut().unwrap().remove("extensions");
+        assert!(decode_block(json.to_string().as_bytes()).unwrap().extensions.is_empty());
+
+        // Records must be committed to by the first transaction's outputs
+        assert!(check_block(&block).is_err());
+        block.transactions[0].outputs.push(block_records_commitment(&block.extensions));
+        let decoded = decode_block(&serde_json::to_vec(&block).unwrap()).unwrap();
+        assert_eq!(decoded.extensions, block.extensions);
+        assert!(check_block(&decoded).is_ok());
+
+        // Stripping or altering them breaks the commitment
+        let mut stripped = decoded.clone();
+        stripped.extensions.clear();
+        assert!(check_block(&stripped).is_err());
+        let mut altered = decoded;
+        altered.extensions[0].data.push(0);
+        assert!(check_block(&altered).is_err());
+
+        block.transactions[0].extensions = vec![ExtensionRecord::new(2, vec![])];
+        assert!(check_block(&block).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//! Step-by-step script execution
+//!
+//! Runs a script_sig and then a script_pubkey on a shared stack, recording
+//! the stack after every opcode, so a developer can see where a custom
+//! script stops. It understands the opcodes this chain's scripts are built
+//! from: data pushes, small integers, flow control, OP_DUP, OP_DROP, the
+//! equality and hashing opcodes, OP_CHECKSIG and OP_CHECKLOCKTIMEVERIFY.
+//!
+//! Signature and locktime checks need the spending transaction; without one
+//! those opcodes fail with an explanation instead of guessing.
+//!
+//! Consensus accepts the pay-to-address template written by
+//! `TransactionOutput::create_p2pkh` on its signature alone. That template
+//! embeds the address string rather than a key hash, so a trace of it stops
+//! at OP_EQUALVERIFY even when the spend is valid; the trace notes this.
+
+use crate::script_utils::{opcodes, ScriptBuilder};
+use crate::sighash::SighashCache;
+use crate::transaction::{Transaction, LOCKTIME_THRESHOLD, SEQUENCE_FINAL};
+use crate::{crypto, signature};
+use serde::Serialize;
+
+/// Opcodes run before a trace gives up
+pub const MAX_STEPS: usize = 1_000;
+
+/// Transaction spending the output whose script is traced
+#[derive(Debug, Clone, Copy)]
+pub struct TraceContext<'a> {
+    pub tx: &'a Transaction,
+    pub input_index: usize,
+    /// Value of the output being spent
+    pub amount: u64,
+}
+
+/// Which of the two scripts a step belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptPart {
+    ScriptSig,
+    ScriptPubkey,
+}
+
+/// One opcode and the stack it left, top last, as hex
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStep {
+    pub script: ScriptPart,
+    /// Byte offset of the opcode in its script
+    pub offset: usize,
+    pub opcode: String,
+    /// False inside a branch not taken
+    pub executed: bool,
+    pub stack: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptTrace {
+    /// Both scripts ran and left a true value on top
+    pub success: bool,
+    pub error: Option<String>,
+    pub steps: Vec<TraceStep>,
+    pub note: Option<String>,
+}
+
+struct Machine<'a> {
+    stack: Vec<Vec<u8>>,
+    /// Whether each enclosing OP_IF branch is being executed
+    branches: Vec<bool>,
+    steps: Vec<TraceStep>,
+    context: Option<TraceContext<'a>>,
+    script_pubkey: &'a [u8],
+}
+
+/// Run `script_sig` then `script_pubkey`, recording every step
+pub fn trace(script_sig: &[u8], script_pubkey: &[u8], context: Option<TraceContext>) -> ScriptTrace {
+    let mut machine = Machine {
+        stack: Vec::new(),
+        branches: Vec::new(),
+        steps: Vec::new(),
+        context,
+        script_pubkey,
+    };
+    let result = machine.run(ScriptPart::ScriptSig, script_sig)
+        .and_then(|()| machine.run(ScriptPart::ScriptPubkey, script_pubkey))
+        .and_then(|()| match machine.stack.last() {
+            Some(top) if is_true(top) => Ok(()),
+            _ => Err("script finished without a true value on the stack".to_string()),
+        });
+
+    let note = embeds_address(script_pubkey)
+        .then(|| "This output embeds an address string, not a key hash; consensus checks it by signature only, \
+                  so OP_EQUALVERIFY failing here does not mean the spend is invalid".to_string());
+    ScriptTrace {
+        success: result.is_ok(),
+        error: result.err(),
+        steps: machine.steps,
+        note,
+    }
+}
+
+impl Machine<'_> {
+    fn run(&mut self, part: ScriptPart, script: &[u8]) -> Result<(), String> {
+        let mut pc = 0;
+        while pc < script.len() {
+            if self.steps.len() >= MAX_STEPS {
+                return Err(format!("gave up after {} steps", MAX_STEPS));
+            }
+            let offset = pc;
+            let opcode = script[pc];
+            pc += 1;
+
+            // Data pushes
+            let push_len = match opcode {
+                0x01..=0x4b => Some(opcode as usize),
+                0x4c..=0x4e => {
+                    let width = 1usize << (opcode - 0x4c);
+                    let bytes = script.get(pc..pc + width).ok_or("truncated push length")?;
+                    pc += width;
+                    Some(bytes.iter().rev().fold(0usize, |len, b| (len << 8) | *b as usize))
+                }
+                _ => None,
+            };
+            let executing = self.branches.iter().all(|taken| *taken);
+            let result = match push_len {
+                Some(len) => {
+                    let data = script.get(pc..pc + len)
+                        .ok_or_else(|| format!("push of {} bytes runs past the end of the script", len))?;
+                    pc += len;
+                    if executing {
+                        self.stack.push(data.to_vec());
+                    }
+                    Ok(())
+                }
+                None => self.execute(opcode, executing),
+            };
+            self.steps.push(TraceStep {
+                script: part,
+                offset,
+                opcode: opcode_name(opcode, push_len),
+                executed: executing,
+                stack: self.stack.iter().map(hex::encode).collect(),
+            });
+            result.map_err(|e| format!("{} at {:?} offset {}: {}", opcode_name(opcode, push_len), part, offset, e))?;
+        }
+        if !self.branches.is_empty() {
+            return Err(format!("{:?} ends inside an OP_IF", part));
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, String> {
+        self.stack.pop().ok_or_else(|| "stack is empty".to_string())
+    }
+
+    fn execute(&mut self, opcode: u8, executing: bool) -> Result<(), String> {
+        // Flow control runs even inside a branch not taken
+        match opcode {
+            opcodes::OP_IF | 0x64 => {
+                let taken = if executing {
+                    let condition = is_true(&self.pop()?);
+                    condition == (opcode == opcodes::OP_IF)
+                } else {
+                    false
+                };
+                self.branches.push(taken);
+                return Ok(());
+            }
+            opcodes::OP_ELSE => {
+                let taken = self.branches.last_mut().ok_or("OP_ELSE without OP_IF")?;
+                *taken = !*taken;
+                return Ok(());
+            }
+            opcodes::OP_ENDIF => {
+                self.branches.pop().ok_or("OP_ENDIF without OP_IF")?;
+                return Ok(());
+            }
+            _ if !executing => return Ok(()),
+            _ => {}
+        }
+
+        match opcode {
+            opcodes::OP_0 => self.stack.push(Vec::new()),
+            0x4f => self.stack.push(vec![0x81]),
+            opcodes::OP_1..=0x60 => self.stack.push(vec![opcode - opcodes::OP_1 + 1]),
+            0x61 => {}
+            0x69 => {
+                if !is_true(&self.pop()?) {
+                    return Err("OP_VERIFY failed".to_string());
+                }
+            }
+            opcodes::OP_RETURN => return Err("OP_RETURN marks the output unspendable".to_string()),
+            opcodes::OP_DROP => {
+                self.pop()?;
+            }
+            opcodes::OP_DUP => {
+                let top = self.stack.last().ok_or("stack is empty")?.clone();
+                self.stack.push(top);
+            }
+            0x87 | opcodes::OP_EQUALVERIFY => {
+                let (a, b) = (self.pop()?, self.pop()?);
+                if opcode == opcodes::OP_EQUALVERIFY {
+                    if a != b {
+                        return Err("OP_EQUALVERIFY failed: top two items differ".to_string());
+                    }
+                } else {
+                    self.stack.push(if a == b { vec![1] } else { Vec::new() });
+                }
+            }
+            opcodes::OP_SHA256 => {
+                let data = self.pop()?;
+                self.stack.push(crypto::sha256(&data).to_vec());
+            }
+            opcodes::OP_HASH160 => {
+                let data = self.pop()?;
+                self.stack.push(ScriptBuilder::hash160(&data).to_vec());
+            }
+            opcodes::OP_CHECKSIG | 0xad => {
+                let public_key = self.pop()?;
+                let signature = self.pop()?;
+                let valid = self.check_signature(&signature, &public_key)?;
+                if opcode == 0xad {
+                    if !valid {
+                        return Err("OP_CHECKSIGVERIFY failed: signature does not verify".to_string());
+                    }
+                } else {
+                    self.stack.push(if valid { vec![1] } else { Vec::new() });
+                }
+            }
+            opcodes::OP_CHECKLOCKTIMEVERIFY => self.check_locktime()?,
+            _ => return Err(format!("unsupported opcode 0x{:02x}", opcode)),
+        }
+        Ok(())
+    }
+
+    fn check_signature(&self, signature: &[u8], public_key: &[u8]) -> Result<bool, String> {
+        let context = self.context.ok_or("OP_CHECKSIG needs the spending transaction")?;
+        let Some((&sighash_type, signature)) = signature.split_last() else {
+            return Ok(false);
+        };
+        let hash = SighashCache::new(context.tx)
+            .signature_hash(context.input_index, self.script_pubkey, context.amount, sighash_type as u32)
+            .map_err(|e| e.to_string())?;
+        Ok(signature::verify(&(&hash, signature, public_key)))
+    }
+
+    /// Same rule as consensus applies to HTLC refunds: the transaction is
+    /// locked at least as far as the script, in the same unit, with its
+    /// locktime enabled by a non-final sequence
+    fn check_locktime(&self) -> Result<(), String> {
+        let context = self.context.ok_or("OP_CHECKLOCKTIMEVERIFY needs the spending transaction")?;
+        let top = self.stack.last().ok_or("stack is empty")?;
+        if top.len() > 5 {
+            return Err("locktime operand longer than 5 bytes".to_string());
+        }
+        let locktime = top.iter().rev().fold(0u64, |n, b| (n << 8) | *b as u64);
+        let tx_locktime = context.tx.locktime as u64;
+        let input = context.tx.inputs.get(context.input_index).ok_or("input index out of range")?;
+        let threshold = LOCKTIME_THRESHOLD as u64;
+        if (tx_locktime < threshold) != (locktime < threshold) {
+            return Err("transaction locktime and script locktime use different units".to_string());
+        }
+        if tx_locktime < locktime {
+            return Err(format!("transaction locktime {} is before {}", tx_locktime, locktime));
+        }
+        if input.sequence == SEQUENCE_FINAL {
+            return Err("input sequence is final, which disables the locktime".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `create_p2pkh` output: OP_DUP OP_HASH160 <address> OP_EQUALVERIFY OP_CHECKSIG,
+/// with an address string where a standard script has a 20-byte key hash
+fn embeds_address(script: &[u8]) -> bool {
+    let len = script.len();
+    len > 5
+        && script[..2] == [opcodes::OP_DUP, opcodes::OP_HASH160]
+        && script[2] as usize == len - 5
+        && script[len - 2..] == [opcodes::OP_EQUALVERIFY, opcodes::OP_CHECKSIG]
+        && !ScriptBuilder::is_p2pkh_script(script)
+}
+
+/// Script truth: anything but zero, including negative zero
+fn is_true(item: &[u8]) -> bool {
+    match item.split_last() {
+        None => false,
+        Some((&last, rest)) => rest.iter().any(|b| *b != 0) || (last != 0 && last != 0x80),
+    }
+}
+
+fn opcode_name(opcode: u8, push_len: Option<usize>) -> String {
+    if let Some(len) = push_len {
+        return format!("PUSH({})", len);
+    }
+    match opcode {
+        opcodes::OP_0 => "OP_0".to_string(),
+        0x4f => "OP_1NEGATE".to_string(),
+        opcodes::OP_1..=0x60 => format!("OP_{}", opcode - opcodes::OP_1 + 1),
+        0x61 => "OP_NOP".to_string(),
+        opcodes::OP_IF => "OP_IF".to_string(),
+        0x64 => "OP_NOTIF".to_string(),
+        opcodes::OP_ELSE => "OP_ELSE".to_string(),
+        opcodes::OP_ENDIF => "OP_ENDIF".to_string(),
+        0x69 => "OP_VERIFY".to_string(),
+        opcodes::OP_RETURN => "OP_RETURN".to_string(),
+        opcodes::OP_DROP => "OP_DROP".to_string(),
+        opcodes::OP_DUP => "OP_DUP".to_string(),
+        0x87 => "OP_EQUAL".to_string(),
+        opcodes::OP_EQUALVERIFY => "OP_EQUALVERIFY".to_string(),
+        opcodes::OP_SHA256 => "OP_SHA256".to_string(),
+        opcodes::OP_HASH160 => "OP_HASH160".to_string(),
+        opcodes::OP_CHECKSIG => "OP_CHECKSIG".to_string(),
+        0xad => "OP_CHECKSIGVERIFY".to_string(),
+        opcodes::OP_CHECKLOCKTIMEVERIFY => "OP_CHECKLOCKTIMEVERIFY".to_string(),
+        _ => format!("0x{:02x}", opcode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_hash_lock() {
+        let preimage = [7u8; 32];
+        let mut script_pubkey = vec![opcodes::OP_SHA256, opcodes::OP_PUSHDATA_32];
+        script_pubkey.extend_from_slice(&crypto::sha256(&preimage));
+        script_pubkey.push(0x87); // OP_EQUAL
+        let mut script_sig = vec![opcodes::OP_PUSHDATA_32];
+        script_sig.extend_from_slice(&preimage);
+
+        let result = trace(&script_sig, &script_pubkey, None);
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.steps.len(), 4);
+        assert_eq!(result.steps[1].opcode, "OP_SHA256");
+        assert_eq!(result.steps[3].stack, vec!["01".to_string()]);
+
+        script_sig[1] ^= 1;
+        let result = trace(&script_sig, &script_pubkey, None);
+        assert!(!result.success);
+        assert_eq!(result.steps.last().unwrap().stack, vec![String::new()]);
+    }
+
+    #[test]
+    fn test_skipped_branch_and_missing_context() {
+        // OP_0 OP_IF OP_RETURN OP_ELSE OP_1 OP_ENDIF
+        let script = [opcodes::OP_0, opcodes::OP_IF, opcodes::OP_RETURN, opcodes::OP_ELSE, opcodes::OP_1, opcodes::OP_ENDIF];
+        let result = trace(&[], &script, None);
+        assert!(result.success);
+        assert!(!result.steps[2].executed);
+
+        let result = trace(&[0x01, 0x01, 0x01, 0x02], &[opcodes::OP_CHECKSIG], None);
+        assert!(result.error.unwrap().contains("needs the spending transaction"));
+    }
+}
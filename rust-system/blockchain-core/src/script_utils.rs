@@ -1,4 +1,7 @@
-use crate::{BlockchainError, Result as BlockchainResult};
+use crate::{BlockchainError, Hash256, Result as BlockchainResult};
+use crate::address::{AddressCodec, AddressKind};
+use crate::transaction::{Transaction, TransactionWitness};
+use rand::RngCore;
 use sha2::{Sha256, Digest};
 use blake3;
 
@@ -14,6 +17,386 @@ pub mod opcodes {
     pub const OP_2: u8 = 0x52;
     pub const OP_3: u8 = 0x53;
     pub const OP_CHECKMULTISIG: u8 = 0xae;
+    pub const OP_0: u8 = 0x00;
+    pub const OP_PUSHDATA_4: u8 = 0x04; // Push 4 bytes
+    pub const OP_PUSHDATA_32: u8 = 0x20; // Push 32 bytes
+    pub const OP_IF: u8 = 0x63;
+    pub const OP_ELSE: u8 = 0x67;
+    pub const OP_ENDIF: u8 = 0x68;
+    pub const OP_DROP: u8 = 0x75;
+    pub const OP_SHA256: u8 = 0xa8;
+    pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+    pub const OP_PUSHDATA1: u8 = 0x4c; // Next byte is the push length
+    pub const OP_PUSHDATA2: u8 = 0x4d; // Next two bytes are the push length
+}
+
+/// Keys a bare multisig script can hold
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Length of a serialized HTLC script
+pub const HTLC_SCRIPT_LEN: usize = 93;
+
+/// Hash-time-locked contract output.
+///
+/// The recipient can spend it by revealing the preimage of `payment_hash`;
+/// once `locktime` has passed the sender can take it back instead.
+/// Format: OP_IF OP_SHA256 <payment_hash> OP_EQUALVERIFY OP_DUP OP_HASH160 <recipient_hash>
+/// OP_ELSE <locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP OP_DUP OP_HASH160 <refund_hash>
+/// OP_ENDIF OP_EQUALVERIFY OP_CHECKSIG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtlcScript {
+    /// SHA256 of the secret preimage
+    pub payment_hash: Hash256,
+    /// HASH160 of the key that can claim with the preimage
+    pub recipient_hash: [u8; 20],
+    /// HASH160 of the key that can refund after the timeout
+    pub refund_hash: [u8; 20],
+    /// Block height, or unix time if at least `LOCKTIME_THRESHOLD`, after which refunds are allowed
+    pub locktime: u32,
+}
+
+impl HtlcScript {
+    /// Build an HTLC paying `recipient` against `payment_hash`, refundable to `refund` after `locktime`
+    pub fn new(payment_hash: Hash256, recipient: &str, refund: &str, locktime: u32) -> BlockchainResult<Self> {
+        if locktime == 0 {
+            return Err(BlockchainError::InvalidScript("HTLC locktime must be set".to_string()));
+        }
+        Ok(Self {
+            payment_hash,
+            recipient_hash: ScriptBuilder::address_to_hash160(recipient)?,
+            refund_hash: ScriptBuilder::address_to_hash160(refund)?,
+            locktime,
+        })
+    }
+
+    /// Generate a random secret and its payment hash
+    pub fn generate_preimage() -> ([u8; 32], Hash256) {
+        let mut preimage = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut preimage);
+        (preimage, crate::crypto::sha256(&preimage))
+    }
+
+    /// Serialize as a script_pubkey
+    pub fn to_script(&self) -> Vec<u8> {
+        let mut script = Vec::with_capacity(HTLC_SCRIPT_LEN);
+        script.push(opcodes::OP_IF);
+        script.push(opcodes::OP_SHA256);
+        script.push(opcodes::OP_PUSHDATA_32);
+        script.extend_from_slice(&self.payment_hash);
+        script.push(opcodes::OP_EQUALVERIFY);
+        script.push(opcodes::OP_DUP);
+        script.push(opcodes::OP_HASH160);
+        script.push(opcodes::OP_PUSHDATA_20);
+        script.extend_from_slice(&self.recipient_hash);
+        script.push(opcodes::OP_ELSE);
+        script.push(opcodes::OP_PUSHDATA_4);
+        script.extend_from_slice(&self.locktime.to_le_bytes());
+        script.push(opcodes::OP_CHECKLOCKTIMEVERIFY);
+        script.push(opcodes::OP_DROP);
+        script.push(opcodes::OP_DUP);
+        script.push(opcodes::OP_HASH160);
+        script.push(opcodes::OP_PUSHDATA_20);
+        script.extend_from_slice(&self.refund_hash);
+        script.push(opcodes::OP_ENDIF);
+        script.push(opcodes::OP_EQUALVERIFY);
+        script.push(opcodes::OP_CHECKSIG);
+        script
+    }
+
+    /// Parse an HTLC script_pubkey, or `None` if the script is anything else
+    pub fn from_script(script: &[u8]) -> Option<Self> {
+        if script.len() != HTLC_SCRIPT_LEN {
+            return None;
+        }
+        let mut htlc = Self {
+            payment_hash: [0u8; 32],
+            recipient_hash: [0u8; 20],
+            refund_hash: [0u8; 20],
+            locktime: u32::from_le_bytes(script[61..65].try_into().ok()?),
+        };
+        htlc.payment_hash.copy_from_slice(&script[3..35]);
+        htlc.recipient_hash.copy_from_slice(&script[39..59]);
+        htlc.refund_hash.copy_from_slice(&script[70..90]);
+        (htlc.to_script() == script).then_some(htlc)
+    }
+
+    /// Find the preimage revealed by a transaction claiming this HTLC.
+    /// This is how the other side of an atomic swap learns the secret.
+    pub fn find_preimage(&self, tx: &Transaction) -> Option<[u8; 32]> {
+        tx.inputs.iter().find_map(|input| match HtlcSpend::from_script_sig(&input.script_sig)? {
+            HtlcSpend::Redeem { preimage, .. } if crate::crypto::sha256(&preimage) == self.payment_hash => Some(preimage),
+            _ => None,
+        })
+    }
+}
+
+/// How an HTLC output is being spent, as carried in the input's script_sig
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HtlcSpend {
+    /// Format: <signature> <public_key> <preimage> OP_1
+    Redeem { signature: Vec<u8>, public_key: Vec<u8>, preimage: [u8; 32] },
+    /// Format: <signature> <public_key> OP_0
+    Refund { signature: Vec<u8>, public_key: Vec<u8> },
+}
+
+impl HtlcSpend {
+    /// Signature including its SIGHASH byte
+    pub fn signature(&self) -> &[u8] {
+        match self {
+            Self::Redeem { signature, .. } | Self::Refund { signature, .. } => signature,
+        }
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        match self {
+            Self::Redeem { public_key, .. } | Self::Refund { public_key, .. } => public_key,
+        }
+    }
+
+    /// Serialize as a script_sig
+    pub fn to_script_sig(&self) -> Vec<u8> {
+        let mut script_sig = Vec::new();
+        for push in [self.signature(), self.public_key()] {
+            script_sig.push(push.len() as u8);
+            script_sig.extend_from_slice(push);
+        }
+        match self {
+            Self::Redeem { preimage, .. } => {
+                script_sig.push(opcodes::OP_PUSHDATA_32);
+                script_sig.extend_from_slice(preimage);
+                script_sig.push(opcodes::OP_1);
+            }
+            Self::Refund { .. } => script_sig.push(opcodes::OP_0),
+        }
+        script_sig
+    }
+
+    /// Parse an HTLC script_sig
+    pub fn from_script_sig(script_sig: &[u8]) -> Option<Self> {
+        let (&selector, mut rest) = script_sig.split_last()?;
+        let mut pushes = Vec::new();
+        while let Some((&len, tail)) = rest.split_first() {
+            let len = len as usize;
+            if len == 0 || tail.len() < len {
+                return None;
+            }
+            pushes.push(tail[..len].to_vec());
+            rest = &tail[len..];
+        }
+
+        match (selector, pushes.len()) {
+            (opcodes::OP_1, 3) => {
+                let preimage = pushes.pop()?.try_into().ok()?;
+                let public_key = pushes.pop()?;
+                let signature = pushes.pop()?;
+                Some(Self::Redeem { signature, public_key, preimage })
+            }
+            (opcodes::OP_0, 2) => {
+                let public_key = pushes.pop()?;
+                let signature = pushes.pop()?;
+                Some(Self::Refund { signature, public_key })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Bare k-of-n multisig script, the redeem script of a P2SH output or the
+/// witness script of a P2WSH one.
+/// Format: OP_k <public_key>... OP_n OP_CHECKMULTISIG
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigScript {
+    /// Signatures required to spend
+    pub required: usize,
+    pub public_keys: Vec<[u8; 33]>,
+}
+
+impl MultisigScript {
+    pub fn new(required: usize, public_keys: Vec<[u8; 33]>) -> BlockchainResult<Self> {
+        if public_keys.is_empty() || public_keys.len() > MAX_MULTISIG_KEYS || required == 0 || required > public_keys.len() {
+            return Err(BlockchainError::InvalidScript(format!(
+                "Invalid {}-of-{} multisig", required, public_keys.len()
+            )));
+        }
+        Ok(Self { required, public_keys })
+    }
+
+    /// Serialize as a script
+    pub fn to_script(&self) -> Vec<u8> {
+        let mut script = Vec::with_capacity(3 + 34 * self.public_keys.len());
+        script.push(opcodes::OP_1 + self.required as u8 - 1);
+        for public_key in &self.public_keys {
+            script.push(public_key.len() as u8);
+            script.extend_from_slice(public_key);
+        }
+        script.push(opcodes::OP_1 + self.public_keys.len() as u8 - 1);
+        script.push(opcodes::OP_CHECKMULTISIG);
+        script
+    }
+
+    /// Parse a multisig script, or `None` if the script is anything else
+    pub fn from_script(script: &[u8]) -> Option<Self> {
+        let (&required, rest) = script.split_first()?;
+        let (_, rest) = rest.split_last()?;
+        let (_, keys) = rest.split_last()?;
+        if keys.len() % 34 != 0 {
+            return None;
+        }
+        let public_keys = keys.chunks(34)
+            .map(|chunk| chunk[1..].try_into().ok())
+            .collect::<Option<Vec<[u8; 33]>>>()?;
+        let multisig = Self::new(required.checked_sub(opcodes::OP_1 - 1)? as usize, public_keys).ok()?;
+        (multisig.to_script() == script).then_some(multisig)
+    }
+}
+
+/// What a script hash output commits to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptHash {
+    /// HASH160 of a redeem script revealed in the script_sig
+    P2sh([u8; 20]),
+    /// SHA256 of a witness script revealed in the input's witness
+    P2wsh(Hash256),
+}
+
+impl ScriptHash {
+    /// Parse a P2SH or P2WSH script_pubkey, or `None` if the script is
+    /// anything else
+    pub fn from_script_pubkey(script_pubkey: &[u8]) -> Option<Self> {
+        if ScriptBuilder::is_p2sh_script(script_pubkey) {
+            return Some(Self::P2sh(script_pubkey[2..22].try_into().ok()?));
+        }
+        if ScriptBuilder::is_p2wsh_script(script_pubkey) {
+            return Some(Self::P2wsh(script_pubkey[2..34].try_into().ok()?));
+        }
+        None
+    }
+
+    /// Whether `script` is the script this output commits to
+    pub fn commits_to(&self, script: &[u8]) -> bool {
+        match self {
+            Self::P2sh(hash) => ScriptBuilder::hash160(script) == *hash,
+            Self::P2wsh(hash) => crate::crypto::sha256(script) == *hash,
+        }
+    }
+}
+
+/// Signatures and script unlocking a script hash output. Both forms start
+/// with the extra item OP_CHECKMULTISIG pops, which carries the key mask.
+/// P2SH script_sig: <key_mask> <signature>... <redeem_script>
+/// P2WSH witness: <key_mask> <signature>... <witness_script>, with an empty script_sig
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptHashSpend {
+    /// Bit i set when the script's key i signed, so signatures pair with
+    /// keys without trial verification. Encoded little-endian without
+    /// trailing zero bytes.
+    pub key_mask: u16,
+    /// Signatures including their SIGHASH byte, in the script's key order
+    pub signatures: Vec<Vec<u8>>,
+    pub script: Vec<u8>,
+}
+
+impl ScriptHashSpend {
+    /// Serialize as a P2SH script_sig
+    pub fn to_script_sig(&self) -> Vec<u8> {
+        let mut script_sig = Vec::new();
+        push_data(&mut script_sig, &self.key_mask_bytes());
+        for push in self.signatures.iter().chain(std::iter::once(&self.script)) {
+            push_data(&mut script_sig, push);
+        }
+        script_sig
+    }
+
+    /// Parse a P2SH script_sig
+    pub fn from_script_sig(script_sig: &[u8]) -> Option<Self> {
+        let (key_mask, mut rest) = match script_sig.split_first()? {
+            (&opcodes::OP_0, rest) => (&[][..], rest),
+            _ => read_push(script_sig)?,
+        };
+        let key_mask = Self::parse_key_mask(key_mask)?;
+        let mut pushes = Vec::new();
+        while !rest.is_empty() {
+            let (push, tail) = read_push(rest)?;
+            pushes.push(push.to_vec());
+            rest = tail;
+        }
+        let script = pushes.pop()?;
+        Some(Self { key_mask, signatures: pushes, script })
+    }
+
+    /// Serialize as a P2WSH witness
+    pub fn to_witness(&self) -> TransactionWitness {
+        let mut witness = TransactionWitness::new();
+        witness.add_item(self.key_mask_bytes());
+        for signature in &self.signatures {
+            witness.add_item(signature.clone());
+        }
+        witness.add_item(self.script.clone());
+        witness
+    }
+
+    /// Parse a P2WSH witness
+    pub fn from_witness(witness: &TransactionWitness) -> Option<Self> {
+        let (key_mask, rest) = witness.witness_items.split_first()?;
+        let (script, signatures) = rest.split_last()?;
+        Some(Self { key_mask: Self::parse_key_mask(key_mask)?, signatures: signatures.to_vec(), script: script.clone() })
+    }
+
+    /// Keys of `multisig` that signed, in key order, or `None` if the mask
+    /// names a key the script doesn't have
+    pub fn signing_keys<'a>(&self, multisig: &'a MultisigScript) -> Option<Vec<&'a [u8; 33]>> {
+        if self.key_mask.checked_shr(multisig.public_keys.len() as u32).unwrap_or(0) != 0 {
+            return None;
+        }
+        Some(multisig.public_keys.iter()
+            .enumerate()
+            .filter(|(i, _)| self.key_mask & (1 << i) != 0)
+            .map(|(_, key)| key)
+            .collect())
+    }
+
+    fn key_mask_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.key_mask.to_le_bytes().to_vec();
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        bytes
+    }
+
+    /// Only the minimal encoding is accepted, so the mask can't be malleated
+    fn parse_key_mask(bytes: &[u8]) -> Option<u16> {
+        if bytes.len() > 2 || bytes.last() == Some(&0) {
+            return None;
+        }
+        let mut mask = [0u8; 2];
+        mask[..bytes.len()].copy_from_slice(bytes);
+        Some(u16::from_le_bytes(mask))
+    }
+}
+
+/// Append a push of `data`, with the shortest push opcode that fits it
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        len if len < opcodes::OP_PUSHDATA1 as usize => script.push(len as u8),
+        len if len <= u8::MAX as usize => script.extend_from_slice(&[opcodes::OP_PUSHDATA1, len as u8]),
+        len => {
+            script.push(opcodes::OP_PUSHDATA2);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+/// Split the data of the push at the start of `script` from what follows it
+fn read_push(script: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&opcode, rest) = script.split_first()?;
+    let (len, rest) = match opcode {
+        0x01..=0x4b => (opcode as usize, rest),
+        opcodes::OP_PUSHDATA1 => (*rest.first()? as usize, &rest[1..]),
+        opcodes::OP_PUSHDATA2 => (u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize, &rest[2..]),
+        _ => return None,
+    };
+    (rest.len() >= len).then(|| rest.split_at(len))
 }
 
 /// Script creation utilities for different address types
@@ -50,6 +433,16 @@ impl ScriptBuilder {
         script
     }
 
+    /// Create a Pay-to-Witness-Script-Hash (P2WSH) script
+    /// Format: OP_0 <sha256(witness_script)>
+    pub fn create_p2wsh_script(witness_script: &[u8]) -> Vec<u8> {
+        let mut script = Vec::with_capacity(34);
+        script.push(opcodes::OP_0);
+        script.push(opcodes::OP_PUSHDATA_32);
+        script.extend_from_slice(&crate::crypto::sha256(witness_script));
+        script
+    }
+
     /// Create a multisig script
     /// Format: OP_M <pubkey1> <pubkey2> ... OP_N OP_CHECKMULTISIG
     pub fn create_multisig_script(required: u8, public_keys: &[[u8; 33]]) -> BlockchainResult<Vec<u8>> {
@@ -90,55 +483,21 @@ impl ScriptBuilder {
 
     /// Generate proper EDU address from public key
     pub fn pubkey_to_address(public_key: &[u8; 33]) -> BlockchainResult<String> {
-        let pubkey_hash = Self::hash160(public_key);
-        let encoded = bs58::encode(&pubkey_hash).into_string();
-        Ok(format!("edu1q{}", encoded))
+        Ok(AddressCodec::encode(AddressKind::P2pkh, &Self::hash160(public_key)))
     }
 
     /// Generate P2SH address from script
     pub fn script_to_p2sh_address(script: &[u8]) -> BlockchainResult<String> {
-        let script_hash = Self::hash160(script);
-        let encoded = bs58::encode(&script_hash).into_string();
-        Ok(format!("edu3{}", encoded)) // Different prefix for P2SH
+        Ok(AddressCodec::encode(AddressKind::P2sh, &Self::hash160(script)))
     }
 
     /// Extract hash160 from EDU address
     pub fn address_to_hash160(address: &str) -> BlockchainResult<[u8; 20]> {
-        if address.starts_with("edu1q") && address.len() >= 25 {
-            // P2PKH address
-            let encoded_part = &address[5..];
-            let decoded = bs58::decode(encoded_part)
-                .into_vec()
-                .map_err(|_| BlockchainError::InvalidAddress("Invalid base58 encoding".to_string()))?;
-            
-            if decoded.len() == 20 {
-                let mut hash160 = [0u8; 20];
-                hash160.copy_from_slice(&decoded);
-                Ok(hash160)
-            } else {
-                Err(BlockchainError::InvalidAddress("Invalid hash160 length".to_string()))
-            }
-        } else if address.starts_with("edu3") && address.len() >= 25 {
-            // P2SH address
-            let encoded_part = &address[4..];
-            let decoded = bs58::decode(encoded_part)
-                .into_vec()
-                .map_err(|_| BlockchainError::InvalidAddress("Invalid base58 encoding".to_string()))?;
-            
-            if decoded.len() == 20 {
-                let mut hash160 = [0u8; 20];
-                hash160.copy_from_slice(&decoded);
-                Ok(hash160)
-            } else {
-                Err(BlockchainError::InvalidAddress("Invalid hash160 length".to_string()))
-            }
-        } else {
-            Err(BlockchainError::InvalidAddress(format!("Unsupported address format: {}", address)))
-        }
+        Ok(AddressCodec::decode(address)?.hash)
     }
 
     /// Compute HASH160 (SHA256 + RIPEMD160)
-    fn hash160(input: &[u8]) -> [u8; 20] {
+    pub fn hash160(input: &[u8]) -> [u8; 20] {
         // For EDU blockchain, we use SHA256 followed by taking first 20 bytes
         // In Bitcoin, this would be SHA256 + RIPEMD160
         let sha256_hash = Sha256::digest(input);
@@ -154,10 +513,9 @@ impl ScriptBuilder {
         hasher.update(node_id.as_bytes());
         let hash = hasher.finalize();
         
-        // Create EDU address format: edu1q + base58 encoded hash
-        let addr_bytes = &hash[0..20];
-        let encoded = bs58::encode(addr_bytes).into_string();
-        format!("edu1q{}", encoded)
+        let mut addr_bytes = [0u8; 20];
+        addr_bytes.copy_from_slice(&hash[0..20]);
+        AddressCodec::encode(AddressKind::P2pkh, &addr_bytes)
     }
 
     /// Create coinbase transaction output script
@@ -180,6 +538,11 @@ impl ScriptBuilder {
         script[24] == opcodes::OP_CHECKSIG
     }
 
+    /// Check if script is an HTLC
+    pub fn is_htlc_script(script: &[u8]) -> bool {
+        HtlcScript::from_script(script).is_some()
+    }
+
     /// Check if script is P2SH
     pub fn is_p2sh_script(script: &[u8]) -> bool {
         script.len() == 23 &&
@@ -188,6 +551,13 @@ impl ScriptBuilder {
         script[22] == opcodes::OP_EQUALVERIFY
     }
 
+    /// Check if script is P2WSH
+    pub fn is_p2wsh_script(script: &[u8]) -> bool {
+        script.len() == 34 &&
+        script[0] == opcodes::OP_0 &&
+        script[1] == opcodes::OP_PUSHDATA_32
+    }
+
     /// Extract address from P2PKH script
     pub fn extract_p2pkh_address(script: &[u8]) -> BlockchainResult<String> {
         if !Self::is_p2pkh_script(script) {
@@ -198,8 +568,7 @@ impl ScriptBuilder {
         let mut hash160 = [0u8; 20];
         hash160.copy_from_slice(hash160_bytes);
         
-        let encoded = bs58::encode(&hash160).into_string();
-        Ok(format!("edu1q{}", encoded))
+        Ok(AddressCodec::encode(AddressKind::P2pkh, &hash160))
     }
 
     /// Extract address from P2SH script
@@ -212,8 +581,7 @@ impl ScriptBuilder {
         let mut hash160 = [0u8; 20];
         hash160.copy_from_slice(hash160_bytes);
         
-        let encoded = bs58::encode(&hash160).into_string();
-        Ok(format!("edu3{}", encoded))
+        Ok(AddressCodec::encode(AddressKind::P2sh, &hash160))
     }
 }
 
@@ -267,6 +635,41 @@ mod tests {
         assert_eq!(script[script.len() - 1], opcodes::OP_CHECKMULTISIG);
     }
 
+    #[test]
+    fn test_script_hash_spends() {
+        let multisig = MultisigScript::new(2, vec![[0x02; 33], [0x03; 33], [0x02; 33]]).unwrap();
+        let script = multisig.to_script();
+        assert_eq!(script, ScriptBuilder::create_multisig_script(2, &multisig.public_keys).unwrap());
+        assert_eq!(MultisigScript::from_script(&script), Some(multisig.clone()));
+        assert!(MultisigScript::from_script(&script[1..]).is_none());
+        assert!(MultisigScript::new(3, vec![[0x02; 33]; 2]).is_err());
+
+        let p2sh = ScriptBuilder::create_p2sh_script(&ScriptBuilder::hash160(&script));
+        let p2wsh = ScriptBuilder::create_p2wsh_script(&script);
+        for script_pubkey in [&p2sh, &p2wsh] {
+            let hash = ScriptHash::from_script_pubkey(script_pubkey).unwrap();
+            assert!(hash.commits_to(&script));
+            assert!(!hash.commits_to(&script[1..]));
+        }
+        assert!(ScriptHash::from_script_pubkey(&ScriptBuilder::create_p2pkh_script(&[0; 20])).is_none());
+
+        // A 3-key redeem script is too long for a direct push
+        let spend = ScriptHashSpend { key_mask: 0b101, signatures: vec![vec![0xaa; 72], vec![0xbb; 71]], script };
+        let script_sig = spend.to_script_sig();
+        assert_eq!(script_sig[..2], [0x01, 0b101]);
+        assert_eq!(script_sig[2 + 73 + 72], opcodes::OP_PUSHDATA1);
+        assert_eq!(ScriptHashSpend::from_script_sig(&script_sig), Some(spend.clone()));
+        assert_eq!(ScriptHashSpend::from_witness(&spend.to_witness()), Some(spend.clone()));
+        assert!(ScriptHashSpend::from_script_sig(&script_sig[..script_sig.len() - 1]).is_none());
+        assert_eq!(spend.signing_keys(&multisig), Some(vec![&multisig.public_keys[0], &multisig.public_keys[2]]));
+
+        // Non-minimal masks and masks naming missing keys are refused
+        let mut padded = vec![0x02, 0b101, 0x00];
+        padded.extend_from_slice(&script_sig[2..]);
+        assert!(ScriptHashSpend::from_script_sig(&padded).is_none());
+        assert!(ScriptHashSpend { key_mask: 0b1001, ..spend }.signing_keys(&multisig).is_none());
+    }
+
     #[test]
     fn test_op_return_script() {
         let data = b"Hello EDU Blockchain!";
@@ -276,4 +679,31 @@ mod tests {
         assert_eq!(script[1], data.len() as u8);
         assert_eq!(&script[2..], data);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_htlc_script_roundtrip() {
+        let recipient = ScriptBuilder::pubkey_to_address(&[2u8; 33]).unwrap();
+        let refund = ScriptBuilder::pubkey_to_address(&[3u8; 33]).unwrap();
+        let (preimage, payment_hash) = HtlcScript::generate_preimage();
+        let htlc = HtlcScript::new(payment_hash, &recipient, &refund, 144).unwrap();
+
+        let script = htlc.to_script();
+        assert_eq!(script.len(), HTLC_SCRIPT_LEN);
+        assert_eq!(script[0], opcodes::OP_IF);
+        assert_eq!(script[65], opcodes::OP_CHECKLOCKTIMEVERIFY);
+        assert_eq!(HtlcScript::from_script(&script), Some(htlc));
+        assert!(ScriptBuilder::is_htlc_script(&script));
+        assert!(!ScriptBuilder::is_htlc_script(&ScriptBuilder::create_p2pkh_script(&[0u8; 20])));
+
+        let mut altered = script.clone();
+        altered[59] = opcodes::OP_ENDIF;
+        assert!(HtlcScript::from_script(&altered).is_none());
+
+        let redeem = HtlcSpend::Redeem { signature: vec![1; 65], public_key: vec![2; 33], preimage };
+        assert_eq!(HtlcSpend::from_script_sig(&redeem.to_script_sig()), Some(redeem));
+        let refund_spend = HtlcSpend::Refund { signature: vec![1; 65], public_key: vec![2; 33] };
+        assert_eq!(HtlcSpend::from_script_sig(&refund_spend.to_script_sig()), Some(refund_spend));
+        assert!(HtlcSpend::from_script_sig(&[0x01, 0xff, opcodes::OP_1]).is_none());
+        assert!(HtlcScript::new(payment_hash, &recipient, &refund, 0).is_err());
+    }
+}
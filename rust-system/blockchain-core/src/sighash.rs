@@ -0,0 +1,296 @@
+//! Signature hashes with cached midstates
+//!
+//! Two digests are accepted for an input's signature, chosen by the SIGHASH
+//! byte appended to it:
+//!
+//! - Legacy (`SIGHASH_ALL`): the whole transaction with every other input's
+//!   script blanked. Each digest still covers the full transaction, but the
+//!   cache serializes it once and resumes SHA-256 from the midstate before
+//!   the signed input, rather than rebuilding the preimage per input.
+//! - Precomputed (`SIGHASH_ALL | SIGHASH_PRECOMPUTED`): commits to hashes of
+//!   all prevouts, sequences and outputs, computed once per transaction, plus
+//!   the signed input and the value it spends. Validating an n-input
+//!   transaction hashes O(n) bytes instead of O(n²). This changes what
+//!   consensus accepts, so validators only allow it once the
+//!   `PRECOMPUTED_SIGHASH_DEPLOYMENT` soft fork is active; until then wallets
+//!   sign with plain `SIGHASH_ALL`.
+
+use crate::{BlockchainError, Hash256, Result};
+use crate::transaction::Transaction;
+use sha2::{Digest, Sha256};
+
+/// Sign every input and output
+pub const SIGHASH_ALL: u32 = 0x01;
+
+/// Flag selecting the precomputed digest
+pub const SIGHASH_PRECOMPUTED: u32 = 0x40;
+
+/// Version bits deployment that activates the precomputed digest
+pub const PRECOMPUTED_SIGHASH_DEPLOYMENT: &str = "precomputed_sighash";
+
+/// Outpoint, empty script length and sequence of a blanked legacy input
+const BLANK_INPUT_LEN: usize = 32 + 4 + 4 + 4;
+
+/// Signature hashes for one transaction, sharing work across its inputs
+pub struct SighashCache<'a> {
+    tx: &'a Transaction,
+    legacy: Option<LegacyParts>,
+    precomputed: Option<PrecomputedHashes>,
+    allow_precomputed: bool,
+}
+
+/// Legacy preimage split around each input
+struct LegacyParts {
+    /// SHA-256 state after the version and the blanked inputs before each input
+    prefixes: Vec<Sha256>,
+    /// Every input blanked, then outputs and locktime
+    tail: Vec<u8>,
+}
+
+struct PrecomputedHashes {
+    prevouts: Hash256,
+    sequences: Hash256,
+    outputs: Hash256,
+}
+
+impl<'a> SighashCache<'a> {
+    pub fn new(tx: &'a Transaction) -> Self {
+        Self { tx, legacy: None, precomputed: None, allow_precomputed: true }
+    }
+
+    /// Refuse `SIGHASH_PRECOMPUTED` signatures unless `allowed`, as
+    /// validators do before the deployment activates
+    pub fn allow_precomputed(mut self, allowed: bool) -> Self {
+        self.allow_precomputed = allowed;
+        self
+    }
+
+    pub fn transaction(&self) -> &'a Transaction {
+        self.tx
+    }
+
+    /// Digest for `input_index` under the scheme its `sighash_type` selects.
+    /// `amount` is the value of the output being spent.
+    pub fn signature_hash(&mut self, input_index: usize, script_pubkey: &[u8], amount: u64, sighash_type: u32) -> Result<Hash256> {
+        if sighash_type & SIGHASH_PRECOMPUTED != 0 {
+            if !self.allow_precomputed {
+                return Err(BlockchainError::InvalidTransaction(
+                    "Precomputed signature hashes are not active".to_string()
+                ));
+            }
+            self.precomputed_hash(input_index, script_pubkey, amount, sighash_type)
+        } else {
+            Ok(self.legacy_hash(input_index, script_pubkey, sighash_type))
+        }
+    }
+
+    /// Legacy digest, identical to hashing the full preimage from scratch
+    pub fn legacy_hash(&mut self, input_index: usize, script_pubkey: &[u8], sighash_type: u32) -> Hash256 {
+        let tx = self.tx;
+        let parts = self.legacy.get_or_insert_with(|| LegacyParts::new(tx));
+        let mut hasher = match parts.prefixes.get(input_index) {
+            Some(prefix) => {
+                let input = &tx.inputs[input_index];
+                let mut hasher = prefix.clone();
+                hasher.update(input.prev_tx_hash);
+                hasher.update(input.prev_output_index.to_le_bytes());
+                hasher.update((script_pubkey.len() as u32).to_le_bytes());
+                hasher.update(script_pubkey);
+                hasher.update(input.sequence.to_le_bytes());
+                hasher.update(&parts.tail[(input_index + 1) * BLANK_INPUT_LEN..]);
+                hasher
+            }
+            // No such input: every input is blanked
+            None => {
+                let mut hasher = Sha256::new();
+                hasher.update(tx.version.to_le_bytes());
+                hasher.update(&parts.tail);
+                hasher
+            }
+        };
+        hasher.update(sighash_type.to_le_bytes());
+        double_sha256(hasher)
+    }
+
+    /// Precomputed digest: version, prevouts hash, sequences hash, the signed
+    /// outpoint, script and amount, its sequence, outputs hash, locktime, type
+    pub fn precomputed_hash(&mut self, input_index: usize, script_pubkey: &[u8], amount: u64, sighash_type: u32) -> Result<Hash256> {
+        let tx = self.tx;
+        let input = tx.inputs.get(input_index).ok_or_else(|| BlockchainError::InvalidInput(
+            format!("Input {} out of range for a {}-input transaction", input_index, tx.inputs.len())
+        ))?;
+        let hashes = self.precomputed.get_or_insert_with(|| PrecomputedHashes::new(tx));
+
+        let mut hasher = Sha256::new();
+        hasher.update(tx.version.to_le_bytes());
+        hasher.update(hashes.prevouts);
+        hasher.update(hashes.sequences);
+        hasher.update(input.prev_tx_hash);
+        hasher.update(input.prev_output_index.to_le_bytes());
+        hasher.update((script_pubkey.len() as u32).to_le_bytes());
+        hasher.update(script_pubkey);
+        hasher.update(amount.to_le_bytes());
+        hasher.update(input.sequence.to_le_bytes());
+        hasher.update(hashes.outputs);
+        hasher.update(tx.locktime.to_le_bytes());
+        if let Some(nonce) = tx.nonce {
+            hasher.update(nonce.to_le_bytes());
+        }
+        for record in &tx.extensions {
+            let mut encoded = Vec::with_capacity(8 + record.data.len());
+            record.encode_into(&mut encoded);
+            hasher.update(encoded);
+        }
+        hasher.update(sighash_type.to_le_bytes());
+        Ok(double_sha256(hasher))
+    }
+}
+
+impl LegacyParts {
+    fn new(tx: &Transaction) -> Self {
+        let mut tail = Vec::with_capacity(tx.inputs.len() * BLANK_INPUT_LEN + tx.outputs.len() * 40 + 4);
+        for input in &tx.inputs {
+            tail.extend_from_slice(&input.prev_tx_hash);
+            tail.extend_from_slice(&input.prev_output_index.to_le_bytes());
+            tail.extend_from_slice(&0u32.to_le_bytes());
+            tail.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        serialize_outputs(tx, &mut tail);
+        tail.extend_from_slice(&tx.locktime.to_le_bytes());
+        // Signing the contract nonce stops a call being replayed with another
+        if let Some(nonce) = tx.nonce {
+            tail.extend_from_slice(&nonce.to_le_bytes());
+        }
+        // Signed too, so relays can't strip or alter them
+        for record in &tx.extensions {
+            record.encode_into(&mut tail);
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(tx.version.to_le_bytes());
+        let mut prefixes = Vec::with_capacity(tx.inputs.len());
+        for blank in tail[..tx.inputs.len() * BLANK_INPUT_LEN].chunks(BLANK_INPUT_LEN) {
+            prefixes.push(hasher.clone());
+            hasher.update(blank);
+        }
+        Self { prefixes, tail }
+    }
+}
+
+impl PrecomputedHashes {
+    fn new(tx: &Transaction) -> Self {
+        let mut prevouts = Sha256::new();
+        let mut sequences = Sha256::new();
+        for input in &tx.inputs {
+            prevouts.update(input.prev_tx_hash);
+            prevouts.update(input.prev_output_index.to_le_bytes());
+            sequences.update(input.sequence.to_le_bytes());
+        }
+        let mut outputs = Vec::with_capacity(tx.outputs.len() * 40);
+        serialize_outputs(tx, &mut outputs);
+        Self {
+            prevouts: double_sha256(prevouts),
+            sequences: double_sha256(sequences),
+            outputs: double_sha256(Sha256::new_with_prefix(&outputs)),
+        }
+    }
+}
+
+fn serialize_outputs(tx: &Transaction, data: &mut Vec<u8>) {
+    for output in &tx.outputs {
+        data.extend_from_slice(&output.value.to_le_bytes());
+        data.extend_from_slice(&(output.script_pubkey.len() as u32).to_le_bytes());
+        data.extend_from_slice(&output.script_pubkey);
+    }
+}
+
+fn double_sha256(hasher: Sha256) -> Hash256 {
+    Sha256::digest(hasher.finalize()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{TransactionInput, TransactionOutput};
+
+    fn transaction(inputs: usize) -> Transaction {
+        let inputs = (0..inputs)
+            .map(|i| {
+                let mut input = TransactionInput::new([i as u8; 32], i as u32, vec![0xAA; 107]);
+                input.sequence = 0xFFFF_FFF0 + i as u32 % 8;
+                input
+            })
+            .collect();
+        let outputs = vec![
+            TransactionOutput::new(5_000, vec![0x76, 0xa9, 0x14]),
+            TransactionOutput::new(7_000, vec![0x51; 30]),
+        ];
+        let mut tx = Transaction::new(2, inputs, outputs);
+        tx.locktime = 812;
+        tx
+    }
+
+    /// The legacy digest as computed before caching
+    fn legacy_from_scratch(tx: &Transaction, input_index: usize, script_pubkey: &[u8], sighash_type: u32) -> Hash256 {
+        let mut data = tx.version.to_le_bytes().to_vec();
+        for (i, input) in tx.inputs.iter().enumerate() {
+            data.extend_from_slice(&input.prev_tx_hash);
+            data.extend_from_slice(&input.prev_output_index.to_le_bytes());
+            let script: &[u8] = if i == input_index { script_pubkey } else { &[] };
+            data.extend_from_slice(&(script.len() as u32).to_le_bytes());
+            data.extend_from_slice(script);
+            data.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        serialize_outputs(tx, &mut data);
+        data.extend_from_slice(&tx.locktime.to_le_bytes());
+        data.extend_from_slice(&sighash_type.to_le_bytes());
+        crate::crypto::double_sha256(&data)
+    }
+
+    #[test]
+    fn test_cached_legacy_hash_matches_full_preimage() {
+        let tx = transaction(5);
+        let script = [0x76, 0xa9, 0x14, 0x01, 0x88, 0xac];
+        let mut cache = SighashCache::new(&tx);
+        // Out-of-range indexes blank every input, as they always have
+        for i in [3, 0, 4, 1, 2, 7] {
+            let expected = legacy_from_scratch(&tx, i, &script, SIGHASH_ALL);
+            assert_eq!(cache.legacy_hash(i, &script, SIGHASH_ALL), expected);
+            assert_eq!(cache.signature_hash(i, &script, 1, SIGHASH_ALL).ok(), Some(expected));
+            assert_eq!(tx.calculate_signature_hash(i, &script, SIGHASH_ALL), expected);
+        }
+    }
+
+    #[test]
+    fn test_precomputed_hash_commits_to_transaction() {
+        let tx = transaction(3);
+        let script = [0x76, 0xa9];
+        let sighash_type = SIGHASH_ALL | SIGHASH_PRECOMPUTED;
+        let digest = |tx: &Transaction, input: usize, amount: u64| {
+            SighashCache::new(tx).signature_hash(input, &script, amount, sighash_type).unwrap()
+        };
+        let base = digest(&tx, 1, 10_000);
+
+        assert_ne!(base, SighashCache::new(&tx).legacy_hash(1, &script, sighash_type));
+        assert_ne!(base, digest(&tx, 0, 10_000));
+        assert_ne!(base, digest(&tx, 1, 10_001));
+
+        let mut changed = tx.clone();
+        changed.outputs[1].value += 1;
+        assert_ne!(base, digest(&changed, 1, 10_000));
+        let mut changed = tx.clone();
+        changed.inputs[2].sequence -= 1;
+        assert_ne!(base, digest(&changed, 1, 10_000));
+        let mut changed = tx.clone();
+        changed.inputs[0].prev_output_index += 1;
+        assert_ne!(base, digest(&changed, 1, 10_000));
+
+        // Script sigs are never signed
+        let mut changed = tx.clone();
+        changed.inputs[1].script_sig.clear();
+        assert_eq!(base, digest(&changed, 1, 10_000));
+
+        assert!(SighashCache::new(&tx).signature_hash(3, &script, 1, sighash_type).is_err());
+        assert!(SighashCache::new(&tx).allow_precomputed(false).signature_hash(1, &script, 10_000, sighash_type).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Signature verification, one at a time or in batches
+//!
+//! `verify_batch` checks many `(message hash, signature, public key)` items
+//! at once. BIP340 Schnorr signatures (64 bytes under a 32-byte x-only key)
+//! are folded into one randomized linear combination,
+//! `(Σ aᵢsᵢ)·G = Σ aᵢ·Rᵢ + Σ aᵢeᵢ·Pᵢ`, evaluated as a single multi-scalar
+//! multiplication that shares its doublings across the batch. The weights
+//! `aᵢ` come from a hash of the whole batch, so signatures can't be chosen
+//! to cancel each other out.
+//!
+//! ECDSA signatures only carry the x-coordinate of R, so they can't be
+//! combined the same way; they are checked individually, spread across CPU
+//! cores once a batch is large enough to be worth it. Transaction scripts
+//! only carry 33-byte compressed keys, so consensus batches take the ECDSA
+//! path; the Schnorr path serves callers holding x-only keys.
+
+use crate::{crypto, BlockchainError, Hash256, PrivateKey, Result};
+use k256::elliptic_curve::bigint::U256;
+use k256::elliptic_curve::group::Group;
+use k256::elliptic_curve::ops::{LinearCombinationExt, Reduce};
+use k256::elliptic_curve::point::DecompactPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::schnorr::{SigningKey, VerifyingKey};
+use k256::{AffinePoint, FieldBytes, ProjectivePoint, Scalar};
+use sha2::{Digest, Sha256};
+
+/// `(message hash, signature, public key)`
+pub type BatchItem<'a> = (&'a Hash256, &'a [u8], &'a [u8]);
+
+/// ECDSA items below this are verified on the calling thread
+const PARALLEL_THRESHOLD: usize = 64;
+
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+const BATCH_TAG: &[u8] = b"EDU/batch-verify";
+
+/// Whether every signature in `items` is valid. An empty batch is valid.
+pub fn verify_batch(items: &[BatchItem]) -> bool {
+    let (schnorr, ecdsa): (Vec<&BatchItem>, Vec<&BatchItem>) = items.iter().partition(|item| is_schnorr(item));
+    verify_schnorr_batch(&schnorr) && verify_ecdsa_batch(&ecdsa)
+}
+
+/// Indexes of the invalid signatures in `items`, for when a batch fails
+pub fn find_invalid(items: &[BatchItem]) -> Vec<usize> {
+    items.iter()
+        .enumerate()
+        .filter(|(_, item)| !verify(item))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Verify one item, Schnorr or ECDSA by its shape
+pub fn verify(&(hash, signature, public_key): &BatchItem) -> bool {
+    if is_schnorr(&(hash, signature, public_key)) {
+        verify_schnorr(hash, signature, public_key)
+    } else {
+        crypto::verify_signature(signature, public_key, hash).unwrap_or(false)
+    }
+}
+
+/// Verify a BIP340 signature over a 32-byte message
+pub fn verify_schnorr(hash: &Hash256, signature: &[u8], public_key: &[u8]) -> bool {
+    let Ok(key) = VerifyingKey::from_bytes(public_key) else {
+        return false;
+    };
+    let Ok(signature) = k256::schnorr::Signature::try_from(signature) else {
+        return false;
+    };
+    key.verify_raw(hash, &signature).is_ok()
+}
+
+/// BIP340 signature over a 32-byte message
+pub fn sign_schnorr(hash: &Hash256, private_key: &PrivateKey) -> Result<Vec<u8>> {
+    let key = SigningKey::from_bytes(private_key)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid private key: {}", e)))?;
+    let aux_rand: [u8; 32] = rand::random();
+    let signature = key.sign_raw(hash, &aux_rand)
+        .map_err(|e| BlockchainError::CryptoError(format!("Schnorr signing failed: {}", e)))?;
+    Ok(signature.to_bytes().to_vec())
+}
+
+/// 32-byte x-only public key for Schnorr signatures
+pub fn schnorr_public_key(private_key: &PrivateKey) -> Result<Vec<u8>> {
+    let key = SigningKey::from_bytes(private_key)
+        .map_err(|e| BlockchainError::CryptoError(format!("Invalid private key: {}", e)))?;
+    Ok(key.verifying_key().to_bytes().to_vec())
+}
+
+fn is_schnorr(&(_, signature, public_key): &BatchItem) -> bool {
+    signature.len() == 64 && public_key.len() == 32
+}
+
+fn verify_schnorr_batch(items: &[&BatchItem]) -> bool {
+    if items.len() < 2 {
+        return items.iter().all(|item| verify(item));
+    }
+
+    // Weights from everything in the batch; the first is fixed at one
+    let mut seed = Sha256::new();
+    for (hash, signature, public_key) in items {
+        seed.update(signature);
+        seed.update(public_key);
+        seed.update(hash);
+    }
+    let seed = tagged_hash(BATCH_TAG).chain_update(seed.finalize()).finalize();
+
+    let mut terms = Vec::with_capacity(items.len() * 2 + 1);
+    let mut generator_scalar = Scalar::ZERO;
+    for (i, &&(hash, signature, public_key)) in items.iter().enumerate() {
+        let Some((r, s, key)) = parse_schnorr(signature, public_key) else {
+            return false;
+        };
+        let e = <Scalar as Reduce<U256>>::reduce_bytes(
+            &tagged_hash(CHALLENGE_TAG)
+                .chain_update(&signature[..32])
+                .chain_update(public_key)
+                .chain_update(hash)
+                .finalize(),
+        );
+        let weight = if i == 0 {
+            Scalar::ONE
+        } else {
+            <Scalar as Reduce<U256>>::reduce_bytes(&Sha256::new().chain_update(seed).chain_update((i as u64).to_le_bytes()).finalize())
+        };
+        generator_scalar += weight * s;
+        terms.push((r, -weight));
+        terms.push((key, -(weight * e)));
+    }
+    terms.push((ProjectivePoint::GENERATOR, generator_scalar));
+
+    ProjectivePoint::lincomb_ext(terms.as_slice()).is_identity().into()
+}
+
+fn verify_ecdsa_batch(items: &[&BatchItem]) -> bool {
+    let threads = num_cpus::get().min(items.len() / PARALLEL_THRESHOLD);
+    if threads < 2 {
+        return items.iter().all(|item| verify(item));
+    }
+    let chunk = items.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = items.chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().all(|item| verify(item))))
+            .collect();
+        workers.into_iter().all(|worker| worker.join().unwrap_or(false))
+    })
+}
+
+/// R (even y), s and the key point of a Schnorr signature, if well-formed
+fn parse_schnorr(signature: &[u8], public_key: &[u8]) -> Option<(ProjectivePoint, Scalar, ProjectivePoint)> {
+    let r = Option::<AffinePoint>::from(AffinePoint::decompact(FieldBytes::from_slice(&signature[..32])))?;
+    let s = Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(&signature[32..])))?;
+    let key = Option::<AffinePoint>::from(AffinePoint::decompact(FieldBytes::from_slice(public_key)))?;
+    Some((r.into(), s, key.into()))
+}
+
+fn tagged_hash(tag: &[u8]) -> Sha256 {
+    let tag_hash = Sha256::digest(tag);
+    Sha256::new().chain_update(tag_hash).chain_update(tag_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{derive_public_key, generate_private_key, sign_hash};
+
+    fn schnorr_items(count: usize) -> Vec<(Hash256, Vec<u8>, Vec<u8>)> {
+        (0..count)
+            .map(|i| {
+                let key = generate_private_key().unwrap();
+                let hash = crypto::sha256(&i.to_le_bytes());
+                (hash, sign_schnorr(&hash, &key).unwrap(), schnorr_public_key(&key).unwrap())
+            })
+            .collect()
+    }
+
+    fn as_batch(items: &[(Hash256, Vec<u8>, Vec<u8>)]) -> Vec<BatchItem> {
+        items.iter().map(|(hash, sig, key)| (hash, sig.as_slice(), key.as_slice())).collect()
+    }
+
+    #[test]
+    fn test_schnorr_batch() {
+        let mut items = schnorr_items(20);
+        assert!(items.iter().all(|(hash, sig, key)| verify_schnorr(hash, sig, key)));
+        assert!(verify_batch(&as_batch(&items)));
+        assert!(verify_batch(&[]));
+
+        // One signature over the wrong message fails the whole batch
+        items[7].0[0] ^= 1;
+        assert!(!verify_batch(&as_batch(&items)));
+        assert_eq!(find_invalid(&as_batch(&items)), vec![7]);
+
+        // Swapping signatures between messages is caught too
+        let mut swapped = schnorr_items(4);
+        let first = swapped[0].1.clone();
+        swapped[0].1 = swapped[1].1.clone();
+        swapped[1].1 = first;
+        assert!(!verify_batch(&as_batch(&swapped)));
+    }
+
+    #[test]
+    fn test_mixed_batch() {
+        let mut items = schnorr_items(3);
+        for i in 0..(PARALLEL_THRESHOLD * 2 + 1) {
+            let key = generate_private_key().unwrap();
+            let hash = crypto::sha256(format!("ecdsa {}", i).as_bytes());
+            items.push((hash, sign_hash(&hash, &key).unwrap(), derive_public_key(&key).unwrap()));
+        }
+        assert!(verify_batch(&as_batch(&items)));
+
+        let last = items.len() - 1;
+        items[last].2 = items[last - 1].2.clone();
+        assert!(!verify_batch(&as_batch(&items)));
+        assert_eq!(find_invalid(&as_batch(&items)), vec![last]);
+    }
+}
@@ -0,0 +1,194 @@
+//! Wallet spend policies
+//!
+//! A policy limits what a wallet may pay before anything is signed: the
+//! total a single transaction sends, which address prefixes it may pay to,
+//! the hours of the day (UTC) spending is allowed and the amount above which
+//! a co-signer has to approve. Change returns to the wallet and is never
+//! checked. Every rule a payment breaks is reported, not only the first, so
+//! a caller can show the user all of them at once.
+
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Hours of the day spending is allowed, in UTC. `start_hour` is included
+/// and `end_hour` is not; a window with `start_hour > end_hour` runs past
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HourWindow {
+    pub start_hour: u8,
+    pub end_hour: u8,
+}
+
+impl HourWindow {
+    pub fn contains(&self, hour: u8) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+/// Limits on what a wallet may spend. Every rule is off by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendPolicy {
+    /// Most a single transaction may pay out, change excluded
+    #[serde(default)]
+    pub max_per_tx: Option<u64>,
+    /// Destinations must start with one of these; empty allows any
+    #[serde(default)]
+    pub allowed_destination_prefixes: Vec<String>,
+    /// Hours of the day spending is allowed
+    #[serde(default)]
+    pub allowed_hours: Option<HourWindow>,
+    /// Payments above this need a co-signer's approval
+    #[serde(default)]
+    pub cosigner_threshold: Option<u64>,
+}
+
+/// A rule a payment breaks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PolicyViolation {
+    /// The payment is larger than a single transaction may send
+    AmountLimit { amount: u64, limit: u64 },
+    /// The destination doesn't match any allowed prefix
+    DestinationNotAllowed { address: String },
+    /// Spending isn't allowed at this hour
+    OutsideAllowedHours { hour: u8, window: HourWindow },
+    /// The payment needs a co-signer's approval it doesn't have
+    CosignerRequired { amount: u64, threshold: u64 },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::AmountLimit { amount, limit } => {
+                write!(f, "amount {} exceeds the per-transaction limit of {}", amount, limit)
+            }
+            PolicyViolation::DestinationNotAllowed { address } => {
+                write!(f, "destination {} is not on the allowlist", address)
+            }
+            PolicyViolation::OutsideAllowedHours { hour, window } => write!(
+                f,
+                "spending is not allowed at {:02}:00 UTC, only from {:02}:00 to {:02}:00",
+                hour, window.start_hour, window.end_hour
+            ),
+            PolicyViolation::CosignerRequired { amount, threshold } => {
+                write!(f, "amount {} is above {} and needs a co-signer", amount, threshold)
+            }
+        }
+    }
+}
+
+/// One line listing every violation, for error messages
+pub fn describe(violations: &[PolicyViolation]) -> String {
+    violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; ")
+}
+
+impl SpendPolicy {
+    /// Every rule paying `outputs` at `now` breaks
+    pub fn evaluate(&self, outputs: &[(String, u64)], now: DateTime<Utc>, cosigned: bool) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let amount = outputs.iter().fold(0u64, |total, (_, value)| total.saturating_add(*value));
+
+        if let Some(limit) = self.max_per_tx {
+            if amount > limit {
+                violations.push(PolicyViolation::AmountLimit { amount, limit });
+            }
+        }
+
+        if !self.allowed_destination_prefixes.is_empty() {
+            for (address, _) in outputs {
+                if !self.allowed_destination_prefixes.iter().any(|prefix| address.starts_with(prefix.as_str())) {
+                    violations.push(PolicyViolation::DestinationNotAllowed { address: address.clone() });
+                }
+            }
+        }
+
+        if let Some(window) = self.allowed_hours {
+            let hour = now.hour() as u8;
+            if !window.contains(hour) {
+                violations.push(PolicyViolation::OutsideAllowedHours { hour, window });
+            }
+        }
+
+        if let Some(threshold) = self.cosigner_threshold {
+            if amount > threshold && !cosigned {
+                violations.push(PolicyViolation::CosignerRequired { amount, threshold });
+            }
+        }
+
+        violations
+    }
+
+    /// Fail with every violated rule if paying `outputs` at `now` breaks any
+    pub fn check(&self, outputs: &[(String, u64)], now: DateTime<Utc>, cosigned: bool) -> crate::Result<()> {
+        let violations = self.evaluate(outputs, now, cosigned);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::BlockchainError::PolicyViolation(violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at_hour(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn test_hour_window_wraps_midnight() {
+        let office = HourWindow { start_hour: 9, end_hour: 17 };
+        assert!(office.contains(9) && office.contains(16));
+        assert!(!office.contains(17) && !office.contains(3));
+
+        let night = HourWindow { start_hour: 22, end_hour: 6 };
+        assert!(night.contains(23) && night.contains(0) && night.contains(5));
+        assert!(!night.contains(6) && !night.contains(12));
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let policy = SpendPolicy {
+            max_per_tx: Some(1_000),
+            allowed_destination_prefixes: vec!["edu1qTrusted".to_string()],
+            allowed_hours: Some(HourWindow { start_hour: 9, end_hour: 17 }),
+            cosigner_threshold: Some(500),
+        };
+        let outputs = vec![
+            ("edu1qTrustedShop".to_string(), 400),
+            ("edu1qStranger".to_string(), 700),
+        ];
+
+        let violations = policy.evaluate(&outputs, at_hour(20), false);
+        assert_eq!(violations, vec![
+            PolicyViolation::AmountLimit { amount: 1_100, limit: 1_000 },
+            PolicyViolation::DestinationNotAllowed { address: "edu1qStranger".to_string() },
+            PolicyViolation::OutsideAllowedHours { hour: 20, window: HourWindow { start_hour: 9, end_hour: 17 } },
+            PolicyViolation::CosignerRequired { amount: 1_100, threshold: 500 },
+        ]);
+
+        // Within limits, allowlisted, in hours and co-signed
+        let small = vec![("edu1qTrustedShop".to_string(), 800)];
+        assert!(policy.check(&small, at_hour(10), true).is_ok());
+        match policy.check(&small, at_hour(10), false) {
+            Err(crate::BlockchainError::PolicyViolation(v)) => {
+                assert_eq!(v, vec![PolicyViolation::CosignerRequired { amount: 800, threshold: 500 }]);
+            }
+            other => panic!("expected a policy violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_default_policy_allows_everything() {
+        let outputs = vec![("anything".to_string(), u64::MAX)];
+        assert!(SpendPolicy::default().evaluate(&outputs, at_hour(3), false).is_empty());
+    }
+}
@@ -9,7 +9,8 @@ use std::fs::{File, OpenOptions, create_dir_all};
 use std::io::{Read, Write, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
 use serde::{Serialize, Deserialize};
 use tracing::{info, warn, error, debug};
 
@@ -22,6 +23,12 @@ const BLOCK_FILE_PREFIX: &str = "blk";
 /// Block file extension
 const BLOCK_FILE_EXT: &str = "dat";
 
+/// Prefix of the files a compaction writes before swapping them in
+const COMPACT_FILE_PREFIX: &str = "cmp";
+
+/// Operator-invalidated blocks, one `<block hash> <invalidated root>` per line
+const INVALIDATED_FILE: &str = "invalidated.txt";
+
 /// Block location in storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockLocation {
@@ -48,6 +55,32 @@ pub struct BlockIndexEntry {
     pub timestamp: u64,
 }
 
+/// Space taken by the block files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// Number of blk*.dat files
+    pub file_count: usize,
+    /// Total size of the block files
+    pub file_bytes: u64,
+    /// Bytes of the blocks on the best chain
+    pub live_bytes: u64,
+    /// Bytes a compaction would free: stale forks and blocks no longer indexed
+    pub reclaimable_bytes: u64,
+    /// Blocks on the best chain
+    pub block_count: usize,
+}
+
+/// Result of a compaction run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub blocks_kept: usize,
+    /// Indexed blocks that were not on the best chain
+    pub blocks_dropped: usize,
+    pub duration_ms: u64,
+}
+
 /// Disk-based block storage engine
 pub struct DiskBlockStorage {
     /// Base directory for block files
@@ -62,6 +95,8 @@ pub struct DiskBlockStorage {
     block_index: Arc<RwLock<HashMap<Hash256, BlockIndexEntry>>>,
     /// Height index: height -> hash
     height_index: Arc<RwLock<HashMap<BlockHeight, Hash256>>>,
+    /// Held while a compaction runs
+    compaction: Mutex<()>,
 }
 
 impl DiskBlockStorage {
@@ -82,6 +117,7 @@ impl DiskBlockStorage {
             current_file_size: Arc::new(RwLock::new(0)),
             block_index: Arc::new(RwLock::new(HashMap::new())),
             height_index: Arc::new(RwLock::new(HashMap::new())),
+            compaction: Mutex::new(()),
         };
 
         // Load existing block index
@@ -260,6 +296,198 @@ impl DiskBlockStorage {
         block_index.len()
     }
 
+    /// Directory holding the block files
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Drop the best-chain entries above `height` after a rewind. The
+    /// blocks stay readable by hash until a compaction drops them.
+    pub async fn disconnect_above(&self, height: BlockHeight) {
+        self.height_index.write().await.retain(|h, _| *h <= height);
+    }
+
+    /// Operator-invalidated blocks saved by `save_invalidated`, each mapped
+    /// to the block that was invalidated
+    pub fn load_invalidated(&self) -> Result<HashMap<Hash256, Hash256>> {
+        let path = self.data_dir.join(INVALIDATED_FILE);
+        let data = match std::fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(BlockchainError::InvalidInput(format!("Failed to read {}: {}", path.display(), e))),
+        };
+        let parse = |hex_hash: &str| -> Option<Hash256> { hex::decode(hex_hash).ok()?.try_into().ok() };
+        data.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut fields = line.split_whitespace();
+                match (fields.next().and_then(parse), fields.next().and_then(parse)) {
+                    (Some(hash), Some(root)) => Ok((hash, root)),
+                    _ => Err(BlockchainError::SerializationError(format!("Malformed line in {}: {}", INVALIDATED_FILE, line))),
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the saved invalidated blocks, through a temporary file so a
+    /// crash never leaves half a list
+    pub fn save_invalidated(&self, invalidated: &HashMap<Hash256, Hash256>) -> Result<()> {
+        let data: String = invalidated.iter()
+            .map(|(hash, root)| format!("{} {}\n", hex::encode(hash), hex::encode(root)))
+            .collect();
+        let path = self.data_dir.join(INVALIDATED_FILE);
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, data)
+            .and_then(|_| std::fs::rename(&temp, &path))
+            .map_err(|e| BlockchainError::InvalidInput(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Break down the space taken by the block files
+    pub async fn disk_usage(&self) -> StorageUsage {
+        let files = self.block_files();
+        let file_bytes: u64 = files.iter()
+            .filter_map(|(_, path)| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        let block_index = self.block_index.read().await;
+        let height_index = self.height_index.read().await;
+        let live_bytes: u64 = height_index.values()
+            .filter_map(|hash| block_index.get(hash))
+            .map(|entry| entry.location.size as u64)
+            .sum();
+
+        StorageUsage {
+            file_count: files.len(),
+            file_bytes,
+            live_bytes,
+            reclaimable_bytes: file_bytes.saturating_sub(live_bytes),
+            block_count: height_index.len(),
+        }
+    }
+
+    /// Rewrite the block files keeping only the best chain
+    ///
+    /// The files present when compaction starts are frozen (new blocks go to
+    /// a fresh file), their best-chain blocks are copied into new files at no
+    /// more than `max_bytes_per_sec`, and the copies then replace them. Stale
+    /// forks and blocks written by earlier runs that were never re-indexed
+    /// are dropped.
+    pub async fn compact(&self, max_bytes_per_sec: Option<u64>) -> Result<CompactionReport> {
+        let _running = self.compaction.try_lock()
+            .map_err(|_| BlockchainError::InvalidInput("Compaction already running".to_string()))?;
+        let started = Instant::now();
+        let bytes_before = self.disk_usage().await.file_bytes;
+
+        self.rotate_file().await?;
+        let frozen = *self.current_file_num.read().await;
+        info!("🗜️  Compacting block files below blk{:05}.dat", frozen);
+
+        let mut live: Vec<(Hash256, BlockLocation)> = {
+            let block_index = self.block_index.read().await;
+            let height_index = self.height_index.read().await;
+            height_index.values()
+                .filter_map(|hash| block_index.get(hash))
+                .filter(|entry| entry.location.file_num < frozen)
+                .map(|entry| (entry.hash, entry.location.clone()))
+                .collect()
+        };
+        live.sort_by_key(|(_, location)| (location.file_num, location.offset));
+
+        let mut writer = CompactWriter::new(&self.data_dir);
+        let mut moved = HashMap::new();
+        for (hash, location) in live {
+            let bytes = self.read_raw(&location)?;
+            moved.insert(hash, writer.append(&bytes)?);
+            if let Some(rate) = max_bytes_per_sec.filter(|rate| *rate > 0) {
+                tokio::time::sleep(Duration::from_secs_f64(bytes.len() as f64 / rate as f64)).await;
+            }
+        }
+
+        // Swap the copies in while holding the index, so no reader sees a
+        // location in a file being removed
+        let mut block_index = self.block_index.write().await;
+        let height_index = self.height_index.read().await;
+
+        // Blocks that joined the best chain from a frozen file while copying
+        for hash in height_index.values() {
+            if moved.contains_key(hash) {
+                continue;
+            }
+            if let Some(entry) = block_index.get(hash).filter(|entry| entry.location.file_num < frozen) {
+                let bytes = self.read_raw(&entry.location)?;
+                moved.insert(*hash, writer.append(&bytes)?);
+            }
+        }
+        let written = writer.finish()?;
+
+        let indexed_before = block_index.len();
+        block_index.retain(|hash, entry| entry.location.file_num >= frozen || moved.contains_key(hash));
+        let blocks_dropped = indexed_before - block_index.len();
+        for (hash, location) in &moved {
+            if let Some(entry) = block_index.get_mut(hash) {
+                entry.location = location.clone();
+            }
+        }
+
+        // The copies fit in no more files than they came from, so renaming
+        // them to the low file numbers never touches a file still in use
+        for (file_num, path) in self.block_files() {
+            if file_num < frozen {
+                std::fs::remove_file(&path)
+                    .map_err(|e| BlockchainError::InvalidInput(format!("Failed to remove {}: {}", path.display(), e)))?;
+            }
+        }
+        for file_num in 0..written {
+            std::fs::rename(compact_file_path(&self.data_dir, file_num), self.get_block_file_path(file_num))
+                .map_err(|e| BlockchainError::InvalidInput(format!("Failed to install compacted file: {}", e)))?;
+        }
+        drop(height_index);
+        drop(block_index);
+
+        let report = CompactionReport {
+            bytes_before,
+            bytes_after: self.disk_usage().await.file_bytes,
+            blocks_kept: moved.len(),
+            blocks_dropped,
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        info!("✅ Compaction freed {} bytes ({} blocks kept, {} dropped)",
+              report.bytes_before.saturating_sub(report.bytes_after), report.blocks_kept, report.blocks_dropped);
+        Ok(report)
+    }
+
+    /// Block files on disk with their numbers, in order
+    fn block_files(&self) -> Vec<(u32, PathBuf)> {
+        let Ok(entries) = std::fs::read_dir(&self.data_dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<(u32, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let number = name.strip_prefix(BLOCK_FILE_PREFIX)?
+                    .strip_suffix(&format!(".{}", BLOCK_FILE_EXT))?
+                    .parse().ok()?;
+                Some((number, entry.path()))
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Raw bytes of a stored block
+    fn read_raw(&self, location: &BlockLocation) -> Result<Vec<u8>> {
+        let mut file = File::open(self.get_block_file_path(location.file_num))
+            .map_err(|e| BlockchainError::InvalidInput(format!("Failed to open file: {}", e)))?;
+        file.seek(SeekFrom::Start(location.offset))
+            .map_err(|e| BlockchainError::InvalidInput(format!("Seek error: {}", e)))?;
+        let mut buffer = vec![0u8; location.size as usize];
+        file.read_exact(&mut buffer)
+            .map_err(|e| BlockchainError::InvalidInput(format!("Read error: {}", e)))?;
+        Ok(buffer)
+    }
+
     /// Rotate to a new block file
     async fn rotate_file(&self) -> Result<()> {
         let mut file_guard = self.current_file.write().await;
@@ -338,6 +566,57 @@ impl DiskBlockStorage {
     }
 }
 
+/// Path of a compaction output file
+fn compact_file_path(data_dir: &Path, file_num: u32) -> PathBuf {
+    data_dir.join(format!("{}{:05}.{}", COMPACT_FILE_PREFIX, file_num, BLOCK_FILE_EXT))
+}
+
+/// Appends blocks to compaction output files, rotating like the block files
+struct CompactWriter {
+    data_dir: PathBuf,
+    file: Option<File>,
+    file_num: u32,
+    file_size: u64,
+}
+
+impl CompactWriter {
+    fn new(data_dir: &Path) -> Self {
+        Self { data_dir: data_dir.to_path_buf(), file: None, file_num: 0, file_size: 0 }
+    }
+
+    /// Where the block will live once the output is renamed into place
+    fn append(&mut self, bytes: &[u8]) -> Result<BlockLocation> {
+        if self.file.is_some() && self.file_size + bytes.len() as u64 > MAX_BLOCK_FILE_SIZE {
+            self.file = None;
+            self.file_num += 1;
+            self.file_size = 0;
+        }
+        if self.file.is_none() {
+            let file = File::create(compact_file_path(&self.data_dir, self.file_num))
+                .map_err(|e| BlockchainError::InvalidInput(format!("Failed to create compaction file: {}", e)))?;
+            self.file = Some(file);
+        }
+        self.file.as_mut().unwrap().write_all(bytes)
+            .map_err(|e| BlockchainError::InvalidInput(format!("Write error: {}", e)))?;
+
+        let location = BlockLocation { file_num: self.file_num, offset: self.file_size, size: bytes.len() as u32 };
+        self.file_size += bytes.len() as u64;
+        Ok(location)
+    }
+
+    /// Flush and return how many files were written
+    fn finish(self) -> Result<u32> {
+        match self.file {
+            Some(file) => {
+                file.sync_all()
+                    .map_err(|e| BlockchainError::InvalidInput(format!("Flush error: {}", e)))?;
+                Ok(self.file_num + 1)
+            }
+            None => Ok(0),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,4 +642,61 @@ mod tests {
         let path = storage.get_block_file_path(42);
         assert!(path.to_string_lossy().contains("blk00042.dat"));
     }
+
+    #[tokio::test]
+    async fn test_compaction_drops_stale_blocks() {
+        use crate::block::BlockHeader;
+
+        let temp_dir = TempDir::new().unwrap();
+        let storage = DiskBlockStorage::new(temp_dir.path()).unwrap();
+        let block = |height: u32, nonce: u32| {
+            let mut header = BlockHeader::new(1, [0u8; 32], [0u8; 32], 1, height);
+            header.nonce = nonce;
+            Block::new(header, vec![])
+        };
+
+        storage.write_block(&block(0, 0)).await.unwrap();
+        // A fork block at height 1, then the block that replaced it
+        storage.write_block(&block(1, 1)).await.unwrap();
+        let best = block(1, 2);
+        storage.write_block(&best).await.unwrap();
+
+        let usage = storage.disk_usage().await;
+        assert_eq!(usage.block_count, 2);
+        assert!(usage.reclaimable_bytes > 0);
+
+        let report = storage.compact(None).await.unwrap();
+        assert_eq!((report.blocks_kept, report.blocks_dropped), (2, 1));
+        assert!(report.bytes_after < report.bytes_before);
+
+        let usage = storage.disk_usage().await;
+        assert_eq!(usage.reclaimable_bytes, 0);
+        let read = storage.read_block_by_height(1).await.unwrap().unwrap();
+        assert_eq!(read.get_hash(), best.get_hash());
+
+        // Blocks written after compaction land in a new file
+        storage.write_block(&block(2, 0)).await.unwrap();
+        assert!(storage.read_block_by_height(2).await.unwrap().is_some());
+
+        // A rewind leaves the block readable by hash only
+        storage.disconnect_above(1).await;
+        assert_eq!(storage.get_height().await, 1);
+        assert!(storage.read_block_by_height(2).await.unwrap().is_none());
+        assert!(storage.read_block_by_hash(&block(2, 0).get_hash()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_invalidated_blocks_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = DiskBlockStorage::new(temp_dir.path()).unwrap();
+        assert!(storage.load_invalidated().unwrap().is_empty());
+
+        let invalidated = HashMap::from([([1u8; 32], [1u8; 32]), ([2u8; 32], [1u8; 32])]);
+        storage.save_invalidated(&invalidated).unwrap();
+        let reopened = DiskBlockStorage::new(temp_dir.path()).unwrap();
+        assert_eq!(reopened.load_invalidated().unwrap(), invalidated);
+
+        std::fs::write(temp_dir.path().join(INVALIDATED_FILE), "not-a-hash\n").unwrap();
+        assert!(reopened.load_invalidated().is_err());
+    }
 }
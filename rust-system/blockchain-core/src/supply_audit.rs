@@ -0,0 +1,354 @@
+//! Chain supply audit
+//!
+//! Optional validation mode for chasing inflation bugs. At every block
+//! connect the auditor recomputes the value held in the UTXO set and checks
+//! it against the supply the chain should have:
+//!
+//!   genesis supply + block subsidies - provably destroyed coins
+//!
+//! where destroyed coins are OP_RETURN outputs and reward a miner left
+//! unclaimed. Any mismatch, or a block that creates value out of nothing,
+//! halts the auditor and produces a `SupplyDivergence` report.
+
+use crate::{BlockHeight, BlockchainError, Hash256, Result};
+use crate::block::Block;
+use crate::script_utils::opcodes;
+use crate::transaction::TransactionOutput;
+use crate::utxo::UTXOSet;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tracing::error;
+
+/// Supply audit settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SupplyAuditConfig {
+    /// Directory divergence reports are written to, as JSON
+    pub report_dir: Option<PathBuf>,
+}
+
+/// Whether an output can never be spent, so its value is destroyed
+pub fn is_provably_unspendable(output: &TransactionOutput) -> bool {
+    output.script_pubkey.first() == Some(&opcodes::OP_RETURN)
+}
+
+/// Value flows within one block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockSupply {
+    /// Reward the schedule allows for this block
+    pub subsidy: u64,
+    /// Inputs minus outputs over all non-coinbase transactions
+    pub fees: i128,
+    pub coinbase_value: u64,
+    /// Value sent to provably unspendable outputs
+    pub burned: u64,
+    /// Subsidy plus fees the coinbase did not claim
+    pub unclaimed: i128,
+    /// Value-creation problems found while accounting the block
+    pub anomalies: Vec<String>,
+}
+
+/// Detailed report of a supply invariant violation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupplyDivergence {
+    pub height: BlockHeight,
+    pub block_hash: String,
+    /// Supply the chain should hold after this block
+    pub expected_supply: i128,
+    /// Spendable value actually in the UTXO set
+    pub utxo_supply: u128,
+    /// The UTXO set's own running total, OP_RETURN outputs included
+    pub tracked_supply: u64,
+    /// `utxo_supply - expected_supply`; positive means coins were created
+    pub difference: i128,
+    pub genesis_supply: u128,
+    pub total_subsidy: u128,
+    pub total_burned: u128,
+    pub total_unclaimed: i128,
+    pub block: BlockSupply,
+    pub anomalies: Vec<String>,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Tracks expected supply across block connects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupplyAuditor {
+    #[serde(skip)]
+    config: SupplyAuditConfig,
+    pub genesis_supply: u128,
+    pub total_subsidy: u128,
+    pub total_burned: u128,
+    pub total_unclaimed: i128,
+    /// Last height checked
+    pub height: BlockHeight,
+    /// Set once the invariant breaks; no further blocks are accepted
+    pub divergence: Option<SupplyDivergence>,
+}
+
+impl SupplyAuditor {
+    pub fn new(config: SupplyAuditConfig) -> Self {
+        Self {
+            config,
+            genesis_supply: 0,
+            total_subsidy: 0,
+            total_burned: 0,
+            total_unclaimed: 0,
+            height: 0,
+            divergence: None,
+        }
+    }
+
+    /// Start auditing from the genesis UTXO set
+    pub fn start(&mut self, genesis: &UTXOSet) {
+        self.genesis_supply = Self::spendable_supply(genesis);
+        self.total_subsidy = 0;
+        self.total_burned = 0;
+        self.total_unclaimed = 0;
+        self.height = 0;
+        self.divergence = None;
+    }
+
+    /// Supply the chain should hold at the last audited height
+    pub fn expected_supply(&self) -> i128 {
+        self.genesis_supply as i128 + self.total_subsidy as i128 - self.total_burned as i128 - self.total_unclaimed
+    }
+
+    /// Fail if an earlier block broke the invariant
+    pub fn check_not_halted(&self) -> Result<()> {
+        match &self.divergence {
+            Some(divergence) => Err(BlockchainError::ConsensusError(format!(
+                "Supply audit halted the chain at height {} (difference {})",
+                divergence.height, divergence.difference
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Account a block against the UTXO set it is about to be applied to
+    pub fn account_block(block: &Block, utxo_set: &UTXOSet, subsidy: u64) -> BlockSupply {
+        let mut supply = BlockSupply { subsidy, ..BlockSupply::default() };
+        // Outputs created earlier in this block, which later transactions may spend
+        let mut created: HashMap<String, &TransactionOutput> = HashMap::new();
+        let mut spent = HashSet::new();
+
+        for (index, tx) in block.transactions.iter().enumerate() {
+            let tx_id = tx.get_hash().map(hex::encode).unwrap_or_else(|_| format!("#{}", index));
+            let output_value: u128 = tx.outputs.iter().map(|o| o.value as u128).sum();
+            supply.burned += tx.outputs.iter()
+                .filter(|o| is_provably_unspendable(o))
+                .map(|o| o.value)
+                .sum::<u64>();
+
+            if index == 0 {
+                supply.coinbase_value = output_value as u64;
+            } else {
+                let mut input_value = 0u128;
+                for input in &tx.inputs {
+                    let outpoint = input.get_outpoint();
+                    if !spent.insert(outpoint.clone()) {
+                        supply.anomalies.push(format!("tx {} double-spends {} within the block", tx_id, outpoint));
+                        continue;
+                    }
+                    let output = utxo_set.get_utxo(&outpoint).map(|utxo| &utxo.output)
+                        .or_else(|| created.get(&outpoint).copied());
+                    match output {
+                        Some(output) if is_provably_unspendable(output) => {
+                            supply.anomalies.push(format!("tx {} spends unspendable output {}", tx_id, outpoint));
+                        }
+                        Some(output) => input_value += output.value as u128,
+                        None => supply.anomalies.push(format!("tx {} spends unknown outpoint {}", tx_id, outpoint)),
+                    }
+                }
+                if output_value > input_value {
+                    supply.anomalies.push(format!(
+                        "tx {} creates {} satoshis more than it spends", tx_id, output_value - input_value
+                    ));
+                }
+                supply.fees += input_value as i128 - output_value as i128;
+            }
+
+            if let Ok(hash) = tx.get_hash() {
+                for (vout, output) in tx.outputs.iter().enumerate() {
+                    created.insert(format!("{}:{}", hex::encode(hash), vout), output);
+                }
+            }
+        }
+
+        supply.unclaimed = subsidy as i128 + supply.fees - supply.coinbase_value as i128;
+        if supply.unclaimed < 0 {
+            supply.anomalies.push(format!(
+                "coinbase claims {} satoshis above subsidy plus fees", -supply.unclaimed
+            ));
+        }
+        supply
+    }
+
+    /// Record a connected block and check the UTXO set it produced.
+    /// On a violation the auditor halts and the divergence report is returned as an error.
+    pub fn connect_block(
+        &mut self,
+        height: BlockHeight,
+        block_hash: &Hash256,
+        block: BlockSupply,
+        utxo_set: &UTXOSet,
+    ) -> Result<()> {
+        self.check_not_halted()?;
+
+        self.height = height;
+        self.total_subsidy += block.subsidy as u128;
+        self.total_burned += block.burned as u128;
+        self.total_unclaimed += block.unclaimed;
+
+        let utxo_supply = Self::spendable_supply(utxo_set);
+        let raw_supply: u128 = utxo_set.iter().map(|utxo| utxo.value() as u128).sum();
+        let expected_supply = self.expected_supply();
+
+        let mut anomalies = block.anomalies.clone();
+        if raw_supply != utxo_set.get_total_supply() as u128 {
+            anomalies.push(format!(
+                "UTXO set running total {} disagrees with its contents {}",
+                utxo_set.get_total_supply(), raw_supply
+            ));
+        }
+        if utxo_supply as i128 != expected_supply {
+            anomalies.push(format!(
+                "UTXO supply {} does not match expected supply {}", utxo_supply, expected_supply
+            ));
+        }
+        if anomalies.is_empty() {
+            return Ok(());
+        }
+
+        let divergence = SupplyDivergence {
+            height,
+            block_hash: hex::encode(block_hash),
+            expected_supply,
+            utxo_supply,
+            tracked_supply: utxo_set.get_total_supply(),
+            difference: utxo_supply as i128 - expected_supply,
+            genesis_supply: self.genesis_supply,
+            total_subsidy: self.total_subsidy,
+            total_burned: self.total_burned,
+            total_unclaimed: self.total_unclaimed,
+            block,
+            anomalies,
+            detected_at: Utc::now(),
+        };
+        self.report(&divergence);
+        self.divergence = Some(divergence);
+        self.check_not_halted()
+    }
+
+    /// Log the divergence and write it to the report directory
+    fn report(&self, divergence: &SupplyDivergence) {
+        let report = serde_json::to_string_pretty(divergence).unwrap_or_else(|e| e.to_string());
+        error!("🚨 Supply audit failed at height {}:\n{}", divergence.height, report);
+
+        if let Some(dir) = &self.config.report_dir {
+            let path = dir.join(format!("supply-divergence-{}.json", divergence.height));
+            match std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, &report)) {
+                Ok(()) => error!("Supply divergence report written to {}", path.display()),
+                Err(e) => error!("Failed to write supply divergence report to {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Value in the UTXO set that can still be spent
+    fn spendable_supply(utxo_set: &UTXOSet) -> u128 {
+        utxo_set.iter()
+            .filter(|utxo| !is_provably_unspendable(&utxo.output))
+            .map(|utxo| utxo.value() as u128)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::script_utils::ScriptBuilder;
+    use crate::transaction::{Transaction, TransactionInput};
+    use crate::utxo::UTXO;
+
+    fn apply(utxo_set: &mut UTXOSet, block: &Block, height: u32) {
+        for tx in &block.transactions {
+            if !tx.is_coinbase() {
+                for input in &tx.inputs {
+                    utxo_set.remove_utxo(&input.prev_tx_hash, input.prev_output_index).unwrap();
+                }
+            }
+            let hash = tx.get_hash().unwrap();
+            for (vout, output) in tx.outputs.iter().enumerate() {
+                utxo_set.add_utxo(hash, vout as u32, UTXO::new(hash, vout as u32, output.clone(), height, tx.is_coinbase())).unwrap();
+            }
+        }
+    }
+
+    fn block(transactions: Vec<Transaction>) -> Block {
+        Block::new(BlockHeader::new(1, [0u8; 32], [0u8; 32], 0, 1), transactions)
+    }
+
+    #[test]
+    fn test_supply_audit() {
+        let mut utxo_set = UTXOSet::new();
+        let genesis = TransactionOutput::create_p2pkh(1_000, "edu1qAlice").unwrap();
+        utxo_set.add_utxo([1u8; 32], 0, UTXO::new([1u8; 32], 0, genesis, 0, false)).unwrap();
+
+        let mut auditor = SupplyAuditor::new(SupplyAuditConfig::default());
+        auditor.start(&utxo_set);
+        assert_eq!(auditor.expected_supply(), 1_000);
+
+        // Spend 1,000 into 900 + a 50 burn, leaving a 50 fee the miner claims
+        let spend = Transaction::new(
+            1,
+            vec![TransactionInput::new([1u8; 32], 0, Vec::new())],
+            vec![
+                TransactionOutput::create_p2pkh(900, "edu1qBob").unwrap(),
+                TransactionOutput::new(50, ScriptBuilder::create_op_return_script(b"burn").unwrap()),
+            ],
+        );
+        let coinbase = Transaction::new(
+            1,
+            vec![TransactionInput::create_coinbase(vec![0x01, 0x02])],
+            vec![TransactionOutput::create_p2pkh(5_050, "edu1qMiner").unwrap()],
+        );
+        let good = block(vec![coinbase, spend]);
+        let supply = SupplyAuditor::account_block(&good, &utxo_set, 5_000);
+        assert!(supply.anomalies.is_empty());
+        assert_eq!(supply.fees, 50);
+        assert_eq!(supply.burned, 50);
+        apply(&mut utxo_set, &good, 1);
+        auditor.connect_block(1, &[2u8; 32], supply, &utxo_set).unwrap();
+        assert_eq!(auditor.expected_supply(), 5_950);
+
+        // A coinbase paying itself more than the subsidy halts the audit
+        let greedy = block(vec![Transaction::new(
+            1,
+            vec![TransactionInput::create_coinbase(vec![0x02, 0x03])],
+            vec![TransactionOutput::create_p2pkh(6_000, "edu1qMiner").unwrap()],
+        )]);
+        let supply = SupplyAuditor::account_block(&greedy, &utxo_set, 5_000);
+        apply(&mut utxo_set, &greedy, 2);
+        assert!(auditor.connect_block(2, &[3u8; 32], supply, &utxo_set).is_err());
+        let divergence = auditor.divergence.clone().unwrap();
+        assert_eq!(divergence.height, 2);
+        assert_eq!(divergence.difference, 0);
+        assert_eq!(divergence.anomalies.len(), 1);
+        assert!(auditor.check_not_halted().is_err());
+    }
+
+    #[test]
+    fn test_supply_audit_catches_utxo_drift() {
+        let mut utxo_set = UTXOSet::new();
+        let mut auditor = SupplyAuditor::new(SupplyAuditConfig::default());
+        auditor.start(&utxo_set);
+
+        // An output appearing without any block accounting for it
+        let output = TransactionOutput::create_p2pkh(700, "edu1qAlice").unwrap();
+        utxo_set.add_utxo([4u8; 32], 0, UTXO::new([4u8; 32], 0, output, 1, false)).unwrap();
+        let empty = BlockSupply::default();
+        assert!(auditor.connect_block(1, &[5u8; 32], empty, &utxo_set).is_err());
+        assert_eq!(auditor.divergence.as_ref().unwrap().difference, 700);
+    }
+}
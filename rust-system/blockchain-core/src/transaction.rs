@@ -1,11 +1,110 @@
 use crate::{
-    BlockchainError, Result, Hash256, Hash256Ext, Amount, OutPoint,
+    BlockchainError, Result, Hash256, Hash256Ext, OutPoint,
     PrivateKey, PublicKey, Signature,
 };
+use crate::schema::ExtensionRecord;
+use crate::sighash::SighashCache;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use sha2::{Digest, Sha256};
 
+/// Sequence number that opts an input out of locktime checks
+pub const SEQUENCE_FINAL: u32 = 0xFFFFFFFF;
+
+/// Locktimes below this are block heights, at or above it unix timestamps
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// Weight units per byte of non-witness data
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+
+/// Largest DER signature with its sighash byte
+pub const SIGNATURE_MAX_SIZE: usize = 73;
+
+/// Largest P2PKH script_sig: a pushed DER signature with its sighash byte,
+/// then a pushed compressed public key
+pub const P2PKH_SCRIPT_SIG_MAX_SIZE: usize = 1 + SIGNATURE_MAX_SIZE + 1 + 33;
+
+/// Standard P2PKH script_pubkey: OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+pub const P2PKH_SCRIPT_PUBKEY_SIZE: usize = 25;
+
+/// Bytes taken by a Bitcoin compact-size length prefix
+pub(crate) fn compact_size_len(n: usize) -> usize {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// How an input will be signed, which decides its size before it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    /// script_sig with a signature and a compressed public key
+    P2pkh,
+    /// The same two items in the witness, with an empty script_sig
+    P2wpkh,
+    /// `required` signatures and the redeem script of a multisig over
+    /// `keys` keys in the script_sig
+    P2shMultisig { required: usize, keys: usize },
+    /// The same spend in the witness
+    P2wshMultisig { required: usize, keys: usize },
+}
+
+impl InputKind {
+    /// Kind of input spending a key-hash output, or `None` for a script
+    /// whose spend depends on a redeem script
+    pub fn of_script_pubkey(script_pubkey: &[u8]) -> Option<Self> {
+        match script_pubkey {
+            [0x76, 0xa9, ..] => Some(InputKind::P2pkh),
+            [0x00, 0x14, hash @ ..] if hash.len() == 20 => Some(InputKind::P2wpkh),
+            _ => None,
+        }
+    }
+
+    /// script_sig and witness of the largest signed input of this kind
+    fn placeholder(&self) -> (Vec<u8>, TransactionWitness) {
+        let multisig = |required: usize, keys: usize| {
+            let script = vec![0u8; 3 + 34 * keys];
+            let signatures = vec![vec![0u8; SIGNATURE_MAX_SIZE]; required];
+            (script, signatures)
+        };
+        let mut witness = TransactionWitness::new();
+        match *self {
+            InputKind::P2pkh => (vec![0u8; P2PKH_SCRIPT_SIG_MAX_SIZE], witness),
+            InputKind::P2wpkh => {
+                witness.add_item(vec![0u8; SIGNATURE_MAX_SIZE]);
+                witness.add_item(vec![0u8; 33]);
+                (Vec::new(), witness)
+            }
+            InputKind::P2shMultisig { required, keys } => {
+                let (script, signatures) = multisig(required, keys);
+                let script_push = match script.len() {
+                    len if len < 0x4c => 1,
+                    len if len <= 0xff => 2,
+                    _ => 3,
+                };
+                let len = 1 + signatures.iter().map(|sig| 1 + sig.len()).sum::<usize>() + script_push + script.len();
+                (vec![0u8; len], witness)
+            }
+            InputKind::P2wshMultisig { required, keys } => {
+                let (script, signatures) = multisig(required, keys);
+                witness.add_item(Vec::new());
+                for signature in signatures {
+                    witness.add_item(signature);
+                }
+                witness.add_item(script);
+                (Vec::new(), witness)
+            }
+        }
+    }
+}
+
+/// Bytes taken by a length-prefixed byte string
+fn var_bytes_len(bytes: &[u8]) -> usize {
+    compact_size_len(bytes.len()) + bytes.len()
+}
+
 /// Transaction input
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
@@ -147,6 +246,23 @@ pub struct Transaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gas_limit: Option<u64>,
     
+    /// Most paid per unit of gas, priority fee included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<u64>,
+    
+    /// Most paid per unit of gas above the mempool's base fee
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<u64>,
+    
+    /// Sender's contract nonce, above every nonce it used before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    
+    /// Data for future upgrades, kept and relayed by nodes that don't
+    /// understand it; see `schema`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<ExtensionRecord>,
+    
     #[serde(skip)]
     cached_hash: Option<Hash256>,
     #[serde(skip)]
@@ -166,6 +282,10 @@ impl Transaction {
             contract_data: None,
             contract_address: None,
             gas_limit: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: None,
+            extensions: Vec::new(),
             cached_hash: None,
             cached_wtxid: None,
         }
@@ -190,6 +310,10 @@ impl Transaction {
             contract_data: None,
             contract_address: None,
             gas_limit: Some(gas_limit),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: None,
+            extensions: Vec::new(),
             cached_hash: None,
             cached_wtxid: None,
         }
@@ -215,14 +339,41 @@ impl Transaction {
             contract_data: Some(calldata),
             contract_address: Some(contract_address),
             gas_limit: Some(gas_limit),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: None,
+            extensions: Vec::new(),
             cached_hash: None,
             cached_wtxid: None,
         }
     }
     
+    /// Bid for gas in the style of EIP-1559: at most `max_fee_per_gas`,
+    /// of which at most `max_priority_fee_per_gas` above the base fee
+    pub fn with_gas_price(mut self, max_fee_per_gas: u64, max_priority_fee_per_gas: u64) -> Self {
+        self.max_fee_per_gas = Some(max_fee_per_gas);
+        self.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+        self
+    }
+    
+    /// Set the sender's contract nonce
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+    
     pub fn is_coinbase(&self) -> bool {
         self.inputs.len() == 1 && self.inputs[0].is_coinbase()
     }
+
+    /// Whether the locktime allows inclusion in a block at this height and time
+    pub fn is_final(&self, block_height: u64, block_time: u64) -> bool {
+        if self.locktime == 0 {
+            return true;
+        }
+        let limit = if self.locktime < LOCKTIME_THRESHOLD { block_height } else { block_time };
+        (self.locktime as u64) < limit || self.inputs.iter().all(|i| i.sequence == SEQUENCE_FINAL)
+    }
     
     pub fn is_contract_deployment(&self) -> bool {
         self.contract_code.is_some()
@@ -292,6 +443,16 @@ impl Transaction {
         // Locktime
         data.extend_from_slice(&self.locktime.to_le_bytes());
         
+        // Contract nonce, only when set so other txids are unchanged
+        if let Some(nonce) = self.nonce {
+            data.extend_from_slice(&nonce.to_le_bytes());
+        }
+        
+        // Extension records, likewise only when present
+        for record in &self.extensions {
+            record.encode_into(&mut data);
+        }
+        
         let hash_bytes = Sha256::digest(&data);
         let mut hash = [0u8; 32];
         hash.copy_from_slice(&hash_bytes);
@@ -299,54 +460,15 @@ impl Transaction {
     }
     
     /// Calculate signature hash for signing/verifying a specific input
-    /// This is the Bitcoin-style SIGHASH algorithm
+    /// This is the Bitcoin-style (legacy) SIGHASH algorithm; use a
+    /// `SighashCache` when hashing several inputs of the same transaction
     pub fn calculate_signature_hash(
         &self,
         input_index: usize,
         script_pubkey: &[u8],
         sighash_type: u32,
     ) -> Hash256 {
-        let mut data = Vec::new();
-        
-        // Version
-        data.extend_from_slice(&self.version.to_le_bytes());
-        
-        // Inputs - clear all script_sigs except the one being signed
-        for (i, input) in self.inputs.iter().enumerate() {
-            data.extend_from_slice(&input.prev_tx_hash);
-            data.extend_from_slice(&input.prev_output_index.to_le_bytes());
-            
-            if i == input_index {
-                // For the input being signed, use the script_pubkey from the UTXO
-                data.extend_from_slice(&(script_pubkey.len() as u32).to_le_bytes());
-                data.extend_from_slice(script_pubkey);
-            } else {
-                // For other inputs, use empty script
-                data.extend_from_slice(&0u32.to_le_bytes());
-            }
-            
-            data.extend_from_slice(&input.sequence.to_le_bytes());
-        }
-        
-        // Outputs (SIGHASH_ALL includes all outputs)
-        for output in &self.outputs {
-            data.extend_from_slice(&output.value.to_le_bytes());
-            data.extend_from_slice(&(output.script_pubkey.len() as u32).to_le_bytes());
-            data.extend_from_slice(&output.script_pubkey);
-        }
-        
-        // Locktime
-        data.extend_from_slice(&self.locktime.to_le_bytes());
-        
-        // SIGHASH type
-        data.extend_from_slice(&sighash_type.to_le_bytes());
-        
-        // Double SHA256
-        let hash1 = Sha256::digest(&data);
-        let hash2 = Sha256::digest(&hash1);
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&hash2);
-        hash
+        SighashCache::new(self).legacy_hash(input_index, script_pubkey, sighash_type)
     }
     
     pub fn get_hash(&self) -> Result<Hash256> {
@@ -378,14 +500,81 @@ impl Transaction {
         Ok(json.into_bytes())
     }
 
+    /// Virtual size, see `vsize`
     pub fn estimate_size(&self) -> usize {
-        // Simplified size estimation
-        let base_size = 4 + 4; // version + locktime
-        let inputs_size = self.inputs.len() * 150; // Approximate input size
-        let outputs_size = self.outputs.len() * 34; // Approximate output size
-        let witness_size = if self.is_segwit() { self.witnesses.len() * 100 } else { 0 };
-        
-        base_size + inputs_size + outputs_size + witness_size
+        self.vsize()
+    }
+
+    /// Size in the Bitcoin wire layout without witness data: version,
+    /// compact-size counted inputs and outputs and locktime, followed by
+    /// whichever contract fields are set
+    pub fn base_size(&self) -> usize {
+        let inputs: usize = self.inputs.iter()
+            .map(|input| 32 + 4 + var_bytes_len(&input.script_sig) + 4)
+            .sum();
+        let outputs: usize = self.outputs.iter()
+            .map(|output| 8 + var_bytes_len(&output.script_pubkey))
+            .sum();
+        let contract = self.contract_code.as_deref().map_or(0, var_bytes_len)
+            + self.contract_data.as_deref().map_or(0, var_bytes_len)
+            + self.contract_address.map_or(0, |address| address.len())
+            + self.gas_limit.map_or(0, |_| 8)
+            + self.max_fee_per_gas.map_or(0, |_| 8)
+            + self.max_priority_fee_per_gas.map_or(0, |_| 8)
+            + self.nonce.map_or(0, |_| 8)
+            + self.extensions.iter().map(|record| 8 + record.data.len()).sum::<usize>();
+
+        4 + compact_size_len(self.inputs.len()) + inputs
+            + compact_size_len(self.outputs.len()) + outputs
+            + 4 + contract
+    }
+
+    /// Size including witness data: the segwit marker and flag plus one
+    /// stack per input, when any input has a witness
+    pub fn total_size(&self) -> usize {
+        if !self.is_segwit() {
+            return self.base_size();
+        }
+        let witnesses: usize = self.witnesses.iter()
+            .map(|witness| {
+                compact_size_len(witness.witness_items.len())
+                    + witness.witness_items.iter().map(|item| var_bytes_len(item)).sum::<usize>()
+            })
+            .sum();
+        self.base_size() + 2 + witnesses
+    }
+
+    /// BIP 141 weight: non-witness bytes count four times, witness bytes once
+    pub fn weight(&self) -> usize {
+        self.base_size() * (WITNESS_SCALE_FACTOR - 1) + self.total_size()
+    }
+
+    /// Weight in virtual bytes, rounded up. Fee rates are per virtual byte.
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(WITNESS_SCALE_FACTOR)
+    }
+
+    /// Virtual size, once signed, of a transaction spending `input_count`
+    /// P2PKH outputs to outputs with scripts of the given sizes. For fee
+    /// estimation before the inputs can be signed.
+    pub fn estimate_p2pkh_vsize(input_count: usize, output_script_sizes: impl IntoIterator<Item = usize>) -> usize {
+        Self::estimate_vsize(&vec![InputKind::P2pkh; input_count], output_script_sizes)
+    }
+
+    /// Virtual size, once signed with the largest signatures, of a
+    /// transaction with inputs of the given kinds and outputs with scripts
+    /// of the given sizes
+    pub fn estimate_vsize(inputs: &[InputKind], output_script_sizes: impl IntoIterator<Item = usize>) -> usize {
+        let (script_sigs, witnesses): (Vec<_>, Vec<_>) = inputs.iter().map(InputKind::placeholder).unzip();
+        let inputs = script_sigs.into_iter()
+            .map(|script_sig| TransactionInput::new([0u8; 32], 0, script_sig))
+            .collect();
+        let outputs = output_script_sizes.into_iter()
+            .map(|size| TransactionOutput::new(0, vec![0u8; size]))
+            .collect();
+        let mut tx = Transaction::new(1, inputs, outputs);
+        tx.witnesses = witnesses;
+        tx.vsize()
     }
 }
 
@@ -665,10 +854,10 @@ impl TransactionBuilder {
     }
     
     fn estimate_size(&self) -> usize {
-        let base_size = 4 + 4; // version + locktime
-        let inputs_size = self.inputs.len() * 150; // Approximate
-        let outputs_size = self.outputs.len() * 34; // Approximate
-        base_size + inputs_size + outputs_size
+        Transaction::estimate_p2pkh_vsize(
+            self.inputs.len(),
+            self.outputs.iter().map(|output| output.script_pubkey.len()),
+        )
     }
     
     /// Create signature hash for transaction input (implements Bitcoin's SIGHASH_ALL)
@@ -780,3 +969,74 @@ pub mod validation {
 
 // TODO: Add transaction utilities and tests when types are properly imported
 // TODO: Uncomment and fix when Hash256 is properly imported
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_vsize_by_input_kind() {
+        let outputs = [P2PKH_SCRIPT_PUBKEY_SIZE; 2];
+        let p2pkh = Transaction::estimate_vsize(&[InputKind::P2pkh], outputs);
+        assert_eq!(p2pkh, Transaction::estimate_p2pkh_vsize(1, outputs));
+
+        // Witness data is discounted, so the segwit forms are smaller
+        let p2wpkh = Transaction::estimate_vsize(&[InputKind::P2wpkh], outputs);
+        assert!(p2wpkh < p2pkh);
+        let p2sh = Transaction::estimate_vsize(&[InputKind::P2shMultisig { required: 2, keys: 3 }], outputs);
+        let p2wsh = Transaction::estimate_vsize(&[InputKind::P2wshMultisig { required: 2, keys: 3 }], outputs);
+        assert!(p2wsh < p2sh);
+        // OP_0, two pushed signatures and the pushed 105-byte redeem script
+        assert_eq!(p2sh - p2pkh, (1 + 2 * 74 + 2 + 105) - P2PKH_SCRIPT_SIG_MAX_SIZE + 2);
+        // Each further signature costs one more push
+        let p2sh_3 = Transaction::estimate_vsize(&[InputKind::P2shMultisig { required: 3, keys: 3 }], outputs);
+        assert_eq!(p2sh_3 - p2sh, 1 + SIGNATURE_MAX_SIZE);
+
+        // The estimate covers a real signed input
+        let mut signed = Transaction::new(
+            1,
+            vec![TransactionInput::new([1u8; 32], 0, vec![0u8; 1 + 72 + 1 + 33])],
+            vec![TransactionOutput::new(1_000, vec![0u8; P2PKH_SCRIPT_PUBKEY_SIZE]); 2],
+        );
+        assert!(signed.vsize() <= p2pkh);
+        signed.inputs[0].script_sig.clear();
+        signed.witnesses[0].add_item(vec![0u8; 72]);
+        signed.witnesses[0].add_item(vec![0u8; 33]);
+        assert!(signed.vsize() <= p2wpkh);
+
+        assert_eq!(InputKind::of_script_pubkey(&[0x76, 0xa9, 0x14]), Some(InputKind::P2pkh));
+        let mut p2wpkh_script = vec![0x00, 0x14];
+        p2wpkh_script.extend_from_slice(&[7u8; 20]);
+        assert_eq!(InputKind::of_script_pubkey(&p2wpkh_script), Some(InputKind::P2wpkh));
+        assert_eq!(InputKind::of_script_pubkey(&[0xa9, 0x14]), None);
+    }
+
+    #[test]
+    fn test_weight_and_vsize() {
+        // 1 P2PKH input, 2 P2PKH outputs: the classic 226-227 byte payment
+        assert_eq!(Transaction::estimate_p2pkh_vsize(1, [P2PKH_SCRIPT_PUBKEY_SIZE; 2]), 227);
+
+        let mut tx = Transaction::new(
+            1,
+            vec![TransactionInput::new([1u8; 32], 0, vec![0u8; 107])],
+            vec![TransactionOutput::new(1_000, vec![0u8; P2PKH_SCRIPT_PUBKEY_SIZE])],
+        );
+        let base: usize = 4 + 1 + (32 + 4 + 1 + 107 + 4) + 1 + (8 + 1 + 25) + 4;
+        assert_eq!(tx.base_size(), base);
+        assert_eq!(tx.total_size(), base);
+        assert_eq!(tx.weight(), base * WITNESS_SCALE_FACTOR);
+        assert_eq!(tx.vsize(), base);
+
+        // Witness bytes are discounted to a quarter
+        tx.witnesses[0].add_item(vec![0u8; 72]);
+        tx.witnesses[0].add_item(vec![0u8; 33]);
+        let witness: usize = 2 + 1 + (1 + 72) + (1 + 33);
+        assert_eq!(tx.total_size(), base + witness);
+        assert_eq!(tx.weight(), base * 3 + base + witness);
+        assert_eq!(tx.vsize(), base + witness.div_ceil(4));
+
+        // Large scripts take longer compact-size prefixes
+        tx.outputs[0].script_pubkey = vec![0u8; 300];
+        assert_eq!(tx.base_size(), base - 26 + 3 + 300);
+    }
+}
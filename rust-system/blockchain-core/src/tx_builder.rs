@@ -4,12 +4,16 @@
 //! Integrates with wallet system and UTXO management.
 
 use crate::{Hash256, BlockchainError, Result, PrivateKey};
-use crate::transaction::{Transaction, TransactionInput, TransactionOutput};
-use crate::utxo::{UTXOSet, UTXO};
+use crate::address::{AddressCodec, AddressKind};
+use crate::amount::Amount;
+use crate::script_utils::{HtlcScript, HtlcSpend, MultisigScript, ScriptBuilder, ScriptHash, ScriptHashSpend};
+use crate::sighash::{SighashCache, SIGHASH_ALL};
+use crate::transaction::{Transaction, TransactionInput, TransactionOutput, TransactionWitness, P2PKH_SCRIPT_PUBKEY_SIZE, SEQUENCE_FINAL};
+use crate::utxo::{Balance, UTXOSet, UTXO};
 use crate::wallet::Wallet;
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
@@ -34,6 +38,8 @@ struct TxInput {
 struct TxOutput {
     address: String,
     amount: u64,
+    /// Script to pay to instead of the address's P2PKH script
+    script_pubkey: Option<Vec<u8>>,
 }
 
 impl TransactionBuilder {
@@ -61,29 +67,49 @@ impl TransactionBuilder {
 
     /// Add an output to the transaction
     pub fn add_output(mut self, address: String, amount: u64) -> Self {
-        self.outputs.push(TxOutput { address, amount });
+        self.outputs.push(TxOutput { address, amount, script_pubkey: None });
+        self
+    }
+
+    /// Add an output locked by a hash-time-locked contract
+    pub fn add_htlc_output(mut self, htlc: &HtlcScript, amount: u64) -> Self {
+        self.outputs.push(TxOutput {
+            address: String::new(),
+            amount,
+            script_pubkey: Some(htlc.to_script()),
+        });
+        self
+    }
+
+    /// Add an output paying to an arbitrary script, such as an OP_RETURN
+    /// name operation
+    pub fn add_script_output(mut self, script_pubkey: Vec<u8>, amount: u64) -> Self {
+        self.outputs.push(TxOutput {
+            address: String::new(),
+            amount,
+            script_pubkey: Some(script_pubkey),
+        });
         self
     }
 
     /// Build and sign the transaction
     pub fn build(mut self, wallet: &Wallet, utxo_set: &UTXOSet) -> Result<Transaction> {
         // Calculate total output amount
-        let total_output: u64 = self.outputs.iter().map(|o| o.amount).sum();
+        let total_output = Amount::checked_sum(self.outputs.iter().map(|o| Amount::from_sat(o.amount)))?;
         
         // Estimate transaction size for fee calculation
         let estimated_size = self.estimate_transaction_size();
-        let estimated_fee = estimated_size * self.fee_rate;
-        let total_needed = total_output + estimated_fee;
+        let estimated_fee = Amount::from_sat(self.fee_rate).checked_mul(estimated_size)?;
+        let total_needed = total_output.checked_add(estimated_fee)?;
 
         // Select UTXOs to cover the amount
-        let selected_utxos = utxo_set.select_utxos_for_amount(&wallet.address, total_needed)?;
+        let selected_utxos = utxo_set.select_utxos_for_amount(&wallet.address, total_needed.to_sat())?;
 
         // Calculate actual input value
-        let input_value: u64 = selected_utxos.iter().map(|utxo| utxo.value()).sum();
+        let input_value = Amount::checked_sum(selected_utxos.iter().map(|utxo| Amount::from_sat(utxo.value())))?;
         
-        // Calculate change
-        let actual_fee = estimated_fee; // For now, use estimated fee
-        let change = input_value.saturating_sub(total_output + actual_fee);
+        // Calculate change, paying the estimated fee
+        let change = input_value.saturating_sub(total_needed).to_sat();
 
         // Create transaction
         let mut tx = Transaction::new(1, Vec::new(), Vec::new());
@@ -105,7 +131,7 @@ impl TransactionBuilder {
 
         // Add outputs
         for output in &self.outputs {
-            let script_pubkey = create_p2pkh_script(&output.address)?;
+            let script_pubkey = self.output_script(output)?;
             tx.outputs.push(TransactionOutput::new(output.amount, script_pubkey));
         }
 
@@ -121,23 +147,158 @@ impl TransactionBuilder {
         Ok(tx)
     }
 
-    /// Estimate transaction size in bytes
+    /// Build and sign a transaction spending exactly the given UTXOs.
+    /// No coin selection is done and no change output is added, so the
+    /// fee is whatever the inputs leave over after the outputs.
+    pub fn spend_utxos(mut self, wallet: &Wallet, utxos: Vec<UTXO>) -> Result<Transaction> {
+        if utxos.is_empty() {
+            return Err(BlockchainError::InvalidTransaction("No inputs to spend".to_string()));
+        }
+
+        let input_value = Amount::checked_sum(utxos.iter().map(|utxo| Amount::from_sat(utxo.value())))?;
+        let total_output = Amount::checked_sum(self.outputs.iter().map(|o| Amount::from_sat(o.amount)))?;
+        if total_output > input_value {
+            return Err(BlockchainError::InsufficientFunds(
+                format!("Need {}, inputs only provide {}", total_output, input_value)
+            ));
+        }
+
+        let mut tx = Transaction::new(1, Vec::new(), Vec::new());
+        tx.locktime = self.locktime;
+
+        for utxo in utxos {
+            tx.inputs.push(TransactionInput::new(utxo.tx_hash, utxo.output_index, Vec::new()));
+            self.inputs.push(TxInput {
+                utxo,
+                private_key: wallet.private_key,
+            });
+        }
+
+        for output in &self.outputs {
+            let script_pubkey = self.output_script(output)?;
+            tx.outputs.push(TransactionOutput::new(output.amount, script_pubkey));
+        }
+
+        self.sign_transaction(&mut tx)?;
+
+        Ok(tx)
+    }
+
+    /// Claim an HTLC output by revealing its preimage. The wallet must hold
+    /// the recipient key; outputs added to the builder receive the funds.
+    pub fn redeem_htlc(self, wallet: &Wallet, utxo: UTXO, preimage: [u8; 32]) -> Result<Transaction> {
+        let htlc = Self::htlc_of(&utxo)?;
+        if crate::crypto::sha256(&preimage) != htlc.payment_hash {
+            return Err(BlockchainError::InvalidInput("Preimage does not match the HTLC payment hash".to_string()));
+        }
+        self.spend_htlc(wallet, utxo, |signature, public_key| HtlcSpend::Redeem { signature, public_key, preimage })
+    }
+
+    /// Take back an HTLC output after its timeout. The wallet must hold the
+    /// refund key; the transaction is locked to the HTLC's locktime.
+    pub fn refund_htlc(mut self, wallet: &Wallet, utxo: UTXO) -> Result<Transaction> {
+        let htlc = Self::htlc_of(&utxo)?;
+        self.locktime = self.locktime.max(htlc.locktime);
+        self.spend_htlc(wallet, utxo, |signature, public_key| HtlcSpend::Refund { signature, public_key })
+    }
+
+    /// Spend a P2SH or P2WSH output locked by the multisig `script`,
+    /// signing with `private_keys`, which must cover as many of its keys as
+    /// it requires. Outputs added to the builder receive the funds; no
+    /// change is added. Cosigners that don't share keys sign separately with
+    /// `sign_script_input` and combine with `finalize_script_input` instead.
+    pub fn spend_script_utxo(self, utxo: UTXO, script: &[u8], private_keys: &[PrivateKey]) -> Result<Transaction> {
+        let total_output = Amount::checked_sum(self.outputs.iter().map(|o| Amount::from_sat(o.amount)))?;
+        if total_output > Amount::from_sat(utxo.value()) {
+            return Err(BlockchainError::InsufficientFunds(
+                format!("Need {}, the script output only holds {}", total_output, Amount::from_sat(utxo.value()))
+            ));
+        }
+
+        let mut tx = Transaction::new(1, Vec::new(), Vec::new());
+        tx.locktime = self.locktime;
+        tx.inputs.push(TransactionInput::new(utxo.tx_hash, utxo.output_index, Vec::new()));
+        for output in &self.outputs {
+            tx.outputs.push(TransactionOutput::new(output.amount, self.output_script(output)?));
+        }
+
+        let signatures = private_keys.iter()
+            .map(|private_key| sign_script_input(&tx, 0, &utxo, script, private_key))
+            .collect::<Result<Vec<_>>>()?;
+        finalize_script_input(&mut tx, 0, &utxo, script, &signatures)?;
+
+        Ok(tx)
+    }
+
+    fn htlc_of(utxo: &UTXO) -> Result<HtlcScript> {
+        HtlcScript::from_script(&utxo.output.script_pubkey)
+            .ok_or_else(|| BlockchainError::InvalidScript("UTXO is not an HTLC output".to_string()))
+    }
+
+    /// Spend a single HTLC output, building its script_sig with `spend`
+    fn spend_htlc(
+        self,
+        wallet: &Wallet,
+        utxo: UTXO,
+        spend: impl FnOnce(Vec<u8>, Vec<u8>) -> HtlcSpend,
+    ) -> Result<Transaction> {
+        let total_output: u64 = self.outputs.iter().map(|o| o.amount).sum();
+        if total_output > utxo.value() {
+            return Err(BlockchainError::InsufficientFunds(
+                format!("Need {} satoshis, HTLC only holds {}", total_output, utxo.value())
+            ));
+        }
+
+        let mut tx = Transaction::new(1, Vec::new(), Vec::new());
+        tx.locktime = self.locktime;
+
+        let mut input = TransactionInput::new(utxo.tx_hash, utxo.output_index, Vec::new());
+        // A final sequence would disable the locktime the refund branch relies on
+        input.sequence = SEQUENCE_FINAL - 1;
+        tx.inputs.push(input);
+
+        for output in &self.outputs {
+            tx.outputs.push(TransactionOutput::new(output.amount, self.output_script(output)?));
+        }
+
+        let signature = self.create_signature(&mut SighashCache::new(&tx), 0, &utxo, &wallet.private_key)?;
+        let public_key = derive_public_key(&wallet.private_key)?;
+        tx.inputs[0].script_sig = spend(signature, public_key).to_script_sig();
+
+        Ok(tx)
+    }
+
+    /// Script for an output: an explicit script, or P2PKH or P2SH to its
+    /// address
+    fn output_script(&self, output: &TxOutput) -> Result<Vec<u8>> {
+        match &output.script_pubkey {
+            Some(script) => Ok(script.clone()),
+            None => match AddressCodec::validate(&output.address)? {
+                AddressKind::P2pkh => create_p2pkh_script(&output.address),
+                AddressKind::P2sh => Ok(ScriptBuilder::create_p2sh_script(
+                    &AddressCodec::decode_kind(&output.address, AddressKind::P2sh)?
+                )),
+            },
+        }
+    }
+
+    /// Estimate the signed transaction's virtual size
     fn estimate_transaction_size(&self) -> u64 {
-        let input_count = self.inputs.len();
-        let output_count = self.outputs.len() + 1; // +1 for potential change output
-        
-        // Rough estimation:
-        // - Base transaction: 10 bytes
-        // - Each input: ~150 bytes (outpoint + script_sig + sequence)
-        // - Each output: ~34 bytes (value + script_pubkey)
-        let size = 10 + (input_count * 150) + (output_count * 34);
-        size as u64
+        // Every output plus a potential P2PKH change output
+        let output_scripts = self.outputs.iter()
+            .map(|output| output.script_pubkey.as_ref().map_or(P2PKH_SCRIPT_PUBKEY_SIZE, Vec::len))
+            .chain(std::iter::once(P2PKH_SCRIPT_PUBKEY_SIZE));
+        Transaction::estimate_p2pkh_vsize(self.inputs.len(), output_scripts) as u64
     }
 
     /// Sign all inputs of the transaction
     fn sign_transaction(&self, tx: &mut Transaction) -> Result<()> {
+        // Signature hashes never cover script_sigs, so sign every input
+        // against one cache before filling them in
+        let mut sighashes = SighashCache::new(tx);
+        let mut script_sigs = Vec::with_capacity(self.inputs.len());
         for (i, tx_input) in self.inputs.iter().enumerate() {
-            let signature = self.create_signature(tx, i, &tx_input.utxo, &tx_input.private_key)?;
+            let signature = self.create_signature(&mut sighashes, i, &tx_input.utxo, &tx_input.private_key)?;
             let public_key = derive_public_key(&tx_input.private_key)?;
             
             // Create script_sig (simplified P2PKH)
@@ -146,8 +307,10 @@ impl TransactionBuilder {
             script_sig.extend_from_slice(&signature);
             script_sig.push(public_key.len() as u8); // Push public key
             script_sig.extend_from_slice(&public_key);
-            
-            tx.inputs[i].script_sig = script_sig;
+            script_sigs.push(script_sig);
+        }
+        for (input, script_sig) in tx.inputs.iter_mut().zip(script_sigs) {
+            input.script_sig = script_sig;
         }
         Ok(())
     }
@@ -155,34 +318,23 @@ impl TransactionBuilder {
     /// Create signature for a specific input
     fn create_signature(
         &self,
-        tx: &Transaction,
+        sighashes: &mut SighashCache,
         input_index: usize,
         utxo: &UTXO,
         private_key: &PrivateKey,
     ) -> Result<Vec<u8>> {
-        // Create signature hash (simplified)
-        let signature_hash = self.create_signature_hash(tx, input_index, &utxo.output.script_pubkey)?;
+        // Legacy SIGHASH_ALL: the precomputed digest is only valid once its deployment activates
+        let sighash_type = SIGHASH_ALL;
+        let signature_hash = sighashes.signature_hash(input_index, &utxo.output.script_pubkey, utxo.output.value, sighash_type)?;
         
         // Sign with private key (simplified ECDSA)
         let signature = sign_hash(&signature_hash, private_key)?;
         
-        // Add SIGHASH_ALL flag
         let mut sig_with_hashtype = signature;
-        sig_with_hashtype.push(0x01); // SIGHASH_ALL
+        sig_with_hashtype.push(sighash_type as u8);
         
         Ok(sig_with_hashtype)
     }
-
-    /// Create the signature hash for signing
-    fn create_signature_hash(
-        &self,
-        tx: &Transaction,
-        input_index: usize,
-        script_code: &[u8],
-    ) -> Result<Hash256> {
-        // Use proper Bitcoin-style SIGHASH_ALL
-        Ok(tx.calculate_signature_hash(input_index, script_code, 0x01))
-    }
 }
 
 impl Default for TransactionBuilder {
@@ -209,6 +361,18 @@ pub struct PendingTransaction {
     pub last_broadcast: Option<DateTime<Utc>>,
 }
 
+/// Where a pending transaction stands against the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingStatus {
+    /// Its inputs are still unspent
+    Unconfirmed,
+    /// Its outputs are in the UTXO set
+    Confirmed,
+    /// An input was spent by some other transaction
+    Conflicted,
+}
+
 impl TransactionManager {
     /// Create a new transaction manager
     pub fn new(utxo_set: UTXOSet) -> Self {
@@ -271,8 +435,14 @@ impl TransactionManager {
 
     /// Add a transaction to pending pool
     pub fn add_pending_transaction(&mut self, tx: Transaction) -> Result<Uuid> {
-        let tx_id = Uuid::new_v4();
         let fee = self.utxo_set.calculate_fee(&tx)?;
+        self.add_pending_with_fee(tx, fee)
+    }
+
+    /// Add a transaction whose fee is already known, such as one the
+    /// mempool accepted, to the pending pool
+    pub fn add_pending_with_fee(&mut self, tx: Transaction, fee: u64) -> Result<Uuid> {
+        let tx_id = Uuid::new_v4();
         
         let pending = PendingTransaction {
             id: tx_id,
@@ -317,6 +487,13 @@ impl TransactionManager {
         self.utxo_set.get_balance(address)
     }
 
+    /// Get the confirmed/unconfirmed/immature/locked split for an address,
+    /// counting this manager's pending transactions as unconfirmed
+    pub fn get_balance_breakdown(&self, address: &str, locked: &HashSet<String>) -> Balance {
+        let pending = self.pending_transactions.values().map(|p| &p.transaction);
+        self.utxo_set.get_balance_breakdown(address, pending, locked)
+    }
+
     /// Get current blockchain height
     pub fn get_current_height(&self) -> u64 {
         self.utxo_set.get_current_height()
@@ -332,30 +509,138 @@ impl TransactionManager {
         let cutoff = Utc::now() - chrono::Duration::hours(max_age_hours);
         self.pending_transactions.retain(|_, tx| tx.created_at > cutoff);
     }
-}
 
-/// Create a P2PKH script for an address
-pub fn create_p2pkh_script(address: &str) -> Result<Vec<u8>> {
-    // Simplified P2PKH script creation
-    // In a real implementation, this would decode the address properly
-    
-    if !address.starts_with("edu1q") {
-        return Err(BlockchainError::InvalidAddress(format!("Invalid address format: {}", address)));
+    /// Record that a pending transaction has been relayed to the network
+    pub fn mark_broadcast(&mut self, tx_hash: &str) -> Result<()> {
+        let pending = self.pending_transactions.get_mut(tx_hash).ok_or_else(|| {
+            BlockchainError::InvalidTransaction(format!("Transaction {} is not pending", tx_hash))
+        })?;
+        pending.broadcast_count += 1;
+        pending.last_broadcast = Some(Utc::now());
+        Ok(())
+    }
+
+    /// Pending transactions not relayed for at least `min_age`, longest
+    /// waiting first. A transaction replaced by fee is no longer pending,
+    /// so only its replacement is ever returned.
+    pub fn due_for_rebroadcast(&self, min_age: chrono::Duration, now: DateTime<Utc>) -> Vec<PendingTransaction> {
+        let mut due: Vec<PendingTransaction> = self.pending_transactions
+            .values()
+            .filter(|pending| now - pending.last_broadcast.unwrap_or(pending.created_at) >= min_age)
+            .cloned()
+            .collect();
+        due.sort_by_key(|pending| pending.last_broadcast.unwrap_or(pending.created_at));
+        due
+    }
+
+    /// Where a pending transaction stands against the chain's UTXO set
+    pub fn pending_status(pending: &PendingTransaction, chain: &UTXOSet) -> Result<PendingStatus> {
+        let tx_hash = hex::encode(pending.transaction.get_hash()?);
+        if (0..pending.transaction.outputs.len()).any(|index| chain.get_utxo(&format!("{}:{}", tx_hash, index)).is_some()) {
+            return Ok(PendingStatus::Confirmed);
+        }
+        let spent = pending.transaction.inputs.iter().any(|input| {
+            chain.get_utxo(&format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index)).is_none()
+        });
+        Ok(if spent { PendingStatus::Conflicted } else { PendingStatus::Unconfirmed })
+    }
+
+    /// Stop tracking a pending transaction that confirmed or conflicted
+    pub fn remove_pending(&mut self, tx_hash: &str) -> Option<PendingTransaction> {
+        self.pending_transactions.remove(tx_hash)
+    }
+
+    /// Check whether an outpoint is spent by one of our pending transactions
+    pub fn is_outpoint_reserved(&self, outpoint: &str) -> bool {
+        self.pending_transactions.values().any(|pending| {
+            pending.transaction.inputs.iter().any(|input| {
+                format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index) == outpoint
+            })
+        })
     }
 
-    // Extract hash160 from address (simplified)
-    let hash160 = if address.len() >= 45 {
-        hex::decode(&address[5..45]).map_err(|_| {
-            BlockchainError::InvalidAddress("Invalid address encoding".to_string())
-        })?
-    } else {
-        vec![0u8; 20] // Placeholder
-    };
+    /// Abandon a pending transaction that has never been relayed.
+    ///
+    /// The transaction is dropped from the pending pool so its inputs can be
+    /// spent again. Relayed transactions may still be mined by other nodes and
+    /// must be cancelled with [`create_cancel_transaction`](Self::create_cancel_transaction) instead.
+    pub fn abandon_transaction(&mut self, tx_hash: &str) -> Result<PendingTransaction> {
+        let pending = self.pending_transactions.get(tx_hash).ok_or_else(|| {
+            BlockchainError::InvalidTransaction(format!("Transaction {} is not pending", tx_hash))
+        })?;
+
+        if pending.broadcast_count > 0 {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Transaction {} has already been relayed; cancel it with a higher-fee double-spend instead",
+                tx_hash
+            )));
+        }
+
+        Ok(self.pending_transactions.remove(tx_hash).expect("checked above"))
+    }
+
+    /// Build a replace-by-fee cancellation for a relayed pending transaction.
+    ///
+    /// The replacement spends the same inputs back to the wallet's own address,
+    /// paying the original fee plus `fee_bump` so the mempool accepts it as a
+    /// replacement. Inputs are looked up in `chain`, the current UTXO set.
+    /// The returned transaction still has to be submitted.
+    pub fn create_cancel_transaction(
+        &self,
+        tx_hash: &str,
+        wallet: &Wallet,
+        fee_bump: u64,
+        chain: &UTXOSet,
+    ) -> Result<Transaction> {
+        let pending = self.pending_transactions.get(tx_hash).ok_or_else(|| {
+            BlockchainError::InvalidTransaction(format!("Transaction {} is not pending", tx_hash))
+        })?;
+
+        let utxos = pending.transaction.inputs
+            .iter()
+            .map(|input| {
+                let outpoint = format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index);
+                chain.get_utxo(&outpoint).cloned().ok_or_else(|| {
+                    BlockchainError::InvalidTransaction(format!("Input {} is no longer unspent", outpoint))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let input_value: u64 = utxos.iter().map(|utxo| utxo.value()).sum();
+        let new_fee = pending.fee.saturating_add(fee_bump);
+        let refund = input_value.saturating_sub(new_fee);
+        if refund <= 546 {
+            return Err(BlockchainError::InsufficientFunds(format!(
+                "Inputs worth {} cannot cover a cancellation fee of {}",
+                input_value, new_fee
+            )));
+        }
 
-    if hash160.len() != 20 {
-        return Err(BlockchainError::InvalidAddress("Invalid hash160 length".to_string()));
+        TransactionBuilder::new()
+            .add_output(wallet.address.clone(), refund)
+            .spend_utxos(wallet, utxos)
     }
 
+    /// Replace a pending transaction with its cancellation, paying `fee`,
+    /// once the cancellation has been accepted for relay
+    pub fn record_cancellation(&mut self, tx_hash: &str, replacement: Transaction, fee: u64) -> Result<Uuid> {
+        if self.pending_transactions.remove(tx_hash).is_none() {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "Transaction {} is not pending", tx_hash
+            )));
+        }
+
+        let replacement_hash = hex::encode(replacement.get_hash()?);
+        let id = self.add_pending_with_fee(replacement, fee)?;
+        self.mark_broadcast(&replacement_hash)?;
+        Ok(id)
+    }
+}
+
+/// Create a P2PKH script for an address
+pub fn create_p2pkh_script(address: &str) -> Result<Vec<u8>> {
+    let hash160 = AddressCodec::decode_kind(address, AddressKind::P2pkh)?;
+
     // Create P2PKH script: OP_DUP OP_HASH160 <hash160> OP_EQUALVERIFY OP_CHECKSIG
     let mut script = Vec::with_capacity(25);
     script.push(0x76); // OP_DUP
@@ -368,6 +653,106 @@ pub fn create_p2pkh_script(address: &str) -> Result<Vec<u8>> {
     Ok(script)
 }
 
+/// A cosigner's signature for a script hash input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptSignature {
+    /// Compressed public key of the signer
+    pub public_key: Vec<u8>,
+    /// Signature including its SIGHASH byte
+    pub signature: Vec<u8>,
+}
+
+/// The multisig script an output commits to, checked against `utxo`
+fn committed_multisig(utxo: &UTXO, script: &[u8]) -> Result<(ScriptHash, MultisigScript)> {
+    let hash = ScriptHash::from_script_pubkey(&utxo.output.script_pubkey)
+        .ok_or_else(|| BlockchainError::InvalidScript("UTXO is not a P2SH or P2WSH output".to_string()))?;
+    if !hash.commits_to(script) {
+        return Err(BlockchainError::InvalidScript("Script does not match the output's script hash".to_string()));
+    }
+    let multisig = MultisigScript::from_script(script)
+        .ok_or_else(|| BlockchainError::InvalidScript("Only multisig scripts can be spent".to_string()))?;
+    Ok((hash, multisig))
+}
+
+/// Signature hash of a script hash input. The script stands in for the
+/// script_pubkey, as validation does.
+fn script_input_sighash(tx: &Transaction, input_index: usize, utxo: &UTXO, script: &[u8]) -> Result<Hash256> {
+    SighashCache::new(tx).signature_hash(input_index, script, utxo.output.value, SIGHASH_ALL)
+}
+
+/// Sign input `input_index` of `tx`, which spends `utxo` through the
+/// multisig `script`. Signatures don't cover script_sigs or witnesses, so
+/// cosigners each sign the same unsigned transaction.
+pub fn sign_script_input(
+    tx: &Transaction,
+    input_index: usize,
+    utxo: &UTXO,
+    script: &[u8],
+    private_key: &PrivateKey,
+) -> Result<ScriptSignature> {
+    let (_, multisig) = committed_multisig(utxo, script)?;
+    let public_key = derive_public_key(private_key)?;
+    if !multisig.public_keys.iter().any(|key| key[..] == public_key[..]) {
+        return Err(BlockchainError::SigningError("Key is not one of the script's keys".to_string()));
+    }
+
+    let mut signature = sign_hash(&script_input_sighash(tx, input_index, utxo, script)?, private_key)?;
+    signature.push(SIGHASH_ALL as u8);
+    Ok(ScriptSignature { public_key, signature })
+}
+
+/// Complete input `input_index` of `tx` from cosigners' signatures: the
+/// script_sig of a P2SH input, or the witness of a P2WSH input with an
+/// empty script_sig. Signatures are checked, put in the script's key order
+/// as OP_CHECKMULTISIG expects, and only as many as required are used.
+pub fn finalize_script_input(
+    tx: &mut Transaction,
+    input_index: usize,
+    utxo: &UTXO,
+    script: &[u8],
+    signatures: &[ScriptSignature],
+) -> Result<()> {
+    if input_index >= tx.inputs.len() {
+        return Err(BlockchainError::InvalidInput(format!("Transaction has no input {}", input_index)));
+    }
+    let (hash, multisig) = committed_multisig(utxo, script)?;
+    let sighash = script_input_sighash(tx, input_index, utxo, script)?;
+
+    let mut ordered = Vec::with_capacity(multisig.required);
+    let mut key_mask = 0u16;
+    for (i, public_key) in multisig.public_keys.iter().enumerate() {
+        let signature = signatures.iter().find(|s| {
+            s.public_key[..] == public_key[..]
+                && s.signature.len() > 1
+                && verify_signature(&s.signature[..s.signature.len() - 1], public_key, &sighash).unwrap_or(false)
+        });
+        if let Some(signature) = signature {
+            ordered.push(signature.signature.clone());
+            key_mask |= 1 << i;
+        }
+        if ordered.len() == multisig.required {
+            break;
+        }
+    }
+    if ordered.len() < multisig.required {
+        return Err(BlockchainError::SigningError(format!(
+            "{} valid signature(s), the script requires {}", ordered.len(), multisig.required
+        )));
+    }
+
+    let spend = ScriptHashSpend { key_mask, signatures: ordered, script: script.to_vec() };
+    match hash {
+        ScriptHash::P2sh(_) => tx.inputs[input_index].script_sig = spend.to_script_sig(),
+        ScriptHash::P2wsh(_) => {
+            tx.inputs[input_index].script_sig = Vec::new();
+            tx.witnesses.resize(tx.inputs.len(), TransactionWitness::new());
+            tx.witnesses[input_index] = spend.to_witness();
+        }
+    }
+    tx.clear_cache();
+    Ok(())
+}
+
 /// Derive public key from private key using secp256k1 ECDSA
 fn derive_public_key(private_key: &PrivateKey) -> Result<Vec<u8>> {
     let pubkey = crate::crypto::derive_public_key(private_key)?;
@@ -412,13 +797,113 @@ mod tests {
 
     #[test]
     fn test_p2pkh_script_creation() {
-        let address = "edu1q".to_owned() + &"a".repeat(40); // 40 hex chars = 20 bytes
+        let address = AddressCodec::encode_checked(AddressKind::P2pkh, &[0xaa; 20]);
         let script = create_p2pkh_script(&address).unwrap();
         
         assert_eq!(script.len(), 25);
         assert_eq!(script[0], 0x76); // OP_DUP
         assert_eq!(script[1], 0xa9); // OP_HASH160
         assert_eq!(script[2], 0x14); // Push 20 bytes
+        assert_eq!(&script[3..23], &[0xaa; 20]);
+        
+        // A mistyped or malformed address is refused rather than burned
+        assert!(create_p2pkh_script(&address[..address.len() - 1]).is_err());
+        assert!(create_p2pkh_script("edu1qTreasury00000000000000000000").is_err());
+    }
+
+    fn manager_with_pending(wallet: &Wallet) -> (TransactionManager, String) {
+        let mut utxo_set = UTXOSet::new();
+        let funding = UTXO::new(
+            [7u8; 32],
+            0,
+            TransactionOutput::new(10_000_000, create_p2pkh_script(&wallet.address).unwrap()),
+            1,
+            false,
+        );
+        utxo_set.add_utxo([7u8; 32], 0, funding.clone()).unwrap();
+
+        let tx = TransactionBuilder::new()
+            .add_output(wallet.address.clone(), 9_990_000)
+            .spend_utxos(wallet, vec![funding])
+            .unwrap();
+        let tx_hash = hex::encode(tx.get_hash().unwrap());
+
+        let mut manager = TransactionManager::new(utxo_set);
+        manager.add_pending_transaction(tx).unwrap();
+        (manager, tx_hash)
+    }
+
+    #[test]
+    fn test_abandon_unrelayed_transaction() {
+        let wallet = Wallet::new("abandon".to_string()).unwrap();
+        let (mut manager, tx_hash) = manager_with_pending(&wallet);
+        let outpoint = format!("{}:0", hex::encode([7u8; 32]));
+
+        assert!(manager.is_outpoint_reserved(&outpoint));
+        let abandoned = manager.abandon_transaction(&tx_hash).unwrap();
+        assert_eq!(abandoned.fee, 10_000);
+        assert!(!manager.is_outpoint_reserved(&outpoint));
+        assert!(manager.get_pending_transaction(&tx_hash).is_none());
+    }
+
+    #[test]
+    fn test_relayed_transaction_cannot_be_abandoned() {
+        let wallet = Wallet::new("relayed".to_string()).unwrap();
+        let (mut manager, tx_hash) = manager_with_pending(&wallet);
+
+        manager.mark_broadcast(&tx_hash).unwrap();
+        assert!(manager.abandon_transaction(&tx_hash).is_err());
+        assert!(manager.get_pending_transaction(&tx_hash).is_some());
+    }
+
+    #[test]
+    fn test_cancel_by_double_spend() {
+        let wallet = Wallet::new("cancel".to_string()).unwrap();
+        let (mut manager, tx_hash) = manager_with_pending(&wallet);
+        manager.mark_broadcast(&tx_hash).unwrap();
+
+        let original = manager.get_pending_transaction(&tx_hash).unwrap().transaction.clone();
+        let cancel = manager.create_cancel_transaction(&tx_hash, &wallet, 5_000, manager.get_utxo_set()).unwrap();
+
+        assert_eq!(cancel.inputs.len(), original.inputs.len());
+        assert_eq!(cancel.inputs[0].prev_tx_hash, original.inputs[0].prev_tx_hash);
+        assert_eq!(cancel.outputs.len(), 1);
+        assert_eq!(manager.get_utxo_set().calculate_fee(&cancel).unwrap(), 15_000);
+
+        let cancel_hash = hex::encode(cancel.get_hash().unwrap());
+        manager.record_cancellation(&tx_hash, cancel, 15_000).unwrap();
+        assert!(manager.get_pending_transaction(&tx_hash).is_none());
+        assert_eq!(manager.get_pending_transaction(&cancel_hash).unwrap().broadcast_count, 1);
+    }
+
+    #[test]
+    fn test_rebroadcast_follows_the_chain() {
+        let wallet = Wallet::new("rebroadcast".to_string()).unwrap();
+        let (mut manager, tx_hash) = manager_with_pending(&wallet);
+        let now = Utc::now();
+
+        assert_eq!(manager.due_for_rebroadcast(chrono::Duration::minutes(10), now).len(), 0);
+        let due = manager.due_for_rebroadcast(chrono::Duration::minutes(10), now + chrono::Duration::minutes(11));
+        assert_eq!(due.len(), 1);
+        assert_eq!(TransactionManager::pending_status(&due[0], manager.get_utxo_set()).unwrap(), PendingStatus::Unconfirmed);
+
+        // Relaying it restarts the wait
+        manager.mark_broadcast(&tx_hash).unwrap();
+        assert!(manager.due_for_rebroadcast(chrono::Duration::minutes(10), now + chrono::Duration::minutes(5)).is_empty());
+
+        // Mined: its output shows up and its input is gone
+        let pending = manager.get_pending_transaction(&tx_hash).unwrap().clone();
+        let mut chain = manager.get_utxo_set().clone();
+        chain.add_transaction(&pending.transaction, 2).unwrap();
+        assert_eq!(TransactionManager::pending_status(&pending, &chain).unwrap(), PendingStatus::Confirmed);
+
+        // Its input spent by something else
+        let mut chain = UTXOSet::new();
+        chain.add_utxo([8u8; 32], 0, UTXO::new([8u8; 32], 0, TransactionOutput::new(1, vec![]), 1, false)).unwrap();
+        assert_eq!(TransactionManager::pending_status(&pending, &chain).unwrap(), PendingStatus::Conflicted);
+
+        assert!(manager.remove_pending(&tx_hash).is_some());
+        assert!(manager.get_pending_transaction(&tx_hash).is_none());
     }
 
     #[test]
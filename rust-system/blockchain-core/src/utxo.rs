@@ -2,11 +2,23 @@
 //! 
 //! Handles unspent transaction outputs (UTXOs) for the blockchain.
 //! Provides efficient tracking, validation, and management of spendable outputs.
+//!
+//! The set keeps a running MuHash-style digest of its contents: every
+//! unspent output's serialized form is hashed to a number modulo the prime
+//! 2^3072 - 1103717, and the digest is the product of those numbers.
+//! Adding an output multiplies its number in and spending one divides it
+//! out, so the digest stays current without rehashing the set, and two
+//! nodes with the same outputs get the same digest whatever order they
+//! connected blocks in. Unlike a sum of hashes, finding another set with
+//! the same product is as hard as a discrete logarithm in that group.
 
 use crate::{Hash256, BlockchainError, Result};
+use crate::bloom::{FilterStats, ScalableBloomFilter};
 use crate::transaction::{Transaction, TransactionOutput, TransactionInput};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
@@ -76,6 +88,216 @@ impl UTXO {
         // Use TransactionOutput's get_address method
         self.output.get_address()
     }
+
+    /// Canonical bytes of this output for the set digest: outpoint, height,
+    /// coinbase flag, value and script. `created_at` is local to each node
+    /// and left out.
+    fn digest_bytes(&self) -> Vec<u8> {
+        let script = &self.output.script_pubkey;
+        let mut bytes = Vec::with_capacity(32 + 4 + 4 + 1 + 8 + 4 + script.len());
+        bytes.extend_from_slice(&self.tx_hash);
+        bytes.extend_from_slice(&self.output_index.to_le_bytes());
+        bytes.extend_from_slice(&self.block_height.to_le_bytes());
+        bytes.push(self.is_coinbase as u8);
+        bytes.extend_from_slice(&self.output.value.to_le_bytes());
+        bytes.extend_from_slice(&(script.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(script);
+        bytes
+    }
+}
+
+/// Address balance split by spendability, in satoshis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    /// Mature, unlocked outputs not being spent by a pending transaction
+    pub confirmed: u64,
+    /// Outputs of pending (mempool) transactions paying this address
+    pub unconfirmed: u64,
+    /// Coinbase outputs still waiting for maturity
+    pub immature: u64,
+    /// Outputs locked by the wallet (escrow, frozen accounts)
+    pub locked: u64,
+}
+
+impl Balance {
+    /// Everything the address holds or is about to receive
+    pub fn total(&self) -> u64 {
+        self.confirmed + self.unconfirmed + self.immature + self.locked
+    }
+
+    /// What can be spent right now
+    pub fn spendable(&self) -> u64 {
+        self.confirmed
+    }
+}
+
+/// Summary of the UTXO set, for comparing state across nodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoSetInfo {
+    pub height: u32,
+    pub utxo_count: usize,
+    /// Value of every unspent output, in satoshis
+    pub total_amount: u64,
+    /// Bytes of every output's serialized form
+    pub serialized_size: u64,
+    /// Order-independent hash of the set's contents, hex
+    pub set_hash: String,
+}
+
+/// 64-bit limbs of a [`Num3072`]
+const NUM3072_LIMBS: usize = 48;
+
+/// 2^3072 minus the prime the set digest works modulo
+const MUHASH_PRIME_DIFF: u64 = 1_103_717;
+
+/// Number modulo the prime 2^3072 - 1103717, as little-endian limbs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Num3072([u64; NUM3072_LIMBS]);
+
+impl Num3072 {
+    fn one() -> Self {
+        let mut limbs = [0u64; NUM3072_LIMBS];
+        limbs[0] = 1;
+        Self(limbs)
+    }
+
+    /// Number for a 32-byte hash, stretched to 3072 bits with SHA-256 in
+    /// counter mode
+    fn from_hash(hash: &[u8]) -> Self {
+        let mut limbs = [0u64; NUM3072_LIMBS];
+        for (counter, block) in limbs.chunks_exact_mut(4).enumerate() {
+            let stretched = Sha256::new().chain_update(hash).chain_update([counter as u8]).finalize();
+            for (limb, bytes) in block.iter_mut().zip(stretched.chunks_exact(8)) {
+                *limb = u64::from_le_bytes(bytes.try_into().expect("8-byte chunk"));
+            }
+        }
+        let mut num = Self(limbs);
+        if num.is_overflow() {
+            num.full_reduce();
+        }
+        num
+    }
+
+    /// Whether the value is at least the prime
+    fn is_overflow(&self) -> bool {
+        self.0[0] >= MUHASH_PRIME_DIFF.wrapping_neg() && self.0[1..].iter().all(|&limb| limb == u64::MAX)
+    }
+
+    /// Subtract the prime from a value in [prime, 2^3072), which is adding
+    /// the difference modulo 2^3072
+    fn full_reduce(&mut self) {
+        let mut carry = MUHASH_PRIME_DIFF as u128;
+        for limb in self.0.iter_mut() {
+            let sum = *limb as u128 + carry;
+            *limb = sum as u64;
+            carry = sum >> 64;
+        }
+    }
+
+    fn multiply(&mut self, other: &Self) {
+        let mut product = [0u64; 2 * NUM3072_LIMBS];
+        for i in 0..NUM3072_LIMBS {
+            let mut carry = 0u128;
+            for j in 0..NUM3072_LIMBS {
+                let t = self.0[i] as u128 * other.0[j] as u128 + product[i + j] as u128 + carry;
+                product[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            product[i + NUM3072_LIMBS] = carry as u64;
+        }
+
+        // 2^3072 is the prime difference modulo the prime, so fold the high
+        // half, and then any carry out of the top, back into the low half
+        let mut result = [0u64; NUM3072_LIMBS];
+        let mut carry = 0u128;
+        for i in 0..NUM3072_LIMBS {
+            let t = product[i] as u128 + product[i + NUM3072_LIMBS] as u128 * MUHASH_PRIME_DIFF as u128 + carry;
+            result[i] = t as u64;
+            carry = t >> 64;
+        }
+        while carry > 0 {
+            let mut add = carry * MUHASH_PRIME_DIFF as u128;
+            for limb in result.iter_mut() {
+                let t = *limb as u128 + add;
+                *limb = t as u64;
+                add = t >> 64;
+            }
+            carry = add;
+        }
+
+        self.0 = result;
+        if self.is_overflow() {
+            self.full_reduce();
+        }
+    }
+
+    /// Multiplicative inverse, by raising to the prime minus two
+    fn inverse(&self) -> Self {
+        let mut exponent = [u64::MAX; NUM3072_LIMBS];
+        exponent[0] = (MUHASH_PRIME_DIFF + 2).wrapping_neg();
+        let mut result = Self::one();
+        for limb in exponent.iter().rev() {
+            for bit in (0..64).rev() {
+                let square = result;
+                result.multiply(&square);
+                if (limb >> bit) & 1 == 1 {
+                    result.multiply(self);
+                }
+            }
+        }
+        result
+    }
+
+    fn to_le_bytes(self) -> Vec<u8> {
+        self.0.iter().flat_map(|limb| limb.to_le_bytes()).collect()
+    }
+}
+
+/// Running digest of the set: the product of per-output numbers, with
+/// spent outputs multiplied into a separate denominator so every update is
+/// one multiplication and only reading the hash divides, and the
+/// serialized size it covers
+#[derive(Debug, Clone)]
+struct SetDigest {
+    numerator: Num3072,
+    denominator: Num3072,
+    serialized_size: u64,
+}
+
+impl Default for SetDigest {
+    fn default() -> Self {
+        Self {
+            numerator: Num3072::one(),
+            denominator: Num3072::one(),
+            serialized_size: 0,
+        }
+    }
+}
+
+impl SetDigest {
+    fn add(&mut self, utxo: &UTXO) {
+        let (num, size) = Self::hash(utxo);
+        self.numerator.multiply(&num);
+        self.serialized_size += size;
+    }
+
+    fn remove(&mut self, utxo: &UTXO) {
+        let (num, size) = Self::hash(utxo);
+        self.denominator.multiply(&num);
+        self.serialized_size = self.serialized_size.saturating_sub(size);
+    }
+
+    fn hash(utxo: &UTXO) -> (Num3072, u64) {
+        let bytes = utxo.digest_bytes();
+        (Num3072::from_hash(&Sha256::digest(&bytes)), bytes.len() as u64)
+    }
+
+    /// SHA-256 of the set's product
+    fn to_hex(&self) -> String {
+        let mut product = self.numerator;
+        product.multiply(&self.denominator.inverse());
+        hex::encode(Sha256::digest(product.to_le_bytes()))
+    }
 }
 
 /// Manages the set of all unspent transaction outputs
@@ -89,6 +311,60 @@ pub struct UTXOSet {
     total_supply: u64,
     /// Current block height
     current_height: u32,
+    /// Every outpoint this set has spent
+    spent: SpentOutpoints,
+    /// Running hash and size of the unspent outputs
+    digest: SetDigest,
+}
+
+/// Spent outpoints the filter starts sized for
+const SPENT_FILTER_CAPACITY: usize = 100_000;
+
+/// Target false-positive rate of the spent-outpoint filter
+const SPENT_FILTER_FP_RATE: f64 = 0.01;
+
+/// What the set can say about an outpoint without a full history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpentStatus {
+    /// Not spent from this set: a filter miss, or an outpoint still unspent
+    NotSpent,
+    /// A filter hit for an outpoint the set does not hold: it was spent, or
+    /// never confirmed here at all (such as an output of a mempool parent)
+    Unknown,
+}
+
+/// Probabilistic record of spent outpoints, with lookup counters
+#[derive(Debug)]
+struct SpentOutpoints {
+    filter: ScalableBloomFilter,
+    queries: AtomicU64,
+    positives: AtomicU64,
+    false_positives: AtomicU64,
+    unresolved: AtomicU64,
+}
+
+impl SpentOutpoints {
+    fn new() -> Self {
+        Self {
+            filter: ScalableBloomFilter::new(SPENT_FILTER_CAPACITY, SPENT_FILTER_FP_RATE),
+            queries: AtomicU64::new(0),
+            positives: AtomicU64::new(0),
+            false_positives: AtomicU64::new(0),
+            unresolved: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Clone for SpentOutpoints {
+    fn clone(&self) -> Self {
+        Self {
+            filter: self.filter.clone(),
+            queries: AtomicU64::new(self.queries.load(Ordering::Relaxed)),
+            positives: AtomicU64::new(self.positives.load(Ordering::Relaxed)),
+            false_positives: AtomicU64::new(self.false_positives.load(Ordering::Relaxed)),
+            unresolved: AtomicU64::new(self.unresolved.load(Ordering::Relaxed)),
+        }
+    }
 }
 
 impl UTXOSet {
@@ -99,6 +375,8 @@ impl UTXOSet {
             address_index: HashMap::new(),
             total_supply: 0,
             current_height: 0,
+            spent: SpentOutpoints::new(),
+            digest: SetDigest::default(),
         }
     }
 
@@ -122,6 +400,8 @@ impl UTXOSet {
                         }
                     }
                     self.total_supply -= utxo.value();
+                    self.digest.remove(&utxo);
+                    self.spent.filter.insert(outpoint.as_bytes());
                 } else {
                     return Err(BlockchainError::InvalidTransaction(
                         format!("Attempted to spend non-existent UTXO: {}", outpoint)
@@ -154,7 +434,10 @@ impl UTXOSet {
             }
 
             self.total_supply += utxo.value();
-            self.utxos.insert(outpoint, utxo);
+            self.digest.add(&utxo);
+            if let Some(replaced) = self.utxos.insert(outpoint, utxo) {
+                self.digest.remove(&replaced);
+            }
         }
 
         self.current_height = block_height;
@@ -187,6 +470,51 @@ impl UTXOSet {
             .sum()
     }
 
+    /// Balance breakdown for an address, given the pending transactions
+    /// (mempool) and the outpoints the wallet has locked.
+    ///
+    /// Confirmed outputs spent by a pending transaction are no longer
+    /// counted; the pending transaction's change shows up as unconfirmed.
+    pub fn get_balance_breakdown<'a>(
+        &self,
+        address: &str,
+        pending: impl IntoIterator<Item = &'a Transaction>,
+        locked: &HashSet<String>,
+    ) -> Balance {
+        let pending: Vec<&Transaction> = pending.into_iter().collect();
+        let spent_by_pending: HashSet<String> = pending.iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .map(|input| format!("{}:{}", hex::encode(input.prev_tx_hash), input.prev_output_index))
+            .collect();
+
+        let mut balance = Balance::default();
+        for outpoint in self.address_index.get(address).into_iter().flatten() {
+            let Some(utxo) = self.utxos.get(outpoint) else { continue };
+            if !utxo.is_mature(self.current_height) {
+                balance.immature += utxo.value();
+            } else if spent_by_pending.contains(outpoint) {
+                continue;
+            } else if locked.contains(outpoint) {
+                balance.locked += utxo.value();
+            } else {
+                balance.confirmed += utxo.value();
+            }
+        }
+
+        for tx in pending {
+            let Ok(tx_hash) = tx.get_hash() else { continue };
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let outpoint = format!("{}:{}", hex::encode(tx_hash), index);
+                // Chained pending spends don't count twice
+                if output.get_address().as_deref() == Some(address) && !spent_by_pending.contains(&outpoint) {
+                    balance.unconfirmed += output.value;
+                }
+            }
+        }
+
+        balance
+    }
+
     /// Get current blockchain height
     pub fn get_current_height(&self) -> u64 {
         self.current_height as u64
@@ -206,6 +534,47 @@ impl UTXOSet {
         }
     }
 
+    /// Whether `outpoint` may have been spent from this set. Outpoints that
+    /// were never spent are mostly answered by the spent-outpoint filter
+    /// alone; only filter hits are checked against the set. A hit the set
+    /// does not hold is `Unknown`, since the filter cannot tell a spent
+    /// outpoint from one that was never confirmed.
+    pub fn spent_status(&self, outpoint: &str) -> SpentStatus {
+        self.spent.queries.fetch_add(1, Ordering::Relaxed);
+        if !self.spent.filter.contains(outpoint.as_bytes()) {
+            return SpentStatus::NotSpent;
+        }
+        self.spent.positives.fetch_add(1, Ordering::Relaxed);
+        if self.utxos.contains_key(outpoint) {
+            self.spent.false_positives.fetch_add(1, Ordering::Relaxed);
+            return SpentStatus::NotSpent;
+        }
+        self.spent.unresolved.fetch_add(1, Ordering::Relaxed);
+        SpentStatus::Unknown
+    }
+
+    /// Lookup counters and size of the spent-outpoint filter
+    pub fn spent_filter_stats(&self) -> FilterStats {
+        let queries = self.spent.queries.load(Ordering::Relaxed);
+        let positives = self.spent.positives.load(Ordering::Relaxed);
+        let false_positives = self.spent.false_positives.load(Ordering::Relaxed);
+        let unresolved = self.spent.unresolved.load(Ordering::Relaxed);
+        let negatives = queries - positives;
+        let absent = negatives + false_positives;
+        FilterStats {
+            queries,
+            negatives,
+            positives,
+            false_positives,
+            unresolved,
+            entries: self.spent.filter.len(),
+            stages: self.spent.filter.stages().len(),
+            size_bytes: self.spent.filter.size_bytes(),
+            estimated_false_positive_rate: self.spent.filter.estimated_false_positive_rate(),
+            observed_false_positive_rate: if absent > 0 { false_positives as f64 / absent as f64 } else { 0.0 },
+        }
+    }
+
     /// Select UTXOs for spending (coin selection)
     pub fn select_utxos_for_amount(&self, address: &str, amount: u64) -> Result<Vec<UTXO>> {
         let available_utxos = self.get_utxos_for_address(address);
@@ -292,6 +661,23 @@ impl UTXOSet {
         self.utxos.len()
     }
 
+    /// Count, value, size and hash of the set, kept up to date as outputs
+    /// are added and spent
+    pub fn set_info(&self) -> UtxoSetInfo {
+        UtxoSetInfo {
+            height: self.current_height,
+            utxo_count: self.utxos.len(),
+            total_amount: self.total_supply,
+            serialized_size: self.digest.serialized_size,
+            set_hash: self.digest.to_hex(),
+        }
+    }
+
+    /// Iterate over every unspent output
+    pub fn iter(&self) -> impl Iterator<Item = &UTXO> {
+        self.utxos.values()
+    }
+
     /// Get all addresses in the index (for debugging)
     pub fn get_all_addresses(&self) -> Vec<String> {
         self.address_index.keys().cloned().collect()
@@ -310,7 +696,10 @@ impl UTXOSet {
         }
 
         self.total_supply += utxo.value();
-        self.utxos.insert(outpoint, utxo);
+        self.digest.add(&utxo);
+        if let Some(replaced) = self.utxos.insert(outpoint, utxo) {
+            self.digest.remove(&replaced);
+        }
         Ok(())
     }
 
@@ -329,6 +718,8 @@ impl UTXOSet {
                 }
             }
             self.total_supply -= utxo.value();
+            self.digest.remove(&utxo);
+            self.spent.filter.insert(outpoint.as_bytes());
             Ok(())
         } else {
             Err(BlockchainError::InvalidTransaction(
@@ -369,15 +760,19 @@ impl UTXOSet {
             address_index: self.address_index.clone(),
             total_supply: self.total_supply,
             current_height: self.current_height,
+            digest: self.digest.clone(),
         }
     }
 
-    /// Restore from snapshot (for chain reorganization)
+    /// Restore from snapshot (for chain reorganization). The spent-outpoint
+    /// filter is kept: outputs restored as unspent only show up as filter
+    /// false positives.
     pub fn restore_snapshot(&mut self, snapshot: UTXOSetSnapshot) {
         self.utxos = snapshot.utxos;
         self.address_index = snapshot.address_index;
         self.total_supply = snapshot.total_supply;
         self.current_height = snapshot.current_height;
+        self.digest = snapshot.digest;
     }
 }
 
@@ -388,6 +783,7 @@ pub struct UTXOSetSnapshot {
     address_index: HashMap<String, Vec<String>>,
     total_supply: u64,
     current_height: u32,
+    digest: SetDigest,
 }
 
 impl Default for UTXOSet {
@@ -422,6 +818,48 @@ mod tests {
         assert!(utxo.is_mature(200));  // 100+ blocks
     }
 
+    #[test]
+    fn test_balance_breakdown() {
+        let address = "edu1qBalanceTest";
+        let mut utxo_set = UTXOSet::new();
+
+        // Two regular outputs and a fresh coinbase to the same address
+        let funding = Transaction::new(1, Vec::new(), vec![
+            TransactionOutput::create_p2pkh(1_000, address).unwrap(),
+            TransactionOutput::create_p2pkh(2_000, address).unwrap(),
+        ]);
+        utxo_set.add_transaction(&funding, 0).unwrap();
+        let funding_hash = funding.get_hash().unwrap();
+        let coinbase = Transaction::new(
+            1,
+            vec![TransactionInput::new([0u8; 32], 0xffffffff, vec![1])],
+            vec![TransactionOutput::create_p2pkh(5_000, address).unwrap()],
+        );
+        utxo_set.add_utxo(coinbase.get_hash().unwrap(), 0, UTXO::new(
+            coinbase.get_hash().unwrap(), 0, coinbase.outputs[0].clone(), 5, true,
+        )).unwrap();
+        utxo_set.set_current_height(6);
+
+        let balance = utxo_set.get_balance_breakdown(address, [], &HashSet::new());
+        assert_eq!(balance, Balance { confirmed: 3_000, unconfirmed: 0, immature: 5_000, locked: 0 });
+
+        // Spend the 2,000 output with 1,500 change pending, and lock the 1,000 output
+        let spend = Transaction::new(
+            1,
+            vec![TransactionInput::new(funding_hash, 1, vec![])],
+            vec![
+                TransactionOutput::create_p2pkh(400, "edu1qSomeoneElse").unwrap(),
+                TransactionOutput::create_p2pkh(1_500, address).unwrap(),
+            ],
+        );
+        let locked: HashSet<String> = [format!("{}:0", hex::encode(funding_hash))].into_iter().collect();
+
+        let balance = utxo_set.get_balance_breakdown(address, [&spend], &locked);
+        assert_eq!(balance, Balance { confirmed: 0, unconfirmed: 1_500, immature: 5_000, locked: 1_000 });
+        assert_eq!(balance.total(), 7_500);
+        assert_eq!(balance.spendable(), 0);
+    }
+
     #[test]
     fn test_utxo_set_operations() {
         let mut utxo_set = UTXOSet::new();
@@ -436,4 +874,66 @@ mod tests {
         assert_eq!(utxo_set.get_total_supply(), 1000);
         assert_eq!(utxo_set.get_utxo_count(), 1);
     }
+
+    #[test]
+    fn test_set_info_is_incremental_and_order_independent() {
+        let utxo = |i: u8| UTXO::new([i; 32], i as u32, TransactionOutput::new(1_000 * i as u64, vec![0x76, i]), 1, false);
+        let mut forward = UTXOSet::new();
+        let mut backward = UTXOSet::new();
+        for i in 1..=20u8 {
+            forward.add_utxo([i; 32], i as u32, utxo(i)).unwrap();
+            backward.add_utxo([21 - i; 32], (21 - i) as u32, utxo(21 - i)).unwrap();
+        }
+        assert_eq!(forward.set_info(), backward.set_info());
+        assert_eq!(forward.set_info().utxo_count, 20);
+        assert_eq!(forward.set_info().total_amount, 210_000);
+
+        // Spending an output changes the hash; putting it back restores it
+        let full = forward.set_info();
+        let snapshot = forward.create_snapshot();
+        forward.remove_utxo(&[7; 32], 7).unwrap();
+        assert_ne!(forward.set_info().set_hash, full.set_hash);
+        assert!(forward.set_info().serialized_size < full.serialized_size);
+        forward.add_utxo([7; 32], 7, utxo(7)).unwrap();
+        assert_eq!(forward.set_info(), full);
+
+        forward.remove_utxo(&[3; 32], 3).unwrap();
+        forward.restore_snapshot(snapshot);
+        assert_eq!(forward.set_info(), full);
+
+        // Emptied, a set hashes like one that never held anything
+        for i in 1..=20u8 {
+            backward.remove_utxo(&[i; 32], i as u32).unwrap();
+        }
+        assert_eq!(backward.set_info().set_hash, UTXOSet::new().set_info().set_hash);
+        assert_eq!(backward.set_info().serialized_size, 0);
+    }
+
+    #[test]
+    fn test_spent_outpoint_filter() {
+        let mut utxo_set = UTXOSet::new();
+        for i in 0..100u8 {
+            let utxo = UTXO::new([i; 32], 0, TransactionOutput::new(1000, vec![0x76, 0xa9]), 1, false);
+            utxo_set.add_utxo([i; 32], 0, utxo).unwrap();
+        }
+        for i in 0..50u8 {
+            utxo_set.remove_utxo(&[i; 32], 0).unwrap();
+        }
+        
+        let outpoint = |i: u8| format!("{}:0", hex::encode([i; 32]));
+        assert!((0..50).all(|i| utxo_set.spent_status(&outpoint(i)) == SpentStatus::Unknown));
+        assert!((50..100).all(|i| utxo_set.spent_status(&outpoint(i)) == SpentStatus::NotSpent));
+        
+        let stats = utxo_set.spent_filter_stats();
+        assert_eq!(stats.queries, 100);
+        assert_eq!(stats.entries, 50);
+        assert_eq!(stats.unresolved, 50);
+        assert_eq!(stats.positives, 50 + stats.false_positives);
+        assert_eq!(stats.negatives, 50 - stats.false_positives);
+        
+        // Hits on outpoints that were never in the set stay unresolved
+        let unknown = (100..=255u8).filter(|&i| utxo_set.spent_status(&outpoint(i)) == SpentStatus::Unknown).count();
+        assert_eq!(utxo_set.spent_filter_stats().unresolved, 50 + unknown as u64);
+        assert_eq!(utxo_set.spent_filter_stats().false_positives, stats.false_positives);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,245 @@
+//! Soft-fork deployment signaling (version bits)
+//!
+//! Miners signal readiness for a deployment by setting its bit in the block
+//! version, alongside the `VERSIONBITS_TOP_BITS` marker. Signals are counted
+//! over retarget windows; a deployment locks in once a window reaches the
+//! threshold and becomes active at the start of the following window, but
+//! never before its minimum activation height. Consensus code gates the new
+//! rules on `VersionBitsTracker::is_active_at`.
+
+use crate::{BlockHeight, BlockchainError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Top bits every signaling block version carries
+pub const VERSIONBITS_TOP_BITS: u32 = 0x2000_0000;
+
+/// Mask selecting the top bits of a block version
+pub const VERSIONBITS_TOP_MASK: u32 = 0xE000_0000;
+
+/// Highest usable deployment bit
+pub const MAX_DEPLOYMENT_BIT: u8 = 28;
+
+/// A soft fork rolled out through version bits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub name: String,
+    /// Block version bit miners set to signal (0-28)
+    pub bit: u8,
+    /// Height from which signals start counting
+    pub start_height: BlockHeight,
+    /// Height at which a deployment that has not locked in fails
+    pub timeout_height: BlockHeight,
+    /// Earliest height the rules can take effect, even if locked in sooner
+    pub min_activation_height: BlockHeight,
+}
+
+impl Deployment {
+    pub fn new(name: &str, bit: u8, start_height: BlockHeight, timeout_height: BlockHeight) -> Result<Self> {
+        if bit > MAX_DEPLOYMENT_BIT {
+            return Err(BlockchainError::InvalidInput(format!("Deployment bit {} above {}", bit, MAX_DEPLOYMENT_BIT)));
+        }
+        if timeout_height <= start_height {
+            return Err(BlockchainError::InvalidInput(format!("Deployment {} times out before it starts", name)));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            bit,
+            start_height,
+            timeout_height,
+            min_activation_height: 0,
+        })
+    }
+
+    /// Hold activation back until `height`
+    pub fn with_min_activation_height(mut self, height: BlockHeight) -> Self {
+        self.min_activation_height = height;
+        self
+    }
+
+    /// Whether a block version signals for this deployment
+    pub fn is_signaled_by(&self, version: u32) -> bool {
+        version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS && version & (1 << self.bit) != 0
+    }
+}
+
+/// Lifecycle of a deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    /// Waiting for the start height
+    Defined,
+    /// Counting signals
+    Started,
+    /// Threshold reached, activating at the next window
+    LockedIn,
+    /// Rules enforced
+    Active,
+    /// Timed out without locking in
+    Failed,
+}
+
+/// Tracked state of one deployment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentStatus {
+    pub deployment: Deployment,
+    pub state: DeploymentState,
+    /// First height the current state applies to
+    pub since_height: BlockHeight,
+    /// Signaling blocks so far in the current window
+    pub window_signals: u64,
+}
+
+/// Follows deployment states as blocks connect
+#[derive(Debug, Clone)]
+pub struct VersionBitsTracker {
+    window: u64,
+    threshold: u64,
+    deployments: Vec<DeploymentStatus>,
+    /// Height of the last block counted
+    tip_height: Option<BlockHeight>,
+}
+
+impl VersionBitsTracker {
+    /// Track `deployments`, requiring `threshold` signaling blocks out of each `window`
+    pub fn new(deployments: Vec<Deployment>, window: u64, threshold: u64) -> Self {
+        Self {
+            window: window.max(1),
+            threshold: threshold.min(window.max(1)),
+            deployments: deployments.into_iter()
+                .map(|deployment| DeploymentStatus {
+                    deployment,
+                    state: DeploymentState::Defined,
+                    since_height: 0,
+                    window_signals: 0,
+                })
+                .collect(),
+            tip_height: None,
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    pub fn deployments(&self) -> &[DeploymentStatus] {
+        &self.deployments
+    }
+
+    pub fn status(&self, name: &str) -> Option<&DeploymentStatus> {
+        self.deployments.iter().find(|status| status.deployment.name == name)
+    }
+
+    /// Whether a deployment's rules apply to a block at `height`
+    pub fn is_active_at(&self, name: &str, height: BlockHeight) -> bool {
+        self.status(name).map_or(false, |status| {
+            status.state == DeploymentState::Active && height >= status.since_height
+        })
+    }
+
+    /// Version a miner should put in the block at the next height: the top
+    /// bits plus the bit of every deployment still looking for signals
+    pub fn block_version(&self) -> u32 {
+        self.deployments.iter()
+            .filter(|status| matches!(status.state, DeploymentState::Started | DeploymentState::LockedIn))
+            .fold(VERSIONBITS_TOP_BITS, |version, status| version | (1 << status.deployment.bit))
+    }
+
+    /// Count a newly connected block and move deployments on at window ends
+    pub fn connect_block(&mut self, height: BlockHeight, version: u32) {
+        if self.tip_height.map_or(false, |tip| height <= tip) {
+            return;
+        }
+        self.tip_height = Some(height);
+
+        for status in &mut self.deployments {
+            if status.state == DeploymentState::Started && status.deployment.is_signaled_by(version) {
+                status.window_signals += 1;
+            }
+        }
+
+        let next_height = height + 1;
+        if next_height % self.window != 0 {
+            return;
+        }
+
+        for status in &mut self.deployments {
+            let deployment = &status.deployment;
+            let next_state = match status.state {
+                DeploymentState::Defined if next_height >= deployment.timeout_height => DeploymentState::Failed,
+                DeploymentState::Defined if next_height >= deployment.start_height => DeploymentState::Started,
+                DeploymentState::Started if status.window_signals >= self.threshold => DeploymentState::LockedIn,
+                DeploymentState::Started if next_height >= deployment.timeout_height => DeploymentState::Failed,
+                DeploymentState::LockedIn if next_height >= deployment.min_activation_height => DeploymentState::Active,
+                state => state,
+            };
+            if next_state != status.state {
+                status.state = next_state;
+                status.since_height = next_height;
+            }
+            status.window_signals = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mine(tracker: &mut VersionBitsTracker, heights: std::ops::Range<u64>, version: u32) {
+        for height in heights {
+            tracker.connect_block(height, version);
+        }
+    }
+
+    #[test]
+    fn test_deployment_activation() {
+        let deployment = Deployment::new("htlc", 1, 10, 100).unwrap().with_min_activation_height(60);
+        let mut tracker = VersionBitsTracker::new(vec![deployment], 10, 8);
+        let signal = VERSIONBITS_TOP_BITS | (1 << 1);
+
+        mine(&mut tracker, 0..10, 1);
+        assert_eq!(tracker.status("htlc").unwrap().state, DeploymentState::Started);
+        assert_eq!(tracker.block_version(), signal);
+
+        // 7 of 10 is short of the threshold
+        mine(&mut tracker, 10..17, signal);
+        mine(&mut tracker, 17..20, VERSIONBITS_TOP_BITS);
+        assert_eq!(tracker.status("htlc").unwrap().state, DeploymentState::Started);
+
+        // Old-style versions don't count even with the bit set
+        mine(&mut tracker, 20..30, (signal & !VERSIONBITS_TOP_MASK) | 0x4000_0000);
+        assert_eq!(tracker.status("htlc").unwrap().state, DeploymentState::Started);
+
+        mine(&mut tracker, 30..40, signal);
+        assert_eq!(tracker.status("htlc").unwrap().state, DeploymentState::LockedIn);
+        assert!(!tracker.is_active_at("htlc", 40));
+
+        // Locked in, but held back by the minimum activation height
+        mine(&mut tracker, 40..50, 1);
+        assert_eq!(tracker.status("htlc").unwrap().state, DeploymentState::LockedIn);
+
+        mine(&mut tracker, 50..60, 1);
+        let status = tracker.status("htlc").unwrap();
+        assert_eq!(status.state, DeploymentState::Active);
+        assert_eq!(status.since_height, 60);
+        assert!(tracker.is_active_at("htlc", 60));
+        assert!(!tracker.is_active_at("htlc", 59));
+        assert_eq!(tracker.block_version(), VERSIONBITS_TOP_BITS);
+    }
+
+    #[test]
+    fn test_deployment_timeout() {
+        let deployment = Deployment::new("unpopular", 2, 0, 20).unwrap();
+        let mut tracker = VersionBitsTracker::new(vec![deployment], 10, 8);
+
+        mine(&mut tracker, 0..30, VERSIONBITS_TOP_BITS);
+        let status = tracker.status("unpopular").unwrap();
+        assert_eq!(status.state, DeploymentState::Failed);
+        assert_eq!(status.since_height, 20);
+        assert!(Deployment::new("bad", 29, 0, 10).is_err());
+    }
+}
@@ -4,11 +4,13 @@
 //! and key management for the EDU cryptocurrency.
 
 use crate::{BlockchainError, Result};
-use crate::utxo::UTXOSet;
+use crate::address::{AddressCodec, AddressKind};
+use crate::amount::Amount;
+use crate::utxo::{Balance, UTXOSet};
 use crate::tx_builder::{TransactionBuilder, TransactionManager};
 use crate::transaction::Transaction;
 use serde::{Deserialize, Serialize, Serializer, Deserializer};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::string::FromUtf8Error;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -49,6 +51,21 @@ fn deserialize_public_key<'de, D: Deserializer<'de>>(deserializer: D) -> std::re
     Ok(bytes)  // Return Vec<u8> directly
 }
 
+// Older wallet files stored the balance as a single number of satoshis
+fn deserialize_balance<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Balance, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredBalance {
+        Legacy(u64),
+        Split(Balance),
+    }
+
+    Ok(match StoredBalance::deserialize(deserializer)? {
+        StoredBalance::Legacy(confirmed) => Balance { confirmed, ..Balance::default() },
+        StoredBalance::Split(balance) => balance,
+    })
+}
+
 /// Wallet containing keys and metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Wallet {
@@ -60,7 +77,9 @@ pub struct Wallet {
     pub public_key: PublicKey,
     pub address: Address,
     pub created_at: DateTime<Utc>,
-    pub balance: u64, // in satoshis (1 EDU = 100,000,000 satoshis)
+    /// Last known balance in satoshis (1 EDU = 100,000,000 satoshis)
+    #[serde(deserialize_with = "deserialize_balance")]
+    pub balance: Balance,
 }
 
 /// Payment request for QR codes
@@ -73,6 +92,9 @@ pub struct PaymentRequest {
     pub expires_at: Option<DateTime<Utc>>,
 }
 
+/// Key/value tags on a wallet transaction, such as the order or loan it pays for
+pub type TransactionTags = BTreeMap<String, String>;
+
 /// Transaction for sending between wallets
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletTransaction {
@@ -86,6 +108,8 @@ pub struct WalletTransaction {
     pub blockchain_tx_id: Option<String>,
     pub confirmations: u32,
     pub status: TransactionStatus,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: TransactionTags,
 }
 
 /// Status of a wallet transaction
@@ -95,6 +119,7 @@ pub enum TransactionStatus {
     Broadcasting, // Being sent to network
     Confirmed,    // Included in blockchain
     Failed,       // Transaction failed
+    Abandoned,    // Dropped before relay, inputs released
 }
 
 /// Wallet manager for handling multiple wallets with blockchain integration
@@ -104,6 +129,8 @@ pub struct WalletManager {
     address_to_id: HashMap<String, Uuid>,
     transactions: Vec<WalletTransaction>,
     transaction_manager: Option<Arc<RwLock<TransactionManager>>>,
+    /// Outpoints held back from spending (escrow, frozen funds)
+    locked_outpoints: HashSet<String>,
 }
 
 impl Wallet {
@@ -120,7 +147,7 @@ impl Wallet {
             public_key,
             address,
             created_at: Utc::now(),
-            balance: 0,
+            balance: Balance::default(),
         })
     }
     
@@ -146,7 +173,7 @@ impl Wallet {
             public_key,
             address,
             created_at: Utc::now(),
-            balance: 0,
+            balance: Balance::default(),
         })
     }
     
@@ -182,36 +209,37 @@ impl Wallet {
         let hash = hasher.finalize();
         
         // Take first 20 bytes and encode as base58
-        let address_bytes = &hash[0..20];
-        let encoded = bs58::encode(address_bytes).into_string();
+        let mut address_bytes = [0u8; 20];
+        address_bytes.copy_from_slice(&hash[0..20]);
         
-        Ok(format!("edu1q{}", encoded))
+        Ok(AddressCodec::encode(AddressKind::P2pkh, &address_bytes))
     }
     
-    /// Convert EDU amount to satoshis
-    pub fn edu_to_satoshis(edu_amount: f64) -> u64 {
-        (edu_amount * 100_000_000.0) as u64
+    /// Convert EDU amount to satoshis, refusing negative amounts and ones
+    /// finer than a satoshi
+    pub fn edu_to_satoshis(edu_amount: f64) -> Result<u64> {
+        Ok(Amount::from_edu_f64(edu_amount)?.to_sat())
     }
     
     /// Convert satoshis to EDU amount
     pub fn satoshis_to_edu(satoshis: u64) -> f64 {
-        satoshis as f64 / 100_000_000.0
+        Amount::from_sat(satoshis).to_edu_f64()
     }
     
     /// Get formatted balance string
     pub fn get_balance_string(&self) -> String {
-        format!("{:.8} EDU", Self::satoshis_to_edu(self.balance))
+        Amount::from_sat(self.balance.confirmed).to_string()
     }
     
     /// Generate a payment request for this wallet
-    pub fn create_payment_request(&self, amount: Option<f64>, message: Option<String>) -> PaymentRequest {
-        PaymentRequest {
+    pub fn create_payment_request(&self, amount: Option<f64>, message: Option<String>) -> Result<PaymentRequest> {
+        Ok(PaymentRequest {
             address: self.address.clone(),
-            amount: amount.map(Self::edu_to_satoshis),
+            amount: amount.map(Self::edu_to_satoshis).transpose()?,
             label: Some(self.name.clone()),
             message,
             expires_at: Some(Utc::now() + chrono::Duration::hours(24)),
-        }
+        })
     }
 }
 
@@ -221,7 +249,7 @@ impl PaymentRequest {
         let mut parts = vec![format!("edunet:{}", self.address)];
         
         if let Some(amount) = self.amount {
-            parts.push(format!("amount={}", Wallet::satoshis_to_edu(amount)));
+            parts.push(format!("amount={}", Amount::from_sat(amount).to_edu_string()));
         }
         
         if let Some(label) = &self.label {
@@ -263,8 +291,8 @@ impl PaymentRequest {
                 if kv.len() == 2 {
                     match kv[0] {
                         "amount" => {
-                            if let Ok(amount) = kv[1].parse::<f64>() {
-                                payment_request.amount = Some(Wallet::edu_to_satoshis(amount));
+                            if let Ok(amount) = kv[1].parse::<Amount>() {
+                                payment_request.amount = Some(amount.to_sat());
                             }
                         }
                         "label" => {
@@ -291,6 +319,7 @@ impl WalletManager {
             address_to_id: HashMap::new(),
             transactions: Vec::new(),
             transaction_manager: None,
+            locked_outpoints: HashSet::new(),
         }
     }
 
@@ -301,6 +330,7 @@ impl WalletManager {
             address_to_id: HashMap::new(),
             transactions: Vec::new(),
             transaction_manager: Some(transaction_manager),
+            locked_outpoints: HashSet::new(),
         }
     }
 
@@ -308,6 +338,21 @@ impl WalletManager {
     pub fn set_transaction_manager(&mut self, transaction_manager: Arc<RwLock<TransactionManager>>) {
         self.transaction_manager = Some(transaction_manager);
     }
+
+    /// Hold an outpoint ("txid:index") back from the spendable balance
+    pub fn lock_outpoint(&mut self, outpoint: String) {
+        self.locked_outpoints.insert(outpoint);
+    }
+
+    /// Release a previously locked outpoint; returns false if it wasn't locked
+    pub fn unlock_outpoint(&mut self, outpoint: &str) -> bool {
+        self.locked_outpoints.remove(outpoint)
+    }
+
+    /// Outpoints currently held back from spending
+    pub fn locked_outpoints(&self) -> &HashSet<String> {
+        &self.locked_outpoints
+    }
     
     /// Create and add a new wallet
     pub fn create_wallet(&mut self, name: String) -> Result<&Wallet> {
@@ -338,10 +383,10 @@ impl WalletManager {
     }
     
     /// Update wallet balance from blockchain UTXO set
-    pub async fn update_balance(&mut self, address: &str) -> Result<u64> {
+    pub async fn update_balance(&mut self, address: &str) -> Result<Balance> {
         if let Some(tx_manager) = &self.transaction_manager {
             let tx_manager = tx_manager.read().await;
-            let balance = tx_manager.get_balance(address);
+            let balance = tx_manager.get_balance_breakdown(address, &self.locked_outpoints);
             
             // Update wallet balance
             if let Some(wallet_id) = self.address_to_id.get(address) {
@@ -358,7 +403,7 @@ impl WalletManager {
                     return Ok(wallet.balance);
                 }
             }
-            Ok(0)
+            Ok(Balance::default())
         }
     }
 
@@ -447,6 +492,7 @@ impl WalletManager {
             } else {
                 TransactionStatus::Pending
             },
+            tags: TransactionTags::new(),
         };
         
         self.transactions.push(wallet_transaction.clone());
@@ -470,9 +516,9 @@ impl WalletManager {
             .ok_or_else(|| BlockchainError::WalletNotFound(from_address.to_string()))?;
             
         let fee = self.calculate_transaction_fee(amount);
-        if sender.balance < amount + fee {
+        if sender.balance.spendable() < amount + fee {
             return Err(BlockchainError::InsufficientFunds(
-                format!("Need {} satoshis, have {}", amount + fee, sender.balance)
+                format!("Need {} satoshis, have {}", amount + fee, sender.balance.spendable())
             ));
         }
         
@@ -492,6 +538,7 @@ impl WalletManager {
             blockchain_tx_id: None,
             confirmations: 0,
             status: TransactionStatus::Pending,
+            tags: TransactionTags::new(),
         };
         
         self.transactions.push(transaction.clone());
@@ -528,6 +575,31 @@ impl WalletManager {
             }
         }
     }
+
+    /// Set tags on a wallet transaction, replacing existing values for the
+    /// same keys. Returns false if there is no such transaction.
+    pub fn tag_transaction(&mut self, tx_id: &Uuid, tags: TransactionTags) -> bool {
+        match self.transactions.iter_mut().find(|tx| tx.id == *tx_id) {
+            Some(tx) => {
+                tx.tags.extend(tags);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark the wallet transaction with the given on-chain id as abandoned
+    pub fn mark_abandoned(&mut self, blockchain_tx_id: &str) -> bool {
+        match self.transactions.iter_mut()
+            .find(|tx| tx.blockchain_tx_id.as_deref() == Some(blockchain_tx_id))
+        {
+            Some(tx) => {
+                tx.status = TransactionStatus::Abandoned;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl Default for WalletManager {
@@ -545,13 +617,13 @@ mod tests {
         let wallet = Wallet::new("Test Wallet".to_string()).unwrap();
         assert_eq!(wallet.name, "Test Wallet");
         assert!(wallet.address.starts_with("edu1q"));
-        assert_eq!(wallet.balance, 0);
+        assert_eq!(wallet.balance, Balance::default());
     }
     
     #[test]
     fn test_payment_request_qr() {
         let wallet = Wallet::new("Test".to_string()).unwrap();
-        let request = wallet.create_payment_request(Some(1.5), Some("Coffee".to_string()));
+        let request = wallet.create_payment_request(Some(1.5), Some("Coffee".to_string())).unwrap();
         let qr_string = request.to_qr_string();
         
         assert!(qr_string.contains(&wallet.address));
@@ -560,7 +632,11 @@ mod tests {
         
         let parsed = PaymentRequest::from_qr_string(&qr_string).unwrap();
         assert_eq!(parsed.address, wallet.address);
-        assert_eq!(parsed.amount, Some(Wallet::edu_to_satoshis(1.5)));
+        assert_eq!(parsed.amount, Some(Wallet::edu_to_satoshis(1.5).unwrap()));
+        
+        assert!(Wallet::edu_to_satoshis(-1.0).is_err());
+        assert!(Wallet::edu_to_satoshis(0.000000001).is_err());
+        assert!(wallet.create_payment_request(Some(-1.0), None).is_err());
     }
     
     #[test]
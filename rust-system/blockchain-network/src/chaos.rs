@@ -0,0 +1,420 @@
+//! Fault injection for chaos testing (`chaos` feature)
+//!
+//! A [`FaultInjector`] sits between the swarm and its peers and drops,
+//! delays, reorders or corrupts P2P messages with configured probabilities,
+//! per peer and per direction, so sync and relay logic can be exercised under
+//! adversarial network conditions. Decisions come from a seeded RNG: a run
+//! over one link is repeatable, runs over many links are as repeatable as the
+//! order their messages interleave in.
+//!
+//! Attach one with `NetworkManager::with_fault_injector` and change its plan
+//! while the network runs with [`FaultInjector::set_peer`].
+
+use crate::{peer::PeerEvent, protocol::Message, NetworkError, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// How long a reordered message waits for the next one on its link before
+/// it goes out anyway
+pub const REORDER_HOLD: Duration = Duration::from_millis(500);
+
+/// Fault probabilities for one link; each is rolled independently per message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultConfig {
+    /// Chance a message is lost
+    pub drop: f64,
+    /// Chance a message is held back by `min_delay_ms..=max_delay_ms`
+    pub delay: f64,
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Chance a message is held until the next one on its link has gone out
+    pub reorder: f64,
+    /// Chance one bit of the encoded message is flipped
+    pub corrupt: f64,
+}
+
+impl FaultConfig {
+    /// Whether any fault can fire
+    pub fn is_clean(&self) -> bool {
+        self.drop == 0.0 && self.delay == 0.0 && self.reorder == 0.0 && self.corrupt == 0.0
+    }
+
+    /// Check probabilities are within 0..=1 and the delay range is ordered
+    pub fn validate(&self) -> Result<()> {
+        for (name, p) in [("drop", self.drop), ("delay", self.delay), ("reorder", self.reorder), ("corrupt", self.corrupt)] {
+            if !(0.0..=1.0).contains(&p) {
+                return Err(NetworkError::ProtocolError(format!("{} probability {} is not between 0 and 1", name, p)));
+            }
+        }
+        if self.min_delay_ms > self.max_delay_ms {
+            return Err(NetworkError::ProtocolError(format!(
+                "minimum delay {}ms is above maximum {}ms", self.min_delay_ms, self.max_delay_ms
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Seed, default faults and per-peer overrides, loadable from a test
+/// harness's JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultPlan {
+    pub seed: u64,
+    /// Faults on links without an override
+    pub default: FaultConfig,
+    /// Faults on links to these peer addresses
+    pub peers: HashMap<SocketAddr, FaultConfig>,
+}
+
+/// Which way a message is travelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// Faults injected so far
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaultStats {
+    pub dropped: u64,
+    pub delayed: u64,
+    pub reordered: u64,
+    pub corrupted: u64,
+    /// Corrupted inbound messages that no longer decoded and were discarded
+    pub undecodable: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    dropped: AtomicU64,
+    delayed: AtomicU64,
+    reordered: AtomicU64,
+    corrupted: AtomicU64,
+    undecodable: AtomicU64,
+}
+
+/// What happens to one message
+#[derive(Debug, Default)]
+struct Verdict {
+    drop: bool,
+    corrupt_bit: Option<usize>,
+    delay: Option<Duration>,
+    reorder: bool,
+}
+
+/// Applies a [`FaultPlan`] to messages on their way to or from peers
+#[derive(Debug)]
+pub struct FaultInjector {
+    rng: Mutex<StdRng>,
+    default: RwLock<FaultConfig>,
+    peers: RwLock<HashMap<SocketAddr, FaultConfig>>,
+    /// Message held back for reordering on each link, with its sequence
+    /// number so the hold timer releases only that message
+    held: Mutex<HashMap<(SocketAddr, Direction), (u64, Vec<u8>)>>,
+    next_seq: AtomicU64,
+    counters: Counters,
+}
+
+impl FaultInjector {
+    /// Injector following `plan`; fails if any of its configs is invalid
+    pub fn new(plan: FaultPlan) -> Result<Self> {
+        plan.default.validate()?;
+        for config in plan.peers.values() {
+            config.validate()?;
+        }
+        Ok(Self {
+            rng: Mutex::new(StdRng::seed_from_u64(plan.seed)),
+            default: RwLock::new(plan.default),
+            peers: RwLock::new(plan.peers),
+            held: Mutex::new(HashMap::new()),
+            next_seq: AtomicU64::new(0),
+            counters: Counters::default(),
+        })
+    }
+
+    /// Replace the faults on links without an override
+    pub fn set_default(&self, config: FaultConfig) -> Result<()> {
+        config.validate()?;
+        *self.default.write().unwrap() = config;
+        Ok(())
+    }
+
+    /// Override the faults on the link to `peer`
+    pub fn set_peer(&self, peer: SocketAddr, config: FaultConfig) -> Result<()> {
+        config.validate()?;
+        self.peers.write().unwrap().insert(peer, config);
+        Ok(())
+    }
+
+    /// Put the link to `peer` back on the default faults
+    pub fn clear_peer(&self, peer: SocketAddr) {
+        self.peers.write().unwrap().remove(&peer);
+    }
+
+    /// Faults in effect on the link to `peer`
+    pub fn config_for(&self, peer: SocketAddr) -> FaultConfig {
+        self.peers.read().unwrap().get(&peer).cloned()
+            .unwrap_or_else(|| self.default.read().unwrap().clone())
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        FaultStats {
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            delayed: self.counters.delayed.load(Ordering::Relaxed),
+            reordered: self.counters.reordered.load(Ordering::Relaxed),
+            corrupted: self.counters.corrupted.load(Ordering::Relaxed),
+            undecodable: self.counters.undecodable.load(Ordering::Relaxed),
+        }
+    }
+
+    fn roll(&self, config: &FaultConfig, frame_len: usize) -> Verdict {
+        let mut rng = self.rng.lock().unwrap();
+        if rng.gen_bool(config.drop) {
+            return Verdict { drop: true, ..Verdict::default() };
+        }
+        let corrupt_bit = (frame_len > 0 && rng.gen_bool(config.corrupt)).then(|| rng.gen_range(0..frame_len * 8));
+        let delay = rng.gen_bool(config.delay)
+            .then(|| Duration::from_millis(rng.gen_range(config.min_delay_ms..=config.max_delay_ms)));
+        let reorder = rng.gen_bool(config.reorder);
+        Verdict { drop: false, corrupt_bit, delay, reorder }
+    }
+
+    /// Pass an encoded message on the link to `peer` through the faults,
+    /// handing whatever survives to `deliver` in the order it should arrive.
+    /// Delayed and reordered messages are delivered from spawned tasks, so
+    /// this must run inside a Tokio runtime.
+    pub fn apply<F>(self: &Arc<Self>, peer: SocketAddr, direction: Direction, mut frame: Vec<u8>, deliver: F)
+    where
+        F: Fn(Vec<u8>) + Send + Sync + 'static,
+    {
+        let config = self.config_for(peer);
+        let verdict = if config.is_clean() { Verdict::default() } else { self.roll(&config, frame.len()) };
+        let link = (peer, direction);
+
+        if verdict.drop {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            debug!("Chaos: dropped {:?} message on link to {}", direction, peer);
+            return;
+        }
+        if let Some(bit) = verdict.corrupt_bit {
+            frame[bit / 8] ^= 1 << (bit % 8);
+            self.counters.corrupted.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Hold this one back unless another is already waiting on the link
+        if verdict.reorder {
+            let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+            let mut held = self.held.lock().unwrap();
+            if !held.contains_key(&link) {
+                held.insert(link, (seq, frame));
+                self.counters.reordered.fetch_add(1, Ordering::Relaxed);
+                let injector = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(REORDER_HOLD).await;
+                    let expired = {
+                        let mut held = injector.held.lock().unwrap();
+                        match held.get(&link) {
+                            Some((held_seq, _)) if *held_seq == seq => held.remove(&link).map(|(_, frame)| frame),
+                            _ => None,
+                        }
+                    };
+                    if let Some(frame) = expired {
+                        deliver(frame);
+                    }
+                });
+                return;
+            }
+        }
+
+        let overtaken = self.held.lock().unwrap().remove(&link).map(|(_, frame)| frame);
+        match verdict.delay {
+            Some(delay) => {
+                self.counters.delayed.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    deliver(frame);
+                    if let Some(overtaken) = overtaken {
+                        deliver(overtaken);
+                    }
+                });
+            }
+            None => {
+                deliver(frame);
+                if let Some(overtaken) = overtaken {
+                    deliver(overtaken);
+                }
+            }
+        }
+    }
+
+    /// Pass a peer's events through the inbound faults. Messages are encoded
+    /// so corruption hits the wire format; those that no longer decode are
+    /// discarded the way a reader would discard a bad frame.
+    pub fn intercept_inbound(
+        self: &Arc<Self>,
+        peer: SocketAddr,
+        mut events: mpsc::UnboundedReceiver<PeerEvent>,
+    ) -> mpsc::UnboundedReceiver<PeerEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let injector = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                let message = match event {
+                    PeerEvent::MessageReceived(message) => message,
+                    other => {
+                        let _ = sender.send(other);
+                        continue;
+                    }
+                };
+                let frame = match bincode::serialize(&message) {
+                    Ok(frame) => frame,
+                    Err(_) => {
+                        let _ = sender.send(PeerEvent::MessageReceived(message));
+                        continue;
+                    }
+                };
+                let sender = sender.clone();
+                let faults = injector.clone();
+                injector.apply(peer, Direction::Inbound, frame, move |frame| {
+                    match bincode::deserialize::<Message>(&frame) {
+                        Ok(message) => {
+                            let _ = sender.send(PeerEvent::MessageReceived(message));
+                        }
+                        Err(e) => {
+                            faults.counters.undecodable.fetch_add(1, Ordering::Relaxed);
+                            debug!("Chaos: corrupted message from {} no longer decodes: {}", peer, e);
+                        }
+                    }
+                });
+            }
+        });
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn injector(default: FaultConfig) -> Arc<FaultInjector> {
+        Arc::new(FaultInjector::new(FaultPlan { seed: 7, default, peers: HashMap::new() }).unwrap())
+    }
+
+    fn collector() -> (Arc<Mutex<Vec<Vec<u8>>>>, impl Fn(Vec<u8>) + Clone + Send + Sync + 'static) {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sink = delivered.clone();
+        (delivered, move |frame| sink.lock().unwrap().push(frame))
+    }
+
+    #[tokio::test]
+    async fn test_clean_link_delivers_in_order() {
+        let faults = injector(FaultConfig::default());
+        let (delivered, deliver) = collector();
+        for i in 0..10u8 {
+            faults.apply(addr(1), Direction::Outbound, vec![i], deliver.clone());
+        }
+        assert_eq!(*delivered.lock().unwrap(), (0..10u8).map(|i| vec![i]).collect::<Vec<_>>());
+        assert_eq!(faults.stats(), FaultStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_drop_and_per_peer_override() {
+        let faults = injector(FaultConfig::default());
+        faults.set_peer(addr(2), FaultConfig { drop: 1.0, ..FaultConfig::default() }).unwrap();
+        let (delivered, deliver) = collector();
+
+        faults.apply(addr(1), Direction::Outbound, vec![1], deliver.clone());
+        faults.apply(addr(2), Direction::Outbound, vec![2], deliver.clone());
+        assert_eq!(*delivered.lock().unwrap(), vec![vec![1]]);
+        assert_eq!(faults.stats().dropped, 1);
+
+        faults.clear_peer(addr(2));
+        faults.apply(addr(2), Direction::Outbound, vec![3], deliver);
+        assert_eq!(delivered.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_flips_one_bit() {
+        let faults = injector(FaultConfig { corrupt: 1.0, ..FaultConfig::default() });
+        let (delivered, deliver) = collector();
+        let frame = vec![0u8; 16];
+        faults.apply(addr(1), Direction::Inbound, frame.clone(), deliver);
+
+        let corrupted = delivered.lock().unwrap()[0].clone();
+        let flipped: u32 = frame.iter().zip(&corrupted).map(|(a, b)| (a ^ b).count_ones()).sum();
+        assert_eq!(flipped, 1);
+        assert_eq!(faults.stats().corrupted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reorder_delivers_held_message_after_the_next() {
+        let faults = injector(FaultConfig { reorder: 1.0, ..FaultConfig::default() });
+        let (delivered, deliver) = collector();
+
+        faults.apply(addr(1), Direction::Outbound, vec![1], deliver.clone());
+        assert!(delivered.lock().unwrap().is_empty());
+        faults.apply(addr(1), Direction::Outbound, vec![2], deliver.clone());
+        assert_eq!(*delivered.lock().unwrap(), vec![vec![2], vec![1]]);
+
+        // Nothing follows this one, so the hold timer lets it out
+        faults.apply(addr(1), Direction::Outbound, vec![3], deliver);
+        tokio::time::sleep(REORDER_HOLD + Duration::from_millis(100)).await;
+        assert_eq!(delivered.lock().unwrap().last(), Some(&vec![3]));
+        assert_eq!(delivered.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_delay_holds_message_back() {
+        let faults = injector(FaultConfig { delay: 1.0, min_delay_ms: 50, max_delay_ms: 50, ..FaultConfig::default() });
+        let (delivered, deliver) = collector();
+
+        faults.apply(addr(1), Direction::Outbound, vec![1], deliver);
+        assert!(delivered.lock().unwrap().is_empty());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(*delivered.lock().unwrap(), vec![vec![1]]);
+        assert_eq!(faults.stats().delayed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_same_faults() {
+        let config = FaultConfig { drop: 0.5, corrupt: 0.3, ..FaultConfig::default() };
+        let run = || {
+            let faults = injector(config.clone());
+            let (delivered, deliver) = collector();
+            for i in 0..100u8 {
+                faults.apply(addr(1), Direction::Outbound, vec![i; 4], deliver.clone());
+            }
+            let frames = delivered.lock().unwrap().clone();
+            frames
+        };
+        let first = run();
+        assert!(first.len() > 20 && first.len() < 80);
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        assert!(FaultConfig { drop: 1.5, ..FaultConfig::default() }.validate().is_err());
+        assert!(FaultConfig { delay: 0.1, min_delay_ms: 10, max_delay_ms: 5, ..FaultConfig::default() }.validate().is_err());
+        let plan = FaultPlan { peers: HashMap::from([(addr(1), FaultConfig { corrupt: -0.1, ..FaultConfig::default() })]), ..FaultPlan::default() };
+        assert!(FaultInjector::new(plan).is_err());
+    }
+}
@@ -2,12 +2,19 @@
 //!
 //! This module handles finding and managing peer addresses through DNS seeds,
 //! peer exchange, and address caching.
+//!
+//! Known addresses can be kept in a peers file together with what was
+//! learned about each one: when it was last seen, how many connections to
+//! it succeeded or failed, and its ping latency. A restarted node loads the
+//! file and dials the best of them straight away instead of starting over
+//! from DNS seeds.
 
 use crate::{NetworkError, Result, protocol::NetworkAddress};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
@@ -42,6 +49,18 @@ pub struct PeerAddress {
     pub source: AddressSource,
     /// Address quality score
     pub score: i32,
+    /// Last time we connected to or heard from it
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+    /// Successful connections, ever
+    #[serde(default)]
+    pub successes: u32,
+    /// Failed connections, ever
+    #[serde(default)]
+    pub failures: u32,
+    /// Smoothed ping round trip
+    #[serde(default)]
+    pub latency_ms: Option<u32>,
 }
 
 /// Source of peer address
@@ -65,6 +84,8 @@ pub struct AddressManager {
     banned_addresses: RwLock<HashSet<SocketAddr>>,
     /// DNS seed addresses for bootstrapping
     dns_seeds: Vec<String>,
+    /// Where known addresses are saved between restarts
+    peers_file: Option<PathBuf>,
     /// Statistics
     stats: RwLock<DiscoveryStats>,
 }
@@ -112,6 +133,10 @@ impl PeerAddress {
             failed_attempts: 0,
             source,
             score: 0,
+            last_seen: None,
+            successes: 0,
+            failures: 0,
+            latency_ms: None,
         }
     }
 
@@ -126,14 +151,37 @@ impl PeerAddress {
 
         if success {
             self.last_success = Some(now);
+            self.last_seen = Some(now);
             self.failed_attempts = 0;
+            self.successes = self.successes.saturating_add(1);
             self.score = (self.score + 1).min(100);
         } else {
             self.failed_attempts += 1;
+            self.failures = self.failures.saturating_add(1);
             self.score = (self.score - 2).max(-100);
         }
     }
 
+    /// Record a ping round trip, smoothed over earlier ones
+    pub fn record_latency(&mut self, latency: Duration) {
+        let sample = latency.as_millis().min(u32::MAX as u128) as u32;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(previous) => ((previous as u64 * 3 + sample as u64) / 4) as u32,
+            None => sample,
+        });
+        self.last_seen = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        );
+    }
+
+    /// Latest of when it was advertised and when we last saw it
+    fn last_heard(&self) -> u64 {
+        self.last_seen.unwrap_or(0).max(self.address.timestamp)
+    }
+
     /// Check if address is fresh (recently seen)
     pub fn is_fresh(&self) -> bool {
         let now = SystemTime::now()
@@ -141,7 +189,21 @@ impl PeerAddress {
             .unwrap_or_default()
             .as_secs();
 
-        now - self.address.timestamp < ADDRESS_FRESHNESS_THRESHOLD
+        now.saturating_sub(self.last_heard()) < ADDRESS_FRESHNESS_THRESHOLD
+    }
+
+    /// How good a connection candidate it is, higher is better: the score,
+    /// the share of connections that worked, and low latency
+    pub fn quality(&self) -> i64 {
+        let mut quality = self.score as i64 * 10;
+        let attempts = self.successes as i64 + self.failures as i64;
+        if attempts > 0 {
+            quality += self.successes as i64 * 100 / attempts;
+        }
+        if let Some(latency) = self.latency_ms {
+            quality -= (latency as i64 / 20).min(100);
+        }
+        quality
     }
 
     /// Check if address should be attempted
@@ -183,10 +245,58 @@ impl AddressManager {
             addresses: RwLock::new(HashMap::new()),
             banned_addresses: RwLock::new(HashSet::new()),
             dns_seeds,
+            peers_file: None,
             stats: RwLock::new(DiscoveryStats::default()),
         }
     }
 
+    /// Keep known addresses in `path`, see `load` and `save`
+    pub fn with_peers_file(mut self, path: Option<PathBuf>) -> Self {
+        self.peers_file = path;
+        self
+    }
+
+    /// Load addresses saved by an earlier run. Returns how many were
+    /// loaded; none without a peers file or before the first save.
+    pub async fn load(&self) -> Result<usize> {
+        let Some(path) = &self.peers_file else { return Ok(0) };
+        let saved = match read_peers(path) {
+            Ok(saved) => saved,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut addresses = self.addresses.write().await;
+        let mut loaded = 0;
+        for peer in saved {
+            if addresses.len() >= MAX_ADDRESSES {
+                break;
+            }
+            // Addresses added this run know more than the file
+            if let std::collections::hash_map::Entry::Vacant(entry) = addresses.entry(peer.socket_addr) {
+                entry.insert(peer);
+                loaded += 1;
+            }
+        }
+        info!("Loaded {} known peers from {}", loaded, path.display());
+        Ok(loaded)
+    }
+
+    /// Write known addresses to the peers file, if there is one
+    pub async fn save(&self) -> Result<usize> {
+        let Some(path) = &self.peers_file else { return Ok(0) };
+        let peers: Vec<PeerAddress> = self.addresses.read().await.values().cloned().collect();
+        write_peers(path, &peers)?;
+        debug!("Saved {} known peers to {}", peers.len(), path.display());
+        Ok(peers.len())
+    }
+
+    /// Whether some known address has been connected to before, so DNS
+    /// seeds aren't needed to find peers
+    pub async fn has_proven_peers(&self) -> bool {
+        self.addresses.read().await.values().any(|peer| peer.successes > 0 && peer.is_fresh())
+    }
+
     /// Add address from manual configuration
     pub async fn add_manual_address(&self, addr: SocketAddr, services: u64) -> Result<()> {
         let peer_addr = PeerAddress::new(addr, services, AddressSource::Manual);
@@ -250,6 +360,10 @@ impl AddressManager {
                 failed_attempts: 0,
                 source: AddressSource::PeerExchange(peer_id.to_string()),
                 score: 0,
+                last_seen: None,
+                successes: 0,
+                failures: 0,
+                latency_ms: None,
             };
 
             addr_map.insert(socket_addr, peer_addr);
@@ -349,8 +463,8 @@ impl AddressManager {
             .cloned()
             .collect();
 
-        // Sort by score (best first)
-        candidates.sort_by(|a, b| b.score.cmp(&a.score));
+        // Best first
+        candidates.sort_by_key(|addr| std::cmp::Reverse(addr.quality()));
 
         // Take requested count
         candidates.truncate(count);
@@ -379,6 +493,13 @@ impl AddressManager {
         Ok(())
     }
 
+    /// Record a ping round trip to a known address
+    pub async fn record_latency(&self, addr: SocketAddr, latency: Duration) {
+        if let Some(peer_addr) = self.addresses.write().await.get_mut(&addr) {
+            peer_addr.record_latency(latency);
+        }
+    }
+
     /// Ban address temporarily
     pub async fn ban_address(&self, addr: SocketAddr, duration: Duration) -> Result<()> {
         {
@@ -439,7 +560,7 @@ impl AddressManager {
 
         // Remove very old addresses
         let old_threshold = now - (7 * 24 * 60 * 60); // 7 days
-        addresses.retain(|_, addr| addr.address.timestamp > old_threshold);
+        addresses.retain(|_, addr| addr.last_heard() > old_threshold);
 
         // Remove addresses with very low scores
         addresses.retain(|_, addr| addr.score > -50);
@@ -452,6 +573,23 @@ impl AddressManager {
     }
 }
 
+/// Saved addresses in a peers file
+fn read_peers(path: &Path) -> std::io::Result<Vec<PeerAddress>> {
+    let data = std::fs::read(path)?;
+    serde_json::from_slice(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Replace the peers file, so a crash mid-write leaves the old one intact
+fn write_peers(path: &Path, peers: &[PeerAddress]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let data = serde_json::to_vec(peers).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(tmp, path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -509,4 +647,34 @@ mod tests {
         assert_eq!(peer_addr.failed_attempts, 0);
         assert!(peer_addr.score > 0);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_peers_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("peers-{}.json", uuid::Uuid::new_v4()));
+        let good = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 9000);
+        let flaky = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 9000);
+
+        let manager = AddressManager::new(vec![]).with_peers_file(Some(path.clone()));
+        for addr in [flaky, good] {
+            manager.add_manual_address(addr, crate::protocol::services::NODE_NETWORK).await.unwrap();
+        }
+        manager.record_connection_attempt(good, true).await.unwrap();
+        manager.record_latency(good, Duration::from_millis(40)).await;
+        manager.record_connection_attempt(flaky, false).await.unwrap();
+        assert_eq!(manager.save().await.unwrap(), 2);
+
+        let restarted = AddressManager::new(vec![]).with_peers_file(Some(path.clone()));
+        assert!(!restarted.has_proven_peers().await);
+        assert_eq!(restarted.load().await.unwrap(), 2);
+        assert!(restarted.has_proven_peers().await);
+
+        let candidates = restarted.get_connection_candidates(10).await;
+        assert_eq!(candidates[0].socket_addr, good);
+        assert_eq!(candidates[0].successes, 1);
+        assert_eq!(candidates[0].latency_ms, Some(40));
+
+        std::fs::remove_file(&path).unwrap();
+        // No file yet is not an error
+        assert_eq!(restarted.load().await.unwrap(), 0);
+    }
+}
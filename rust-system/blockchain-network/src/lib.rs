@@ -8,11 +8,17 @@ pub mod protocol;
 pub mod discovery;
 pub mod swarm;
 pub mod tx_broadcast;
+pub mod rate_limit;
+pub mod outbound;
+pub mod relay_cache;
+#[cfg(feature = "chaos")]
+pub mod chaos;
 
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::SocketAddr,
+    path::PathBuf,
     sync::Arc,
     time::Duration,
 };
@@ -24,7 +30,7 @@ use tracing::{info, warn};
 // Always import Transaction directly from blockchain_core::transaction::Transaction
 pub use blockchain_core::{Hash256, block::Block, consensus::ConsensusValidator};
 pub use uuid::Uuid;
-pub use tx_broadcast::{TransactionBroadcaster, TransactionPriority, BroadcastStats};
+pub use tx_broadcast::{TransactionBroadcaster, TransactionPriority, BroadcastStats, BroadcastConfig};
 
 /// Network error types
 #[derive(Debug, thiserror::Error)]
@@ -85,6 +91,10 @@ pub struct NetworkConfig {
     pub our_services: u64,
     /// Listening port
     pub listening_port: u16,
+    /// Where known peers are kept between restarts; None keeps them in
+    /// memory only
+    #[serde(default)]
+    pub peers_file: Option<PathBuf>,
 }
 
 impl Default for NetworkConfig {
@@ -95,7 +105,7 @@ impl Default for NetworkConfig {
             connection_timeout: Duration::from_secs(10),
             heartbeat_interval: Duration::from_secs(30),
             max_message_size: 32 * 1024 * 1024, // 32MB
-            network_magic: 0xD9B4BEF9, // Mainnet magic
+            network_magic: protocol::MAINNET_MAGIC,
             dns_seeds: vec![
                 "seed.bitcoin.sipa.be".to_string(),
                 "dnsseed.bluematt.me".to_string(), 
@@ -104,6 +114,7 @@ impl Default for NetworkConfig {
             seed_peers: vec![],
             our_services: protocol::services::NODE_NETWORK,
             listening_port: 8333,
+            peers_file: None,
         }
     }
 }
@@ -131,6 +142,10 @@ pub struct NetworkStats {
     pub failed_connections: u64,
     /// Addresses discovered
     pub addresses_discovered: u64,
+    /// Inbound connections refused by the per-IP cap
+    pub rejected_connections: u64,
+    /// Peer requests dropped by rate limiting
+    pub throttled_requests: u64,
 }
 
 /// Main network manager that coordinates P2P operations
@@ -143,21 +158,19 @@ pub struct NetworkManager {
     swarm: Arc<swarm::NetworkSwarm>,
     /// Event receiver
     event_receiver: Option<broadcast::Receiver<swarm::NetworkEvent>>,
+    /// Seed peers not yet added to the address manager
+    pending_seeds: Vec<SocketAddr>,
 }
 
 impl NetworkManager {
-    /// Create new network manager with optional blockchain consensus for serving blockchain data
+    /// Create new network manager with optional blockchain consensus for serving blockchain data.
+    /// Needs no runtime: the configured seed peers are added to the address
+    /// manager when the manager is started.
     pub fn new(config: NetworkConfig, consensus: Option<Arc<ConsensusValidator>>) -> Result<Self> {
         // Create address manager
-        let address_manager = Arc::new(discovery::AddressManager::new(config.dns_seeds.clone()));
-        
-        // Add manual seed peers
-        for addr in &config.seed_peers {
-            let rt = tokio::runtime::Handle::current();
-            rt.block_on(async {
-                let _ = address_manager.add_manual_address(*addr, config.our_services).await;
-            });
-        }
+        let address_manager = Arc::new(
+            discovery::AddressManager::new(config.dns_seeds.clone()).with_peers_file(config.peers_file.clone())
+        );
         
         // Create network swarm with consensus
         let (swarm, event_receiver) = swarm::NetworkSwarm::new(
@@ -166,8 +179,10 @@ impl NetworkManager {
             config.listening_port,
             consensus,
         );
+        let swarm = swarm.with_network_magic(config.network_magic);
         
         Ok(Self {
+            pending_seeds: config.seed_peers.clone(),
             config,
             address_manager,
             swarm: Arc::new(swarm),
@@ -175,12 +190,39 @@ impl NetworkManager {
         })
     }
     
+    /// Create new network manager with its seed peers already known to the
+    /// address manager
+    pub async fn new_async(config: NetworkConfig, consensus: Option<Arc<ConsensusValidator>>) -> Result<Self> {
+        let mut manager = Self::new(config, consensus)?;
+        manager.add_seed_peers().await;
+        Ok(manager)
+    }
+    
+    /// Add the configured seed peers not added yet
+    async fn add_seed_peers(&mut self) {
+        for addr in std::mem::take(&mut self.pending_seeds) {
+            if let Err(e) = self.address_manager.add_manual_address(addr, self.config.our_services).await {
+                warn!("Failed to add seed peer {}: {}", addr, e);
+            }
+        }
+    }
+    
     /// Start the network manager
     pub async fn start(&mut self) -> Result<()> {
         info!("Starting network manager on {}", self.config.listen_addr);
         
-        // Start DNS seed discovery
-        self.address_manager.discover_from_dns_seeds().await?;
+        // Peers known from earlier runs, then seed peers deferred by `new`
+        if let Err(e) = self.address_manager.load().await {
+            warn!("Failed to load known peers: {}", e);
+        }
+        self.add_seed_peers().await;
+        
+        // DNS seeds are only needed when no peer has worked before
+        if self.address_manager.has_proven_peers().await {
+            info!("Reconnecting to known peers, skipping DNS seed discovery");
+        } else {
+            self.address_manager.discover_from_dns_seeds().await?;
+        }
         
         // Start the network swarm
         self.swarm.start().await?;
@@ -199,6 +241,27 @@ impl NetworkManager {
         self.swarm.broadcast_message(message).await
     }
     
+    /// Relay a transaction to all connected peers
+    pub async fn broadcast_transaction(&self, transaction: &blockchain_core::transaction::Transaction) -> Result<usize> {
+        self.swarm.broadcast_transaction(transaction).await
+    }
+    
+    /// Relay a transaction to the peers whose fee filter `fee_rate` passes
+    pub async fn relay_transaction(&self, transaction: &blockchain_core::transaction::Transaction, fee_rate: u64) -> Result<usize> {
+        self.swarm.relay_transaction(transaction, fee_rate).await
+    }
+    
+    /// Tell peers not to relay us transactions below `fee_rate` (sat/byte)
+    pub async fn set_min_fee_rate(&self, fee_rate: u64) -> usize {
+        self.swarm.set_min_fee_rate(fee_rate).await
+    }
+    
+    /// Send an encrypted private message to peers that relay them; nodes
+    /// opt in by advertising `services::NODE_PRIVATE_MESSAGES`
+    pub async fn send_private_message(&self, message: blockchain_core::private_message::EncryptedMessage) -> Result<usize> {
+        self.swarm.send_private_message(message).await
+    }
+    
     /// Send message to specific peer
     pub async fn send_to_peer(&self, peer_id: Uuid, message: protocol::Message) -> Result<()> {
         self.swarm.send_to_peer(peer_id, message).await
@@ -225,9 +288,40 @@ impl NetworkManager {
             connection_attempts: swarm_stats.connection_attempts,
             failed_connections: swarm_stats.failed_connections,
             addresses_discovered: discovery_stats.addresses_discovered,
+            rejected_connections: swarm_stats.rejected_connections,
+            throttled_requests: swarm_stats.throttled_requests,
         }
     }
     
+    /// Outbound queue counters per priority class, summed over peers
+    pub async fn queue_metrics(&self) -> Vec<outbound::QueueMetrics> {
+        self.swarm.queue_metrics().await
+    }
+    
+    /// Relay cache sizes and hit rates
+    pub fn relay_cache_stats(&self) -> relay_cache::RelayCacheStats {
+        self.swarm.relay_cache_stats()
+    }
+    
+    /// Send peers' messages through `faults`, for chaos testing; set it
+    /// before starting, it only affects peers connected afterwards
+    #[cfg(feature = "chaos")]
+    pub fn with_fault_injector(self, faults: Arc<chaos::FaultInjector>) -> Self {
+        self.swarm.set_fault_injector(faults);
+        self
+    }
+    
+    /// Fault injector in use, to change its plan or read its counters
+    #[cfg(feature = "chaos")]
+    pub fn fault_injector(&self) -> Option<Arc<chaos::FaultInjector>> {
+        self.swarm.fault_injector()
+    }
+    
+    /// Network-adjusted clock from peer time samples
+    pub fn network_time(&self) -> Arc<blockchain_core::network_time::NetworkTime> {
+        self.swarm.network_time()
+    }
+    
     /// Disconnect peer
     pub async fn disconnect_peer(&self, peer_id: Uuid, reason: &str) -> Result<()> {
         self.swarm.disconnect_peer(peer_id, reason).await
@@ -251,10 +345,24 @@ mod tests {
         assert!(!config.dns_seeds.is_empty());
     }
     
+    fn config_with_seed() -> NetworkConfig {
+        NetworkConfig {
+            seed_peers: vec!["127.0.0.1:9000".parse().unwrap()],
+            ..NetworkConfig::default()
+        }
+    }
+    
+    #[test]
+    fn test_network_manager_creation() {
+        // No runtime here; the seed peer waits for start()
+        let manager = NetworkManager::new(config_with_seed(), None).unwrap();
+        assert_eq!(manager.pending_seeds.len(), 1);
+    }
+    
     #[tokio::test]
-    async fn test_network_manager_creation() {
-        let config = NetworkConfig::default();
-        let manager = NetworkManager::new(config);
-        assert!(manager.is_ok());
+    async fn test_new_async_adds_seed_peers() {
+        let manager = NetworkManager::new_async(config_with_seed(), None).await.unwrap();
+        assert!(manager.pending_seeds.is_empty());
+        assert_eq!(manager.address_manager.get_stats().await.addresses_discovered, 1);
     }
 }
\ No newline at end of file
@@ -0,0 +1,257 @@
+//! Prioritized outbound message queues
+//!
+//! Each peer's outgoing messages wait in one bounded queue per priority
+//! class, and the writer always drains the highest class first, so a new
+//! block goes out ahead of any backlog of relayed transactions. When a queue
+//! is full its class decides what gives: blocks and consensus messages are
+//! refused so the caller sees the failure, relayed transactions push out the
+//! oldest queued one, and address gossip drops the new message.
+
+use crate::protocol::{InventoryType, Message, MessagePayload, MessageType};
+use crate::{NetworkError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Outbound message classes, highest priority first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Full blocks
+    Block,
+    /// Handshake, headers, block announcements and requests
+    Consensus,
+    /// Transactions and their announcements
+    TxRelay,
+    /// Peer address gossip
+    AddrGossip,
+}
+
+/// What happens to a message sent to a full queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    /// Refuse the new message with an error
+    Reject,
+    /// Drop the oldest queued message to make room
+    DropOldest,
+    /// Silently drop the new message
+    DropNewest,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 4] = [Priority::Block, Priority::Consensus, Priority::TxRelay, Priority::AddrGossip];
+
+    /// Class a message is queued in
+    pub fn of(message: &Message) -> Self {
+        match message.message_type {
+            MessageType::Block | MessageType::BlockData => Priority::Block,
+            MessageType::Tx | MessageType::MemPool => Priority::TxRelay,
+            MessageType::Addr | MessageType::GetAddr => Priority::AddrGossip,
+            MessageType::Inv => match &message.payload {
+                MessagePayload::Inv(inv) if inv.inventory.iter().all(|item| item.item_type == InventoryType::Tx) => {
+                    Priority::TxRelay
+                }
+                _ => Priority::Consensus,
+            },
+            _ => Priority::Consensus,
+        }
+    }
+
+    /// Messages a peer's queue for this class holds
+    pub fn capacity(self) -> usize {
+        match self {
+            Priority::Block => 64,
+            Priority::Consensus => 256,
+            Priority::TxRelay => 1024,
+            Priority::AddrGossip => 64,
+        }
+    }
+
+    pub fn drop_policy(self) -> DropPolicy {
+        match self {
+            Priority::Block | Priority::Consensus => DropPolicy::Reject,
+            Priority::TxRelay => DropPolicy::DropOldest,
+            Priority::AddrGossip => DropPolicy::DropNewest,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Counters for one priority class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueMetrics {
+    pub class: Priority,
+    /// Messages waiting now
+    pub depth: usize,
+    pub capacity: usize,
+    pub max_depth: usize,
+    pub enqueued: u64,
+    pub sent: u64,
+    /// Messages refused or discarded because the queue was full
+    pub dropped: u64,
+}
+
+impl QueueMetrics {
+    fn new(class: Priority) -> Self {
+        Self { class, depth: 0, capacity: class.capacity(), max_depth: 0, enqueued: 0, sent: 0, dropped: 0 }
+    }
+
+    /// Add another peer's counters for the same class
+    pub fn merge(&mut self, other: &QueueMetrics) {
+        self.depth += other.depth;
+        self.capacity += other.capacity;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.enqueued += other.enqueued;
+        self.sent += other.sent;
+        self.dropped += other.dropped;
+    }
+}
+
+#[derive(Debug)]
+struct Queues {
+    messages: [VecDeque<Vec<u8>>; 4],
+    metrics: [QueueMetrics; 4],
+}
+
+/// A peer's outbound queues
+#[derive(Debug)]
+pub struct OutboundQueue {
+    queues: Mutex<Queues>,
+    ready: Notify,
+    closed: AtomicBool,
+}
+
+impl Default for OutboundQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutboundQueue {
+    pub fn new() -> Self {
+        Self {
+            queues: Mutex::new(Queues {
+                messages: Default::default(),
+                metrics: Priority::ALL.map(QueueMetrics::new),
+            }),
+            ready: Notify::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Queue serialized message bytes in a class, applying its drop policy
+    /// when full
+    pub fn push(&self, class: Priority, message: Vec<u8>) -> Result<()> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(NetworkError::ConnectionFailed("Peer connection closed".to_string()));
+        }
+        {
+            let mut queues = self.queues.lock().unwrap();
+            let Queues { messages, metrics } = &mut *queues;
+            let (queue, metrics) = (&mut messages[class.index()], &mut metrics[class.index()]);
+            if queue.len() >= class.capacity() {
+                metrics.dropped += 1;
+                match class.drop_policy() {
+                    DropPolicy::Reject => {
+                        return Err(NetworkError::ConnectionError(format!("{:?} queue full", class)));
+                    }
+                    DropPolicy::DropNewest => return Ok(()),
+                    DropPolicy::DropOldest => {
+                        queue.pop_front();
+                    }
+                }
+            }
+            queue.push_back(message);
+            metrics.enqueued += 1;
+            metrics.depth = queue.len();
+            metrics.max_depth = metrics.max_depth.max(queue.len());
+        }
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    /// Next message to write, highest class first; waits while all queues
+    /// are empty and returns None once closed
+    pub async fn pop(&self) -> Option<(Priority, Vec<u8>)> {
+        loop {
+            if self.closed.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Some(next) = self.try_pop() {
+                return Some(next);
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    fn try_pop(&self) -> Option<(Priority, Vec<u8>)> {
+        let mut queues = self.queues.lock().unwrap();
+        let Queues { messages, metrics } = &mut *queues;
+        Priority::ALL.into_iter().find_map(|class| {
+            let message = messages[class.index()].pop_front()?;
+            let metrics = &mut metrics[class.index()];
+            metrics.sent += 1;
+            metrics.depth = messages[class.index()].len();
+            Some((class, message))
+        })
+    }
+
+    /// Stop the writer; queued messages are discarded
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.ready.notify_one();
+    }
+
+    pub fn metrics(&self) -> Vec<QueueMetrics> {
+        self.queues.lock().unwrap().metrics.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_blocks_jump_the_tx_backlog() {
+        let queue = OutboundQueue::new();
+        for i in 0..10u8 {
+            queue.push(Priority::TxRelay, vec![i]).unwrap();
+        }
+        queue.push(Priority::AddrGossip, vec![100]).unwrap();
+        queue.push(Priority::Block, vec![200]).unwrap();
+
+        assert_eq!(queue.pop().await, Some((Priority::Block, vec![200])));
+        assert_eq!(queue.pop().await, Some((Priority::TxRelay, vec![0])));
+    }
+
+    #[test]
+    fn test_drop_policies_when_full() {
+        let queue = OutboundQueue::new();
+        for i in 0..Priority::TxRelay.capacity() + 1 {
+            queue.push(Priority::TxRelay, (i as u32).to_le_bytes().to_vec()).unwrap();
+        }
+        for _ in 0..Priority::Block.capacity() {
+            queue.push(Priority::Block, vec![0]).unwrap();
+        }
+        assert!(queue.push(Priority::Block, vec![0]).is_err());
+
+        let metrics = queue.metrics();
+        assert_eq!(metrics[Priority::Block.index()].dropped, 1);
+        assert_eq!(metrics[Priority::TxRelay.index()].dropped, 1);
+        assert_eq!(metrics[Priority::TxRelay.index()].depth, Priority::TxRelay.capacity());
+
+        // The oldest transaction made room for the newest
+        while let Some((class, message)) = queue.try_pop() {
+            if class == Priority::TxRelay {
+                assert_eq!(message, 1u32.to_le_bytes().to_vec());
+                break;
+            }
+        }
+    }
+}
@@ -3,7 +3,10 @@
 //! This module manages individual peer connections, including TCP connection handling,
 //! message serialization/deserialization, and peer statistics tracking.
 
-use crate::{NetworkError, Result};
+use crate::{
+    outbound::{OutboundQueue, Priority, QueueMetrics},
+    NetworkError, Result,
+};
 use serde::{Deserialize, Serialize};
 use std::{
     net::SocketAddr,
@@ -94,10 +97,13 @@ pub struct Peer {
     state: Arc<Mutex<PeerState>>,
     /// TCP stream
     stream: Arc<Mutex<Option<TcpStream>>>,
-    /// Message sender channel
-    message_tx: mpsc::UnboundedSender<Vec<u8>>,
+    /// Outgoing messages by priority class
+    outbound: Arc<OutboundQueue>,
     /// Statistics
     stats: Arc<Mutex<PeerStats>>,
+    /// Faults applied to messages we send this peer
+    #[cfg(feature = "chaos")]
+    faults: std::sync::OnceLock<Arc<crate::chaos::FaultInjector>>,
 }
 
 impl PeerInfo {
@@ -132,18 +138,18 @@ impl PeerInfo {
 impl Peer {
     /// Create new peer from existing connection
     pub fn new(stream: TcpStream, info: PeerInfo) -> Result<Self> {
-        let (message_tx, message_rx) = mpsc::unbounded_channel();
-        
         let peer = Self {
             info,
             state: Arc::new(Mutex::new(PeerState::Connecting)),
             stream: Arc::new(Mutex::new(Some(stream))),
-            message_tx,
+            outbound: Arc::new(OutboundQueue::new()),
             stats: Arc::new(Mutex::new(PeerStats::default())),
+            #[cfg(feature = "chaos")]
+            faults: std::sync::OnceLock::new(),
         };
 
         // Start message handling task
-        peer.start_message_handler(message_rx);
+        peer.start_message_handler();
 
         Ok(peer)
     }
@@ -274,26 +280,46 @@ impl Peer {
         }
     }
 
-    /// Send message through message channel  
+    /// Queue a message for sending, in its priority class
     pub async fn send_message(&self, message: crate::protocol::Message) -> Result<()> {
         // Serialize message to bytes
         let message_bytes = bincode::serialize(&message)
             .map_err(|e| NetworkError::SerializationError(e.to_string()))?;
         
-        self.message_tx
-            .send(message_bytes)
-            .map_err(|e| NetworkError::ConnectionFailed(format!("Channel send failed: {}", e)))?;
-        Ok(())
+        #[cfg(feature = "chaos")]
+        if let Some(faults) = self.faults.get() {
+            let outbound = Arc::clone(&self.outbound);
+            let priority = Priority::of(&message);
+            faults.apply(self.info.address, crate::chaos::Direction::Outbound, message_bytes, move |frame| {
+                let _ = outbound.push(priority, frame);
+            });
+            return Ok(());
+        }
+
+        self.outbound.push(Priority::of(&message), message_bytes)
+    }
+
+    /// Send this peer's messages through `faults`; only the first injector
+    /// set sticks
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(&self, faults: Arc<crate::chaos::FaultInjector>) {
+        let _ = self.faults.set(faults);
+    }
+
+    /// Outbound queue counters, one entry per priority class
+    pub fn queue_metrics(&self) -> Vec<QueueMetrics> {
+        self.outbound.metrics()
     }
 
     /// Start background message handler
-    fn start_message_handler(&self, mut message_rx: mpsc::UnboundedReceiver<Vec<u8>>) {
+    fn start_message_handler(&self) {
         let peer_id = self.info.peer_id.clone();
         let stream_handle = Arc::clone(&self.stream);
         let stats_handle = Arc::clone(&self.stats);
+        let outbound = Arc::clone(&self.outbound);
         
         tokio::spawn(async move {
-            while let Some(message) = message_rx.recv().await {
+            while let Some((_, message)) = outbound.pop().await {
                 // Handle actual message sending through TCP stream
                 let send_result = Self::send_message_to_stream(
                     &stream_handle, 
@@ -373,6 +399,8 @@ impl Peer {
             };
         }
 
+        self.outbound.close();
+
         // Close the stream
         let mut stream_guard = self.stream.lock().await;
         if let Some(mut stream) = stream_guard.take() {
@@ -512,6 +540,13 @@ impl Peer {
     }
 }
 
+impl Drop for Peer {
+    fn drop(&mut self) {
+        // Lets the writer task finish
+        self.outbound.close();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,14 +4,27 @@
 //! between blockchain nodes in the hybrid network.
 
 use crate::{NetworkError, Result};
-use blockchain_core::{Hash256, BlockHeight};
+use blockchain_core::{Hash256, BlockHeight, block::Block};
+use blockchain_core::private_message::EncryptedMessage;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Magic bytes for message headers (network identification)
-pub const MAINNET_MAGIC: u32 = 0xD9B4BEF9;
+/// Magic bytes for message headers (network identification). EduNet
+/// mainnet's is defined here only; nodes, wallets and the web backend all
+/// take it from this constant.
+pub const MAINNET_MAGIC: u32 = 0xED000001;
 pub const TESTNET_MAGIC: u32 = 0x0709110B;
 
+/// Protocol version we speak
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// First protocol version that understands `SendHeaders`; older peers only
+/// get block announcements as inventory
+pub const SENDHEADERS_VERSION: u32 = 2;
+
+/// First protocol version that understands `FeeFilter`
+pub const FEEFILTER_VERSION: u32 = 3;
+
 /// Maximum message payload size (32MB)
 pub const MAX_PAYLOAD_SIZE: usize = 32 * 1024 * 1024;
 
@@ -60,6 +73,12 @@ pub enum MessageType {
     Headers,
     /// Not found response
     NotFound,
+    /// Announce new blocks to me with headers, not inventory
+    SendHeaders,
+    /// Encrypted wallet-to-wallet message
+    PrivateMessage,
+    /// Don't relay me transactions paying less than this fee rate
+    FeeFilter,
 }
 
 /// Main protocol message structure
@@ -120,6 +139,12 @@ pub enum MessagePayload {
     Headers(HeadersMessage),
     /// Not found response
     NotFound(NotFoundMessage),
+    /// Headers announcement preference (no data)
+    SendHeaders,
+    /// Encrypted message for the holder of a public key
+    PrivateMessage(EncryptedMessage),
+    /// Lowest fee rate the sender wants transactions relayed at
+    FeeFilter(FeeFilterMessage),
 }
 
 /// Version handshake message
@@ -143,6 +168,12 @@ pub struct VersionMessage {
     pub start_height: BlockHeight,
     /// Whether to relay transactions
     pub relay: bool,
+    /// Magic of the network the sender is on
+    #[serde(default)]
+    pub network_magic: u32,
+    /// Sender's genesis block hash, zero if it has no chain
+    #[serde(default)]
+    pub genesis_hash: Hash256,
 }
 
 /// Network address structure
@@ -308,6 +339,8 @@ impl Message {
         start_height: BlockHeight,
         remote_addr: NetworkAddress,
         local_addr: NetworkAddress,
+        network_magic: u32,
+        genesis_hash: Hash256,
     ) -> Self {
         let version = VersionMessage {
             version: protocol_version,
@@ -322,11 +355,39 @@ impl Message {
             user_agent,
             start_height,
             relay: true,
+            network_magic,
+            genesis_hash,
         };
 
         Self::new(MessageType::Version, MessagePayload::Version(version))
     }
 
+    /// Create reject message
+    pub fn reject(message: &str, ccode: u8, reason: String) -> Self {
+        let reject = RejectMessage {
+            message: message.to_string(),
+            ccode,
+            reason,
+            data: Vec::new(),
+        };
+        Self::new(MessageType::Reject, MessagePayload::Reject(reject))
+    }
+
+    /// Create sendheaders message
+    pub fn send_headers() -> Self {
+        Self::new(MessageType::SendHeaders, MessagePayload::SendHeaders)
+    }
+
+    /// Create feefilter message
+    pub fn fee_filter(fee_rate: u64) -> Self {
+        Self::new(MessageType::FeeFilter, MessagePayload::FeeFilter(FeeFilterMessage { fee_rate }))
+    }
+
+    /// Create private message relay
+    pub fn private_message(message: EncryptedMessage) -> Self {
+        Self::new(MessageType::PrivateMessage, MessagePayload::PrivateMessage(message))
+    }
+
     /// Create verack message
     pub fn verack() -> Self {
         Self::new(MessageType::VerAck, MessagePayload::VerAck)
@@ -415,6 +476,28 @@ impl Message {
     }
 }
 
+impl VersionMessage {
+    /// Why a peer sending this is on another network, if it is. A zero
+    /// genesis hash on either side is not compared: that node has no chain
+    /// to compare yet.
+    pub fn network_mismatch(&self, network_magic: u32, genesis_hash: Hash256) -> Option<String> {
+        if self.network_magic != network_magic {
+            return Some(format!(
+                "Wrong network: magic {:#010x}, expected {:#010x}",
+                self.network_magic, network_magic
+            ));
+        }
+        let unknown = [0u8; 32];
+        if self.genesis_hash != unknown && genesis_hash != unknown && self.genesis_hash != genesis_hash {
+            return Some(format!(
+                "Wrong chain: genesis {}, expected {}",
+                hex::encode(self.genesis_hash), hex::encode(genesis_hash)
+            ));
+        }
+        None
+    }
+}
+
 impl NetworkAddress {
     /// Create new network address
     pub fn new(ip: [u8; 16], port: u16, services: u64) -> Self {
@@ -490,6 +573,14 @@ pub mod services {
     pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
     /// Node can serve network addresses via DNS
     pub const NODE_NETWORK_LIMITED: u64 = 1 << 10;
+    /// Node relays encrypted wallet messages; opt-in
+    pub const NODE_PRIVATE_MESSAGES: u64 = 1 << 11;
+}
+
+/// Reject message codes
+pub mod reject {
+    /// Peer is on another network or chain
+    pub const WRONG_NETWORK: u8 = 0x13;
 }
 
 // ==================== SYNC PROTOCOL MESSAGES ====================
@@ -548,6 +639,8 @@ pub struct HeadersMessage {
 /// Block header information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockHeaderInfo {
+    /// Block version, needed to recompute the hash
+    pub version: u32,
     /// Block height
     pub height: u64,
     /// Block hash
@@ -564,6 +657,29 @@ pub struct BlockHeaderInfo {
     pub nonce: u32,
 }
 
+impl BlockHeaderInfo {
+    /// Header of a block, as announced to peers
+    pub fn from_block(block: &Block) -> Self {
+        Self {
+            version: block.header.version,
+            height: block.header.height as u64,
+            hash: block.get_hash(),
+            prev_hash: block.header.prev_block_hash,
+            merkle_root: block.header.merkle_root,
+            timestamp: block.header.timestamp as u64,
+            difficulty: block.header.difficulty_target,
+            nonce: block.header.nonce,
+        }
+    }
+}
+
+/// Fee filter message: transactions paying less than `fee_rate` (sat/byte)
+/// would only be refused by the sender's mempool, so they aren't sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeFilterMessage {
+    pub fee_rate: u64,
+}
+
 /// Not found message (block or transaction not found)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotFoundMessage {
@@ -740,4 +856,44 @@ mod tests {
         assert_eq!(msg.message_type, deserialized.message_type);
         assert_eq!(msg.timestamp, deserialized.timestamp);
     }
+
+    #[test]
+    fn test_sendheaders_and_header_announcement() {
+        let msg = Message::deserialize(&Message::send_headers().serialize().unwrap()).unwrap();
+        assert_eq!(msg.message_type, MessageType::SendHeaders);
+        assert!(matches!(msg.payload, MessagePayload::SendHeaders));
+
+        let block = Block::create_genesis_block("announce");
+        let header = BlockHeaderInfo::from_block(&block);
+        assert_eq!(header.hash, block.get_hash());
+        assert_eq!(header.prev_hash, block.header.prev_block_hash);
+        assert_eq!(header.height, block.header.height as u64);
+    }
+
+    #[test]
+    fn test_fee_filter_round_trip() {
+        let msg = Message::deserialize(&Message::fee_filter(2_500).serialize().unwrap()).unwrap();
+        assert_eq!(msg.message_type, MessageType::FeeFilter);
+        match msg.payload {
+            MessagePayload::FeeFilter(filter) => assert_eq!(filter.fee_rate, 2_500),
+            _ => panic!("Expected feefilter payload"),
+        }
+    }
+
+    #[test]
+    fn test_version_from_another_network() {
+        let addr = NetworkAddress::from_ipv4([127, 0, 0, 1], 9000, services::NODE_NETWORK);
+        let genesis = [7u8; 32];
+        let version = |magic, genesis_hash| match Message::version(1, 0, "test".to_string(), 0, addr.clone(), addr.clone(), magic, genesis_hash).payload {
+            MessagePayload::Version(version) => version,
+            _ => panic!("Expected version payload"),
+        };
+
+        assert_eq!(version(MAINNET_MAGIC, genesis).network_mismatch(MAINNET_MAGIC, genesis), None);
+        assert!(version(TESTNET_MAGIC, genesis).network_mismatch(MAINNET_MAGIC, genesis).unwrap().starts_with("Wrong network"));
+        assert!(version(MAINNET_MAGIC, [8u8; 32]).network_mismatch(MAINNET_MAGIC, genesis).unwrap().starts_with("Wrong chain"));
+        // Either side without a chain yet is not turned away for it
+        assert_eq!(version(MAINNET_MAGIC, [0u8; 32]).network_mismatch(MAINNET_MAGIC, genesis), None);
+        assert_eq!(version(MAINNET_MAGIC, genesis).network_mismatch(MAINNET_MAGIC, [0u8; 32]), None);
+    }
 }
\ No newline at end of file
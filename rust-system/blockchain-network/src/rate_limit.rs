@@ -0,0 +1,84 @@
+//! Token-bucket rate limiting for peer requests
+//!
+//! Each peer gets a bucket of request credit that refills at a steady rate.
+//! Cheap requests cost little, requests that make us read and send blocks
+//! cost more, and a peer that runs its bucket dry has its requests dropped
+//! until it refills, so one host cannot tie up our disk or bandwidth.
+
+use std::time::Instant;
+
+/// Credit a request costs to serve
+pub mod cost {
+    /// Each header in a GetHeaders range
+    pub const HEADER: f64 = 0.05;
+    /// Each block in a GetData or GetBlockByHeight request
+    pub const BLOCK: f64 = 10.0;
+    /// Each transaction in a GetData request
+    pub const TX: f64 = 1.0;
+    /// Each encrypted private message relayed
+    pub const PRIVATE_MESSAGE: f64 = 5.0;
+}
+
+/// A refilling allowance of request credit
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket holding `capacity` credit, refilling `refill_per_sec`
+    pub fn new(capacity: f64, refill_per_sec: f64) -> Self {
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Spend `cost` credit if the bucket holds it
+    pub fn try_take(&mut self, cost: f64) -> bool {
+        self.try_take_at(cost, Instant::now())
+    }
+
+    fn try_take_at(&mut self, cost: f64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Credit currently available
+    pub fn available(&self) -> f64 {
+        self.tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_drains_and_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(100.0, 10.0);
+        bucket.last_refill = start;
+
+        assert!(bucket.try_take_at(60.0, start));
+        assert!(!bucket.try_take_at(60.0, start));
+
+        // Four seconds refill 40 credit, never past capacity
+        assert!(bucket.try_take_at(60.0, start + Duration::from_secs(4)));
+        assert!(bucket.try_take_at(0.0, start + Duration::from_secs(60)));
+        assert_eq!(bucket.available(), 100.0);
+    }
+}
@@ -0,0 +1,181 @@
+//! In-memory caches for relaying
+//!
+//! While a new block propagates, many peers ask for it within a few seconds.
+//! Keeping the most recently connected blocks and relayed transactions in
+//! LRU caches lets the swarm answer those GetData requests from memory
+//! instead of going back to the chain for each one.
+
+use blockchain_core::{block::Block, transaction::Transaction, Hash256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Mutex;
+
+/// Recent blocks kept
+pub const BLOCK_CACHE_SIZE: usize = 32;
+
+/// Recent transactions kept
+pub const TX_CACHE_SIZE: usize = 10_000;
+
+/// Least-recently-used cache with a fixed capacity
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    /// Use stamp -> key, oldest first
+    order: BTreeMap<u64, K>,
+    next_stamp: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_stamp: 0,
+        }
+    }
+
+    fn stamp(&mut self) -> u64 {
+        self.next_stamp += 1;
+        self.next_stamp
+    }
+
+    /// Look up a value, marking it most recently used
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let stamp = self.stamp();
+        let (value, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        *used = stamp;
+        self.order.insert(stamp, key.clone());
+        Some(value.clone())
+    }
+
+    /// Insert or refresh a value, evicting the least recently used if full
+    pub fn insert(&mut self, key: K, value: V) {
+        let stamp = self.stamp();
+        if let Some((_, used)) = self.entries.insert(key.clone(), (value, stamp)) {
+            self.order.remove(&used);
+        } else if self.entries.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.order.insert(stamp, key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Hit and miss counters for the relay caches
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RelayCacheStats {
+    pub blocks_cached: usize,
+    pub block_hits: u64,
+    pub block_misses: u64,
+    pub transactions_cached: usize,
+    pub tx_hits: u64,
+    pub tx_misses: u64,
+}
+
+#[derive(Debug)]
+struct Counters {
+    block_hits: u64,
+    block_misses: u64,
+    tx_hits: u64,
+    tx_misses: u64,
+}
+
+/// Recently connected blocks and relayed transactions
+#[derive(Debug)]
+pub struct RelayCache {
+    blocks: Mutex<LruCache<Hash256, Block>>,
+    transactions: Mutex<LruCache<Hash256, Transaction>>,
+    counters: Mutex<Counters>,
+}
+
+impl Default for RelayCache {
+    fn default() -> Self {
+        Self::new(BLOCK_CACHE_SIZE, TX_CACHE_SIZE)
+    }
+}
+
+impl RelayCache {
+    pub fn new(blocks: usize, transactions: usize) -> Self {
+        Self {
+            blocks: Mutex::new(LruCache::new(blocks)),
+            transactions: Mutex::new(LruCache::new(transactions)),
+            counters: Mutex::new(Counters { block_hits: 0, block_misses: 0, tx_hits: 0, tx_misses: 0 }),
+        }
+    }
+
+    pub fn insert_block(&self, block: &Block) {
+        self.blocks.lock().unwrap().insert(block.get_hash(), block.clone());
+    }
+
+    pub fn insert_transaction(&self, hash: Hash256, transaction: &Transaction) {
+        self.transactions.lock().unwrap().insert(hash, transaction.clone());
+    }
+
+    pub fn get_block(&self, hash: &Hash256) -> Option<Block> {
+        let block = self.blocks.lock().unwrap().get(hash);
+        let mut counters = self.counters.lock().unwrap();
+        match block {
+            Some(_) => counters.block_hits += 1,
+            None => counters.block_misses += 1,
+        }
+        block
+    }
+
+    pub fn get_transaction(&self, hash: &Hash256) -> Option<Transaction> {
+        let transaction = self.transactions.lock().unwrap().get(hash);
+        let mut counters = self.counters.lock().unwrap();
+        match transaction {
+            Some(_) => counters.tx_hits += 1,
+            None => counters.tx_misses += 1,
+        }
+        transaction
+    }
+
+    pub fn stats(&self) -> RelayCacheStats {
+        let counters = self.counters.lock().unwrap();
+        RelayCacheStats {
+            blocks_cached: self.blocks.lock().unwrap().len(),
+            block_hits: counters.block_hits,
+            block_misses: counters.block_misses,
+            transactions_cached: self.transactions.lock().unwrap().len(),
+            tx_hits: counters.tx_hits,
+            tx_misses: counters.tx_misses,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        assert_eq!(cache.get(&1), Some("a"));
+
+        // 2 is now the least recently used
+        cache.insert(3, "c");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("a"));
+        assert_eq!(cache.get(&3), Some("c"));
+
+        cache.insert(3, "d");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&3), Some("d"));
+    }
+}
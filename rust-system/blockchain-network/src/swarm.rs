@@ -6,15 +6,22 @@
 use crate::{
     NetworkError, Result,
     peer::{Peer, PeerEvent, PeerStats},
-    protocol::{Message, NetworkAddress, services},
+    protocol::{self, Message, NetworkAddress, services},
     discovery::{AddressManager, PeerAddress},
+    rate_limit::{cost, TokenBucket},
+    outbound::{Priority, QueueMetrics},
+    relay_cache::{LruCache, RelayCache, RelayCacheStats},
 };
-use blockchain_core::{Hash256, block::Block, transaction::Transaction, consensus::ConsensusValidator};
+use blockchain_core::{Hash256, block::Block, transaction::Transaction, consensus::ConsensusValidator, network_time::NetworkTime};
+use blockchain_core::private_message::{EncryptedMessage, DEFAULT_POW_BITS};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::SocketAddr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
 use tokio::{
@@ -43,6 +50,25 @@ const PING_INTERVAL: Duration = Duration::from_secs(30);
 /// Peer timeout (no messages received)
 const PEER_TIMEOUT: Duration = Duration::from_secs(90);
 
+/// Inbound connections accepted from a single IP address
+const MAX_INBOUND_PER_IP: usize = 4;
+
+/// Time an inbound peer has to send its version message
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Request credit a peer starts with, and how fast it refills
+const REQUEST_BUDGET: f64 = 200.0;
+const REQUEST_REFILL_PER_SEC: f64 = 20.0;
+
+/// Inventory items allowed in one GetData; more gets the peer disconnected
+const MAX_GETDATA_ITEMS: usize = 1000;
+
+/// Headers served for one GetHeaders request
+const MAX_HEADERS_PER_REQUEST: u64 = 2000;
+
+/// Private message ids remembered so each is delivered and relayed once
+const SEEN_MESSAGES_SIZE: usize = 10_000;
+
 /// Network events broadcasted to subscribers
 #[derive(Debug, Clone)]
 pub enum NetworkEvent {
@@ -78,6 +104,23 @@ pub enum NetworkEvent {
         peer_id: Uuid,
         tx_hashes: Vec<Hash256>,
     },
+    /// Block headers received, in answer to GetHeaders or announcing a new block
+    HeadersReceived {
+        peer_id: Uuid,
+        headers: Vec<crate::protocol::BlockHeaderInfo>,
+    },
+    /// Peer reported its best chain
+    PeerHeight {
+        peer_id: Uuid,
+        height: u64,
+        best_block_hash: Hash256,
+    },
+    /// Encrypted private message that passed validation; only the recipient
+    /// can open it
+    PrivateMessageReceived {
+        peer_id: Uuid,
+        message: EncryptedMessage,
+    },
 }
 
 /// Connection direction
@@ -100,6 +143,18 @@ struct ConnectedPeer {
     stats: PeerStats,
     /// Task handle for peer processing
     task_handle: JoinHandle<()>,
+    /// Version message received
+    handshake_done: Arc<AtomicBool>,
+    /// Peer asked for new blocks as headers rather than inventory
+    prefers_headers: AtomicBool,
+    /// Peer relays encrypted private messages
+    relays_messages: AtomicBool,
+    /// Protocol version from the peer's version message
+    protocol_version: AtomicU32,
+    /// Lowest fee rate the peer wants transactions relayed at
+    fee_filter: AtomicU64,
+    /// Credit for expensive requests
+    requests: Mutex<TokenBucket>,
 }
 
 /// Network swarm statistics
@@ -125,6 +180,22 @@ pub struct SwarmStats {
     pub connection_attempts: u64,
     /// Failed connections
     pub failed_connections: u64,
+    /// Inbound connections refused by the per-IP cap
+    pub rejected_connections: u64,
+    /// Peer requests dropped for exceeding their rate limit
+    pub throttled_requests: u64,
+    /// Peers disconnected for being on another network or chain
+    pub wrong_network_peers: u64,
+    /// Block announcements sent as headers
+    pub header_announcements: u64,
+    /// Block announcements sent as inventory
+    pub inv_announcements: u64,
+    /// Private messages accepted and relayed
+    pub private_messages: u64,
+    /// Private messages dropped as invalid, expired or short on work
+    pub rejected_private_messages: u64,
+    /// Transaction relays skipped for paying less than the peer's fee filter
+    pub fee_filtered_relays: u64,
 }
 
 /// Network swarm for managing peer connections
@@ -145,8 +216,22 @@ pub struct NetworkSwarm {
     our_services: u64,
     /// Our listening port
     listening_port: u16,
+    /// Magic of the network we are on, checked against peers' versions
+    network_magic: u32,
     /// Blockchain consensus validator (optional - for serving blockchain data)
     consensus: Option<Arc<ConsensusValidator>>,
+    /// Peer clock samples from version handshakes
+    network_time: Arc<NetworkTime>,
+    /// Recent blocks and transactions, for serving GetData from memory
+    relay_cache: RelayCache,
+    /// Ids of private messages already handled
+    seen_messages: Mutex<LruCache<Hash256, ()>>,
+    /// Fee rate below which our mempool refuses transactions, sent to peers
+    /// as our fee filter; zero sends none
+    min_fee_rate: AtomicU64,
+    /// Faults applied to every peer's messages, for chaos testing
+    #[cfg(feature = "chaos")]
+    faults: std::sync::OnceLock<Arc<crate::chaos::FaultInjector>>,
 }
 
 /// Internal swarm events
@@ -183,6 +268,10 @@ impl NetworkSwarm {
     ) -> (Self, broadcast::Receiver<NetworkEvent>) {
         let (event_sender, event_receiver) = broadcast::channel(1000);
         let (internal_sender, internal_receiver) = mpsc::channel(1000);
+        // Share consensus's clock so peer samples reach block validation
+        let network_time = consensus.as_ref()
+            .map(|consensus| consensus.network_time())
+            .unwrap_or_default();
 
         let swarm = Self {
             peers: RwLock::new(HashMap::new()),
@@ -193,12 +282,43 @@ impl NetworkSwarm {
             stats: RwLock::new(SwarmStats::default()),
             our_services,
             listening_port,
+            network_magic: protocol::MAINNET_MAGIC,
             consensus,
+            network_time,
+            relay_cache: RelayCache::default(),
+            seen_messages: Mutex::new(LruCache::new(SEEN_MESSAGES_SIZE)),
+            min_fee_rate: AtomicU64::new(0),
+            #[cfg(feature = "chaos")]
+            faults: std::sync::OnceLock::new(),
         };
 
         (swarm, event_receiver)
     }
 
+    /// Only talk to peers on the network with this magic
+    pub fn with_network_magic(mut self, network_magic: u32) -> Self {
+        self.network_magic = network_magic;
+        self
+    }
+
+    /// Pass messages to and from peers connected from now on through
+    /// `faults`; only the first injector set sticks
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(&self, faults: Arc<crate::chaos::FaultInjector>) {
+        let _ = self.faults.set(faults);
+    }
+
+    /// Fault injector in use, if any
+    #[cfg(feature = "chaos")]
+    pub fn fault_injector(&self) -> Option<Arc<crate::chaos::FaultInjector>> {
+        self.faults.get().cloned()
+    }
+
+    /// Network-adjusted clock fed by peer version messages
+    pub fn network_time(&self) -> Arc<NetworkTime> {
+        self.network_time.clone()
+    }
+
     /// Start the network swarm
     pub async fn start(&self) -> Result<()> {
         info!("Starting network swarm...");
@@ -215,6 +335,7 @@ impl NetworkSwarm {
         let connection_task = self.start_connection_manager();
         let maintenance_task = self.start_maintenance_task();
         let ping_task = self.start_ping_task();
+        let cache_task = self.cache_connected_blocks();
 
         // Main event loop
         let event_loop = async move {
@@ -236,6 +357,9 @@ impl NetworkSwarm {
             _ = ping_task => {
                 warn!("Ping task ended");
             }
+            _ = cache_task => {
+                warn!("Block cache task ended");
+            }
             _ = event_loop => {
                 warn!("Event loop ended");
             }
@@ -335,10 +459,27 @@ impl NetworkSwarm {
         stats
     }
 
+    /// Outbound queue counters per priority class, summed over peers
+    pub async fn queue_metrics(&self) -> Vec<QueueMetrics> {
+        let peers = self.peers.read().await;
+        let mut totals: Vec<QueueMetrics> = Vec::new();
+        for connected_peer in peers.values() {
+            for metrics in connected_peer.peer.queue_metrics() {
+                match totals.iter_mut().find(|total| total.class == metrics.class) {
+                    Some(total) => total.merge(&metrics),
+                    None => totals.push(metrics),
+                }
+            }
+        }
+        totals.sort_by_key(|total| Priority::ALL.iter().position(|class| *class == total.class));
+        totals
+    }
+
     /// Broadcast new block to all peers
     pub async fn broadcast_block(&self, block: &Block) -> Result<usize> {
         let block_hash = block.get_hash();
         let block_data = self.serialize_block(block).await?;
+        self.relay_cache.insert_block(block);
         
         let block_message = Message::block(block_hash, block_data);
         self.broadcast_message(block_message).await
@@ -357,11 +498,60 @@ impl NetworkSwarm {
         };
         
         let tx_data = self.serialize_transaction(transaction).await?;
+        self.relay_cache.insert_transaction(tx_hash, transaction);
         let tx_message = Message::tx(tx_hash, tx_data);
         
         self.broadcast_message(tx_message).await
     }
 
+    /// Relay a transaction paying `fee_rate` (sat/byte) to the peers whose
+    /// fee filter it passes
+    pub async fn relay_transaction(&self, transaction: &Transaction, fee_rate: u64) -> Result<usize> {
+        let tx_hash = transaction.calculate_hash();
+        let tx_data = self.serialize_transaction(transaction).await?;
+        self.relay_cache.insert_transaction(tx_hash, transaction);
+        let tx_message = Message::tx(tx_hash, tx_data);
+
+        let (mut sent_count, mut filtered) = (0u64, 0u64);
+        for connected_peer in self.peers.read().await.values() {
+            if fee_rate < connected_peer.fee_filter.load(Ordering::Relaxed) {
+                filtered += 1;
+                continue;
+            }
+            match connected_peer.peer.send_message(tx_message.clone()).await {
+                Ok(()) => sent_count += 1,
+                Err(e) => warn!("Failed to relay transaction to peer {}: {}", connected_peer.peer.get_id(), e),
+            }
+        }
+
+        let mut stats = self.stats.write().await;
+        stats.messages_sent += sent_count;
+        stats.fee_filtered_relays += filtered;
+        Ok(sent_count as usize)
+    }
+
+    /// Change the fee rate below which we don't want transactions and tell
+    /// the peers that understand fee filters. Returns how many were told;
+    /// nothing is sent if the rate didn't change.
+    pub async fn set_min_fee_rate(&self, fee_rate: u64) -> usize {
+        if self.min_fee_rate.swap(fee_rate, Ordering::Relaxed) == fee_rate {
+            return 0;
+        }
+        let filter = Message::fee_filter(fee_rate);
+        let mut sent_count = 0;
+        for connected_peer in self.peers.read().await.values() {
+            if connected_peer.protocol_version.load(Ordering::Relaxed) < protocol::FEEFILTER_VERSION {
+                continue;
+            }
+            if connected_peer.peer.send_message(filter.clone()).await.is_ok() {
+                sent_count += 1;
+            }
+        }
+        self.stats.write().await.messages_sent += sent_count as u64;
+        debug!("Fee filter now {} sat/byte, sent to {} peers", fee_rate, sent_count);
+        sent_count
+    }
+
     /// Announce block inventory to peers
     pub async fn announce_block_inventory(&self, block_hashes: Vec<Hash256>) -> Result<usize> {
         use crate::protocol::{InventoryItem, InventoryType};
@@ -375,6 +565,37 @@ impl NetworkSwarm {
         self.broadcast_message(inv_message).await
     }
 
+    /// Announce a new block: as its header to peers that sent SendHeaders,
+    /// which then fetch it without an inv/getheaders round trip, and as
+    /// inventory to the rest
+    pub async fn announce_block(&self, block: &Block) -> Result<usize> {
+        use crate::protocol::{BlockHeaderInfo, InventoryItem};
+
+        let headers = Message::headers(vec![BlockHeaderInfo::from_block(block)]);
+        let inv = Message::inv(vec![InventoryItem::block(block.get_hash())]);
+        let (mut by_headers, mut by_inv) = (0u64, 0u64);
+        {
+            let peers = self.peers.read().await;
+            for connected_peer in peers.values() {
+                let prefers_headers = connected_peer.prefers_headers.load(Ordering::Relaxed);
+                let message = if prefers_headers { headers.clone() } else { inv.clone() };
+                if let Err(e) = connected_peer.peer.send_message(message).await {
+                    warn!("Failed to announce block to peer {}: {}", connected_peer.peer.get_id(), e);
+                } else if prefers_headers {
+                    by_headers += 1;
+                } else {
+                    by_inv += 1;
+                }
+            }
+        }
+
+        let mut stats = self.stats.write().await;
+        stats.messages_sent += by_headers + by_inv;
+        stats.header_announcements += by_headers;
+        stats.inv_announcements += by_inv;
+        Ok((by_headers + by_inv) as usize)
+    }
+
     /// Announce transaction inventory to peers
     pub async fn announce_transaction_inventory(&self, tx_hashes: Vec<Hash256>) -> Result<usize> {
         use crate::protocol::{InventoryItem, InventoryType};
@@ -448,15 +669,34 @@ impl NetworkSwarm {
                     peer.disconnect("Inbound connection limit reached").await?;
                     return Ok(());
                 }
+                let from_ip = self.peers.read().await.values()
+                    .filter(|p| p.direction == ConnectionDirection::Inbound && p.peer.get_address().ip() == address.ip())
+                    .count();
+                if from_ip >= MAX_INBOUND_PER_IP {
+                    self.stats.write().await.rejected_connections += 1;
+                    warn!("Refusing inbound connection from {}: {} already connected from that address", address, from_ip);
+                    peer.disconnect("Too many connections from this address").await?;
+                    return Ok(());
+                }
             }
         }
 
         // Start peer message handling
         let internal_sender = self.internal_sender.clone();
         let peer_clone = peer.clone();
+        #[cfg(feature = "chaos")]
+        let faults = self.fault_injector();
+        #[cfg(feature = "chaos")]
+        if let Some(faults) = &faults {
+            peer.set_fault_injector(faults.clone());
+        }
         
         let task_handle = tokio::spawn(async move {
             let mut event_receiver = peer_clone.get_event_receiver().await;
+            #[cfg(feature = "chaos")]
+            if let Some(faults) = faults {
+                event_receiver = faults.intercept_inbound(address, event_receiver);
+            }
             
             while let Some(event) = event_receiver.recv().await {
                 match event {
@@ -477,6 +717,24 @@ impl NetworkSwarm {
             }
         });
 
+        // Inbound peers must introduce themselves promptly or lose the slot
+        let handshake_done = Arc::new(AtomicBool::new(direction == ConnectionDirection::Outbound));
+        if direction == ConnectionDirection::Inbound {
+            let handshake_done = handshake_done.clone();
+            let internal_sender = self.internal_sender.clone();
+            let peer = peer.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(HANDSHAKE_TIMEOUT).await;
+                if !handshake_done.load(Ordering::Relaxed) {
+                    let _ = peer.disconnect("Handshake timeout").await;
+                    let _ = internal_sender.send(SwarmEvent::PeerDisconnected {
+                        peer_id,
+                        reason: "Handshake timeout".to_string(),
+                    }).await;
+                }
+            });
+        }
+
         // Add to connected peers
         let connected_peer = ConnectedPeer {
             peer: peer.clone(),
@@ -484,6 +742,12 @@ impl NetworkSwarm {
             connected_at: Instant::now(),
             stats: PeerStats::default(),
             task_handle,
+            handshake_done,
+            prefers_headers: AtomicBool::new(false),
+            relays_messages: AtomicBool::new(false),
+            protocol_version: AtomicU32::new(0),
+            fee_filter: AtomicU64::new(0),
+            requests: Mutex::new(TokenBucket::new(REQUEST_BUDGET, REQUEST_REFILL_PER_SEC)),
         };
 
         {
@@ -508,9 +772,42 @@ impl NetworkSwarm {
               if matches!(direction, ConnectionDirection::Outbound) { "outbound" } else { "inbound" },
               peer_id, address);
 
+        // Introduce ourselves, so the peer can tell whether we share a chain
+        let version = Message::version(
+            protocol::PROTOCOL_VERSION,
+            self.our_services,
+            format!("EduNet/{}", env!("CARGO_PKG_VERSION")),
+            self.best_height().await,
+            network_address(address, 0),
+            network_address(SocketAddr::from(([0, 0, 0, 0], self.listening_port)), self.our_services),
+            self.network_magic,
+            self.genesis_hash().await,
+        );
+        if let Err(e) = self.send_to_peer(peer_id, version).await {
+            warn!("Failed to send version to peer {}: {}", peer_id, e);
+        }
+
         Ok(())
     }
 
+    /// Hash of our genesis block, zero without a chain
+    async fn genesis_hash(&self) -> Hash256 {
+        match &self.consensus {
+            Some(consensus) => consensus.get_block_by_height(0).await
+                .map(|genesis| genesis.header.calculate_hash())
+                .unwrap_or([0u8; 32]),
+            None => [0u8; 32],
+        }
+    }
+
+    /// Height of our best block, zero without a chain
+    async fn best_height(&self) -> u64 {
+        match &self.consensus {
+            Some(consensus) => consensus.get_chain_state().await.height,
+            None => 0,
+        }
+    }
+
     /// Handle peer disconnection
     async fn handle_peer_disconnection(&self, peer_id: Uuid, reason: &str) -> Result<()> {
         let mut peers = self.peers.write().await;
@@ -591,9 +888,58 @@ impl NetworkSwarm {
                     let _ = self.event_sender.send(NetworkEvent::TransactionInventory { peer_id, tx_hashes });
                 }
             }
+            crate::protocol::MessagePayload::Version(version) => {
+                // A peer on another network would only fail later, on its
+                // blocks; tell it why and hang up now
+                if let Some(reason) = version.network_mismatch(self.network_magic, self.genesis_hash().await) {
+                    warn!("Peer {} ({}) is on another network: {}", peer_id, version.user_agent, reason);
+                    self.stats.write().await.wrong_network_peers += 1;
+                    let _ = self.send_to_peer(peer_id, Message::reject("version", protocol::reject::WRONG_NETWORK, reason.clone())).await;
+                    self.disconnect_peer(peer_id, &reason).await?;
+                    return Ok(());
+                }
+                if let Some(connected_peer) = self.peers.read().await.get(&peer_id) {
+                    connected_peer.handshake_done.store(true, Ordering::Relaxed);
+                    connected_peer.relays_messages.store(version.services & services::NODE_PRIVATE_MESSAGES != 0, Ordering::Relaxed);
+                    connected_peer.protocol_version.store(version.version, Ordering::Relaxed);
+                }
+                self.network_time.add_peer_time(&peer_id.to_string(), version.timestamp);
+                debug!("Peer {} runs {} at height {}", peer_id, version.user_agent, version.start_height);
+                // Peers that understand it are asked for headers announcements
+                if version.version >= protocol::SENDHEADERS_VERSION {
+                    self.send_to_peer(peer_id, Message::send_headers()).await?;
+                }
+                let min_fee_rate = self.min_fee_rate.load(Ordering::Relaxed);
+                if version.version >= protocol::FEEFILTER_VERSION && min_fee_rate > 0 {
+                    self.send_to_peer(peer_id, Message::fee_filter(min_fee_rate)).await?;
+                }
+            }
+            crate::protocol::MessagePayload::SendHeaders => {
+                if let Some(connected_peer) = self.peers.read().await.get(&peer_id) {
+                    connected_peer.prefers_headers.store(true, Ordering::Relaxed);
+                }
+                debug!("Peer {} wants new blocks announced as headers", peer_id);
+            }
+            crate::protocol::MessagePayload::FeeFilter(filter) => {
+                if let Some(connected_peer) = self.peers.read().await.get(&peer_id) {
+                    connected_peer.fee_filter.store(filter.fee_rate, Ordering::Relaxed);
+                }
+                debug!("Peer {} wants transactions paying at least {} sat/byte", peer_id, filter.fee_rate);
+            }
             crate::protocol::MessagePayload::GetData(get_data_msg) => {
-                // Handle data requests
-                self.handle_get_data_request(peer_id, get_data_msg).await?;
+                if get_data_msg.inventory.len() > MAX_GETDATA_ITEMS {
+                    self.disconnect_peer(peer_id, "Oversized getdata request").await?;
+                    return Ok(());
+                }
+                let request_cost = get_data_msg.inventory.iter()
+                    .map(|item| match item.item_type {
+                        crate::protocol::InventoryType::Block => cost::BLOCK,
+                        _ => cost::TX,
+                    })
+                    .sum();
+                if self.charge(peer_id, request_cost).await {
+                    self.handle_get_data_request(peer_id, get_data_msg).await?;
+                }
             }
             crate::protocol::MessagePayload::GetBlockchainHeight => {
                 // Handle blockchain height request
@@ -602,27 +948,57 @@ impl NetworkSwarm {
             crate::protocol::MessagePayload::BlockchainHeight(height_msg) => {
                 // Blockchain height response (processed by sync engine)
                 debug!("Received blockchain height {} from peer {}", height_msg.height, peer_id);
+                let _ = self.event_sender.send(NetworkEvent::PeerHeight {
+                    peer_id,
+                    height: height_msg.height,
+                    best_block_hash: height_msg.best_block_hash,
+                });
             }
             crate::protocol::MessagePayload::GetBlockByHeight(req) => {
                 // Handle block request by height
-                self.handle_get_block_by_height(peer_id, req.height).await?;
+                if self.charge(peer_id, cost::BLOCK).await {
+                    self.handle_get_block_by_height(peer_id, req.height).await?;
+                }
             }
             crate::protocol::MessagePayload::BlockData(block_msg) => {
                 // Block data response (processed by sync engine)
                 debug!("Received block data at height {} from peer {}", block_msg.height, peer_id);
+                match self.deserialize_block(&block_msg.block_data).await {
+                    Ok(block) => {
+                        let _ = self.event_sender.send(NetworkEvent::BlockReceived { peer_id, block });
+                    }
+                    Err(e) => {
+                        warn!("Failed to deserialize block data from peer {}: {}", peer_id, e);
+                    }
+                }
             }
             crate::protocol::MessagePayload::GetHeaders(headers_req) => {
                 // Handle headers request
-                self.handle_get_headers(peer_id, headers_req).await?;
+                let count = headers_req.end_height.saturating_sub(headers_req.start_height)
+                    .saturating_add(1)
+                    .min(MAX_HEADERS_PER_REQUEST);
+                if self.charge(peer_id, count as f64 * cost::HEADER).await {
+                    self.handle_get_headers(peer_id, headers_req).await?;
+                }
             }
             crate::protocol::MessagePayload::Headers(headers_msg) => {
                 // Headers response (processed by sync engine)
                 debug!("Received {} headers from peer {}", headers_msg.count, peer_id);
+                let _ = self.event_sender.send(NetworkEvent::HeadersReceived {
+                    peer_id,
+                    headers: headers_msg.headers.clone(),
+                });
             }
             crate::protocol::MessagePayload::NotFound(not_found) => {
                 // Block or transaction not found
                 debug!("Peer {} reported not found: {:?}", peer_id, not_found.item_type);
             }
+            crate::protocol::MessagePayload::PrivateMessage(private_message) => {
+                // Only nodes that opted in carry the overlay
+                if self.our_services & services::NODE_PRIVATE_MESSAGES != 0 && self.charge(peer_id, cost::PRIVATE_MESSAGE).await {
+                    self.handle_private_message(Some(peer_id), private_message).await;
+                }
+            }
             _ => {
                 // Other messages handled by peer directly
                 debug!("Received message type from peer {}: {:?}", peer_id, message);
@@ -632,6 +1008,67 @@ impl NetworkSwarm {
         Ok(())
     }
 
+    /// Check a private message, hand it to subscribers and pass it on to the
+    /// other peers that relay messages. Returns the number of peers it went
+    /// to; messages that fail validation or were already seen go nowhere.
+    async fn handle_private_message(&self, from: Option<Uuid>, message: &EncryptedMessage) -> usize {
+        if let Err(e) = message.validate(self.network_time.adjusted_time(), DEFAULT_POW_BITS) {
+            self.stats.write().await.rejected_private_messages += 1;
+            debug!("Dropping private message from {:?}: {}", from, e);
+            return 0;
+        }
+        {
+            let mut seen = self.seen_messages.lock().unwrap();
+            let id = message.id();
+            if seen.get(&id).is_some() {
+                return 0;
+            }
+            seen.insert(id, ());
+        }
+        if let Some(peer_id) = from {
+            let _ = self.event_sender.send(NetworkEvent::PrivateMessageReceived { peer_id, message: message.clone() });
+        }
+
+        let relay = Message::private_message(message.clone());
+        let mut sent_count = 0;
+        for (peer_id, connected_peer) in self.peers.read().await.iter() {
+            if Some(*peer_id) == from || !connected_peer.relays_messages.load(Ordering::Relaxed) {
+                continue;
+            }
+            match connected_peer.peer.send_message(relay.clone()).await {
+                Ok(()) => sent_count += 1,
+                Err(e) => debug!("Failed to relay private message to peer {}: {}", peer_id, e),
+            }
+        }
+
+        let mut stats = self.stats.write().await;
+        stats.private_messages += 1;
+        stats.messages_sent += sent_count as u64;
+        sent_count
+    }
+
+    /// Send an encrypted private message into the overlay. Fails if the
+    /// message wouldn't pass the checks relaying peers apply.
+    pub async fn send_private_message(&self, message: EncryptedMessage) -> Result<usize> {
+        message.validate(self.network_time.adjusted_time(), DEFAULT_POW_BITS)
+            .map_err(|e| NetworkError::InvalidMessage(e.to_string()))?;
+        Ok(self.handle_private_message(None, &message).await)
+    }
+
+    /// Spend a peer's request credit; false (and the request dropped) if it
+    /// has run out
+    async fn charge(&self, peer_id: Uuid, request_cost: f64) -> bool {
+        let allowed = match self.peers.read().await.get(&peer_id) {
+            Some(connected_peer) => connected_peer.requests.lock().unwrap().try_take(request_cost),
+            None => false,
+        };
+        if !allowed {
+            self.stats.write().await.throttled_requests += 1;
+            debug!("Dropping request from peer {}: rate limit exceeded", peer_id);
+        }
+        allowed
+    }
+
     /// Handle GetData request from peer
     async fn handle_get_data_request(
         &self,
@@ -733,7 +1170,7 @@ impl NetworkSwarm {
         let headers = if let Some(ref consensus) = self.consensus {
             consensus.get_block_headers(
                 request.start_height,
-                request.end_height,
+                request.end_height.min(request.start_height.saturating_add(MAX_HEADERS_PER_REQUEST - 1)),
                 request.stop_hash,
             ).await.unwrap_or_else(|e| {
                 warn!("Failed to get headers: {}", e);
@@ -747,6 +1184,7 @@ impl NetworkSwarm {
         let headers_len = headers.len();
         let protocol_headers: Vec<crate::protocol::BlockHeaderInfo> = headers.into_iter()
             .map(|h| crate::protocol::BlockHeaderInfo {
+                version: h.version,
                 height: h.height,
                 hash: h.hash,
                 prev_hash: h.prev_hash,
@@ -866,8 +1304,11 @@ impl NetworkSwarm {
 
     /// Perform periodic maintenance
     async fn perform_maintenance(&self) -> Result<()> {
-        // Clean up old addresses
+        // Clean up old addresses and save the rest for the next start
         self.address_manager.cleanup_addresses().await?;
+        if let Err(e) = self.address_manager.save().await {
+            warn!("Failed to save known peers: {}", e);
+        }
         
         // Check for stale connections
         let now = Instant::now();
@@ -895,6 +1336,34 @@ impl NetworkSwarm {
         Ok(())
     }
 
+    /// Cache and announce each block consensus connects, ready for the
+    /// GetData requests that follow its announcement
+    async fn cache_connected_blocks(&self) {
+        let Some(consensus) = self.consensus.clone() else {
+            return std::future::pending().await;
+        };
+        let mut tips = consensus.subscribe_tip();
+        loop {
+            match tips.recv().await {
+                Ok(tip) => {
+                    if let Some(block) = consensus.get_block_by_height(tip.height).await {
+                        self.relay_cache.insert_block(&block);
+                        if let Err(e) = self.announce_block(&block).await {
+                            warn!("Failed to announce block {}: {}", tip.height, e);
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Relay cache sizes and hit rates
+    pub fn relay_cache_stats(&self) -> RelayCacheStats {
+        self.relay_cache.stats()
+    }
+
     /// Start ping task
     async fn start_ping_task(&self) -> ! {
         let mut interval = interval(PING_INTERVAL);
@@ -913,8 +1382,11 @@ impl NetworkSwarm {
         let peers = self.peers.read().await;
         
         for connected_peer in peers.values() {
-            if let Err(e) = connected_peer.peer.ping().await {
-                warn!("Failed to ping peer {}: {}", connected_peer.peer.get_id(), e);
+            match connected_peer.peer.ping().await {
+                Ok(latency) => {
+                    self.address_manager.record_latency(connected_peer.peer.get_address(), latency).await;
+                }
+                Err(e) => warn!("Failed to ping peer {}: {}", connected_peer.peer.get_id(), e),
             }
         }
         
@@ -923,24 +1395,16 @@ impl NetworkSwarm {
 
     /// Deserialize block data from network protocol
     async fn deserialize_block(&self, block_data: &[u8]) -> Result<Block> {
-        // Use serde_json for now (matching the serialize method in transaction.rs)
-        // In production, this would use binary Bitcoin protocol format
-        let json_str = String::from_utf8(block_data.to_vec())
-            .map_err(|e| NetworkError::SerializationError(format!("Invalid UTF-8 in block data: {}", e)))?;
-        
-        serde_json::from_str(&json_str)
-            .map_err(|e| NetworkError::SerializationError(format!("Failed to deserialize block: {}", e)))
+        // Tolerates fields added by newer releases
+        blockchain_core::schema::decode_block(block_data)
+            .map_err(|e| NetworkError::SerializationError(e.to_string()))
     }
 
     /// Deserialize transaction data from network protocol  
     async fn deserialize_transaction(&self, tx_data: &[u8]) -> Result<Transaction> {
-        // Use serde_json for now (matching the serialize method in transaction.rs)
-        // In production, this would use binary Bitcoin protocol format
-        let json_str = String::from_utf8(tx_data.to_vec())
-            .map_err(|e| NetworkError::SerializationError(format!("Invalid UTF-8 in transaction data: {}", e)))?;
-        
-        serde_json::from_str(&json_str)
-            .map_err(|e| NetworkError::SerializationError(format!("Failed to deserialize transaction: {}", e)))
+        // Tolerates fields added by newer releases
+        blockchain_core::schema::decode_transaction(tx_data)
+            .map_err(|e| NetworkError::SerializationError(e.to_string()))
     }
 
     /// Serialize block for network transmission
@@ -959,90 +1423,31 @@ impl NetworkSwarm {
             .map_err(|e| NetworkError::SerializationError(format!("Failed to serialize transaction: {}", e)))
     }
 
-    /// Lookup block by hash from storage
+    /// Lookup block by hash, from the relay cache or the chain
     async fn lookup_block_by_hash(&self, block_hash: Hash256) -> Option<Block> {
-        // Real blockchain storage integration
-        // This performs actual block lookup from the blockchain storage system
-        
-        debug!("Looking up block by hash: {}", hex::encode(block_hash));
-        
-        // In a real implementation, this would query the storage backend
-        // For now, we simulate proper storage access patterns
-        
-        // First check if this is a valid block hash format
-        if block_hash.iter().all(|&b| b == 0) {
-            debug!("Invalid block hash (all zeros): {}", hex::encode(block_hash));
-            return None;
+        if let Some(block) = self.relay_cache.get_block(&block_hash) {
+            return Some(block);
         }
-        
-        // Log the lookup attempt for monitoring
-        info!("Block lookup requested for hash: {}", hex::encode(block_hash));
-        
-        // In production, this would call:
-        // match self.storage_engine.get_block(block_hash).await {
-        //     Ok(Some(block)) => {
-        //         info!("Found block at height {}", block.header.height);
-        //         Some(block)
-        //     },
-        //     Ok(None) => {
-        //         debug!("Block not found: {}", hex::encode(block_hash));
-        //         None
-        //     },
-        //     Err(e) => {
-        //         error!("Storage error during block lookup: {}", e);
-        //         None
-        //     }
-        // }
-        
-        // For now, return None but with proper error handling structure
-        debug!("Block storage integration pending - returning None for hash: {}", hex::encode(block_hash));
-        None
+        let block = self.consensus.as_ref()?.get_block_by_hash(&block_hash).await?;
+        self.relay_cache.insert_block(&block);
+        Some(block)
     }
 
-    /// Lookup transaction by hash from mempool or confirmed transactions
+    /// Lookup a recently relayed transaction by hash
     async fn lookup_transaction_by_hash(&self, tx_hash: Hash256) -> Option<Transaction> {
-        // Real transaction lookup with proper mempool and storage integration
-        
-        debug!("Looking up transaction by hash: {}", hex::encode(tx_hash));
-        
-        // First check if this is a valid transaction hash format
-        if tx_hash.iter().all(|&b| b == 0) {
-            debug!("Invalid transaction hash (all zeros): {}", hex::encode(tx_hash));
-            return None;
+        let transaction = self.relay_cache.get_transaction(&tx_hash);
+        if transaction.is_none() {
+            debug!("Transaction {} not in relay cache", hex::encode(tx_hash));
         }
-        
-        // Log the lookup attempt for monitoring
-        info!("Transaction lookup requested for hash: {}", hex::encode(tx_hash));
-        
-        // In production, this would follow the proper lookup hierarchy:
-        // 1. Check mempool for unconfirmed transactions (fastest)
-        // 2. Check blockchain storage for confirmed transactions
-        
-        // Simulated mempool check:
-        // if let Some(tx) = self.mempool.get_transaction(tx_hash).await {
-        //     info!("Found unconfirmed transaction in mempool: {}", hex::encode(tx_hash));
-        //     return Some(tx);
-        // }
-        
-        // Simulated storage check:
-        // match self.storage_engine.get_transaction(tx_hash).await {
-        //     Ok(Some(tx)) => {
-        //         info!("Found confirmed transaction in storage: {}", hex::encode(tx_hash));
-        //         Some(tx)
-        //     },
-        //     Ok(None) => {
-        //         debug!("Transaction not found: {}", hex::encode(tx_hash));
-        //         None
-        //     },
-        //     Err(e) => {
-        //         error!("Storage error during transaction lookup: {}", e);
-        //         None
-        //     }
-        // }
-        
-        // For now, return None but with proper error handling and logging
-        debug!("Transaction lookup integration pending - returning None for hash: {}", hex::encode(tx_hash));
-        None
+        transaction
+    }
+}
+
+/// Wire form of a socket address
+fn network_address(address: SocketAddr, services: u64) -> NetworkAddress {
+    match address {
+        SocketAddr::V4(v4) => NetworkAddress::from_ipv4(v4.ip().octets(), v4.port(), services),
+        SocketAddr::V6(v6) => NetworkAddress::new(v6.ip().octets(), v6.port(), services),
     }
 }
 
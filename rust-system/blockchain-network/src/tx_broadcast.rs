@@ -1,3 +1,16 @@
+//! Transaction broadcasting
+//!
+//! Broadcasts don't go straight to every peer. Each transaction is queued
+//! in a lane and `flush` sends what is due, urgent lane first, so a wallet's
+//! payment goes out ahead of a backlog of rebroadcasts. Every peer has a
+//! send window, a number of transactions it is sent per interval; once that
+//! is used up the rest wait for the next window instead of overflowing the
+//! peer's outbound queue, which would silently push out older
+//! transactions. A send that fails for a reason that may pass, such as a
+//! full queue or no peers at all, is retried with exponential backoff until
+//! the retries run out. Transactions already broadcast or queued are not
+//! queued again.
+
 use crate::{
     NetworkManager, NetworkError, protocol::{Message, MessagePayload, TxMessage, MessageType}
 };
@@ -6,14 +19,18 @@ use blockchain_core::{
     Hash256, BlockchainError, Result as BlockchainResult
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH, Duration, Instant},
 };
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{info, warn, error, debug};
 use uuid::Uuid;
 
+/// How often the background task sends what is due
+const FLUSH_INTERVAL: Duration = Duration::from_millis(250);
+
 /// Transaction broadcast manager
 pub struct TransactionBroadcaster {
     network: Arc<NetworkManager>,
@@ -21,6 +38,8 @@ pub struct TransactionBroadcaster {
     pending_transactions: Arc<RwLock<HashMap<Hash256, PendingTransaction>>>,
     /// Recently seen transaction hashes (for duplicate detection)
     seen_transactions: Arc<RwLock<HashSet<Hash256>>>,
+    /// Queued sends, per-peer windows and retry state
+    scheduler: Mutex<SendScheduler>,
     /// Maximum time to keep pending transactions
     max_pending_time: Duration,
     /// Maximum number of transactions to keep in memory
@@ -31,6 +50,8 @@ pub struct TransactionBroadcaster {
     failed_broadcasts: Arc<std::sync::atomic::AtomicU64>,
     /// Duplicate reception counter
     duplicate_receptions: Arc<std::sync::atomic::AtomicU64>,
+    /// Sends retried after a transient failure
+    retried_sends: Arc<std::sync::atomic::AtomicU64>,
 }
 
 /// Pending transaction metadata
@@ -49,7 +70,7 @@ pub struct PendingTransaction {
 }
 
 /// Transaction priority levels
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TransactionPriority {
     Low,
     Normal,
@@ -57,6 +78,60 @@ pub enum TransactionPriority {
     Critical,
 }
 
+/// Broadcast queues, drained in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lane {
+    /// First broadcasts of high and critical priority transactions
+    Urgent,
+    /// First broadcasts of everything else
+    Normal,
+    /// Rebroadcasts of transactions still unconfirmed
+    Rebroadcast,
+}
+
+impl Lane {
+    pub const ALL: [Lane; 3] = [Lane::Urgent, Lane::Normal, Lane::Rebroadcast];
+
+    /// Lane a first broadcast of `priority` is queued in
+    pub fn of(priority: TransactionPriority) -> Self {
+        if priority >= TransactionPriority::High {
+            Lane::Urgent
+        } else {
+            Lane::Normal
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Send windows and retry policy of the broadcaster
+#[derive(Debug, Clone)]
+pub struct BroadcastConfig {
+    /// Transactions sent to one peer per window
+    pub peer_window: usize,
+    pub window_interval: Duration,
+    /// Retries after the first attempt before a broadcast is given up
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            peer_window: 64,
+            window_interval: Duration::from_secs(1),
+            max_retries: 5,
+            base_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
 /// Transaction broadcast statistics
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct BroadcastStats {
@@ -66,24 +141,163 @@ pub struct BroadcastStats {
     pub successful_broadcasts: u64,
     pub failed_broadcasts: u64,
     pub duplicate_receptions: u64,
+    /// Sends waiting in each lane
+    pub queued: HashMap<Lane, usize>,
+    pub retried_sends: u64,
+}
+
+/// A transaction waiting to be sent
+#[derive(Debug, Clone)]
+struct BroadcastJob {
+    tx_hash: Hash256,
+    message: Message,
+    lane: Lane,
+    /// Failed attempts so far
+    attempts: u32,
+    next_attempt: Instant,
+    /// Peers that already have it
+    sent_to: HashSet<Uuid>,
+}
+
+/// Sends one peer got in the current window
+#[derive(Debug)]
+struct SendWindow {
+    started: Instant,
+    sent: usize,
+}
+
+/// Lanes of queued sends and the peers' send windows
+#[derive(Debug)]
+struct SendScheduler {
+    config: BroadcastConfig,
+    lanes: [VecDeque<BroadcastJob>; 3],
+    /// Hashes in any lane, or being sent
+    queued: HashSet<Hash256>,
+    windows: HashMap<Uuid, SendWindow>,
+}
+
+impl SendScheduler {
+    fn new(config: BroadcastConfig) -> Self {
+        Self {
+            config,
+            lanes: Default::default(),
+            queued: HashSet::new(),
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Queue a send; false if the transaction is already queued
+    fn enqueue(&mut self, job: BroadcastJob) -> bool {
+        if !self.queued.insert(job.tx_hash) {
+            return false;
+        }
+        self.lanes[job.lane.index()].push_back(job);
+        true
+    }
+
+    /// Take the first job due by `now`, highest lane first
+    fn next_due(&mut self, now: Instant) -> Option<BroadcastJob> {
+        self.lanes.iter_mut().find_map(|lane| {
+            let index = lane.iter().position(|job| job.next_attempt <= now)?;
+            lane.remove(index)
+        })
+    }
+
+    /// Put back a job taken by `next_due`
+    fn requeue(&mut self, job: BroadcastJob) {
+        self.lanes[job.lane.index()].push_back(job);
+    }
+
+    /// A job taken by `next_due` is done with
+    fn finish(&mut self, tx_hash: &Hash256) {
+        self.queued.remove(tx_hash);
+    }
+
+    /// Drop a queued transaction
+    fn remove(&mut self, tx_hash: &Hash256) {
+        if self.queued.remove(tx_hash) {
+            for lane in self.lanes.iter_mut() {
+                lane.retain(|job| &job.tx_hash != tx_hash);
+            }
+        }
+    }
+
+    /// Use a slot of `peer`'s window; false once the window is full
+    fn take_window(&mut self, peer: Uuid, now: Instant) -> bool {
+        let window = self.windows.entry(peer).or_insert(SendWindow { started: now, sent: 0 });
+        if now.duration_since(window.started) >= self.config.window_interval {
+            *window = SendWindow { started: now, sent: 0 };
+        }
+        if window.sent >= self.config.peer_window {
+            return false;
+        }
+        window.sent += 1;
+        true
+    }
+
+    /// Forget the windows of peers no longer connected
+    fn retain_peers(&mut self, connected: &[Uuid]) {
+        self.windows.retain(|peer, _| connected.contains(peer));
+    }
+
+    /// Wait before retry number `attempts`
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.config.base_backoff.saturating_mul(factor).min(self.config.max_backoff)
+    }
+
+    fn depths(&self) -> HashMap<Lane, usize> {
+        Lane::ALL.into_iter().map(|lane| (lane, self.lanes[lane.index()].len())).collect()
+    }
+}
+
+/// Failures a later attempt may not hit: a full outbound queue, a timeout
+fn is_transient(error: &NetworkError) -> bool {
+    matches!(
+        error,
+        NetworkError::ConnectionError(_)
+            | NetworkError::ConnectionTimeout
+            | NetworkError::Timeout
+            | NetworkError::IoError(_)
+    )
 }
 
 impl TransactionBroadcaster {
     /// Create a new transaction broadcaster
     pub fn new(network: Arc<NetworkManager>) -> Self {
+        Self::with_config(network, BroadcastConfig::default())
+    }
+
+    /// Create a broadcaster with its own send windows and retry policy
+    pub fn with_config(network: Arc<NetworkManager>, config: BroadcastConfig) -> Self {
         Self {
             network,
             pending_transactions: Arc::new(RwLock::new(HashMap::new())),
             seen_transactions: Arc::new(RwLock::new(HashSet::new())),
+            scheduler: Mutex::new(SendScheduler::new(config)),
             max_pending_time: Duration::from_secs(300), // 5 minutes
             max_pending_count: 10000, // Maximum 10k pending transactions
             successful_broadcasts: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             failed_broadcasts: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             duplicate_receptions: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            retried_sends: Arc::new(std::sync::atomic::AtomicU64::new(0)),
         }
     }
 
-    /// Broadcast a transaction to the network
+    /// Send queued transactions as they come due, until aborted
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.flush().await;
+            }
+        })
+    }
+
+    /// Broadcast a transaction to the network. It is queued in the lane of
+    /// its priority and sent as far as the peers' windows allow; the rest,
+    /// and any retries, go out on later flushes.
     pub async fn broadcast_transaction(
         &self,
         transaction: Transaction,
@@ -106,65 +320,32 @@ impl TransactionBroadcaster {
 
         info!("Broadcasting transaction: {}", hex::encode(tx_hash));
 
-        // Serialize the transaction
-        let tx_data = bincode::serialize(&transaction)
-            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        let message = self.tx_message(tx_hash, &transaction)?;
 
-        // Create network message
-        let tx_message = TxMessage {
-            tx_hash,
-            tx_data,
+        // Track the pending transaction
+        let pending = PendingTransaction {
+            transaction,
+            broadcast_time: Instant::now(),
+            peer_count: 0,
+            rebroadcast_count: 0,
+            priority,
         };
 
-        let message = Message {
-            message_type: MessageType::Tx,
-            payload: MessagePayload::Tx(tx_message),
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            checksum: self.calculate_checksum(&tx_hash),
-        };
-
-        // Broadcast to network
-        match self.network.broadcast_message(message).await {
-            Ok(peer_count) => {
-                info!("Transaction {} broadcast to {} peers", hex::encode(tx_hash), peer_count);
-                
-                // Increment successful broadcast counter
-                self.successful_broadcasts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                
-                // Track the pending transaction
-                let pending = PendingTransaction {
-                    transaction,
-                    broadcast_time: Instant::now(),
-                    peer_count,
-                    rebroadcast_count: 0,
-                    priority,
-                };
-
-                {
-                    let mut pending_txs = self.pending_transactions.write().await;
-                    pending_txs.insert(tx_hash, pending);
-                }
-
-                // Mark as seen
-                {
-                    let mut seen = self.seen_transactions.write().await;
-                    seen.insert(tx_hash);
-                }
+        {
+            let mut pending_txs = self.pending_transactions.write().await;
+            pending_txs.insert(tx_hash, pending);
+        }
 
-                Ok(tx_hash)
-            }
-            Err(e) => {
-                error!("Failed to broadcast transaction {}: {}", hex::encode(tx_hash), e);
+        // Mark as seen
+        {
+            let mut seen = self.seen_transactions.write().await;
+            seen.insert(tx_hash);
+        }
                 
-                // Increment failed broadcast counter
-                self.failed_broadcasts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.enqueue(tx_hash, message, Lane::of(priority)).await;
+        self.flush().await;
                 
-                Err(BlockchainError::InvalidTransaction(format!("Broadcast failed: {}", e)))
-            }
-        }
+        Ok(tx_hash)
     }
 
     /// Handle incoming transaction from the network
@@ -216,7 +397,8 @@ impl TransactionBroadcaster {
         Ok(())
     }
 
-    /// Rebroadcast high-priority pending transactions
+    /// Queue rebroadcasts of high-priority pending transactions, behind any
+    /// first broadcasts
     pub async fn rebroadcast_pending(&self) -> BlockchainResult<usize> {
         let mut rebroadcast_count = 0;
         let now = Instant::now();
@@ -236,43 +418,33 @@ impl TransactionBroadcaster {
                pending.rebroadcast_count < 3 &&
                now.duration_since(pending.broadcast_time) > Duration::from_secs(60) {
                 
-                if let Ok(tx_data) = bincode::serialize(&pending.transaction) {
-                    let tx_message = TxMessage {
-                        tx_hash: *tx_hash,
-                        tx_data,
-                    };
-
-                    let message = Message {
-                        message_type: MessageType::Tx,
-                        payload: MessagePayload::Tx(tx_message),
-                        timestamp: SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs(),
-                        checksum: self.calculate_checksum(tx_hash),
-                    };
-
-                    if let Ok(peer_count) = self.network.broadcast_message(message).await {
+                if let Ok(message) = self.tx_message(*tx_hash, &pending.transaction) {
+                    if self.enqueue(*tx_hash, message, Lane::Rebroadcast).await {
                         pending.rebroadcast_count += 1;
-                        pending.peer_count = peer_count;
                         rebroadcast_count += 1;
-                        info!("Rebroadcast transaction {} to {} peers", 
-                              hex::encode(tx_hash), peer_count);
+                        debug!("Queued rebroadcast of transaction {}", hex::encode(tx_hash));
                     }
                 }
             }
         }
 
         // Remove expired transactions
-        for tx_hash in to_remove {
-            pending_txs.remove(&tx_hash);
+        {
+            let mut scheduler = self.scheduler.lock().await;
+            for tx_hash in to_remove {
+                pending_txs.remove(&tx_hash);
+                scheduler.remove(&tx_hash);
+            }
         }
+        drop(pending_txs);
 
+        self.flush().await;
         Ok(rebroadcast_count)
     }
 
     /// Remove confirmed transaction from pending list
     pub async fn confirm_transaction(&self, tx_hash: &Hash256) {
+        self.scheduler.lock().await.remove(tx_hash);
         let mut pending_txs = self.pending_transactions.write().await;
         if let Some(pending) = pending_txs.remove(tx_hash) {
             info!("Transaction {} confirmed and removed from pending", 
@@ -284,6 +456,7 @@ impl TransactionBroadcaster {
     pub async fn get_stats(&self) -> BroadcastStats {
         let pending_txs = self.pending_transactions.read().await;
         let seen_txs = self.seen_transactions.read().await;
+        let queued = self.scheduler.lock().await.depths();
         
         BroadcastStats {
             total_broadcasted: pending_txs.len() as u64,
@@ -292,6 +465,8 @@ impl TransactionBroadcaster {
             successful_broadcasts: self.successful_broadcasts.load(std::sync::atomic::Ordering::Relaxed),
             failed_broadcasts: self.failed_broadcasts.load(std::sync::atomic::Ordering::Relaxed),
             duplicate_receptions: self.duplicate_receptions.load(std::sync::atomic::Ordering::Relaxed),
+            queued,
+            retried_sends: self.retried_sends.load(std::sync::atomic::Ordering::Relaxed),
         }
     }
 
@@ -322,44 +497,129 @@ impl TransactionBroadcaster {
             .collect()
     }
 
-    /// Force rebroadcast a specific transaction
+    /// Force rebroadcast a specific transaction, in the urgent lane
     pub async fn force_rebroadcast(&self, tx_hash: &Hash256) -> BlockchainResult<()> {
-        let pending_txs = self.pending_transactions.read().await;
+        let message = {
+            let pending_txs = self.pending_transactions.read().await;
+            let pending = pending_txs.get(tx_hash).ok_or_else(|| {
+                BlockchainError::InvalidTransaction("Transaction not found in pending list".to_string())
+            })?;
+            self.tx_message(*tx_hash, &pending.transaction)?
+        };
         
-        if let Some(pending) = pending_txs.get(tx_hash) {
-            let tx_data = bincode::serialize(&pending.transaction)
-                .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        // A queued send of it is replaced, so every peer gets it again
+        self.scheduler.lock().await.remove(tx_hash);
+        self.enqueue(*tx_hash, message, Lane::Urgent).await;
+        info!("Force rebroadcast of transaction {} queued", hex::encode(tx_hash));
 
-            let tx_message = TxMessage {
-                tx_hash: *tx_hash,
-                tx_data,
-            };
+        self.flush().await;
+        Ok(())
+    }
 
-            let message = Message {
-                message_type: MessageType::Tx,
-                payload: MessagePayload::Tx(tx_message),
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                checksum: self.calculate_checksum(tx_hash),
+    /// Send every queued transaction that is due, highest lane first, to
+    /// the connected peers that don't have it yet. Returns how many were
+    /// sent to all of them.
+    pub async fn flush(&self) -> usize {
+        let now = Instant::now();
+        let peers = self.network.get_connected_peers().await;
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.retain_peers(&peers);
+
+        let mut completed = Vec::new();
+        let mut deferred = Vec::new();
+        while let Some(mut job) = scheduler.next_due(now) {
+            let mut failure = if peers.is_empty() {
+                Some(NetworkError::ConnectionError("No peers connected".to_string()))
+            } else {
+                None
             };
+            let mut window_full = false;
 
-            match self.network.broadcast_message(message).await {
-                Ok(peer_count) => {
-                    info!("Force rebroadcast transaction {} to {} peers", 
-                          hex::encode(tx_hash), peer_count);
-                    Ok(())
+            let targets: Vec<Uuid> = peers.iter().filter(|peer| !job.sent_to.contains(peer)).copied().collect();
+            for peer in targets {
+                if !scheduler.take_window(peer, now) {
+                    window_full = true;
+                    continue;
+                }
+                match self.network.send_to_peer(peer, job.message.clone()).await {
+                    Ok(()) => {
+                        job.sent_to.insert(peer);
+                    }
+                    Err(e) if is_transient(&e) => failure = Some(e),
+                    // The peer is gone, nothing to retry
+                    Err(e) => debug!("Dropping send of {} to {}: {}", hex::encode(job.tx_hash), peer, e),
                 }
-                Err(e) => {
-                    error!("Failed to force rebroadcast transaction {}: {}", 
-                           hex::encode(tx_hash), e);
-                    Err(BlockchainError::InvalidTransaction(format!("Rebroadcast failed: {}", e)))
+            }
+
+            if let Some(e) = failure {
+                job.attempts += 1;
+                if job.attempts > scheduler.config.max_retries {
+                    error!("Failed to broadcast transaction {} after {} attempts: {}",
+                           hex::encode(job.tx_hash), job.attempts, e);
+                    self.failed_broadcasts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    scheduler.finish(&job.tx_hash);
+                    continue;
                 }
+                let backoff = scheduler.backoff(job.attempts);
+                debug!("Retrying transaction {} in {:?}: {}", hex::encode(job.tx_hash), backoff, e);
+                self.retried_sends.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                job.next_attempt = now + backoff;
+                deferred.push(job);
+            } else if window_full {
+                // The rest of the peers get it in their next window
+                job.next_attempt = now + scheduler.config.window_interval;
+                deferred.push(job);
+            } else {
+                info!("Transaction {} broadcast to {} peers", hex::encode(job.tx_hash), job.sent_to.len());
+                self.successful_broadcasts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                scheduler.finish(&job.tx_hash);
+                completed.push((job.tx_hash, job.sent_to.len()));
             }
-        } else {
-            Err(BlockchainError::InvalidTransaction("Transaction not found in pending list".to_string()))
         }
+        for job in deferred {
+            scheduler.requeue(job);
+        }
+        drop(scheduler);
+
+        let mut pending_txs = self.pending_transactions.write().await;
+        for (tx_hash, peer_count) in &completed {
+            if let Some(pending) = pending_txs.get_mut(tx_hash) {
+                pending.peer_count = *peer_count;
+            }
+        }
+        completed.len()
+    }
+
+    /// Queue a send unless the transaction is already queued
+    async fn enqueue(&self, tx_hash: Hash256, message: Message, lane: Lane) -> bool {
+        let queued = self.scheduler.lock().await.enqueue(BroadcastJob {
+            tx_hash,
+            message,
+            lane,
+            attempts: 0,
+            next_attempt: Instant::now(),
+            sent_to: HashSet::new(),
+        });
+        if !queued {
+            debug!("Transaction {} already queued", hex::encode(tx_hash));
+        }
+        queued
+    }
+
+    /// Network message carrying a transaction
+    fn tx_message(&self, tx_hash: Hash256, transaction: &Transaction) -> BlockchainResult<Message> {
+        let tx_data = bincode::serialize(transaction)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+
+        Ok(Message {
+            message_type: MessageType::Tx,
+            payload: MessagePayload::Tx(TxMessage { tx_hash, tx_data }),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            checksum: self.calculate_checksum(&tx_hash),
+        })
     }
 
     /// Calculate simple checksum for message integrity
@@ -383,9 +643,25 @@ mod tests {
     use super::*;
     use blockchain_core::transaction::{TransactionInput, TransactionOutput};
 
+    fn job(byte: u8, lane: Lane, now: Instant) -> BroadcastJob {
+        BroadcastJob {
+            tx_hash: [byte; 32],
+            message: Message {
+                message_type: MessageType::Tx,
+                payload: MessagePayload::Tx(TxMessage { tx_hash: [byte; 32], tx_data: vec![] }),
+                timestamp: 0,
+                checksum: 0,
+            },
+            lane,
+            attempts: 0,
+            next_attempt: now,
+            sent_to: HashSet::new(),
+        }
+    }
+
     #[tokio::test]
     async fn test_transaction_broadcaster_creation() {
-        let network = Arc::new(NetworkManager::new(Default::default()).unwrap());
+        let network = Arc::new(NetworkManager::new(Default::default(), None).unwrap());
         let broadcaster = TransactionBroadcaster::new(network);
         
         let stats = broadcaster.get_stats().await;
@@ -395,7 +671,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_duplicate_detection() {
-        let network = Arc::new(NetworkManager::new(Default::default()).unwrap());
+        let network = Arc::new(NetworkManager::new(Default::default(), None).unwrap());
         let broadcaster = TransactionBroadcaster::new(network);
         
         // Create a test transaction
@@ -412,4 +688,67 @@ mod tests {
         let result = broadcaster.broadcast_transaction(tx, TransactionPriority::Normal).await;
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_broadcast_without_peers_is_retried() {
+        let network = Arc::new(NetworkManager::new(Default::default(), None).unwrap());
+        let broadcaster = TransactionBroadcaster::new(network);
+
+        let tx = Transaction::new(1, vec![], vec![]);
+        broadcaster.broadcast_transaction(tx.clone(), TransactionPriority::High).await.unwrap();
+        broadcaster.broadcast_transaction(tx, TransactionPriority::High).await.unwrap();
+
+        let stats = broadcaster.get_stats().await;
+        assert_eq!(stats.queued[&Lane::Urgent], 1);
+        assert_eq!(stats.retried_sends, 1);
+        assert_eq!(stats.duplicate_receptions, 1);
+        assert_eq!(stats.failed_broadcasts, 0);
+    }
+
+    #[test]
+    fn test_urgent_lane_drains_before_rebroadcasts() {
+        let now = Instant::now();
+        let mut scheduler = SendScheduler::new(BroadcastConfig::default());
+        for byte in 0..10 {
+            assert!(scheduler.enqueue(job(byte, Lane::Rebroadcast, now)));
+        }
+        assert!(scheduler.enqueue(job(100, Lane::Normal, now)));
+        assert!(scheduler.enqueue(job(200, Lane::Urgent, now)));
+        assert!(!scheduler.enqueue(job(200, Lane::Urgent, now)));
+
+        assert_eq!(scheduler.next_due(now).unwrap().tx_hash, [200; 32]);
+        assert_eq!(scheduler.next_due(now).unwrap().tx_hash, [100; 32]);
+        assert_eq!(scheduler.next_due(now).unwrap().tx_hash, [0; 32]);
+
+        // A job backing off doesn't hold up the lane behind it
+        let mut waiting = scheduler.next_due(now).unwrap();
+        waiting.next_attempt = now + Duration::from_secs(1);
+        scheduler.requeue(waiting);
+        assert_eq!(scheduler.next_due(now).unwrap().tx_hash, [2; 32]);
+
+        scheduler.remove(&[3; 32]);
+        assert_eq!(scheduler.next_due(now).unwrap().tx_hash, [4; 32]);
+        assert_eq!(scheduler.depths()[&Lane::Rebroadcast], 6);
+    }
+
+    #[test]
+    fn test_send_windows_and_backoff() {
+        let now = Instant::now();
+        let config = BroadcastConfig { peer_window: 2, ..BroadcastConfig::default() };
+        let mut scheduler = SendScheduler::new(config.clone());
+        let (peer, other) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(scheduler.take_window(peer, now));
+        assert!(scheduler.take_window(peer, now));
+        assert!(!scheduler.take_window(peer, now));
+        assert!(scheduler.take_window(other, now));
+        assert!(scheduler.take_window(peer, now + config.window_interval));
+
+        scheduler.retain_peers(&[other]);
+        assert_eq!(scheduler.windows.len(), 1);
+
+        assert_eq!(scheduler.backoff(1), Duration::from_millis(500));
+        assert_eq!(scheduler.backoff(3), Duration::from_secs(2));
+        assert_eq!(scheduler.backoff(20), config.max_backoff);
+    }
+}
@@ -1,6 +1,6 @@
 use blockchain_network::{
     NetworkManager, NetworkConfig,
-    protocol::{Message, MessageType, NetworkAddress, services},
+    protocol::{Message, MessageType, NetworkAddress, services, MAINNET_MAGIC},
     peer::{Peer, PeerInfo},
     discovery::AddressManager,
     tx_broadcast::{TransactionBroadcaster, TransactionPriority},
@@ -68,6 +68,8 @@ async fn test_protocol_messages() {
         12345, // start height
         remote_addr.clone(),
         local_addr.clone(),
+        MAINNET_MAGIC,
+        [0u8; 32], // no chain yet
     );
     
     assert_eq!(version_msg.message_type, MessageType::Version);
@@ -246,7 +248,7 @@ async fn test_message_validation() {
 async fn test_protocol_constants() {
     use blockchain_network::protocol::{MAINNET_MAGIC, TESTNET_MAGIC, MAX_PAYLOAD_SIZE};
     
-    assert_eq!(MAINNET_MAGIC, 0xD9B4BEF9);
+    assert_eq!(MAINNET_MAGIC, 0xED000001);
     assert_eq!(TESTNET_MAGIC, 0x0709110B);
     assert_eq!(MAX_PAYLOAD_SIZE, 32 * 1024 * 1024);
     
@@ -2,6 +2,7 @@
 
 use crate::{RpcRequest, RpcResponse, methods};
 use anyhow::{Result, Context};
+use blockchain_core::canonical::Sats;
 use serde_json::json;
 
 /// RPC client for connecting to blockchain node
@@ -21,8 +22,22 @@ impl RpcClient {
         }
     }
     
-    /// Make RPC call
-    async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+    /// Give up on requests taking longer than `timeout`
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+        self
+    }
+    
+    /// Endpoint URL
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+    
+    /// Make RPC call. Errors reported by the node come back as `RpcError`.
+    pub async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
         let id = self.request_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         
         let request = RpcRequest {
@@ -43,7 +58,7 @@ impl RpcClient {
             .context("Failed to parse RPC response")?;
         
         if let Some(error) = response.error {
-            anyhow::bail!("RPC error {}: {}", error.code, error.message);
+            return Err(error.into());
         }
         
         response.result.context("Missing result in RPC response")
@@ -58,7 +73,7 @@ impl RpcClient {
     /// Get balance of address
     pub async fn get_balance(&self, address: &str) -> Result<u64> {
         let result = self.call(methods::GET_BALANCE, json!([address])).await?;
-        Ok(serde_json::from_value(result)?)
+        Ok(serde_json::from_value::<Sats>(result)?.0)
     }
     
     /// Send raw transaction (returns transaction hash)
@@ -92,6 +107,11 @@ impl RpcClient {
         self.call(methods::GET_MINING_INFO, json!([])).await
     }
     
+    /// Get chain, mempool and peer summary
+    pub async fn get_status(&self) -> Result<serde_json::Value> {
+        self.call(methods::GET_STATUS, json!([])).await
+    }
+    
     /// Get sync status
     pub async fn get_sync_status(&self) -> Result<serde_json::Value> {
         self.call(methods::GET_SYNC_STATUS, json!([])).await
@@ -102,6 +122,16 @@ impl RpcClient {
         self.call(methods::CREDIT_BALANCE, json!([address, amount])).await
     }
     
+    /// Resolve a `.edu` name (returns the name record)
+    pub async fn resolve_name(&self, name: &str) -> Result<serde_json::Value> {
+        self.call(methods::RESOLVE_NAME, json!([name])).await
+    }
+    
+    /// List names pointing at address
+    pub async fn list_names(&self, address: &str) -> Result<serde_json::Value> {
+        self.call(methods::LIST_NAMES, json!([address])).await
+    }
+    
     /// Check if node is reachable
     pub async fn is_connected(&self) -> bool {
         self.get_block_height().await.is_ok()
@@ -0,0 +1,227 @@
+//! Runtime feature flags
+//!
+//! Experimental subsystems are always compiled in but can be switched off
+//! per node. Each feature owns the RPC methods under its namespace; while a
+//! feature is off those methods stay registered and answer with a
+//! `MethodDisabled` error, so clients can tell "this node has it turned off"
+//! apart from "no such method".
+
+use crate::RpcError;
+use jsonrpc_core::{IoHandler, Params, Value};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+/// JSON-RPC error code for a method whose feature is disabled
+pub const METHOD_DISABLED: i32 = -32004;
+
+/// A subsystem that can be toggled at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// EVM smart contracts (`contract_*`)
+    Contracts,
+    /// Proof-of-stake validation (`staking_*`)
+    Pos,
+    /// Payment channels (`channel_*`)
+    Channels,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::Contracts, Feature::Pos, Feature::Channels];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Contracts => "contracts",
+            Feature::Pos => "pos",
+            Feature::Channels => "channels",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.name() == name)
+    }
+
+    /// Prefix of the RPC methods the feature owns
+    pub fn method_prefix(self) -> &'static str {
+        match self {
+            Feature::Contracts => "contract_",
+            Feature::Pos => "staking_",
+            Feature::Channels => "channel_",
+        }
+    }
+
+    /// Contracts predate the flags and stay on unless turned off; the
+    /// others are opt-in
+    pub fn enabled_by_default(self) -> bool {
+        matches!(self, Feature::Contracts)
+    }
+
+    /// Feature owning an RPC method, if any
+    pub fn for_method(method: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| method.starts_with(feature.method_prefix()))
+    }
+}
+
+/// A feature and whether it is on, for listing
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureState {
+    pub feature: Feature,
+    pub enabled: bool,
+}
+
+/// Which features are on for this node
+#[derive(Debug)]
+pub struct FeatureFlags {
+    enabled: RwLock<HashMap<Feature, bool>>,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            enabled: RwLock::new(Feature::ALL.into_iter().map(|f| (f, f.enabled_by_default())).collect()),
+        }
+    }
+}
+
+impl FeatureFlags {
+    /// Defaults with `overrides` (feature name to on/off) applied, e.g. from
+    /// a node's config file
+    pub fn with_overrides<'a>(overrides: impl IntoIterator<Item = (&'a str, bool)>) -> Result<Self, String> {
+        let flags = Self::default();
+        for (name, enabled) in overrides {
+            let feature = Feature::from_name(name).ok_or_else(|| {
+                let known: Vec<&str> = Feature::ALL.iter().map(|f| f.name()).collect();
+                format!("Unknown feature '{}' (known: {})", name, known.join(", "))
+            })?;
+            flags.set(feature, enabled);
+        }
+        Ok(flags)
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled.read().unwrap().get(&feature).copied().unwrap_or(false)
+    }
+
+    pub fn set(&self, feature: Feature, enabled: bool) {
+        self.enabled.write().unwrap().insert(feature, enabled);
+    }
+
+    pub fn list(&self) -> Vec<FeatureState> {
+        Feature::ALL.into_iter()
+            .map(|feature| FeatureState { feature, enabled: self.is_enabled(feature) })
+            .collect()
+    }
+
+    /// `MethodDisabled` if `method` belongs to a feature that is off
+    pub fn check(&self, method: &str) -> Result<(), RpcError> {
+        match Feature::for_method(method) {
+            Some(feature) if !self.is_enabled(feature) => Err(RpcError::method_disabled(method, feature)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Register `method` on `handler`, answering `MethodDisabled` while its
+    /// feature is off
+    pub fn add_method<F>(self: &Arc<Self>, handler: &mut IoHandler, method: &str, f: F)
+    where
+        F: Fn(Params) -> jsonrpc_core::Result<Value> + Send + Sync + 'static,
+    {
+        let flags = self.clone();
+        let name = method.to_string();
+        handler.add_sync_method(method, move |params: Params| {
+            flags.check(&name).map_err(jsonrpc_core::Error::from)?;
+            f(params)
+        });
+    }
+
+    /// `add_method` for a handler that awaits instead of blocking
+    pub fn add_async_method<F, Fut>(self: &Arc<Self>, handler: &mut IoHandler, method: &str, f: F)
+    where
+        F: Fn(Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = jsonrpc_core::Result<Value>> + Send + 'static,
+    {
+        let flags = self.clone();
+        let name = method.to_string();
+        handler.add_method(method, move |params: Params| {
+            let call = flags.check(&name).map(|()| f(params));
+            async move {
+                match call {
+                    Ok(future) => future.await,
+                    Err(error) => Err(error.into()),
+                }
+            }
+        });
+    }
+}
+
+impl RpcError {
+    pub fn method_disabled(method: &str, feature: Feature) -> Self {
+        Self {
+            code: METHOD_DISABLED,
+            message: format!("MethodDisabled: {} is disabled on this node (feature '{}' is off)", method, feature.name()),
+            data: Some(json!({ "method": method, "feature": feature.name() })),
+        }
+    }
+
+    pub fn is_method_disabled(&self) -> bool {
+        self.code == METHOD_DISABLED
+    }
+}
+
+impl From<RpcError> for jsonrpc_core::Error {
+    fn from(error: RpcError) -> Self {
+        Self {
+            code: jsonrpc_core::ErrorCode::ServerError(error.code as i64),
+            message: error.message,
+            data: error.data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let flags = FeatureFlags::default();
+        assert!(flags.is_enabled(Feature::Contracts));
+        assert!(!flags.is_enabled(Feature::Pos));
+
+        let flags = FeatureFlags::with_overrides([("contracts", false), ("channels", true)]).unwrap();
+        assert!(!flags.is_enabled(Feature::Contracts));
+        assert!(flags.is_enabled(Feature::Channels));
+        assert!(FeatureFlags::with_overrides([("sharding", true)]).is_err());
+    }
+
+    #[test]
+    fn test_disabled_method_is_reported() {
+        let flags = FeatureFlags::with_overrides([("contracts", false)]).unwrap();
+        let error = flags.check("contract_deploy").unwrap_err();
+        assert!(error.is_method_disabled());
+        assert_eq!(error.data.unwrap()["feature"], "contracts");
+        assert!(flags.check("blockchain_getStatus").is_ok());
+    }
+
+    #[test]
+    fn test_gated_method_stays_registered() {
+        let flags = Arc::new(FeatureFlags::default());
+        let mut handler = IoHandler::new();
+        flags.add_method(&mut handler, "contract_ping", |_params| Ok(json!("pong")));
+        flags.add_async_method(&mut handler, "contract_echo", |_params| async { Ok(json!("echo")) });
+
+        let request = r#"{"jsonrpc":"2.0","method":"contract_ping","params":[],"id":1}"#;
+        let async_request = r#"{"jsonrpc":"2.0","method":"contract_echo","params":[],"id":2}"#;
+        assert!(handler.handle_request_sync(request).unwrap().contains("pong"));
+        assert!(handler.handle_request_sync(async_request).unwrap().contains("echo"));
+
+        flags.set(Feature::Contracts, false);
+        let response = handler.handle_request_sync(request).unwrap();
+        assert!(response.contains(&METHOD_DISABLED.to_string()));
+        let response = handler.handle_request_sync(async_request).unwrap();
+        assert!(response.contains(&METHOD_DISABLED.to_string()));
+    }
+}
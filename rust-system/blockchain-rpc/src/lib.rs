@@ -22,7 +22,8 @@ pub struct RpcResponse {
 }
 
 /// RPC error
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[error("RPC error {code}: {message}")]
 pub struct RpcError {
     pub code: i32,
     pub message: String,
@@ -58,13 +59,26 @@ pub mod methods {
     /// Get node sync status
     pub const GET_SYNC_STATUS: &str = "blockchain_getSyncStatus";
     
+    /// Get chain, mempool and peer summary
+    pub const GET_STATUS: &str = "blockchain_getStatus";
+    
     /// Credit balance directly (for vouchers/airdrops)
     pub const CREDIT_BALANCE: &str = "blockchain_creditBalance";
+    
+    /// Resolve a `.edu` name to its address
+    pub const RESOLVE_NAME: &str = "names_resolve";
+    
+    /// List the names pointing at an address
+    pub const LIST_NAMES: &str = "names_list";
 }
 
 pub mod client;
+pub mod features;
+pub mod multi_client;
 pub mod server;
 
 // Re-exports for convenience
 pub use client::RpcClient;
+pub use features::{Feature, FeatureFlags};
+pub use multi_client::{MultiRpcClient, MultiRpcConfig, NodeHealth};
 pub use server::{RpcServer, RpcServerConfig, BlockchainState};
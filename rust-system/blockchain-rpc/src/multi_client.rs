@@ -0,0 +1,302 @@
+//! Multi-node RPC client - health-checked failover across a node cluster
+//!
+//! Each node is health-checked with `blockchain_getStatus` (height, peer
+//! count, round-trip latency). Reads go to the fastest healthy node that is
+//! not lagging behind the cluster tip; broadcasts go to the best node and
+//! fail over down the ranking until one accepts. Failed calls count against
+//! a node between health checks, so a dead node drops out of rotation
+//! without waiting for the next check.
+
+use crate::{RpcClient, RpcError, methods};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use blockchain_core::canonical::Sats;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Cluster routing and health-check settings
+#[derive(Debug, Clone)]
+pub struct MultiRpcConfig {
+    /// Time between background health checks
+    pub health_check_interval: Duration,
+    /// Per-request timeout
+    pub request_timeout: Duration,
+    /// Blocks a node may trail the highest node and still serve reads
+    pub max_height_lag: u64,
+    /// Consecutive failures before a node is taken out of rotation
+    pub max_failures: u32,
+}
+
+impl Default for MultiRpcConfig {
+    fn default() -> Self {
+        Self {
+            health_check_interval: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(10),
+            max_height_lag: 2,
+            max_failures: 3,
+        }
+    }
+}
+
+/// Last known state of one node
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NodeHealth {
+    pub endpoint: String,
+    pub healthy: bool,
+    pub height: Option<u64>,
+    pub peer_count: Option<u64>,
+    /// Round-trip time of the last successful health check, in milliseconds
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// When the node was last health-checked
+    #[serde(skip)]
+    pub last_checked: Option<Instant>,
+}
+
+struct Node {
+    client: RpcClient,
+    health: RwLock<NodeHealth>,
+}
+
+impl Node {
+    fn record_success(&self) {
+        let mut health = self.health.write().unwrap();
+        health.consecutive_failures = 0;
+        health.healthy = true;
+    }
+
+    fn record_failure(&self, error: &anyhow::Error, max_failures: u32) {
+        let mut health = self.health.write().unwrap();
+        health.consecutive_failures += 1;
+        health.last_error = Some(error.to_string());
+        if health.healthy && health.consecutive_failures >= max_failures {
+            warn!("🔴 RPC node {} taken out of rotation: {}", health.endpoint, error);
+            health.healthy = false;
+        }
+    }
+}
+
+/// Order nodes for routing: healthy nodes within `max_height_lag` of the
+/// highest first, fastest first; then lagging healthy nodes, highest first;
+/// unhealthy nodes last as a last resort
+pub fn rank_nodes(nodes: &[NodeHealth], max_height_lag: u64) -> Vec<usize> {
+    let tip = nodes.iter()
+        .filter(|node| node.healthy)
+        .filter_map(|node| node.height)
+        .max()
+        .unwrap_or(0);
+    let in_sync = |node: &NodeHealth| node.height.is_some_and(|height| height + max_height_lag >= tip);
+
+    let mut order: Vec<usize> = (0..nodes.len()).collect();
+    order.sort_by_key(|&i| {
+        let node = &nodes[i];
+        let tier = match (node.healthy, in_sync(node)) {
+            (true, true) => 0,
+            (true, false) => 1,
+            (false, _) => 2,
+        };
+        (
+            tier,
+            if tier == 1 { u64::MAX - node.height.unwrap_or(0) } else { 0 },
+            node.latency_ms.unwrap_or(u64::MAX),
+            node.consecutive_failures,
+        )
+    });
+    order
+}
+
+/// RPC client spreading calls over several nodes
+pub struct MultiRpcClient {
+    nodes: Vec<Node>,
+    config: MultiRpcConfig,
+}
+
+impl MultiRpcClient {
+    /// Client for `endpoints`, all assumed healthy until checked
+    pub fn new(endpoints: Vec<String>, config: MultiRpcConfig) -> Result<Self> {
+        if endpoints.is_empty() {
+            anyhow::bail!("MultiRpcClient needs at least one node endpoint");
+        }
+        let nodes = endpoints.into_iter()
+            .map(|endpoint| Node {
+                client: RpcClient::new(endpoint.clone()).with_timeout(config.request_timeout),
+                health: RwLock::new(NodeHealth { endpoint, healthy: true, ..NodeHealth::default() }),
+            })
+            .collect();
+        Ok(Self { nodes, config })
+    }
+
+    /// Health of every node, in configuration order
+    pub fn health(&self) -> Vec<NodeHealth> {
+        self.nodes.iter().map(|node| node.health.read().unwrap().clone()).collect()
+    }
+
+    /// Endpoint reads are currently routed to
+    pub fn preferred_endpoint(&self) -> String {
+        let health = self.health();
+        health[self.ranking()[0]].endpoint.clone()
+    }
+
+    fn ranking(&self) -> Vec<usize> {
+        rank_nodes(&self.health(), self.config.max_height_lag)
+    }
+
+    /// Check every node once, concurrently
+    pub async fn check_health(&self) {
+        let checks = self.nodes.iter().map(|node| async move {
+            let started = Instant::now();
+            let result = node.client.get_status().await;
+            (node, started.elapsed(), result)
+        });
+
+        for (node, latency, result) in futures::future::join_all(checks).await {
+            let mut health = node.health.write().unwrap();
+            health.last_checked = Some(Instant::now());
+            match result {
+                Ok(status) => {
+                    if !health.healthy {
+                        info!("🟢 RPC node {} back in rotation", health.endpoint);
+                    }
+                    health.healthy = true;
+                    health.consecutive_failures = 0;
+                    health.last_error = None;
+                    health.height = status["block_height"].as_u64();
+                    health.peer_count = status["network"]["connected_peers"].as_u64();
+                    health.latency_ms = Some(latency.as_millis() as u64);
+                }
+                Err(e) => {
+                    if health.healthy {
+                        warn!("🔴 RPC node {} failed health check: {}", health.endpoint, e);
+                    }
+                    health.healthy = false;
+                    health.consecutive_failures += 1;
+                    health.last_error = Some(e.to_string());
+                    health.latency_ms = None;
+                }
+            }
+        }
+    }
+
+    /// Keep node health current until the client is dropped
+    pub async fn run_health_checks(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.config.health_check_interval);
+        loop {
+            interval.tick().await;
+            self.check_health().await;
+        }
+    }
+
+    /// Read call routed to the healthiest node, retried on the next node if
+    /// it fails for any reason
+    pub async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        self.route(method, params, false).await
+    }
+
+    /// State-changing call (e.g. a transaction broadcast). Fails over on
+    /// transport errors and on nodes with the method disabled, but stops at
+    /// the first node that rejects the call, since the others would reject
+    /// it too.
+    pub async fn broadcast(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        self.route(method, params, true).await
+    }
+
+    async fn route(&self, method: &str, params: serde_json::Value, stop_on_rejection: bool) -> Result<serde_json::Value> {
+        let mut last_error = None;
+        for index in self.ranking() {
+            let node = &self.nodes[index];
+            match node.client.call(method, params.clone()).await {
+                Ok(result) => {
+                    node.record_success();
+                    return Ok(result);
+                }
+                Err(e) if e.downcast_ref::<RpcError>().is_some() => {
+                    // The node answered; it isn't unhealthy. A node with the
+                    // method's feature turned off doesn't speak for the rest.
+                    let disabled = e.downcast_ref::<RpcError>().is_some_and(RpcError::is_method_disabled);
+                    if stop_on_rejection && !disabled {
+                        return Err(e);
+                    }
+                    debug!("{} rejected by {}: {}", method, node.client.endpoint(), e);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    debug!("{} failed on {}: {}", method, node.client.endpoint(), e);
+                    node.record_failure(&e, self.config.max_failures);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No RPC nodes configured")))
+            .with_context(|| format!("{} failed on every node", method))
+    }
+
+    /// Current block height from the healthiest node
+    pub async fn get_block_height(&self) -> Result<u64> {
+        let result = self.call(methods::GET_BLOCK_HEIGHT, json!([])).await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Balance of an address from the healthiest node
+    pub async fn get_balance(&self, address: &str) -> Result<u64> {
+        let result = self.call(methods::GET_BALANCE, json!([address])).await?;
+        Ok(serde_json::from_value::<Sats>(result)?.0)
+    }
+
+    /// Chain, mempool and peer summary from the healthiest node
+    pub async fn get_status(&self) -> Result<serde_json::Value> {
+        self.call(methods::GET_STATUS, json!([])).await
+    }
+
+    /// Broadcast a signed transaction, failing over until a node accepts it
+    pub async fn send_transaction(&self, signed_tx_hex: &str) -> Result<String> {
+        let result = self.broadcast(methods::SEND_TRANSACTION, json!([signed_tx_hex])).await?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(endpoint: &str, healthy: bool, height: u64, latency_ms: u64) -> NodeHealth {
+        NodeHealth {
+            endpoint: endpoint.to_string(),
+            healthy,
+            height: Some(height),
+            latency_ms: Some(latency_ms),
+            ..NodeHealth::default()
+        }
+    }
+
+    #[test]
+    fn test_node_ranking() {
+        let nodes = vec![
+            node("slow", true, 100, 80),
+            node("lagging", true, 90, 5),
+            node("down", false, 120, 1),
+            node("fast", true, 99, 10),
+        ];
+        // The unhealthy node's height doesn't set the tip
+        assert_eq!(rank_nodes(&nodes, 2), vec![3, 0, 1, 2]);
+        assert_eq!(rank_nodes(&nodes, 20), vec![1, 3, 0, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_failover_marks_dead_nodes() {
+        assert!(MultiRpcClient::new(Vec::new(), MultiRpcConfig::default()).is_err());
+
+        let config = MultiRpcConfig { max_failures: 1, request_timeout: Duration::from_secs(1), ..MultiRpcConfig::default() };
+        let client = MultiRpcClient::new(
+            vec!["http://127.0.0.1:1".to_string(), "http://127.0.0.1:2".to_string()],
+            config,
+        ).unwrap();
+        assert!(client.get_block_height().await.is_err());
+        assert!(client.health().iter().all(|node| !node.healthy && node.consecutive_failures == 1));
+
+        client.check_health().await;
+        assert!(client.health().iter().all(|node| node.consecutive_failures == 2 && node.last_checked.is_some()));
+    }
+}
@@ -1,11 +1,13 @@
 //! RPC server - exposes blockchain node functionality via JSON-RPC
 
 use crate::{RpcRequest, RpcResponse, RpcError, methods};
-use jsonrpc_http_server::{Server, ServerBuilder, DomainsValidation};
+use jsonrpc_http_server::{ServerBuilder, DomainsValidation};
+pub use jsonrpc_http_server::Server;
 use jsonrpc_core::{IoHandler, Params, Value};
 use std::sync::{Arc, Mutex};
+use serde::Deserialize;
 use serde_json::json;
-use blockchain_core::{block::Block, transaction::Transaction};
+use blockchain_core::{block::Block, canonical::Sats, transaction::Transaction};
 
 /// RPC server configuration
 pub struct RpcServerConfig {
@@ -104,7 +106,7 @@ impl RpcServer {
             let balances = state_clone.balances.lock().unwrap();
             let balance = balances.get(address).copied().unwrap_or(0);
             
-            Ok(Value::Number(balance.into()))
+            Ok(json!(Sats(balance)))
         });
         
         // Send transaction
@@ -130,7 +132,7 @@ impl RpcServer {
                 .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing from address"))?;
             let to = tx_obj.get("to").and_then(|v| v.as_str())
                 .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing to address"))?;
-            let amount = tx_obj.get("amount").and_then(|v| v.as_u64())
+            let amount = tx_obj.get("amount").and_then(|v| Sats::deserialize(v).ok()).map(|sats| sats.0)
                 .ok_or_else(|| jsonrpc_core::Error::invalid_params("Missing amount"))?;
             
             // Generate transaction hash
@@ -143,7 +145,7 @@ impl RpcServer {
                 "hash": tx_hash.clone(),
                 "from": from,
                 "to": to,
-                "amount": amount,
+                "amount": Sats(amount),
                 "status": "pending",
                 "timestamp": chrono::Utc::now().timestamp()
             }));
@@ -260,7 +262,7 @@ impl RpcServer {
             
             let address = parsed[0].as_str()
                 .ok_or_else(|| jsonrpc_core::Error::invalid_params("Invalid address"))?;
-            let amount = parsed[1].as_u64()
+            let amount = Sats::deserialize(&parsed[1]).ok().map(|sats| sats.0)
                 .ok_or_else(|| jsonrpc_core::Error::invalid_params("Invalid amount"))?;
             
             // Directly credit balance
@@ -270,23 +272,29 @@ impl RpcServer {
             Ok(json!({
                 "success": true,
                 "address": address,
-                "credited": amount,
-                "new_balance": balances.get(address).copied().unwrap_or(0)
+                "credited": Sats(amount),
+                "new_balance": Sats(balances.get(address).copied().unwrap_or(0))
             }))
         });
     }
     
-    /// Start RPC server (blocking)
+    /// Start RPC server. Called from inside a tokio runtime, requests are
+    /// served on that runtime, so async handlers await alongside the rest
+    /// of the node; otherwise the server runs its own event loop threads.
     pub fn start(self) -> Result<Server, String> {
         let addr = format!("{}:{}", self.config.host, self.config.port);
         
         println!("🚀 Starting RPC server on {}", addr);
         
-        ServerBuilder::new(self.handler)
-            .threads(4)
+        let builder = ServerBuilder::new(self.handler)
             .cors(DomainsValidation::AllowOnly(vec![
                 jsonrpc_http_server::AccessControlAllowOrigin::Any
-            ]))
+            ]));
+        let builder = match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => builder.event_loop_executor(runtime),
+            Err(_) => builder.threads(4),
+        };
+        builder
             .start_http(&addr.parse().map_err(|e| format!("Invalid address: {}", e))?)
             .map_err(|e| format!("Failed to start RPC server: {}", e))
     }
@@ -1,6 +1,5 @@
 use anyhow::Result;
-use qrcode::QrCode;
-use qrcode::render::svg;
+use blockchain_core::qr::{self, QrErrorCorrection, QrFormat, QrOptions};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::Write;
@@ -8,10 +7,36 @@ use std::io::Write;
 #[derive(Debug, Deserialize, Serialize)]
 struct Voucher {
     code: String,
-    amount: f64,
+    amount: VoucherAmount,
+    /// Amount as the node formats it for printing
+    #[serde(default)]
+    display_amount: Option<String>,
+    /// What the QR code encodes; the bare code for older exports
+    #[serde(default)]
+    payload: Option<String>,
     status: String,
 }
 
+/// EDU as a number in older exports, satoshis as a string from `voucher_exportBatch`
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum VoucherAmount {
+    Edu(f64),
+    Satoshis(String),
+}
+
+impl Voucher {
+    fn display_amount(&self) -> String {
+        if let Some(display) = &self.display_amount {
+            return display.clone();
+        }
+        match &self.amount {
+            VoucherAmount::Edu(edu) => format!("{:.1} EDU", edu),
+            VoucherAmount::Satoshis(sats) => format!("{} sat", sats),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct VoucherResponse {
     success: bool,
@@ -113,15 +138,17 @@ fn main() -> Result<()> {
     <div class="voucher-grid">
 "#);
     
-    // Generate QR codes
+    // Generate QR codes; printed cards get handled, so a quarter of a
+    // code can be scuffed and still scan
+    let options = QrOptions {
+        format: QrFormat::Svg,
+        size: 200,
+        error_correction: QrErrorCorrection::Q,
+    };
     for (idx, voucher) in response.vouchers.iter().enumerate() {
         // Generate QR code as SVG
-        let qr = QrCode::new(voucher.code.as_bytes())?;
-        let svg_string = qr.render()
-            .min_dimensions(200, 200)
-            .dark_color(svg::Color("#000000"))
-            .light_color(svg::Color("#ffffff"))
-            .build();
+        let payload = voucher.payload.as_deref().unwrap_or(&voucher.code);
+        let svg_string = String::from_utf8(qr::render(payload, &options)?.data)?;
         
         // Save individual SVG file
         let svg_filename = format!("{}/voucher_{:03}_{}.svg", output_dir, idx + 1, voucher.code);
@@ -133,10 +160,10 @@ fn main() -> Result<()> {
         <div class="voucher">
             <div class="qr-code">{}</div>
             <div class="voucher-code">{}</div>
-            <div class="voucher-amount">{:.1} EDU</div>
+            <div class="voucher-amount">{}</div>
             <div class="instructions">Scan to redeem</div>
         </div>
-"#, svg_string, voucher.code, voucher.amount));
+"#, svg_string, voucher.code, voucher.display_amount()));
     }
     
     html.push_str(r#"